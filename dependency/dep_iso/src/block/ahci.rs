@@ -26,6 +26,18 @@ pub struct AHCI<P: Provider> {
     cmd_table: &'static mut AHCICommandTable,
     data: &'static mut [u8],
     port: &'static mut AHCIPort,
+    identity: AtaIdentity,
+}
+
+/// Drive identity from IDENTIFY DEVICE
+pub struct AtaIdentity {
+    pub serial: String,
+    pub firmware: String,
+    pub model: String,
+    /// Capacity in 512-byte sectors
+    pub sectors: u64,
+    /// The SMART feature set is supported and enabled (words 82 and 85, bit 0)
+    pub smart: bool,
 }
 
 /// AHCI Generic Host Control (3.1)
@@ -249,6 +261,13 @@ const FIS_REG_H2D: u8 = 0x27;
 const CMD_READ_DMA_EXT: u8 = 0x25;
 const CMD_WRITE_DMA_EXT: u8 = 0x35;
 const CMD_IDENTIFY_DEVICE: u8 = 0xec;
+const CMD_FLUSH_CACHE_EXT: u8 = 0xea;
+const CMD_SMART: u8 = 0xb0;
+const SMART_READ_DATA: u8 = 0xd0;
+/// SMART commands carry this signature in LBA 23:8
+const SMART_LBA_SIGNATURE: u64 = 0xc2_4f00;
+/// SMART attribute holding the drive temperature in Celsius
+const SMART_ATTR_TEMPERATURE: u8 = 194;
 
 /// SATA Register FIS - Host to Device
 ///
@@ -429,7 +448,20 @@ impl<P: Provider> AHCI<P> {
                 identify_data.lba48_sectors
             );
 
-            let data = unsafe { slice::from_raw_parts_mut(data_va as *mut u8, BLOCK_SIZE) };
+            let words = unsafe { slice::from_raw_parts(data_va as *const u16, BLOCK_SIZE / 2) };
+            let identity = AtaIdentity {
+                serial: from_ata_string(&identify_data.serial).trim_end().into(),
+                firmware: from_ata_string(&identify_data.firmware).trim_end().into(),
+                model: from_ata_string(&identify_data.model).trim_end().into(),
+                sectors: if identify_data.lba48_sectors != 0 {
+                    identify_data.lba48_sectors
+                } else {
+                    identify_data.lba_sectors as u64
+                },
+                smart: words[82].get_bit(0) && words[85].get_bit(0),
+            };
+
+            let data = unsafe { slice::from_raw_parts_mut(data_va as *mut u8, P::PAGE_SIZE) };
 
             Some(AHCI {
                 header,
@@ -441,6 +473,7 @@ impl<P: Provider> AHCI<P> {
                 cmd_table,
                 data,
                 port,
+                identity,
             })
         } else {
             None
@@ -448,16 +481,39 @@ impl<P: Provider> AHCI<P> {
     }
 
     pub fn read_block(&mut self, block_id: usize, buf: &mut [u8]) -> usize {
+        let len = buf.len().min(BLOCK_SIZE);
+        self.read_blocks(block_id, &mut buf[..len])
+    }
+
+    /// Read consecutive sectors starting at `block_id`, up to a page of them
+    /// per command; a partial last sector is read whole and truncated
+    pub fn read_blocks(&mut self, block_id: usize, buf: &mut [u8]) -> usize {
+        for (i, chunk) in buf.chunks_mut(P::PAGE_SIZE).enumerate() {
+            let sectors = (chunk.len() + BLOCK_SIZE - 1) / BLOCK_SIZE;
+            let lba = block_id + i * (P::PAGE_SIZE / BLOCK_SIZE);
+            // 7.25 READ DMA EXT - 25h, DMA
+            self.transfer(CMD_READ_DMA_EXT, lba, sectors, false);
+            chunk.clone_from_slice(&self.data[..chunk.len()]);
+        }
+        buf.len()
+    }
+
+    /// Run a DMA command moving `sectors` sectors through the data page
+    fn transfer(&mut self, command: u8, block_id: usize, sectors: usize, write: bool) {
         // cfl=4
-        self.cmd_list[0].flags = 4;
+        self.cmd_list[0].flags = if write {
+            4 | CommandHeaderFlags::WRITE.bits() // device write
+        } else {
+            4
+        };
+        self.cmd_table.prdt[0].byte_count_i = (sectors * BLOCK_SIZE - 1) as u32;
 
         let fis = &mut self.cmd_table.cfis;
         // Register FIS from HBA to device
         fis.fis_type = FIS_REG_H2D;
         fis.cflags = 1 << 7;
-        // 7.25 READ DMA EXT - 25h, DMA
-        fis.command = CMD_READ_DMA_EXT;
-        fis.sector_count = 1;
+        fis.command = command;
+        fis.sector_count = sectors as u16;
         fis.dev_head = 0x40; // LBA
         fis.control = 0x80; // LBA48
         fis.set_lba(block_id as u64);
@@ -465,34 +521,91 @@ impl<P: Provider> AHCI<P> {
         self.port.issue_command(0);
         self.port.spin_on_slot(0);
 
-        let len = buf.len().min(BLOCK_SIZE);
-        buf[..len].clone_from_slice(&self.data[0..len]);
-        len
+        // the other commands move a single sector
+        self.cmd_table.prdt[0].byte_count_i = (BLOCK_SIZE - 1) as u32;
     }
 
-    pub fn write_block(&mut self, block_id: usize, buf: &[u8]) -> usize {
-        // cfl=4
-        self.cmd_list[0].flags = 4 | CommandHeaderFlags::WRITE.bits(); // device write
+    pub fn identity(&self) -> &AtaIdentity {
+        &self.identity
+    }
 
-        let len = buf.len().min(BLOCK_SIZE);
-        self.data[0..len].clone_from_slice(&buf[..len]);
+    /// Drive temperature in Celsius from the SMART attributes,
+    /// `None` if SMART is off or the drive does not report it
+    pub fn smart_temperature(&mut self) -> Option<u8> {
+        if !self.identity.smart {
+            return None;
+        }
+        // cfl=4
+        self.cmd_list[0].flags = 4;
 
         let fis = &mut self.cmd_table.cfis;
         // Register FIS from HBA to device
         fis.fis_type = FIS_REG_H2D;
         fis.cflags = 1 << 7;
-        // ATA8-ACS
-        // 7.63 WRITE DMA EXT - 35h, DMA
-        fis.command = CMD_WRITE_DMA_EXT;
+        // 7.52.6 SMART READ DATA - B0h/D0h, PIO Data-In
+        fis.command = CMD_SMART;
+        fis.feature_lo = SMART_READ_DATA;
         fis.sector_count = 1;
+        fis.dev_head = 0;
+        fis.control = 0;
+        fis.set_lba(SMART_LBA_SIGNATURE);
+
+        self.port.issue_command(0);
+        self.port.spin_on_slot(0);
+        // the DMA commands leave the feature register alone
+        self.cmd_table.cfis.feature_lo = 0;
+
+        // ERR in the status register
+        if self.port.task_file_data.read().get_bit(0) {
+            return None;
+        }
+        // 30 attributes of 12 bytes from offset 2: id, flags(2), value, worst, raw(6), reserved
+        self.data[2..362]
+            .chunks(12)
+            .find(|attr| attr[0] == SMART_ATTR_TEMPERATURE)
+            .map(|attr| attr[5])
+    }
+
+    /// Write the drive's volatile cache to the media, returns once it is done
+    pub fn flush(&mut self) {
+        // cfl=4, no data to transfer
+        self.cmd_list[0].flags = 4;
+        self.cmd_list[0].prdt_length = 0;
+
+        let fis = &mut self.cmd_table.cfis;
+        // Register FIS from HBA to device
+        fis.fis_type = FIS_REG_H2D;
+        fis.cflags = 1 << 7;
+        // 7.11 FLUSH CACHE EXT - EAh, Non-data
+        fis.command = CMD_FLUSH_CACHE_EXT;
+        fis.sector_count = 0;
         fis.dev_head = 0x40; // LBA
-        fis.control = 0x80; // LBA48
-        fis.set_lba(block_id as u64);
+        fis.control = 0;
+        fis.set_lba(0);
 
         self.port.issue_command(0);
         self.port.spin_on_slot(0);
 
-        len
+        self.cmd_list[0].prdt_length = 1;
+    }
+
+    pub fn write_block(&mut self, block_id: usize, buf: &[u8]) -> usize {
+        let len = buf.len().min(BLOCK_SIZE);
+        self.write_blocks(block_id, &buf[..len])
+    }
+
+    /// Write consecutive sectors starting at `block_id`, up to a page of them
+    /// per command; a partial last sector is padded with what the data page held
+    pub fn write_blocks(&mut self, block_id: usize, buf: &[u8]) -> usize {
+        for (i, chunk) in buf.chunks(P::PAGE_SIZE).enumerate() {
+            let sectors = (chunk.len() + BLOCK_SIZE - 1) / BLOCK_SIZE;
+            let lba = block_id + i * (P::PAGE_SIZE / BLOCK_SIZE);
+            self.data[..chunk.len()].clone_from_slice(chunk);
+            // ATA8-ACS
+            // 7.63 WRITE DMA EXT - 35h, DMA
+            self.transfer(CMD_WRITE_DMA_EXT, lba, sectors, true);
+        }
+        buf.len()
     }
 }
 
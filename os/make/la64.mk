@@ -87,9 +87,9 @@ kernel:
 		mv cargo_config .cargo; \
 	fi
 ifeq ($(MODE), debug)
-	@LOG=$(LOG) cargo build --features "board_$(BOARD) $(LOG_OPTION) block_$(BLK_MODE) oom_handler" --no-default-features --target loongarch64-unknown-none
+	@LOG=$(LOG) cargo build --features "board_$(BOARD) $(LOG_OPTION) block_$(BLK_MODE) oom_handler $(EXTRA_FEATURES)" --no-default-features --target loongarch64-unknown-none
 else
-	@LOG=$(LOG) cargo build --release --features "board_$(BOARD) $(LOG_OPTION) block_$(BLK_MODE) oom_handler" --no-default-features --target loongarch64-unknown-none
+	@LOG=$(LOG) cargo build --release --features "board_$(BOARD) $(LOG_OPTION) block_$(BLK_MODE) oom_handler $(EXTRA_FEATURES)" --no-default-features --target loongarch64-unknown-none
 endif
 	@mv .cargo cargo_config
 
@@ -147,6 +147,29 @@ comp:
 		-no-reboot \
 		-rtc base=utc
 
+# 只跑内核自检后关机，不进入用户态；全部通过时返回 0
+# ext4 的分配器、extent 树与目录项自检要求 ext4 的块大小等于 BLOCK_SZ，只在 LoongArch 上编译，
+# 因此还要检查这几项确实运行并通过了
+EXT4_CHECKS := ext4_alloc ext4_extents ext4_dirents
+selftest:
+	@$(MAKE) -f make/la64.mk build EXTRA_FEATURES=selftest_halt
+	@timeout 120 qemu-system-loongarch64 \
+		-machine virt \
+		-kernel $(KERNEL_LA) \
+		-m 1024 \
+		-nographic \
+		-smp 1 \
+		-drive file=$(SDCARD_LA),if=none,format=raw,id=x0 \
+		-device virtio-blk-pci,drive=x0 \
+		-no-reboot \
+		-rtc base=utc \
+		| tee selftest.log
+	@for check in $(EXT4_CHECKS); do \
+		grep -q -E "\[selftest\] $$check +ok" selftest.log \
+			|| { echo "selftest: $$check did not pass"; exit 1; }; \
+	done
+	@grep -q "\[selftest\] [0-9]* passed, 0 failed" selftest.log
+
 comp-gdb:
 	@qemu-system-loongarch64 \
 		-machine virt \
@@ -161,4 +184,4 @@ comp-gdb:
 		-S \
 		-s
 
-.PHONY: all build kernel fs-img user clean run gdb comp comp-gdb
\ No newline at end of file
+.PHONY: all build kernel fs-img user clean run gdb comp comp-gdb selftest
\ No newline at end of file
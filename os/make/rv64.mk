@@ -10,6 +10,14 @@ ROOTFS_IMG_NAME = rootfs-rv.img
 ROOTFS_IMG_DIR := ../fs-img-dir
 CORE_NUM := 4
 LOG := off
+EXTRA_FEATURES ?=
+# newc 格式的 cpio 归档，启动时解包到根目录，例如 INITRD=rootfs.cpio（由 find . | cpio -o -H newc 生成）
+INITRD ?=
+# 非空时加一块 virtio 网卡，接 QEMU 的用户态网络，内核地址为 10.0.2.15，
+# 例如 NET=1 HOSTFWD=tcp::5001-:5001 把宿主机的 5001 端口转发进来
+NET ?=
+HOSTFWD ?=
+COMMA := ,
 KERNEL_RV := ../kernel-qemu
 KERNEL_LA := ../kernel-la
 SDCARD_RV := ../sdcard.img
@@ -95,9 +103,9 @@ kernel:
 	fi
 	@cp -f src/hal/arch/riscv/linker-$(BOARD).ld src/hal/arch/riscv/linker.ld
     ifeq ($(MODE), debug)
-		@LOG=${LOG} cargo build --target $(TARGET) --features "board_$(BOARD) $(LOG_OPTION) block_$(BLK_MODE) oom_handler" --no-default-features
+		@LOG=${LOG} cargo build --target $(TARGET) --features "board_$(BOARD) $(LOG_OPTION) block_$(BLK_MODE) oom_handler $(EXTRA_FEATURES)" --no-default-features
     else
-		@LOG=${LOG} cargo build --target $(TARGET) --release --features "board_$(BOARD) $(LOG_OPTION) block_$(BLK_MODE) oom_handler" --no-default-features
+		@LOG=${LOG} cargo build --target $(TARGET) --release --features "board_$(BOARD) $(LOG_OPTION) block_$(BLK_MODE) oom_handler $(EXTRA_FEATURES)" --no-default-features
     endif
 	@mv .cargo cargo_config
 
@@ -115,7 +123,9 @@ ifeq ($(BOARD), rvqemu)
   		-drive if=none,file=$(ROOTFS_IMG),format=raw,id=x0 \
         -device virtio-blk-device,drive=x0,bus=virtio-mmio-bus.0 \
   		-m 1024 \
-  		-smp threads=$(CORE_NUM)
+  		-smp threads=$(CORE_NUM) \
+  		$(if $(INITRD),-initrd $(INITRD)) \
+  		$(if $(NET),-netdev user$(COMMA)id=net0$(if $(HOSTFWD),$(COMMA)hostfwd=$(HOSTFWD)) -device virtio-net-device$(COMMA)netdev=net0)
 endif
 
 monitor:
@@ -184,4 +194,110 @@ test: build
 		-device virtio-net-device,netdev=net \
 		-netdev user,id=net 
 
+# 以不同的 -m 启动，确认帧分配器的可用帧数随设备树报告的内存大小变化
+MEMTEST_SIZES ?= 128M 256M 512M 1024M
+memtest: build
+	@for mem in $(MEMTEST_SIZES); do \
+		printf "%-6s" $$mem; \
+		timeout 30 qemu-system-riscv64 \
+			-machine virt \
+			-kernel $(KERNEL_RV) \
+			-m $$mem \
+			-nographic \
+			-smp 1 \
+			-bios default \
+			-drive file=$(SDCARD_RV),if=none,format=raw,id=x0 \
+			-device virtio-blk-device,drive=x0,bus=virtio-mmio-bus.0 \
+			| grep -a -m1 "Physical Frames"; \
+	done
+
+# 测试镜像跑完后由 reboot(LINUX_REBOOT_CMD_POWER_OFF) 关机，QEMU 应在超时前以 0 退出
+POWEROFF_TIMEOUT ?= 600
+poweroff-test: build
+	@timeout $(POWEROFF_TIMEOUT) qemu-system-riscv64 \
+		-machine virt \
+		-kernel $(KERNEL_RV) \
+		-m 1024 \
+		-nographic \
+		-smp $(CORE_NUM) \
+		-bios default \
+		-drive file=$(SDCARD_RV),if=none,format=raw,id=x0 \
+		-device virtio-blk-device,drive=x0,bus=virtio-mmio-bus.0 \
+		-no-reboot; \
+	status=$$?; \
+	if [ $$status -eq 0 ]; then echo "poweroff-test: passed"; \
+	else echo "poweroff-test: FAILED, qemu exited with $$status"; exit 1; fi
+
+# 只跑内核自检后关机，不进入用户态；全部通过时返回 0
+# 同时检查堆初始化之前的早期输出是否可见，以及多核同时打印时各行是否完整
+selftest:
+	@$(MAKE) -f make/rv64.mk build EXTRA_FEATURES=selftest_halt
+	@timeout 60 qemu-system-riscv64 \
+		-machine virt \
+		-kernel $(KERNEL_RV) \
+		-m 1024 \
+		-nographic \
+		-smp $(CORE_NUM) \
+		-bios default \
+		-drive file=$(SDCARD_RV),if=none,format=raw,id=x0 \
+		-device virtio-blk-device,drive=x0,bus=virtio-mmio-bus.0 \
+		-no-reboot \
+		| tee selftest.log
+	@grep -q "\[Boot\] early console up" selftest.log || (echo "selftest: no early console output"; exit 1)
+	@test `grep -a -c "\[selftest\] hart" selftest.log` -eq \
+		`grep -a -c -E "^\[selftest\] hart [0-9]+ line [0-9]+ \.{48}.?$$" selftest.log` \
+		|| (echo "selftest: interleaved console lines"; exit 1)
+	@grep -q "\[selftest\] [0-9]* passed, 0 failed" selftest.log
+
+# 自检在第一次启动时故意 panic，panic_reboot 使内核写入崩溃转储后重启，
+# 第二次启动的自检从 /proc/crashdump 读回上一次的 panic 信息，通过后关机
+crashdump-test:
+	@$(MAKE) -f make/rv64.mk build EXTRA_FEATURES=selftest_halt
+	@timeout 120 qemu-system-riscv64 \
+		-machine virt \
+		-kernel $(KERNEL_RV) \
+		-append "panic_reboot" \
+		-m 1024 \
+		-nographic \
+		-bios default \
+		-drive file=$(SDCARD_RV),if=none,format=raw,id=x0 \
+		-device virtio-blk-device,drive=x0,bus=virtio-mmio-bus.0 \
+		| tee crashdump.log
+	@grep -q "controlled panic for the crash dump" crashdump.log \
+		|| (echo "crashdump-test: first boot did not panic"; exit 1)
+	@grep -q "the previous boot panicked, crash dump in /proc/crashdump" crashdump.log \
+		|| (echo "crashdump-test: no crash dump found after reboot"; exit 1)
+	@grep -q "\[selftest\] crashdump *ok" crashdump.log \
+		|| (echo "crashdump-test: crash dump check failed"; exit 1)
+	@echo "crashdump-test: passed"
+
+# initproc 缺失或不是合法 ELF 时，内核应打印诊断信息并关机，而不是 panic
+# 损坏的 init 程序通过 initramfs 放入根目录，再用 init= 指定
+INITPROC_FALLBACK_DIR := ../initproc-fallback
+initproc-fallback-test: build
+	@rm -rf $(INITPROC_FALLBACK_DIR) && mkdir -p $(INITPROC_FALLBACK_DIR)/root
+	@printf 'this is not an ELF file\n' > $(INITPROC_FALLBACK_DIR)/root/broken_init
+	@cd $(INITPROC_FALLBACK_DIR)/root && find . | cpio -o -H newc --quiet > ../rootfs.cpio
+	@for case in "broken_init:not a valid ELF executable" "missing_init:not found"; do \
+		init=$${case%%:*}; reason=$${case#*:}; \
+		timeout 60 qemu-system-riscv64 \
+			-machine virt \
+			-kernel $(KERNEL_RV) \
+			-initrd $(INITPROC_FALLBACK_DIR)/rootfs.cpio \
+			-append "init=/$$init" \
+			-m 1024 \
+			-nographic \
+			-bios default \
+			-drive file=$(SDCARD_RV),if=none,format=raw,id=x0 \
+			-device virtio-blk-device,drive=x0,bus=virtio-mmio-bus.0 \
+			-no-reboot > $(INITPROC_FALLBACK_DIR)/$$init.log; \
+		grep -q "cannot start init process \"/$$init\": $$reason" $(INITPROC_FALLBACK_DIR)/$$init.log \
+			|| { echo "initproc-fallback-test: no diagnostic for $$init"; exit 1; }; \
+		grep -q "contents of /:" $(INITPROC_FALLBACK_DIR)/$$init.log \
+			|| { echo "initproc-fallback-test: root not listed for $$init"; exit 1; }; \
+		! grep -q -i "panicked" $(INITPROC_FALLBACK_DIR)/$$init.log \
+			|| { echo "initproc-fallback-test: kernel panicked for $$init"; exit 1; }; \
+	done
+	@echo "initproc-fallback-test: passed"
+
 .PHONY: user
\ No newline at end of file
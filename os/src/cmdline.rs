@@ -0,0 +1,157 @@
+//! Kernel command line
+//!
+//! Boot options are taken from `/chosen/bootargs` in the device tree. Without one,
+//! the `BOOTARGS` environment variable at build time is used instead, e.g.
+//! `BOOTARGS="init=/busybox nosmp" make ...`.
+//!
+//! Supported options, later ones override earlier ones, unknown ones are ignored:
+//! - `loglevel=<0-7|off|error|warn|info|debug|trace>`
+//! - `init=<path>`: program started as pid 1, `initproc` by default
+//! - `ro` / `rw`: with `ro` the preloaded binaries are not written to the root filesystem
+//! - `nosmp`: only the boot hart runs, secondary harts are not started
+//! - `initrdmem=<addr>,<size>`: initramfs already in RAM, for loaders that do not report
+//!   it in the device tree
+//! - `panic_reboot`: reboot after a panic instead of powering off, so that the next boot
+//!   can serve the crash dump from `/proc/crashdump`
+//! - `ramdisk_size=<KiB>`: size of the RAM disk `/dev/ram0`, 4096 by default, 0 to go without
+//! - `root=/dev/<disk><N>`: partition `N` of the boot disk holds the root filesystem,
+//!   `root=/dev/<disk>` for the whole disk; by default the first partition that is not
+//!   an EFI system partition, or the whole disk if it has no partition table
+
+use log::LevelFilter;
+use spin::Mutex;
+
+pub const DEFAULT_INIT: &str = "initproc";
+/// 4 MiB like Linux's `CONFIG_BLK_DEV_RAM_SIZE`
+pub const DEFAULT_RAMDISK_SIZE: usize = 4096 * 1024;
+/// Longer `init=` paths are ignored
+pub const MAX_INIT_PATH: usize = 64;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Cmdline {
+    pub loglevel: Option<LevelFilter>,
+    pub read_only: bool,
+    pub nosmp: bool,
+    pub panic_reboot: bool,
+    /// `[start, end)` given by `initrdmem=`
+    pub initrd: Option<(usize, usize)>,
+    /// Size of `/dev/ram0` in bytes
+    pub ramdisk_size: usize,
+    /// Partition number given by `root=`, 0 for the whole disk
+    pub root: Option<usize>,
+    init: [u8; MAX_INIT_PATH],
+    init_len: usize,
+}
+
+impl Cmdline {
+    pub const DEFAULT: Self = Self {
+        loglevel: None,
+        read_only: false,
+        nosmp: false,
+        panic_reboot: false,
+        initrd: None,
+        ramdisk_size: DEFAULT_RAMDISK_SIZE,
+        root: None,
+        init: [0; MAX_INIT_PATH],
+        init_len: 0,
+    };
+
+    pub fn parse(cmdline: &str) -> Self {
+        let mut result = Self::DEFAULT;
+        for option in cmdline.split_ascii_whitespace() {
+            let (key, value) = match option.split_once('=') {
+                Some((key, value)) => (key, Some(value)),
+                None => (option, None),
+            };
+            match (key, value) {
+                ("loglevel", Some(value)) => {
+                    if let Some(level) = parse_loglevel(value) {
+                        result.loglevel = Some(level);
+                    }
+                }
+                ("init", Some(path)) if !path.is_empty() && path.len() <= MAX_INIT_PATH => {
+                    result.init[..path.len()].copy_from_slice(path.as_bytes());
+                    result.init_len = path.len();
+                }
+                ("ro", None) => result.read_only = true,
+                ("rw", None) => result.read_only = false,
+                ("nosmp", None) => result.nosmp = true,
+                ("panic_reboot", None) => result.panic_reboot = true,
+                ("initrdmem", Some(value)) => {
+                    if let Some((start, size)) = value.split_once(',') {
+                        if let (Some(start), Some(size)) = (parse_number(start), parse_number(size))
+                        {
+                            result.initrd = (size != 0).then_some((start, start + size));
+                        }
+                    }
+                }
+                ("ramdisk_size", Some(value)) => {
+                    if let Some(kib) = parse_number(value).and_then(|kib| kib.checked_mul(1024)) {
+                        result.ramdisk_size = kib;
+                    }
+                }
+                ("root", Some(device)) => {
+                    let name = device.strip_prefix("/dev/").unwrap_or(device);
+                    let disk = name.trim_end_matches(|c: char| c.is_ascii_digit());
+                    if !disk.is_empty() && disk.bytes().all(|byte| byte.is_ascii_lowercase()) {
+                        result.root = Some(name[disk.len()..].parse().unwrap_or(0));
+                    }
+                }
+                _ => {}
+            }
+        }
+        result
+    }
+
+    /// Path of the first user program
+    pub fn init_path(&self) -> &str {
+        match self.init_len {
+            0 => DEFAULT_INIT,
+            // copied from a `&str` in `parse`, always valid UTF-8
+            len => core::str::from_utf8(&self.init[..len]).unwrap_or(DEFAULT_INIT),
+        }
+    }
+}
+
+/// Decimal, or hexadecimal with a `0x` prefix
+fn parse_number(value: &str) -> Option<usize> {
+    match value.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+/// Numeric levels follow the Linux console loglevels
+fn parse_loglevel(value: &str) -> Option<LevelFilter> {
+    Some(match value {
+        "off" => LevelFilter::Off,
+        "0" | "1" | "2" | "3" | "error" => LevelFilter::Error,
+        "4" | "warn" => LevelFilter::Warn,
+        "5" | "6" | "info" => LevelFilter::Info,
+        "7" | "debug" => LevelFilter::Debug,
+        "trace" => LevelFilter::Trace,
+        _ => return None,
+    })
+}
+
+static CMDLINE: Mutex<Cmdline> = Mutex::new(Cmdline::DEFAULT);
+
+/// Parse the command line and apply `loglevel=`, must run after `console::log_init`
+pub fn init() {
+    let platform = crate::hal::fdt::platform();
+    let bootargs = match platform.as_ref().map(|info| info.bootargs()) {
+        Some(bootargs) if !bootargs.is_empty() => bootargs,
+        _ => option_env!("BOOTARGS").unwrap_or(""),
+    };
+    let cmdline = Cmdline::parse(bootargs);
+    println!("[kernel] command line: \"{}\"", bootargs);
+    if let Some(level) = cmdline.loglevel {
+        log::set_max_level(level);
+    }
+    *CMDLINE.lock() = cmdline;
+}
+
+/// Options in effect, the defaults until [`init`] has run
+pub fn get() -> Cmdline {
+    *CMDLINE.lock()
+}
@@ -1,6 +1,7 @@
 use crate::hal::{console_flush, console_putchar, disable_interrupts, restore_interrupts};
 use crate::task::current_task;
 use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicBool, Ordering};
 use log::{self, Level, LevelFilter, Log, Metadata, Record};
 use spin::Mutex;
 
@@ -27,16 +28,134 @@ impl Write for KernelOutput {
 }
 
 /// Global stdout with spinlock protection
+///
+/// Kept in `.data` so the lock is valid before `.bss` is cleared, whichever hart
+/// gets to print first. A whole `print!`/`println!` is written under the lock,
+/// lines from different harts never interleave.
+#[link_section = ".data"]
 static STDOUT: Mutex<KernelOutput> = Mutex::new(KernelOutput);
 
+/// Set by [`log_init`], output before that takes the lock-free early path.
+/// In `.data` for the same reason as `STDOUT`.
+#[link_section = ".data"]
+static CONSOLE_READY: AtomicBool = AtomicBool::new(false);
+
+/// How long [`panic_print`] waits for `STDOUT` before writing anyway
+const PANIC_LOCK_SPINS: usize = 1 << 20;
+
+/// Bytes of recent console output kept for the crash dump
+const LOG_RING_SIZE: usize = 4096;
+
+/// The most recent console output, oldest bytes are overwritten first
+struct LogRing {
+    buf: [u8; LOG_RING_SIZE],
+    /// Bytes written so far, the next one goes to `written % LOG_RING_SIZE`
+    written: usize,
+}
+
+impl Write for LogRing {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &byte in s.as_bytes() {
+            self.buf[self.written % LOG_RING_SIZE] = byte;
+            self.written += 1;
+        }
+        Ok(())
+    }
+}
+
+impl LogRing {
+    /// The last `lines` complete lines, as the part before and after the wrap point
+    fn tail(&self, lines: usize) -> (&[u8], &[u8]) {
+        let head = self.written % LOG_RING_SIZE;
+        let (older, newer) = if self.written > LOG_RING_SIZE {
+            (&self.buf[head..], &self.buf[..head])
+        } else {
+            (&self.buf[..0], &self.buf[..head])
+        };
+        let byte = |i: usize| match older.get(i) {
+            Some(&byte) => byte,
+            None => newer[i - older.len()],
+        };
+        let len = older.len() + newer.len();
+        // the newline ending the last line does not start a new one
+        let mut line_start = None;
+        let mut found = 0;
+        for i in (0..len.saturating_sub(1)).rev() {
+            if byte(i) == b'\n' {
+                found += 1;
+                line_start = Some(i + 1);
+                if found == lines {
+                    break;
+                }
+            }
+        }
+        // once wrapped, the oldest line has lost its beginning
+        let start = match line_start {
+            Some(start) if found == lines || self.written > LOG_RING_SIZE => start,
+            _ => 0,
+        };
+        if start < older.len() {
+            (&older[start..], newer)
+        } else {
+            (&newer[start - older.len()..], &newer[..0])
+        }
+    }
+}
+
+/// Written together with `STDOUT`, under its lock
+static LOG_RING: Mutex<LogRing> = Mutex::new(LogRing {
+    buf: [0; LOG_RING_SIZE],
+    written: 0,
+});
+
+/// Print without locking or allocating, usable from the first instructions of `rust_main`.
+///
+/// Only the boot hart runs this early, so there is nobody to race with.
+pub fn early_print(args: fmt::Arguments) {
+    KernelOutput.write_fmt(args).unwrap();
+}
+
 /// Print formatted output to console with interrupt protection
+///
+/// Falls back to [`early_print`] before the console is initialized.
 pub fn print(args: fmt::Arguments) {
+    if !CONSOLE_READY.load(Ordering::Acquire) {
+        return early_print(args);
+    }
     // Disable interrupts before acquiring lock to prevent deadlock from timer interrupt
     let interrupts_were_enabled = disable_interrupts();
-    STDOUT.lock().write_fmt(args).unwrap();
+    let mut stdout = STDOUT.lock();
+    stdout.write_fmt(args).unwrap();
+    let _ = LOG_RING.lock().write_fmt(args);
+    drop(stdout);
     restore_interrupts(interrupts_were_enabled);
 }
 
+/// Pass the last `lines` lines of console output to `f`, in two parts because the
+/// buffer wraps around. For the panic handler: gives up if the buffer is locked.
+pub fn try_log_tail<R>(lines: usize, f: impl FnOnce(&[u8], &[u8]) -> R) -> Option<R> {
+    let ring = LOG_RING.try_lock()?;
+    let (older, newer) = ring.tail(lines);
+    Some(f(older, newer))
+}
+
+/// Print from the panic handler.
+///
+/// The panicking hart may itself hold `STDOUT` (a panic inside `print`), or another
+/// hart may have died holding it. Wait a bounded time for the lock so that output
+/// stays readable in the common case, then give up and write raw.
+pub fn panic_print(args: fmt::Arguments) {
+    disable_interrupts();
+    for _ in 0..PANIC_LOCK_SPINS {
+        if let Some(mut stdout) = STDOUT.try_lock() {
+            let _ = stdout.write_fmt(args);
+            return;
+        }
+        core::hint::spin_loop();
+    }
+    let _ = KernelOutput.write_fmt(args);
+}
+
 // 下面的宏定义不用动，它们会调用上面的 print 函数
 #[macro_export]
 macro_rules! print {
@@ -63,6 +182,7 @@ pub fn log_init() {
         Some("trace") => LevelFilter::Trace,
         _ => LevelFilter::Off,
     });
+    CONSOLE_READY.store(true, Ordering::Release);
 }
 
 struct Logger;
@@ -76,12 +196,17 @@ impl Log for Logger {
             return;
         }
 
-        print!("\x1b[{}m", level_to_color_code(record.level()));
+        // one print per record, so that records from different harts do not interleave
+        let color = level_to_color_code(record.level());
         match current_task() {
-            Some(task) => println!("pid {}: {}", task.pid.0, record.args()),
-            None => println!("kernel: {}", record.args()),
+            Some(task) => println!(
+                "\x1b[{}mpid {}: {}\x1b[0m",
+                color,
+                task.pid.0,
+                record.args()
+            ),
+            None => println!("\x1b[{}mkernel: {}\x1b[0m", color, record.args()),
         }
-        print!("\x1b[0m")
     }
 
     fn flush(&self) {}
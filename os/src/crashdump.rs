@@ -0,0 +1,194 @@
+//! Crash dump kept across a reboot
+//!
+//! On panic the kernel writes a plain-text report into the last [`REGION_SIZE`] bytes
+//! of RAM: the panic location and message, the last trap, the current task with its
+//! user registers, and the tail of the console output. The region is neither handed
+//! to the frame allocator nor cleared at boot, so after a warm reboot (`panic_reboot`
+//! on the command line) the next kernel finds the report and serves it from
+//! `/proc/crashdump`.
+//!
+//! QEMU loads its device tree at the start of the last 2 MiB of RAM and keeps RAM
+//! contents over a reset, so the end of RAM is left alone until the next boot.
+//!
+//! The region starts with a 16-byte header, magic, text length and a checksum of
+//! the text, written last so that a report cut short by a second fault is ignored.
+
+use crate::console::try_log_tail;
+use crate::hal::{get_bad_addr, get_bad_ins_addr, get_exception_cause};
+use crate::task::processor::current_cpu_id;
+use crate::task::try_current_task;
+use alloc::string::String;
+use core::convert::TryInto;
+use core::fmt::{self, Write};
+use core::panic::Location;
+use spin::Mutex;
+
+/// Bytes reserved at the end of RAM, header included
+pub const REGION_SIZE: usize = 0x10000;
+pub const HEADER_LEN: usize = 16;
+const MAGIC: u64 = u64::from_le_bytes(*b"NPUCRASH");
+/// Console lines copied into the report
+const LOG_LINES: usize = 32;
+
+/// FNV-1a
+fn checksum(data: &[u8]) -> u32 {
+    data.iter().fold(0x811c_9dc5, |hash, &byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// Formats the text of a report into a region, dropping whatever does not fit
+pub struct Writer<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> Writer<'a> {
+    /// Invalidates the report already in `buf`
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        buf[..HEADER_LEN].fill(0);
+        Self { buf, len: 0 }
+    }
+
+    /// Append raw console output, without the escape sequences that color log lines
+    pub fn write_console(&mut self, output: &[u8]) {
+        let mut in_escape = false;
+        for &byte in output {
+            match byte {
+                0x1b => in_escape = true,
+                _ if in_escape => in_escape = !byte.is_ascii_alphabetic(),
+                b'\r' => {}
+                _ if byte.is_ascii() => self.push(byte),
+                _ => self.push(b'?'),
+            }
+        }
+    }
+
+    fn push(&mut self, byte: u8) {
+        if let Some(slot) = self.buf.get_mut(HEADER_LEN + self.len) {
+            *slot = byte;
+            self.len += 1;
+        }
+    }
+
+    /// Write the header, the report is valid from here on
+    pub fn finish(self) {
+        let sum = checksum(&self.buf[HEADER_LEN..HEADER_LEN + self.len]);
+        self.buf[8..12].copy_from_slice(&(self.len as u32).to_le_bytes());
+        self.buf[12..16].copy_from_slice(&sum.to_le_bytes());
+        self.buf[..8].copy_from_slice(&MAGIC.to_le_bytes());
+    }
+}
+
+impl Write for Writer<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        // a truncated report is still worth keeping, so never fail
+        for &byte in s.as_bytes() {
+            self.push(byte);
+        }
+        Ok(())
+    }
+}
+
+/// The text of the report in `region`, `None` if there is no intact one
+pub fn parse(region: &[u8]) -> Option<&str> {
+    let header = region.get(..HEADER_LEN)?;
+    if header[..8] != MAGIC.to_le_bytes() {
+        return None;
+    }
+    let len = u32::from_le_bytes(header[8..12].try_into().unwrap()) as usize;
+    let sum = u32::from_le_bytes(header[12..16].try_into().unwrap());
+    let text = region.get(HEADER_LEN..HEADER_LEN + len)?;
+    if checksum(text) != sum {
+        return None;
+    }
+    core::str::from_utf8(text).ok()
+}
+
+/// Report left by the previous boot, taken out of the region by [`init`]
+static PREVIOUS: Mutex<Option<String>> = Mutex::new(None);
+
+/// Physical `[start, end)` of the dump region
+pub fn region() -> (usize, usize) {
+    let end = crate::mm::memory_end();
+    (end - REGION_SIZE, end)
+}
+
+fn region_bytes() -> &'static mut [u8] {
+    // the kernel address space maps physical memory one-to-one
+    unsafe { core::slice::from_raw_parts_mut(region().0 as *mut u8, REGION_SIZE) }
+}
+
+/// Pick up the report of the previous boot, if any, and clear the region.
+/// Needs the heap.
+pub fn init() {
+    let region = region_bytes();
+    if let Some(text) = parse(region) {
+        println!(
+            "[kernel] the previous boot panicked, crash dump in /proc/crashdump ({} bytes)",
+            text.len()
+        );
+        *PREVIOUS.lock() = Some(String::from(text));
+    }
+    region[..HEADER_LEN].fill(0);
+}
+
+/// The report left by the previous boot
+pub fn previous() -> Option<String> {
+    PREVIOUS.lock().clone()
+}
+
+/// Format the report of a panic into `w`.
+///
+/// Called from the panic handler: takes no lock that may be held and does not
+/// allocate, anything it cannot get is reported as unavailable.
+pub fn write_report(
+    w: &mut Writer,
+    location: Option<&Location>,
+    message: Option<&fmt::Arguments>,
+) -> fmt::Result {
+    writeln!(w, "NPUCore crash dump")?;
+    match location {
+        Some(location) => write!(w, "panic: {}: ", location)?,
+        None => write!(w, "panic: ")?,
+    }
+    match message {
+        Some(message) => writeln!(w, "{}", message)?,
+        None => writeln!(w, "(no message)")?,
+    }
+    writeln!(w, "hart: {}", current_cpu_id())?;
+    writeln!(
+        w,
+        "last trap: {:?} at pc {:#x}, bad address {:#x}",
+        get_exception_cause(),
+        get_bad_ins_addr(),
+        get_bad_addr()
+    )?;
+    match try_current_task() {
+        Ok(Some(task)) => {
+            writeln!(w, "task: pid {} tid {}", task.pid.0, task.tid)?;
+            match task.try_acquire_inner_lock() {
+                Some(inner) => writeln!(w, "user registers: {:x?}", inner.get_trap_cx().gp)?,
+                None => writeln!(w, "user registers: unavailable, task locked")?,
+            }
+        }
+        Ok(None) => writeln!(w, "task: none")?,
+        Err(()) => writeln!(w, "task: unavailable, processor locked")?,
+    }
+    writeln!(w, "console, last {} lines:", LOG_LINES)?;
+    let copied = try_log_tail(LOG_LINES, |older, newer| {
+        w.write_console(older);
+        w.write_console(newer);
+    });
+    if copied.is_none() {
+        writeln!(w, "unavailable, console buffer locked")?;
+    }
+    Ok(())
+}
+
+/// Write the report of a panic to the dump region, from the panic handler
+pub fn save(location: Option<&Location>, message: Option<&fmt::Arguments>) {
+    let mut w = Writer::new(region_bytes());
+    let _ = write_report(&mut w, location, message);
+    w.finish();
+}
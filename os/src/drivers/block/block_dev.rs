@@ -2,10 +2,21 @@
 //!
 //! Defines the common interface for all block storage devices
 
+use alloc::string::String;
 use core::any::Any;
 
 use crate::hal::BLOCK_SZ;
 
+/// Identity and health data reported by a drive
+pub struct DriveHealth {
+    pub model: String,
+    pub serial: String,
+    /// Capacity in 512-byte sectors
+    pub sectors: u64,
+    /// Temperature in Celsius, `None` if the drive does not report it
+    pub temperature: Option<u8>,
+}
+
 /// Block device trait
 ///
 /// Provides block-level read/write operations for storage devices.
@@ -36,6 +47,51 @@ pub trait BlockDevice: Send + Sync + Any {
     /// May panic if buf size is not a multiple of BLOCK_SZ (implementation-dependent)
     fn write_block(&self, block_id: usize, buf: &[u8]);
 
+    /// Read consecutive blocks into several buffers
+    ///
+    /// The first buffer receives the blocks from `block_id` on, each following
+    /// buffer the blocks right after the previous one. Every buffer holds a
+    /// whole number of blocks.
+    ///
+    /// Devices able to scatter one transfer over several buffers override this,
+    /// the default reads each buffer with its own `read_block`.
+    fn read_blocks(&self, mut block_id: usize, bufs: &mut [&mut [u8]]) {
+        for buf in bufs.iter_mut() {
+            self.read_block(block_id, buf);
+            block_id += buf.len() / BLOCK_SZ;
+        }
+    }
+
+    /// Write consecutive blocks from several buffers, laid out as for `read_blocks`
+    fn write_blocks(&self, mut block_id: usize, bufs: &[&[u8]]) {
+        for buf in bufs.iter() {
+            self.write_block(block_id, buf);
+            block_id += buf.len() / BLOCK_SZ;
+        }
+    }
+
+    /// Whether the device refuses writes
+    ///
+    /// `write_block` on a read-only device drops the data and logs an error;
+    /// filesystems on it can only be mounted read-only, which keeps writes
+    /// from reaching it in the first place.
+    fn read_only(&self) -> bool {
+        false
+    }
+
+    /// Make the writes done so far durable by flushing the device's write cache
+    ///
+    /// Devices without a volatile cache keep the default no-op.
+    fn flush(&self) {}
+
+    /// Identity and health data of a physical drive, `None` for other devices
+    fn health(&self) -> Option<DriveHealth> {
+        None
+    }
+
+    /// Handle the device interrupt, called by the interrupt controller after claiming it
+    fn handle_irq(&self) {}
+
     /// Clear a block (fill with specified byte value)
     ///
     /// # Arguments
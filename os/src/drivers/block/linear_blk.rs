@@ -0,0 +1,83 @@
+//! Linear block device
+//!
+//! Concatenates ranges of other block devices like device-mapper's linear
+//! target: the blocks of the first range come first, followed by those of the
+//! second and so on. Blocks past the last range read as zeros and writes to
+//! them are dropped. The device is read-only if any backing device is.
+
+use super::BlockDevice;
+use crate::hal::BLOCK_SZ;
+use crate::syscall::errno::EINVAL;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+/// A range of blocks on a backing device
+pub struct LinearTarget {
+    pub device: Arc<dyn BlockDevice>,
+    /// First block of the range on `device`
+    pub offset: usize,
+    /// Number of blocks in the range
+    pub len: usize,
+}
+
+pub struct LinearDevice {
+    targets: Vec<LinearTarget>,
+}
+
+impl LinearDevice {
+    /// A device made of `targets` in order, EINVAL if there are none or one is
+    /// empty
+    pub fn new(targets: Vec<LinearTarget>) -> Result<Self, isize> {
+        if targets.is_empty() || targets.iter().any(|target| target.len == 0) {
+            return Err(EINVAL);
+        }
+        Ok(Self { targets })
+    }
+
+    /// Number of blocks
+    pub fn blocks(&self) -> usize {
+        self.targets.iter().map(|target| target.len).sum()
+    }
+
+    /// The backing device and the block on it holding block `block_id`
+    fn map(&self, block_id: usize) -> Option<(&Arc<dyn BlockDevice>, usize)> {
+        let mut start = 0;
+        for target in &self.targets {
+            if block_id < start + target.len {
+                return Some((&target.device, target.offset + block_id - start));
+            }
+            start += target.len;
+        }
+        None
+    }
+}
+
+impl BlockDevice for LinearDevice {
+    /// A buffer of several blocks may span ranges, it is split into blocks
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        for (index, block) in buf.chunks_mut(BLOCK_SZ).enumerate() {
+            match self.map(block_id + index) {
+                Some((device, backing_id)) => device.read_block(backing_id, block),
+                None => block.fill(0),
+            }
+        }
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        for (index, block) in buf.chunks(BLOCK_SZ).enumerate() {
+            if let Some((device, backing_id)) = self.map(block_id + index) {
+                device.write_block(backing_id, block);
+            }
+        }
+    }
+
+    fn read_only(&self) -> bool {
+        self.targets.iter().any(|target| target.device.read_only())
+    }
+
+    fn flush(&self) {
+        for target in &self.targets {
+            target.device.flush();
+        }
+    }
+}
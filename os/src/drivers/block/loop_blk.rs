@@ -0,0 +1,99 @@
+//! Loop block device
+//!
+//! Presents a regular file as a block device: block `n` is the `BLOCK_SZ`
+//! bytes of the file at offset `n * BLOCK_SZ`. Blocks past the end of the
+//! file read as zeros and writing them extends the file. A device without a
+//! backing file reads as zeros and drops writes. A device backed by a file
+//! opened read-only is itself read-only.
+
+use super::BlockDevice;
+use crate::fs::file_trait::File;
+use crate::hal::BLOCK_SZ;
+use crate::syscall::errno::{EBUSY, ENXIO};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+use lazy_static::*;
+use log::error;
+use spin::RwLock;
+
+/// Number of `/dev/loopN` devices
+pub const LOOP_DEVICE_COUNT: usize = 4;
+
+pub struct LoopDevice {
+    backing: RwLock<Option<Arc<dyn File>>>,
+    read_only: AtomicBool,
+}
+
+lazy_static! {
+    /// The devices behind `/dev/loop0` to `/dev/loop3`
+    pub static ref LOOP_DEVICES: Vec<Arc<LoopDevice>> = (0..LOOP_DEVICE_COUNT)
+        .map(|_| Arc::new(LoopDevice::new()))
+        .collect();
+}
+
+impl LoopDevice {
+    pub fn new() -> Self {
+        Self {
+            backing: RwLock::new(None),
+            read_only: AtomicBool::new(false),
+        }
+    }
+
+    /// Back the device with `file`, EBUSY if it already has a backing file
+    pub fn attach(&self, file: Arc<dyn File>) -> Result<(), isize> {
+        let mut backing = self.backing.write();
+        if backing.is_some() {
+            return Err(EBUSY);
+        }
+        self.read_only.store(!file.writable(), Ordering::Release);
+        *backing = Some(file);
+        Ok(())
+    }
+
+    /// Drop the backing file, ENXIO if there is none. Filesystems mounted from
+    /// the device stay mounted and read zeros from then on.
+    pub fn detach(&self) -> Result<(), isize> {
+        match self.backing.write().take() {
+            Some(_) => Ok(()),
+            None => Err(ENXIO),
+        }
+    }
+
+    pub fn is_attached(&self) -> bool {
+        self.backing.read().is_some()
+    }
+}
+
+impl BlockDevice for LoopDevice {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        let mut offset = block_id * BLOCK_SZ;
+        let len = match self.backing.read().as_ref() {
+            Some(file) => file.read(Some(&mut offset), buf).min(buf.len()),
+            None => 0,
+        };
+        buf[len..].fill(0);
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        if self.read_only() {
+            error!("[loop] write to block {} of a read-only device dropped", block_id);
+            return;
+        }
+        if let Some(file) = self.backing.read().as_ref() {
+            let mut offset = block_id * BLOCK_SZ;
+            file.write(Some(&mut offset), buf);
+        }
+    }
+
+    fn read_only(&self) -> bool {
+        self.read_only.load(Ordering::Acquire)
+    }
+
+    /// The backing file's data reaches the disk below it
+    fn flush(&self) {
+        if let Some(file) = self.backing.read().as_ref() {
+            file.fsync();
+        }
+    }
+}
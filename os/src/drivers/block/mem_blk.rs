@@ -47,4 +47,20 @@ impl BlockDevice for MemBlockWrapper {
         let blk = self.0.lock();
         blk.block_refmut(block_id, buf.len()).copy_from_slice(buf);
     }
+    /// 读取连续的多个块，依次存入各个缓冲区
+    fn read_blocks(&self, mut block_id: usize, bufs: &mut [&mut [u8]]) {
+        let blk = self.0.lock();
+        for buf in bufs.iter_mut() {
+            buf.copy_from_slice(blk.block_ref(block_id, buf.len()));
+            block_id += buf.len() / BLOCK_SZ;
+        }
+    }
+    /// 把各个缓冲区依次写入连续的多个块
+    fn write_blocks(&self, mut block_id: usize, bufs: &[&[u8]]) {
+        let blk = self.0.lock();
+        for buf in bufs.iter() {
+            blk.block_refmut(block_id, buf.len()).copy_from_slice(buf);
+            block_id += buf.len() / BLOCK_SZ;
+        }
+    }
 }
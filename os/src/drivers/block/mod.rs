@@ -2,20 +2,53 @@
 //!
 //! Provides unified block device interface with multiple implementations:
 //! - Memory block device (for testing without real storage)
+//! - A RAM disk sized at boot for scratch filesystems
+//! - Loop devices presenting a regular file as a block device
+//! - A linear device concatenating ranges of other block devices
+//! - Partitions of a disk found in its MBR or GPT partition table
 //! - SATA disk driver
 //! - VirtIO block device (MMIO and PCI variants)
 //!
 //! The actual implementation is selected at compile time via feature flags.
+//!
+//! A physical drive may report its identity and health (`BlockDevice::health`),
+//! the SATA disk is then exposed as `/dev/sda` with `/sys/block/sda/size`.
+//!
+//! A device may be read-only (`BlockDevice::read_only`), filesystems on it can
+//! then only be mounted with MS_RDONLY.
+//!
+//! The caches batch their transfers through a `RequestQueue`, which merges
+//! adjacent blocks into multi-block device requests.
+//!
+//! The disk's partition table is read when it is first used: `DISK` is the
+//! whole disk, `PARTITIONS` its partitions, and `BLOCK_DEVICE` the one holding
+//! the root filesystem (see `root=` in `cmdline`).
 
 mod block_dev;
+mod linear_blk;
+mod loop_blk;
 mod mem_blk;
+mod partition;
+mod ram_blk;
+mod request_queue;
 mod sata_blk;
 #[cfg(feature = "block_virt")]
 mod virtio_blk;
 #[cfg(feature = "block_virt_pci")]
 mod virtio_blk_pci;
 
-pub use block_dev::BlockDevice;
+pub use block_dev::{BlockDevice, DriveHealth};
+pub use linear_blk::{LinearDevice, LinearTarget};
+pub use loop_blk::{LoopDevice, LOOP_DEVICES};
+pub use partition::{probe_partitions, Partition, PartitionBlockDevice};
+pub use ram_blk::{RamDisk, RAM_DISK};
+pub use request_queue::RequestQueue;
+#[cfg(feature = "block_virt")]
+pub(crate) use virtio_blk::{probe_mmio, VirtioHal};
+#[cfg(feature = "virtio_irq")]
+pub(crate) use virtio_blk::mmio_irq;
+#[cfg(feature = "block_virt_pci")]
+pub(crate) use virtio_blk_pci::{enumerate_pci, VirtioHal};
 
 // Select block device implementation based on features
 #[cfg(feature = "block_mem")]
@@ -29,11 +62,66 @@ type BlockDeviceImpl = virtio_blk_pci::VirtIOBlock;
 
 use crate::hal::BLOCK_SZ;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use lazy_static::*;
 
 lazy_static! {
-    /// Global block device instance
-    pub static ref BLOCK_DEVICE: Arc<dyn BlockDevice> = Arc::new(BlockDeviceImpl::new());
+    /// The whole disk
+    pub static ref DISK: Arc<dyn BlockDevice> = Arc::new(BlockDeviceImpl::new());
+    /// Partitions of `DISK` ordered by number
+    pub static ref PARTITIONS: Vec<Arc<PartitionBlockDevice>> = probe_partitions(&DISK);
+    /// Device holding the root filesystem
+    pub static ref BLOCK_DEVICE: Arc<dyn BlockDevice> = root_device();
+}
+
+/// Name of `DISK` under `/dev`, a SATA drive is `sda` and any other disk `vda`
+pub fn disk_name() -> &'static str {
+    if DISK.health().is_some() {
+        "sda"
+    } else {
+        "vda"
+    }
+}
+
+/// The partition given by `root=`, by default the first one that is not an EFI
+/// system partition, or the whole disk if there is none
+fn root_device() -> Arc<dyn BlockDevice> {
+    for partition in PARTITIONS.iter() {
+        let Partition {
+            number,
+            start,
+            sectors,
+            ..
+        } = partition.partition();
+        println!(
+            "[kernel] {}{}: sectors {}..{}",
+            disk_name(),
+            number,
+            start,
+            start + sectors
+        );
+    }
+    let root = crate::cmdline::get().root;
+    if root == Some(0) {
+        return DISK.clone();
+    }
+    let named = root.and_then(|number| {
+        let partition = PARTITIONS
+            .iter()
+            .find(|partition| partition.partition().number == number);
+        if partition.is_none() {
+            log::warn!("[kernel] root partition {} not found", number);
+        }
+        partition
+    });
+    match named.or_else(|| {
+        PARTITIONS
+            .iter()
+            .find(|partition| !partition.partition().efi_system)
+    }) {
+        Some(partition) => partition.clone(),
+        None => DISK.clone(),
+    }
 }
 
 /// Test block device read/write operations
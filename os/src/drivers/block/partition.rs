@@ -0,0 +1,323 @@
+//! Partition tables
+//!
+//! Reads the MBR or GPT partition table of a disk and presents every
+//! partition as a `PartitionBlockDevice`, a window of the disk starting at the
+//! partition's first block. Partitions are numbered as in Linux device names:
+//! MBR primary partitions are 1 to 4 by slot and logical partitions in the
+//! extended partition follow from 5, GPT partitions take the number of their
+//! entry.
+//!
+//! A disk whose first sector is the boot sector of a FAT filesystem has no
+//! partition table even though it ends in the same 0x55AA signature. Table
+//! offsets are in 512-byte sectors, a partition not aligned to `BLOCK_SZ` is
+//! skipped.
+
+use super::BlockDevice;
+use crate::hal::BLOCK_SZ;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+
+/// Sector size the partition tables count in
+const SECTOR_SZ: usize = 512;
+const SECTORS_PER_BLOCK: u64 = (BLOCK_SZ / SECTOR_SZ) as u64;
+/// MBR partition types
+const MBR_EXTENDED: [u8; 3] = [0x05, 0x0f, 0x85];
+const MBR_EFI_SYSTEM: u8 = 0xef;
+const MBR_GPT_PROTECTIVE: u8 = 0xee;
+/// Logical partitions followed in an extended partition at most
+const MAX_LOGICAL: usize = 64;
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+/// Entries read from a GPT at most
+const MAX_GPT_ENTRIES: usize = 128;
+/// C12A7328-F81F-11D2-BA4B-00A0C93EC93B as stored on disk
+const GPT_EFI_SYSTEM: [u8; 16] = [
+    0x28, 0x73, 0x2a, 0xc1, 0x1f, 0xf8, 0xd2, 0x11, 0xba, 0x4b, 0x00, 0xa0, 0xc9, 0x3e, 0xc9, 0x3b,
+];
+
+/// A partition listed in a partition table
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Partition {
+    /// Number in the device name, 2 for `/dev/vda2`
+    pub number: usize,
+    /// First sector
+    pub start: u64,
+    /// Length in sectors
+    pub sectors: u64,
+    /// EFI system partition, which never holds the root filesystem
+    pub efi_system: bool,
+}
+
+/// The blocks of one partition of a disk
+///
+/// Blocks past the end of the partition read as zeros and writes to them are
+/// dropped, so that a corrupt filesystem cannot reach its neighbours.
+pub struct PartitionBlockDevice {
+    device: Arc<dyn BlockDevice>,
+    partition: Partition,
+    /// First block of the partition on `device`
+    offset: usize,
+    /// Number of blocks
+    len: usize,
+}
+
+impl PartitionBlockDevice {
+    /// The blocks of `partition` on `device`, `None` if it does not start on a
+    /// block boundary
+    pub fn new(device: Arc<dyn BlockDevice>, partition: Partition) -> Option<Self> {
+        if partition.start * SECTOR_SZ as u64 % BLOCK_SZ as u64 != 0 {
+            return None;
+        }
+        Some(Self {
+            device,
+            partition,
+            offset: (partition.start / SECTORS_PER_BLOCK) as usize,
+            len: (partition.sectors / SECTORS_PER_BLOCK) as usize,
+        })
+    }
+
+    pub fn partition(&self) -> &Partition {
+        &self.partition
+    }
+
+    /// Number of blocks
+    pub fn blocks(&self) -> usize {
+        self.len
+    }
+
+    /// The block on the disk of block `block_id`, if all `len` bytes from it
+    /// are inside the partition
+    fn map(&self, block_id: usize, len: usize) -> Option<usize> {
+        let blocks = (len + BLOCK_SZ - 1) / BLOCK_SZ;
+        (block_id + blocks <= self.len).then_some(self.offset + block_id)
+    }
+}
+
+impl BlockDevice for PartitionBlockDevice {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        match self.map(block_id, buf.len()) {
+            Some(disk_id) => self.device.read_block(disk_id, buf),
+            None => {
+                log::error!(
+                    "[partition] read of block {} past the end of partition {}",
+                    block_id,
+                    self.partition.number
+                );
+                buf.fill(0);
+            }
+        }
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        match self.map(block_id, buf.len()) {
+            Some(disk_id) => self.device.write_block(disk_id, buf),
+            None => log::error!(
+                "[partition] write of block {} past the end of partition {}",
+                block_id,
+                self.partition.number
+            ),
+        }
+    }
+
+    fn read_blocks(&self, block_id: usize, bufs: &mut [&mut [u8]]) {
+        let len = bufs.iter().map(|buf| buf.len()).sum();
+        match self.map(block_id, len) {
+            Some(disk_id) => self.device.read_blocks(disk_id, bufs),
+            None => {
+                let mut block_id = block_id;
+                for buf in bufs.iter_mut() {
+                    self.read_block(block_id, buf);
+                    block_id += buf.len() / BLOCK_SZ;
+                }
+            }
+        }
+    }
+
+    fn write_blocks(&self, block_id: usize, bufs: &[&[u8]]) {
+        let len = bufs.iter().map(|buf| buf.len()).sum();
+        match self.map(block_id, len) {
+            Some(disk_id) => self.device.write_blocks(disk_id, bufs),
+            None => {
+                let mut block_id = block_id;
+                for buf in bufs.iter() {
+                    self.write_block(block_id, buf);
+                    block_id += buf.len() / BLOCK_SZ;
+                }
+            }
+        }
+    }
+
+    fn read_only(&self) -> bool {
+        self.device.read_only()
+    }
+
+    fn flush(&self) {
+        self.device.flush();
+    }
+}
+
+/// Find the partitions of `disk` and wrap each one that starts on a block boundary
+pub fn probe_partitions(disk: &Arc<dyn BlockDevice>) -> Vec<Arc<PartitionBlockDevice>> {
+    read_partitions(disk.as_ref())
+        .into_iter()
+        .filter_map(|partition| {
+            let device = PartitionBlockDevice::new(disk.clone(), partition);
+            if device.is_none() {
+                log::warn!(
+                    "[partition] partition {} at sector {} is not block aligned, skipped",
+                    partition.number,
+                    partition.start
+                );
+            }
+            device.map(Arc::new)
+        })
+        .collect()
+}
+
+/// The partitions listed in the partition table of `disk`, empty if it has none
+fn read_partitions(disk: &dyn BlockDevice) -> Vec<Partition> {
+    let mbr = read_sector(disk, 0);
+    if !is_mbr(&mbr) {
+        return Vec::new();
+    }
+    let entries = mbr_entries(&mbr);
+    if entries.iter().any(|entry| entry.kind == MBR_GPT_PROTECTIVE) {
+        return read_gpt(disk);
+    }
+    let mut partitions = Vec::new();
+    for (slot, entry) in entries.iter().enumerate() {
+        if entry.kind == 0 || entry.sectors == 0 {
+            continue;
+        }
+        if MBR_EXTENDED.contains(&entry.kind) {
+            read_logical(disk, entry.start, &mut partitions);
+            continue;
+        }
+        partitions.push(Partition {
+            number: slot + 1,
+            start: entry.start,
+            sectors: entry.sectors,
+            efi_system: entry.kind == MBR_EFI_SYSTEM,
+        });
+    }
+    partitions.sort_by_key(|partition| partition.number);
+    partitions
+}
+
+/// Sector `lba` of `disk`
+fn read_sector(disk: &dyn BlockDevice, lba: u64) -> [u8; SECTOR_SZ] {
+    let offset = lba as usize * SECTOR_SZ;
+    let mut block = vec![0u8; BLOCK_SZ];
+    disk.read_block(offset / BLOCK_SZ, &mut block);
+    let mut sector = [0u8; SECTOR_SZ];
+    sector.copy_from_slice(&block[offset % BLOCK_SZ..offset % BLOCK_SZ + SECTOR_SZ]);
+    sector
+}
+
+fn u32_at(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn u64_at(buf: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap())
+}
+
+/// One of the four partition entries of an MBR or EBR
+struct MbrEntry {
+    boot: u8,
+    kind: u8,
+    start: u64,
+    sectors: u64,
+}
+
+fn mbr_entries(sector: &[u8; SECTOR_SZ]) -> [MbrEntry; 4] {
+    [0, 1, 2, 3].map(|slot| {
+        let entry = &sector[446 + 16 * slot..446 + 16 * (slot + 1)];
+        MbrEntry {
+            boot: entry[0],
+            kind: entry[4],
+            start: u32_at(entry, 8) as u64,
+            sectors: u32_at(entry, 12) as u64,
+        }
+    })
+}
+
+/// Whether `sector` holds a partition table rather than a FAT boot sector
+fn is_mbr(sector: &[u8; SECTOR_SZ]) -> bool {
+    if sector[510..] != [0x55, 0xaa] {
+        return false;
+    }
+    // The file system type of a FAT32 and of a FAT12/16 boot sector
+    if &sector[82..85] == b"FAT" || &sector[54..57] == b"FAT" {
+        return false;
+    }
+    let entries = mbr_entries(sector);
+    entries
+        .iter()
+        .all(|entry| entry.boot == 0 || entry.boot == 0x80)
+        && entries
+            .iter()
+            .any(|entry| entry.kind != 0 && entry.sectors != 0)
+}
+
+/// Follow the chain of extended boot records of the extended partition at
+/// `base`, appending the logical partitions numbered from 5
+fn read_logical(disk: &dyn BlockDevice, base: u64, partitions: &mut Vec<Partition>) {
+    let mut ebr = base;
+    for number in 5..5 + MAX_LOGICAL {
+        let sector = read_sector(disk, ebr);
+        if sector[510..] != [0x55, 0xaa] {
+            return;
+        }
+        let [logical, next, ..] = mbr_entries(&sector);
+        if logical.kind != 0 && logical.sectors != 0 {
+            partitions.push(Partition {
+                number,
+                start: ebr + logical.start,
+                sectors: logical.sectors,
+                efi_system: logical.kind == MBR_EFI_SYSTEM,
+            });
+        }
+        // The next EBR is given relative to the start of the extended partition
+        if !MBR_EXTENDED.contains(&next.kind) || next.start == 0 {
+            return;
+        }
+        ebr = base + next.start;
+    }
+}
+
+/// The partitions of the GPT whose header is in sector 1
+fn read_gpt(disk: &dyn BlockDevice) -> Vec<Partition> {
+    let header = read_sector(disk, 1);
+    if &header[..8] != GPT_SIGNATURE {
+        log::warn!("[partition] protective MBR without a GPT header");
+        return Vec::new();
+    }
+    let entries_lba = u64_at(&header, 72);
+    let count = (u32_at(&header, 80) as usize).min(MAX_GPT_ENTRIES);
+    let entry_size = u32_at(&header, 84) as usize;
+    if !(128..=SECTOR_SZ).contains(&entry_size) || SECTOR_SZ % entry_size != 0 {
+        return Vec::new();
+    }
+    let per_sector = SECTOR_SZ / entry_size;
+    let mut partitions = Vec::new();
+    let mut sector = [0u8; SECTOR_SZ];
+    for index in 0..count {
+        if index % per_sector == 0 {
+            sector = read_sector(disk, entries_lba + (index / per_sector) as u64);
+        }
+        let entry = &sector[index % per_sector * entry_size..][..entry_size];
+        let (kind, first, last) = (&entry[..16], u64_at(entry, 32), u64_at(entry, 40));
+        if kind.iter().all(|&byte| byte == 0) || last < first {
+            continue;
+        }
+        partitions.push(Partition {
+            number: index + 1,
+            start: first,
+            sectors: last - first + 1,
+            efi_system: kind == GPT_EFI_SYSTEM,
+        });
+    }
+    partitions
+}
@@ -0,0 +1,117 @@
+//! RAM disk
+//!
+//! A block device of a size fixed at boot (`ramdisk_size=` on the command
+//! line) whose blocks live in physical frames. Frames are only allocated when
+//! a page is first written, pages never written read as zeros, so an unused
+//! RAM disk costs no memory. The contents are lost on reboot.
+
+use super::BlockDevice;
+use crate::config::PAGE_SIZE;
+use crate::hal::BLOCK_SZ;
+use crate::mm::{frame_alloc, FrameTracker};
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use lazy_static::*;
+use log::error;
+use spin::Mutex;
+
+pub struct RamDisk {
+    /// Size in bytes, a multiple of `BLOCK_SZ`
+    size: usize,
+    /// Frames of the pages written so far, by page index
+    pages: Mutex<BTreeMap<usize, Arc<FrameTracker>>>,
+}
+
+lazy_static! {
+    /// The device behind `/dev/ram0`, `None` with `ramdisk_size=0`
+    pub static ref RAM_DISK: Option<Arc<RamDisk>> =
+        match crate::cmdline::get().ramdisk_size {
+            0 => None,
+            size => Some(Arc::new(RamDisk::new(size))),
+        };
+}
+
+impl RamDisk {
+    /// A RAM disk of `size` bytes rounded down to whole blocks
+    pub fn new(size: usize) -> Self {
+        Self {
+            size: size / BLOCK_SZ * BLOCK_SZ,
+            pages: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Size in bytes
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Number of frames allocated to the disk
+    pub fn allocated_pages(&self) -> usize {
+        self.pages.lock().len()
+    }
+
+    /// Copy the bytes at `offset` into `buf`, stopping at the end of the disk.
+    /// Returns the number of bytes read.
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let len = buf.len().min(self.size.saturating_sub(offset));
+        let pages = self.pages.lock();
+        let mut done = 0;
+        while done < len {
+            let (page, start) = ((offset + done) / PAGE_SIZE, (offset + done) % PAGE_SIZE);
+            let chunk = (PAGE_SIZE - start).min(len - done);
+            let dst = &mut buf[done..done + chunk];
+            match pages.get(&page) {
+                Some(frame) => {
+                    dst.copy_from_slice(&frame.ppn.get_bytes_array()[start..start + chunk])
+                }
+                None => dst.fill(0),
+            }
+            done += chunk;
+        }
+        len
+    }
+
+    /// Copy `buf` to the bytes at `offset`, stopping at the end of the disk or
+    /// when no frame is left. Returns the number of bytes written.
+    pub fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let len = buf.len().min(self.size.saturating_sub(offset));
+        let mut pages = self.pages.lock();
+        let mut done = 0;
+        while done < len {
+            let (page, start) = ((offset + done) / PAGE_SIZE, (offset + done) % PAGE_SIZE);
+            let chunk = (PAGE_SIZE - start).min(len - done);
+            let src = &buf[done..done + chunk];
+            let frame = match pages.get(&page) {
+                Some(frame) => frame,
+                // a page still reading as zeros stays unallocated
+                None if src.iter().all(|&byte| byte == 0) => {
+                    done += chunk;
+                    continue;
+                }
+                None => match frame_alloc() {
+                    Some(frame) => pages.entry(page).or_insert(frame),
+                    None => {
+                        error!("[ram_disk] out of memory, write to page {} dropped", page);
+                        break;
+                    }
+                },
+            };
+            frame.ppn.get_bytes_array()[start..start + chunk].copy_from_slice(src);
+            done += chunk;
+        }
+        done
+    }
+}
+
+impl BlockDevice for RamDisk {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        let len = self.read_at(block_id * BLOCK_SZ, buf);
+        buf[len..].fill(0);
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        if self.write_at(block_id * BLOCK_SZ, buf) < buf.len() {
+            error!("[ram_disk] write to block {} not completed", block_id);
+        }
+    }
+}
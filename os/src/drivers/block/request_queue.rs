@@ -0,0 +1,159 @@
+//! Block request queue
+//!
+//! Sits between the caches and `BlockDevice`: callers queue the blocks they
+//! want to read or write, then dispatch the whole batch at once. Dispatching
+//! sorts the requests by block number and merges adjacent requests of the
+//! same direction into one device request, so a run of cache pages costs a
+//! handful of large transfers instead of one transfer per block. A merged
+//! run goes out as one vectored `read_blocks`/`write_blocks` call over the
+//! callers' buffers.
+//!
+//! The policy is a one-way elevator with reads first: callers are usually
+//! waiting on the reads, while writes are write-back. A request overlapping
+//! a queued request of the other direction (or an earlier write) acts as a
+//! barrier and dispatches what is queued before it, so requests touching the
+//! same block still reach the device in submission order.
+
+use super::BlockDevice;
+use crate::hal::BLOCK_SZ;
+use crate::utils::telemetry::{BLOCK_MERGES, BLOCK_REQUESTS};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Largest merged request, in blocks
+const MAX_MERGE_BLOCKS: usize = 32;
+
+/// A queued transfer of whole blocks
+enum Request<'a> {
+    Read { block_id: usize, buf: &'a mut [u8] },
+    Write { block_id: usize, buf: &'a [u8] },
+}
+
+impl Request<'_> {
+    fn block_id(&self) -> usize {
+        match self {
+            Request::Read { block_id, .. } | Request::Write { block_id, .. } => *block_id,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Request::Read { buf, .. } => buf.len(),
+            Request::Write { buf, .. } => buf.len(),
+        }
+    }
+
+    fn blocks(&self) -> usize {
+        self.len() / BLOCK_SZ
+    }
+
+    fn is_write(&self) -> bool {
+        matches!(self, Request::Write { .. })
+    }
+
+    fn overlaps(&self, other: &Self) -> bool {
+        self.block_id() < other.block_id() + other.blocks()
+            && other.block_id() < self.block_id() + self.blocks()
+    }
+}
+
+/// A batch of requests to `device` borrowing the buffers they transfer
+///
+/// Buffers must be non-empty multiples of `BLOCK_SZ`. Reads land in their
+/// buffers when `dispatch` returns.
+pub struct RequestQueue<'a> {
+    device: &'a dyn BlockDevice,
+    requests: Vec<Request<'a>>,
+}
+
+impl<'a> RequestQueue<'a> {
+    pub fn new(device: &'a dyn BlockDevice) -> Self {
+        Self {
+            device,
+            requests: Vec::new(),
+        }
+    }
+
+    /// Queue a read of `buf.len() / BLOCK_SZ` blocks starting at `block_id`
+    pub fn read(&mut self, block_id: usize, buf: &'a mut [u8]) {
+        self.push(Request::Read { block_id, buf });
+    }
+
+    /// Queue a write of `buf.len() / BLOCK_SZ` blocks starting at `block_id`
+    pub fn write(&mut self, block_id: usize, buf: &'a [u8]) {
+        self.push(Request::Write { block_id, buf });
+    }
+
+    fn push(&mut self, request: Request<'a>) {
+        debug_assert!(request.blocks() > 0 && request.len() % BLOCK_SZ == 0);
+        // Reads may overlap reads; anything else touching the same blocks is a barrier
+        let barrier = self
+            .requests
+            .iter()
+            .any(|queued| (queued.is_write() || request.is_write()) && queued.overlaps(&request));
+        if barrier {
+            self.dispatch_queued();
+        }
+        self.requests.push(request);
+    }
+
+    /// Send all queued requests to the device, merged and in elevator order
+    pub fn dispatch(mut self) {
+        self.dispatch_queued();
+    }
+
+    fn dispatch_queued(&mut self) {
+        let mut requests = core::mem::take(&mut self.requests);
+        requests.sort_by_key(|request| (request.is_write(), request.block_id()));
+        let mut requests = requests.into_iter().peekable();
+        while let Some(first) = requests.next() {
+            let mut run = vec![first];
+            while let Some(next) = requests.peek() {
+                let last = run.last().unwrap();
+                let blocks: usize = run.iter().map(Request::blocks).sum();
+                if next.is_write() != last.is_write()
+                    || next.block_id() != last.block_id() + last.blocks()
+                    || blocks + next.blocks() > MAX_MERGE_BLOCKS
+                {
+                    break;
+                }
+                run.push(requests.next().unwrap());
+            }
+            BLOCK_MERGES.add(run.len() as u64 - 1);
+            submit(run, self.device);
+        }
+    }
+}
+
+/// Issue a run of adjacent requests of one direction as a single device request
+fn submit(mut run: Vec<Request>, device: &dyn BlockDevice) {
+    BLOCK_REQUESTS.inc();
+    let block_id = run[0].block_id();
+    if run.len() == 1 {
+        match &mut run[0] {
+            Request::Read { buf, .. } => device.read_block(block_id, buf),
+            Request::Write { buf, .. } => device.write_block(block_id, buf),
+        }
+        return;
+    }
+    // Each buffer is borrowed on its own, hand them to the device as one vectored transfer
+    if run[0].is_write() {
+        let bufs: Vec<&[u8]> = run
+            .iter()
+            .map(|request| match request {
+                Request::Write { buf, .. } => &**buf,
+                Request::Read { .. } => unreachable!(),
+            })
+            .collect();
+        device.write_blocks(block_id, &bufs);
+    } else {
+        let mut bufs: Vec<&mut [u8]> = run
+            .iter_mut()
+            .map(|request| match request {
+                Request::Read { buf, .. } => &mut **buf,
+                Request::Write { .. } => unreachable!(),
+            })
+            .collect();
+        device.read_blocks(block_id, &mut bufs);
+    }
+}
@@ -1,7 +1,8 @@
 use crate::config::PAGE_SIZE;
-use crate::drivers::block::BlockDevice;
+use crate::drivers::block::{BlockDevice, DriveHealth};
 use crate::hal::BLOCK_SZ;
 use crate::mm::{frame_alloc, frame_dealloc, PhysAddr};
+use crate::utils::telemetry::BLOCK_FLUSHES;
 use isomorphic_drivers::{
     block::ahci::{AHCI, BLOCK_SIZE},
     provider,
@@ -18,22 +19,53 @@ impl SataBlock {
 }
 
 impl BlockDevice for SataBlock {
-    fn read_block(&self, mut block_id: usize, buf: &mut [u8]) {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
         // 内核BLOCK_SZ为2048，SATA驱动中BLOCK_SIZE为512，四倍转化关系
-        block_id = block_id * (BLOCK_SZ / BLOCK_SIZE);
-        for buf in buf.chunks_mut(BLOCK_SIZE) {
-            self.0.lock().read_block(block_id, buf);
-            block_id += 1;
+        // 驱动一条命令最多传输一页的扇区
+        self.0
+            .lock()
+            .read_blocks(block_id * (BLOCK_SZ / BLOCK_SIZE), buf);
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        self.0
+            .lock()
+            .write_blocks(block_id * (BLOCK_SZ / BLOCK_SIZE), buf);
+    }
+
+    fn read_blocks(&self, mut block_id: usize, bufs: &mut [&mut [u8]]) {
+        let mut ahci = self.0.lock();
+        for buf in bufs.iter_mut() {
+            ahci.read_blocks(block_id * (BLOCK_SZ / BLOCK_SIZE), buf);
+            block_id += buf.len() / BLOCK_SZ;
         }
     }
 
-    fn write_block(&self, mut block_id: usize, buf: &[u8]) {
-        block_id = block_id * (BLOCK_SZ / BLOCK_SIZE);
-        for buf in buf.chunks(BLOCK_SIZE) {
-            self.0.lock().write_block(block_id, buf);
-            block_id += 1;
+    fn write_blocks(&self, mut block_id: usize, bufs: &[&[u8]]) {
+        let mut ahci = self.0.lock();
+        for buf in bufs.iter() {
+            ahci.write_blocks(block_id * (BLOCK_SZ / BLOCK_SIZE), buf);
+            block_id += buf.len() / BLOCK_SZ;
         }
     }
+
+    fn flush(&self) {
+        self.0.lock().flush();
+        BLOCK_FLUSHES.inc();
+    }
+
+    // 温度每次都重新读取 SMART 数据，其余信息来自初始化时的 IDENTIFY DEVICE
+    fn health(&self) -> Option<DriveHealth> {
+        let mut ahci = self.0.lock();
+        let temperature = ahci.smart_temperature();
+        let identity = ahci.identity();
+        Some(DriveHealth {
+            model: identity.model.clone(),
+            serial: identity.serial.clone(),
+            sectors: identity.sectors,
+            temperature,
+        })
+    }
 }
 
 pub struct Provider;
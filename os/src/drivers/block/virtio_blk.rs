@@ -4,13 +4,28 @@ use crate::mm::{
     StepByOne, VirtAddr,
 };
 use crate::config::{PAGE_SIZE, PAGE_SIZE_BITS};
-use alloc::{sync::Arc, vec::Vec};
+use crate::utils::telemetry::BLOCK_FLUSHES;
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
 use lazy_static::*;
 use spin::Mutex;
 use core::ptr::NonNull;
 use virtio_drivers::device::blk::VirtIOBlk;
+#[cfg(feature = "virtio_irq")]
+use crate::task::{
+    block_current_and_run_next, current_task, wait_with_timeout, wake_interruptible,
+    TaskControlBlock,
+};
+#[cfg(feature = "virtio_irq")]
+use crate::timer::TimeSpec;
+#[cfg(feature = "virtio_irq")]
+use alloc::{boxed::Box, sync::Weak};
+#[cfg(feature = "virtio_irq")]
+use virtio_drivers::device::blk::{BlkReq, BlkResp};
 use virtio_drivers::transport::mmio::{MmioTransport, VirtIOHeader};
+use virtio_drivers::transport::{DeviceType, Transport};
 use virtio_drivers::{Hal, BufferDirection};
+#[cfg(feature = "virtio_irq")]
+use virtio_drivers::Error;
 
 /// VirtIO block device sector size (512 bytes)
 const VIRTIO_BLK_SIZE: usize = 512;
@@ -18,10 +33,59 @@ const VIRTIO_BLK_SIZE: usize = 512;
 #[allow(unused)]
 const VIRTIO0: usize = 0x10001000;
 
-pub struct VirtIOBlock(Mutex<VirtIOBlk<VirtioHal, MmioTransport>>);
+/// 等待中断时的超时，防止在睡眠前到达的中断使任务一直睡下去
+#[cfg(feature = "virtio_irq")]
+const IRQ_WAIT_TIMEOUT: TimeSpec = TimeSpec {
+    tv_sec: 0,
+    tv_nsec: 10_000_000,
+};
+
+/// virtio 块设备
+///
+/// 开启 `virtio_irq` 时，任务发出的请求整块提交到虚拟队列后睡眠在这个请求上，
+/// 设备中断处理中按已用环的顺序完成请求、填入结果并唤醒各自的任务。
+/// 多个任务的请求可以同时在途，队列满时后来的任务睡眠等待空位。
+/// 注意任务睡眠时可能持有文件系统的锁，同一核上争用这些锁的任务会一直自旋，
+/// 直到睡眠的任务被其他核调度。没有当前任务时（如启动阶段）仍然轮询。
+pub struct VirtIOBlock {
+    device: Mutex<VirtIOBlk<VirtioHal, MmioTransport>>,
+    /// 在途的请求，以令牌为键；只在持有 `device` 时访问
+    #[cfg(feature = "virtio_irq")]
+    inflight: Mutex<BTreeMap<u16, InFlight>>,
+    /// 等待队列空位的任务
+    #[cfg(feature = "virtio_irq")]
+    submitters: Mutex<Vec<Weak<TaskControlBlock>>>,
+}
+
+/// 一个在途的请求，由提交的任务分配与释放，完成前地址不变
+#[cfg(feature = "virtio_irq")]
+struct Request {
+    req: BlkReq,
+    resp: BlkResp,
+    /// 读写的缓冲区，提交的任务睡眠期间一直有效
+    buf: *mut u8,
+    len: usize,
+    write: bool,
+    /// 设备完成请求后由 `reap` 填入
+    result: Option<Result<(), Error>>,
+    /// 等待这个请求的任务
+    waiter: Weak<TaskControlBlock>,
+}
+
+/// 在途请求的指针
+#[cfg(feature = "virtio_irq")]
+struct InFlight(*mut Request);
+
+// 请求只在持有设备锁时经这个指针访问
+#[cfg(feature = "virtio_irq")]
+unsafe impl Send for InFlight {}
 
 lazy_static! {
     static ref QUEUE_FRAMES: Mutex<Vec<Arc<FrameTracker>>> = Mutex::new(Vec::new());
+    /// `share` 给设备的缓冲区的页帧，以起始物理地址为键，`unshare` 时取出并释放。
+    /// 网卡每收发一个包就 share 一次，不能像 `QUEUE_FRAMES` 那样只增不减
+    static ref SHARED_FRAMES: Mutex<BTreeMap<usize, Vec<Arc<FrameTracker>>>> =
+        Mutex::new(BTreeMap::new());
 }
 
 impl BlockDevice for VirtIOBlock {
@@ -29,34 +93,273 @@ impl BlockDevice for VirtIOBlock {
         // Convert filesystem block to virtio sectors
         let sectors_per_block = BLOCK_SZ / VIRTIO_BLK_SIZE;
         let start_sector = block_id * sectors_per_block;
-        for (i, chunk) in buf.chunks_mut(VIRTIO_BLK_SIZE).enumerate() {
-            self.0
-                .lock()
-                .read_blocks(start_sector + i, chunk)
+        #[cfg(feature = "virtio_irq")]
+        if current_task().is_some() {
+            self.submit(start_sector, &[(buf.as_mut_ptr(), buf.len())], false)
                 .expect("Error when reading VirtIOBlk");
+            return;
         }
+        // 多个块合并成的请求一次读完
+        self.device
+            .lock()
+            .read_blocks(start_sector, buf)
+            .expect("Error when reading VirtIOBlk");
     }
     fn write_block(&self, block_id: usize, buf: &[u8]) {
         // Convert filesystem block to virtio sectors
         let sectors_per_block = BLOCK_SZ / VIRTIO_BLK_SIZE;
         let start_sector = block_id * sectors_per_block;
-        for (i, chunk) in buf.chunks(VIRTIO_BLK_SIZE).enumerate() {
-            self.0
-                .lock()
-                .write_blocks(start_sector + i, chunk)
+        #[cfg(feature = "virtio_irq")]
+        if current_task().is_some() {
+            // 写请求不会修改缓冲区
+            self.submit(start_sector, &[(buf.as_ptr() as *mut u8, buf.len())], true)
+                .expect("Error when writing VirtIOBlk");
+            return;
+        }
+        self.device
+            .lock()
+            .write_blocks(start_sector, buf)
+            .expect("Error when writing VirtIOBlk");
+    }
+    fn read_blocks(&self, block_id: usize, bufs: &mut [&mut [u8]]) {
+        let mut sector = block_id * (BLOCK_SZ / VIRTIO_BLK_SIZE);
+        #[cfg(feature = "virtio_irq")]
+        if current_task().is_some() {
+            // 各缓冲区作为独立的请求同时在途
+            let bufs: Vec<_> = bufs
+                .iter_mut()
+                .map(|buf| (buf.as_mut_ptr(), buf.len()))
+                .collect();
+            self.submit(sector, &bufs, false)
+                .expect("Error when reading VirtIOBlk");
+            return;
+        }
+        let mut device = self.device.lock();
+        for buf in bufs.iter_mut() {
+            device
+                .read_blocks(sector, buf)
+                .expect("Error when reading VirtIOBlk");
+            sector += buf.len() / VIRTIO_BLK_SIZE;
+        }
+    }
+    fn write_blocks(&self, block_id: usize, bufs: &[&[u8]]) {
+        let mut sector = block_id * (BLOCK_SZ / VIRTIO_BLK_SIZE);
+        #[cfg(feature = "virtio_irq")]
+        if current_task().is_some() {
+            let bufs: Vec<_> = bufs
+                .iter()
+                .map(|buf| (buf.as_ptr() as *mut u8, buf.len()))
+                .collect();
+            self.submit(sector, &bufs, true)
+                .expect("Error when writing VirtIOBlk");
+            return;
+        }
+        let mut device = self.device.lock();
+        for buf in bufs {
+            device
+                .write_blocks(sector, buf)
                 .expect("Error when writing VirtIOBlk");
+            sector += buf.len() / VIRTIO_BLK_SIZE;
+        }
+    }
+    // 未协商 VIRTIO_BLK_F_FLUSH 的设备没有易失的写缓存，virtio-drivers 会忽略该请求
+    fn flush(&self) {
+        #[cfg(feature = "virtio_irq")]
+        if current_task().is_some() {
+            // 刷新请求很少，直接轮询等待完成；已用环须按顺序取出，先等在途的请求全部完成，
+            // 刷新期间持有设备锁，不会有新的请求提交
+            let mut device = loop {
+                self.reap();
+                let device = self.device.lock();
+                if self.inflight.lock().is_empty() {
+                    break device;
+                }
+                drop(device);
+                self.sleep(&self.submitters);
+            };
+            device.flush().expect("Error when flushing VirtIOBlk");
+            BLOCK_FLUSHES.inc();
+            return;
         }
+        self.device
+            .lock()
+            .flush()
+            .expect("Error when flushing VirtIOBlk");
+        BLOCK_FLUSHES.inc();
+    }
+    #[cfg(feature = "virtio_irq")]
+    fn handle_irq(&self) {
+        self.device.lock().ack_interrupt();
+        self.reap();
     }
 }
 
+#[cfg(feature = "virtio_irq")]
+impl VirtIOBlock {
+    /// 当前任务登记在 `waiters` 中睡眠，直到被唤醒或超时
+    fn sleep(&self, waiters: &Mutex<Vec<Weak<TaskControlBlock>>>) {
+        let task = current_task().unwrap();
+        waiters.lock().push(Arc::downgrade(&task));
+        wait_with_timeout(Arc::downgrade(&task), TimeSpec::now() + IRQ_WAIT_TIMEOUT);
+        drop(task);
+        block_current_and_run_next();
+    }
+    /// 从扇区 `sector` 起依次提交 `bufs` 中每个缓冲区的读写请求，让它们同时在途，
+    /// 睡眠直到全部完成；返回第一个出错的请求的错误
+    fn submit(
+        &self,
+        mut sector: usize,
+        bufs: &[(*mut u8, usize)],
+        write: bool,
+    ) -> Result<(), Error> {
+        let waiter = Arc::downgrade(&current_task().unwrap());
+        let mut requests = Vec::with_capacity(bufs.len());
+        let mut error = None;
+        for &(buf, len) in bufs {
+            let request = Box::into_raw(Box::new(Request {
+                req: BlkReq::default(),
+                resp: BlkResp::default(),
+                buf,
+                len,
+                write,
+                result: None,
+                waiter: waiter.clone(),
+            }));
+            let token = loop {
+                let mut device = self.device.lock();
+                // 请求在途期间 buf、req、resp 都不被访问，完成时用同样的参数取回
+                let token = unsafe {
+                    let request = &mut *request;
+                    let buf = core::slice::from_raw_parts_mut(buf, len);
+                    if write {
+                        device.write_blocks_nb(sector, &mut request.req, buf, &mut request.resp)
+                    } else {
+                        device.read_blocks_nb(sector, &mut request.req, buf, &mut request.resp)
+                    }
+                };
+                match token {
+                    Ok(token) => {
+                        self.inflight.lock().insert(token, InFlight(request));
+                        break Ok(token);
+                    }
+                    Err(Error::QueueFull) => {
+                        drop(device);
+                        self.sleep(&self.submitters);
+                    }
+                    Err(error) => break Err(error),
+                }
+            };
+            if let Err(err) = token {
+                drop(unsafe { Box::from_raw(request) });
+                error = Some(err);
+                break;
+            }
+            requests.push(request);
+            sector += len / VIRTIO_BLK_SIZE;
+        }
+        // 已经提交的请求即使后面的提交出错也要等它们完成，才能释放
+        let mut result = Ok(());
+        for request in requests {
+            loop {
+                // 补上睡眠前已经到达的中断
+                self.reap();
+                let device = self.device.lock();
+                if let Some(done) = unsafe { (*request).result.take() } {
+                    drop(device);
+                    drop(unsafe { Box::from_raw(request) });
+                    result = result.and(done);
+                    break;
+                }
+                drop(device);
+                let task = current_task().unwrap();
+                wait_with_timeout(Arc::downgrade(&task), TimeSpec::now() + IRQ_WAIT_TIMEOUT);
+                drop(task);
+                block_current_and_run_next();
+            }
+        }
+        error.map_or(result, Err)
+    }
+    /// 按已用环的顺序完成设备处理完的请求，唤醒等待它们的任务
+    fn reap(&self) {
+        let mut device = self.device.lock();
+        let mut inflight = self.inflight.lock();
+        let mut woken = Vec::new();
+        while let Some(token) = device.peek_used() {
+            let request = match inflight.remove(&token) {
+                Some(InFlight(request)) => unsafe { &mut *request },
+                None => break,
+            };
+            let buf = unsafe { core::slice::from_raw_parts_mut(request.buf, request.len) };
+            let result = unsafe {
+                if request.write {
+                    device.complete_write_blocks(token, &request.req, buf, &mut request.resp)
+                } else {
+                    device.complete_read_blocks(token, &request.req, buf, &mut request.resp)
+                }
+            };
+            // 填入结果后提交的任务随时可能释放请求，先取出等待的任务
+            woken.push(request.waiter.clone());
+            request.result = Some(result);
+        }
+        drop(inflight);
+        drop(device);
+        if woken.is_empty() {
+            return;
+        }
+        woken.append(&mut core::mem::take(&mut *self.submitters.lock()));
+        for task in woken.iter().filter_map(Weak::upgrade) {
+            wake_interruptible(task);
+        }
+    }
+}
+
+/// 在设备树给出的 virtio-mmio 槽位中找到第一个 `device_type` 类型的设备，
+/// 没有设备树时使用 `VIRTIO0`，返回槽位的地址与传输层
+pub(crate) fn probe_mmio(device_type: DeviceType) -> Option<(usize, MmioTransport)> {
+    let fallback = [(VIRTIO0, 0x1000)];
+    let platform = crate::hal::fdt::platform();
+    let slots = match &platform {
+        Some(info) if !info.virtio_mmio().is_empty() => info.virtio_mmio(),
+        _ => &fallback,
+    };
+    slots.iter().find_map(|&(base, _)| {
+        let header = NonNull::new(base as *mut VirtIOHeader)?;
+        // 空槽位的 device_id 为 0，MmioTransport::new 会返回错误
+        let transport = unsafe { MmioTransport::new(header) }.ok()?;
+        (transport.device_type() == device_type).then_some((base, transport))
+    })
+}
+
+/// 位于 `base` 的 virtio-mmio 槽位的中断源编号
+///
+/// QEMU virt 的 virtio-mmio 槽位从 VIRTIO0 起每 0x1000 一个，中断源依次为 1..=8
+#[cfg(feature = "virtio_irq")]
+pub(crate) fn mmio_irq(base: usize) -> usize {
+    1 + (base - VIRTIO0) / 0x1000
+}
+
 impl VirtIOBlock {
     #[allow(unused)]
     pub fn new() -> Self {
-        let header = NonNull::new(VIRTIO0 as *mut VirtIOHeader).unwrap();
-        let transport = unsafe { MmioTransport::new(header) }.unwrap();
-        Self(Mutex::new(
-            VirtIOBlk::<VirtioHal, MmioTransport>::new(transport).unwrap(),
-        ))
+        #[cfg_attr(not(feature = "virtio_irq"), allow(unused_variables))]
+        let (base, transport) =
+            probe_mmio(DeviceType::Block).expect("no virtio block device found");
+        #[cfg_attr(not(feature = "virtio_irq"), allow(unused_mut))]
+        let mut device = VirtIOBlk::<VirtioHal, MmioTransport>::new(transport).unwrap();
+        #[cfg(feature = "virtio_irq")]
+        {
+            device.enable_interrupts();
+            crate::hal::arch::riscv::plic::register_irq_handler(mmio_irq(base), || {
+                crate::drivers::block::DISK.handle_irq()
+            });
+        }
+        Self {
+            device: Mutex::new(device),
+            #[cfg(feature = "virtio_irq")]
+            inflight: Mutex::new(BTreeMap::new()),
+            #[cfg(feature = "virtio_irq")]
+            submitters: Mutex::new(Vec::new()),
+        }
     }
 }
 
@@ -88,7 +391,7 @@ unsafe impl Hal for VirtioHal {
             let dma_buf = core::slice::from_raw_parts_mut(pa_start as *mut u8, len);
             dma_buf.copy_from_slice(buffer_ref);
         }
-        QUEUE_FRAMES.lock().extend(frames);
+        SHARED_FRAMES.lock().insert(pa_start, frames);
         pa_start
     }
 
@@ -101,12 +404,7 @@ unsafe impl Hal for VirtioHal {
             buffer_ref.copy_from_slice(dma_buf);
         }
         // Deallocate DMA frames
-        let mut ppn = PhysAddr(paddr).floor();
-        let end_ppn = PhysAddr(paddr + len).ceil();
-        while ppn != end_ppn {
-            frame_dealloc(ppn);
-            ppn.step();
-        }
+        SHARED_FRAMES.lock().remove(&paddr);
     }
 }
 
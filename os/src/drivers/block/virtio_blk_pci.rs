@@ -26,20 +26,46 @@ lazy_static! {
     static ref QUEUE_FRAMES: Mutex<Vec<Arc<FrameTracker>>> = Mutex::new(Vec::new());
 }
 
+/// BAR 地址由所有 PCI 设备共用一个分配器，块设备与网卡的 BAR 不会重叠
+static PCI_ALLOCATOR: Mutex<PciRangeAllocator> = Mutex::new(PciRangeAllocator::new(VIRT_PCI_BASE, VIRT_PCI_SIZE));
+
 impl BlockDevice for VirtIOBlock {
     fn read_block(&self, block_id: usize, buf: &mut [u8]) {
         assert!(buf.len() % BLOCK_SZ == 0);
-        for (i, chunk) in buf.chunks_mut(VIRT_IO_BLOCK_SZ).enumerate() {
-            let virtio_block_id = block_id * BLOCK_RATIO + i;
-            self.0.lock().read_blocks(virtio_block_id, chunk).expect("read error");
-        }
+        // 多个块一次请求读完
+        self.0
+            .lock()
+            .read_blocks(block_id * BLOCK_RATIO, buf)
+            .expect("read error");
     }
 
     fn write_block(&self, block_id: usize, buf: &[u8]) {
         assert!(buf.len() % BLOCK_SZ == 0);
-        for (i, chunk) in buf.chunks(VIRT_IO_BLOCK_SZ).enumerate() {
-            let virtio_block_id = block_id * BLOCK_RATIO + i;
-            self.0.lock().write_blocks(virtio_block_id, chunk).expect("write error");
+        self.0
+            .lock()
+            .write_blocks(block_id * BLOCK_RATIO, buf)
+            .expect("write error");
+    }
+
+    fn read_blocks(&self, mut block_id: usize, bufs: &mut [&mut [u8]]) {
+        let mut device = self.0.lock();
+        for buf in bufs.iter_mut() {
+            assert!(buf.len() % BLOCK_SZ == 0);
+            device
+                .read_blocks(block_id * BLOCK_RATIO, buf)
+                .expect("read error");
+            block_id += buf.len() / BLOCK_SZ;
+        }
+    }
+
+    fn write_blocks(&self, mut block_id: usize, bufs: &[&[u8]]) {
+        let mut device = self.0.lock();
+        for buf in bufs.iter() {
+            assert!(buf.len() % BLOCK_SZ == 0);
+            device
+                .write_blocks(block_id * BLOCK_RATIO, buf)
+                .expect("write error");
+            block_id += buf.len() / BLOCK_SZ;
         }
     }
 }
@@ -67,7 +93,8 @@ const fn align_up(addr: usize, align: usize) -> usize {
     (addr + align - 1) & !(align - 1)
 }
 
-fn enumerate_pci() -> Option<PciTransport> {
+/// 在 PCI 总线 0 上找到第一个给定类型的 virtio 设备，为它分配 BAR 并使能
+pub(crate) fn enumerate_pci(device_type: DeviceType) -> Option<PciTransport> {
     let mmconfig_base = PCI_ECAM_BASE as *mut u8;
     println!("[PCI] ECAM base: {:#x}", mmconfig_base as usize);
 
@@ -78,10 +105,10 @@ fn enumerate_pci() -> Option<PciTransport> {
         println!("[PCI] Device {:?}: vendor={:#x} device={:#x}", device_function, info.vendor_id, info.device_id);
         if let Some(virtio_type) = virtio_device_type(&info) {
             println!("[PCI] VirtIO device: {:?}", virtio_type);
-            if virtio_type != DeviceType::Block { continue; }
+            if virtio_type != device_type { continue; }
 
             println!("[PCI] Configuring BARs...");
-            let mut allocator = PciRangeAllocator::new(VIRT_PCI_BASE, VIRT_PCI_SIZE);
+            let mut allocator = PCI_ALLOCATOR.lock();
             let mut bar_index = 0;
             while bar_index < 6 {
                 let bar = pci_root.bar_info(device_function, bar_index).unwrap();
@@ -117,7 +144,7 @@ impl VirtIOBlock {
     pub fn new() -> Self {
         Self(Mutex::new(
             VirtIOBlk::<VirtioHal, PciTransport>::new(
-                enumerate_pci().expect("No VirtIO block device")
+                enumerate_pci(DeviceType::Block).expect("No VirtIO block device")
             ).expect("Invalid VirtIO device")
         ))
     }
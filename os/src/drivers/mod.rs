@@ -3,10 +3,15 @@
 //! This module provides device driver implementations:
 //! - Block device drivers (disk, memory block device)
 //! - Serial port drivers (NS16550A UART)
+//! - Network device drivers (virtio-net over MMIO or PCI), used by the network stack in `net`
+//! - Real-time clock drivers, backing `/dev/misc/rtc` and the initial `CLOCK_REALTIME`
 
 pub mod block;
+pub mod net;
+pub mod rtc;
 pub mod serial;
 
 pub use block::BLOCK_DEVICE;
+pub use net::{NetDevice, NET_DEVICE};
 #[cfg(feature = "loongarch64")]
 pub use serial::ns16550a::Ns16550a;
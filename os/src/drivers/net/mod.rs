@@ -0,0 +1,37 @@
+//! Network device drivers
+//!
+//! A device moves whole Ethernet frames, everything above that (ARP, IP, ICMP,
+//! TCP and UDP) is done by the network stack in `crate::net`.
+//!
+//! - VirtIO network device (MMIO and PCI variants), probed on the same
+//!   transport as the virtio block device
+//!
+//! Without a device the network stack only serves the loopback addresses.
+
+mod net_dev;
+#[cfg(any(feature = "block_virt", feature = "block_virt_pci"))]
+mod virtio_net;
+#[cfg(feature = "block_virt_pci")]
+mod virtio_net_pci;
+
+pub use net_dev::NetDevice;
+
+use alloc::sync::Arc;
+use lazy_static::*;
+
+lazy_static! {
+    /// The first network device found at boot
+    pub static ref NET_DEVICE: Option<Arc<dyn NetDevice>> = probe();
+}
+
+fn probe() -> Option<Arc<dyn NetDevice>> {
+    #[cfg(feature = "block_virt")]
+    if let Some(device) = virtio_net::probe() {
+        return Some(Arc::new(device));
+    }
+    #[cfg(feature = "block_virt_pci")]
+    if let Some(device) = virtio_net_pci::probe() {
+        return Some(Arc::new(device));
+    }
+    None
+}
@@ -0,0 +1,24 @@
+//! Network device trait definition
+//!
+//! Defines the common interface for all network cards
+
+use alloc::vec::Vec;
+
+/// Network device trait
+///
+/// A device moves whole Ethernet frames. The network stack polls it: frames
+/// are taken with `receive` whenever the smoltcp interface is polled, an
+/// interrupt only acknowledges the device and wakes the hart.
+pub trait NetDevice: Send + Sync {
+    /// Hardware address used as the source of sent frames
+    fn mac_address(&self) -> [u8; 6];
+
+    /// Take the next received frame, `None` if none has arrived
+    fn receive(&self) -> Option<Vec<u8>>;
+
+    /// Send a frame, the frame is dropped if the device has no room for it
+    fn transmit(&self, frame: &[u8]);
+
+    /// Handle the device interrupt, called by the interrupt controller after claiming it
+    fn handle_irq(&self) {}
+}
@@ -0,0 +1,93 @@
+use super::NetDevice;
+use crate::drivers::block::VirtioHal;
+use alloc::vec::Vec;
+use spin::Mutex;
+use virtio_drivers::device::net::{TxBuffer, VirtIONet};
+use virtio_drivers::transport::Transport;
+#[cfg(feature = "block_virt")]
+use {
+    crate::drivers::block::probe_mmio,
+    virtio_drivers::transport::{mmio::MmioTransport, DeviceType},
+};
+
+/// 收发队列的长度
+const QUEUE_SIZE: usize = 16;
+/// 接收缓冲区的大小，放得下一个 1514 字节的以太网帧与 virtio-net 的包头
+const RX_BUFFER_LEN: usize = 2048;
+
+/// virtio 网卡，MMIO 与 PCI 两种传输方式共用
+///
+/// 收发都由网络栈轮询完成；开启 `virtio_irq` 时设备的中断只做应答
+pub struct VirtIONetDevice<T: Transport> {
+    device: Mutex<VirtIONet<VirtioHal, T, QUEUE_SIZE>>,
+}
+
+impl<T: Transport> VirtIONetDevice<T> {
+    /// 在已找到的传输上初始化网卡，失败时返回 `None`
+    pub fn new(transport: T) -> Option<Self> {
+        match VirtIONet::new(transport, RX_BUFFER_LEN) {
+            Ok(device) => Some(Self {
+                device: Mutex::new(device),
+            }),
+            Err(err) => {
+                log::error!("[VirtIONetDevice::new] cannot initialize: {:?}", err);
+                None
+            }
+        }
+    }
+}
+
+/// 在 virtio-mmio 槽位中找到第一个网卡，没有时返回 `None`
+#[cfg(feature = "block_virt")]
+pub fn probe() -> Option<VirtIONetDevice<MmioTransport>> {
+    #[cfg_attr(not(feature = "virtio_irq"), allow(unused_variables))]
+    let (base, transport) = probe_mmio(DeviceType::Network)?;
+    let device = VirtIONetDevice::new(transport)?;
+    #[cfg(feature = "virtio_irq")]
+    {
+        device.device.lock().enable_interrupts();
+        crate::hal::arch::riscv::plic::register_irq_handler(
+            crate::drivers::block::mmio_irq(base),
+            || {
+                if let Some(device) = crate::drivers::NET_DEVICE.as_ref() {
+                    device.handle_irq();
+                }
+            },
+        );
+    }
+    Some(device)
+}
+
+impl<T: Transport + Send> NetDevice for VirtIONetDevice<T> {
+    fn mac_address(&self) -> [u8; 6] {
+        self.device.lock().mac_address()
+    }
+
+    fn receive(&self) -> Option<Vec<u8>> {
+        let mut device = self.device.lock();
+        let rx_buf = device.receive().ok()?;
+        let frame = rx_buf.packet().to_vec();
+        // 缓冲区还给接收队列，失败时队列少一个缓冲区，不影响继续收包
+        if let Err(err) = device.recycle_rx_buffer(rx_buf) {
+            log::warn!("[VirtIONetDevice::receive] cannot recycle: {:?}", err);
+        }
+        Some(frame)
+    }
+
+    fn transmit(&self, frame: &[u8]) {
+        let mut device = self.device.lock();
+        if !device.can_send() {
+            log::warn!("[VirtIONetDevice::transmit] queue full, frame dropped");
+            return;
+        }
+        // 发送会等到设备用完缓冲区才返回
+        if let Err(err) = device.send(TxBuffer::from(frame)) {
+            log::warn!("[VirtIONetDevice::transmit] send failed: {:?}", err);
+        }
+    }
+
+    fn handle_irq(&self) {
+        // 收到的帧留给下一次轮询取走，这里只应答中断
+        self.device.lock().ack_interrupt();
+    }
+}
@@ -0,0 +1,11 @@
+use super::virtio_net::VirtIONetDevice;
+use crate::drivers::block::enumerate_pci;
+use virtio_drivers::transport::pci::PciTransport;
+use virtio_drivers::transport::DeviceType;
+
+/// 在 PCI 总线上找到第一个 virtio 网卡，没有时返回 `None`
+///
+/// PCI 的中断没有接入 PLIC，网卡只靠轮询
+pub fn probe() -> Option<VirtIONetDevice<PciTransport>> {
+    VirtIONetDevice::new(enumerate_pci(DeviceType::Network)?)
+}
@@ -0,0 +1,54 @@
+//! Goldfish RTC
+//!
+//! A 64-bit counter of nanoseconds since the epoch split into two 32-bit
+//! registers. Reading `TIME_LOW` latches `TIME_HIGH`, writing `TIME_LOW`
+//! commits the value previously written to `TIME_HIGH`.
+
+use crate::fs::dev::hwclock::Rtc;
+use crate::timer::NSEC_PER_SEC;
+use core::ptr::{read_volatile, write_volatile};
+
+const TIME_LOW: usize = 0x00;
+const TIME_HIGH: usize = 0x04;
+
+/// QEMU virt places the RTC here, used when there is no device tree
+#[cfg(feature = "board_rvqemu")]
+const VIRT_RTC: usize = 0x10_1000;
+
+pub struct GoldfishRtc {
+    base: usize,
+}
+
+impl GoldfishRtc {
+    fn reg(&self, offset: usize) -> *mut u32 {
+        (self.base + offset) as *mut u32
+    }
+}
+
+impl Rtc for GoldfishRtc {
+    fn read_time(&self) -> u64 {
+        let low = unsafe { read_volatile(self.reg(TIME_LOW)) } as u64;
+        let high = unsafe { read_volatile(self.reg(TIME_HIGH)) } as u64;
+        ((high << 32) | low) / NSEC_PER_SEC as u64
+    }
+
+    fn set_time(&self, secs: u64) {
+        let ns = secs * NSEC_PER_SEC as u64;
+        unsafe {
+            write_volatile(self.reg(TIME_HIGH), (ns >> 32) as u32);
+            write_volatile(self.reg(TIME_LOW), ns as u32);
+        }
+    }
+}
+
+/// The RTC named by the device tree
+pub fn probe() -> Option<GoldfishRtc> {
+    let base = match crate::hal::fdt::platform() {
+        Some(info) => info.rtc?.0,
+        #[cfg(feature = "board_rvqemu")]
+        None => VIRT_RTC,
+        #[cfg(not(feature = "board_rvqemu"))]
+        None => return None,
+    };
+    Some(GoldfishRtc { base })
+}
@@ -0,0 +1,89 @@
+//! Loongson RTC
+//!
+//! Only the TOY ("time of year") counter is used. It keeps the broken-down
+//! time in two registers: month, day, hour, minute and second packed into
+//! one and the year since 1900 in the other.
+
+use super::RtcTime;
+use crate::fs::dev::hwclock::Rtc;
+use core::ptr::{read_volatile, write_volatile};
+
+const TOY_WRITE0: usize = 0x24;
+const TOY_WRITE1: usize = 0x28;
+const TOY_READ0: usize = 0x2c;
+const TOY_READ1: usize = 0x30;
+const RTC_CTRL: usize = 0x40;
+
+/// The oscillator and the TOY counter enable bits of `RTC_CTRL`
+const CTRL_EO: u32 = 1 << 8;
+const CTRL_TOYEN: u32 = 1 << 11;
+
+/// `(shift, width)` of the fields in `TOY_READ0` and `TOY_WRITE0`
+const TOY_MON: (u32, u32) = (26, 6);
+const TOY_DAY: (u32, u32) = (21, 5);
+const TOY_HOUR: (u32, u32) = (16, 5);
+const TOY_MIN: (u32, u32) = (10, 6);
+const TOY_SEC: (u32, u32) = (4, 6);
+
+fn field(value: u32, (shift, width): (u32, u32)) -> i32 {
+    ((value >> shift) & ((1 << width) - 1)) as i32
+}
+
+fn pack(value: i32, (shift, width): (u32, u32)) -> u32 {
+    (value as u32 & ((1 << width) - 1)) << shift
+}
+
+pub struct Ls7aRtc {
+    base: usize,
+}
+
+impl Ls7aRtc {
+    /// The counter stops while disabled, so enable it before use
+    pub fn new(base: usize) -> Self {
+        let rtc = Self { base };
+        unsafe {
+            let ctrl = read_volatile(rtc.reg(RTC_CTRL));
+            write_volatile(rtc.reg(RTC_CTRL), ctrl | CTRL_EO | CTRL_TOYEN);
+        }
+        rtc
+    }
+
+    fn reg(&self, offset: usize) -> *mut u32 {
+        (self.base + offset) as *mut u32
+    }
+}
+
+impl Rtc for Ls7aRtc {
+    fn read_time(&self) -> u64 {
+        let (toy, year) = unsafe {
+            (
+                read_volatile(self.reg(TOY_READ0)),
+                read_volatile(self.reg(TOY_READ1)),
+            )
+        };
+        let time = RtcTime {
+            tm_sec: field(toy, TOY_SEC),
+            tm_min: field(toy, TOY_MIN),
+            tm_hour: field(toy, TOY_HOUR),
+            tm_mday: field(toy, TOY_DAY),
+            tm_mon: field(toy, TOY_MON) - 1,
+            tm_year: year as i32,
+            ..Default::default()
+        };
+        // a counter that was never set may hold an invalid date
+        time.to_unix().unwrap_or(0)
+    }
+
+    fn set_time(&self, secs: u64) {
+        let time = RtcTime::from_unix(secs);
+        let toy = pack(time.tm_sec, TOY_SEC)
+            | pack(time.tm_min, TOY_MIN)
+            | pack(time.tm_hour, TOY_HOUR)
+            | pack(time.tm_mday, TOY_DAY)
+            | pack(time.tm_mon + 1, TOY_MON);
+        unsafe {
+            write_volatile(self.reg(TOY_WRITE0), toy);
+            write_volatile(self.reg(TOY_WRITE1), time.tm_year as u32);
+        }
+    }
+}
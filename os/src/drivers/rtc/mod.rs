@@ -0,0 +1,114 @@
+//! Real-time clock drivers
+//!
+//! The RTC keeps wall-clock time while the machine is off. It is read once at
+//! boot to seed `CLOCK_REALTIME` and is otherwise only accessed through
+//! `/dev/misc/rtc` (`fs::dev::hwclock`).
+//!
+//! - Goldfish RTC, found in the device tree of QEMU virt (RISC-V)
+//! - TOY counter of the Loongson 7A bridge and the 2K1000 (LoongArch)
+//!
+//! Without an RTC `CLOCK_REALTIME` starts at the epoch.
+
+#[cfg(feature = "riscv")]
+mod goldfish;
+#[cfg(feature = "loongarch64")]
+mod ls7a;
+
+use crate::fs::dev::hwclock::register_rtc;
+use crate::fs::timestamp::{days_of_month, is_leap_year, SECONDS_PER_DAY};
+use alloc::sync::Arc;
+use core::convert::TryFrom;
+
+/// `struct rtc_time`, broken-down UTC time as used by the RTC ioctls
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(C)]
+pub struct RtcTime {
+    pub tm_sec: i32,
+    pub tm_min: i32,
+    pub tm_hour: i32,
+    /// Day of the month, 1-based
+    pub tm_mday: i32,
+    /// Month, 0-based
+    pub tm_mon: i32,
+    /// Years since 1900
+    pub tm_year: i32,
+    pub tm_wday: i32,
+    pub tm_yday: i32,
+    pub tm_isdst: i32,
+}
+
+impl RtcTime {
+    /// Break down `secs` seconds since the epoch
+    pub fn from_unix(secs: u64) -> Self {
+        let mut days = (secs / SECONDS_PER_DAY as u64) as u32;
+        let rest = (secs % SECONDS_PER_DAY as u64) as i32;
+        // 1970-01-01 was a Thursday
+        let wday = ((days + 4) % 7) as i32;
+        let mut year = 1970;
+        while days >= days_of_year(year) {
+            days -= days_of_year(year);
+            year += 1;
+        }
+        let yday = days as i32;
+        let mut month = 1;
+        while days >= days_of_month(year, month) as u32 {
+            days -= days_of_month(year, month) as u32;
+            month += 1;
+        }
+        Self {
+            tm_sec: rest % 60,
+            tm_min: rest / 60 % 60,
+            tm_hour: rest / 3600,
+            tm_mday: days as i32 + 1,
+            tm_mon: month as i32 - 1,
+            tm_year: year as i32 - 1900,
+            tm_wday: wday,
+            tm_yday: yday,
+            tm_isdst: 0,
+        }
+    }
+
+    /// Seconds since the epoch, `None` if a field is out of range or the time
+    /// is before the epoch. `tm_wday` and `tm_yday` are ignored.
+    pub fn to_unix(&self) -> Option<u64> {
+        let year = u32::try_from(self.tm_year.checked_add(1900)?).ok()?;
+        let month = u8::try_from(self.tm_mon.checked_add(1)?).ok()?;
+        if year < 1970
+            || !(1..=12).contains(&month)
+            || !(1..=days_of_month(year, month) as i32).contains(&self.tm_mday)
+            || !(0..24).contains(&self.tm_hour)
+            || !(0..60).contains(&self.tm_min)
+            || !(0..60).contains(&self.tm_sec)
+        {
+            return None;
+        }
+        let days = (1970..year).map(days_of_year).sum::<u32>()
+            + (1..month)
+                .map(|month| days_of_month(year, month) as u32)
+                .sum::<u32>()
+            + self.tm_mday as u32
+            - 1;
+        let secs = self.tm_hour * 3600 + self.tm_min * 60 + self.tm_sec;
+        Some(days as u64 * SECONDS_PER_DAY as u64 + secs as u64)
+    }
+}
+
+fn days_of_year(year: u32) -> u32 {
+    if is_leap_year(year) {
+        366
+    } else {
+        365
+    }
+}
+
+/// Find the platform RTC and register it with `hwclock`
+pub fn init() {
+    #[cfg(feature = "riscv")]
+    if let Some(rtc) = goldfish::probe() {
+        register_rtc(Arc::new(rtc));
+    }
+    #[cfg(feature = "loongarch64")]
+    register_rtc(Arc::new(ls7a::Ls7aRtc::new(
+        crate::hal::arch::board::RTC_BASE,
+    )));
+}
@@ -2,4 +2,19 @@
     For K210, we use k210-pac directly.
 */
 pub mod ns16550a;
+#[cfg(feature = "uart_irq")]
+pub mod rx;
 //mod uart;
+
+/// 取出控制台收到的一个字节，没有输入时返回 `None`
+/// # 说明
+/// 打开串口接收中断后从接收缓冲区中取，否则直接轮询控制台
+pub fn getchar() -> Option<u8> {
+    #[cfg(feature = "uart_irq")]
+    if rx::enabled() {
+        return rx::pop();
+    }
+    // 没有输入时 console_getchar 返回 -1
+    let c = crate::hal::console_getchar() as u8;
+    (c != 255).then_some(c)
+}
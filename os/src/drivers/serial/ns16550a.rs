@@ -15,6 +15,12 @@ impl Ns16550a {
         // already init in RustSBI
         Self { base }
     }
+
+    /// 打开接收中断：接收 FIFO 中有数据时产生中断，读空后撤销
+    #[cfg(feature = "uart_irq")]
+    pub fn enable_rx_interrupt(&self) {
+        unsafe { write_volatile((self.base + offsets::IER) as *mut u8, masks::ERBFI) };
+    }
 }
 
 impl embedded_hal::serial::ErrorType for Ns16550a {
@@ -75,4 +81,7 @@ mod offsets {
 mod masks {
     pub const THRE: u8 = 1 << 5;
     pub const DR: u8 = 1;
+    /// IER 中的接收数据可用中断
+    #[cfg(feature = "uart_irq")]
+    pub const ERBFI: u8 = 1;
 }
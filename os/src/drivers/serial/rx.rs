@@ -0,0 +1,94 @@
+//! 串口接收中断
+//!
+//! 打开 NS16550A 的接收中断并经 PLIC 送达：中断处理程序把收到的字节放入环形缓冲区，
+//! 再交给控制台的行规程 (`fs::dev::tty::console_input`)，读控制台的任务因此可以睡眠等待，
+//! 不必反复轮询 SBI。
+
+use super::ns16550a::Ns16550a;
+use crate::utils::interrupt_guard::InterruptGuard;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use embedded_hal::serial::nb::Read;
+use spin::Mutex;
+
+/// 设备树中没有串口时使用 QEMU virt 的 UART0
+const VIRT_UART: usize = 0x1000_0000;
+/// QEMU virt 上 UART0 的中断源编号
+const VIRT_UART_IRQ: usize = 10;
+const RX_RING_SIZE: usize = 1024;
+
+/// 接收环形缓冲区，满时丢弃新收到的字节
+struct RxRing {
+    buf: [u8; RX_RING_SIZE],
+    head: usize,
+    len: usize,
+}
+
+impl RxRing {
+    const fn new() -> Self {
+        Self {
+            buf: [0; RX_RING_SIZE],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, c: u8) {
+        if self.len < RX_RING_SIZE {
+            self.buf[(self.head + self.len) % RX_RING_SIZE] = c;
+            self.len += 1;
+        }
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+        let c = self.buf[self.head];
+        self.head = (self.head + 1) % RX_RING_SIZE;
+        self.len -= 1;
+        Some(c)
+    }
+}
+
+/// 中断处理程序写入，任务取出时须关中断
+static RX_RING: Mutex<RxRing> = Mutex::new(RxRing::new());
+/// 串口的地址，为 0 表示还没有打开接收中断
+static UART_BASE: AtomicUsize = AtomicUsize::new(0);
+
+/// 打开串口的接收中断并在 PLIC 中登记
+pub fn init() {
+    let base = crate::hal::fdt::platform()
+        .and_then(|info| info.uart)
+        .map_or(VIRT_UART, |(base, _)| base);
+    Ns16550a::new(base).enable_rx_interrupt();
+    UART_BASE.store(base, Ordering::Release);
+    crate::hal::arch::riscv::plic::register_irq_handler(VIRT_UART_IRQ, handle_irq);
+}
+
+/// 控制台的输入是否由接收中断送达
+pub fn enabled() -> bool {
+    UART_BASE.load(Ordering::Acquire) != 0
+}
+
+/// 取出一个收到的字节
+pub fn pop() -> Option<u8> {
+    let _guard = InterruptGuard::new();
+    RX_RING.lock().pop()
+}
+
+/// 接收缓冲区中是否还有字节
+pub fn pending() -> bool {
+    let _guard = InterruptGuard::new();
+    RX_RING.lock().len != 0
+}
+
+/// 串口中断：读空接收 FIFO，再交给控制台处理
+pub fn handle_irq() {
+    let mut uart = Ns16550a::new(UART_BASE.load(Ordering::Acquire));
+    let mut ring = RX_RING.lock();
+    while let Ok(c) = uart.read() {
+        ring.push(c);
+    }
+    drop(ring);
+    crate::fs::dev::tty::console_input();
+}
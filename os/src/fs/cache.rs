@@ -1,9 +1,12 @@
 use crate::config::MEMORY_HIGH_BASE;
 use crate::config::{PAGE_SIZE, PAGE_SIZE_BITS};
+use crate::drivers::block::RequestQueue;
 use crate::hal::{BLOCK_SZ, BUFFER_CACHE_NUM};
 use crate::mm::{frame_alloc, FrameTracker, KERNEL_SPACE};
+use crate::utils::telemetry::PAGE_CACHE_PAGES;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
+use core::ops::Range;
 use spin::Mutex;
 
 use super::BlockDevice;
@@ -24,7 +27,7 @@ pub trait Cache {
     /// # 参数
     /// + `block_ids`: cache内的块号
     /// + `block_device`: 块设备对象
-    fn sync(&self, _block_ids: Vec<usize>, _block_device: &Arc<dyn BlockDevice>) {}
+    fn sync(&mut self, _block_ids: Vec<usize>, _block_device: &Arc<dyn BlockDevice>) {}
 }
 
 /// 优先级上限
@@ -33,6 +36,8 @@ const PRIORITY_UPPERBOUND: usize = 1;
 const BUFFER_SIZE: usize = BLOCK_SZ;
 /// 页缓存数量（每页包含块数量）
 const PAGE_BUFFERS: usize = PAGE_SIZE / BUFFER_SIZE;
+/// `get_caches` 一批最多读入的页数
+pub const READ_BATCH_PAGES: usize = 16;
 
 /// 缓存池大小
 const CACHEPOOLSIZE: usize = BUFFER_CACHE_NUM >> (BLOCK_SZ / 512).trailing_zeros();
@@ -124,6 +129,22 @@ impl BlockCacheManager {
             }
         }
     }
+    /// 写回所有脏块，块仍留在缓存中；所有脏块合并成一批请求写回
+    pub fn sync_all(&self, block_device: &Arc<dyn BlockDevice>) {
+        let mut dirty = Vec::new();
+        for buffer_cache in &self.cache_pool {
+            let mut locked = buffer_cache.lock();
+            if locked.dirty && locked.block_id != usize::MAX {
+                locked.dirty = false;
+                dirty.push(locked);
+            }
+        }
+        let mut queue = RequestQueue::new(block_device.as_ref());
+        for locked in dirty.iter() {
+            queue.write(locked.block_id, locked.buffer.as_ref());
+        }
+        queue.dispatch();
+    }
     fn alloc_buffer_cache(&self, block_device: &Arc<dyn BlockDevice>) -> Arc<Mutex<BufferCache>> {
         loop {
             for buffer_cache in &self.cache_pool {
@@ -202,6 +223,9 @@ pub struct PageCache {
     priority: usize,
     page_ptr: &'static mut [u8; PAGE_SIZE],
     tracker: Arc<FrameTracker>,
+    /// 被共享映射（MAP_SHARED）写入过
+    /// 用户通过映射写入时内核映射的脏位不会改变，由缺页处理设置
+    dirty: bool,
 }
 
 impl Cache for PageCache {
@@ -229,17 +253,10 @@ impl Cache for PageCache {
         })
     }
 
-    fn sync(&self, block_ids: Vec<usize>, block_device: &Arc<dyn BlockDevice>) {
-        let lock = KERNEL_SPACE.try_lock();
-        match lock {
-            Some(lock) => {
-                if !lock.is_dirty(self.tracker.ppn).unwrap() {
-                    return;
-                }
-            }
-            None => {}
+    fn sync(&mut self, block_ids: Vec<usize>, block_device: &Arc<dyn BlockDevice>) {
+        if self.take_dirty() {
+            self.write_back(block_ids, block_device)
         }
-        self.write_back(block_ids, block_device)
     }
 }
 
@@ -248,13 +265,20 @@ impl PageCache {
         let tracker = unsafe { crate::mm::frame_alloc_uninit().unwrap() };
         let page_ptr = (tracker.ppn.0 << PAGE_SIZE_BITS) as *mut [u8; PAGE_SIZE];
         let page_ptr = unsafe { page_ptr.as_mut().unwrap() };
+        PAGE_CACHE_PAGES.inc();
         Self {
             priority: 0,
             page_ptr,
             tracker,
+            dirty: false,
         }
     }
 
+    /// 标记为脏页，写回时不论内核映射的脏位都会写回
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
     pub fn get_tracker(&self) -> Arc<FrameTracker> {
         self.tracker.clone()
     }
@@ -264,64 +288,29 @@ impl PageCache {
     /// + block_id：块号
     /// + block_device：块设备对象
     pub fn read_in(&mut self, block_ids: Vec<usize>, block_device: &Arc<dyn BlockDevice>) {
-        // 如果传入块号列表为空，则去需任何操作，直接返回
-        if block_ids.is_empty() {
-            return;
-        }
+        let mut queue = RequestQueue::new(block_device.as_ref());
+        self.queue_read_in(&block_ids, &mut queue);
+        queue.dispatch();
+        self.loaded();
+    }
+
+    /// 把读入这一页的请求加入 `queue`，请求完成后须调用 `loaded`
+    /// # 参数
+    /// + block_ids：页内各块的块号，块数不超过 PAGE_BUFFERS
+    /// + queue：块设备的请求队列，相邻的块在其中合并
+    fn queue_read_in<'a>(&'a mut self, block_ids: &[usize], queue: &mut RequestQueue<'a>) {
         // 块号数量限制，若块号长度大于PAGE_BUFFERS，越界panic
-        // PAGE_BUFFERS 大小为 2
-        // 也就是每页只有两块
         assert!(block_ids.len() <= PAGE_BUFFERS);
-
-        // 初始化变量
-        // 当前连续块序列的起始块号
-        let mut start_block_id = usize::MAX;
-        // 当前连续块序列的长度
-        let mut con_length = 0;
-        // 缓存起始位置索引，用于计算写入的位置
-        let mut start_buf_id = 0;
-        // 遍历块号列表
-        for block_id in block_ids.iter() {
-            // 如果当前没有连续块序列
-            // 初始化start_block_id以及con_length
-            if con_length == 0 {
-                // 获取起始块号
-                start_block_id = *block_id;
-                // 连续块数量设置为1
-                con_length = 1;
-            } else if *block_id != start_block_id + con_length {
-                // 若当前块号不属于当前连续序列，处理已经累积的连续块
-                // （实际上连续块最多就两个，也就是一页里面最多两个连续块）
-                let buf = unsafe {
-                    core::slice::from_raw_parts_mut(
-                        // 从缓存页面的起始指针加上指针偏移量
-                        // 也就是 start_buf_id * BUFFER_SIZE
-                        // 计算写入位置
-                        self.page_ptr.as_mut_ptr().add(start_buf_id * BUFFER_SIZE),
-                        // 长度为连续块长度(或者说数量) * BUFFER_SIZE(块大小)
-                        con_length * BUFFER_SIZE,
-                    )
-                };
-                // 块设备读取数据，存入buf
-                block_device.read_block(start_block_id, buf);
-                // 更新起始位置索引、起始块号以及连续块长度
-                start_buf_id += con_length;
-                start_block_id = *block_id;
-                con_length = 1;
-            } else {
-                // 若当前块号属于当前连续序列
-                // 延长连续序列长度
-                con_length += 1;
-            }
+        let mut buffers = self.page_ptr.chunks_mut(BUFFER_SIZE);
+        for (&block_id, buf) in block_ids.iter().zip(&mut buffers) {
+            queue.read(block_id, buf);
         }
-        let buf = unsafe {
-            core::slice::from_raw_parts_mut(
-                self.page_ptr.as_mut_ptr().add(start_buf_id * BUFFER_SIZE),
-                con_length * BUFFER_SIZE,
-            )
-        };
-        block_device.read_block(start_block_id, buf);
-        self.page_ptr[block_ids.len() * BUFFER_SIZE..].fill(0);
+        // 没有块的部分（文件末尾之后或空洞）读出 0，块号为空时整页都是 0
+        buffers.for_each(|buf| buf.fill(0));
+    }
+
+    /// 页的内容已经读入
+    fn loaded(&self) {
         #[cfg(feature = "loongarch64")]
         KERNEL_SPACE
             .lock()
@@ -339,42 +328,42 @@ impl PageCache {
     /// + block_ids: 块号
     /// + block_device: 块设备对象
     pub fn write_back(&self, block_ids: Vec<usize>, block_device: &Arc<dyn BlockDevice>) {
-        // 如果块号为空，直接返回
-        if block_ids.is_empty() {
-            return;
+        let mut queue = RequestQueue::new(block_device.as_ref());
+        self.queue_write_back(&block_ids, &mut queue);
+        queue.dispatch();
+    }
+
+    /// 把写回这一页的请求加入 `queue`
+    fn queue_write_back<'a>(&'a self, block_ids: &[usize], queue: &mut RequestQueue<'a>) {
+        for (&block_id, buf) in block_ids.iter().zip(self.page_ptr.chunks(BUFFER_SIZE)) {
+            queue.write(block_id, buf);
         }
+    }
 
-        // 获取起始块号
-        let mut start_block_id = usize::MAX;
-        let mut con_length = 0;
-        let mut start_buf_id = 0;
-        for block_id in block_ids.iter() {
-            if con_length == 0 {
-                start_block_id = *block_id;
-                con_length = 1;
-            } else if *block_id != start_block_id + con_length {
-                let buf = unsafe {
-                    core::slice::from_raw_parts(
-                        self.page_ptr.as_ptr().add(start_buf_id * BUFFER_SIZE),
-                        con_length * BUFFER_SIZE,
-                    )
-                };
-                block_device.write_block(start_block_id, buf);
-
-                start_buf_id += con_length;
-                start_block_id = *block_id;
-                con_length = 1;
-            } else {
-                con_length += 1;
+    /// 是否需要写回；返回真时清除脏标记，调用者须接着写回
+    fn take_dirty(&mut self) -> bool {
+        if !self.dirty {
+            let lock = KERNEL_SPACE.try_lock();
+            match lock {
+                Some(lock) => {
+                    if !lock.is_dirty(self.tracker.ppn).unwrap() {
+                        return false;
+                    }
+                }
+                None => {}
             }
         }
-        let buf = unsafe {
-            core::slice::from_raw_parts(
-                self.page_ptr.as_ptr().add(start_buf_id * BUFFER_SIZE),
-                con_length * BUFFER_SIZE,
-            )
-        };
-        block_device.write_block(start_block_id, buf);
+        // 仍被共享映射的页随时可能再次写入且不会再缺页，保留脏标记
+        if Arc::strong_count(&self.tracker) == 1 {
+            self.dirty = false;
+        }
+        true
+    }
+}
+
+impl Drop for PageCache {
+    fn drop(&mut self) {
+        PAGE_CACHE_PAGES.dec();
     }
 }
 
@@ -469,6 +458,60 @@ impl PageCacheManager {
         page_cache
     }
 
+    /// 获取 `range` 中的各页缓存，不在缓存中的页合并成一批请求读入
+    /// # 参数
+    /// + range: cache内块号的范围，不超过 READ_BATCH_PAGES 页
+    /// + neighbor: 闭包，返回一页对应的块号
+    /// + block_device: 块设备对象
+    pub fn get_caches<FUNC>(
+        &self,
+        range: Range<usize>,
+        neighbor: FUNC,
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> Vec<Arc<Mutex<PageCache>>>
+    where
+        FUNC: Fn(usize) -> Vec<usize>,
+    {
+        debug_assert!(range.len() <= READ_BATCH_PAGES);
+        crate::mm::frame_reserve(range.len());
+        let mut lock = self.cache_pool.lock();
+        while range.end > lock.len() {
+            lock.push(None);
+        }
+        let mut missing: Vec<(usize, PageCache)> = range
+            .clone()
+            .filter(|&inner_cache_id| lock[inner_cache_id].is_none())
+            .map(|inner_cache_id| (inner_cache_id, PageCache::new()))
+            .collect();
+        let block_ids: Vec<Vec<usize>> = missing
+            .iter()
+            .map(|&(inner_cache_id, _)| neighbor(inner_cache_id))
+            .collect();
+        let mut queue = RequestQueue::new(block_device.as_ref());
+        for ((_, page_cache), block_ids) in missing.iter_mut().zip(block_ids.iter()) {
+            page_cache.queue_read_in(block_ids, &mut queue);
+        }
+        queue.dispatch();
+        let mut allocated_cache = self.allocated_cache.lock();
+        for (inner_cache_id, page_cache) in missing {
+            page_cache.loaded();
+            lock[inner_cache_id] = Some(Arc::new(Mutex::new(page_cache)));
+            allocated_cache.push(inner_cache_id);
+        }
+        drop(allocated_cache);
+        range
+            .map(|inner_cache_id| {
+                let page_cache = lock[inner_cache_id].clone().unwrap();
+                let mut inner_lock = page_cache.lock();
+                if inner_lock.priority < PRIORITY_UPPERBOUND {
+                    inner_lock.priority += 1;
+                }
+                drop(inner_lock);
+                page_cache
+            })
+            .collect()
+    }
+
     pub fn oom<FUNC>(&self, neighbor: FUNC, block_device: &Arc<dyn BlockDevice>) -> usize
     where
         FUNC: Fn(usize) -> Vec<usize>,
@@ -502,16 +545,62 @@ impl PageCacheManager {
         dropped
     }
 
+    /// 释放所有未被他人引用的缓存页，不论优先级，脏页先写回
+    /// 用于 `/proc/sys/vm/drop_caches`
+    /// # 返回值
+    /// + 释放的页数
+    pub fn drop_all<FUNC>(&self, neighbor: FUNC, block_device: &Arc<dyn BlockDevice>) -> usize
+    where
+        FUNC: Fn(usize) -> Vec<usize>,
+    {
+        let mut lock = self.cache_pool.lock();
+        let mut dropped = 0;
+        self.allocated_cache.lock().retain(|&inner_cache_id| {
+            let inner = lock[inner_cache_id].as_ref().unwrap();
+            // 被映射或正在使用的页保留
+            if Arc::strong_count(inner) > 1 {
+                return true;
+            }
+            let mut inner_lock = inner.lock();
+            if Arc::strong_count(&inner_lock.tracker) > 1 {
+                return true;
+            }
+            inner_lock.sync(neighbor(inner_cache_id), block_device);
+            drop(inner_lock);
+            lock[inner_cache_id] = None;
+            dropped += 1;
+            false
+        });
+        dropped
+    }
+
+    /// 写回所有脏页，缓存页仍然保留；所有脏页的写回合并成一批请求
+    /// 用于 fsync
+    pub fn sync_all<FUNC>(&self, neighbor: FUNC, block_device: &Arc<dyn BlockDevice>)
+    where
+        FUNC: Fn(usize) -> Vec<usize>,
+    {
+        let lock = self.cache_pool.lock();
+        let mut dirty = Vec::new();
+        for &inner_cache_id in self.allocated_cache.lock().iter() {
+            let mut inner_lock = lock[inner_cache_id].as_ref().unwrap().lock();
+            if inner_lock.take_dirty() {
+                dirty.push((neighbor(inner_cache_id), inner_lock));
+            }
+        }
+        let mut queue = RequestQueue::new(block_device.as_ref());
+        for (block_ids, inner_lock) in dirty.iter() {
+            inner_lock.queue_write_back(block_ids, &mut queue);
+        }
+        queue.dispatch();
+    }
+
     pub fn notify_new_size(&self, new_size: usize) {
         let mut lock = self.cache_pool.lock();
         let new_pages = (new_size + PAGE_SIZE - 1) / PAGE_SIZE;
-        while lock.len() > new_pages {
-            lock.pop().unwrap().map(|cache| {
-                if Arc::strong_count(&cache) > 1 {
-                    panic!("page cache was used by others");
-                }
-            });
-        }
+        // 仍被他人持有的页（如映射了该文件的地址空间）只是脱离本文件：不再写回，
+        // 由最后的持有者释放
+        lock.truncate(new_pages);
         lock.shrink_to_fit();
 
         self.allocated_cache
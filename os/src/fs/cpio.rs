@@ -0,0 +1,149 @@
+//! Reader for "newc" cpio archives
+//!
+//! This is the format produced by `find . | cpio -o -H newc` and used for Linux
+//! initramfs images. Every entry is a 110-byte ASCII header, followed by the
+//! NUL-terminated name and the file data, each padded to 4 bytes. The archive
+//! ends with an entry named `TRAILER!!!`.
+
+pub const HEADER_LEN: usize = 110;
+const MAGIC: &[u8] = b"070701";
+/// Same layout, the `check` field holds a checksum that we do not verify
+const MAGIC_CRC: &[u8] = b"070702";
+const TRAILER: &str = "TRAILER!!!";
+
+const S_IFMT: u32 = 0o170000;
+pub const S_IFDIR: u32 = 0o040000;
+pub const S_IFREG: u32 = 0o100000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpioError {
+    BadMagic,
+    BadHeader,
+    Truncated,
+}
+
+pub struct Entry<'a> {
+    pub name: &'a str,
+    pub mode: u32,
+    pub data: &'a [u8],
+}
+
+impl<'a> Entry<'a> {
+    pub fn is_dir(&self) -> bool {
+        self.mode & S_IFMT == S_IFDIR
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.mode & S_IFMT == S_IFREG
+    }
+}
+
+/// Iterates over the entries of an archive, stops after the trailer or the first error
+pub struct Reader<'a> {
+    archive: &'a [u8],
+    off: usize,
+    done: bool,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(archive: &'a [u8]) -> Self {
+        Self {
+            archive,
+            off: 0,
+            done: false,
+        }
+    }
+
+    fn field(header: &[u8], index: usize) -> Result<usize, CpioError> {
+        let start = 6 + index * 8;
+        let hex =
+            core::str::from_utf8(&header[start..start + 8]).map_err(|_| CpioError::BadHeader)?;
+        usize::from_str_radix(hex, 16).map_err(|_| CpioError::BadHeader)
+    }
+
+    fn next_entry(&mut self) -> Result<Entry<'a>, CpioError> {
+        let header = self
+            .archive
+            .get(self.off..self.off + HEADER_LEN)
+            .ok_or(CpioError::Truncated)?;
+        if &header[..6] != MAGIC && &header[..6] != MAGIC_CRC {
+            return Err(CpioError::BadMagic);
+        }
+        let mode = Self::field(header, 1)? as u32;
+        let file_size = Self::field(header, 6)?;
+        let name_size = Self::field(header, 11)?;
+        if name_size == 0 {
+            return Err(CpioError::BadHeader);
+        }
+
+        let name_start = self.off + HEADER_LEN;
+        let name = self
+            .archive
+            .get(name_start..name_start + name_size - 1)
+            .ok_or(CpioError::Truncated)?;
+        let name = core::str::from_utf8(name).map_err(|_| CpioError::BadHeader)?;
+        let data_start = (name_start + name_size).next_multiple_of(4);
+        let data = self
+            .archive
+            .get(data_start..data_start + file_size)
+            .ok_or(CpioError::Truncated)?;
+        self.off = (data_start + file_size).next_multiple_of(4);
+        Ok(Entry { name, mode, data })
+    }
+}
+
+impl<'a> Iterator for Reader<'a> {
+    type Item = Result<Entry<'a>, CpioError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.next_entry() {
+            Ok(entry) if entry.name == TRAILER => {
+                self.done = true;
+                None
+            }
+            Ok(entry) => Some(Ok(entry)),
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// Build a newc archive from `(name, mode, data)` triples, for the self tests
+#[cfg(feature = "selftest")]
+pub fn build(entries: &[(&str, u32, &[u8])]) -> alloc::vec::Vec<u8> {
+    use alloc::{format, vec::Vec};
+    let mut archive = Vec::new();
+    let trailer = (TRAILER, 0, &[][..]);
+    for (ino, &(name, mode, data)) in entries.iter().chain([&trailer]).enumerate() {
+        let fields = [
+            ino + 1,
+            mode as usize,
+            0,
+            0,
+            1,
+            0,
+            data.len(),
+            0,
+            0,
+            0,
+            0,
+            name.len() + 1,
+            0,
+        ];
+        archive.extend_from_slice(MAGIC);
+        for value in fields {
+            archive.extend_from_slice(format!("{:08x}", value).as_bytes());
+        }
+        archive.extend_from_slice(name.as_bytes());
+        archive.push(0);
+        archive.resize(archive.len().next_multiple_of(4), 0);
+        archive.extend_from_slice(data);
+        archive.resize(archive.len().next_multiple_of(4), 0);
+    }
+    archive
+}
@@ -0,0 +1,217 @@
+use crate::fs::{dirent::Dirent, DiskInodeType};
+use alloc::sync::Arc;
+
+use crate::{
+    drivers::block::BlockDevice,
+    fs::{directory_tree::DirectoryTreeNode, file_trait::File, layout::Stat, StatMode},
+    mm::{copy_to_user, UserBuffer},
+    syscall::errno::{EINVAL, EIO, ENOTDIR, ENOTTY, ESPIPE},
+    task::current_user_token,
+};
+
+/// Size of the device in bytes, as Linux's `BLKGETSIZE64`
+pub const BLKGETSIZE64: u32 = 0x8008_1272;
+/// Fill the `DiskHealth` pointed to by the argument
+pub const DISK_GET_HEALTH: u32 = 0x4401;
+
+/// Argument of `DISK_GET_HEALTH`, strings are NUL-padded ASCII
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct DiskHealth {
+    /// Capacity in 512-byte sectors
+    pub sectors: u64,
+    /// Temperature in Celsius, -1 if the drive does not report it
+    pub temperature: i32,
+    pub reserved: u32,
+    pub model: [u8; 40],
+    pub serial: [u8; 24],
+}
+
+/// Copy `src` into the NUL-padded field `dst`, cutting it if too long
+fn fill(dst: &mut [u8], src: &str) {
+    let len = src.len().min(dst.len() - 1);
+    dst[..len].copy_from_slice(&src.as_bytes()[..len]);
+}
+
+/// Physical disk
+/// `/dev/sda` gives the identity and health data of the boot disk through
+/// ioctl(DISK_GET_HEALTH) and its size through ioctl(BLKGETSIZE64). It cannot
+/// be read, written or mounted directly, its partitions are `/dev/sdaN`.
+pub struct Disk {
+    device: Arc<dyn BlockDevice>,
+}
+
+impl Disk {
+    pub fn new(device: Arc<dyn BlockDevice>) -> Self {
+        Self { device }
+    }
+}
+
+#[allow(unused)]
+impl File for Disk {
+    fn deep_clone(&self) -> Arc<dyn File> {
+        Arc::new(Disk::new(self.device.clone()))
+    }
+
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    /// 不支持按字节读写，sendfile 等在内核中读写文件时返回 EINVAL
+    fn read(&self, offset: Option<&mut usize>, buf: &mut [u8]) -> usize {
+        EINVAL as usize
+    }
+
+    fn write(&self, offset: Option<&mut usize>, buf: &[u8]) -> usize {
+        EINVAL as usize
+    }
+
+    fn r_ready(&self) -> bool {
+        true
+    }
+
+    fn w_ready(&self) -> bool {
+        true
+    }
+
+    fn get_size(&self) -> usize {
+        0
+    }
+
+    fn get_stat(&self) -> Stat {
+        Stat::new(
+            crate::makedev!(0, 5),
+            1,
+            StatMode::S_IFBLK.bits() | 0o660,
+            1,
+            crate::makedev!(8, 0),
+            0,
+            0,
+            0,
+            0,
+        )
+    }
+
+    fn read_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
+        0
+    }
+
+    fn write_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
+        0
+    }
+
+    fn get_file_type(&self) -> DiskInodeType {
+        DiskInodeType::File
+    }
+
+    fn info_dirtree_node(
+        &self,
+        dirnode_ptr: alloc::sync::Weak<crate::fs::directory_tree::DirectoryTreeNode>,
+    ) {
+    }
+
+    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>> {
+        None
+    }
+
+    fn open(&self, flags: crate::fs::layout::OpenFlags, special_use: bool) -> Arc<dyn File> {
+        self.deep_clone()
+    }
+
+    fn open_subfile(
+        &self,
+    ) -> Result<alloc::vec::Vec<(alloc::string::String, alloc::sync::Arc<dyn File>)>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn create(&self, name: &str, file_type: DiskInodeType) -> Result<Arc<dyn File>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn link_child(&self, name: &str, child: &Self) -> Result<(), isize>
+    where
+        Self: Sized,
+    {
+        Err(ENOTDIR)
+    }
+
+    fn unlink(&self, delete: bool) -> Result<(), isize> {
+        Ok(())
+    }
+
+    fn get_dirent(&self, count: usize) -> alloc::vec::Vec<Dirent> {
+        alloc::vec::Vec::new()
+    }
+
+    fn lseek(&self, offset: isize, whence: crate::fs::SeekWhence) -> Result<usize, isize> {
+        Err(ESPIPE)
+    }
+
+    fn modify_size(&self, diff: isize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn truncate_size(&self, new_size: usize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn set_timestamp(&self, ctime: Option<usize>, atime: Option<usize>, mtime: Option<usize>) {}
+
+    fn get_single_cache(
+        &self,
+        offset: usize,
+    ) -> Result<Arc<spin::Mutex<crate::fs::PageCache>>, ()> {
+        Err(())
+    }
+
+    fn get_all_caches(
+        &self,
+    ) -> Result<alloc::vec::Vec<Arc<spin::Mutex<crate::fs::PageCache>>>, ()> {
+        Err(())
+    }
+
+    fn oom(&self) -> usize {
+        0
+    }
+
+    fn hang_up(&self) -> bool {
+        false
+    }
+
+    /// 设备不再报告身份信息时返回 EIO
+    fn ioctl(&self, cmd: u32, argp: usize) -> isize {
+        let health = match self.device.health() {
+            Some(health) => health,
+            None => return EIO,
+        };
+        let token = current_user_token();
+        let result = match cmd {
+            BLKGETSIZE64 => copy_to_user(token, &(health.sectors * 512), argp as *mut u64),
+            DISK_GET_HEALTH => {
+                let mut data = DiskHealth {
+                    sectors: health.sectors,
+                    temperature: health.temperature.map_or(-1, |temperature| temperature as i32),
+                    reserved: 0,
+                    model: [0; 40],
+                    serial: [0; 24],
+                };
+                fill(&mut data.model, &health.model);
+                fill(&mut data.serial, &health.serial);
+                copy_to_user(token, &data, argp as *mut DiskHealth)
+            }
+            _ => return ENOTTY,
+        };
+        match result {
+            Ok(()) => 0,
+            Err(errno) => errno,
+        }
+    }
+
+    fn fcntl(&self, cmd: u32, arg: u32) -> isize {
+        0
+    }
+}
@@ -0,0 +1,179 @@
+use crate::fs::directory_tree::{drop_dentries, drop_page_caches, DROP_DENTRIES, DROP_PAGE_CACHE};
+use crate::fs::{dirent::Dirent, DiskInodeType};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use spin::Mutex;
+
+use crate::{
+    fs::{directory_tree::DirectoryTreeNode, file_trait::File, layout::Stat, StatMode},
+    mm::UserBuffer,
+    syscall::errno::{EACCES, EINVAL, ENOTDIR, ESPIPE},
+};
+
+/// `/proc/sys/vm/drop_caches`，只写
+/// 写入 1 释放页缓存，2 释放目录项与 inode 缓存，3 两者都释放
+pub struct DropCaches;
+
+impl DropCaches {
+    /// 解析写入的值并释放对应的缓存
+    fn apply(buf: &[u8]) -> Result<(), isize> {
+        let value = core::str::from_utf8(buf)
+            .ok()
+            .and_then(|value| value.trim().parse::<usize>().ok())
+            .filter(|value| (1..=(DROP_PAGE_CACHE | DROP_DENTRIES)).contains(value))
+            .ok_or(EINVAL)?;
+        if value & DROP_PAGE_CACHE != 0 {
+            let pages = drop_page_caches();
+            log::info!("[drop_caches] dropped {} page cache pages", pages);
+        }
+        if value & DROP_DENTRIES != 0 {
+            let dentries = drop_dentries();
+            log::info!("[drop_caches] dropped {} dentries", dentries);
+        }
+        Ok(())
+    }
+}
+
+impl File for DropCaches {
+    fn deep_clone(&self) -> Arc<dyn File> {
+        Arc::new(DropCaches)
+    }
+
+    fn readable(&self) -> bool {
+        false
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, _offset: Option<&mut usize>, _buf: &mut [u8]) -> usize {
+        0
+    }
+
+    fn write(&self, _offset: Option<&mut usize>, buf: &[u8]) -> usize {
+        match Self::apply(buf) {
+            Ok(()) => buf.len(),
+            Err(errno) => errno as usize,
+        }
+    }
+
+    fn r_ready(&self) -> bool {
+        false
+    }
+
+    fn w_ready(&self) -> bool {
+        true
+    }
+
+    fn get_size(&self) -> usize {
+        0
+    }
+
+    fn get_stat(&self) -> Stat {
+        Stat::new(
+            crate::makedev!(0, 5),
+            1,
+            StatMode::S_IFREG.bits() | 0o200,
+            1,
+            0,
+            0,
+            0,
+            0,
+            0,
+        )
+    }
+
+    fn read_user(&self, _offset: Option<usize>, _buf: UserBuffer) -> usize {
+        0
+    }
+
+    fn write_user(&self, _offset: Option<usize>, buf: UserBuffer) -> usize {
+        // "3\n" 之类的短字符串，更长的一定不合法
+        let mut value = [0u8; 16];
+        if buf.len() > value.len() {
+            return EINVAL as usize;
+        }
+        let len = buf.read(&mut value);
+        match Self::apply(&value[..len]) {
+            Ok(()) => buf.len(),
+            Err(errno) => errno as usize,
+        }
+    }
+
+    fn get_file_type(&self) -> DiskInodeType {
+        DiskInodeType::File
+    }
+
+    fn info_dirtree_node(&self, _dirnode_ptr: alloc::sync::Weak<DirectoryTreeNode>) {}
+
+    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>> {
+        None
+    }
+
+    fn open(&self, _flags: crate::fs::layout::OpenFlags, _special_use: bool) -> Arc<dyn File> {
+        Arc::new(DropCaches)
+    }
+
+    fn open_subfile(&self) -> Result<Vec<(String, Arc<dyn File>)>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn create(&self, _name: &str, _file_type: DiskInodeType) -> Result<Arc<dyn File>, isize> {
+        Err(EACCES)
+    }
+
+    fn link_child(&self, _name: &str, _child: &Self) -> Result<(), isize> {
+        Err(EACCES)
+    }
+
+    fn unlink(&self, _delete: bool) -> Result<(), isize> {
+        Err(EACCES)
+    }
+
+    fn get_dirent(&self, _count: usize) -> Vec<Dirent> {
+        Vec::new()
+    }
+
+    fn lseek(
+        &self,
+        _offset: isize,
+        _whence: crate::fs::layout::SeekWhence,
+    ) -> Result<usize, isize> {
+        Err(ESPIPE)
+    }
+
+    fn modify_size(&self, _diff: isize) -> Result<(), isize> {
+        Err(EACCES)
+    }
+
+    fn truncate_size(&self, _new_size: usize) -> Result<(), isize> {
+        Err(EACCES)
+    }
+
+    fn set_timestamp(&self, _ctime: Option<usize>, _atime: Option<usize>, _mtime: Option<usize>) {}
+
+    fn get_single_cache(
+        &self,
+        _offset: usize,
+    ) -> Result<Arc<Mutex<crate::fs::cache::PageCache>>, ()> {
+        Err(())
+    }
+
+    fn get_all_caches(&self) -> Result<Vec<Arc<Mutex<crate::fs::cache::PageCache>>>, ()> {
+        Err(())
+    }
+
+    fn hang_up(&self) -> bool {
+        false
+    }
+
+    fn fcntl(&self, _cmd: u32, _arg: u32) -> isize {
+        -1
+    }
+
+    fn oom(&self) -> usize {
+        0
+    }
+}
@@ -1,8 +1,38 @@
 use crate::{
-    fs::{dirent::Dirent, file_trait::File, DiskInodeType},
-    syscall::errno::{ENOTDIR, SUCCESS},
+    drivers::rtc::RtcTime,
+    fs::{dirent::Dirent, file_trait::File, DiskInodeType, StatMode},
+    mm::{copy_to_user, get_from_user},
+    syscall::errno::{EINVAL, ENODEV, ENOTDIR, ENOTTY, ESPIPE, SUCCESS},
+    task::current_user_token,
 };
+use alloc::sync::Arc;
+use spin::Mutex;
+
+/// 读出 RTC 的时间，参数为 `struct rtc_time`
+const RTC_RD_TIME: u32 = 0x8024_7009;
+/// 设置 RTC 的时间，不影响 CLOCK_REALTIME
+const RTC_SET_TIME: u32 = 0x4024_700a;
+
+/// 硬件实时时钟，时间为 Unix 时间（秒）
+pub trait Rtc: Send + Sync {
+    fn read_time(&self) -> u64;
+    fn set_time(&self, secs: u64);
+}
+
+/// 系统使用的 RTC，由驱动在探测到设备后注册
+static RTC: Mutex<Option<Arc<dyn Rtc>>> = Mutex::new(None);
+
+/// 注册系统的 RTC，已有 RTC 时替换它
+pub fn register_rtc(rtc: Arc<dyn Rtc>) {
+    *RTC.lock() = Some(rtc);
+}
 
+/// 读出 RTC 中的时间，没有注册 RTC 时返回 `None`
+pub fn read_rtc() -> Option<u64> {
+    RTC.lock().as_ref().map(|rtc| rtc.read_time())
+}
+
+/// /dev/misc/rtc，通过 ioctl 读写系统的 RTC
 pub struct Hwclock;
 
 #[allow(unused)]
@@ -12,11 +42,11 @@ impl File for Hwclock {
     }
 
     fn readable(&self) -> bool {
-        todo!()
+        true
     }
 
     fn writable(&self) -> bool {
-        todo!()
+        false
     }
 
     fn read(&self, offset: Option<&mut usize>, buf: &mut [u8]) -> usize {
@@ -28,11 +58,11 @@ impl File for Hwclock {
     }
 
     fn r_ready(&self) -> bool {
-        todo!()
+        true
     }
 
     fn w_ready(&self) -> bool {
-        todo!()
+        false
     }
 
     fn read_user(&self, offset: Option<usize>, buf: crate::mm::UserBuffer) -> usize {
@@ -48,7 +78,17 @@ impl File for Hwclock {
     }
 
     fn get_stat(&self) -> crate::fs::Stat {
-        todo!()
+        crate::fs::Stat::new(
+            crate::makedev!(0, 5),
+            1,
+            StatMode::S_IFCHR.bits() | 0o600,
+            1,
+            crate::makedev!(254, 0),
+            0,
+            0,
+            0,
+            0,
+        )
     }
 
     fn get_file_type(&self) -> DiskInodeType {
@@ -101,7 +141,7 @@ impl File for Hwclock {
     }
 
     fn lseek(&self, offset: isize, whence: crate::fs::SeekWhence) -> Result<usize, isize> {
-        todo!()
+        Err(ESPIPE)
     }
 
     fn modify_size(&self, diff: isize) -> Result<(), isize> {
@@ -134,14 +174,41 @@ impl File for Hwclock {
     }
 
     fn hang_up(&self) -> bool {
-        todo!()
+        false
     }
 
     fn fcntl(&self, cmd: u32, arg: u32) -> isize {
         todo!()
     }
 
-    fn ioctl(&self, _cmd: u32, _argp: usize) -> isize {
-        SUCCESS
+    fn ioctl(&self, cmd: u32, argp: usize) -> isize {
+        let rtc = match RTC.lock().clone() {
+            Some(rtc) => rtc,
+            None => return ENODEV,
+        };
+        let token = current_user_token();
+        match cmd {
+            RTC_RD_TIME => {
+                let time = RtcTime::from_unix(rtc.read_time());
+                match copy_to_user(token, &time, argp as *mut RtcTime) {
+                    Ok(()) => SUCCESS,
+                    Err(errno) => errno,
+                }
+            }
+            RTC_SET_TIME => {
+                let time = match get_from_user(token, argp as *const RtcTime) {
+                    Ok(time) => time,
+                    Err(errno) => return errno,
+                };
+                match time.to_unix() {
+                    Some(secs) => {
+                        rtc.set_time(secs);
+                        SUCCESS
+                    }
+                    None => EINVAL,
+                }
+            }
+            _ => ENOTTY,
+        }
     }
 }
@@ -0,0 +1,202 @@
+use crate::fs::{dirent::Dirent, DiskInodeType};
+use alloc::sync::Arc;
+
+use crate::{
+    drivers::block::{BlockDevice, LoopDevice},
+    fs::{directory_tree::DirectoryTreeNode, file_trait::File, layout::Stat, StatMode},
+    mm::UserBuffer,
+    syscall::errno::{EINVAL, ENOTDIR, ENOTTY, ENXIO, ESPIPE},
+    task::current_task,
+};
+
+/// Associate the regular file open as descriptor `argp` with the device
+pub const LOOP_SET_FD: u32 = 0x4C00;
+/// Dissociate the device from its backing file
+pub const LOOP_CLR_FD: u32 = 0x4C01;
+
+/// Loop device
+/// `/dev/loopN` presents the regular file given by ioctl(LOOP_SET_FD) as a
+/// block device, mount(2) can then mount the FAT32 image stored in the file.
+/// The device is read-only if the descriptor was opened read-only.
+pub struct Loop {
+    index: usize,
+    device: Arc<LoopDevice>,
+}
+
+impl Loop {
+    pub fn new(index: usize, device: Arc<LoopDevice>) -> Self {
+        Self { index, device }
+    }
+}
+
+#[allow(unused)]
+impl File for Loop {
+    fn deep_clone(&self) -> Arc<dyn File> {
+        Arc::new(Loop::new(self.index, self.device.clone()))
+    }
+
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    /// 不支持按字节读写，sendfile 等在内核中读写文件时返回 EINVAL
+    fn read(&self, offset: Option<&mut usize>, buf: &mut [u8]) -> usize {
+        EINVAL as usize
+    }
+
+    fn write(&self, offset: Option<&mut usize>, buf: &[u8]) -> usize {
+        EINVAL as usize
+    }
+
+    fn r_ready(&self) -> bool {
+        true
+    }
+
+    fn w_ready(&self) -> bool {
+        true
+    }
+
+    fn get_size(&self) -> usize {
+        0
+    }
+
+    fn get_stat(&self) -> Stat {
+        Stat::new(
+            crate::makedev!(0, 5),
+            1,
+            StatMode::S_IFBLK.bits() | 0o660,
+            1,
+            crate::makedev!(7, 0) | self.index as u64,
+            0,
+            0,
+            0,
+            0,
+        )
+    }
+
+    fn read_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
+        0
+    }
+
+    fn write_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
+        0
+    }
+
+    fn get_file_type(&self) -> DiskInodeType {
+        DiskInodeType::File
+    }
+
+    fn info_dirtree_node(
+        &self,
+        dirnode_ptr: alloc::sync::Weak<crate::fs::directory_tree::DirectoryTreeNode>,
+    ) {
+    }
+
+    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>> {
+        None
+    }
+
+    fn open(&self, flags: crate::fs::layout::OpenFlags, special_use: bool) -> Arc<dyn File> {
+        Arc::new(Loop::new(self.index, self.device.clone()))
+    }
+
+    fn open_subfile(
+        &self,
+    ) -> Result<alloc::vec::Vec<(alloc::string::String, alloc::sync::Arc<dyn File>)>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn create(&self, name: &str, file_type: DiskInodeType) -> Result<Arc<dyn File>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn link_child(&self, name: &str, child: &Self) -> Result<(), isize>
+    where
+        Self: Sized,
+    {
+        Err(ENOTDIR)
+    }
+
+    fn unlink(&self, delete: bool) -> Result<(), isize> {
+        Ok(())
+    }
+
+    fn get_dirent(&self, count: usize) -> alloc::vec::Vec<Dirent> {
+        alloc::vec::Vec::new()
+    }
+
+    fn lseek(&self, offset: isize, whence: crate::fs::SeekWhence) -> Result<usize, isize> {
+        Err(ESPIPE)
+    }
+
+    fn modify_size(&self, diff: isize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn truncate_size(&self, new_size: usize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn set_timestamp(&self, ctime: Option<usize>, atime: Option<usize>, mtime: Option<usize>) {}
+
+    fn get_single_cache(
+        &self,
+        offset: usize,
+    ) -> Result<Arc<spin::Mutex<crate::fs::PageCache>>, ()> {
+        Err(())
+    }
+
+    fn get_all_caches(
+        &self,
+    ) -> Result<alloc::vec::Vec<Arc<spin::Mutex<crate::fs::PageCache>>>, ()> {
+        Err(())
+    }
+
+    fn oom(&self) -> usize {
+        0
+    }
+
+    fn block_device(&self) -> Result<Arc<dyn BlockDevice>, isize> {
+        if self.device.is_attached() {
+            Ok(self.device.clone())
+        } else {
+            Err(ENXIO)
+        }
+    }
+
+    fn hang_up(&self) -> bool {
+        false
+    }
+
+    /// LOOP_SET_FD 要求 `argp` 是打开的普通文件，设备已有后备文件时返回 EBUSY；
+    /// LOOP_CLR_FD 在设备没有后备文件时返回 ENXIO
+    fn ioctl(&self, cmd: u32, argp: usize) -> isize {
+        let result = match cmd {
+            LOOP_SET_FD => {
+                let file = match current_task().unwrap().files.lock().get_ref(argp) {
+                    Ok(file_descriptor) => file_descriptor.file.clone(),
+                    Err(errno) => return errno,
+                };
+                // 只有磁盘上的文件有文件句柄，设备文件、管道与套接字都不能作为后备文件
+                if !file.is_file() || file.file_handle().is_none() {
+                    return EINVAL;
+                }
+                self.device.attach(file)
+            }
+            LOOP_CLR_FD => self.device.detach(),
+            _ => return ENOTTY,
+        };
+        match result {
+            Ok(()) => 0,
+            Err(errno) => errno,
+        }
+    }
+
+    fn fcntl(&self, cmd: u32, arg: u32) -> isize {
+        0
+    }
+}
@@ -0,0 +1,246 @@
+use crate::fs::{dirent::Dirent, DiskInodeType};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::*;
+use spin::RwLock;
+
+use crate::{
+    drivers::block::{BlockDevice, LinearDevice, LinearTarget, LOOP_DEVICES},
+    fs::{directory_tree::DirectoryTreeNode, file_trait::File, layout::Stat, StatMode},
+    mm::{copy_from_user, UserBuffer},
+    syscall::errno::{EBUSY, EINVAL, ENOTDIR, ENOTTY, ENXIO, ESPIPE},
+    task::current_user_token,
+};
+
+/// Load the linear table `DmTable` pointed to by the argument, numbered after
+/// device-mapper's `DM_TABLE_LOAD` but taking this kernel's own table instead
+/// of `struct dm_ioctl`
+pub const DM_TABLE_LOAD: u32 = 0xFD09;
+/// Remove the table, numbered after device-mapper's `DM_TABLE_CLEAR`
+pub const DM_TABLE_CLEAR: u32 = 0xFD0A;
+/// Most ranges a table can have
+pub const DM_MAX_TARGETS: usize = 8;
+
+/// A range of `len` blocks starting at block `offset` of `/dev/loop{dev}`
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct DmTarget {
+    pub dev: u64,
+    pub offset: u64,
+    pub len: u64,
+}
+
+/// Argument of `DM_TABLE_LOAD`: the first `count` targets in order
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct DmTable {
+    pub count: u64,
+    pub targets: [DmTarget; DM_MAX_TARGETS],
+}
+
+lazy_static! {
+    /// The device behind `/dev/dm-0`, `None` until a table is loaded
+    static ref DM_DEVICE: RwLock<Option<Arc<LinearDevice>>> = RwLock::new(None);
+}
+
+/// Device-mapper device
+/// `/dev/dm-0` concatenates ranges of loop devices into one block device, see
+/// [`LinearDevice`]. Its table is set by ioctl(DM_TABLE_LOAD).
+pub struct Mapper;
+
+impl Mapper {
+    fn load(table: &DmTable) -> Result<(), isize> {
+        if table.count as usize > DM_MAX_TARGETS {
+            return Err(EINVAL);
+        }
+        let mut targets = Vec::with_capacity(table.count as usize);
+        for target in &table.targets[..table.count as usize] {
+            let device: Arc<dyn BlockDevice> = match LOOP_DEVICES.get(target.dev as usize) {
+                Some(device) => device.clone(),
+                None => return Err(ENXIO),
+            };
+            targets.push(LinearTarget {
+                device,
+                offset: target.offset as usize,
+                len: target.len as usize,
+            });
+        }
+        let device = LinearDevice::new(targets)?;
+        let mut dm_device = DM_DEVICE.write();
+        if dm_device.is_some() {
+            return Err(EBUSY);
+        }
+        *dm_device = Some(Arc::new(device));
+        Ok(())
+    }
+}
+
+#[allow(unused)]
+impl File for Mapper {
+    fn deep_clone(&self) -> Arc<dyn File> {
+        Arc::new(Mapper {})
+    }
+
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    /// 不支持按字节读写，sendfile 等在内核中读写文件时返回 EINVAL
+    fn read(&self, offset: Option<&mut usize>, buf: &mut [u8]) -> usize {
+        EINVAL as usize
+    }
+
+    fn write(&self, offset: Option<&mut usize>, buf: &[u8]) -> usize {
+        EINVAL as usize
+    }
+
+    fn r_ready(&self) -> bool {
+        true
+    }
+
+    fn w_ready(&self) -> bool {
+        true
+    }
+
+    fn get_size(&self) -> usize {
+        0
+    }
+
+    fn get_stat(&self) -> Stat {
+        Stat::new(
+            crate::makedev!(0, 5),
+            1,
+            StatMode::S_IFBLK.bits() | 0o660,
+            1,
+            crate::makedev!(253, 0),
+            0,
+            0,
+            0,
+            0,
+        )
+    }
+
+    fn read_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
+        0
+    }
+
+    fn write_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
+        0
+    }
+
+    fn get_file_type(&self) -> DiskInodeType {
+        DiskInodeType::File
+    }
+
+    fn info_dirtree_node(
+        &self,
+        dirnode_ptr: alloc::sync::Weak<crate::fs::directory_tree::DirectoryTreeNode>,
+    ) {
+    }
+
+    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>> {
+        None
+    }
+
+    fn open(&self, flags: crate::fs::layout::OpenFlags, special_use: bool) -> Arc<dyn File> {
+        Arc::new(Mapper {})
+    }
+
+    fn open_subfile(
+        &self,
+    ) -> Result<alloc::vec::Vec<(alloc::string::String, alloc::sync::Arc<dyn File>)>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn create(&self, name: &str, file_type: DiskInodeType) -> Result<Arc<dyn File>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn link_child(&self, name: &str, child: &Self) -> Result<(), isize>
+    where
+        Self: Sized,
+    {
+        Err(ENOTDIR)
+    }
+
+    fn unlink(&self, delete: bool) -> Result<(), isize> {
+        Ok(())
+    }
+
+    fn get_dirent(&self, count: usize) -> alloc::vec::Vec<Dirent> {
+        alloc::vec::Vec::new()
+    }
+
+    fn lseek(&self, offset: isize, whence: crate::fs::SeekWhence) -> Result<usize, isize> {
+        Err(ESPIPE)
+    }
+
+    fn modify_size(&self, diff: isize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn truncate_size(&self, new_size: usize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn set_timestamp(&self, ctime: Option<usize>, atime: Option<usize>, mtime: Option<usize>) {}
+
+    fn get_single_cache(
+        &self,
+        offset: usize,
+    ) -> Result<Arc<spin::Mutex<crate::fs::PageCache>>, ()> {
+        Err(())
+    }
+
+    fn get_all_caches(
+        &self,
+    ) -> Result<alloc::vec::Vec<Arc<spin::Mutex<crate::fs::PageCache>>>, ()> {
+        Err(())
+    }
+
+    fn oom(&self) -> usize {
+        0
+    }
+
+    fn block_device(&self) -> Result<Arc<dyn BlockDevice>, isize> {
+        match DM_DEVICE.read().as_ref() {
+            Some(device) => Ok(device.clone()),
+            None => Err(ENXIO),
+        }
+    }
+
+    fn hang_up(&self) -> bool {
+        false
+    }
+
+    /// DM_TABLE_LOAD 在已有表时返回 EBUSY，表中的设备不是回环设备时返回 ENXIO；
+    /// DM_TABLE_CLEAR 在没有表时返回 ENXIO，已挂载的文件系统仍使用原来的表
+    fn ioctl(&self, cmd: u32, argp: usize) -> isize {
+        let result = match cmd {
+            DM_TABLE_LOAD => {
+                let mut table = DmTable::default();
+                match copy_from_user(current_user_token(), argp as *const DmTable, &mut table) {
+                    Ok(()) => Mapper::load(&table),
+                    Err(errno) => Err(errno),
+                }
+            }
+            DM_TABLE_CLEAR => match DM_DEVICE.write().take() {
+                Some(_) => Ok(()),
+                None => Err(ENXIO),
+            },
+            _ => return ENOTTY,
+        };
+        match result {
+            Ok(()) => 0,
+            Err(errno) => errno,
+        }
+    }
+
+    fn fcntl(&self, cmd: u32, arg: u32) -> isize {
+        0
+    }
+}
@@ -1,8 +1,18 @@
+pub mod disk;
+pub mod drop_caches;
 pub mod hwclock;
 pub mod interrupts;
+pub mod loop_dev;
+pub mod mapper;
 pub mod null;
+pub mod partition;
 pub mod pipe;
+pub mod proc_file;
+pub mod pty;
+pub mod ram;
+pub mod signalfd;
 pub mod socket;
+pub mod sysctl;
 pub mod tty;
 pub mod zero;
 pub mod urandom;
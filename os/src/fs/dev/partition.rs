@@ -0,0 +1,189 @@
+use crate::fs::{dirent::Dirent, DiskInodeType};
+use alloc::sync::Arc;
+
+use crate::{
+    drivers::block::{BlockDevice, PartitionBlockDevice},
+    fs::{directory_tree::DirectoryTreeNode, file_trait::File, layout::Stat, StatMode},
+    hal::BLOCK_SZ,
+    mm::{copy_to_user, UserBuffer},
+    syscall::errno::{EINVAL, ENOTDIR, ENOTTY, ESPIPE},
+    task::current_user_token,
+};
+
+use super::disk::BLKGETSIZE64;
+
+/// Disk partition
+/// `/dev/vdaN` (`/dev/sdaN` on a SATA drive) is partition `N` of the boot disk,
+/// mount(2) can mount the FAT32 filesystem on it. Its size is given by
+/// ioctl(BLKGETSIZE64); it cannot be read or written directly.
+pub struct DiskPartition {
+    device: Arc<PartitionBlockDevice>,
+    /// `makedev` of the disk's major number, the minor is the partition number
+    disk_major: u64,
+}
+
+impl DiskPartition {
+    pub fn new(device: Arc<PartitionBlockDevice>, disk_major: u64) -> Self {
+        Self { device, disk_major }
+    }
+}
+
+#[allow(unused)]
+impl File for DiskPartition {
+    fn deep_clone(&self) -> Arc<dyn File> {
+        Arc::new(DiskPartition::new(self.device.clone(), self.disk_major))
+    }
+
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    /// 不支持按字节读写，sendfile 等在内核中读写文件时返回 EINVAL
+    fn read(&self, offset: Option<&mut usize>, buf: &mut [u8]) -> usize {
+        EINVAL as usize
+    }
+
+    fn write(&self, offset: Option<&mut usize>, buf: &[u8]) -> usize {
+        EINVAL as usize
+    }
+
+    fn r_ready(&self) -> bool {
+        true
+    }
+
+    fn w_ready(&self) -> bool {
+        true
+    }
+
+    fn get_size(&self) -> usize {
+        self.device.blocks() * BLOCK_SZ
+    }
+
+    fn get_stat(&self) -> Stat {
+        let number = self.device.partition().number as u64;
+        Stat::new(
+            crate::makedev!(0, 5),
+            1,
+            StatMode::S_IFBLK.bits() | 0o660,
+            1,
+            self.disk_major | number,
+            self.get_size() as i64,
+            0,
+            0,
+            0,
+        )
+    }
+
+    fn read_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
+        0
+    }
+
+    fn write_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
+        0
+    }
+
+    fn get_file_type(&self) -> DiskInodeType {
+        DiskInodeType::File
+    }
+
+    fn info_dirtree_node(
+        &self,
+        dirnode_ptr: alloc::sync::Weak<crate::fs::directory_tree::DirectoryTreeNode>,
+    ) {
+    }
+
+    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>> {
+        None
+    }
+
+    fn open(&self, flags: crate::fs::layout::OpenFlags, special_use: bool) -> Arc<dyn File> {
+        self.deep_clone()
+    }
+
+    fn open_subfile(
+        &self,
+    ) -> Result<alloc::vec::Vec<(alloc::string::String, alloc::sync::Arc<dyn File>)>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn create(&self, name: &str, file_type: DiskInodeType) -> Result<Arc<dyn File>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn link_child(&self, name: &str, child: &Self) -> Result<(), isize>
+    where
+        Self: Sized,
+    {
+        Err(ENOTDIR)
+    }
+
+    fn unlink(&self, delete: bool) -> Result<(), isize> {
+        Ok(())
+    }
+
+    fn get_dirent(&self, count: usize) -> alloc::vec::Vec<Dirent> {
+        alloc::vec::Vec::new()
+    }
+
+    fn lseek(&self, offset: isize, whence: crate::fs::SeekWhence) -> Result<usize, isize> {
+        Err(ESPIPE)
+    }
+
+    fn modify_size(&self, diff: isize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn truncate_size(&self, new_size: usize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn set_timestamp(&self, ctime: Option<usize>, atime: Option<usize>, mtime: Option<usize>) {}
+
+    fn get_single_cache(
+        &self,
+        offset: usize,
+    ) -> Result<Arc<spin::Mutex<crate::fs::PageCache>>, ()> {
+        Err(())
+    }
+
+    fn get_all_caches(
+        &self,
+    ) -> Result<alloc::vec::Vec<Arc<spin::Mutex<crate::fs::PageCache>>>, ()> {
+        Err(())
+    }
+
+    fn oom(&self) -> usize {
+        0
+    }
+
+    fn block_device(&self) -> Result<Arc<dyn BlockDevice>, isize> {
+        Ok(self.device.clone())
+    }
+
+    fn hang_up(&self) -> bool {
+        false
+    }
+
+    fn ioctl(&self, cmd: u32, argp: usize) -> isize {
+        let result = match cmd {
+            BLKGETSIZE64 => copy_to_user(
+                current_user_token(),
+                &(self.get_size() as u64),
+                argp as *mut u64,
+            ),
+            _ => return ENOTTY,
+        };
+        match result {
+            Ok(()) => 0,
+            Err(errno) => errno,
+        }
+    }
+
+    fn fcntl(&self, cmd: u32, arg: u32) -> isize {
+        0
+    }
+}
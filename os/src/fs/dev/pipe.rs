@@ -6,6 +6,7 @@ use crate::fs::StatMode;
 use crate::syscall::errno::*;
 use crate::task::block_current_and_run_next;
 use crate::task::current_task;
+use crate::task::signal_pending;
 use crate::task::wait_with_timeout;
 use crate::timer::TimeSpec;
 use crate::{fs::file_trait::File, mm::UserBuffer};
@@ -56,6 +57,8 @@ pub struct PipeRingBuffer {
     status: RingBufferStatus,
     write_end: Option<Weak<Pipe>>,
     read_end: Option<Weak<Pipe>>,
+    /// 每次读写数据加一，边沿触发的 epoll 据此判断是否有新事件
+    generation: usize,
 }
 
 impl PipeRingBuffer {
@@ -71,6 +74,7 @@ impl PipeRingBuffer {
             status: RingBufferStatus::EMPTY,
             write_end: None,
             read_end: None,
+            generation: 0,
         }
     }
     #[allow(unused)]
@@ -108,6 +112,7 @@ impl PipeRingBuffer {
         } else {
             begin + read_bytes
         };
+        self.generation = self.generation.wrapping_add(1);
         read_bytes
     }
     #[inline]
@@ -130,6 +135,7 @@ impl PipeRingBuffer {
         } else {
             begin + write_bytes
         };
+        self.generation = self.generation.wrapping_add(1);
         write_bytes
     }
     fn set_write_end(&mut self, write_end: &Arc<Pipe>) {
@@ -177,19 +183,16 @@ impl File for Pipe {
         }
         let mut read_size = 0usize;
         loop {
-            let task = current_task().unwrap();
-            let inner = task.acquire_inner_lock();
-            // if !inner.sigpending.difference(inner.sigmask).is_empty() {
-            //     return ERESTART as usize;
-            // }
-            drop(inner);
-            drop(task);
             let mut ring = self.buffer.lock();
             if ring.status == RingBufferStatus::EMPTY {
                 if ring.all_write_ends_closed() {
                     return read_size;
                 }
                 drop(ring);
+                // 阻塞前有信号挂起则返回，信号处理后按 SA_RESTART 重新读写或返回 EINTR
+                if signal_pending() {
+                    return ERESTART as usize;
+                }
                 let task = current_task().unwrap();
                 wait_with_timeout(Arc::downgrade(&task), TimeSpec::now());
                 drop(task);
@@ -219,19 +222,16 @@ impl File for Pipe {
         let mut write_size = 0usize;
 
         loop {
-            let task = current_task().unwrap();
-            let inner = task.acquire_inner_lock();
-            // if !inner.sigpending.difference(inner.sigmask).is_empty() {
-            //     return ERESTART as usize;
-            // }
-            drop(inner);
-            drop(task);
             let mut ring = self.buffer.lock();
             if ring.status == RingBufferStatus::FULL {
                 if ring.all_read_ends_closed() {
                     return write_size;
                 }
                 drop(ring);
+                // 阻塞前有信号挂起则返回，信号处理后按 SA_RESTART 重新读写或返回 EINTR
+                if signal_pending() {
+                    return ERESTART as usize;
+                }
                 let task = current_task().unwrap();
                 wait_with_timeout(Arc::downgrade(&task), TimeSpec::now());
                 drop(task);
@@ -270,20 +270,16 @@ impl File for Pipe {
         }
         let mut read_size = 0usize;
         loop {
-            let task = current_task().unwrap();
-            let inner = task.acquire_inner_lock();
-            // 注释掉下面内容，pipe测例通过，跟读出pipe内容有关
-            // if !inner.sigpending.difference(inner.sigmask).is_empty() {
-            //     return ERESTART as usize;
-            // }
-            drop(inner);
-            drop(task);
             let mut ring = self.buffer.lock();
             if ring.status == RingBufferStatus::EMPTY {
                 if ring.all_write_ends_closed() {
                     return read_size;
                 }
                 drop(ring);
+                // 阻塞前有信号挂起则返回，信号处理后按 SA_RESTART 重新读写或返回 EINTR
+                if signal_pending() {
+                    return ERESTART as usize;
+                }
                 let task = current_task().unwrap();
                 wait_with_timeout(Arc::downgrade(&task), TimeSpec::now());
                 drop(task);
@@ -317,19 +313,16 @@ impl File for Pipe {
         }
         let mut write_size = 0usize;
         loop {
-            let task = current_task().unwrap();
-            let inner = task.acquire_inner_lock();
-            // if !inner.sigpending.difference(inner.sigmask).is_empty() {
-            //     return ERESTART as usize;
-            // }
-            drop(inner);
-            drop(task);
             let mut ring = self.buffer.lock();
             if ring.status == RingBufferStatus::FULL {
                 if ring.all_read_ends_closed() {
                     return write_size;
                 }
                 drop(ring);
+                // 阻塞前有信号挂起则返回，信号处理后按 SA_RESTART 重新读写或返回 EINTR
+                if signal_pending() {
+                    return ERESTART as usize;
+                }
                 let task = current_task().unwrap();
                 wait_with_timeout(Arc::downgrade(&task), TimeSpec::now());
                 drop(task);
@@ -455,6 +448,10 @@ impl File for Pipe {
         }
     }
 
+    fn poll_generation(&self) -> Option<usize> {
+        Some(self.buffer.lock().generation)
+    }
+
     fn fcntl(&self, cmd: u32, arg: u32) -> isize {
         // use crate::config::PAGE_SIZE;
         // use crate::syscall::fs::Fcntl_Command;
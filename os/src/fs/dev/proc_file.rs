@@ -0,0 +1,208 @@
+use crate::fs::{dirent::Dirent, DiskInodeType};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use spin::Mutex;
+
+use crate::{
+    fs::{directory_tree::DirectoryTreeNode, file_trait::File, layout::Stat, StatMode},
+    mm::UserBuffer,
+    syscall::errno::{EACCES, EINVAL, ENOTDIR, ESPIPE},
+};
+
+/// 从 `offset` 开始读 `contents` 中最多 `len` 字节，返回内容与读出的范围；
+/// 未给出偏移时使用并推进文件偏移量 `file_offset`
+pub fn read_range(
+    contents: String,
+    offset: Option<usize>,
+    file_offset: &Mutex<usize>,
+    len: usize,
+) -> (String, usize, usize) {
+    let range = |start: usize| {
+        let start = start.min(contents.len());
+        (start, (start + len).min(contents.len()))
+    };
+    let (start, end) = match offset {
+        Some(offset) => range(offset),
+        None => {
+            let mut offset = file_offset.lock();
+            let (start, end) = range(*offset);
+            *offset = (*offset).max(end);
+            (start, end)
+        }
+    };
+    (contents, start, end)
+}
+
+/// `/proc` 下的只读文本文件，每次读取时由 `contents` 重新生成内容
+pub struct ProcFile {
+    /// 文件偏移量
+    pub offset: Mutex<usize>,
+    contents: fn() -> String,
+}
+
+impl ProcFile {
+    pub fn new(contents: fn() -> String) -> Self {
+        Self {
+            offset: Mutex::new(0),
+            contents,
+        }
+    }
+
+    fn contents(&self) -> String {
+        (self.contents)()
+    }
+}
+
+impl File for ProcFile {
+    fn deep_clone(&self) -> Arc<dyn File> {
+        Arc::new(ProcFile {
+            offset: Mutex::new(*self.offset.lock()),
+            contents: self.contents,
+        })
+    }
+
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    fn read(&self, offset: Option<&mut usize>, buf: &mut [u8]) -> usize {
+        let (contents, start, end) = read_range(
+            self.contents(),
+            offset.as_deref().copied(),
+            &self.offset,
+            buf.len(),
+        );
+        buf[..end - start].copy_from_slice(&contents.as_bytes()[start..end]);
+        if let Some(offset) = offset {
+            *offset = end;
+        }
+        end - start
+    }
+
+    fn write(&self, _offset: Option<&mut usize>, _buf: &[u8]) -> usize {
+        ESPIPE as usize
+    }
+
+    fn r_ready(&self) -> bool {
+        true
+    }
+
+    fn w_ready(&self) -> bool {
+        false
+    }
+
+    fn get_size(&self) -> usize {
+        self.contents().len()
+    }
+
+    fn get_stat(&self) -> Stat {
+        Stat::new(
+            crate::makedev!(0, 5),
+            1,
+            StatMode::S_IFREG.bits() | 0o444,
+            1,
+            0,
+            self.get_size() as i64,
+            0,
+            0,
+            0,
+        )
+    }
+
+    fn read_user(&self, offset: Option<usize>, mut buf: UserBuffer) -> usize {
+        let (contents, start, end) = read_range(self.contents(), offset, &self.offset, buf.len());
+        buf.write(&contents.as_bytes()[start..end]);
+        end - start
+    }
+
+    fn write_user(&self, _offset: Option<usize>, _buf: UserBuffer) -> usize {
+        ESPIPE as usize
+    }
+
+    fn get_file_type(&self) -> DiskInodeType {
+        DiskInodeType::File
+    }
+
+    fn info_dirtree_node(&self, _dirnode_ptr: alloc::sync::Weak<DirectoryTreeNode>) {}
+
+    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>> {
+        None
+    }
+
+    fn open(&self, _flags: crate::fs::layout::OpenFlags, _special_use: bool) -> Arc<dyn File> {
+        Arc::new(ProcFile::new(self.contents))
+    }
+
+    fn open_subfile(&self) -> Result<Vec<(String, Arc<dyn File>)>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn create(&self, _name: &str, _file_type: DiskInodeType) -> Result<Arc<dyn File>, isize> {
+        Err(EACCES)
+    }
+
+    fn link_child(&self, _name: &str, _child: &Self) -> Result<(), isize> {
+        Err(EACCES)
+    }
+
+    fn unlink(&self, _delete: bool) -> Result<(), isize> {
+        Err(EACCES)
+    }
+
+    fn get_dirent(&self, _count: usize) -> Vec<Dirent> {
+        Vec::new()
+    }
+
+    fn lseek(&self, offset: isize, whence: crate::fs::layout::SeekWhence) -> Result<usize, isize> {
+        let mut current_offset = self.offset.lock();
+        let new_offset = match whence {
+            crate::fs::layout::SeekWhence::SEEK_SET => offset,
+            crate::fs::layout::SeekWhence::SEEK_CUR => *current_offset as isize + offset,
+            crate::fs::layout::SeekWhence::SEEK_END => self.get_size() as isize + offset,
+            _ => return Err(EINVAL),
+        };
+        if new_offset < 0 {
+            return Err(EINVAL);
+        }
+        *current_offset = new_offset as usize;
+        Ok(new_offset as usize)
+    }
+
+    fn modify_size(&self, _diff: isize) -> Result<(), isize> {
+        Err(EACCES)
+    }
+
+    fn truncate_size(&self, _new_size: usize) -> Result<(), isize> {
+        Err(EACCES)
+    }
+
+    fn set_timestamp(&self, _ctime: Option<usize>, _atime: Option<usize>, _mtime: Option<usize>) {}
+
+    fn get_single_cache(
+        &self,
+        _offset: usize,
+    ) -> Result<Arc<Mutex<crate::fs::cache::PageCache>>, ()> {
+        Err(())
+    }
+
+    fn get_all_caches(&self) -> Result<Vec<Arc<Mutex<crate::fs::cache::PageCache>>>, ()> {
+        Err(())
+    }
+
+    fn hang_up(&self) -> bool {
+        false
+    }
+
+    fn fcntl(&self, _cmd: u32, _arg: u32) -> isize {
+        -1
+    }
+
+    fn oom(&self) -> usize {
+        0
+    }
+}
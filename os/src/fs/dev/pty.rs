@@ -0,0 +1,586 @@
+use crate::fs::directory_tree::{add_pts_node, remove_pts_node, DirectoryTreeNode};
+use crate::fs::dirent::Dirent;
+use crate::fs::file_trait::File;
+use crate::fs::layout::{OpenFlags, SeekWhence, Stat};
+use crate::fs::DiskInodeType;
+use crate::fs::StatMode;
+use crate::mm::{translated_ref, translated_refmut, UserBuffer};
+use crate::syscall::errno::*;
+use crate::task::{
+    current_user_token, kill_pgrp, signal_pending, suspend_current_and_run_next, Signals,
+};
+use alloc::collections::{BTreeSet, VecDeque};
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec;
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+use num_enum::FromPrimitive;
+use spin::Mutex;
+
+use super::tty::{hang_up_session, LineDiscipline, LocalModes, TeletypeCommand, LINE_BUF_SIZE};
+
+lazy_static! {
+    /// 正在使用的伪终端编号
+    static ref PTY_INDICES: Mutex<BTreeSet<usize>> = Mutex::new(BTreeSet::new());
+}
+
+/// 从设备写出、等待主设备读出的数据的上限
+const OUTPUT_BUF_SIZE: usize = 4096;
+
+/// 一对伪终端主从设备共享的状态
+struct PtyInner {
+    /// 主设备写入的数据经过行规程成为从设备的输入
+    ldisc: LineDiscipline,
+    /// 从设备写出、经过输出加工、等待主设备读出的数据
+    output: VecDeque<u8>,
+    /// TIOCSPTLCK 设置的锁，锁住时打开从设备返回 EIO；新分配的伪终端是锁住的
+    locked: bool,
+    master_closed: bool,
+    /// 共用这个主设备的文件个数，dup 与 fork 各得到一个，全部关闭后主设备才算关闭
+    master_opens: usize,
+    /// 从设备被打开的次数，dup 与 fork 得到的文件描述符共用一次打开
+    slave_opens: usize,
+    /// 从设备打开过又全部关闭后，读主设备返回 EIO
+    slave_opened: bool,
+    /// 每次有数据进出或一端关闭时加一，边沿触发的 epoll 据此判断是否有新事件
+    generation: usize,
+}
+
+impl PtyInner {
+    fn new() -> Self {
+        Self {
+            ldisc: LineDiscipline::new(),
+            output: VecDeque::new(),
+            locked: true,
+            master_closed: false,
+            master_opens: 1,
+            slave_opens: 0,
+            slave_opened: false,
+            generation: 0,
+        }
+    }
+
+    fn slave_hung_up(&self) -> bool {
+        self.slave_opened && self.slave_opens == 0
+    }
+}
+
+/// /dev/ptmx：每次打开分配一对新的伪终端，返回主设备，从设备出现为 /dev/pts/N
+pub struct Ptmx;
+
+/// 伪终端主设备：写入的数据是从设备的输入，读出从设备的输出
+pub struct PtyMaster {
+    index: usize,
+    pty: Arc<Mutex<PtyInner>>,
+    nonblock: bool,
+}
+
+/// 伪终端从设备，即 /dev/pts/N，行为与终端相同。
+/// 目录树中的节点不算一次打开，打开它得到的才算
+pub struct PtySlave {
+    index: usize,
+    pty: Arc<Mutex<PtyInner>>,
+    opened: bool,
+    nonblock: bool,
+}
+
+impl Drop for PtyMaster {
+    /// 主设备关闭后从设备挂断：读返回 0，写返回 EIO，不能再被打开；
+    /// 从设备脱离控制它的会话，前台进程组收到 SIGHUP
+    fn drop(&mut self) {
+        let mut inner = self.pty.lock();
+        inner.master_opens -= 1;
+        if inner.master_opens > 0 {
+            return;
+        }
+        inner.master_closed = true;
+        inner.generation = inner.generation.wrapping_add(1);
+        let session = inner.ldisc.settings.session.clone();
+        drop(inner);
+        hang_up_session(&session);
+        remove_pts_node(self.index);
+        PTY_INDICES.lock().remove(&self.index);
+    }
+}
+
+impl Drop for PtySlave {
+    fn drop(&mut self) {
+        if self.opened {
+            let mut inner = self.pty.lock();
+            inner.slave_opens -= 1;
+            inner.generation = inner.generation.wrapping_add(1);
+        }
+    }
+}
+
+/// 伪终端设备共有的、与读写无关的 `File` 方法
+macro_rules! pty_common_methods {
+    () => {
+        fn readable(&self) -> bool {
+            true
+        }
+
+        fn writable(&self) -> bool {
+            true
+        }
+
+        fn read(&self, _offset: Option<&mut usize>, _buf: &mut [u8]) -> usize {
+            EINVAL as usize
+        }
+
+        fn write(&self, _offset: Option<&mut usize>, _buf: &[u8]) -> usize {
+            EINVAL as usize
+        }
+
+        fn get_size(&self) -> usize {
+            0
+        }
+
+        fn get_file_type(&self) -> DiskInodeType {
+            DiskInodeType::File
+        }
+
+        fn info_dirtree_node(&self, _dirnode_ptr: Weak<DirectoryTreeNode>) {}
+
+        fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>> {
+            None
+        }
+
+        fn open_subfile(&self) -> Result<Vec<(String, Arc<dyn File>)>, isize> {
+            Err(ENOTDIR)
+        }
+
+        fn create(&self, _name: &str, _file_type: DiskInodeType) -> Result<Arc<dyn File>, isize> {
+            Err(ENOTDIR)
+        }
+
+        fn link_child(&self, _name: &str, _child: &Self) -> Result<(), isize> {
+            Err(ENOTDIR)
+        }
+
+        fn unlink(&self, _delete: bool) -> Result<(), isize> {
+            Err(EPERM)
+        }
+
+        fn get_dirent(&self, _count: usize) -> Vec<Dirent> {
+            Vec::new()
+        }
+
+        fn lseek(&self, _offset: isize, _whence: SeekWhence) -> Result<usize, isize> {
+            Err(ESPIPE)
+        }
+
+        fn modify_size(&self, _diff: isize) -> Result<(), isize> {
+            Err(EINVAL)
+        }
+
+        fn truncate_size(&self, _new_size: usize) -> Result<(), isize> {
+            Err(EINVAL)
+        }
+
+        fn set_timestamp(
+            &self,
+            _ctime: Option<usize>,
+            _atime: Option<usize>,
+            _mtime: Option<usize>,
+        ) {
+        }
+
+        fn get_single_cache(
+            &self,
+            _offset: usize,
+        ) -> Result<Arc<Mutex<crate::fs::cache::PageCache>>, ()> {
+            Err(())
+        }
+
+        fn get_all_caches(&self) -> Result<Vec<Arc<Mutex<crate::fs::cache::PageCache>>>, ()> {
+            Err(())
+        }
+
+        fn oom(&self) -> usize {
+            0
+        }
+
+        fn fcntl(&self, _cmd: u32, _arg: u32) -> isize {
+            EINVAL
+        }
+    };
+}
+
+impl File for Ptmx {
+    fn deep_clone(&self) -> Arc<dyn File> {
+        Arc::new(Ptmx {})
+    }
+
+    fn r_ready(&self) -> bool {
+        false
+    }
+
+    fn w_ready(&self) -> bool {
+        false
+    }
+
+    fn read_user(&self, _offset: Option<usize>, _buf: UserBuffer) -> usize {
+        EINVAL as usize
+    }
+
+    fn write_user(&self, _offset: Option<usize>, _buf: UserBuffer) -> usize {
+        EINVAL as usize
+    }
+
+    fn get_stat(&self) -> Stat {
+        Stat::new(
+            crate::makedev!(0, 5),
+            1,
+            StatMode::S_IFCHR.bits() | 0o666,
+            1,
+            crate::makedev!(5, 2),
+            0,
+            0,
+            0,
+            0,
+        )
+    }
+
+    /// 分配未使用的最小编号，在 /dev/pts 下放入从设备，返回主设备
+    fn open(&self, flags: OpenFlags, _special_use: bool) -> Arc<dyn File> {
+        let index = {
+            let mut indices = PTY_INDICES.lock();
+            let index = (0..).find(|index| !indices.contains(index)).unwrap();
+            indices.insert(index);
+            index
+        };
+        let pty = Arc::new(Mutex::new(PtyInner::new()));
+        add_pts_node(
+            index,
+            Arc::new(PtySlave {
+                index,
+                pty: pty.clone(),
+                opened: false,
+                nonblock: false,
+            }),
+        );
+        Arc::new(PtyMaster {
+            index,
+            pty,
+            nonblock: flags.contains(OpenFlags::O_NONBLOCK),
+        })
+    }
+
+    fn hang_up(&self) -> bool {
+        false
+    }
+
+    pty_common_methods!();
+}
+
+impl File for PtyMaster {
+    /// 新的主设备只能通过打开 /dev/ptmx 得到，这里得到的与原来的共用同一对伪终端
+    fn deep_clone(&self) -> Arc<dyn File> {
+        self.pty.lock().master_opens += 1;
+        Arc::new(PtyMaster {
+            index: self.index,
+            pty: self.pty.clone(),
+            nonblock: self.nonblock,
+        })
+    }
+
+    /// 从设备写出了数据，或从设备已全部关闭
+    fn r_ready(&self) -> bool {
+        let inner = self.pty.lock();
+        !inner.output.is_empty() || inner.slave_hung_up()
+    }
+
+    fn w_ready(&self) -> bool {
+        self.pty.lock().ldisc.room() > 0
+    }
+
+    /// 读出从设备的输出；没有数据时阻塞，从设备打开过又全部关闭后返回 EIO
+    fn read_user(&self, offset: Option<usize>, mut buf: UserBuffer) -> usize {
+        if offset.is_some() {
+            return ESPIPE as usize;
+        }
+        if buf.len() == 0 {
+            return 0;
+        }
+        loop {
+            let mut inner = self.pty.lock();
+            if !inner.output.is_empty() {
+                let read_size = inner.output.len().min(buf.len());
+                let data: Vec<u8> = inner.output.drain(..read_size).collect();
+                inner.generation = inner.generation.wrapping_add(1);
+                drop(inner);
+                return buf.write(&data);
+            }
+            if inner.slave_hung_up() {
+                return EIO as usize;
+            }
+            drop(inner);
+            if self.nonblock {
+                return EAGAIN as usize;
+            }
+            if signal_pending() {
+                return ERESTART as usize;
+            }
+            suspend_current_and_run_next();
+        }
+    }
+
+    /// 写入的数据逐字节交给行规程，回显进入从设备的输出，产生的信号发给前台进程组；
+    /// 输入缓冲区满时阻塞，返回接收的字节数
+    fn write_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
+        if offset.is_some() {
+            return ESPIPE as usize;
+        }
+        let mut data = vec![0u8; buf.len()];
+        buf.read(&mut data);
+        let mut written = 0;
+        while written < data.len() {
+            let mut inner = self.pty.lock();
+            if inner.ldisc.room() == 0 {
+                drop(inner);
+                if written > 0 {
+                    break;
+                }
+                if self.nonblock {
+                    return EAGAIN as usize;
+                }
+                if signal_pending() {
+                    return ERESTART as usize;
+                }
+                suspend_current_and_run_next();
+                continue;
+            }
+            let mut echo = Vec::new();
+            let mut signals = Vec::new();
+            while written < data.len() && inner.ldisc.room() > 0 {
+                if let Some(signal) = inner.ldisc.receive(data[written], &mut echo) {
+                    signals.push(signal);
+                }
+                written += 1;
+            }
+            let PtyInner { ldisc, output, .. } = &mut *inner;
+            ldisc.output(&echo, output);
+            inner.generation = inner.generation.wrapping_add(1);
+            let pgid = inner.ldisc.settings.foreground_pgid();
+            drop(inner);
+            if pgid != 0 {
+                for signal in signals {
+                    kill_pgrp(pgid, signal);
+                }
+            }
+        }
+        written
+    }
+
+    fn get_stat(&self) -> Stat {
+        Stat::new(
+            crate::makedev!(0, 5),
+            1,
+            StatMode::S_IFCHR.bits() | 0o620,
+            1,
+            crate::makedev!(5, 2),
+            0,
+            0,
+            0,
+            0,
+        )
+    }
+
+    fn open(&self, _flags: OpenFlags, _special_use: bool) -> Arc<dyn File> {
+        self.deep_clone()
+    }
+
+    fn hang_up(&self) -> bool {
+        self.pty.lock().slave_hung_up()
+    }
+
+    fn poll_generation(&self) -> Option<usize> {
+        Some(self.pty.lock().generation)
+    }
+
+    /// TIOCGPTN 取得从设备的编号，TIOCSPTLCK 锁住或解锁从设备，其余与从设备相同
+    fn ioctl(&self, cmd: u32, argp: usize) -> isize {
+        let token = current_user_token();
+        match TeletypeCommand::from_primitive(cmd) {
+            TeletypeCommand::TIOCGPTN => match translated_refmut(token, argp as *mut u32) {
+                Ok(word) => {
+                    *word = self.index as u32;
+                    SUCCESS
+                }
+                Err(errno) => errno,
+            },
+            TeletypeCommand::TIOCSPTLCK => match translated_ref(token, argp as *const i32) {
+                Ok(word) => {
+                    self.pty.lock().locked = *word != 0;
+                    SUCCESS
+                }
+                Err(errno) => errno,
+            },
+            _ => self.pty.lock().ldisc.ioctl(cmd, argp),
+        }
+    }
+
+    pty_common_methods!();
+}
+
+impl PtySlave {
+    /// 打开一次从设备
+    fn open_once(&self, nonblock: bool) -> Arc<dyn File> {
+        let mut inner = self.pty.lock();
+        inner.slave_opens += 1;
+        inner.slave_opened = true;
+        Arc::new(PtySlave {
+            index: self.index,
+            pty: self.pty.clone(),
+            opened: true,
+            nonblock,
+        })
+    }
+}
+
+impl File for PtySlave {
+    fn deep_clone(&self) -> Arc<dyn File> {
+        self.open_once(self.nonblock)
+    }
+
+    /// 有一行（规范模式）或任意输入可读，或主设备已关闭
+    fn r_ready(&self) -> bool {
+        let inner = self.pty.lock();
+        inner.ldisc.readable() || inner.master_closed
+    }
+
+    fn w_ready(&self) -> bool {
+        let inner = self.pty.lock();
+        inner.output.len() < OUTPUT_BUF_SIZE || inner.master_closed
+    }
+
+    /// 按行规程读出输入；没有输入时阻塞，主设备关闭后返回 0
+    fn read_user(&self, offset: Option<usize>, mut buf: UserBuffer) -> usize {
+        if offset.is_some() {
+            return ESPIPE as usize;
+        }
+        if let Err(errno) = self
+            .pty
+            .lock()
+            .ldisc
+            .settings
+            .check_background(Signals::SIGTTIN)
+        {
+            return errno as usize;
+        }
+        let mut data = vec![0u8; buf.len().min(LINE_BUF_SIZE)];
+        loop {
+            let mut inner = self.pty.lock();
+            if let Some(read_size) = inner.ldisc.read(&mut data) {
+                inner.generation = inner.generation.wrapping_add(1);
+                drop(inner);
+                return buf.write(&data[..read_size]);
+            }
+            if inner.master_closed {
+                return 0;
+            }
+            drop(inner);
+            if self.nonblock {
+                return EAGAIN as usize;
+            }
+            if signal_pending() {
+                return ERESTART as usize;
+            }
+            suspend_current_and_run_next();
+        }
+    }
+
+    /// 输出经过加工后交给主设备读出；输出缓冲区满时阻塞，主设备关闭后返回 EIO
+    fn write_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
+        if offset.is_some() {
+            return ESPIPE as usize;
+        }
+        let mut data = vec![0u8; buf.len()];
+        buf.read(&mut data);
+        {
+            let inner = self.pty.lock();
+            let settings = &inner.ldisc.settings;
+            if settings.lflag().contains(LocalModes::TOSTOP) {
+                if let Err(errno) = settings.check_background(Signals::SIGTTOU) {
+                    return errno as usize;
+                }
+            }
+        }
+        let mut written = 0;
+        while written < data.len() {
+            let mut inner = self.pty.lock();
+            if inner.master_closed {
+                return EIO as usize;
+            }
+            let room = OUTPUT_BUF_SIZE.saturating_sub(inner.output.len());
+            if room == 0 {
+                drop(inner);
+                if written > 0 {
+                    break;
+                }
+                if self.nonblock {
+                    return EAGAIN as usize;
+                }
+                if signal_pending() {
+                    return ERESTART as usize;
+                }
+                suspend_current_and_run_next();
+                continue;
+            }
+            let end = data.len().min(written + room);
+            let PtyInner { ldisc, output, .. } = &mut *inner;
+            ldisc.output(&data[written..end], output);
+            inner.generation = inner.generation.wrapping_add(1);
+            written = end;
+        }
+        written
+    }
+
+    fn get_stat(&self) -> Stat {
+        Stat::new(
+            crate::makedev!(0, 5),
+            1,
+            StatMode::S_IFCHR.bits() | 0o620,
+            1,
+            // makedev(136, index)
+            crate::makedev!(136, 0)
+                | ((self.index as u64 & !0xff) << 12)
+                | (self.index as u64 & 0xff),
+            0,
+            0,
+            0,
+            0,
+        )
+    }
+
+    /// 从设备被锁住或主设备已关闭时不能打开
+    fn may_open(&self, _flags: OpenFlags) -> Result<(), isize> {
+        let inner = self.pty.lock();
+        if inner.locked || inner.master_closed {
+            Err(EIO)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn open(&self, flags: OpenFlags, _special_use: bool) -> Arc<dyn File> {
+        self.pty.lock().ldisc.settings.open_as_ctty(flags);
+        self.open_once(flags.contains(OpenFlags::O_NONBLOCK))
+    }
+
+    fn hang_up(&self) -> bool {
+        self.pty.lock().master_closed
+    }
+
+    fn poll_generation(&self) -> Option<usize> {
+        Some(self.pty.lock().generation)
+    }
+
+    fn ioctl(&self, cmd: u32, argp: usize) -> isize {
+        self.pty.lock().ldisc.ioctl(cmd, argp)
+    }
+
+    pty_common_methods!();
+}
@@ -0,0 +1,246 @@
+use crate::fs::{dirent::Dirent, DiskInodeType};
+use alloc::sync::Arc;
+use spin::Mutex;
+
+use crate::{
+    drivers::block::{BlockDevice, RamDisk},
+    fs::{directory_tree::DirectoryTreeNode, file_trait::File, layout::Stat, SeekWhence, StatMode},
+    mm::{copy_to_user, UserBuffer},
+    syscall::errno::{EINVAL, ENOTDIR, ENOTTY},
+    task::current_user_token,
+};
+
+use super::disk::BLKGETSIZE64;
+
+/// RAM disk
+/// `/dev/ram0` reads and writes the bytes of a [`RamDisk`] at the file offset,
+/// so that a filesystem image can be written to it, and mount(2) can mount
+/// the FAT32 filesystem on it. Its size is set by `ramdisk_size=` at boot and
+/// given by ioctl(BLKGETSIZE64).
+pub struct Ram {
+    device: Arc<RamDisk>,
+    offset: Mutex<usize>,
+}
+
+impl Ram {
+    pub fn new(device: Arc<RamDisk>) -> Self {
+        Self {
+            device,
+            offset: Mutex::new(0),
+        }
+    }
+}
+
+#[allow(unused)]
+impl File for Ram {
+    fn deep_clone(&self) -> Arc<dyn File> {
+        Arc::new(Ram {
+            device: self.device.clone(),
+            offset: Mutex::new(*self.offset.lock()),
+        })
+    }
+
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, offset: Option<&mut usize>, buf: &mut [u8]) -> usize {
+        let mut file_offset = self.offset.lock();
+        let offset = offset.unwrap_or(&mut *file_offset);
+        let len = self.device.read_at(*offset, buf);
+        *offset += len;
+        len
+    }
+
+    fn write(&self, offset: Option<&mut usize>, buf: &[u8]) -> usize {
+        let mut file_offset = self.offset.lock();
+        let offset = offset.unwrap_or(&mut *file_offset);
+        let len = self.device.write_at(*offset, buf);
+        *offset += len;
+        len
+    }
+
+    fn r_ready(&self) -> bool {
+        true
+    }
+
+    fn w_ready(&self) -> bool {
+        true
+    }
+
+    fn get_size(&self) -> usize {
+        self.device.size()
+    }
+
+    fn get_stat(&self) -> Stat {
+        Stat::new(
+            crate::makedev!(0, 5),
+            1,
+            StatMode::S_IFBLK.bits() | 0o660,
+            1,
+            crate::makedev!(1, 0),
+            self.device.size() as i64,
+            0,
+            0,
+            0,
+        )
+    }
+
+    /// 读到设备末尾为止
+    fn read_user(&self, offset: Option<usize>, mut buf: UserBuffer) -> usize {
+        let mut file_offset = self.offset.lock();
+        let positioned = offset.is_some();
+        let mut offset = offset.unwrap_or(*file_offset);
+        let mut total = 0;
+        for slice in buf.buffers.iter_mut() {
+            let len = self.device.read_at(offset, slice);
+            offset += len;
+            total += len;
+            if len < slice.len() {
+                break;
+            }
+        }
+        // pread/pwrite 不移动文件偏移量
+        if !positioned {
+            *file_offset = offset;
+        }
+        total
+    }
+
+    /// 写到设备末尾或内存耗尽为止
+    fn write_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
+        let mut file_offset = self.offset.lock();
+        let positioned = offset.is_some();
+        let mut offset = offset.unwrap_or(*file_offset);
+        let mut total = 0;
+        for slice in buf.buffers.iter() {
+            let len = self.device.write_at(offset, slice);
+            offset += len;
+            total += len;
+            if len < slice.len() {
+                break;
+            }
+        }
+        // pread/pwrite 不移动文件偏移量
+        if !positioned {
+            *file_offset = offset;
+        }
+        total
+    }
+
+    fn get_file_type(&self) -> DiskInodeType {
+        DiskInodeType::File
+    }
+
+    fn info_dirtree_node(
+        &self,
+        dirnode_ptr: alloc::sync::Weak<crate::fs::directory_tree::DirectoryTreeNode>,
+    ) {
+    }
+
+    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>> {
+        None
+    }
+
+    fn open(&self, flags: crate::fs::layout::OpenFlags, special_use: bool) -> Arc<dyn File> {
+        Arc::new(Ram::new(self.device.clone()))
+    }
+
+    fn open_subfile(
+        &self,
+    ) -> Result<alloc::vec::Vec<(alloc::string::String, alloc::sync::Arc<dyn File>)>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn create(&self, name: &str, file_type: DiskInodeType) -> Result<Arc<dyn File>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn link_child(&self, name: &str, child: &Self) -> Result<(), isize>
+    where
+        Self: Sized,
+    {
+        Err(ENOTDIR)
+    }
+
+    fn unlink(&self, delete: bool) -> Result<(), isize> {
+        Ok(())
+    }
+
+    fn get_dirent(&self, count: usize) -> alloc::vec::Vec<Dirent> {
+        alloc::vec::Vec::new()
+    }
+
+    fn lseek(&self, offset: isize, whence: SeekWhence) -> Result<usize, isize> {
+        let mut file_offset = self.offset.lock();
+        let new_offset = match whence {
+            SeekWhence::SEEK_SET => offset,
+            SeekWhence::SEEK_CUR => *file_offset as isize + offset,
+            SeekWhence::SEEK_END => self.device.size() as isize + offset,
+            _ => return Err(EINVAL),
+        };
+        if new_offset < 0 {
+            return Err(EINVAL);
+        }
+        *file_offset = new_offset as usize;
+        Ok(new_offset as usize)
+    }
+
+    fn modify_size(&self, diff: isize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn truncate_size(&self, new_size: usize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn set_timestamp(&self, ctime: Option<usize>, atime: Option<usize>, mtime: Option<usize>) {}
+
+    fn get_single_cache(
+        &self,
+        offset: usize,
+    ) -> Result<Arc<spin::Mutex<crate::fs::PageCache>>, ()> {
+        Err(())
+    }
+
+    fn get_all_caches(
+        &self,
+    ) -> Result<alloc::vec::Vec<Arc<spin::Mutex<crate::fs::PageCache>>>, ()> {
+        Err(())
+    }
+
+    fn oom(&self) -> usize {
+        0
+    }
+
+    fn hang_up(&self) -> bool {
+        false
+    }
+
+    fn block_device(&self) -> Result<Arc<dyn BlockDevice>, isize> {
+        Ok(self.device.clone())
+    }
+
+    fn ioctl(&self, cmd: u32, argp: usize) -> isize {
+        let result = match cmd {
+            BLKGETSIZE64 => copy_to_user(
+                current_user_token(),
+                &(self.device.size() as u64),
+                argp as *mut u64,
+            ),
+            _ => return ENOTTY,
+        };
+        match result {
+            Ok(()) => 0,
+            Err(errno) => errno,
+        }
+    }
+
+    fn fcntl(&self, cmd: u32, arg: u32) -> isize {
+        0
+    }
+}
@@ -0,0 +1,249 @@
+use crate::fs::directory_tree::DirectoryTreeNode;
+use crate::fs::dirent::Dirent;
+use crate::fs::file_trait::File;
+use crate::fs::layout::{OpenFlags, SeekWhence, Stat};
+use crate::fs::DiskInodeType;
+use crate::mm::UserBuffer;
+use crate::syscall::errno::*;
+use crate::task::{current_task, suspend_current_and_run_next, Signals};
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use spin::Mutex;
+
+/// `struct signalfd_siginfo`，每个读出的信号一个，共 128 字节
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SignalfdSiginfo {
+    ssi_signo: u32,
+    ssi_errno: i32,
+    ssi_code: i32,
+    /// 发送者的 pid 等其余字段，挂起信号只记录了信号本身，都为 0
+    __pad: [u8; 128 - 3 * core::mem::size_of::<u32>()],
+}
+
+impl SignalfdSiginfo {
+    fn new(signum: usize) -> Self {
+        Self {
+            ssi_signo: signum as u32,
+            ssi_errno: 0,
+            // SI_USER
+            ssi_code: 0,
+            __pad: [0; 128 - 3 * core::mem::size_of::<u32>()],
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(
+                self as *const Self as *const u8,
+                core::mem::size_of::<Self>(),
+            )
+        }
+    }
+}
+
+/// signalfd：把读者挂起的、属于 `mask` 的信号以 `SignalfdSiginfo` 的形式读出，
+/// 读出的信号不再以异步方式递送。
+/// 通常先用 sigprocmask 屏蔽这些信号，使它们一直挂起直到被读出
+pub struct SignalFd {
+    mask: Mutex<Signals>,
+    /// SFD_NONBLOCK：没有信号时读返回 EAGAIN 而不是等待
+    nonblock: bool,
+}
+
+impl SignalFd {
+    pub fn new(mask: Signals, nonblock: bool) -> Self {
+        Self {
+            mask: Mutex::new(Self::allowed(mask)),
+            nonblock,
+        }
+    }
+
+    /// SIGKILL 与 SIGSTOP 不能通过 signalfd 接收，静默地从 `mask` 中去掉
+    fn allowed(mask: Signals) -> Signals {
+        mask - (Signals::SIGKILL | Signals::SIGSTOP)
+    }
+
+    /// 对已有的 signalfd 调用 signalfd4 时替换它的信号集
+    pub fn set_mask(&self, mask: Signals) {
+        *self.mask.lock() = Self::allowed(mask);
+    }
+
+    /// 从当前任务的挂起信号中取出至多 `max` 个属于 `mask` 的信号，按信号编号从小到大
+    fn dequeue(&self, max: usize) -> Vec<usize> {
+        let mask = *self.mask.lock();
+        let task = current_task().unwrap();
+        let mut inner = task.acquire_inner_lock();
+        let mut signums = Vec::new();
+        while signums.len() < max {
+            let signum = match (inner.sigpending & mask).peek_front() {
+                Some(signum) => signum,
+                None => break,
+            };
+            inner
+                .sigpending
+                .remove(Signals::from_signum(signum).unwrap());
+            signums.push(signum);
+        }
+        signums
+    }
+
+    /// 当前任务是否有未被屏蔽、又不属于 `mask` 的信号挂起，有则等待应被打断
+    fn interrupted(&self) -> bool {
+        let mask = *self.mask.lock();
+        let task = current_task().unwrap();
+        let inner = task.acquire_inner_lock();
+        !(inner.sigpending - inner.sigmask - mask).is_empty()
+    }
+}
+
+impl File for SignalFd {
+    fn deep_clone(&self) -> Arc<dyn File> {
+        Arc::new(SignalFd::new(*self.mask.lock(), self.nonblock))
+    }
+
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    fn read(&self, _offset: Option<&mut usize>, _buf: &mut [u8]) -> usize {
+        EINVAL as usize
+    }
+
+    fn write(&self, _offset: Option<&mut usize>, _buf: &[u8]) -> usize {
+        EINVAL as usize
+    }
+
+    /// 读者有属于 `mask` 的信号挂起时可读
+    fn r_ready(&self) -> bool {
+        let mask = *self.mask.lock();
+        let task = current_task().unwrap();
+        let inner = task.acquire_inner_lock();
+        !(inner.sigpending & mask).is_empty()
+    }
+
+    fn w_ready(&self) -> bool {
+        false
+    }
+
+    /// 读出尽可能多的信号，缓冲区放不下一个 `SignalfdSiginfo` 时返回 EINVAL；
+    /// 没有信号时阻塞，直到有信号挂起，或被其他信号打断（按 SA_RESTART 重新读或返回 EINTR）
+    fn read_user(&self, offset: Option<usize>, mut buf: UserBuffer) -> usize {
+        if offset.is_some() {
+            return ESPIPE as usize;
+        }
+        let size = core::mem::size_of::<SignalfdSiginfo>();
+        if buf.len() < size {
+            return EINVAL as usize;
+        }
+        loop {
+            let signums = self.dequeue(buf.len() / size);
+            if !signums.is_empty() {
+                let mut read_size = 0;
+                for signum in signums {
+                    read_size += buf.write_at(read_size, SignalfdSiginfo::new(signum).as_bytes());
+                }
+                return read_size;
+            }
+            if self.nonblock {
+                return EAGAIN as usize;
+            }
+            if self.interrupted() {
+                return ERESTART as usize;
+            }
+            suspend_current_and_run_next();
+        }
+    }
+
+    fn write_user(&self, _offset: Option<usize>, _buf: UserBuffer) -> usize {
+        EINVAL as usize
+    }
+
+    fn get_size(&self) -> usize {
+        0
+    }
+
+    fn get_stat(&self) -> Stat {
+        Stat::new(0, 1, 0o600, 1, 0, 0, 0, 0, 0)
+    }
+
+    fn get_file_type(&self) -> DiskInodeType {
+        DiskInodeType::File
+    }
+
+    fn is_file(&self) -> bool {
+        false
+    }
+
+    fn info_dirtree_node(&self, _dirnode_ptr: Weak<DirectoryTreeNode>) {}
+
+    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>> {
+        None
+    }
+
+    fn open(&self, _flags: OpenFlags, _special_use: bool) -> Arc<dyn File> {
+        self.deep_clone()
+    }
+
+    fn open_subfile(&self) -> Result<Vec<(String, Arc<dyn File>)>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn create(&self, _name: &str, _file_type: DiskInodeType) -> Result<Arc<dyn File>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn link_child(&self, _name: &str, _child: &Self) -> Result<(), isize> {
+        Err(ENOTDIR)
+    }
+
+    fn unlink(&self, _delete: bool) -> Result<(), isize> {
+        Err(EACCES)
+    }
+
+    fn get_dirent(&self, _count: usize) -> Vec<Dirent> {
+        Vec::new()
+    }
+
+    fn lseek(&self, _offset: isize, _whence: SeekWhence) -> Result<usize, isize> {
+        Err(ESPIPE)
+    }
+
+    fn modify_size(&self, _diff: isize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn truncate_size(&self, _new_size: usize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn set_timestamp(&self, _ctime: Option<usize>, _atime: Option<usize>, _mtime: Option<usize>) {}
+
+    fn get_single_cache(
+        &self,
+        _offset: usize,
+    ) -> Result<Arc<Mutex<crate::fs::cache::PageCache>>, ()> {
+        Err(())
+    }
+
+    fn get_all_caches(&self) -> Result<Vec<Arc<Mutex<crate::fs::cache::PageCache>>>, ()> {
+        Err(())
+    }
+
+    fn oom(&self) -> usize {
+        0
+    }
+
+    fn hang_up(&self) -> bool {
+        false
+    }
+
+    fn fcntl(&self, _cmd: u32, _arg: u32) -> isize {
+        EINVAL
+    }
+}
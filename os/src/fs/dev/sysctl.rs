@@ -0,0 +1,207 @@
+use super::proc_file::read_range;
+use crate::fs::{dirent::Dirent, DiskInodeType};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use spin::Mutex;
+
+use crate::{
+    fs::{directory_tree::DirectoryTreeNode, file_trait::File, layout::Stat, StatMode},
+    mm::UserBuffer,
+    syscall::errno::{EACCES, EINVAL, ENOTDIR},
+};
+
+/// `/proc/sys` 下的一个可调参数，读出当前值，写入新值
+///
+/// 读写都以一行文本进行，由 `get`/`set` 负责格式化与解析
+pub struct Sysctl {
+    /// 文件偏移量
+    pub offset: Mutex<usize>,
+    /// 当前值的文本，以换行结尾
+    get: fn() -> String,
+    /// 解析写入的文本（已去掉首尾空白）并设置新值
+    set: fn(&str) -> Result<(), isize>,
+}
+
+/// 一次写入的最大长度，可调参数都是短字符串
+const MAX_WRITE_LEN: usize = 256;
+
+impl Sysctl {
+    pub fn new(get: fn() -> String, set: fn(&str) -> Result<(), isize>) -> Self {
+        Self {
+            offset: Mutex::new(0),
+            get,
+            set,
+        }
+    }
+
+    fn apply(&self, buf: &[u8]) -> Result<(), isize> {
+        let value = core::str::from_utf8(buf).map_err(|_| EINVAL)?;
+        (self.set)(value.trim())
+    }
+}
+
+impl File for Sysctl {
+    fn deep_clone(&self) -> Arc<dyn File> {
+        Arc::new(Sysctl {
+            offset: Mutex::new(*self.offset.lock()),
+            get: self.get,
+            set: self.set,
+        })
+    }
+
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, offset: Option<&mut usize>, buf: &mut [u8]) -> usize {
+        let (contents, start, end) = read_range(
+            (self.get)(),
+            offset.as_deref().copied(),
+            &self.offset,
+            buf.len(),
+        );
+        buf[..end - start].copy_from_slice(&contents.as_bytes()[start..end]);
+        if let Some(offset) = offset {
+            *offset = end;
+        }
+        end - start
+    }
+
+    fn write(&self, _offset: Option<&mut usize>, buf: &[u8]) -> usize {
+        match self.apply(buf) {
+            Ok(()) => buf.len(),
+            Err(errno) => errno as usize,
+        }
+    }
+
+    fn r_ready(&self) -> bool {
+        true
+    }
+
+    fn w_ready(&self) -> bool {
+        true
+    }
+
+    fn get_size(&self) -> usize {
+        (self.get)().len()
+    }
+
+    fn get_stat(&self) -> Stat {
+        Stat::new(
+            crate::makedev!(0, 5),
+            1,
+            StatMode::S_IFREG.bits() | 0o644,
+            1,
+            0,
+            self.get_size() as i64,
+            0,
+            0,
+            0,
+        )
+    }
+
+    fn read_user(&self, offset: Option<usize>, mut buf: UserBuffer) -> usize {
+        let (contents, start, end) = read_range((self.get)(), offset, &self.offset, buf.len());
+        buf.write(&contents.as_bytes()[start..end]);
+        end - start
+    }
+
+    fn write_user(&self, _offset: Option<usize>, buf: UserBuffer) -> usize {
+        let mut value = [0u8; MAX_WRITE_LEN];
+        if buf.len() > value.len() {
+            return EINVAL as usize;
+        }
+        let len = buf.read(&mut value);
+        match self.apply(&value[..len]) {
+            Ok(()) => buf.len(),
+            Err(errno) => errno as usize,
+        }
+    }
+
+    fn get_file_type(&self) -> DiskInodeType {
+        DiskInodeType::File
+    }
+
+    fn info_dirtree_node(&self, _dirnode_ptr: alloc::sync::Weak<DirectoryTreeNode>) {}
+
+    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>> {
+        None
+    }
+
+    fn open(&self, _flags: crate::fs::layout::OpenFlags, _special_use: bool) -> Arc<dyn File> {
+        Arc::new(Sysctl::new(self.get, self.set))
+    }
+
+    fn open_subfile(&self) -> Result<Vec<(String, Arc<dyn File>)>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn create(&self, _name: &str, _file_type: DiskInodeType) -> Result<Arc<dyn File>, isize> {
+        Err(EACCES)
+    }
+
+    fn link_child(&self, _name: &str, _child: &Self) -> Result<(), isize> {
+        Err(EACCES)
+    }
+
+    fn unlink(&self, _delete: bool) -> Result<(), isize> {
+        Err(EACCES)
+    }
+
+    fn get_dirent(&self, _count: usize) -> Vec<Dirent> {
+        Vec::new()
+    }
+
+    fn lseek(&self, offset: isize, whence: crate::fs::layout::SeekWhence) -> Result<usize, isize> {
+        let mut current_offset = self.offset.lock();
+        let new_offset = match whence {
+            crate::fs::layout::SeekWhence::SEEK_SET => offset,
+            crate::fs::layout::SeekWhence::SEEK_CUR => *current_offset as isize + offset,
+            crate::fs::layout::SeekWhence::SEEK_END => self.get_size() as isize + offset,
+            _ => return Err(EINVAL),
+        };
+        if new_offset < 0 {
+            return Err(EINVAL);
+        }
+        *current_offset = new_offset as usize;
+        Ok(new_offset as usize)
+    }
+
+    fn modify_size(&self, _diff: isize) -> Result<(), isize> {
+        Err(EACCES)
+    }
+
+    fn truncate_size(&self, _new_size: usize) -> Result<(), isize> {
+        Err(EACCES)
+    }
+
+    fn set_timestamp(&self, _ctime: Option<usize>, _atime: Option<usize>, _mtime: Option<usize>) {}
+
+    fn get_single_cache(
+        &self,
+        _offset: usize,
+    ) -> Result<Arc<Mutex<crate::fs::cache::PageCache>>, ()> {
+        Err(())
+    }
+
+    fn get_all_caches(&self) -> Result<Vec<Arc<Mutex<crate::fs::cache::PageCache>>>, ()> {
+        Err(())
+    }
+
+    fn hang_up(&self) -> bool {
+        false
+    }
+
+    fn fcntl(&self, _cmd: u32, _arg: u32) -> isize {
+        -1
+    }
+
+    fn oom(&self) -> usize {
+        0
+    }
+}
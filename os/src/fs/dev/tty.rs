@@ -1,15 +1,29 @@
+use crate::drivers::serial;
 use crate::fs::directory_tree::DirectoryTreeNode;
 use crate::fs::dirent::Dirent;
 use crate::fs::file_trait::File;
-use crate::fs::layout::Stat;
+use crate::fs::layout::{OpenFlags, Stat};
 use crate::fs::DiskInodeType;
 use crate::fs::StatMode;
-use crate::hal::console_getchar;
+use crate::hal::console_putchar;
 use crate::mm::{copy_from_user, copy_to_user};
 use crate::mm::{translated_ref, translated_refmut, UserBuffer};
 use crate::syscall::errno::*;
 
-use alloc::sync::Arc;
+#[cfg(feature = "uart_irq")]
+use crate::task::{block_current_and_run_next, wait_with_timeout};
+use crate::task::{
+    current_task, find_processes, kill_pgrp, signal_ignored_or_blocked, signal_pending,
+    suspend_current_and_run_next, wake_interruptible, Signals, TaskControlBlock,
+};
+#[cfg(feature = "uart_irq")]
+use crate::timer::TimeSpec;
+#[cfg(feature = "uart_irq")]
+use crate::utils::InterruptGuard;
+use alloc::collections::VecDeque;
+use alloc::sync::{Arc, Weak};
+use alloc::vec;
+use alloc::vec::Vec;
 use lazy_static::lazy_static;
 use log::{info, warn};
 use num_enum::FromPrimitive;
@@ -39,24 +53,574 @@ impl Default for WinSize {
     }
 }
 
+/// 终端所属的会话与前台进程组
+/// # 说明
+/// + 终端成为某个会话的控制终端后，会话中的进程都持有它，以此找到自己的控制终端
+/// + `sid` 为 0 时终端不是任何会话的控制终端，此时不检查调用者的会话
+#[derive(Default)]
+pub struct TerminalSession {
+    pub sid: usize,
+    pub foreground_pgid: usize,
+}
+
+impl TerminalSession {
+    /// 终端是否是别的会话（不是 `sid`）的控制终端
+    fn foreign(&self, sid: usize) -> bool {
+        self.sid != 0 && self.sid != sid
+    }
+}
+
+/// 终端挂断或控制它的会话首进程退出：终端脱离会话，前台进程组收到 SIGHUP 与 SIGCONT
+pub fn hang_up_session(session: &Mutex<TerminalSession>) {
+    let pgid = {
+        let mut session = session.lock();
+        session.sid = 0;
+        core::mem::take(&mut session.foreground_pgid)
+    };
+    if pgid != 0 {
+        kill_pgrp(pgid, Signals::SIGHUP);
+        kill_pgrp(pgid, Signals::SIGCONT);
+    }
+}
+
+/// 会话首进程退出时释放控制终端
+pub fn release_ctty_on_exit(task: &TaskControlBlock) {
+    if task.pid.0 != task.tgid {
+        return;
+    }
+    let ctty = {
+        let mut inner = task.acquire_inner_lock();
+        if inner.sid != task.tgid {
+            return;
+        }
+        let ctty = inner.controlling_terminal().cloned();
+        inner.ctty = None;
+        ctty
+    };
+    if let Some(ctty) = ctty {
+        hang_up_session(&ctty);
+    }
+}
+
+/// 当前进程的会话号与进程组号
+fn caller_ids() -> (usize, usize) {
+    let task = current_task().unwrap();
+    let inner = task.acquire_inner_lock();
+    (inner.sid, inner.pgid)
+}
+
+/// 终端的属性：termios、窗口大小、会话与前台进程组，控制台与伪终端共用
+#[derive(Default)]
+pub struct TerminalSettings {
+    pub session: Arc<Mutex<TerminalSession>>,
+    pub winsize: WinSize,
+    pub termios: Termios,
+}
+
+impl TerminalSettings {
+    pub fn foreground_pgid(&self) -> usize {
+        self.session.lock().foreground_pgid
+    }
+
+    pub fn iflag(&self) -> InputModes {
+        InputModes::from_bits_truncate(self.termios.iflag)
+    }
+
+    pub fn oflag(&self) -> OutputModes {
+        OutputModes::from_bits_truncate(self.termios.oflag)
+    }
+
+    pub fn lflag(&self) -> LocalModes {
+        LocalModes::from_bits_truncate(self.termios.lflag)
+    }
+
+    /// ISIG 时字符 `c` 产生的信号
+    pub fn signal_char(&self, c: u8) -> Option<Signals> {
+        if !self.lflag().contains(LocalModes::ISIG) {
+            return None;
+        }
+        let cc = self.termios.cc;
+        if c == cc[VINTR] {
+            Some(Signals::SIGINT)
+        } else if c == cc[VQUIT] {
+            Some(Signals::SIGQUIT)
+        } else if c == cc[VSUSP] {
+            Some(Signals::SIGTSTP)
+        } else {
+            None
+        }
+    }
+
+    /// 检查当前进程能否读（`SIGTTIN`）或写（`SIGTTOU`）终端
+    /// # 说明
+    /// + 终端是别的会话的控制终端、没有前台进程组或当前进程在前台进程组中时可以访问
+    /// + 后台进程组忽略或屏蔽了该信号时，读返回 EIO，写照常进行
+    /// + 否则向后台进程组发送该信号并返回 ERESTART，进程继续后重新执行系统调用
+    pub fn check_background(&self, signal: Signals) -> Result<(), isize> {
+        let (sid, pgid) = caller_ids();
+        let session = self.session.lock();
+        if session.foreign(sid) || session.foreground_pgid == 0 || session.foreground_pgid == pgid {
+            return Ok(());
+        }
+        drop(session);
+        if signal_ignored_or_blocked(signal) {
+            return if signal == Signals::SIGTTIN {
+                Err(EIO)
+            } else {
+                Ok(())
+            };
+        }
+        kill_pgrp(pgid, signal);
+        Err(ERESTART)
+    }
+
+    /// 让终端成为 `task` 所在会话的控制终端，前台进程组为 `task` 所在的进程组
+    /// # 说明
+    /// 只有没有控制终端的会话首进程可以获得控制终端；终端已经是别的会话的控制终端时，
+    /// `steal` 为真才把它抢过来，原来的会话随之失去控制终端
+    fn acquire_ctty(&self, task: &TaskControlBlock, steal: bool) -> isize {
+        let mut inner = task.acquire_inner_lock();
+        if let Some(ctty) = inner.controlling_terminal() {
+            return if Arc::ptr_eq(ctty, &self.session) {
+                SUCCESS
+            } else {
+                EPERM
+            };
+        }
+        if inner.sid != task.tgid {
+            return EPERM;
+        }
+        let mut session = self.session.lock();
+        if session.sid != 0 && !steal {
+            return EPERM;
+        }
+        session.sid = inner.sid;
+        session.foreground_pgid = inner.pgid;
+        drop(session);
+        inner.ctty = Some(self.session.clone());
+        SUCCESS
+    }
+
+    /// 没有指定 O_NOCTTY 时，没有控制终端的会话首进程打开的终端成为它的控制终端
+    pub fn open_as_ctty(&self, flags: OpenFlags) {
+        if flags.contains(OpenFlags::O_NOCTTY) {
+            return;
+        }
+        if let Some(task) = current_task() {
+            self.acquire_ctty(&task, false);
+        }
+    }
+
+    /// 读写 termios、会话、前台进程组与窗口大小的 ioctl，其余命令返回 ENOTTY
+    /// # 说明
+    /// + 终端是别的会话的控制终端时，读写前台进程组返回 ENOTTY
+    /// + 还没有前台进程组时，TIOCGPGRP 把调用者所在的进程组设为前台进程组
+    /// + TIOCSPGRP 要求进程组在调用者的会话中，后台进程调用时与写终端一样受 SIGTTOU 约束
+    /// + TIOCNOTTY 放弃控制终端，会话首进程放弃时整个会话失去它，前台进程组收到 SIGHUP
+    pub fn ioctl(&mut self, cmd: u32, argp: usize) -> isize {
+        let token = crate::task::current_user_token();
+        match TeletypeCommand::from_primitive(cmd) {
+            TeletypeCommand::TCGETS | TeletypeCommand::TCGETA => {
+                match copy_to_user(token, &self.termios, argp as *mut Termios) {
+                    Ok(()) => SUCCESS,
+                    Err(errno) => errno,
+                }
+            }
+            TeletypeCommand::TCSETS
+            | TeletypeCommand::TCSETSW
+            | TeletypeCommand::TCSETSF
+            | TeletypeCommand::TCSETA
+            | TeletypeCommand::TCSETAW
+            | TeletypeCommand::TCSETAF => {
+                match copy_from_user(token, argp as *const Termios, &mut self.termios) {
+                    Ok(()) => SUCCESS,
+                    Err(errno) => errno,
+                }
+            }
+            TeletypeCommand::TIOCGPGRP => match translated_refmut(token, argp as *mut u32) {
+                Ok(word) => {
+                    let (sid, pgid) = caller_ids();
+                    let mut session = self.session.lock();
+                    if session.foreign(sid) {
+                        return ENOTTY;
+                    }
+                    if session.foreground_pgid == 0 {
+                        session.foreground_pgid = pgid;
+                    }
+                    *word = session.foreground_pgid as u32;
+                    SUCCESS
+                }
+                Err(errno) => errno,
+            },
+            TeletypeCommand::TIOCSPGRP => match translated_ref(token, argp as *const i32) {
+                Ok(&pgid) => {
+                    if pgid < 0 {
+                        return EINVAL;
+                    }
+                    let (sid, _) = caller_ids();
+                    if self.session.lock().foreign(sid) {
+                        return ENOTTY;
+                    }
+                    let in_session = |task: &TaskControlBlock, task_pgid| {
+                        task_pgid == pgid as usize && task.acquire_inner_lock().sid == sid
+                    };
+                    if find_processes(in_session).is_empty() {
+                        return EPERM;
+                    }
+                    if let Err(errno) = self.check_background(Signals::SIGTTOU) {
+                        return errno;
+                    }
+                    self.session.lock().foreground_pgid = pgid as usize;
+                    SUCCESS
+                }
+                Err(errno) => errno,
+            },
+            TeletypeCommand::TIOCSCTTY => self.acquire_ctty(&current_task().unwrap(), argp == 1),
+            TeletypeCommand::TIOCNOTTY => {
+                let task = current_task().unwrap();
+                let mut inner = task.acquire_inner_lock();
+                match inner.controlling_terminal() {
+                    Some(ctty) if Arc::ptr_eq(ctty, &self.session) => {}
+                    _ => return ENOTTY,
+                }
+                inner.ctty = None;
+                let leader = inner.sid == task.tgid;
+                drop(inner);
+                if leader {
+                    hang_up_session(&self.session);
+                }
+                SUCCESS
+            }
+            TeletypeCommand::TIOCGSID => match translated_refmut(token, argp as *mut u32) {
+                Ok(word) => {
+                    let (sid, _) = caller_ids();
+                    let session = self.session.lock();
+                    if session.sid != sid {
+                        return ENOTTY;
+                    }
+                    *word = sid as u32;
+                    SUCCESS
+                }
+                Err(errno) => errno,
+            },
+            TeletypeCommand::TIOCGWINSZ => {
+                match copy_to_user(token, &self.winsize, argp as *mut WinSize) {
+                    Ok(()) => SUCCESS,
+                    Err(errno) => errno,
+                }
+            }
+            TeletypeCommand::TIOCSWINSZ => {
+                match copy_from_user(token, argp as *const WinSize, &mut self.winsize) {
+                    Ok(()) => SUCCESS,
+                    Err(errno) => errno,
+                }
+            }
+            _ => ENOTTY,
+        }
+    }
+}
+
+/// 规范模式下的一行与非规范模式下的输入的上限，与 Linux 的 N_TTY_BUF_SIZE 相同
+pub const LINE_BUF_SIZE: usize = 4096;
+
+/// 行规程：按照 termios 加工写入终端的输入与终端写出的输出
+/// # 说明
+/// + 规范模式（ICANON）下输入按行编辑，一行结束后才能读出，一次至多读出一行；
+///   行首的 VEOF 结束一个空行，读到它时 read 返回 0
+/// + 非规范模式下输入立即可读，VMIN 与 VTIME 按 1 与 0 处理
+/// + ISIG 时 VINTR、VQUIT 与 VSUSP 不进入输入，而是产生 SIGINT、SIGQUIT 与 SIGTSTP
+pub struct LineDiscipline {
+    pub settings: TerminalSettings,
+    /// 可以读出的输入
+    input: VecDeque<u8>,
+    /// 规范模式下 `input` 中各个已结束的行的长度
+    lines: VecDeque<usize>,
+    /// 规范模式下正在编辑的行
+    line: Vec<u8>,
+}
+
+impl LineDiscipline {
+    pub fn new() -> Self {
+        Self {
+            settings: TerminalSettings::default(),
+            input: VecDeque::new(),
+            lines: VecDeque::new(),
+            line: Vec::new(),
+        }
+    }
+
+    /// 还能接收多少字节的输入，为 0 时再写入的输入被丢弃
+    pub fn room(&self) -> usize {
+        LINE_BUF_SIZE.saturating_sub(self.input.len() + self.line.len())
+    }
+
+    /// 是否有输入可以读出
+    pub fn readable(&self) -> bool {
+        if self.settings.lflag().contains(LocalModes::ICANON) {
+            !self.lines.is_empty()
+        } else {
+            !self.input.is_empty()
+        }
+    }
+
+    /// 接收一个写入终端的字节，要回显的字节（尚未经过输出加工）追加到 `echo`，
+    /// 返回它产生的信号
+    pub fn receive(&mut self, mut c: u8, echo: &mut Vec<u8>) -> Option<Signals> {
+        let iflag = self.settings.iflag();
+        let lflag = self.settings.lflag();
+        let cc = self.settings.termios.cc;
+        if c == b'\r' {
+            if iflag.contains(InputModes::IGNCR) {
+                return None;
+            }
+            if iflag.contains(InputModes::ICRNL) {
+                c = b'\n';
+            }
+        } else if c == b'\n' && iflag.contains(InputModes::INLCR) {
+            c = b'\r';
+        }
+        if let Some(signal) = self.settings.signal_char(c) {
+            if !lflag.contains(LocalModes::NOFLSH) {
+                self.input.clear();
+                self.lines.clear();
+                self.line.clear();
+            }
+            if lflag.contains(LocalModes::ECHO) {
+                Self::echo_char(c, lflag, echo);
+            }
+            return Some(signal);
+        }
+        if !lflag.contains(LocalModes::ICANON) {
+            if self.room() > 0 {
+                self.input.push_back(c);
+                if lflag.contains(LocalModes::ECHO) {
+                    Self::echo_char(c, lflag, echo);
+                }
+            }
+            return None;
+        }
+        let echo_erase = lflag.contains(LocalModes::ECHO | LocalModes::ECHOE);
+        if c == cc[VERASE] {
+            if self.line.pop().is_some() && echo_erase {
+                echo.extend_from_slice(b"\x08 \x08");
+            }
+        } else if c == cc[VKILL] {
+            let erased = self.line.len();
+            self.line.clear();
+            if lflag.contains(LocalModes::ECHO | LocalModes::ECHOKE) {
+                for _ in 0..erased {
+                    echo.extend_from_slice(b"\x08 \x08");
+                }
+            } else if lflag.contains(LocalModes::ECHO | LocalModes::ECHOK) {
+                echo.push(b'\n');
+            }
+        } else if c == cc[VEOF] {
+            self.end_line();
+        } else if self.room() > 0 {
+            self.line.push(c);
+            if lflag.contains(LocalModes::ECHO)
+                || (c == b'\n' && lflag.contains(LocalModes::ECHONL))
+            {
+                Self::echo_char(c, lflag, echo);
+            }
+            if c == b'\n' {
+                self.end_line();
+            }
+        }
+        None
+    }
+
+    /// 读出输入，没有可读的输入时返回 `None`
+    pub fn read(&mut self, buf: &mut [u8]) -> Option<usize> {
+        let len = if self.settings.lflag().contains(LocalModes::ICANON) {
+            *self.lines.front()?
+        } else if self.input.is_empty() {
+            return None;
+        } else {
+            self.input.len()
+        };
+        let read_size = len.min(buf.len());
+        for (dst, src) in buf.iter_mut().zip(self.input.drain(..read_size)) {
+            *dst = src;
+        }
+        if let Some(line_len) = self.lines.front_mut() {
+            *line_len -= read_size;
+            if *line_len == 0 {
+                self.lines.pop_front();
+            }
+        }
+        Some(read_size)
+    }
+
+    /// 终端的 ioctl，修改 termios 后按新的模式整理已有的输入
+    pub fn ioctl(&mut self, cmd: u32, argp: usize) -> isize {
+        let ret = self.settings.ioctl(cmd, argp);
+        if self.settings.lflag().contains(LocalModes::ICANON) {
+            // 非规范模式下写入的输入作为一行
+            let in_lines: usize = self.lines.iter().sum();
+            if in_lines < self.input.len() {
+                self.lines.push_back(self.input.len() - in_lines);
+            }
+        } else {
+            // 正在编辑的行立即可读
+            self.input.extend(self.line.drain(..));
+            self.lines.clear();
+        }
+        ret
+    }
+
+    /// 输出加工：OPOST 与 ONLCR 时把 '\n' 换成 "\r\n"，结果追加到 `out`
+    pub fn output(&self, src: &[u8], out: &mut VecDeque<u8>) {
+        let oflag = self.settings.oflag();
+        let onlcr = oflag.contains(OutputModes::OPOST | OutputModes::ONLCR);
+        for &c in src {
+            if c == b'\n' && onlcr {
+                out.push_back(b'\r');
+            }
+            out.push_back(c);
+        }
+    }
+
+    fn end_line(&mut self) {
+        self.lines.push_back(self.line.len());
+        self.input.extend(self.line.drain(..));
+    }
+
+    /// ECHOCTL 时控制字符回显为 "^X"
+    fn echo_char(c: u8, lflag: LocalModes, echo: &mut Vec<u8>) {
+        if lflag.contains(LocalModes::ECHOCTL)
+            && (c < b' ' && c != b'\n' && c != b'\t' || c == 0x7f)
+        {
+            echo.push(b'^');
+            echo.push(c ^ 0x40);
+        } else {
+            echo.push(c);
+        }
+    }
+}
+
+/// 等待控制台输入的任务，由串口接收中断唤醒
+static READERS: Mutex<Vec<Weak<TaskControlBlock>>> = Mutex::new(Vec::new());
+/// 等待输入时最长的睡眠时间，防止错过唤醒
+#[cfg(feature = "uart_irq")]
+const INPUT_WAIT_TIMEOUT: TimeSpec = TimeSpec {
+    tv_sec: 0,
+    tv_nsec: 50_000_000,
+};
+
+/// 控制台收到输入后调用（串口接收中断或时钟中断）：终端空闲时立即交给行规程，
+/// 这样没有进程在读终端时回显与 Ctrl-C 也能生效，有输入可读时唤醒等待输入的任务
+/// # 说明
+/// 终端正被使用时不处理，由持有终端的任务处理
+pub fn console_input() {
+    let readable = match TTY.inner.try_lock() {
+        Some(mut inner) => {
+            let signals = inner.receive_input();
+            let pgid = inner.ldisc.settings.foreground_pgid();
+            let readable = inner.ldisc.readable();
+            drop(inner);
+            signal_foreground(pgid, signals);
+            readable
+        }
+        None => true,
+    };
+    if readable {
+        let readers = core::mem::take(&mut *READERS.lock());
+        for task in readers.iter().filter_map(Weak::upgrade) {
+            wake_interruptible(task);
+        }
+    }
+}
+
+/// 时钟中断中处理控制台的输入，把 ISIG 的控制字符发给前台进程组，
+/// 这样前台进程没有在读终端时 Ctrl-C 与 Ctrl-Z 也能生效
+/// # 说明
+/// + 只在从用户态进入的时钟中断中调用，控制台正被使用时直接返回
+/// + 打开串口接收中断后输入由中断处理，这里什么也不做
+pub fn poll_console_signal() {
+    #[cfg(feature = "uart_irq")]
+    if serial::rx::enabled() {
+        return;
+    }
+    console_input();
+}
+
+/// 睡眠等待控制台的输入；没有串口接收中断时让出处理器，之后重新轮询
+fn wait_input() {
+    #[cfg(feature = "uart_irq")]
+    if serial::rx::enabled() {
+        let task = current_task().unwrap();
+        let reader = Arc::downgrade(&task);
+        {
+            let _guard = InterruptGuard::new();
+            READERS.lock().push(reader.clone());
+        }
+        // 登记之后才到达的输入一定会唤醒本任务
+        if !serial::rx::pending() {
+            wait_with_timeout(reader.clone(), TimeSpec::now() + INPUT_WAIT_TIMEOUT);
+            drop(task);
+            block_current_and_run_next();
+        }
+        let _guard = InterruptGuard::new();
+        READERS.lock().retain(|task| !task.ptr_eq(&reader));
+        return;
+    }
+    suspend_current_and_run_next();
+}
+
+/// 把行规程产生的信号发给前台进程组
+fn signal_foreground(pgid: usize, signals: Vec<Signals>) {
+    if pgid != 0 {
+        for signal in signals {
+            kill_pgrp(pgid, signal);
+        }
+    }
+}
+
+/// 输出到控制台；回显中可能有不完整的 UTF-8 序列，这时逐字节输出
+fn write_console(bytes: &[u8]) {
+    match core::str::from_utf8(bytes) {
+        Ok(content) => print!("{}", content),
+        Err(_) => bytes.iter().for_each(|&c| console_putchar(c as usize)),
+    }
+}
+
 pub struct TeletypeInner {
-    last_char: u8,
-    foreground_pgid: u32,
-    winsize: WinSize,
-    termios: Termios,
+    ldisc: LineDiscipline,
 }
 
 impl Default for TeletypeInner {
     fn default() -> Self {
         Self {
-            last_char: 255,
-            foreground_pgid: Default::default(),
-            winsize: WinSize::default(),
-            termios: Termios::default(),
+            ldisc: LineDiscipline::new(),
+        }
+    }
+}
+
+impl TeletypeInner {
+    /// 把控制台收到的输入交给行规程并输出回显，返回产生的信号
+    fn receive_input(&mut self) -> Vec<Signals> {
+        let mut echo = Vec::new();
+        let mut signals = Vec::new();
+        while self.ldisc.room() > 0 {
+            let c = match serial::getchar() {
+                Some(c) => c,
+                None => break,
+            };
+            if let Some(signal) = self.ldisc.receive(c, &mut echo) {
+                signals.push(signal);
+            }
         }
+        if !echo.is_empty() {
+            let mut out = VecDeque::new();
+            self.ldisc.output(&echo, &mut out);
+            write_console(out.make_contiguous());
+        }
+        signals
     }
 }
 
+/// 控制台终端，输入经过行规程，输出直接写到控制台
 #[derive(Default)]
 pub struct Teletype {
     inner: Mutex<TeletypeInner>,
@@ -66,6 +630,17 @@ impl Teletype {
     pub fn new() -> Self {
         Default::default()
     }
+
+    /// 先把控制台收到的输入交给行规程，再对行规程调用 `f`
+    fn with_input<T>(&self, f: impl FnOnce(&mut LineDiscipline) -> T) -> T {
+        let mut inner = self.inner.lock();
+        let signals = inner.receive_input();
+        let pgid = inner.ldisc.settings.foreground_pgid();
+        let ret = f(&mut inner.ldisc);
+        drop(inner);
+        signal_foreground(pgid, signals);
+        ret
+    }
 }
 
 // TODO: independ of rust sbi
@@ -100,134 +675,50 @@ impl File for Teletype {
         }
     }
 
-    #[cfg(feature = "board_k210")]
-    fn r_ready(&self) -> bool {
-        let mut inner = self.inner.lock();
-        // in this case, user program call pselect() before, should return true
-        if inner.last_char == 0 {
-            true
-        // in this case, user program call read() before, should return false
-        } else {
-            inner.last_char = 0;
-            false
-        }
-    }
-
-    #[cfg(not(any(feature = "board_k210")))]
     fn r_ready(&self) -> bool {
-        let mut inner = self.inner.lock();
-        // buffer has valid data
-        if inner.last_char != 255 {
-            true
-        // peek next char
-        } else {
-            inner.last_char = console_getchar() as u8;
-            inner.last_char != 255
-        }
+        self.with_input(|ldisc| ldisc.readable())
     }
 
     fn w_ready(&self) -> bool {
         true
     }
 
-    #[cfg(feature = "board_k210")]
+    /// 按行规程读出控制台的输入；没有输入时睡眠等待
     fn read_user(&self, offset: Option<usize>, mut buf: UserBuffer) -> usize {
         if offset.is_some() {
             return ESPIPE as usize;
         }
-        let mut inner = self.inner.lock();
-        // block read here, infallible
-        unsafe {
-            buf.buffers[0]
-                .as_mut_ptr()
-                .write_volatile(console_getchar() as u8);
-        }
-        if inner.termios.lflag & LocalModes::ECHO.bits() != 0 {
-            if inner.last_char == '\r' as u8 {
-                print!("\n");
-            } else {
-                print!("{}", inner.last_char as char);
-            }
-        }
-        // fake failed reading to make pseudo non-block reading,
-        // in order to return properly in r_ready(),
-        // so that we could let bash echo what we input on k210.
-        inner.last_char = 255;
-        1
-    }
-
-#[cfg(not(any(feature = "board_k210")))]
-    fn read_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
-        if offset.is_some() {
-            return ESPIPE as usize;
+        if let Err(errno) = self
+            .inner
+            .lock()
+            .ldisc
+            .settings
+            .check_background(Signals::SIGTTIN)
+        {
+            return errno as usize;
         }
-        
-        let mut count = 0;
-        for ptr in buf {
-            let mut c: u8;
-            loop {
-                // 1. 获取锁，检查是否有字符
-                let mut inner = self.inner.lock();
-                
-                // 如果 last_char 是 255，尝试从硬件读取一次
-                if inner.last_char == 255 {
-                    inner.last_char = console_getchar() as u8;
-                }
-
-                // 检查是否读取到了有效字符
-                if inner.last_char != 255 {
-                    c = inner.last_char;
-                    // 读取后，暂时将 last_char 置无效（消费掉）
-                    // 注意：原逻辑是在循环末尾再次预读取，这里简化逻辑确保一致性
-                    inner.last_char = 255; 
-                    drop(inner); // 拿到字符后立即释放锁
-                    break;
-                }
-
-                // 2. 如果没有字符，释放锁并挂起
-                drop(inner); // <--- 关键：挂起前必须释放锁！
-                crate::task::suspend_current_and_run_next(); // <--- 关键：加上括号！
-            }
-
-            // 3. 将字符写入用户缓冲区
-            unsafe {
-                ptr.write_volatile(c);
-            }
-
-            // 4. 处理回显 (Echo)
-            // 重新获取锁来检查 ECHO 标志
-            let echo = {
-                let inner = self.inner.lock();
-                inner.termios.lflag & LocalModes::ECHO.bits() != 0
-            };
-
-            if echo {
-                // 在没有持有锁的情况下打印，防止死锁
-                if c == b'\r' {
-                    print!("\n");
-                } else {
-                    print!("{}", c as char);
-                }
+        let mut data = vec![0u8; buf.len().min(LINE_BUF_SIZE)];
+        loop {
+            if let Some(read_size) = self.with_input(|ldisc| ldisc.read(&mut data)) {
+                return buf.write(&data[..read_size]);
             }
-
-            // 5. 预读取下一个字符（保持原逻辑的预读取行为）
-            // 虽然这步不是严格必要，但为了保持和原逻辑行为接近
-            let next = console_getchar() as u8;
-            if next != 255 {
-                let mut inner = self.inner.lock();
-                inner.last_char = next;
+            if signal_pending() {
+                return ERESTART as usize;
             }
-
-            count += 1;
+            wait_input();
         }
-        count
     }
-    
+
     fn write_user(&self, offset: Option<usize>, user_buffer: UserBuffer) -> usize {
         if offset.is_some() {
             return ESPIPE as usize;
         }
-        let _inner = self.inner.lock();
+        let inner = self.inner.lock();
+        if inner.ldisc.settings.lflag().contains(LocalModes::TOSTOP) {
+            if let Err(errno) = inner.ldisc.settings.check_background(Signals::SIGTTOU) {
+                return errno as usize;
+            }
+        }
         for buffer in user_buffer.buffers.iter() {
             match core::str::from_utf8(*buffer) {
                 Ok(content) => print!("{}", content),
@@ -270,6 +761,7 @@ impl File for Teletype {
     }
 
     fn open(&self, flags: crate::fs::layout::OpenFlags, special_use: bool) -> Arc<dyn File> {
+        self.inner.lock().ldisc.settings.open_as_ctty(flags);
         TTY.clone()
     }
 
@@ -336,56 +828,7 @@ impl File for Teletype {
             TeletypeCommand::from_primitive(cmd),
             argp
         );
-        let mut inner = self.inner.lock();
-        let token = crate::task::current_user_token();
-        match TeletypeCommand::from_primitive(cmd) {
-            TeletypeCommand::TCGETS | TeletypeCommand::TCGETA => {
-                match copy_to_user(token, &inner.termios, argp as *mut Termios) {
-                    Ok(()) => SUCCESS,
-                    Err(errno) => errno,
-                }
-            }
-            TeletypeCommand::TCSETS
-            | TeletypeCommand::TCSETSW
-            | TeletypeCommand::TCSETSF
-            | TeletypeCommand::TCSETA
-            | TeletypeCommand::TCSETAW
-            | TeletypeCommand::TCSETAF => match copy_from_user(
-                token,
-                argp as *const Termios,
-                &mut inner.termios,
-            ) {
-                Ok(()) => SUCCESS,
-                Err(errno) => errno,
-            },
-            TeletypeCommand::TIOCGPGRP => match translated_refmut(token, argp as *mut u32) {
-                Ok(word) => {
-                    *word = inner.foreground_pgid;
-                    SUCCESS
-                }
-                Err(errno) => errno,
-            },
-            TeletypeCommand::TIOCSPGRP => match translated_ref(token, argp as *const u32) {
-                Ok(word) => {
-                    inner.foreground_pgid = *word;
-                    SUCCESS
-                }
-                Err(errno) => errno,
-            },
-            TeletypeCommand::TIOCGWINSZ => {
-                match copy_to_user(token, &inner.winsize, argp as *mut WinSize) {
-                    Ok(()) => SUCCESS,
-                    Err(errno) => errno,
-                }
-            }
-            TeletypeCommand::TIOCSWINSZ => {
-                match copy_from_user(token, argp as *const WinSize, &mut inner.winsize) {
-                    Ok(()) => SUCCESS,
-                    Err(errno) => errno,
-                }
-            }
-            _ => ENOTTY,
-        }
+        self.inner.lock().ldisc.ioctl(cmd, argp)
     }
 
     fn fcntl(&self, cmd: u32, arg: u32) -> isize {
@@ -417,6 +860,8 @@ pub enum TeletypeCommand {
     /// Sets the serial port settings after flushing the input and output buffers.
     TCSETAF = 0x5408,
 
+    /// Make this terminal the controlling terminal of the calling session leader.
+    TIOCSCTTY = 0x540E,
     /// Get the process group ID of the foreground process group on this terminal.
     TIOCGPGRP = 0x540F,
     /// Set the foreground process group ID of this terminal.
@@ -426,6 +871,16 @@ pub enum TeletypeCommand {
     TIOCGWINSZ = 0x5413,
     /// Set window size.
     TIOCSWINSZ = 0x5414,
+    /// Give up this controlling terminal.
+    TIOCNOTTY = 0x5422,
+    /// Get the session ID of the session this terminal controls.
+    TIOCGSID = 0x5429,
+
+    // For pseudo-terminal masters
+    /// Get the index of the slave, the N in /dev/pts/N.
+    TIOCGPTN = 0x80045430,
+    /// Lock (nonzero argument) or unlock the slave; a locked slave cannot be opened.
+    TIOCSPTLCK = 0x40045431,
 
     /// Non-cloexec
     FIONCLEX = 0x5450,
@@ -500,6 +955,34 @@ impl Default for Termios {
     }
 }
 
+/// Index of the interrupt character in `Termios::cc`.
+pub const VINTR: usize = 0;
+/// Index of the quit character.
+pub const VQUIT: usize = 1;
+/// Index of the erase character.
+pub const VERASE: usize = 2;
+/// Index of the kill (erase line) character.
+pub const VKILL: usize = 3;
+/// Index of the end-of-file character.
+pub const VEOF: usize = 4;
+/// Index of the suspend character.
+pub const VSUSP: usize = 10;
+
+bitflags! {
+    pub struct InputModes : u32 {
+        const INLCR = 0o000100;
+        const IGNCR = 0o000200;
+        const ICRNL = 0o000400;
+    }
+}
+
+bitflags! {
+    pub struct OutputModes : u32 {
+        const OPOST = 0o000001;
+        const ONLCR = 0o000004;
+    }
+}
+
 bitflags! {
     pub struct LocalModes : u32 {
         const ISIG = 0o000001;
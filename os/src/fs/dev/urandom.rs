@@ -5,6 +5,7 @@ use crate::{
     fs::{directory_tree::DirectoryTreeNode, file_trait::File, layout::Stat, StatMode},
     mm::UserBuffer,
     syscall::errno::{ENOTDIR, ESPIPE},
+    utils::random::{fill_random, fill_user},
 };
 
 pub struct Urandom;
@@ -22,9 +23,9 @@ impl File for Urandom {
     fn writable(&self) -> bool {
         true
     }
-    ///todo: 未实现随机，read函数什么都没做，返回0
     fn read(&self, offset: Option<&mut usize>, buf: &mut [u8]) -> usize {
-        0
+        fill_random(buf);
+        buf.len()
     }
 
     fn write(&self, offset: Option<&mut usize>, buf: &[u8]) -> usize {
@@ -58,7 +59,7 @@ impl File for Urandom {
     }
 
     fn read_user(&self, offset: Option<usize>, mut buf: UserBuffer) -> usize {
-        buf.clear();
+        fill_user(&mut buf);
         buf.len()
     }
 
@@ -1,25 +1,37 @@
 use super::inode::DiskInodeType;
+use super::mount::{walk_mnt_ns, MountNamespace};
 use super::vfs::VFS;
 use super::{
     cache::BlockCacheManager,
-    dev::{interrupts::Interrupts, null::Null, tty::Teletype, zero::Zero},
+    dev::{
+        disk::Disk, drop_caches::DropCaches, interrupts::Interrupts, loop_dev::Loop,
+        mapper::Mapper, null::Null, partition::DiskPartition, proc_file::ProcFile, ram::Ram,
+        sysctl::Sysctl, pty::Ptmx, tty::Teletype, zero::Zero,
+    },
+    fat32::EasyFileSystem,
     file_trait::File,
-    filesystem::FileSystem,
-    layout::OpenFlags,
+    filesystem::{pre_mount, FileSystem},
+    layout::{OpenFlags, ResolveFlags},
     Hwclock,
 };
 use crate::fs::dev::urandom::Urandom;
 use crate::fs::fat32::FatOSInode;
-#[cfg(feature = "oom_handler")]
 use crate::mm::tlb_invalidate;
+use crate::utils::telemetry::{export_path, set_export_path, EXPORT_INTERVAL_SECS};
+use crate::task::processor::{gang_sched_enabled, set_gang_sched};
+use crate::task::{cpuset, current_task};
+use crate::syscall::audit;
 use crate::syscall::errno::*;
+use crate::drivers::block::{disk_name, BlockDevice, DISK, LOOP_DEVICES, PARTITIONS, RAM_DISK};
 use crate::{drivers::BLOCK_DEVICE, fs::filesystem::FS_Type};
 use alloc::{
     collections::BTreeMap,
+    format,
     string::{String, ToString},
     sync::{Arc, Weak},
     vec::Vec,
 };
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use lazy_static::*;
 use spin::{Mutex, MutexGuard, RwLock, RwLockWriteGuard};
 
@@ -48,6 +60,8 @@ lazy_static! {
         Mutex::new((Vec::new(), 0));
     static ref PATH_CACHE: Mutex<(String, Weak<DirectoryTreeNode>)> =
         Mutex::new(("".to_string(), Weak::new()));
+    // 伪终端从设备所在的 /dev/pts
+    static ref DEVPTS: Mutex<Weak<DirectoryTreeNode>> = Mutex::new(Weak::new());
 }
 
 // 插入一个节点到 DIRECTORY_VEC 中
@@ -81,6 +95,90 @@ fn update_directory_vec(lock: &mut MutexGuard<(Vec<Weak<DirectoryTreeNode>>, usi
     **lock = (new_vec, 0);
 }
 
+/// 路径长度（含结尾的 '\0'）的上限，/proc/sys/fs/path_max 只能在此之下调整
+pub const PATH_MAX: usize = 4096;
+/// 路径中每一级名字的最大长度
+pub const NAME_MAX: usize = 255;
+/// 当前的路径长度上限，不小于 NAME_MAX + 1，使任何单级名字都能被使用
+static PATH_LIMIT: AtomicUsize = AtomicUsize::new(PATH_MAX);
+
+/// 设置路径长度上限，超出 (NAME_MAX, PATH_MAX] 返回 EINVAL
+fn set_path_limit(value: &str) -> Result<(), isize> {
+    let limit = value.parse::<usize>().map_err(|_| EINVAL)?;
+    if limit <= NAME_MAX || limit > PATH_MAX {
+        return Err(EINVAL);
+    }
+    PATH_LIMIT.store(limit, Ordering::Relaxed);
+    Ok(())
+}
+
+/// 有任务调用过 chroot 之后，路径解析才需要查看任务的根目录
+static CHROOTED: AtomicBool = AtomicBool::new(false);
+
+/// 路径解析的边界：chroot 设置的根目录，以及有绑定挂载或 pivot_root 之后的挂载命名空间
+struct WalkRoot {
+    chroot: Option<Arc<DirectoryTreeNode>>,
+    ns: Option<Arc<MountNamespace>>,
+}
+
+impl WalkRoot {
+    /// 当前任务的边界；从未有过 chroot、绑定挂载与 pivot_root 时两者都为 None，不必加锁
+    fn current() -> Self {
+        let chroot = if CHROOTED.load(Ordering::Acquire) {
+            current_task()
+                .and_then(|task| task.fs.lock().root.clone())
+                .and_then(|root| root.file.get_dirtree_node())
+        } else {
+            None
+        };
+        Self {
+            chroot,
+            ns: walk_mnt_ns(),
+        }
+    }
+
+    /// 所有任务看到的目录树都相同，路径缓存才可用
+    fn is_plain(&self) -> bool {
+        self.chroot.is_none() && self.ns.is_none()
+    }
+
+    /// 绝对路径的起点
+    fn root(&self) -> Arc<DirectoryTreeNode> {
+        match (&self.chroot, &self.ns) {
+            (Some(root), _) => root.clone(),
+            (None, Some(ns)) => ns.root(),
+            (None, None) => ROOT.clone(),
+        }
+    }
+
+    /// `node` 是否是根目录，根目录的 ".." 是它自己
+    fn is_root(&self, node: &DirectoryTreeNode) -> bool {
+        self.chroot
+            .as_ref()
+            .map_or(false, |root| core::ptr::eq(Arc::as_ptr(root), node))
+            || self.ns.as_ref().map_or(false, |ns| ns.is_root(node))
+    }
+
+    /// 进入目录 `inode`：该目录上有绑定挂载，或旧的根目录被 pivot_root 移到了该目录上时，
+    /// 进入绑定的源目录或旧的根目录
+    fn enter(&self, inode: Arc<DirectoryTreeNode>) -> Arc<DirectoryTreeNode> {
+        match &self.ns {
+            Some(ns) => ns.enter(inode),
+            None => inode,
+        }
+    }
+
+    /// 被 pivot_root 移走的旧根目录所在的目录
+    fn covered(&self, node: &DirectoryTreeNode) -> Option<Arc<DirectoryTreeNode>> {
+        self.ns.as_ref().and_then(|ns| ns.covered(node))
+    }
+}
+
+/// chroot 设置任务的根目录之后调用，此后的路径解析都查看任务的根目录
+pub fn enable_chroot() {
+    CHROOTED.store(true, Ordering::Release);
+}
+
 pub struct DirectoryTreeNode {
     /// 如果这是个目录
     /// 1. cwd 当前工作目录
@@ -148,17 +246,30 @@ impl DirectoryTreeNode {
         let mut pathv = Vec::<String>::with_capacity(8);
         // 循环获取父节点，直到根节点为止，并将每一级的节点名称添加到pathv中
         let mut current_inode = self.get_arc();
-        loop {
+        let walk = WalkRoot::current();
+        let at_root = loop {
+            // chroot 或 pivot_root 之后路径从任务的根目录算起，旧的根目录在它被移到的目录之下
+            if walk.is_root(&current_inode) {
+                break true;
+            }
+            if let Some(mount_point) = walk.covered(&current_inode) {
+                current_inode = mount_point;
+                continue;
+            }
             let lock = current_inode.father.lock();
             let par_inode = match lock.upgrade() {
                 Some(inode) => inode.clone(),
-                None => break,
+                None => break false,
             };
             drop(lock);
             pathv.push(current_inode.name.clone());
             current_inode = par_inode;
-        }
-        pathv.push(current_inode.name.clone());
+        };
+        pathv.push(if at_root {
+            String::new()
+        } else {
+            current_inode.name.clone()
+        });
         pathv.reverse();
         if pathv.len() == 1 {
             "/".to_string()
@@ -181,13 +292,21 @@ impl DirectoryTreeNode {
     /// # 参数
     /// + path: 路径
     /// # 返回值
-    /// + 一个 Vec<&str> 类型，存储路径的每一级目录
+    /// + 一个 Vec<&str> 类型，存储路径的每一级目录；
+    ///   路径加上结尾的 '\0' 超过长度上限，或某一级名字长于 NAME_MAX 时返回 ENAMETOOLONG
     /// # 说明
     /// 比如路径是“/lib/a/.././d/c”
     /// 那么存入的内容就是
     /// ["a", "d", "c"]
-    fn parse_dir_path(path: &str) -> Vec<&str> {
-        path.split('/').fold(Vec::with_capacity(8), |mut v, s| {
+    /// 路径逐级循环解析而不递归，长度上限同时限制了解析的步数
+    fn parse_dir_path(path: &str) -> Result<Vec<&str>, isize> {
+        if path.len() >= PATH_LIMIT.load(Ordering::Relaxed) {
+            return Err(ENAMETOOLONG);
+        }
+        if path.split('/').any(|s| s.len() > NAME_MAX) {
+            return Err(ENAMETOOLONG);
+        }
+        Ok(path.split('/').fold(Vec::with_capacity(8), |mut v, s| {
             match s {
                 // 去掉空字符串和当前目录
                 "" | "." => {}
@@ -203,7 +322,7 @@ impl DirectoryTreeNode {
                 }
             }
             v
-        })
+        }))
     }
 
     // 缓存该文件夹下的所有子文件到lock中
@@ -254,14 +373,43 @@ impl DirectoryTreeNode {
 
     // 通过一个动态数组 components 来进入某个目录
     pub fn cd_comp(&self, components: &Vec<&str>) -> Result<Arc<Self>, isize> {
+        self.cd_comp_resolve(components, ResolveFlags::empty())
+    }
+
+    /// 与 `cd_comp` 相同，但在逐级进入时遵守 openat2 的 `resolve` 限制：
+    /// RESOLVE_BENEATH 时越过起点的 ".." 返回 EXDEV，RESOLVE_IN_ROOT 时停在起点，
+    /// 其余限制见 [`Self::check_resolve`]
+    fn cd_comp_resolve(
+        &self,
+        components: &Vec<&str>,
+        resolve: ResolveFlags,
+    ) -> Result<Arc<Self>, isize> {
+        let walk = WalkRoot::current();
         let mut current_inode = self.get_arc();
+        // 当前节点在起点之下的层数
+        let mut depth = 0usize;
         for component in components {
             if *component == ".." {
+                if depth == 0 && resolve.contains(ResolveFlags::RESOLVE_BENEATH) {
+                    return Err(EXDEV);
+                }
+                if depth == 0 && resolve.contains(ResolveFlags::RESOLVE_IN_ROOT) {
+                    continue;
+                }
+                depth = depth.saturating_sub(1);
+                // 根目录的 ".." 是它自己，被移走的旧根目录的 ".." 是它所在目录的父目录
+                if walk.is_root(&current_inode) {
+                    continue;
+                }
+                if let Some(mount_point) = walk.covered(&current_inode) {
+                    current_inode = mount_point;
+                }
                 let lock = current_inode.father.lock();
                 let par_inode = lock.upgrade();
                 match par_inode {
                     Some(par_inode) => {
                         drop(lock);
+                        par_inode.check_resolve(&current_inode, resolve)?;
                         current_inode = par_inode;
                     }
                     None => {}
@@ -271,23 +419,43 @@ impl DirectoryTreeNode {
             let mut lock = current_inode.children.write();
             match current_inode.try_to_open_subfile(component, &mut lock) {
                 Ok(child_inode) => {
-                    let child_inode = child_inode.clone();
                     drop(lock);
+                    let child_inode = walk.enter(child_inode);
+                    child_inode.check_resolve(&current_inode, resolve)?;
                     current_inode = child_inode.clone()
                 }
                 Err(errno) => return Err(errno),
             }
+            depth += 1;
         }
         Ok(current_inode)
     }
+
+    /// 路径解析从 `from` 走到本节点时是否违反 `resolve` 限制：
+    /// RESOLVE_NO_SYMLINKS 时本节点是符号链接返回 ELOOP，
+    /// RESOLVE_NO_XDEV 时两者不在同一个文件系统中返回 EXDEV。
+    /// 目录树中没有 /proc/self/fd 之类的魔术链接，RESOLVE_NO_MAGICLINKS 总能满足
+    fn check_resolve(&self, from: &Self, resolve: ResolveFlags) -> Result<(), isize> {
+        if resolve.contains(ResolveFlags::RESOLVE_NO_SYMLINKS)
+            && self.file.get_file_type() == DiskInodeType::Link
+        {
+            return Err(ELOOP);
+        }
+        if resolve.contains(ResolveFlags::RESOLVE_NO_XDEV)
+            && !Arc::ptr_eq(&self.filesystem, &from.filesystem)
+        {
+            return Err(EXDEV);
+        }
+        Ok(())
+    }
     // 调用 cd_comp 方法，通过一个字符串 path 来进入某个目录
     // 其中 path 会调用 parse_dir_path 方法来解析
     pub fn cd_path(&self, path: &str) -> Result<Arc<Self>, isize> {
-        let components = Self::parse_dir_path(path);
+        let components = Self::parse_dir_path(path)?;
         let inode = if path.starts_with("/") {
-            &**ROOT
+            WalkRoot::current().root()
         } else {
-            &self
+            self.get_arc()
         };
         inode.cd_comp(&components)
     }
@@ -338,6 +506,21 @@ impl DirectoryTreeNode {
         flags: OpenFlags,
         special_use: bool,
     ) -> Result<Arc<dyn File>, isize> {
+        self.open_resolve(path, flags, ResolveFlags::empty(), special_use)
+    }
+
+    /// openat2 的 open：路径解析遵守 `resolve` 限制，见 [`Self::cd_comp_resolve`]。
+    /// RESOLVE_BENEATH 时绝对路径返回 EXDEV，RESOLVE_IN_ROOT 时绝对路径从本节点开始解析
+    pub fn open_resolve(
+        &self,
+        path: &str,
+        flags: OpenFlags,
+        resolve: ResolveFlags,
+        special_use: bool,
+    ) -> Result<Arc<dyn File>, isize> {
+        if path.starts_with('/') && resolve.contains(ResolveFlags::RESOLVE_BENEATH) {
+            return Err(EXDEV);
+        }
         log::debug!("[open]: cwd: {}, path: {}", self.get_cwd(), path);
         // println!("open file in dtn: cwd: {} name: {}",self.get_cwd(), path );
 
@@ -379,16 +562,20 @@ impl DirectoryTreeNode {
         };
 
         // 获取目录树根节点
-        let inode = if path.starts_with("/") {
-            &**ROOT
+        let walk = WalkRoot::current();
+        let inode = if path.starts_with("/") && !resolve.contains(ResolveFlags::RESOLVE_IN_ROOT) {
+            walk.root()
         } else {
-            &self
+            self.get_arc()
         };
+        // 受限的解析需要逐级检查，不使用也不更新路径缓存；
+        // chroot、绑定挂载或 pivot_root 之后各任务看到的目录树可能不同，同一路径可能指向不同节点
+        let cacheable = path.starts_with('/') && resolve.is_empty() && walk.is_plain();
 
         // 获取路径缓存
         let mut path_cache_lock = PATH_CACHE.lock();
         // 如果路径以 '/' 开头，且路径等于缓存路径，且缓存路径的弱引用存在
-        let inode = if path.starts_with('/')
+        let inode = if cacheable
             && path == path_cache_lock.0
             && path_cache_lock.1.upgrade().is_some()
         {
@@ -396,28 +583,35 @@ impl DirectoryTreeNode {
             path_cache_lock.1.upgrade().unwrap()
         } else {
             // 解析路径
-            let mut components = Self::parse_dir_path(path);
+            let mut components = Self::parse_dir_path(path)?;
             // 获取目录栈的栈顶，也就是父目录或者文件本身
             let last_comp = components.pop();
             // 从剩余的路径中获取父目录节点
-            let inode = match inode.cd_comp(&components) {
+            let inode = match inode.cd_comp_resolve(&components, resolve) {
                 Ok(inode) => inode,
                 Err(errno) => return Err(errno),
             };
-            // 若最后一个组件存在，则进行处理
-            if let Some(last_comp) = last_comp {
+            // 若最后一个组件存在，则进行处理；
+            // 它也可能是越过起点的 ".."（".." 只会出现在开头），沿父节点走而不是打开磁盘上的 ".." 目录项，
+            // 这样才会停在根目录并遵守 resolve 限制
+            if last_comp == Some("..") {
+                inode.cd_comp_resolve(&Vec::from([".."]), resolve)?
+            } else if let Some(last_comp) = last_comp {
                 let mut lock = inode.children.write();
                 match inode.try_to_open_subfile(last_comp, &mut lock) {
-                    Ok(inode) => {
+                    Ok(child) => {
                         if flags.contains(OpenFlags::O_CREAT | OpenFlags::O_EXCL) {
                             return Err(EEXIST);
                         }
-                        inode
+                        let child = walk.enter(child);
+                        child.check_resolve(&inode, resolve)?;
+                        child
                     }
                     Err(ENOENT) => {
                         if !flags.contains(OpenFlags::O_CREAT) {
                             return Err(ENOENT);
                         }
+                        inode.check_writable()?;
                         // println!("last_comp:{:?}", last_comp);
                         let new_file = match inode.create(last_comp, DiskInodeType::File) {
                             Ok(file) => file,
@@ -443,6 +637,12 @@ impl DirectoryTreeNode {
             }
         };
 
+        if !inode.file.is_dir()
+            && flags.intersects(OpenFlags::O_WRONLY | OpenFlags::O_RDWR | OpenFlags::O_TRUNC)
+        {
+            inode.check_writable()?;
+        }
+
         if flags.contains(OpenFlags::O_TRUNC) {
             match inode.file.truncate_size(0) {
                 Ok(_) => {}
@@ -467,28 +667,107 @@ impl DirectoryTreeNode {
             return Err(ENOTDIR);
         }
 
+        inode.file.may_open(flags)?;
+
         if special_use {
             *inode.spe_usage.lock() += 1;
         }
 
-        if path.starts_with('/') && path != path_cache_lock.0 {
+        if cacheable && path != path_cache_lock.0 {
             *path_cache_lock = (path.to_string(), Arc::downgrade(&inode.get_arc()));
         }
 
         Ok(inode.file.open(flags, special_use))
     }
 
+    /// 节点所在文件系统的编号
+    pub fn fs_id(&self) -> usize {
+        self.filesystem.fs_id
+    }
+
+    /// 节点所在的文件系统以只读方式挂载时返回 EROFS
+    fn check_writable(&self) -> Result<(), isize> {
+        if self.filesystem.read_only {
+            Err(EROFS)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// 节点是否仍在目录树中（没有被删除）
+    fn is_attached(&self) -> bool {
+        let father = match self.father.lock().upgrade() {
+            Some(father) => father,
+            None => return true,
+        };
+        let children = father.children.read();
+        match children.as_ref().and_then(|map| map.get(&self.name)) {
+            Some(child) => core::ptr::eq(Arc::as_ptr(child), self),
+            None => false,
+        }
+    }
+
+    /// 按文件句柄打开与本节点同一文件系统中的文件
+    /// # 参数
+    /// + ino: 文件句柄中的 inode 号
+    /// + generation: 文件句柄中 inode 的版本号
+    /// + flags: 打开方式
+    /// # 说明
+    /// + 先在目录树已缓存的节点中查找，找不到时交给文件系统按 inode 号从磁盘读出
+    /// + inode 号相同但版本号不同，说明 inode 已被回收给了别的文件，返回 ESTALE
+    pub fn open_by_handle(
+        &self,
+        ino: u64,
+        generation: u32,
+        flags: OpenFlags,
+    ) -> Result<Arc<dyn File>, isize> {
+        // 先取出节点再释放锁，节点析构时也要获取 DIRECTORY_VEC
+        let inodes: Vec<Arc<DirectoryTreeNode>> = DIRECTORY_VEC
+            .lock()
+            .0
+            .iter()
+            .filter_map(|inode| inode.upgrade())
+            .filter(|inode| inode.filesystem.fs_id == self.filesystem.fs_id)
+            .collect();
+        let inode = inodes.into_iter().find(|inode| {
+            inode.is_attached()
+                && matches!(inode.file.file_handle(), Some((node_ino, _)) if node_ino == ino)
+        });
+        let inode = match inode {
+            Some(inode) => inode,
+            None => return self.file.open_by_inode(ino, generation, flags),
+        };
+        if inode.file.file_handle() != Some((ino, generation)) {
+            return Err(ESTALE);
+        }
+        if inode.file.is_file()
+            && *inode.spe_usage.lock() > 0
+            && (flags.contains(OpenFlags::O_WRONLY) || flags.contains(OpenFlags::O_RDWR))
+        {
+            return Err(ETXTBSY);
+        }
+        if inode.file.is_dir()
+            && (flags.contains(OpenFlags::O_WRONLY) || flags.contains(OpenFlags::O_RDWR))
+        {
+            return Err(EISDIR);
+        }
+        if !inode.file.is_dir() && flags.contains(OpenFlags::O_DIRECTORY) {
+            return Err(ENOTDIR);
+        }
+        Ok(inode.file.open(flags, false))
+    }
+
 
 
     // 创建一个文件夹
     pub fn mkdir(&self, path: &str) -> Result<(), isize> {
         let inode = if path.starts_with("/") {
-            &**ROOT
+            WalkRoot::current().root()
         } else {
-            &self
+            self.get_arc()
         };
 
-        let mut components = Self::parse_dir_path(path);
+        let mut components = Self::parse_dir_path(path)?;
         let last_comp = components.pop();
         let inode = match inode.cd_comp(&components) {
             Ok(inode) => inode,
@@ -502,6 +781,7 @@ impl DirectoryTreeNode {
                     return Err(EEXIST);
                 }
                 Err(ENOENT) => {
+                    inode.check_writable()?;
                     let new_file = match inode.create(last_comp, DiskInodeType::Directory) {
                         Ok(file) => file,
                         Err(errno) => return Err(errno),
@@ -528,6 +808,41 @@ impl DirectoryTreeNode {
         Ok(())
     }
 
+    /// 在 `path` 处放入只存在于目录树中的特殊文件 `file`，例如 bind 到路径上的 AF_UNIX 套接字。
+    /// 路径已存在时返回 EEXIST
+    pub fn mknod(&self, path: &str, file: Arc<dyn File>) -> Result<(), isize> {
+        let inode = if path.starts_with("/") {
+            WalkRoot::current().root()
+        } else {
+            self.get_arc()
+        };
+
+        let mut components = Self::parse_dir_path(path)?;
+        let last_comp = match components.pop() {
+            Some(last_comp) => last_comp,
+            None => return Err(EEXIST),
+        };
+        let inode = inode.cd_comp(&components)?;
+
+        let mut lock = inode.children.write();
+        match inode.try_to_open_subfile(last_comp, &mut lock) {
+            Ok(_) => Err(EEXIST),
+            Err(ENOENT) => {
+                inode.check_writable()?;
+                let key = last_comp.to_string();
+                let value = Self::new(
+                    key.clone(),
+                    Arc::new(FileSystem::new(FS_Type::Null)),
+                    file,
+                    Arc::downgrade(&inode.get_arc()),
+                );
+                lock.as_mut().unwrap().insert(key, value);
+                Ok(())
+            }
+            Err(errno) => Err(errno),
+        }
+    }
+
     // 删除一个文件夹或文件
     pub fn delete(&self, path: &str, delete_directory: bool) -> Result<(), isize> {
         if path.split('/').last().map_or(true, |x| x == ".") {
@@ -535,12 +850,12 @@ impl DirectoryTreeNode {
         }
 
         let inode = if path.starts_with("/") {
-            &**ROOT
+            WalkRoot::current().root()
         } else {
-            &self
+            self.get_arc()
         };
 
-        let components = Self::parse_dir_path(path);
+        let components = Self::parse_dir_path(path)?;
         let last_comp = *components.last().unwrap();
         let inode = match inode.cd_comp(&components) {
             Ok(inode) => inode,
@@ -551,6 +866,8 @@ impl DirectoryTreeNode {
             return Err(EBUSY);
         }
 
+        inode.check_writable()?;
+
         if !delete_directory && inode.file.is_dir() {
             return Err(EISDIR);
         }
@@ -584,8 +901,8 @@ impl DirectoryTreeNode {
         assert!(old_path.starts_with('/'));
         assert!(new_path.starts_with('/'));
 
-        let mut old_comps = Self::parse_dir_path(old_path);
-        let mut new_comps = Self::parse_dir_path(new_path);
+        let mut old_comps = Self::parse_dir_path(old_path)?;
+        let mut new_comps = Self::parse_dir_path(new_path)?;
 
         if old_comps == new_comps {
             return Ok(());
@@ -598,11 +915,12 @@ impl DirectoryTreeNode {
         let old_last_comp = old_comps.pop().unwrap();
         let new_last_comp = new_comps.pop().unwrap();
 
-        let old_par_inode = match ROOT.cd_comp(&old_comps) {
+        let root = WalkRoot::current().root();
+        let old_par_inode = match root.cd_comp(&old_comps) {
             Ok(inode) => inode,
             Err(errno) => return Err(errno),
         };
-        let new_par_inode = match ROOT.cd_comp(&new_comps) {
+        let new_par_inode = match root.cd_comp(&new_comps) {
             Ok(inode) => inode,
             Err(errno) => return Err(errno),
         };
@@ -637,6 +955,7 @@ impl DirectoryTreeNode {
         if old_inode.filesystem.fs_id != new_par_inode.filesystem.fs_id {
             return Err(EXDEV);
         }
+        old_inode.check_writable()?;
         let old_key = old_last_comp.to_string();
         let new_key = new_last_comp.to_string();
         match new_par_inode.try_to_open_subfile(new_last_comp, &mut (*new_lock.lock())) {
@@ -716,11 +1035,107 @@ pub fn oom() -> usize {
     }
 }
 
+/// `/proc/sys/vm/drop_caches` 的取值
+pub const DROP_PAGE_CACHE: usize = 1;
+pub const DROP_DENTRIES: usize = 2;
+
+/// 释放所有文件未被引用的页缓存，脏页先写回，返回释放的页数
+pub fn drop_page_caches() -> usize {
+    tlb_invalidate();
+    // 先取出所有节点再释放锁，节点析构时也要获取 DIRECTORY_VEC
+    let inodes: Vec<Arc<DirectoryTreeNode>> = DIRECTORY_VEC
+        .lock()
+        .0
+        .iter()
+        .filter_map(|inode| inode.upgrade())
+        .collect();
+    inodes.iter().map(|inode| inode.file.drop_caches()).sum()
+}
+
+/// 释放未被使用的目录项及其 inode，返回释放的节点数
+///
+/// 只有子节点全部可以从磁盘重新读出且无人引用时，才丢弃一个目录的子节点表。
+/// 被丢弃的 inode 先释放其页缓存（脏页写回），否则析构时数据会丢失。
+/// 打开的文件描述符只引用 inode 而不引用节点，节点被丢弃后 inode 仍留在 inode 缓存中，
+/// 再次查找该路径时得到同一个 inode，不会出现两个大小不同的 inode。
+pub fn drop_dentries() -> usize {
+    ROOT.drop_cached_children()
+}
+
+impl DirectoryTreeNode {
+    /// 自底向上丢弃可以丢弃的子节点表
+    fn drop_cached_children(&self) -> usize {
+        let mut lock = self.children.write();
+        let map = match lock.as_ref() {
+            Some(map) => map,
+            None => return 0,
+        };
+        let mut dropped = 0;
+        let mut droppable = !matches!(self.filesystem.fs_type, FS_Type::Null);
+        for child in map.values() {
+            dropped += child.drop_cached_children();
+            // /dev、/proc 下的虚拟文件无法重新读出；被打开、执行或作为 cwd 的节点不能丢弃
+            droppable &= !matches!(child.filesystem.fs_type, FS_Type::Null)
+                && Arc::strong_count(child) == 1
+                && Arc::strong_count(&child.file) == 1
+                && *child.spe_usage.lock() == 0
+                && child.children.read().is_none();
+        }
+        if droppable {
+            for child in map.values() {
+                child.file.drop_caches();
+            }
+            dropped += map.len();
+            *lock = None;
+        }
+        dropped
+    }
+}
+
+/// 打开块设备 `block_device` 上的 FAT32 文件系统，返回要挂载到 `mount_point` 上的根目录
+/// # 说明
+/// + 根目录与挂载点同名、同父目录，其下文件的路径与根目录的 ".." 都与挂载点的一致
+/// + ext4 的实现只支持根文件系统，不能再挂载
+/// + `read_only` 时在其中创建、写入、删除与重命名文件都返回 EROFS
+/// # 错误
+/// + 设备上不是 FAT32 文件系统时返回 EINVAL，设备只读而 `read_only` 为假时返回 EACCES
+pub fn open_fs_root(
+    block_device: Arc<dyn BlockDevice>,
+    mount_point: &DirectoryTreeNode,
+    read_only: bool,
+) -> Result<Arc<DirectoryTreeNode>, isize> {
+    if block_device.read_only() && !read_only {
+        return Err(EACCES);
+    }
+    match pre_mount(&block_device) {
+        FS_Type::Fat32 => {}
+        _ => return Err(EINVAL),
+    }
+    let vfs: Arc<dyn VFS> =
+        EasyFileSystem::open(block_device, Arc::new(Mutex::new(BlockCacheManager::new())));
+    Ok(DirectoryTreeNode::new(
+        mount_point.name.clone(),
+        Arc::new(if read_only {
+            FileSystem::new_read_only(FS_Type::Fat32)
+        } else {
+            FileSystem::new(FS_Type::Fat32)
+        }),
+        <dyn VFS>::root_osinode(&vfs),
+        mount_point.father.lock().clone(),
+    ))
+}
+
 // 初始化文件系统
 pub fn init_fs() {
+    super::mount::init(match FILE_SYSTEM.get_filesystem_type() {
+        FS_Type::Ext4 => "ext4",
+        FS_Type::Fat32 => "vfat",
+        FS_Type::Null => "none",
+    });
     init_device_directory();
     init_tmp_directory();
     init_proc_directory();
+    init_sys_directory();
 }
 #[allow(unused)]
 // 初始化设备目录
@@ -771,9 +1186,76 @@ fn init_device_directory() {
     let mut lock = dev_inode.children.write();
     lock.as_mut().unwrap().insert("null".to_string(), null_dev);
     lock.as_mut().unwrap().insert("zero".to_string(), zero_dev);
+    lock.as_mut().unwrap().insert("urandom".to_string(), urandom_dev);
     lock.as_mut().unwrap().insert("tty".to_string(), tty_dev);
+    for (index, device) in LOOP_DEVICES.iter().enumerate() {
+        let name = format!("loop{}", index);
+        let loop_dev = DirectoryTreeNode::new(
+            name.clone(),
+            Arc::new(FileSystem::new(FS_Type::Null)),
+            Arc::new(Loop::new(index, device.clone())),
+            Arc::downgrade(&dev_inode.get_arc()),
+        );
+        lock.as_mut().unwrap().insert(name, loop_dev);
+    }
+    let dm_dev = DirectoryTreeNode::new(
+        "dm-0".to_string(),
+        Arc::new(FileSystem::new(FS_Type::Null)),
+        Arc::new(Mapper {}),
+        Arc::downgrade(&dev_inode.get_arc()),
+    );
+    lock.as_mut().unwrap().insert("dm-0".to_string(), dm_dev);
+    // 启动参数 ramdisk_size=0 时没有 /dev/ram0
+    if let Some(ram_disk) = RAM_DISK.as_ref() {
+        let ram_dev = DirectoryTreeNode::new(
+            "ram0".to_string(),
+            Arc::new(FileSystem::new(FS_Type::Null)),
+            Arc::new(Ram::new(ram_disk.clone())),
+            Arc::downgrade(&dev_inode.get_arc()),
+        );
+        lock.as_mut().unwrap().insert("ram0".to_string(), ram_dev);
+    }
+    // 能报告身份信息的物理磁盘（SATA）出现为 /dev/sda
+    if DISK.health().is_some() {
+        let disk_dev = DirectoryTreeNode::new(
+            "sda".to_string(),
+            Arc::new(FileSystem::new(FS_Type::Null)),
+            Arc::new(Disk::new(DISK.clone())),
+            Arc::downgrade(&dev_inode.get_arc()),
+        );
+        lock.as_mut().unwrap().insert("sda".to_string(), disk_dev);
+    }
+    // 磁盘的各个分区出现为 /dev/vda1、/dev/vda2……（SATA 磁盘为 /dev/sda1……）
+    let disk_major = if DISK.health().is_some() {
+        crate::makedev!(8, 0)
+    } else {
+        crate::makedev!(254, 0)
+    };
+    for partition in PARTITIONS.iter() {
+        let name = format!("{}{}", disk_name(), partition.partition().number);
+        let partition_dev = DirectoryTreeNode::new(
+            name.clone(),
+            Arc::new(FileSystem::new(FS_Type::Null)),
+            Arc::new(DiskPartition::new(partition.clone(), disk_major)),
+            Arc::downgrade(&dev_inode.get_arc()),
+        );
+        lock.as_mut().unwrap().insert(name, partition_dev);
+    }
+    let ptmx_dev = DirectoryTreeNode::new(
+        "ptmx".to_string(),
+        Arc::new(FileSystem::new(FS_Type::Null)),
+        Arc::new(Ptmx {}),
+        Arc::downgrade(&dev_inode.get_arc()),
+    );
+    lock.as_mut().unwrap().insert("ptmx".to_string(), ptmx_dev);
     drop(lock);
 
+    dev_inode.mkdir("pts");
+    match dev_inode.cd_path("./pts") {
+        Ok(inode) => *DEVPTS.lock() = Arc::downgrade(&inode),
+        Err(_) => panic!("pts directory doesn't exist"),
+    }
+
     let misc_inode = match dev_inode.cd_path("./misc") {
         Ok(inode) => inode,
         Err(_) => panic!("misc directory doesn't exist"),
@@ -791,6 +1273,34 @@ fn init_device_directory() {
         .insert("rtc".to_string(), hwclock_dev);
     drop(lock);
 }
+/// 把伪终端 `index` 的从设备 `slave` 放到 /dev/pts/`index`
+pub fn add_pts_node(index: usize, slave: Arc<dyn File>) {
+    let pts_inode = match DEVPTS.lock().upgrade() {
+        Some(inode) => inode,
+        None => return,
+    };
+    let name = index.to_string();
+    let pts_dev = DirectoryTreeNode::new(
+        name.clone(),
+        Arc::new(FileSystem::new(FS_Type::Null)),
+        slave,
+        Arc::downgrade(&pts_inode),
+    );
+    let mut lock = pts_inode.children.write();
+    if pts_inode.cache_all_subfile(&mut lock).is_ok() {
+        lock.as_mut().unwrap().insert(name, pts_dev);
+    }
+}
+
+/// 主设备关闭后从 /dev/pts 中去掉伪终端 `index` 的从设备
+pub fn remove_pts_node(index: usize) {
+    if let Some(pts_inode) = DEVPTS.lock().upgrade() {
+        if let Some(map) = pts_inode.children.write().as_mut() {
+            map.remove(&index.to_string());
+        }
+    }
+}
+
 // 初始化临时文件目录
 fn init_tmp_directory() {
     match ROOT.mkdir("/tmp") {
@@ -798,43 +1308,207 @@ fn init_tmp_directory() {
     }
     println!("[kernel] init_tmp_directory successfully!");
 }
+/// 在目录 `parent` 下放入名为 `name` 的虚拟文件 `file`
+fn add_proc_node(parent: &Arc<DirectoryTreeNode>, name: &str, file: Arc<dyn File>) {
+    let node = DirectoryTreeNode::new(
+        name.to_string(),
+        Arc::new(FileSystem::new(FS_Type::Null)),
+        file,
+        Arc::downgrade(parent),
+    );
+    let mut lock = parent.children.write();
+    let _ = parent.cache_all_subfile(&mut lock);
+    lock.as_mut().unwrap().insert(name.to_string(), node);
+}
+
+/// 创建目录 `path` 并返回它，`path` 的父目录须已存在
+fn proc_subdir(path: &str) -> Arc<DirectoryTreeNode> {
+    let _ = ROOT.mkdir(path);
+    match ROOT.cd_path(path) {
+        Ok(inode) => inode,
+        Err(_) => panic!("{} directory doesn't exist", path),
+    }
+}
+
 // 初始化进程目录
 fn init_proc_directory() {
-    match ROOT.mkdir("/proc") {
-        _ => {}
-    }
-    println!("[kernel] init_proc_directory successfully!");
+    let proc_inode = proc_subdir("/proc");
     match ROOT.open("/proc/meminfo", OpenFlags::O_CREAT, false) {
         _ => {}
     }
-    println!("[kernel] init_proc_meminfo_directory successfully!");
-    match ROOT.open("/proc/mounts", OpenFlags::O_CREAT, false) {
-        _ => {}
+
+    // /proc/interrupts 为各中断的计数
+    add_proc_node(&proc_inode, "interrupts", Arc::new(Interrupts::new()));
+    // 添加一些测试数据
+    crate::fs::dev::interrupts::Interrupts::debug_add_test_data();
+    // /proc/crashdump 为上一次启动 panic 时的崩溃转储
+    add_proc_node(
+        &proc_inode,
+        "crashdump",
+        Arc::new(ProcFile::new(|| {
+            crate::crashdump::previous().unwrap_or_default()
+        })),
+    );
+    // /proc/schedstat 为各 CPU 的上下文切换统计
+    add_proc_node(
+        &proc_inode,
+        "schedstat",
+        Arc::new(ProcFile::new(crate::task::processor::format_schedstat)),
+    );
+    // /proc/self/sched 为读取它的任务自己的调度统计
+    add_proc_node(
+        &proc_subdir("/proc/self"),
+        "sched",
+        Arc::new(ProcFile::new(crate::task::processor::format_self_sched)),
+    );
+    // /proc/mounts 为挂载表
+    add_proc_node(
+        &proc_inode,
+        "mounts",
+        Arc::new(ProcFile::new(super::mount::format_mounts)),
+    );
+    // /proc/audit 为审计日志中的系统调用记录
+    add_proc_node(
+        &proc_inode,
+        "audit",
+        Arc::new(ProcFile::new(audit::format_log)),
+    );
+    // /proc/health 读取时运行内核自检并给出总体状态
+    add_proc_node(
+        &proc_inode,
+        "health",
+        Arc::new(ProcFile::new(crate::utils::telemetry::format_health)),
+    );
+    // /proc/sched_debug 为各 CPU 的调度器事件日志
+    #[cfg(feature = "sched_debug")]
+    add_proc_node(
+        &proc_inode,
+        "sched_debug",
+        Arc::new(ProcFile::new(crate::task::sched_debug::format_events)),
+    );
+    // /proc/kmemleak 按调用点列出存活较久的内核堆分配
+    #[cfg(feature = "kmemleak")]
+    add_proc_node(
+        &proc_inode,
+        "kmemleak",
+        Arc::new(ProcFile::new(crate::mm::kmemleak::format_report)),
+    );
+
+    // /proc/sys/vm/drop_caches 写入后释放页缓存与目录项缓存
+    let _ = ROOT.mkdir("/proc/sys");
+    add_proc_node(
+        &proc_subdir("/proc/sys/vm"),
+        "drop_caches",
+        Arc::new(DropCaches),
+    );
+
+    // /proc/sys/fs/path_max 读写路径长度上限
+    let path_max_sysctl = Sysctl::new(
+        || format!("{}\n", PATH_LIMIT.load(Ordering::Relaxed)),
+        set_path_limit,
+    );
+    add_proc_node(
+        &proc_subdir("/proc/sys/fs"),
+        "path_max",
+        Arc::new(path_max_sysctl),
+    );
+
+    // /proc/sys/kernel 下为指标导出线程、组调度与审计的参数
+    let interval_sysctl = Sysctl::new(
+        || format!("{}\n", EXPORT_INTERVAL_SECS.load(Ordering::Relaxed)),
+        |value| {
+            let secs = value.parse::<usize>().map_err(|_| EINVAL)?;
+            EXPORT_INTERVAL_SECS.store(secs, Ordering::Relaxed);
+            Ok(())
+        },
+    );
+    let path_sysctl = Sysctl::new(|| format!("{}\n", export_path()), set_export_path);
+    let gang_sysctl = Sysctl::new(
+        || format!("{}\n", gang_sched_enabled() as usize),
+        |value| {
+            let enabled = match value {
+                "0" => false,
+                "1" => true,
+                _ => return Err(EINVAL),
+            };
+            set_gang_sched(enabled);
+            Ok(())
+        },
+    );
+    let audit_sysctl = Sysctl::new(audit::format_watched, audit::set_watched);
+    let kernel_inode = proc_subdir("/proc/sys/kernel");
+    for (name, sysctl) in [
+        ("metrics_export_interval", interval_sysctl),
+        ("metrics_export_path", path_sysctl),
+        ("sched_gang", gang_sysctl),
+        ("audit_syscalls", audit_sysctl),
+    ] {
+        add_proc_node(&kernel_inode, name, Arc::new(sysctl));
     }
-    println!("[kernel] init_proc_mounts_directory successfully!");
-    
-    // 创建 /proc/interrupts 虚拟文件
-    let proc_inode = match ROOT.cd_path("/proc") {
+    println!("[kernel] init_proc_directory successfully!");
+}
+
+// 初始化 /sys 目录，/sys/fs/cgroup/cpuset.cpus 读写当前任务所在 cpuset 的 CPU 列表，
+// 有物理磁盘时 /sys/block/sda/size 给出它的容量
+fn init_sys_directory() {
+    let _ = ROOT.mkdir("/sys");
+    let _ = ROOT.mkdir("/sys/fs");
+    let _ = ROOT.mkdir("/sys/fs/cgroup");
+    let cgroup_inode = match ROOT.cd_path("/sys/fs/cgroup") {
         Ok(inode) => inode,
-        Err(_) => panic!("proc directory doesn't exist"),
+        Err(_) => panic!("/sys/fs/cgroup directory doesn't exist"),
     };
-    
-    let interrupts_dev = DirectoryTreeNode::new(
-        "interrupts".to_string(),
+    let cpus_sysctl = Sysctl::new(
+        || {
+            let cpuset = current_task().map_or(cpuset::ROOT_CPUSET, |task| {
+                task.acquire_inner_lock().sched_entity.cpuset
+            });
+            format!("{}\n", cpuset::format_cpu_list(cpuset::cpus(cpuset)))
+        },
+        |value| {
+            let cpus = cpuset::parse_cpu_list(value)?;
+            let task = current_task().ok_or(ESRCH)?;
+            let mut inner = task.acquire_inner_lock();
+            inner.sched_entity.cpuset = cpuset::assign(inner.sched_entity.cpuset, cpus)?;
+            Ok(())
+        },
+    );
+    let cpus_dev = DirectoryTreeNode::new(
+        "cpuset.cpus".to_string(),
         Arc::new(FileSystem::new(FS_Type::Null)),
-        Arc::new(Interrupts::new()),
-        Arc::downgrade(&proc_inode.get_arc()),
+        Arc::new(cpus_sysctl),
+        Arc::downgrade(&cgroup_inode.get_arc()),
     );
-    
-    let mut lock = proc_inode.children.write();
-    proc_inode.cache_all_subfile(&mut lock);
+    let mut lock = cgroup_inode.children.write();
+    let _ = cgroup_inode.cache_all_subfile(&mut lock);
     lock.as_mut()
         .unwrap()
-        .insert("interrupts".to_string(), interrupts_dev);
+        .insert("cpuset.cpus".to_string(), cpus_dev);
     drop(lock);
-    
-    // 添加一些测试数据
-    crate::fs::dev::interrupts::Interrupts::debug_add_test_data();
-    
-    println!("[kernel] init_proc_interrupts_directory successfully!");
+    println!("[kernel] init_sys_cgroup successfully!");
+
+    // /sys/block/sda/size：/dev/sda 的容量，以 512 字节扇区计
+    if DISK.health().is_none() {
+        return;
+    }
+    let _ = ROOT.mkdir("/sys/block");
+    let _ = ROOT.mkdir("/sys/block/sda");
+    let sda_inode = match ROOT.cd_path("/sys/block/sda") {
+        Ok(inode) => inode,
+        Err(_) => panic!("/sys/block/sda directory doesn't exist"),
+    };
+    let size_dev = DirectoryTreeNode::new(
+        "size".to_string(),
+        Arc::new(FileSystem::new(FS_Type::Null)),
+        Arc::new(ProcFile::new(|| {
+            let sectors = DISK.health().map_or(0, |health| health.sectors);
+            format!("{}\n", sectors)
+        })),
+        Arc::downgrade(&sda_inode.get_arc()),
+    );
+    let mut lock = sda_inode.children.write();
+    let _ = sda_inode.cache_all_subfile(&mut lock);
+    lock.as_mut().unwrap().insert("size".to_string(), size_dev);
+    drop(lock);
+    println!("[kernel] init_sys_block successfully!");
 }
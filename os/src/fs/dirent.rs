@@ -1,10 +1,17 @@
-use core::mem::size_of;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 
-const NAME_LIMIT: usize = 128;
-#[derive(Clone, Copy, Debug)]
-#[repr(C)]
+/// `d_ino`、`d_off`、`d_reclen` 与 `d_type` 共占的字节数
+const HEADER_LEN: usize = 19;
+/// 每条记录的长度都是 8 的倍数，下一条记录的 `d_ino` 才能对齐
+const RECORD_ALIGN: usize = 8;
+/// 最短的目录项（名字只有一个字节）编码后的长度
+pub const MIN_RECLEN: usize = Dirent::reclen(1);
+
+#[derive(Clone, Debug)]
 /// Native Linux directory entry structure.
-/// In theory, the d_name may NOT have a fixed size and `d_name` may be arbitrarily lone.
+/// The `d_name` has no fixed size: an encoded record is as long as its header, the
+/// null-terminated name and the padding up to 8 bytes, see `encode_into`.
 pub struct Dirent {
     /// Inode 节点号
     pub d_ino: usize,
@@ -14,23 +21,50 @@ pub struct Dirent {
     pub d_reclen: u16,
     /// Type of the file
     pub d_type: u8,
-    /// The Filename (null-terminated)
-    /// # Note
-    /// We use fix-sized d_name array.
-    pub d_name: [u8; NAME_LIMIT],
+    /// The Filename, the null terminator is added when encoding
+    pub d_name: String,
 }
 
 impl Dirent {
     /// Offset to next `linux_dirent`
     pub fn new(d_ino: usize, d_off: isize, d_type: u8, d_name: &str) -> Self {
-        let mut dirent = Self {
+        Self {
             d_ino,
             d_off,
-            d_reclen: size_of::<Self>() as u16,
+            d_reclen: Self::reclen(d_name.len()) as u16,
             d_type,
-            d_name: [0; NAME_LIMIT],
-        };
-        dirent.d_name[0..d_name.len()].copy_from_slice(d_name.as_bytes());
-        dirent
+            d_name: d_name.to_string(),
+        }
+    }
+
+    /// 名字长 `name_len` 字节的目录项编码后的长度
+    pub const fn reclen(name_len: usize) -> usize {
+        (HEADER_LEN + name_len + 1 + RECORD_ALIGN - 1) & !(RECORD_ALIGN - 1)
     }
+
+    /// 按 `struct linux_dirent64` 的布局把目录项追加到 `buf` 末尾，名字后补 0 直到 `d_reclen`
+    pub fn encode_into(&self, buf: &mut Vec<u8>) {
+        let start = buf.len();
+        buf.extend_from_slice(&(self.d_ino as u64).to_ne_bytes());
+        buf.extend_from_slice(&(self.d_off as i64).to_ne_bytes());
+        buf.extend_from_slice(&self.d_reclen.to_ne_bytes());
+        buf.push(self.d_type);
+        buf.extend_from_slice(self.d_name.as_bytes());
+        buf.resize(start + self.d_reclen as usize, 0);
+    }
+}
+
+/// 依次取出 `entries` 中的目录项，直到下一条放不进 `count` 字节的缓冲区
+pub fn take_fitting(entries: impl IntoIterator<Item = Dirent>, count: usize) -> Vec<Dirent> {
+    let mut left = count;
+    entries
+        .into_iter()
+        .take_while(|dirent| match left.checked_sub(dirent.d_reclen as usize) {
+            Some(rest) => {
+                left = rest;
+                true
+            }
+            None => false,
+        })
+        .collect()
 }
@@ -1,14 +1,10 @@
 use core::cmp::min;
 
-use alloc::vec::Vec;
-use alloc::vec;
-
-use crate::fs::directory_tree::GLOBAL_BLOCK_SIZE;
-use crate::fs::ext4::bitmap::{ext4_bmap_bit_find_clr, ext4_bmap_bit_set, ext4_bmap_is_bit_clr};
-use crate::fs::ext4::block_group::{Block, Ext4BlockGroup};
-use crate::fs::ext4::error::Errno;
-
-use super::bitmap::ext4_bmap_bits_free;
+use crate::fs::ext4::bitmap::{
+    ext4_bmap_bit_clr, ext4_bmap_bit_find_clr, ext4_bmap_bit_set, ext4_bmap_is_bit_set,
+};
+use crate::fs::ext4::block_group::{Block, Ext4BlockGroup, EXT4_BG_BLOCK_UNINIT};
+use crate::syscall::errno::{EIO, ENOSPC};
 
 use super::ext4fs::Ext4FileSystem;
 use super::*;
@@ -78,11 +74,22 @@ impl Ext4FileSystem {
         (self.superblock.blocks_per_group() as u64 * bgid as u64) + index as u64
     }
 
-    /// Allocate a new block.
+    /// Number of blocks in a block group, the last group may be shorter.
+    fn blocks_in_group(&self, bgid: u32) -> u32 {
+        let first = self.get_block_of_bgid(bgid);
+        let total = self.superblock.blocks_count() as u64;
+        min(
+            self.superblock.blocks_per_group() as u64,
+            total.saturating_sub(first),
+        ) as u32
+    }
+
+    /// Allocate a new block for an inode.
     ///
     /// Params:
-    /// `inode_ref` - Reference to the inode.
-    /// `goal` - Absolute address of the block.
+    /// `inode_ref` - Reference to the inode, its block count is increased but
+    /// it is not written back.
+    /// `goal` - Preferred absolute address of the block.
     ///
     /// Returns:
     /// `Result<Ext4Fsblk>` - The physical block number allocated.
@@ -91,319 +98,146 @@ impl Ext4FileSystem {
         inode_ref: &mut Ext4InodeRef,
         goal: Option<Ext4Fsblk>,
     ) -> Result<Ext4Fsblk, isize> {
-        let mut alloc: Ext4Fsblk = 0;
-        let super_block = &self.superblock;
-        let blocks_per_group = super_block.blocks_per_group();
-        let mut bgid;
-        let mut idx_in_bg;
-
-        if let Some(goal) = goal {
-            bgid = self.get_bgid_of_block(goal);
-            idx_in_bg = self.addr_to_idx_bg(goal);
-        } else {
-            bgid = 0;
-            idx_in_bg = 0;
-        }
-
-        let block_group_count = super_block.block_group_count();
-        let mut count = block_group_count;
-
-        while count > 0 {
-            // Load block group reference
-            let mut block_group =
-                Ext4BlockGroup::load_new(self.block_device.clone(), super_block, bgid as usize);
-
-            let free_blocks = block_group.get_free_blocks_count();
-            if free_blocks == 0 {
-                // Try next block group
-                bgid = (bgid + 1) % block_group_count;
-                count -= 1;
-
-                if count == 0 {
-                    println!("[balloc] No free blocks available in all block groups");
-                    return Err(Errno::ENOSPC as isize);
-                }
-                continue;
-            }
-
-            // Compute indexes
-            let first_in_bg = self.get_block_of_bgid(bgid);
-            let first_in_bg_index = self.addr_to_idx_bg(first_in_bg);
-
-            if idx_in_bg < first_in_bg_index {
-                idx_in_bg = first_in_bg_index;
-            }
-
-            // Load block with bitmap
-            let bmp_blk_adr = block_group.get_block_bitmap_block(super_block);
-            let mut bitmap_block =
-                Block::load_offset(self.block_device.clone(), bmp_blk_adr as usize * self.block_size);
-
-            // Check if goal is free
-            if ext4_bmap_is_bit_clr(&bitmap_block.data, idx_in_bg) {
-                ext4_bmap_bit_set(&mut bitmap_block.data, idx_in_bg);
-                block_group.set_block_group_balloc_bitmap_csum(super_block, &bitmap_block.data);
-                // 此处不需要考虑对齐
-                self.block_device
-                    .write_block(bmp_blk_adr as usize, &bitmap_block.data);
-                alloc = self.bg_idx_to_addr(idx_in_bg, bgid);
-
-                /* Update free block counts */
-                self.update_free_block_counts(inode_ref, &mut block_group, bgid as usize)?;
-                return Ok(alloc);
-            }
-
-            // Try to find free block near to goal
-            let blk_in_bg = blocks_per_group;
-            let end_idx = min((idx_in_bg + 63) & !63, blk_in_bg);
-
-            for tmp_idx in (idx_in_bg + 1)..end_idx {
-                if ext4_bmap_is_bit_clr(&bitmap_block.data, tmp_idx) {
-                    ext4_bmap_bit_set(&mut bitmap_block.data, tmp_idx);
-                    block_group
-                        .set_block_group_balloc_bitmap_csum(super_block, &bitmap_block.data);
-                    // 此处不需要考虑对齐
-                    self.block_device
-                        .write_block(bmp_blk_adr as usize, &bitmap_block.data);
-                    alloc = self.bg_idx_to_addr(tmp_idx, bgid);
-                    self.update_free_block_counts(inode_ref, &mut block_group, bgid as usize)?;
-                    return Ok(alloc);
-                }
-            }
-
-            // Find free bit in bitmap
-            let mut rel_blk_idx = 0;
-            if ext4_bmap_bit_find_clr(&bitmap_block.data, idx_in_bg, blk_in_bg, &mut rel_blk_idx) {
-                ext4_bmap_bit_set(&mut bitmap_block.data, rel_blk_idx);
-                block_group.set_block_group_balloc_bitmap_csum(super_block, &bitmap_block.data);
-                // 此处不需要考虑对齐
-                self.block_device
-                    .write_block(bmp_blk_adr as usize, &bitmap_block.data);
-                alloc = self.bg_idx_to_addr(rel_blk_idx, bgid);
-                self.update_free_block_counts(inode_ref, &mut block_group, bgid as usize)?;
-                return Ok(alloc);
-            }
-
-            // No free block found in this group, try other block groups
-            bgid = (bgid + 1) % block_group_count;
-            count -= 1;
-        }
-
-        println!("No free blocks available in all block groups");
-        return Err(Errno::ENOSPC as isize);
+        let block = self.balloc_alloc(goal)?;
+        let blocks = inode_ref.inode.blocks_count() + (self.block_size / 512) as u64;
+        inode_ref.inode.set_blocks_count(blocks);
+        Ok(block)
     }
 
-    /// Allocate a new block start from a specific bgid
+    /// Allocate a block not owned by any inode.
     ///
-    /// Params:
-    /// `inode_ref` - Reference to the inode.
-    /// `start_bgid` - Start bgid of free block search
+    /// The first free block at or after `goal` is taken, wrapping around
+    /// through all block groups. Groups whose block bitmap is not
+    /// initialized are skipped.
     ///
     /// Returns:
-    /// `Result<Ext4Fsblk>` - The physical block number allocated.
-    pub fn balloc_alloc_block_from(
-        &self,
-        inode_ref: &mut Ext4InodeRef,
-        start_bgid: &mut u32,
-    ) -> Result<Ext4Fsblk, isize> {
-        let mut alloc: Ext4Fsblk = 0;
+    /// `Result<Ext4Fsblk>` - The physical block number allocated, `ENOSPC`
+    /// if the file system is full.
+    pub fn balloc_alloc(&self, goal: Option<Ext4Fsblk>) -> Result<Ext4Fsblk, isize> {
+        let _guard = self.alloc_lock.lock();
         let super_block = &self.superblock;
-        let blocks_per_group = super_block.blocks_per_group();
-
         let block_group_count = super_block.block_group_count();
-
-        // 修正起始块组，防止越界
-        let mut bgid = if *start_bgid >= block_group_count { 0 } else { *start_bgid };
-        let mut idx_in_bg = 0;
-
-        let mut count = block_group_count;
-
-        while count > 0 {
-            // Load block group reference
+        let first_data_block = super_block.first_data_block() as u64;
+        let goal = goal
+            .filter(|&goal| goal >= first_data_block && goal < super_block.blocks_count() as u64)
+            .unwrap_or(first_data_block);
+        let goal_bgid = self.get_bgid_of_block(goal);
+
+        for i in 0..block_group_count {
+            let bgid = (goal_bgid + i) % block_group_count;
             let mut block_group =
                 Ext4BlockGroup::load_new(self.block_device.clone(), super_block, bgid as usize);
-
-            let free_blocks = block_group.get_free_blocks_count();
-            if free_blocks == 0 {
-                // Try next block group
-                bgid = (bgid + 1) % block_group_count;
-                count -= 1;
-
-                if count == 0 {
-                    println!("No free blocks available in all block groups");
-                    return Err(Errno::ENOSPC as isize);
-                }
+            let free_blocks = block_group.get_free_blocks_count(super_block);
+            if free_blocks == 0 || block_group.flags & EXT4_BG_BLOCK_UNINIT != 0 {
                 continue;
             }
 
-            // Compute indexes
-            let first_in_bg = self.get_block_of_bgid(bgid);
-            let first_in_bg_index = self.addr_to_idx_bg(first_in_bg);
-
-            if idx_in_bg < first_in_bg_index {
-                idx_in_bg = first_in_bg_index;
-            }
-
             // Load block with bitmap
             let bmp_blk_adr = block_group.get_block_bitmap_block(super_block);
             let mut bitmap_block =
                 Block::load_offset(self.block_device.clone(), bmp_blk_adr as usize * self.block_size);
 
-            // Check if goal is free
-            if ext4_bmap_is_bit_clr(&bitmap_block.data, idx_in_bg) {
-                ext4_bmap_bit_set(&mut bitmap_block.data, idx_in_bg);
-                block_group.set_block_group_balloc_bitmap_csum(super_block, &bitmap_block.data);
-                // 此处不需要考虑对齐
-                self.block_device
-                    .write_block(bmp_blk_adr as usize, &bitmap_block.data);
-                alloc = self.bg_idx_to_addr(idx_in_bg, bgid);
-
-                /* Update free block counts */
-                self.update_free_block_counts(inode_ref, &mut block_group, bgid as usize)?;
-
-                *start_bgid = bgid;
-                return Ok(alloc);
-            }
-
-            // Try to find free block near to goal
-            let blk_in_bg = blocks_per_group;
-            let end_idx = min((idx_in_bg + 63) & !63, blk_in_bg);
-
-            for tmp_idx in (idx_in_bg + 1)..end_idx {
-                if ext4_bmap_is_bit_clr(&bitmap_block.data, tmp_idx) {
-                    ext4_bmap_bit_set(&mut bitmap_block.data, tmp_idx);
-                    block_group
-                        .set_block_group_balloc_bitmap_csum(super_block, &bitmap_block.data);
-                    // 此处不需要考虑对齐
-                    self.block_device
-                        .write_block(bmp_blk_adr as usize, &bitmap_block.data);
-                    alloc = self.bg_idx_to_addr(tmp_idx, bgid);
-                    self.update_free_block_counts(inode_ref, &mut block_group, bgid as usize)?;
-
-                    *start_bgid = bgid;
-                    return Ok(alloc);
-                }
+            // Search from the goal to the end of the group, then from the start
+            let blk_in_bg = self.blocks_in_group(bgid);
+            let start = if i == 0 { self.addr_to_idx_bg(goal) } else { 0 };
+            let mut idx_in_bg = 0;
+            if !ext4_bmap_bit_find_clr(&bitmap_block.data, start, blk_in_bg, &mut idx_in_bg)
+                && !ext4_bmap_bit_find_clr(&bitmap_block.data, 0, start, &mut idx_in_bg)
+            {
+                // The counter disagrees with the bitmap, try other block groups
+                continue;
             }
 
-            // Find free bit in bitmap
-            let mut rel_blk_idx = 0;
-            if ext4_bmap_bit_find_clr(&bitmap_block.data, idx_in_bg, blk_in_bg, &mut rel_blk_idx) {
-                ext4_bmap_bit_set(&mut bitmap_block.data, rel_blk_idx);
-                block_group.set_block_group_balloc_bitmap_csum(super_block, &bitmap_block.data);
-                // 此处不需要考虑对齐
-                self.block_device
-                    .write_block(bmp_blk_adr as usize, &bitmap_block.data);
-                alloc = self.bg_idx_to_addr(rel_blk_idx, bgid);
-                self.update_free_block_counts(inode_ref, &mut block_group, bgid as usize)?;
+            ext4_bmap_bit_set(&mut bitmap_block.data, idx_in_bg);
+            block_group.set_block_group_balloc_bitmap_csum(super_block, &bitmap_block.data);
+            self.block_device
+                .write_block(bmp_blk_adr as usize, &bitmap_block.data);
 
-                *start_bgid = bgid;
-                return Ok(alloc);
-            }
+            block_group.set_free_blocks_count(super_block, free_blocks - 1);
+            block_group.sync_to_disk_with_csum(
+                self.block_device.clone(),
+                bgid as usize,
+                super_block,
+            );
+            self.update_superblock_counts(-1, 0);
 
-            // No free block found in this group, try other block groups
-            bgid = (bgid + 1) % block_group_count;
-            count -= 1;
+            return Ok(self.bg_idx_to_addr(idx_in_bg, bgid));
         }
-        println!("No free blocks available in all block groups");
-        return Err(Errno::ENOSPC as isize);
+
+        println!("[balloc] No free blocks available in all block groups");
+        Err(ENOSPC)
     }
 
-    fn update_free_block_counts(
+    /// Free blocks owned by an inode.
+    ///
+    /// Params:
+    /// `inode_ref` - Reference to the inode, its block count is decreased but
+    /// it is not written back.
+    /// `start` - First absolute block address to free.
+    /// `count` - Number of blocks.
+    pub fn balloc_free_blocks(
         &self,
         inode_ref: &mut Ext4InodeRef,
-        block_group: &mut Ext4BlockGroup,
-        bgid: usize,
+        start: Ext4Fsblk,
+        count: u32,
     ) -> Result<(), isize> {
-        let mut super_block = self.superblock;
-
-        // 更新超级块的空闲块数
-        let mut super_blk_free_blocks = super_block.free_blocks_count();
-        super_blk_free_blocks -= 1;
-        super_block.set_free_blocks_count(super_blk_free_blocks);
-        super_block.sync_to_disk_with_csum(self.block_device.clone());
-
-        // Update inode blocks (different block size!) count
-        let mut inode_blocks = inode_ref.inode.blocks_count();
-        // inode_blocks += block_size / EXT4_INODE_BLOCK_SIZE as u64;
-        inode_blocks += (self.block_size / self.superblock.inode_size as usize) as u64;
-        inode_ref.inode.set_blocks_count(inode_blocks);
-        self.write_back_inode(inode_ref);
-
-        // Update block group free blocks count
-        let mut fb_cnt = block_group.get_free_blocks_count();
-        fb_cnt -= 1;
-        block_group.set_free_blocks_count(fb_cnt as u32);
-        block_group.sync_to_disk_with_csum(self.block_device.clone(), bgid, &super_block);
-
+        self.balloc_free(start, count)?;
+        let freed = count as u64 * (self.block_size / 512) as u64;
+        let blocks = inode_ref.inode.blocks_count().saturating_sub(freed);
+        inode_ref.inode.set_blocks_count(blocks);
         Ok(())
     }
 
-    #[allow(unused)]
-    pub fn balloc_free_blocks(&self, inode_ref: &mut Ext4InodeRef, start: Ext4Fsblk, count: u32) {
-        // log::trace!("balloc_free_blocks start {:x?} count {:x?}", start, count);
-        let mut count = count as usize;
-        let mut start = start;
-
-        let mut super_block = self.superblock;
-
-        let blocks_per_group = super_block.blocks_per_group();
-
-        let bgid = start / blocks_per_group as u64;
-
-        let mut bg_first = start / blocks_per_group as u64;
-        let mut bg_last = (start + count as u64 - 1) / blocks_per_group as u64;
+    /// Free blocks not owned by any inode.
+    ///
+    /// The range may span several block groups. Ranges reaching outside the
+    /// data blocks of the file system are refused with `EIO`.
+    pub fn balloc_free(&self, start: Ext4Fsblk, count: u32) -> Result<(), isize> {
+        let _guard = self.alloc_lock.lock();
+        let super_block = &self.superblock;
+        let end = start + count as u64;
+        if start < super_block.first_data_block() as u64 || end > super_block.blocks_count() as u64
+        {
+            println!(
+                "[balloc] freeing blocks {:#x}..{:#x} out of range",
+                start, end
+            );
+            return Err(EIO);
+        }
 
-        while bg_first <= bg_last {
-            let idx_in_bg = start % blocks_per_group as u64;
+        let mut start = start;
+        while start < end {
+            let bgid = self.get_bgid_of_block(start);
+            let idx_in_bg = self.addr_to_idx_bg(start);
+            let len = min(end - start, (self.blocks_in_group(bgid) - idx_in_bg) as u64) as u32;
 
             let mut bg =
-                Ext4BlockGroup::load_new(self.block_device.clone(), &super_block, bgid as usize);
-
-            let block_bitmap_block = bg.get_block_bitmap_block(&super_block);
-            let mut raw_data = [0u8; BLOCK_SIZE];
-            self.block_device
-                .read_block(block_bitmap_block as usize, &mut raw_data);
-            let mut data: &mut Vec<u8> = &mut raw_data.to_vec();
-
-            let mut free_cnt = self.block_size * 8 - idx_in_bg as usize;
-
-            if count > free_cnt {
-            } else {
-                free_cnt = count;
+                Ext4BlockGroup::load_new(self.block_device.clone(), super_block, bgid as usize);
+            let block_bitmap_block = bg.get_block_bitmap_block(super_block);
+            let mut bitmap_block = Block::load_offset(
+                self.block_device.clone(),
+                block_bitmap_block as usize * self.block_size,
+            );
+
+            // Only count the blocks that were really in use
+            let mut free_cnt = 0;
+            for idx in idx_in_bg..idx_in_bg + len {
+                if ext4_bmap_is_bit_set(&bitmap_block.data, idx) {
+                    ext4_bmap_bit_clr(&mut bitmap_block.data, idx);
+                    free_cnt += 1;
+                }
             }
-
-            ext4_bmap_bits_free(data, idx_in_bg as u32, free_cnt as u32);
-
-            count -= free_cnt;
-            start += free_cnt as u64;
-
-            bg.set_block_group_balloc_bitmap_csum(&super_block, data);
-            // 此处不需要考虑对齐
+            bg.set_block_group_balloc_bitmap_csum(super_block, &bitmap_block.data);
             self.block_device
-                .write_block(block_bitmap_block as usize, data);
-
-            /* Update superblock free blocks count */
-            let mut super_blk_free_blocks = super_block.free_blocks_count();
-
-            super_blk_free_blocks += free_cnt as u64;
-            super_block.set_free_blocks_count(super_blk_free_blocks);
-            super_block.sync_to_disk_with_csum(self.block_device.clone());
+                .write_block(block_bitmap_block as usize, &bitmap_block.data);
 
-            /* Update inode blocks (different block size!) count */
-            let mut inode_blocks = inode_ref.inode.blocks_count();
-            // let ext4_inode_block_size = self.superblock.inode_size() as usize;
-            // inode_blocks -= (free_cnt * (self.block_size / EXT4_INODE_BLOCK_SIZE)) as u64;
-            inode_blocks -= (free_cnt * (self.block_size / self.superblock.inode_size as usize)) as u64;
-            inode_ref.inode.set_blocks_count(inode_blocks);
-            self.write_back_inode(inode_ref);
+            let fb_cnt = bg.get_free_blocks_count(super_block) + free_cnt;
+            bg.set_free_blocks_count(super_block, fb_cnt);
+            bg.sync_to_disk_with_csum(self.block_device.clone(), bgid as usize, super_block);
+            self.update_superblock_counts(free_cnt as i64, 0);
 
-            /* Update block group free blocks count */
-            let mut fb_cnt = bg.get_free_blocks_count();
-            fb_cnt += free_cnt as u64;
-            bg.set_free_blocks_count(fb_cnt as u32);
-            bg.sync_to_disk_with_csum(self.block_device.clone(), bgid as usize, &super_block);
-
-            bg_first += 1;
+            start += len as u64;
         }
+        Ok(())
     }
 }
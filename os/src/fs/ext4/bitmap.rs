@@ -32,6 +32,9 @@ pub fn ext4_bmap_bit_clr(bmap: &mut [u8], bit: u32) {
 /// 参数 ebit: 结束位索引
 /// 参数 bit_id: 用于存储空闲位的索引
 pub fn ext4_bmap_bit_find_clr(bmap: &[u8], sbit: u32, ebit: u32, bit_id: &mut u32) -> bool {
+    if ebit <= sbit {
+        return false;
+    }
     let mut i: u32;
     let mut bcnt = ebit - sbit;
 
@@ -43,7 +46,7 @@ pub fn ext4_bmap_bit_find_clr(bmap: &[u8], sbit: u32, ebit: u32, bit_id: &mut u3
         }
 
         if ext4_bmap_is_bit_clr(bmap, i) {
-            *bit_id = sbit;
+            *bit_id = i;
             return true;
         }
 
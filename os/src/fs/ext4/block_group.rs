@@ -1,13 +1,21 @@
 use core::panic;
 
 use super::{
-    crc::{ext4_crc32c, EXT4_CRC32_INIT}, superblock::Ext4Superblock, BlockDevice, BLOCK_SIZE, EXT4_MAX_BLOCK_GROUP_DESCRIPTOR_SIZE, EXT4_MIN_BLOCK_GROUP_DESCRIPTOR_SIZE
+    crc::{ext4_crc16, ext4_crc32c, EXT4_CRC32_INIT},
+    superblock::Ext4Superblock,
+    BlockDevice, BLOCK_SIZE, EXT4_MAX_BLOCK_GROUP_DESCRIPTOR_SIZE,
+    EXT4_MIN_BLOCK_GROUP_DESCRIPTOR_SIZE,
 };
 use crate::fs::directory_tree::{FILE_SYSTEM, GLOBAL_BLOCK_SIZE};
 use crate::math::is_power_of;
 use alloc::vec;
 use alloc::{sync::Arc, vec::Vec};
 
+/// 块组的inode表与inode位图尚未初始化
+pub const EXT4_BG_INODE_UNINIT: u16 = 0x0001;
+/// 块组的块位图尚未初始化
+pub const EXT4_BG_BLOCK_UNINIT: u16 = 0x0002;
+
 #[derive(Debug, Default, Clone, Copy)]
 #[repr(C, packed)]
 /// Ext4块组描述符
@@ -130,7 +138,7 @@ impl Ext4BlockGroup {
     pub fn get_itable_unused(&mut self, s: &Ext4Superblock) -> u32 {
         let mut v = self.itable_unused_lo as u32;
         if s.desc_size() > EXT4_MIN_BLOCK_GROUP_DESCRIPTOR_SIZE {
-            v |= ((self.itable_unused_hi as u64) << 32) as u32;
+            v |= (self.itable_unused_hi as u32) << 16;
         }
         v
     }
@@ -139,16 +147,16 @@ impl Ext4BlockGroup {
     pub fn get_used_dirs_count(&self, s: &Ext4Superblock) -> u32 {
         let mut v = self.used_dirs_count_lo as u32;
         if s.desc_size() > EXT4_MIN_BLOCK_GROUP_DESCRIPTOR_SIZE {
-            v |= ((self.used_dirs_count_hi as u64) << 32) as u32;
+            v |= (self.used_dirs_count_hi as u32) << 16;
         }
         v
     }
 
     /// 设置块组中使用的目录数
     pub fn set_used_dirs_count(&mut self, s: &Ext4Superblock, cnt: u32) {
-        self.itable_unused_lo = (cnt & 0xffff) as u16;
+        self.used_dirs_count_lo = (cnt & 0xffff) as u16;
         if s.desc_size() > EXT4_MIN_BLOCK_GROUP_DESCRIPTOR_SIZE {
-            self.itable_unused_hi = (cnt >> 16) as u16;
+            self.used_dirs_count_hi = (cnt >> 16) as u16;
         }
     }
 
@@ -169,8 +177,12 @@ impl Ext4BlockGroup {
     }
 
     /// 获取块组中空闲的Inode节点数
-    pub fn get_free_inodes_count(&self) -> u32 {
-        ((self.free_inodes_count_hi as u64) << 32) as u32 | self.free_inodes_count_lo as u32
+    pub fn get_free_inodes_count(&self, s: &Ext4Superblock) -> u32 {
+        let mut v = self.free_inodes_count_lo as u32;
+        if s.desc_size() > EXT4_MIN_BLOCK_GROUP_DESCRIPTOR_SIZE {
+            v |= (self.free_inodes_count_hi as u32) << 16;
+        }
+        v
     }
 
     /// 获取块组的Inode节点表块号
@@ -182,38 +194,48 @@ impl Ext4BlockGroup {
 /// 同步块组到磁盘
 impl Ext4BlockGroup {
     /// 计算并返回块组描述符的校验和。
+    /// # 说明
+    /// + metadata_csum：crc32c(uuid + 块组号 + 描述符) 的低16位
+    /// + gdt_csum：crc16(uuid + 块组号 + 描述符)，跳过校验和字段本身
+    /// + 两者都未启用时描述符没有校验和，返回原值
     #[allow(unused)]
     pub fn get_block_group_checksum(&mut self, bgid: u32, super_block: &Ext4Superblock) -> u16 {
-        let desc_size = super_block.desc_size();
+        let desc_size = super_block.desc_size() as usize;
 
-        let mut orig_checksum = 0;
-        let mut checksum = 0;
-
-        orig_checksum = self.checksum;
+        let orig_checksum = self.checksum;
 
         // 准备：暂时将bg校验和设为0
         self.checksum = 0;
 
-        // uuid checksum
-        checksum = ext4_crc32c(
-            EXT4_CRC32_INIT,
-            &super_block.uuid,
-            super_block.uuid.len() as u32,
-        );
-
-        // bgid checksum
-        checksum = ext4_crc32c(checksum, &bgid.to_le_bytes(), 4);
-
         // cast self to &[u8]
         let self_bytes =
-            unsafe { core::slice::from_raw_parts(self as *const _ as *const u8, 0x40) };
-
-        // bg checksum
-        checksum = ext4_crc32c(checksum, self_bytes, desc_size as u32);
+            unsafe { core::slice::from_raw_parts(self as *const _ as *const u8, desc_size) };
+
+        let checksum = if super_block.has_metadata_csum() {
+            // uuid checksum
+            let mut checksum = ext4_crc32c(
+                EXT4_CRC32_INIT,
+                &super_block.uuid,
+                super_block.uuid.len() as u32,
+            );
+            // bgid checksum
+            checksum = ext4_crc32c(checksum, &bgid.to_le_bytes(), 4);
+            // bg checksum
+            checksum = ext4_crc32c(checksum, self_bytes, desc_size as u32);
+            (checksum & 0xFFFF) as u16
+        } else if super_block.has_gdt_csum() {
+            let csum_offset = 0x1E;
+            let mut checksum = ext4_crc16(!0, &super_block.uuid);
+            checksum = ext4_crc16(checksum, &bgid.to_le_bytes());
+            checksum = ext4_crc16(checksum, &self_bytes[..csum_offset]);
+            ext4_crc16(checksum, &self_bytes[csum_offset + 2..])
+        } else {
+            orig_checksum
+        };
 
         self.checksum = orig_checksum;
 
-        (checksum & 0xFFFF) as u16
+        checksum
     }
 
     /// 将块组数据同步到磁盘。
@@ -240,15 +262,16 @@ impl Ext4BlockGroup {
         // 计算偏移量
         let offset = (bgid % dsc_cnt) * super_block.desc_size as usize;
 
+        // 只写回描述符本身，32 字节的描述符后面紧跟着下一个块组的描述符
         let data = unsafe {
             core::slice::from_raw_parts(
                 self as *const _ as *const u8,
-                core::mem::size_of::<Ext4BlockGroup>(),
+                super_block.desc_size() as usize,
             )
         };
 
         // 确保数据不会超出块大小
-        if offset + data.len() >= block_size {
+        if offset + data.len() > block_size {
             panic!("Data exceeds block size");
         }
 
@@ -303,18 +326,20 @@ impl Ext4BlockGroup {
     }
 
     /// Get the count of free blocks in this block group.
-    pub fn get_free_blocks_count(&self) -> u64 {
-        let mut v = self.free_blocks_count_lo as u64;
-        if self.free_blocks_count_hi != 0 {
-            v |= (self.free_blocks_count_hi as u64) << 32;
+    pub fn get_free_blocks_count(&self, s: &Ext4Superblock) -> u32 {
+        let mut v = self.free_blocks_count_lo as u32;
+        if s.desc_size() > EXT4_MIN_BLOCK_GROUP_DESCRIPTOR_SIZE {
+            v |= (self.free_blocks_count_hi as u32) << 16;
         }
         v
     }
 
     /// Set the count of free blocks in this block group.
-    pub fn set_free_blocks_count(&mut self, cnt: u32) {
+    pub fn set_free_blocks_count(&mut self, s: &Ext4Superblock, cnt: u32) {
         self.free_blocks_count_lo = (cnt & 0xffff) as u16;
-        self.free_blocks_count_hi = (cnt >> 16) as u16;
+        if s.desc_size() > EXT4_MIN_BLOCK_GROUP_DESCRIPTOR_SIZE {
+            self.free_blocks_count_hi = (cnt >> 16) as u16;
+        }
     }
 
     /// Set the inode allocation bitmap checksum for this block group.
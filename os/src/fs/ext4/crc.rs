@@ -75,3 +75,20 @@ pub fn crc32(crc: u32, buf: &[u8], size: u32, tab: &[u32]) -> u32 {
 pub fn ext4_crc32c(crc: u32, buf: &[u8], size: u32) -> u32 {
     crc32(crc, buf, size, &CRC32C_TAB)
 }
+
+/// 计算CRC16校验和（多项式 0x8005，按位反转）
+/// 未启用 metadata_csum、启用了 gdt_csum 的文件系统用它计算块组描述符校验和
+pub fn ext4_crc16(crc: u16, buf: &[u8]) -> u16 {
+    let mut crc = crc;
+    for &byte in buf {
+        crc ^= byte as u16;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xA001
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
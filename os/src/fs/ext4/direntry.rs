@@ -1,16 +1,15 @@
-use core::{convert::TryFrom, fmt::Debug, intrinsics::size_of};
-
-use crate::fs::directory_tree::{FILE_SYSTEM, GLOBAL_BLOCK_SIZE};
+use core::convert::{TryFrom, TryInto};
+use core::{fmt::Debug, intrinsics::size_of};
 
 use super::block_group::Block;
 use super::ext4fs::Ext4FileSystem;
 use super::*;
 use super::{crc::*, superblock::Ext4Superblock};
+use crate::syscall::errno::{EIO, ENOENT, ENOSPC, ENOTEMPTY};
 use alloc::string::{String, ToString};
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use error::{Errno, Ext4Error};
-use isomorphic_drivers::block;
 
 bitflags! {
     // #[derive(PartialEq, Eq)]
@@ -26,6 +25,22 @@ bitflags! {
     }
 }
 
+impl DirEntryType {
+    /// inode的类型对应的目录项类型
+    pub fn from_inode(inode: &Ext4Inode) -> Self {
+        match inode.file_type() {
+            InodeFileType::S_IFREG => Self::EXT4_DE_REG_FILE,
+            InodeFileType::S_IFDIR => Self::EXT4_DE_DIR,
+            InodeFileType::S_IFCHR => Self::EXT4_DE_CHRDEV,
+            InodeFileType::S_IFBLK => Self::EXT4_DE_BLKDEV,
+            InodeFileType::S_IFIFO => Self::EXT4_DE_FIFO,
+            InodeFileType::S_IFSOCK => Self::EXT4_DE_SOCK,
+            InodeFileType::S_IFLNK => Self::EXT4_DE_SYMLINK,
+            _ => Self::EXT4_DE_UNKNOWN,
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 /// 目录项结构体
@@ -189,27 +204,6 @@ impl Ext4DirEntry {
 }
 
 impl Ext4DirEntry {
-    /// Get the checksum of the directory entry.
-    #[allow(unused)]
-    pub fn ext4_dir_get_csum(&self, s: &Ext4Superblock, blk_data: &[u8], ino_gen: u32) -> u32 {
-        let ino_index = self.inode;
-
-        let mut csum = 0;
-
-        let uuid = s.uuid;
-
-        csum = ext4_crc32c(EXT4_CRC32_INIT, &uuid, uuid.len() as u32);
-        csum = ext4_crc32c(csum, &ino_index.to_le_bytes(), 4);
-        csum = ext4_crc32c(csum, &ino_gen.to_le_bytes(), 4);
-        let mut data = [0u8; 0xff4];
-        unsafe {
-            core::ptr::copy_nonoverlapping(blk_data.as_ptr(), data.as_mut_ptr(), blk_data.len());
-        }
-
-        csum = ext4_crc32c(csum, &data[..], 0xff4);
-        csum
-    }
-
     /// Write de to block
     pub fn write_de_to_blk(&self, dst_blk: &mut Block, offset: usize) {
         let count = core::mem::size_of::<Ext4DirEntry>() / core::mem::size_of::<u8>();
@@ -246,21 +240,25 @@ impl Ext4DirEntryTail {
         }
     }
 
+    /// 计算目录块的校验和：crc32c(uuid + 目录的inode号 + generation + 尾部之前的数据)
     pub fn tail_set_csum(
         &mut self,
         s: &Ext4Superblock,
-        diren: &Ext4DirEntry,
-        blk_data: &[u8],
+        dir_ino: u32,
         ino_gen: u32,
+        blk_data: &[u8],
     ) {
-        let csum = diren.ext4_dir_get_csum(s, blk_data, ino_gen);
-        self.checksum = csum;
+        let data = &blk_data[..blk_data.len() - size_of::<Ext4DirEntryTail>()];
+        let mut csum = ext4_crc32c(EXT4_CRC32_INIT, &s.uuid, s.uuid.len() as u32);
+        csum = ext4_crc32c(csum, &dir_ino.to_le_bytes(), 4);
+        csum = ext4_crc32c(csum, &ino_gen.to_le_bytes(), 4);
+        self.checksum = ext4_crc32c(csum, data, data.len() as u32);
     }
 
+    /// 写入块的末尾
     pub fn copy_to_slice(&self, array: &mut [u8]) {
-        let block_size = *GLOBAL_BLOCK_SIZE;
         unsafe {
-            let offset = block_size - core::mem::size_of::<Ext4DirEntryTail>();
+            let offset = array.len() - core::mem::size_of::<Ext4DirEntryTail>();
             let de_ptr = self as *const Ext4DirEntryTail as *const u8;
             let array_ptr = array as *mut [u8] as *mut u8;
             let count = core::mem::size_of::<Ext4DirEntryTail>();
@@ -302,14 +300,9 @@ impl Ext4FileSystem {
 
         // iterate all blocks
         while iblock < total_blocks {
-            let search_path = self.find_extent(&parent, iblock as u32);
-
-            if let Ok(path) = search_path {
-                // get the last path
-                let path = path.path.last().unwrap();
-
+            if let Ok(pblock) = self.get_pblock_idx(&parent, iblock as u32) {
                 // get physical block id
-                fblock = path.pblock;
+                fblock = pblock;
 
                 // load physical block
                 let mut ext4block =
@@ -360,6 +353,10 @@ impl Ext4FileSystem {
                 result.prev_offset = prev_de_offset;
                 return Ok(de);
             }
+            // 损坏的目录项
+            if de.entry_len() == 0 {
+                break;
+            }
 
             prev_de_offset = offset;
             // go to next entry
@@ -375,53 +372,13 @@ impl Ext4FileSystem {
     /// # 返回值
     /// + `Vec<Ext4DirEntry>` - 目录项列表
     pub fn dir_get_entries(&self, inode: u32) -> Vec<Ext4DirEntry> {
-        let mut entries = Vec::new();
-
         // 加载inode
         let inode_ref = self.get_inode_ref(inode);
         // assert!(inode_ref.inode.is_dir());
         if !inode_ref.inode.is_dir() {
             return Vec::new();
         }
-
-        // 计算总块数
-        let inode_size = inode_ref.inode.size();
-        let total_blocks = inode_size / self.block_size as u64;
-
-        // 从第一个逻辑块开始
-        let mut iblock = 0;
-
-        // 遍历所有块
-        while iblock < total_blocks {
-            // 获取逻辑块号对应的物理块号(此处为路径，路径中包含有物理块号)
-            let search_path = self.find_extent(&inode_ref, iblock as u32);
-
-            if let Ok(path) = search_path {
-                // get the last path
-                let path = path.path.last().unwrap();
-
-                // 获取物理块号
-                let fblock = path.pblock;
-
-                // 加载物理块
-                let ext4block =
-                    Block::load_offset(self.block_device.clone(), fblock as usize * self.block_size);
-                let mut offset = 0;
-
-                // 遍历块内所有项
-                while offset < self.block_size - core::mem::size_of::<Ext4DirEntryTail>() {
-                    let de: Ext4DirEntry = ext4block.read_offset_as(offset);
-                    if !de.unused() {
-                        entries.push(de);
-                    }
-                    offset += de.entry_len() as usize;
-                }
-            }
-
-            // 前往下一个逻辑块
-            iblock += 1;
-        }
-        entries
+        self.dir_get_entries_from_inode_ref(Arc::new(inode_ref))
     }
 
     pub fn dir_get_entries_from_inode_ref(&self, inode_ref: Arc<Ext4InodeRef>) -> Vec<Ext4DirEntry> {
@@ -440,15 +397,7 @@ impl Ext4FileSystem {
         // 遍历所有块
         while iblock < total_blocks {
             // 获取逻辑块号对应的物理块号
-            let search_path = self.find_extent(&inode_ref, iblock as u32);
-
-            if let Ok(path) = search_path {
-                // get the last path
-                let path = path.path.last().unwrap();
-
-                // 获取物理块号
-                let fblock = path.pblock;
-
+            if let Ok(fblock) = self.get_pblock_idx(&inode_ref, iblock as u32) {
                 // 加载物理块
                 let ext4block =
                     Block::load_offset(self.block_device.clone(), fblock as usize * self.block_size);
@@ -460,6 +409,9 @@ impl Ext4FileSystem {
                     if !de.unused() {
                         entries.push(de);
                     }
+                    if de.entry_len() == 0 {
+                        break;
+                    }
                     offset += de.entry_len() as usize;
                 }
             }
@@ -470,15 +422,29 @@ impl Ext4FileSystem {
         entries
     }
 
-    pub fn dir_set_csum(&self, dst_blk: &mut Block, ino_gen: u32) {
-        let parent_de: Ext4DirEntry = dst_blk.read_offset_as(0);
-
-        let tail_offset = self.block_size - size_of::<Ext4DirEntryTail>();
-        let mut tail: Ext4DirEntryTail = *dst_blk.read_offset_as_mut(tail_offset);
-
-        tail.tail_set_csum(&self.superblock, &parent_de, &dst_blk.data[..], ino_gen);
+    /// 目录块中目录项区域的结尾，有 metadata_csum 特性时块的最后12字节是校验和
+    fn dir_entries_end(&self) -> usize {
+        if self.superblock.has_metadata_csum() {
+            self.block_size - size_of::<Ext4DirEntryTail>()
+        } else {
+            self.block_size
+        }
+    }
 
-        tail.copy_to_slice(&mut dst_blk.data);
+    /// 重新计算目录块尾部的校验和，没有 metadata_csum 特性时不做任何事
+    pub fn dir_set_csum(&self, dst_blk: &mut Block, dir: &Ext4InodeRef) {
+        if !self.superblock.has_metadata_csum() {
+            return;
+        }
+        let data = &mut dst_blk.data[..self.block_size];
+        let mut tail = Ext4DirEntryTail::new();
+        tail.tail_set_csum(
+            &self.superblock,
+            dir.inode_num,
+            dir.inode.generation(),
+            data,
+        );
+        tail.copy_to_slice(data);
     }
 
     /// Add a new entry to a directory
@@ -496,6 +462,9 @@ impl Ext4FileSystem {
         child: &Ext4InodeRef,
         name: &str,
     ) -> Result<usize, isize> {
+        self.dir_drop_index(parent)?;
+        let de_type = DirEntryType::from_inode(&child.inode);
+
         // calculate total blocks
         let inode_size: u64 = parent.inode.size();
         let block_size = self.superblock.block_size();
@@ -511,14 +480,16 @@ impl Ext4FileSystem {
             let mut ext4block =
                 Block::load_offset(self.block_device.clone(), pblock as usize * self.block_size);
 
-            let result = self.try_insert_to_existing_block(&mut ext4block, name, child.inode_num);
-
-            if result.is_ok() {
-                // set checksum
-                self.dir_set_csum(&mut ext4block, parent.inode.generation());
-                ext4block.sync_blk_to_disk(self.block_device.clone());
-
-                return Ok(EOK);
+            match self.try_insert_to_existing_block(&mut ext4block, name, child.inode_num, de_type)
+            {
+                Ok(_) => {
+                    // set checksum
+                    self.dir_set_csum(&mut ext4block, parent);
+                    ext4block.sync_blk_to_disk(self.block_device.clone());
+                    return Ok(EOK);
+                }
+                Err(ENOSPC) => {}
+                Err(errno) => return Err(errno),
             }
 
             // go ot next block
@@ -534,11 +505,10 @@ impl Ext4FileSystem {
 
         // write new entry to the new block
         // must succeed, as we just allocated the block
-        let de_type = DirEntryType::EXT4_DE_DIR;
         self.insert_to_new_block(&mut new_ext4block, child.inode_num, name, de_type);
 
         // set checksum
-        self.dir_set_csum(&mut new_ext4block, parent.inode.generation());
+        self.dir_set_csum(&mut new_ext4block, parent);
         new_ext4block.sync_blk_to_disk(self.block_device.clone());
 
         Ok(EOK)
@@ -550,72 +520,59 @@ impl Ext4FileSystem {
     /// block: &mut Block - block to insert the new entry
     /// name: &str - name of the new entry
     /// inode: u32 - inode number of the new entry
+    /// de_type: DirEntryType - type of the new entry
     ///
     /// Returns:
-    /// `Result<usize>` - status of the operation
+    /// `Result<usize>` - status of the operation, ENOSPC if the block is full
     pub fn try_insert_to_existing_block(
         &self,
         block: &mut Block,
         name: &str,
         child_inode: u32,
+        de_type: DirEntryType,
     ) -> Result<usize, isize> {
         // required length aligned to 4 bytes
-        let required_len = {
-            let mut len = size_of::<Ext4DirEntry>() + name.len();
-            if len % 4 != 0 {
-                len += 4 - (len % 4);
-            }
-            len
-        };
+        let required_len = dir_entry_len(name.len());
+        let end = self.dir_entries_end();
 
         let mut offset = 0;
 
         // Start from the first entry
-        while offset < self.block_size - size_of::<Ext4DirEntryTail>() {
-            let mut de = Ext4DirEntry::try_from(&block.data[offset..]).unwrap();
-
-            if de.unused() {
-                continue;
-            }
-
-            let inode = de.inode;
-            let rec_len = de.entry_len;
-
-            let used_len = de.name_len as usize;
-            let mut sz = core::mem::size_of::<Ext4FakeDirEntry>() + used_len;
-            if used_len % 4 != 0 {
-                sz += 4 - used_len % 4;
+        while offset < end {
+            let (inode, rec_len, name_len) = read_dir_entry_head(&block.data, offset);
+            if rec_len < dir_entry_len(0) || offset + rec_len > end {
+                println!("[kernel direntry] corrupted directory entry at {}", offset);
+                return Err(EIO);
             }
 
-            let free_space = rec_len as usize - sz;
+            // 空闲的目录项整个可用，否则用它对齐后的实际长度之后的部分
+            let used_len = if inode == 0 {
+                0
+            } else {
+                dir_entry_len(name_len)
+            };
 
             // If there is enough free space
-            if free_space >= required_len {
-                // Create new directory entry
-                let mut new_entry = Ext4DirEntry::default();
-
-                // Update existing entry length and copy both entries back to block data
-                de.entry_len = sz as u16;
-
-                let de_type = DirEntryType::EXT4_DE_DIR;
-                new_entry.write_entry(free_space as u16, child_inode, name, de_type);
-
-                // update parent_de and new_de to blk_data
-                de.copy_to_slice(&mut block.data, offset);
-                new_entry.copy_to_slice(&mut block.data, offset + sz);
-
-                // Sync to disk
-                block.sync_blk_to_disk(self.block_device.clone());
-
+            if rec_len - used_len >= required_len {
+                if used_len != 0 {
+                    set_dir_entry_len(&mut block.data, offset, used_len);
+                }
+                write_dir_entry(
+                    &mut block.data,
+                    offset + used_len,
+                    child_inode,
+                    rec_len - used_len,
+                    name,
+                    de_type,
+                );
                 return Ok(EOK);
             }
 
             // Move to the next entry
-            offset += de.entry_len() as usize;
+            offset += rec_len;
         }
 
-        println!("[kernel direntry] No space in block for new entry");
-        return Err(Errno::ENOSPC as isize);
+        Err(ENOSPC)
     }
 
     /// Insert a new entry to a new block
@@ -631,44 +588,111 @@ impl Ext4FileSystem {
         name: &str,
         de_type: DirEntryType,
     ) {
-        // write new entry
-        let mut new_entry = Ext4DirEntry::default();
-        let el = self.block_size - size_of::<Ext4DirEntryTail>();
-        new_entry.write_entry(el as u16, inode, name, de_type);
-        new_entry.copy_to_slice(&mut block.data, 0);
+        // 新块中的数据是之前释放时留下的
+        block.data.fill(0);
 
-        copy_dir_entry_to_array(&new_entry, &mut block.data, 0);
+        // write new entry
+        let el = self.dir_entries_end();
+        write_dir_entry(&mut block.data, 0, inode, el, name, de_type);
 
         // init tail for new block
-        let tail = Ext4DirEntryTail::new();
-        tail.copy_to_slice(&mut block.data);
+        if self.superblock.has_metadata_csum() {
+            let tail = Ext4DirEntryTail::new();
+            tail.copy_to_slice(&mut block.data[..self.block_size]);
+        }
     }
 
-    pub fn dir_remove_entry(&self, parent: &mut Ext4InodeRef, path: &str) -> Result<usize, isize> {
-        // get remove_entry pos in parent and its prev entry
-        let mut result = Ext4DirSearchResult::new(Ext4DirEntry::default());
-
-        // let r = self.dir_find_entry(parent.inode_num, path, &mut result)?;
-        let r = self.dir_find_entry(parent.inode_num, path, &mut result);
-
-        let mut ext4block =
-            Block::load_offset(self.block_device.clone(), result.pblock_id * self.block_size);
-
-        let de_del_entry_len = result.dentry.entry_len();
+    /// 从目录中删除名为 `name` 的目录项
+    /// # 说明
+    /// + 块中第一个目录项只清空inode号，其余目录项并入前一个目录项
+    /// # 返回值
+    /// + 没有该目录项时返回 ENOENT
+    pub fn dir_remove_entry(&self, parent: &mut Ext4InodeRef, name: &str) -> Result<usize, isize> {
+        self.dir_drop_index(parent)?;
 
-        // prev entry
-        let pde: &mut Ext4DirEntry = ext4block.read_offset_as_mut(result.prev_offset);
+        let end = self.dir_entries_end();
+        let total_blocks = parent.inode.size() / self.block_size as u64;
+        for iblock in 0..total_blocks {
+            let pblock = self.get_pblock_idx(parent, iblock as u32)?;
+            let mut ext4block =
+                Block::load_offset(self.block_device.clone(), pblock as usize * self.block_size);
 
-        pde.entry_len += de_del_entry_len;
+            let mut offset = 0;
+            let mut prev_offset = None;
+            while offset < end {
+                let (inode, rec_len, name_len) = read_dir_entry_head(&ext4block.data, offset);
+                if rec_len < dir_entry_len(0) || offset + rec_len > end {
+                    return Err(EIO);
+                }
+                if inode != 0
+                    && name_len == name.len()
+                    && 8 + name_len <= rec_len
+                    && &ext4block.data[offset + 8..offset + 8 + name_len] == name.as_bytes()
+                {
+                    match prev_offset {
+                        Some(prev_offset) => {
+                            let (_, prev_len, _) =
+                                read_dir_entry_head(&ext4block.data, prev_offset);
+                            set_dir_entry_len(&mut ext4block.data, prev_offset, prev_len + rec_len);
+                        }
+                        None => ext4block.data[offset..offset + 4].fill(0),
+                    }
+                    self.dir_set_csum(&mut ext4block, parent);
+                    ext4block.sync_blk_to_disk(self.block_device.clone());
+                    return Ok(EOK);
+                }
+                prev_offset = Some(offset);
+                offset += rec_len;
+            }
+        }
 
-        let de_del: &mut Ext4DirEntry = ext4block.read_offset_as_mut(result.offset);
+        Err(ENOENT)
+    }
 
-        de_del.inode = 0;
+    /// 把哈希索引（htree）目录转为线性目录，之后的增删只需维护线性的目录项
+    /// # 说明
+    /// + 索引根在第0块 ".." 目录项的空闲空间中，索引节点是一个占满整块的空目录项，
+    ///   线性读取时都被跳过，只需把它们改写成普通的空闲空间并补上校验和尾部
+    fn dir_drop_index(&self, dir: &mut Ext4InodeRef) -> Result<(), isize> {
+        let flags = dir.inode.flags();
+        if flags & EXT4_INODE_FLAG_INDEX as u32 == 0 {
+            return Ok(());
+        }
 
-        self.dir_set_csum(&mut ext4block, parent.inode.generation());
-        ext4block.sync_blk_to_disk(self.block_device.clone());
+        let end = self.dir_entries_end();
+        let total_blocks = dir.inode.size() / self.block_size as u64;
+        for iblock in 0..total_blocks {
+            let pblock = self.get_pblock_idx(dir, iblock as u32)?;
+            let mut ext4block =
+                Block::load_offset(self.block_device.clone(), pblock as usize * self.block_size);
+            let data = &mut ext4block.data[..self.block_size];
+
+            let (inode, rec_len, _) = read_dir_entry_head(data, 0);
+            if iblock == 0 {
+                // "." 之后的 ".." 覆盖了整个索引根
+                let dotdot = rec_len;
+                let dotdot_len = dir_entry_len(2);
+                if dotdot + dotdot_len > end {
+                    return Err(EIO);
+                }
+                data[dotdot + dotdot_len..].fill(0);
+                set_dir_entry_len(data, dotdot, end - dotdot);
+            } else if inode == 0 && rec_len == self.block_size {
+                data.fill(0);
+                set_dir_entry_len(data, 0, end);
+            } else {
+                continue;
+            }
+            if self.superblock.has_metadata_csum() {
+                Ext4DirEntryTail::new().copy_to_slice(data);
+            }
+            self.dir_set_csum(&mut ext4block, dir);
+            ext4block.sync_blk_to_disk(self.block_device.clone());
+        }
 
-        Ok(EOK)
+        dir.inode.set_flags(flags & !(EXT4_INODE_FLAG_INDEX as u32));
+        self.write_back_inode(dir);
+        Ok(())
     }
 
     pub fn dir_has_entry(&self, dir_inode: u32) -> bool {
@@ -687,14 +711,9 @@ impl Ext4FileSystem {
 
         // iterate all blocks
         while iblock < total_blocks {
-            let search_path = self.find_extent(&parent, iblock as u32);
-
-            if let Ok(path) = search_path {
-                // get the last path
-                let path = path.path.last().unwrap();
-
+            if let Ok(pblock) = self.get_pblock_idx(&parent, iblock as u32) {
                 // get physical block id
-                fblock = path.pblock;
+                fblock = pblock;
 
                 // load physical block
                 let ext4block =
@@ -704,6 +723,9 @@ impl Ext4FileSystem {
                 let mut offset = 0;
                 while offset < self.block_size - core::mem::size_of::<Ext4DirEntryTail>() {
                     let de: Ext4DirEntry = ext4block.read_offset_as(offset);
+                    if de.entry_len == 0 {
+                        break;
+                    }
                     offset = offset + de.entry_len as usize;
                     if de.inode == 0 {
                         continue;
@@ -722,32 +744,26 @@ impl Ext4FileSystem {
         false
     }
 
+    /// 删除空目录
     pub fn dir_remove(&self, parent: u32, path: &str) -> Result<usize, isize> {
         let mut search_result = Ext4DirSearchResult::new(Ext4DirEntry::default());
 
-        // let r = self.dir_find_entry(parent as u32, path, &mut search_result)?;
-        let r = self.dir_find_entry(parent, path, &mut search_result);
+        if self
+            .dir_find_entry(parent, path, &mut search_result)
+            .is_err()
+        {
+            return Err(ENOENT);
+        }
 
         let mut parent_inode_ref = self.get_inode_ref(parent);
         let mut child_inode_ref = self.get_inode_ref(search_result.dentry.inode);
 
         if self.dir_has_entry(child_inode_ref.inode_num) {
-            println!("[kernel] rm dir with chidren not supported");
-            return Err(Errno::ENOTSUP as isize);
+            return Err(ENOTEMPTY);
         }
 
-        self.truncate_inode(&mut child_inode_ref, 0)?;
-
-        self.unlink(&mut parent_inode_ref, &mut child_inode_ref, path)?;
-
-        self.write_back_inode(&mut parent_inode_ref);
-
-        // to do
-        // ext4_inode_set_del_time
-        // ext4_inode_set_links_cnt
-        // ext4_fs_free_inode(&child)
-
-        Ok(EOK)
+        // unlink 释放子目录的数据块与inode，并写回父目录
+        self.unlink(&mut parent_inode_ref, &mut child_inode_ref, path)
     }
 }
 
@@ -759,3 +775,35 @@ pub fn copy_dir_entry_to_array(header: &Ext4DirEntry, array: &mut [u8], offset:
         core::ptr::copy_nonoverlapping(de_ptr, array_ptr.add(offset), count);
     }
 }
+
+/// 名字长为 `name_len` 的目录项占用的长度，按4字节对齐
+fn dir_entry_len(name_len: usize) -> usize {
+    (size_of::<Ext4FakeDirEntry>() + name_len + 3) & !3
+}
+
+/// 块中 `offset` 处目录项的inode号、目录项长度与名字长度
+fn read_dir_entry_head(data: &[u8], offset: usize) -> (u32, usize, usize) {
+    let inode = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+    let rec_len = u16::from_le_bytes(data[offset + 4..offset + 6].try_into().unwrap());
+    (inode, rec_len as usize, data[offset + 6] as usize)
+}
+
+fn set_dir_entry_len(data: &mut [u8], offset: usize, rec_len: usize) {
+    data[offset + 4..offset + 6].copy_from_slice(&(rec_len as u16).to_le_bytes());
+}
+
+/// 在块中 `offset` 处写入目录项，只写入头部与名字，不越过目录项的边界
+fn write_dir_entry(
+    data: &mut [u8],
+    offset: usize,
+    inode: u32,
+    rec_len: usize,
+    name: &str,
+    de_type: DirEntryType,
+) {
+    data[offset..offset + 4].copy_from_slice(&inode.to_le_bytes());
+    set_dir_entry_len(data, offset, rec_len);
+    data[offset + 6] = name.len() as u8;
+    data[offset + 7] = de_type.bits();
+    data[offset + 8..offset + 8 + name.len()].copy_from_slice(name.as_bytes());
+}
@@ -3,24 +3,21 @@ use core::mem::size_of;
 use core::panic;
 
 use alloc::string::String;
-use alloc::vec;
 use alloc::{sync::Arc, vec::Vec};
 use spin::{Mutex, MutexGuard, RwLockReadGuard, RwLockWriteGuard};
-use crate::fs::directory_tree::{FILE_SYSTEM, GLOBAL_BLOCK_SIZE};
 use crate::fs::inode::{InodeLock, InodeTime};
 use crate::fs::DiskInodeType;
 use crate::fs::{inode::InodeTrait, vfs::VFS};
+use crate::syscall::errno::EIO;
 
 use super::*;
 use super::{
-    bitmap::{ext4_bmap_bit_find_clr, ext4_bmap_bit_set},
     block_group::{Block, Ext4BlockGroup},
     crc::{ext4_crc32c, EXT4_CRC32_INIT},
     direntry::DirEntryType,
     ext4fs::Ext4FileSystem,
     extent::{Ext4Extent, Ext4ExtentHeader, Ext4ExtentIndex},
     superblock::Ext4Superblock,
-    BlockDevice,
 };
 
 bitflags! {
@@ -451,85 +448,58 @@ impl Ext4Inode {
             self.i_checksum_hi = (checksum >> 16) as u16;
         }
     }
+    /// 将inode结构体复制到磁盘上的inode槽位，槽位小于结构体时只复制槽位大小
     fn copy_to_slice(&self, slice: &mut [u8]) {
+        let len = min(size_of::<Ext4Inode>(), slice.len());
         unsafe {
             let inode_ptr = self as *const Ext4Inode as *const u8;
-            let array_ptr = slice.as_ptr() as *mut u8;
-            core::ptr::copy_nonoverlapping(inode_ptr, array_ptr, 0x9c);
+            core::ptr::copy_nonoverlapping(inode_ptr, slice.as_mut_ptr(), len);
         }
     }
-    #[allow(unused)]
-    pub fn get_inode_checksum(&mut self, inode_id: u32, super_block: &Ext4Superblock) -> u32 {
-        let inode_size = super_block.inode_size();
-
-        let orig_checksum = self.get_checksum(super_block);
-        let mut checksum = 0;
 
-        let ino_index = inode_id;
-        let ino_gen = self.generation;
-
-        // Preparation: temporarily set bg checksum to 0
-        self.osd2.l_i_checksum_lo = 0;
-        self.i_checksum_hi = 0;
-
-        checksum = ext4_crc32c(
+    /// 计算inode的校验和
+    /// # 参数
+    /// + inode_id: inode号
+    /// + super_block: 超级块
+    /// + slot: 磁盘上完整的inode槽位，其中的校验和字段须已清零
+    pub fn get_inode_checksum(
+        &self,
+        inode_id: u32,
+        super_block: &Ext4Superblock,
+        slot: &[u8],
+    ) -> u32 {
+        let mut checksum = ext4_crc32c(
             EXT4_CRC32_INIT,
             &super_block.uuid,
             super_block.uuid.len() as u32,
         );
-        checksum = ext4_crc32c(checksum, &ino_index.to_le_bytes(), 4);
-        checksum = ext4_crc32c(checksum, &ino_gen.to_le_bytes(), 4);
-
-        let mut raw_data = [0u8; 0x100];
-        self.copy_to_slice(&mut raw_data);
-
-        // inode checksum
-        checksum = ext4_crc32c(checksum, &raw_data, inode_size as u32);
-
-        self.set_inode_checksum_value(super_block, inode_id, checksum);
-
-        if inode_size == 128 {
+        checksum = ext4_crc32c(checksum, &inode_id.to_le_bytes(), 4);
+        checksum = ext4_crc32c(checksum, &self.generation.to_le_bytes(), 4);
+        checksum = ext4_crc32c(checksum, slot, slot.len() as u32);
+        if super_block.inode_size() == EXT4_GOOD_OLD_INODE_SIZE {
             checksum &= 0xFFFF;
         }
-
         checksum
     }
 
-    pub fn set_inode_checksum(&mut self, super_block: &Ext4Superblock, inode_id: u32) {
-        let inode_size = super_block.inode_size();
-        let checksum = self.get_inode_checksum(inode_id, super_block);
-
-        self.osd2.l_i_checksum_lo = ((checksum << 16) >> 16) as u16;
-        if inode_size > 128 {
-            self.i_checksum_hi = (checksum >> 16) as u16;
-        }
-    }
-
-    pub fn sync_inode_to_disk(&self, block_device: Arc<dyn BlockDevice>, inode_pos: usize) {
-        let block_size = *GLOBAL_BLOCK_SIZE;
-        let data = unsafe {
-            core::slice::from_raw_parts(self as *const _ as *const u8, size_of::<Ext4Inode>())
-        };
-        // println!("data is {:?}", data);
-        // 因为写入的是inode的大小，不大可能越界。
-        // 所以先读取一个块，再覆写，再写入
-        // println!("[kernel sync to disk] Ext4Inode is {:#?}", self);
-        // println!("[kernel] data len is {} inode_pos is {}",data.len(), inode_pos);
-        // 计算要写入的块号
-        let block_id = inode_pos / block_size;
-        // println!("[kernel] block_id is {}", block_id);
-        // 计算在一个块上的偏移量
-        let offset = inode_pos % block_size;
-        // println!("[kernel] offset is {}", offset);
-        let mut buf = vec![0u8; crate::hal::BLOCK_SZ];
-        // 读取一个块
-        block_device.read_block(block_id, &mut buf);
-        // 偏移量加上数据长度不能超过块大小
-        if offset + data.len() > block_size {
-            panic!("[kernel fs error] over border");
+    /// 将inode写入磁盘上的inode槽位，启用了元数据校验和时同时更新校验和
+    /// # 说明
+    /// + 校验和覆盖整个槽位，包括结构体之外的扩展字段
+    pub fn set_inode_checksum(
+        &mut self,
+        super_block: &Ext4Superblock,
+        inode_id: u32,
+        slot: &mut [u8],
+    ) {
+        if !super_block.has_metadata_csum() {
+            self.copy_to_slice(slot);
+            return;
         }
-        buf[offset..offset + data.len()].copy_from_slice(&data);
-        block_device.write_block(block_id, &buf);
+        self.set_inode_checksum_value(super_block, inode_id, 0);
+        self.copy_to_slice(slot);
+        let checksum = self.get_inode_checksum(inode_id, super_block, slot);
+        self.set_inode_checksum_value(super_block, inode_id, checksum);
+        self.copy_to_slice(slot);
     }
 }
 
@@ -807,12 +777,14 @@ impl InodeTrait for Ext4Inode {
 }
 
 impl Ext4FileSystem {
+    /// inode所在的块组号，inode号从1开始
     pub fn get_bgid_of_inode(&self, inode_num: u32) -> u32 {
-        inode_num / self.superblock.inodes_per_group()
+        (inode_num - 1) / self.superblock.inodes_per_group()
     }
 
+    /// inode在块组内的索引
     pub fn inode_to_bgidx(&self, inode_num: u32) -> u32 {
-        inode_num % self.superblock.inodes_per_group()
+        (inode_num - 1) % self.superblock.inodes_per_group()
     }
 
     /// 获取Inode的地址
@@ -870,26 +842,46 @@ impl Ext4FileSystem {
     }
 
     /// 带校验和回写inode信息
+    /// # 说明
+    /// + 在磁盘上的inode槽位上读改写，结构体没有覆盖的扩展字段保持不变
     pub fn write_back_inode(&self, inode_ref: &mut Ext4InodeRef) {
-        let inode_pos = self.inode_disk_pos(inode_ref.inode_num);
+        self.sync_inode_slot(inode_ref, false);
+    }
 
-        // make sure self.superblock is up-to-date
-        inode_ref
-            .inode
-            .set_inode_checksum(&self.superblock, inode_ref.inode_num);
+    /// 带校验和写入新分配的inode
+    /// # 说明
+    /// + 槽位中上一个使用者留下的扩展字段被清零
+    pub fn write_back_new_inode(&self, inode_ref: &mut Ext4InodeRef) {
+        self.sync_inode_slot(inode_ref, true);
+    }
+
+    fn sync_inode_slot(&self, inode_ref: &mut Ext4InodeRef, clear: bool) {
+        let inode_pos = self.inode_disk_pos(inode_ref.inode_num);
+        let inode_size = self.superblock.inode_size() as usize;
+        let mut block = Block::load_offset(self.block_device.clone(), inode_pos);
+        let offset = inode_pos % self.block_size;
+        let slot = &mut block.data[offset..offset + inode_size];
+        if clear {
+            slot.fill(0);
+        }
         inode_ref
             .inode
-            .sync_inode_to_disk(self.block_device.clone(), inode_pos);
+            .set_inode_checksum(&self.superblock, inode_ref.inode_num, slot);
+        self.block_device
+            .write_block(inode_pos / self.block_size, &block.data);
     }
 
     /// 不带校验和回写inode信息
     pub fn write_back_inode_without_csum(&self, inode_ref: &Ext4InodeRef) {
         let inode_pos = self.inode_disk_pos(inode_ref.inode_num);
-        // println!("[kernel write_back_inode_without_csum] inode_pos: {:?}, inode_num: {}", inode_pos, inode_ref.inode_num);
-
+        let inode_size = self.superblock.inode_size() as usize;
+        let mut block = Block::load_offset(self.block_device.clone(), inode_pos);
+        let offset = inode_pos % self.block_size;
         inode_ref
             .inode
-            .sync_inode_to_disk(self.block_device.clone(), inode_pos);
+            .copy_to_slice(&mut block.data[offset..offset + inode_size]);
+        self.block_device
+            .write_block(inode_pos / self.block_size, &block.data);
     }
 
     /// 获取逻辑块号对应的物理块号
@@ -897,81 +889,19 @@ impl Ext4FileSystem {
     /// + inode_ref: &Ext4InodeRef - inode封装对象
     /// + lblock: Ext4Lblk - 逻辑块号
     /// # 返回值
-    /// + `Result<Ext4Fsblk>` - 物理块号
+    /// + `Result<Ext4Fsblk>` - 物理块号，逻辑块没有映射（空洞）时返回 EIO
     pub fn get_pblock_idx(
         &self,
         inode_ref: &Ext4InodeRef,
         lblock: Ext4Lblk,
     ) -> Result<Ext4Fsblk, isize> {
-        let search_path = self.find_extent(inode_ref, lblock);
-        if let Ok(path) = search_path {
-            // get the last path
-            let path = path.path.last().unwrap();
-
-            // 获取物理块号
-            let fblock = path.pblock;
-
-            return Ok(fblock);
-        }
-
-        panic!("search extent fail!");
-        // Err(Errno::EIO as isize)
+        self.find_pblock(inode_ref, lblock)?.ok_or(EIO)
     }
 
-    /// 分配一个新的块
-    pub fn allocate_new_block(&self, inode_ref: &mut Ext4InodeRef) -> Result<Ext4Fsblk, isize> {
-        let mut super_block = self.superblock;
-        let inodes_per_group = super_block.inodes_per_group();
-        let bgid = (inode_ref.inode_num - 1) / inodes_per_group;
-        let index = (inode_ref.inode_num - 1) % inodes_per_group;
-
-        // load block group
-        let mut block_group =
-            Ext4BlockGroup::load_new(self.block_device.clone(), &super_block, bgid as usize);
-
-        let block_bitmap_block = block_group.get_block_bitmap_block(&super_block);
-
-        let mut block_bmap_raw_data = [0u8; BLOCK_SIZE];
-        self.block_device
-            .read_block(block_bitmap_block as usize, &mut block_bmap_raw_data);
-        let data: &mut Vec<u8> = &mut block_bmap_raw_data.to_vec();
-        let mut rel_blk_idx = 0;
-
-        ext4_bmap_bit_find_clr(data, index, 0x8000, &mut rel_blk_idx);
-        ext4_bmap_bit_set(data, rel_blk_idx);
-
-        block_group.set_block_group_balloc_bitmap_csum(&super_block, data);
-        // todo!();
-        self.block_device
-            .write_block(block_bitmap_block as usize, data);
-
-        /* Update superblock free blocks count */
-        let mut super_blk_free_blocks = super_block.free_blocks_count();
-        super_blk_free_blocks -= 1;
-        super_block.set_free_blocks_count(super_blk_free_blocks);
-        super_block.sync_to_disk_with_csum(self.block_device.clone());
-
-        /* Update inode blocks (different block size!) count */
-        let mut inode_blocks = inode_ref.inode.blocks_count();
-        // inode_blocks += (self.block_size / EXT4_INODE_BLOCK_SIZE) as u64;
-        inode_blocks += (self.block_size / self.superblock.inode_size as usize) as u64;
-        inode_ref.inode.set_blocks_count(inode_blocks);
-        self.write_back_inode(inode_ref);
-
-        /* Update block group free blocks count */
-        let mut fb_cnt = block_group.get_free_blocks_count();
-        fb_cnt -= 1;
-        block_group.set_free_blocks_count(fb_cnt as u32);
-        block_group.sync_to_disk_with_csum(self.block_device.clone(), bgid as usize, &super_block);
-
-        Ok(rel_blk_idx as Ext4Fsblk)
-    }
-
-    /// Append a new block to the inode and update the extent tree.
+    /// 为inode在文件末尾追加一个块并更新extent树与文件大小
     ///
     /// Params:
     /// inode_ref: &mut Ext4InodeRef - inode reference
-    /// iblock: Ext4Lblk - logical block id
     ///
     /// Returns:
     /// `Result<Ext4Fsblk>` - physical block id of the new block
@@ -979,55 +909,23 @@ impl Ext4FileSystem {
         let inode_size = inode_ref.inode.size();
         let iblock = ((inode_size as usize + self.block_size - 1) / self.block_size) as u32;
 
-        let mut newex: Ext4Extent = Ext4Extent::default();
-
-        let new_block = self.balloc_alloc_block(inode_ref, None)?;
-
-        newex.first_block = iblock;
-        newex.store_pblock(new_block);
-        newex.block_count = min(1, EXT_MAX_BLOCKS - iblock) as u16;
-
-        self.insert_extent(inode_ref, &mut newex)?;
-
-        // Update the inode size
-        let mut inode_size = inode_ref.inode.size();
-        inode_size += self.block_size as u64;
-        inode_ref.inode.set_size(inode_size);
-        self.write_back_inode(inode_ref);
-
-        Ok(new_block)
-    }
-
-    /// Append a new block to the inode and update the extent tree.From a specific bgid
-    ///
-    /// Params:
-    /// inode_ref: &mut Ext4InodeRef - inode reference
-    /// bgid: Start bgid of free block search
-    ///
-    /// Returns:
-    /// `Result<Ext4Fsblk>` - physical block id of the new block
-    pub fn append_inode_pblk_from(
-        &self,
-        inode_ref: &mut Ext4InodeRef,
-        start_bgid: &mut u32,
-    ) -> Result<Ext4Fsblk, isize> {
-        let inode_size = inode_ref.inode.size();
-        let iblock = ((inode_size as usize + self.block_size - 1) / self.block_size) as u32;
-
-        let mut newex: Ext4Extent = Ext4Extent::default();
-
-        let new_block = self.balloc_alloc_block_from(inode_ref, start_bgid)?;
-
-        newex.first_block = iblock;
-        newex.store_pblock(new_block);
-        newex.block_count = min(1, EXT_MAX_BLOCKS - iblock) as u16;
-
-        self.insert_extent(inode_ref, &mut newex)?;
+        // 尽量紧接在前一个块之后
+        let goal = match iblock {
+            0 => None,
+            _ => self
+                .find_pblock(inode_ref, iblock - 1)?
+                .map(|pblock| pblock + 1),
+        };
+        let new_block = self.balloc_alloc_block(inode_ref, goal)?;
+        if let Err(errno) = self.insert_extent(inode_ref, iblock, new_block, 1) {
+            self.balloc_free_blocks(inode_ref, new_block, 1)?;
+            return Err(errno);
+        }
 
         // Update the inode size
-        let mut inode_size = inode_ref.inode.size();
-        inode_size += self.block_size as u64;
-        inode_ref.inode.set_size(inode_size);
+        inode_ref
+            .inode
+            .set_size((iblock as u64 + 1) * self.block_size as u64);
         self.write_back_inode(inode_ref);
 
         Ok(new_block)
@@ -1035,18 +933,13 @@ impl Ext4FileSystem {
 
     /// 分配一个新inode
     /// # 参数
-    /// + inode_mode: u16 - inode 文件类型和权限
+    /// + is_dir: 是否是文件夹
     ///
     /// # 返回值
     /// + `Result<u32>` - inode 号
     pub fn alloc_inode(&self, is_dir: bool) -> Result<u32, isize> {
         // 分配inode号
-        let inode_num = self.ialloc_alloc_inode(is_dir);
-        if let Ok(inode_num) = inode_num {
-            Ok(inode_num)
-        } else {
-            panic!("alloc_inode failed:{:?}", inode_num);
-        }
+        self.ialloc_alloc_inode(is_dir)
     }
 
     pub fn correspond_inode_mode(&self, filetype: u8) -> u16 {
@@ -35,6 +35,8 @@ pub struct Ext4FileSystem {
     // pub inode_table_start_block: u32,
     /// 缓存管理器
     pub cache_mgr: Arc<Mutex<BlockCacheManager>>,
+    /// 分配锁，块与inode的分配、释放在位图、块组描述符与超级块上的读改写需要互斥
+    pub alloc_lock: Mutex<()>,
 }
 
 impl Ext4FileSystem {
@@ -59,6 +61,7 @@ impl Ext4FileSystem {
             superblock,
             block_size,
             cache_mgr,
+            alloc_lock: Mutex::new(()),
         };
         // ext4fs.test_info();
         ext4fs
@@ -130,6 +133,7 @@ impl Ext4FileSystem {
             if let Err(e) = r {
                 if e.error() != Errno::ENOENT || !create {
                     println!("[kernel generic_open] No such file or directory");
+                    return Err(-(e.error() as isize));
                 }
 
                 // 创建新 inode
@@ -198,13 +202,39 @@ impl Ext4FileSystem {
                     // inode_table_start_block: super_block.get_inode_table_start(),
                     /// 缓存管理器
                     cache_mgr: ext4_cache_mgr,
+                    alloc_lock: Mutex::new(()),
                 };
                 ext4fs.test_info();
                 Arc::new(ext4fs)
             })
     }
+    /// 分配不属于任何文件的块（用于交换区）
+    /// # 参数
+    /// + blocks: 需要的块设备块数
+    /// # 返回值
+    /// + 块设备块号，按整个文件系统块分配，可能多于 `blocks`；空间不足时少于 `blocks`
     pub fn alloc_blocks(&self, blocks: usize) -> Vec<usize> {
-        todo!()
+        let blk_per_fs_blk = self.block_size / BLOCK_SZ;
+        let alloc_num = (blocks + blk_per_fs_blk - 1) / blk_per_fs_blk;
+        let mut block_ids = Vec::<usize>::with_capacity(alloc_num * blk_per_fs_blk);
+        let mut goal = None;
+        for _ in 0..alloc_num {
+            let block = match self.balloc_alloc(goal) {
+                Ok(block) => block,
+                Err(errno) => {
+                    log::error!(
+                        "[ext4] alloc_blocks: only {} blocks allocated",
+                        block_ids.len()
+                    );
+                    break;
+                }
+            };
+            for offset in 0..blk_per_fs_blk {
+                block_ids.push(block as usize * blk_per_fs_blk + offset);
+            }
+            goal = Some(block + 1);
+        }
+        block_ids
     }
     fn root_inode(&self) -> Arc<dyn InodeTrait> {
         todo!();
@@ -223,6 +253,15 @@ impl Ext4FileSystem {
         let r = self.generic_open(path, &mut parent, true, filetype.bits(), &mut nameoff);
         Ok(EOK)
     }
+    /// 删除父目录中名为 `name` 的目录项，并减少子inode的链接数
+    /// # 参数
+    /// + parent: 父目录
+    /// + child: 目录项指向的inode
+    /// + name: 目录项名
+    /// # 说明
+    /// + 目录的 "." 与子目录中的 ".." 也是链接，删除目录时子目录链接数清零，父目录链接数减一
+    /// + 链接数降为 0 时释放数据块与inode，并记下删除时间
+    /// + 父目录与子inode都会写回磁盘
     pub fn unlink(
         &self,
         parent: &mut Ext4InodeRef,
@@ -232,11 +271,48 @@ impl Ext4FileSystem {
         self.dir_remove_entry(parent, name)?;
 
         let is_dir = child.inode.is_dir();
+        let now = crate::timer::realtime_now().tv_sec as u32;
+        if is_dir {
+            child.inode.set_links_count(0);
+            let links = parent.inode.links_count();
+            if links > 2 {
+                parent.inode.set_links_count(links - 1);
+            }
+        } else {
+            let links = child.inode.links_count();
+            child.inode.set_links_count(links.saturating_sub(1));
+        }
+        parent.inode.set_mtime(now);
+        parent.inode.set_ctime(now);
+        self.write_back_inode(parent);
+
+        if child.inode.links_count() > 0 {
+            child.inode.set_ctime(now);
+            self.write_back_inode(child);
+            return Ok(EOK);
+        }
 
-        self.ialloc_free_inode(child.inode_num, is_dir);
+        self.truncate_inode(child, 0)?;
+        child.inode.set_dtime(now);
+        self.write_back_inode(child);
+        self.ialloc_free_inode(child.inode_num, is_dir)?;
 
         Ok(EOK)
     }
+
+    /// 按增量更新磁盘上超级块中的空闲块数与空闲inode数
+    /// # 说明
+    /// + 内存中的 `superblock` 只在挂载时读取，计数以磁盘上的超级块为准
+    /// + 调用者须持有 `alloc_lock`
+    pub fn update_superblock_counts(&self, blocks_diff: i64, inodes_diff: i32) {
+        let block = Block::load_superblock(self.block_device.clone(), 0);
+        let mut super_block = block.read_offset_as_superblock(SUPERBLOCK_OFFSET);
+        let free_blocks = super_block.free_blocks_count() as i64 + blocks_diff;
+        super_block.set_free_blocks_count(free_blocks.max(0) as u64);
+        let free_inodes = super_block.free_inodes_count() as i64 + inodes_diff as i64;
+        super_block.set_free_inodes_count(free_inodes.max(0) as u32);
+        super_block.sync_to_disk_with_csum(self.block_device.clone());
+    }
 }
 
 impl Ext4FileSystem {
@@ -1,11 +1,10 @@
-use core::panic;
-use core::{convert::TryInto, intrinsics::size_of};
+use core::convert::TryInto;
+use core::mem::size_of;
 
-use super::block_group::Block;
+use super::crc::{ext4_crc32c, EXT4_CRC32_INIT};
 use super::ext4fs::Ext4FileSystem;
 use super::*;
-use crate::fs::directory_tree::{FILE_SYSTEM, GLOBAL_BLOCK_SIZE};
-use crate::syscall::errno::SUCCESS;
+use crate::syscall::errno::{EINVAL, EIO};
 use alloc::vec;
 use alloc::vec::Vec;
 
@@ -62,40 +61,6 @@ pub struct Ext4Extent {
     pub start_lo: u32,
 }
 
-/// Extent tree node. Includes the header, the data.
-#[derive(Clone, Debug)]
-pub struct ExtentNode {
-    pub header: Ext4ExtentHeader,
-    pub data: NodeData,
-    pub is_root: bool,
-}
-
-/// Data of extent tree.
-#[derive(Clone, Debug)]
-pub enum NodeData {
-    Root([u32; 15]),
-    Internal(Vec<u8>), // size = BLOCK_SIZE
-}
-
-/// Search path in the extent tree.
-#[derive(Clone, Debug)]
-pub struct SearchPath {
-    pub depth: u16,                // 当前深度
-    pub maxdepth: u16,             // 最大深度
-    pub path: Vec<ExtentPathNode>, // search result of each level
-}
-
-/// Extent tree node search result
-#[derive(Clone, Debug)]
-pub struct ExtentPathNode {
-    pub header: Ext4ExtentHeader,       // save header for convenience
-    pub index: Option<Ext4ExtentIndex>, // for convenience(you can get index through pos of extent node)
-    pub extent: Option<Ext4Extent>,     // same reason as above
-    pub position: usize,                // position of search result in the node
-    pub pblock: u64,                    // physical block of search result
-    pub pblock_of_node: usize,          // 当前节点物理块号
-}
-
 /// load methods for Ext4ExtentHeader
 impl Ext4ExtentHeader {
     /// Load the extent header from u32 array.
@@ -174,243 +139,6 @@ impl Ext4Extent {
     }
 }
 
-impl ExtentNode {
-    /// Load the extent node from the data.
-    pub fn load_from_data(data: &[u8], is_root: bool) -> Self {
-        if is_root {
-            if data.len() != 15 * 4 {
-                // return_errno_with_message(Errno::EINVAL, "Invalid data length for root node");
-                panic!("Invalid data length for root node");
-            }
-
-            let mut root_data = [0u32; 15];
-            for (i, chunk) in data.chunks(4).enumerate() {
-                root_data[i] = u32::from_le_bytes(chunk.try_into().unwrap());
-            }
-
-            let header = Ext4ExtentHeader::load_from_u32(&root_data);
-
-            ExtentNode {
-                header,
-                data: NodeData::Root(root_data),
-                is_root,
-            }
-        } else {
-            let block_size = *GLOBAL_BLOCK_SIZE;
-            if data.len() != block_size {
-                // return_errno_with_message(Errno::EINVAL, "Invalid data length for root node");
-                panic!("Invalid data length for root node");
-            }
-            let header = Ext4ExtentHeader::load_from_u8(&data[..size_of::<Ext4ExtentHeader>()]);
-            ExtentNode {
-                header,
-                data: NodeData::Internal(data.to_vec()),
-                is_root,
-            }
-        }
-    }
-
-    /// Load the extent node from the data mutably.
-    pub fn load_from_data_mut(data: &mut [u8], is_root: bool) -> Self {
-        if is_root {
-            if data.len() != 15 * 4 {
-                // return_errno_with_message(Errno::EINVAL, "Invalid data length for root node");
-                panic!("Invalid data length for root node");
-            }
-
-            let mut root_data = [0u32; 15];
-            for (i, chunk) in data.chunks(4).enumerate() {
-                root_data[i] = u32::from_le_bytes(chunk.try_into().unwrap());
-            }
-
-            let header = *Ext4ExtentHeader::load_from_u32_mut(&mut root_data);
-
-            ExtentNode {
-                header,
-                data: NodeData::Root(root_data),
-                is_root,
-            }
-        } else {
-            let block_size = *GLOBAL_BLOCK_SIZE;
-            if data.len() != block_size {
-                panic!("Invalid data length for root node")
-            }
-            let header =
-                *Ext4ExtentHeader::load_from_u8_mut(&mut data[..size_of::<Ext4ExtentHeader>()]);
-            ExtentNode {
-                header,
-                data: NodeData::Internal(data.to_vec()),
-                is_root,
-            }
-        }
-    }
-}
-
-impl ExtentNode {
-    /// Binary search for the extent that contains the given block.
-    pub fn binsearch_extent(&mut self, lblock: Ext4Lblk) -> Option<(Ext4Extent, usize)> {
-        // 空节点
-        if self.header.entries_count == 0 {
-            match &self.data {
-                NodeData::Root(root_data) => {
-                    let extent = Ext4Extent::load_from_u32(&root_data[3..]);
-                    return Some((extent, 0));
-                }
-                NodeData::Internal(internal_data) => {
-                    let extent = Ext4Extent::load_from_u8(&internal_data[12..]);
-                    return Some((extent, 0));
-                }
-            }
-        }
-
-        match &mut self.data {
-            NodeData::Root(root_data) => {
-                let header = self.header;
-                let mut l = 1;
-                let mut r = header.entries_count as usize - 1;
-                while l <= r {
-                    let m = l + (r - l) / 2;
-                    let idx = 3 + m * 3;
-                    let ext = Ext4Extent::load_from_u32(&root_data[idx..]);
-                    if lblock < ext.first_block {
-                        r = m - 1;
-                    } else {
-                        l = m + 1;
-                    }
-                }
-                let idx = 3 + (l - 1) * 3;
-                let ext = Ext4Extent::load_from_u32(&root_data[idx..]);
-
-                Some((ext, l - 1))
-            }
-            NodeData::Internal(internal_data) => {
-                let mut l = 1;
-                let mut r = (self.header.entries_count - 1) as usize;
-                while l <= r {
-                    let m = l + (r - l) / 2;
-                    let offset = size_of::<Ext4ExtentHeader>() + m * size_of::<Ext4Extent>();
-                    let mut ext = Ext4Extent::load_from_u8_mut(&mut internal_data[offset..]);
-
-                    if lblock < ext.first_block {
-                        r = m - 1;
-                    } else {
-                        l = m + 1;
-                    }
-                }
-
-                let offset = size_of::<Ext4ExtentHeader>() + (l - 1) * size_of::<Ext4Extent>();
-                let mut ext = Ext4Extent::load_from_u8_mut(&mut internal_data[offset..]);
-                Some((ext, l - 1))
-            }
-        }
-    }
-
-    /// Binary search for the closest index of the given block.
-    /// 二分查找
-    pub fn binsearch_idx(&self, lblock: Ext4Lblk) -> Option<usize> {
-        if self.header.entries_count == 0 {
-            return None;
-        }
-
-        match &self.data {
-            NodeData::Root(root_data) => {
-                // Root node handling
-                let start = size_of::<Ext4ExtentHeader>() / 4;
-                let indexes = &root_data[start..];
-
-                let mut l = 1; // Skip the first index
-                let mut r = self.header.entries_count as usize - 1;
-
-                while l <= r {
-                    let m = l + (r - l) / 2;
-                    let offset = m * size_of::<Ext4ExtentIndex>() / 4; // Convert to u32 offset
-                    let extent_index = Ext4ExtentIndex::load_from_u32(&indexes[offset..]);
-
-                    if lblock < extent_index.first_block {
-                        if m == 0 {
-                            break; // Prevent underflow
-                        }
-                        r = m - 1;
-                    } else {
-                        l = m + 1;
-                    }
-                }
-
-                if l == 0 {
-                    return None;
-                }
-
-                Some(l - 1)
-            }
-            NodeData::Internal(internal_data) => {
-                // Internal node handling
-                let start = size_of::<Ext4ExtentHeader>();
-                let indexes = &internal_data[start..];
-
-                let mut l = 0;
-                let mut r = (self.header.entries_count - 1) as usize;
-
-                while l <= r {
-                    let m = l + (r - l) / 2;
-                    let offset = m * size_of::<Ext4ExtentIndex>();
-                    let extent_index = Ext4ExtentIndex::load_from_u8(&indexes[offset..]);
-
-                    if lblock < extent_index.first_block {
-                        if m == 0 {
-                            break; // Prevent underflow
-                        }
-                        r = m - 1;
-                    } else {
-                        l = m + 1;
-                    }
-                }
-
-                if l == 0 {
-                    return None;
-                }
-
-                Some(l - 1)
-            }
-        }
-    }
-
-    /// Get the index node at the given position.
-    pub fn get_index(&self, pos: usize) -> Ext4ExtentIndex {
-        match &self.data {
-            NodeData::Root(root_data) => {
-                let start = size_of::<Ext4ExtentHeader>() / 4;
-                let indexes = &root_data[start..];
-                let offset = pos * size_of::<Ext4ExtentIndex>() / 4;
-                Ext4ExtentIndex::load_from_u32(&indexes[offset..])
-            }
-            NodeData::Internal(internal_data) => {
-                let start = size_of::<Ext4ExtentHeader>();
-                let indexes = &internal_data[start..];
-                let offset = pos * size_of::<Ext4ExtentIndex>();
-                Ext4ExtentIndex::load_from_u8(&indexes[offset..])
-            }
-        }
-    }
-
-    /// Get the extent node at the given position.
-    pub fn get_extent(&self, pos: usize) -> Option<Ext4Extent> {
-        match &self.data {
-            NodeData::Root(root_data) => {
-                let start = size_of::<Ext4ExtentHeader>() / 4;
-                let extents = &root_data[start..];
-                let offset = pos * size_of::<Ext4Extent>() / 4;
-                Some(Ext4Extent::load_from_u32(&extents[offset..]))
-            }
-            NodeData::Internal(internal_data) => {
-                let start = size_of::<Ext4ExtentHeader>();
-                let extents = &internal_data[start..];
-                let offset = pos * size_of::<Ext4Extent>();
-                Some(Ext4Extent::load_from_u8(&extents[offset..]))
-            }
-        }
-    }
-}
-
 impl Ext4ExtentIndex {
     /// Get the physical block number to which this index points.
     pub fn get_pblock(&self) -> u64 {
@@ -520,968 +248,554 @@ impl Ext4ExtentHeader {
     }
 }
 
-impl SearchPath {
-    pub fn new() -> Self {
-        SearchPath {
-            depth: 0,
-            maxdepth: 4,
-            path: vec![],
+impl Ext4Extent {
+    /// 紧跟在本extent之后的 `next` 能否并入本extent：
+    /// 逻辑块与物理块都连续、都已写入且合并后不超过最大长度
+    fn can_append(&self, next: &Ext4Extent) -> bool {
+        let len = self.get_actual_len() as u32;
+        !self.is_unwritten()
+            && !next.is_unwritten()
+            && self.first_block as u64 + len as u64 == next.first_block as u64
+            && self.get_pblock() + len as u64 == next.get_pblock()
+            && len + next.get_actual_len() as u32 <= EXT_INIT_MAX_LEN as u32
+    }
+
+    /// 设置长度，保留未写入标记
+    fn set_len(&mut self, len: u16, unwritten: bool) {
+        self.set_actual_len(len);
+        if unwritten {
+            self.mark_unwritten();
         }
     }
 }
 
-impl Default for SearchPath {
-    fn default() -> Self {
-        Self::new()
-    }
+/// extent树的一个节点
+/// + 根节点保存在inode的 i_block 中，其余节点各占一个块
+/// + 头部之后是12字节的表项：叶子节点为 `Ext4Extent`，索引节点为 `Ext4ExtentIndex`，
+///   两者的第一个字段都是起始逻辑块号
+struct ExtentNode {
+    /// 节点所在的物理块号，根节点为 None
+    pblock: Option<Ext4Fsblk>,
+    /// 节点的原始数据，根节点为 i_block 的60字节，其余节点为整块
+    data: Vec<u8>,
 }
 
-/// for extent
-///
+const EXTENT_HEADER_SIZE: usize = size_of::<Ext4ExtentHeader>();
+const EXTENT_ENTRY_SIZE: usize = size_of::<Ext4Extent>();
 
-impl Ext4FileSystem {
-    /// Find an extent in the extent tree.
-    ///
-    /// # 参数
-    /// + inode_ref: &Ext4InodeRef - inode reference
-    /// + lblock: Ext4Lblk - logical block id
-    ///
-    /// # 返回值
-    /// + `Result<SearchPath>` - search path
-    /// # 说明
-    /// + 如果 depth > 0，则查找extent_index，查找目标 lblock 对应的 extent。
-    /// + 如果 depth = 0，则直接在root节点中查找 extent，查找目标 lblock 对应的 extent。
-    pub fn find_extent(
-        &self,
-        inode_ref: &Ext4InodeRef,
-        lblock: Ext4Lblk,
-    ) -> Result<SearchPath, isize> {
-        let mut search_path = SearchPath::new();
-
-        // Load the root node
-        let root_data: &[u8; 60] =
-            unsafe { core::mem::transmute::<&[u32; 15], &[u8; 60]>(&inode_ref.inode.block) };
-        let mut node = ExtentNode::load_from_data(root_data, true);
-
-        let mut depth = node.header.depth;
-
-        // Traverse down the tree if depth > 0
-        let mut pblock_of_node = 0;
-        while depth > 0 {
-            let index_pos = node.binsearch_idx(lblock);
-            if let Some(pos) = index_pos {
-                let index = node.get_index(pos);
-                let next_block = index.leaf_lo;
-
-                search_path.path.push(ExtentPathNode {
-                    header: node.header,
-                    index: Some(index),
-                    extent: None,
-                    position: pos,
-                    pblock: next_block as u64,
-                    pblock_of_node,
-                });
-
-                let next_block = search_path.path.last().unwrap().index.unwrap().leaf_lo;
-                let mut next_data = vec![0u8; crate::hal::BLOCK_SZ];
-                self.block_device
-                    .read_block(next_block as usize, &mut next_data);
-                node = ExtentNode::load_from_data_mut(&mut next_data, false);
-                depth -= 1;
-                search_path.depth += 1;
-                pblock_of_node = next_block as usize;
-            } else {
-                // return_errno_with_message(Errno::ENOENT, "Extentindex not found");
-                panic!("Extentindex not found");
-            }
+impl ExtentNode {
+    /// 读出inode的extent树根节点
+    fn root(inode_ref: &Ext4InodeRef) -> Result<Self, isize> {
+        let mut data = vec![0u8; size_of::<[u32; 15]>()];
+        for (chunk, word) in data.chunks_mut(4).zip(inode_ref.inode.block.iter()) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        let node = Self { pblock: None, data };
+        if !node.is_valid() {
+            log::error!(
+                "[ext4 extent] inode {} has no valid extent tree",
+                inode_ref.inode_num
+            );
+            return Err(EIO);
         }
+        Ok(node)
+    }
 
-        // Handle the case where depth is 0
-        if let Some((extent, pos)) = node.binsearch_extent(lblock) {
-            search_path.path.push(ExtentPathNode {
-                header: node.header,
-                index: None,
-                extent: Some(extent),
-                position: pos,
-                // pblock: extent.get_pblock(),
-                pblock: lblock as u64 - extent.get_first_block() as u64 + extent.get_pblock(),
-                pblock_of_node,
-            });
-            search_path.maxdepth = node.header.depth;
-
-            Ok(search_path)
-        } else {
-            search_path.path.push(ExtentPathNode {
-                header: node.header,
-                index: None,
-                extent: None,
-                position: 0,
-                pblock: 0,
-                pblock_of_node,
-            });
-            Ok(search_path)
-        }
+    /// 位于物理块 `pblock` 的空节点
+    fn new_block(block_size: usize, pblock: Ext4Fsblk, depth: u16) -> Self {
+        let mut node = Self {
+            pblock: Some(pblock),
+            data: vec![0u8; block_size],
+        };
+        let max_entries = (block_size - EXTENT_HEADER_SIZE) / EXTENT_ENTRY_SIZE;
+        node.set_header(Ext4ExtentHeader::new(
+            EXT4_EXTENT_MAGIC,
+            0,
+            max_entries as u16,
+            depth,
+            0,
+        ));
+        node
     }
 
-    /// Insert an extent into the extent tree.
-    pub fn insert_extent(
-        &self,
-        inode_ref: &mut Ext4InodeRef,
-        newex: &mut Ext4Extent,
-    ) -> Result<(), isize> {
-        let newex_first_block = newex.first_block;
+    fn read_at<T: Copy>(&self, offset: usize) -> T {
+        let bytes = &self.data[offset..offset + size_of::<T>()];
+        unsafe { core::ptr::read_unaligned(bytes.as_ptr() as *const T) }
+    }
 
-        let mut search_path = self.find_extent(inode_ref, newex_first_block)?;
+    fn write_at<T: Copy>(&mut self, offset: usize, value: T) {
+        let bytes = &mut self.data[offset..offset + size_of::<T>()];
+        unsafe { core::ptr::write_unaligned(bytes.as_mut_ptr() as *mut T, value) }
+    }
 
-        let depth = search_path.depth as usize;
-        let node = &search_path.path[depth]; // Get the node at the current depth
+    fn header(&self) -> Ext4ExtentHeader {
+        self.read_at(0)
+    }
 
-        let at_root = node.pblock_of_node == 0;
-        let header = node.header;
+    fn set_header(&mut self, header: Ext4ExtentHeader) {
+        self.write_at(0, header)
+    }
 
-        // Node is empty (no extents)
-        if header.entries_count == 0 {
-            // If the node is empty, insert the new extent directly
-            self.insert_new_extent(inode_ref, &mut search_path, newex)?;
-            return Ok(());
-        }
+    fn entries(&self) -> usize {
+        self.header().entries_count as usize
+    }
 
-        // Insert to exsiting extent
-        if let Some(mut ex) = node.extent {
-            let pos = node.position;
-            let last_extent_pos = header.entries_count as usize - 1;
-
-            // Try to Insert to found_ext
-            // found_ext:   |<---found_ext--->|         |<---ext2--->|
-            //              20              30         50          60
-            // insert:      |<---found_ext---><---newex--->|         |<---ext2--->|
-            //              20              30            40         50          60
-            // merge:       |<---newex--->|      |<---ext2--->|
-            //              20           40      50          60
-            if self.can_merge(&ex, newex) {
-                self.merge_extent(&search_path, &mut ex, &newex)?;
-
-                if at_root {
-                    // we are at root
-                    *inode_ref.inode.root_extent_mut_at(node.position) = ex;
-                }
-                return Ok(());
-            }
+    fn depth(&self) -> u16 {
+        self.header().depth
+    }
 
-            // Insert right
-            // found_ext:   |<---found_ext--->|         |<---next_extent--->|
-            //              10               20         30                40
-            // insert:      |<---found_ext--->|<---newex---><---next_extent--->|
-            //              10               20            30                40
-            // merge:       |<---found_ext--->|<---newex--->|
-            //              10               20            40
-            if pos < last_extent_pos
-                && ((ex.first_block + ex.block_count as u32) < newex.first_block)
-            {
-                if let Some(next_extent) = self.get_extent_from_node(node, pos + 1) {
-                    if self.can_merge(&next_extent, &newex) {
-                        self.merge_extent(&search_path, newex, &next_extent)?;
-                        return Ok(());
-                    }
-                }
-            }
+    fn is_full(&self) -> bool {
+        let header = self.header();
+        header.entries_count >= header.max_entries_count
+    }
 
-            // Insert left
-            //  found_ext:  |<---found_ext--->|         |<---ext2--->|
-            //              20              30         40          50
-            // insert:   |<---prev_extent---><---newex--->|<---found_ext--->|....|<---ext2--->|
-            //           0                  10          20                 30    40          50
-            // merge:    |<---newex--->|<---found_ext--->|....|<---ext2--->|
-            //           0            20                30    40          50
-            if pos > 0 && (newex.first_block + newex.block_count as u32) < ex.first_block {
-                if let Some(mut prev_extent) = self.get_extent_from_node(node, pos - 1) {
-                    if self.can_merge(&prev_extent, &newex) {
-                        self.merge_extent(&search_path, &mut prev_extent, &newex)?;
-                        return Ok(());
-                    }
-                }
-            }
-        }
+    /// 魔数正确，且表项数不超过节点能容纳的数目
+    fn is_valid(&self) -> bool {
+        let header = self.header();
+        let capacity = (self.data.len() - EXTENT_HEADER_SIZE) / EXTENT_ENTRY_SIZE;
+        header.magic == EXT4_EXTENT_MAGIC
+            && header.entries_count <= header.max_entries_count
+            && header.max_entries_count as usize <= capacity
+    }
 
-        // Check if there's space to insert the new extent
-        //                full         full
-        // Before:   |<---ext1--->|<---ext2--->|
-        //           10           20          30
+    /// 块节点尾部校验和的位置，紧跟在所有表项之后
+    fn tail_offset(&self) -> usize {
+        EXTENT_HEADER_SIZE + self.header().max_entries_count as usize * EXTENT_ENTRY_SIZE
+    }
 
-        //                full          full
-        // insert:   |<---ext1--->|<---ext2--->|<---newex--->|
-        //           10           20           30           35
-        if header.entries_count < header.max_entries_count {
-            self.insert_new_extent(inode_ref, &mut search_path, newex)?;
-        } else {
-            // Create a new leaf node
-            self.create_new_leaf(inode_ref, &mut search_path, newex)?;
-        }
+    fn entry_offset(pos: usize) -> usize {
+        EXTENT_HEADER_SIZE + pos * EXTENT_ENTRY_SIZE
+    }
 
-        Ok(())
+    /// 第 `pos` 项的起始逻辑块号
+    fn first_block(&self, pos: usize) -> Ext4Lblk {
+        self.read_at(Self::entry_offset(pos))
     }
 
-    /// Get extent from the node at the given position.
-    fn get_extent_from_node(&self, node: &ExtentPathNode, pos: usize) -> Option<Ext4Extent> {
-        let mut data = vec![0u8; crate::hal::BLOCK_SZ];
-        self.block_device
-            .read_block(node.pblock as usize, &mut data);
-        let extent_node = ExtentNode::load_from_data(&data, false);
+    fn set_first_block(&mut self, pos: usize, lblock: Ext4Lblk) {
+        self.write_at(Self::entry_offset(pos), lblock)
+    }
 
-        extent_node.get_extent(pos)
+    fn extent(&self, pos: usize) -> Ext4Extent {
+        self.read_at(Self::entry_offset(pos))
     }
 
-    /// Check if two extents can be merged.
-    fn can_merge(&self, ex1: &Ext4Extent, ex2: &Ext4Extent) -> bool {
-        // Check if the extents have the same unwritten state
-        if ex1.is_unwritten() != ex2.is_unwritten() {
-            return false;
-        }
+    fn set_extent(&mut self, pos: usize, extent: &Ext4Extent) {
+        self.write_at(Self::entry_offset(pos), *extent)
+    }
 
-        let ext1_ee_len = ex1.get_actual_len();
-        let ext2_ee_len = ex2.get_actual_len();
+    fn index(&self, pos: usize) -> Ext4ExtentIndex {
+        self.read_at(Self::entry_offset(pos))
+    }
 
-        // Check if the block ranges are contiguous
-        if ex1.first_block + ext1_ee_len as u32 != ex2.first_block {
-            return false;
-        }
+    /// 第一个起始逻辑块号大于 `lblock` 的表项的位置
+    fn upper_bound(&self, lblock: Ext4Lblk) -> usize {
+        let entries = self.entries();
+        (0..entries)
+            .find(|&pos| self.first_block(pos) > lblock)
+            .unwrap_or(entries)
+    }
 
-        // Check if the merged length would exceed the maximum allowed length
-        if ext1_ee_len + ext2_ee_len > EXT_INIT_MAX_LEN {
-            return false;
-        }
+    fn set_entries(&mut self, entries: usize) {
+        let mut header = self.header();
+        header.set_entries_count(entries as u16);
+        self.set_header(header);
+    }
 
-        // Check if the physical blocks are contiguous
-        if ex1.get_pblock() + ext1_ee_len as u64 == ex2.get_pblock() {
-            return true;
-        }
-        false
+    /// 在 `pos` 处插入一项，之后的表项后移，调用者保证节点未满
+    fn insert_entry<T: Copy>(&mut self, pos: usize, entry: T) {
+        let entries = self.entries();
+        self.data.copy_within(
+            Self::entry_offset(pos)..Self::entry_offset(entries),
+            Self::entry_offset(pos + 1),
+        );
+        self.write_at(Self::entry_offset(pos), entry);
+        self.set_entries(entries + 1);
+    }
+
+    /// 删除第 `pos` 项，之后的表项前移
+    fn remove_entry(&mut self, pos: usize) {
+        let entries = self.entries();
+        self.data.copy_within(
+            Self::entry_offset(pos + 1)..Self::entry_offset(entries),
+            Self::entry_offset(pos),
+        );
+        self.data[Self::entry_offset(entries - 1)..Self::entry_offset(entries)].fill(0);
+        self.set_entries(entries - 1);
     }
 
-    fn merge_extent(
+    /// 把第 `from` 项及之后的表项移到空节点 `other` 中
+    fn move_entries_to(&mut self, from: usize, other: &mut ExtentNode) {
+        let entries = self.entries();
+        let range = Self::entry_offset(from)..Self::entry_offset(entries);
+        let len = range.len();
+        other.data[EXTENT_HEADER_SIZE..EXTENT_HEADER_SIZE + len]
+            .copy_from_slice(&self.data[range.clone()]);
+        other.set_entries(entries - from);
+        self.data[range].fill(0);
+        self.set_entries(from);
+    }
+}
+
+/// extent树的读写
+/// + 树中的每个索引的起始逻辑块号与其子节点第一项的相同
+/// + 修改后的根节点只写入 `inode_ref`，由调用者写回inode
+impl Ext4FileSystem {
+    /// 查找逻辑块对应的物理块
+    /// # 参数
+    /// + inode_ref: 文件的inode
+    /// + lblock: 逻辑块号
+    /// # 返回值
+    /// + 物理块号与是否为未写入的extent，空洞时为 None
+    pub fn find_extent_block(
         &self,
-        search_path: &SearchPath,
-        left_ext: &mut Ext4Extent,
-        right_ext: &Ext4Extent,
-    ) -> Result<(), isize> {
-        let unwritten = left_ext.is_unwritten();
-        let len = left_ext.get_actual_len() + right_ext.get_actual_len();
-        left_ext.set_actual_len(len);
-        if unwritten {
-            left_ext.mark_unwritten();
-        }
-        let depth = search_path.depth as usize;
-
-        let header = search_path.path[depth].header;
-
-        if header.max_entries_count > 4 {
-            let node = &search_path.path[depth];
-            let block = node.pblock_of_node;
-            let new_ex_offset = core::mem::size_of::<Ext4ExtentHeader>()
-                + core::mem::size_of::<Ext4Extent>() * (node.position);
-            let mut ext4block = Block::load_offset(self.block_device.clone(), block * self.block_size);
-            let left_ext: &mut Ext4Extent = ext4block.read_offset_as_mut(new_ex_offset);
-            let unwritten = left_ext.is_unwritten();
-            let len = left_ext.get_actual_len() + right_ext.get_actual_len();
-            left_ext.set_actual_len(len);
-            if unwritten {
-                left_ext.mark_unwritten();
+        inode_ref: &Ext4InodeRef,
+        lblock: Ext4Lblk,
+    ) -> Result<Option<(Ext4Fsblk, bool)>, isize> {
+        let mut node = ExtentNode::root(inode_ref)?;
+        loop {
+            let pos = node.upper_bound(lblock);
+            if pos == 0 {
+                return Ok(None);
+            }
+            if node.depth() == 0 {
+                let extent = node.extent(pos - 1);
+                let offset = lblock - extent.first_block;
+                if offset >= extent.get_actual_len() as u32 {
+                    return Ok(None);
+                }
+                return Ok(Some((
+                    extent.get_pblock() + offset as u64,
+                    extent.is_unwritten(),
+                )));
             }
-
-            ext4block.sync_blk_to_disk(self.block_device.clone());
+            let index = node.index(pos - 1);
+            node = self.load_extent_node(inode_ref, index.get_pblock(), node.depth() - 1)?;
         }
+    }
 
-        Ok(())
+    /// 查找逻辑块对应的物理块，空洞与未写入的extent都返回 None，读出来都是0
+    pub fn find_pblock(
+        &self,
+        inode_ref: &Ext4InodeRef,
+        lblock: Ext4Lblk,
+    ) -> Result<Option<Ext4Fsblk>, isize> {
+        Ok(match self.find_extent_block(inode_ref, lblock)? {
+            Some((pblock, false)) => Some(pblock),
+            _ => None,
+        })
     }
 
-    fn insert_new_extent(
+    /// 把物理块 `pblock` 起的 `len` 个块映射到逻辑块 `lblock` 起的范围
+    /// # 参数
+    /// + inode_ref: 文件的inode，树中的新块计入它的 i_blocks
+    /// + lblock: 起始逻辑块号，调用者保证这一范围尚未映射
+    /// + pblock: 起始物理块号
+    /// + len: 块数，不超过 `EXT_INIT_MAX_LEN`
+    /// # 说明
+    /// + 能与前后的extent合并时直接合并，否则插入新的extent，节点满时分裂，根节点满时树高加一
+    pub fn insert_extent(
         &self,
         inode_ref: &mut Ext4InodeRef,
-        search_path: &mut SearchPath,
-        new_extent: &mut Ext4Extent,
+        lblock: Ext4Lblk,
+        pblock: Ext4Fsblk,
+        len: u32,
     ) -> Result<(), isize> {
-        let depth = search_path.depth as usize;
-        let node = &mut search_path.path[depth]; // Get the node at the current depth
-        let header = node.header;
-
-        // insert at root
-        if depth == 0 {
-            // Node is empty (no extents)
-            if header.entries_count == 0 {
-                *inode_ref.inode.root_extent_mut_at(node.position) = *new_extent;
-                inode_ref.inode.root_extent_header_mut().entries_count += 1;
-
-                self.write_back_inode(inode_ref);
-                return Ok(());
-            }
-            // Not empty, insert at search result pos + 1
-            log::trace!(
-                "insert newex at pos {:x?} current entry_count {:x?} ex {:x?}",
-                node.position + 1,
-                header.entries_count,
-                new_extent
-            );
-            *inode_ref.inode.root_extent_mut_at(node.position + 1) = *new_extent;
-            inode_ref.inode.root_extent_header_mut().entries_count += 1;
-            return Ok(());
-        } else {
-            // insert at nonroot
-            // log::trace!(
-            //     "insert newex at pos {:x?} current entry_count {:x?} ex {:x?}",
-            //     node.position + 1,
-            //     header.entries_count,
-            //     new_extent
-            // );
-
-            // load block
-            let node_block = node.pblock_of_node;
-            let mut ext4block =
-                Block::load_offset(self.block_device.clone(), node_block * self.block_size);
-            let new_ex_offset = core::mem::size_of::<Ext4ExtentHeader>()
-                + core::mem::size_of::<Ext4Extent>() * (node.position + 1);
-
-            // insert new extent
-            let ex: &mut Ext4Extent = ext4block.read_offset_as_mut(new_ex_offset);
-            *ex = *new_extent;
-            let header: &mut Ext4ExtentHeader = ext4block.read_offset_as_mut(0);
-
-            // update entry count
-            header.entries_count += 1;
-
-            // sync to disk
-            ext4block.sync_blk_to_disk(self.block_device.clone());
-
-            return Ok(());
+        if len == 0 || len > EXT_INIT_MAX_LEN as u32 {
+            return Err(EINVAL);
         }
-
-        // panic!("Not supported insert extent at nonroot");
+        let mut extent = Ext4Extent::default();
+        extent.set_first_block(lblock);
+        extent.store_pblock(pblock);
+        extent.set_actual_len(len as u16);
+        self.insert_extent_entry(inode_ref, &extent)
     }
 
-    // finds empty index and adds new leaf. if no free index is found, then it requests in-depth growing.
-    fn create_new_leaf(
+    fn insert_extent_entry(
         &self,
         inode_ref: &mut Ext4InodeRef,
-        search_path: &mut SearchPath,
-        new_extent: &mut Ext4Extent,
+        extent: &Ext4Extent,
     ) -> Result<(), isize> {
-        // log::info!("search path {:x?}", search_path);
-
-        // tree is full, time to grow in depth
-        self.ext_grow_indepth(inode_ref);
-
-        // insert again
-        self.insert_extent(inode_ref, new_extent)
-    }
-
-    // allocates new block
-    // moves top-level data (index block or leaf) into the new block
-    // initializes new top-level, creating index that points to the
-    // just created block
-    fn ext_grow_indepth(&self, inode_ref: &mut Ext4InodeRef) -> Result<(), isize> {
-        // Try to prepend new index to old one
-        let new_block = self.balloc_alloc_block(inode_ref, None)?;
-
-        // load new block
-        let mut new_ext4block =
-            Block::load_offset(self.block_device.clone(), new_block as usize * self.block_size);
-
-        // move top-level index/leaf into new block
-        let data_to_copy = &inode_ref.inode.block;
-        let data_to_copy = data_to_copy.as_ptr() as *const u8;
-        unsafe {
-            core::ptr::copy_nonoverlapping(data_to_copy, new_ext4block.data.as_mut_ptr(), 60)
-        };
-
-        // zero out unused area in the extent block
-        new_ext4block.data[60..].fill(0);
-
-        // set new block header
-        let new_header = Ext4ExtentHeader::load_from_u8_mut(&mut new_ext4block.data);
-        new_header.set_magic();
-        let space = (self.block_size - core::mem::size_of::<Ext4ExtentHeader>())
-            / core::mem::size_of::<Ext4Extent>();
-        new_header.set_max_entries_count(space as u16);
-
-        // Update top-level index: num,max,pointer
-        let root_header = inode_ref.inode.root_extent_header_mut();
-        root_header.set_entries_count(1);
-        root_header.add_depth();
-
-        let root_depth = root_header.depth;
-        let root_first_extent_block = inode_ref.inode.root_extent_at(0).first_block;
-        let root_first_index = inode_ref.inode.root_first_index_mut();
-        root_first_index.store_pblock(new_block);
-        if root_depth == 0 {
-            // Root extent block becomes index block
-            root_first_index.first_block = root_first_extent_block;
-        }
-
-        new_ext4block.sync_blk_to_disk(self.block_device.clone());
-        self.write_back_inode(inode_ref);
-
+        let mut root = ExtentNode::root(inode_ref)?;
+        // 根节点满时在 insert_entry_split 中增加树高，不会返回新的兄弟节点
+        self.insert_into_node(inode_ref, &mut root, extent)?;
         Ok(())
     }
-}
 
-impl Ext4FileSystem {
-    // Assuming init state
-    // depth 0 (root node)
-    // +--------+--------+--------+
-    // |  idx1  |  idx2  |  idx3  |
-    // +--------+--------+--------+
-    //     |         |         |
-    //     v         v         v
-    //
-    // depth 1 (internal node)
-    // +--------+...+--------+  +--------+...+--------+ ......
-    // |  idx1  |...|  idxn  |  |  idx1  |...|  idxn  | ......
-    // +--------+...+--------+  +--------+...+--------+ ......
-    //     |           |         |             |
-    //     v           v         v             v
-    //
-    // depth 2 (leaf nodes)
-    // +--------+...+--------+  +--------+...+--------+  ......
-    // | ext1   |...| extn   |  | ext1   |...| extn   |  ......
-    // +--------+...+--------+  +--------+...+--------+  ......
-    pub fn extent_remove_space(
+    /// 把extent插入以 `node` 为根的子树
+    /// # 返回值
+    /// + `node` 分裂时，新的兄弟节点在父节点中的索引
+    fn insert_into_node(
         &self,
         inode_ref: &mut Ext4InodeRef,
-        from: u32,
-        to: u32,
-    ) -> Result<usize, isize> {
-        // log::info!("Remove space from {:x?} to {:x?}", from, to);
-        let mut search_path = self.find_extent(inode_ref, from)?;
-
-        // for i in search_path.path.iter() {
-        //     log::info!("from Path: {:x?}", i);
-        // }
-
-        let depth = search_path.depth as usize;
-
-        /* If we do remove_space inside the range of an extent */
-        let mut ex = search_path.path[depth].extent.unwrap();
-        if ex.get_first_block() < from
-            && to < (ex.get_first_block() + ex.get_actual_len() as u32 - 1)
-        {
-            let mut newex = Ext4Extent::default();
-            let unwritten = ex.is_unwritten();
-            let ee_block = ex.first_block;
-            let block_count = ex.block_count;
-            let newblock = to + 1 - ee_block + ex.get_pblock() as u32;
-            ex.block_count = from as u16 - ee_block as u16;
-
-            if unwritten {
-                ex.mark_unwritten();
-            }
-            newex.first_block = to + 1;
-            newex.block_count = (ee_block + block_count as u32 - 1 - to) as u16;
-            newex.start_lo = newblock;
-            newex.start_hi = ((newblock as u64) >> 32) as u16;
-
-            self.insert_extent(inode_ref, &mut newex)?;
-
-            return Ok(SUCCESS as usize);
-        }
-
-        // log::warn!("Remove space in depth: {:x?}", depth);
-
-        let mut i = depth as isize;
-
-        while i >= 0 {
-            // we are at the leaf node
-            // depth 0 (root node)
-            // +--------+--------+--------+
-            // |  idx1  |  idx2  |  idx3  |
-            // +--------+--------+--------+
-            //              |path
-            //              v
-            //              idx2
-            // depth 1 (internal node)
-            // +--------+--------+--------+ ......
-            // |  idx1  |  idx2  |  idx3  | ......
-            // +--------+--------+--------+ ......
-            //              |path
-            //              v
-            //              ext2
-            // depth 2 (leaf nodes)
-            // +--------+--------+..+--------+
-            // | ext1   | ext2   |..|last_ext|
-            // +--------+--------+..+--------+
-            //            ^            ^
-            //            |            |
-            //            from         to(exceed last ext, rest of the extents will be removed)
-            if i as usize == depth {
-                let node_pblock = search_path.path[i as usize].pblock_of_node;
-
-                let header = search_path.path[i as usize].header;
-                let entries_count = header.entries_count;
-
-                // we are at root
-                if node_pblock == 0 {
-                    let first_ex = inode_ref.inode.root_extent_at(0);
-                    let last_ex = inode_ref.inode.root_extent_at(entries_count as usize - 1);
-
-                    let mut leaf_from = first_ex.first_block;
-                    let mut leaf_to = last_ex.first_block + last_ex.get_actual_len() as u32 - 1;
-                    if leaf_from < from {
-                        leaf_from = from;
+        node: &mut ExtentNode,
+        extent: &Ext4Extent,
+    ) -> Result<Option<Ext4ExtentIndex>, isize> {
+        let pos = node.upper_bound(extent.first_block);
+        if node.depth() == 0 {
+            let entries = node.entries();
+            if pos > 0 {
+                let mut prev = node.extent(pos - 1);
+                if prev.can_append(extent) {
+                    prev.set_actual_len(prev.get_actual_len() + extent.get_actual_len());
+                    // 新的extent正好填上前后两个extent之间的空洞
+                    if pos < entries && prev.can_append(&node.extent(pos)) {
+                        let next = node.extent(pos);
+                        prev.set_actual_len(prev.get_actual_len() + next.get_actual_len());
+                        node.remove_entry(pos);
                     }
-                    if leaf_to > to {
-                        leaf_to = to;
-                    }
-                    // log::trace!("from {:x?} to {:x?} leaf_from {:x?} leaf_to {:x?}", from, to, leaf_from, leaf_to);
-                    self.ext_remove_leaf(inode_ref, &mut search_path, leaf_from, leaf_to)?;
-
-                    i -= 1;
-                    continue;
+                    node.set_extent(pos - 1, &prev);
+                    self.store_extent_node(inode_ref, node);
+                    return Ok(None);
                 }
-                let ext4block =
-                    Block::load_offset(self.block_device.clone(), node_pblock * self.block_size);
-
-                let header = search_path.path[i as usize].header;
-                let entries_count = header.entries_count;
-
-                let first_ex: Ext4Extent = ext4block.read_offset_as(size_of::<Ext4ExtentHeader>());
-                let last_ex: Ext4Extent = ext4block.read_offset_as(
-                    size_of::<Ext4ExtentHeader>()
-                        + size_of::<Ext4Extent>() * (entries_count - 1) as usize,
-                );
-
-                let mut leaf_from = first_ex.first_block;
-                let mut leaf_to = last_ex.first_block + last_ex.get_actual_len() as u32 - 1;
-
-                if leaf_from < from {
-                    leaf_from = from;
-                }
-                if leaf_to > to {
-                    leaf_to = to;
-                }
-                // log::trace!(
-                //     "from {:x?} to {:x?} leaf_from {:x?} leaf_to {:x?}",
-                //     from,
-                //     to,
-                //     leaf_from,
-                //     leaf_to
-                // );
-
-                self.ext_remove_leaf(inode_ref, &mut search_path, leaf_from, leaf_to)?;
-
-                i -= 1;
-                continue;
             }
-
-            // log::trace!("---at level---{:?}\n", i);
-
-            // we are at index
-            // example i=1, depth=2
-            // depth 0 (root node) - 处理的索引节点
-            // +--------+--------+--------+
-            // |  idx1  |  idx2  |  idx3  |
-            // +--------+--------+--------+
-            //            |path     | 下一个要处理的节点(more_to_rm?)
-            //            v         v
-            //           idx2
-            //
-            // depth 1 (internal node)
-            // +--------++--------+...+--------+
-            // |  idx1  ||  idx2  |...|  idxn  |
-            // +--------++--------+...+--------+
-            //            |path
-            //            v
-            //            ext2
-            // depth 2 (leaf nodes)
-            // +--------+--------+..+--------+
-            // | ext1   | ext2   |..|last_ext|
-            // +--------+--------+..+--------+
-            let header = search_path.path[i as usize].header;
-            if self.more_to_rm(&search_path.path[i as usize], to) {
-                // todo
-                // load next idx
-
-                // go to this node's child
-                i += 1;
-            } else {
-                if i > 0 {
-                    // empty
-                    if header.entries_count == 0 {
-                        self.ext_remove_idx(inode_ref, &mut search_path, i as u16 - 1)?;
-                    }
+            if pos < entries {
+                let mut next = node.extent(pos);
+                if extent.can_append(&next) {
+                    next.set_first_block(extent.first_block);
+                    next.store_pblock(extent.get_pblock());
+                    next.set_actual_len(extent.get_actual_len() + next.get_actual_len());
+                    node.set_extent(pos, &next);
+                    self.store_extent_node(inode_ref, node);
+                    return Ok(None);
                 }
-
-                let idx = i;
-                if idx - 1 < 0 {
-                    break;
+            }
+            return self.insert_entry_split(inode_ref, node, pos, *extent);
+        }
+
+        // 索引节点：进入覆盖该逻辑块的子树，比所有索引都小时进入第一棵
+        let pos = pos.max(1) - 1;
+        let index = node.index(pos);
+        let mut child = self.load_extent_node(inode_ref, index.get_pblock(), node.depth() - 1)?;
+        let sibling = self.insert_into_node(inode_ref, &mut child, extent)?;
+        let first_block = child.first_block(0);
+        let moved = first_block != index.first_block;
+        node.set_first_block(pos, first_block);
+        match sibling {
+            Some(sibling) => self.insert_entry_split(inode_ref, node, pos + 1, sibling),
+            None => {
+                if moved {
+                    self.store_extent_node(inode_ref, node);
                 }
-                i -= 1;
+                Ok(None)
             }
         }
-
-        Ok(EOK)
     }
 
-    pub fn ext_remove_leaf(
+    /// 在 `node` 的 `pos` 处插入表项并写回，节点满时先分裂
+    /// # 返回值
+    /// + 分裂出的新节点在父节点中的索引，根节点不分裂而是把内容移到新块中、树高加一
+    fn insert_entry_split<T: Copy>(
         &self,
         inode_ref: &mut Ext4InodeRef,
-        path: &mut SearchPath,
-        from: u32,
-        to: u32,
-    ) -> Result<usize, isize> {
-        // log::trace!("Remove leaf from {:x?} to {:x?}", from, to);
-
-        // depth 0 (root node)
-        // +--------+--------+--------+
-        // |  idx1  |  idx2  |  idx3  |
-        // +--------+--------+--------+
-        //     |         |         |
-        //     v         v         v
-        //     ^
-        //     Current position
-        let depth = inode_ref.inode.root_header_depth();
-        let mut header = path.path[depth as usize].header;
-
-        let mut new_entry_count = header.entries_count;
-        let mut ex2 = Ext4Extent::default();
-
-        /* find where to start removing */
-        let pos = path.path[depth as usize].position;
-        let entry_count = header.entries_count;
-
-        // depth 1 (internal node)
-        // +--------+...+--------+  +--------+...+--------+ ......
-        // |  idx1  |...|  idxn  |  |  idx1  |...|  idxn  | ......
-        // +--------+...+--------+  +--------+...+--------+ ......
-        //     |           |         |             |
-        //     v           v         v             v
-        //     ^
-        //     Current loaded node
-
-        // load node data
-        let node_disk_pos = path.path[depth as usize].pblock_of_node * self.block_size;
-
-        let mut ext4block = if node_disk_pos == 0 {
-            // we are at root
-            Block::load_inode_root_block(&inode_ref.inode.block)
+        node: &mut ExtentNode,
+        pos: usize,
+        entry: T,
+    ) -> Result<Option<Ext4ExtentIndex>, isize> {
+        if !node.is_full() {
+            node.insert_entry(pos, entry);
+            self.store_extent_node(inode_ref, node);
+            return Ok(None);
+        }
+
+        let depth = node.depth();
+        let entries = node.entries();
+        let new_pblock =
+            self.balloc_alloc_block(inode_ref, node.pblock.map(|pblock| pblock + 1))?;
+        let mut new_node = ExtentNode::new_block(self.block_size, new_pblock, depth);
+        let mut index = Ext4ExtentIndex::default();
+        index.store_pblock(new_pblock);
+
+        if node.pblock.is_none() {
+            node.move_entries_to(0, &mut new_node);
+            new_node.insert_entry(pos, entry);
+            self.store_extent_node(inode_ref, &mut new_node);
+
+            index.first_block = new_node.first_block(0);
+            let mut header = node.header();
+            header.set_depth(depth + 1);
+            node.set_header(header);
+            node.insert_entry(0, index);
+            self.store_extent_node(inode_ref, node);
+            return Ok(None);
+        }
+
+        // 在末尾追加时（顺序写文件）新节点只放新的一项，原节点保持满，否则移走后一半
+        let split = if pos == entries { entries } else { entries / 2 };
+        node.move_entries_to(split, &mut new_node);
+        if pos < split {
+            node.insert_entry(pos, entry);
         } else {
-            Block::load_offset(self.block_device.clone(), node_disk_pos)
-        };
-
-        // depth 2 (leaf nodes)
-        // +--------+...+--------+  +--------+...+--------+  ......
-        // | ext1   |...| extn   |  | ext1   |...| extn   |  ......
-        // +--------+...+--------+  +--------+...+--------+  ......
-        //     ^
-        //     Current start extent
-
-        // start from pos
-        for i in pos..entry_count as usize {
-            let ex: &mut Ext4Extent = ext4block
-                .read_offset_as_mut(size_of::<Ext4ExtentHeader>() + i * size_of::<Ext4Extent>());
-
-            if ex.first_block > to {
-                break;
-            }
-
-            let mut new_len = 0;
-            let mut start = ex.first_block;
-            let mut new_start = ex.first_block;
-
-            let mut len = ex.get_actual_len();
-            let mut newblock = ex.get_pblock();
-
-            // Initial state:
-            // +--------+...+--------+  +--------+...+--------+  ......
-            // | ext1   |...| ext2   |  | ext3   |...| extn   |  ......
-            // +--------+...+--------+  +--------+...+--------+  ......
-            //               ^                    ^
-            //              from                  to
-
-            // Case 1: Remove a portion within the extent
-            if start < from {
-                len -= from as u16 - start as u16;
-                new_len = from - start;
-                start = from;
-            } else {
-                // Case 2: Adjust extent that partially overlaps the 'to' boundary
-                if start + len as u32 - 1 > to {
-                    new_len = start + len as u32 - 1 - to;
-                    len -= new_len as u16;
-                    new_start = to + 1;
-                    newblock += (to + 1 - start) as u64;
-                    ex2 = *ex;
-                }
-            }
-
-            // After removing range from `from` to `to`:
-            // +--------+...+--------+  +--------+...+--------+  ......
-            // | ext1   |...[removed]|  |[removed]|...| extn   |  ......
-            // +--------+...+--------+  +--------+...+--------+  ......
-            //               ^                    ^
-            //              from                  to
-            //                                  new_start
-
-            // Remove blocks within the extent
-            self.ext_remove_blocks(inode_ref, ex, start, start + len as u32 - 1);
-
-            ex.first_block = new_start;
-            // log::trace!("after remove leaf ex first_block {:x?}", ex.first_block);
-
-            if new_len == 0 {
-                new_entry_count -= 1;
-            } else {
-                let unwritten = ex.is_unwritten();
-                ex.store_pblock(newblock as u64);
-                ex.block_count = new_len as u16;
-
-                if unwritten {
-                    ex.mark_unwritten();
-                }
-            }
-        }
-
-        // Move remaining extents to the start:
-        // Before:
-        // +--------+--------+...+--------+
-        // | ext3   | ext4   |...| extn   |
-        // +--------+--------+...+--------+
-        //      ^       ^
-        //      rm      rm
-        // After:
-        // +--------+.+--------+--------+...
-        // | ext1   |.| extn   | [empty]|...
-        // +--------+.+--------+--------+...
-
-        // Move any remaining extents to the starting position of the node.
-        if ex2.first_block > 0 {
-            let start_index = size_of::<Ext4ExtentHeader>() + pos * size_of::<Ext4Extent>();
-            let end_index =
-                size_of::<Ext4ExtentHeader>() + entry_count as usize * size_of::<Ext4Extent>();
-            let remaining_extents: Vec<u8> = ext4block.data[start_index..end_index].to_vec();
-            ext4block.data[size_of::<Ext4ExtentHeader>()
-                ..size_of::<Ext4ExtentHeader>() + remaining_extents.len()]
-                .copy_from_slice(&remaining_extents);
+            new_node.insert_entry(pos - split, entry);
         }
+        self.store_extent_node(inode_ref, &mut new_node);
+        self.store_extent_node(inode_ref, node);
 
-        // Update the entries count in the header
-        header.entries_count = new_entry_count;
-
-        /*
-         * If the extent pointer is pointed to the first extent of the node, and
-         * there's still extents presenting, we may need to correct the indexes
-         * of the paths.
-         */
-        if pos == 0 && new_entry_count > 0 {
-            self.ext_correct_indexes(path)?;
-        }
-
-        /* if this leaf is free, then we should
-         * remove it from index block above */
-        if new_entry_count == 0 {
-            // if we are at root?
-            if path.path[depth as usize].pblock_of_node == 0 {
-                return Ok(EOK);
-            }
-            self.ext_remove_idx(inode_ref, path, depth - 1)?;
-        } else if depth > 0 {
-            // go to next index
-            path.path[depth as usize - 1].position += 1;
-        }
-
-        Ok(EOK)
-    }
-
-    fn ext_remove_index_block(&self, inode_ref: &mut Ext4InodeRef, index: &mut Ext4ExtentIndex) {
-        let block_to_free = index.get_pblock();
-
-        // log::trace!("remove index's block {:x?}", block_to_free);
-        self.balloc_free_blocks(inode_ref, block_to_free as _, 1);
+        index.first_block = new_node.first_block(0);
+        Ok(Some(index))
     }
 
-    fn ext_remove_idx(
+    /// 删除逻辑块 `from` 到 `to`（包含）的映射并释放对应的数据块
+    /// # 说明
+    /// + 被截断的extent缩短，删空的树节点被释放，整棵树删空时根节点恢复为深度0
+    /// + 删除范围在一个extent中间时，该extent被拆成两个
+    pub fn extent_remove_space(
         &self,
         inode_ref: &mut Ext4InodeRef,
-        path: &mut SearchPath,
-        depth: u16,
-    ) -> Result<usize, isize> {
-        // log::trace!("Remove index at depth {:x?}", depth);
-
-        // Initial state:
-        // +--------+--------+--------+
-        // |  idx1  |  idx2  |  idx3  |
-        // +--------+--------+--------+
-        //           ^
-        // Current index to remove (pos=1)
-
-        // Removing index:
-        // +--------+--------+--------+
-        // |  idx1  |[empty] |  idx3  |
-        // +--------+--------+--------+
-        //           ^
-        // Current index to remove
-
-        // After moving remaining indexes:
-        // +--------+--------+--------+
-        // |  idx1  |  idx3  |[empty] |
-        // +--------+--------+--------+
-        //           ^
-        // Current index to remove
-
-        let mut i = depth as usize;
-        let mut header = path.path[i].header;
-
-        // 获取要删除的索引块
-        let leaf_block = path.path[i].index.unwrap().get_pblock();
-
-        // 如果当前索引不是最后一个索引，将后续的索引前移
-        if path.path[i].position != header.entries_count as usize - 1 {
-            let start_pos = size_of::<Ext4ExtentHeader>()
-                + path.path[i].position * size_of::<Ext4ExtentIndex>();
-            let end_pos = size_of::<Ext4ExtentHeader>()
-                + (header.entries_count as usize) * size_of::<Ext4ExtentIndex>();
-
-            let node_disk_pos = path.path[i].pblock_of_node * self.block_size;
-            let mut ext4block = Block::load_offset(self.block_device.clone(), node_disk_pos);
-
-            let remaining_indexes: Vec<u8> =
-                ext4block.data[start_pos + size_of::<Ext4ExtentIndex>()..end_pos].to_vec();
-            ext4block.data[start_pos..start_pos + remaining_indexes.len()]
-                .copy_from_slice(&remaining_indexes);
-            let remaining_size = remaining_indexes.len();
-
-            // 清空剩余位置
-            let empty_start = start_pos + remaining_size;
-            let empty_end = end_pos;
-            ext4block.data[empty_start..empty_end].fill(0);
+        from: Ext4Lblk,
+        to: Ext4Lblk,
+    ) -> Result<(), isize> {
+        if from > to {
+            return Ok(());
         }
-
-        // 更新头部的entries_count
-        header.entries_count -= 1;
-
-        // 释放索引块
-        self.ext_remove_index_block(inode_ref, &mut path.path[i].index.unwrap());
-
-        // Updating parent index if necessary:
-        // +--------+--------+--------+
-        // |  idx1  |  idx3  |[empty] |
-        // +--------+--------+--------+
-        //           ^
-        // Updated parent index if necessary
-
-        // 如果当前层不是根，需要检查是否需要更新父节点索引
-        while i > 0 {
-            if path.path[i].position != 0 {
-                break;
-            }
-
-            let parent_idx = i - 1;
-            let parent_index = &mut path.path[parent_idx].index.unwrap();
-            let current_index = &path.path[i].index.unwrap();
-
-            parent_index.first_block = current_index.first_block;
-            self.write_back_inode(inode_ref);
-
-            i -= 1;
+        let mut root = ExtentNode::root(inode_ref)?;
+        let rest = self.remove_from_node(inode_ref, &mut root, from, to)?;
+        if root.entries() == 0 {
+            let mut header = root.header();
+            header.set_depth(0);
+            root.set_header(header);
         }
-
-        Ok(EOK)
-    }
-
-    /// Correct the first block of the parent index.
-    fn ext_correct_indexes(&self, path: &mut SearchPath) -> Result<usize, isize> {
-        // if child get removed from parent, we need to update the parent's first_block
-        let mut depth = path.depth as usize;
-
-        // depth 2:
-        // +--------+--------+--------+
-        // |[empty] |  ext2  |  ext3  |
-        // +--------+--------+--------+
-        // ^
-        // pos=0, ext1_first_block=0(removed) parent index first block=0
-
-        // depth 2:
-        // +--------+--------+--------+
-        // |  ext2  |  ext3  |[empty] |
-        // +--------+--------+--------+
-        // ^
-        // pos=0, now first_block=ext2_first_block
-
-        // 更新父节点索引：
-        // depth 1:
-        // +-----------------------+
-        // | idx1_2 |...| idx1_n   |
-        // +-----------------------+
-        //     ^
-        //     更新父节点索引(first_block)
-
-        // depth 0:
-        // +--------+--------+--------+
-        // |  idx1  |  idx2  |  idx3  |
-        // +--------+--------+--------+
-        //     |
-        //     更新根节点索引(first_block)
-
-        while depth > 0 {
-            let parent_idx = depth - 1;
-
-            // 获取当前层的 extent
-            if let Some(child_extent) = path.path[depth].extent {
-                // 获取父节点
-                let parent_node = &mut path.path[parent_idx];
-                // 获取父节点的索引，并更新 first_block
-                if let Some(ref mut parent_index) = parent_node.index {
-                    parent_index.first_block = child_extent.first_block;
-                }
-            }
-
-            depth -= 1;
+        self.store_extent_node(inode_ref, &mut root);
+        // 从中间拆开的extent的后半段重新插入
+        if let Some(rest) = rest {
+            self.insert_extent_entry(inode_ref, &rest)?;
         }
-
-        Ok(EOK)
+        Ok(())
     }
 
-    fn ext_remove_blocks(
+    /// 在以 `node` 为根的子树中删除 `from` 到 `to` 的映射，`node` 本身由调用者写回或释放
+    /// # 返回值
+    /// + 删除范围在一个extent中间时，该extent的后半段
+    fn remove_from_node(
         &self,
         inode_ref: &mut Ext4InodeRef,
-        ex: &mut Ext4Extent,
-        from: u32,
-        to: u32,
-    ) {
-        let len = to - from + 1;
-        let num = from - ex.first_block;
-        let start: u32 = ex.get_pblock() as u32 + num;
-        self.balloc_free_blocks(inode_ref, start as _, len);
-    }
-
-    pub fn more_to_rm(&self, path: &ExtentPathNode, to: u32) -> bool {
-        let header = path.header;
-
-        // No Sibling exists
-        if header.entries_count == 1 {
-            return false;
+        node: &mut ExtentNode,
+        from: Ext4Lblk,
+        to: Ext4Lblk,
+    ) -> Result<Option<Ext4Extent>, isize> {
+        let (from, end) = (from as u64, to as u64 + 1);
+        let mut rest = None;
+        let mut pos = 0;
+        while pos < node.entries() {
+            let start = node.first_block(pos) as u64;
+            if node.depth() == 0 {
+                let mut extent = node.extent(pos);
+                let unwritten = extent.is_unwritten();
+                let extent_end = start + extent.get_actual_len() as u64;
+                if extent_end <= from || start >= end {
+                    pos += 1;
+                    continue;
+                }
+                let cut_start = start.max(from);
+                let cut_end = extent_end.min(end);
+                self.balloc_free_blocks(
+                    inode_ref,
+                    extent.get_pblock() + (cut_start - start),
+                    (cut_end - cut_start) as u32,
+                )?;
+                if cut_start == start && cut_end == extent_end {
+                    node.remove_entry(pos);
+                    continue;
+                }
+                if cut_end < extent_end {
+                    let mut tail = extent;
+                    tail.set_first_block(cut_end as Ext4Lblk);
+                    tail.store_pblock(extent.get_pblock() + (cut_end - start));
+                    tail.set_len((extent_end - cut_end) as u16, unwritten);
+                    if cut_start == start {
+                        extent = tail;
+                    } else {
+                        extent.set_len((cut_start - start) as u16, unwritten);
+                        rest = Some(tail);
+                    }
+                } else {
+                    extent.set_len((cut_start - start) as u16, unwritten);
+                }
+                node.set_extent(pos, &extent);
+                pos += 1;
+            } else {
+                let next_start = if pos + 1 < node.entries() {
+                    node.first_block(pos + 1) as u64
+                } else {
+                    u64::MAX
+                };
+                if next_start <= from || start >= end {
+                    pos += 1;
+                    continue;
+                }
+                let index = node.index(pos);
+                let mut child =
+                    self.load_extent_node(inode_ref, index.get_pblock(), node.depth() - 1)?;
+                if let Some(tail) = self.remove_from_node(
+                    inode_ref,
+                    &mut child,
+                    from as Ext4Lblk,
+                    (end - 1) as Ext4Lblk,
+                )? {
+                    rest = Some(tail);
+                }
+                if child.entries() == 0 {
+                    self.balloc_free_blocks(inode_ref, index.get_pblock(), 1)?;
+                    node.remove_entry(pos);
+                    continue;
+                }
+                self.store_extent_node(inode_ref, &mut child);
+                node.set_first_block(pos, child.first_block(0));
+                pos += 1;
+            }
         }
+        Ok(rest)
+    }
 
-        let pos = path.position;
-        if pos > header.entries_count as usize - 1 {
-            return false;
+    /// 读出位于物理块 `pblock`、深度为 `depth` 的树节点
+    fn load_extent_node(
+        &self,
+        inode_ref: &Ext4InodeRef,
+        pblock: Ext4Fsblk,
+        depth: u16,
+    ) -> Result<ExtentNode, isize> {
+        let mut data = vec![0u8; self.block_size];
+        self.block_device.read_block(pblock as usize, &mut data);
+        let node = ExtentNode {
+            pblock: Some(pblock),
+            data,
+        };
+        if !node.is_valid() || node.depth() != depth {
+            log::error!(
+                "[ext4 extent] inode {}: bad extent block {}",
+                inode_ref.inode_num,
+                pblock
+            );
+            return Err(EIO);
         }
+        Ok(node)
+    }
 
-        // Check if index is out of bounds
-        if let Some(index) = path.index {
-            let last_index_pos = header.entries_count as usize - 1;
-            let node_disk_pos = path.pblock_of_node * self.block_size;
-            let ext4block = Block::load_offset(self.block_device.clone(), node_disk_pos);
-            let last_index: Ext4ExtentIndex =
-                ext4block.read_offset_as(size_of::<Ext4ExtentIndex>() * last_index_pos);
-
-            if path.position > last_index_pos || index.first_block > last_index.first_block {
-                return false;
-            }
-
-            // Check if index's first_block is greater than 'to'
-            if index.first_block > to {
-                return false;
+    /// 写回树节点：根节点写入inode的 i_block，块节点写回磁盘并更新尾部校验和
+    fn store_extent_node(&self, inode_ref: &mut Ext4InodeRef, node: &mut ExtentNode) {
+        let pblock = match node.pblock {
+            Some(pblock) => pblock,
+            None => {
+                for (word, chunk) in inode_ref.inode.block.iter_mut().zip(node.data.chunks(4)) {
+                    *word = u32::from_le_bytes(chunk.try_into().unwrap());
+                }
+                return;
             }
+        };
+        if self.superblock.has_metadata_csum() {
+            let tail = node.tail_offset();
+            let checksum = self.extent_block_checksum(inode_ref, &node.data[..tail]);
+            node.write_at(tail, checksum);
         }
+        self.block_device.write_block(pblock as usize, &node.data);
+    }
 
-        true
+    /// extent块的校验和：crc32c(uuid + inode号 + generation + 头部与所有表项)
+    fn extent_block_checksum(&self, inode_ref: &Ext4InodeRef, data: &[u8]) -> u32 {
+        let uuid = &self.superblock.uuid;
+        let mut checksum = ext4_crc32c(EXT4_CRC32_INIT, uuid, uuid.len() as u32);
+        checksum = ext4_crc32c(checksum, &inode_ref.inode_num.to_le_bytes(), 4);
+        checksum = ext4_crc32c(checksum, &inode_ref.inode.generation().to_le_bytes(), 4);
+        ext4_crc32c(checksum, data, data.len() as u32)
     }
 }
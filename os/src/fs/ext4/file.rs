@@ -1,9 +1,9 @@
-use crate::fs::directory_tree::{FILE_SYSTEM, GLOBAL_BLOCK_SIZE};
+use crate::fs::directory_tree::GLOBAL_BLOCK_SIZE;
+use crate::syscall::errno::EINVAL;
 
 use super::*;
 use alloc::vec::Vec;
 use alloc::vec;
-use block_group::Block;
 use ext4fs::Ext4FileSystem;
 use path::path_check;
 use spin::RwLock;
@@ -206,6 +206,9 @@ impl Ext4FileSystem {
     ///
     /// Returns:
     /// `Result<usize>` - status of the operation
+    ///
+    /// 新目录在这里写入 "." 与 ".."，链接数为2，父目录的链接数加一；
+    /// 父目录与子inode都会写回磁盘
     pub fn link(
         &self,
         parent: &mut Ext4InodeRef,
@@ -213,35 +216,38 @@ impl Ext4FileSystem {
         name: &str,
     ) -> Result<usize, isize> {
         // Add a directory entry in the parent directory pointing to the child inode
-
-        // at this point should insert to existing block
         self.dir_add_entry(parent, child, name)?;
-        self.write_back_inode_without_csum(parent);
 
         // If this is the first link. add '.' and '..' entries
         if child.inode.is_dir() {
-            // let child_ref = child.clone();
             let new_child_ref = Ext4InodeRef {
                 inode_num: child.inode_num,
                 inode: child.inode,
             };
 
             // at this point child need a new block
-            self.dir_add_entry(child, &new_child_ref, ".")?;
-
-            // at this point should insert to existing block
-            self.dir_add_entry(child, &new_child_ref, "..")?;
+            let result = self
+                .dir_add_entry(child, &new_child_ref, ".")
+                .and_then(|_| self.dir_add_entry(child, parent, ".."));
+            if let Err(errno) = result {
+                self.dir_remove_entry(parent, name)?;
+                return Err(errno);
+            }
 
             child.inode.set_links_count(2);
             let link_cnt = parent.inode.links_count() + 1;
             parent.inode.set_links_count(link_cnt);
-
-            return Ok(EOK);
+        } else {
+            // Increment the link count of the child inode
+            let link_cnt = child.inode.links_count() + 1;
+            child.inode.set_links_count(link_cnt);
         }
 
-        // Increment the link count of the child inode
-        let link_cnt = child.inode.links_count() + 1;
-        child.inode.set_links_count(link_cnt);
+        let now = crate::timer::realtime_now().tv_sec as u32;
+        parent.inode.set_mtime(now);
+        parent.inode.set_ctime(now);
+        self.write_back_inode(parent);
+        self.write_back_inode(child);
 
         Ok(EOK)
     }
@@ -255,66 +261,35 @@ impl Ext4FileSystem {
     /// # 返回值:
     /// + `Result<Ext4InodeRef>` - 新文件的inode
     pub fn create(&self, parent: u32, name: &str, inode_mode: u16) -> Result<Ext4InodeRef, isize> {
-        // println!("in1, parent:{:?}", parent);
-        // let a = 1+2+parent;
-        // log::info!("a = {:?}", a);
-        // // Ext4FileSystem被锁了？
-        // let dummy = self.get_inode_ref(a);
-        // println!("dummy = {:#?}", dummy);
-        // 获取父目录的inode
-        let mut parent_inode_ref = self.get_inode_ref(parent);
-        // println!("in2");
-        // 创建一个新inode
-        let init_child_ref = self.create_inode(inode_mode)?;
-
-        // println!("in3");
-        // 写回inode
-        // TODO: 在使用LoongsonNand的时候读和写的数据不一样
-        self.write_back_inode_without_csum(&init_child_ref);
-        let mut child_inode_ref = self.get_inode_ref(init_child_ref.inode_num);
-        // println!("in4");
-        // 链接新 inode 到父目录
-        self.link(&mut parent_inode_ref, &mut child_inode_ref, name)?;
-
-        // 写回父目录 inode
-        self.write_back_inode(&mut parent_inode_ref);
-        // 写回新 inode
-        self.write_back_inode(&mut child_inode_ref);
-
-        Ok(child_inode_ref)
+        self.create_with_attr(parent, name, inode_mode, 0, 0)
     }
 
     /// 创建inode
     /// # 参数
     /// + inode_mode: inode类型
     /// # 返回值
-    /// + 新inode
+    /// + 新inode，尚未写回磁盘
     pub fn create_inode(&self, inode_mode: u16) -> Result<Ext4InodeRef, isize> {
         // 匹配新inode的文件类型
         let inode_file_type_bits = inode_mode & EXT4_INODE_MODE_TYPE_MASK;
-        // println!(
-        //     "[kernel create_inode] inode_mode {:?}, {:?}",
-        //     inode_mode,
-        //     InodeFileType::from_bits(inode_file_type_bits)
-        // );
         let inode_file_type = match InodeFileType::from_bits(inode_file_type_bits) {
             Some(file_type) => file_type,
             None => InodeFileType::S_IFREG,
         };
-        // println!("[kernel create_inode] {:?}", inode_file_type);
 
         // 判断是否是文件夹
         let is_dir = inode_file_type == InodeFileType::S_IFDIR;
 
         // 分配inode
-        let inode_num = self.alloc_inode(is_dir);
-        if let Err(e) = inode_num {
-            return Err(e);
-        }
+        let inode_num = self.alloc_inode(is_dir)?;
 
         // 初始化inode
         let mut inode = Ext4Inode::default();
 
+        // 版本号在上一次使用该 inode 号的文件的基础上加一，旧文件的文件句柄因此失效
+        let old_generation = self.get_inode_ref(inode_num).inode.generation();
+        inode.set_generation(old_generation.wrapping_add(1));
+
         // 设置文件类型和权限
         inode.set_mode(inode_mode | 0o777);
 
@@ -326,14 +301,17 @@ impl Ext4FileSystem {
             inode.set_i_extra_isize(extra_size);
         }
 
+        let now = crate::timer::realtime_now().tv_sec as u32;
+        inode.set_atime(now);
+        inode.set_ctime(now);
+        inode.set_mtime(now);
+        inode.set_i_crtime(now);
+
         // set extent
         inode.set_flags(EXT4_INODE_FLAG_EXTENTS as u32);
         inode.extent_tree_init();
 
-        let inode_ref = Ext4InodeRef {
-            inode_num: inode_num.unwrap(),
-            inode,
-        };
+        let inode_ref = Ext4InodeRef { inode_num, inode };
 
         Ok(inode_ref)
     }
@@ -348,6 +326,7 @@ impl Ext4FileSystem {
     /// gid: u32 - group id
     ///
     /// Returns:
+    /// `Result<Ext4InodeRef>` - 新文件的inode，链接失败时新inode被回收
     pub fn create_with_attr(
         &self,
         parent: u32,
@@ -358,20 +337,19 @@ impl Ext4FileSystem {
     ) -> Result<Ext4InodeRef, isize> {
         let mut parent_inode_ref = self.get_inode_ref(parent);
 
-        // let mut child_inode_ref = self.create_inode(inode_mode)?;
-        let mut init_child_ref = self.create_inode(inode_mode)?;
-
-        init_child_ref.inode.set_uid(uid);
-        init_child_ref.inode.set_gid(gid);
+        let mut child_inode_ref = self.create_inode(inode_mode)?;
+        child_inode_ref.inode.set_uid(uid);
+        child_inode_ref.inode.set_gid(gid);
 
-        self.write_back_inode_without_csum(&init_child_ref);
-        // load new
-        let mut child_inode_ref = self.get_inode_ref(init_child_ref.inode_num);
+        // inode表中的槽位可能残留着之前的数据，整个重写
+        self.write_back_new_inode(&mut child_inode_ref);
 
-        self.link(&mut parent_inode_ref, &mut child_inode_ref, name)?;
-
-        self.write_back_inode(&mut parent_inode_ref);
-        self.write_back_inode(&mut child_inode_ref);
+        if let Err(errno) = self.link(&mut parent_inode_ref, &mut child_inode_ref, name) {
+            let is_dir = child_inode_ref.inode.is_dir();
+            self.truncate_inode(&mut child_inode_ref, 0)?;
+            self.ialloc_free_inode(child_inode_ref.inode_num, is_dir)?;
+            return Err(errno);
+        }
 
         Ok(child_inode_ref)
     }
@@ -382,89 +360,38 @@ impl Ext4FileSystem {
     /// + offset: usize - offset from where to read
     /// + read_buf: &mut [u8] - 存储读取的数据的buffer
     /// # 返回值
-    /// `Result<usize>`：读取的字节数
+    /// `Result<usize>`：读取的字节数，空洞读出来是0
     pub fn read_at(&self, inode: u32, offset: usize, read_buf: &mut [u8]) -> Result<usize, isize> {
-        // 缓冲区为空，返回 0
-        let mut read_buf_len = read_buf.len();
-        if read_buf_len == 0 {
-            return Ok(0);
-        }
-
         // 获取ext4inoderef对象
         let inode_ref = self.get_inode_ref(inode);
 
-        // 获取文件大小
-        let file_size = inode_ref.inode.size();
-
-        // 如果偏移量大于文件大小，返回 0
-        if offset >= file_size as usize {
+        // 获取文件大小，偏移量超出文件大小时读不到数据
+        let file_size = inode_ref.inode.size() as usize;
+        if offset >= file_size {
             return Ok(0);
         }
+        let size_to_read = min(read_buf.len(), file_size - offset);
 
-        // 如果 offset + read_buf_len 大于 file_size，调整读取大小
-        if offset + read_buf_len > file_size as usize {
-            read_buf_len = file_size as usize - offset;
-        }
-
-        // adjust the read buffer size if the read buffer size is greater than the file size
-        // 这步是不是和上一步重了？
-        let size_to_read = min(read_buf_len, file_size as usize - offset);
-
-        // 计算起始块以及未对齐大小
-        let iblock_start = offset / self.block_size;
-        let iblock_last = (offset + size_to_read + self.block_size - 1) / self.block_size; // round up to include the last partial block
-        let unaligned_start_offset = offset % self.block_size;
-
-        // Buffer to keep track of read bytes
+        let mut data = vec![0u8; self.block_size];
         let mut cursor = 0;
-        let mut total_bytes_read = 0;
-        let mut iblock = iblock_start;
-
-        // Unaligned read at the beginning
-        // 处理起始块未对齐的情况
-        if unaligned_start_offset > 0 {
-            let adjust_read_size = min(self.block_size - unaligned_start_offset, size_to_read);
+        while cursor < size_to_read {
+            let iblock = (offset + cursor) / self.block_size;
+            let block_offset = (offset + cursor) % self.block_size;
+            let len = min(self.block_size - block_offset, size_to_read - cursor);
+            let dst = &mut read_buf[cursor..cursor + len];
 
             // 获取逻辑块号对应的物理块号
-            let pblock_idx = self.get_pblock_idx(&inode_ref, iblock as u32)?;
-
-            // 读取数据
-            let mut data = [0u8; BLOCK_SIZE];
-            self.block_device.read_block(pblock_idx as usize, &mut data);
-
-            // 将数据复制到read_buf中
-            read_buf[cursor..cursor + adjust_read_size].copy_from_slice(
-                &data[unaligned_start_offset..unaligned_start_offset + adjust_read_size],
-            );
-
-            // 更新 cursor 以及 total_bytes_read
-            cursor += adjust_read_size;
-            total_bytes_read += adjust_read_size;
-            iblock += 1;
-        }
-
-        // Continue with full block reads
-        // 继续处理整个的块
-        while total_bytes_read < size_to_read {
-            let read_length = core::cmp::min(self.block_size, size_to_read - total_bytes_read);
-
-            // 获取逻辑块号对应的物理块号
-            let pblock_idx = self.get_pblock_idx(&inode_ref, iblock as u32)?;
-
-            // 读取数据
-            let mut data = vec![0u8; crate::hal::BLOCK_SZ];
-            self.block_device.read_block(pblock_idx as usize, &mut data);
-
-            // 将读取到的数据复制到read_buf中
-            read_buf[cursor..cursor + read_length].copy_from_slice(&data[..read_length]);
-
-            // 更新 cursor 以及 total_bytes_read
-            cursor += read_length;
-            total_bytes_read += read_length;
-            iblock += 1;
+            match self.find_pblock(&inode_ref, iblock as u32)? {
+                Some(pblock) => {
+                    self.block_device.read_block(pblock as usize, &mut data);
+                    dst.copy_from_slice(&data[block_offset..block_offset + len]);
+                }
+                None => dst.fill(0),
+            }
+            cursor += len;
         }
 
-        Ok(min(total_bytes_read, size_to_read))
+        Ok(size_to_read)
     }
 
     /// 将数据按指定的offset写入到一个文件中
@@ -475,131 +402,111 @@ impl Ext4FileSystem {
     /// write_buf: &[u8] - 要写入的buffer
     ///
     /// Returns:
-    /// `Result<usize>` - 写入的字节数
+    /// `Result<usize>` - 写入的字节数，写入部分数据后空间不足时返回已写入的字节数
     pub fn write_at(&self, inode: u32, offset: usize, write_buf: &[u8]) -> Result<usize, isize> {
         // write_buf为空, 返回0
-        let write_buf_len = write_buf.len();
-        if write_buf_len == 0 {
+        if write_buf.is_empty() {
             return Ok(0);
         }
 
         // get the inode reference
         let mut inode_ref = self.get_inode_ref(inode);
 
-        // Get the file size
-        let file_size = inode_ref.inode.size();
-
-        // Calculate the start and end block index
-        let iblock_start = offset / self.block_size;
-        let iblock_last = (offset + write_buf_len + self.block_size - 1) / self.block_size; // round up to include the last partial block
-
-        // start block index
-        let mut iblk_idx = iblock_start;
-        let ifile_blocks = (file_size + self.block_size as u64 - 1) / self.block_size as u64;
-
-        // Calculate the unaligned size
-        let unaligned = offset % self.block_size;
-
-        // Buffer to keep track of written bytes
+        let mut data = vec![0u8; self.block_size];
         let mut written = 0;
-
-        // Start bgid
-        let mut start_bgid = 1;
-
-        // Unaligned write
-        if unaligned > 0 {
-            let len = min(write_buf_len, self.block_size - unaligned);
-            // Get the physical block id, if the block is not present, append a new block
-            let pblock_idx = if iblk_idx < ifile_blocks as usize {
-                self.get_pblock_idx(&inode_ref, iblk_idx as u32)?
-            } else {
-                // physical block not exist, append a new block
-                self.append_inode_pblk_from(&mut inode_ref, &mut start_bgid)?
-            };
-
-            let mut block =
-                Block::load_offset(self.block_device.clone(), pblock_idx as usize * self.block_size);
-
-            block.write_offset(unaligned, &write_buf[..len], len);
-            block.sync_blk_to_disk(self.block_device.clone());
-            drop(block);
-
-            written += len;
-            iblk_idx += 1;
-        }
-
-        // Aligned write
-        let mut fblock_start = 0;
-        let mut fblock_count = 0;
-
-        while written < write_buf_len {
-            while iblk_idx < iblock_last && written < write_buf_len {
-                // Get the physical block id, if the block is not present, append a new block
-                let pblock_idx = if iblk_idx < ifile_blocks as usize {
-                    self.get_pblock_idx(&inode_ref, iblk_idx as u32)?
-                } else {
-                    // physical block not exist, append a new block
-                    self.append_inode_pblk_from(&mut inode_ref, &mut start_bgid)?
-                };
-                if fblock_start == 0 {
-                    fblock_start = pblock_idx;
+        let mut result = Ok(());
+        while written < write_buf.len() {
+            let iblock = ((offset + written) / self.block_size) as Ext4Lblk;
+            let block_offset = (offset + written) % self.block_size;
+            let len = min(self.block_size - block_offset, write_buf.len() - written);
+
+            let pblock = match self.map_block(&mut inode_ref, iblock) {
+                Ok((pblock, true)) => {
+                    data.fill(0);
+                    pblock
                 }
-
-                // Check if the block is contiguous
-                if fblock_start + fblock_count != pblock_idx {
+                Ok((pblock, false)) => {
+                    // 只写块的一部分时先读出原有数据
+                    if len < self.block_size {
+                        self.block_device.read_block(pblock as usize, &mut data);
+                    }
+                    pblock
+                }
+                Err(errno) => {
+                    result = Err(errno);
                     break;
                 }
-
-                fblock_count += 1;
-                iblk_idx += 1;
-            }
-
-            // Write contiguous blocks at once
-            let len = min(fblock_count as usize * self.block_size, write_buf_len - written);
-
-            for i in 0..fblock_count {
-                let block_offset = fblock_start as usize * self.block_size + i as usize * self.block_size;
-                let mut block = Block::load_offset(self.block_device.clone(), block_offset);
-                let write_size = min(self.block_size, write_buf_len - written);
-                block.write_offset(0, &write_buf[written..written + write_size], write_size);
-                block.sync_blk_to_disk(self.block_device.clone());
-                drop(block);
-                written += write_size;
-            }
-
-            fblock_start = 0;
-            fblock_count = 0;
-        }
-
-        // Final unaligned write if any
-        if written < write_buf_len {
-            let len = write_buf_len - written;
-            // Get the physical block id, if the block is not present, append a new block
-            let pblock_idx = if iblk_idx < ifile_blocks as usize {
-                self.get_pblock_idx(&inode_ref, iblk_idx as u32)?
-            } else {
-                // physical block not exist, append a new block
-                self.append_inode_pblk(&mut inode_ref)?
             };
-
-            let mut block =
-                Block::load_offset(self.block_device.clone(), pblock_idx as usize * self.block_size);
-            block.write_offset(0, &write_buf[written..], len);
-            block.sync_blk_to_disk(self.block_device.clone());
-            drop(block);
-
+            data[block_offset..block_offset + len]
+                .copy_from_slice(&write_buf[written..written + len]);
+            self.block_device.write_block(pblock as usize, &data);
             written += len;
         }
 
-        // Update file size if necessary
-        if offset + write_buf_len > file_size as usize {
-            log::trace!("set file size {:x}", offset + write_buf_len);
-            inode_ref.inode.set_size((offset + write_buf_len) as u64);
+        // 即使中途出错，已经分配的块也要随inode一起写回
+        if offset + written > inode_ref.inode.size() as usize {
+            inode_ref.inode.set_size((offset + written) as u64);
+        }
+        if written > 0 {
+            let now = crate::timer::realtime_now().tv_sec as u32;
+            inode_ref.inode.set_mtime(now);
+            inode_ref.inode.set_ctime(now);
+        }
+        self.write_back_inode(&mut inode_ref);
+
+        match result {
+            Err(errno) if written == 0 => Err(errno),
+            _ => Ok(written),
+        }
+    }
 
-            self.write_back_inode(&mut inode_ref);
+    /// 获取逻辑块对应的物理块，空洞处分配一个新块
+    /// # 返回值
+    /// + 物理块号与是否是新分配的块，新块的内容未初始化
+    /// # 说明
+    /// + 新块尽量紧接在前一个逻辑块之后；未写入的extent中的块被删去后重新分配
+    /// + inode的修改由调用者写回
+    pub fn map_block(
+        &self,
+        inode_ref: &mut Ext4InodeRef,
+        lblock: Ext4Lblk,
+    ) -> Result<(Ext4Fsblk, bool), isize> {
+        match self.find_extent_block(inode_ref, lblock)? {
+            Some((pblock, false)) => return Ok((pblock, false)),
+            Some((_, true)) => self.extent_remove_space(inode_ref, lblock, lblock)?,
+            None => {}
         }
+        let goal = match lblock {
+            0 => None,
+            _ => self
+                .find_pblock(inode_ref, lblock - 1)?
+                .map(|pblock| pblock + 1),
+        };
+        let pblock = self.balloc_alloc_block(inode_ref, goal)?;
+        if let Err(errno) = self.insert_extent(inode_ref, lblock, pblock, 1) {
+            self.balloc_free_blocks(inode_ref, pblock, 1)?;
+            return Err(errno);
+        }
+        Ok((pblock, true))
+    }
 
-        Ok(written)
+    /// 为逻辑块 `from` 到 `to`（不包含）中的空洞分配清零的块
+    /// # 说明
+    /// + 页缓存只能写回已经映射的块，通过页缓存写入的范围要先分配好块
+    /// + inode的修改由调用者写回
+    pub fn map_blocks(
+        &self,
+        inode_ref: &mut Ext4InodeRef,
+        from: Ext4Lblk,
+        to: Ext4Lblk,
+    ) -> Result<(), isize> {
+        let zero = vec![0u8; self.block_size];
+        for lblock in from..to {
+            if let (pblock, true) = self.map_block(inode_ref, lblock)? {
+                self.block_device.write_block(pblock as usize, &zero);
+            }
+        }
+        Ok(())
     }
 
     /// File remove
@@ -616,11 +523,8 @@ impl Ext4FileSystem {
         let mut nameoff = 0;
         let child_inode = self.generic_open(path, &mut parent_inode_num, false, 0, &mut nameoff)?;
 
+        // 最后一个链接被删除时 unlink 释放数据块与inode
         let mut child_inode_ref = self.get_inode_ref(child_inode);
-        let child_link_cnt = child_inode_ref.inode.links_count();
-        if child_link_cnt == 1 {
-            self.truncate_inode(&mut child_inode_ref, 0)?;
-        }
 
         // get child name
         let mut is_goal = false;
@@ -645,31 +549,41 @@ impl Ext4FileSystem {
     /// new_size: u64 - 文件的新大小
     /// + 返回值
     /// `Result<usize>` - 操作状态
+    /// + 说明
+    /// 缩小时释放新大小之后的块，并把最后一个块中新大小之后的部分清零；
+    /// 扩大时只修改文件大小，新增的部分是空洞，读出 0，写入时才分配块。inode会写回磁盘
     pub fn truncate_inode(
         &self,
         inode_ref: &mut Ext4InodeRef,
         new_size: u64,
     ) -> Result<usize, isize> {
         let old_size = inode_ref.inode.size();
-        // assert!(old_size > new_size);
-        if old_size >= new_size{
-            // println!("[kernel] this may need to be changed");
-            inode_ref.inode.set_size(new_size);
-            // 确保立即同步到磁盘，避免缓存不一致
-            self.write_back_inode(inode_ref);
-            return Ok(EOK)
-        }
-
         let block_size = self.block_size as u64;
         let new_blocks_cnt = ((new_size + block_size - 1) / block_size) as u32;
-        let old_blocks_cnt = ((old_size + block_size - 1) / block_size) as u32;
-        let diff_blocks_cnt = old_blocks_cnt - new_blocks_cnt;
 
-        if diff_blocks_cnt > 0 {
+        if inode_ref.inode.flags() & EXT4_INODE_FLAG_EXTENTS as u32 == 0 {
+            // 内容保存在inode中的快速符号链接没有数据块，其余不使用extent的文件不支持
+            if inode_ref.inode.blocks_count() != 0 {
+                return Err(EINVAL);
+            }
+        } else if new_size < old_size {
             self.extent_remove_space(inode_ref, new_blocks_cnt, EXT_MAX_BLOCKS)?;
+
+            let tail = (new_size % block_size) as usize;
+            if tail != 0 {
+                if let Some(pblock) = self.find_pblock(inode_ref, new_blocks_cnt - 1)? {
+                    let mut data = vec![0u8; self.block_size];
+                    self.block_device.read_block(pblock as usize, &mut data);
+                    data[tail..].fill(0);
+                    self.block_device.write_block(pblock as usize, &data);
+                }
+            }
         }
 
+        let now = crate::timer::realtime_now().tv_sec as u32;
         inode_ref.inode.set_size(new_size);
+        inode_ref.inode.set_mtime(now);
+        inode_ref.inode.set_ctime(now);
         self.write_back_inode(inode_ref);
 
         Ok(EOK)
@@ -0,0 +1,487 @@
+//! 自检用的 ext4 镜像与一致性检查
+//!
+//! [`mkfs`] 在内存盘上格式化一个只有一个块组的 ext4；[`Ext4FileSystem::fsck`]
+//! 不经过分配器、extent 树与目录项的代码，直接按磁盘格式读出位图、inode、
+//! extent 树与目录块，检查计数、校验和、块的归属与链接数是否一致
+
+use super::crc::{ext4_crc32c, EXT4_CRC32_INIT};
+use super::direntry::{Ext4DirEntry, Ext4DirSearchResult};
+use super::ext4fs::Ext4FileSystem;
+use super::superblock::EXT4_FEATURE_RO_COMPAT_METADATA_CSUM;
+use super::{
+    BlockCacheManager, BLOCK_SIZE, EXT4_EXTENT_MAGIC, EXT4_INODE_FLAG_EXTENTS, EXT_INIT_MAX_LEN,
+    ROOT_INODE,
+};
+use crate::drivers::block::{BlockDevice, RamDisk};
+use alloc::{sync::Arc, vec, vec::Vec};
+use core::convert::TryInto;
+use spin::Mutex;
+
+/// 块组描述符所在的块
+const GDT_BLOCK: usize = 1;
+/// 块位图所在的块
+const BLOCK_BITMAP: usize = 2;
+/// inode位图所在的块
+const INODE_BITMAP: usize = 3;
+/// inode表的第一个块
+const INODE_TABLE: usize = 4;
+const INODE_SIZE: usize = 256;
+const DESC_SIZE: usize = 32;
+/// 第一个非保留的inode号，之前的除根目录外都不使用
+const FIRST_INO: u32 = 11;
+/// 目录块尾部校验和的长度
+const DIR_TAIL_SIZE: usize = 12;
+const UUID: [u8; 16] = *b"npucore-selftest";
+const S_IFMT: u16 = 0xF000;
+const S_IFDIR: u16 = 0x4000;
+
+fn get16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap())
+}
+
+fn get32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn put16(data: &mut [u8], offset: usize, value: u16) {
+    data[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn put32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn bit(bitmap: &[u8], index: usize) -> bool {
+    bitmap[index / 8] & (1 << (index % 8)) != 0
+}
+
+fn set_bit(bitmap: &mut [u8], index: usize) {
+    bitmap[index / 8] |= 1 << (index % 8);
+}
+
+/// 依次对 `parts` 计算 crc32c，与 ext4 一样不取反
+fn csum(parts: &[&[u8]]) -> u32 {
+    parts.iter().fold(EXT4_CRC32_INIT, |crc, part| {
+        ext4_crc32c(crc, part, part.len() as u32)
+    })
+}
+
+/// 属于某个inode的元数据块的校验和：crc32c(uuid + inode号 + generation + 数据)
+fn inode_csum(ino: u32, generation: u32, data: &[u8]) -> u32 {
+    csum(&[&UUID, &ino.to_le_bytes(), &generation.to_le_bytes(), data])
+}
+
+/// 在块中 `offset` 处写入一个目录项
+fn put_dirent(block: &mut [u8], offset: usize, ino: u32, rec_len: usize, name: &str) {
+    put32(block, offset, ino);
+    put16(block, offset + 4, rec_len as u16);
+    block[offset + 6] = name.len() as u8;
+    block[offset + 7] = 2;
+    block[offset + 8..offset + 8 + name.len()].copy_from_slice(name.as_bytes());
+}
+
+/// 在内存盘上格式化一个 ext4 并打开
+/// # 参数
+/// + blocks: 块数，不超过一个块组
+/// + inodes: inode数，是每块inode数的整数倍
+/// # 说明
+/// + 开启 extents、filetype 与 metadata_csum，inode为256字节，块组描述符为32字节
+/// + 依次是超级块、块组描述符、块位图、inode位图、inode表与根目录的块
+pub fn mkfs(blocks: u32, inodes: u32) -> Ext4FileSystem {
+    let (blocks, inodes) = (blocks as usize, inodes as usize);
+    assert!(blocks <= BLOCK_SIZE * 8 && inodes % (BLOCK_SIZE / INODE_SIZE) == 0);
+    let root_block = INODE_TABLE + inodes * INODE_SIZE / BLOCK_SIZE;
+    let free_blocks = (blocks - root_block - 1) as u32;
+    let free_inodes = (inodes - FIRST_INO as usize + 1) as u32;
+    let device: Arc<dyn BlockDevice> = Arc::new(RamDisk::new(blocks * BLOCK_SIZE));
+
+    // 块组之外的位也置位，分配器不会越过块组的末尾
+    let mut bitmap = vec![0u8; BLOCK_SIZE];
+    for index in (0..=root_block).chain(blocks..BLOCK_SIZE * 8) {
+        set_bit(&mut bitmap, index);
+    }
+    let block_bitmap_csum = csum(&[&UUID, &bitmap]);
+    device.write_block(BLOCK_BITMAP, &bitmap);
+
+    let mut bitmap = vec![0u8; BLOCK_SIZE];
+    for index in (0..FIRST_INO as usize - 1).chain(inodes..BLOCK_SIZE * 8) {
+        set_bit(&mut bitmap, index);
+    }
+    let inode_bitmap_csum = csum(&[&UUID, &bitmap[..inodes / 8]]);
+    device.write_block(INODE_BITMAP, &bitmap);
+
+    // 根目录只有 "." 与 ".."
+    let mut block = vec![0u8; BLOCK_SIZE];
+    let tail = BLOCK_SIZE - DIR_TAIL_SIZE;
+    put_dirent(&mut block, 0, ROOT_INODE, 12, ".");
+    put_dirent(&mut block, 12, ROOT_INODE, tail - 12, "..");
+    put16(&mut block, tail + 4, DIR_TAIL_SIZE as u16);
+    block[tail + 7] = 0xDE;
+    let checksum = inode_csum(ROOT_INODE, 0, &block[..tail]);
+    put32(&mut block, tail + 8, checksum);
+    device.write_block(root_block, &block);
+
+    let mut table = vec![0u8; BLOCK_SIZE];
+    let offset = (ROOT_INODE as usize - 1) * INODE_SIZE;
+    let slot = &mut table[offset..offset + INODE_SIZE];
+    put16(slot, 0x00, S_IFDIR | 0o755);
+    put32(slot, 0x04, BLOCK_SIZE as u32);
+    put16(slot, 0x1A, 2);
+    put32(slot, 0x1C, (BLOCK_SIZE / 512) as u32);
+    put32(slot, 0x20, EXT4_INODE_FLAG_EXTENTS as u32);
+    put16(slot, 0x28, EXT4_EXTENT_MAGIC);
+    put16(slot, 0x2A, 1);
+    put16(slot, 0x2C, 4);
+    put16(slot, 0x38, 1);
+    put32(slot, 0x3C, root_block as u32);
+    put16(slot, 0x80, 32);
+    let checksum = inode_csum(ROOT_INODE, 0, slot);
+    put16(slot, 0x7C, checksum as u16);
+    put16(slot, 0x82, (checksum >> 16) as u16);
+    device.write_block(INODE_TABLE, &table);
+
+    let mut gdt = vec![0u8; BLOCK_SIZE];
+    put32(&mut gdt, 0x00, BLOCK_BITMAP as u32);
+    put32(&mut gdt, 0x04, INODE_BITMAP as u32);
+    put32(&mut gdt, 0x08, INODE_TABLE as u32);
+    put16(&mut gdt, 0x0C, free_blocks as u16);
+    put16(&mut gdt, 0x0E, free_inodes as u16);
+    put16(&mut gdt, 0x10, 1);
+    put16(&mut gdt, 0x18, block_bitmap_csum as u16);
+    put16(&mut gdt, 0x1A, inode_bitmap_csum as u16);
+    put16(&mut gdt, 0x1C, free_inodes as u16);
+    let checksum = csum(&[&UUID, &0u32.to_le_bytes(), &gdt[..DESC_SIZE]]);
+    put16(&mut gdt, 0x1E, checksum as u16);
+    device.write_block(GDT_BLOCK, &gdt);
+
+    let mut sb = vec![0u8; 1024];
+    put32(&mut sb, 0x00, inodes as u32);
+    put32(&mut sb, 0x04, blocks as u32);
+    put32(&mut sb, 0x0C, free_blocks);
+    put32(&mut sb, 0x10, free_inodes);
+    put32(&mut sb, 0x18, (BLOCK_SIZE / 1024).trailing_zeros());
+    put32(&mut sb, 0x1C, (BLOCK_SIZE / 1024).trailing_zeros());
+    put32(&mut sb, 0x20, (BLOCK_SIZE * 8) as u32);
+    put32(&mut sb, 0x24, (BLOCK_SIZE * 8) as u32);
+    put32(&mut sb, 0x28, inodes as u32);
+    put16(&mut sb, 0x38, 0xEF53);
+    put16(&mut sb, 0x3A, 1);
+    put16(&mut sb, 0x3C, 1);
+    put32(&mut sb, 0x4C, 1);
+    put32(&mut sb, 0x54, FIRST_INO);
+    put16(&mut sb, 0x58, INODE_SIZE as u16);
+    // filetype | extents
+    put32(&mut sb, 0x60, 0x0002 | 0x0040);
+    put32(&mut sb, 0x64, EXT4_FEATURE_RO_COMPAT_METADATA_CSUM);
+    sb[0x68..0x78].copy_from_slice(&UUID);
+    put16(&mut sb, 0xFE, DESC_SIZE as u16);
+    put16(&mut sb, 0x15C, 32);
+    put16(&mut sb, 0x15E, 32);
+    sb[0x175] = 1;
+    let checksum = csum(&[&sb[..0x3FC]]);
+    put32(&mut sb, 0x3FC, checksum);
+    let mut block = vec![0u8; BLOCK_SIZE];
+    block[1024..2048].copy_from_slice(&sb);
+    device.write_block(0, &block);
+
+    Ext4FileSystem::open_ext4rs(device, Arc::new(Mutex::new(BlockCacheManager::new())))
+}
+
+/// 按磁盘格式读出的 extent 树
+pub struct ExtentTree {
+    /// 每层各节点的表项数，从根开始
+    pub levels: Vec<Vec<usize>>,
+    /// 叶子中的 extent：起始逻辑块、起始物理块与块数，按逻辑块排列
+    pub extents: Vec<(u32, u64, u32)>,
+    /// 根之外的树节点所在的块
+    pub nodes: Vec<u64>,
+}
+
+impl ExtentTree {
+    /// 逻辑块映射到的物理块
+    pub fn lookup(&self, lblock: u32) -> Option<u64> {
+        self.extents
+            .iter()
+            .find(|&&(first, _, len)| lblock >= first && lblock - first < len)
+            .map(|&(first, pblock, _)| pblock + (lblock - first) as u64)
+    }
+
+    /// 读出树中的一个节点，检查头部、顺序与块节点的校验和
+    /// # 返回值
+    /// + 节点第一项的起始逻辑块号
+    fn walk(
+        &mut self,
+        fs: &Ext4FileSystem,
+        inode: &[u8],
+        ino: u32,
+        node: &[u8],
+        level: usize,
+        depth: u16,
+    ) -> Result<Option<u32>, &'static str> {
+        let entries = get16(node, 2) as usize;
+        let max = get16(node, 4) as usize;
+        if get16(node, 0) != EXT4_EXTENT_MAGIC || get16(node, 6) != depth {
+            return Err("bad extent header");
+        }
+        if entries > max || 12 + max * 12 > node.len() || (level > 0 && entries == 0) {
+            return Err("bad extent entry count");
+        }
+        if level > 0 {
+            let tail = 12 + max * 12;
+            if get32(node, tail) != inode_csum(ino, get32(inode, 0x64), &node[..tail]) {
+                return Err("bad extent block checksum");
+            }
+        }
+        if self.levels.len() <= level {
+            self.levels.push(Vec::new());
+        }
+        self.levels[level].push(entries);
+
+        let mut prev_end = None;
+        for pos in 0..entries {
+            let entry = &node[12 + pos * 12..24 + pos * 12];
+            let first = get32(entry, 0);
+            if prev_end.map_or(false, |end| (first as u64) < end) {
+                return Err("extent entries out of order");
+            }
+            if depth == 0 {
+                let len = match get16(entry, 4) {
+                    len if len > EXT_INIT_MAX_LEN => len - EXT_INIT_MAX_LEN,
+                    len => len,
+                } as u32;
+                let pblock = (get16(entry, 6) as u64) << 32 | get32(entry, 8) as u64;
+                if len == 0 {
+                    return Err("empty extent");
+                }
+                self.extents.push((first, pblock, len));
+                prev_end = Some(first as u64 + len as u64);
+            } else {
+                let pblock = (get16(entry, 8) as u64) << 32 | get32(entry, 4) as u64;
+                let mut child = vec![0u8; BLOCK_SIZE];
+                fs.block_device.read_block(pblock as usize, &mut child);
+                self.nodes.push(pblock);
+                let child_first = self.walk(fs, inode, ino, &child, level + 1, depth - 1)?;
+                if child_first != Some(first) {
+                    return Err("index does not start at its child");
+                }
+                prev_end = Some(first as u64 + 1);
+            }
+        }
+        Ok((entries > 0).then(|| get32(node, 12)))
+    }
+}
+
+/// 磁盘上超级块与块组描述符中的计数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Usage {
+    pub free_blocks: u32,
+    pub free_inodes: u32,
+    pub used_dirs: u16,
+}
+
+impl Ext4FileSystem {
+    /// 读出磁盘上的空闲块数、空闲inode数与目录数，内存中的超级块只在挂载时读取
+    pub fn usage(&self) -> Usage {
+        let mut block = vec![0u8; BLOCK_SIZE];
+        self.block_device.read_block(0, &mut block);
+        let (free_blocks, free_inodes) = (get32(&block, 1024 + 0x0C), get32(&block, 1024 + 0x10));
+        self.block_device.read_block(GDT_BLOCK, &mut block);
+        Usage {
+            free_blocks,
+            free_inodes,
+            used_dirs: get16(&block, 0x10),
+        }
+    }
+
+    /// 目录 `parent` 中名为 `name` 的目录项指向的inode号
+    pub fn lookup(&self, parent: u32, name: &str) -> Option<u32> {
+        let mut result = Ext4DirSearchResult::new(Ext4DirEntry::default());
+        self.dir_find_entry(parent, name, &mut result).ok()?;
+        Some(result.dentry.inode)
+    }
+
+    /// 直接从磁盘上的inode表读出inode号为 `ino` 的槽位
+    fn raw_inode(&self, ino: u32) -> Vec<u8> {
+        let mut gdt = vec![0u8; BLOCK_SIZE];
+        self.block_device.read_block(GDT_BLOCK, &mut gdt);
+        let offset = (ino as usize - 1) * INODE_SIZE;
+        let mut block = vec![0u8; BLOCK_SIZE];
+        let table = get32(&gdt, 0x08) as usize;
+        self.block_device
+            .read_block(table + offset / BLOCK_SIZE, &mut block);
+        block[offset % BLOCK_SIZE..offset % BLOCK_SIZE + INODE_SIZE].to_vec()
+    }
+
+    /// 按磁盘格式读出inode的extent树
+    pub fn extent_tree(&self, ino: u32) -> Result<ExtentTree, &'static str> {
+        let inode = self.raw_inode(ino);
+        if get32(&inode, 0x20) & EXT4_INODE_FLAG_EXTENTS as u32 == 0 {
+            return Err("inode has no extent tree");
+        }
+        let root = &inode[0x28..0x64];
+        let mut tree = ExtentTree {
+            levels: Vec::new(),
+            extents: Vec::new(),
+            nodes: Vec::new(),
+        };
+        if get16(root, 4) != 4 {
+            return Err("bad extent root");
+        }
+        tree.walk(self, &inode, ino, root, 0, get16(root, 6))?;
+        Ok(tree)
+    }
+
+    /// 检查 [`mkfs`] 格式化的文件系统是否一致
+    /// # 说明
+    /// + 超级块、块组描述符、两个位图、inode、extent块与目录块的校验和
+    /// + 超级块与块组描述符中的空闲数与位图相符
+    /// + 置位的块正好是元数据块与各inode的数据块、extent块，没有块属于两个inode
+    /// + inode的 i_blocks 与占用的块数相符，映射的块不越过文件末尾，目录没有空洞
+    /// + 目录块中的目录项首尾相接，正好覆盖到尾部校验和之前
+    /// + 在用的inode正好是被目录项引用的inode，链接数等于引用它的目录项数
+    pub fn fsck(&self) -> Result<(), &'static str> {
+        let mut block = vec![0u8; BLOCK_SIZE];
+        self.block_device.read_block(0, &mut block);
+        let sb = &block[1024..2048];
+        if get32(sb, 0x3FC) != csum(&[&sb[..0x3FC]]) {
+            return Err("bad superblock checksum");
+        }
+        let (inodes, blocks) = (get32(sb, 0x00) as usize, get32(sb, 0x04) as usize);
+        let (sb_free_blocks, sb_free_inodes) = (get32(sb, 0x0C), get32(sb, 0x10));
+
+        let mut gdt = vec![0u8; BLOCK_SIZE];
+        self.block_device.read_block(GDT_BLOCK, &mut gdt);
+        let mut desc = gdt[..DESC_SIZE].to_vec();
+        put16(&mut desc, 0x1E, 0);
+        if get16(&gdt, 0x1E) != csum(&[&UUID, &0u32.to_le_bytes(), &desc]) as u16 {
+            return Err("bad group descriptor checksum");
+        }
+        let mut block_bitmap = vec![0u8; BLOCK_SIZE];
+        self.block_device
+            .read_block(get32(&gdt, 0x00) as usize, &mut block_bitmap);
+        let mut inode_bitmap = vec![0u8; BLOCK_SIZE];
+        self.block_device
+            .read_block(get32(&gdt, 0x04) as usize, &mut inode_bitmap);
+        if get16(&gdt, 0x18) != csum(&[&UUID, &block_bitmap]) as u16 {
+            return Err("bad block bitmap checksum");
+        }
+        if get16(&gdt, 0x1A) != csum(&[&UUID, &inode_bitmap[..inodes / 8]]) as u16 {
+            return Err("bad inode bitmap checksum");
+        }
+        let free_blocks = (0..blocks).filter(|&i| !bit(&block_bitmap, i)).count() as u32;
+        let free_inodes = (0..inodes).filter(|&i| !bit(&inode_bitmap, i)).count() as u32;
+        if get16(&gdt, 0x0C) as u32 != free_blocks || sb_free_blocks != free_blocks {
+            return Err("free block count differs from bitmap");
+        }
+        if get16(&gdt, 0x0E) as u32 != free_inodes || sb_free_inodes != free_inodes {
+            return Err("free inode count differs from bitmap");
+        }
+
+        // 元数据块属于inode 0
+        let metadata = INODE_TABLE + inodes * INODE_SIZE / BLOCK_SIZE;
+        let mut owner: Vec<Option<u32>> = (0..blocks)
+            .map(|pblock| (pblock < metadata).then_some(0))
+            .collect();
+        let mut refs = vec![0u16; inodes + 1];
+        let mut links = vec![0u16; inodes + 1];
+        for ino in (ROOT_INODE..=ROOT_INODE).chain(FIRST_INO..=inodes as u32) {
+            if !bit(&inode_bitmap, ino as usize - 1) {
+                continue;
+            }
+            let mut inode = self.raw_inode(ino);
+            let checksum = get16(&inode, 0x7C) as u32 | (get16(&inode, 0x82) as u32) << 16;
+            put16(&mut inode, 0x7C, 0);
+            put16(&mut inode, 0x82, 0);
+            if checksum != inode_csum(ino, get32(&inode, 0x64), &inode) {
+                return Err("bad inode checksum");
+            }
+            links[ino as usize] = get16(&inode, 0x1A);
+            let tree = self.extent_tree(ino)?;
+            let size = get32(&inode, 0x04) as u64 | (get32(&inode, 0x6C) as u64) << 32;
+            let size_blocks = (size + BLOCK_SIZE as u64 - 1) / BLOCK_SIZE as u64;
+            let mut used = tree.nodes.len() as u64;
+            for &(first, pblock, len) in &tree.extents {
+                if first as u64 + len as u64 > size_blocks {
+                    return Err("block mapped past the end of file");
+                }
+                used += len as u64;
+            }
+            for pblock in tree
+                .extents
+                .iter()
+                .flat_map(|&(_, pblock, len)| pblock..pblock + len as u64)
+                .chain(tree.nodes.iter().copied())
+            {
+                let slot = owner.get_mut(pblock as usize).ok_or("block out of range")?;
+                if slot.replace(ino).is_some() {
+                    return Err("block owned twice");
+                }
+            }
+            let i_blocks = get32(&inode, 0x1C) as u64 | (get16(&inode, 0x74) as u64) << 32;
+            if i_blocks != used * (BLOCK_SIZE / 512) as u64 {
+                return Err("i_blocks differs from mapped blocks");
+            }
+            if get16(&inode, 0x00) & S_IFMT == S_IFDIR {
+                if size % BLOCK_SIZE as u64 != 0 {
+                    return Err("directory size not whole blocks");
+                }
+                for lblock in 0..size_blocks as u32 {
+                    let pblock = tree.lookup(lblock).ok_or("hole in directory")?;
+                    self.block_device.read_block(pblock as usize, &mut block);
+                    self.fsck_dir_block(ino, get32(&inode, 0x64), &block, &mut refs)?;
+                }
+            }
+        }
+        for ino in (ROOT_INODE..=ROOT_INODE).chain(FIRST_INO..=inodes as u32) {
+            let ino = ino as usize;
+            if bit(&inode_bitmap, ino - 1) != (refs[ino] > 0) {
+                return Err("inode bitmap differs from directory entries");
+            }
+            if refs[ino] != links[ino] {
+                return Err("link count differs from directory entries");
+            }
+        }
+        for (pblock, owner) in owner.iter().enumerate() {
+            if bit(&block_bitmap, pblock) != owner.is_some() {
+                return Err("block bitmap differs from mapped blocks");
+            }
+        }
+        Ok(())
+    }
+
+    /// 检查一个目录块，并记下目录项引用的inode
+    fn fsck_dir_block(
+        &self,
+        dir: u32,
+        generation: u32,
+        block: &[u8],
+        refs: &mut [u16],
+    ) -> Result<(), &'static str> {
+        let tail = BLOCK_SIZE - DIR_TAIL_SIZE;
+        if get32(block, tail) != 0 || get16(block, tail + 4) as usize != DIR_TAIL_SIZE {
+            return Err("bad directory block tail");
+        }
+        if block[tail + 7] != 0xDE
+            || get32(block, tail + 8) != inode_csum(dir, generation, &block[..tail])
+        {
+            return Err("bad directory block checksum");
+        }
+        let mut offset = 0;
+        while offset < tail {
+            let ino = get32(block, offset) as usize;
+            let rec_len = get16(block, offset + 4) as usize;
+            let name_len = block[offset + 6] as usize;
+            if rec_len < 12 || rec_len % 4 != 0 || offset + rec_len > tail {
+                return Err("bad directory entry length");
+            }
+            if ino != 0 {
+                if 8 + name_len > rec_len || ino >= refs.len() {
+                    return Err("bad directory entry");
+                }
+                refs[ino] += 1;
+            }
+            offset += rec_len;
+        }
+        Ok(())
+    }
+}
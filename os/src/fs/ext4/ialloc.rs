@@ -1,9 +1,8 @@
-use crate::fs::{directory_tree::GLOBAL_BLOCK_SIZE, ext4::{block_group::Ext4BlockGroup, BLOCK_SIZE}};
-use alloc::vec;
+use crate::fs::ext4::block_group::{Block, Ext4BlockGroup, EXT4_BG_INODE_UNINIT};
+use crate::syscall::errno::{EIO, ENOSPC};
 
 use super::{
-    bitmap::{ext4_bmap_bit_clr, ext4_bmap_bit_find_clr, ext4_bmap_bit_set},
-    error::Errno,
+    bitmap::{ext4_bmap_bit_clr, ext4_bmap_bit_find_clr, ext4_bmap_bit_set, ext4_bmap_is_bit_set},
     ext4fs::Ext4FileSystem,
 };
 
@@ -12,129 +11,110 @@ impl Ext4FileSystem {
     /// # 参数
     /// + is_dir: 是否是文件夹
     /// # 返回值
-    /// + 新的inode号
+    /// + 新的inode号，没有空闲inode时返回 ENOSPC
+    /// # 说明
+    /// + inode位图尚未初始化的块组会被跳过
     pub fn ialloc_alloc_inode(&self, is_dir: bool) -> Result<u32, isize> {
-        let mut bgid = 0;
-        let bg_count = self.superblock.block_group_count();
-        let mut super_block = self.superblock;
-
-        while bgid <= bg_count {
-            if bgid == bg_count {
-                bgid = 0;
-                continue;
-            }
+        let _guard = self.alloc_lock.lock();
+        let super_block = &self.superblock;
+        let bg_count = super_block.block_group_count();
 
+        for bgid in 0..bg_count {
             // 获取块组
             let mut bg =
-                Ext4BlockGroup::load_new(self.block_device.clone(), &super_block, bgid as usize);
-
-            let mut free_inodes = bg.get_free_inodes_count();
-
-            if free_inodes > 0 {
-                let inode_bitmap_block = bg.get_inode_bitmap_block(&super_block);
-
-                let mut raw_data = [0u8; BLOCK_SIZE];
-                self.block_device
-                    .read_block(inode_bitmap_block as usize, &mut raw_data);
-
-                let inodes_in_bg = super_block.get_inodes_in_group_cnt(bgid);
-
-                let bitmap_data = &mut raw_data[..];
-
-                let mut idx_in_bg = 0;
+                Ext4BlockGroup::load_new(self.block_device.clone(), super_block, bgid as usize);
 
-                ext4_bmap_bit_find_clr(bitmap_data, 0, inodes_in_bg, &mut idx_in_bg);
-                ext4_bmap_bit_set(bitmap_data, idx_in_bg);
-
-                // update bitmap in disk
-                // 此处因为是直接进行块单位的写入，所以不需要考虑对齐
-                self.block_device
-                    .write_block(inode_bitmap_block as usize, bitmap_data);
-
-                bg.set_block_group_ialloc_bitmap_csum(&super_block, bitmap_data);
-
-                // 修改文件系统计数器
-                free_inodes -= 1;
-                bg.set_free_inodes_count(&super_block, free_inodes);
-
-                /* Increment used directories counter */
-                if is_dir {
-                    let used_dirs = bg.get_used_dirs_count(&super_block) + 1;
-                    bg.set_used_dirs_count(&super_block, used_dirs);
-                }
-
-                // 减少未使用inode计数
-                let mut unused = bg.get_itable_unused(&super_block);
-                let free = inodes_in_bg - unused;
-                if idx_in_bg >= free {
-                    unused = inodes_in_bg - (idx_in_bg + 1);
-                    bg.set_itable_unused(&super_block, unused);
-                }
-
-                // 同步块组内容
-                bg.sync_to_disk_with_csum(self.block_device.clone(), bgid as usize, &super_block);
+            let free_inodes = bg.get_free_inodes_count(super_block);
+            if free_inodes == 0 || bg.flags & EXT4_BG_INODE_UNINIT != 0 {
+                continue;
+            }
 
-                // 更新超级块
-                super_block.decrease_free_inodes_count();
-                super_block.sync_to_disk_with_csum(self.block_device.clone());
-                // 看是否写入成功
-                let mut test_super_block = vec![0u8; crate::hal::BLOCK_SZ];
-                self.block_device.read_block(0, &mut test_super_block);
+            let inode_bitmap_block = bg.get_inode_bitmap_block(super_block);
+            let mut bitmap_block = Block::load_offset(
+                self.block_device.clone(),
+                inode_bitmap_block as usize * self.block_size,
+            );
 
-                /* Compute the absolute i-nodex number */
-                // 计算inode号
-                let inodes_per_group = super_block.inodes_per_group();
-                let inode_num = bgid * inodes_per_group + (idx_in_bg + 1);
+            let inodes_in_bg = super_block.get_inodes_in_group_cnt(bgid);
+            let mut idx_in_bg = 0;
+            if !ext4_bmap_bit_find_clr(&bitmap_block.data, 0, inodes_in_bg, &mut idx_in_bg) {
+                // 计数与位图不一致，换下一个块组
+                continue;
+            }
+            ext4_bmap_bit_set(&mut bitmap_block.data, idx_in_bg);
+
+            // 先计算位图校验和（保存在块组描述符中），再写回位图
+            bg.set_block_group_ialloc_bitmap_csum(super_block, &bitmap_block.data);
+            self.block_device
+                .write_block(inode_bitmap_block as usize, &bitmap_block.data);
+
+            // 修改块组计数器
+            bg.set_free_inodes_count(super_block, free_inodes - 1);
+            if is_dir {
+                let used_dirs = bg.get_used_dirs_count(super_block) + 1;
+                bg.set_used_dirs_count(super_block, used_dirs);
+            }
 
-                return Ok(inode_num);
+            // inode表中从未使用过的部分缩小到新inode之后
+            let unused = bg.get_itable_unused(super_block);
+            if idx_in_bg >= inodes_in_bg - unused {
+                bg.set_itable_unused(super_block, inodes_in_bg - (idx_in_bg + 1));
             }
 
-            bgid += 1;
+            // 同步块组与超级块
+            bg.sync_to_disk_with_csum(self.block_device.clone(), bgid as usize, super_block);
+            self.update_superblock_counts(0, -1);
+
+            // 计算inode号，inode号从1开始
+            return Ok(bgid * super_block.inodes_per_group() + idx_in_bg + 1);
         }
 
         println!("[kernel ialloc] alloc inode failed");
-        return Err(Errno::ENOSPC as isize);
+        Err(ENOSPC)
     }
 
-    pub fn ialloc_free_inode(&self, index: u32, is_dir: bool) {
-        // Compute index of block group
-        let bgid = self.get_bgid_of_inode(index);
-        let block_device = self.block_device.clone();
-
-        let mut super_block = self.superblock;
-        let mut bg =
-            Ext4BlockGroup::load_new(self.block_device.clone(), &super_block, bgid as usize);
-
-        // Load inode bitmap block
-        let inode_bitmap_block = bg.get_inode_bitmap_block(&self.superblock);
-        let mut bitmap_data = [0u8; BLOCK_SIZE];
-        self.block_device
-            .read_block(inode_bitmap_block as usize, &mut bitmap_data);
+    /// 释放inode号
+    /// # 参数
+    /// + index: inode号
+    /// + is_dir: 是否是文件夹
+    pub fn ialloc_free_inode(&self, index: u32, is_dir: bool) -> Result<(), isize> {
+        let _guard = self.alloc_lock.lock();
+        let super_block = &self.superblock;
+        if index == 0 || index > super_block.total_inodes() {
+            return Err(EIO);
+        }
 
-        // Find index within group and clear bit
+        // 计算块组号与块组内索引
+        let bgid = self.get_bgid_of_inode(index);
         let index_in_group = self.inode_to_bgidx(index);
-        ext4_bmap_bit_clr(&mut bitmap_data, index_in_group);
-
-        // Set new checksum after modification
-        // update bitmap in disk
-        // 此处与上面的ialloc_alloc_inode函数一样，不需要考虑对齐
+        let mut bg =
+            Ext4BlockGroup::load_new(self.block_device.clone(), super_block, bgid as usize);
+
+        // 清除位图中的对应位
+        let inode_bitmap_block = bg.get_inode_bitmap_block(super_block);
+        let mut bitmap_block = Block::load_offset(
+            self.block_device.clone(),
+            inode_bitmap_block as usize * self.block_size,
+        );
+        if !ext4_bmap_is_bit_set(&bitmap_block.data, index_in_group) {
+            println!("[kernel ialloc] inode {} is already free", index);
+            return Err(EIO);
+        }
+        ext4_bmap_bit_clr(&mut bitmap_block.data, index_in_group);
+        bg.set_block_group_ialloc_bitmap_csum(super_block, &bitmap_block.data);
         self.block_device
-            .write_block(inode_bitmap_block as usize, &bitmap_data);
-        bg.set_block_group_ialloc_bitmap_csum(&super_block, &bitmap_data);
+            .write_block(inode_bitmap_block as usize, &bitmap_block.data);
 
-        // Update free inodes count in block group
-        let free_inodes = bg.get_free_inodes_count() + 1;
-        bg.set_free_inodes_count(&self.superblock, free_inodes);
-
-        // If inode was a directory, decrement the used directories count
+        // 修改块组计数器
+        let free_inodes = bg.get_free_inodes_count(super_block) + 1;
+        bg.set_free_inodes_count(super_block, free_inodes);
         if is_dir {
-            let used_dirs = bg.get_used_dirs_count(&self.superblock) - 1;
-            bg.set_used_dirs_count(&self.superblock, used_dirs);
+            let used_dirs = bg.get_used_dirs_count(super_block).saturating_sub(1);
+            bg.set_used_dirs_count(super_block, used_dirs);
         }
 
-        bg.sync_to_disk_with_csum(block_device.clone(), bgid as usize, &super_block);
-
-        super_block.decrease_free_inodes_count();
-        super_block.sync_to_disk_with_csum(self.block_device.clone());
+        bg.sync_to_disk_with_csum(self.block_device.clone(), bgid as usize, super_block);
+        self.update_superblock_counts(0, 1);
+        Ok(())
     }
 }
@@ -16,7 +16,7 @@ use crate::{
     },
     lang_items::Bytes,
     mm::UserBuffer,
-    syscall::errno::{EINVAL, ENOTDIR, ENOTEMPTY},
+    syscall::errno::{EACCES, EINVAL, ENOTDIR, ENOTEMPTY, ESTALE},
 };
 use alloc::{
     format,
@@ -37,7 +37,7 @@ use super::{
     direntry::Ext4DirEntry,
     ext4fs::Ext4FileSystem,
     file::{Ext4FileContent, Ext4FileContentWrapper},
-    Cache, Ext4Inode, Ext4InodeRef, InodePerm, PageCacheManager,
+    Cache, Ext4Inode, Ext4InodeRef, InodePerm, PageCacheManager, READ_BATCH_PAGES,
 };
 
 // 可能后续会用到？
@@ -264,10 +264,8 @@ impl File for Ext4OSInode {
         match offset {
             Some(offset) => {
                 let mut start = *offset;
-                let diff_len = buf.len() as isize + start as isize - old_size as isize;
-
-                if diff_len > 0 {
-                    self.truncate_size((old_size as isize + diff_len) as usize).unwrap();
+                if self.prepare_write(start, buf.len(), old_size).is_err() {
+                    return 0;
                 }
 
                 let inode_ref = self.inode.lock().clone();
@@ -277,10 +275,8 @@ impl File for Ext4OSInode {
             None => {
                 let mut offset = self.offset.lock();
                 let start = *offset;
-                let diff_len = buf.len() as isize + start as isize - old_size as isize;
-
-                if diff_len > 0 {
-                    self.truncate_size((old_size as isize + diff_len) as usize).unwrap();
+                if self.prepare_write(start, buf.len(), old_size).is_err() {
+                    return 0;
                 }
 
                 let inode_ref = self.inode.lock().clone();
@@ -431,6 +427,46 @@ impl File for Ext4OSInode {
         self.dirnode_ptr.lock().upgrade()
     }
 
+    /// 文件句柄由 inode 号与 inode 的版本号组成
+    fn file_handle(&self) -> Option<(u64, u32)> {
+        let inode_ref = self.inode.lock();
+        Some((inode_ref.inode_num as u64, inode_ref.inode.generation()))
+    }
+
+    /// 按 inode 号从磁盘读出文件
+    /// # 说明
+    /// + 读出的文件不在目录树中，没有共享的页缓存，因此只能只读打开
+    /// + inode 已释放（链接数为 0）或版本号不符（被回收给了别的文件）时返回 ESTALE
+    fn open_by_inode(
+        &self,
+        ino: u64,
+        generation: u32,
+        flags: OpenFlags,
+    ) -> Result<Arc<dyn File>, isize> {
+        if ino == 0 || ino > self.ext4fs.superblock.total_inodes() as u64 {
+            return Err(ESTALE);
+        }
+        let inode_ref = self.ext4fs.get_inode_ref(ino as u32);
+        if inode_ref.inode.links_count() == 0 || inode_ref.inode.generation() != generation {
+            return Err(ESTALE);
+        }
+        if flags.intersects(OpenFlags::O_WRONLY | OpenFlags::O_RDWR | OpenFlags::O_TRUNC) {
+            return Err(EACCES);
+        }
+        Ok(Arc::new(Self {
+            inode_lock: Arc::new(RwLock::new(InodeLock {})),
+            readable: true,
+            writable: false,
+            special_use: false,
+            append: false,
+            inode: Arc::new(Mutex::new(inode_ref)),
+            offset: Mutex::new(0),
+            dirnode_ptr: Arc::new(Mutex::new(Weak::new())),
+            ext4fs: self.ext4fs.clone(),
+            file_cache_manager: Arc::new(PageCacheManager::new()),
+        }))
+    }
+
     /// 打开文件
     fn open(&self, flags: OpenFlags, special_use: bool) -> Arc<dyn File> {
         Arc::new(Self {
@@ -504,143 +540,118 @@ impl File for Ext4OSInode {
         let inode_mode = match file_type {
             DiskInodeType::File => InodeFileType::S_IFREG.bits(),
             DiskInodeType::Directory => InodeFileType::S_IFDIR.bits(),
-            _ => todo!(),
+            _ => return Err(EINVAL),
         };
-
-        let inode_ref = self.inode.lock();
-        let mut parent_inode_num = inode_ref.inode_num.clone();
-        // println!("self(parent) inode num:{:?} self(parent) name:{:?}", parent_inode_num, inode_ref);
-        let mut nameoff = 0;
-        // println!("in here!!!???");
-        if inode_mode == InodeFileType::S_IFDIR.bits() {
-            let new_inode_num = self.ext4fs.generic_open(
-                name,
-                &mut parent_inode_num,
-                true,
-                inode_mode,
-                &mut nameoff,
-            );
-            // println!("inhere???");
-            if let Ok(new_inode_num) = new_inode_num {
-                let new_inode_ref = self.ext4fs.get_inode_ref(new_inode_num);
-                // println!("new_inode_ref:{:#?}", new_inode_ref);
-                return Ok(Arc::new(Self {
-                    inode_lock: Arc::new(RwLock::new(InodeLock {})),
-                    readable: true,
-                    writable: true,
-                    special_use: false,
-                    append: false,
-                    inode: Arc::new(Mutex::new(new_inode_ref)),
-                    offset: Mutex::new(0),
-                    dirnode_ptr: Arc::new(Mutex::new(Weak::new())),
-                    ext4fs: self.ext4fs.clone(),
-                    // maybe wrong
-                    file_cache_manager: Arc::new(PageCacheManager::new()),
-                }));
-            } else {
-                panic!()
-            }
-        }
-        // println!("[kernel] name={} inode_mode={}", name, inode_mode);
         let inode_perm = (InodePerm::S_IREAD | InodePerm::S_IWRITE).bits();
-        // println!("[kernel] inode_perm = {}", inode_perm);
+
+        let mut parent_inode_ref = self.inode.lock();
+        let parent_inode_num = parent_inode_ref.inode_num;
         let new_inode_ref = self
             .ext4fs
-            // .create(self.inode.inode_num, name, inode_mode | inode_perm);
-            .create(parent_inode_num, name, inode_mode | inode_perm);
-        // xein TODO: 此处有问题
-        // println!("cre1");
-        if let Ok(inode_ref) = new_inode_ref {
-            // println!("Successfully here");
-            return Ok(Arc::new(Self {
-                inode_lock: Arc::new(RwLock::new(InodeLock {})),
-                readable: true,
-                writable: true,
-                special_use: false,
-                append: false,
-                inode: Arc::new(Mutex::new(inode_ref)),
-                offset: Mutex::new(0),
-                dirnode_ptr: Arc::new(Mutex::new(Weak::new())),
-                ext4fs: self.ext4fs.clone(),
-                // maybe wrong
-                file_cache_manager: Arc::new(PageCacheManager::new()),
-            }))
-        } else {
-            panic!()
-        }
+            .create(parent_inode_num, name, inode_mode | inode_perm)?;
+        // 父目录的大小、链接数与时间都可能改变了
+        *parent_inode_ref = self.ext4fs.get_inode_ref(parent_inode_num);
+
+        Ok(Arc::new(Self {
+            inode_lock: Arc::new(RwLock::new(InodeLock {})),
+            readable: true,
+            writable: true,
+            special_use: false,
+            append: false,
+            inode: Arc::new(Mutex::new(new_inode_ref)),
+            offset: Mutex::new(0),
+            dirnode_ptr: Arc::new(Mutex::new(Weak::new())),
+            ext4fs: self.ext4fs.clone(),
+            // maybe wrong
+            file_cache_manager: Arc::new(PageCacheManager::new()),
+        }))
     }
 
+    /// 重命名时在本目录中建立 `child` 的目录项，`child` 已经由 `unlink(false)` 从原来的目录中移除
     fn link_child(&self, name: &str, child: &Self) -> Result<(), isize>
     where
         Self: Sized,
     {
         let mut parent_inode_ref = self.inode.lock();
         let mut child_inode_ref = child.inode.lock();
-        self.ext4fs.dir_add_entry(&mut parent_inode_ref, &child_inode_ref, name).map(|_|())
+        // 以磁盘上的inode为准
+        *parent_inode_ref = self.ext4fs.get_inode_ref(parent_inode_ref.inode_num);
+        *child_inode_ref = self.ext4fs.get_inode_ref(child_inode_ref.inode_num);
+
+        self.ext4fs
+            .dir_add_entry(&mut parent_inode_ref, &child_inode_ref, name)?;
+        if child_inode_ref.inode.is_dir() {
+            // 移动的目录的 ".." 改为指向新的父目录
+            self.ext4fs.dir_remove_entry(&mut child_inode_ref, "..")?;
+            self.ext4fs
+                .dir_add_entry(&mut child_inode_ref, &parent_inode_ref, "..")?;
+            let links = parent_inode_ref.inode.links_count();
+            parent_inode_ref.inode.set_links_count(links + 1);
+        }
+
+        let now = crate::timer::realtime_now().tv_sec as u32;
+        parent_inode_ref.set_mtime(now);
+        parent_inode_ref.set_ctime(now);
+        child_inode_ref.set_ctime(now);
+        self.ext4fs.write_back_inode(&mut parent_inode_ref);
+        self.ext4fs.write_back_inode(&mut child_inode_ref);
+        Ok(())
     }
 
-    // remove file
+    /// 从父目录中删除本文件的目录项
+    /// # 说明
+    /// + `delete` 为真时减少链接数，最后一个链接被删除时释放数据块与inode
+    /// + `delete` 为假时是重命名的前一半，只移除目录项，之后由 `link_child` 在新位置建立
     fn unlink(&self, delete: bool) -> Result<(), isize> {
-        // 先读取自己的 inode，检查是不是非空目录
-        let inode_ref = self.inode.lock();
-        if inode_ref.inode.get_file_type() == DiskInodeType::Directory
-            && self.ext4fs.dir_has_entry(inode_ref.inode_num)
-        {
+        // 以磁盘上的inode为准，检查是不是非空目录
+        let mut inode_ref = self.inode.lock();
+        *inode_ref = self.ext4fs.get_inode_ref(inode_ref.inode_num);
+        let is_dir = inode_ref.inode.is_dir();
+        if delete && is_dir && self.ext4fs.dir_has_entry(inode_ref.inode_num) {
             // 非空目录不能删除
             return Err(ENOTEMPTY);
         }
-        // 记录一下要删除的 inode 编号和类型
-        let ino = inode_ref.inode_num;
-        let is_dir = inode_ref.inode.get_file_type() == DiskInodeType::Directory;
-        drop(inode_ref); // 一旦记录完信息就释放自锁，后面再按需锁父/子 inode
 
         // 找到自己在目录树中的节点，以及父目录 inode
         let dir_node_weak = self.dirnode_ptr.lock().clone();
         let dir_node = dir_node_weak.upgrade().ok_or(ENOTEMPTY)?;
         let father_inode = dir_node.father_arc();
         let parent_osinode = &father_inode.file;
-        // println!("[kernel in unlink] parent osinode: {:?}", parent_osinode.get_file_type());
         let parent = parent_osinode
             .clone()
-            .downcast_arc::<Ext4OSInode>() 
+            .downcast_arc::<Ext4OSInode>()
             .map_err(|_| ENOTEMPTY)?;
 
         let mut parent_inode_ref = parent.inode.lock();
-        let mut parent_inode_ref = parent.inode.lock();
-
-        // 拿到要删除的 child inode 引用
-        let mut child_inode_ref = self.ext4fs.get_inode_ref(ino);
+        *parent_inode_ref = self.ext4fs.get_inode_ref(parent_inode_ref.inode_num);
+        let name = dir_node.name.as_str();
 
-        // 如果需要释放数据块，就先把大小截断到 0
         if delete {
-            self.ext4fs.truncate_inode(&mut child_inode_ref, 0)?;
+            // 删除目录项并更新链接数，链接数为 0 时释放数据块与inode
+            self.ext4fs
+                .unlink(&mut parent_inode_ref, &mut inode_ref, name)?;
+            if inode_ref.inode.links_count() == 0 {
+                // inode 已释放，缓存的页不能再写回
+                self.file_cache_manager.notify_new_size(0);
+            }
+            return Ok(());
         }
 
-        // 打印日志，执行 unlink（删除目录项并更新 link_count）
-        // println!(
-        //     "[kernel in unlink] removing {} {:?}",
-        //     dir_node.name,
-        //     if is_dir { "directory" } else { "file" }
-        // );
-        self.ext4fs.unlink(
-            &mut parent_inode_ref,
-            &mut child_inode_ref,
-            dir_node.name.as_str(),
-        );
-
-        // 写回父 inode
-        self.ext4fs.write_back_inode(&mut parent_inode_ref);
-        // 若释放了数据块，也要写回子 inode
-        if delete {
-            self.ext4fs.write_back_inode(&mut child_inode_ref);
+        self.ext4fs.dir_remove_entry(&mut parent_inode_ref, name)?;
+        if is_dir {
+            // 子目录的 ".." 不再指向原来的父目录
+            let links = parent_inode_ref.inode.links_count();
+            if links > 2 {
+                parent_inode_ref.inode.set_links_count(links - 1);
+            }
         }
-
-        // println!("unlink done");
-
+        let now = crate::timer::realtime_now().tv_sec as u32;
+        parent_inode_ref.set_mtime(now);
+        parent_inode_ref.set_ctime(now);
+        self.ext4fs.write_back_inode(&mut parent_inode_ref);
         Ok(())
     }
 
-
     /// 获取目录项
     /// # 参数
     /// + count：要获取的目录项数量
@@ -651,8 +662,6 @@ impl File for Ext4OSInode {
         const DT_DIR: u8 = 4;
         const DT_REG: u8 = 8;
 
-        use core::mem;
-
         // 锁定目录 inode 和内部偏移
         let _inode_lock = self.inode_lock.write();
         let mut offset = self.offset.lock();
@@ -662,17 +671,20 @@ impl File for Ext4OSInode {
         // 从 ext4fs 读取整个目录项列表
         let entries = self.ext4fs.dir_get_entries(inode_ref.inode_num);
 
-        // 计算每次最多返回的目录项数
-        let max_items = count / mem::size_of::<Dirent>();
         let old_index = *offset;
         let mut result = Vec::new();
         let mut new_index = old_index;
+        // 缓冲区剩余的字节数
+        let mut left = count;
 
-        // 遍历 entries，从 old_index 开始，最多收集 max_items 条
+        // 遍历 entries，从 old_index 开始，收集到缓冲区放不下下一条为止
         for (idx, entry) in entries.iter().enumerate().skip(old_index) {
-            if result.len() >= max_items {
+            let name = entry.get_name();
+            let reclen = Dirent::reclen(name.len());
+            if reclen > left {
                 break;
             }
+            left -= reclen;
             new_index = idx + 1;
 
             // 映射 ext4 条目到通用 Dirent
@@ -684,11 +696,12 @@ impl File for Ext4OSInode {
                 },
                 None => panic!("unknown entry type"),
             };
+            // d_off 是下一条目录项的下标，与 lseek 的偏移量一致
             result.push(Dirent::new(
                 entry.inode as usize,
-                entry.entry_len as isize,
+                new_index as isize,
                 d_type,
-                &entry.get_name().as_str(),
+                name.as_str(),
             ));
         }
 
@@ -715,52 +728,39 @@ impl File for Ext4OSInode {
     }
 
     fn modify_size(&self, diff: isize) -> Result<(), isize> {
-        println!("Should not into here!");
-        // let inode_lock = self.inode_lock.write();
-        let inode_ref = self.inode.lock();
-        debug_assert!(diff.saturating_add(inode_ref.inode.size() as isize) >= 0);
-
-        let old_size = inode_ref.inode.size() as u32;
-        let new_size = (old_size as isize + diff) as u32;
-
-        // drop(inode_lock);
-
-        if diff > 0 {
-            todo!()
-        } else {
-            self.file_cache_manager.notify_new_size(new_size as usize);
-        }
-
-        Ok(())
+        let old_size = self.inode.lock().inode.size() as isize;
+        debug_assert!(diff.saturating_add(old_size) >= 0);
+        self.truncate_size(diff.saturating_add(old_size).max(0) as usize)
     }
 
+    /// 修改文件大小，缩小时丢弃新大小之后的缓存，扩大出的部分是空洞，不分配块
     fn truncate_size(&self, new_size: usize) -> Result<(), isize> {
-        //dead lock
         let mut inode_ref = self.inode.lock();
-        let result = self.ext4fs.truncate_inode(&mut inode_ref, new_size as u64);
-        if let Ok(result) = result {
-            Ok(())
-        } else {
-            panic!("truncate_inode failed: {:?}", result)
+        *inode_ref = self.ext4fs.get_inode_ref(inode_ref.inode_num);
+        let old_size = inode_ref.inode.size() as usize;
+        if new_size < old_size {
+            // 先丢弃缓存，被截掉的块释放之后不能再写回
+            self.file_cache_manager.notify_new_size(new_size);
+            let tail = new_size % PageCacheManager::CACHE_SZ;
+            if tail != 0 {
+                if let Some(cache) = self
+                    .file_cache_manager
+                    .try_get_cache(new_size / PageCacheManager::CACHE_SZ)
+                {
+                    cache.lock().modify(0, |data_block: &mut [u8; PAGE_SIZE]| {
+                        data_block[tail..].fill(0);
+                    });
+                }
+            }
         }
+        self.ext4fs
+            .truncate_inode(&mut inode_ref, new_size as u64)
+            .map(|_| ())
     }
 
     fn set_timestamp(&self, ctime: Option<usize>, atime: Option<usize>, mtime: Option<usize>) {
-        // unsafe {
-        //     // 将 Arc 转换为裸指针
-        //     let ptr = Arc::as_ptr(&self.inode) as *mut Ext4Inode;
-        //     // 直接修改数据
-        //     if let Some(ctime) = ctime {
-        //         (*ptr).set_ctime(ctime as u32);
-        //     }
-        //     if let Some(atime) = atime {
-        //         (*ptr).set_atime(atime as u32);
-        //     }
-        //     if let Some(mtime) = mtime {
-        //         (*ptr).set_mtime(mtime as u32);
-        //     }
-        // }
         let mut inode_ref = self.inode.lock();
+        *inode_ref = self.ext4fs.get_inode_ref(inode_ref.inode_num);
         if let Some(ct) = ctime {
             inode_ref.set_ctime(ct as u32);
         }
@@ -770,11 +770,12 @@ impl File for Ext4OSInode {
         if let Some(mt) = mtime {
             inode_ref.set_mtime(mt as u32);
         }
+        self.ext4fs.write_back_inode(&mut inode_ref);
     }
 
     /// 获取单个缓存页
     fn get_single_cache(&self, offset: usize) -> Result<Arc<Mutex<PageCache>>, ()> {
-        let inode_ref = self.inode.lock();
+        let mut inode_ref = self.inode.lock();
         // 传入的 offset 实际上是cache号，或者说是第几个块
         // TODO:
         // 写到此处的时候还没有搞透彻pagecache到底是
@@ -785,6 +786,18 @@ impl File for Ext4OSInode {
             panic!("Invalid cache offset");
             return Err(());
         }
+        // 共享映射中写入的页要能写回，先给该页中文件末尾之前的空洞分配块
+        let block_size = self.ext4fs.block_size;
+        let end = (offset + PAGE_SIZE).min(inode_ref.inode.size() as usize);
+        if offset < end {
+            let result = self.ext4fs.map_blocks(
+                &mut inode_ref,
+                (offset / block_size) as u32,
+                ((end + block_size - 1) / block_size) as u32,
+            );
+            self.ext4fs.write_back_inode(&mut inode_ref);
+            result.map_err(|_| ())?;
+        }
         // 将偏移量按页大小对齐并转换为缓存页ID
         let inner_cache_id = offset >> 12;
         let result = self.file_cache_manager.get_cache(
@@ -828,9 +841,24 @@ impl File for Ext4OSInode {
         Ok(cache_list)
     }
 
-    /// 这个先不考虑实现
+    /// 写回本文件的脏页，再让设备把写缓存落盘
+    fn fsync(&self) {
+        let inode_ref = Arc::new(self.inode.lock().clone());
+        let neighbor = |inner_cache_id| self.get_neighboring_blk(inner_cache_id, inode_ref.clone());
+        self.file_cache_manager
+            .sync_all(neighbor, &self.ext4fs.block_device);
+        self.ext4fs.block_device.flush();
+    }
+
+    /// 内存不足时写回并释放不常用的缓存页，inode 正在被使用时跳过
     fn oom(&self) -> usize {
-        todo!()
+        let inode_ref = match self.inode.try_lock() {
+            Some(inode_ref) => Arc::new(inode_ref.clone()),
+            None => return 0,
+        };
+        let neighbor = |inner_cache_id| self.get_neighboring_blk(inner_cache_id, inode_ref.clone());
+        self.file_cache_manager
+            .oom(neighbor, &self.ext4fs.block_device)
     }
 
     /// 这个也一样
@@ -877,13 +905,11 @@ impl Ext4OSInode {
                 // );
                 break;
             }
-            // 获取物理块号
-            // TODO: 此处可能有问题
-            let start_block_id = self
-                .ext4fs
-                .get_pblock_idx(&inode_ref, blk_id as u32)
-                .unwrap();
-            block_ids.push(start_block_id as usize);
+            // 获取物理块号，空洞之后的块不属于这一页的连续块
+            match self.ext4fs.get_pblock_idx(&inode_ref, blk_id as u32) {
+                Ok(block_id) => block_ids.push(block_id as usize),
+                Err(_) => break,
+            }
             blk_id += 1;
         }
         // println!("[kernel in get_neighboring_blk] block_ids: {:?}", block_ids);
@@ -905,35 +931,34 @@ impl Ext4OSInode {
             return 0;
         }
         let mut start_cache = start / PageCacheManager::CACHE_SZ;
+        let end_cache = (end - 1) / PageCacheManager::CACHE_SZ + 1;
         let mut read_size = 0;
-        loop {
-            // 计算当前块的结束位置
-            let mut end_current_block =
-                (start / PageCacheManager::CACHE_SZ + 1) * PageCacheManager::CACHE_SZ;
-            end_current_block = end_current_block.min(end);
-            // 读取并更新读取长度
-            // TODO: 后期记得尝试加锁！
-            let block_read_size = end_current_block - start;
-            self.file_cache_manager
-                .get_cache(
-                    start_cache,
-                    || -> Vec<usize> { self.get_neighboring_blk(start_cache, inode_ref.clone()) },
-                    &self.ext4fs.block_device,
-                )
-                .lock()
-                .read(0, |data_block: &[u8; PAGE_SIZE]| {
+        while start_cache < end_cache {
+            // 一批页一起获取，不在缓存中的页合并成较大的请求读入
+            let batch_end = end_cache.min(start_cache + READ_BATCH_PAGES);
+            let caches = self.file_cache_manager.get_caches(
+                start_cache..batch_end,
+                |inner_cache_id| self.get_neighboring_blk(inner_cache_id, inode_ref.clone()),
+                &self.ext4fs.block_device,
+            );
+            for cache in caches {
+                // 计算当前块的结束位置
+                let end_current_block = ((start / PageCacheManager::CACHE_SZ + 1)
+                    * PageCacheManager::CACHE_SZ)
+                    .min(end);
+                // 读取并更新读取长度
+                // TODO: 后期记得尝试加锁！
+                let block_read_size = end_current_block - start;
+                cache.lock().read(0, |data_block: &[u8; PAGE_SIZE]| {
                     let dst = &mut buffer[read_size..read_size + block_read_size];
                     let src = &data_block[start % PageCacheManager::CACHE_SZ
                         ..start % PageCacheManager::CACHE_SZ + block_read_size];
                     dst.copy_from_slice(src);
                 });
-            read_size += block_read_size;
-
-            if end_current_block == end {
-                break;
+                read_size += block_read_size;
+                start = end_current_block;
             }
-            start_cache += 1;
-            start = end_current_block;
+            start_cache = batch_end;
         }
         read_size
     }
@@ -965,6 +990,34 @@ impl Ext4OSInode {
 }
 
 impl Ext4OSInode {
+    /// 通过页缓存写入 `start` 起的 `len` 字节之前，为这一范围分配好块：
+    /// 超出文件末尾时扩大文件，扩大出的部分与文件中的空洞都是未映射的，
+    /// 页缓存只能写回已经映射的块
+    fn prepare_write(&self, start: usize, len: usize, old_size: usize) -> Result<(), isize> {
+        if start + len > old_size {
+            self.truncate_size(start + len)?;
+        }
+        let block_size = self.ext4fs.block_size;
+        let from = start / block_size;
+        let to = (start + len + block_size - 1) / block_size;
+        if from < to {
+            let mut inode_ref = self.inode.lock();
+            let result = self
+                .ext4fs
+                .map_blocks(&mut inode_ref, from as u32, to as u32);
+            self.ext4fs.write_back_inode(&mut inode_ref);
+            drop(inode_ref);
+            if let Err(errno) = result {
+                // 空间不足，文件恢复原来的大小，已经分配的块随之释放
+                if start + len > old_size {
+                    self.truncate_size(old_size)?;
+                }
+                return Err(errno);
+            }
+        }
+        Ok(())
+    }
+
     fn update_block_cache(&self, offset: usize, buf: &[u8], inode_ref: Arc<Ext4InodeRef>) -> usize {
         let mut start = offset;
         let old_size = inode_ref.inode.get_file_size() as usize;
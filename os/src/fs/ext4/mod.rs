@@ -8,15 +8,24 @@ mod ext4_inode;
 pub mod ext4fs;
 mod extent;
 mod file;
+/// 自检用的镜像与一致性检查，ext4 的块大小须等于 `BLOCK_SZ`，只在 LoongArch 上成立
+#[cfg(all(feature = "selftest", feature = "loongarch64"))]
+mod fsck;
 mod ialloc;
 pub mod layout;
 mod path;
 mod superblock;
 mod test;
 #[allow(unused)]
-pub use super::cache::{BlockCacheManager, BufferCache, Cache, PageCache, PageCacheManager};
+pub use super::cache::{
+    BlockCacheManager, BufferCache, Cache, PageCache, PageCacheManager, READ_BATCH_PAGES,
+};
 pub use crate::drivers::block::BlockDevice;
 pub use ext4_inode::*;
+#[cfg(feature = "selftest")]
+pub use crc::ext4_crc32c;
+#[cfg(all(feature = "selftest", feature = "loongarch64"))]
+pub use fsck::mkfs;
 
 /// Inode相关的常量
 /// 根目录的inode号
@@ -40,6 +49,8 @@ pub const EXT4_INODE_MODE_PERM_MASK: u16 = 0x0FFF;
 pub const EXT4_GOOD_OLD_INODE_SIZE: u16 = 128;
 /// Inode扩展标志
 pub const EXT4_INODE_FLAG_EXTENTS: usize = 0x00080000; /* Inode uses extents */
+/// 目录使用哈希索引（htree）
+pub const EXT4_INODE_FLAG_INDEX: usize = 0x00001000;
 /// BLock group descriptor flags.
 /// 最小块组描述符大小
 pub const EXT4_MIN_BLOCK_GROUP_DESCRIPTOR_SIZE: u16 = 32;
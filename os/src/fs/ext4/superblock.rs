@@ -7,6 +7,10 @@ use crc::{ext4_crc32c, EXT4_CRC32_INIT};
 
 use super::*;
 pub const SUPERBLOCK_OFFSET: usize = 1024;
+/// 只读兼容特性：元数据校验和
+pub const EXT4_FEATURE_RO_COMPAT_METADATA_CSUM: u32 = 0x0400;
+/// 只读兼容特性：块组描述符校验和 (gdt_csum/uninit_bg)
+pub const EXT4_FEATURE_RO_COMPAT_GDT_CSUM: u32 = 0x0010;
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -207,6 +211,20 @@ impl Ext4Superblock {
         self.free_inodes_count -= 1;
     }
 
+    pub fn set_free_inodes_count(&mut self, free_inodes: u32) {
+        self.free_inodes_count = free_inodes;
+    }
+
+    /// 是否启用了元数据校验和 (metadata_csum)
+    pub fn has_metadata_csum(&self) -> bool {
+        self.features_read_only & EXT4_FEATURE_RO_COMPAT_METADATA_CSUM != 0
+    }
+
+    /// 是否启用了块组描述符校验和 (gdt_csum)，与 metadata_csum 同时启用时以后者为准
+    pub fn has_gdt_csum(&self) -> bool {
+        self.features_read_only & EXT4_FEATURE_RO_COMPAT_GDT_CSUM != 0
+    }
+
     pub fn free_blocks_count(&self) -> u64 {
         self.free_blocks_count_lo as u64 | ((self.free_blocks_count_hi as u64) << 32).to_le()
     }
@@ -26,6 +26,15 @@ pub struct Fat {
     vacant_clus: Mutex<VecDeque<u32>>,
     /// The final unused cluster id we found
     hint: Mutex<usize>,
+    /// 空闲簇的计数，首次使用时遍历 FAT 得到
+    clus_count: Mutex<Option<ClusCount>>,
+}
+
+/// 空闲簇的数目，以及其中为文件末尾的空洞预留的簇数
+struct ClusCount {
+    free: usize,
+    /// 预留的簇只能由预留它们的文件分配，保证扩展出的空洞总能在写回时得到簇
+    reserved: usize,
 }
 
 impl Fat {
@@ -96,6 +105,7 @@ impl Fat {
             tot_ent: clus,
             vacant_clus: Mutex::new(VecDeque::new()),
             hint: Mutex::new(0),
+            clus_count: Mutex::new(None),
         }
     }
 
@@ -151,11 +161,27 @@ impl Fat {
     /// # 返回值
     /// 簇号列表
     pub fn alloc(
+        &self,
+        block_device: &Arc<dyn BlockDevice>,
+        alloc_num: usize,
+        last: Option<u32>,
+    ) -> Vec<u32> {
+        self.alloc_reserved(block_device, alloc_num, last, 0)
+    }
+
+    /// 与 [`Fat::alloc`] 相同，但其中 `reserved` 个簇从调用者预留的簇中分配；
+    /// 其余的簇不能用到为别的文件预留的簇
+    pub fn alloc_reserved(
         &self,
         block_device: &Arc<dyn BlockDevice>,
         alloc_num: usize,
         mut last: Option<u32>,
+        reserved: usize,
     ) -> Vec<u32> {
+        let mut count = self.clus_count(block_device);
+        let count = count.as_mut().unwrap();
+        let reserved = reserved.min(alloc_num).min(count.reserved);
+        let alloc_num = alloc_num.min(count.free - (count.reserved - reserved));
         // 先在内存中创建一个空的簇号列表
         let mut allocated_cluster = Vec::with_capacity(alloc_num);
         // 需要一个锁来保证进程间的互斥
@@ -174,6 +200,8 @@ impl Fat {
         }
         // 设置最后一个簇的下一个簇为EOC
         self.set_next_clus(block_device, last, EOC);
+        count.free -= allocated_cluster.len();
+        count.reserved -= reserved.min(allocated_cluster.len());
         allocated_cluster
     }
 
@@ -216,6 +244,46 @@ impl Fat {
         Some(free_clus_id)
     }
 
+    /// 数据区的簇数
+    pub fn total_clus(&self) -> usize {
+        self.tot_ent
+    }
+
+    /// 空闲簇的计数，第一次调用时遍历 FAT 统计，之后由分配与释放维护
+    fn clus_count(&self, block_device: &Arc<dyn BlockDevice>) -> MutexGuard<Option<ClusCount>> {
+        let mut count = self.clus_count.lock();
+        if count.is_none() {
+            let free = (0..self.tot_ent as u32)
+                .filter(|&clus_id| FAT_ENTRY_FREE == self.get_next_clus_num(clus_id, block_device))
+                .count();
+            *count = Some(ClusCount { free, reserved: 0 });
+        }
+        count
+    }
+
+    /// 空闲簇的数目，包括已预留的簇
+    pub fn free_clus(&self, block_device: &Arc<dyn BlockDevice>) -> usize {
+        self.clus_count(block_device).as_ref().unwrap().free
+    }
+
+    /// 为文件的空洞预留 `num` 个簇，未预留的空闲簇不够时返回假
+    pub fn reserve(&self, block_device: &Arc<dyn BlockDevice>, num: usize) -> bool {
+        let mut count = self.clus_count(block_device);
+        let count = count.as_mut().unwrap();
+        if count.free - count.reserved < num {
+            return false;
+        }
+        count.reserved += num;
+        true
+    }
+
+    /// 归还预留的 `num` 个簇
+    pub fn unreserve(&self, num: usize) {
+        if let Some(count) = self.clus_count.lock().as_mut() {
+            count.reserved -= num;
+        }
+    }
+
     /// Find next free cluster from data area.
     /// # Argument
     /// + `start`: The cluster id to traverse to find the next free cluster
@@ -248,7 +316,9 @@ impl Fat {
         last: Option<u32>,
     ) {
         // Before freeing, a lock
+        let mut count = self.clus_count(block_device);
         let mut lock = self.vacant_clus.lock();
+        count.as_mut().unwrap().free += cluster_list.len();
         for cluster_id in cluster_list {
             self.set_next_clus(block_device, Some(cluster_id), FAT_ENTRY_FREE);
             if lock.len() < VACANT_CLUS_CACHE_SIZE {
@@ -257,4 +327,9 @@ impl Fat {
         }
         self.set_next_clus(block_device, last, EOC);
     }
+
+    /// Write the modified FAT sectors back to the device
+    pub fn sync(&self, block_device: &Arc<dyn BlockDevice>) {
+        self.fat_cache_mgr.lock().sync_all(block_device);
+    }
 }
@@ -3,14 +3,17 @@ use super::DiskInodeType;
 use crate::fs::fat32::dir_iter::*;
 use crate::fs::fat32::layout::{FATDirEnt, FATDiskInodeType, FATLongDirEnt, FATShortDirEnt};
 use crate::fs::fat32::EasyFileSystem;
-use crate::fs::fat32::{BlockCacheManager, Cache, PageCache, PageCacheManager};
+use crate::fs::fat32::{BlockCacheManager, Cache, PageCache, PageCacheManager, READ_BATCH_PAGES};
 use crate::fs::inode::InodeLock;
 use crate::fs::inode::InodeTime;
 use crate::fs::inode::InodeTrait;
+use crate::fs::inode::{InodeKey, INODE_CACHE};
 use crate::fs::vfs::VFSFileContent;
 use crate::fs::vfs::VFS;
+use crate::syscall::errno::ENOSPC;
 use alloc::string::String;
 use alloc::sync::Arc;
+use alloc::vec;
 use alloc::vec::Vec;
 use core::any::Any;
 use core::convert::TryInto;
@@ -25,10 +28,13 @@ pub struct FileContent {
     /// 所以需要遍历FAT32来获取size
     size: u32,
     /// 簇列表
+    /// 可能只覆盖文件的开头：之后直到 `size` 的部分是空洞，读出 0 且不占用簇
     clus_list: Vec<u32>,
     /// 如果该文件是个目录，那么
     /// hint 会记录最后一个目录项的位置（第一个字节为0x00）
     hint: u32,
+    /// 为末尾的空洞预留的簇数，写回之前给空洞分配簇时一定够用
+    reserved: usize,
 }
 
 impl VFSFileContent for FileContent {}
@@ -82,10 +88,14 @@ impl Drop for FatInode {
             let mut lock = self.file_content.write();
             let length = lock.clus_list.len();
             self.dealloc_clus(&mut lock, length);
+            self.fs.fat.unreserve(lock.reserved);
+            lock.reserved = 0;
         } else {
             if self.parent_dir.lock().is_none() {
                 return;
             }
+            // FAT32 的簇链表示不了空洞，写回大小之前先给空洞分配簇
+            self.fill_hole_on_disk();
             let par_dir_lock = self.parent_dir.lock();
             let (parent_dir, offset) = par_dir_lock.as_ref().unwrap();
 
@@ -141,6 +151,7 @@ impl FatInode {
             size,
             clus_list,
             hint,
+            reserved: 0,
         });
         let parent_dir = Mutex::new(parent_dir);
         let time = InodeTime::new();
@@ -271,18 +282,28 @@ impl FatInode {
 /// 文件内容操作相关方法
 impl FatInode {
     /// 分配需要的簇
-    /// 需要尽可能多的分配簇，然后追加到`lock`中的`clus_list`中
+    /// 需要尽可能多的分配簇，然后追加到`lock`中的`clus_list`中，先用掉为空洞预留的簇
     /// # 参数
     /// + `lock`: 目标文件内容（锁）
     /// + `alloc_num`: 需要分配的簇数
     fn alloc_clus(&self, lock: &mut RwLockWriteGuard<FileContent>, alloc_num: usize) {
-        let clus_list = &mut lock.clus_list;
-        let mut new_clus_list = self.fs.fat.alloc(
+        let mut new_clus_list = self.fs.fat.alloc_reserved(
             &self.fs.block_device,
             alloc_num,
-            clus_list.last().map(|clus| *clus),
+            lock.clus_list.last().map(|clus| *clus),
+            lock.reserved,
         );
-        clus_list.append(&mut new_clus_list);
+        let used = lock.reserved.min(new_clus_list.len());
+        lock.reserved -= used;
+        lock.clus_list.append(&mut new_clus_list);
+    }
+    /// 归还比末尾空洞所需更多的预留簇
+    fn release_reserved(&self, lock: &mut RwLockWriteGuard<FileContent>) {
+        let hole = (self.total_clus(lock.size) as usize).saturating_sub(lock.clus_list.len());
+        if lock.reserved > hole {
+            self.fs.fat.unreserve(lock.reserved - hole);
+            lock.reserved = hole;
+        }
     }
     /// 从lock中的clus_list释放一定数量的簇
     /// 当要释放的数量超过可用数量时，`clus_list` 会被清空
@@ -302,6 +323,33 @@ impl FatInode {
             clus_list.last().map(|x| *x),
         );
     }
+    /// 给文件末尾的空洞分配簇并直接在磁盘上清零，用于写回目录项之前
+    /// 空洞的簇在扩展文件时已经预留（见 `reserve_hole_lock`），分配不会失败
+    fn fill_hole_on_disk(&self) {
+        let mut lock = self.file_content.write();
+        let first_new = lock.clus_list.len();
+        let missing = (self.total_clus(lock.size) as usize).saturating_sub(first_new);
+        if missing == 0 {
+            return;
+        }
+        self.alloc_clus(&mut lock, missing);
+        let clus_size = self.fs.clus_size();
+        let zeros = vec![0u8; clus_size as usize];
+        for clus in lock.clus_list[first_new..].iter() {
+            self.fs
+                .block_device
+                .write_block(self.fs.first_sector_of_cluster(*clus) as usize, &zeros);
+        }
+        let backed = lock.clus_list.len() as u32 * clus_size;
+        if lock.size > backed {
+            log::error!(
+                "[fill_hole_on_disk] reserved clusters missing, size {} cut to {}",
+                lock.size,
+                backed
+            );
+            lock.size = backed;
+        }
+    }
     fn clear_at_block_cache_lock(
         &self,
         _inode_lock: &RwLockWriteGuard<InodeLock>,
@@ -758,8 +806,23 @@ impl FatInode {
     /// + `offset`: the offset of the short directory entry in the `parent_dir`
     /// # 返回值
     /// 指向Inode的指针
+    /// 目录项仍对应一个在用的 inode 时返回它（见 inode 缓存），否则新建并放入缓存
     pub fn from_fat_ent(parent_dir: &Arc<Self>, ent: &FATShortDirEnt, offset: u32) -> Arc<Self> {
-        Self::new(
+        let key = Self::dir_ent_key(parent_dir, offset);
+        // 查找与插入在同一次加锁内完成，并发的查找不会各建一个 inode
+        let mut cache = INODE_CACHE.lock();
+        if let Some(key) = key {
+            let cached = cache
+                .get(key)
+                .and_then(|inode| inode.as_any_arc().downcast::<Self>().ok());
+            // 缓存的 inode 已被删除或改名时，它不再对应这个目录项
+            if let Some(inode) = cached {
+                if !*inode.deleted.lock() && inode.cache_key() == Some(key) {
+                    return inode;
+                }
+            }
+        }
+        let inode = Self::new(
             ent.get_first_clus(),
             if ent.is_dir() {
                 DiskInodeType::Directory
@@ -773,7 +836,21 @@ impl FatInode {
             },
             Some((parent_dir.clone(), offset)),
             parent_dir.fs.clone(),
-        )
+        );
+        if let Some(key) = key {
+            cache.insert(key, &(inode.clone() as Arc<dyn InodeTrait>));
+        }
+        inode
+    }
+
+    /// 父目录中偏移 `offset` 处目录项的 inode 缓存键：与 Linux vfat 的 i_pos 一样，
+    /// 以目录项的位置（父目录的首簇与偏移）作为 inode 号，空文件没有首簇也能区分
+    fn dir_ent_key(parent_dir: &Arc<Self>, offset: u32) -> Option<InodeKey> {
+        let parent_clus = parent_dir.get_first_clus_lock(&parent_dir.file_content.read())?;
+        Some(InodeKey {
+            device: Arc::as_ptr(&parent_dir.fs) as usize,
+            ino: (parent_clus as u64) << 32 | offset as u64,
+        })
     }
 
     /// Fill out an empty directory with only the '.' & '..' entries.
@@ -976,43 +1053,43 @@ impl InodeTrait for FatInode {
     ) -> usize {
         let mut start = offset;
         let size = self.file_content.read().size as usize;
-        let end = (offset + buf.len()).min(size);
+        // 从文件末尾或之后开始的读返回 0，跨过文件末尾的读只读到末尾为止
+        let end = offset.saturating_add(buf.len()).min(size);
         if start >= end {
             return 0;
         }
         let mut start_cache = start / PageCacheManager::CACHE_SZ;
+        let end_cache = (end - 1) / PageCacheManager::CACHE_SZ + 1;
         let mut read_size = 0;
-        loop {
-            // calculate end of current block
-            // 计算当前块的结束位置
-            let mut end_current_block =
-                (start / PageCacheManager::CACHE_SZ + 1) * PageCacheManager::CACHE_SZ;
-            end_current_block = end_current_block.min(end);
-            // 读取并更新读取长度
+        while start_cache < end_cache {
+            // 一批页一起获取，不在缓存中的页合并成较大的请求读入
+            let batch_end = end_cache.min(start_cache + READ_BATCH_PAGES);
             let lock = self.file_content.read();
-            let block_read_size = end_current_block - start;
-            self.file_cache_mgr
-                .get_cache(
-                    start_cache,
-                    || -> Vec<usize> { self.get_neighboring_sec(&lock.clus_list, start_cache) },
-                    &self.fs.block_device,
-                )
-                .lock()
+            let caches = self.file_cache_mgr.get_caches(
+                start_cache..batch_end,
+                |inner_cache_id| self.get_neighboring_sec(&lock.clus_list, inner_cache_id),
+                &self.fs.block_device,
+            );
+            drop(lock);
+            for cache in caches {
+                // calculate end of current block
+                // 计算当前块的结束位置
+                let end_current_block = ((start / PageCacheManager::CACHE_SZ + 1)
+                    * PageCacheManager::CACHE_SZ)
+                    .min(end);
+                // 读取并更新读取长度
+                let block_read_size = end_current_block - start;
                 // I know hardcoding 4096 in is bad, but I can't get around Rust's syntax checking...
-                .read(0, |data_block: &[u8; 4096]| {
+                cache.lock().read(0, |data_block: &[u8; 4096]| {
                     let dst = &mut buf[read_size..read_size + block_read_size];
                     let src = &data_block[start % PageCacheManager::CACHE_SZ
                         ..start % PageCacheManager::CACHE_SZ + block_read_size];
                     dst.copy_from_slice(src);
                 });
-            drop(lock);
-            read_size += block_read_size;
-            // move to next block
-            if end_current_block == end {
-                break;
+                read_size += block_read_size;
+                start = end_current_block;
             }
-            start_cache += 1;
-            start = end_current_block;
+            start_cache = batch_end;
         }
         read_size
     }
@@ -1025,7 +1102,8 @@ impl InodeTrait for FatInode {
     ) -> usize {
         let mut start = offset;
         let size = self.file_content.read().size as usize;
-        let end = (offset + buf.len()).min(size);
+        // 从文件末尾或之后开始的读返回 0，跨过文件末尾的读只读到末尾为止
+        let end = offset.saturating_add(buf.len()).min(size);
         if start >= end {
             return 0;
         }
@@ -1096,16 +1174,27 @@ impl InodeTrait for FatInode {
         offset: usize,
         buf: &[u8],
     ) -> usize {
+        if buf.is_empty() {
+            return 0;
+        }
         let mut start = offset;
         let old_size = self.get_file_size() as usize;
-        let diff_len = buf.len() as isize + offset as isize - old_size as isize;
-        if diff_len > 0 as isize {
-            // allocate as many clusters as possible.
-            self.modify_size_lock(inode_lock, diff_len, false);
+        let end = offset + buf.len();
+        if end > old_size {
+            // 先把文件扩展到写入的末尾，旧末尾与 offset 之间读出 0
+            self.modify_size_lock(inode_lock, (end - old_size) as isize, true);
+        }
+        // 写入的范围及其之前的空洞都需要簇
+        let backed = self.fill_hole_lock(inode_lock, end);
+        if backed < end && end > old_size {
+            // 磁盘已满，文件只扩展到写得进去的位置
+            let new_size = backed.max(old_size);
+            self.modify_size_lock(inode_lock, new_size as isize - end as isize, true);
+        }
+        let end = backed;
+        if start >= end {
+            return 0;
         }
-        let end = (offset + buf.len()).min(self.get_file_size() as usize);
-
-        debug_assert!(start <= end);
 
         let mut start_cache = start / PageCacheManager::CACHE_SZ;
         let mut write_size = 0;
@@ -1370,11 +1459,39 @@ impl InodeTrait for FatInode {
         self.file_cache_mgr.oom(neighbor, &self.fs.block_device)
     }
 
+    /// 不论优先级，释放所有未被引用的缓存页，脏页先写回
+    fn drop_caches(&self) -> usize {
+        let neighbor = |inner_cache_id| {
+            self.get_neighboring_sec(&self.file_content.read().clus_list, inner_cache_id)
+        };
+        self.file_cache_mgr.drop_all(neighbor, &self.fs.block_device)
+    }
+
+    /// 根目录与已删除的文件没有目录项，不在缓存中
+    fn cache_key(&self) -> Option<InodeKey> {
+        match &*self.parent_dir.lock() {
+            Some((parent_dir, offset)) => Self::dir_ent_key(parent_dir, *offset),
+            None => None,
+        }
+    }
+
+    /// 写回本文件的脏页与 FAT 表，再让设备把写缓存落盘
+    fn fsync(&self) {
+        let neighbor = |inner_cache_id| {
+            self.get_neighboring_sec(&self.file_content.read().clus_list, inner_cache_id)
+        };
+        self.file_cache_mgr.sync_all(neighbor, &self.fs.block_device);
+        self.fs.fat.sync(&self.fs.block_device);
+        self.fs.block_device.flush();
+    }
+
     /// 改变当前文件的大小
     /// This operation is ignored if the result size is negative
     /// # 参数
     /// + `inode_lock`: inode锁
     /// + `diff`: file 大小的改变量
+    /// + `clear`: 为真时扩展出的部分读出 0：已有簇的部分清零，其余留作空洞，
+    ///   直到写入时才分配簇；为假时立即为扩展出的部分分配簇（目录）
     /// # 警告
     /// This function will not modify its parent directory (since we changed the size of the current file),
     /// we will modify it when it is deleted.
@@ -1386,24 +1503,30 @@ impl InodeTrait for FatInode {
         let old_size = lock.size;
         let new_size = (lock.size as isize + diff) as u32;
 
-        let old_clus_num = self.total_clus(old_size) as usize;
+        let allocated = lock.clus_list.len();
         let new_clus_num = self.total_clus(new_size) as usize;
 
         if diff > 0 {
-            self.alloc_clus(&mut lock, new_clus_num - old_clus_num);
+            if !clear {
+                self.alloc_clus(&mut lock, new_clus_num.saturating_sub(allocated));
+            }
         } else {
-            self.dealloc_clus(&mut lock, old_clus_num - new_clus_num);
+            self.dealloc_clus(&mut lock, allocated.saturating_sub(new_clus_num));
         }
 
+        let backed = lock.clus_list.len() as u32 * self.fs.clus_size();
         lock.size = new_size;
+        self.release_reserved(&mut lock);
         drop(lock);
 
         if diff > 0 {
-            if clear {
+            // 最后一个簇中旧末尾之后的部分可能是以前的数据
+            let clear_end = new_size.min(backed);
+            if clear && clear_end > old_size {
                 self.clear_at_block_cache_lock(
                     inode_lock,
                     old_size as usize,
-                    (new_size - old_size) as usize,
+                    (clear_end - old_size) as usize,
                 );
             }
         } else {
@@ -1411,6 +1534,64 @@ impl InodeTrait for FatInode {
         }
     }
 
+    /// 为 `end` 之前的空洞分配簇，新簇中属于文件的部分清零
+    /// FAT32 的簇链只能从头连续，`end` 之前的所有空洞都会被填上
+    fn fill_hole_lock(&self, inode_lock: &RwLockWriteGuard<InodeLock>, end: usize) -> usize {
+        let clus_size = self.fs.clus_size() as usize;
+        let mut lock = self.file_content.write();
+        let old_backed = lock.clus_list.len() * clus_size;
+        if end <= old_backed {
+            return end;
+        }
+        let missing = self.total_clus(end as u32) as usize - lock.clus_list.len();
+        self.alloc_clus(&mut lock, missing);
+        let backed = lock.clus_list.len() * clus_size;
+        let size = lock.size as usize;
+        drop(lock);
+        // 新分配的簇里是磁盘上以前的数据
+        let clear_end = backed.min(size);
+        if clear_end > old_backed {
+            self.clear_at_block_cache_lock(inode_lock, old_backed, clear_end - old_backed);
+        }
+        backed.min(end)
+    }
+
+    /// 簇链只能从头连续，有簇的开头部分是数据，之后到文件末尾是空洞
+    fn data_extents(&self) -> Option<Vec<(usize, usize)>> {
+        let lock = self.file_content.read();
+        let backed = lock.clus_list.len() * self.fs.clus_size() as usize;
+        let end = backed.min(lock.size as usize);
+        Some(if end == 0 { Vec::new() } else { vec![(0, end)] })
+    }
+
+    /// 文件扩展到 `new_size` 时为末尾的空洞预留簇
+    fn reserve_hole_lock(
+        &self,
+        _inode_lock: &RwLockWriteGuard<InodeLock>,
+        new_size: usize,
+    ) -> Result<(), isize> {
+        let mut lock = self.file_content.write();
+        let hole =
+            (self.total_clus(new_size as u32) as usize).saturating_sub(lock.clus_list.len());
+        if hole > lock.reserved {
+            if !self.fs.fat.reserve(&self.fs.block_device, hole - lock.reserved) {
+                return Err(ENOSPC);
+            }
+            lock.reserved = hole;
+        }
+        Ok(())
+    }
+
+    /// 以簇为块报告文件系统的容量与空闲空间，空洞预留的簇仍算作空闲
+    fn fs_blocks(&self) -> Option<(usize, u64, u64)> {
+        let free = self.fs.fat.free_clus(&self.fs.block_device);
+        Some((
+            self.fs.clus_size() as usize,
+            self.fs.fat.total_clus() as u64,
+            free as u64,
+        ))
+    }
+
     fn is_empty_dir_lock(&self, inode_lock: &RwLockWriteGuard<InodeLock>) -> bool {
         if !self.is_dir() {
             return false;
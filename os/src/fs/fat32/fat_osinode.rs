@@ -6,9 +6,14 @@ use alloc::{
 use spin::Mutex;
 
 use crate::{
+    config::PAGE_SIZE,
     fs::{
-        directory_tree::DirectoryTreeNode, fat32::layout::FATDiskInodeType, file_trait::File,
-        inode::InodeTrait, Dirent, OpenFlags, SeekWhence, Stat, StatMode,
+        directory_tree::DirectoryTreeNode,
+        dirent::{take_fitting, MIN_RECLEN},
+        fat32::layout::FATDiskInodeType,
+        file_trait::File,
+        inode::{InodeTrait, INODE_CACHE},
+        Dirent, OpenFlags, SeekWhence, Stat, StatMode,
     },
     mm::UserBuffer,
     syscall::errno::*,
@@ -144,12 +149,17 @@ impl File for FatOSInode {
     fn w_ready(&self) -> bool {
         true
     }
+    /// 读到文件末尾为止，偏移在文件末尾或之后时返回 0
+    /// # 说明
+    /// + 整个读持有 inode 的读锁，并发的写与截断要么在读之前、要么在读之后生效，
+    ///   读到的大小与内容总是同一时刻的文件
+    /// + 先锁文件偏移再锁 inode，与 `write` 的加锁顺序一致
     fn read_user(&self, offset: Option<usize>, mut buf: UserBuffer) -> usize {
         let mut total_read_size = 0usize;
 
-        let inode_lock = self.inner.read();
         match offset {
             Some(mut offset) => {
+                let inode_lock = self.inner.read();
                 let mut offset = &mut offset;
                 for slice in buf.buffers.iter_mut() {
                     let read_size =
@@ -164,6 +174,7 @@ impl File for FatOSInode {
             }
             None => {
                 let mut offset = self.offset.lock();
+                let inode_lock = self.inner.read();
                 for slice in buf.buffers.iter_mut() {
                     let read_size =
                         self.inner
@@ -182,9 +193,9 @@ impl File for FatOSInode {
     fn write_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
         let mut total_write_size = 0usize;
 
-        let inode_lock = self.inner.write();
         match offset {
             Some(mut offset) => {
+                let inode_lock = self.inner.write();
                 let mut offset = &mut offset;
                 for slice in buf.buffers.iter() {
                     let write_size =
@@ -197,6 +208,7 @@ impl File for FatOSInode {
             }
             None => {
                 let mut offset = self.offset.lock();
+                let inode_lock = self.inner.write();
                 if self.append {
                     *offset = self.inner.get_file_size_wlock(&inode_lock) as usize;
                 }
@@ -250,6 +262,25 @@ impl File for FatOSInode {
         self.dirnode_ptr.lock().upgrade()
     }
 
+    fn data_extents(&self) -> Option<Vec<(usize, usize)>> {
+        self.inner.data_extents()
+    }
+
+    fn fs_blocks(&self) -> Option<(usize, u64, u64)> {
+        self.inner.fs_blocks()
+    }
+
+    /// FAT32 没有 inode 号与版本号，用目录项的位置（父目录首簇与偏移，同 inode 缓存键）
+    /// 作 inode 号、创建时间作版本号
+    /// # 说明
+    /// + 目录项的位置不随文件增长改变，空文件也有文件句柄
+    /// + 根目录没有目录项，无法生成文件句柄
+    /// + 目录项被回收给新建的文件时，两者的创建时间一般不同
+    fn file_handle(&self) -> Option<(u64, u32)> {
+        let (_, _, _, ctime, _) = self.inner.stat_lock(&self.inner.read());
+        self.inner.cache_key().map(|key| (key.ino, ctime as u32))
+    }
+
     /// 打开文件
     /// # 参数
     /// + flags: 标志
@@ -344,6 +375,10 @@ impl File for FatOSInode {
         {
             panic!();
         }
+        // 改名后按新目录项的位置登记，之后查找新路径仍得到这个 inode
+        if let Some(key) = child.inner.cache_key() {
+            INODE_CACHE.lock().insert(key, &child.inner);
+        }
         Ok(())
     }
     fn unlink(&self, delete: bool) -> Result<(), isize> {
@@ -379,23 +414,14 @@ impl File for FatOSInode {
             offset,
             count
         );
-        // 通过调用dirent_info_lock获取元组项
+        // 通过调用dirent_info_lock获取元组项，目录项长度不一，先按最短的长度多取一些
         let vec = self
             .inner
-            .dirent_info_lock(
-                &inode_lock,
-                *offset as u32,
-                count / core::mem::size_of::<Dirent>(),
-            )
+            .dirent_info_lock(&inode_lock, *offset as u32, count / MIN_RECLEN)
             .unwrap();
-        // 获取最后一个目录项距离下一个目录项的偏移量
-        // fat32下并不是一次性获取完，而是分很多次
-        if let Some((_, next_offset, _, _)) = vec.last() {
-            *offset = *next_offset;
-        }
-        // 迭代vec来获取需要的目录项
-        vec.iter()
-            .map(|(name, offset, first_clus, type_)| {
+        // 迭代vec来获取需要的目录项，只保留放得进缓冲区的部分
+        let dirents = take_fitting(
+            vec.iter().map(|(name, offset, first_clus, type_)| {
                 let d_type = match type_ {
                     FATDiskInodeType::AttrDirectory | FATDiskInodeType::AttrVolumeID => DT_DIR,
                     FATDiskInodeType::AttrArchive => DT_REG,
@@ -407,8 +433,15 @@ impl File for FatOSInode {
                     d_type,
                     name.as_str(),
                 )
-            })
-            .collect()
+            }),
+            count,
+        );
+        // 最后一个返回的目录项的 d_off 即下一个目录项的偏移量
+        // fat32下并不是一次性获取完，而是分很多次
+        if let Some(last) = dirents.last() {
+            *offset = last.d_off as usize;
+        }
+        dirents
     }
     fn lseek(&self, offset: isize, whence: SeekWhence) -> Result<usize, isize> {
         let inode_lock = self.inner.write();
@@ -428,12 +461,20 @@ impl File for FatOSInode {
     }
     fn modify_size(&self, diff: isize) -> Result<(), isize> {
         let inode_lock = self.inner.write();
+        if diff > 0 {
+            let old_size = self.inner.get_file_size_wlock(&inode_lock) as usize;
+            self.inner.reserve_hole_lock(&inode_lock, old_size + diff as usize)?;
+        }
         self.inner.modify_size_lock(&inode_lock, diff, true);
         Ok(())
     }
     fn truncate_size(&self, new_size: usize) -> Result<(), isize> {
         let inode_lock = self.inner.write();
         let old_size = self.inner.get_file_size_wlock(&inode_lock);
+        // FAT32 的簇链表示不了空洞，空洞在写回前要分配簇，扩展时先预留好
+        if new_size > old_size as usize {
+            self.inner.reserve_hole_lock(&inode_lock, new_size)?;
+        }
         self.inner
             .modify_size_lock(&inode_lock, new_size as isize - old_size as isize, true);
         Ok(())
@@ -455,6 +496,11 @@ impl File for FatOSInode {
         if offset & 0xfff != 0 {
             return Err(());
         }
+        // 共享映射中写入的页要能写回，先给该页及之前的空洞分配簇
+        let inode_lock = self.inner.write();
+        let size = self.inner.get_file_size_wlock(&inode_lock) as usize;
+        self.inner.fill_hole_lock(&inode_lock, (offset + PAGE_SIZE).min(size));
+        drop(inode_lock);
         let inode_lock = self.inner.read();
         let inner_cache_id = offset >> 12;
         Ok(self
@@ -471,6 +517,14 @@ impl File for FatOSInode {
         self.inner.oom()
     }
 
+    fn drop_caches(&self) -> usize {
+        self.inner.drop_caches()
+    }
+
+    fn fsync(&self) {
+        self.inner.fsync()
+    }
+
     fn hang_up(&self) -> bool {
         todo!()
     }
@@ -5,7 +5,7 @@ pub mod fat_inode;
 pub mod fat_osinode;
 pub mod layout;
 
-pub use super::cache::{BlockCacheManager, Cache, PageCache, PageCacheManager};
+pub use super::cache::{BlockCacheManager, Cache, PageCache, PageCacheManager, READ_BATCH_PAGES};
 pub use super::inode::DiskInodeType;
 pub use crate::drivers::block::BlockDevice;
 use bitmap::Fat;
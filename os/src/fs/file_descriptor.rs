@@ -5,22 +5,28 @@ use crate::{
     config::SYSTEM_FD_LIMIT,
     mm::{Frame, UserBuffer},
     syscall::errno::*,
+    task::suspend_current_and_run_next,
+    utils::telemetry::OPEN_FDS,
 };
 use alloc::{
+    collections::BTreeSet,
     string::{String, ToString},
     sync::Arc,
+    vec,
     vec::Vec,
 };
 use core::slice::{Iter, IterMut};
-use spin::Mutex;
+use spin::{Mutex, MutexGuard};
 
-use super::layout::{OpenFlags, SeekWhence, Stat};
+use super::layout::{OpenFlags, ResolveFlags, SeekWhence, Stat};
 
 #[derive(Clone)]
 pub struct FileDescriptor {
     cloexec: bool,
     nonblock: bool,
     pub file: Arc<dyn File>,
+    /// 文件偏移锁，同一次打开得到的所有 fd（dup、fork 的副本）共用一把
+    pos_lock: Arc<Mutex<()>>,
 }
 
 #[allow(unused)]
@@ -30,6 +36,23 @@ impl FileDescriptor {
             cloexec,
             nonblock,
             file,
+            pos_lock: Arc::new(Mutex::new(())),
+        }
+    }
+    /// 获取文件偏移锁，只有普通文件和目录需要（同 Linux 的 `FMODE_ATOMIC_POS`）
+    /// # 说明
+    /// + 终端、管道、套接字的读可能长时间阻塞，而 0、1、2 号 fd 以及 fork 出的子进程
+    ///   共用同一个 `FileDescriptor`，对它们加锁会让阻塞的读挡住别的写，因此返回 `None`
+    /// + 普通文件和目录的读写不会阻塞，持有者很快释放，等待者让出处理器即可
+    fn lock_pos(&self) -> Option<MutexGuard<()>> {
+        if !self.file.is_file() && !self.file.is_dir() {
+            return None;
+        }
+        loop {
+            if let Some(guard) = self.pos_lock.try_lock() {
+                return Some(guard);
+            }
+            suspend_current_and_run_next();
         }
     }
     pub fn set_cloexec(&mut self, flag: bool) {
@@ -76,10 +99,26 @@ impl FileDescriptor {
     pub fn w_ready(&self) -> bool {
         self.file.w_ready()
     }
+    /// 普通文件读到文件末尾为止：跨过末尾的读返回实际读到的字节数，
+    /// 从末尾或之后开始的读返回 0
+    /// # 说明
+    /// + `offset` 为 `None` 时，普通文件和目录在整个读期间持有文件偏移锁：
+    ///   共用同一 fd 的线程并发读时，每次读取出偏移、读数据、推进偏移是一步完成的，
+    ///   不会重复读或跳过
+    /// + 其余文件（终端、管道、套接字）不持有偏移锁，阻塞的读不会挡住同一 fd 上的写
     pub fn read_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
+        let _pos = match offset {
+            Some(_) => None,
+            None => self.lock_pos(),
+        };
         self.file.read_user(offset, buf)
     }
+    /// 与 `read_user` 相同，`offset` 为 `None` 时普通文件和目录在整个写期间持有文件偏移锁
     pub fn write_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
+        let _pos = match offset {
+            Some(_) => None,
+            None => self.lock_pos(),
+        };
         self.file.write_user(offset, buf)
     }
     pub fn get_stat(&self) -> Stat {
@@ -103,6 +142,16 @@ impl FileDescriptor {
         )
     }
     pub fn open(&self, path: &str, flags: OpenFlags, special_use: bool) -> Result<Self, isize> {
+        self.open_resolve(path, flags, ResolveFlags::empty(), special_use)
+    }
+    /// 以本目录为起点打开 `path`，路径解析遵守 openat2 的 `resolve` 限制
+    pub fn open_resolve(
+        &self,
+        path: &str,
+        flags: OpenFlags,
+        resolve: ResolveFlags,
+        special_use: bool,
+    ) -> Result<Self, isize> {
         if path == "" {
             return Ok(self.clone());
         }
@@ -114,13 +163,22 @@ impl FileDescriptor {
             Some(inode) => inode,
             None => return Err(ENOENT),
         };
-        let file = match inode.open(path, flags, special_use) {
+        let file = match inode.open_resolve(path, flags, resolve, special_use) {
             Ok(file) => file,
             Err(errno) => return Err(errno),
         };
         let cloexec = flags.contains(OpenFlags::O_CLOEXEC);
         Ok(Self::new(cloexec, false, file))
     }
+    pub fn open_by_handle(&self, ino: u64, generation: u32, flags: OpenFlags) -> Result<Self, isize> {
+        let inode = match self.file.get_dirtree_node() {
+            Some(inode) => inode,
+            None => return Err(ESTALE),
+        };
+        let file = inode.open_by_handle(ino, generation, flags)?;
+        let cloexec = flags.contains(OpenFlags::O_CLOEXEC);
+        Ok(Self::new(cloexec, false, file))
+    }
     pub fn mkdir(&self, path: &str) -> Result<(), isize> {
         if self.file.is_file() && !path.starts_with('/') {
             return Err(ENOTDIR);
@@ -132,6 +190,17 @@ impl FileDescriptor {
         };
         inode.mkdir(path)
     }
+    pub fn mknod(&self, path: &str, file: Arc<dyn File>) -> Result<(), isize> {
+        if self.file.is_file() && !path.starts_with('/') {
+            return Err(ENOTDIR);
+        }
+        let inode = self.file.get_dirtree_node();
+        let inode = match inode {
+            Some(inode) => inode,
+            None => return Err(ENOENT),
+        };
+        inode.mknod(path, file)
+    }
     pub fn delete(&self, path: &str, delete_directory: bool) -> Result<(), isize> {
         if self.file.is_file() && !path.starts_with('/') {
             return Err(ENOTDIR);
@@ -173,7 +242,7 @@ impl FileDescriptor {
 
     /// 获取目录项数组
     /// # 参数
-    /// + count：要获取的目录项数量
+    /// + count：缓冲区的字节数，返回的目录项的 d_reclen 之和不超过它
     pub fn get_dirent(&self, count: usize) -> Result<Vec<Dirent>, isize> {
         // 非目录，没有目录项，直接返回
         if !self.file.is_dir() {
@@ -185,8 +254,43 @@ impl FileDescriptor {
     pub fn get_offset(&self) -> usize {
         self.lseek(0, SeekWhence::SEEK_CUR).unwrap()
     }
+    /// 移动文件偏移量，与读写一样持有文件偏移锁
     pub fn lseek(&self, offset: isize, whence: SeekWhence) -> Result<usize, isize> {
-        self.file.lseek(offset, whence)
+        let _pos = self.lock_pos();
+        match whence {
+            SeekWhence::SEEK_DATA | SeekWhence::SEEK_HOLE => self.seek_hole_data(offset, whence),
+            _ => self.file.lseek(offset, whence),
+        }
+    }
+    /// `SEEK_DATA` 与 `SEEK_HOLE`：移到 `offset` 及之后的第一个数据或空洞处
+    /// # 说明
+    /// + 没有空洞的文件整个都是数据，文件末尾是一个隐含的空洞
+    /// + `offset` 在文件末尾或之后、或其后再没有数据时返回 `ENXIO`，偏移不变
+    /// + 不能 seek 的文件返回其 lseek 的错误
+    fn seek_hole_data(&self, offset: isize, whence: SeekWhence) -> Result<usize, isize> {
+        let old_offset = self.file.lseek(0, SeekWhence::SEEK_CUR)?;
+        let size = self.file.lseek(0, SeekWhence::SEEK_END)?;
+        let offset = offset as usize;
+        let extents = self.file.data_extents().unwrap_or_else(|| vec![(0, size)]);
+        let pos = if offset >= size {
+            None
+        } else if whence == SeekWhence::SEEK_DATA {
+            extents
+                .iter()
+                .find(|&&(_, end)| end > offset)
+                .map(|&(start, _)| start.max(offset))
+                .filter(|&pos| pos < size)
+        } else {
+            let data = extents.iter().find(|&&(start, end)| start <= offset && offset < end);
+            Some(data.map_or(offset, |&(_, end)| end).min(size))
+        };
+        match pos {
+            Some(pos) => self.file.lseek(pos as isize, SeekWhence::SEEK_SET),
+            None => {
+                self.file.lseek(old_offset as isize, SeekWhence::SEEK_SET)?;
+                Err(ENXIO)
+            }
+        }
     }
     pub fn get_size(&self) -> usize {
         self.file.get_size()
@@ -220,6 +324,28 @@ impl FileDescriptor {
     pub fn ioctl(&self, cmd: u32, argp: usize) -> isize {
         self.file.ioctl(cmd, argp)
     }
+    /// ioctl(FICLONE)：让本文件成为 `src` 所指文件的写时复制副本
+    /// # 说明
+    /// + `src` 须可读、本文件须可写，否则返回 `EBADF`
+    /// + 两者都须是普通文件，否则返回 `EINVAL`
+    /// + 两者不在同一个文件系统上时返回 `EXDEV`
+    /// + 文件系统不能在文件间共享块时返回 `EOPNOTSUPP`
+    pub fn reflink_from(&self, src: &FileDescriptor) -> Result<(), isize> {
+        if !src.readable() || !self.writable() {
+            return Err(EBADF);
+        }
+        if !src.file.is_file() || !self.file.is_file() {
+            return Err(EINVAL);
+        }
+        if let (Some(src_node), Some(dst_node)) =
+            (src.file.get_dirtree_node(), self.file.get_dirtree_node())
+        {
+            if src_node.fs_id() != dst_node.fs_id() {
+                return Err(EXDEV);
+            }
+        }
+        self.file.reflink_from(&src.file)
+    }
     // for execve
     /// 映射到内核空间
     /// # 参数
@@ -250,12 +376,16 @@ impl FileDescriptor {
     }
 }
 /// ### 文件描述符表
-#[derive(Clone)]
+/// 表中每个非空项都计入全局的 `OPEN_FDS`，克隆时增加、销毁时减少
+///
+/// 同一进程的线程共享 `Arc<Mutex<FdTable>>`，系统调用在一次加锁内完成查找与插入，
+/// 因此每个方法只需保证返回时 `recycled` 恰好是 `inner` 中全部空项的下标：
+/// 回收的 fd 不会被分配两次，空出的 fd 也不会丢失
 pub struct FdTable {
     // 文件描述符 数组
     inner: Vec<Option<FileDescriptor>>,
-    // 已回收的文件描述符
-    recycled: Vec<u8>,
+    // 已回收的文件描述符，即 inner 中空项的下标，有序以便取最小的
+    recycled: BTreeSet<usize>,
     soft_limit: usize,
     hard_limit: usize,
 }
@@ -266,9 +396,10 @@ impl FdTable {
     pub const DEFAULT_FD_LIMIT: usize = 128;
     pub const SYSTEM_FD_LIMIT: usize = SYSTEM_FD_LIMIT;
     pub fn new(inner: Vec<Option<FileDescriptor>>) -> Self {
+        OPEN_FDS.add(Self::count_open(&inner));
         Self {
+            recycled: Self::free_slots(&inner),
             inner,
-            recycled: Vec::new(),
             soft_limit: FdTable::DEFAULT_FD_LIMIT,
             hard_limit: FdTable::SYSTEM_FD_LIMIT,
         }
@@ -283,8 +414,9 @@ impl FdTable {
                 self.inner.len(),
                 self.soft_limit
             );
+            OPEN_FDS.sub(Self::count_open(&self.inner[limit..]));
             self.inner.truncate(limit);
-            self.recycled.retain(|&fd| (fd as usize) < limit);
+            self.recycled.retain(|&fd| fd < limit);
         }
         self.soft_limit = limit;
     }
@@ -298,8 +430,9 @@ impl FdTable {
                 self.inner.len(),
                 self.soft_limit
             );
+            OPEN_FDS.sub(Self::count_open(&self.inner[limit..]));
             self.inner.truncate(limit);
-            self.recycled.retain(|&fd| (fd as usize) < limit);
+            self.recycled.retain(|&fd| fd < limit);
         }
         self.hard_limit = limit;
     }
@@ -330,7 +463,8 @@ impl FdTable {
         }
         match self.inner[fd].take() {
             Some(file_descriptor) => {
-                self.recycled.push(fd as u8);
+                OPEN_FDS.dec();
+                self.recycled.insert(fd);
                 Ok(file_descriptor)
             }
             None => Err(EBADF),
@@ -352,17 +486,9 @@ impl FdTable {
         }
         Ok(())
     }
-    pub fn find_min(&mut self) -> Option<u8> {
-        if let Some(&min_value) = self.recycled.iter().min() {
-            if let Some(index) = self.recycled.iter().position(|&x| x == min_value) {
-                self.recycled.remove(index);
-                Some(min_value)
-            } else {
-                None
-            }
-        } else {
-            None
-        }
+    /// 取出最小的已回收 fd
+    pub fn find_min(&mut self) -> Option<usize> {
+        self.recycled.pop_first()
     }
     #[inline]
     pub fn insert(&mut self, file_descriptor: FileDescriptor) -> Result<usize, isize> {
@@ -370,8 +496,8 @@ impl FdTable {
         // let fd = match self.recycled.pop() {
         let fd = match self.find_min(){
             Some(fd) => {
-                self.inner[fd as usize] = Some(file_descriptor);
-                fd as usize
+                self.inner[fd] = Some(file_descriptor);
+                fd
             }
             None => {
                 let current = self.inner.len();
@@ -383,6 +509,7 @@ impl FdTable {
                 }
             }
         };
+        OPEN_FDS.inc();
         Ok(fd)
     }
 
@@ -396,7 +523,8 @@ impl FdTable {
         let current = self.inner.len();
         if pos < current {
             if self.inner[pos].is_none() {
-                self.recycled.retain(|&fd| fd as usize != pos);
+                self.recycled.remove(&pos);
+                OPEN_FDS.inc();
             }
             self.inner[pos] = Some(file_descriptor);
             Ok(pos)
@@ -404,11 +532,10 @@ impl FdTable {
             if pos >= self.soft_limit {
                 return Err(EMFILE);
             } else {
-                (current..pos)
-                    .rev()
-                    .for_each(|fd| self.recycled.push(fd as u8));
+                self.recycled.extend(current..pos);
                 self.inner.resize(pos, None);
                 self.inner.push(Some(file_descriptor));
+                OPEN_FDS.inc();
                 Ok(pos)
             }
         }
@@ -420,6 +547,15 @@ impl FdTable {
         &mut self,
         file_descriptor: FileDescriptor,
         hint: usize,
+    ) -> Result<usize, isize> {
+        let fd = self.try_insert_at_inner(file_descriptor, hint)?;
+        OPEN_FDS.inc();
+        Ok(fd)
+    }
+    fn try_insert_at_inner(
+        &mut self,
+        file_descriptor: FileDescriptor,
+        hint: usize,
     ) -> Result<usize, isize> {
         if hint >= self.soft_limit {
             return Err(EMFILE);
@@ -427,10 +563,12 @@ impl FdTable {
         let current = self.inner.len();
         if hint < current {
             match self.inner[hint] {
-                Some(_) => match self.recycled.iter().copied().find(|&fd| fd as usize > hint) {
+                // 取大于 hint 的最小已回收 fd，并把它移出回收集合
+                Some(_) => match self.recycled.range(hint..).next().copied() {
                     Some(fd) => {
-                        self.inner[fd as usize] = Some(file_descriptor);
-                        Ok(fd as usize)
+                        self.recycled.remove(&fd);
+                        self.inner[fd] = Some(file_descriptor);
+                        Ok(fd)
                     }
                     None => {
                         if current == self.soft_limit {
@@ -442,7 +580,7 @@ impl FdTable {
                     }
                 },
                 None => {
-                    self.recycled.retain(|&fd| fd as usize != hint);
+                    self.recycled.remove(&hint);
                     self.inner[hint] = Some(file_descriptor);
                     Ok(hint)
                 }
@@ -451,7 +589,7 @@ impl FdTable {
             if hint >= self.soft_limit {
                 return Err(EMFILE);
             } else {
-                (current..hint).for_each(|fd| self.recycled.push(fd as u8));
+                self.recycled.extend(current..hint);
                 self.inner.resize(hint, None);
                 self.inner.push(Some(file_descriptor));
                 Ok(hint)
@@ -463,7 +601,44 @@ impl FdTable {
         if fd >= self.inner.len() {
             None
         } else {
-            self.inner[fd].take()
+            let file_descriptor = self.inner[fd].take();
+            if file_descriptor.is_some() {
+                OPEN_FDS.dec();
+                self.recycled.insert(fd);
+            }
+            file_descriptor
+        }
+    }
+    /// 关闭所有设置了 close-on-exec 的文件描述符
+    pub fn close_on_exec(&mut self) {
+        for fd in 0..self.inner.len() {
+            if matches!(&self.inner[fd], Some(file) if file.get_cloexec()) {
+                self.remove(fd).ok();
+            }
         }
     }
+    fn count_open(slots: &[Option<FileDescriptor>]) -> u64 {
+        slots.iter().filter(|slot| slot.is_some()).count() as u64
+    }
+    fn free_slots(slots: &[Option<FileDescriptor>]) -> BTreeSet<usize> {
+        (0..slots.len()).filter(|&fd| slots[fd].is_none()).collect()
+    }
+}
+
+impl Clone for FdTable {
+    fn clone(&self) -> Self {
+        OPEN_FDS.add(Self::count_open(&self.inner));
+        Self {
+            inner: self.inner.clone(),
+            recycled: self.recycled.clone(),
+            soft_limit: self.soft_limit,
+            hard_limit: self.hard_limit,
+        }
+    }
+}
+
+impl Drop for FdTable {
+    fn drop(&mut self) {
+        OPEN_FDS.sub(Self::count_open(&self.inner));
+    }
 }
\ No newline at end of file
@@ -7,7 +7,11 @@
 //! - Pipes and sockets
 
 use super::{dirent::Dirent, fat32::DiskInodeType};
-use crate::{mm::UserBuffer, syscall::errno::ENOTTY};
+use crate::{
+    drivers::block::BlockDevice,
+    mm::UserBuffer,
+    syscall::errno::{ENOTBLK, ENOTTY, EOPNOTSUPP, ESTALE},
+};
 use __alloc::string::String;
 use alloc::{
     sync::{Arc, Weak},
@@ -89,6 +93,14 @@ pub trait File: DowncastSync {
     /// * `flags` - Open flags (read/write/create/etc.)
     /// * `special_use` - Special usage flag
     fn open(&self, flags: OpenFlags, special_use: bool) -> Arc<dyn File>;
+
+    /// Check whether the file in the directory tree may be opened now, before `open`
+    ///
+    /// # Errors
+    /// * `EIO` - A pseudo-terminal slave is locked or its master is closed
+    fn may_open(&self, _flags: OpenFlags) -> Result<(), isize> {
+        Ok(())
+    }
     
     /// Open subfiles (for directories)
     fn open_subfile(&self) -> Result<Vec<(String, Arc<dyn File>)>, isize>;
@@ -114,7 +126,8 @@ pub trait File: DowncastSync {
     /// Get directory entries
     ///
     /// # Arguments
-    /// * `count` - Maximum number of entries to return
+    /// * `count` - Size of the caller's buffer in bytes; the `d_reclen` of the
+    ///   returned entries add up to at most `count`
     fn get_dirent(&self, count: usize) -> Vec<Dirent>;
     
     /// Get current file offset
@@ -138,8 +151,67 @@ pub trait File: DowncastSync {
     fn get_all_caches(&self) -> Result<Vec<Arc<Mutex<PageCache>>>, ()>;
     /// memory related
     fn oom(&self) -> usize;
+    /// `/proc/sys/vm/drop_caches`: drop the unused page cache, returns pages dropped
+    fn drop_caches(&self) -> usize {
+        0
+    }
+    /// fsync(2): write the dirty data and metadata back and flush the device's
+    /// write cache, nothing to do for files not backed by a disk
+    fn fsync(&self) {}
+    /// Replace the contents of this regular file with a copy-on-write clone of
+    /// `src`, a regular file of the same filesystem
+    ///
+    /// # Errors
+    /// * `EOPNOTSUPP` - The filesystem cannot share blocks between files
+    /// * `EXDEV` - `src` is on another filesystem
+    fn reflink_from(&self, _src: &Arc<dyn File>) -> Result<(), isize> {
+        Err(EOPNOTSUPP)
+    }
+    /// Byte ranges of the file holding data, in order and not adjacent; the
+    /// rest below the file size is holes. `None` if the file cannot have holes
+    fn data_extents(&self) -> Option<Vec<(usize, usize)>> {
+        None
+    }
+    /// Block size, total and free blocks of the filesystem holding the file,
+    /// `None` if it does not live on a block filesystem
+    fn fs_blocks(&self) -> Option<(usize, u64, u64)> {
+        None
+    }
+    /// File handle: the inode number and generation identifying this file on its
+    /// filesystem, `None` if the filesystem cannot reopen files by handle
+    fn file_handle(&self) -> Option<(u64, u32)> {
+        None
+    }
+    /// Open a file of the same filesystem that is not in the directory tree by its
+    /// inode number and generation
+    ///
+    /// # Errors
+    /// * `ESTALE` - The inode was freed or recycled for another file
+    fn open_by_inode(
+        &self,
+        _ino: u64,
+        _generation: u32,
+        _flags: OpenFlags,
+    ) -> Result<Arc<dyn File>, isize> {
+        Err(ESTALE)
+    }
+    /// The block device behind a block special file, for mount(2)
+    ///
+    /// # Errors
+    /// * `ENOTBLK` - This is not a block special file
+    /// * `ENXIO` - The device is not set up yet
+    fn block_device(&self) -> Result<Arc<dyn BlockDevice>, isize> {
+        Err(ENOTBLK)
+    }
     /// poll, select related
     fn hang_up(&self) -> bool;
+    /// A counter bumped whenever the readiness may have changed, e.g. each
+    /// time data moves through a pipe. Edge-triggered epoll reports a file
+    /// again only after it changes; `None` makes epoll report it whenever it
+    /// is ready
+    fn poll_generation(&self) -> Option<usize> {
+        None
+    }
     /// iotcl
     fn ioctl(&self, _cmd: u32, _argp: usize) -> isize {
         ENOTTY
@@ -4,7 +4,7 @@ use core::ops::AddAssign;
 use lazy_static::*;
 use spin::Mutex;
 
-use crate::drivers::BLOCK_DEVICE;
+use crate::drivers::block::BlockDevice;
 
 #[allow(unused, non_camel_case_types)]
 #[derive(Debug)]
@@ -18,6 +18,8 @@ pub enum FS_Type {
 pub struct FileSystem {
     pub fs_id: usize,
     pub fs_type: FS_Type,
+    /// 以只读方式挂载，修改其中的文件返回 EROFS
+    pub read_only: bool,
 }
 
 lazy_static! {
@@ -28,13 +30,22 @@ impl FileSystem {
     pub fn new(fs_type: FS_Type) -> Self {
         FS_ID_COUNTER.lock().add_assign(1);
         let fs_id = *FS_ID_COUNTER.lock();
-        Self { fs_id, fs_type }
+        Self {
+            fs_id,
+            fs_type,
+            read_only: false,
+        }
+    }
+
+    pub fn new_read_only(fs_type: FS_Type) -> Self {
+        Self {
+            read_only: true,
+            ..Self::new(fs_type)
+        }
     }
 }
 
-pub fn pre_mount() -> FS_Type {
-    // 获取块设备
-    let block_device = BLOCK_DEVICE.clone();
+pub fn pre_mount(block_device: &Arc<dyn BlockDevice>) -> FS_Type {
     let mut buf = [0u8; BLOCK_SIZE];
 
     // 1. 判断是否为 FAT32
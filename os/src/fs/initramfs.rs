@@ -0,0 +1,94 @@
+//! Early userspace from an initramfs
+//!
+//! A newc cpio archive left in RAM by the bootloader is unpacked into the root
+//! directory tree before `initproc` is loaded, so programs and directories can be
+//! added without rebuilding the disk image. With `block_mem` the root filesystem is
+//! itself in RAM, which gives a disk-less boot.
+//!
+//! The archive is found through `/chosen/linux,initrd-{start,end}` in the device tree
+//! (`qemu -initrd rootfs.cpio`), or `initrdmem=<addr>,<size>` on the command line.
+//! Its frames are kept from the frame allocator until it has been unpacked.
+
+use super::cpio::{CpioError, Reader};
+use super::{OpenFlags, ROOT_FD};
+use crate::syscall::errno::EEXIST;
+use alloc::format;
+use alloc::string::String;
+
+/// Physical `[start, end)` of the archive, `None` when no initramfs was passed
+pub fn region() -> Option<(usize, usize)> {
+    crate::hal::fdt::platform()
+        .and_then(|info| info.initrd)
+        .or(crate::cmdline::get().initrd)
+}
+
+fn join(dest: &str, name: &str) -> String {
+    format!("{}/{}", dest.trim_end_matches('/'), name)
+}
+
+/// Archives made without `find` may list `a/b` before, or without, `a`
+fn mkdir_parents(path: &str) {
+    for (i, _) in path.match_indices('/').filter(|&(i, _)| i != 0) {
+        let _ = ROOT_FD.mkdir(&path[..i]);
+    }
+}
+
+/// Create the directories and regular files of `archive` under `dest`.
+///
+/// Existing files are overwritten, other entry types (symlinks, device nodes) are
+/// skipped. Returns the number of directories and files created.
+pub fn unpack_into(archive: &[u8], dest: &str) -> Result<(usize, usize), CpioError> {
+    let (mut dirs, mut files) = (0, 0);
+    for entry in Reader::new(archive) {
+        let entry = entry?;
+        let name = entry.name.trim_start_matches("./").trim_start_matches('/');
+        if name.is_empty() || name == "." {
+            continue;
+        }
+        let path = join(dest, name);
+        mkdir_parents(&path);
+        if entry.is_dir() {
+            match ROOT_FD.mkdir(&path) {
+                Ok(()) => dirs += 1,
+                Err(EEXIST) => {}
+                Err(errno) => log::warn!("[initramfs] mkdir {} failed: {}", path, errno),
+            }
+        } else if entry.is_file() {
+            match ROOT_FD.open(&path, OpenFlags::O_CREAT | OpenFlags::O_TRUNC, false) {
+                Ok(file) => {
+                    file.write(None, entry.data);
+                    files += 1;
+                }
+                Err(errno) => log::warn!("[initramfs] create {} failed: {}", path, errno),
+            }
+        } else {
+            log::warn!("[initramfs] skipping {}, mode {:#o}", path, entry.mode);
+        }
+    }
+    Ok((dirs, files))
+}
+
+/// Unpack the initramfs handed over by the bootloader into `/`, if there is one
+pub fn init() {
+    let (start, end) = match region() {
+        Some(region) => region,
+        None => return,
+    };
+    if end > crate::mm::memory_end() {
+        println!(
+            "[kernel] initramfs at [{:#x}, {:#x}) is outside of managed memory, ignored",
+            start, end
+        );
+        return;
+    }
+    // the kernel address space maps physical memory one-to-one
+    let archive = unsafe { core::slice::from_raw_parts(start as *const u8, end - start) };
+    match unpack_into(archive, "/") {
+        Ok((dirs, files)) => println!(
+            "[kernel] initramfs at {:#x}: {} directories, {} files",
+            start, dirs, files
+        ),
+        Err(err) => println!("[kernel] initramfs at {:#x} is corrupt: {:?}", start, err),
+    }
+    crate::mm::frame_release_reserved();
+}
@@ -5,17 +5,24 @@
 //! - Block-level read/write operations
 //! - Directory entry management
 //! - Cache management
+//!
+//! Inodes in use are kept in the inode cache ([`INODE_CACHE`]) by
+//! [`InodeKey`], so that every lookup of a file, including one made after its
+//! directory entry was dropped from the directory tree, shares one inode
+//! object and sees the same size, page cache and locks.
 
 use crate::fs::*;
 use core::any::Any;
 
 use crate::fs::fat32::layout::FATDiskInodeType;
 use crate::fs::vfs::VFS;
-use alloc::sync::Arc;
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
 use alloc::vec::Vec;
 use downcast_rs::*;
 use fat32::fat_inode::FileContent;
 use fat32::layout::FATShortDirEnt;
+use lazy_static::*;
 use spin::{Mutex, MutexGuard, RwLockReadGuard, RwLockWriteGuard};
 #[allow(unused)]
 use vfs::VFSDirEnt;
@@ -141,9 +148,52 @@ pub trait InodeTrait: DowncastSync {
     
     /// Out-of-memory handler
     fn oom(&self) -> usize;
+
+    /// Drop every page cache nobody else holds, writing dirty pages back first.
+    /// Returns the number of pages dropped
+    fn drop_caches(&self) -> usize {
+        0
+    }
+
+    /// Write the dirty pages and the filesystem metadata back, then flush the
+    /// device's write cache
+    fn fsync(&self) {}
+
+    /// Key of the inode in the inode cache, `None` if it is not cached
+    fn cache_key(&self) -> Option<InodeKey> {
+        None
+    }
     
     /// Modify size with lock
     fn modify_size_lock(&self, inode_lock: &RwLockWriteGuard<InodeLock>, diff: isize, clear: bool);
+
+    /// Back the holes before `end` with blocks so the pages there can be
+    /// written back. Returns how many bytes before `end` are backed, less than
+    /// `end` when the filesystem is full
+    fn fill_hole_lock(&self, _inode_lock: &RwLockWriteGuard<InodeLock>, end: usize) -> usize {
+        end
+    }
+
+    /// Byte ranges of the file holding data, in order and not adjacent, `None`
+    /// if the filesystem cannot have holes
+    fn data_extents(&self) -> Option<Vec<(usize, usize)>> {
+        None
+    }
+
+    /// Reserve blocks for the hole left by growing the file to `new_size`, so
+    /// that backing it later cannot fail. `ENOSPC` if the filesystem is too full
+    fn reserve_hole_lock(
+        &self,
+        _inode_lock: &RwLockWriteGuard<InodeLock>,
+        _new_size: usize,
+    ) -> Result<(), isize> {
+        Ok(())
+    }
+
+    /// Block size, total and free blocks of the filesystem holding the inode
+    fn fs_blocks(&self) -> Option<(usize, u64, u64)> {
+        None
+    }
     
     /// Check if directory is empty with lock
     fn is_empty_dir_lock(&self, inode_lock: &RwLockWriteGuard<InodeLock>) -> bool;
@@ -197,6 +247,51 @@ pub trait InodeTrait: DowncastSync {
 }
 impl_downcast!(sync InodeTrait);
 
+/// Identity of an inode: the filesystem instance it belongs to and a number
+/// unique within it that stays the same across lookups
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct InodeKey {
+    pub device: usize,
+    pub ino: u64,
+}
+
+/// Inodes by key, held weakly: an inode leaves the cache once the last
+/// descriptor, directory tree node or child holding it is gone
+pub struct InodeCache {
+    inodes: BTreeMap<InodeKey, Weak<dyn InodeTrait>>,
+    /// Dead entries are pruned when the map grows to this length
+    prune_at: usize,
+}
+
+impl InodeCache {
+    const MIN_PRUNE_AT: usize = 64;
+
+    pub fn new() -> Self {
+        Self {
+            inodes: BTreeMap::new(),
+            prune_at: Self::MIN_PRUNE_AT,
+        }
+    }
+
+    /// The live inode cached under `key`
+    pub fn get(&self, key: InodeKey) -> Option<Arc<dyn InodeTrait>> {
+        self.inodes.get(&key).and_then(|inode| inode.upgrade())
+    }
+
+    /// Cache `inode` under `key`, replacing whatever was there
+    pub fn insert(&mut self, key: InodeKey, inode: &Arc<dyn InodeTrait>) {
+        self.inodes.insert(key, Arc::downgrade(inode));
+        if self.inodes.len() >= self.prune_at {
+            self.inodes.retain(|_, inode| inode.strong_count() > 0);
+            self.prune_at = (2 * self.inodes.len()).max(Self::MIN_PRUNE_AT);
+        }
+    }
+}
+
+lazy_static! {
+    pub static ref INODE_CACHE: Mutex<InodeCache> = Mutex::new(InodeCache::new());
+}
+
 pub struct InodeTime {
     create_time: u64,
     access_time: u64,
@@ -28,11 +28,35 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// `resolve` field of openat2's `struct open_how`
+    pub struct ResolveFlags: u64 {
+        /// Fail with EXDEV if the walk crosses into another filesystem
+        const RESOLVE_NO_XDEV       =   0x01;
+        /// Fail on procfs-style magic links such as `/proc/self/fd/N`
+        const RESOLVE_NO_MAGICLINKS =   0x02;
+        /// Fail with ELOOP on any symbolic link in the path
+        const RESOLVE_NO_SYMLINKS   =   0x04;
+        /// Fail with EXDEV if the path leaves the starting directory
+        const RESOLVE_BENEATH       =   0x08;
+        /// Resolve as if the starting directory were the root
+        const RESOLVE_IN_ROOT       =   0x10;
+        /// Only resolve from cached dentries, openat2 answers EAGAIN
+        const RESOLVE_CACHED        =   0x20;
+    }
+}
+
+/// ioctl(dst_fd, FICLONE, src_fd): make the destination a copy-on-write
+/// clone of the source, sharing its blocks until either is modified
+pub const FICLONE: u32 = 0x4004_9409;
+
 bitflags! {
     pub struct SeekWhence: u32 {
         const SEEK_SET  =   0; /* set to offset bytes.  */
         const SEEK_CUR  =   1; /* set to its current location plus offset bytes.  */
         const SEEK_END  =   2; /* set to the size of the file plus offset bytes.  */
+        const SEEK_DATA =   3; /* set to the next data at or after offset.  */
+        const SEEK_HOLE =   4; /* set to the next hole at or after offset.  */
     }
 }
 
@@ -10,20 +10,23 @@
 //! - Swap file support (optional)
 
 mod cache;
+pub mod cpio;
 pub mod dev;
 pub mod directory_tree;
 mod ext4;
 pub mod fat32;
+pub mod initramfs;
 pub mod file_trait;
 mod filesystem;
 mod layout;
+pub mod mount;
 pub mod poll;
 #[cfg(feature = "swap")]
 pub mod swap;
 pub mod dirent;
 pub mod file_descriptor;
 mod inode;
-mod timestamp;
+pub mod timestamp;
 mod vfs;
 
 
@@ -38,6 +41,13 @@ pub use self::dev::{
 pub use self::layout::*;
 
 pub use self::fat32::DiskInodeType;
+#[cfg(feature = "selftest")]
+pub use self::ext4::ext4_crc32c;
+#[cfg(all(feature = "selftest", feature = "loongarch64"))]
+pub use self::ext4::{
+    ext4fs::Ext4FileSystem, mkfs as mkfs_ext4, Ext4InodeRef, InodeFileType,
+    EXT_MAX_BLOCKS, ROOT_INODE as EXT4_ROOT_INODE,
+};
 pub use crate::drivers::block::BlockDevice;
 
 use self::cache::PageCache;
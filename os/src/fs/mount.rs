@@ -0,0 +1,678 @@
+//! Mount table
+//!
+//! Records what is mounted where, for `/proc/mounts`, statmount(2) and
+//! listmount(2). Mount 1 is the root filesystem; `/dev` and `/proc`, whose
+//! nodes the kernel creates itself, are listed as devtmpfs and proc mounts.
+//!
+//! sys_mount only records a new filesystem mount, the directory tree below
+//! the mount point keeps showing the disk, unless the source is a loop device
+//! holding a FAT32 image. A mount's parent is the most recent mount
+//! whose mount point contains its own.
+//!
+//! Each task belongs to a [`MountNamespace`], which holds the mount table and
+//! the directory absolute paths start from. unshare(CLONE_NEWNS) gives a task
+//! a copy of its namespace; pivot_root(2) then changes the copy's root and
+//! moves the old root onto a directory below the new one.
+//!
+//! A bind mount (MS_BIND), a filesystem mounted from a loop device and the old
+//! root moved by pivot_root *cover* a directory: path walks reaching it enter
+//! the source directory, the filesystem's root or the old root instead. Path walks only consult the namespace once some namespace
+//! covers a directory.
+//!
+//! Mounts are private unless made shared. Bind mounts of a shared mount are
+//! its peers: a mount made below one peer is copied to the same place below
+//! the others and below their slaves, and unmounted from there with it.
+//! Propagation stays within one namespace.
+
+use super::directory_tree::{DirectoryTreeNode, ROOT};
+use crate::syscall::errno::{EBUSY, EINVAL};
+use crate::task::current_task;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
+use lazy_static::*;
+use spin::{Mutex, RwLock};
+
+/// Id of the root mount
+pub const ROOT_MOUNT_ID: u64 = 1;
+
+/// `MOUNT_ATTR_*` bits of `Mount::attr`
+pub const MOUNT_ATTR_RDONLY: u64 = 0x1;
+pub const MOUNT_ATTR_NOSUID: u64 = 0x2;
+pub const MOUNT_ATTR_NODEV: u64 = 0x4;
+pub const MOUNT_ATTR_NOEXEC: u64 = 0x8;
+pub const MOUNT_ATTR_NOATIME: u64 = 0x10;
+pub const MOUNT_ATTR_STRICTATIME: u64 = 0x20;
+pub const MOUNT_ATTR_NODIRATIME: u64 = 0x80;
+
+/// `Mount::propagation` values, the `MS_*` flags selecting them
+pub const MS_UNBINDABLE: u64 = 1 << 17;
+pub const MS_PRIVATE: u64 = 1 << 18;
+pub const MS_SLAVE: u64 = 1 << 19;
+pub const MS_SHARED: u64 = 1 << 20;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mount {
+    pub id: u64,
+    /// Id of the mount containing the mount point, the root is its own parent
+    pub parent_id: u64,
+    /// Device or name given to mount(2)
+    pub source: String,
+    /// Absolute path of the mount point
+    pub mount_point: String,
+    pub fs_type: String,
+    /// `MOUNT_ATTR_*` bits
+    pub attr: u64,
+    /// Directory of the mounted filesystem shown at the mount point, `/` unless
+    /// this is a bind mount of a subdirectory
+    pub root: String,
+    /// One of `MS_SHARED`, `MS_PRIVATE`, `MS_SLAVE` or `MS_UNBINDABLE`
+    pub propagation: u64,
+    /// Peer group of a shared mount, 0 otherwise. A group is named by the id
+    /// of the mount that started it.
+    pub peer_group: u64,
+    /// Peer group a slave mount receives mount events from, 0 otherwise
+    pub master: u64,
+}
+
+impl Mount {
+    /// Magic number of the filesystem type, as `statfs` reports it
+    pub fn magic(&self) -> u64 {
+        match self.fs_type.as_str() {
+            "ext4" => 0xef53,
+            "vfat" => 0x4d44,
+            "proc" => 0x9fa0,
+            "sysfs" => 0x62656572,
+            "tmpfs" | "devtmpfs" => 0x01021994,
+            _ => 0,
+        }
+    }
+
+    /// Mount options as listed in `/proc/mounts`
+    pub fn options(&self) -> String {
+        let mut options = String::from(if self.attr & MOUNT_ATTR_RDONLY != 0 {
+            "ro"
+        } else {
+            "rw"
+        });
+        for (bit, name) in [
+            (MOUNT_ATTR_NOSUID, "nosuid"),
+            (MOUNT_ATTR_NODEV, "nodev"),
+            (MOUNT_ATTR_NOEXEC, "noexec"),
+            (MOUNT_ATTR_NOATIME, "noatime"),
+            (MOUNT_ATTR_STRICTATIME, "strictatime"),
+            (MOUNT_ATTR_NODIRATIME, "nodiratime"),
+        ] {
+            if self.attr & bit != 0 {
+                options.push(',');
+                options.push_str(name);
+            }
+        }
+        options
+    }
+}
+
+/// Whether `path` is `dir` or lies below it
+fn is_within(path: &str, dir: &str) -> bool {
+    dir == "/"
+        || path == dir
+        || (path.starts_with(dir) && path.as_bytes().get(dir.len()) == Some(&b'/'))
+}
+
+/// The part of `path` below `dir`, empty for `dir` itself, `path` must be within `dir`
+fn below<'a>(path: &'a str, dir: &str) -> &'a str {
+    match dir {
+        "/" if path == "/" => "",
+        "/" => path,
+        _ => &path[dir.len()..],
+    }
+}
+
+/// `dir` followed by `rest`, a result of [`below`]
+fn join(dir: &str, rest: &str) -> String {
+    match (dir, rest) {
+        (_, "") => dir.to_string(),
+        ("/", _) => rest.to_string(),
+        _ => format!("{}{}", dir, rest),
+    }
+}
+
+#[derive(Clone)]
+pub struct MountTable {
+    /// Mounts in the order they were made
+    mounts: Vec<Mount>,
+    next_id: u64,
+}
+
+impl MountTable {
+    pub const fn new() -> Self {
+        Self {
+            mounts: Vec::new(),
+            next_id: ROOT_MOUNT_ID,
+        }
+    }
+
+    /// Mount `fs_type` on the absolute path `mount_point`, returns the new mount id
+    pub fn add(&mut self, source: &str, mount_point: &str, fs_type: &str, attr: u64) -> u64 {
+        self.insert(source, mount_point, fs_type, attr, "/")
+    }
+
+    /// Bind mount the directory at `source_path` onto `mount_point`, returns
+    /// the new mount id. A bind of a shared mount joins its peer group.
+    ///
+    /// # Errors
+    /// * `EINVAL` - The mount `source_path` lies on is unbindable
+    pub fn bind(&mut self, source_path: &str, mount_point: &str, attr: u64) -> Result<u64, isize> {
+        let source = match self.lookup(source_path) {
+            Some(source) if source.propagation == MS_UNBINDABLE => return Err(EINVAL),
+            Some(source) => source.clone(),
+            None => return Err(EINVAL),
+        };
+        let root = join(&source.root, below(source_path, &source.mount_point));
+        let id = self.insert(&source.source, mount_point, &source.fs_type, attr, &root);
+        if source.peer_group != 0 {
+            let mount = self.mounts.iter_mut().find(|mount| mount.id == id).unwrap();
+            mount.propagation = MS_SHARED;
+            mount.peer_group = source.peer_group;
+        }
+        Ok(id)
+    }
+
+    fn push(&mut self, mount_point: &str, template: &Mount) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        let parent_id = self.lookup(mount_point).map_or(id, |parent| parent.id);
+        self.mounts.push(Mount {
+            id,
+            parent_id,
+            mount_point: mount_point.to_string(),
+            ..template.clone()
+        });
+        id
+    }
+
+    /// Add a mount and, when it lands in a shared mount, a copy at the
+    /// corresponding place in every mount receiving its events. The mount and
+    /// its copies form a new peer group.
+    fn insert(
+        &mut self,
+        source: &str,
+        mount_point: &str,
+        fs_type: &str,
+        attr: u64,
+        root: &str,
+    ) -> u64 {
+        let parent = self.lookup(mount_point).cloned();
+        let mut template = Mount {
+            id: 0,
+            parent_id: 0,
+            source: source.to_string(),
+            mount_point: String::new(),
+            fs_type: fs_type.to_string(),
+            attr,
+            root: root.to_string(),
+            propagation: MS_PRIVATE,
+            peer_group: 0,
+            master: 0,
+        };
+        let parent = match parent {
+            Some(parent) if parent.peer_group != 0 => parent,
+            _ => return self.push(mount_point, &template),
+        };
+        template.propagation = MS_SHARED;
+        template.peer_group = self.next_id;
+        let id = self.push(mount_point, &template);
+        for receiver in self.receivers(&parent) {
+            if let Some(copy_point) = Self::translate(&parent, &receiver, mount_point) {
+                self.push(&copy_point, &template);
+            }
+        }
+        id
+    }
+
+    /// Mounts receiving the mount events under `mount`: its peers and their slaves
+    fn receivers(&self, mount: &Mount) -> Vec<Mount> {
+        if mount.peer_group == 0 {
+            return Vec::new();
+        }
+        self.mounts
+            .iter()
+            .filter(|other| {
+                other.id != mount.id
+                    && (other.peer_group == mount.peer_group || other.master == mount.peer_group)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// The path in mount `to` showing the same directory as `path` in mount
+    /// `from`, `None` when `to` does not show it
+    fn translate(from: &Mount, to: &Mount, path: &str) -> Option<String> {
+        let fs_path = join(&from.root, below(path, &from.mount_point));
+        if !is_within(&fs_path, &to.root) {
+            return None;
+        }
+        Some(join(&to.mount_point, below(&fs_path, &to.root)))
+    }
+
+    /// Change the propagation type of the most recent mount on `mount_point`
+    /// to `propagation`, one of the `MS_*` values. A mount made shared starts
+    /// its own peer group; a shared mount made a slave receives the events of
+    /// its old peers; a mount that receives from nobody cannot be a slave and
+    /// becomes private.
+    ///
+    /// # Errors
+    /// * `EINVAL` - Nothing is mounted there
+    pub fn set_propagation(&mut self, mount_point: &str, propagation: u64) -> Result<(), isize> {
+        let mount = self.topmost_mut(mount_point).ok_or(EINVAL)?;
+        match propagation {
+            MS_SHARED => {
+                if mount.peer_group == 0 {
+                    mount.peer_group = mount.id;
+                }
+            }
+            MS_SLAVE => {
+                if mount.peer_group != 0 {
+                    mount.master = mount.peer_group;
+                    mount.peer_group = 0;
+                }
+            }
+            _ => {
+                mount.peer_group = 0;
+                mount.master = 0;
+            }
+        }
+        mount.propagation = match propagation {
+            MS_SLAVE if mount.master == 0 => MS_PRIVATE,
+            propagation => propagation,
+        };
+        Ok(())
+    }
+
+    /// The mount `path` lies on
+    pub fn lookup(&self, path: &str) -> Option<&Mount> {
+        // `max_by_key` returns the last of equally long mount points, the most recent
+        self.mounts
+            .iter()
+            .filter(|mount| is_within(path, &mount.mount_point))
+            .max_by_key(|mount| mount.mount_point.len())
+    }
+
+    /// The most recent mount on exactly `mount_point`
+    pub fn topmost_mut(&mut self, mount_point: &str) -> Option<&mut Mount> {
+        self.mounts
+            .iter_mut()
+            .rev()
+            .find(|mount| mount.mount_point == mount_point)
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Mount> {
+        self.mounts.iter().find(|mount| mount.id == id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Mount> {
+        self.mounts.iter()
+    }
+
+    /// Ids of the mounts below mount `id`, children before grandchildren
+    pub fn descendants(&self, id: u64) -> Vec<u64> {
+        let mut found = Vec::new();
+        let mut next = 0;
+        let mut parent = id;
+        loop {
+            found.extend(
+                self.mounts
+                    .iter()
+                    .filter(|mount| mount.parent_id == parent && mount.id != parent)
+                    .map(|mount| mount.id),
+            );
+            match found.get(next) {
+                Some(&child) => parent = child,
+                None => return found,
+            }
+            next += 1;
+        }
+    }
+
+    /// Unmount the most recent mount on `mount_point`, and the copies
+    /// propagated from it that nothing is mounted on
+    ///
+    /// # Errors
+    /// * `EINVAL` - Nothing is mounted there
+    /// * `EBUSY` - It is the root, or other mounts sit on top of it
+    pub fn remove(&mut self, mount_point: &str) -> Result<Mount, isize> {
+        let index = self
+            .mounts
+            .iter()
+            .rposition(|mount| mount.mount_point == mount_point)
+            .ok_or(EINVAL)?;
+        let id = self.mounts[index].id;
+        if self.mounts[index].parent_id == id || !self.descendants(id).is_empty() {
+            return Err(EBUSY);
+        }
+        let mount = self.mounts.remove(index);
+        // The copies made when it was mounted in a shared mount go with it
+        if let Some(parent) = self.get(mount.parent_id).cloned() {
+            if mount.peer_group != 0 {
+                for receiver in self.receivers(&parent) {
+                    let copy = Self::translate(&parent, &receiver, &mount.mount_point)
+                        .and_then(|copy_point| {
+                            self.mounts.iter().rposition(|copy| {
+                                copy.mount_point == copy_point
+                                    && copy.peer_group == mount.peer_group
+                            })
+                        });
+                    if let Some(copy) = copy {
+                        if self.descendants(self.mounts[copy].id).is_empty() {
+                            self.mounts.remove(copy);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(mount)
+    }
+
+    /// Make the most recent mount on `new_root` the root and move the old root
+    /// onto `put_old`, both absolute paths that must not end in `/`. Mount
+    /// points are rewritten as seen from the new root.
+    ///
+    /// # Errors
+    /// * `EINVAL` - Nothing is mounted on `new_root`, it is the root already,
+    ///   or `put_old` is not at or below it
+    pub fn pivot(&mut self, new_root: &str, put_old: &str) -> Result<(), isize> {
+        if new_root == "/" || !is_within(put_old, new_root) {
+            return Err(EINVAL);
+        }
+        let new_root_id = self
+            .mounts
+            .iter()
+            .rev()
+            .find(|mount| mount.mount_point == new_root)
+            .ok_or(EINVAL)?
+            .id;
+        let old_root_id = self
+            .mounts
+            .iter()
+            .find(|mount| mount.parent_id == mount.id)
+            .ok_or(EINVAL)?
+            .id;
+        // `put_old` lies on the new root or a mount below it
+        let old_root_parent = self.lookup(put_old).map_or(new_root_id, |mount| mount.id);
+        let put_old = &put_old[new_root.len()..];
+        for mount in self.mounts.iter_mut() {
+            let mount_point = &mount.mount_point;
+            mount.mount_point = if is_within(mount_point, new_root) {
+                match &mount_point[new_root.len()..] {
+                    "" => "/".to_string(),
+                    rest => rest.to_string(),
+                }
+            } else if put_old.is_empty() {
+                mount_point.clone()
+            } else if mount_point == "/" {
+                put_old.to_string()
+            } else {
+                format!("{}{}", put_old, mount_point)
+            };
+            if mount.id == new_root_id {
+                mount.parent_id = new_root_id;
+            } else if mount.id == old_root_id {
+                mount.parent_id = old_root_parent;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A directory whose path walks enter another directory instead
+#[derive(Clone)]
+struct Cover {
+    mount_point: Arc<DirectoryTreeNode>,
+    root: Arc<DirectoryTreeNode>,
+    /// Id of the bind or filesystem mount, `None` for an old root moved by
+    /// pivot_root
+    mount: Option<u64>,
+}
+
+/// A set of mounts and the root directory seen by the tasks sharing it
+pub struct MountNamespace {
+    pub mounts: Mutex<MountTable>,
+    /// Where absolute paths start
+    root: RwLock<Arc<DirectoryTreeNode>>,
+    /// Bind mounts, filesystems mounted from loop devices and the directories
+    /// pivot_root moved old roots onto
+    covers: RwLock<Vec<Cover>>,
+}
+
+/// Set once some namespace covers a directory, until then path walks never
+/// have to look at a namespace
+static COVERED: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    /// The namespace of the tasks that never unshared one
+    pub static ref INIT_MNT_NS: Arc<MountNamespace> = Arc::new(MountNamespace {
+        mounts: Mutex::new(MountTable::new()),
+        root: RwLock::new(ROOT.clone()),
+        covers: RwLock::new(Vec::new()),
+    });
+}
+
+/// Directories a namespace uses as its root or in a cover cannot be removed,
+/// except `ROOT`, which the kernel never releases
+fn pin(node: &Arc<DirectoryTreeNode>) {
+    if !Arc::ptr_eq(node, &ROOT) {
+        node.add_special_use();
+    }
+}
+
+fn unpin(node: &Arc<DirectoryTreeNode>) {
+    if !Arc::ptr_eq(node, &ROOT) {
+        node.sub_special_use();
+    }
+}
+
+fn is_node(node: &Arc<DirectoryTreeNode>, other: &DirectoryTreeNode) -> bool {
+    core::ptr::eq(Arc::as_ptr(node), other)
+}
+
+impl MountNamespace {
+    /// A copy with the same mounts and root, for unshare(CLONE_NEWNS)
+    pub fn copy(&self) -> Arc<Self> {
+        let ns = Arc::new(Self {
+            mounts: Mutex::new(self.mounts.lock().clone()),
+            root: RwLock::new(self.root()),
+            covers: RwLock::new(self.covers.read().clone()),
+        });
+        for node in ns.pinned() {
+            pin(&node);
+        }
+        ns
+    }
+
+    /// The directories [`pin`] was called on for this namespace
+    fn pinned(&self) -> Vec<Arc<DirectoryTreeNode>> {
+        let mut pinned = Vec::new();
+        for cover in self.covers.read().iter() {
+            pinned.push(cover.mount_point.clone());
+            pinned.push(cover.root.clone());
+        }
+        pinned.push(self.root());
+        pinned
+    }
+
+    pub fn root(&self) -> Arc<DirectoryTreeNode> {
+        self.root.read().clone()
+    }
+
+    pub fn is_root(&self, node: &DirectoryTreeNode) -> bool {
+        is_node(&self.root.read(), node)
+    }
+
+    /// The directory path walks enter when they reach `node`: the top of the
+    /// most recent mount on it, following mounts stacked on that one
+    pub fn enter(&self, node: Arc<DirectoryTreeNode>) -> Arc<DirectoryTreeNode> {
+        let covers = self.covers.read();
+        let mut node = node;
+        // Each cover is followed at most once, binding a directory onto
+        // itself or below itself cannot loop
+        for _ in 0..covers.len() {
+            match covers.iter().rev().find(|cover| is_node(&cover.mount_point, &node)) {
+                Some(cover) if !Arc::ptr_eq(&cover.root, &node) => node = cover.root.clone(),
+                _ => break,
+            }
+        }
+        node
+    }
+
+    /// The directory the old root `node` was moved onto, its ".." leads to the
+    /// directory's parent. The top of a bind mount is the source directory
+    /// itself, its ".." leads to the source's parent.
+    pub fn covered(&self, node: &DirectoryTreeNode) -> Option<Arc<DirectoryTreeNode>> {
+        self.covers
+            .read()
+            .iter()
+            .rev()
+            .find(|cover| cover.mount.is_none() && is_node(&cover.root, node))
+            .map(|cover| cover.mount_point.clone())
+    }
+
+    fn cover(&self, cover: Cover) {
+        pin(&cover.mount_point);
+        pin(&cover.root);
+        self.covers.write().push(cover);
+        COVERED.store(true, Ordering::Release);
+    }
+
+    /// Make `new_root`, the directory at absolute path `new_root_path`, the
+    /// root and move the old root onto `put_old` at `put_old_path`, see
+    /// [`MountTable::pivot`]
+    pub fn pivot_root(
+        &self,
+        new_root: Arc<DirectoryTreeNode>,
+        new_root_path: &str,
+        put_old: Arc<DirectoryTreeNode>,
+        put_old_path: &str,
+    ) -> Result<(), isize> {
+        self.mounts.lock().pivot(new_root_path, put_old_path)?;
+        pin(&new_root);
+        let old_root = core::mem::replace(&mut *self.root.write(), new_root);
+        self.cover(Cover {
+            mount_point: put_old,
+            root: old_root.clone(),
+            mount: None,
+        });
+        // The old root stays pinned by the cover
+        unpin(&old_root);
+        Ok(())
+    }
+
+    /// Bind mount directory `source` at `source_path` onto directory `target`
+    /// at `mount_point`: the same files show up at both paths, see
+    /// [`MountTable::bind`]. Copies propagated to peers are only recorded in
+    /// the table; peers made by bind mounts show the same directories, so the
+    /// files are already visible there.
+    pub fn bind(
+        &self,
+        source: Arc<DirectoryTreeNode>,
+        source_path: &str,
+        target: Arc<DirectoryTreeNode>,
+        mount_point: &str,
+        attr: u64,
+    ) -> Result<u64, isize> {
+        let id = self.mounts.lock().bind(source_path, mount_point, attr)?;
+        self.cover(Cover {
+            mount_point: target,
+            root: source,
+            mount: Some(id),
+        });
+        Ok(id)
+    }
+
+    /// Mount the filesystem whose root directory is `root` onto directory
+    /// `target` at `mount_point`, listed as `source` of type `fs_type`
+    pub fn mount_fs(
+        &self,
+        root: Arc<DirectoryTreeNode>,
+        source: &str,
+        target: Arc<DirectoryTreeNode>,
+        mount_point: &str,
+        fs_type: &str,
+        attr: u64,
+    ) -> u64 {
+        let id = self.mounts.lock().add(source, mount_point, fs_type, attr);
+        self.cover(Cover {
+            mount_point: target,
+            root,
+            mount: Some(id),
+        });
+        id
+    }
+
+    /// Unmount the most recent mount on `mount_point`, see [`MountTable::remove`]
+    pub fn unmount(&self, mount_point: &str) -> Result<(), isize> {
+        let mount = self.mounts.lock().remove(mount_point)?;
+        let mut covers = self.covers.write();
+        if let Some(index) = covers.iter().position(|cover| cover.mount == Some(mount.id)) {
+            let cover = covers.remove(index);
+            unpin(&cover.mount_point);
+            unpin(&cover.root);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for MountNamespace {
+    fn drop(&mut self) {
+        for node in self.pinned() {
+            unpin(&node);
+        }
+    }
+}
+
+/// The current task's mount namespace
+pub fn current_mnt_ns() -> Arc<MountNamespace> {
+    current_task().map_or_else(|| INIT_MNT_NS.clone(), |task| task.mnt_ns.lock().clone())
+}
+
+/// The current namespace when path walks have to consult it, that is once
+/// some namespace has a bind mount or has been pivoted
+pub fn walk_mnt_ns() -> Option<Arc<MountNamespace>> {
+    if COVERED.load(Ordering::Acquire) {
+        Some(current_mnt_ns())
+    } else {
+        None
+    }
+}
+
+/// Record the mounts that exist from boot, `root_fs_type` is the root filesystem's
+pub fn init(root_fs_type: &str) {
+    let mut mounts = INIT_MNT_NS.mounts.lock();
+    mounts.add("/dev/root", "/", root_fs_type, 0);
+    mounts.add("devtmpfs", "/dev", "devtmpfs", MOUNT_ATTR_NOSUID);
+    mounts.add(
+        "proc",
+        "/proc",
+        "proc",
+        MOUNT_ATTR_NOSUID | MOUNT_ATTR_NODEV | MOUNT_ATTR_NOEXEC,
+    );
+}
+
+/// Contents of `/proc/mounts`
+pub fn format_mounts() -> String {
+    let mut output = String::new();
+    for mount in current_mnt_ns().mounts.lock().iter() {
+        writeln!(
+            output,
+            "{} {} {} {} 0 0",
+            mount.source,
+            mount.mount_point,
+            mount.fs_type,
+            mount.options()
+        )
+        .ok();
+    }
+    output
+}
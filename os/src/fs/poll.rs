@@ -1,8 +1,26 @@
 use crate::{
     mm::try_get_from_user, syscall::errno::EFAULT, task::signal::Signals, timer::TimeSpec,
 };
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
 use alloc::vec::Vec;
 use core::ptr::null_mut;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use spin::Mutex;
+
+use super::{
+    cache::PageCache,
+    directory_tree::DirectoryTreeNode,
+    dirent::Dirent,
+    ext4::layout::Ext4OSInode,
+    fat32::FatOSInode,
+    file_trait::File,
+    layout::{OpenFlags, SeekWhence, Stat},
+    DiskInodeType,
+};
+use crate::mm::UserBuffer;
+use crate::syscall::errno::{EACCES, EEXIST, EINTR, EINVAL, ELOOP, ENOENT, ENOTDIR, EPERM, ESPIPE};
 
 use crate::{
     mm::{copy_from_user_array, copy_to_user_array},
@@ -400,3 +418,454 @@ pub fn pselect(
     }
     done as isize
 }
+
+bitflags! {
+    /// Event bits of `struct epoll_event`.
+    pub struct EpollEvents: u32 {
+    /// There is data to read.
+    const EPOLLIN = 0x001;
+    /// There is urgent data to read.
+    const EPOLLPRI = 0x002;
+    /// Writing now will not block.
+    const EPOLLOUT = 0x004;
+    /// Error condition, always reported.
+    const EPOLLERR = 0x008;
+    /// Hung up, always reported.
+    const EPOLLHUP = 0x010;
+    /// The peer closed its writing half.
+    const EPOLLRDHUP = 0x2000;
+    /// Wake only one of the epoll instances waiting on the file.
+    const EPOLLEXCLUSIVE = 1 << 28;
+    /// Keep the system from suspending while the event is pending.
+    const EPOLLWAKEUP = 1 << 29;
+    /// Report the file once, then disable it until `EPOLL_CTL_MOD`.
+    const EPOLLONESHOT = 1 << 30;
+    /// Report a file only when its readiness changes.
+    const EPOLLET = 1 << 31;
+    }
+}
+
+/// `struct epoll_event` as laid out on RISC-V and LoongArch,
+/// where it is not packed.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EpollEvent {
+    pub events: u32,
+    pub data: u64,
+}
+
+/// `epoll_ctl` operations.
+pub const EPOLL_CTL_ADD: usize = 1;
+pub const EPOLL_CTL_DEL: usize = 2;
+pub const EPOLL_CTL_MOD: usize = 3;
+
+/// How deep epoll instances may be nested in one another, as in Linux.
+const EPOLL_MAX_NESTS: usize = 4;
+
+/// A file registered with an epoll instance.
+struct EpollItem {
+    /// The registration lives as long as the open file, not the descriptor:
+    /// it goes away once every descriptor of the file is closed
+    file: Weak<dyn File>,
+    /// The requested events and flags
+    events: EpollEvents,
+    data: u64,
+    /// For `EPOLLET`, the events and the poll generation last reported
+    reported: Option<(EpollEvents, usize)>,
+    /// An `EPOLLONESHOT` registration that has fired
+    disabled: bool,
+}
+
+/// The file behind an epoll descriptor: the files it watches, keyed by the
+/// descriptor they were added with.
+///
+/// Readiness is checked by asking each file through `r_ready`, `w_ready` and
+/// `hang_up` when waiting, there are no wake-up callbacks. Edge-triggered
+/// registrations rely on [`File::poll_generation`] to tell new events from
+/// old ones; files without a generation behave as level-triggered, which may
+/// report more often but never misses an event.
+pub struct EpollInstance {
+    items: Mutex<BTreeMap<usize, EpollItem>>,
+    /// Where the next scan starts: the descriptor after the last one reported,
+    /// so busy low descriptors cannot starve higher ones
+    next_fd: AtomicUsize,
+}
+
+/// The events `file` is ready for, `EPOLLERR` and `EPOLLHUP` included.
+fn ready_events(file: &Arc<dyn File>) -> EpollEvents {
+    let mut events = EpollEvents::empty();
+    if file.r_ready() {
+        events |= EpollEvents::EPOLLIN;
+    }
+    if file.w_ready() {
+        events |= EpollEvents::EPOLLOUT;
+    }
+    if file.hang_up() {
+        events |= EpollEvents::EPOLLHUP;
+    }
+    events
+}
+
+impl EpollInstance {
+    pub fn new() -> Self {
+        Self {
+            items: Mutex::new(BTreeMap::new()),
+            next_fd: AtomicUsize::new(0),
+        }
+    }
+
+    /// Whether `self` is `target` or watches it, directly or through at most
+    /// `depth` levels of nested epoll instances.
+    /// `Err(())` if the nesting goes deeper than that
+    fn reaches(&self, target: &EpollInstance, depth: usize) -> Result<bool, ()> {
+        if core::ptr::eq(self, target) {
+            return Ok(true);
+        }
+        if depth == 0 {
+            return Err(());
+        }
+        for item in self.items.lock().values() {
+            if let Some(file) = item.file.upgrade() {
+                if let Some(nested) = file.downcast_ref::<EpollInstance>() {
+                    if nested.reaches(target, depth - 1)? {
+                        return Ok(true);
+                    }
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    /// Nesting depth of the epoll instances under `self`, 0 if it watches none.
+    fn nesting(&self) -> usize {
+        self.items
+            .lock()
+            .values()
+            .filter_map(|item| item.file.upgrade())
+            .filter_map(|file| {
+                file.downcast_ref::<EpollInstance>()
+                    .map(|nested| nested.nesting() + 1)
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// `epoll_ctl`: add, change or remove the registration of `file` under
+    /// descriptor `fd`.
+    /// # Errors
+    /// * `EEXIST`: adding a descriptor that is already registered
+    /// * `ENOENT`: changing or removing one that is not
+    /// * `ELOOP`: adding an epoll instance that watches `self`, or nesting
+    ///   epoll instances too deep
+    /// * `EPERM`: adding a regular file or directory, which are always ready
+    /// * `EINVAL`: an unknown operation, or `EPOLLEXCLUSIVE` used with `EPOLL_CTL_MOD`
+    pub fn control(
+        &self,
+        op: usize,
+        fd: usize,
+        file: &Arc<dyn File>,
+        event: EpollEvent,
+    ) -> Result<(), isize> {
+        let events = EpollEvents::from_bits_truncate(event.events);
+        if op == EPOLL_CTL_ADD {
+            if file.downcast_ref::<FatOSInode>().is_some()
+                || file.downcast_ref::<Ext4OSInode>().is_some()
+            {
+                return Err(EPERM);
+            }
+            if let Some(nested) = file.downcast_ref::<EpollInstance>() {
+                match nested.reaches(self, EPOLL_MAX_NESTS) {
+                    Ok(false) if nested.nesting() < EPOLL_MAX_NESTS => {}
+                    _ => return Err(ELOOP),
+                }
+            }
+        }
+        let mut items = self.items.lock();
+        // 描述符被关闭后重新分配给另一个文件时，旧的登记已经失效
+        let registered = items.get(&fd).map_or(false, |item| {
+            item.file
+                .upgrade()
+                .map_or(false, |registered| Arc::ptr_eq(&registered, file))
+        });
+        match op {
+            EPOLL_CTL_ADD => {
+                if registered {
+                    return Err(EEXIST);
+                }
+                items.insert(
+                    fd,
+                    EpollItem {
+                        file: Arc::downgrade(file),
+                        events,
+                        data: event.data,
+                        reported: None,
+                        disabled: false,
+                    },
+                );
+            }
+            EPOLL_CTL_MOD => {
+                if !registered {
+                    return Err(ENOENT);
+                }
+                let item = items.get_mut(&fd).unwrap();
+                if events.contains(EpollEvents::EPOLLEXCLUSIVE)
+                    || item.events.contains(EpollEvents::EPOLLEXCLUSIVE)
+                {
+                    return Err(EINVAL);
+                }
+                item.events = events;
+                item.data = event.data;
+                item.reported = None;
+                item.disabled = false;
+            }
+            EPOLL_CTL_DEL => {
+                if !registered {
+                    return Err(ENOENT);
+                }
+                items.remove(&fd);
+            }
+            _ => return Err(EINVAL),
+        }
+        Ok(())
+    }
+
+    /// Collect at most `max` ready events. With `consume`, the events count as
+    /// reported: edge-triggered files are not reported again until they
+    /// change, one-shot files are disabled, and the next scan resumes after
+    /// the last descriptor reported, wrapping around.
+    fn collect(&self, max: usize, consume: bool) -> Vec<EpollEvent> {
+        let mut ready = Vec::new();
+        let mut items = self.items.lock();
+        items.retain(|_, item| item.file.strong_count() > 0);
+        let start = self.next_fd.load(Ordering::Relaxed);
+        let fds: Vec<usize> = items
+            .range(start..)
+            .chain(items.range(..start))
+            .map(|(&fd, _)| fd)
+            .collect();
+        for fd in fds {
+            if ready.len() == max {
+                break;
+            }
+            let item = items.get_mut(&fd).unwrap();
+            if item.disabled {
+                continue;
+            }
+            let file = match item.file.upgrade() {
+                Some(file) => file,
+                None => continue,
+            };
+            let events =
+                ready_events(&file) & (item.events | EpollEvents::EPOLLERR | EpollEvents::EPOLLHUP);
+            if events.is_empty() {
+                if consume {
+                    item.reported = None;
+                }
+                continue;
+            }
+            if item.events.contains(EpollEvents::EPOLLET) {
+                if let Some(generation) = file.poll_generation() {
+                    if item.reported == Some((events, generation)) {
+                        continue;
+                    }
+                    if consume {
+                        item.reported = Some((events, generation));
+                    }
+                }
+            }
+            if consume && item.events.contains(EpollEvents::EPOLLONESHOT) {
+                item.disabled = true;
+            }
+            ready.push(EpollEvent {
+                events: events.bits(),
+                data: item.data,
+            });
+            if consume {
+                self.next_fd.store(fd + 1, Ordering::Relaxed);
+            }
+        }
+        ready
+    }
+}
+
+/// Wait until the epoll instance `epoll` has ready events, `timeout` expires
+/// or a signal arrives, and copy at most `max_events` events to `events`.
+/// # Arguments
+/// * `timeout`: in milliseconds, negative to wait indefinitely, 0 to return at once
+/// * `sigmask`: the sigmask in use during the wait, as in `ppoll`
+/// # Return Values
+/// * The number of events copied, 0 if the timeout expired
+/// * `EINTR` if an unblocked signal is pending, `EFAULT` if `events` is not writable
+pub fn epoll_pwait(
+    epoll: &EpollInstance,
+    events: *mut EpollEvent,
+    max_events: usize,
+    timeout: isize,
+    sigmask: *const Signals,
+) -> isize {
+    let task = current_task().unwrap();
+    let token = task.get_user_token();
+    let timeout = if timeout < 0 {
+        None
+    } else {
+        Some(TimeSpec::from_ms(timeout as usize) + TimeSpec::now())
+    };
+    // push to the top of TrapContext page, make use of redundant space
+    let oldsig =
+        ((task.trap_cx_user_va() + crate::config::PAGE_SIZE) as *mut Signals).wrapping_sub(1);
+    if !sigmask.is_null() {
+        sigprocmask(SigMaskHow::SIG_SETMASK.bits(), sigmask, oldsig);
+    }
+    drop(task);
+
+    let ret = loop {
+        let ready = epoll.collect(max_events, true);
+        if !ready.is_empty() {
+            log::trace!("[epoll_pwait] result: {:?}", ready);
+            break match copy_to_user_array(token, &ready[0], events, ready.len()) {
+                Ok(()) => ready.len() as isize,
+                Err(_) => EFAULT,
+            };
+        }
+        if let Some(timeout) = timeout {
+            if TimeSpec::now() >= timeout {
+                break 0;
+            }
+        }
+        let task = current_task().unwrap();
+        let inner = task.acquire_inner_lock();
+        if !inner.sigpending.difference(inner.sigmask).is_empty() {
+            break EINTR;
+        }
+        drop(inner);
+        drop(task);
+        suspend_current_and_run_next();
+    };
+    if !sigmask.is_null() {
+        sigprocmask(
+            SigMaskHow::SIG_SETMASK.bits(),
+            oldsig,
+            null_mut::<Signals>(),
+        );
+    }
+    ret
+}
+
+impl File for EpollInstance {
+    fn deep_clone(&self) -> Arc<dyn File> {
+        Arc::new(EpollInstance::new())
+    }
+
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    fn read(&self, _offset: Option<&mut usize>, _buf: &mut [u8]) -> usize {
+        EINVAL as usize
+    }
+
+    fn write(&self, _offset: Option<&mut usize>, _buf: &[u8]) -> usize {
+        EINVAL as usize
+    }
+
+    /// An epoll instance is readable when a wait on it would return events
+    fn r_ready(&self) -> bool {
+        !self.collect(1, false).is_empty()
+    }
+
+    fn w_ready(&self) -> bool {
+        false
+    }
+
+    fn read_user(&self, _offset: Option<usize>, _buf: UserBuffer) -> usize {
+        EINVAL as usize
+    }
+
+    fn write_user(&self, _offset: Option<usize>, _buf: UserBuffer) -> usize {
+        EINVAL as usize
+    }
+
+    fn get_size(&self) -> usize {
+        0
+    }
+
+    fn get_stat(&self) -> Stat {
+        Stat::new(0, 1, 0o600, 1, 0, 0, 0, 0, 0)
+    }
+
+    fn get_file_type(&self) -> DiskInodeType {
+        DiskInodeType::File
+    }
+
+    fn is_file(&self) -> bool {
+        false
+    }
+
+    fn info_dirtree_node(&self, _dirnode_ptr: Weak<DirectoryTreeNode>) {}
+
+    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>> {
+        None
+    }
+
+    fn open(&self, _flags: OpenFlags, _special_use: bool) -> Arc<dyn File> {
+        Arc::new(EpollInstance::new())
+    }
+
+    fn open_subfile(&self) -> Result<Vec<(String, Arc<dyn File>)>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn create(&self, _name: &str, _file_type: DiskInodeType) -> Result<Arc<dyn File>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn link_child(&self, _name: &str, _child: &Self) -> Result<(), isize> {
+        Err(ENOTDIR)
+    }
+
+    fn unlink(&self, _delete: bool) -> Result<(), isize> {
+        Err(EACCES)
+    }
+
+    fn get_dirent(&self, _count: usize) -> Vec<Dirent> {
+        Vec::new()
+    }
+
+    fn lseek(&self, _offset: isize, _whence: SeekWhence) -> Result<usize, isize> {
+        Err(ESPIPE)
+    }
+
+    fn modify_size(&self, _diff: isize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn truncate_size(&self, _new_size: usize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn set_timestamp(&self, _ctime: Option<usize>, _atime: Option<usize>, _mtime: Option<usize>) {}
+
+    fn get_single_cache(&self, _offset: usize) -> Result<Arc<Mutex<PageCache>>, ()> {
+        Err(())
+    }
+
+    fn get_all_caches(&self) -> Result<Vec<Arc<Mutex<PageCache>>>, ()> {
+        Err(())
+    }
+
+    fn oom(&self) -> usize {
+        0
+    }
+
+    fn hang_up(&self) -> bool {
+        false
+    }
+
+    fn fcntl(&self, _cmd: u32, _arg: u32) -> isize {
+        EINVAL
+    }
+}
@@ -50,7 +50,7 @@ impl VFS {
         block_device: Arc<dyn BlockDevice>,
         index_cache_mgr: Arc<spin::Mutex<BlockCacheManager>>,
     ) -> Arc<Self> {
-        let fs_type = pre_mount();
+        let fs_type = pre_mount(&block_device);
         match fs_type {
             FS_Type::Fat32 => EasyFileSystem::open(block_device, index_cache_mgr),
             // FS_Type::Ext4 => Ext4FileSystem::open(block_device, index_cache_mgr),
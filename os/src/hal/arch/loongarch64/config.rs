@@ -5,6 +5,8 @@ pub const USER_STACK_SIZE: usize = PAGE_SIZE * 40;
 pub const USER_HEAP_SIZE: usize = PAGE_SIZE * 40;
 pub const SYSTEM_TASK_LIMIT: usize = 128;
 pub const SYSTEM_FD_LIMIT: usize = 256;
+/// 全系统打开的文件描述符数量上限，用于健康检查
+pub const SYSTEM_OPEN_FILE_LIMIT: usize = 1024;
 pub const BLOCK_SZ: usize = 4096;
 pub const PAGE_SIZE: usize = 0x1000;
 pub const PAGE_SIZE_BITS: usize = PAGE_SIZE.trailing_zeros() as usize;
@@ -50,6 +52,9 @@ pub const MEMORY_HIGH_BASE_VPN: usize = MEMORY_HIGH_BASE >> PAGE_SIZE_BITS;
 pub const USER_STACK_BASE: usize = TASK_SIZE - PAGE_SIZE | LA_START;
 pub const MEMORY_START: usize = 0x0000_0000_9000_0000;
 pub const MEMORY_END: usize = MEMORY_SIZE + MEMORY_START;
+/// LoongArch 暂不从设备树探测内存，以下两者只用于校验
+pub const MEMORY_SIZE_MIN: usize = MEMORY_SIZE;
+pub const MEMORY_SIZE_MAX: usize = MEMORY_SIZE;
 
 pub const SV39_SPACE: usize = 1 << 39;
 pub const USR_SPACE_LEN: usize = SV39_SPACE >> 2;
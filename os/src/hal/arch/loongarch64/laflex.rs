@@ -39,6 +39,8 @@ bitflags! {
         const P = 1 << 7;
         /// Writable Bit
         const W = 1 << 8;
+        /// Software Bit, the page may be dropped by reclaim (MADV_FREE)
+        const FREEABLE = 1 << 9;
 
         /// Not Readable Bit
         const NR = 1 << (usize::BITS-3); // 61
@@ -114,6 +116,18 @@ impl LAFlexPageTableEntry {
         self.flags().contains(LAPTEFlagBits::D)
     }
     #[inline(always)]
+    pub fn is_freeable(&self) -> bool {
+        self.flags().contains(LAPTEFlagBits::FREEABLE)
+    }
+    #[inline(always)]
+    pub fn set_freeable(&mut self, freeable: bool) {
+        if freeable {
+            self.bits |= LAPTEFlagBits::FREEABLE.bits();
+        } else {
+            self.bits &= !LAPTEFlagBits::FREEABLE.bits();
+        }
+    }
+    #[inline(always)]
     pub fn writable(&self) -> bool {
         self.flags().contains(LAPTEFlagBits::W)
     }
@@ -464,6 +478,14 @@ impl PageTable for LAFlexPageTable {
             Err(())
         }
     }
+    fn set_freeable(&mut self, vpn: VirtPageNum, freeable: bool) -> Result<(), ()> {
+        if let Some(pte) = self.find_pte_refmut(vpn) {
+            pte.set_freeable(freeable);
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
     fn activate(&self) {
         tlb_global_invalidate();
         if self.is_kernel_pt() {
@@ -482,6 +504,9 @@ impl PageTable for LAFlexPageTable {
             self.find_pte(vpn).map(|pte| pte.is_dirty())
         }
     }
+    fn is_freeable(&self, vpn: VirtPageNum) -> Option<bool> {
+        self.find_pte(vpn).map(|pte| pte.is_freeable())
+    }
     fn readable(&self, vpn: VirtPageNum) -> Option<bool> {
         self.find_pte(vpn).map(|pte| pte.readable())
     }
@@ -19,7 +19,7 @@ pub mod time;
 pub mod trap;
 pub type KernelPageTableImpl = laflex::LAFlexPageTable;
 pub type PageTableImpl = laflex::LAFlexPageTable;
-pub use sbi::{console_flush, console_getchar, console_putchar, shutdown};
+pub use sbi::{console_flush, console_getchar, console_putchar, reboot, shutdown};
 pub use switch::__switch;
 pub use tlb::{tlb_global_invalidate, tlb_invalidate};
 
@@ -147,3 +147,22 @@ pub fn restore_interrupts(was_enabled: bool) {
         CrMd::read().set_ie(true).write();
     }
 }
+
+/// 空闲核等待中断，LoongArch 暂未实现核间中断，保持忙等
+pub fn idle_wait() {}
+
+/// 唤醒空闲核，见 [`idle_wait`]
+pub fn wake_cpu(_cpu_id: usize) {}
+
+/// 硬件观察点，LoongArch 暂未实现
+pub fn set_watchpoint(_owner: usize, _addr: usize) -> Result<(), isize> {
+    Err(crate::syscall::errno::ENODEV)
+}
+
+/// 见 [`set_watchpoint`]
+pub fn clear_watchpoint(_owner: usize) {}
+
+/// 单步，LoongArch 暂未实现
+pub fn single_step_supported() -> bool {
+    false
+}
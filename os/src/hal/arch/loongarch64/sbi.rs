@@ -42,4 +42,15 @@ pub fn shutdown() -> ! {
         }
     }
     loop {}
-}
\ No newline at end of file
+}
+
+pub fn reboot() -> ! {
+    #[cfg(feature="board_laqemu")]
+    {
+        // GED 的复位寄存器紧跟在睡眠控制寄存器之后
+        unsafe {
+            (0x100E_001E as *mut u8).write_volatile(0x42);
+        }
+    }
+    shutdown()
+}
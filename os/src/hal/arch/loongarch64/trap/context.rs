@@ -1,7 +1,7 @@
 use core::fmt::Debug;
 
 use crate::{
-    hal::arch::loongarch64::register::PrMd,
+    hal::{arch::loongarch64::register::PrMd, TrapContextOps},
     task::{SignalStack, Signals},
 };
 
@@ -170,6 +170,8 @@ impl TrapContext {
     pub fn set_sp(&mut self, sp: usize) {
         self.gp.sp = sp;
     }
+    /// FP registers are restored from the context on every return, nothing to do.
+    pub fn invalidate_fp(&mut self) {}
     pub fn app_init_context(
         entry: usize,
         sp: usize,
@@ -193,3 +195,29 @@ impl TrapContext {
         cx
     }
 }
+
+impl TrapContextOps for TrapContext {
+    fn syscall_nr(&self) -> usize {
+        self.gp.a7
+    }
+    fn syscall_args(&self) -> [usize; 6] {
+        [
+            self.gp.a0, self.gp.a1, self.gp.a2, self.gp.a3, self.gp.a4, self.gp.a5,
+        ]
+    }
+    fn origin_arg0(&self) -> usize {
+        self.origin_a0
+    }
+    fn return_value(&self) -> usize {
+        self.gp.a0
+    }
+    fn set_return(&mut self, ret: usize) {
+        self.gp.a0 = ret;
+    }
+    fn pc(&self) -> usize {
+        self.gp.pc
+    }
+    fn set_pc(&mut self, pc: usize) {
+        self.gp.pc = pc;
+    }
+}
@@ -13,11 +13,13 @@ use crate::hal::arch::loongarch64::laflex::LAFlexPageTable;
 use crate::hal::arch::loongarch64::register::{CrMd, ECfg, LineBasedInterrupt, PrMd, TCfg, TIClr};
 use crate::hal::arch::loongarch64::trap::mem_access::Instruction;
 use crate::hal::arch::TICKS_PER_SEC;
+use crate::hal::TrapContextOps;
+use crate::utils::telemetry::heartbeat;
 use crate::mm::{copy_from_user, copy_to_user, frame_reserve, MemoryError, PageTable, VirtAddr};
 use crate::syscall::syscall;
 use crate::task::{
-    current_task, current_trap_cx, current_user_token, do_signal, do_wake_expired,
-    suspend_current_and_run_next, Signals,
+    current_task, current_trap_cx, current_user_token, do_signal, do_wake_expired, run_tasks,
+    preempt_current_and_run_next, resched_if_needed, task_tick, Signals,
 };
 use core::arch::{asm, global_asm};
 use core::ptr::{addr_of, addr_of_mut};
@@ -166,8 +168,8 @@ pub fn trap_handler() -> ! {
     }
     set_kernel_trap_entry();
 
-    {
-        let task = current_task().unwrap();
+    // 与 RISC-V 一致：仅当有任务时记录时间
+    if let Some(task) = current_task() {
         let mut inner = task.acquire_inner_lock();
         inner.update_process_times_enter_trap();
     }
@@ -175,21 +177,28 @@ pub fn trap_handler() -> ! {
     let cause = get_exception_cause();
     let stval = get_bad_addr();
     let badi = get_bad_instruction();
-    log::info!("[trap_handler]Cause:{:?}", cause);
+    log::trace!("[trap_handler]Cause:{:?}", cause);
     match cause {
         Trap::Exception(Exception::Syscall) => {
-            // jump to next instruction anyway
-            let mut cx = current_trap_cx();
-            ERA::read().next_ins().write();
-            cx.gp.pc += 4;
-            // get system call return value
-            let result = syscall(
-                cx.gp.a7,
-                [cx.gp.a0, cx.gp.a1, cx.gp.a2, cx.gp.a3, cx.gp.a4, cx.gp.a5],
-            );
-            // cx is changed during sys_exec, so we have to call it again
-            cx = current_trap_cx();
-            cx.gp.a0 = result as usize;
+            // 在单独的块中取出系统调用号和参数，保证 syscall 之前 task 已被 Drop，
+            // 否则 sys_exit 不返回时栈上残留的引用会使引用计数无法清零
+            let (syscall_id, args) = if let Some(task) = current_task() {
+                let inner = task.acquire_inner_lock();
+                let cx = inner.get_trap_cx();
+                // jump to next instruction anyway
+                ERA::read().next_ins().write();
+                cx.advance_pc();
+                (cx.syscall_nr(), cx.syscall_args())
+            } else {
+                panic!("Syscall from Idle is impossible!");
+            };
+            let result = syscall(syscall_id, args);
+            // cx is changed during sys_exec, so we have to fetch it again
+            if let Some(task) = current_task() {
+                let mut inner = task.acquire_inner_lock();
+                inner.get_trap_cx().set_return(result as usize);
+                inner.in_syscall = true;
+            }
         }
         Trap::Exception(Exception::PagePrivilegeIllegal)
         | Trap::Exception(Exception::PageInvalidFetch)
@@ -198,7 +207,10 @@ pub fn trap_handler() -> ! {
         | Trap::Exception(Exception::PageModifyFault)
         | Trap::Exception(Exception::PageNonReadableFault)
         | Trap::Exception(Exception::PageNonExecutableFault) => {
-            let task = current_task().unwrap();
+            let task = match current_task() {
+                Some(task) => task,
+                None => panic!("Kernel PageFault in Idle/Init! cause: {:?}, badv: {:#x}", cause, stval),
+            };
             let addr = VirtAddr::from(get_bad_addr());
             log::debug!("[page_fault] pid: {}, type: {:?}", task.pid.0, cause);
             log::debug!(
@@ -243,31 +255,56 @@ pub fn trap_handler() -> ! {
                 }
             };
         }
+        // 用户态访问内核地址或取指地址未对齐，与 RISC-V 的 access fault 一样交给信号处理
+        Trap::Exception(Exception::AddressError) | Trap::Exception(Exception::BoundsCheckFault) => {
+            if let Some(task) = current_task() {
+                log::debug!(
+                    "[trap_handler] pid: {}, {:?}, badv: {:#x}",
+                    task.pid.0,
+                    cause,
+                    stval
+                );
+                task.acquire_inner_lock().add_signal(Signals::SIGSEGV);
+            } else {
+                panic!("{:?} in Idle! badv: {:#x}", cause, stval);
+            }
+        }
         Trap::Exception(Exception::InstructionNonDefined)
         | Trap::Exception(Exception::InstructionPrivilegeIllegal) => {
-            let task = current_task().unwrap();
-            let mut inner = task.acquire_inner_lock();
-            inner.add_signal(Signals::SIGILL);
+            if let Some(task) = current_task() {
+                let mut inner = task.acquire_inner_lock();
+                inner.add_signal(Signals::SIGILL);
+            } else {
+                panic!("IllegalInstruction in Idle!");
+            }
         }
         Trap::Interrupt(Interrupt::Timer) => {
             do_wake_expired();
+            #[cfg(feature = "load_balance")]
+            crate::task::load_balance_tick();
+            // 从用户态进入时才检查控制台的 Ctrl-C 与 Ctrl-Z
+            crate::fs::dev::tty::poll_console_signal();
+            heartbeat();
             // 记录时钟中断次数（中断号5）
             crate::fs::dev::interrupts::Interrupts::increment_interrupt_count(5);
+            crate::utils::random::add_interrupt_randomness(5);
             TIClr::read().clear_timer().write();
             enable_timer_interrupt();
-            suspend_current_and_run_next();
+            tick_or_schedule();
         }
         Trap::Interrupt(Interrupt::HWI0) => {
             // 记录外部中断次数（中断号9）
             crate::fs::dev::interrupts::Interrupts::increment_interrupt_count(9);
+            crate::utils::random::add_interrupt_randomness(9);
             // 这里可以添加具体的外部中断处理逻辑
-            suspend_current_and_run_next();
+            yield_or_schedule();
         }
         Trap::Interrupt(Interrupt::HWI1) => {
             // 记录外部中断次数（中断号10）
             crate::fs::dev::interrupts::Interrupts::increment_interrupt_count(10);
+            crate::utils::random::add_interrupt_randomness(10);
             // 这里可以添加具体的外部中断处理逻辑
-            suspend_current_and_run_next();
+            yield_or_schedule();
         }
         Trap::Exception(Exception::Breakpoint) => {
             read_bp();
@@ -279,11 +316,17 @@ pub fn trap_handler() -> ! {
             let mut i = 0;
             copy_from_user(token, pc as *const u32, addr_of_mut!(i)).unwrap();
             let ins = Instruction::from(i);
-            let op = ins.get_op_code();
-            if op.is_err() {
-                panic!("Unsupported OpCode! Instruction: {:?} ", ins);
-            }
-            let op = op.unwrap();
+            let op = match ins.get_op_code() {
+                Ok(op) => op,
+                // 无法模拟的非对齐访问，与 Linux 一致发送 SIGBUS
+                Err(_) => {
+                    log::warn!("[trap_handler] unsupported unaligned access: {:?}", ins);
+                    if let Some(task) = current_task() {
+                        task.acquire_inner_lock().add_signal(Signals::SIGBUS);
+                    }
+                    leave_trap(cause)
+                }
+            };
             let addr = BadV::read().get_vaddr();
             //debug!("{:#x}: {:?}, {:#x}", pc, op, addr);
             let sz = op.get_size();
@@ -334,23 +377,47 @@ pub fn trap_handler() -> ! {
                 );
             }
         }
-        Trap::Interrupt(Interrupt::IPI)
-        | Trap::MachineError(_)
-        | Trap::Unknown
-        | Trap::Exception(Exception::AddressError)
-        | _ => {
+        Trap::Interrupt(Interrupt::IPI) | Trap::MachineError(_) | Trap::Unknown | _ => {
             panic!(
                 "Unsupported trap {:?}, stval = {:#x}, BadI = {:#x}!",
                 cause, stval, badi
             );
         }
     }
-    {
-        let task = current_task().unwrap();
+    leave_trap(cause);
+}
+
+/// 时钟/外部中断后让出 CPU；Idle 状态下没有可挂起的任务，直接回到调度循环
+fn yield_or_schedule() {
+    if current_task().is_some() {
+        preempt_current_and_run_next();
+    } else {
+        run_tasks();
+    }
+}
+
+/// 时钟中断后只在时间片用完时让出 CPU；Idle 状态下直接回到调度循环
+fn tick_or_schedule() {
+    if current_task().is_some() {
+        task_tick();
+    } else {
+        run_tasks();
+    }
+}
+
+/// 需要时先让出 CPU，再记录离开内核的时间并返回用户态
+fn leave_trap(cause: Trap) -> ! {
+    resched_if_needed();
+    if let Some(task) = current_task() {
         let mut inner = task.acquire_inner_lock();
         inner.update_process_times_leave_trap(cause);
+        drop(inner);
+        drop(task);
+        trap_return();
+    } else {
+        run_tasks();
+        panic!("Unreachable in trap_handler: run_tasks returned!");
     }
-    trap_return();
 }
 
 fn read_bp() {
@@ -508,8 +575,12 @@ pub extern "C" fn trap_from_kernel(gr: &mut GeneralRegs) {
         Trap::Interrupt(Interrupt::Timer) => {
             // 唤醒过期的任务
             do_wake_expired();
+            #[cfg(feature = "load_balance")]
+            crate::task::load_balance_tick();
+            heartbeat();
             // 记录时钟中断次数（中断号5）
             crate::fs::dev::interrupts::Interrupts::increment_interrupt_count(5);
+            crate::utils::random::add_interrupt_randomness(5);
             // 清除定时器中断
             TIClr::read().clear_timer().write();
             // 重新使能定时器中断
@@ -8,14 +8,15 @@ pub use loongarch64::{
     config::BUFFER_CACHE_NUM,
     config::KERNEL_HEAP_SIZE,
     config::MEMORY_END,
-    console_flush, console_getchar, console_putchar, machine_init, shutdown,
+    console_flush, console_getchar, console_putchar, machine_init, reboot, shutdown,
     time::{get_clock_freq, get_time, TICKS_PER_SEC},
     KernelPageTableImpl, PageTableImpl, __switch, kstack_alloc, tlb_invalidate,
     trap::{
-        get_bad_addr, get_bad_instruction, get_exception_cause, trap_handler, trap_return,
-        MachineContext, TrapContext, TrapImpl, UserContext,
+        get_bad_addr, get_bad_ins_addr, get_bad_instruction, get_exception_cause, trap_handler,
+        trap_return, MachineContext, TrapContext, TrapImpl, UserContext,
     },
-    disable_interrupts, restore_interrupts,
+    disable_interrupts, restore_interrupts, idle_wait, wake_cpu,
+    clear_watchpoint, set_watchpoint, single_step_supported,
     trap_cx_bottom_from_tid, ustack_bottom_from_tid, KernelStack, BLOCK_SZ,
 };
 #[cfg(feature = "riscv")]
@@ -30,15 +31,16 @@ pub use riscv::{
     kern_stack::KernelStack,
     machine_init,
     rv_board::MMIO,
-    sbi::{console_flush, console_getchar, console_putchar, set_timer, shutdown},
+    sbi::{console_flush, console_getchar, console_putchar, reboot, set_timer, shutdown},
     sv39::tlb_invalidate,
     switch::__switch,
     time::{get_clock_freq, get_time, TICKS_PER_SEC},
     trap::{
-        context::TrapContext, get_bad_addr, get_bad_instruction, get_exception_cause, trap_handler,
-        trap_return, UserContext,
+        context::TrapContext, get_bad_addr, get_bad_ins_addr, get_bad_instruction,
+        get_exception_cause, trap_handler, trap_return, UserContext,
     },
-    disable_interrupts, restore_interrupts, boot_entry_paddr,
+    disable_interrupts, restore_interrupts, idle_wait, wake_cpu, boot_entry_paddr,
     ap_init, ap_finish_init,
+    debug_trigger::{clear_watchpoint, set_watchpoint, single_step_supported},
     KernelPageTableImpl, MachineContext, PageTableImpl, TrapImpl,
 };
@@ -27,6 +27,10 @@ pub const MEMORY_END: usize = MEMORY_START + MEMORY_SIZE;
 pub const MEMORY_END: usize = 0x9000_0000;
 #[cfg(feature = "board_cv1811h")]
 pub const MEMORY_END: usize = 0x9000_0000; //256M
+/// 设备树报告的内存小于该值时无法启动
+pub const MEMORY_SIZE_MIN: usize = 0x800_0000;
+/// 设备树报告的内存超过该值时截断，帧分配器与内核线性映射的元数据都放在内核堆中
+pub const MEMORY_SIZE_MAX: usize = 0x4000_0000;
 pub const PAGE_SIZE: usize = 0x1000;
 pub const PAGE_SIZE_BITS: usize = 0xc;
 
@@ -41,6 +45,8 @@ pub const DISK_IMAGE_BASE: usize = MEMORY_START + 0x1000_0000;
 
 pub const SYSTEM_TASK_LIMIT: usize = 128;
 pub const SYSTEM_FD_LIMIT: usize = 256;
+/// 全系统打开的文件描述符数量上限，用于健康检查
+pub const SYSTEM_OPEN_FILE_LIMIT: usize = 1024;
 
 pub const BLOCK_SZ: usize = 512;
 
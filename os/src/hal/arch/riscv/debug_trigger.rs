@@ -0,0 +1,252 @@
+//! 硬件调试触发器：写观察点 (Watchpoint) 与单步 (Single-step)
+//!
+//! 触发器 CSR (tselect/tdata1/tdata2) 只能在 M 态访问，这里通过 SBI DBTR 扩展
+//! 请固件代为安装。两种触发器命中时都在用户态产生断点异常，内核向任务发送 SIGTRAP。
+//!
+//! - 写观察点：每个任务只支持一个，任务写入该地址时，写操作执行前陷入。
+//!   观察点记录在以 pid 为键的表中，fork 出的子进程不继承，exec 与退出时清除。
+//!   观察点是一次性的：命中后立即卸载，从信号处理函数返回后被拦下的写操作
+//!   可以正常完成，而不会反复陷入。
+//! - 单步：使用 icount 触发器，计数为 1，任务每执行完一条用户态指令就陷入一次。
+//!   是否单步由任务自己的标志决定，每次返回用户态时重新装入计数。
+//! - 触发器属于各个 CPU。返回用户态前若本 CPU 上安装的不是当前任务的触发器，
+//!   就先卸载旧的再按需安装新的，与惰性浮点记录寄存器所有者的做法相同。
+
+use alloc::collections::BTreeMap;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use spin::Mutex;
+
+use super::sbi;
+use crate::config::MAX_CPU_NUM;
+use crate::syscall::errno::{EBUSY, ENODEV};
+use crate::task::processor::current_cpu_id;
+
+const TDATA1_TYPE_SHIFT: usize = 60;
+/// mcontrol6 (Sdtrig 1.0)
+const TRIGGER_MCONTROL6: usize = 6;
+/// 旧版 mcontrol，QEMU 等实现仍在使用
+const TRIGGER_MCONTROL: usize = 2;
+/// 两种类型中 action = 0 (断点异常)、match = 0 (地址相等) 的位都为 0，
+/// U 与 store 位的位置也相同
+const TDATA1_U: usize = 1 << 3;
+const TDATA1_STORE: usize = 1 << 1;
+
+/// 指令计数触发器，action = 0
+const TRIGGER_ICOUNT: usize = 3;
+const ICOUNT_COUNT_SHIFT: usize = 10;
+const ICOUNT_U: usize = 1 << 6;
+
+/// 尚未探测固件支持的触发器类型
+const UNPROBED: usize = usize::MAX;
+/// 固件没有可用的触发器
+const UNSUPPORTED: usize = 0;
+
+fn write_trigger(trigger_type: usize) -> usize {
+    (trigger_type << TDATA1_TYPE_SHIFT) | TDATA1_U | TDATA1_STORE
+}
+
+/// 用户态每执行完一条指令触发一次
+fn step_trigger() -> usize {
+    (TRIGGER_ICOUNT << TDATA1_TYPE_SHIFT) | (1 << ICOUNT_COUNT_SHIFT) | ICOUNT_U
+}
+
+/// 固件可用的写观察点触发器类型，首次使用时探测
+static TRIGGER_TYPE: AtomicUsize = AtomicUsize::new(UNPROBED);
+/// 固件是否提供 icount 触发器，首次使用时探测
+static STEP_TRIGGER: AtomicUsize = AtomicUsize::new(UNPROBED);
+
+fn trigger_type() -> Option<usize> {
+    let mut trigger_type = TRIGGER_TYPE.load(Ordering::Relaxed);
+    if trigger_type == UNPROBED {
+        trigger_type = [TRIGGER_MCONTROL6, TRIGGER_MCONTROL]
+            .iter()
+            .copied()
+            .find(|&ty| sbi::debug_num_triggers(write_trigger(ty)) > 0)
+            .unwrap_or(UNSUPPORTED);
+        TRIGGER_TYPE.store(trigger_type, Ordering::Relaxed);
+    }
+    (trigger_type != UNSUPPORTED).then_some(trigger_type)
+}
+
+/// 固件能否为单步安装 icount 触发器
+pub fn single_step_supported() -> bool {
+    let mut supported = STEP_TRIGGER.load(Ordering::Relaxed);
+    if supported == UNPROBED {
+        supported = (sbi::debug_num_triggers(step_trigger()) > 0) as usize;
+        STEP_TRIGGER.store(supported, Ordering::Relaxed);
+    }
+    supported != UNSUPPORTED
+}
+
+/// 与固件交换参数的共享内存：tstate/tdata1/tdata2/tdata3，
+/// 安装后第一个字为触发器编号。内核恒等映射，虚拟地址即物理地址
+#[repr(C, align(16))]
+struct ShmemEntry([usize; 4]);
+
+static mut SHMEM: [ShmemEntry; MAX_CPU_NUM] = {
+    const INIT: ShmemEntry = ShmemEntry([0; 4]);
+    [INIT; MAX_CPU_NUM]
+};
+
+/// 观察点的 `addr` 为被观察的地址，单步的 `addr` 为安装时任务将要执行的指令地址
+#[derive(Clone, Copy)]
+struct Installed {
+    owner: usize,
+    addr: usize,
+    index: usize,
+}
+
+/// 每个 CPU 上的触发器状态，只由该 CPU 自己访问
+struct HartTriggers {
+    shmem_set: bool,
+    watch: Option<Installed>,
+    step: Option<Installed>,
+}
+
+static HARTS: [Mutex<HartTriggers>; MAX_CPU_NUM] = {
+    const INIT: Mutex<HartTriggers> = Mutex::new(HartTriggers {
+        shmem_set: false,
+        watch: None,
+        step: None,
+    });
+    [INIT; MAX_CPU_NUM]
+};
+
+/// 每个任务 (pid) 的观察点地址
+static WATCHES: Mutex<BTreeMap<usize, usize>> = Mutex::new(BTreeMap::new());
+/// 已设置的观察点与已安装的触发器总数，为 0 时返回用户态无需加锁
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+
+impl HartTriggers {
+    /// 安装一个触发器，返回其编号
+    fn install(&mut self, cpu_id: usize, tdata1: usize, tdata2: usize) -> Option<usize> {
+        let entry = unsafe { &mut *core::ptr::addr_of_mut!(SHMEM[cpu_id]) };
+        if !self.shmem_set {
+            if sbi::debug_set_shmem(entry as *mut ShmemEntry as usize) != 0 {
+                return None;
+            }
+            self.shmem_set = true;
+        }
+        entry.0 = [0, tdata1, tdata2, 0];
+        if sbi::debug_install_triggers(1) != 0 {
+            log::warn!(
+                "[debug_trigger] cpu {} failed to install tdata1 {:#x}",
+                cpu_id,
+                tdata1
+            );
+            return None;
+        }
+        ACTIVE.fetch_add(1, Ordering::Relaxed);
+        Some(entry.0[0])
+    }
+
+    fn uninstall(index: usize) {
+        sbi::debug_uninstall_triggers(index, 1);
+        ACTIVE.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    fn install_watch(&mut self, cpu_id: usize, trigger_type: usize, owner: usize, addr: usize) {
+        self.watch = self
+            .install(cpu_id, write_trigger(trigger_type), addr)
+            .map(|index| Installed { owner, addr, index });
+    }
+
+    fn uninstall_watch(&mut self) {
+        if let Some(installed) = self.watch.take() {
+            Self::uninstall(installed.index);
+        }
+    }
+
+    /// icount 命中后计数归零，每次返回用户态都重新安装
+    fn rearm_step(&mut self, cpu_id: usize, owner: usize, step_pc: Option<usize>) {
+        if let Some(installed) = self.step.take() {
+            Self::uninstall(installed.index);
+        }
+        if let Some(pc) = step_pc {
+            self.step = self
+                .install(cpu_id, step_trigger(), 0)
+                .map(|index| Installed {
+                    owner,
+                    addr: pc,
+                    index,
+                });
+        }
+    }
+}
+
+/// 为任务 `owner` 设置写观察点，`addr` 为 0 时清除
+///
+/// 固件不支持触发器时返回 ENODEV，已有观察点时返回 EBUSY
+pub fn set_watchpoint(owner: usize, addr: usize) -> Result<(), isize> {
+    if addr == 0 {
+        clear_watchpoint(owner);
+        return Ok(());
+    }
+    if trigger_type().is_none() {
+        return Err(ENODEV);
+    }
+    let mut watches = WATCHES.lock();
+    if watches.contains_key(&owner) {
+        return Err(EBUSY);
+    }
+    watches.insert(owner, addr);
+    ACTIVE.fetch_add(1, Ordering::Relaxed);
+    Ok(())
+}
+
+/// 清除任务 `owner` 的观察点，任务 exec 或退出时调用
+///
+/// 其他 CPU 上残留的触发器在它们下一次返回用户态时卸载
+pub fn clear_watchpoint(owner: usize) {
+    if WATCHES.lock().remove(&owner).is_some() {
+        ACTIVE.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// 返回用户态前调用，使本 CPU 的触发器与任务 `owner` 的观察点和单步状态一致
+///
+/// 任务需要单步时 `step_pc` 为它将要执行的指令地址
+pub fn on_trap_return(owner: usize, step_pc: Option<usize>) {
+    let step_pc = step_pc.filter(|_| single_step_supported());
+    if ACTIVE.load(Ordering::Relaxed) == 0 && step_pc.is_none() {
+        return;
+    }
+    let wanted = WATCHES.lock().get(&owner).copied();
+    let cpu_id = current_cpu_id();
+    let mut hart = HARTS[cpu_id].lock();
+    match (hart.watch, wanted) {
+        (Some(installed), Some(addr)) if installed.owner == owner && installed.addr == addr => {}
+        (None, None) => {}
+        _ => {
+            hart.uninstall_watch();
+            if let (Some(addr), Some(trigger_type)) = (wanted, trigger_type()) {
+                hart.install_watch(cpu_id, trigger_type, owner, addr);
+            }
+        }
+    }
+    if step_pc.is_some() || hart.step.is_some() {
+        hart.rearm_step(cpu_id, owner, step_pc);
+    }
+}
+
+/// 用户态断点异常时调用，`pc` 为异常指令地址
+///
+/// 本 CPU 上安装着任务 `owner` 的观察点时，视为观察点命中：卸载触发器并清除
+/// 观察点，返回命中的地址；否则是单步或 ebreak，返回 `None`。
+/// 观察点在写指令执行前陷入，单步在指令执行后陷入，因此同时安装两者时，
+/// `pc` 仍是单步安装时的地址说明还没有指令执行完，是观察点命中
+pub fn on_breakpoint(owner: usize, pc: usize) -> Option<usize> {
+    let mut hart = HARTS[current_cpu_id()].lock();
+    if matches!(hart.step, Some(step) if step.owner == owner && step.addr != pc) {
+        return None;
+    }
+    match hart.watch {
+        Some(installed) if installed.owner == owner => {
+            hart.uninstall_watch();
+            drop(hart);
+            clear_watchpoint(owner);
+            Some(installed.addr)
+        }
+        _ => None,
+    }
+}
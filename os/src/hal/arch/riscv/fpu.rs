@@ -0,0 +1,178 @@
+//! 惰性浮点上下文 (Lazy FPU)
+//!
+//! 用户任务默认以 `sstatus.FS = Off` 返回用户态，第一次执行浮点指令时会触发
+//! IllegalInstruction。此时把任务保存在 `TrapContext` 中的浮点寄存器装入硬件，
+//! 并把当前 CPU 的浮点寄存器所有者 (FP owner) 记为该任务，之后该任务在本 CPU 上
+//! 往返用户态都不再搬运浮点寄存器。
+//!
+//! - 陷入时 (`__alltraps`) 只有 `FS == Dirty` 才把浮点寄存器写回 `TrapContext`。
+//!   因此前一个所有者的最新状态在它离开用户态时就已经落到了它自己的上下文里，
+//!   新任务抢占所有权时不需要再额外保存。
+//! - 返回用户态时若本 CPU 的所有者不是当前任务，则把 FS 置为 Off，等待首次使用。
+//! - 内核以 `FS = Clean` 运行；若返回用户态时发现 FS 变成了 Dirty，说明内核自身
+//!   用到了浮点寄存器，此时丢弃本 CPU 的所有者记录。
+
+use core::arch::asm;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use riscv::register::sstatus::{self, Sstatus, FS};
+
+use super::trap::context::{FloatRegs, TrapContext};
+use crate::config::MAX_CPU_NUM;
+use crate::task::processor::current_cpu_id;
+use crate::utils::telemetry::{FPU_RESTORES, FPU_SAVES};
+
+/// 没有任何任务拥有该 CPU 的寄存器
+const NO_OWNER: usize = usize::MAX;
+
+const SSTATUS_FS_SHIFT: usize = 13;
+const SSTATUS_FS_MASK: usize = 0b11 << SSTATUS_FS_SHIFT;
+
+/// 记录每个 CPU 上惰性装载的寄存器组属于哪个任务 (pid)
+pub struct OwnerTable([AtomicUsize; MAX_CPU_NUM]);
+
+impl OwnerTable {
+    pub const fn new() -> Self {
+        const INIT: AtomicUsize = AtomicUsize::new(NO_OWNER);
+        Self([INIT; MAX_CPU_NUM])
+    }
+    /// 本 CPU 的寄存器是否属于 `owner`
+    pub fn owned_by(&self, cpu_id: usize, owner: usize) -> bool {
+        self.0[cpu_id].load(Ordering::Relaxed) == owner
+    }
+    /// 本 CPU 的寄存器已被破坏，不再属于任何任务
+    pub fn clear(&self, cpu_id: usize) {
+        self.0[cpu_id].store(NO_OWNER, Ordering::Relaxed);
+    }
+    /// 把本 CPU 的寄存器记为 `owner` 所有
+    pub fn claim(&self, cpu_id: usize, owner: usize) {
+        self.0[cpu_id].store(owner, Ordering::Relaxed);
+        // 任务可能迁移过，其他 CPU 上残留的寄存器已经过时
+        for (i, slot) in self.0.iter().enumerate() {
+            if i != cpu_id {
+                let _ = slot.compare_exchange(owner, NO_OWNER, Ordering::Relaxed, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// 每个 CPU 当前浮点寄存器中装载的是哪个任务的状态
+static FP_OWNER: OwnerTable = OwnerTable::new();
+
+/// 修改保存在上下文中的 sstatus.FS 字段
+pub fn set_fs(sstatus: &mut Sstatus, fs: FS) {
+    let bits = (sstatus.bits() & !SSTATUS_FS_MASK) | ((fs as usize) << SSTATUS_FS_SHIFT);
+    // Sstatus 内部只有一个 usize
+    *sstatus = unsafe { core::mem::transmute::<usize, Sstatus>(bits) };
+}
+
+/// 陷入内核后调用
+///
+/// `__alltraps` 在 FS 为 Dirty 时已经保存了浮点寄存器，这里只做统计并把
+/// 上下文中的状态降为 Clean，表示上下文与硬件寄存器一致。
+/// 返回本次陷入是否保存了浮点寄存器。
+pub fn on_trap_enter(cx: &mut TrapContext) -> bool {
+    if cx.sstatus.fs() != FS::Dirty {
+        return false;
+    }
+    FPU_SAVES.inc();
+    set_fs(&mut cx.sstatus, FS::Clean);
+    true
+}
+
+/// 返回用户态前调用
+///
+/// `owner` 为即将返回的任务的 pid。
+pub fn on_trap_return(owner: usize, cx: &mut TrapContext) {
+    let cpu_id = current_cpu_id();
+    // 内核在本次陷入期间改写过浮点寄存器，硬件中的内容已不属于任何任务
+    if sstatus::read().fs() == FS::Dirty {
+        FP_OWNER.clear(cpu_id);
+    }
+    if cx.sstatus.fs() != FS::Off && !FP_OWNER.owned_by(cpu_id, owner) {
+        set_fs(&mut cx.sstatus, FS::Off);
+    }
+}
+
+/// 处理 FS 为 Off 时的非法指令
+///
+/// 若上下文中 FS 为 Off，则认为这是任务首次使用浮点单元：装载它的浮点寄存器，
+/// 记录所有者并返回 `true`，调用者应直接重新执行该指令。否则返回 `false`，
+/// 表示这是一条真正的非法指令。
+pub fn try_claim(owner: usize, cx: &mut TrapContext) -> bool {
+    if cx.sstatus.fs() != FS::Off {
+        return false;
+    }
+    let cpu_id = current_cpu_id();
+    unsafe {
+        load_fp_regs(&cx.fp);
+        // 装载本身把硬件的 FS 置为了 Dirty，恢复为 Clean，否则本次返回用户态时
+        // on_trap_return 会误以为内核用过浮点寄存器而丢弃刚记下的所有者，
+        // 任务重新执行该指令时再次陷入，永远无法前进
+        sstatus::set_fs(FS::Clean);
+    }
+    FP_OWNER.claim(cpu_id, owner);
+    set_fs(&mut cx.sstatus, FS::Clean);
+    FPU_RESTORES.inc();
+    true
+}
+
+/// 丢弃硬件中的浮点状态，下一次使用时从上下文重新装载
+///
+/// 用于内核直接改写了上下文中浮点寄存器的场合（如 sigreturn、clone）。
+pub fn force_reload(cx: &mut TrapContext) {
+    if cx.sstatus.fs() != FS::Off {
+        set_fs(&mut cx.sstatus, FS::Off);
+    }
+}
+
+/// 从 `FloatRegs` 装载 f0-f31 与 fcsr
+unsafe fn load_fp_regs(fp: &FloatRegs) {
+    asm!(
+        "fld f0, 0*8({regs})",
+        "fld f1, 1*8({regs})",
+        "fld f2, 2*8({regs})",
+        "fld f3, 3*8({regs})",
+        "fld f4, 4*8({regs})",
+        "fld f5, 5*8({regs})",
+        "fld f6, 6*8({regs})",
+        "fld f7, 7*8({regs})",
+        "fld f8, 8*8({regs})",
+        "fld f9, 9*8({regs})",
+        "fld f10, 10*8({regs})",
+        "fld f11, 11*8({regs})",
+        "fld f12, 12*8({regs})",
+        "fld f13, 13*8({regs})",
+        "fld f14, 14*8({regs})",
+        "fld f15, 15*8({regs})",
+        "fld f16, 16*8({regs})",
+        "fld f17, 17*8({regs})",
+        "fld f18, 18*8({regs})",
+        "fld f19, 19*8({regs})",
+        "fld f20, 20*8({regs})",
+        "fld f21, 21*8({regs})",
+        "fld f22, 22*8({regs})",
+        "fld f23, 23*8({regs})",
+        "fld f24, 24*8({regs})",
+        "fld f25, 25*8({regs})",
+        "fld f26, 26*8({regs})",
+        "fld f27, 27*8({regs})",
+        "fld f28, 28*8({regs})",
+        "fld f29, 29*8({regs})",
+        "fld f30, 30*8({regs})",
+        "fld f31, 31*8({regs})",
+        "lwu {tmp}, 32*8({regs})",
+        "csrw fcsr, {tmp}",
+        regs = in(reg) fp as *const FloatRegs,
+        tmp = out(reg) _,
+        out("f0") _, out("f1") _, out("f2") _, out("f3") _,
+        out("f4") _, out("f5") _, out("f6") _, out("f7") _,
+        out("f8") _, out("f9") _, out("f10") _, out("f11") _,
+        out("f12") _, out("f13") _, out("f14") _, out("f15") _,
+        out("f16") _, out("f17") _, out("f18") _, out("f19") _,
+        out("f20") _, out("f21") _, out("f22") _, out("f23") _,
+        out("f24") _, out("f25") _, out("f26") _, out("f27") _,
+        out("f28") _, out("f29") _, out("f30") _, out("f31") _,
+        options(nostack),
+    );
+}
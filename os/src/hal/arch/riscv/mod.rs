@@ -1,10 +1,16 @@
 pub mod config;
+pub mod fpu;
 pub mod kern_stack;
+#[cfg(any(feature = "virtio_irq", feature = "uart_irq"))]
+pub mod plic;
 pub mod sbi;
 pub mod sv39;
 pub mod switch;
 pub mod time;
 pub mod trap;
+#[cfg(feature = "rvv")]
+pub mod vector;
+pub mod debug_trigger;
 
 #[cfg(feature = "board_rvqemu")]
 #[path = "../../platform/riscv/qemu.rs"]
@@ -18,7 +24,10 @@ pub mod rv_board;
 /// 设置陷入向量并启用时钟中断
 pub fn machine_init() {
     trap::init();
+    #[cfg(feature = "rvv")]
+    vector::init();
     trap::enable_timer_interrupt();
+    trap::enable_software_interrupt();
     set_next_trigger();
 }
 
@@ -32,10 +41,15 @@ pub fn ap_init() {
 /// 启用时钟中断
 pub fn ap_finish_init() {
     trap::enable_timer_interrupt();
+    trap::enable_software_interrupt();
+    #[cfg(any(feature = "virtio_irq", feature = "uart_irq"))]
+    plic::init_hart();
     set_next_trigger();
 }
 
+use core::sync::atomic::{AtomicBool, Ordering};
 use time::set_next_trigger;
+use crate::utils::telemetry::HART_SUSPENDS;
 
 pub use trap::context::MachineContext;
 
@@ -47,6 +61,35 @@ pub type ExceptionImpl = riscv::register::scause::Exception;
 
 pub fn bootstrap_init() {}
 
+/// SBI 实现不支持 HSM 挂起时退回 wfi
+static HART_SUSPEND_SUPPORTED: AtomicBool = AtomicBool::new(true);
+
+/// 空闲核等待中断
+///
+/// 优先使用 SBI HSM 保持型挂起，由时钟中断或 [`wake_cpu`] 发出的 IPI 唤醒。
+/// 调用时应关闭 sstatus.SIE，挂起由 sie 中使能的中断唤醒，返回后由调用者开中断处理。
+pub fn idle_wait() {
+    if HART_SUSPEND_SUPPORTED.load(Ordering::Relaxed) {
+        if sbi::hart_suspend(sbi::HART_SUSPEND_RETENTIVE, 0, 0) == 0 {
+            HART_SUSPENDS.inc();
+            return;
+        }
+        HART_SUSPEND_SUPPORTED.store(false, Ordering::Relaxed);
+        log::warn!("[idle] SBI HSM hart_suspend unavailable, falling back to wfi");
+    }
+    unsafe { riscv::asm::wfi() };
+}
+
+/// 向处于空闲等待的核发送 IPI
+pub fn wake_cpu(cpu_id: usize) {
+    sbi::send_ipi(1 << cpu_id, 0);
+}
+
+/// 清除本核挂起的 S 态软件中断
+pub fn clear_ipi() {
+    unsafe { core::arch::asm!("csrc sip, {}", in(reg) 1usize << 1) };
+}
+
 pub fn boot_entry_paddr(entry_vaddr: usize) -> usize {
     entry_vaddr & !0xffffffff00000000
 }
@@ -0,0 +1,156 @@
+//! PLIC（平台级中断控制器）
+//!
+//! 驱动用 `register_irq_handler` 登记中断源与处理函数：登记时设置中断源的优先级，
+//! 并在所有已上线核的 S 态上下文中使能它，之后上线的核在 `init_hart` 中按使能掩码使能。
+//! 外部中断到来时 claim 出中断源，调用登记的处理函数，处理完再 complete。
+//! 目前接入 virtio 块设备与网卡 (`virtio_irq`) 以及串口接收 (`uart_irq`) 的中断；
+//! 没有打开 `uart_irq` 时串口中断保持关闭，控制台使用 SBI 轮询。
+
+use crate::hal::config::MAX_CPU_NUM;
+use crate::task::processor::current_cpu_id;
+use crate::utils::InterruptGuard;
+use core::ptr::{read_volatile, write_volatile};
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use riscv::register::sie;
+use spin::RwLock;
+
+/// 设备树中没有 PLIC 时使用 QEMU virt 的地址
+const VIRT_PLIC: usize = 0xC00_0000;
+const PRIORITY_BASE: usize = 0x0;
+const PENDING_BASE: usize = 0x1000;
+const ENABLE_BASE: usize = 0x2000;
+const ENABLE_STRIDE: usize = 0x80;
+const CONTEXT_BASE: usize = 0x20_0000;
+const CONTEXT_STRIDE: usize = 0x1000;
+/// 支持的中断源数，QEMU virt 上的中断源编号小于 96
+pub const MAX_IRQ: usize = 128;
+
+/// 中断处理函数，在关中断的外部中断处理中调用
+pub type IrqHandler = fn();
+
+/// 各中断源的处理函数，中断源 0 保留
+static HANDLERS: RwLock<[Option<IrqHandler>; MAX_IRQ]> = RwLock::new([None; MAX_IRQ]);
+/// 已登记的中断源的使能掩码，每个核的 S 态上下文都按它使能
+static ENABLE_MASK: [AtomicU32; MAX_IRQ / 32] = [
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+];
+/// 已经调用过 `init_hart` 的核
+static ONLINE_HARTS: AtomicUsize = AtomicUsize::new(0);
+
+fn base() -> usize {
+    crate::hal::fdt::platform()
+        .and_then(|info| info.plic)
+        .map_or(VIRT_PLIC, |(base, _)| base)
+}
+
+/// 核 `hart` 的 S 态上下文，QEMU virt 上每个核的 M 态与 S 态上下文交替排列
+fn context(hart: usize) -> usize {
+    2 * hart + 1
+}
+
+fn reg(offset: usize) -> *mut u32 {
+    (base() + offset) as *mut u32
+}
+
+/// 核 `hart` 的 S 态上下文中包含中断源 `irq` 的使能寄存器
+fn enable_reg(hart: usize, irq: usize) -> *mut u32 {
+    reg(ENABLE_BASE + context(hart) * ENABLE_STRIDE + irq / 32 * 4)
+}
+
+/// claim/complete 寄存器
+fn claim_reg(hart: usize) -> *mut u32 {
+    reg(CONTEXT_BASE + context(hart) * CONTEXT_STRIDE + 4)
+}
+
+/// 在核 `hart` 上使能或屏蔽中断源 `irq`
+pub fn set_hart_enabled(hart: usize, irq: usize, enabled: bool) {
+    assert!(irq != 0 && irq < MAX_IRQ && hart < MAX_CPU_NUM);
+    let enable = enable_reg(hart, irq);
+    let bit = 1 << (irq % 32);
+    unsafe {
+        let mask = read_volatile(enable);
+        write_volatile(enable, if enabled { mask | bit } else { mask & !bit });
+    }
+}
+
+/// 登记中断源 `irq` 的处理函数，并在所有已上线的核上使能它；
+/// 已有处理函数时替换原来的
+pub fn register_irq_handler(irq: usize, handler: IrqHandler) {
+    assert!(irq != 0 && irq < MAX_IRQ, "invalid PLIC source {}", irq);
+    {
+        let _guard = InterruptGuard::new();
+        HANDLERS.write()[irq] = Some(handler);
+    }
+    unsafe { write_volatile(reg(PRIORITY_BASE + 4 * irq), 1) };
+    ENABLE_MASK[irq / 32].fetch_or(1 << (irq % 32), Ordering::SeqCst);
+    // 与 init_hart 的顺序相反：先公开掩码再读上线的核，两边至少有一方使能该中断源
+    let online = ONLINE_HARTS.load(Ordering::SeqCst);
+    for hart in (0..MAX_CPU_NUM).filter(|hart| online & 1 << hart != 0) {
+        set_hart_enabled(hart, irq, true);
+    }
+    unsafe { sie::set_sext() };
+}
+
+/// 注销中断源 `irq` 的处理函数，并在所有核上屏蔽它
+#[allow(unused)]
+pub fn unregister_irq_handler(irq: usize) {
+    assert!(irq != 0 && irq < MAX_IRQ, "invalid PLIC source {}", irq);
+    ENABLE_MASK[irq / 32].fetch_and(!(1 << (irq % 32)), Ordering::SeqCst);
+    let online = ONLINE_HARTS.load(Ordering::SeqCst);
+    for hart in (0..MAX_CPU_NUM).filter(|hart| online & 1 << hart != 0) {
+        set_hart_enabled(hart, irq, false);
+    }
+    unsafe { write_volatile(reg(PRIORITY_BASE + 4 * irq), 0) };
+    let _guard = InterruptGuard::new();
+    HANDLERS.write()[irq] = None;
+}
+
+/// 中断源 `irq` 是否在等待处理
+#[allow(unused)]
+pub fn is_pending(irq: usize) -> bool {
+    let pending = unsafe { read_volatile(reg(PENDING_BASE + irq / 32 * 4)) };
+    pending & 1 << (irq % 32) != 0
+}
+
+/// 在本核的 S 态上下文中按使能掩码使能已登记的中断源并打开外部中断
+pub fn init_hart() {
+    let hart = current_cpu_id();
+    ONLINE_HARTS.fetch_or(1 << hart, Ordering::SeqCst);
+    unsafe {
+        for (word, mask) in ENABLE_MASK.iter().enumerate() {
+            let enable = reg(ENABLE_BASE + context(hart) * ENABLE_STRIDE + word * 4);
+            write_volatile(enable, mask.load(Ordering::SeqCst));
+        }
+        // 阈值为 0：优先级大于 0 的中断源都可以送达
+        write_volatile(reg(CONTEXT_BASE + context(hart) * CONTEXT_STRIDE), 0);
+        sie::set_sext();
+    }
+}
+
+/// 取出本核一个待处理的中断源，没有时返回 `None`
+pub fn claim() -> Option<usize> {
+    match unsafe { read_volatile(claim_reg(current_cpu_id())) } {
+        0 => None,
+        irq => Some(irq as usize),
+    }
+}
+
+/// 通知 PLIC 中断源 `irq` 已处理完
+pub fn complete(irq: usize) {
+    unsafe { write_volatile(claim_reg(current_cpu_id()), irq as u32) };
+}
+
+/// 处理本核收到的外部中断，直到没有待处理的中断源
+pub fn handle_external() {
+    while let Some(irq) = claim() {
+        let handler = HANDLERS.read().get(irq).copied().flatten();
+        match handler {
+            Some(handler) => handler(),
+            None => log::warn!("[plic] no handler for source {}", irq),
+        }
+        complete(irq);
+    }
+}
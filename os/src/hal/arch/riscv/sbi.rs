@@ -43,10 +43,18 @@ pub fn console_getchar() -> usize {
 pub fn console_flush() {}
 
 pub fn shutdown() -> ! {
+    system_reset(SRST_TYPE_SHUTDOWN, SRST_REASON_NONE);
+    // SRST 不可用时退回到 legacy 接口
     sbi_call(SBI_SHUTDOWN, 0, 0, 0);
     panic!("It should shutdown!");
 }
 
+pub fn reboot() -> ! {
+    system_reset(SRST_TYPE_COLD_REBOOT, SRST_REASON_NONE);
+    // legacy 接口没有重启功能，只能关机
+    shutdown()
+}
+
 // ================= 新增：HSM 扩展 (用于多核启动) =================
 
 const SBI_EXT_HSM: usize = 0x48534D;
@@ -71,3 +79,106 @@ pub fn hart_start(hartid: usize, start_addr: usize, opaque: usize) -> usize {
     ret
 }
 
+// ================= HSM 挂起与 IPI 扩展 (用于空闲核省电) =================
+
+const SBI_FID_HART_SUSPEND: usize = 3;
+const SBI_EXT_IPI: usize = 0x735049;
+const SBI_FID_SEND_IPI: usize = 0;
+
+/// 默认的保持型挂起：寄存器与 CSR 状态不变，被中断唤醒后从 ecall 之后继续执行
+pub const HART_SUSPEND_RETENTIVE: usize = 0;
+
+/// 调用 SBI v0.2+ 扩展，返回 a0 中的错误码
+#[inline(always)]
+fn sbi_call_ext(eid: usize, fid: usize, arg0: usize, arg1: usize, arg2: usize) -> isize {
+    let mut ret: isize;
+    unsafe {
+        asm!(
+            "ecall",
+            inlateout("x10") arg0 => ret,
+            inlateout("x11") arg1 => _,
+            in("x12") arg2,
+            in("x16") fid,
+            in("x17") eid,
+        );
+    }
+    ret
+}
+
+/// 挂起当前核，直到有中断挂起 (sip & sie != 0)
+/// 对保持型挂起，`resume_addr` 与 `opaque` 会被忽略
+pub fn hart_suspend(suspend_type: usize, resume_addr: usize, opaque: usize) -> isize {
+    sbi_call_ext(SBI_EXT_HSM, SBI_FID_HART_SUSPEND, suspend_type, resume_addr, opaque)
+}
+
+/// 向 `hart_mask_base` 起、由 `hart_mask` 选中的核发送 S 态软件中断
+pub fn send_ipi(hart_mask: usize, hart_mask_base: usize) -> isize {
+    sbi_call_ext(SBI_EXT_IPI, SBI_FID_SEND_IPI, hart_mask, hart_mask_base, 0)
+}
+
+
+// ================= SRST 扩展 (关机与重启) =================
+
+const SBI_EXT_SRST: usize = 0x53525354;
+const SBI_FID_SYSTEM_RESET: usize = 0;
+
+pub const SRST_TYPE_SHUTDOWN: usize = 0;
+pub const SRST_TYPE_COLD_REBOOT: usize = 1;
+pub const SRST_TYPE_WARM_REBOOT: usize = 2;
+pub const SRST_REASON_NONE: usize = 0;
+pub const SRST_REASON_SYSTEM_FAILURE: usize = 1;
+
+/// 请求关机或重启，成功时不会返回；
+/// 返回值为 SBI 错误码 (如固件不支持 SRST 时的 SBI_ERR_NOT_SUPPORTED)
+pub fn system_reset(reset_type: usize, reason: usize) -> isize {
+    sbi_call_ext(SBI_EXT_SRST, SBI_FID_SYSTEM_RESET, reset_type, reason, 0)
+}
+
+// ================= DBTR 扩展 (硬件触发器，用于观察点) =================
+
+const SBI_EXT_DBTR: usize = 0x44425452;
+const SBI_FID_DBTR_NUM_TRIGGERS: usize = 0;
+const SBI_FID_DBTR_SET_SHMEM: usize = 1;
+const SBI_FID_DBTR_INSTALL_TRIGGERS: usize = 3;
+const SBI_FID_DBTR_UNINSTALL_TRIGGERS: usize = 5;
+
+/// 调用 SBI v0.2+ 扩展，返回 a0 中的错误码与 a1 中的返回值
+#[inline(always)]
+fn sbi_call_ext_value(eid: usize, fid: usize, arg0: usize, arg1: usize, arg2: usize) -> (isize, usize) {
+    let (error, value);
+    unsafe {
+        asm!(
+            "ecall",
+            inlateout("x10") arg0 => error,
+            inlateout("x11") arg1 => value,
+            in("x12") arg2,
+            in("x16") fid,
+            in("x17") eid,
+        );
+    }
+    (error, value)
+}
+
+/// 可以按 `tdata1` 配置的触发器个数，固件不支持 DBTR 时为 0
+pub fn debug_num_triggers(tdata1: usize) -> usize {
+    match sbi_call_ext_value(SBI_EXT_DBTR, SBI_FID_DBTR_NUM_TRIGGERS, tdata1, 0, 0) {
+        (0, count) => count,
+        _ => 0,
+    }
+}
+
+/// 设置本核与固件交换触发器参数的共享内存，`shmem_pa` 为物理地址
+pub fn debug_set_shmem(shmem_pa: usize) -> isize {
+    sbi_call_ext(SBI_EXT_DBTR, SBI_FID_DBTR_SET_SHMEM, shmem_pa, 0, 0)
+}
+
+/// 安装共享内存中的 `count` 个触发器 (每项为 tstate/tdata1/tdata2/tdata3)，
+/// 成功后每项的第一个字被改写为分配到的触发器编号
+pub fn debug_install_triggers(count: usize) -> isize {
+    sbi_call_ext(SBI_EXT_DBTR, SBI_FID_DBTR_INSTALL_TRIGGERS, count, 0, 0)
+}
+
+/// 卸载从 `index_base` 起、由 `index_mask` 选中的触发器
+pub fn debug_uninstall_triggers(index_base: usize, index_mask: usize) -> isize {
+    sbi_call_ext(SBI_EXT_DBTR, SBI_FID_DBTR_UNINSTALL_TRIGGERS, index_base, index_mask, 0)
+}
@@ -39,6 +39,8 @@ pub struct Sv39PageTableEntry {
 
 impl Sv39PageTableEntry {
     const PPN_MASK: usize = ((1usize << 44) - 1) << 10;
+    /// RSW 中的软件位，标记 MADV_FREE 后未被写过的页
+    const FREEABLE: usize = 1 << 8;
     pub fn new(ppn: PhysPageNum, flags: PTEFlags) -> Self {
         Sv39PageTableEntry {
             bits: ppn.0 << 10 | flags.bits as usize,
@@ -74,6 +76,16 @@ impl Sv39PageTableEntry {
     pub fn clear_dirty(&mut self) {
         self.bits &= !(PTEFlags::D.bits() as usize);
     }
+    pub fn is_freeable(&self) -> bool {
+        self.bits & Self::FREEABLE != 0
+    }
+    pub fn set_freeable(&mut self, freeable: bool) {
+        if freeable {
+            self.bits |= Self::FREEABLE;
+        } else {
+            self.bits &= !Self::FREEABLE;
+        }
+    }
     pub fn revoke_read(&mut self) {
         self.bits &= !(PTEFlags::R.bits() as usize);
     }
@@ -326,6 +338,14 @@ impl PageTable for Sv39PageTable {
             Err(())
         }
     }
+    fn set_freeable(&mut self, vpn: VirtPageNum, freeable: bool) -> Result<(), ()> {
+        if let Some(pte) = self.find_pte_refmut(vpn) {
+            pte.set_freeable(freeable);
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
     fn activate(&self) {
         // TODO:
         let satp = self.token();
@@ -341,6 +361,9 @@ impl PageTable for Sv39PageTable {
     fn is_dirty(&self, vpn: VirtPageNum) -> Option<bool> {
         self.find_pte(vpn).map(|pte| pte.is_dirty())
     }
+    fn is_freeable(&self, vpn: VirtPageNum) -> Option<bool> {
+        self.find_pte(vpn).map(|pte| pte.is_freeable())
+    }
     fn readable(&self, vpn: VirtPageNum) -> Option<bool> {
         self.find_pte(vpn).map(|pte| pte.readable())
     }
@@ -1,5 +1,10 @@
-use riscv::register::sstatus::{self, set_spp, Sstatus, SPP};
+use riscv::register::sstatus::{self, set_spp, Sstatus, FS, SPP};
 
+use super::super::fpu::{self, set_fs};
+#[cfg(feature = "rvv")]
+use super::super::vector::{self, VectorRegs};
+
+use crate::hal::TrapContextOps;
 use crate::task::{SignalStack, Signals};
 
 /// General registers
@@ -88,12 +93,22 @@ pub struct TrapContext {
     /// The current sp to be recovered on next entry into kernel space.
     pub kernel_sp: usize,
     pub kernel_tp: usize,
+    /// Vector registers, placed last so the offsets used by trap.S stay unchanged
+    #[cfg(feature = "rvv")]
+    pub vec: VectorRegs,
 }
 
 impl TrapContext {
     pub fn set_sp(&mut self, sp: usize) {
         self.gp.sp = sp;
     }
+    /// Must be called after the kernel rewrites `fp`/`vec` directly (sigreturn, clone),
+    /// so that the registers are reloaded from this context on next use.
+    pub fn invalidate_fp(&mut self) {
+        fpu::force_reload(self);
+        #[cfg(feature = "rvv")]
+        vector::force_reload(self);
+    }
     pub fn app_init_context(
         entry: usize,
         sp: usize,
@@ -107,28 +122,52 @@ impl TrapContext {
         }
         // Re-read sstatus after modification
         let mut sstatus_after = sstatus::read();
-        
-        // Lazy FPU: Disable FPU by default (set FS=Off)
-        // This will cause IllegalInstruction trap on first FPU use,
-        // which then enables FPU lazily
-        // NOTE: We keep FPU enabled for now to avoid breaking existing code
-        // To fully enable Lazy FPU, uncomment the following:
-        // let sstatus_bits = unsafe { core::mem::transmute::<_, usize>(sstatus_after) };
-        // let sstatus_fpu_off = sstatus_bits & !0x6000; // Clear FS bits (set to Off)
-        // sstatus_after = unsafe { core::mem::transmute::<_, Sstatus>(sstatus_fpu_off) };
-        
+        // Lazy FPU: start with FS=Off, the first FP instruction traps and
+        // loads the (zeroed) FP registers, see `fpu::try_claim`
+        set_fs(&mut sstatus_after, FS::Off);
+        #[cfg(feature = "rvv")]
+        vector::force_reload_status(&mut sstatus_after);
+
         let mut cx = Self {
             gp: GeneralRegs::default(),
             fp: FloatRegs::default(),
             origin_a0: 0,
-            sstatus: sstatus_after, // Use the modified value!
+            sstatus: sstatus_after,
             kernel_satp,
             trap_handler,
             kernel_sp,
             kernel_tp: 0,
+            #[cfg(feature = "rvv")]
+            vec: VectorRegs::new(),
         };
         cx.gp.pc = entry;
         cx.set_sp(sp);
         cx
     }
 }
+
+impl TrapContextOps for TrapContext {
+    fn syscall_nr(&self) -> usize {
+        self.gp.a7
+    }
+    fn syscall_args(&self) -> [usize; 6] {
+        [
+            self.gp.a0, self.gp.a1, self.gp.a2, self.gp.a3, self.gp.a4, self.gp.a5,
+        ]
+    }
+    fn origin_arg0(&self) -> usize {
+        self.origin_a0
+    }
+    fn return_value(&self) -> usize {
+        self.gp.a0
+    }
+    fn set_return(&mut self, ret: usize) {
+        self.gp.a0 = ret;
+    }
+    fn pc(&self) -> usize {
+        self.gp.pc
+    }
+    fn set_pc(&mut self, pc: usize) {
+        self.gp.pc = pc;
+    }
+}
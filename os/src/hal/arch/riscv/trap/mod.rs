@@ -1,14 +1,21 @@
 pub mod context;
 use core::arch::{asm, global_asm};
 
+use super::fpu;
+#[cfg(feature = "rvv")]
+use super::vector;
+use super::debug_trigger;
 use super::TrapImpl;
 use crate::config::TRAMPOLINE;
+use crate::hal::TrapContextOps;
 use crate::hal::arch::riscv::time::set_next_trigger;
 use crate::mm::{frame_reserve, MemoryError, VirtAddr};
 use crate::syscall::syscall;
+use crate::utils::telemetry::heartbeat;
+use crate::task::task::TaskControlBlockInner;
 use crate::task::{
-    current_task, do_signal, do_wake_expired, run_tasks, suspend_current_and_run_next,
-    Signals,
+    current_task, do_signal, do_wake_expired, preempt_current_and_run_next, resched_if_needed,
+    run_tasks, task_tick, Signals,
 };
 pub use context::UserContext;
 use riscv::register::{
@@ -19,6 +26,11 @@ use riscv::register::{
 
 pub static mut TIMER_INTERRUPT: usize = 0;
 
+/// 最近一次陷入时的指令地址
+pub fn get_bad_ins_addr() -> usize {
+    sepc::read()
+}
+
 pub fn get_bad_addr() -> usize {
     stval::read()
 }
@@ -68,6 +80,12 @@ pub fn enable_timer_interrupt() {
         sie::set_stimer();
     }
 }
+/// 用于唤醒空闲核的 IPI
+pub fn enable_software_interrupt() {
+    unsafe {
+        sie::set_ssoft();
+    }
+}
 
 #[no_mangle]
 pub fn trap_handler() -> ! {
@@ -89,6 +107,9 @@ pub fn trap_handler() -> ! {
     if let Some(task) = current_task() {
         let mut inner = task.acquire_inner_lock();
         inner.update_process_times_enter_trap();
+        inner.fpu_saves += fpu::on_trap_enter(inner.get_trap_cx()) as usize;
+        #[cfg(feature = "rvv")]
+        vector::on_trap_enter(inner.get_trap_cx());
     }
 
     let scause = scause::read();
@@ -102,11 +123,8 @@ pub fn trap_handler() -> ! {
             let (syscall_id, args) = if let Some(task) = current_task() {
                 let mut inner = task.acquire_inner_lock();
                 let cx = inner.get_trap_cx();
-                cx.gp.pc += 4; // 跳过 ecall 指令
-                (
-                    cx.gp.a7,
-                    [cx.gp.a0, cx.gp.a1, cx.gp.a2, cx.gp.a3, cx.gp.a4, cx.gp.a5],
-                )
+                cx.advance_pc(); // 跳过 ecall 指令
+                (cx.syscall_nr(), cx.syscall_args())
             } else {
                  // 之前添加的 Panic 调试信息
                  let raw_tp: usize;
@@ -125,8 +143,8 @@ pub fn trap_handler() -> ! {
             // 重新获取任务上下文写入返回值
             if let Some(task) = current_task() {
                 let mut inner = task.acquire_inner_lock();
-                let cx = inner.get_trap_cx();
-                cx.gp.a0 = result as usize;
+                inner.get_trap_cx().set_return(result as usize);
+                inner.in_syscall = true;
             }
         }
         Trap::Exception(Exception::StoreFault)
@@ -169,11 +187,34 @@ pub fn trap_handler() -> ! {
         Trap::Exception(Exception::IllegalInstruction) => {
             if let Some(task) = current_task() {
                 let mut inner = task.acquire_inner_lock();
-                inner.add_signal(Signals::SIGILL);
+                // FS/VS=Off 时的首次浮点/向量指令：装载对应上下文后重新执行该指令
+                #[cfg(feature = "rvv")]
+                let claimed = if vector::is_vector_insn(stval) {
+                    vector::try_claim(task.pid.0, inner.get_trap_cx())
+                } else {
+                    claim_fpu(task.pid.0, &mut inner)
+                };
+                #[cfg(not(feature = "rvv"))]
+                let claimed = claim_fpu(task.pid.0, &mut inner);
+                if !claimed {
+                    inner.add_signal(Signals::SIGILL);
+                }
             } else {
                  panic!("IllegalInstruction in Idle!");
             }
         }
+        Trap::Exception(Exception::Breakpoint) => {
+            if let Some(task) = current_task() {
+                // 观察点命中、单步或用户程序执行了 ebreak
+                let pc = task.acquire_inner_lock().get_trap_cx().gp.pc;
+                if let Some(addr) = debug_trigger::on_breakpoint(task.pid.0, pc) {
+                    log::debug!("[watchpoint] pid {} writing to {:#x}", task.pid.0, addr);
+                }
+                task.acquire_inner_lock().add_signal(Signals::SIGTRAP);
+            } else {
+                panic!("Breakpoint in Idle!");
+            }
+        }
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
 
             if unsafe { TIMER_INTERRUPT } % 100 == 0 {
@@ -181,15 +222,22 @@ pub fn trap_handler() -> ! {
             }
 
             do_wake_expired();
+            #[cfg(feature = "load_balance")]
+            crate::task::load_balance_tick();
+            heartbeat();
+            // 从用户态进入时才检查控制台的 Ctrl-C 与 Ctrl-Z
+            crate::fs::dev::tty::poll_console_signal();
             crate::fs::dev::interrupts::Interrupts::increment_interrupt_count(5);
+            crate::utils::random::add_interrupt_randomness(5);
             set_next_trigger();
             
             // 【关键修复】区分有任务和无任务(Idle)的情况
             if current_task().is_some() {
-                suspend_current_and_run_next();
+                // 时间片用完才会被抢占
+                task_tick();
                 // Debug: verify task is still current after resume
                 if current_task().is_none() {
-                    panic!("[trap_handler] current_task is None after suspend_current_and_run_next!");
+                    panic!("[trap_handler] current_task is None after task_tick!");
                 }
             } else {
                 // 如果是 Idle 状态，不要走 trap_return (那会尝试切回用户态并 panic)
@@ -197,12 +245,24 @@ pub fn trap_handler() -> ! {
                 run_tasks();
             }
         }
+        Trap::Interrupt(Interrupt::SupervisorSoft) => {
+            super::clear_ipi();
+            // 本核队列中有新任务，或唤醒的任务要求抢占，与时钟中断一样让出 CPU
+            if current_task().is_some() {
+                preempt_current_and_run_next();
+            } else {
+                run_tasks();
+            }
+        }
         Trap::Interrupt(Interrupt::SupervisorExternal) => {
             crate::fs::dev::interrupts::Interrupts::increment_interrupt_count(9);
+            crate::utils::random::add_interrupt_randomness(9);
+            #[cfg(any(feature = "virtio_irq", feature = "uart_irq"))]
+            super::plic::handle_external();
             
             // 【关键修复】同上
             if current_task().is_some() {
-                suspend_current_and_run_next();
+                preempt_current_and_run_next();
             } else {
                 run_tasks();
             }
@@ -216,6 +276,9 @@ pub fn trap_handler() -> ! {
         }
     }
 
+    // 本 CPU 唤醒了应抢占当前任务的任务
+    resched_if_needed();
+
     // 只有当有任务时，才执行从 Trap 返回到用户态的逻辑
     if let Some(task) = current_task() {
         let mut inner = task.acquire_inner_lock();
@@ -231,6 +294,13 @@ pub fn trap_handler() -> ! {
     }
 }
 
+/// 为 `owner` 装载浮点上下文（见 `fpu::try_claim`），并计入任务的装载次数
+fn claim_fpu(owner: usize, inner: &mut TaskControlBlockInner) -> bool {
+    let claimed = fpu::try_claim(owner, inner.get_trap_cx());
+    inner.fpu_restores += claimed as usize;
+    claimed
+}
+
 #[no_mangle]
 pub fn trap_return() -> ! {
     do_signal();
@@ -244,6 +314,18 @@ pub fn trap_return() -> ! {
     // 目标 CPU 的定时器可能已经在之前设置并 pending
     set_next_trigger();
     
+    // 惰性浮点/向量：本 CPU 的寄存器不属于该任务时关闭 FS/VS
+    let step_pc = {
+        let inner = task.acquire_inner_lock();
+        let trap_cx = inner.get_trap_cx();
+        fpu::on_trap_return(task.pid.0, trap_cx);
+        #[cfg(feature = "rvv")]
+        vector::on_trap_return(task.pid.0, trap_cx);
+        // SIGTRAP 被屏蔽时（如正在执行 SIGTRAP 的处理函数）暂停单步
+        (inner.single_step && !inner.sigmask.contains(Signals::SIGTRAP)).then_some(trap_cx.gp.pc)
+    };
+    debug_trigger::on_trap_return(task.pid.0, step_pc);
+
     let trap_cx_ptr = task.trap_cx_user_va();
     let user_satp = task.get_user_token();
     drop(task);
@@ -292,6 +374,9 @@ pub fn trap_from_kernel() {
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
             set_next_trigger();
             do_wake_expired(); 
+            #[cfg(feature = "load_balance")]
+            crate::task::load_balance_tick();
+            heartbeat();
 
             // === 【诊断代码】每 100 次时钟中断打印一个点 ===
             unsafe {
@@ -321,6 +406,10 @@ pub fn trap_from_kernel() {
             }
             */
         }
+        // 空闲核被 IPI 唤醒，清除后返回 run_tasks 重新取任务
+        Trap::Interrupt(Interrupt::SupervisorSoft) => {
+            super::clear_ipi();
+        }
         // 【修复】：添加对内核态外部中断的处理
         // 防止 UART 中断打断内核执行时导致 Panic
         Trap::Interrupt(Interrupt::SupervisorExternal) => {
@@ -331,6 +420,10 @@ pub fn trap_from_kernel() {
             
             // 简单的防 Panic 处理：
             crate::fs::dev::interrupts::Interrupts::increment_interrupt_count(9);
+            crate::utils::random::add_interrupt_randomness(9);
+            // 开启 virtio_irq 或 uart_irq 时设备中断经 PLIC 送达，空闲核在这里唤醒等待 I/O 或输入的任务
+            #[cfg(any(feature = "virtio_irq", feature = "uart_irq"))]
+            super::plic::handle_external();
             
             // 甚至可以选择让出 CPU（如果是在等待输入的循环中被中断）
             // if current_task().is_some() {
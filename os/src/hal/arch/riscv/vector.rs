@@ -0,0 +1,239 @@
+//! RISC-V 向量扩展 (V) 上下文
+//!
+//! 与 [`super::fpu`] 相同的惰性策略，只是状态位换成了 `sstatus.VS`：
+//! 任务以 `VS = Off` 返回用户态，首次执行向量指令时装载 v0-v31/vl/vtype，
+//! 陷入时只有 `VS == Dirty` 才写回 `TrapContext`。
+//!
+//! 启动时通过尝试打开 `sstatus.VS` 检测硬件是否支持 V 扩展，
+//! 不支持（或 VLEN 超出 [`VLENB_MAX`]）时所有向量指令都按非法指令处理。
+
+use core::arch::asm;
+use core::fmt::{self, Debug, Formatter};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use riscv::register::sstatus::Sstatus;
+
+use super::fpu::OwnerTable;
+use super::trap::context::TrapContext;
+use crate::task::processor::current_cpu_id;
+use crate::utils::telemetry::{VECTOR_RESTORES, VECTOR_SAVES};
+
+/// 支持的最大向量寄存器字节数 (VLEN = 512)
+pub const VLENB_MAX: usize = 64;
+
+const SSTATUS_VS_SHIFT: usize = 9;
+const SSTATUS_VS_MASK: usize = 0b11 << SSTATUS_VS_SHIFT;
+const VS_OFF: usize = 0;
+const VS_CLEAN: usize = 2;
+const VS_DIRTY: usize = 3;
+
+static HAS_VECTOR: AtomicBool = AtomicBool::new(false);
+static VLENB: AtomicUsize = AtomicUsize::new(0);
+static VEC_OWNER: OwnerTable = OwnerTable::new();
+
+/// 向量寄存器组
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct VectorRegs {
+    pub vstart: usize,
+    pub vl: usize,
+    pub vtype: usize,
+    pub vcsr: usize,
+    /// v0-v31，每个寄存器占 vlenb 字节，依次紧密排列
+    pub v: [u8; 32 * VLENB_MAX],
+}
+
+impl VectorRegs {
+    pub const fn new() -> Self {
+        Self {
+            vstart: 0,
+            vl: 0,
+            vtype: 0,
+            vcsr: 0,
+            v: [0; 32 * VLENB_MAX],
+        }
+    }
+}
+
+impl Debug for VectorRegs {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VectorRegs")
+            .field("vl", &self.vl)
+            .field("vtype", &format_args!("{:#x}", self.vtype))
+            .finish()
+    }
+}
+
+fn vs(sstatus: &Sstatus) -> usize {
+    (sstatus.bits() & SSTATUS_VS_MASK) >> SSTATUS_VS_SHIFT
+}
+
+fn set_vs(sstatus: &mut Sstatus, val: usize) {
+    let bits = (sstatus.bits() & !SSTATUS_VS_MASK) | (val << SSTATUS_VS_SHIFT);
+    // Sstatus 内部只有一个 usize
+    *sstatus = unsafe { core::mem::transmute::<usize, Sstatus>(bits) };
+}
+
+/// 检测 V 扩展
+///
+/// 不支持 V 扩展时 sstatus.VS 为只读的 0。
+pub fn init() {
+    let vlenb: usize;
+    let enabled: usize;
+    unsafe {
+        asm!(
+            "li {tmp}, {initial}",
+            "csrs sstatus, {tmp}",
+            "csrr {enabled}, sstatus",
+            "and {enabled}, {enabled}, {mask}",
+            tmp = out(reg) _,
+            enabled = out(reg) enabled,
+            initial = const 1 << SSTATUS_VS_SHIFT,
+            mask = in(reg) SSTATUS_VS_MASK,
+        );
+    }
+    if enabled == 0 {
+        println!("[kernel] vector extension not present");
+        return;
+    }
+    unsafe {
+        asm!(
+            ".option push",
+            ".option arch, +v",
+            "csrr {vlenb}, vlenb",
+            ".option pop",
+            "li {tmp}, {mask}",
+            "csrc sstatus, {tmp}",
+            vlenb = out(reg) vlenb,
+            tmp = out(reg) _,
+            mask = const SSTATUS_VS_MASK,
+        );
+    }
+    if vlenb > VLENB_MAX {
+        println!("[kernel] VLEN {} exceeds {}, vector extension disabled", vlenb * 8, VLENB_MAX * 8);
+        return;
+    }
+    VLENB.store(vlenb, Ordering::Relaxed);
+    HAS_VECTOR.store(true, Ordering::Relaxed);
+    println!("[kernel] vector extension enabled, VLEN = {}", vlenb * 8);
+}
+
+/// 判断触发非法指令异常的指令是否为向量指令
+///
+/// 包括 OP-V、向量访存（与浮点访存共用操作码，由 width 字段区分）以及对向量 CSR 的访问。
+pub fn is_vector_insn(insn: usize) -> bool {
+    let opcode = insn & 0x7f;
+    let funct3 = (insn >> 12) & 0x7;
+    match opcode {
+        // OP-V
+        0x57 => true,
+        // LOAD-FP / STORE-FP: width 为 1/2/3/4 时是标量浮点访存
+        0x07 | 0x27 => !(1..=4).contains(&funct3),
+        // SYSTEM: csrrw/csrrs/csrrc 及其立即数形式
+        0x73 if funct3 != 0 && funct3 != 4 => {
+            matches!(insn >> 20, 0x008 | 0x009 | 0x00a | 0x00f | 0xc20 | 0xc21 | 0xc22)
+        }
+        _ => false,
+    }
+}
+
+/// 陷入内核后调用，`VS == Dirty` 时保存向量寄存器
+pub fn on_trap_enter(cx: &mut TrapContext) {
+    if vs(&cx.sstatus) == VS_DIRTY {
+        unsafe { save_vector_regs(&mut cx.vec, VLENB.load(Ordering::Relaxed)) };
+        VECTOR_SAVES.inc();
+        set_vs(&mut cx.sstatus, VS_CLEAN);
+    }
+}
+
+/// 返回用户态前调用，本 CPU 的向量寄存器不属于该任务时关闭 VS
+pub fn on_trap_return(owner: usize, cx: &mut TrapContext) {
+    if vs(&cx.sstatus) != VS_OFF && !VEC_OWNER.owned_by(current_cpu_id(), owner) {
+        set_vs(&mut cx.sstatus, VS_OFF);
+    }
+}
+
+/// 处理 VS 为 Off 时的向量指令，语义同 [`super::fpu::try_claim`]
+pub fn try_claim(owner: usize, cx: &mut TrapContext) -> bool {
+    if !HAS_VECTOR.load(Ordering::Relaxed) || vs(&cx.sstatus) != VS_OFF {
+        return false;
+    }
+    // 装载期间需要打开 VS，返回用户态时 sstatus 会被上下文中的值覆盖
+    unsafe {
+        asm!("csrs sstatus, {tmp}", tmp = in(reg) SSTATUS_VS_MASK);
+        load_vector_regs(&cx.vec, VLENB.load(Ordering::Relaxed));
+    }
+    VEC_OWNER.claim(current_cpu_id(), owner);
+    set_vs(&mut cx.sstatus, VS_CLEAN);
+    VECTOR_RESTORES.inc();
+    true
+}
+
+/// 丢弃硬件中的向量状态，下一次使用时从上下文重新装载
+pub fn force_reload(cx: &mut TrapContext) {
+    force_reload_status(&mut cx.sstatus);
+}
+
+/// 同 [`force_reload`]，用于上下文尚未构造完成时
+pub fn force_reload_status(sstatus: &mut Sstatus) {
+    set_vs(sstatus, VS_OFF);
+}
+
+/// 保存 v0-v31 及相关 CSR，调用时 sstatus.VS 必须处于开启状态
+unsafe fn save_vector_regs(vec: &mut VectorRegs, vlenb: usize) {
+    asm!(
+        ".option push",
+        ".option arch, +v",
+        "csrr {tmp}, vstart",
+        "sd {tmp}, 0({base})",
+        "csrr {tmp}, vl",
+        "sd {tmp}, 8({base})",
+        "csrr {tmp}, vtype",
+        "sd {tmp}, 16({base})",
+        "csrr {tmp}, vcsr",
+        "sd {tmp}, 24({base})",
+        "addi {ptr}, {base}, 32",
+        "vs8r.v v0, ({ptr})",
+        "add {ptr}, {ptr}, {step}",
+        "vs8r.v v8, ({ptr})",
+        "add {ptr}, {ptr}, {step}",
+        "vs8r.v v16, ({ptr})",
+        "add {ptr}, {ptr}, {step}",
+        "vs8r.v v24, ({ptr})",
+        ".option pop",
+        base = in(reg) vec as *mut VectorRegs,
+        step = in(reg) vlenb * 8,
+        ptr = out(reg) _,
+        tmp = out(reg) _,
+        options(nostack),
+    );
+}
+
+/// 装载 v0-v31 并恢复 vl/vtype/vstart/vcsr
+unsafe fn load_vector_regs(vec: &VectorRegs, vlenb: usize) {
+    asm!(
+        ".option push",
+        ".option arch, +v",
+        "addi {ptr}, {base}, 32",
+        "vl8re8.v v0, ({ptr})",
+        "add {ptr}, {ptr}, {step}",
+        "vl8re8.v v8, ({ptr})",
+        "add {ptr}, {ptr}, {step}",
+        "vl8re8.v v16, ({ptr})",
+        "add {ptr}, {ptr}, {step}",
+        "vl8re8.v v24, ({ptr})",
+        "ld {tmp}, 8({base})",
+        "ld {ptr}, 16({base})",
+        "vsetvl x0, {tmp}, {ptr}",
+        "ld {tmp}, 0({base})",
+        "csrw vstart, {tmp}",
+        "ld {tmp}, 24({base})",
+        "csrw vcsr, {tmp}",
+        ".option pop",
+        base = in(reg) vec as *const VectorRegs,
+        step = in(reg) vlenb * 8,
+        ptr = out(reg) _,
+        tmp = out(reg) _,
+        options(nostack),
+    );
+}
@@ -0,0 +1,412 @@
+//! Flattened device tree (FDT) parsing
+//!
+//! The bootloader passes the physical address of a DTB in `a1` on RISC-V.
+//! We only need a handful of facts from it (RAM range, UART, PLIC, virtio-mmio slots),
+//! so this is a small allocation-free walker over the structure block rather than a
+//! full device tree library. It runs before the heap is set up, and before the frame
+//! allocator may hand out the pages holding the blob.
+//!
+//! If no DTB is passed (or it is malformed), callers fall back to the board constants
+//! in `hal::platform`.
+
+use crate::hal::MMIO;
+use core::fmt::{self, Debug, Formatter};
+use spin::Mutex;
+
+pub const FDT_MAGIC: u32 = 0xd00d_feed;
+
+pub const FDT_BEGIN_NODE: u32 = 0x1;
+pub const FDT_END_NODE: u32 = 0x2;
+pub const FDT_PROP: u32 = 0x3;
+const FDT_NOP: u32 = 0x4;
+pub const FDT_END: u32 = 0x9;
+
+/// Deepest node nesting we track; real device trees rarely exceed 5
+const MAX_DEPTH: usize = 16;
+/// Refuse blobs larger than this, a sane DTB is a few KiB
+const MAX_TOTAL_SIZE: usize = 0x20_0000;
+/// Maximum number of virtio-mmio slots remembered
+pub const MAX_VIRTIO_MMIO: usize = 8;
+/// Longer `/chosen/bootargs` are truncated
+pub const MAX_BOOTARGS: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FdtError {
+    BadMagic,
+    BadVersion,
+    Truncated,
+    BadStructure,
+}
+
+fn be32(data: &[u8], off: usize) -> Result<u32, FdtError> {
+    data.get(off..off + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or(FdtError::Truncated)
+}
+
+fn cstr(data: &[u8]) -> &[u8] {
+    match data.iter().position(|&c| c == 0) {
+        Some(len) => &data[..len],
+        None => data,
+    }
+}
+
+/// A parsed DTB header plus the blob it describes
+pub struct Fdt<'a> {
+    data: &'a [u8],
+    struct_off: usize,
+    struct_size: usize,
+    strings_off: usize,
+    strings_size: usize,
+}
+
+impl<'a> Fdt<'a> {
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self, FdtError> {
+        if be32(data, 0)? != FDT_MAGIC {
+            return Err(FdtError::BadMagic);
+        }
+        let total_size = be32(data, 4)? as usize;
+        if total_size > data.len() {
+            return Err(FdtError::Truncated);
+        }
+        // last_comp_version: the structure block layout has been stable since v16
+        if be32(data, 24)? > 17 {
+            return Err(FdtError::BadVersion);
+        }
+        let fdt = Self {
+            data: &data[..total_size],
+            struct_off: be32(data, 8)? as usize,
+            strings_off: be32(data, 12)? as usize,
+            strings_size: be32(data, 32)? as usize,
+            struct_size: be32(data, 36)? as usize,
+        };
+        if fdt.struct_off + fdt.struct_size > total_size
+            || fdt.strings_off + fdt.strings_size > total_size
+        {
+            return Err(FdtError::Truncated);
+        }
+        Ok(fdt)
+    }
+
+    /// # Safety
+    /// `addr` must be 0 or point to readable memory holding a DTB.
+    pub unsafe fn from_ptr(addr: usize) -> Result<Self, FdtError> {
+        if addr == 0 || addr % 4 != 0 {
+            return Err(FdtError::BadMagic);
+        }
+        let header = core::slice::from_raw_parts(addr as *const u8, 8);
+        if be32(header, 0)? != FDT_MAGIC {
+            return Err(FdtError::BadMagic);
+        }
+        let total_size = be32(header, 4)? as usize;
+        if total_size > MAX_TOTAL_SIZE {
+            return Err(FdtError::Truncated);
+        }
+        Self::from_bytes(core::slice::from_raw_parts(addr as *const u8, total_size))
+    }
+
+    pub fn total_size(&self) -> usize {
+        self.data.len()
+    }
+
+    fn prop_name(&self, nameoff: usize) -> Result<&'a [u8], FdtError> {
+        if nameoff >= self.strings_size {
+            return Err(FdtError::BadStructure);
+        }
+        let start = self.strings_off + nameoff;
+        Ok(cstr(
+            &self.data[start..self.strings_off + self.strings_size],
+        ))
+    }
+
+    /// Visit every node once all of its properties have been seen.
+    ///
+    /// Children are visited before their parent.
+    pub fn for_each_node(&self, mut f: impl FnMut(&Node<'a>)) -> Result<(), FdtError> {
+        let block = &self.data[self.struct_off..self.struct_off + self.struct_size];
+        let mut stack: [Node<'a>; MAX_DEPTH] = [Node::EMPTY; MAX_DEPTH];
+        let mut depth = 0;
+        let mut off = 0;
+        loop {
+            let token = be32(block, off)?;
+            off += 4;
+            match token {
+                FDT_BEGIN_NODE => {
+                    if depth == MAX_DEPTH {
+                        return Err(FdtError::BadStructure);
+                    }
+                    let name = cstr(block.get(off..).ok_or(FdtError::Truncated)?);
+                    off = (off + name.len() + 1).next_multiple_of(4);
+                    // #address-cells/#size-cells describe the children's `reg`
+                    let (addr_cells, size_cells) = match depth {
+                        0 => (2, 1),
+                        _ => (
+                            stack[depth - 1].child_addr_cells,
+                            stack[depth - 1].child_size_cells,
+                        ),
+                    };
+                    stack[depth] = Node {
+                        name,
+                        addr_cells,
+                        size_cells,
+                        ..Node::EMPTY
+                    };
+                    depth += 1;
+                }
+                FDT_END_NODE => {
+                    if depth == 0 {
+                        return Err(FdtError::BadStructure);
+                    }
+                    depth -= 1;
+                    f(&stack[depth]);
+                }
+                FDT_PROP => {
+                    if depth == 0 {
+                        return Err(FdtError::BadStructure);
+                    }
+                    let len = be32(block, off)? as usize;
+                    let name = self.prop_name(be32(block, off + 4)? as usize)?;
+                    let value = block
+                        .get(off + 8..off + 8 + len)
+                        .ok_or(FdtError::Truncated)?;
+                    off = (off + 8 + len).next_multiple_of(4);
+                    let node = &mut stack[depth - 1];
+                    match name {
+                        b"compatible" => node.compatible = value,
+                        b"reg" => node.reg = value,
+                        b"device_type" => node.device_type = cstr(value),
+                        b"bootargs" => node.bootargs = cstr(value),
+                        b"linux,initrd-start" => node.initrd_start = value,
+                        b"linux,initrd-end" => node.initrd_end = value,
+                        b"#address-cells" => node.child_addr_cells = be32(value, 0)?,
+                        b"#size-cells" => node.child_size_cells = be32(value, 0)?,
+                        _ => {}
+                    }
+                }
+                FDT_NOP => {}
+                FDT_END => {
+                    return if depth == 0 {
+                        Ok(())
+                    } else {
+                        Err(FdtError::BadStructure)
+                    }
+                }
+                _ => return Err(FdtError::BadStructure),
+            }
+        }
+    }
+}
+
+/// The properties of a node that the kernel cares about
+#[derive(Clone, Copy)]
+pub struct Node<'a> {
+    pub name: &'a [u8],
+    compatible: &'a [u8],
+    reg: &'a [u8],
+    device_type: &'a [u8],
+    bootargs: &'a [u8],
+    initrd_start: &'a [u8],
+    initrd_end: &'a [u8],
+    addr_cells: u32,
+    size_cells: u32,
+    child_addr_cells: u32,
+    child_size_cells: u32,
+}
+
+impl<'a> Node<'a> {
+    const EMPTY: Self = Self {
+        name: b"",
+        compatible: b"",
+        reg: b"",
+        device_type: b"",
+        bootargs: b"",
+        initrd_start: b"",
+        initrd_end: b"",
+        addr_cells: 2,
+        size_cells: 1,
+        child_addr_cells: 2,
+        child_size_cells: 1,
+    };
+
+    /// `compatible` is a list of NUL-terminated strings
+    pub fn is_compatible(&self, model: &str) -> bool {
+        self.compatible
+            .split(|&c| c == 0)
+            .any(|s| s == model.as_bytes())
+    }
+
+    pub fn is_device_type(&self, ty: &str) -> bool {
+        self.device_type == ty.as_bytes()
+    }
+
+    /// Node name without the `@unit-address` suffix
+    pub fn base_name(&self) -> &'a [u8] {
+        self.name.split(|&c| c == b'@').next().unwrap_or(self.name)
+    }
+
+    /// `(address, size)` pairs of the `reg` property
+    pub fn reg(&self) -> impl Iterator<Item = (usize, usize)> + 'a {
+        let (ac, sc) = (self.addr_cells as usize, self.size_cells as usize);
+        let stride = (ac + sc) * 4;
+        let read_cells = |cells: &[u8]| {
+            cells.chunks_exact(4).fold(0usize, |acc, c| {
+                (acc << 32) | u32::from_be_bytes([c[0], c[1], c[2], c[3]]) as usize
+            })
+        };
+        self.reg
+            .chunks_exact(if stride == 0 { 4 } else { stride })
+            .filter(move |_| stride != 0 && ac <= 2 && sc <= 2)
+            .map(move |entry| (read_cells(&entry[..ac * 4]), read_cells(&entry[ac * 4..])))
+    }
+
+    fn first_reg(&self) -> Option<(usize, usize)> {
+        self.reg().next()
+    }
+
+    /// `[start, end)` from `linux,initrd-start`/`linux,initrd-end`, either may be 32 or 64 bits
+    fn initrd(&self) -> Option<(usize, usize)> {
+        let read = |value: &[u8]| match value.len() {
+            4 => be32(value, 0).ok().map(|v| v as usize),
+            8 => Some(((be32(value, 0).ok()? as usize) << 32) | be32(value, 4).ok()? as usize),
+            _ => None,
+        };
+        let (start, end) = (read(self.initrd_start)?, read(self.initrd_end)?);
+        (start < end).then_some((start, end))
+    }
+}
+
+/// Facts about the machine discovered from the device tree
+#[derive(Clone, Copy)]
+pub struct PlatformInfo {
+    /// `(base, size)` of the first RAM bank
+    pub memory: Option<(usize, usize)>,
+    pub uart: Option<(usize, usize)>,
+    pub plic: Option<(usize, usize)>,
+    /// Goldfish RTC
+    pub rtc: Option<(usize, usize)>,
+    /// `[start, end)` of the initramfs loaded by the bootloader
+    pub initrd: Option<(usize, usize)>,
+    virtio: [(usize, usize); MAX_VIRTIO_MMIO],
+    virtio_num: usize,
+    bootargs: [u8; MAX_BOOTARGS],
+    bootargs_len: usize,
+}
+
+impl PlatformInfo {
+    pub const fn empty() -> Self {
+        Self {
+            memory: None,
+            uart: None,
+            plic: None,
+            rtc: None,
+            initrd: None,
+            virtio: [(0, 0); MAX_VIRTIO_MMIO],
+            virtio_num: 0,
+            bootargs: [0; MAX_BOOTARGS],
+            bootargs_len: 0,
+        }
+    }
+
+    pub fn from_fdt(fdt: &Fdt) -> Result<Self, FdtError> {
+        let mut info = Self::empty();
+        fdt.for_each_node(|node| {
+            if info.memory.is_none()
+                && (node.is_device_type("memory") || node.base_name() == b"memory")
+            {
+                info.memory = node.first_reg().filter(|&(_, size)| size != 0);
+            } else if info.uart.is_none()
+                && (node.is_compatible("ns16550a") || node.is_compatible("snps,dw-apb-uart"))
+            {
+                info.uart = node.first_reg();
+            } else if info.plic.is_none()
+                && (node.is_compatible("riscv,plic0") || node.is_compatible("sifive,plic-1.0.0"))
+            {
+                info.plic = node.first_reg();
+            } else if info.rtc.is_none() && node.is_compatible("google,goldfish-rtc") {
+                info.rtc = node.first_reg();
+            } else if node.is_compatible("virtio,mmio") && info.virtio_num < MAX_VIRTIO_MMIO {
+                if let Some(reg) = node.first_reg() {
+                    info.virtio[info.virtio_num] = reg;
+                    info.virtio_num += 1;
+                }
+            } else if node.base_name() == b"chosen" {
+                let len = node.bootargs.len().min(MAX_BOOTARGS);
+                info.bootargs[..len].copy_from_slice(&node.bootargs[..len]);
+                info.bootargs_len = len;
+                info.initrd = node.initrd();
+            }
+        })?;
+        // children are visited first, keep the slots in address order
+        info.virtio[..info.virtio_num].sort_unstable();
+        Ok(info)
+    }
+
+    pub fn virtio_mmio(&self) -> &[(usize, usize)] {
+        &self.virtio[..self.virtio_num]
+    }
+
+    /// `/chosen/bootargs`, empty if absent or not valid UTF-8
+    pub fn bootargs(&self) -> &str {
+        core::str::from_utf8(&self.bootargs[..self.bootargs_len]).unwrap_or("")
+    }
+
+    /// Device regions the kernel must map, empty if nothing was discovered
+    pub fn mmio_regions(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.uart
+            .iter()
+            .chain(self.plic.iter())
+            .chain(self.rtc.iter())
+            .chain(self.virtio_mmio().iter())
+            .copied()
+    }
+}
+
+impl Debug for PlatformInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let region = |r: Option<(usize, usize)>| r.map(|(b, s)| (b as *const u8, s));
+        f.debug_struct("PlatformInfo")
+            .field("memory", &region(self.memory))
+            .field("uart", &region(self.uart))
+            .field("plic", &region(self.plic))
+            .field("rtc", &region(self.rtc))
+            .field("virtio_mmio", &self.virtio_num)
+            .field("initrd", &self.initrd.map(|(s, e)| (s as *const u8, e - s)))
+            .field("bootargs", &self.bootargs())
+            .finish()
+    }
+}
+
+/// `None` until [`init`] found a valid DTB.
+/// Kept in `.data` so that clearing `.bss` after parsing does not wipe it.
+#[link_section = ".data"]
+static PLATFORM: Mutex<Option<PlatformInfo>> = Mutex::new(None);
+
+/// Parse the DTB handed over by the bootloader, must run on the boot hart before
+/// `.bss` is cleared and before the frame allocator is initialized.
+pub fn init(dtb_pa: usize) -> Result<(), FdtError> {
+    let info = unsafe { PlatformInfo::from_fdt(&Fdt::from_ptr(dtb_pa)?)? };
+    *PLATFORM.lock() = Some(info);
+    Ok(())
+}
+
+/// Discovered platform, `None` means the board constants are in effect
+pub fn platform() -> Option<PlatformInfo> {
+    *PLATFORM.lock()
+}
+
+/// RAM `(base, size)` from the device tree
+pub fn memory_region() -> Option<(usize, usize)> {
+    platform().and_then(|info| info.memory)
+}
+
+/// Call `f` with every device region to be identity-mapped into the kernel space.
+///
+/// Falls back to the board's `MMIO` table when nothing was discovered.
+pub fn for_each_mmio_region(mut f: impl FnMut(usize, usize)) {
+    match platform() {
+        Some(info) if info.mmio_regions().next().is_some() => {
+            info.mmio_regions().for_each(|(base, size)| f(base, size))
+        }
+        _ => MMIO.iter().for_each(|&(base, size)| f(base, size)),
+    }
+}
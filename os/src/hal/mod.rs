@@ -1,12 +1,15 @@
 pub mod arch;
+pub mod fdt;
+mod trap_context;
+pub use trap_context::TrapContextOps;
 pub use arch::__switch;
 pub use arch::config;
 pub use arch::kstack_alloc;
-pub use arch::shutdown;
+pub use arch::{reboot, shutdown};
 pub use arch::tlb_invalidate;
 pub use arch::{bootstrap_init, machine_init};
 pub use arch::{console_flush, console_getchar, console_putchar};
-pub use arch::{get_bad_addr, get_bad_instruction, get_exception_cause};
+pub use arch::{get_bad_addr, get_bad_ins_addr, get_bad_instruction, get_exception_cause};
 pub use arch::{get_clock_freq, get_time};
 pub use arch::{trap_cx_bottom_from_tid, ustack_bottom_from_tid};
 pub use arch::{trap_handler, trap_return};
@@ -14,7 +17,8 @@ pub use arch::{
     KernelPageTableImpl, KernelStack, MachineContext, PageTableImpl, TrapContext, TrapImpl,
     UserContext,
 };
-pub use arch::{disable_interrupts, restore_interrupts};
+pub use arch::{disable_interrupts, idle_wait, restore_interrupts, wake_cpu};
+pub use arch::{clear_watchpoint, set_watchpoint, single_step_supported};
 #[cfg(feature = "riscv")]
 pub use arch::boot_entry_paddr;
 #[cfg(feature = "riscv")]
@@ -8,3 +8,4 @@ pub const BLOCK_SZ: usize = 4096;
 // warning: 不能移除“ + HIGH_BASE_EIGHT”，会导致开发板上地址错误
 pub const UART_BASE: usize = 0x1FE2_0000 + HIGH_BASE_EIGHT;
 pub const ACPI_BASE: usize = 0x1FE2_7000 + HIGH_BASE_EIGHT;
+pub const RTC_BASE: usize = 0x1FE0_7800 + HIGH_BASE_EIGHT;
@@ -8,3 +8,5 @@ pub const BLOCK_SZ: usize = 4096;
 // warning: 不能移除“ + HIGH_BASE_EIGHT”，会导致开发板上地址错误
 pub const UART_BASE: usize = 0x1fe001e0;
 pub const ACPI_BASE: usize = 0x1FE2_7000 + HIGH_BASE_EIGHT;
+// 7A 桥片的 RTC
+pub const RTC_BASE: usize = 0x100D_0100 + HIGH_BASE_EIGHT;
@@ -5,6 +5,7 @@ pub const MMIO: &[(usize, usize)] = &[
     (0x1000_0000, 0x1000),
     (0x1000_1000, 0x1000),
     (0xC00_0000, 0x40_0000),
+    (0x10_1000, 0x1000), // goldfish RTC
 ];
 
 // pub type BlockDeviceImpl = crate::drivers::block::VirtIOBlock;
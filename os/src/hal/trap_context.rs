@@ -0,0 +1,35 @@
+//! Architecture-neutral access to the user trap context
+//!
+//! RISC-V and LoongArch keep the syscall number, arguments and return value in
+//! differently named registers. Generic code (syscall dispatch, signal delivery)
+//! should go through [`TrapContextOps`] instead of touching `cx.gp.*` directly.
+
+/// Operations on a saved user context that every architecture provides
+pub trait TrapContextOps {
+    /// Length in bytes of the syscall instruction (`ecall` / `syscall`)
+    const SYSCALL_INSN_LEN: usize = 4;
+
+    /// Syscall number
+    fn syscall_nr(&self) -> usize;
+    /// The six syscall arguments
+    fn syscall_args(&self) -> [usize; 6];
+    /// First syscall argument as it was on entry, before being overwritten by the return value
+    fn origin_arg0(&self) -> usize;
+    /// Current value of the return register
+    fn return_value(&self) -> usize;
+    /// Write the return register
+    fn set_return(&mut self, ret: usize);
+    /// User pc to resume at
+    fn pc(&self) -> usize;
+    fn set_pc(&mut self, pc: usize);
+
+    /// Step over the syscall instruction so that the task resumes after it
+    fn advance_pc(&mut self) {
+        self.set_pc(self.pc() + Self::SYSCALL_INSN_LEN);
+    }
+    /// Undo a completed syscall so that it is issued again with its original arguments
+    fn restart_syscall(&mut self) {
+        self.set_pc(self.pc() - Self::SYSCALL_INSN_LEN);
+        self.set_return(self.origin_arg0());
+    }
+}
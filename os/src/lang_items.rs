@@ -1,25 +1,33 @@
-use crate::hal::shutdown;
+use crate::console::panic_print;
+use crate::hal::{reboot, shutdown};
 use core::panic::PanicInfo;
 
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
+    // 不能用 print!：本核或其他核可能正持有控制台锁
     if let Some(location) = info.location() {
-        print!(
+        panic_print(format_args!(
             "[kernel] panicked at {}:{}:{}: ",
             location.file(),
             location.line(),
             location.column()
-        );
+        ));
     } else {
-        print!("[kernel] panicked: ");
+        panic_print(format_args!("[kernel] panicked: "));
     }
 
     if let Some(message) = info.message() {
-        println!("{}", message);
+        panic_print(format_args!(concat!("{}", crate::newline!()), message));
     } else {
-        println!("(panic message)");
+        panic_print(format_args!(concat!("(panic message)", crate::newline!())));
     }
 
+    // 写入崩溃转储，重启后可从 /proc/crashdump 读出
+    crate::crashdump::save(info.location(), info.message());
+    if crate::cmdline::get().panic_reboot {
+        panic_print(format_args!(concat!("[kernel] rebooting", crate::newline!())));
+        reboot()
+    }
     shutdown()
 }
 
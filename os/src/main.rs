@@ -27,6 +27,8 @@ extern crate bitflags;
 
 #[macro_use]
 mod console;
+mod cmdline;
+mod crashdump;
 mod drivers;
 mod fs;
 mod hal;
@@ -34,6 +36,8 @@ mod lang_items;
 mod math;
 mod mm;
 mod net;
+#[cfg(feature = "selftest")]
+mod selftest;
 mod syscall;
 mod task;
 mod timer;
@@ -67,7 +71,8 @@ fn mem_clear() {
     unsafe {
         core::slice::from_raw_parts_mut(
             sbss as usize as *mut u8,
-            crate::config::MEMORY_END - sbss as usize,
+            // 保留上一次 panic 留下的崩溃转储
+            crate::crashdump::region().0 - sbss as usize,
         )
         .fill(0);
     }
@@ -131,7 +136,7 @@ extern "C" {
 use crate::hal::TrapContext;
 
 #[no_mangle]
-pub fn rust_main(hart_id: usize) -> ! {
+pub fn rust_main(hart_id: usize, dtb_pa: usize) -> ! {
     
     #[cfg(target_arch = "riscv64")]
     unsafe {
@@ -153,13 +158,20 @@ pub fn rust_main(hart_id: usize) -> ! {
     }
 
     // ⚠️ 注意：在锁初始化之前，尽量不要多核同时 Println，否则还是会乱。
-    // 这里我们先不打印，等 Console 初始化好后再打印。
+    // 这里只有 BSP 通过早期输出路径打印，AP 等 Console 初始化好后再打印。
 
     if is_bsp {
         // ==========================
         //       主核 (BSP) 逻辑
         // ==========================
         
+        // 此时 BSS 尚未清空、堆也未初始化，println 走无锁的早期输出路径
+        println!("[Boot] early console up, hart {}", hart_id);
+
+        // 解析引导程序通过 a1 传入的设备树，必须先于清空 BSS（zero_init 会连同 DTB 一起清零）
+        #[cfg(feature = "riscv")]
+        let fdt_result = hal::fdt::init(dtb_pa);
+
         // 清空 BSS (必须最先做，且只能做一次)
         mem_clear();
         
@@ -169,14 +181,29 @@ pub fn rust_main(hart_id: usize) -> ! {
         // 此时 Println 应该是安全的了
         println!("[kernel] Console initialized by BSP.");
         println!("[Boot] Hart {} is BSP, starting initialization...", hart_id);
+        #[cfg(feature = "riscv")]
+        match fdt_result {
+            Ok(()) => println!("[Boot] {:?} from DTB at {:#x}", hal::fdt::platform().unwrap(), dtb_pa),
+            Err(err) => println!("[Boot] no usable DTB at {:#x} ({:?}), using board constants", dtb_pa, err),
+        }
 
         bootstrap_init();
 
+        // 解析启动参数 (loglevel=/init=/ro/nosmp)
+        cmdline::init();
+
         #[cfg(all(feature = "block_mem"))]
         move_to_high_address();
 
         mm::init(); // 初始化堆
         println!("[kernel] Heap initialized.");
+        // 取出上一次启动 panic 时留下的崩溃转储
+        crashdump::init();
+        // 在其他子系统初始化前收集计时器抖动，为熵池提供种子
+        utils::random::init();
+        // 用 RTC 中的时间初始化 CLOCK_REALTIME
+        drivers::rtc::init();
+        timer::init_realtime();
 
         // 初始化其他子系统...
         fs::directory_tree::init_fs();
@@ -185,6 +212,10 @@ pub fn rust_main(hart_id: usize) -> ! {
         net::config::init();
         println!("[Debug] net::config::init() done.");
 
+        // 控制台输入改由串口接收中断送达
+        #[cfg(feature = "uart_irq")]
+        drivers::serial::rx::init();
+
         #[cfg(feature = "block_virt")]
         println!("[kernel] block in virt mode!");
         
@@ -192,15 +223,25 @@ pub fn rust_main(hart_id: usize) -> ! {
         println!("[kernel] oom_handler is enabled!");
 
         #[cfg(any(feature = "block_virt_pci", feature = "block_virt"))]
-        {
+        if cmdline::get().read_only {
+            println!("[kernel] root mounted read-only, preloaded binaries are not flushed");
+        } else {
             println!("[Debug] Calling fs::flush_preload()..."); 
             fs::flush_preload();
             println!("[Debug] fs::flush_preload() done.");
         }
 
+        // 解包引导程序传入的 initramfs，其中的文件会覆盖预加载的同名程序
+        fs::initramfs::init();
+
+        #[cfg(feature = "selftest")]
+        selftest::run();
+
         println!("[kernel] Loading initproc... (before call)");
         task::add_initproc();
         println!("[kernel] Initproc loaded! (after call)");
+        // 内核线程须在 initproc 之后创建，以免占用其 pid
+        task::kthread::spawn("kmetricsd", utils::telemetry::metrics_exporter);
 
         // ------------------------------------------
         //         唤醒从核 (Secondary Harts)
@@ -211,10 +252,15 @@ pub fn rust_main(hart_id: usize) -> ! {
         // 这里沿用你原来的逻辑
         let start_paddr = start_vaddr & !0xffffffff00000000; 
 
-        println!("[Boot] BSP is waking up secondary harts...");
+        let nosmp = cmdline::get().nosmp;
+        if nosmp {
+            println!("[Boot] nosmp: secondary harts are not started.");
+        } else {
+            println!("[Boot] BSP is waking up secondary harts...");
+        }
 
         for i in 0..MAX_CPU_NUM {
-            if i == hart_id { continue; } // 跳过自己
+            if i == hart_id || nosmp { continue; } // 跳过自己；nosmp 时不启动任何从核
 
             #[cfg(feature = "riscv")]
             {
@@ -222,6 +268,8 @@ pub fn rust_main(hart_id: usize) -> ! {
                 let ret = sbi::hart_start(i, start_paddr, 0);
                 if ret == 0 {
                     println!("[Boot] Hart {} started command sent.", i);
+                    #[cfg(feature = "selftest")]
+                    selftest::hart_started();
                 } else {
                     println!("[Boot] Failed to start Hart {} (error: {}).", i, ret);
                 }
@@ -268,6 +316,10 @@ pub fn rust_main(hart_id: usize) -> ! {
     //     所有核心通用逻辑
     // ==========================
     
+    // 所有核同时打印，检验控制台锁
+    #[cfg(feature = "selftest")]
+    selftest::finish(hart_id, is_bsp);
+
     #[cfg(target_arch = "riscv64")]
     unsafe { riscv::register::sstatus::set_sie(); }
 
@@ -21,7 +21,6 @@
 #[cfg(feature = "oom_handler")]
 use super::super::fs;
 use super::{PhysAddr, PhysPageNum};
-use crate::hal::MEMORY_END;
 #[cfg(feature = "oom_handler")]
 use crate::task::current_task;
 
@@ -85,6 +84,8 @@ pub struct StackFrameAllocator {
     end: usize,
     /// List of recycled frames
     recycled: Vec<usize>,
+    /// Frames `[start, end)` skipped over by `current`, e.g. an initramfs not yet unpacked
+    reserved: (usize, usize),
 }
 
 impl StackFrameAllocator {
@@ -97,9 +98,41 @@ impl StackFrameAllocator {
         println!("last {} Physical Frames.", last_frames);
     }
 
+    /// Keep `[l, r)` from being allocated until [`Self::release_reserved`]
+    pub fn reserve(&mut self, l: PhysPageNum, r: PhysPageNum) {
+        let (l, r) = (l.0.max(self.current), r.0.min(self.end));
+        if l < r {
+            self.reserved = (l, r);
+        }
+    }
+
+    /// Hand the reserved frames over to the allocator
+    pub fn release_reserved(&mut self) {
+        let (l, r) = core::mem::take(&mut self.reserved);
+        // frames behind `current` would otherwise never be handed out
+        if self.current >= r {
+            self.recycled.extend(l..r);
+        }
+    }
+
     /// Get the number of unallocated frames
     pub fn unallocated_frames(&self) -> usize {
-        self.end - self.current + self.recycled.len()
+        let (l, r) = self.reserved;
+        let reserved = if self.current <= l { r - l } else { 0 };
+        self.end - self.current + self.recycled.len() - reserved
+    }
+
+    /// Next never-allocated frame, skipping over the reserved range
+    fn next_frame(&mut self) -> Option<usize> {
+        if self.current == self.reserved.0 {
+            self.current = self.reserved.1;
+        }
+        if self.current == self.end {
+            None
+        } else {
+            self.current += 1;
+            Some(self.current - 1)
+        }
     }
 }
 
@@ -109,6 +142,7 @@ impl FrameAllocator for StackFrameAllocator {
             current: 0,
             end: 0,
             recycled: Vec::new(),
+            reserved: (0, 0),
         }
     }
 
@@ -119,18 +153,17 @@ impl FrameAllocator for StackFrameAllocator {
             let frame_tracker = FrameTracker::new(ppn.into());
             log::trace!("[frame_alloc] {:?}", frame_tracker);
             Some(frame_tracker)
-        } else if self.current == self.end {
-            // 无可用帧
-            None
-        } else {
+        } else if let Some(ppn) = self.next_frame() {
             // 否则分配当前页
-            self.current += 1;
             #[cfg(not(feature = "zero_init"))]
-            let frame_tracker = FrameTracker::new((self.current - 1).into());
+            let frame_tracker = FrameTracker::new(ppn.into());
             #[cfg(feature = "zero_init")]
-            let frame_tracker = unsafe { FrameTracker::new_uninit((self.current - 1).into()) };
+            let frame_tracker = unsafe { FrameTracker::new_uninit(ppn.into()) };
             log::trace!("[frame_alloc] {:?}", frame_tracker);
             Some(frame_tracker)
+        } else {
+            // 无可用帧
+            None
         }
     }
     unsafe fn alloc_uninit(&mut self) -> Option<FrameTracker> {
@@ -138,13 +171,12 @@ impl FrameAllocator for StackFrameAllocator {
             let frame_tracker = FrameTracker::new_uninit(ppn.into());
             //log::trace!("[frame_alloc_uninit] {:?}", frame_tracker);
             Some(frame_tracker)
-        } else if self.current == self.end {
-            None
-        } else {
-            self.current += 1;
-            let frame_tracker = FrameTracker::new_uninit((self.current - 1).into());
+        } else if let Some(ppn) = self.next_frame() {
+            let frame_tracker = FrameTracker::new_uninit(ppn.into());
             log::trace!("[frame_alloc_uninit] {:?}", frame_tracker);
             Some(frame_tracker)
+        } else {
+            None
         }
     }
     /// 释放一个物理页
@@ -175,13 +207,23 @@ pub fn init_frame_allocator() {
         // 内核结束地址？
         fn ekernel();
     }
-    FRAME_ALLOCATOR.write().init(
+    let mut allocator = FRAME_ALLOCATOR.write();
+    allocator.init(
         // 从内核结束地址ekernel
         PhysAddr::from(ekernel as usize).ceil(),
-        // 到内存结束地址
-        PhysAddr::from(MEMORY_END).floor(),
+        // 到内存结束地址，末尾留给崩溃转储
+        PhysAddr::from(crate::crashdump::region().0).floor(),
         // 作为可用物理内存
     );
+    // initramfs 解包之前，其所在的帧不能分配出去
+    if let Some((start, end)) = crate::fs::initramfs::region() {
+        allocator.reserve(PhysAddr::from(start).floor(), PhysAddr::from(end).ceil());
+    }
+}
+
+/// initramfs 解包完成后归还其占用的帧
+pub fn frame_release_reserved() {
+    FRAME_ALLOCATOR.write().release_reserved();
 }
 
 /// 尝试使用所有可能的方法来释放制定数量为`req`的页
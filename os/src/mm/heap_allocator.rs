@@ -5,9 +5,16 @@
 use crate::hal::KERNEL_HEAP_SIZE;
 use buddy_system_allocator::LockedHeap;
 
+#[cfg(not(feature = "kmemleak"))]
 #[global_allocator]
 static HEAP_ALLOCATOR: LockedHeap<32> = LockedHeap::empty();
 
+/// 开启 kmemleak 时记录每次分配的调用点，见 `/proc/kmemleak`
+#[cfg(feature = "kmemleak")]
+#[global_allocator]
+static HEAP_ALLOCATOR: super::kmemleak::TrackingAllocator<LockedHeap<32>> =
+    super::kmemleak::TrackingAllocator::new(LockedHeap::empty());
+
 #[alloc_error_handler]
 pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
     panic!("Heap allocation error, layout = {:?}", layout);
@@ -0,0 +1,323 @@
+//! Kernel memory leak detector
+//!
+//! With the `kmemleak` feature the kernel heap is wrapped in a [`TrackingAllocator`]
+//! that records the size, allocation time and call site of every live allocation.
+//! `/proc/kmemleak` lists the allocations older than [`MIN_LEAK_AGE_MS`] grouped
+//! by call site, the sites that keep accumulating memory are the leak suspects.
+//!
+//! A call site is the chain of the first [`SITE_DEPTH`] return addresses above
+//! the allocator, taken by walking the frame pointers (the kernel is built with
+//! `-Cforce-frame-pointers`). The innermost ones usually belong to `alloc`
+//! itself, resolve the addresses with `addr2line -e os` to find the caller.
+//!
+//! The records live in a fixed-size table so that tracking never allocates.
+//! Allocations made while the table is full are counted but not tracked.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::fmt;
+use core::ops::Deref;
+
+/// Return addresses recorded per allocation
+pub const SITE_DEPTH: usize = 4;
+/// Live allocations that can be tracked at the same time
+pub const MAX_TRACKED: usize = 16384;
+/// Allocations younger than this are not reported
+pub const MIN_LEAK_AGE_MS: usize = 5000;
+/// Distinct call sites listed in a report
+pub const MAX_REPORTED_SITES: usize = 64;
+
+/// Return addresses of an allocation, innermost first, unused entries are 0
+pub type Site = [usize; SITE_DEPTH];
+
+#[derive(Debug, Clone, Copy)]
+struct Record {
+    /// 0 for an empty slot
+    ptr: usize,
+    size: usize,
+    time_ms: usize,
+    site: Site,
+}
+
+impl Record {
+    const EMPTY: Self = Self {
+        ptr: 0,
+        size: 0,
+        time_ms: 0,
+        site: [0; SITE_DEPTH],
+    };
+}
+
+/// Long-lived allocations sharing a call site
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LeakGroup {
+    pub site: Site,
+    pub count: usize,
+    pub bytes: usize,
+    /// Allocation time of the oldest allocation in ms
+    pub oldest_ms: usize,
+}
+
+/// Open addressing hash table of live allocations keyed by address
+pub struct LeakTable<const N: usize> {
+    slots: [Record; N],
+    live: usize,
+    untracked: usize,
+}
+
+impl<const N: usize> LeakTable<N> {
+    pub const fn new() -> Self {
+        Self {
+            slots: [Record::EMPTY; N],
+            live: 0,
+            untracked: 0,
+        }
+    }
+
+    fn home(ptr: usize) -> usize {
+        ((ptr >> 3).wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 16) % N
+    }
+
+    fn find(&self, ptr: usize) -> Option<usize> {
+        let mut index = Self::home(ptr);
+        for _ in 0..N {
+            match self.slots[index].ptr {
+                0 => return None,
+                slot if slot == ptr => return Some(index),
+                _ => index = (index + 1) % N,
+            }
+        }
+        None
+    }
+
+    /// Track an allocation, it is only counted if the table is full
+    pub fn insert(&mut self, ptr: usize, size: usize, time_ms: usize, site: Site) -> bool {
+        if ptr == 0 || self.live == N {
+            self.untracked += 1;
+            return false;
+        }
+        let mut index = Self::home(ptr);
+        while self.slots[index].ptr != 0 {
+            index = (index + 1) % N;
+        }
+        self.slots[index] = Record {
+            ptr,
+            size,
+            time_ms,
+            site,
+        };
+        self.live += 1;
+        true
+    }
+
+    /// Stop tracking a freed allocation, false if it was not tracked
+    pub fn remove(&mut self, ptr: usize) -> bool {
+        let mut hole = match self.find(ptr) {
+            Some(index) => index,
+            None => return false,
+        };
+        self.slots[hole] = Record::EMPTY;
+        self.live -= 1;
+        // shift the rest of the probe run back so lookups never stop at the hole
+        let mut index = hole;
+        loop {
+            index = (index + 1) % N;
+            if self.slots[index].ptr == 0 {
+                break;
+            }
+            let home = Self::home(self.slots[index].ptr);
+            let reachable = if hole <= index {
+                hole < home && home <= index
+            } else {
+                hole < home || home <= index
+            };
+            if !reachable {
+                self.slots[hole] = self.slots[index];
+                self.slots[index] = Record::EMPTY;
+                hole = index;
+            }
+        }
+        true
+    }
+
+    /// Allocations currently tracked
+    pub fn live(&self) -> usize {
+        self.live
+    }
+
+    /// Allocations that found the table full
+    pub fn untracked(&self) -> usize {
+        self.untracked
+    }
+
+    /// Group the allocations made at least `min_age_ms` before `now_ms` by call site.
+    /// Fills `groups` in no particular order and returns how many were filled,
+    /// plus the number of allocations whose site did not fit.
+    pub fn group_leaks(
+        &self,
+        now_ms: usize,
+        min_age_ms: usize,
+        groups: &mut [LeakGroup],
+    ) -> (usize, usize) {
+        let (mut filled, mut omitted) = (0, 0);
+        for record in self.slots.iter().filter(|record| record.ptr != 0) {
+            if now_ms.saturating_sub(record.time_ms) < min_age_ms {
+                continue;
+            }
+            let group = match groups[..filled]
+                .iter()
+                .position(|group| group.site == record.site)
+            {
+                Some(index) => &mut groups[index],
+                None if filled < groups.len() => {
+                    groups[filled] = LeakGroup {
+                        site: record.site,
+                        oldest_ms: record.time_ms,
+                        ..LeakGroup::default()
+                    };
+                    filled += 1;
+                    &mut groups[filled - 1]
+                }
+                None => {
+                    omitted += 1;
+                    continue;
+                }
+            };
+            group.count += 1;
+            group.bytes += record.size;
+            group.oldest_ms = group.oldest_ms.min(record.time_ms);
+        }
+        (filled, omitted)
+    }
+}
+
+/// One line per call site, the sites holding the most memory first
+pub fn format_groups(
+    groups: &mut [LeakGroup],
+    omitted: usize,
+    now_ms: usize,
+    output: &mut impl fmt::Write,
+) -> fmt::Result {
+    groups.sort_unstable_by(|a, b| b.bytes.cmp(&a.bytes));
+    for group in groups.iter() {
+        write!(
+            output,
+            "{} bytes in {} allocations, oldest {} ms, site",
+            group.bytes,
+            group.count,
+            now_ms.saturating_sub(group.oldest_ms)
+        )?;
+        for &addr in group.site.iter().take_while(|&&addr| addr != 0) {
+            write!(output, " {:#x}", addr)?;
+        }
+        writeln!(output)?;
+    }
+    if omitted > 0 {
+        writeln!(output, "{} allocations at other sites", omitted)?;
+    }
+    Ok(())
+}
+
+static TRACKER: spin::Mutex<LeakTable<MAX_TRACKED>> = spin::Mutex::new(LeakTable::new());
+
+/// Global allocator wrapper recording every live allocation in the leak table
+pub struct TrackingAllocator<A> {
+    inner: A,
+}
+
+impl<A> TrackingAllocator<A> {
+    pub const fn new(inner: A) -> Self {
+        Self { inner }
+    }
+}
+
+impl<A> Deref for TrackingAllocator<A> {
+    type Target = A;
+
+    fn deref(&self) -> &A {
+        &self.inner
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for TrackingAllocator<A> {
+    #[inline(never)]
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.inner.alloc(layout);
+        if !ptr.is_null() {
+            track(ptr as usize, layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        TRACKER.lock().remove(ptr as usize);
+        self.inner.dealloc(ptr, layout);
+    }
+}
+
+#[inline(never)]
+fn track(ptr: usize, size: usize) {
+    let site = call_site();
+    let now = crate::timer::get_time_ms();
+    TRACKER.lock().insert(ptr, size, now, site);
+}
+
+/// Frames of `call_site`, `track` and `TrackingAllocator::alloc`
+const SKIPPED_FRAMES: usize = 3;
+
+/// Walk the frame pointer chain: the return address is saved at `fp - 8`
+/// and the caller's frame pointer at `fp - 16` on both architectures
+#[inline(never)]
+fn call_site() -> Site {
+    let mut site = [0; SITE_DEPTH];
+    let (sp, mut fp): (usize, usize);
+    #[cfg(target_arch = "riscv64")]
+    unsafe {
+        core::arch::asm!("mv {}, sp", "mv {}, s0", out(reg) sp, out(reg) fp);
+    }
+    #[cfg(target_arch = "loongarch64")]
+    unsafe {
+        core::arch::asm!("move {}, $sp", "move {}, $fp", out(reg) sp, out(reg) fp);
+    }
+    #[cfg(not(any(target_arch = "riscv64", target_arch = "loongarch64")))]
+    {
+        (sp, fp) = (0, 0);
+    }
+    // stay on the current kernel stack: frames only grow towards its top
+    let limit = sp.saturating_add(crate::config::KERNEL_STACK_SIZE);
+    let mut depth = 0;
+    while depth < SKIPPED_FRAMES + SITE_DEPTH && fp > sp && fp <= limit && fp % 8 == 0 {
+        let (ra, caller_fp) =
+            unsafe { (*((fp - 8) as *const usize), *((fp - 16) as *const usize)) };
+        if depth >= SKIPPED_FRAMES {
+            site[depth - SKIPPED_FRAMES] = ra;
+        }
+        if caller_fp <= fp {
+            break;
+        }
+        fp = caller_fp;
+        depth += 1;
+    }
+    site
+}
+
+/// Contents of `/proc/kmemleak`
+pub fn format_report() -> alloc::string::String {
+    use core::fmt::Write;
+    let now = crate::timer::get_time_ms();
+    let mut groups = [LeakGroup::default(); MAX_REPORTED_SITES];
+    // copy out under the lock, formatting allocates
+    let (filled, omitted, live, untracked) = {
+        let tracker = TRACKER.lock();
+        let (filled, omitted) = tracker.group_leaks(now, MIN_LEAK_AGE_MS, &mut groups);
+        (filled, omitted, tracker.live(), tracker.untracked())
+    };
+    let mut output = alloc::string::String::new();
+    writeln!(
+        output,
+        "live allocations: {}, untracked: {}, reporting those older than {} ms",
+        live, untracked, MIN_LEAK_AGE_MS
+    )
+    .ok();
+    format_groups(&mut groups[..filled], omitted, now, &mut output).ok();
+    output
+}
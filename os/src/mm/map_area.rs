@@ -14,12 +14,11 @@ use super::zram::{ZramTracker, ZRAM_DEVICE};
 use super::MemoryError;
 use super::VPNRange;
 use super::KERNEL_SPACE;
-use super::{frame_alloc, FrameTracker};
+use super::{frame_alloc, tlb_invalidate, FrameTracker};
 use super::{PhysPageNum, VirtAddr, VirtPageNum};
 use crate::fs::file_trait::File;
 #[cfg(feature = "swap")]
 use crate::fs::swap::{SwapTracker, SWAP_DEVICE};
-use crate::fs::SeekWhence;
 use crate::mm::frame_allocator::frame_alloc_uninit;
 
 #[cfg(feature = "oom_handler")]
@@ -265,12 +264,24 @@ impl LinearMap {
         }
         self.vpn_range = VPNRange::new(new_vpn_start, vpn_end);
         if new_vpn_start < vpn_start {
-            self.frames.rotate_left(vpn_start.0 - new_vpn_start.0);
+            // pages added at the bottom are unallocated
+            let diff = vpn_start.0 - new_vpn_start.0;
+            self.frames
+                .resize(vpn_end.0 - new_vpn_start.0, Frame::Unallocated);
+            self.frames.rotate_right(diff);
+            #[cfg(feature = "oom_handler")]
+            self.active.iter_mut().for_each(|idx| *idx += diff as u16);
         } else {
-            self.frames.rotate_left(new_vpn_start.0 - vpn_start.0);
+            let diff = new_vpn_start.0 - vpn_start.0;
+            self.frames.rotate_left(diff);
+            self.frames
+                .resize(vpn_end.0 - new_vpn_start.0, Frame::Unallocated);
+            #[cfg(feature = "oom_handler")]
+            {
+                self.active.retain(|&idx| idx as usize >= diff);
+                self.active.iter_mut().for_each(|idx| *idx -= diff as u16);
+            }
         }
-        self.frames
-            .resize(vpn_end.0 - new_vpn_start.0, Frame::Unallocated);
         Ok(())
     }
     pub fn set_end(&mut self, new_vpn_end: VirtPageNum) -> Result<(), ()> {
@@ -404,6 +415,8 @@ impl Debug for MapArea {
                 "map_file",
                 &if self.map_file.is_some() { "yes" } else { "no" },
             )
+            .field("map_offset", &self.map_offset)
+            .field("shared", &self.shared)
             .finish()
     }
 }
@@ -419,6 +432,14 @@ pub struct MapArea {
     /// Permissions which are the or of RWXU, where U stands for user.
     pub map_perm: MapPermission,
     pub map_file: Option<Arc<dyn File>>,
+    /// Offset in `map_file` of the first page of the area.
+    /// Pages are read from there on the first fault, nothing is read at map time.
+    pub map_offset: usize,
+    /// `MAP_SHARED` file mapping: the pages are the page cache pages of `map_file`,
+    /// so writes reach the file instead of being copied on write.
+    pub shared: bool,
+    /// `MAP_GROWSDOWN`: a page fault right below the area extends it downward.
+    pub grows_down: bool,
 }
 
 impl MapArea {
@@ -443,6 +464,9 @@ impl MapArea {
             map_type,
             map_perm,
             map_file,
+            map_offset: 0,
+            shared: false,
+            grows_down: false,
         }
     }
     /// Copier, but the physical pages are not allocated,
@@ -456,6 +480,9 @@ impl MapArea {
             map_type: another.map_type,
             map_perm: another.map_perm,
             map_file: another.map_file.clone(),
+            map_offset: another.map_offset,
+            shared: another.shared,
+            grows_down: another.grows_down,
         }
     }
     /// Create `MapArea` from `Vec<Arc<FrameTracker>>`. This function should only be used to
@@ -485,6 +512,9 @@ impl MapArea {
             map_type,
             map_perm,
             map_file: None,
+            map_offset: 0,
+            shared: false,
+            grows_down: false,
         }
     }
 
@@ -558,13 +588,24 @@ impl MapArea {
     }
 
     // xein TODO:
+    /// Map the frames of `self`, a clone of an area in `src_page_table`, into `dst_page_table`
+    /// for copy on write: both page tables then map them without W, and whoever writes first
+    /// gets a copy in [`MapArea::copy_on_write`].
+    /// Only pages held in memory are looked up, a sparse area costs nothing for its holes.
+    /// # Note
+    /// The caller must flush the TLB, stale entries would still let the source write.
     pub fn map_from_existing_page_table<T: PageTable>(
         &mut self,
         dst_page_table: &mut T,
         src_page_table: &mut T,
     ) -> Result<(), ()> {
         let map_perm = self.map_perm.difference(MapPermission::W);
-        for vpn in self.inner.vpn_range {
+        let start_vpn = self.inner.get_start();
+        for (idx, frame) in self.inner.frames.iter().enumerate() {
+            if !matches!(frame, Frame::InMemory(_)) {
+                continue;
+            }
+            let vpn = VirtPageNum::from(start_vpn.0 + idx);
             if let Some(ppn) = src_page_table.block_and_ret_mut(vpn) {
                 if !dst_page_table.is_mapped(vpn) {
                     dst_page_table.map(vpn, ppn, map_perm);
@@ -658,10 +699,34 @@ impl MapArea {
             new_ppn
                 .get_bytes_array()
                 .copy_from_slice(old_ppn.get_bytes_array());
+            // the old read-only translation may still be cached
+            tlb_invalidate();
             trace!("[copy_on_write] copy occurred");
             Ok(new_ppn)
         }
     }
+    /// Write fault on a page of a `MAP_SHARED` file mapping. The page is the page
+    /// cache page itself, so instead of copying it, mark it dirty for writeback and
+    /// grant write access.
+    pub fn write_shared<T: PageTable>(
+        &mut self,
+        page_table: &mut T,
+        vpn: VirtPageNum,
+    ) -> Result<PhysPageNum, MemoryError> {
+        let ppn = match self.inner.get_mut(&vpn) {
+            Frame::InMemory(frame) => frame.ppn,
+            _ => return Err(MemoryError::NotInMemory),
+        };
+        let file = self.map_file.as_ref().unwrap();
+        file.get_single_cache(self.offset_of(vpn))
+            .map_err(|_| MemoryError::BeyondEOF)?
+            .lock()
+            .mark_dirty();
+        page_table.set_pte_flags(vpn, self.map_perm).unwrap();
+        // the read-only translation may still be cached
+        tlb_invalidate();
+        Ok(ppn)
+    }
     /// If `new_end` is equal to the current end of area, do nothing and return `Ok(())`.
     pub fn expand_to<T: PageTable>(&mut self, new_end: VirtAddr) -> Result<(), ()> {
         let new_end_vpn: VirtPageNum = new_end.ceil();
@@ -734,6 +799,7 @@ impl MapArea {
         // `set_start` must be done after calling `map_one`
         // for the similar reason with `expand_to`
         self.inner.set_start(new_start_vpn)?;
+        self.map_offset += VirtAddr::from(new_start_vpn).0 - VirtAddr::from(old_start_vpn).0;
         if has_unmapped_page {
             warn!("[rshrink_to] Some pages are already unmapped, is it caused by lazy alloc?");
             Err(())
@@ -765,26 +831,16 @@ impl MapArea {
         }
     }
     pub fn into_two(&mut self, cut: VirtPageNum) -> Result<Self, ()> {
-        let second_file = if let Some(file) = &self.map_file {
-            let new_file = file.deep_clone();
-            new_file
-                .lseek(
-                    (file.get_offset() + VirtAddr::from(cut).0
-                        - VirtAddr::from(self.inner.vpn_range.get_start()).0)
-                        as isize,
-                    SeekWhence::SEEK_SET,
-                )
-                .unwrap();
-            Some(new_file)
-        } else {
-            None
-        };
+        let second_offset = self.offset_of(cut);
         let second_frames = self.inner.into_two(cut)?;
         Ok(MapArea {
             inner: second_frames,
             map_type: self.map_type,
             map_perm: self.map_perm,
-            map_file: second_file,
+            map_file: self.map_file.clone(),
+            map_offset: second_offset,
+            shared: self.shared,
+            grows_down: self.grows_down,
         })
     }
     pub fn into_three(
@@ -792,58 +848,74 @@ impl MapArea {
         first_cut: VirtPageNum,
         second_cut: VirtPageNum,
     ) -> Result<(Self, Self), ()> {
-        // 1. 记录原始起始 VPN，用于后续 file offset 计算
-        let old_start_vpn = self.inner.vpn_range.get_start();
-        let base_va = VirtAddr::from(old_start_vpn).0;
-
-        // 2. 拆分出三段 linear map
+        let (second_offset, third_offset) = (self.offset_of(first_cut), self.offset_of(second_cut));
         let (second_frames, third_frames) = self.inner.into_three(first_cut, second_cut)?;
-
-        // 3. 根据是否文件映射，生成第二/三段的 file 句柄并调整偏移
-        if let Some(ref file) = self.map_file {
-            // 第一段 self.map_file 保持原 offset
-            let orig_offset = file.get_offset();
-
-            // 第二段
-            let second_file = file.deep_clone();
-            let offset1 = orig_offset + (VirtAddr::from(first_cut).0 - base_va);
-            second_file.lseek(offset1 as isize, SeekWhence::SEEK_SET).unwrap();
-
-            // 第三段
-            let mut third_file = file.deep_clone();
-            let offset2 = orig_offset + (VirtAddr::from(second_cut).0 - base_va);
-            third_file.lseek(offset2 as isize, SeekWhence::SEEK_SET).unwrap();
-
-            Ok((
-                MapArea {
-                    inner: second_frames,
-                    map_type: self.map_type,
-                    map_perm: self.map_perm,
-                    map_file: Some(second_file),
-                },
-                MapArea {
-                    inner: third_frames,
-                    map_type: self.map_type,
-                    map_perm: self.map_perm,
-                    map_file: Some(third_file),
-                },
-            ))
-        } else {
-            // 非文件映射，保持原有逻辑
-            Ok((
-                MapArea {
-                    inner: second_frames,
-                    map_type: self.map_type,
-                    map_perm: self.map_perm,
-                    map_file: None,
-                },
-                MapArea {
-                    inner: third_frames,
-                    map_type: self.map_type,
-                    map_perm: self.map_perm,
-                    map_file: None,
-                },
-            ))
+        Ok((
+            MapArea {
+                inner: second_frames,
+                map_type: self.map_type,
+                map_perm: self.map_perm,
+                map_file: self.map_file.clone(),
+                map_offset: second_offset,
+                shared: self.shared,
+                grows_down: self.grows_down,
+            },
+            MapArea {
+                inner: third_frames,
+                map_type: self.map_type,
+                map_perm: self.map_perm,
+                map_file: self.map_file.clone(),
+                map_offset: third_offset,
+                shared: self.shared,
+                grows_down: self.grows_down,
+            },
+        ))
+    }
+    /// Offset in `map_file` of the page `vpn` in this area
+    pub fn offset_of(&self, vpn: VirtPageNum) -> usize {
+        self.map_offset + VirtAddr::from(vpn).0 - VirtAddr::from(self.inner.get_start()).0
+    }
+    /// MADV_FREE：标记 `vpn_range` 中的页可被回收路径直接丢弃
+    ///
+    /// 在内存中的页去掉写权限并打上标记，写入时由写缺页清除标记；
+    /// 已被压缩或换出的页内容不再需要，直接丢弃
+    #[cfg(feature = "oom_handler")]
+    pub fn lazy_free<T: PageTable>(&mut self, page_table: &mut T, vpn_range: VPNRange) {
+        for vpn in vpn_range {
+            match self.inner.get_mut(&vpn) {
+                Frame::InMemory(_) => {
+                    page_table
+                        .set_pte_flags(vpn, self.map_perm - MapPermission::W)
+                        .unwrap();
+                    page_table.set_freeable(vpn, true).unwrap();
+                }
+                Frame::Compressed(_) => {
+                    *self.inner.get_mut(&vpn) = Frame::Unallocated;
+                    self.inner.compressed -= 1;
+                }
+                Frame::SwappedOut(_) => {
+                    *self.inner.get_mut(&vpn) = Frame::Unallocated;
+                    self.inner.swapped -= 1;
+                }
+                Frame::Unallocated => {}
+            }
+        }
+    }
+    /// 丢弃第 `idx` 页，如果它仍带有 MADV_FREE 标记且没有被共享。
+    /// 丢弃后再次访问时得到零页
+    #[cfg(feature = "oom_handler")]
+    fn drop_freeable<T: PageTable>(&mut self, page_table: &mut T, idx: usize) -> bool {
+        let vpn = VirtPageNum::from(self.inner.get_start().0 + idx);
+        match &self.inner.frames[idx] {
+            Frame::InMemory(frame)
+                if Arc::strong_count(frame) == 1 && page_table.is_freeable(vpn) == Some(true) =>
+            {
+                page_table.unmap(vpn);
+                self.inner.frames[idx] = Frame::Unallocated;
+                trace!("[drop_freeable] drop lazily freed page, vpn: {:?}", vpn);
+                true
+            }
+            _ => false,
         }
     }
     #[cfg(feature = "oom_handler")]
@@ -851,8 +923,14 @@ impl MapArea {
         let start_vpn = self.get_inner().vpn_range.get_start();
         let compressed_before = self.get_inner().compressed;
         let swapped_before = self.get_inner().swapped;
+        let mut freed = 0;
         warn!("{:?}", self.inner.active);
         while let Some(idx) = self.inner.active.pop_front() {
+            // lazily freed pages are dropped without saving
+            if self.drop_freeable(page_table, idx as usize) {
+                freed += 1;
+                continue;
+            }
             let frame = &mut self.inner.frames[idx as usize];
             // first, try to compress
             match frame.zip() {
@@ -878,14 +956,19 @@ impl MapArea {
                 _ => unreachable!(),
             }
         }
-        self.inner.compressed + self.inner.swapped - compressed_before - swapped_before
+        freed + self.inner.compressed + self.inner.swapped - compressed_before - swapped_before
     }
     #[cfg(feature = "oom_handler")]
     pub fn force_swap<T: PageTable>(&mut self, page_table: &mut T) -> usize {
         let start_vpn = self.inner.vpn_range.get_start();
         let swapped_before = self.inner.swapped;
+        let mut freed = 0;
         warn!("{:?}", self.inner.active);
         while let Some(idx) = self.inner.active.pop_front() {
+            if self.drop_freeable(page_table, idx as usize) {
+                freed += 1;
+                continue;
+            }
             let frame = &mut self.inner.frames[idx as usize];
             match frame.force_swap_out() {
                 Ok(swap_id) => {
@@ -901,7 +984,7 @@ impl MapArea {
                 _ => unreachable!(),
             }
         }
-        self.inner.swapped - swapped_before
+        freed + self.inner.swapped - swapped_before
     }
 }
 
@@ -1,10 +1,10 @@
 use super::map_area::*;
 use super::page_table::PageTable;
-use super::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
+use super::{PhysAddr, PhysPageNum, VPNRange, VirtAddr, VirtPageNum};
 use crate::config::*;
-use crate::fs::SeekWhence;
+use crate::fs::file_trait::File;
 use crate::hal::TrapContext;
-use crate::hal::{MMIO, TICKS_PER_SEC};
+use crate::hal::TICKS_PER_SEC;
 use crate::should_map_trampoline;
 use crate::syscall::errno::*;
 use crate::task::{
@@ -36,6 +36,12 @@ lazy_static! {
         Arc::new(Mutex::new(MemorySet::new_kernel()));
 }
 
+/// Largest size a `MAP_GROWSDOWN` area grows to, the stack limit reported by `prlimit`
+const GROWSDOWN_LIMIT: usize = USER_STACK_SIZE;
+
+/// Free frames `MAP_POPULATE` leaves for allocations that cannot fail
+const POPULATE_FRAMES_RESERVED: usize = 256;
+
 /// Return the root PPN of kernel space
 pub fn kernel_token() -> usize {
     KERNEL_SPACE.lock().token()
@@ -288,6 +294,8 @@ impl<T: PageTable> MemorySet<T> {
     /// Checks the permission to decide whether to copy.
     pub fn do_page_fault(&mut self, addr: VirtAddr) -> Result<PhysAddr, MemoryError> {
         let vpn = addr.floor();
+        // 栈式区域 (MAP_GROWSDOWN) 下方的缺页使区域向下扩展
+        self.grow_down_to(vpn);
         // 在所有内存区域中查找包含发生页错误的虚拟页号的区域
         if let Some(area) = self.areas.iter_mut().find(|area| {
             // 检查内存区域是否具有读权限和用户权限
@@ -302,50 +310,46 @@ impl<T: PageTable> MemorySet<T> {
             if !self.page_table.is_mapped(vpn) {
                 // === 处理文件映射的页面（延迟分配） ===
                 if let Some(file) = area.map_file.clone() {
-                    // 获取文件当前的偏移量
-                    let old_offset = file.get_offset();
-                    // 计算当前页面在虚拟地址空间中的起始地址
-                    let page_start_va = VirtAddr::from(vpn).0;
-                    // 计算内存区域的起始虚拟地址
-                    let area_start_va = VirtAddr::from(area.get_start::<T>()).0;
-                    // 计算当前页面在内存区域中的偏移量
-                    let offset_in_area = page_start_va - area_start_va;
-                    
+                    // 当前页面在文件中的偏移量，由区域记录的起始偏移算出，不依赖文件的读写位置
+                    let file_offset = area.offset_of(vpn);
+
                     // 检查是否超出文件末尾 (EOF) - 如果超出则发送 SIGBUS 信号
                     // (file.get_size() + PAGE_SIZE - 1) & !0xfff 将文件大小向上对齐到页边界
-                    if old_offset + offset_in_area > (file.get_size() + PAGE_SIZE - 1) & !0xfff {
+                    if file_offset > (file.get_size() + PAGE_SIZE - 1) & !0xfff {
                         return Err(MemoryError::BeyondEOF);
                     }
                     
-                    // 根据内存区域的写权限选择不同的处理方式
-                    if area.map_perm.contains(MapPermission::W) {
+                    // 根据内存区域的写权限选择不同的处理方式，共享映射总是映射缓存页面
+                    if area.map_perm.contains(MapPermission::W) && !area.shared {
                         // === 可写的文件映射：分配新页面并从文件读取数据 ===
                         let allocated_ppn = area.map_one_zeroed_unchecked(&mut self.page_table, vpn);
-                        // 定位到文件中对应的位置
-                        file.lseek(offset_in_area as isize, SeekWhence::SEEK_CUR)
-                            .unwrap();
-                        // 从文件读取数据到新分配的物理页面
-                        file.read(None, unsafe {
+                        // 从文件对应位置读取数据到新分配的物理页面，文件末尾之后保持为零
+                        let mut offset = file_offset;
+                        file.read(Some(&mut offset), unsafe {
                             core::slice::from_raw_parts_mut(
                                 PhysAddr::from(allocated_ppn).0 as *mut u8,
                                 PAGE_SIZE,
                             )
                         });
-                        // 恢复文件的原始偏移量
-                        file.lseek(old_offset as isize, SeekWhence::SEEK_SET)
-                            .unwrap();
                         Ok(allocated_ppn.offset(addr.page_offset()))
                     } else {
-                        // === 只读的文件映射：直接映射到文件缓存页面 ===
+                        // === 只读或共享的文件映射：直接映射到文件缓存页面 ===
+                        // 缓存页可能正被其他核读写或写回，等待其释放；
+                        // 取不到缓存页（如为空洞分配块时空间不足）时发送 SIGBUS
                         let cache_phys_page = file
-                            .get_single_cache(old_offset + offset_in_area)
-                            .unwrap()
-                            .try_lock()
-                            .unwrap()
+                            .get_single_cache(file_offset)
+                            .map_err(|_| MemoryError::BeyondEOF)?
+                            .lock()
                             .get_tracker();
                         let cache_ppn = cache_phys_page.ppn;
                         // 直接将虚拟页号映射到缓存的物理页号
-                        self.page_table.map(vpn, cache_ppn, area.map_perm);
+                        // 共享映射先不给写权限，首次写入时缺页，由此把缓存页标记为脏页
+                        let map_perm = if area.shared {
+                            area.map_perm - MapPermission::W
+                        } else {
+                            area.map_perm
+                        };
+                        self.page_table.map(vpn, cache_ppn, map_perm);
                         area.inner.alloc_in_memory(vpn, cache_phys_page);
                         Ok(cache_ppn.offset(addr.page_offset()))
                     }
@@ -405,7 +409,14 @@ impl<T: PageTable> MemorySet<T> {
                 }
             } else {
                 // mapped before the assignment
-                if area.map_perm.contains(MapPermission::W) {
+                if area.shared && area.map_perm.contains(MapPermission::W) {
+                    // Writes go to the page cache page, which is shared rather than copied.
+                    let ppn = area.write_shared(&mut self.page_table, vpn)?;
+                    info!("[do_page_fault] addr: {:?}, solution: write shared", addr);
+                    Ok(ppn.offset(addr.page_offset()))
+                } else if area.map_perm.contains(MapPermission::W) {
+                    // A write cancels MADV_FREE, the page must be kept from now on.
+                    self.page_table.set_freeable(vpn, false).unwrap();
                     // Whoever triggers this fault shall cause the area to be copied into a new area.
                     let allocated_ppn = area.copy_on_write(&mut self.page_table, vpn)?;
                     info!("[do_page_fault] addr: {:?}, solution: copy on write", addr);
@@ -425,6 +436,78 @@ impl<T: PageTable> MemorySet<T> {
             Err(MemoryError::BadAddress)
         }
     }
+    /// Access to `addr` on behalf of a tracer (`PTRACE_PEEK*`/`PTRACE_POKE*`).
+    /// Pages not present yet are faulted in. A write to a private mapping copies
+    /// the page first, even if the mapping is read-only, so breakpoints can be
+    /// planted in the text without touching the file cache or the other
+    /// processes sharing the page.
+    pub fn access_remote(&mut self, addr: VirtAddr, write: bool) -> Result<PhysAddr, MemoryError> {
+        let vpn = addr.floor();
+        if !self.page_table.is_mapped(vpn) {
+            self.do_page_fault(addr)?;
+        }
+        if !write {
+            return self
+                .page_table
+                .translate_va(addr)
+                .ok_or(MemoryError::BadAddress);
+        }
+        let area = self
+            .areas
+            .iter_mut()
+            .find(|area| area.get_start::<T>() <= vpn && vpn < area.get_end::<T>())
+            .ok_or(MemoryError::BadAddress)?;
+        let ppn = if area.shared {
+            if !area.map_perm.contains(MapPermission::W) {
+                return Err(MemoryError::NoPermission);
+            }
+            area.write_shared(&mut self.page_table, vpn)?
+        } else {
+            if area.map_perm.contains(MapPermission::W) {
+                self.page_table.set_freeable(vpn, false).unwrap();
+            }
+            area.copy_on_write(&mut self.page_table, vpn)?
+        };
+        Ok(ppn.offset(addr.page_offset()))
+    }
+    /// Extend the `MAP_GROWSDOWN` area right above `vpn` down to it.
+    /// The area may grow up to [`GROWSDOWN_LIMIT`] and keeps a guard page
+    /// above whatever is mapped below it. Returns whether the area was extended.
+    fn grow_down_to(&mut self, vpn: VirtPageNum) -> bool {
+        if self
+            .areas
+            .iter()
+            .any(|area| area.get_start::<T>() <= vpn && vpn < area.get_end::<T>())
+        {
+            return false;
+        }
+        let idx = match self
+            .areas
+            .iter()
+            .enumerate()
+            .filter(|(_, area)| area.get_start::<T>() > vpn)
+            .min_by_key(|(_, area)| area.get_start::<T>())
+        {
+            Some((idx, area)) if area.grows_down => idx,
+            _ => return false,
+        };
+        let start = self.areas[idx].get_start::<T>();
+        if (self.areas[idx].get_end::<T>().0 - vpn.0) * PAGE_SIZE > GROWSDOWN_LIMIT {
+            warn!("[grow_down_to] {:?} is beyond the growth limit", vpn);
+            return false;
+        }
+        let guard = vpn.0.saturating_sub(1);
+        if self
+            .areas
+            .iter()
+            .any(|area| area.get_start::<T>() < start && area.get_end::<T>().0 > guard)
+        {
+            warn!("[grow_down_to] {:?} runs into the guard page", vpn);
+            return false;
+        }
+        trace!("[grow_down_to] grow area at {:?} down to {:?}", start, vpn);
+        self.areas[idx].inner.set_start(vpn).is_ok()
+    }
     #[cfg(feature = "loongarch64")]
     #[cfg(feature = "oom_handler")]
     pub fn do_shallow_clean(&mut self) -> usize {
@@ -566,18 +649,14 @@ impl<T: PageTable> MemorySet<T> {
         anonymous_identical_map!(
             "physical memory",
             ekernel,
-            MEMORY_END,
+            super::memory_end(),
             MapPermission::R | MapPermission::W
         );
 
         println!("mapping memory-mapped registers");
-        for pair in MMIO {
-            anonymous_identical_map!(
-                (*pair).0,
-                ((*pair).0 + (*pair).1),
-                MapPermission::R | MapPermission::W
-            );
-        }
+        crate::hal::fdt::for_each_mmio_region(|base, size| {
+            anonymous_identical_map!(base, base + size, MapPermission::R | MapPermission::W);
+        });
         memory_set
     }
     pub fn map_elf(&mut self, elf: &xmas_elf::ElfFile) -> Result<(usize, ELFInfo), isize> {
@@ -713,7 +792,7 @@ impl<T: PageTable> MemorySet<T> {
         }
         // map signaltrampoline
         memory_set.map_signaltrampoline();
-        let elf = xmas_elf::ElfFile::new(elf_data).unwrap();
+        let elf = xmas_elf::ElfFile::new(elf_data).map_err(|_| ENOEXEC)?;
         let (program_break, elf_info) = memory_set.map_elf(&elf)?;
 
         Ok((memory_set, program_break, elf_info))
@@ -742,6 +821,8 @@ impl<T: PageTable> MemorySet<T> {
                 user_space.areas[i].inner.vpn_range
             );
         }
+        // the parent has lost W on the shared pages, drop its writable translations
+        crate::mm::tlb_invalidate();
         // copy trap context area
         let trap_cx_area = user_space.areas.last().unwrap();
         let area = MapArea::from_another(trap_cx_area);
@@ -867,8 +948,10 @@ impl<T: PageTable> MemorySet<T> {
             if let Some(idx) = idx {
                 let area = &mut self.areas[idx];
                 if flags.contains(MapFlags::MAP_PRIVATE | MapFlags::MAP_ANONYMOUS)
+                    && !flags.contains(MapFlags::MAP_GROWSDOWN)
                     && prot == area.map_perm
                     && area.map_file.is_none()
+                    && !area.grows_down
                 {
                     debug!("[mmap] merge with previous area, call expand_to");
                     let end_va: VirtAddr = area.get_end::<T>().into();
@@ -887,6 +970,15 @@ impl<T: PageTable> MemorySet<T> {
                 }
             }
         };
+        let grows_down =
+            flags.contains(MapFlags::MAP_GROWSDOWN | MapFlags::MAP_ANONYMOUS);
+        // leave room below for the area to grow into
+        let start_va = if grows_down && !flags.contains(MapFlags::MAP_FIXED) {
+            let room = GROWSDOWN_LIMIT.saturating_sub(len) & !(PAGE_SIZE - 1);
+            VirtAddr::from(start_va.0 + room)
+        } else {
+            start_va
+        };
         let mut new_area = MapArea::new(
             start_va,
             VirtAddr::from(start_va.0 + len),
@@ -894,6 +986,7 @@ impl<T: PageTable> MemorySet<T> {
             prot,
             None,
         );
+        new_area.grows_down = grows_down;
         if !flags.contains(MapFlags::MAP_ANONYMOUS) {
             warn!("[mmap] file-backed map!");
             let fd_table = task.files.lock();
@@ -902,9 +995,17 @@ impl<T: PageTable> MemorySet<T> {
                     if !file_descriptor.readable() {
                         return EACCES;
                     }
-                    let file = file_descriptor.file.deep_clone();
-                    file.lseek(offset as isize, SeekWhence::SEEK_SET).unwrap();
-                    new_area.map_file = Some(file);
+                    // 共享映射的写入会写回文件
+                    if flags.contains(MapFlags::MAP_SHARED)
+                        && prot.contains(MapPermission::W)
+                        && !file_descriptor.writable()
+                    {
+                        return EACCES;
+                    }
+                    // 页面在首次访问时才从页缓存读入，这里只记下文件与偏移
+                    new_area.map_file = Some(file_descriptor.file.clone());
+                    new_area.map_offset = offset;
+                    new_area.shared = flags.contains(MapFlags::MAP_SHARED);
                 }
                 Err(errno) => return errno,
             }
@@ -940,6 +1041,73 @@ impl<T: PageTable> MemorySet<T> {
 
         start_va.0 as isize
     }
+    /// Fault in every page of `[start, start + len)` for `MAP_POPULATE`.
+    /// Best effort: stops at the first page that fails to fault in, e.g. beyond the end
+    /// of the file, or once free frames run low, as prefaulting should not force a reclaim.
+    /// Returns the number of pages faulted in.
+    pub fn populate(&mut self, start: usize, len: usize) -> usize {
+        let start_vpn = VirtAddr::from(start).floor();
+        let end_vpn = VirtAddr::from(start + len).ceil();
+        let mut populated = 0;
+        for vpn in VPNRange::new(start_vpn, end_vpn) {
+            if self.page_table.is_mapped(vpn) {
+                continue;
+            }
+            if super::unallocated_frames() < POPULATE_FRAMES_RESERVED {
+                warn!("[populate] free frames running low, stop at {:?}", vpn);
+                break;
+            }
+            if let Err(error) = self.do_page_fault(vpn.into()) {
+                warn!("[populate] stop at {:?}: {:?}", vpn, error);
+                break;
+            }
+            populated += 1;
+        }
+        populated
+    }
+    /// Residency of the pages of `[start, start + len)` for `mincore`, 1 if the page is in memory
+    pub fn mincore(&mut self, start: usize, len: usize) -> Result<Vec<u8>, isize> {
+        let start_va = VirtAddr::from(start);
+        if !start_va.aligned() {
+            return Err(EINVAL);
+        }
+        let end = start.checked_add(len).ok_or(ENOMEM)?;
+        let start_vpn = start_va.floor();
+        let end_vpn = VirtAddr::from(end).ceil();
+        let mut vec = Vec::with_capacity(end_vpn.0 - start_vpn.0);
+        for vpn in VPNRange::new(start_vpn, end_vpn) {
+            if !self
+                .areas
+                .iter()
+                .any(|area| area.get_start::<T>() <= vpn && vpn < area.get_end::<T>())
+            {
+                return Err(ENOMEM);
+            }
+            vec.push(self.page_table.is_mapped(vpn) as u8);
+        }
+        Ok(vec)
+    }
+    /// Files of the `MAP_SHARED` mappings overlapping `[start, start + len)`,
+    /// whose dirty pages `msync` and `munmap` write back.
+    pub fn shared_files(&self, start: usize, len: usize) -> Vec<Arc<dyn File>> {
+        let start_vpn = VirtAddr::from(start).floor();
+        let end_vpn = VirtAddr::from(start.saturating_add(len)).ceil();
+        let mut files: Vec<Arc<dyn File>> = Vec::new();
+        for area in self.areas.iter().filter(|area| area.shared) {
+            if area
+                .check_overlapping(start_vpn, end_vpn)
+                .filter(|(overlap_start, overlap_end)| overlap_start < overlap_end)
+                .is_none()
+            {
+                continue;
+            }
+            let file = area.map_file.as_ref().unwrap();
+            if !files.iter().any(|other| Arc::ptr_eq(other, file)) {
+                files.push(file.clone());
+            }
+        }
+        files
+    }
     pub fn munmap(&mut self, start: usize, len: usize) -> Result<(), isize> {
         let start_va = VirtAddr::from(start);
         let end_va = VirtAddr::from(start + len);
@@ -1002,6 +1170,46 @@ impl<T: PageTable> MemorySet<T> {
             Err(EINVAL)
         }
     }
+    /// MADV_FREE on `[start, start + len)`: the anonymous pages in it may be dropped
+    /// by reclaim instead of being compressed or swapped out, unless written to first.
+    /// Dropped pages read back as zero.
+    #[cfg(feature = "oom_handler")]
+    pub fn lazy_free(&mut self, start: usize, len: usize) -> Result<(), isize> {
+        let start_va = VirtAddr::from(start);
+        if !start_va.aligned() {
+            warn!("[lazy_free] Not aligned");
+            return Err(EINVAL);
+        }
+        let end = start.checked_add(len).ok_or(EINVAL)?;
+        let start_vpn = start_va.floor();
+        let end_vpn = VirtAddr::from(end).ceil();
+        let overlaps = |area: &MapArea| {
+            area.check_overlapping(start_vpn, end_vpn)
+                .filter(|(overlap_start, overlap_end)| overlap_start < overlap_end)
+        };
+        // only private anonymous memory may be dropped
+        if self
+            .areas
+            .iter()
+            .any(|area| overlaps(area).is_some() && area.map_file.is_some())
+        {
+            return Err(EINVAL);
+        }
+        let page_table = &mut self.page_table;
+        let mut covered = 0;
+        for area in self.areas.iter_mut() {
+            if let Some((overlap_start, overlap_end)) = overlaps(area) {
+                area.lazy_free(page_table, VPNRange::new(overlap_start, overlap_end));
+                covered += overlap_end.0 - overlap_start.0;
+            }
+        }
+        crate::mm::tlb_invalidate();
+        // pages outside of any area
+        if covered < end_vpn.0 - start_vpn.0 {
+            return Err(ENOMEM);
+        }
+        Ok(())
+    }
     pub fn mprotect(&mut self, addr: usize, len: usize, prot: usize) -> Result<(), isize> {
         let start_va = VirtAddr::from(addr);
         let end_va = VirtAddr::from(addr + len);
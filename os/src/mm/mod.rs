@@ -28,6 +28,8 @@ pub mod address;
 pub mod bitmap_alloc;
 mod frame_allocator;
 mod heap_allocator;
+#[cfg(feature = "kmemleak")]
+pub mod kmemleak;
 mod map_area;
 pub mod memory_builder;
 mod memory_set;
@@ -35,13 +37,15 @@ mod page_table;
 #[cfg(feature = "zram")]
 mod zram;
 
+use crate::config::{MEMORY_SIZE_MAX, MEMORY_SIZE_MIN, MEMORY_START};
+use crate::hal::MEMORY_END;
 pub use crate::hal::{KernelPageTableImpl, PageTableImpl};
 pub use address::PPNRange;
 use address::VPNRange;
 pub use address::{PhysAddr, PhysPageNum, StepByOne, VirtAddr, VirtPageNum};
 pub use frame_allocator::{
-    frame_alloc, frame_alloc_uninit, frame_dealloc, frame_reserve, frames_alloc,
-    unallocated_frames, FrameTracker,
+    frame_alloc, frame_alloc_uninit, frame_dealloc, frame_release_reserved, frame_reserve,
+    frames_alloc, unallocated_frames, FrameTracker,
 };
 pub use map_area::{Frame, MapFlags, MapPermission};
 pub use memory_set::{kernel_token, MemoryError, MemorySet, KERNEL_SPACE};
@@ -54,10 +58,41 @@ pub use page_table::{
 /// Initialize the memory management subsystem
 pub fn init() {
     heap_allocator::init_heap();
+    if let Some((base, size)) = crate::hal::fdt::memory_region() {
+        assert!(
+            size >= MEMORY_SIZE_MIN,
+            "[kernel] {:#x} bytes of RAM at {:#x}, at least {:#x} required",
+            size,
+            base,
+            MEMORY_SIZE_MIN
+        );
+        if size > MEMORY_SIZE_MAX {
+            println!(
+                "[kernel] only the first {:#x} of {:#x} bytes of RAM will be used",
+                MEMORY_SIZE_MAX, size
+            );
+        }
+    }
+    println!(
+        "[kernel] physical memory: [{:#x}, {:#x})",
+        MEMORY_START,
+        memory_end()
+    );
     frame_allocator::init_frame_allocator();
     KERNEL_SPACE.lock().activate();
 }
 
+/// End of the physical memory managed by the frame allocator
+///
+/// Taken from the device tree when it describes the RAM the kernel was linked for,
+/// clamped to `MEMORY_SIZE_MAX`; otherwise the board's `MEMORY_END`.
+pub fn memory_end() -> usize {
+    match crate::hal::fdt::memory_region() {
+        Some((base, size)) if base == MEMORY_START => base + size.min(MEMORY_SIZE_MAX),
+        _ => MEMORY_END,
+    }
+}
+
 pub use crate::hal::tlb_invalidate;
 
 #[macro_export]
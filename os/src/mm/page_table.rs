@@ -63,6 +63,11 @@ pub trait PageTable {
     fn set_pte_flags(&mut self, vpn: VirtPageNum, flags: MapPermission) -> Result<(), ()>;
     fn clear_access_bit(&mut self, vpn: VirtPageNum) -> Result<(), ()>;
     fn clear_dirty_bit(&mut self, vpn: VirtPageNum) -> Result<(), ()>;
+    /// Tag or untag a page that reclaim may drop instead of saving (`MADV_FREE`)
+    ///
+    /// The tag lives in a software bit of the PTE, so it is gone once the page is
+    /// unmapped or mapped again
+    fn set_freeable(&mut self, vpn: VirtPageNum, freeable: bool) -> Result<(), ()>;
 
     fn new() -> Self;
 
@@ -88,6 +93,7 @@ pub trait PageTable {
 
     fn is_valid(&self, vpn: VirtPageNum) -> Option<bool>;
     fn is_dirty(&self, vpn: VirtPageNum) -> Option<bool>;
+    fn is_freeable(&self, vpn: VirtPageNum) -> Option<bool>;
     fn readable(&self, vpn: VirtPageNum) -> Option<bool>;
     fn writable(&self, vpn: VirtPageNum) -> Option<bool>;
     fn executable(&self, vpn: VirtPageNum) -> Option<bool>;
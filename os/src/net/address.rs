@@ -3,8 +3,6 @@ use crate::utils::error::SyscallErr;
 use crate::utils::error::SyscallRet;
 use crate::utils::{error::GeneralRet, random::RNG};
 use core::convert::TryInto;
-use core::mem;
-use core::slice;
 use smoltcp::wire::{IpAddress, IpEndpoint, IpListenEndpoint, Ipv4Address, Ipv6Address};
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Ord)]
@@ -24,17 +22,15 @@ impl SocketAddrv4 {
         log::info!("[SocketAddrv4::new] new addr: {:?}", addr);
         addr
     }
-    pub fn fill(&self, addr_buf: &mut [u8], addrlen: usize) {
-        self._fill(addr_buf, addrlen);
+    /// `addr_buf` holds a whole `struct sockaddr_in`, `SOCKADDR_IN_LEN` bytes
+    pub fn fill(&self, addr_buf: &mut [u8]) {
+        self._fill(addr_buf);
     }
-    pub fn _fill(&self, addr_buf: &mut [u8], addrlen: usize) {
+    pub fn _fill(&self, addr_buf: &mut [u8]) {
         addr_buf.fill(0);
         addr_buf[0..2].copy_from_slice(u16::to_ne_bytes(AF_INET).as_slice());
         addr_buf[2..4].copy_from_slice(self.sin_port.as_slice());
         addr_buf[4..8].copy_from_slice(self.sin_addr.as_slice());
-        unsafe {
-            *(addrlen as *mut u32) = 8;
-        }
     }
 }
 
@@ -107,18 +103,16 @@ impl SocketAddrv6 {
         log::debug!("[SocketAddrv6::new] new addr: {:?}", addr);
         addr
     }
-    pub fn fill(&self, addr_buf: &mut [u8], addrlen: usize) {
-        self._fill(addr_buf, addrlen)
+    /// `addr_buf` holds a whole `struct sockaddr_in6`, `SOCKADDR_IN6_LEN` bytes
+    pub fn fill(&self, addr_buf: &mut [u8]) {
+        self._fill(addr_buf)
     }
-    pub fn _fill(&self, addr_buf: &mut [u8], addrlen: usize) {
+    pub fn _fill(&self, addr_buf: &mut [u8]) {
         addr_buf.fill(0);
         addr_buf[0..2].copy_from_slice(u16::to_ne_bytes(AF_INET6).as_slice());
         addr_buf[2..4].copy_from_slice(self.sin6_port.as_slice());
         addr_buf[4..8].copy_from_slice(self.sin6_flowinfo.as_slice());
         addr_buf[8..24].copy_from_slice(self.sin6_addr.as_slice());
-        unsafe {
-            *(addrlen as *mut u32) = 24;
-        }
     }
 }
 
@@ -199,7 +193,16 @@ pub fn _endpoint(addr_buf: &[u8]) -> GeneralRet<IpEndpoint> {
     Ok(IpEndpoint::new(addr, listen_endpoint.port))
 }
 use crate::net::current_task;
-use crate::mm::translated_refmut;
+use crate::mm::{copy_to_user, get_from_user, translated_byte_buffer, UserBuffer};
+
+/// `sizeof(struct sockaddr_in)`, the address is followed by 8 bytes of padding
+pub const SOCKADDR_IN_LEN: usize = 16;
+/// `sizeof(struct sockaddr_in6)`, the address is followed by the scope id
+pub const SOCKADDR_IN6_LEN: usize = 28;
+
+/// Writes `endpoint` to the user's `addr` like Linux does: the address is cut to the
+/// buffer length given in `*addrlen`, which then receives the full length.
+/// Nothing is written when `addr` is null.
 pub fn fill_with_endpoint(endpoint: IpEndpoint, addr: usize, addrlen: usize) -> SyscallRet {
     _fill_with_endpoint(endpoint, addr, addrlen)
 }
@@ -213,28 +216,42 @@ pub fn _fill_with_endpoint(endpoint: IpEndpoint, addr: usize, addrlen: usize) ->
         addr,
         endpoint
     );
-    let task = current_task().unwrap();
-    let token = task.get_user_token();
-    let addr = translated_refmut(token, addr as *mut u8).unwrap();
-    match endpoint.addr {
+    if addr == 0 {
+        return Ok(0);
+    }
+    let mut bytes = [0u8; SOCKADDR_IN6_LEN];
+    let bytes = match endpoint.addr {
         IpAddress::Ipv4(_) => {
-            let len = mem::size_of::<u16>() + mem::size_of::<SocketAddrv4>();
-            let addr_buf = unsafe { slice::from_raw_parts_mut(addr as *mut u8, len) };
-            SocketAddrv4::from(endpoint).fill(addr_buf, addrlen);
+            SocketAddrv4::from(endpoint).fill(&mut bytes[..SOCKADDR_IN_LEN]);
+            &bytes[..SOCKADDR_IN_LEN]
         }
         IpAddress::Ipv6(_) => {
-            let len = mem::size_of::<u16>() + mem::size_of::<SocketAddrv6>();
-            let addr_buf = unsafe { slice::from_raw_parts_mut(addr as *mut u8, len) };
-            SocketAddrv6::from(endpoint).fill(addr_buf, addrlen);
+            SocketAddrv6::from(endpoint).fill(&mut bytes);
+            &bytes[..]
         }
+    };
+    let token = current_task().unwrap().get_user_token();
+    let len = get_from_user(token, addrlen as *const u32).map_err(|_| SyscallErr::EFAULT)?;
+    let len = (len as usize).min(bytes.len());
+    if len > 0 {
+        let buffer = translated_byte_buffer(token, addr as *const u8, len)
+            .map_err(|_| SyscallErr::EFAULT)?;
+        UserBuffer::new(buffer).write(&bytes[..len]);
     }
+    copy_to_user(token, &(bytes.len() as u32), addrlen as *mut u32)
+        .map_err(|_| SyscallErr::EFAULT)?;
     Ok(0)
 }
 
 pub fn _listen_endpoint(addr_buf: &[u8]) -> GeneralRet<IpListenEndpoint> {
+    if addr_buf.len() < 2 {
+        return Err(SyscallErr::EINVAL);
+    }
     let family = u16::from_ne_bytes(addr_buf[0..2].try_into().expect("family size wrong"));
     log::info!("[address::listen_enpoint] addr family {}", family);
     match family {
+        AF_INET if addr_buf.len() < SOCKADDR_IN_LEN => Err(SyscallErr::EINVAL),
+        AF_INET6 if addr_buf.len() < 24 => Err(SyscallErr::EINVAL),
         AF_INET => {
             let ipv4 = SocketAddrv4::new(addr_buf);
             Ok(IpListenEndpoint::from(ipv4))
@@ -1,17 +1,25 @@
+use super::device::LinkDevice;
+use super::loopback::{Loopback, LOOPBACK_MAC};
+use crate::drivers::NET_DEVICE;
 use crate::timer::current_time_duration;
 use alloc::vec;
 use smoltcp::{
     iface::{Config, Interface, SocketHandle, SocketSet},
-    phy::{Device, Loopback, Medium},
     socket::{tcp, udp, AnySocket},
     time::Instant,
-    wire::{EthernetAddress, IpAddress, IpCidr},
+    wire::{EthernetAddress, IpAddress, IpCidr, Ipv4Address},
 };
 
 use spin::Mutex;
 
 pub static NET_INTERFACE: NetInterface = NetInterface::new();
 
+/// Address on the network card, the one QEMU's user mode network hands out
+const CARD_IPV4: Ipv4Address = Ipv4Address([10, 0, 2, 15]);
+const CARD_IPV4_PREFIX: u8 = 24;
+/// Default gateway behind the network card, QEMU's user mode network router
+const CARD_GATEWAY: Ipv4Address = Ipv4Address([10, 0, 2, 2]);
+
 pub fn init() {
     NET_INTERFACE.init();
 }
@@ -21,21 +29,18 @@ pub struct NetInterface<'a> {
 }
 
 pub struct NetInterfaceInner<'a> {
-    pub device: Loopback,
+    pub device: LinkDevice,
     pub iface: Interface,
     pub sockets: SocketSet<'a>,
 }
 
 impl<'a> NetInterfaceInner<'a> {
     fn new() -> Self {
-        let mut device = Loopback::new(Medium::Ethernet);
+        let card = NET_DEVICE.clone();
+        let mac = EthernetAddress(card.as_ref().map_or(LOOPBACK_MAC, |card| card.mac_address()));
+        let mut device = LinkDevice::new(Loopback::new(), card, mac);
         let iface = {
-            let config = match device.capabilities().medium {
-                Medium::Ethernet => {
-                    Config::new(EthernetAddress([0x02, 0x00, 0x00, 0x00, 0x00, 0x01]).into())
-                }
-                Medium::Ip => Config::new(smoltcp::wire::HardwareAddress::Ip),
-            };
+            let config = Config::new(mac.into());
 
             let mut iface = Interface::new(
                 config,
@@ -50,6 +55,26 @@ impl<'a> NetInterfaceInner<'a> {
                     .push(IpCidr::new(IpAddress::v6(0, 0, 0, 0, 0, 0, 0, 1), 128))
                     .unwrap();
             });
+            let mut local_ipv4 = vec![Ipv4Address::new(127, 0, 0, 1)];
+            if NET_DEVICE.is_some() {
+                iface.update_ip_addrs(|ip_addrs| {
+                    ip_addrs
+                        .push(IpCidr::new(CARD_IPV4.into(), CARD_IPV4_PREFIX))
+                        .unwrap();
+                });
+                iface
+                    .routes_mut()
+                    .add_default_ipv4_route(CARD_GATEWAY)
+                    .unwrap();
+                local_ipv4.push(CARD_IPV4);
+                log::info!(
+                    "[NetInterface] card {} at {}/{}",
+                    mac,
+                    CARD_IPV4,
+                    CARD_IPV4_PREFIX
+                );
+            }
+            device.set_local_ipv4(local_ipv4);
             iface
         };
         Self {
@@ -0,0 +1,141 @@
+//! The link layer under the smoltcp interface
+//!
+//! The interface sits on two devices: the software [`Loopback`] and, when one
+//! was found, a network card. Frames for the interface itself go to the
+//! loopback device and never reach the card, neither does anything for
+//! `127.0.0.0/8` or `::1`. Broadcast and multicast frames go to the card and
+//! are heard locally as well, except ARP requests for our own or loopback
+//! addresses, which only make sense locally. Without a card every frame loops
+//! back.
+
+use super::loopback::Loopback;
+use crate::drivers::NetDevice;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use smoltcp::phy::{self, DeviceCapabilities, Medium};
+use smoltcp::time::Instant;
+use smoltcp::wire::{
+    ArpPacket, EthernetAddress, EthernetFrame, EthernetProtocol, Ipv4Address, Ipv4Packet,
+    Ipv6Packet,
+};
+
+/// Frame size limit on the card, 1500 bytes of payload and the Ethernet header
+const ETHERNET_MTU: usize = 1514;
+/// Frame size limit when everything loops back, as large as an IP packet can be
+const LOOPBACK_MTU: usize = 65535;
+
+pub struct LinkDevice {
+    loopback: Loopback,
+    card: Option<Arc<dyn NetDevice>>,
+    mac: EthernetAddress,
+    /// IPv4 addresses of the interface, ARP requests for them stay local
+    local_ipv4: Vec<Ipv4Address>,
+}
+
+impl LinkDevice {
+    pub fn new(loopback: Loopback, card: Option<Arc<dyn NetDevice>>, mac: EthernetAddress) -> Self {
+        Self {
+            loopback,
+            card,
+            mac,
+            local_ipv4: Vec::new(),
+        }
+    }
+
+    pub fn set_local_ipv4(&mut self, addrs: Vec<Ipv4Address>) {
+        self.local_ipv4 = addrs;
+    }
+
+    /// Whether `frame` is only meant for this host: an ARP request for one of
+    /// our own or a loopback address, or an IP packet to a loopback address
+    fn stays_local(&self, frame: &EthernetFrame<&[u8]>) -> bool {
+        match frame.ethertype() {
+            EthernetProtocol::Arp => match ArpPacket::new_checked(frame.payload()) {
+                Ok(arp) => {
+                    let target = Ipv4Address::from_bytes(arp.target_protocol_addr());
+                    target.is_loopback() || self.local_ipv4.contains(&target)
+                }
+                Err(_) => false,
+            },
+            EthernetProtocol::Ipv4 => Ipv4Packet::new_checked(frame.payload())
+                .map_or(false, |packet| packet.dst_addr().is_loopback()),
+            EthernetProtocol::Ipv6 => Ipv6Packet::new_checked(frame.payload())
+                .map_or(false, |packet| packet.dst_addr().is_loopback()),
+            _ => false,
+        }
+    }
+
+    fn dispatch(&mut self, frame: Vec<u8>) {
+        let card = match &self.card {
+            Some(card) => card.clone(),
+            None => return self.loopback.transmit(&frame),
+        };
+        let (to_us, to_card) = match EthernetFrame::new_checked(&frame[..]) {
+            Ok(parsed) if parsed.dst_addr() == self.mac => (true, false),
+            Ok(parsed) if self.stays_local(&parsed) => (true, false),
+            Ok(parsed) if parsed.dst_addr().is_multicast() => (true, true),
+            Ok(_) => (false, true),
+            Err(_) => (false, false),
+        };
+        if to_card {
+            card.transmit(&frame);
+        }
+        if to_us {
+            self.loopback.transmit(&frame);
+        }
+    }
+}
+
+impl phy::Device for LinkDevice {
+    type RxToken<'a> = RxToken;
+    type TxToken<'a> = TxToken<'a>;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let frame = match self.loopback.receive() {
+            Some(frame) => frame,
+            None => self.card.as_ref()?.receive()?,
+        };
+        Some((RxToken(frame), TxToken(self)))
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        Some(TxToken(self))
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.medium = Medium::Ethernet;
+        caps.max_transmission_unit = if self.card.is_some() {
+            ETHERNET_MTU
+        } else {
+            LOOPBACK_MTU
+        };
+        caps
+    }
+}
+
+pub struct RxToken(Vec<u8>);
+
+impl phy::RxToken for RxToken {
+    fn consume<R, F>(mut self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        f(&mut self.0)
+    }
+}
+
+pub struct TxToken<'a>(&'a mut LinkDevice);
+
+impl<'a> phy::TxToken for TxToken<'a> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut frame = vec![0; len];
+        let result = f(&mut frame);
+        self.0.dispatch(frame);
+        result
+    }
+}
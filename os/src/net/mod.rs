@@ -1,6 +1,7 @@
 #[allow(unused)]
 use crate::{
     fs::{file_descriptor::FileDescriptor, file_trait::File, OpenFlags},
+    mm::UserBuffer,
     net::{tcp::TcpSocket, udp::UdpSocket},
     task::current_task,
     utils::error::{GeneralRet, SyscallErr, SyscallRet},
@@ -13,6 +14,8 @@ use spin::Mutex;
 
 pub mod address;
 pub mod config;
+mod device;
+mod loopback;
 mod tcp;
 mod udp;
 mod unix;
@@ -20,7 +23,7 @@ mod unix;
 pub type Fd = usize;
 
 pub use tcp::TCP_MSS;
-pub use unix::make_unix_socket_pair;
+pub use unix::{make_unix_socket_pair, UnixAddr, UnixSocket};
 // pub use unix::UNIX_SOCKET_BUF_MANAGER;
 
 /// domain
@@ -29,7 +32,6 @@ pub const AF_INET: u16 = 2;
 pub const AF_INET6: u16 = 10;
 
 /// shutdown
-#[allow(unused)]
 pub const SHUT_RD: u32 = 0;
 pub const SHUT_WR: u32 = 1;
 #[allow(unused)]
@@ -42,11 +44,25 @@ bitflags! {
         const SOCK_STREAM = 1 << 0;
         /// for UDP
         const SOCK_DGRAM = 1 << 1;
+        const SOCK_NONBLOCK = 1 << 11;
         /// unused now
         const SOCK_CLOEXEC = 1 << 19;
     }
 }
 
+impl SocketType {
+    /// The type without SOCK_NONBLOCK and SOCK_CLOEXEC, if it is exactly one of
+    /// SOCK_STREAM and SOCK_DGRAM
+    pub fn kind(self) -> Option<SocketType> {
+        let kind = self & (SocketType::SOCK_STREAM | SocketType::SOCK_DGRAM);
+        if kind == SocketType::SOCK_STREAM || kind == SocketType::SOCK_DGRAM {
+            Some(kind)
+        } else {
+            None
+        }
+    }
+}
+
 // pub const MAX_BUFFER_SIZE: usize = 1 << 15;
 // pub const MAX_BUFFER_SIZE: usize = 1 << 16;
 pub const MAX_BUFFER_SIZE: usize = 1 << 17;
@@ -66,6 +82,18 @@ pub trait Socket: File {
     fn shutdown(&self, how: u32) -> GeneralRet<()>;
     fn set_nagle_enabled(&self, enabled: bool) -> SyscallRet;
     fn set_keep_alive(&self, enabled: bool) -> SyscallRet;
+    /// Sends `buf`, to `dest` instead of the connected peer if given
+    fn send_to(&self, buf: UserBuffer, dest: Option<IpEndpoint>) -> SyscallRet;
+    /// Receives into `buf`, returning the length and where the data came from
+    fn recv_from(&self, buf: UserBuffer) -> GeneralRet<(usize, Option<IpEndpoint>)>;
+}
+
+/// Whether the current task has an unblocked signal pending, a blocking socket call
+/// then gives up with EINTR
+fn interrupted() -> bool {
+    let task = current_task().unwrap();
+    let inner = task.acquire_inner_lock();
+    !(inner.sigpending - inner.sigmask).is_empty()
 }
 
 impl dyn Socket {
@@ -80,47 +108,33 @@ impl dyn Socket {
                     OpenFlags::O_RDWR
                 };
                 info!("[Socket::alloc] flags: {:?}", flags);
-                if socket_type.contains(SocketType::SOCK_DGRAM) {
-                    let socket = UdpSocket::new();
-                    let socket = Arc::new(socket);
-                    // current_process().inner_handler(|proc| {
-                    //     let fd = proc.fd_table.alloc_fd()?;
-                    //     proc.fd_table.put(fd, FdInfo::new(socket.clone(), flags));
-                    //     proc.socket_table.insert(fd, socket);
-                    //     Ok(fd)
-                    // })
-                    let current_tcb = current_task().unwrap();
-                    let fd = current_tcb.files.lock().insert(FileDescriptor::new(false, false, socket.clone())).unwrap();
-                    current_tcb.socket_table.lock().insert(fd, socket);
-                    Ok(fd)
-                } else if socket_type.contains(SocketType::SOCK_STREAM) {
-                    let socket = TcpSocket::new();
-                    let socket = Arc::new(socket);
-                    // current_process().inner_handler(|proc| {
-                    //     let fd = proc.fd_table.alloc_fd()?;
-                    //     proc.fd_table.put(fd, FdInfo::new(socket.clone(), flags));
-                    //     proc.socket_table.insert(fd, socket);
-                    //     Ok(fd)
-                    // })
-                    let current_tcb = current_task().unwrap();
-                    let fd = current_tcb.files.lock().insert(FileDescriptor::new(false, false, socket.clone())).unwrap();
-                    current_tcb.socket_table.lock().insert(fd, socket);
-                    Ok(fd)
-                } else {
-                    Err(SyscallErr::EINVAL)
-                }
+                let nonblock = socket_type.contains(SocketType::SOCK_NONBLOCK);
+                let socket: Arc<dyn Socket> = match socket_type.kind() {
+                    Some(SocketType::SOCK_DGRAM) => UdpSocket::new(nonblock),
+                    Some(SocketType::SOCK_STREAM) => TcpSocket::new(nonblock),
+                    _ => return Err(SyscallErr::EINVAL),
+                };
+                let current_tcb = current_task().unwrap();
+                let fd = current_tcb
+                    .files
+                    .lock()
+                    .insert(FileDescriptor::new(
+                        flags.contains(OpenFlags::O_CLOEXEC),
+                        nonblock,
+                        socket.clone(),
+                    ))
+                    .map_err(|_| SyscallErr::EMFILE)?;
+                current_tcb.socket_table.lock().insert(fd, socket);
+                Ok(fd)
             }
             AF_UNIX => {
-                Ok(4)
-                // todo!()
-                // let socket = UnixSocket::new();
-                // let socket = Arc::new(Socket::UnixSocket(socket));
-                // current_process().inner_handler(|proc| {
-                //     let fd = proc.fd_table.alloc_fd()?;
-                //     proc.fd_table.put(fd, socket.clone());
-                //     proc.socket_table.insert(fd, socket);
-                //     Ok(fd)
-                // })
+                let socket_type = SocketType::from_bits(socket_type).ok_or(SyscallErr::EINVAL)?;
+                let kind = socket_type.kind().ok_or(SyscallErr::EINVAL)?;
+                let socket =
+                    UnixSocket::new(kind, socket_type.contains(SocketType::SOCK_NONBLOCK));
+                socket
+                    .install(socket_type.contains(SocketType::SOCK_CLOEXEC))
+                    .map_err(|_| SyscallErr::EMFILE)
             }
             _ => Err(SyscallErr::EINVAL),
         }
@@ -153,7 +167,6 @@ impl SocketTable {
         
         self.0.get(&fd)
     }
-    #[allow(unused)]
     pub fn take(&mut self, fd: Fd) -> Option<Arc<dyn Socket>> {
         
         self.0.remove(&fd)
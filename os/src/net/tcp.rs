@@ -1,10 +1,10 @@
-use super::{Mutex, Socket};
+use super::{interrupted, Mutex, Socket};
 use crate::{
-    fs::{file_trait::File, FileDescriptor, OpenFlags}, net::{
+    fs::{file_trait::File, FileDescriptor, OpenFlags, StatMode}, net::{
         address,
         config::NET_INTERFACE,
         MAX_BUFFER_SIZE, SHUT_WR,
-    }, task::current_task, utils::{
+    }, syscall::errno::*, task::current_task, utils::{
         error::{GeneralRet, SyscallErr, SyscallRet},
         random::RNG,
     }
@@ -41,6 +41,9 @@ pub const TCP_MSS: u32 = if TCP_MSS_DEFAULT > MAX_BUFFER_SIZE as u32 {
 pub struct TcpSocket {
     inner: Mutex<TcpSocketInner>,
     socket_handler: SocketHandle,
+    /// SOCK_NONBLOCK：读写、connect 与 accept 不等待
+    nonblock: bool,
+    selfptr: Weak<TcpSocket>,
 }
 
 #[allow(unused)]
@@ -75,26 +78,29 @@ impl Socket for TcpSocket {
     }
 
     fn accept(&self, sockfd: u32, addr: usize, addrlen: usize) -> crate::utils::error::SyscallRet {
-        // get old socket
+        // 等待连接时不能持有 fd 表的锁
         let task = current_task().unwrap();
-        let mut fd_table = task.files.lock();
-        let mut socket_table = task.socket_table.lock();
-        let old_file = fd_table.get_ref(sockfd as usize).unwrap();
-        let old_nonblock = old_file.get_nonblock();
-        let old_cloexec = old_file.get_cloexec();
+        let (old_nonblock, old_cloexec) = {
+            let fd_table = task.files.lock();
+            let old_file = fd_table
+                .get_ref(sockfd as usize)
+                .map_err(|_| SyscallErr::EBADF)?;
+            (old_file.get_nonblock(), old_file.get_cloexec())
+        };
 
-        let peer_addr = self._accept(old_nonblock)?;
+        let peer_addr = self._accept(old_nonblock || self.nonblock)?;
         log::info!("[Socket::accept] get peer_addr: {:?}", peer_addr);
         let local = self.loacl_endpoint();
         log::info!("[Socket::accept] new socket try bind to : {:?}", local);
-        let new_socket = TcpSocket::new();
+        let new_socket = TcpSocket::new(self.nonblock);
         use core::convert::TryInto;
         new_socket.bind(local.try_into().expect("cannot convert to ListenEndpoint"))?;
         log::info!("[Socket::accept] new socket listen");
         new_socket.listen()?;
         address::fill_with_endpoint(peer_addr, addr, addrlen)?;
-        let new_socket = Arc::new(new_socket);
         log::debug!("[Socket::accept] take old sock");
+        let mut fd_table = task.files.lock();
+        let mut socket_table = task.socket_table.lock();
         // 取出旧的
         let old_file = fd_table.take(sockfd as usize).unwrap();
         let old_socket: Option<Arc<dyn Socket>> =
@@ -118,27 +124,34 @@ impl Socket for TcpSocket {
         super::SocketType::SOCK_STREAM
     }
 
+    /// 对端回复 RST 时返回 ECONNREFUSED；非阻塞时发出 SYN 后即返回 EINPROGRESS
     fn connect<'a>(&'a self, addr_buf: &'a [u8]) -> crate::utils::error::SyscallRet {
         let remote_endpoint = address::endpoint(addr_buf)?;
         self._connect(remote_endpoint)?;
         loop {
+            NET_INTERFACE.poll();
             let state = NET_INTERFACE.tcp_socket(self.socket_handler, |socket| socket.state());
             match state {
                 tcp::State::Closed => {
-                    // close but not already connect, retry
-                    info!("[Tcp::connect] {} already closed, try again", self.socket_handler);
-                    self._connect(remote_endpoint)?;
+                    info!("[Tcp::connect] {} refused", self.socket_handler);
+                    return Err(SyscallErr::ECONNREFUSED);
                 }
                 tcp::State::Established => {
                     info!("[Tcp::connect] {} connected, state {:?}", self.socket_handler, state);
+                    self.inner.lock().last_state = state;
                     return Ok(0);
                 }
                 _ => {
                     info!("[Tcp::connect] {} not connect yet, state {:?}", self.socket_handler, state);
                 }
             }
+            if self.nonblock {
+                return Err(SyscallErr::EINPROGRESS);
+            }
+            if interrupted() {
+                return Err(SyscallErr::EINTR);
+            }
             suspend_current_and_run_next();
-            // thread::sleep(Duration::from_secs(1));
         }
     }
     fn recv_buf_size(&self) -> usize {
@@ -193,17 +206,31 @@ impl Socket for TcpSocket {
         }
         Ok(0)
     }
+
+    /// 已连接的流套接字忽略 `dest`
+    fn send_to(&self, buf: UserBuffer, _dest: Option<IpEndpoint>) -> SyscallRet {
+        let mut data = vec![0u8; buf.len()];
+        buf.read(&mut data);
+        self._write(&data)
+    }
+
+    fn recv_from(&self, mut buf: UserBuffer) -> GeneralRet<(usize, Option<IpEndpoint>)> {
+        let mut data = vec![0u8; buf.len().min(MAX_BUFFER_SIZE)];
+        let len = self._read(&mut data)?;
+        buf.write(&data[..len]);
+        Ok((len, self.remote_endpoint()))
+    }
 }
 
 impl TcpSocket {
-    pub fn new() -> Self {
+    pub fn new(nonblock: bool) -> Arc<Self> {
         let tx_buf = socket::tcp::SocketBuffer::new(vec![0 as u8; MAX_BUFFER_SIZE]);
         let rx_buf = socket::tcp::SocketBuffer::new(vec![0 as u8; MAX_BUFFER_SIZE]);
         let socket = socket::tcp::Socket::new(rx_buf, tx_buf);
         let socket_handler = NET_INTERFACE.add_socket(socket);
         info!("[TcpSocket::new] new {}", socket_handler);
         NET_INTERFACE.poll();
-        Self {
+        Arc::new_cyclic(|selfptr| Self {
             socket_handler,
             inner: Mutex::new(TcpSocketInner {
                 local_endpoint: IpListenEndpoint {
@@ -215,7 +242,9 @@ impl TcpSocket {
                 recvbuf_size: MAX_BUFFER_SIZE,
                 sendbuf_size: MAX_BUFFER_SIZE,
             }),
-        }
+            nonblock,
+            selfptr: selfptr.clone(),
+        })
     }
 
     fn _connect(&self, remote_endpoint: IpEndpoint) -> GeneralRet<()> {
@@ -255,21 +284,15 @@ impl TcpSocket {
                     log::info!("[TcpAcceptFuture::poll] state become {:?}", socket.state());
                     return Ok(socket.remote_endpoint().unwrap());
                 }
-                // log::info!(
-                //     "[TcpAcceptFuture::poll] not syn yet, state {:?}",
-                //     socket.state()
-                // );
-                if nonblock {
-                    log::info!("[TcpAcceptFuture::poll] flags set nonblock");
-                    return Err(SyscallErr::EAGAIN);
-                }
-                // 使用 continue 跳过当前循环并开始下一次迭代
-                return Err(SyscallErr::EAGAIN);
+                Err(SyscallErr::EAGAIN)
             });
             NET_INTERFACE.poll();
             match ret {
                 Ok(endpoint) => return GeneralRet::Ok(endpoint),
-                Err(SyscallErr::EAGAIN) => {
+                Err(SyscallErr::EAGAIN) if !nonblock => {
+                    if interrupted() {
+                        return Err(SyscallErr::EINTR);
+                    }
                     suspend_current_and_run_next();
                     // 如果返回 EAGAIN 错误，继续循环
                     continue;
@@ -278,6 +301,18 @@ impl TcpSocket {
             }
         }
     }
+    /// 是否曾经建立过连接：从未连接的套接字读写返回 ENOTCONN，
+    /// 连接关闭后读到文件末尾、写返回 EPIPE
+    fn was_connected(&self) -> bool {
+        !matches!(
+            self.inner.lock().last_state,
+            tcp::State::Closed | tcp::State::Listen
+        )
+    }
+    /// 监听中的套接字是否有连接等待 accept
+    fn is_listening(&self) -> bool {
+        self.inner.lock().last_state == tcp::State::Listen
+    }
 }
 use crate::task::suspend_current_and_run_next;
 impl Drop for TcpSocket {
@@ -301,148 +336,227 @@ impl Drop for TcpSocket {
 }
 
 impl File for TcpSocket {
-    fn deep_clone(&self) -> Arc<dyn File>{
-        todo!();
+    /// dup 得到的 fd 与原 fd 共用同一个套接字
+    fn deep_clone(&self) -> Arc<dyn File> {
+        self.selfptr.upgrade().unwrap()
     }
-    fn readable(&self) -> bool{
+    fn readable(&self) -> bool {
         true
     }
-    fn writable(&self) -> bool{
+    fn writable(&self) -> bool {
         true
     }
-    fn read(&self, _offset: Option<&mut usize>, buf: &mut [u8]) -> usize{
-        let ret = self._read(buf).unwrap();
-        ret
+    fn read(&self, offset: Option<&mut usize>, buf: &mut [u8]) -> usize {
+        if offset.is_some() {
+            return ESPIPE as usize;
+        }
+        match self._read(buf) {
+            Ok(len) => len,
+            Err(err) => -(err as isize) as usize,
+        }
     }
-    fn write(&self, _offset: Option<&mut usize>, buf: &[u8]) -> usize{
+    fn write(&self, offset: Option<&mut usize>, buf: &[u8]) -> usize {
+        if offset.is_some() {
+            return ESPIPE as usize;
+        }
+        match self._write(buf) {
+            Ok(len) => len,
+            Err(err) => -(err as isize) as usize,
+        }
+    }
+    /// 有数据可读、对端已关闭，或监听中有连接等待 accept 时可读
+    fn r_ready(&self) -> bool {
+        let listening = self.is_listening();
         NET_INTERFACE.poll();
-        let ret = NET_INTERFACE.tcp_socket(self.socket_handler, |socket| {
-            if !socket.may_send() {
-                log::info!("[TcpSendFuture::poll] err when send");
-                return SyscallErr::ENOTCONN as usize;
-            }
-            if !socket.can_send() {
-                log::info!("[TcpSendFuture::poll] cannot send yet");
-                suspend_current_and_run_next();
-                return SyscallErr::EAGAIN as usize;
-            }
-            log::info!("[TcpSendFuture::poll] start to send...");
-            info!(
-                "[TcpSendFuture::poll] {:?} -> {:?}",
-                socket.local_endpoint(),
-                socket.remote_endpoint()
-            );
-            match socket.send_slice(buf) {
-                Ok(nbytes) => {
-                    info!("[TcpSendFuture::poll] send {} bytes", nbytes);
-                    return nbytes;
-                }
-                Err(_) => SyscallErr::ENOTCONN as usize,
-            }
-        });
+        NET_INTERFACE.tcp_socket(self.socket_handler, |socket| match socket.state() {
+            tcp::State::Listen | tcp::State::SynSent => false,
+            tcp::State::SynReceived => listening,
+            tcp::State::Established if listening => true,
+            _ => socket.can_recv() || !socket.may_recv(),
+        })
+    }
+    /// 发送缓冲区有空间，或连接已不能再写（写会立即返回错误）时可写
+    fn w_ready(&self) -> bool {
         NET_INTERFACE.poll();
-        ret
+        NET_INTERFACE.tcp_socket(self.socket_handler, |socket| match socket.state() {
+            tcp::State::Listen | tcp::State::SynSent | tcp::State::SynReceived => false,
+            _ => socket.can_send() || !socket.may_send(),
+        })
     }
-    fn r_ready(&self) -> bool{true}
-    fn w_ready(&self) -> bool{true}
-    fn read_user(&self, _offset: Option<usize>, buf: UserBuffer) -> usize{
-        let mut buffers = buf.buffers;
-        let buf = unsafe { core::slice::from_raw_parts_mut(buffers[0].as_mut_ptr() as *mut u8, buf.len as usize) };
-        let ret = self._read(buf).unwrap();
-        ret
+    fn read_user(&self, offset: Option<usize>, mut buf: UserBuffer) -> usize {
+        if offset.is_some() {
+            return ESPIPE as usize;
+        }
+        let mut data = vec![0u8; buf.len().min(MAX_BUFFER_SIZE)];
+        match self._read(&mut data) {
+            Ok(len) => buf.write(&data[..len]),
+            Err(err) => -(err as isize) as usize,
+        }
+    }
+    fn write_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
+        if offset.is_some() {
+            return ESPIPE as usize;
+        }
+        let mut data = vec![0u8; buf.len()];
+        buf.read(&mut data);
+        match self._write(&data) {
+            Ok(len) => len,
+            Err(err) => -(err as isize) as usize,
+        }
+    }
+    fn get_size(&self) -> usize {
+        0
+    }
+    fn get_stat(&self) -> Stat {
+        Stat::new(0, 1, StatMode::S_IFSOCK.bits() | 0o777, 1, 0, 0, 0, 0, 0)
+    }
+    fn get_file_type(&self) -> DiskInodeType {
+        DiskInodeType::Socket
+    }
+    fn info_dirtree_node(&self, _dirnode_ptr: Weak<DirectoryTreeNode>) {}
+    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>> {
+        None
     }
-    fn write_user(&self, _offset: Option<usize>, buf: UserBuffer) -> usize{
-        let mut buffers = buf.buffers;
-        let buf = unsafe { core::slice::from_raw_parts_mut(buffers[0].as_mut_ptr() as *mut u8, buf.len as usize) };
-        self.write(None, buf)
-    }
-    fn get_size(&self) -> usize{todo!();}
-    fn get_stat(&self) -> Stat{todo!();}
-    fn get_file_type(&self) -> DiskInodeType{todo!();}
-    fn is_dir(&self) -> bool {todo!();}
-    fn is_file(&self) -> bool {todo!();}
-    fn info_dirtree_node(&self, _dirnode_ptr: Weak<DirectoryTreeNode>){todo!();}
-    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>>{todo!();}
     /// open
-    fn open(&self, _flags: OpenFlags, _special_use: bool) -> Arc<dyn File>{todo!();}
-    fn open_subfile(&self) -> Result<Vec<(String, Arc<dyn File>)>, isize>{todo!();}
+    fn open(&self, _flags: OpenFlags, _special_use: bool) -> Arc<dyn File> {
+        self.deep_clone()
+    }
+    fn open_subfile(&self) -> Result<Vec<(String, Arc<dyn File>)>, isize> {
+        Err(ENOTDIR)
+    }
     /// create
-    fn create(&self, _name: &str, _file_type: DiskInodeType) -> Result<Arc<dyn File>, isize>{todo!();}
-    fn link_child(&self, _name: &str, _child: &Self) -> Result<(), isize>{todo!();}
+    fn create(&self, _name: &str, _file_type: DiskInodeType) -> Result<Arc<dyn File>, isize> {
+        Err(ENOTDIR)
+    }
+    fn link_child(&self, _name: &str, _child: &Self) -> Result<(), isize> {
+        Err(ENOTDIR)
+    }
     /// delete(unlink)
-    fn unlink(&self, _delete: bool) -> Result<(), isize>{todo!();}
+    fn unlink(&self, _delete: bool) -> Result<(), isize> {
+        Err(EACCES)
+    }
     /// dirent
-    fn get_dirent(&self, _count: usize) -> Vec<Dirent>{todo!();}
+    fn get_dirent(&self, _count: usize) -> Vec<Dirent> {
+        Vec::new()
+    }
     /// offset
-    fn get_offset(&self) -> usize {todo!();}
-    fn lseek(&self, _offset: isize, _whence: SeekWhence) -> Result<usize, isize>{todo!();}
+    fn get_offset(&self) -> usize {
+        0
+    }
+    fn lseek(&self, _offset: isize, _whence: SeekWhence) -> Result<usize, isize> {
+        Err(ESPIPE)
+    }
     /// size
-    fn modify_size(&self, _diff: isize) -> Result<(), isize>{todo!();}
-    fn truncate_size(&self, _new_size: usize) -> Result<(), isize>{todo!();}
+    fn modify_size(&self, _diff: isize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+    fn truncate_size(&self, _new_size: usize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
     // time
-    fn set_timestamp(&self, _ctime: Option<usize>, _atime: Option<usize>, _mtime: Option<usize>){todo!();}
+    fn set_timestamp(&self, _ctime: Option<usize>, _atime: Option<usize>, _mtime: Option<usize>) {}
     /// cache
-    fn get_single_cache(&self, _offset: usize) -> Result<Arc<Mutex<PageCache>>, ()>{todo!();}
-    fn get_all_caches(&self) -> Result<Vec<Arc<Mutex<PageCache>>>, ()>{todo!();}
+    fn get_single_cache(&self, _offset: usize) -> Result<Arc<Mutex<PageCache>>, ()> {
+        Err(())
+    }
+    fn get_all_caches(&self) -> Result<Vec<Arc<Mutex<PageCache>>>, ()> {
+        Err(())
+    }
     /// memory related
-    fn oom(&self) -> usize{todo!();}
+    fn oom(&self) -> usize {
+        0
+    }
     /// poll, select related
-    fn hang_up(&self) -> bool{todo!();}
-    /// iotcl
-    fn ioctl(&self, _cmd: u32, _argp: usize) -> isize {todo!();}
+    /// 连接已完全关闭
+    fn hang_up(&self) -> bool {
+        self.was_connected()
+            && NET_INTERFACE.tcp_socket(self.socket_handler, |socket| !socket.is_open())
+    }
     /// fcntl
-    fn fcntl(&self, _cmd: u32, _arg: u32) -> isize{todo!();}
-
-    
+    fn fcntl(&self, _cmd: u32, _arg: u32) -> isize {
+        EINVAL
+    }
 }
 
 impl TcpSocket {
+    /// 读到数据、对端关闭（返回 0）或出错为止；没有数据时非阻塞的套接字返回 EAGAIN
     fn _read<'a>(&'a self, buf: &'a mut [u8]) -> GeneralRet<usize> {
+        let was_connected = self.was_connected();
         loop {
             NET_INTERFACE.poll();
             let ret = NET_INTERFACE.tcp_socket(self.socket_handler, |socket| {
-                if socket.state() == tcp::State::CloseWait || 
-                socket.state() == tcp::State::TimeWait || socket.state() == tcp::State::FinWait2{
-                    log::info!("[TcpRecvFuture::poll] state become {:?}", socket.state());
-                    return Ok(0);
-                }
-                if !socket.may_recv() {
-                    log::info!(
-                        "[TcpRecvFuture::poll] err when recv, state {:?}",
-                        socket.state()
+                // 对端关闭后缓冲区中剩下的数据仍然可以读出
+                if socket.can_recv() {
+                    info!(
+                        "[TcpRecvFuture::poll] {:?} <- {:?}",
+                        socket.local_endpoint(),
+                        socket.remote_endpoint()
                     );
-                    return Err(SyscallErr::ENOTCONN);
+                    return socket.recv_slice(buf).map_err(|_| SyscallErr::ENOTCONN);
                 }
-                log::info!("[TcpRecvFuture::poll] state {:?}", socket.state());
-                if !socket.can_recv() {
-                    // panic!();
-                    log::info!("[TcpRecvFuture::poll] cannot recv yet");
-                    return Err(SyscallErr::EAGAIN);
-                }
-                log::info!("[TcpRecvFuture::poll] start to recv...");
-                info!(
-                    "[TcpRecvFuture::poll] {:?} <- {:?}",
-                    socket.local_endpoint(),
-                    socket.remote_endpoint()
-                );
-                match socket.recv_slice(buf) {
-                    Ok(nbytes) => {
-                        info!("[TcpRecvFuture::poll] recv {} bytes", nbytes);
-                        Ok(nbytes)
+                match socket.state() {
+                    tcp::State::SynSent | tcp::State::SynReceived => Err(SyscallErr::EAGAIN),
+                    tcp::State::Listen => Err(SyscallErr::ENOTCONN),
+                    tcp::State::Closed if !was_connected => Err(SyscallErr::ENOTCONN),
+                    _ if socket.may_recv() => Err(SyscallErr::EAGAIN),
+                    state => {
+                        log::info!("[TcpRecvFuture::poll] state become {:?}", state);
+                        Ok(0)
                     }
-                    Err(_) => return Err(SyscallErr::ENOTCONN),
                 }
             });
             NET_INTERFACE.poll();
             match ret {
-                Ok(result) => return GeneralRet::Ok(result),
-                Err(SyscallErr::EAGAIN) => {
+                Err(SyscallErr::EAGAIN) if !self.nonblock => {
+                    if interrupted() {
+                        return Err(SyscallErr::EINTR);
+                    }
                     suspend_current_and_run_next();
-                    // 如果返回 EAGAIN 错误，继续循环
-                    continue;
                 }
-                Err(err) => return GeneralRet::Err(err),
+                ret => return ret,
+            }
+        }
+    }
+    /// 阻塞的套接字等到 `buf` 全部放入发送缓冲区，非阻塞的只放入当前放得下的部分；
+    /// 已经发出一部分后出错或被信号打断时返回已发出的字节数
+    fn _write(&self, buf: &[u8]) -> GeneralRet<usize> {
+        let was_connected = self.was_connected();
+        let mut sent = 0;
+        loop {
+            NET_INTERFACE.poll();
+            let ret = NET_INTERFACE.tcp_socket(self.socket_handler, |socket| {
+                match socket.state() {
+                    tcp::State::SynSent | tcp::State::SynReceived => Err(SyscallErr::EAGAIN),
+                    _ if !socket.may_send() => Err(if was_connected {
+                        SyscallErr::EPIPE
+                    } else {
+                        SyscallErr::ENOTCONN
+                    }),
+                    _ if !socket.can_send() => Err(SyscallErr::EAGAIN),
+                    _ => socket
+                        .send_slice(&buf[sent..])
+                        .map_err(|_| SyscallErr::EPIPE),
+                }
+            });
+            NET_INTERFACE.poll();
+            let err = match ret {
+                Ok(len) => {
+                    sent += len;
+                    if sent == buf.len() {
+                        return Ok(sent);
+                    }
+                    SyscallErr::EAGAIN
+                }
+                Err(err) => err,
+            };
+            if err != SyscallErr::EAGAIN || self.nonblock || interrupted() {
+                return match sent {
+                    0 if err == SyscallErr::EAGAIN && !self.nonblock => Err(SyscallErr::EINTR),
+                    0 => Err(err),
+                    sent => Ok(sent),
+                };
             }
+            suspend_current_and_run_next();
         }
     }
-}
\ No newline at end of file
+}
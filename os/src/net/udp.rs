@@ -1,7 +1,10 @@
-use super::{address::SocketAddrv4, config::NET_INTERFACE, Mutex, Socket, MAX_BUFFER_SIZE};
+use super::{
+    address::SocketAddrv4, config::NET_INTERFACE, interrupted, Mutex, Socket, MAX_BUFFER_SIZE,
+};
 use crate::{
-    fs::{file_trait::File, OpenFlags},
+    fs::{file_trait::File, OpenFlags, StatMode},
     net::address,
+    syscall::errno::*,
     utils::error::{GeneralRet, SyscallErr, SyscallRet},
 };
 use alloc::vec;
@@ -28,9 +31,15 @@ use crate::fs::Dirent;
 use crate::fs::SeekWhence;
 use crate::fs::fat32::PageCache;
 
+/// 收发缓冲区中最多排队的报文数
+const UDP_PACKET_QUEUE_LEN: usize = 64;
+
 pub struct UdpSocket {
     inner: Mutex<UdpSocketInner>,
     socket_handler: SocketHandle,
+    /// SOCK_NONBLOCK：收发不等待
+    nonblock: bool,
+    selfptr: Weak<UdpSocket>,
 }
 
 #[allow(unused)]
@@ -58,36 +67,8 @@ impl Socket for UdpSocket {
     fn connect<'a>(&'a self, addr_buf: &'a [u8]) -> crate::utils::error::SyscallRet {
         let remote_endpoint = address::endpoint(addr_buf)?;
         log::info!("[Udp::connect] connect to {:?}", remote_endpoint);
-        let mut inner = self.inner.lock();
-        inner.remote_endpoint = Some(remote_endpoint);
-        NET_INTERFACE.poll();
-        NET_INTERFACE.udp_socket(self.socket_handler, |socket| {
-            let local = socket.endpoint();
-            info!("[Udp::connect] local: {:?}", local);
-            if local.port == 0 {
-                info!("[Udp::connect] don't have local");
-                let addr = SocketAddrv4::new([0; 16].as_slice());
-                let endpoint = IpListenEndpoint::from(addr);
-                let ret = socket.bind(endpoint);
-                if ret.is_err() {
-                    match ret.err().unwrap() {
-                        socket::udp::BindError::Unaddressable => {
-                            info!("[Udp::bind] unaddr");
-                            return Err(SyscallErr::EINVAL);
-                        }
-                        socket::udp::BindError::InvalidState => {
-                            info!("[Udp::bind] invaild state");
-                            return Err(SyscallErr::EINVAL);
-                        }
-                    }
-                }
-                log::info!("[Udp::bind] bind to {:?}", endpoint);
-                Ok(())
-            } else {
-                Ok(())
-            }
-        })?;
-        NET_INTERFACE.poll();
+        self.autobind()?;
+        self.inner.lock().remote_endpoint = Some(remote_endpoint);
         Ok(0)
     }
 
@@ -97,7 +78,7 @@ impl Socket for UdpSocket {
         _addr: usize,
         _addrlen: usize,
     ) -> crate::utils::error::SyscallRet {
-        todo!();
+        Err(SyscallErr::EOPNOTSUPP)
     }
 
     fn socket_type(&self) -> super::SocketType {
@@ -143,31 +124,59 @@ impl Socket for UdpSocket {
     fn set_keep_alive(&self, _enabled: bool) -> SyscallRet {
         Err(SyscallErr::EOPNOTSUPP)
     }
+
+    /// 没有 `dest` 时发给 connect 指定的对端，都没有时返回 EDESTADDRREQ
+    fn send_to(&self, buf: UserBuffer, dest: Option<IpEndpoint>) -> SyscallRet {
+        let mut data = vec![0u8; buf.len()];
+        buf.read(&mut data);
+        self._write(&data, dest)
+    }
+
+    fn recv_from(&self, mut buf: UserBuffer) -> GeneralRet<(usize, Option<IpEndpoint>)> {
+        let mut data = vec![0u8; buf.len().min(MAX_BUFFER_SIZE)];
+        let (len, sender) = self._read(&mut data)?;
+        buf.write(&data[..len]);
+        Ok((len, Some(sender)))
+    }
 }
 
 impl UdpSocket {
-    pub fn new() -> Self {
+    pub fn new(nonblock: bool) -> Arc<Self> {
         let tx_buf = socket::udp::PacketBuffer::new(
-            vec![PacketMetadata::EMPTY, PacketMetadata::EMPTY],
+            vec![PacketMetadata::EMPTY; UDP_PACKET_QUEUE_LEN],
             vec![0 as u8; MAX_BUFFER_SIZE],
         );
         let rx_buf = socket::udp::PacketBuffer::new(
-            vec![PacketMetadata::EMPTY, PacketMetadata::EMPTY],
+            vec![PacketMetadata::EMPTY; UDP_PACKET_QUEUE_LEN],
             vec![0 as u8; MAX_BUFFER_SIZE],
         );
         let socket = socket::udp::Socket::new(rx_buf, tx_buf);
         let socket_handler = NET_INTERFACE.add_socket(socket);
         log::info!("[UdpSocket::new] new {}", socket_handler);
         NET_INTERFACE.poll();
-        Self {
+        Arc::new_cyclic(|selfptr| Self {
             inner: Mutex::new(UdpSocketInner {
                 remote_endpoint: None,
                 recvbuf_size: MAX_BUFFER_SIZE,
                 sendbuf_size: MAX_BUFFER_SIZE,
             }),
             socket_handler,
+            nonblock,
+            selfptr: selfptr.clone(),
+        })
+    }
 
-        }
+    /// 还没有绑定端口时绑定到任意地址的随机端口
+    fn autobind(&self) -> GeneralRet<()> {
+        NET_INTERFACE.udp_socket(self.socket_handler, |socket| {
+            if socket.endpoint().port != 0 {
+                return Ok(());
+            }
+            let addr = SocketAddrv4::new([0; 16].as_slice());
+            let endpoint = IpListenEndpoint::from(addr);
+            log::info!("[Udp::bind] bind to {:?}", endpoint);
+            socket.bind(endpoint).map_err(|_| SyscallErr::EINVAL)
+        })
     }
 }
 
@@ -189,141 +198,199 @@ impl Drop for UdpSocket {
 }
 use crate::task::suspend_current_and_run_next;
 impl File for UdpSocket {
-    fn deep_clone(&self) -> Arc<dyn File>{
-        todo!();
+    /// dup 得到的 fd 与原 fd 共用同一个套接字
+    fn deep_clone(&self) -> Arc<dyn File> {
+        self.selfptr.upgrade().unwrap()
     }
-    fn readable(&self) -> bool{
-        todo!();
+    fn readable(&self) -> bool {
+        true
     }
-    fn writable(&self) -> bool{
+    fn writable(&self) -> bool {
         true
     }
-    fn read(&self, _offset: Option<&mut usize>, buf: &mut [u8]) -> usize{
-        let ret = self._read(buf).unwrap();
-        ret
+    fn read(&self, offset: Option<&mut usize>, buf: &mut [u8]) -> usize {
+        if offset.is_some() {
+            return ESPIPE as usize;
+        }
+        match self._read(buf) {
+            Ok((len, _)) => len,
+            Err(err) => -(err as isize) as usize,
+        }
+    }
+    fn write(&self, offset: Option<&mut usize>, buf: &[u8]) -> usize {
+        if offset.is_some() {
+            return ESPIPE as usize;
+        }
+        match self._write(buf, None) {
+            Ok(len) => len,
+            Err(err) => -(err as isize) as usize,
+        }
     }
-    fn write(&self, _offset: Option<&mut usize>, buf: &[u8]) -> usize{
+    fn r_ready(&self) -> bool {
         NET_INTERFACE.poll();
-        let ret = NET_INTERFACE.udp_socket(self.socket_handler, |socket| {
-            if !socket.can_send() {
-                log::info!("[UdpSendFuture::poll] cannot send yet");
-                suspend_current_and_run_next();
-                return SyscallErr::EAGAIN as usize;
-            }
-            log::info!("[UdpSendFuture::poll] start to send...");
-            let remote = self.inner.lock().remote_endpoint;
-            let meta = UdpMetadata {
-                endpoint: remote.unwrap(),
-                meta: PacketMeta::default(),
-            };
-            info!(
-                "[UdpSendFuture::poll] {:?} -> {:?}",
-                socket.endpoint(),
-                remote
-            );
-            let len = buf.len();
-            let ret =  socket.send_slice(buf, meta);
-            if let Some(err) = ret.err() {
-                if err == SendError::Unaddressable {
-                    return SyscallErr::ENOTCONN as usize;
-                } else {
-                    return SyscallErr::ENOBUFS as usize;
-                }
-            } else {
-                log::debug!("[UdpSendFuture::poll] send {} bytes", len);
-                return len;
-            }
-        });
+        NET_INTERFACE.udp_socket(self.socket_handler, |socket| socket.can_recv())
+    }
+    fn w_ready(&self) -> bool {
         NET_INTERFACE.poll();
-        ret
-    }
-    fn r_ready(&self) -> bool{true}
-    fn w_ready(&self) -> bool{todo!();}
-    fn read_user(&self, _offset: Option<usize>, buf: UserBuffer) -> usize{
-        let mut buffers = buf.buffers;
-        let buf = unsafe { core::slice::from_raw_parts_mut(buffers[0].as_mut_ptr() as *mut u8, buf.len as usize) };
-        let ret = self._read(buf).unwrap();
-        ret
-    }
-    fn write_user(&self, _offset: Option<usize>, buf: UserBuffer) -> usize{
-        let mut buffers = buf.buffers;
-        let buf = unsafe { core::slice::from_raw_parts_mut(buffers[0].as_mut_ptr() as *mut u8, buf.len as usize) };
-        self.write(None, buf)
-    }
-    fn get_size(&self) -> usize{todo!();}
-    fn get_stat(&self) -> Stat{todo!();}
-    fn get_file_type(&self) -> DiskInodeType{todo!();}
-    fn is_dir(&self) -> bool {todo!();}
-    fn is_file(&self) -> bool {todo!();}
-    fn info_dirtree_node(&self, _dirnode_ptr: Weak<DirectoryTreeNode>){todo!();}
-    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>>{todo!();}
+        NET_INTERFACE.udp_socket(self.socket_handler, |socket| socket.can_send())
+    }
+    fn read_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
+        if offset.is_some() {
+            return ESPIPE as usize;
+        }
+        match self.recv_from(buf) {
+            Ok((len, _)) => len,
+            Err(err) => -(err as isize) as usize,
+        }
+    }
+    fn write_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
+        if offset.is_some() {
+            return ESPIPE as usize;
+        }
+        match self.send_to(buf, None) {
+            Ok(len) => len,
+            Err(err) => -(err as isize) as usize,
+        }
+    }
+    fn get_size(&self) -> usize {
+        0
+    }
+    fn get_stat(&self) -> Stat {
+        Stat::new(0, 1, StatMode::S_IFSOCK.bits() | 0o777, 1, 0, 0, 0, 0, 0)
+    }
+    fn get_file_type(&self) -> DiskInodeType {
+        DiskInodeType::Socket
+    }
+    fn info_dirtree_node(&self, _dirnode_ptr: Weak<DirectoryTreeNode>) {}
+    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>> {
+        None
+    }
     /// open
-    fn open(&self, _flags: OpenFlags, _special_use: bool) -> Arc<dyn File>{todo!();}
-    fn open_subfile(&self) -> Result<Vec<(String, Arc<dyn File>)>, isize>{todo!();}
+    fn open(&self, _flags: OpenFlags, _special_use: bool) -> Arc<dyn File> {
+        self.deep_clone()
+    }
+    fn open_subfile(&self) -> Result<Vec<(String, Arc<dyn File>)>, isize> {
+        Err(ENOTDIR)
+    }
     /// create
-    fn create(&self, _name: &str, _file_type: DiskInodeType) -> Result<Arc<dyn File>, isize>{todo!();}
-    fn link_child(&self, _name: &str, _child: &Self) -> Result<(), isize>{todo!();}
+    fn create(&self, _name: &str, _file_type: DiskInodeType) -> Result<Arc<dyn File>, isize> {
+        Err(ENOTDIR)
+    }
+    fn link_child(&self, _name: &str, _child: &Self) -> Result<(), isize> {
+        Err(ENOTDIR)
+    }
     /// delete(unlink)
-    fn unlink(&self, _delete: bool) -> Result<(), isize>{todo!();}
+    fn unlink(&self, _delete: bool) -> Result<(), isize> {
+        Err(EACCES)
+    }
     /// dirent
-    fn get_dirent(&self, _count: usize) -> Vec<Dirent>{todo!();}
+    fn get_dirent(&self, _count: usize) -> Vec<Dirent> {
+        Vec::new()
+    }
     /// offset
-    fn get_offset(&self) -> usize {todo!();}
-    fn lseek(&self, _offset: isize, _whence: SeekWhence) -> Result<usize, isize>{todo!();}
+    fn get_offset(&self) -> usize {
+        0
+    }
+    fn lseek(&self, _offset: isize, _whence: SeekWhence) -> Result<usize, isize> {
+        Err(ESPIPE)
+    }
     /// size
-    fn modify_size(&self, _diff: isize) -> Result<(), isize>{todo!();}
-    fn truncate_size(&self, _new_size: usize) -> Result<(), isize>{todo!();}
+    fn modify_size(&self, _diff: isize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+    fn truncate_size(&self, _new_size: usize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
     // time
-    fn set_timestamp(&self, _ctime: Option<usize>, _atime: Option<usize>, _mtime: Option<usize>){todo!();}
+    fn set_timestamp(&self, _ctime: Option<usize>, _atime: Option<usize>, _mtime: Option<usize>) {}
     /// cache
-    fn get_single_cache(&self, _offset: usize) -> Result<Arc<Mutex<PageCache>>, ()>{todo!();}
-    fn get_all_caches(&self) -> Result<Vec<Arc<Mutex<PageCache>>>, ()>{todo!();}
+    fn get_single_cache(&self, _offset: usize) -> Result<Arc<Mutex<PageCache>>, ()> {
+        Err(())
+    }
+    fn get_all_caches(&self) -> Result<Vec<Arc<Mutex<PageCache>>>, ()> {
+        Err(())
+    }
     /// memory related
-    fn oom(&self) -> usize{todo!();}
+    fn oom(&self) -> usize {
+        0
+    }
     /// poll, select related
-    fn hang_up(&self) -> bool{todo!();}
-    /// iotcl
-    fn ioctl(&self, _cmd: u32, _argp: usize) -> isize {todo!();}
+    fn hang_up(&self) -> bool {
+        false
+    }
     /// fcntl
-    fn fcntl(&self, _cmd: u32, _arg: u32) -> isize{todo!();}
-
+    fn fcntl(&self, _cmd: u32, _arg: u32) -> isize {
+        EINVAL
+    }
 }
 
 impl UdpSocket {
-    fn _read<'a>(&'a self, buf: &'a mut [u8]) -> GeneralRet<usize> {
+    /// 取出一个报文，`buf` 放不下时多余的部分被丢弃；返回长度与发送者
+    fn _read<'a>(&'a self, buf: &'a mut [u8]) -> GeneralRet<(usize, IpEndpoint)> {
         loop {
             NET_INTERFACE.poll();
             let ret = NET_INTERFACE.udp_socket(self.socket_handler, |socket| {
                 if !socket.can_recv() {
-                    // panic!();
-                    log::info!("[UdpRecvFuture::poll] cannot recv yet");
                     return Err(SyscallErr::EAGAIN);
                 }
-                log::info!("[UdpRecvFuture::poll] start to recv...");
-                let (ret, meta) = socket
-                    .recv_slice(buf)
-                    .ok()
-                    .ok_or(SyscallErr::ENOTCONN)?;
-                let remote = Some(meta.endpoint);
+                let (len, meta) = socket.recv_slice(buf).ok().ok_or(SyscallErr::ENOTCONN)?;
                 info!(
                     "[UdpRecvFuture::poll] {:?} <- {:?}",
                     socket.endpoint(),
+                    meta.endpoint
+                );
+                log::debug!("[UdpRecvFuture::poll] recv {} bytes", len);
+                Ok((len, meta.endpoint))
+            });
+            NET_INTERFACE.poll();
+            match ret {
+                Err(SyscallErr::EAGAIN) if !self.nonblock => {
+                    if interrupted() {
+                        return Err(SyscallErr::EINTR);
+                    }
+                    suspend_current_and_run_next();
+                }
+                ret => return ret,
+            }
+        }
+    }
+    /// 把 `buf` 作为一个报文发给 `dest` 或已连接的对端，发送缓冲区满时等待
+    fn _write(&self, buf: &[u8], dest: Option<IpEndpoint>) -> GeneralRet<usize> {
+        let remote = dest
+            .or(self.inner.lock().remote_endpoint)
+            .ok_or(SyscallErr::EDESTADDRREQ)?;
+        self.autobind()?;
+        loop {
+            NET_INTERFACE.poll();
+            let ret = NET_INTERFACE.udp_socket(self.socket_handler, |socket| {
+                if buf.len() > socket.payload_send_capacity() {
+                    return Err(SyscallErr::EMSGSIZE);
+                }
+                let meta = UdpMetadata {
+                    endpoint: remote,
+                    meta: PacketMeta::default(),
+                };
+                info!(
+                    "[UdpSendFuture::poll] {:?} -> {:?}",
+                    socket.endpoint(),
                     remote
                 );
-                self.inner.lock().remote_endpoint = remote;
-                log::debug!("[UdpRecvFuture::poll] recv {} bytes", ret);
-                Ok(ret)
+                match socket.send_slice(buf, meta) {
+                    Ok(()) => Ok(buf.len()),
+                    Err(SendError::Unaddressable) => Err(SyscallErr::EINVAL),
+                    Err(SendError::BufferFull) => Err(SyscallErr::EAGAIN),
+                }
             });
             NET_INTERFACE.poll();
             match ret {
-                Ok(result) => return GeneralRet::Ok(result),
-                Err(SyscallErr::EAGAIN) => {
+                Err(SyscallErr::EAGAIN) if !self.nonblock => {
+                    if interrupted() {
+                        return Err(SyscallErr::EINTR);
+                    }
                     suspend_current_and_run_next();
-                    // 如果返回 EAGAIN 错误，继续循环
-                    continue;
                 }
-                Err(err) => return GeneralRet::Err(err),
+                ret => return ret,
             }
         }
     }
-}
\ No newline at end of file
+}
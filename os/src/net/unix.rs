@@ -1,161 +1,828 @@
-use super::Mutex;
-use super::Socket;
-use crate::{
-    fs::{
-        dev::pipe::{make_pipe,Pipe},
-        file_trait::File,  OpenFlags,
-    },
-    utils::error::{SyscallErr,SyscallRet},
-};
-use alloc::sync::Arc;
-use smoltcp::wire::IpEndpoint;
-
-use crate::mm::UserBuffer;
-use crate::fs::Stat;
-use crate::fs::DiskInodeType;
-use alloc::sync::Weak;
+use super::{interrupted, Socket, SocketType, AF_UNIX, SHUT_RD};
+use crate::fs::dev::pipe::{make_pipe, Pipe};
 use crate::fs::directory_tree::DirectoryTreeNode;
-use alloc::vec::Vec;
-use alloc::string::String;
-use crate::fs::Dirent;
-use crate::fs::SeekWhence;
 use crate::fs::fat32::PageCache;
-#[allow(unused)]
-pub struct UnixSocket<const N: usize> {
-    //file_meta: FileMeta,
-    // read_end: Arc<Pipe<N>>,
-    // write_end: Arc<Pipe<N>>,
-    read_end: Arc<Pipe>,
-    write_end: Arc<Pipe>,
+use crate::fs::file_trait::File;
+use crate::fs::{Dirent, DiskInodeType, FileDescriptor, OpenFlags, SeekWhence, Stat, StatMode};
+use crate::mm::{copy_to_user, get_from_user, translated_byte_buffer, UserBuffer};
+use crate::syscall::errno::*;
+use crate::task::{current_task, suspend_current_and_run_next};
+use crate::utils::error::{GeneralRet, SyscallErr, SyscallRet};
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::{String, ToString};
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use smoltcp::wire::{IpEndpoint, IpListenEndpoint};
+use spin::Mutex;
+
+/// sockaddr_un 中 sun_path 的长度
+const UNIX_PATH_MAX: usize = 108;
+/// 数据报套接字的接收队列中最多排队的报文数，队列满时发送者等待
+const DGRAM_QUEUE_LEN: usize = 64;
+/// 流套接字未指定 backlog 时最多排队的连接数
+const DEFAULT_BACKLOG: usize = 16;
+
+/// 抽象命名空间：名字到绑定它的套接字
+static ABSTRACT_NAMES: Mutex<BTreeMap<Vec<u8>, Weak<UnixSocket>>> = Mutex::new(BTreeMap::new());
+
+/// 自动绑定时分配的抽象名字的序号
+static AUTOBIND_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// AF_UNIX 套接字的地址
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum UnixAddr {
+    /// 未绑定，getsockname 只返回地址族
+    Unnamed,
+    /// 抽象命名空间中的名字：sun_path 以 '\0' 开头，名字不出现在文件系统中
+    Abstract(Vec<u8>),
+    /// 文件系统中的路径，bind 时在目录树中创建套接字文件
+    Path(String),
+}
+
+impl UnixAddr {
+    /// 解析用户传入的 `struct sockaddr_un`，`buf` 的长度即 addrlen
+    pub fn parse(buf: &[u8]) -> Result<Self, isize> {
+        if buf.len() < 2 || u16::from_ne_bytes([buf[0], buf[1]]) != AF_UNIX {
+            return Err(EINVAL);
+        }
+        let path = &buf[2..];
+        if path.len() > UNIX_PATH_MAX {
+            return Err(EINVAL);
+        }
+        match path.first() {
+            None => Ok(UnixAddr::Unnamed),
+            Some(0) => Ok(UnixAddr::Abstract(path[1..].to_vec())),
+            Some(_) => {
+                let len = path
+                    .iter()
+                    .position(|&byte| byte == 0)
+                    .unwrap_or(path.len());
+                match core::str::from_utf8(&path[..len]) {
+                    Ok(path) => Ok(UnixAddr::Path(path.to_string())),
+                    Err(_) => Err(EINVAL),
+                }
+            }
+        }
+    }
+
+    /// 按 `struct sockaddr_un` 写回用户的 `addr`，`addrlen` 为值-结果参数：
+    /// 传入缓冲区长度，返回地址的实际长度，缓冲区不够时截断
+    pub fn fill(&self, addr: usize, addrlen: usize) -> Result<(), isize> {
+        if addr == 0 {
+            return Ok(());
+        }
+        let mut bytes = AF_UNIX.to_ne_bytes().to_vec();
+        match self {
+            UnixAddr::Unnamed => {}
+            UnixAddr::Abstract(name) => {
+                bytes.push(0);
+                bytes.extend_from_slice(name);
+            }
+            UnixAddr::Path(path) => {
+                bytes.extend_from_slice(path.as_bytes());
+                bytes.push(0);
+            }
+        }
+        let token = current_task().unwrap().get_user_token();
+        let len = (get_from_user(token, addrlen as *const u32)? as usize).min(bytes.len());
+        if len > 0 {
+            UserBuffer::new(translated_byte_buffer(token, addr as *const u8, len)?)
+                .write(&bytes[..len]);
+        }
+        copy_to_user(token, &(bytes.len() as u32), addrlen as *mut u32)
+    }
+}
+
+/// 流套接字的连接状态
+enum StreamState {
+    Unconnected,
+    /// 已 listen，`backlog` 中是已建立、等待 accept 的服务端套接字
+    Listening {
+        backlog: VecDeque<Arc<UnixSocket>>,
+        max: usize,
+    },
+    /// 已连接：数据经两条管道双向传输，`write_end` 在 shutdown(SHUT_WR) 后为 None
+    Connected {
+        read_end: Arc<Pipe>,
+        write_end: Option<Arc<Pipe>>,
+        peer: UnixAddr,
+    },
+}
+
+/// AF_UNIX 套接字，SOCK_STREAM 或 SOCK_DGRAM
+pub struct UnixSocket {
+    socket_type: SocketType,
+    /// SOCK_NONBLOCK：需要等待时返回 EAGAIN
+    nonblock: bool,
+    local: Mutex<UnixAddr>,
+    /// 流套接字的连接状态
+    state: Mutex<StreamState>,
+    /// 数据报套接字收到的报文与发送者的地址
+    inbox: Mutex<VecDeque<(UnixAddr, Vec<u8>)>>,
+    /// 数据报套接字 connect 指定的默认对端
+    peer: Mutex<Option<(UnixAddr, Weak<UnixSocket>)>>,
+    /// 指向自己的弱引用，用于登记名字和 dup
+    selfptr: Weak<UnixSocket>,
 }
 
-impl<const N: usize> Socket for UnixSocket<N> {
-    fn bind(&self, _addr: smoltcp::wire::IpListenEndpoint) -> crate::utils::error::SyscallRet {
-        todo!();
+impl UnixSocket {
+    pub fn new(socket_type: SocketType, nonblock: bool) -> Arc<Self> {
+        Self::with_state(
+            socket_type,
+            nonblock,
+            UnixAddr::Unnamed,
+            StreamState::Unconnected,
+        )
+    }
+
+    fn with_state(
+        socket_type: SocketType,
+        nonblock: bool,
+        local: UnixAddr,
+        state: StreamState,
+    ) -> Arc<Self> {
+        Arc::new_cyclic(|selfptr| Self {
+            socket_type,
+            nonblock,
+            local: Mutex::new(local),
+            state: Mutex::new(state),
+            inbox: Mutex::new(VecDeque::new()),
+            peer: Mutex::new(None),
+            selfptr: selfptr.clone(),
+        })
+    }
+
+    fn is_stream(&self) -> bool {
+        self.socket_type.contains(SocketType::SOCK_STREAM)
     }
 
-    fn listen(&self) -> crate::utils::error::SyscallRet {
-        todo!();
-   }
+    /// 放入当前任务的文件描述符表与套接字表，返回 fd
+    pub fn install(self: &Arc<Self>, cloexec: bool) -> Result<usize, isize> {
+        let task = current_task().unwrap();
+        let fd =
+            task.files
+                .lock()
+                .insert(FileDescriptor::new(cloexec, self.nonblock, self.clone()))?;
+        task.socket_table.lock().insert(fd, self.clone());
+        Ok(fd)
+    }
+
+    /// 按地址找到绑定它的套接字，类型不同时返回 EPROTOTYPE
+    fn lookup(&self, addr: &UnixAddr) -> Result<Arc<UnixSocket>, isize> {
+        let socket = match addr {
+            UnixAddr::Unnamed => return Err(EINVAL),
+            UnixAddr::Abstract(name) => ABSTRACT_NAMES.lock().get(name).and_then(Weak::upgrade),
+            UnixAddr::Path(path) => {
+                let task = current_task().unwrap();
+                let working_inode = task.fs.lock().working_inode.as_ref().clone();
+                working_inode
+                    .open(path, OpenFlags::O_RDONLY, false)?
+                    .file
+                    .downcast_arc::<UnixSocketNode>()
+                    .ok()
+                    .and_then(|node| node.socket.upgrade())
+            }
+        };
+        // 名字仍在但套接字已关闭，与 Linux 一样拒绝连接
+        let socket = socket.ok_or(ECONNREFUSED)?;
+        if socket.socket_type != self.socket_type {
+            return Err(EPROTOTYPE);
+        }
+        Ok(socket)
+    }
+
+    /// 绑定地址。路径地址在目录树中创建套接字文件，已存在时返回 EADDRINUSE；
+    /// 只有地址族的地址自动绑定一个抽象名字
+    pub fn bind(&self, addr: UnixAddr) -> Result<(), isize> {
+        let mut local = self.local.lock();
+        if *local != UnixAddr::Unnamed {
+            return Err(EINVAL);
+        }
+        let addr = match addr {
+            UnixAddr::Unnamed => UnixAddr::Abstract(
+                alloc::format!("{:05x}", AUTOBIND_ID.fetch_add(1, Ordering::Relaxed)).into_bytes(),
+            ),
+            addr => addr,
+        };
+        match &addr {
+            UnixAddr::Abstract(name) => {
+                let mut names = ABSTRACT_NAMES.lock();
+                if names.get(name).and_then(Weak::upgrade).is_some() {
+                    return Err(EADDRINUSE);
+                }
+                names.insert(name.clone(), self.selfptr.clone());
+            }
+            UnixAddr::Path(path) => {
+                let task = current_task().unwrap();
+                let working_inode = task.fs.lock().working_inode.as_ref().clone();
+                let node = Arc::new(UnixSocketNode {
+                    socket: self.selfptr.clone(),
+                });
+                match working_inode.mknod(path, node) {
+                    Ok(()) => {}
+                    Err(EEXIST) => return Err(EADDRINUSE),
+                    Err(errno) => return Err(errno),
+                }
+            }
+            UnixAddr::Unnamed => unreachable!(),
+        }
+        *local = addr;
+        Ok(())
+    }
+
+    /// 开始接受连接，`backlog` 为 0 时使用默认值
+    pub fn listen(&self, backlog: usize) -> Result<(), isize> {
+        if !self.is_stream() {
+            return Err(EOPNOTSUPP);
+        }
+        if *self.local.lock() == UnixAddr::Unnamed {
+            self.bind(UnixAddr::Unnamed)?;
+        }
+        let max = if backlog == 0 {
+            DEFAULT_BACKLOG
+        } else {
+            backlog
+        };
+        let mut state = self.state.lock();
+        match &mut *state {
+            StreamState::Unconnected => {
+                *state = StreamState::Listening {
+                    backlog: VecDeque::new(),
+                    max,
+                };
+                Ok(())
+            }
+            StreamState::Listening { max: old_max, .. } => {
+                *old_max = max;
+                Ok(())
+            }
+            StreamState::Connected { .. } => Err(EINVAL),
+        }
+    }
+
+    /// 流套接字连接到正在监听 `addr` 的套接字：连接放入对方的 backlog 后即返回；
+    /// 数据报套接字只记录默认对端
+    pub fn connect(&self, addr: UnixAddr) -> Result<(), isize> {
+        let target = self.lookup(&addr)?;
+        if !self.is_stream() {
+            *self.peer.lock() = Some((addr, Arc::downgrade(&target)));
+            return Ok(());
+        }
+        match &*self.state.lock() {
+            StreamState::Unconnected => {}
+            StreamState::Listening { .. } => return Err(EINVAL),
+            StreamState::Connected { .. } => return Err(EISCONN),
+        }
+        let (client_read, server_write) = make_pipe();
+        let (server_read, client_write) = make_pipe();
+        let local = self.local.lock().clone();
+        let server = Self::with_state(
+            self.socket_type,
+            false,
+            addr.clone(),
+            StreamState::Connected {
+                read_end: server_read,
+                write_end: Some(server_write),
+                peer: local,
+            },
+        );
+        loop {
+            match &mut *target.state.lock() {
+                StreamState::Listening { backlog, max } => {
+                    if backlog.len() < *max {
+                        backlog.push_back(server);
+                        break;
+                    }
+                }
+                _ => return Err(ECONNREFUSED),
+            }
+            if self.nonblock {
+                return Err(EAGAIN);
+            }
+            if interrupted() {
+                return Err(EINTR);
+            }
+            suspend_current_and_run_next();
+        }
+        *self.state.lock() = StreamState::Connected {
+            read_end: client_read,
+            write_end: Some(client_write),
+            peer: addr,
+        };
+        Ok(())
+    }
+
+    /// 取出一个已建立的连接，没有时等待
+    pub fn accept(&self) -> Result<Arc<UnixSocket>, isize> {
+        loop {
+            match &mut *self.state.lock() {
+                StreamState::Listening { backlog, .. } => {
+                    if let Some(server) = backlog.pop_front() {
+                        return Ok(server);
+                    }
+                }
+                _ => return Err(EINVAL),
+            }
+            if self.nonblock {
+                return Err(EAGAIN);
+            }
+            if interrupted() {
+                return Err(EINTR);
+            }
+            suspend_current_and_run_next();
+        }
+    }
+
+    pub fn local_addr(&self) -> UnixAddr {
+        self.local.lock().clone()
+    }
+
+    pub fn peer_addr(&self) -> Result<UnixAddr, isize> {
+        if !self.is_stream() {
+            return match &*self.peer.lock() {
+                Some((addr, _)) => Ok(addr.clone()),
+                None => Err(ENOTCONN),
+            };
+        }
+        match &*self.state.lock() {
+            StreamState::Connected { peer, .. } => Ok(peer.clone()),
+            _ => Err(ENOTCONN),
+        }
+    }
+
+    /// 发送数据。数据报套接字发往 `dest`，未指定时发往 connect 的对端；
+    /// 流套接字只能发往已连接的对端
+    pub fn send_to(&self, buf: UserBuffer, dest: Option<UnixAddr>) -> Result<usize, isize> {
+        if self.is_stream() {
+            let write_end = match (&*self.state.lock(), dest.is_some()) {
+                (StreamState::Connected { .. }, true) => return Err(EISCONN),
+                (StreamState::Connected { write_end, .. }, false) => {
+                    write_end.clone().ok_or(EPIPE)?
+                }
+                (_, true) => return Err(EOPNOTSUPP),
+                (_, false) => return Err(ENOTCONN),
+            };
+            if write_end.hang_up() {
+                return Err(EPIPE);
+            }
+            if self.nonblock && !write_end.w_ready() {
+                return Err(EAGAIN);
+            }
+            return Ok(write_end.write_user(None, buf));
+        }
+        let target = match dest {
+            Some(addr) => self.lookup(&addr)?,
+            None => match &*self.peer.lock() {
+                Some((_, peer)) => peer.upgrade().ok_or(ECONNREFUSED)?,
+                None => return Err(ENOTCONN),
+            },
+        };
+        let mut data = alloc::vec![0u8; buf.len()];
+        buf.read(&mut data);
+        let len = data.len();
+        let message = (self.local_addr(), data);
+        loop {
+            let mut inbox = target.inbox.lock();
+            if inbox.len() < DGRAM_QUEUE_LEN {
+                inbox.push_back(message);
+                return Ok(len);
+            }
+            drop(inbox);
+            if self.nonblock {
+                return Err(EAGAIN);
+            }
+            if interrupted() {
+                return Err(EINTR);
+            }
+            suspend_current_and_run_next();
+        }
+    }
+
+    /// 接收数据，返回读出的字节数与发送者的地址。
+    /// 数据报一次读出一个报文，缓冲区放不下的部分被丢弃
+    pub fn recv_from(&self, buf: UserBuffer) -> Result<(usize, UnixAddr), isize> {
+        if self.is_stream() {
+            let (read_end, peer) = match &*self.state.lock() {
+                StreamState::Connected { read_end, peer, .. } => (read_end.clone(), peer.clone()),
+                _ => return Err(ENOTCONN),
+            };
+            if self.nonblock && !read_end.r_ready() && !read_end.hang_up() {
+                return Err(EAGAIN);
+            }
+            return Ok((read_end.read_user(None, buf), peer));
+        }
+        let mut buf = buf;
+        loop {
+            if let Some((sender, data)) = self.inbox.lock().pop_front() {
+                return Ok((buf.write(&data), sender));
+            }
+            if self.nonblock {
+                return Err(EAGAIN);
+            }
+            if interrupted() {
+                return Err(EINTR);
+            }
+            suspend_current_and_run_next();
+        }
+    }
+}
+
+impl Drop for UnixSocket {
+    fn drop(&mut self) {
+        // 路径地址的套接字文件留在目录树中，之后连接它返回 ECONNREFUSED
+        if let UnixAddr::Abstract(name) = &*self.local.lock() {
+            let mut names = ABSTRACT_NAMES.lock();
+            if names
+                .get(name)
+                .map_or(false, |socket| socket.ptr_eq(&self.selfptr))
+            {
+                names.remove(name);
+            }
+        }
+    }
+}
+
+/// 创建一对互相连接的套接字
+pub fn make_unix_socket_pair(
+    socket_type: SocketType,
+    nonblock: bool,
+) -> (Arc<UnixSocket>, Arc<UnixSocket>) {
+    if !socket_type.contains(SocketType::SOCK_STREAM) {
+        let socket1 = UnixSocket::new(socket_type, nonblock);
+        let socket2 = UnixSocket::new(socket_type, nonblock);
+        *socket1.peer.lock() = Some((UnixAddr::Unnamed, Arc::downgrade(&socket2)));
+        *socket2.peer.lock() = Some((UnixAddr::Unnamed, Arc::downgrade(&socket1)));
+        return (socket1, socket2);
+    }
+    let (read1, write1) = make_pipe();
+    let (read2, write2) = make_pipe();
+    let connected = |read_end, write_end| StreamState::Connected {
+        read_end,
+        write_end: Some(write_end),
+        peer: UnixAddr::Unnamed,
+    };
+    let socket1 = UnixSocket::with_state(
+        socket_type,
+        nonblock,
+        UnixAddr::Unnamed,
+        connected(read1, write2),
+    );
+    let socket2 = UnixSocket::with_state(
+        socket_type,
+        nonblock,
+        UnixAddr::Unnamed,
+        connected(read2, write1),
+    );
+    (socket1, socket2)
+}
+
+/// 网络协议栈的接口只面向 inet 地址，AF_UNIX 的 bind、listen、connect 与 accept
+/// 由系统调用直接转到 [`UnixSocket`] 的同名方法
+impl Socket for UnixSocket {
+    fn bind(&self, _addr: IpListenEndpoint) -> SyscallRet {
+        Err(SyscallErr::EOPNOTSUPP)
+    }
+
+    fn listen(&self) -> SyscallRet {
+        Err(SyscallErr::EOPNOTSUPP)
+    }
 
     fn connect(&self, _addr_buf: &[u8]) -> SyscallRet {
-        todo!();
+        Err(SyscallErr::EOPNOTSUPP)
     }
 
     fn accept(&self, _sockfd: u32, _addr: usize, _addrlen: usize) -> SyscallRet {
-        todo!();
+        Err(SyscallErr::EOPNOTSUPP)
     }
 
-    fn socket_type(&self) -> super::SocketType {
-        todo!()
+    fn socket_type(&self) -> SocketType {
+        self.socket_type
     }
 
     fn recv_buf_size(&self) -> usize {
-        todo!()
+        super::MAX_BUFFER_SIZE
     }
 
     fn send_buf_size(&self) -> usize {
-        todo!()
+        super::MAX_BUFFER_SIZE
     }
 
-    fn set_recv_buf_size(&self, _size: usize) {
-        todo!()
-    }
+    fn set_recv_buf_size(&self, _size: usize) {}
 
-    fn set_send_buf_size(&self, _size: usize) {
-        todo!()
-    }
+    fn set_send_buf_size(&self, _size: usize) {}
 
-    fn loacl_endpoint(&self) -> smoltcp::wire::IpListenEndpoint {
-        todo!()
+    fn loacl_endpoint(&self) -> IpListenEndpoint {
+        IpListenEndpoint::default()
     }
 
     fn remote_endpoint(&self) -> Option<IpEndpoint> {
         None
     }
 
-    fn shutdown(&self, how: u32) -> crate::utils::error::GeneralRet<()> {
+    /// 关闭写方向后对端读到文件末尾，只关闭读方向时什么也不做
+    fn shutdown(&self, how: u32) -> GeneralRet<()> {
         log::info!("[UnixSocket::shutdown] how {}", how);
+        if how != SHUT_RD {
+            if let StreamState::Connected { write_end, .. } = &mut *self.state.lock() {
+                *write_end = None;
+            }
+        }
         Ok(())
     }
 
-    fn set_nagle_enabled(&self, _enabled: bool) -> crate::utils::error::SyscallRet {
+    fn set_nagle_enabled(&self, _enabled: bool) -> SyscallRet {
+        Err(SyscallErr::EOPNOTSUPP)
+    }
+
+    fn set_keep_alive(&self, _enabled: bool) -> SyscallRet {
+        Err(SyscallErr::EOPNOTSUPP)
+    }
+
+    fn send_to(&self, _buf: UserBuffer, _dest: Option<IpEndpoint>) -> SyscallRet {
         Err(SyscallErr::EOPNOTSUPP)
     }
 
-    fn set_keep_alive(&self, _enabled: bool) -> crate::utils::error::SyscallRet {
+    fn recv_from(&self, _buf: UserBuffer) -> GeneralRet<(usize, Option<IpEndpoint>)> {
         Err(SyscallErr::EOPNOTSUPP)
     }
 }
 
-impl<const N: usize> UnixSocket<N> {
-    pub fn new(read_end: Arc<Pipe>, write_end: Arc<Pipe>) -> Self {
-        Self {
-            //file_meta: FileMeta::new(crate::fs::InodeMode::FileSOCK),
-            // buf: Mutex::new(VecDeque::new()),
-            read_end,
-            write_end,
+impl File for UnixSocket {
+    /// dup 得到的 fd 与原 fd 共用同一个套接字
+    fn deep_clone(&self) -> Arc<dyn File> {
+        self.selfptr.upgrade().unwrap()
+    }
+
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, _offset: Option<&mut usize>, _buf: &mut [u8]) -> usize {
+        EINVAL as usize
+    }
+
+    fn write(&self, _offset: Option<&mut usize>, _buf: &[u8]) -> usize {
+        EINVAL as usize
+    }
+
+    /// 有数据、对端已关闭或（监听时）有连接等待 accept 时可读
+    fn r_ready(&self) -> bool {
+        if !self.is_stream() {
+            return !self.inbox.lock().is_empty();
+        }
+        match &*self.state.lock() {
+            StreamState::Connected { read_end, .. } => read_end.r_ready() || read_end.hang_up(),
+            StreamState::Listening { backlog, .. } => !backlog.is_empty(),
+            StreamState::Unconnected => false,
         }
     }
+
+    fn w_ready(&self) -> bool {
+        if !self.is_stream() {
+            return true;
+        }
+        match &*self.state.lock() {
+            StreamState::Connected {
+                write_end: Some(write_end),
+                ..
+            } => write_end.w_ready(),
+            _ => false,
+        }
+    }
+
+    fn read_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
+        if offset.is_some() {
+            return ESPIPE as usize;
+        }
+        match self.recv_from(buf) {
+            Ok((len, _)) => len,
+            Err(errno) => errno as usize,
+        }
+    }
+
+    fn write_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
+        if offset.is_some() {
+            return ESPIPE as usize;
+        }
+        match self.send_to(buf, None) {
+            Ok(len) => len,
+            Err(errno) => errno as usize,
+        }
+    }
+
+    fn get_size(&self) -> usize {
+        0
+    }
+
+    fn get_stat(&self) -> Stat {
+        Stat::new(0, 1, StatMode::S_IFSOCK.bits() | 0o777, 1, 0, 0, 0, 0, 0)
+    }
+
+    fn get_file_type(&self) -> DiskInodeType {
+        DiskInodeType::Socket
+    }
+
+    fn is_file(&self) -> bool {
+        false
+    }
+
+    fn info_dirtree_node(&self, _dirnode_ptr: Weak<DirectoryTreeNode>) {}
+
+    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>> {
+        None
+    }
+
+    fn open(&self, _flags: OpenFlags, _special_use: bool) -> Arc<dyn File> {
+        self.deep_clone()
+    }
+
+    fn open_subfile(&self) -> Result<Vec<(String, Arc<dyn File>)>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn create(&self, _name: &str, _file_type: DiskInodeType) -> Result<Arc<dyn File>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn link_child(&self, _name: &str, _child: &Self) -> Result<(), isize> {
+        Err(ENOTDIR)
+    }
+
+    fn unlink(&self, _delete: bool) -> Result<(), isize> {
+        Err(EACCES)
+    }
+
+    fn get_dirent(&self, _count: usize) -> Vec<Dirent> {
+        Vec::new()
+    }
+
+    fn lseek(&self, _offset: isize, _whence: SeekWhence) -> Result<usize, isize> {
+        Err(ESPIPE)
+    }
+
+    fn modify_size(&self, _diff: isize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn truncate_size(&self, _new_size: usize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn set_timestamp(&self, _ctime: Option<usize>, _atime: Option<usize>, _mtime: Option<usize>) {}
+
+    fn get_single_cache(&self, _offset: usize) -> Result<Arc<Mutex<PageCache>>, ()> {
+        Err(())
+    }
+
+    fn get_all_caches(&self) -> Result<Vec<Arc<Mutex<PageCache>>>, ()> {
+        Err(())
+    }
+
+    fn oom(&self) -> usize {
+        0
+    }
+
+    /// 流套接字的对端关闭了连接
+    fn hang_up(&self) -> bool {
+        match &*self.state.lock() {
+            StreamState::Connected { read_end, .. } => read_end.hang_up(),
+            _ => false,
+        }
+    }
+
+    fn fcntl(&self, _cmd: u32, _arg: u32) -> isize {
+        EINVAL
+    }
 }
-impl<const N: usize> File for UnixSocket<N> {
-    fn deep_clone(&self) -> Arc<dyn File>{
-        todo!();
-    }
-    fn readable(&self) -> bool{
-        todo!();
-    }
-    fn writable(&self) -> bool{
-        todo!();
-    }
-    fn read(&self, _offset: Option<&mut usize>, _buf: &mut [u8]) -> usize{todo!();}
-    fn write(&self, _offset: Option<&mut usize>, _buf: &[u8]) -> usize{todo!();}
-    fn r_ready(&self) -> bool{todo!();}
-    fn w_ready(&self) -> bool{todo!();}
-    fn read_user(&self, _offset: Option<usize>, _buf: UserBuffer) -> usize{todo!();}
-    fn write_user(&self, _offset: Option<usize>, _buf: UserBuffer) -> usize{todo!();}
-    fn get_size(&self) -> usize{todo!();}
-    fn get_stat(&self) -> Stat{todo!();}
-    fn get_file_type(&self) -> DiskInodeType{todo!();}
-    fn is_dir(&self) -> bool {todo!();}
-    fn is_file(&self) -> bool {todo!();}
-    fn info_dirtree_node(&self, _dirnode_ptr: Weak<DirectoryTreeNode>){todo!();}
-    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>>{todo!();}
-    /// open
-    fn open(&self, _flags: OpenFlags, _special_use: bool) -> Arc<dyn File>{todo!();}
-    fn open_subfile(&self) -> Result<Vec<(String, Arc<dyn File>)>, isize>{todo!();}
-    /// create
-    fn create(&self, _name: &str, _file_type: DiskInodeType) -> Result<Arc<dyn File>, isize>{todo!();}
-    fn link_child(&self, _name: &str, _child: &Self) -> Result<(), isize>{todo!();}
-    /// delete(unlink)
-    fn unlink(&self, _delete: bool) -> Result<(), isize>{todo!();}
-    /// dirent
-    fn get_dirent(&self, _count: usize) -> Vec<Dirent>{todo!();}
-    /// offset
-    fn get_offset(&self) -> usize {todo!();}
-    fn lseek(&self, _offset: isize, _whence: SeekWhence) -> Result<usize, isize>{todo!();}
-    /// size
-    fn modify_size(&self, _diff: isize) -> Result<(), isize>{todo!();}
-    fn truncate_size(&self, _new_size: usize) -> Result<(), isize>{todo!();}
-    // time
-    fn set_timestamp(&self, _ctime: Option<usize>, _atime: Option<usize>, _mtime: Option<usize>){todo!();}
-    /// cache
-    fn get_single_cache(&self, _offset: usize) -> Result<Arc<Mutex<PageCache>>, ()>{todo!();}
-    fn get_all_caches(&self) -> Result<Vec<Arc<Mutex<PageCache>>>, ()>{todo!();}
-    /// memory related
-    fn oom(&self) -> usize{todo!();}
-    /// poll, select related
-    fn hang_up(&self) -> bool{todo!();}
-    /// iotcl
-    fn ioctl(&self, _cmd: u32, _argp: usize) -> isize {todo!();}
-    /// fcntl
-    fn fcntl(&self, _cmd: u32, _arg: u32) -> isize{todo!();}
+
+/// bind 到路径上的套接字在目录树中的文件。
+/// 只用于 connect 与 sendto 按路径找到套接字，打开后不能读写
+pub struct UnixSocketNode {
+    socket: Weak<UnixSocket>,
 }
 
-pub fn make_unix_socket_pair<const N: usize>() -> (Arc<UnixSocket<N>>, Arc<UnixSocket<N>>){
-    let (read1, write1) = make_pipe();
-    let (read2, write2) = make_pipe();
-    let socket1 = Arc::new(UnixSocket::new(read1, write2));
-    let socket2 = Arc::new(UnixSocket::new(read2, write1));
-    (socket1, socket2)
+impl File for UnixSocketNode {
+    fn deep_clone(&self) -> Arc<dyn File> {
+        Arc::new(UnixSocketNode {
+            socket: self.socket.clone(),
+        })
+    }
+
+    fn readable(&self) -> bool {
+        false
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    fn read(&self, _offset: Option<&mut usize>, _buf: &mut [u8]) -> usize {
+        ENXIO as usize
+    }
+
+    fn write(&self, _offset: Option<&mut usize>, _buf: &[u8]) -> usize {
+        ENXIO as usize
+    }
+
+    fn r_ready(&self) -> bool {
+        false
+    }
+
+    fn w_ready(&self) -> bool {
+        false
+    }
+
+    fn read_user(&self, _offset: Option<usize>, _buf: UserBuffer) -> usize {
+        ENXIO as usize
+    }
+
+    fn write_user(&self, _offset: Option<usize>, _buf: UserBuffer) -> usize {
+        ENXIO as usize
+    }
+
+    fn get_size(&self) -> usize {
+        0
+    }
+
+    fn get_stat(&self) -> Stat {
+        Stat::new(0, 1, StatMode::S_IFSOCK.bits() | 0o755, 1, 0, 0, 0, 0, 0)
+    }
+
+    fn get_file_type(&self) -> DiskInodeType {
+        DiskInodeType::Socket
+    }
+
+    fn is_file(&self) -> bool {
+        false
+    }
+
+    fn info_dirtree_node(&self, _dirnode_ptr: Weak<DirectoryTreeNode>) {}
+
+    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>> {
+        None
+    }
+
+    fn open(&self, _flags: OpenFlags, _special_use: bool) -> Arc<dyn File> {
+        self.deep_clone()
+    }
+
+    fn open_subfile(&self) -> Result<Vec<(String, Arc<dyn File>)>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn create(&self, _name: &str, _file_type: DiskInodeType) -> Result<Arc<dyn File>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn link_child(&self, _name: &str, _child: &Self) -> Result<(), isize> {
+        Err(ENOTDIR)
+    }
+
+    /// unlink 只把文件从目录树中移除，已绑定的套接字不受影响
+    fn unlink(&self, _delete: bool) -> Result<(), isize> {
+        Ok(())
+    }
+
+    fn get_dirent(&self, _count: usize) -> Vec<Dirent> {
+        Vec::new()
+    }
+
+    fn lseek(&self, _offset: isize, _whence: SeekWhence) -> Result<usize, isize> {
+        Err(ESPIPE)
+    }
+
+    fn modify_size(&self, _diff: isize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn truncate_size(&self, _new_size: usize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn set_timestamp(&self, _ctime: Option<usize>, _atime: Option<usize>, _mtime: Option<usize>) {}
+
+    fn get_single_cache(&self, _offset: usize) -> Result<Arc<Mutex<PageCache>>, ()> {
+        Err(())
+    }
+
+    fn get_all_caches(&self) -> Result<Vec<Arc<Mutex<PageCache>>>, ()> {
+        Err(())
+    }
+
+    fn oom(&self) -> usize {
+        0
+    }
+
+    fn hang_up(&self) -> bool {
+        false
+    }
+
+    fn fcntl(&self, _cmd: u32, _arg: u32) -> isize {
+        EINVAL
+    }
 }
@@ -0,0 +1,157 @@
+//! Boot-time self tests
+//!
+//! Built with the `selftest` feature, [`run`] is called by the boot hart once the
+//! kernel subsystems are up and before `initproc` is scheduled. Every check prints
+//! a pass/fail line. Once the other harts are up, [`finish`] has all of them print
+//! concurrently and then the summary follows, which CI can grep without running a
+//! full userspace.
+//!
+//! With `selftest_halt` the machine is powered off after the summary,
+//! otherwise booting continues into userspace.
+
+mod block;
+mod boot;
+mod ext4;
+mod fs;
+mod hal;
+mod mm;
+mod sched;
+mod task;
+mod timer;
+mod utils;
+
+use crate::hal::shutdown;
+use crate::utils::telemetry::{Counter, PerCpuCounter};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+type CheckResult = Result<(), &'static str>;
+
+struct Check {
+    name: &'static str,
+    run: fn() -> CheckResult,
+}
+
+/// Checks of every subsystem, run in this order
+const CHECKS: &[&[Check]] = &[
+    mm::CHECKS,
+    block::CHECKS,
+    sched::CHECKS,
+    utils::CHECKS,
+    task::CHECKS,
+    boot::CHECKS,
+    fs::CHECKS,
+    ext4::CHECKS,
+    timer::CHECKS,
+    hal::CHECKS,
+];
+
+fn ensure(cond: bool, msg: &'static str) -> CheckResult {
+    if cond {
+        Ok(())
+    } else {
+        Err(msg)
+    }
+}
+
+/// Checks run by [`run`] and [`finish`], counted in the summary
+fn check_count() -> usize {
+    CHECKS.iter().map(|checks| checks.len()).sum::<usize>() + SMP_CHECKS
+}
+
+/// `take` on the counters all secondary harts incremented lost no increment
+/// and left nothing behind
+fn check_take_smp(total: u64, per_cpu_total: u64) -> CheckResult {
+    let expected = (HARTS.load(Ordering::Relaxed) as u64 - 1) * TAKE_INCREMENTS;
+    ensure(total == expected, "Counter::take lost increments")?;
+    ensure(
+        per_cpu_total == expected,
+        "PerCpuCounter::take lost increments",
+    )?;
+    ensure(
+        TAKE_COUNTER.get() == 0 && TAKE_PER_CPU.sum() == 0,
+        "counts left after take",
+    )
+}
+
+/// Failed checks, the summary is printed once all harts are done
+static FAILED: AtomicUsize = AtomicUsize::new(0);
+/// Harts expected in [`finish`], the boot hart plus every started secondary
+static HARTS: AtomicUsize = AtomicUsize::new(1);
+static HARTS_DONE: AtomicUsize = AtomicUsize::new(0);
+
+const CONSOLE_LINES: usize = 32;
+/// Checks run by [`finish`] once all harts are up, counted in the summary
+const SMP_CHECKS: usize = 1;
+/// Increments of the `take` counters by every secondary hart in [`finish`]
+const TAKE_INCREMENTS: u64 = 100_000;
+static TAKE_COUNTER: Counter = Counter::new("selftest_take_total", "incremented while taken");
+static TAKE_PER_CPU: PerCpuCounter = PerCpuCounter::new("selftest_take_per_cpu_total");
+/// Long enough that a line takes many `console_putchar` calls to write
+const CONSOLE_PADDING: &str = "................................................";
+
+/// Run every check and print the results, on the boot hart before other harts start
+pub fn run() {
+    println!("[selftest] running {} checks", check_count());
+    for check in CHECKS.iter().flat_map(|checks| checks.iter()) {
+        report(check.name, (check.run)());
+    }
+}
+
+fn report(name: &str, result: CheckResult) {
+    match result {
+        Ok(()) => println!("[selftest] {:<16} ok", name),
+        Err(msg) => {
+            FAILED.fetch_add(1, Ordering::Relaxed);
+            println!("[selftest] {:<16} FAILED: {}", name, msg);
+        }
+    }
+}
+
+/// Count a secondary hart that will call [`finish`], before it is released
+pub fn hart_started() {
+    HARTS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Called by every hart before entering the scheduler.
+///
+/// All harts print at once to exercise the console lock: each line must come out
+/// whole, which the `selftest` make target checks. The secondary harts then
+/// increment the `take` counters while the boot hart keeps taking from them like
+/// a periodic exporter; once the others are done it checks that the interval
+/// deltas add up to every increment and prints the summary.
+pub fn finish(hart_id: usize, is_bsp: bool) {
+    for line in 0..CONSOLE_LINES {
+        println!(
+            "[selftest] hart {} line {:02} {}",
+            hart_id, line, CONSOLE_PADDING
+        );
+    }
+    if !is_bsp {
+        for _ in 0..TAKE_INCREMENTS {
+            TAKE_COUNTER.inc();
+            TAKE_PER_CPU.inc();
+        }
+        HARTS_DONE.fetch_add(1, Ordering::AcqRel);
+        return;
+    }
+    HARTS_DONE.fetch_add(1, Ordering::AcqRel);
+    let (mut total, mut per_cpu_total) = (0, 0);
+    while HARTS_DONE.load(Ordering::Acquire) < HARTS.load(Ordering::Relaxed) {
+        total += TAKE_COUNTER.take();
+        per_cpu_total += TAKE_PER_CPU.take();
+        core::hint::spin_loop();
+    }
+    total += TAKE_COUNTER.take();
+    per_cpu_total += TAKE_PER_CPU.take();
+    report("take_smp", check_take_smp(total, per_cpu_total));
+    let failed = FAILED.load(Ordering::Relaxed);
+    println!(
+        "[selftest] {} passed, {} failed",
+        check_count() - failed,
+        failed
+    );
+    if cfg!(feature = "selftest_halt") {
+        println!("[selftest] halting");
+        shutdown();
+    }
+}
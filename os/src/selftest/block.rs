@@ -0,0 +1,241 @@
+//! Block layer checks: RAM disks, linear devices, the request queue and partition tables
+
+use crate::drivers::block::{
+    probe_partitions, BlockDevice, LinearDevice, LinearTarget, RamDisk, RequestQueue,
+};
+use crate::drivers::BLOCK_DEVICE;
+use crate::hal::BLOCK_SZ;
+use crate::utils::telemetry::BLOCK_REQUESTS;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use super::{ensure, Check, CheckResult};
+
+pub(super) const CHECKS: &[Check] = &[
+    Check {
+        name: "block_device",
+        run: check_block_device,
+    },
+    Check {
+        name: "linear_device",
+        run: check_linear_device,
+    },
+    Check {
+        name: "request_queue",
+        run: check_request_queue,
+    },
+    Check {
+        name: "partitions",
+        run: check_partitions,
+    },
+];
+
+/// Write a pattern to a block and read it back, then restore the original contents
+fn check_block_device() -> CheckResult {
+    const BLOCK_ID: usize = 0;
+    let device = BLOCK_DEVICE.clone();
+    let mut saved = [0u8; BLOCK_SZ];
+    let mut buf = [0u8; BLOCK_SZ];
+    device.read_block(BLOCK_ID, &mut saved);
+    let pattern: Vec<u8> = (0..BLOCK_SZ).map(|i| (i as u8) ^ 0x5a).collect();
+    device.write_block(BLOCK_ID, &pattern);
+    device.read_block(BLOCK_ID, &mut buf);
+    let matched = buf[..] == pattern[..];
+    device.write_block(BLOCK_ID, &saved);
+    ensure(matched, "read back differs from written data")?;
+    device.read_block(BLOCK_ID, &mut buf);
+    ensure(buf == saved, "original contents not restored")
+}
+
+/// A RAM disk of `blocks` blocks, every byte set to `fill`
+fn ram_disk(blocks: usize, fill: u8) -> Arc<RamDisk> {
+    let disk = Arc::new(RamDisk::new(blocks * BLOCK_SZ));
+    disk.write_at(0, &alloc::vec![fill; blocks * BLOCK_SZ]);
+    disk
+}
+
+/// Change the contents of `disk` in place through `edit`
+fn edit_ram_disk(disk: &RamDisk, edit: impl FnOnce(&mut [u8])) {
+    let mut image = alloc::vec![0u8; disk.size()];
+    disk.read_at(0, &mut image);
+    edit(&mut image);
+    disk.write_at(0, &image);
+}
+
+/// Map the last two blocks of one RAM disk and the first two of another into a
+/// linear device: a read across the boundary gets blocks from both, a write
+/// across it lands on both, and blocks past the end read as zeros
+fn check_linear_device() -> CheckResult {
+    let (first, second) = (ram_disk(4, 0x11), ram_disk(4, 0x22));
+    let device = LinearDevice::new(alloc::vec![
+        LinearTarget {
+            device: first.clone(),
+            offset: 2,
+            len: 2,
+        },
+        LinearTarget {
+            device: second.clone(),
+            offset: 0,
+            len: 2,
+        },
+    ])
+    .map_err(|_| "table refused")?;
+    ensure(device.blocks() == 4, "wrong number of blocks")?;
+    let mut buf = alloc::vec![0u8; 2 * BLOCK_SZ];
+    device.read_block(1, &mut buf);
+    ensure(
+        buf[..BLOCK_SZ].iter().all(|&byte| byte == 0x11)
+            && buf[BLOCK_SZ..].iter().all(|&byte| byte == 0x22),
+        "read across the boundary differs",
+    )?;
+    device.write_block(1, &alloc::vec![0x33u8; 2 * BLOCK_SZ]);
+    let mut block = [0u8; BLOCK_SZ];
+    first.read_block(3, &mut block);
+    ensure(block == [0x33; BLOCK_SZ], "write missed the first device")?;
+    second.read_block(0, &mut block);
+    ensure(block == [0x33; BLOCK_SZ], "write missed the second device")?;
+    second.read_block(1, &mut block);
+    ensure(block == [0x22; BLOCK_SZ], "write went past its blocks")?;
+    device.read_block(4, &mut block);
+    ensure(block == [0; BLOCK_SZ], "block past the end not zero")?;
+    ensure(LinearDevice::new(Vec::new()).is_err(), "empty table accepted")
+}
+
+/// Three single-block writes queued out of order reach the disk as one merged
+/// request after the read queued with them, a vectored read splits the
+/// blocks across its buffers, and a read of a block with a queued write sees
+/// the written data
+fn check_request_queue() -> CheckResult {
+    let disk = ram_disk(8, 0x11);
+    let blocks: Vec<_> = (1..=3u8).map(|fill| alloc::vec![fill; BLOCK_SZ]).collect();
+    let mut read = alloc::vec![0u8; BLOCK_SZ];
+    let before = BLOCK_REQUESTS.get();
+    let mut queue = RequestQueue::new(disk.as_ref());
+    queue.write(3, &blocks[2]);
+    queue.write(1, &blocks[0]);
+    queue.read(6, &mut read);
+    queue.write(2, &blocks[1]);
+    queue.dispatch();
+    ensure(
+        BLOCK_REQUESTS.get() - before == 2,
+        "adjacent writes were not merged",
+    )?;
+    ensure(read.iter().all(|&byte| byte == 0x11), "queued read differs")?;
+    let mut written = alloc::vec![0u8; 3 * BLOCK_SZ];
+    disk.read_block(1, &mut written);
+    ensure(
+        written
+            .chunks(BLOCK_SZ)
+            .zip(&blocks)
+            .all(|(got, want)| got == &want[..]),
+        "merged write put blocks in the wrong place",
+    )?;
+    let (mut first, mut rest) = ([0u8; BLOCK_SZ], alloc::vec![0u8; 2 * BLOCK_SZ]);
+    disk.read_blocks(1, &mut [&mut first[..], &mut rest[..]]);
+    ensure(
+        first[..] == written[..BLOCK_SZ] && rest[..] == written[BLOCK_SZ..],
+        "vectored read split the blocks wrongly",
+    )?;
+    let mut queue = RequestQueue::new(disk.as_ref());
+    let rewrite = [0x44u8; BLOCK_SZ];
+    queue.write(6, &rewrite);
+    queue.read(6, &mut read);
+    queue.dispatch();
+    ensure(read == rewrite, "read overtook a queued write to its block")
+}
+
+/// Fill MBR partition entry `slot` of the sector at `sector`
+fn mbr_entry(sector: &mut [u8], slot: usize, kind: u8, start: u32, sectors: u32) {
+    let entry = &mut sector[446 + 16 * slot..446 + 16 * (slot + 1)];
+    entry[4] = kind;
+    entry[8..12].copy_from_slice(&start.to_le_bytes());
+    entry[12..16].copy_from_slice(&sectors.to_le_bytes());
+}
+
+/// Numbers and EFI system flags of the partitions found on `disk`
+fn partitions(disk: &Arc<RamDisk>) -> Vec<(usize, bool)> {
+    let disk: Arc<dyn BlockDevice> = disk.clone();
+    probe_partitions(&disk)
+        .iter()
+        .map(|partition| {
+            (
+                partition.partition().number,
+                partition.partition().efi_system,
+            )
+        })
+        .collect()
+}
+
+/// An MBR with an EFI system partition, a primary partition and a logical one
+/// in an extended partition, then a GPT with two used entries, are read with
+/// Linux's partition numbers; a partition's blocks map into its range and stop
+/// at its end, and a FAT boot sector is not taken for a partition table
+fn check_partitions() -> CheckResult {
+    const SECTOR: usize = 512;
+    let disk = ram_disk(64 * SECTOR / BLOCK_SZ, 0x11);
+    edit_ram_disk(&disk, |image| {
+        image[..4 * SECTOR].fill(0);
+        image[48 * SECTOR..49 * SECTOR].fill(0);
+        mbr_entry(&mut image[..SECTOR], 0, 0xef, 8, 8);
+        mbr_entry(&mut image[..SECTOR], 1, 0x83, 16, 32);
+        mbr_entry(&mut image[..SECTOR], 2, 0x05, 48, 16);
+        mbr_entry(&mut image[48 * SECTOR..49 * SECTOR], 0, 0x83, 8, 8);
+        image[510..512].copy_from_slice(&[0x55, 0xaa]);
+        image[48 * SECTOR + 510..49 * SECTOR].copy_from_slice(&[0x55, 0xaa]);
+    });
+    ensure(
+        partitions(&disk) == [(1, true), (2, false), (5, false)],
+        "MBR partitions misread",
+    )?;
+    let device: Arc<dyn BlockDevice> = disk.clone();
+    let found = probe_partitions(&device);
+    let root = &found[1];
+    ensure(
+        root.blocks() == 32 * SECTOR / BLOCK_SZ,
+        "wrong partition size",
+    )?;
+    root.write_block(0, &[0x22; BLOCK_SZ]);
+    let mut block = [0u8; BLOCK_SZ];
+    disk.read_at(16 * SECTOR, &mut block);
+    ensure(
+        block == [0x22; BLOCK_SZ],
+        "partition block not at its offset",
+    )?;
+    block.fill(0x33);
+    root.read_block(root.blocks(), &mut block);
+    ensure(block == [0; BLOCK_SZ], "block past the partition not zero")?;
+    edit_ram_disk(&disk, |image| {
+        image[..4 * SECTOR].fill(0);
+        mbr_entry(&mut image[..SECTOR], 0, 0xee, 1, 63);
+        image[510..512].copy_from_slice(&[0x55, 0xaa]);
+        let header = &mut image[SECTOR..2 * SECTOR];
+        header[..8].copy_from_slice(b"EFI PART");
+        header[72..80].copy_from_slice(&2u64.to_le_bytes());
+        header[80..84].copy_from_slice(&4u32.to_le_bytes());
+        header[84..88].copy_from_slice(&128u32.to_le_bytes());
+        let esp = [
+            0x28, 0x73, 0x2a, 0xc1, 0x1f, 0xf8, 0xd2, 0x11, 0xba, 0x4b, 0x00, 0xa0, 0xc9, 0x3e,
+            0xc9, 0x3b,
+        ];
+        for (index, kind, first, last) in [(0, esp, 8u64, 15u64), (2, [0x83; 16], 16, 47)] {
+            let entry = &mut image[2 * SECTOR + 128 * index..2 * SECTOR + 128 * (index + 1)];
+            entry[..16].copy_from_slice(&kind);
+            entry[32..40].copy_from_slice(&first.to_le_bytes());
+            entry[40..48].copy_from_slice(&last.to_le_bytes());
+        }
+    });
+    ensure(
+        partitions(&disk) == [(1, true), (3, false)],
+        "GPT partitions misread",
+    )?;
+    edit_ram_disk(&disk, |image| {
+        image[..SECTOR].fill(0);
+        image[..3].copy_from_slice(&[0xeb, 0x58, 0x90]);
+        image[82..87].copy_from_slice(b"FAT32");
+        image[510..512].copy_from_slice(&[0x55, 0xaa]);
+    });
+    ensure(
+        partitions(&disk).is_empty(),
+        "FAT boot sector read as a partition table",
+    )
+}
@@ -0,0 +1,230 @@
+//! Boot checks: the command line, the initramfs and the crash dump
+
+use crate::cmdline::{Cmdline, DEFAULT_INIT, DEFAULT_RAMDISK_SIZE};
+use crate::crashdump;
+use crate::fs::cpio::{self, CpioError, S_IFDIR, S_IFREG};
+use crate::fs::{initramfs, OpenFlags, ROOT_FD};
+use alloc::vec::Vec;
+use core::fmt::Write;
+use log::LevelFilter;
+
+use super::{ensure, Check, CheckResult};
+
+pub(super) const CHECKS: &[Check] = &[
+    Check {
+        name: "initramfs",
+        run: check_initramfs,
+    },
+    Check {
+        name: "crashdump_format",
+        run: check_crashdump_format,
+    },
+    Check {
+        name: "crashdump",
+        run: check_crashdump,
+    },
+    Check {
+        name: "cmdline",
+        run: check_cmdline,
+    },
+    Check {
+        name: "cpio",
+        run: check_cpio,
+    },
+];
+
+/// Unpack a two-file archive into a scratch directory and read both files back
+fn check_initramfs() -> CheckResult {
+    const DEST: &str = "/initramfs_selftest";
+    const FILES: [(&str, &[u8]); 2] = [
+        ("bin/hello", b"hello from initramfs"),
+        ("etc/motd", b"welcome"),
+    ];
+    let archive = cpio::build(&[
+        ("bin", S_IFDIR | 0o755, b""),
+        (FILES[0].0, S_IFREG | 0o755, FILES[0].1),
+        (FILES[1].0, S_IFREG | 0o644, FILES[1].1),
+    ]);
+    let unpacked = initramfs::unpack_into(&archive, DEST).map_err(|_| "archive rejected")?;
+    let mut result = ensure(unpacked.1 == FILES.len(), "not every file created");
+    for (name, data) in FILES {
+        let path = alloc::format!("{}/{}", DEST, name);
+        let mut buf = [0u8; 32];
+        let read = match ROOT_FD.open(&path, OpenFlags::O_RDONLY, false) {
+            Ok(file) => file.read(None, &mut buf),
+            Err(_) => 0,
+        };
+        result = result.and(ensure(&buf[..read] == data, "unpacked file not readable"));
+        let _ = ROOT_FD.delete(&path, false);
+    }
+    for dir in ["bin", "etc"] {
+        let _ = ROOT_FD.delete(&alloc::format!("{}/{}", DEST, dir), true);
+    }
+    let _ = ROOT_FD.delete(DEST, true);
+    result
+}
+
+/// A report reads back with the escape sequences of the console output
+/// stripped, a corrupted one not at all, and one too long for its region
+/// comes back cut short
+fn check_crashdump_format() -> CheckResult {
+    let mut region = alloc::vec![0u8; 256];
+    ensure(crashdump::parse(&region).is_none(), "empty region parsed")?;
+    let mut w = crashdump::Writer::new(&mut region);
+    let _ = write!(w, "panic: {}", 42);
+    w.write_console(b"\x1b[34mkernel: hello\x1b[0m\r\n");
+    w.finish();
+    ensure(
+        crashdump::parse(&region) == Some("panic: 42kernel: hello\n"),
+        "report not read back",
+    )?;
+    region[crashdump::HEADER_LEN] = b'P';
+    ensure(
+        crashdump::parse(&region).is_none(),
+        "corrupted report parsed",
+    )?;
+    let mut region = alloc::vec![0u8; crashdump::HEADER_LEN + 8];
+    let mut w = crashdump::Writer::new(&mut region);
+    let _ = write!(w, "0123456789");
+    w.finish();
+    ensure(
+        crashdump::parse(&region) == Some("01234567"),
+        "report not truncated to its region",
+    )
+}
+
+/// Message of the panic that `panic_reboot` boots trigger on purpose
+const CONTROLLED_PANIC: &str = "selftest: controlled panic for the crash dump";
+
+/// Format a report into a scratch buffer and parse it back.
+///
+/// With `panic_reboot`, the first boot panics on purpose; after the reboot the
+/// report of that panic must be readable from `/proc/crashdump`.
+fn check_crashdump() -> CheckResult {
+    let mut region = alloc::vec![0u8; 4096];
+    let mut w = crashdump::Writer::new(&mut region);
+    let _ = crashdump::write_report(
+        &mut w,
+        Some(core::panic::Location::caller()),
+        Some(&format_args!("selftest report")),
+    );
+    w.finish();
+    let text = crashdump::parse(&region).ok_or("report not parsed back")?;
+    ensure(
+        text.contains("panic: ") && text.contains("selftest report"),
+        "panic message missing",
+    )?;
+    ensure(text.contains("console, last"), "console output missing")?;
+    if !crate::cmdline::get().panic_reboot {
+        return Ok(());
+    }
+    if crashdump::previous().is_none() {
+        panic!("{}", CONTROLLED_PANIC);
+    }
+    let mut buf = alloc::vec![0u8; crashdump::REGION_SIZE];
+    let read = match ROOT_FD.open("/proc/crashdump", OpenFlags::O_RDONLY, false) {
+        Ok(file) => file.read(None, &mut buf),
+        Err(_) => return Err("/proc/crashdump not found"),
+    };
+    let text = core::str::from_utf8(&buf[..read]).map_err(|_| "dump is not UTF-8")?;
+    ensure(
+        text.contains(CONTROLLED_PANIC),
+        "previous panic not in /proc/crashdump",
+    )?;
+    ensure(
+        text.contains("[selftest] initramfs"),
+        "console output not in /proc/crashdump",
+    )
+}
+
+/// Parse a sample command line, then one with repeated, bogus and empty options
+fn check_cmdline() -> CheckResult {
+    let cmdline = Cmdline::parse(
+        "console=ttyS0 loglevel=4 init=/busybox ro nosmp panic_reboot initrdmem=0x84000000,4096",
+    );
+    ensure(
+        cmdline.loglevel == Some(LevelFilter::Warn),
+        "numeric loglevel",
+    )?;
+    ensure(cmdline.init_path() == "/busybox", "init path")?;
+    ensure(
+        cmdline.read_only && cmdline.nosmp && cmdline.panic_reboot,
+        "flags not set",
+    )?;
+    ensure(
+        cmdline.initrd == Some((0x8400_0000, 0x8400_1000)),
+        "initrdmem range",
+    )?;
+    ensure(
+        Cmdline::parse("ramdisk_size=64").ramdisk_size == 64 * 1024,
+        "ramdisk_size not in KiB",
+    )?;
+    ensure(
+        Cmdline::parse("root=/dev/vda2").root == Some(2)
+            && Cmdline::parse("root=/dev/sda").root == Some(0)
+            && Cmdline::parse("root=PARTUUID=1234").root.is_none(),
+        "root partition",
+    )?;
+    let cmdline = Cmdline::parse("");
+    ensure(
+        cmdline.loglevel.is_none()
+            && cmdline.init_path() == DEFAULT_INIT
+            && !cmdline.read_only
+            && !cmdline.nosmp
+            && !cmdline.panic_reboot
+            && cmdline.ramdisk_size == DEFAULT_RAMDISK_SIZE,
+        "wrong defaults",
+    )?;
+    let cmdline = Cmdline::parse("ro loglevel=debug rw loglevel=bogus init= nosmp=1");
+    ensure(
+        cmdline.loglevel == Some(LevelFilter::Debug),
+        "bogus loglevel overrode a valid one",
+    )?;
+    ensure(cmdline.init_path() == DEFAULT_INIT, "empty init accepted")?;
+    ensure(
+        !cmdline.read_only && !cmdline.nosmp,
+        "later option did not override, or flag took a value",
+    )
+}
+
+/// Read the entries of a small archive back, and reject truncated or foreign ones
+fn check_cpio() -> CheckResult {
+    let archive = cpio::build(&[
+        ("bin", S_IFDIR | 0o755, b""),
+        ("bin/hello", S_IFREG | 0o755, b"hello world"),
+        ("etc.txt", S_IFREG | 0o644, b"abc"),
+    ]);
+    let entries = cpio::Reader::new(&archive)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| "archive rejected")?;
+    ensure(entries.len() == 3, "wrong number of entries")?;
+    ensure(
+        entries[0].is_dir() && entries[1].is_file(),
+        "wrong entry types",
+    )?;
+    ensure(
+        entries[1].name == "bin/hello" && entries[1].data == b"hello world",
+        "first file differs",
+    )?;
+    ensure(
+        entries[2].name == "etc.txt" && entries[2].data == b"abc",
+        "second file differs",
+    )?;
+    let archive = cpio::build(&[("a", S_IFREG | 0o644, b"0123456789")]);
+    ensure(
+        matches!(
+            cpio::Reader::new(&archive[..cpio::HEADER_LEN + 8]).next(),
+            Some(Err(CpioError::Truncated))
+        ),
+        "truncated archive accepted",
+    )?;
+    let mut bad = archive.clone();
+    bad[0] = b'1';
+    ensure(
+        matches!(
+            cpio::Reader::new(&bad).next(),
+            Some(Err(CpioError::BadMagic))
+        ),
+        "bad magic accepted",
+    )
+}
@@ -0,0 +1,364 @@
+//! ext4 checks, all but the checksum one format a scratch ext4 image and so
+//! need the ext4 driver of the LoongArch build
+
+use crate::fs::ext4_crc32c;
+#[cfg(feature = "loongarch64")]
+use crate::fs::{
+    mkfs_ext4, Ext4FileSystem, Ext4InodeRef, InodeFileType, EXT4_ROOT_INODE, EXT_MAX_BLOCKS,
+};
+#[cfg(feature = "loongarch64")]
+use crate::hal::BLOCK_SZ;
+#[cfg(feature = "loongarch64")]
+use crate::syscall::errno::{EIO, ENOSPC};
+#[cfg(feature = "loongarch64")]
+use alloc::vec::Vec;
+
+use super::{ensure, Check, CheckResult};
+
+pub(super) const CHECKS: &[Check] = &[
+    Check {
+        name: "ext4_crc32c",
+        run: check_ext4_crc32c,
+    },
+    #[cfg(feature = "loongarch64")]
+    Check {
+        name: "ext4_alloc",
+        run: check_ext4_alloc,
+    },
+    #[cfg(feature = "loongarch64")]
+    Check {
+        name: "ext4_extents",
+        run: check_ext4_extents,
+    },
+    #[cfg(feature = "loongarch64")]
+    Check {
+        name: "ext4_dirents",
+        run: check_ext4_dirents,
+    },
+];
+
+/// The ext4 checksums are CRC-32C without the final inversion, so inverting
+/// the result gives the standard check value
+fn check_ext4_crc32c() -> CheckResult {
+    ensure(
+        ext4_crc32c(!0, b"123456789", 9) ^ !0 == 0xE306_9283,
+        "wrong CRC-32C check value",
+    )?;
+    ensure(
+        ext4_crc32c(ext4_crc32c(!0, b"1234", 4), b"56789", 5) == ext4_crc32c(!0, b"123456789", 9),
+        "CRC-32C not continued across buffers",
+    )
+}
+
+/// Blocks and inodes freed by the ext4 allocators are handed out again, the
+/// search for a free block wraps around the group, and the free counts in the
+/// group descriptor and superblock follow the bitmaps
+#[cfg(feature = "loongarch64")]
+fn check_ext4_alloc() -> CheckResult {
+    let fs = mkfs_ext4(256, 32);
+    let usage = fs.usage();
+    fs.fsck()?;
+
+    let first = fs.balloc_alloc(None).map_err(|_| "no free block")?;
+    ensure(
+        fs.balloc_alloc(Some(first)) == Ok(first + 1),
+        "goal block not taken",
+    )?;
+    ensure(
+        fs.usage().free_blocks == usage.free_blocks - 2,
+        "free block count not lowered",
+    )?;
+    ensure(
+        fs.balloc_free(first, 1).is_ok() && fs.balloc_alloc(None) == Ok(first),
+        "freed block not reused",
+    )?;
+    ensure(
+        fs.balloc_alloc(Some(255)) == Ok(255),
+        "last block not taken",
+    )?;
+    ensure(
+        fs.balloc_alloc(Some(255)) == Ok(first + 2),
+        "search did not wrap around",
+    )?;
+    let mut taken = Vec::from([first, first + 1, 255, first + 2]);
+    while let Ok(block) = fs.balloc_alloc(None) {
+        taken.push(block);
+    }
+    ensure(
+        taken.len() == usage.free_blocks as usize && fs.balloc_alloc(None) == Err(ENOSPC),
+        "full group not reported",
+    )?;
+    for &block in &taken {
+        fs.balloc_free(block, 1).map_err(|_| "block not freed")?;
+    }
+    ensure(fs.usage() == usage, "free block count not restored")?;
+    ensure(
+        fs.balloc_free(first, 1).is_ok() && fs.usage() == usage,
+        "double free counted",
+    )?;
+    ensure(
+        fs.balloc_free(250, 10) == Err(EIO),
+        "free past the end accepted",
+    )?;
+    fs.fsck()?;
+
+    ensure(
+        fs.ialloc_alloc_inode(false) == Ok(11) && fs.ialloc_alloc_inode(true) == Ok(12),
+        "reserved inode handed out",
+    )?;
+    ensure(
+        fs.usage().free_inodes == usage.free_inodes - 2
+            && fs.usage().used_dirs == usage.used_dirs + 1,
+        "inode counts not updated",
+    )?;
+    ensure(
+        fs.ialloc_free_inode(11, false).is_ok() && fs.ialloc_alloc_inode(false) == Ok(11),
+        "freed inode not reused",
+    )?;
+    ensure(
+        fs.ialloc_free_inode(12, true).is_ok() && fs.usage().used_dirs == usage.used_dirs,
+        "directory count not lowered",
+    )?;
+    ensure(
+        fs.ialloc_free_inode(12, true) == Err(EIO),
+        "double free accepted",
+    )?;
+    let mut taken = Vec::from([11]);
+    while let Ok(ino) = fs.ialloc_alloc_inode(false) {
+        taken.push(ino);
+    }
+    ensure(
+        taken.len() == usage.free_inodes as usize && fs.ialloc_alloc_inode(false) == Err(ENOSPC),
+        "no free inode not reported",
+    )?;
+    for &ino in &taken {
+        fs.ialloc_free_inode(ino, false)
+            .map_err(|_| "inode not freed")?;
+    }
+    ensure(fs.usage() == usage, "free inode count not restored")?;
+    fs.fsck()
+}
+
+/// Maps logical block `lblock` of `file` to a newly allocated block
+#[cfg(feature = "loongarch64")]
+fn ext4_map_block(
+    fs: &Ext4FileSystem,
+    file: &mut Ext4InodeRef,
+    lblock: u32,
+) -> Result<(u32, u64), &'static str> {
+    let pblock = fs
+        .balloc_alloc_block(file, None)
+        .map_err(|_| "no free block")?;
+    fs.insert_extent(file, lblock, pblock, 1)
+        .map_err(|_| "extent not inserted")?;
+    Ok((lblock, pblock))
+}
+
+/// Writes `file` back and compares its extent tree, read from the disk, with
+/// the number of entries in each node and with the blocks mapped so far
+#[cfg(feature = "loongarch64")]
+fn ext4_check_extents(
+    fs: &Ext4FileSystem,
+    file: &mut Ext4InodeRef,
+    mapped: &[(u32, u64)],
+    levels: &[&[usize]],
+) -> CheckResult {
+    let end = mapped
+        .iter()
+        .map(|&(lblock, _)| lblock + 1)
+        .max()
+        .unwrap_or(0);
+    file.inode.set_size(end as u64 * BLOCK_SZ as u64);
+    fs.write_back_inode(file);
+    let tree = fs.extent_tree(file.inode_num)?;
+    ensure(
+        tree.levels
+            .iter()
+            .map(Vec::as_slice)
+            .eq(levels.iter().copied()),
+        "wrong extent tree shape",
+    )?;
+    ensure(
+        tree.extents
+            .iter()
+            .map(|&(_, _, len)| len as usize)
+            .sum::<usize>()
+            == mapped.len(),
+        "wrong number of blocks mapped",
+    )?;
+    for &(lblock, pblock) in mapped {
+        ensure(
+            tree.lookup(lblock) == Some(pblock) && fs.find_pblock(file, lblock) == Ok(Some(pblock)),
+            "logical block mapped wrong",
+        )?;
+    }
+    ensure(
+        fs.find_pblock(file, 3) == Ok(None) && fs.find_pblock(file, end) == Ok(None),
+        "hole mapped",
+    )?;
+    fs.fsck()
+}
+
+/// Extents of an ext4 file move from the inode into a leaf block once the
+/// root is full, full leaves split in half or, when appending, start a new
+/// leaf, a full root index adds a level, and removing a range frees every
+/// data and tree block or splits the extent it falls in
+#[cfg(feature = "loongarch64")]
+fn check_ext4_extents() -> CheckResult {
+    let fs = mkfs_ext4(2048, 64);
+    let mut file = fs
+        .create(EXT4_ROOT_INODE, "extents", InodeFileType::S_IFREG.bits())
+        .map_err(|_| "file not created")?;
+    let usage = fs.usage();
+
+    // Every other logical block, so that no two extents merge
+    let mut mapped = Vec::new();
+    for i in 0..4 {
+        mapped.push(ext4_map_block(&fs, &mut file, 2 * i)?);
+    }
+    ext4_check_extents(&fs, &mut file, &mapped, &[&[4]])?;
+    mapped.push(ext4_map_block(&fs, &mut file, 8)?);
+    ext4_check_extents(&fs, &mut file, &mapped, &[&[1], &[5]])?;
+    for i in 5..341 {
+        mapped.push(ext4_map_block(&fs, &mut file, 2 * i)?);
+    }
+    ext4_check_extents(&fs, &mut file, &mapped, &[&[2], &[340, 1]])?;
+    mapped.push(ext4_map_block(&fs, &mut file, 1)?);
+    ext4_check_extents(&fs, &mut file, &mapped, &[&[3], &[171, 170, 1]])?;
+    for i in 341..1021 {
+        mapped.push(ext4_map_block(&fs, &mut file, 2 * i)?);
+    }
+    ext4_check_extents(
+        &fs,
+        &mut file,
+        &mapped,
+        &[&[1], &[5], &[171, 170, 340, 340, 1]],
+    )?;
+
+    fs.extent_remove_space(&mut file, 0, EXT_MAX_BLOCKS)
+        .map_err(|_| "extents not removed")?;
+    ext4_check_extents(&fs, &mut file, &[], &[&[0]])?;
+    ensure(
+        fs.usage() == usage && file.inode.blocks_count() == 0,
+        "blocks left after removal",
+    )?;
+
+    let start = fs
+        .balloc_alloc_block(&mut file, None)
+        .map_err(|_| "no free block")?;
+    for pblock in start + 1..start + 8 {
+        ensure(
+            fs.balloc_alloc_block(&mut file, Some(pblock)) == Ok(pblock),
+            "free blocks not contiguous",
+        )?;
+    }
+    fs.insert_extent(&mut file, 0, start, 8)
+        .and_then(|_| fs.extent_remove_space(&mut file, 3, 4))
+        .map_err(|_| "middle of extent not removed")?;
+    let mapped: Vec<_> = (0..3).chain(5..8).map(|i| (i, start + i as u64)).collect();
+    ext4_check_extents(&fs, &mut file, &mapped, &[&[2]])?;
+    ensure(
+        fs.usage().free_blocks == usage.free_blocks - 6,
+        "removed blocks not freed",
+    )
+}
+
+/// Names of ext4 directory entries 48 bytes long, so that 200 of them spill
+/// into a third directory block
+#[cfg(feature = "loongarch64")]
+fn ext4_dirent_name(i: usize) -> alloc::string::String {
+    alloc::format!("dirent-{:033}", i)
+}
+
+/// Entries added to an ext4 directory fill its blocks and then get a new
+/// block, removed entries are merged into the one before them or, first in a
+/// block, only cleared, and their space is reused; after every step the
+/// entries of each block cover it up to the checksum tail
+#[cfg(feature = "loongarch64")]
+fn check_ext4_dirents() -> CheckResult {
+    let fs = mkfs_ext4(256, 256);
+    let usage = fs.usage();
+    let block_size = BLOCK_SZ as u64;
+    let root_size = || fs.get_inode_ref(EXT4_ROOT_INODE).inode.size();
+
+    let mut inodes = Vec::new();
+    for i in 0..200 {
+        let file = fs
+            .create(
+                EXT4_ROOT_INODE,
+                &ext4_dirent_name(i),
+                InodeFileType::S_IFREG.bits(),
+            )
+            .map_err(|_| "file not created")?;
+        inodes.push(file.inode_num);
+    }
+    ensure(
+        root_size() == 3 * block_size,
+        "directory not grown to 3 blocks",
+    )?;
+    ensure(
+        (0..200).all(|i| fs.lookup(EXT4_ROOT_INODE, &ext4_dirent_name(i)) == Some(inodes[i])),
+        "entry not found",
+    )?;
+    ensure(
+        fs.dir_get_entries(EXT4_ROOT_INODE).len() == 202,
+        "wrong number of entries",
+    )?;
+    fs.fsck()?;
+
+    // The first 84 names fill the first block after "." and "..", so name 84
+    // is the first entry of the second block
+    let mut root = fs.get_inode_ref(EXT4_ROOT_INODE);
+    for i in (0..200).step_by(2) {
+        let mut file = fs.get_inode_ref(inodes[i]);
+        fs.unlink(&mut root, &mut file, &ext4_dirent_name(i))
+            .map_err(|_| "file not unlinked")?;
+    }
+    ensure(
+        (0..200).all(|i| {
+            let found = fs.lookup(EXT4_ROOT_INODE, &ext4_dirent_name(i));
+            found == if i % 2 == 0 { None } else { Some(inodes[i]) }
+        }),
+        "wrong entries after unlink",
+    )?;
+    ensure(
+        fs.dir_get_entries(EXT4_ROOT_INODE).len() == 102
+            && fs.usage().free_inodes == usage.free_inodes - 100,
+        "unlinked entries counted",
+    )?;
+    fs.fsck()?;
+
+    for i in (0..200).step_by(2) {
+        fs.create(
+            EXT4_ROOT_INODE,
+            &ext4_dirent_name(i),
+            InodeFileType::S_IFREG.bits(),
+        )
+        .map_err(|_| "file not recreated")?;
+    }
+    ensure(root_size() == 3 * block_size, "freed entries not reused")?;
+    ensure(
+        fs.dir_get_entries(EXT4_ROOT_INODE).len() == 202,
+        "wrong number of entries",
+    )?;
+    fs.fsck()?;
+
+    let mut dir = fs
+        .create(EXT4_ROOT_INODE, "subdir", InodeFileType::S_IFDIR.bits())
+        .map_err(|_| "directory not created")?;
+    ensure(
+        fs.get_inode_ref(EXT4_ROOT_INODE).inode.links_count() == 3
+            && fs.lookup(dir.inode_num, "..") == Some(EXT4_ROOT_INODE),
+        "directory not linked to its parent",
+    )?;
+    fs.fsck()?;
+    let mut root = fs.get_inode_ref(EXT4_ROOT_INODE);
+    fs.unlink(&mut root, &mut dir, "subdir")
+        .map_err(|_| "directory not unlinked")?;
+    ensure(
+        fs.get_inode_ref(EXT4_ROOT_INODE).inode.links_count() == 2
+            && fs.lookup(EXT4_ROOT_INODE, "subdir").is_none(),
+        "directory link left",
+    )?;
+    fs.fsck()
+}
@@ -0,0 +1,270 @@
+//! File system checks: fd tables, directory entries, mounts and the tty line discipline
+
+use crate::fs::dev::tty::LineDiscipline;
+use crate::fs::dirent::{take_fitting, Dirent};
+use crate::fs::file_descriptor::FdTable;
+use crate::fs::mount::{
+    Mount, MountTable, MOUNT_ATTR_RDONLY, MS_PRIVATE, MS_SHARED, MS_SLAVE, MS_UNBINDABLE,
+};
+use crate::fs::{OpenFlags, ROOT_FD};
+use crate::syscall::errno::{EBUSY, EINVAL};
+use crate::task::Signals;
+use alloc::vec::Vec;
+
+use super::{ensure, Check, CheckResult};
+
+pub(super) const CHECKS: &[Check] = &[
+    Check {
+        name: "fd_slots",
+        run: check_fd_table_slots,
+    },
+    Check {
+        name: "dirent_records",
+        run: check_dirent_records,
+    },
+    Check {
+        name: "mount_table",
+        run: check_mount_table,
+    },
+    Check {
+        name: "mount_pivot",
+        run: check_mount_pivot,
+    },
+    Check {
+        name: "mount_propagate",
+        run: check_mount_propagation,
+    },
+    Check {
+        name: "line_discipline",
+        run: check_line_discipline,
+    },
+];
+
+/// Free fds are handed out lowest first and exactly once, whether close, take,
+/// F_DUPFD or a dup2 past the end of the table freed or filled them
+fn check_fd_table_slots() -> CheckResult {
+    let file = ROOT_FD
+        .open("/dev/null", OpenFlags::O_RDONLY, false)
+        .map_err(|_| "open /dev/null failed")?;
+    let mut table = FdTable::new(Vec::new());
+    for _ in 0..6 {
+        table.insert(file.clone()).map_err(|_| "insert failed")?;
+    }
+    table.remove(1).map_err(|_| "close failed")?;
+    table.remove(3).map_err(|_| "close failed")?;
+    table.take(4).ok_or("take failed")?;
+    ensure(
+        table.try_insert_at(file.clone(), 2) == Ok(3),
+        "F_DUPFD skipped the lowest free fd above the hint",
+    )?;
+    ensure(table.insert(file.clone()) == Ok(1), "lowest free fd not reused")?;
+    ensure(
+        table.insert(file.clone()) == Ok(4),
+        "fd filled by F_DUPFD handed out again, or fd freed by take lost",
+    )?;
+    ensure(table.insert(file.clone()) == Ok(6), "table not grown when full")?;
+    ensure(table.insert_at(file.clone(), 9) == Ok(9), "dup2 past the end failed")?;
+    ensure(
+        table.insert(file.clone()) == Ok(7)
+            && table.insert(file.clone()) == Ok(8)
+            && table.insert(file.clone()) == Ok(10),
+        "fds skipped by dup2 mishandled",
+    )
+}
+
+/// Directory records are as long as their name needs, padded to 8 bytes, and
+/// getdents fills a buffer only with the records that fit whole
+fn check_dirent_records() -> CheckResult {
+    ensure(
+        Dirent::reclen(1) == 24 && Dirent::reclen(4) == 24 && Dirent::reclen(5) == 32,
+        "wrong record length",
+    )?;
+    let long = "x".repeat(200);
+    let mut buf = Vec::new();
+    Dirent::new(7, 1, 8, &long).encode_into(&mut buf);
+    ensure(
+        buf.len() == 224 && buf[16..18] == 224u16.to_ne_bytes(),
+        "long name not in a longer record",
+    )?;
+    ensure(
+        &buf[19..219] == long.as_bytes() && buf[219] == 0,
+        "name not NUL terminated",
+    )?;
+    let fitting = take_fitting(
+        [Dirent::new(1, 1, 8, "a"), Dirent::new(2, 2, 8, &long)],
+        24 + 223,
+    );
+    ensure(fitting.len() == 1, "record past the buffer taken")
+}
+
+/// Mounts hang off the mount holding their mount point, and only mounts with
+/// nothing mounted on them can be removed
+fn check_mount_table() -> CheckResult {
+    let mut table = MountTable::new();
+    let root = table.add("/dev/root", "/", "ext4", 0);
+    let mnt = table.add("tmpfs", "/mnt", "tmpfs", 0);
+    let inner = table.add("tmpfs", "/mnt/inner", "tmpfs", MOUNT_ATTR_RDONLY);
+    let other = table.add("tmpfs", "/mntx", "tmpfs", 0);
+    let parent = |id| table.get(id).map(|mount| mount.parent_id);
+    ensure(
+        parent(root) == Some(root)
+            && parent(mnt) == Some(root)
+            && parent(inner) == Some(mnt)
+            && parent(other) == Some(root),
+        "wrong parent mount",
+    )?;
+    ensure(
+        table.descendants(root) == [mnt, other, inner],
+        "wrong descendants",
+    )?;
+    ensure(
+        table.get(inner).map(Mount::options).as_deref() == Some("ro"),
+        "read-only attribute not shown",
+    )?;
+    ensure(
+        table.remove("/mnt").err() == Some(EBUSY) && table.remove("/").err() == Some(EBUSY),
+        "busy mount removed",
+    )?;
+    ensure(
+        table.remove("/nowhere").err() == Some(EINVAL),
+        "missing mount removed",
+    )?;
+    ensure(
+        table.remove("/mnt/inner").map(|mount| mount.id) == Ok(inner)
+            && table.remove("/mnt").map(|mount| mount.id) == Ok(mnt),
+        "unmount in order failed",
+    )
+}
+
+/// pivot_root moves the old root under the new one and every mount along
+/// with it, and refuses a new root that is not a mount point
+fn check_mount_pivot() -> CheckResult {
+    let mut table = MountTable::new();
+    let root = table.add("/dev/root", "/", "ext4", 0);
+    let proc = table.add("proc", "/proc", "proc", 0);
+    let new_root = table.add("tmpfs", "/jail", "tmpfs", 0);
+    let inner = table.add("tmpfs", "/jail/old/inner", "tmpfs", 0);
+    ensure(
+        table.pivot("/", "/old") == Err(EINVAL)
+            && table.pivot("/jail", "/old") == Err(EINVAL)
+            && table.pivot("/proc/x", "/proc/x/old") == Err(EINVAL),
+        "bad pivot accepted",
+    )?;
+    table
+        .pivot("/jail", "/jail/old/inner")
+        .map_err(|_| "pivot failed")?;
+    let point = |id| table.get(id).map(|mount| mount.mount_point.as_str());
+    ensure(
+        point(new_root) == Some("/")
+            && point(inner) == Some("/old/inner")
+            && point(root) == Some("/old/inner")
+            && point(proc) == Some("/old/inner/proc"),
+        "mounts not moved",
+    )?;
+    let parent = |id| table.get(id).map(|mount| mount.parent_id);
+    ensure(
+        parent(new_root) == Some(new_root) && parent(root) == Some(inner),
+        "wrong parent after pivot",
+    )?;
+    ensure(table.remove("/").err() == Some(EBUSY), "new root removed")
+}
+
+/// A mount under a shared mount shows up in its peers and in bind mounts of
+/// the subtree, not in private ones, and an unbindable mount cannot be bound
+fn check_mount_propagation() -> CheckResult {
+    let mut table = MountTable::new();
+    table.add("/dev/root", "/", "ext4", 0);
+    let a = table.bind("/a", "/a", 0).map_err(|_| "bind failed")?;
+    table
+        .set_propagation("/a", MS_SHARED)
+        .map_err(|_| "not made shared")?;
+    let b = table.bind("/a", "/b", 0).map_err(|_| "bind failed")?;
+    let c = table.bind("/a/sub", "/c", 0).map_err(|_| "bind failed")?;
+    table.bind("/a", "/d", 0).map_err(|_| "bind failed")?;
+    table
+        .set_propagation("/d", MS_PRIVATE)
+        .map_err(|_| "not made private")?;
+    ensure(
+        table.get(b).map(|mount| mount.peer_group) == Some(a),
+        "bind of a shared mount not a peer",
+    )?;
+    ensure(
+        table.get(c).map(|mount| mount.root.as_str()) == Some("/a/sub"),
+        "wrong root of a subtree bind",
+    )?;
+    table.add("tmpfs", "/a/sub/mnt", "tmpfs", 0);
+    let points: Vec<&str> = table
+        .iter()
+        .map(|mount| mount.mount_point.as_str())
+        .collect();
+    ensure(
+        points.contains(&"/b/sub/mnt") && points.contains(&"/c/mnt"),
+        "mount not propagated",
+    )?;
+    ensure(
+        !points.contains(&"/d/sub/mnt"),
+        "mount propagated to a private mount",
+    )?;
+    table.remove("/a/sub/mnt").map_err(|_| "unmount failed")?;
+    ensure(
+        table
+            .iter()
+            .all(|mount| !mount.mount_point.ends_with("/mnt")),
+        "unmount not propagated",
+    )?;
+    table
+        .set_propagation("/b", MS_SLAVE)
+        .map_err(|_| "not made slave")?;
+    ensure(
+        table.get(b).map(|mount| mount.master) == Some(a),
+        "slave lost its master",
+    )?;
+    table
+        .set_propagation("/d", MS_UNBINDABLE)
+        .map_err(|_| "not made unbindable")?;
+    ensure(
+        table.bind("/d", "/e", 0) == Err(EINVAL),
+        "unbindable mount bound",
+    )
+}
+
+/// Canonical input is edited and readable a line at a time, with CR turned into NL
+/// and the echo in caret notation; VEOF at the start of a line reads as end of
+/// file and VINTR raises SIGINT and discards the pending input
+fn check_line_discipline() -> CheckResult {
+    let mut ldisc = LineDiscipline::new();
+    let mut echo = Vec::new();
+    for &c in b"ab\x7fc" {
+        ensure(
+            ldisc.receive(c, &mut echo).is_none(),
+            "a plain byte raised a signal",
+        )?;
+    }
+    ensure(!ldisc.readable(), "an unfinished line is readable")?;
+    ldisc.receive(b'\r', &mut echo);
+    ensure(echo == b"ab\x08 \x08c\n", "wrong echo")?;
+    let mut buf = [0u8; 2];
+    ensure(
+        ldisc.read(&mut buf) == Some(2) && buf == *b"ac",
+        "wrong first part",
+    )?;
+    ensure(
+        ldisc.read(&mut buf) == Some(1) && buf[0] == b'\n',
+        "wrong rest of line",
+    )?;
+    ensure(ldisc.read(&mut buf).is_none(), "read past the line")?;
+    ldisc.receive(4, &mut echo);
+    ensure(ldisc.read(&mut buf) == Some(0), "VEOF is not end of file")?;
+    ldisc.receive(b'x', &mut echo);
+    echo.clear();
+    ensure(
+        ldisc.receive(3, &mut echo) == Some(Signals::SIGINT),
+        "VINTR raised no SIGINT",
+    )?;
+    ldisc.receive(b'\n', &mut echo);
+    ensure(echo == b"^C\n", "VINTR not echoed as ^C")?;
+    ensure(
+        ldisc.read(&mut buf) == Some(1) && buf[0] == b'\n',
+        "input before VINTR was kept",
+    )
+}
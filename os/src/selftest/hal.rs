@@ -0,0 +1,234 @@
+//! Hardware abstraction checks: trap contexts and the device tree parser
+
+use crate::hal::fdt::{
+    Fdt, FdtError, PlatformInfo, FDT_BEGIN_NODE, FDT_END, FDT_END_NODE, FDT_MAGIC, FDT_PROP,
+};
+use crate::hal::TrapContextOps;
+use alloc::vec::Vec;
+
+use super::{ensure, Check, CheckResult};
+
+pub(super) const CHECKS: &[Check] = &[
+    Check {
+        name: "trap_context",
+        run: check_trap_context,
+    },
+    Check {
+        name: "fdt",
+        run: check_fdt,
+    },
+];
+
+/// Register layout loosely following RISC-V: a0-a5 arguments, a7 syscall number
+struct MockContext {
+    pc: usize,
+    a: [usize; 8],
+    origin_a0: usize,
+}
+
+impl TrapContextOps for MockContext {
+    fn syscall_nr(&self) -> usize {
+        self.a[7]
+    }
+    fn syscall_args(&self) -> [usize; 6] {
+        [self.a[0], self.a[1], self.a[2], self.a[3], self.a[4], self.a[5]]
+    }
+    fn origin_arg0(&self) -> usize {
+        self.origin_a0
+    }
+    fn return_value(&self) -> usize {
+        self.a[0]
+    }
+    fn set_return(&mut self, ret: usize) {
+        self.a[0] = ret;
+    }
+    fn pc(&self) -> usize {
+        self.pc
+    }
+    fn set_pc(&mut self, pc: usize) {
+        self.pc = pc;
+    }
+}
+
+/// The provided `advance_pc` steps over the syscall instruction and
+/// `restart_syscall` steps back onto it with the original first argument
+fn check_trap_context() -> CheckResult {
+    let mut cx = MockContext {
+        pc: 0x1000,
+        a: [3, 1, 4, 1, 5, 9, 0, 64],
+        origin_a0: 3,
+    };
+    ensure(
+        cx.syscall_nr() == 64 && cx.syscall_args() == [3, 1, 4, 1, 5, 9],
+        "syscall registers",
+    )?;
+    cx.advance_pc();
+    cx.set_return(42);
+    ensure(
+        cx.pc() == 0x1004 && cx.return_value() == 42,
+        "return past the syscall",
+    )?;
+    let mut cx = MockContext {
+        pc: 0x2000,
+        a: [7, 0, 0, 0, 0, 0, 0, 63],
+        origin_a0: 7,
+    };
+    cx.advance_pc();
+    cx.set_return(-4isize as usize);
+    cx.restart_syscall();
+    ensure(
+        cx.pc() == 0x2000 && cx.syscall_args()[0] == 7,
+        "restart did not reissue the syscall",
+    )
+}
+
+/// Minimal DTB writer, just enough to build self test blobs
+struct DtbBuilder {
+    structure: Vec<u8>,
+    strings: Vec<u8>,
+}
+
+impl DtbBuilder {
+    fn new() -> Self {
+        Self {
+            structure: Vec::new(),
+            strings: Vec::new(),
+        }
+    }
+    fn token(&mut self, token: u32) {
+        self.structure.extend_from_slice(&token.to_be_bytes());
+    }
+    fn pad(&mut self) {
+        while self.structure.len() % 4 != 0 {
+            self.structure.push(0);
+        }
+    }
+    fn begin(&mut self, name: &str) {
+        self.token(FDT_BEGIN_NODE);
+        self.structure.extend_from_slice(name.as_bytes());
+        self.structure.push(0);
+        self.pad();
+    }
+    fn end(&mut self) {
+        self.token(FDT_END_NODE);
+    }
+    fn prop(&mut self, name: &str, value: &[u8]) {
+        let nameoff = self.strings.len() as u32;
+        self.strings.extend_from_slice(name.as_bytes());
+        self.strings.push(0);
+        self.token(FDT_PROP);
+        self.token(value.len() as u32);
+        self.token(nameoff);
+        self.structure.extend_from_slice(value);
+        self.pad();
+    }
+    fn prop_cells(&mut self, name: &str, cells: &[u32]) {
+        let value: Vec<u8> = cells.iter().flat_map(|c| c.to_be_bytes()).collect();
+        self.prop(name, &value);
+    }
+    fn finish(mut self) -> Vec<u8> {
+        self.token(FDT_END);
+        let header_size = 40 + 16; // header + empty memory reservation map
+        let struct_off = header_size;
+        let strings_off = struct_off + self.structure.len();
+        let total = strings_off + self.strings.len();
+        let mut blob = Vec::new();
+        for word in [
+            FDT_MAGIC,
+            total as u32,
+            struct_off as u32,
+            strings_off as u32,
+            40,
+            17,
+            16,
+            0,
+            self.strings.len() as u32,
+            self.structure.len() as u32,
+        ] {
+            blob.extend_from_slice(&word.to_be_bytes());
+        }
+        blob.extend_from_slice(&[0; 16]);
+        blob.extend_from_slice(&self.structure);
+        blob.extend_from_slice(&self.strings);
+        blob
+    }
+}
+
+/// A trimmed-down version of what qemu `virt` generates with `-m 512M`
+fn sample_dtb() -> Vec<u8> {
+    let mut b = DtbBuilder::new();
+    b.begin("");
+    b.prop_cells("#address-cells", &[2]);
+    b.prop_cells("#size-cells", &[2]);
+    b.begin("chosen");
+    b.prop("bootargs", b"loglevel=4 nosmp\0");
+    b.prop_cells("linux,initrd-start", &[0x8800_0000]);
+    b.prop_cells("linux,initrd-end", &[0, 0x8800_1200]);
+    b.end();
+    b.begin("memory@80000000");
+    b.prop("device_type", b"memory\0");
+    b.prop_cells("reg", &[0, 0x8000_0000, 0, 0x2000_0000]);
+    b.end();
+    b.begin("soc");
+    b.prop_cells("#address-cells", &[2]);
+    b.prop_cells("#size-cells", &[2]);
+    b.begin("virtio_mmio@10002000");
+    b.prop("compatible", b"virtio,mmio\0");
+    b.prop_cells("reg", &[0, 0x1000_2000, 0, 0x1000]);
+    b.end();
+    b.begin("virtio_mmio@10001000");
+    b.prop("compatible", b"virtio,mmio\0");
+    b.prop_cells("reg", &[0, 0x1000_1000, 0, 0x1000]);
+    b.end();
+    b.begin("serial@10000000");
+    b.prop("compatible", b"ns16550a\0");
+    b.prop_cells("reg", &[0, 0x1000_0000, 0, 0x100]);
+    b.end();
+    b.begin("rtc@101000");
+    b.prop("compatible", b"google,goldfish-rtc\0");
+    b.prop_cells("reg", &[0, 0x10_1000, 0, 0x1000]);
+    b.end();
+    b.begin("plic@c000000");
+    b.prop("compatible", b"sifive,plic-1.0.0\0riscv,plic0\0");
+    b.prop_cells("reg", &[0, 0xc00_0000, 0, 0x60_0000]);
+    b.end();
+    b.end();
+    b.end();
+    b.finish()
+}
+
+/// Parse a sample device tree into the platform description, and reject
+/// truncated or foreign blobs
+fn check_fdt() -> CheckResult {
+    let mut blob = sample_dtb();
+    let fdt = Fdt::from_bytes(&blob).map_err(|_| "sample blob rejected")?;
+    let info = PlatformInfo::from_fdt(&fdt).map_err(|_| "sample blob not parsed")?;
+    ensure(
+        info.memory == Some((0x8000_0000, 0x2000_0000)),
+        "wrong memory size",
+    )?;
+    ensure(
+        info.uart == Some((0x1000_0000, 0x100))
+            && info.plic == Some((0xc00_0000, 0x60_0000))
+            && info.rtc == Some((0x10_1000, 0x1000)),
+        "wrong device regions",
+    )?;
+    ensure(
+        info.virtio_mmio() == [(0x1000_1000, 0x1000), (0x1000_2000, 0x1000)],
+        "virtio devices not sorted",
+    )?;
+    ensure(info.bootargs() == "loglevel=4 nosmp", "wrong bootargs")?;
+    ensure(
+        info.initrd == Some((0x8800_0000, 0x8800_1200)),
+        "wrong initrd range",
+    )?;
+    ensure(
+        Fdt::from_bytes(&blob[..20]).err() == Some(FdtError::Truncated),
+        "truncated blob accepted",
+    )?;
+    blob[0] = 0;
+    ensure(
+        Fdt::from_bytes(&blob).err() == Some(FdtError::BadMagic),
+        "bad magic accepted",
+    )
+}
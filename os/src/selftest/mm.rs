@@ -0,0 +1,223 @@
+//! Memory management checks: frames, page tables, the page cache and leak tracking
+
+use crate::config::PAGE_SIZE;
+use crate::fs::{OpenFlags, ROOT_FD};
+use crate::hal::PageTableImpl;
+#[cfg(feature = "kmemleak")]
+use crate::mm::kmemleak::{format_groups, LeakGroup, LeakTable, MIN_LEAK_AGE_MS, SITE_DEPTH};
+#[cfg(all(feature = "oom_handler", feature = "riscv"))]
+use crate::mm::MemorySet;
+use crate::mm::{
+    frame_alloc, unallocated_frames, MapPermission, PageTable, PhysAddr, PhysPageNum, VirtAddr,
+    VirtPageNum,
+};
+use crate::utils::telemetry::PAGE_CACHE_PAGES;
+use alloc::vec::Vec;
+
+use super::{ensure, Check, CheckResult};
+
+pub(super) const CHECKS: &[Check] = &[
+    Check {
+        name: "frame_alloc",
+        run: check_frame_alloc,
+    },
+    Check {
+        name: "page_table",
+        run: check_page_table,
+    },
+    Check {
+        name: "drop_caches",
+        run: check_drop_caches,
+    },
+    #[cfg(all(feature = "oom_handler", feature = "riscv"))]
+    Check {
+        name: "madv_free",
+        run: check_madv_free,
+    },
+    #[cfg(feature = "kmemleak")]
+    Check {
+        name: "kmemleak",
+        run: check_kmemleak,
+    },
+];
+
+/// Frames come back zeroed, distinct, and are returned to the allocator on drop
+fn check_frame_alloc() -> CheckResult {
+    const FRAME_NUM: usize = 16;
+    let before = unallocated_frames();
+    let mut frames = Vec::with_capacity(FRAME_NUM);
+    for _ in 0..FRAME_NUM {
+        frames.push(frame_alloc().ok_or("out of frames")?);
+    }
+    ensure(
+        unallocated_frames() == before - FRAME_NUM,
+        "free count not decreased",
+    )?;
+    let mut ppns: Vec<usize> = frames.iter().map(|frame| frame.ppn.0).collect();
+    ppns.sort_unstable();
+    ppns.dedup();
+    ensure(ppns.len() == FRAME_NUM, "same frame handed out twice")?;
+    ensure(
+        frames
+            .iter()
+            .all(|frame| frame.ppn.get_bytes_array().iter().all(|&b| b == 0)),
+        "frame not zeroed",
+    )?;
+    drop(frames);
+    ensure(unallocated_frames() == before, "frames leaked")
+}
+
+/// Map a frame into a fresh page table, translate it, then unmap it
+fn check_page_table() -> CheckResult {
+    let frame = frame_alloc().ok_or("out of frames")?;
+    let va: VirtAddr = 0x1000_0000.into();
+    let vpn: VirtPageNum = va.floor();
+    let mut page_table = PageTableImpl::new();
+    page_table.map(vpn, frame.ppn, MapPermission::R | MapPermission::W);
+    let mapped: Option<PhysPageNum> = page_table.translate(vpn);
+    ensure(mapped == Some(frame.ppn), "translate after map")?;
+    ensure(
+        page_table
+            .translate_va((va.0 + 0x123).into())
+            .map(|pa| pa.0)
+            == Some(PhysAddr::from(frame.ppn).0 + 0x123),
+        "translate_va offset",
+    )?;
+    page_table.unmap(vpn);
+    ensure(
+        page_table.translate(vpn).is_none(),
+        "still mapped after unmap",
+    )
+}
+
+/// Fill the page cache with a scratch file, then drop it through
+/// `/proc/sys/vm/drop_caches`: the cache gauge must fall to near zero
+fn check_drop_caches() -> CheckResult {
+    const PATH: &str = "/drop_caches_selftest";
+    const FILE_PAGES: usize = 16;
+    /// Pages an executable mapped straight from the page cache keeps, e.g. `initproc`
+    const NEAR_ZERO_PAGES: u64 = 64;
+    let file = ROOT_FD
+        .open(PATH, OpenFlags::O_CREAT | OpenFlags::O_TRUNC, false)
+        .map_err(|_| "scratch file not created")?;
+    let data = alloc::vec![0x5au8; FILE_PAGES * PAGE_SIZE];
+    let written = file.write(None, &data);
+    drop(file);
+    let before = PAGE_CACHE_PAGES.get();
+    let dropped = match ROOT_FD.open("/proc/sys/vm/drop_caches", OpenFlags::O_WRONLY, false) {
+        Ok(drop_caches) => drop_caches.write(None, b"1\n") == 2,
+        Err(_) => false,
+    };
+    let after = PAGE_CACHE_PAGES.get();
+    // written back before being dropped
+    let mut buf = alloc::vec![0u8; data.len()];
+    let read = match ROOT_FD.open(PATH, OpenFlags::O_RDONLY, false) {
+        Ok(file) => file.read(None, &mut buf),
+        Err(_) => 0,
+    };
+    let _ = ROOT_FD.delete(PATH, false);
+    ensure(written == data.len(), "scratch file not written")?;
+    ensure(before >= FILE_PAGES as u64, "page cache gauge not raised")?;
+    ensure(dropped, "write to /proc/sys/vm/drop_caches failed")?;
+    ensure(
+        after <= NEAR_ZERO_PAGES && after + FILE_PAGES as u64 <= before,
+        "page cache gauge not near zero",
+    )?;
+    ensure(read == data.len() && buf == data, "dirty pages lost")
+}
+
+/// MADV_FREE some anonymous pages of a scratch address space, write to one of them,
+/// then reclaim: the untouched pages must read back as zero, the written one intact.
+///
+/// RISC-V only, the reclaim of the current task on LoongArch skips the mmap area.
+#[cfg(all(feature = "oom_handler", feature = "riscv"))]
+fn check_madv_free() -> CheckResult {
+    const PAGES: usize = 4;
+    const WRITTEN: usize = 1;
+    let frames_before = unallocated_frames();
+    let start = crate::config::MMAP_BASE;
+    let page = |i: usize| VirtAddr::from(start + i * PAGE_SIZE);
+    let mut vm = MemorySet::<PageTableImpl>::new_bare();
+    vm.insert_framed_area(
+        page(0),
+        page(PAGES),
+        MapPermission::R | MapPermission::W | MapPermission::U,
+    );
+    for i in 0..PAGES {
+        let ppn = vm.translate(page(i).floor()).ok_or("page not mapped")?;
+        ppn.get_bytes_array().fill(0xa0 + i as u8);
+    }
+    vm.lazy_free(start, PAGES * PAGE_SIZE)
+        .map_err(|_| "MADV_FREE rejected")?;
+    // a user store takes a write fault on the tagged page first
+    let written = vm
+        .do_page_fault(page(WRITTEN))
+        .map_err(|_| "write fault failed")?;
+    written.floor().get_bytes_array().fill(0x5a);
+    let released = vm.do_shallow_clean();
+    let mut contents = Vec::with_capacity(PAGES);
+    for i in 0..PAGES {
+        let pa = vm
+            .do_page_fault(page(i))
+            .map_err(|_| "page not faulted back in")?;
+        let bytes = pa.floor().get_bytes_array();
+        contents.push(bytes.iter().all(|&b| b == bytes[0]).then_some(bytes[0]));
+    }
+    drop(vm);
+    ensure(released >= PAGES, "pages not reclaimed")?;
+    ensure(
+        contents
+            .iter()
+            .enumerate()
+            .all(|(i, &byte)| byte == Some(if i == WRITTEN { 0x5a } else { 0 })),
+        "wrong contents after reclaim",
+    )?;
+    ensure(unallocated_frames() == frames_before, "frames leaked")
+}
+
+/// Old allocations still live are grouped by call site, freed and recent
+/// ones are not reported, and a full table counts what it cannot track
+#[cfg(feature = "kmemleak")]
+fn check_kmemleak() -> CheckResult {
+    let mut table = LeakTable::<64>::new();
+    let leak_site = [0x8020_1234, 0x8020_5678, 0, 0];
+    let freed_site = [0x8020_9abc, 0, 0, 0];
+    for i in 0..8 {
+        ensure(
+            table.insert(0x9000_0000 + i * 64, 32, 100, leak_site)
+                && table.insert(0x9100_0000 + i * 64, 16, 100, freed_site),
+            "allocation not tracked",
+        )?;
+    }
+    for i in 0..8 {
+        ensure(table.remove(0x9100_0000 + i * 64), "tracked allocation lost")?;
+    }
+    ensure(!table.remove(0x9100_0000), "allocation freed twice")?;
+    ensure(table.live() == 8, "wrong live count")?;
+    // a recent allocation at the same site is too young to report
+    table.insert(0x9200_0000, 4096, 9000, leak_site);
+    let mut groups = [LeakGroup::default(); 4];
+    let (filled, omitted) = table.group_leaks(10000, MIN_LEAK_AGE_MS, &mut groups);
+    ensure((filled, omitted) == (1, 0), "wrong leak groups")?;
+    let mut output = alloc::string::String::new();
+    format_groups(&mut groups[..filled], omitted, 10000, &mut output)
+        .map_err(|_| "report not formatted")?;
+    ensure(
+        output == "256 bytes in 8 allocations, oldest 9900 ms, site 0x80201234 0x80205678\n",
+        "wrong report",
+    )?;
+
+    let mut table = LeakTable::<8>::new();
+    for i in 1..=8 {
+        ensure(table.insert(i * 8, 8, 0, [i, 0, 0, 0]), "table full early")?;
+    }
+    ensure(
+        !table.insert(0x1000, 8, 0, [0; SITE_DEPTH]) && table.untracked() == 1,
+        "insert into a full table not counted",
+    )?;
+    // removal keeps every remaining entry reachable
+    for i in (1..=8).step_by(2).chain((2..=8).step_by(2)) {
+        ensure(table.remove(i * 8), "entry unreachable after removal")?;
+    }
+    ensure(table.live() == 0, "entries left")
+}
@@ -0,0 +1,745 @@
+//! Scheduler checks: CFS accounting, preemption, task placement and load balancing
+
+use crate::syscall::errno::EINVAL;
+use crate::task::cfs_scheduler::{
+    sched_period, weighted_slice, CfsRunQueue, SchedEntity, SchedPolicy, MIGRATION_COOLDOWN_NS,
+    MIN_GRANULARITY_NS, NICE_0_WEIGHT,
+};
+#[cfg(not(feature = "sched_fifo"))]
+use crate::task::cfs_scheduler::{
+    GANG_VRUNTIME_SLACK_NS, SCHED_LATENCY_NS, VRUNTIME_NORMALIZE_THRESHOLD,
+};
+#[cfg(not(feature = "sched_fifo"))]
+use crate::task::processor::{current_cpu_id, resched_cpu, take_need_resched, NO_TGID};
+use crate::task::sched_class::RR_TIMESLICE_NS;
+use crate::task::{
+    balance_pair, cpuset, fallback_cpu, CpuLoad, TaskControlBlock, TaskManager, INITPROC,
+};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use super::{ensure, Check, CheckResult};
+
+pub(super) const CHECKS: &[Check] = &[
+    Check {
+        name: "scheduler",
+        run: check_scheduler,
+    },
+    Check {
+        name: "steal_affinity",
+        run: check_steal_affinity,
+    },
+    Check {
+        name: "steal_cooldown",
+        run: check_steal_cooldown,
+    },
+    Check {
+        name: "migration_cool",
+        run: check_migration_cooldown,
+    },
+    Check {
+        name: "load_balance",
+        run: check_load_balance,
+    },
+    #[cfg(feature = "load_balance")]
+    Check {
+        name: "balance_online",
+        run: check_balance_online,
+    },
+    #[cfg(not(feature = "sched_fifo"))]
+    Check {
+        name: "tick_preempt",
+        run: check_tick_preempt,
+    },
+    #[cfg(not(feature = "sched_fifo"))]
+    Check {
+        name: "wakeup_preempt",
+        run: check_wakeup_preempt,
+    },
+    Check {
+        name: "rt_preempt",
+        run: check_rt_preempt,
+    },
+    Check {
+        name: "cfs_accounting",
+        run: check_cfs_accounting,
+    },
+    Check {
+        name: "cfs_churn",
+        run: check_cfs_churn,
+    },
+    Check {
+        name: "vruntime_rebase",
+        run: check_vruntime_rebase,
+    },
+    Check {
+        name: "sched_period",
+        run: check_sched_period,
+    },
+    #[cfg(not(feature = "sched_fifo"))]
+    Check {
+        name: "vruntime_normalize",
+        run: check_vruntime_normalize,
+    },
+    #[cfg(not(feature = "sched_fifo"))]
+    Check {
+        name: "gang_pick",
+        run: check_gang_pick,
+    },
+    Check {
+        name: "next_buddy",
+        run: check_next_buddy,
+    },
+    Check {
+        name: "cpuset_placement",
+        run: check_cpuset_placement,
+    },
+    Check {
+        name: "cpu_list",
+        run: check_cpu_list,
+    },
+    Check {
+        name: "pi_boost",
+        run: check_pi_boost,
+    },
+    #[cfg(feature = "work_stealing")]
+    Check {
+        name: "steal_idle",
+        run: check_steal_idle,
+    },
+];
+
+/// Enqueue `initproc` into a private run queue and dequeue it again
+fn check_scheduler() -> CheckResult {
+    let mut manager = TaskManager::new();
+    ensure(manager.fetch().is_none(), "empty queue returned a task")?;
+    manager.add(INITPROC.clone());
+    ensure(manager.ready_count() == 1, "ready count after enqueue")?;
+    let task = manager.fetch().ok_or("enqueued task not fetched")?;
+    ensure(Arc::ptr_eq(&task, &INITPROC), "fetched a different task")?;
+    ensure(manager.ready_count() == 0, "ready count after dequeue")
+}
+
+/// Entry of the kernel threads the scheduler checks queue but never run
+fn never_run() -> ! {
+    unreachable!("selftest thread scheduled")
+}
+
+/// Queue two kernel threads pinned to CPU 0: CPU 1 must not steal either of them,
+/// CPU 0 itself still may
+fn check_steal_affinity() -> CheckResult {
+    let mut manager = TaskManager::new();
+    for _ in 0..2 {
+        let task = Arc::new(TaskControlBlock::new_kernel_thread(never_run));
+        task.acquire_inner_lock().sched_entity.set_affinity(1 << 0);
+        manager.add(task);
+    }
+    ensure(
+        manager.steal_from_cfs_for_cpu(1).is_none(),
+        "task pinned to CPU 0 stolen by CPU 1",
+    )?;
+    let task = manager
+        .steal_from_cfs_for_cpu(0)
+        .ok_or("CPU 0 could not take its own task")?;
+    let pinned = task.acquire_inner_lock().sched_entity.can_run_on(0);
+    ensure(pinned, "stolen task cannot run on CPU 0")
+}
+
+/// Steal from a private queue of two kernel threads, then put the stolen one back:
+/// the next steal must take the other thread, and once both have just migrated
+/// neither may be stolen again
+fn check_steal_cooldown() -> CheckResult {
+    let mut manager = TaskManager::new();
+    for _ in 0..2 {
+        manager.add(Arc::new(TaskControlBlock::new_kernel_thread(never_run)));
+    }
+    let first = manager.steal_from_cfs_for_cpu(1).ok_or("nothing stolen")?;
+    manager.add(first.clone());
+    let second = manager
+        .steal_from_cfs_for_cpu(0)
+        .ok_or("fresh task not stolen")?;
+    ensure(
+        !Arc::ptr_eq(&first, &second),
+        "task migrated twice within the cooldown",
+    )?;
+    manager.add(second);
+    ensure(
+        manager.steal_from_cfs_for_cpu(1).is_none(),
+        "recently migrated task stolen again",
+    )
+}
+
+/// Only moving to another CPU starts the migration cooldown, which lasts
+/// `MIGRATION_COOLDOWN_NS`
+fn check_migration_cooldown() -> CheckResult {
+    let mut entity = SchedEntity::new(0);
+    ensure(
+        !entity.recently_migrated(1_000),
+        "fresh entity in cooldown",
+    )?;
+    // staying on the same CPU is not a migration
+    entity.migrate_to(0, 1_000);
+    ensure(
+        !entity.recently_migrated(1_000),
+        "same CPU counted as a migration",
+    )?;
+    entity.migrate_to(1, 10_000_000);
+    ensure(entity.last_cpu == 1, "new CPU not recorded")?;
+    ensure(
+        entity.recently_migrated(10_000_000 + MIGRATION_COOLDOWN_NS - 1)
+            && !entity.recently_migrated(10_000_000 + MIGRATION_COOLDOWN_NS),
+        "cooldown of the wrong length",
+    )
+}
+
+/// Pick the CPUs to balance from a set of loads, then move a task between two
+/// private queues the way the periodic balancer does: only a task light enough
+/// and allowed on the idle CPU moves, and it is not moved on again at once
+fn check_load_balance() -> CheckResult {
+    let load = |nr_running, weight| Some(CpuLoad { nr_running, weight });
+    ensure(
+        balance_pair(&[load(1, 3072), None, load(0, 0)]).is_none(),
+        "picked the only task of a CPU to move",
+    )?;
+    ensure(
+        balance_pair(&[load(2, 2048), load(2, 2048)]).is_none(),
+        "balanced equal loads",
+    )?;
+    ensure(
+        balance_pair(&[load(3, 3072), None, load(1, 1024), load(0, 0)]) == Some((0, 3, 3072)),
+        "wrong busiest or idlest CPU",
+    )?;
+    let mut busy = TaskManager::new();
+    let pinned = Arc::new(TaskControlBlock::new_kernel_thread(never_run));
+    pinned
+        .acquire_inner_lock()
+        .sched_entity
+        .set_affinity(1 << 0);
+    busy.add(pinned);
+    let free = Arc::new(TaskControlBlock::new_kernel_thread(never_run));
+    busy.add(free.clone());
+    let weight = free.acquire_inner_lock().sched_entity.weight as u64;
+    let mut running = SchedEntity::new(0);
+    running.set_policy(SchedPolicy::Fifo, 10);
+    ensure(
+        busy.cfs_load(Some(&running))
+            == CpuLoad {
+                nr_running: 2,
+                weight: 2 * weight,
+            },
+        "CFS load counts the wrong tasks",
+    )?;
+    ensure(
+        busy.detach_for_balance(1, weight - 1).is_none(),
+        "moved a task heavier than allowed",
+    )?;
+    let (task, lag) = busy
+        .detach_for_balance(1, weight)
+        .ok_or("nothing detached")?;
+    ensure(Arc::ptr_eq(&task, &free), "detached the pinned task")?;
+    let mut idle = TaskManager::new();
+    idle.attach_balanced(task, lag);
+    ensure(
+        busy.ready_count() == 1 && idle.ready_count() == 1,
+        "task not moved between the queues",
+    )?;
+    ensure(
+        idle.detach_for_balance(0, u64::MAX).is_none(),
+        "balanced task moved on again at once",
+    )
+}
+
+/// Only the CPUs passed as online get a load: a hart that never booted has an
+/// empty queue and would otherwise always be picked to receive tasks
+#[cfg(feature = "load_balance")]
+fn check_balance_online() -> CheckResult {
+    use crate::config::MAX_CPU_NUM;
+    let offline = MAX_CPU_NUM - 1;
+    let loads = crate::task::cpu_loads((0..MAX_CPU_NUM).filter(|&cpu_id| cpu_id != offline));
+    ensure(loads[offline].is_none(), "offline CPU given a load")?;
+    ensure(
+        balance_pair(&loads).map_or(true, |(busiest, idlest, _)| {
+            busiest != offline && idlest != offline
+        }),
+        "offline CPU picked for balancing",
+    )?;
+    let load = |nr_running, weight| Some(CpuLoad { nr_running, weight });
+    let mut loads = [None; MAX_CPU_NUM];
+    loads[0] = load(2, 2048);
+    ensure(balance_pair(&loads).is_none(), "balanced onto an offline CPU")
+}
+
+/// A CPU-bound task alone on its CPU is not preempted by the tick however long it
+/// runs; once another task is queued it runs out its slice and is then preempted
+#[cfg(not(feature = "sched_fifo"))]
+fn check_tick_preempt() -> CheckResult {
+    const MS: u64 = 1_000_000;
+    let mut manager = TaskManager::new();
+    let mut curr = SchedEntity::new(0);
+    curr.sum_exec_runtime = 100 * MS;
+    curr.vruntime = 100 * MS;
+    ensure(
+        !manager.should_preempt_on_tick(&curr),
+        "sole task preempted",
+    )?;
+    manager.add(Arc::new(TaskControlBlock::new_kernel_thread(never_run)));
+    // the waiting task is placed after min_vruntime, keep the running one level with it
+    let (mut curr, slice) = (SchedEntity::new(0), SCHED_LATENCY_NS / 2);
+    curr.vruntime = SCHED_LATENCY_NS / 2;
+    curr.sum_exec_runtime = slice / 2;
+    ensure(
+        !manager.should_preempt_on_tick(&curr),
+        "task preempted mid-slice",
+    )?;
+    curr.sum_exec_runtime = slice;
+    ensure(
+        manager.should_preempt_on_tick(&curr),
+        "task not preempted after its slice",
+    )
+}
+
+/// A woken RT task preempts a running CFS task at once, a woken CFS task only when
+/// its vruntime lags far enough behind; the request reaches the CPU as a flag
+#[cfg(not(feature = "sched_fifo"))]
+fn check_wakeup_preempt() -> CheckResult {
+    const MS: u64 = 1_000_000;
+    let manager = TaskManager::new();
+    let mut curr = SchedEntity::new(0);
+    curr.vruntime = 10 * MS;
+    let mut rt = SchedEntity::new(0);
+    rt.policy = SchedPolicy::Fifo;
+    rt.rt_priority = 10;
+    ensure(
+        manager.should_preempt_on_wakeup(&curr, &rt),
+        "RT wakeup did not preempt CFS task",
+    )?;
+    let mut cfs = SchedEntity::new(0);
+    cfs.vruntime = curr.vruntime - MS / 2;
+    ensure(
+        !manager.should_preempt_on_wakeup(&curr, &cfs),
+        "CFS wakeup within granularity preempted",
+    )?;
+    cfs.vruntime = curr.vruntime - 5 * MS;
+    ensure(
+        manager.should_preempt_on_wakeup(&curr, &cfs),
+        "lagging CFS wakeup did not preempt",
+    )?;
+    let mut higher = rt;
+    higher.rt_priority = 50;
+    ensure(
+        !manager.should_preempt_on_wakeup(&higher, &rt),
+        "RT wakeup preempted a higher priority RT task",
+    )?;
+    resched_cpu(current_cpu_id());
+    ensure(take_need_resched(), "reschedule request lost")?;
+    ensure(!take_need_resched(), "reschedule request not cleared")
+}
+
+/// A RT kernel thread queued on a private manager at `priority`
+fn rt_thread(priority: u8, preempted: bool) -> Arc<TaskControlBlock> {
+    let task = Arc::new(TaskControlBlock::new_kernel_thread(never_run));
+    let mut inner = task.acquire_inner_lock();
+    inner.sched_entity.set_policy(SchedPolicy::Fifo, priority);
+    inner.sched_entity.preempted = preempted;
+    drop(inner);
+    task
+}
+
+/// A FIFO task keeps the CPU while only tasks of its priority wait, a RR task
+/// yields to them once its timeslice is used up; both yield to a higher priority
+/// at once, and a preempted task is picked again before the others of its priority
+fn check_rt_preempt() -> CheckResult {
+    let mut manager = TaskManager::new();
+    let waiting = rt_thread(10, false);
+    manager.add(waiting.clone());
+    let mut curr = SchedEntity::new(0);
+    curr.set_policy(SchedPolicy::Fifo, 10);
+    curr.sum_exec_runtime = 10 * RR_TIMESLICE_NS;
+    ensure(
+        !manager.should_preempt_on_tick(&curr),
+        "FIFO task preempted by its own priority",
+    )?;
+    curr.policy = SchedPolicy::RoundRobin;
+    ensure(
+        manager.should_preempt_on_tick(&curr),
+        "RR task kept the CPU after its timeslice",
+    )?;
+    curr.sum_exec_runtime = RR_TIMESLICE_NS / 2;
+    ensure(
+        !manager.should_preempt_on_tick(&curr),
+        "RR task preempted mid-slice",
+    )?;
+    let preempted = rt_thread(10, true);
+    manager.add(preempted.clone());
+    let higher = rt_thread(50, false);
+    manager.add(higher.clone());
+    ensure(
+        manager.should_preempt_on_tick(&curr),
+        "RT task kept the CPU from a higher priority",
+    )?;
+    for &(expected, what) in [
+        (&higher, "higher priority not picked first"),
+        (&preempted, "preempted task not first of its queue"),
+        (&waiting, "waiting task lost"),
+    ]
+    .iter()
+    {
+        let task = manager.fetch().ok_or(what)?;
+        ensure(Arc::ptr_eq(&task, expected), what)?;
+    }
+    Ok(())
+}
+
+/// Enqueue, dequeue and pick kernel threads of mixed nice values on a private CFS
+/// queue: its total weight is the sum of the queued weights after every step and
+/// min_vruntime never goes back
+fn check_cfs_accounting() -> CheckResult {
+    fn expect_weights(
+        rq: &CfsRunQueue,
+        queued: &[(Arc<TaskControlBlock>, SchedEntity)],
+    ) -> CheckResult {
+        let sum: u64 = queued.iter().map(|(_, entity)| entity.weight as u64).sum();
+        ensure(
+            rq.len() == queued.len(),
+            "queue length differs from queued tasks",
+        )?;
+        ensure(
+            rq.total_weight() == sum,
+            "total weight differs from queued weights",
+        )
+    }
+    let mut rq = CfsRunQueue::new();
+    let mut queued = Vec::new();
+    for nice in [-10, 0, 5, 19, -5] {
+        let task = Arc::new(TaskControlBlock::new_kernel_thread(never_run));
+        let mut entity = SchedEntity::new(nice);
+        rq.enqueue(task.clone(), &mut entity, true);
+        queued.push((task, entity));
+        expect_weights(&rq, &queued)?;
+    }
+    // enqueueing a task again must not count it twice
+    let (task, mut entity) = queued[1].clone();
+    rq.enqueue(task, &mut entity, false);
+    expect_weights(&rq, &queued)?;
+    let (task, entity) = queued.remove(2);
+    rq.dequeue(&task, &entity);
+    expect_weights(&rq, &queued)?;
+    let mut min_vruntime = rq.min_vruntime();
+    while let Some(task) = rq.pick_next() {
+        let index = queued
+            .iter()
+            .position(|(queued, _)| Arc::ptr_eq(queued, &task))
+            .ok_or("picked a task that was not queued")?;
+        queued.remove(index);
+        expect_weights(&rq, &queued)?;
+        ensure(rq.min_vruntime() >= min_vruntime, "min_vruntime went back")?;
+        min_vruntime = rq.min_vruntime();
+        if queued.len() == 2 {
+            // a waking task with a stale vruntime is placed at min_vruntime, not before it
+            let task = Arc::new(TaskControlBlock::new_kernel_thread(never_run));
+            let mut entity = SchedEntity::new(10);
+            rq.enqueue(task.clone(), &mut entity, false);
+            ensure(
+                entity.vruntime >= min_vruntime,
+                "task placed before min_vruntime",
+            )?;
+            queued.push((task, entity));
+            expect_weights(&rq, &queued)?;
+        }
+    }
+    ensure(
+        rq.is_empty() && queued.is_empty(),
+        "tasks left after picking all",
+    )
+}
+
+/// Cycle kernel threads through pick, run and enqueue the way the dispatcher
+/// does: a stray dequeue of the picked task changes nothing and the queue length
+/// and weight stay those of the tasks waiting
+fn check_cfs_churn() -> CheckResult {
+    const TASKS: usize = 3;
+    let mut rq = CfsRunQueue::new();
+    let mut entities = Vec::new();
+    for nice in [0, 5, -5] {
+        let task = Arc::new(TaskControlBlock::new_kernel_thread(never_run));
+        let mut entity = SchedEntity::new(nice);
+        rq.enqueue(task.clone(), &mut entity, true);
+        entities.push((task, entity));
+    }
+    let total_weight = rq.total_weight();
+    for _ in 0..100 {
+        let task = rq.pick_next().ok_or("queue ran dry")?;
+        ensure(
+            rq.len() == TASKS - 1,
+            "pick did not remove exactly one task",
+        )?;
+        let (_, entity) = entities
+            .iter_mut()
+            .find(|(queued, _)| Arc::ptr_eq(queued, &task))
+            .ok_or("picked a task that was not queued")?;
+        ensure(!rq.dequeue(&task, entity), "picked task dequeued again")?;
+        ensure(
+            rq.len() == TASKS - 1,
+            "dequeue of a picked task changed the count",
+        )?;
+        entity.vruntime += entity.calc_delta_vruntime(MIN_GRANULARITY_NS);
+        rq.enqueue(task, entity, false);
+        ensure(
+            rq.len() == TASKS && rq.total_weight() == total_weight,
+            "counters drifted after a cycle",
+        )?;
+    }
+    for _ in 0..TASKS {
+        rq.pick_next().ok_or("queued task missing")?;
+    }
+    ensure(
+        rq.is_empty() && rq.total_weight() == 0,
+        "queue not empty after picking all",
+    )
+}
+
+/// Rebasing keeps the order and the gaps between vruntimes, and rebasing back
+/// onto a queue that has not normalized restores the old values
+fn check_vruntime_rebase() -> CheckResult {
+    let base = crate::task::cfs_scheduler::VRUNTIME_NORMALIZE_THRESHOLD;
+    let mut behind = SchedEntity::new(0);
+    behind.vruntime = base + 1_000;
+    let mut ahead = SchedEntity::new(0);
+    ahead.vruntime = base + 5_000;
+    behind.rebase(base);
+    ahead.rebase(base);
+    ensure(
+        (behind.vruntime, ahead.vruntime) == (1_000, 5_000),
+        "order lost in the rebase",
+    )?;
+    // back on a queue that has not normalized
+    ahead.rebase(0);
+    ensure(
+        ahead.vruntime == base + 5_000,
+        "rebase back did not restore vruntime",
+    )
+}
+
+/// The period stretches to `MIN_GRANULARITY_NS` per task once there are too
+/// many tasks for `SCHED_LATENCY_NS`, and equal tasks share it exactly
+fn check_sched_period() -> CheckResult {
+    let latency = crate::task::cfs_scheduler::SCHED_LATENCY_NS;
+    ensure(
+        sched_period(1) == latency && sched_period(8) == latency,
+        "period of few tasks not the latency",
+    )?;
+    let nr_running = 32;
+    let period = sched_period(nr_running);
+    ensure(
+        period == nr_running as u64 * MIN_GRANULARITY_NS,
+        "period not stretched",
+    )?;
+    let weight = NICE_0_WEIGHT as u64;
+    let slice = weighted_slice(weight, weight * nr_running as u64, nr_running);
+    ensure(slice >= MIN_GRANULARITY_NS, "slice below the granularity")?;
+    // equal tasks share the stretched period exactly
+    ensure(
+        slice * nr_running as u64 == period,
+        "slices do not add up to the period",
+    )
+}
+
+/// Queue kernel threads with vruntimes at the normalization threshold: picks keep
+/// their order across the rebase, and a task coming back with the old base is
+/// rebased on enqueue
+#[cfg(not(feature = "sched_fifo"))]
+fn check_vruntime_normalize() -> CheckResult {
+    const MS: u64 = 1_000_000;
+    let mut rq = CfsRunQueue::new();
+    let mut tasks = Vec::new();
+    for i in 0..3 {
+        let task = Arc::new(TaskControlBlock::new_kernel_thread(never_run));
+        {
+            let mut inner = task.acquire_inner_lock();
+            inner.sched_entity.vruntime = VRUNTIME_NORMALIZE_THRESHOLD + i * MS;
+            rq.enqueue(task.clone(), &mut inner.sched_entity, false);
+        }
+        tasks.push(task);
+    }
+    let vruntime_of =
+        |task: &Arc<TaskControlBlock>| task.acquire_inner_lock().sched_entity.vruntime;
+    // the first pick reaches the threshold, the second one normalizes
+    let first = rq.pick_next().ok_or("queue empty")?;
+    ensure(Arc::ptr_eq(&first, &tasks[0]), "picked out of order")?;
+    ensure(
+        rq.min_vruntime() >= VRUNTIME_NORMALIZE_THRESHOLD,
+        "threshold not reached",
+    )?;
+    let second = rq.pick_next().ok_or("queue empty")?;
+    ensure(
+        Arc::ptr_eq(&second, &tasks[1]),
+        "picked out of order after normalizing",
+    )?;
+    ensure(rq.min_vruntime() == MS, "min_vruntime not normalized")?;
+    ensure(vruntime_of(&tasks[2]) == 2 * MS, "queued task not rebased")?;
+    // the first task ran on from its old base and now lags behind the third
+    {
+        let mut inner = first.acquire_inner_lock();
+        inner.sched_entity.vruntime += 3 * MS;
+        rq.enqueue(first.clone(), &mut inner.sched_entity, false);
+    }
+    ensure(vruntime_of(&first) == 3 * MS, "returning task not rebased")?;
+    let third = rq.pick_next().ok_or("queue empty")?;
+    ensure(
+        Arc::ptr_eq(&third, &tasks[2]),
+        "rebased task overtook a queued one",
+    )?;
+    let last = rq.pick_next().ok_or("returning task lost")?;
+    ensure(
+        Arc::ptr_eq(&last, &first),
+        "picked a task that was not queued",
+    )
+}
+
+/// A gang pick takes a thread of a process running elsewhere ahead of the leftmost
+/// task while it lags by no more than the slack, and nothing of another process
+#[cfg(not(feature = "sched_fifo"))]
+fn check_gang_pick() -> CheckResult {
+    let mut rq = CfsRunQueue::new();
+    let mut tasks = Vec::new();
+    for vruntime in [0, GANG_VRUNTIME_SLACK_NS / 2, 2 * GANG_VRUNTIME_SLACK_NS] {
+        let task = Arc::new(TaskControlBlock::new_kernel_thread(never_run));
+        let mut entity = SchedEntity::new(0);
+        entity.vruntime = vruntime;
+        rq.enqueue(task.clone(), &mut entity, false);
+        tasks.push(task);
+    }
+    ensure(
+        rq.pick_sibling(&[tasks[2].tgid]).is_none(),
+        "picked a sibling far beyond the leftmost task",
+    )?;
+    ensure(
+        rq.pick_sibling(&[NO_TGID]).is_none(),
+        "picked without a running sibling",
+    )?;
+    let picked = rq
+        .pick_sibling(&[tasks[1].tgid])
+        .ok_or("sibling within the slack not picked")?;
+    ensure(Arc::ptr_eq(&picked, &tasks[1]), "picked the wrong sibling")?;
+    let next = rq.pick_next().ok_or("leftmost task lost")?;
+    ensure(
+        Arc::ptr_eq(&next, &tasks[0]),
+        "leftmost task skipped after the gang pick",
+    )
+}
+
+/// A directed yield target is picked ahead of the leftmost task, once, and
+/// takes precedence over a gang pick
+fn check_next_buddy() -> CheckResult {
+    let mut rq = CfsRunQueue::new();
+    let mut tasks = Vec::new();
+    for vruntime in [0, 1_000_000, 2_000_000] {
+        let task = Arc::new(TaskControlBlock::new_kernel_thread(never_run));
+        let mut entity = SchedEntity::new(0);
+        entity.vruntime = vruntime;
+        rq.enqueue(task.clone(), &mut entity, false);
+        tasks.push(task);
+    }
+    ensure(!rq.set_next(usize::MAX), "buddy set for a task not queued")?;
+    ensure(rq.set_next(tasks[2].pid.0), "queued task not made the buddy")?;
+    ensure(
+        rq.pick_sibling(&[tasks[1].tgid]).is_none(),
+        "gang pick ran ahead of the buddy",
+    )?;
+    let picked = rq.pick_next().ok_or("buddy lost")?;
+    ensure(Arc::ptr_eq(&picked, &tasks[2]), "buddy not picked first")?;
+    let next = rq.pick_next().ok_or("leftmost task lost")?;
+    ensure(
+        Arc::ptr_eq(&next, &tasks[0]),
+        "buddy picked again instead of the leftmost task",
+    )
+}
+
+/// Confine an entity to a cpuset of CPU 1 only: neither its affinity nor the
+/// wakeup fallback may place it on CPU 0
+fn check_cpuset_placement() -> CheckResult {
+    let id = cpuset::assign(cpuset::ROOT_CPUSET, 1 << 1).map_err(|_| "no free cpuset")?;
+    let mut entity = SchedEntity::new(0);
+    entity.cpuset = id;
+    let result = (|| {
+        ensure(entity.allowed_cpus() == 1 << 1, "cpuset not applied")?;
+        ensure(!entity.can_run_on(0), "confined entity may run on CPU 0")?;
+        ensure(
+            fallback_cpu(entity.allowed_cpus(), 0) == 1,
+            "placed outside cpuset",
+        )?;
+        entity.set_affinity(1 << 0);
+        ensure(
+            entity.allowed_cpus() == 1 << 1,
+            "affinity disjoint from cpuset escaped it",
+        )?;
+        entity.set_affinity(usize::MAX);
+        ensure(
+            fallback_cpu(SchedEntity::new(0).allowed_cpus(), 0) == 0,
+            "unconfined entity moved off the current CPU",
+        )?;
+        ensure(
+            SchedEntity::inherit(&entity).cpuset == id,
+            "forked entity left the cpuset",
+        )
+    })();
+    cpuset::leave(id);
+    result
+}
+
+/// CPU lists as in `cpuset.cpus` convert to bitmasks and back
+fn check_cpu_list() -> CheckResult {
+    ensure(
+        cpuset::parse_cpu_list("0-1") == Ok(0b11)
+            && cpuset::parse_cpu_list("0-1,3") == Ok(0b1011)
+            && cpuset::parse_cpu_list("2") == Ok(0b100),
+        "CPU list not parsed",
+    )?;
+    ensure(
+        cpuset::parse_cpu_list("3-1") == Err(EINVAL) && cpuset::parse_cpu_list("x") == Err(EINVAL),
+        "bad CPU list accepted",
+    )?;
+    ensure(
+        cpuset::format_cpu_list(0b1011) == "0-1,3"
+            && cpuset::format_cpu_list(0b100) == "2"
+            && cpuset::format_cpu_list(0).is_empty(),
+        "CPU list not formatted",
+    )
+}
+
+/// An inherited priority above the task's own makes it run as a RT task at
+/// that priority, dropping the boost restores the policy it was given
+fn check_pi_boost() -> CheckResult {
+    let mut entity = SchedEntity::new(0);
+    entity.set_pi_priority(20);
+    ensure(
+        entity.policy == SchedPolicy::Fifo && entity.rt_priority == 20,
+        "boosted CFS task does not run as SCHED_FIFO",
+    )?;
+    entity.set_policy(SchedPolicy::RoundRobin, 10);
+    ensure(
+        entity.policy == SchedPolicy::RoundRobin && entity.rt_priority == 20,
+        "sched_setscheduler below the boost dropped it",
+    )?;
+    entity.set_policy(SchedPolicy::RoundRobin, 30);
+    ensure(
+        !entity.is_pi_boosted() && entity.rt_priority == 30,
+        "a lower inherited priority overrode the task's own",
+    )?;
+    entity.set_policy(SchedPolicy::Normal, 0);
+    entity.set_pi_priority(0);
+    ensure(
+        entity.policy == SchedPolicy::Normal && entity.rt_priority == 0,
+        "dropping the boost did not restore SCHED_NORMAL",
+    )
+}
+
+/// With every run queue nearly empty the `nr_running` hints rule out all victims:
+/// an idle CPU must give up without trying a single lock, where a plain scan
+/// would have tried every other CPU
+#[cfg(feature = "work_stealing")]
+fn check_steal_idle() -> CheckResult {
+    let (stolen, attempts) = crate::task::steal_attempts_when_idle();
+    ensure(!stolen, "stole a task while all CPUs were idle")?;
+    ensure(attempts == 0, "locked a run queue while all CPUs were idle")
+}
@@ -0,0 +1,72 @@
+//! Task checks: process accounting records and futex operations
+
+use crate::task::acct;
+use crate::task::threads::FutexWakeOp;
+
+use super::{ensure, Check, CheckResult};
+
+pub(super) const CHECKS: &[Check] = &[
+    Check {
+        name: "acct_encoding",
+        run: check_acct_encoding,
+    },
+    Check {
+        name: "futex_wake_op",
+        run: check_futex_wake_op,
+    },
+];
+
+/// comp_t keeps small values exact and scales large ones by powers of 8,
+/// and the record has the size `acct(5)` readers expect
+fn check_acct_encoding() -> CheckResult {
+    ensure(
+        acct::encode_comp_t(0) == 0 && acct::encode_comp_t(8191) == 8191,
+        "small comp_t not exact",
+    )?;
+    // 8192 = 1024 * 8^1
+    ensure(
+        acct::encode_comp_t(8192) == (1 << 13) | 1024,
+        "large comp_t not scaled",
+    )?;
+    ensure(
+        core::mem::size_of::<acct::AcctV3>() == 64,
+        "acct_v3 record is not 64 bytes",
+    )?;
+    // bit patterns of 1.0f32 and 100.0f32
+    ensure(
+        acct::encode_float(1) == 0x3f80_0000 && acct::encode_float(100) == 0x42c8_0000,
+        "wrong float encoding",
+    )
+}
+
+/// `FUTEX_WAKE_OP` encodings as built by glibc's `FUTEX_OP(op, oparg, cmp, cmparg)`
+fn check_futex_wake_op() -> CheckResult {
+    let encode = |op: u32, oparg: u32, cmp: u32, cmparg: u32| {
+        (op << 28) | (cmp << 24) | ((oparg & 0xfff) << 12) | (cmparg & 0xfff)
+    };
+    // glibc's lll_futex_wake_unlock: set to 0, wake if the old value was above 1
+    let unlock = FutexWakeOp::decode(encode(0, 0, 4, 1)).map_err(|_| "valid op rejected")?;
+    ensure(unlock.apply(2) == 0, "FUTEX_OP_SET")?;
+    ensure(
+        unlock.test(2) && !unlock.test(1),
+        "FUTEX_OP_CMP_GT compared wrong",
+    )?;
+    let add = FutexWakeOp::decode(encode(1, -3i32 as u32, 2, 0)).unwrap();
+    ensure(add.apply(5) == 2, "negative oparg not sign extended")?;
+    ensure(
+        add.test(-1i32 as u32) && !add.test(0),
+        "FUTEX_OP_CMP_LT is not a signed comparison",
+    )?;
+    let shifted = FutexWakeOp::decode(encode(8 | 2, 4, 0, 0)).unwrap();
+    ensure(shifted.apply(1) == 0x11, "FUTEX_OP_OPARG_SHIFT ignored")?;
+    let andn = FutexWakeOp::decode(encode(3, 0b110, 5, -1i32 as u32)).unwrap();
+    ensure(
+        andn.apply(0b111) == 0b001 && andn.test(-1i32 as u32),
+        "FUTEX_OP_ANDN or FUTEX_OP_CMP_GE wrong",
+    )?;
+    ensure(
+        FutexWakeOp::decode(encode(5, 0, 0, 0)).is_err()
+            && FutexWakeOp::decode(encode(0, 0, 6, 0)).is_err(),
+        "unknown op or comparison accepted",
+    )
+}
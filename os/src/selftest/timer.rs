@@ -0,0 +1,60 @@
+//! Clock checks: CLOCK_REALTIME offsets and RTC time conversion
+
+use crate::drivers::rtc::RtcTime;
+use crate::timer::{realtime_now, realtime_to_monotonic, set_realtime, TimeSpec};
+
+use super::{ensure, Check, CheckResult};
+
+pub(super) const CHECKS: &[Check] = &[
+    Check {
+        name: "realtime_offset",
+        run: check_realtime_offset,
+    },
+    Check {
+        name: "rtc_time",
+        run: check_rtc_time,
+    },
+];
+
+/// Setting CLOCK_REALTIME only changes its offset from the boot clock: a
+/// realtime deadline converts back to the boot clock it was derived from
+fn check_realtime_offset() -> CheckResult {
+    let saved = realtime_now();
+    let now = TimeSpec::now();
+    set_realtime(now + TimeSpec::from_s(1_000_000));
+    let real = realtime_now();
+    let deadline = realtime_to_monotonic(real + TimeSpec::from_s(5));
+    set_realtime(saved);
+    ensure(
+        real >= now + TimeSpec::from_s(1_000_000),
+        "CLOCK_REALTIME is behind the time it was set to",
+    )?;
+    ensure(
+        deadline > now + TimeSpec::from_s(4) && deadline <= TimeSpec::now() + TimeSpec::from_s(5),
+        "a realtime deadline did not convert back to the boot clock",
+    )
+}
+
+/// Broken-down RTC time round-trips through seconds since the epoch, leap days
+/// included, and impossible dates are rejected
+fn check_rtc_time() -> CheckResult {
+    // 2000-02-29 12:34:56, a Tuesday
+    let leap_day = 951_827_696;
+    let time = RtcTime::from_unix(leap_day);
+    ensure(
+        (time.tm_year, time.tm_mon, time.tm_mday) == (100, 1, 29)
+            && (time.tm_hour, time.tm_min, time.tm_sec) == (12, 34, 56)
+            && (time.tm_wday, time.tm_yday) == (2, 59),
+        "leap day broken down wrongly",
+    )?;
+    ensure(time.to_unix() == Some(leap_day), "leap day did not round-trip")?;
+    ensure(
+        RtcTime::from_unix(0).to_unix() == Some(0),
+        "epoch did not round-trip",
+    )?;
+    let feb_30 = RtcTime {
+        tm_mday: 30,
+        ..time
+    };
+    ensure(feb_30.to_unix().is_none(), "February 30th accepted")
+}
@@ -0,0 +1,226 @@
+//! Checks of the kernel utilities: the entropy pool, metrics and `/proc/health`
+
+use crate::fs::file_descriptor::FdTable;
+use crate::fs::{OpenFlags, ROOT_FD};
+use crate::mm::{frame_alloc, unallocated_frames};
+use crate::utils::random::{EntropyPool, POOL_READY_BITS};
+use crate::utils::telemetry::{
+    Histogram, LabeledCounter, PerCpuGauge, CRITICAL_MEMORY_FRAMES, LOW_MEMORY_FRAMES, MAX_LABELS,
+    OPEN_FDS, OVERFLOW_LABEL,
+};
+use alloc::vec::Vec;
+
+use super::{ensure, Check, CheckResult};
+
+pub(super) const CHECKS: &[Check] = &[
+    Check {
+        name: "entropy_pool",
+        run: check_entropy_pool,
+    },
+    Check {
+        name: "health",
+        run: check_health_low_memory,
+    },
+    Check {
+        name: "fd_usage",
+        run: check_health_fd_usage,
+    },
+    Check {
+        name: "histogram_pct",
+        run: check_histogram_percentile,
+    },
+    Check {
+        name: "labeled_counter",
+        run: check_labeled_counter,
+    },
+    Check {
+        name: "runq_imbalance",
+        run: check_run_queue_imbalance,
+    },
+];
+
+/// Feed two private entropy pools the same samples but one: the pools only count
+/// as ready once enough entropy is credited, differing samples give different
+/// output and the same pool never repeats itself
+fn check_entropy_pool() -> CheckResult {
+    let mut pools = [EntropyPool::new(), EntropyPool::new()];
+    for sample in 0..POOL_READY_BITS as u64 {
+        ensure(!pools[0].is_ready(), "ready before enough entropy")?;
+        for pool in pools.iter_mut() {
+            pool.mix(sample, 1);
+        }
+    }
+    ensure(pools[0].is_ready(), "not ready after enough entropy")?;
+    pools[1].mix(1, 0);
+    let mut output = [[0u8; 100]; 3];
+    pools[0].fill(&mut output[0]);
+    pools[1].fill(&mut output[1]);
+    ensure(
+        output[0] != output[1],
+        "a different sample left the output unchanged",
+    )?;
+    pools[0].fill(&mut output[2]);
+    ensure(output[0] != output[2], "the same pool repeated its output")?;
+    ensure(
+        output[0][64..] != output[0][..36],
+        "blocks of one fill repeat",
+    )
+}
+
+/// Hold frames until free memory falls between the low and critical thresholds:
+/// `/proc/health` must report memory, and so the overall status, as degraded
+fn check_health_low_memory() -> CheckResult {
+    let target = (LOW_MEMORY_FRAMES + CRITICAL_MEMORY_FRAMES) / 2;
+    let mut held = Vec::new();
+    while unallocated_frames() > target {
+        match frame_alloc() {
+            Some(frame) => held.push(frame),
+            None => break,
+        }
+    }
+    let mut buf = [0u8; 512];
+    let read = match ROOT_FD.open("/proc/health", OpenFlags::O_RDONLY, false) {
+        Ok(health) => health.read(None, &mut buf),
+        Err(_) => 0,
+    };
+    let low = unallocated_frames();
+    drop(held);
+    ensure(
+        low > CRITICAL_MEMORY_FRAMES && low <= LOW_MEMORY_FRAMES,
+        "free memory not brought into the low band",
+    )?;
+    let report = core::str::from_utf8(&buf[..read]).map_err(|_| "report not utf-8")?;
+    ensure(
+        report.contains("\nmemory: degraded"),
+        "memory check not degraded",
+    )?;
+    ensure(
+        report.starts_with("status: degraded\n"),
+        "overall status not degraded",
+    )
+}
+
+/// Fill fd tables until half of the system-wide fd limit is open: `/proc/health`
+/// must report file descriptors as degraded, and healthy again once they are closed
+fn check_health_fd_usage() -> CheckResult {
+    let limit = crate::config::SYSTEM_OPEN_FILE_LIMIT as u64;
+    let read_health =
+        |buf: &mut [u8]| match ROOT_FD.open("/proc/health", OpenFlags::O_RDONLY, false) {
+            Ok(health) => health.read(None, buf),
+            Err(_) => 0,
+        };
+    let file = ROOT_FD
+        .open("/dev/null", OpenFlags::O_RDONLY, false)
+        .map_err(|_| "open /dev/null failed")?;
+    let before = OPEN_FDS.get();
+    let mut tables = Vec::new();
+    while OPEN_FDS.get() < limit / 2 {
+        let mut table = FdTable::new(Vec::new());
+        while OPEN_FDS.get() < limit / 2 && table.insert(file.clone()).is_ok() {}
+        tables.push(table);
+    }
+    let open = OPEN_FDS.get();
+    let mut buf = [0u8; 512];
+    let read = read_health(&mut buf);
+    drop(tables);
+    ensure(open == limit / 2, "open fds not counted on insert")?;
+    ensure(OPEN_FDS.get() == before, "open fds not released on drop")?;
+    let report = core::str::from_utf8(&buf[..read]).map_err(|_| "report not utf-8")?;
+    ensure(
+        report.contains("\nfile_descriptors: degraded"),
+        "fd check not degraded",
+    )?;
+    let read = read_health(&mut buf);
+    let report = core::str::from_utf8(&buf[..read]).map_err(|_| "report not utf-8")?;
+    ensure(
+        report.contains("\nfile_descriptors: healthy"),
+        "fd check not healthy after close",
+    )
+}
+
+/// Percentiles interpolate within the bucket holding them instead of
+/// reporting its upper edge
+fn check_histogram_percentile() -> CheckResult {
+    static HISTOGRAM: Histogram = Histogram::new("selftest_latency", "uniform 1us..1ms");
+    // 1us, 2us, ..., 1ms: the true p99 is 990us, the p99 bucket is (500us, 1ms]
+    for i in 1..=1000u64 {
+        HISTOGRAM.observe(i * 1_000);
+    }
+    let truth = 990_000i64;
+    let bucket_edge = 1_000_000i64;
+    let p99 = HISTOGRAM.percentile(99.0) as i64;
+    ensure(
+        (p99 - truth).abs() < (bucket_edge - truth).abs() && (p99 - truth).abs() <= 1_000,
+        "p99 not interpolated",
+    )?;
+    ensure(
+        HISTOGRAM.percentile(50.0) == 500_000
+            && HISTOGRAM.percentile(100.0) == 1_000_000
+            && HISTOGRAM.percentile(0.0) == 1_000,
+        "wrong p0, p50 or p100",
+    )
+}
+
+/// Labeled counters format one line per label, and labels past the limit
+/// are counted under the overflow label
+fn check_labeled_counter() -> CheckResult {
+    static SYSCALLS: LabeledCounter =
+        LabeledCounter::new("selftest_syscalls", "by name", "name");
+    static IRQS: LabeledCounter = LabeledCounter::new("selftest_irqs", "by device", "device");
+    static DEVICES: LabeledCounter =
+        LabeledCounter::new("selftest_bounded", "by device", "device");
+    SYSCALLS.inc("read");
+    SYSCALLS.add("write", 3);
+    SYSCALLS.inc("read");
+    IRQS.inc("virtio-blk");
+    let mut output = alloc::string::String::new();
+    SYSCALLS
+        .format_into(&mut output)
+        .and_then(|_| IRQS.format_into(&mut output))
+        .map_err(|_| "not formatted")?;
+    ensure(
+        output
+            == "selftest_syscalls{name=\"read\"}: 2\n\
+                selftest_syscalls{name=\"write\"}: 3\n\
+                selftest_irqs{device=\"virtio-blk\"}: 1\n",
+        "wrong labeled lines",
+    )?;
+    for i in 0..MAX_LABELS + 8 {
+        DEVICES.inc(alloc::boxed::Box::leak(
+            alloc::format!("dev{}", i).into_boxed_str(),
+        ));
+    }
+    DEVICES.inc("dev0");
+    ensure(
+        DEVICES.snapshot().len() == MAX_LABELS + 1,
+        "labels not bounded",
+    )?;
+    ensure(
+        DEVICES.get("dev0") == 2 && DEVICES.get(OVERFLOW_LABEL) == 8,
+        "labels past the limit not counted as overflow",
+    )
+}
+
+/// The run queue imbalance is the gap between the longest and the shortest
+/// queue, and a single CPU is never imbalanced
+fn check_run_queue_imbalance() -> CheckResult {
+    static RUN_QUEUE: PerCpuGauge = PerCpuGauge::new("selftest_run_queue_length");
+    let cpus = || 0..2;
+    ensure(RUN_QUEUE.imbalance(cpus()) == 0, "empty queues imbalanced")?;
+    // tasks created on CPU 0 pile up there
+    for len in 1..=8 {
+        RUN_QUEUE.set(0, len);
+        ensure(
+            RUN_QUEUE.imbalance(cpus()) == len,
+            "imbalance is not the queue gap",
+        )?;
+    }
+    // until CPU 1 takes half of them
+    RUN_QUEUE.set(0, 4);
+    RUN_QUEUE.set(1, 4);
+    ensure(RUN_QUEUE.imbalance(cpus()) == 0, "even queues imbalanced")?;
+    ensure(
+        RUN_QUEUE.imbalance(0..1) == 0 && RUN_QUEUE.imbalance(0..0) == 0,
+        "single CPU imbalanced",
+    )
+}
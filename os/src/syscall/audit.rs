@@ -0,0 +1,204 @@
+//! Syscall audit log
+//!
+//! Security-relevant syscalls are recorded in a ring buffer read through
+//! `/proc/audit`, one line per call with the time, the caller's pid and uid, the
+//! arguments and the result:
+//!
+//! ```text
+//! 1532 pid=7 uid=0 syscall=execve path="/bin/sh" ret=0
+//! ```
+//!
+//! The watched syscalls default to execve, setuid, mount, ptrace and opens for
+//! writing. `/proc/sys/kernel/audit_syscalls` lists them by name and replaces the
+//! set when written a list of syscall names or numbers. Only numbers below
+//! [`MAX_AUDIT_SYSCALL`] can be watched, checking the set is a single atomic load.
+
+use super::dispatch::get_syscall_name;
+use super::errno::EINVAL;
+use super::fs::OpenHow;
+use super::syscall_id::*;
+use crate::fs::OpenFlags;
+use crate::mm::{get_from_user, translated_str};
+use crate::task::{current_task, current_user_token};
+use crate::timer::get_time_ms;
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::Mutex;
+
+/// Records kept, the oldest are dropped first
+pub const AUDIT_LOG_SIZE: usize = 256;
+/// Syscalls numbered from here on cannot be watched
+pub const MAX_AUDIT_SYSCALL: usize = 512;
+/// Not in the syscall table, but still worth auditing when attempted
+const SYSCALL_SETUID: usize = 146;
+
+const DEFAULT_WATCHED: &[usize] = &[
+    SYSCALL_EXECVE,
+    SYSCALL_OPENAT,
+    SYSCALL_OPENAT2,
+    SYSCALL_OPEN,
+    SYSCALL_SETUID,
+    SYSCALL_MOUNT,
+    SYSCALL_PTRACE,
+];
+
+const WATCHED_WORDS: usize = MAX_AUDIT_SYSCALL / 64;
+
+const fn default_watched() -> [u64; WATCHED_WORDS] {
+    let mut words = [0; WATCHED_WORDS];
+    let mut i = 0;
+    while i < DEFAULT_WATCHED.len() {
+        let id = DEFAULT_WATCHED[i];
+        words[id / 64] |= 1 << (id % 64);
+        i += 1;
+    }
+    words
+}
+
+/// Bitmap of the watched syscall numbers
+static WATCHED: [AtomicU64; WATCHED_WORDS] = {
+    const DEFAULT: [u64; WATCHED_WORDS] = default_watched();
+    const ZERO: AtomicU64 = AtomicU64::new(0);
+    let mut watched = [ZERO; WATCHED_WORDS];
+    let mut word = 0;
+    while word < WATCHED_WORDS {
+        watched[word] = AtomicU64::new(DEFAULT[word]);
+        word += 1;
+    }
+    watched
+};
+
+static LOG: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Whether syscall `id` is audited
+#[inline]
+pub fn is_watched(id: usize) -> bool {
+    id < MAX_AUDIT_SYSCALL && WATCHED[id / 64].load(Ordering::Relaxed) & (1 << (id % 64)) != 0
+}
+
+/// Describe the arguments of a watched call, `None` for calls not worth a record
+/// (opens that cannot write). Must run before the call: a successful execve
+/// replaces the address space the strings are read from.
+pub fn describe(id: usize, args: &[usize; 6]) -> Option<String> {
+    let token = current_user_token();
+    let string = |ptr: usize| translated_str(token, ptr as *const u8).unwrap_or_default();
+    let writes = |flags: usize| {
+        OpenFlags::from_bits_truncate(flags as u32).intersects(
+            OpenFlags::O_WRONLY | OpenFlags::O_RDWR | OpenFlags::O_CREAT | OpenFlags::O_TRUNC,
+        )
+    };
+    let how_flags = |ptr: usize| {
+        get_from_user(token, ptr as *const OpenHow).map_or(0, |how| how.flags as usize)
+    };
+    let mut detail = String::new();
+    match id {
+        SYSCALL_EXECVE => write!(detail, "path={:?}", string(args[0])),
+        SYSCALL_OPENAT if !writes(args[2]) => return None,
+        SYSCALL_OPENAT => write!(detail, "path={:?} flags={:#o}", string(args[1]), args[2]),
+        SYSCALL_OPENAT2 if !writes(how_flags(args[2])) => return None,
+        SYSCALL_OPENAT2 => write!(
+            detail,
+            "path={:?} flags={:#o}",
+            string(args[1]),
+            how_flags(args[2])
+        ),
+        SYSCALL_OPEN if !writes(args[1]) => return None,
+        SYSCALL_OPEN => write!(detail, "path={:?} flags={:#o}", string(args[0]), args[1]),
+        SYSCALL_MOUNT => write!(
+            detail,
+            "source={:?} target={:?} fstype={:?}",
+            string(args[0]),
+            string(args[1]),
+            string(args[2])
+        ),
+        SYSCALL_PTRACE => write!(detail, "request={} pid={}", args[0], args[1]),
+        SYSCALL_SETUID => write!(detail, "uid={}", args[0]),
+        _ => write!(
+            detail,
+            "a0={:#x} a1={:#x} a2={:#x} a3={:#x}",
+            args[0], args[1], args[2], args[3]
+        ),
+    }
+    .ok()?;
+    Some(detail)
+}
+
+/// Append the record of a finished call described by [`describe`]
+pub fn log(id: usize, detail: &str, ret: isize) {
+    let pid = current_task().map_or(0, |task| task.pid.0);
+    let mut record = String::new();
+    write!(
+        record,
+        "{} pid={} uid={} syscall={} {} ret={}",
+        get_time_ms(),
+        pid,
+        super::process::sys_getuid(),
+        name(id),
+        detail,
+        ret
+    )
+    .ok();
+    let mut log = LOG.lock();
+    if log.len() == AUDIT_LOG_SIZE {
+        log.pop_front();
+    }
+    log.push_back(record);
+}
+
+fn name(id: usize) -> &'static str {
+    match id {
+        SYSCALL_SETUID => "setuid",
+        id => get_syscall_name(id),
+    }
+}
+
+/// Contents of `/proc/audit`, oldest record first
+pub fn format_log() -> String {
+    let mut output = String::new();
+    for record in LOG.lock().iter() {
+        writeln!(output, "{}", record).ok();
+    }
+    output
+}
+
+/// Contents of `/proc/sys/kernel/audit_syscalls`
+pub fn format_watched() -> String {
+    let mut output = String::new();
+    for id in (0..MAX_AUDIT_SYSCALL).filter(|&id| is_watched(id)) {
+        match name(id) {
+            "unknown" => write!(output, "{} ", id),
+            name => write!(output, "{} ", name),
+        }
+        .ok();
+    }
+    output.pop();
+    output.push('\n');
+    output
+}
+
+/// Watch exactly the syscalls in a whitespace or comma separated list of names
+/// and numbers, an empty list turns auditing off
+pub fn set_watched(list: &str) -> Result<(), isize> {
+    let mut watched = [0u64; WATCHED_WORDS];
+    for word in list
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|word| !word.is_empty())
+    {
+        let id = match word.parse::<usize>() {
+            Ok(id) => id,
+            Err(_) => (0..MAX_AUDIT_SYSCALL)
+                .find(|&id| name(id) == word)
+                .ok_or(EINVAL)?,
+        };
+        if id >= MAX_AUDIT_SYSCALL {
+            return Err(EINVAL);
+        }
+        watched[id / 64] |= 1 << (id % 64);
+    }
+    for (word, bits) in WATCHED.iter().zip(watched) {
+        word.store(bits, Ordering::Relaxed);
+    }
+    Ok(())
+}
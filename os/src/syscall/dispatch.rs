@@ -27,6 +27,7 @@ use super::syscall_id::*;
 use crate::fs::poll::FdSet;
 use crate::task::Rusage;
 use crate::timer::{ITimerVal, TimeSpec, Times};
+use crate::utils::telemetry::SYSCALLS_BY_NAME;
 
 /// Maximum syscall number supported
 pub const MAX_SYSCALL_NR: usize = 512;
@@ -138,10 +139,6 @@ fn wrap_dup(a: &SyscallArgs) -> isize {
     sys_dup(a.arg(0))
 }
 
-fn wrap_dup2(a: &SyscallArgs) -> isize {
-    sys_dup2(a.arg(0), a.arg(1))
-}
-
 fn wrap_dup3(a: &SyscallArgs) -> isize {
     sys_dup3(a.arg(0), a.arg(1), a.arg_u32(2))
 }
@@ -170,6 +167,18 @@ fn wrap_mount(a: &SyscallArgs) -> isize {
     sys_mount(a.arg_ptr(0), a.arg_ptr(1), a.arg_ptr(2), a.arg(3), a.arg_ptr(4))
 }
 
+fn wrap_pivot_root(a: &SyscallArgs) -> isize {
+    sys_pivot_root(a.arg_ptr(0), a.arg_ptr(1))
+}
+
+fn wrap_statmount(a: &SyscallArgs) -> isize {
+    sys_statmount(a.arg_ptr(0), a.arg_mut_ptr(1), a.arg(2), a.arg_u32(3))
+}
+
+fn wrap_listmount(a: &SyscallArgs) -> isize {
+    sys_listmount(a.arg_ptr(0), a.arg_mut_ptr(1), a.arg(2), a.arg_u32(3))
+}
+
 fn wrap_statfs(a: &SyscallArgs) -> isize {
     sys_statfs(a.arg_ptr(0), a.arg_mut_ptr(1))
 }
@@ -186,6 +195,10 @@ fn wrap_chdir(a: &SyscallArgs) -> isize {
     sys_chdir(a.arg_ptr(0))
 }
 
+fn wrap_chroot(a: &SyscallArgs) -> isize {
+    sys_chroot(a.arg_ptr(0))
+}
+
 fn wrap_fchmodat(_a: &SyscallArgs) -> isize {
     sys_fchmodat()
 }
@@ -194,6 +207,18 @@ fn wrap_openat(a: &SyscallArgs) -> isize {
     sys_openat(a.arg(0), a.arg_ptr(1), a.arg_u32(2), a.arg_u32(3))
 }
 
+fn wrap_openat2(a: &SyscallArgs) -> isize {
+    sys_openat2(a.arg(0), a.arg_ptr(1), a.arg_ptr(2), a.arg(3))
+}
+
+fn wrap_name_to_handle_at(a: &SyscallArgs) -> isize {
+    sys_name_to_handle_at(a.arg(0), a.arg_ptr(1), a.arg_mut_ptr(2), a.arg_mut_ptr(3), a.arg_u32(4))
+}
+
+fn wrap_open_by_handle_at(a: &SyscallArgs) -> isize {
+    sys_open_by_handle_at(a.arg(0), a.arg_ptr(1), a.arg_u32(2))
+}
+
 fn wrap_close(a: &SyscallArgs) -> isize {
     sys_close(a.arg(0))
 }
@@ -253,6 +278,22 @@ fn wrap_ppoll(a: &SyscallArgs) -> isize {
     sys_ppoll(a.arg(0), a.arg(1), a.arg(2), a.arg(3))
 }
 
+fn wrap_signalfd4(a: &SyscallArgs) -> isize {
+    sys_signalfd4(a.arg_isize(0), a.arg(1), a.arg(2), a.arg_u32(3))
+}
+
+fn wrap_epoll_create1(a: &SyscallArgs) -> isize {
+    sys_epoll_create1(a.arg_u32(0))
+}
+
+fn wrap_epoll_ctl(a: &SyscallArgs) -> isize {
+    sys_epoll_ctl(a.arg(0), a.arg(1), a.arg(2), a.arg(3))
+}
+
+fn wrap_epoll_pwait(a: &SyscallArgs) -> isize {
+    sys_epoll_pwait(a.arg(0), a.arg(1), a.arg(2), a.arg_i32(3), a.arg(4))
+}
+
 fn wrap_splice(a: &SyscallArgs) -> isize {
     sys_splice(
         a.arg(0),
@@ -284,6 +325,10 @@ fn wrap_utimensat(a: &SyscallArgs) -> isize {
     sys_utimensat(a.arg(0), a.arg_ptr(1), a.arg_ptr(2), a.arg_u32(3))
 }
 
+fn wrap_acct(a: &SyscallArgs) -> isize {
+    sys_acct(a.arg_ptr(0))
+}
+
 fn wrap_exit(a: &SyscallArgs) -> isize {
     sys_exit(a.arg_u32(0))
 }
@@ -296,6 +341,10 @@ fn wrap_set_tid_address(a: &SyscallArgs) -> isize {
     sys_set_tid_address(a.arg(0))
 }
 
+fn wrap_unshare(a: &SyscallArgs) -> isize {
+    sys_unshare(a.arg_u32(0))
+}
+
 fn wrap_futex(a: &SyscallArgs) -> isize {
     sys_futex(
         a.arg_mut_ptr(0),
@@ -323,6 +372,30 @@ fn wrap_setitimer(a: &SyscallArgs) -> isize {
     sys_setitimer(a.arg(0), a.arg_ptr(1), a.arg_mut_ptr(2))
 }
 
+fn wrap_timer_create(a: &SyscallArgs) -> isize {
+    sys_timer_create(a.arg(0), a.arg_ptr(1), a.arg_mut_ptr(2))
+}
+
+fn wrap_timer_gettime(a: &SyscallArgs) -> isize {
+    sys_timer_gettime(a.arg(0), a.arg_mut_ptr(1))
+}
+
+fn wrap_timer_getoverrun(a: &SyscallArgs) -> isize {
+    sys_timer_getoverrun(a.arg(0))
+}
+
+fn wrap_timer_settime(a: &SyscallArgs) -> isize {
+    sys_timer_settime(a.arg(0), a.arg_u32(1), a.arg_ptr(2), a.arg_mut_ptr(3))
+}
+
+fn wrap_timer_delete(a: &SyscallArgs) -> isize {
+    sys_timer_delete(a.arg(0))
+}
+
+fn wrap_clock_settime(a: &SyscallArgs) -> isize {
+    sys_clock_settime(a.arg(0), a.arg_ptr(1))
+}
+
 fn wrap_clock_gettime(a: &SyscallArgs) -> isize {
     sys_clock_gettime(a.arg(0), a.arg_mut_ptr(1))
 }
@@ -335,10 +408,18 @@ fn wrap_syslog(a: &SyscallArgs) -> isize {
     sys_syslog(a.arg_u32(0), a.arg_mut_ptr(1), a.arg_u32(2))
 }
 
+fn wrap_ptrace(a: &SyscallArgs) -> isize {
+    sys_ptrace(a.arg(0), a.arg(1), a.arg(2), a.arg(3))
+}
+
 fn wrap_yield(_a: &SyscallArgs) -> isize {
     sys_yield()
 }
 
+fn wrap_sched_yield_to(a: &SyscallArgs) -> isize {
+    sys_sched_yield_to(a.arg(0))
+}
+
 fn wrap_kill(a: &SyscallArgs) -> isize {
     sys_kill(a.arg(0), a.arg(1))
 }
@@ -375,6 +456,10 @@ fn wrap_getpriority(a: &SyscallArgs) -> isize {
     sys_getpriority(a.arg_i32(0), a.arg_i32(1))
 }
 
+fn wrap_reboot(a: &SyscallArgs) -> isize {
+    sys_reboot(a.arg_u32(0), a.arg_u32(1), a.arg_u32(2), a.arg(3))
+}
+
 // Scheduler syscall wrappers
 fn wrap_sched_setscheduler(a: &SyscallArgs) -> isize {
     sys_sched_setscheduler(a.arg(0), a.arg_u32(1), a.arg_ptr(2))
@@ -420,6 +505,10 @@ fn wrap_getpgid(a: &SyscallArgs) -> isize {
     sys_getpgid(a.arg(0))
 }
 
+fn wrap_getsid(a: &SyscallArgs) -> isize {
+    sys_getsid(a.arg(0))
+}
+
 fn wrap_setsid(_a: &SyscallArgs) -> isize {
     sys_setsid()
 }
@@ -436,6 +525,10 @@ fn wrap_umask(a: &SyscallArgs) -> isize {
     sys_umask(a.arg_u32(0))
 }
 
+fn wrap_getcpu(a: &SyscallArgs) -> isize {
+    sys_getcpu(a.arg_mut_ptr(0), a.arg_mut_ptr(1))
+}
+
 fn wrap_gettimeofday(a: &SyscallArgs) -> isize {
     sys_gettimeofday(a.arg_mut_ptr(0), a.arg_mut_ptr(1))
 }
@@ -556,6 +649,10 @@ fn wrap_msync(a: &SyscallArgs) -> isize {
     sys_msync(a.arg(0), a.arg(1), a.arg_u32(2))
 }
 
+fn wrap_mincore(a: &SyscallArgs) -> isize {
+    sys_mincore(a.arg(0), a.arg(1), a.arg_mut_ptr(2))
+}
+
 fn wrap_madvise(a: &SyscallArgs) -> isize {
     sys_madvise(a.arg(0), a.arg(1), a.arg_u32(2))
 }
@@ -596,6 +693,10 @@ fn wrap_shutdown(_a: &SyscallArgs) -> isize {
     sys_shutdown()
 }
 
+fn wrap_watchpoint(a: &SyscallArgs) -> isize {
+    sys_watchpoint(a.arg(0), a.arg_u32(1))
+}
+
 fn wrap_get_time(_a: &SyscallArgs) -> isize {
     sys_get_time()
 }
@@ -617,8 +718,10 @@ pub fn dispatch_syscall(id: usize, args: [usize; 6]) -> Option<(&'static str, is
     
     let (name, handler): (&'static str, Option<SyscallHandler>) = match id {
         SYSCALL_GETCWD => ("getcwd", Some(wrap_getcwd)),
+        SYSCALL_EPOLL_CREATE1 => ("epoll_create1", Some(wrap_epoll_create1)),
+        SYSCALL_EPOLL_CTL => ("epoll_ctl", Some(wrap_epoll_ctl)),
+        SYSCALL_EPOLL_PWAIT => ("epoll_pwait", Some(wrap_epoll_pwait)),
         SYSCALL_DUP => ("dup", Some(wrap_dup)),
-        SYSCALL_DUP2 => ("dup2", Some(wrap_dup2)),
         SYSCALL_DUP3 => ("dup3", Some(wrap_dup3)),
         SYSCALL_FCNTL => ("fcntl", Some(wrap_fcntl)),
         SYSCALL_IOCTL => ("ioctl", Some(wrap_ioctl)),
@@ -626,12 +729,19 @@ pub fn dispatch_syscall(id: usize, args: [usize; 6]) -> Option<(&'static str, is
         SYSCALL_UNLINKAT => ("unlinkat", Some(wrap_unlinkat)),
         SYSCALL_UMOUNT2 => ("umount2", Some(wrap_umount2)),
         SYSCALL_MOUNT => ("mount", Some(wrap_mount)),
+        SYSCALL_PIVOT_ROOT => ("pivot_root", Some(wrap_pivot_root)),
+        SYSCALL_STATMOUNT => ("statmount", Some(wrap_statmount)),
+        SYSCALL_LISTMOUNT => ("listmount", Some(wrap_listmount)),
         SYSCALL_STATFS => ("statfs", Some(wrap_statfs)),
         SYSCALL_FTRUNCATE => ("ftruncate", Some(wrap_ftruncate)),
         SYSCALL_FACCESSAT => ("faccessat", Some(wrap_faccessat)),
         SYSCALL_CHDIR => ("chdir", Some(wrap_chdir)),
+        SYSCALL_CHROOT => ("chroot", Some(wrap_chroot)),
         SYSCALL_FCHMODAT => ("fchmodat", Some(wrap_fchmodat)),
         SYSCALL_OPENAT => ("openat", Some(wrap_openat)),
+        SYSCALL_OPENAT2 => ("openat2", Some(wrap_openat2)),
+        SYSCALL_NAME_TO_HANDLE_AT => ("name_to_handle_at", Some(wrap_name_to_handle_at)),
+        SYSCALL_OPEN_BY_HANDLE_AT => ("open_by_handle_at", Some(wrap_open_by_handle_at)),
         SYSCALL_CLOSE => ("close", Some(wrap_close)),
         SYSCALL_PIPE2 => ("pipe2", Some(wrap_pipe2)),
         SYSCALL_GETDENTS64 => ("getdents64", Some(wrap_getdents64)),
@@ -645,24 +755,35 @@ pub fn dispatch_syscall(id: usize, args: [usize; 6]) -> Option<(&'static str, is
         SYSCALL_SENDFILE => ("sendfile", Some(wrap_sendfile)),
         SYSCALL_PSELECT6 => ("pselect6", Some(wrap_pselect6)),
         SYSCALL_PPOLL => ("ppoll", Some(wrap_ppoll)),
+        SYSCALL_SIGNALFD4 => ("signalfd4", Some(wrap_signalfd4)),
         SYSCALL_SPLICE => ("splice", Some(wrap_splice)),
         SYSCALL_READLINKAT => ("readlinkat", Some(wrap_readlinkat)),
         SYSCALL_FSTATAT => ("fstatat", Some(wrap_fstatat)),
         SYSCALL_FSTAT => ("fstat", Some(wrap_fstat)),
         SYSCALL_FSYNC => ("fsync", Some(wrap_fsync)),
         SYSCALL_UTIMENSAT => ("utimensat", Some(wrap_utimensat)),
+        SYSCALL_ACCT => ("acct", Some(wrap_acct)),
         SYSCALL_EXIT => ("exit", Some(wrap_exit)),
         SYSCALL_EXIT_GROUP => ("exit_group", Some(wrap_exit_group)),
         SYSCALL_SET_TID_ADDRESS => ("set_tid_address", Some(wrap_set_tid_address)),
+        SYSCALL_UNSHARE => ("unshare", Some(wrap_unshare)),
         SYSCALL_FUTEX => ("futex", Some(wrap_futex)),
         SYSCALL_SET_ROBUST_LIST => ("set_robust_list", Some(wrap_set_robust_list)),
         SYSCALL_GET_ROBUST_LIST => ("get_robust_list", Some(wrap_get_robust_list)),
         SYSCALL_NANOSLEEP => ("nanosleep", Some(wrap_nanosleep)),
         SYSCALL_SETITIMER => ("setitimer", Some(wrap_setitimer)),
+        SYSCALL_TIMER_CREATE => ("timer_create", Some(wrap_timer_create)),
+        SYSCALL_TIMER_GETTIME => ("timer_gettime", Some(wrap_timer_gettime)),
+        SYSCALL_TIMER_GETOVERRUN => ("timer_getoverrun", Some(wrap_timer_getoverrun)),
+        SYSCALL_TIMER_SETTIME => ("timer_settime", Some(wrap_timer_settime)),
+        SYSCALL_TIMER_DELETE => ("timer_delete", Some(wrap_timer_delete)),
+        SYSCALL_CLOCK_SETTIME => ("clock_settime", Some(wrap_clock_settime)),
         SYSCALL_CLOCK_GETTIME => ("clock_gettime", Some(wrap_clock_gettime)),
         SYSCALL_CLOCK_NANOSLEEP => ("clock_nanosleep", Some(wrap_clock_nanosleep)),
         SYSCALL_SYSLOG => ("syslog", Some(wrap_syslog)),
+        SYSCALL_PTRACE => ("ptrace", Some(wrap_ptrace)),
         SYSCALL_YIELD => ("yield", Some(wrap_yield)),
+        SYSCALL_SCHED_YIELD_TO => ("sched_yield_to", Some(wrap_sched_yield_to)),
         SYSCALL_KILL => ("kill", Some(wrap_kill)),
         SYSCALL_TKILL => ("tkill", Some(wrap_tkill)),
         SYSCALL_TGKILL => ("tgkill", Some(wrap_tgkill)),
@@ -672,6 +793,7 @@ pub fn dispatch_syscall(id: usize, args: [usize; 6]) -> Option<(&'static str, is
         SYSCALL_SIGRETURN => ("sigreturn", Some(wrap_sigreturn)),
         SYSCALL_SETPRIORITY => ("setpriority", Some(wrap_setpriority)),
         SYSCALL_GETPRIORITY => ("getpriority", Some(wrap_getpriority)),
+        SYSCALL_REBOOT => ("reboot", Some(wrap_reboot)),
         SYSCALL_SCHED_SETPARAM => ("sched_setparam", Some(wrap_sched_setparam)),
         SYSCALL_SCHED_GETPARAM => ("sched_getparam", Some(wrap_sched_getparam)),
         SYSCALL_SCHED_SETSCHEDULER => ("sched_setscheduler", Some(wrap_sched_setscheduler)),
@@ -683,10 +805,12 @@ pub fn dispatch_syscall(id: usize, args: [usize; 6]) -> Option<(&'static str, is
         SYSCALL_TIMES => ("times", Some(wrap_times)),
         SYSCALL_SETPGID => ("setpgid", Some(wrap_setpgid)),
         SYSCALL_GETPGID => ("getpgid", Some(wrap_getpgid)),
+        SYSCALL_GETSID => ("getsid", Some(wrap_getsid)),
         SYSCALL_SETSID => ("setsid", Some(wrap_setsid)),
         SYSCALL_UNAME => ("uname", Some(wrap_uname)),
         SYSCALL_GETRUSAGE => ("getrusage", Some(wrap_getrusage)),
         SYSCALL_UMASK => ("umask", Some(wrap_umask)),
+        SYSCALL_GETCPU => ("getcpu", Some(wrap_getcpu)),
         SYSCALL_GET_TIME_OF_DAY => ("gettimeofday", Some(wrap_gettimeofday)),
         SYSCALL_GETPID => ("getpid", Some(wrap_getpid)),
         SYSCALL_GETPPID => ("getppid", Some(wrap_getppid)),
@@ -717,6 +841,7 @@ pub fn dispatch_syscall(id: usize, args: [usize; 6]) -> Option<(&'static str, is
         SYSCALL_MMAP => ("mmap", Some(wrap_mmap)),
         SYSCALL_MPROTECT => ("mprotect", Some(wrap_mprotect)),
         SYSCALL_MSYNC => ("msync", Some(wrap_msync)),
+        SYSCALL_MINCORE => ("mincore", Some(wrap_mincore)),
         SYSCALL_MADVISE => ("madvise", Some(wrap_madvise)),
         SYSCALL_WAIT4 => ("wait4", Some(wrap_wait4)),
         SYSCALL_PRLIMIT => ("prlimit", Some(wrap_prlimit)),
@@ -728,20 +853,25 @@ pub fn dispatch_syscall(id: usize, args: [usize; 6]) -> Option<(&'static str, is
         SYSCALL_FACCESSAT2 => ("faccessat2", Some(wrap_faccessat2)),
         // Non-standard syscalls
         SYSCALL_SHUTDOWN => ("shutdown", Some(wrap_shutdown)),
+        SYSCALL_WATCHPOINT => ("watchpoint", Some(wrap_watchpoint)),
         SYSCALL_GET_TIME => ("get_time", Some(wrap_get_time)),
         SYSCALL_OPEN => ("open", Some(wrap_open)),
         _ => ("unknown", None),
     };
     
-    handler.map(|h| (name, h(&syscall_args)))
+    let handler = handler?;
+    SYSCALLS_BY_NAME.inc(name);
+    Some((name, handler(&syscall_args)))
 }
 
 /// Get syscall name from ID (for logging)
 pub fn get_syscall_name(id: usize) -> &'static str {
     match id {
         SYSCALL_GETCWD => "getcwd",
+        SYSCALL_EPOLL_CREATE1 => "epoll_create1",
+        SYSCALL_EPOLL_CTL => "epoll_ctl",
+        SYSCALL_EPOLL_PWAIT => "epoll_pwait",
         SYSCALL_DUP => "dup",
-        SYSCALL_DUP2 => "dup2",
         SYSCALL_DUP3 => "dup3",
         SYSCALL_FCNTL => "fcntl",
         SYSCALL_IOCTL => "ioctl",
@@ -749,12 +879,19 @@ pub fn get_syscall_name(id: usize) -> &'static str {
         SYSCALL_UNLINKAT => "unlinkat",
         SYSCALL_UMOUNT2 => "umount2",
         SYSCALL_MOUNT => "mount",
+        SYSCALL_PIVOT_ROOT => "pivot_root",
+        SYSCALL_STATMOUNT => "statmount",
+        SYSCALL_LISTMOUNT => "listmount",
         SYSCALL_STATFS => "statfs",
         SYSCALL_FTRUNCATE => "ftruncate",
         SYSCALL_FACCESSAT => "faccessat",
         SYSCALL_CHDIR => "chdir",
+        SYSCALL_CHROOT => "chroot",
         SYSCALL_FCHMODAT => "fchmodat",
         SYSCALL_OPENAT => "openat",
+        SYSCALL_OPENAT2 => "openat2",
+        SYSCALL_NAME_TO_HANDLE_AT => "name_to_handle_at",
+        SYSCALL_OPEN_BY_HANDLE_AT => "open_by_handle_at",
         SYSCALL_CLOSE => "close",
         SYSCALL_PIPE2 => "pipe2",
         SYSCALL_GETDENTS64 => "getdents64",
@@ -768,24 +905,35 @@ pub fn get_syscall_name(id: usize) -> &'static str {
         SYSCALL_SENDFILE => "sendfile",
         SYSCALL_PSELECT6 => "pselect6",
         SYSCALL_PPOLL => "ppoll",
+        SYSCALL_SIGNALFD4 => "signalfd4",
         SYSCALL_SPLICE => "splice",
         SYSCALL_READLINKAT => "readlinkat",
         SYSCALL_FSTATAT => "fstatat",
         SYSCALL_FSTAT => "fstat",
         SYSCALL_FSYNC => "fsync",
         SYSCALL_UTIMENSAT => "utimensat",
+        SYSCALL_ACCT => "acct",
         SYSCALL_EXIT => "exit",
         SYSCALL_EXIT_GROUP => "exit_group",
         SYSCALL_SET_TID_ADDRESS => "set_tid_address",
+        SYSCALL_UNSHARE => "unshare",
         SYSCALL_FUTEX => "futex",
         SYSCALL_SET_ROBUST_LIST => "set_robust_list",
         SYSCALL_GET_ROBUST_LIST => "get_robust_list",
         SYSCALL_NANOSLEEP => "nanosleep",
         SYSCALL_SETITIMER => "setitimer",
+        SYSCALL_TIMER_CREATE => "timer_create",
+        SYSCALL_TIMER_GETTIME => "timer_gettime",
+        SYSCALL_TIMER_GETOVERRUN => "timer_getoverrun",
+        SYSCALL_TIMER_SETTIME => "timer_settime",
+        SYSCALL_TIMER_DELETE => "timer_delete",
+        SYSCALL_CLOCK_SETTIME => "clock_settime",
         SYSCALL_CLOCK_GETTIME => "clock_gettime",
         SYSCALL_CLOCK_NANOSLEEP => "clock_nanosleep",
         SYSCALL_SYSLOG => "syslog",
+        SYSCALL_PTRACE => "ptrace",
         SYSCALL_YIELD => "yield",
+        SYSCALL_SCHED_YIELD_TO => "sched_yield_to",
         SYSCALL_KILL => "kill",
         SYSCALL_TKILL => "tkill",
         SYSCALL_TGKILL => "tgkill",
@@ -793,13 +941,16 @@ pub fn get_syscall_name(id: usize) -> &'static str {
         SYSCALL_SIGPROCMASK => "sigprocmask",
         SYSCALL_SIGTIMEDWAIT => "sigtimedwait",
         SYSCALL_SIGRETURN => "sigreturn",
+        SYSCALL_REBOOT => "reboot",
         SYSCALL_TIMES => "times",
         SYSCALL_SETPGID => "setpgid",
         SYSCALL_GETPGID => "getpgid",
+        SYSCALL_GETSID => "getsid",
         SYSCALL_SETSID => "setsid",
         SYSCALL_UNAME => "uname",
         SYSCALL_GETRUSAGE => "getrusage",
         SYSCALL_UMASK => "umask",
+        SYSCALL_GETCPU => "getcpu",
         SYSCALL_GET_TIME_OF_DAY => "gettimeofday",
         SYSCALL_GETPID => "getpid",
         SYSCALL_GETPPID => "getppid",
@@ -830,6 +981,7 @@ pub fn get_syscall_name(id: usize) -> &'static str {
         SYSCALL_MMAP => "mmap",
         SYSCALL_MPROTECT => "mprotect",
         SYSCALL_MSYNC => "msync",
+        SYSCALL_MINCORE => "mincore",
         SYSCALL_MADVISE => "madvise",
         SYSCALL_WAIT4 => "wait4",
         SYSCALL_PRLIMIT => "prlimit",
@@ -840,6 +992,7 @@ pub fn get_syscall_name(id: usize) -> &'static str {
         SYSCALL_STATX => "statx",
         SYSCALL_FACCESSAT2 => "faccessat2",
         SYSCALL_SHUTDOWN => "shutdown",
+        SYSCALL_WATCHPOINT => "watchpoint",
         SYSCALL_GET_TIME => "get_time",
         SYSCALL_OPEN => "open",
         _ => "unknown",
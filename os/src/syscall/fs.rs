@@ -1,6 +1,15 @@
-use crate::fs::poll::{ppoll, pselect, FdSet, PollFd};
+use crate::fs::poll::{
+    epoll_pwait, ppoll, pselect, EpollEvent, EpollInstance, FdSet, PollFd, EPOLL_CTL_DEL,
+};
 use crate::fs::*;
 use crate::fs::dev::pipe::Pipe;
+use crate::fs::dev::signalfd::SignalFd;
+use crate::fs::directory_tree::{open_fs_root, DirectoryTreeNode};
+use crate::fs::mount::{
+    current_mnt_ns, MOUNT_ATTR_NOATIME, MOUNT_ATTR_NODEV, MOUNT_ATTR_NODIRATIME, MOUNT_ATTR_NOEXEC,
+    MOUNT_ATTR_NOSUID, MOUNT_ATTR_RDONLY, MOUNT_ATTR_STRICTATIME, ROOT_MOUNT_ID,
+};
+use crate::config::PAGE_SIZE;
 use crate::hal::BLOCK_SZ;
 use crate::mm::{
     copy_from_user, copy_from_user_array, copy_to_user, copy_to_user_array, copy_to_user_string,
@@ -10,9 +19,11 @@ use crate::mm::{
 use crate::task::{current_task, current_user_token};
 use crate::timer::TimeSpec;
 use alloc::boxed::Box;
+use alloc::format;
 use alloc::string::String;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
-use core::mem::size_of;
+use core::convert::TryFrom;
 use core::panic;
 use log::{debug, error, info, trace, warn};
 use num_enum::FromPrimitive;
@@ -199,7 +210,8 @@ pub fn sys_getcwd(buf: usize, size: usize) -> isize {
         // The size argument is zero and buf is not a NULL pointer.
         return EINVAL;
     }
-    let working_dir = task.fs.lock().working_inode.get_cwd().unwrap();
+    let working_inode = task.fs.lock().working_inode.clone();
+    let working_dir = working_inode.get_cwd().unwrap();
     if working_dir.len() >= size {
         // The size argument is less than the length of the absolute pathname of the working directory,
         // including the terminating null byte.
@@ -230,9 +242,9 @@ pub fn sys_lseek(fd: usize, offset: isize, whence: u32) -> isize {
         fd, offset, whence,
     );
     let task = current_task().unwrap();
-    let fd_table = task.files.lock();
-    let file_descriptor = match fd_table.get_ref(fd) {
-        Ok(file_descriptor) => file_descriptor,
+    // lseek 要等文件偏移锁，不持有文件描述符表的锁
+    let file_descriptor = match task.files.lock().get_ref(fd) {
+        Ok(file_descriptor) => file_descriptor.clone(),
         Err(errno) => return errno,
     };
     match file_descriptor.lseek(offset, whence) {
@@ -243,9 +255,9 @@ pub fn sys_lseek(fd: usize, offset: isize, whence: u32) -> isize {
 
 pub fn sys_read(fd: usize, buf: usize, count: usize) -> isize {
     let task = current_task().unwrap();
-    let fd_table = task.files.lock();
-    let file_descriptor = match fd_table.get_ref(fd) {
-        Ok(file_descriptor) => file_descriptor,
+    // 读写可能阻塞（管道、终端），不持有文件描述符表的锁
+    let file_descriptor = match task.files.lock().get_ref(fd) {
+        Ok(file_descriptor) => file_descriptor.clone(),
         Err(errno) => return errno,
     };
     // fd is not open for reading
@@ -266,9 +278,8 @@ pub fn sys_read(fd: usize, buf: usize, count: usize) -> isize {
 
 pub fn sys_write(fd: usize, buf: usize, count: usize) -> isize {
     let task = current_task().unwrap();
-    let fd_table = task.files.lock();
-    let file_descriptor = match fd_table.get_ref(fd) {
-        Ok(file_descriptor) => file_descriptor,
+    let file_descriptor = match task.files.lock().get_ref(fd) {
+        Ok(file_descriptor) => file_descriptor.clone(),
         Err(errno) => return errno,
     };
     if !file_descriptor.writable() {
@@ -288,15 +299,18 @@ pub fn sys_write(fd: usize, buf: usize, count: usize) -> isize {
 
 pub fn sys_pread(fd: usize, buf: usize, count: usize, offset: usize) -> isize {
     let task = current_task().unwrap();
-    let fd_table = task.files.lock();
-    let file_descriptor = match fd_table.get_ref(fd) {
-        Ok(file_descriptor) => file_descriptor,
+    let file_descriptor = match task.files.lock().get_ref(fd) {
+        Ok(file_descriptor) => file_descriptor.clone(),
         Err(errno) => return errno,
     };
     // fd is not open for reading
     if !file_descriptor.readable() {
         return EBADF;
     }
+    // 负的偏移
+    if (offset as isize) < 0 {
+        return EINVAL;
+    }
     let token = task.get_user_token();
     file_descriptor.read_user(
         Some(offset),
@@ -311,15 +325,18 @@ pub fn sys_pread(fd: usize, buf: usize, count: usize, offset: usize) -> isize {
 
 pub fn sys_pwrite(fd: usize, buf: usize, count: usize, offset: usize) -> isize {
     let task = current_task().unwrap();
-    let fd_table = task.files.lock();
-    let file_descriptor = match fd_table.get_ref(fd) {
-        Ok(file_descriptor) => file_descriptor,
+    let file_descriptor = match task.files.lock().get_ref(fd) {
+        Ok(file_descriptor) => file_descriptor.clone(),
         Err(errno) => return errno,
     };
     // fd is not open for writing
     if !file_descriptor.writable() {
         return EBADF;
     }
+    // 负的偏移
+    if (offset as isize) < 0 {
+        return EINVAL;
+    }
     let token = task.get_user_token();
     file_descriptor.write_user(
         Some(offset),
@@ -341,9 +358,8 @@ struct IOVec {
 
 pub fn sys_readv(fd: usize, iov: usize, iovcnt: usize) -> isize {
     let task = current_task().unwrap();
-    let fd_table = task.files.lock();
-    let file_descriptor = match fd_table.get_ref(fd) {
-        Ok(file_descriptor) => file_descriptor,
+    let file_descriptor = match task.files.lock().get_ref(fd) {
+        Ok(file_descriptor) => file_descriptor.clone(),
         Err(errno) => return errno,
     };
     // fd is not open for reading
@@ -380,9 +396,8 @@ pub fn sys_readv(fd: usize, iov: usize, iovcnt: usize) -> isize {
 
 pub fn sys_writev(fd: usize, iov: usize, iovcnt: usize) -> isize {
     let task = current_task().unwrap();
-    let fd_table = task.files.lock();
-    let file_descriptor = match fd_table.get_ref(fd) {
-        Ok(file_descriptor) => file_descriptor,
+    let file_descriptor = match task.files.lock().get_ref(fd) {
+        Ok(file_descriptor) => file_descriptor.clone(),
         Err(errno) => return errno,
     };
     // fd is not open for writing
@@ -721,7 +736,11 @@ pub fn sys_close(fd: usize) -> isize {
     let task = current_task().unwrap();
     let mut fd_table = task.files.lock();
     match fd_table.remove(fd) {
-        Ok(_) => SUCCESS,
+        Ok(_) => {
+            // 关闭的 fd 不再持有套接字，AF_UNIX 的对端在所有 fd 关闭后读到文件末尾
+            task.socket_table.lock().take(fd);
+            SUCCESS
+        }
         Err(errno) => errno,
     }
 }
@@ -769,7 +788,11 @@ pub fn sys_pipe2(pipefd: usize, flags: u32) -> isize {
         pipe_write,
     )) {
         Ok(fd) => fd,
-        Err(errno) => return errno,
+        Err(errno) => {
+            // 两端要么都分配要么都不分配，不能留下只有读端的 fd
+            fd_table.remove(read_fd).ok();
+            return errno;
+        }
     };
 
     let token = task.get_user_token();
@@ -782,6 +805,8 @@ pub fn sys_pipe2(pipefd: usize, flags: u32) -> isize {
     .is_err()
     {
         log::error!("[sys_pipe2] Failed to copy to {:?}", pipefd);
+        fd_table.remove(read_fd).ok();
+        fd_table.remove(write_fd).ok();
         return EFAULT;
     };
     info!(
@@ -797,9 +822,9 @@ pub fn sys_pipe2(pipefd: usize, flags: u32) -> isize {
 /// # 参数
 /// + fd：文件描述符
 /// + dirp：用于存储获取到的目录项的指针
-/// + count：要获取的目录项的数量
+/// + count：dirp 缓冲区的字节数
 /// # 返回值
-/// + 成功：返回获取的目录项数量
+/// + 成功：返回写入的字节数，目录项长度不一，每条的 d_reclen 按 8 字节对齐
 /// + 失败：返回错误码
 pub fn sys_getdents64(fd: usize, dirp: *mut u8, count: usize) -> isize {
     let task = current_task().unwrap();
@@ -821,22 +846,19 @@ pub fn sys_getdents64(fd: usize, dirp: *mut u8, count: usize) -> isize {
         Ok(vec) => vec,
         Err(errno) => return errno,
     };
-    // 将结果复制到用户态的数组中
-    if copy_to_user_array(
-        token,
-        dirent_vec.as_ptr(),
-        dirp as *mut Dirent,
-        dirent_vec.len(),
-    )
-    .is_err()
-    {
+    // 按 linux_dirent64 的布局逐条编码
+    let mut buf = Vec::new();
+    for dirent in dirent_vec.iter() {
+        dirent.encode_into(&mut buf);
+    }
+    // 将结果复制到用户态的缓冲区中
+    if copy_to_user_array(token, buf.as_ptr(), dirp, buf.len()).is_err() {
         log::error!("[sys_getdents64] Failed to copy to {:?}", dirp);
         return EFAULT;
     };
     // info!("[sys_getdents64] fd: {}, count: {}", fd, count);
-    // println!("dirent count: {}", count / size_of::<Dirent>());
-    // 返回获取的目录项数量
-    dirent_vec.len() as isize * size_of::<Dirent>() as isize
+    // 返回写入的字节数
+    buf.len() as isize
 }
 
 pub fn sys_dup(oldfd: usize) -> isize {
@@ -854,23 +876,6 @@ pub fn sys_dup(oldfd: usize) -> isize {
     newfd as isize
 }
 
-pub fn sys_dup2(oldfd: usize, newfd: usize) -> isize {
-    let task = current_task().unwrap();
-    // if oldfd == newfd {
-    //     return EINVAL;
-    // }
-    let mut fd_table = task.files.lock();
-    let mut file_descriptor = match fd_table.get_ref(oldfd) {
-        Ok(file_descriptor) => file_descriptor.clone(),
-        Err(errno) => return errno,
-    };
-    file_descriptor.set_cloexec(false);
-    match fd_table.insert_at(file_descriptor, newfd) {
-        Ok(fd) => fd as isize,
-        Err(errno) => errno,
-    }
-}
-
 pub fn sys_dup3(oldfd: usize, newfd: usize, flags: u32) -> isize {
     info!(
         "[sys_dup3] oldfd: {}, newfd: {}, flags: {:?}",
@@ -1094,14 +1099,28 @@ pub struct Statfs {
     /// Padding bytes reserved for future use
     f_spare: [usize; 4],
 }
-/// Fake implement for statfs syscall
-pub fn sys_statfs(_path: *const u8, buf: *mut Statfs) -> isize {
+/// statfs 系统调用
+/// 路径所在的文件系统报告块数时返回真实的容量与空闲空间，其余字段仍是假数据
+pub fn sys_statfs(path: *const u8, buf: *mut Statfs) -> isize {
+    let token = current_user_token();
+    let path = match translated_str(token, path) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    let file_descriptor = match __openat(AT_FDCWD, path.as_str()) {
+        Ok(file_descriptor) => file_descriptor,
+        Err(errno) => return errno,
+    };
+    let (f_bsize, f_blocks, f_bfree) = file_descriptor
+        .file
+        .fs_blocks()
+        .unwrap_or((BLOCK_SZ, 10000, 9000));
     let statfs = Box::new(Statfs {
         f_type: 0xf2f52010,
-        f_bsize: BLOCK_SZ,
-        f_blocks: 10000,
-        f_bfree: 9000,
-        f_bavail: 9000,
+        f_bsize,
+        f_blocks,
+        f_bfree,
+        f_bavail: f_bfree,
         f_files: 1000,
         f_ffree: 960,
         f_fsid: [114, 514],
@@ -1110,7 +1129,6 @@ pub fn sys_statfs(_path: *const u8, buf: *mut Statfs) -> isize {
         f_flag: 0,
         f_spare: [0; 4],
     });
-    let token = current_task().unwrap().get_user_token();
     if copy_to_user(token, statfs.as_ref(), buf).is_err() {
         log::error!("[sys_statfs] Failed to copy to {:?}", buf);
         return EFAULT;
@@ -1122,10 +1140,12 @@ pub fn sys_fsync(fd: usize) -> isize {
     let task = current_task().unwrap();
 
     info!("[sys_fsync] fd: {}", fd);
-    let fd_table = task.files.lock();
-    if let Err(errno) = fd_table.check(fd) {
-        return errno;
-    }
+    let file = match task.files.lock().get_ref(fd) {
+        Ok(file_descriptor) => file_descriptor.file.clone(),
+        Err(errno) => return errno,
+    };
+    // 写回时可能睡眠等待块设备，不能持有文件描述符表的锁
+    file.fsync();
     SUCCESS
 }
 
@@ -1144,11 +1164,37 @@ pub fn sys_chdir(path: *const u8) -> isize {
     };
     info!("[sys_chdir] path: {}", path);
 
-    let mut lock = task.fs.lock();
-
-    match lock.working_inode.cd(&path) {
+    let working_inode = task.fs.lock().working_inode.clone();
+    match working_inode.cd(&path) {
         Ok(new_working_inode) => {
-            lock.working_inode = new_working_inode;
+            task.fs.lock().working_inode = new_working_inode;
+            SUCCESS
+        }
+        Err(errno) => errno,
+    }
+}
+
+/// 系统调用 sys_chroot
+/// # 说明
+/// + 把调用者的根目录设为 path：此后绝对路径从该目录开始解析，".." 停在该目录
+/// + 工作目录不变，它在新的根目录之外时仍可用相对路径访问根目录以外的文件，与 Linux 相同
+/// + 需要特权，有效用户不是 root 时返回 EPERM
+pub fn sys_chroot(path: *const u8) -> isize {
+    if super::process::sys_geteuid() != 0 {
+        return EPERM;
+    }
+    let task = current_task().unwrap();
+    let token = task.get_user_token();
+    let path = match translated_str(token, path) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    info!("[sys_chroot] path: {}", path);
+    let working_inode = task.fs.lock().working_inode.clone();
+    match working_inode.cd(&path) {
+        Ok(root) => {
+            task.fs.lock().root = Some(root);
+            directory_tree::enable_chroot();
             SUCCESS
         }
         Err(errno) => errno,
@@ -1196,6 +1242,287 @@ pub fn sys_openat(dirfd: usize, path: *const u8, flags: u32, mode: u32) -> isize
     new_fd as isize
 }
 
+/// openat2 的 `struct open_how`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OpenHow {
+    pub flags: u64,
+    pub mode: u64,
+    pub resolve: u64,
+}
+
+/// 第一版 `struct open_how` 的大小，更新的版本只在末尾追加字段
+const OPEN_HOW_SIZE_VER0: usize = 24;
+
+/// 系统调用sys_openat2
+/// # 说明
+/// + openat 的扩展版本，`how.resolve` 限制路径解析的方式，如不越出 dirfd、不经过符号链接
+/// # 参数
+/// + dirfd, path：同 openat
+/// + how：用户态的 open_how 结构体
+/// + size：调用者所用的 open_how 的大小
+/// # 返回值
+/// + 成功：返回新的文件描述符
+/// + 失败：size 小于第一版时返回 EINVAL，超出内核所知的部分不全为 0 时返回 E2BIG；
+///   flags、mode 或 resolve 不合法时返回 EINVAL，RESOLVE_CACHED 时返回 EAGAIN；
+///   越出 dirfd 或跨越文件系统时返回 EXDEV，遇到被禁止的符号链接时返回 ELOOP
+pub fn sys_openat2(dirfd: usize, path: *const u8, how: *const OpenHow, size: usize) -> isize {
+    let task = current_task().unwrap();
+    let token = task.get_user_token();
+    if size < OPEN_HOW_SIZE_VER0 {
+        return EINVAL;
+    }
+    if size > PAGE_SIZE {
+        return E2BIG;
+    }
+    // 更新版本追加的字段必须为 0，内核才能忽略它们
+    if size > OPEN_HOW_SIZE_VER0 {
+        let mut tail = alloc::vec![0u8; size - OPEN_HOW_SIZE_VER0];
+        let tail_ptr = (how as *const u8).wrapping_add(OPEN_HOW_SIZE_VER0);
+        if let Err(errno) = copy_from_user_array(token, tail_ptr, tail.as_mut_ptr(), tail.len()) {
+            return errno;
+        }
+        if tail.iter().any(|&byte| byte != 0) {
+            return E2BIG;
+        }
+    }
+    let mut open_how = OpenHow::default();
+    if let Err(errno) = copy_from_user(token, how, &mut open_how) {
+        return errno;
+    }
+    let flags = match u32::try_from(open_how.flags)
+        .ok()
+        .and_then(OpenFlags::from_bits)
+    {
+        Some(flags) => flags,
+        None => return EINVAL,
+    };
+    let resolve = match ResolveFlags::from_bits(open_how.resolve) {
+        Some(resolve) => resolve,
+        None => return EINVAL,
+    };
+    if resolve.contains(ResolveFlags::RESOLVE_BENEATH | ResolveFlags::RESOLVE_IN_ROOT) {
+        return EINVAL;
+    }
+    // 目录树没有“只查缓存”的解析方式，按 Linux 的约定让调用者去掉该标志重试
+    if resolve.contains(ResolveFlags::RESOLVE_CACHED) {
+        return EAGAIN;
+    }
+    // mode 只在创建文件时有意义
+    if open_how.mode & !0o7777 != 0
+        || (open_how.mode != 0 && !flags.intersects(OpenFlags::O_CREAT | OpenFlags::O_TMPFILE))
+    {
+        return EINVAL;
+    }
+    let path = match translated_str(token, path) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    info!(
+        "[sys_openat2] dirfd: {}, path: {}, flags: {:?}, resolve: {:?}",
+        dirfd as isize, path, flags, resolve
+    );
+    let mut fd_table = task.files.lock();
+    let file_descriptor = match dirfd {
+        AT_FDCWD => task.fs.lock().working_inode.as_ref().clone(),
+        fd => match fd_table.get_ref(fd) {
+            Ok(file_descriptor) => file_descriptor.clone(),
+            Err(errno) => return errno,
+        },
+    };
+    let new_file_descriptor = match file_descriptor.open_resolve(&path, flags, resolve, false) {
+        Ok(file_descriptor) => file_descriptor,
+        Err(errno) => return errno,
+    };
+    match fd_table.insert(new_file_descriptor) {
+        Ok(fd) => fd as isize,
+        Err(errno) => errno,
+    }
+}
+
+/// `struct file_handle` 的头部，`f_handle` 紧随其后
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FileHandleHeader {
+    /// `f_handle` 的字节数
+    pub handle_bytes: u32,
+    pub handle_type: i32,
+}
+
+/// `f_handle` 的内容，对用户不透明
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+struct FileId {
+    /// 文件所在文件系统的编号，与 name_to_handle_at 返回的 mount_id 相同
+    fs_id: u32,
+    /// inode 的版本号，inode 被回收后改变
+    generation: u32,
+    ino: u64,
+}
+
+/// 由 64 位 inode 号与 32 位版本号组成的句柄，沿用 Linux 的 `FILEID_INO64_GEN`
+const FILEID_INO64_GEN: i32 = 0x81;
+/// `handle_bytes` 的上限
+const MAX_HANDLE_SZ: u32 = 128;
+
+bitflags! {
+    pub struct NameToHandleFlags: u32 {
+        const AT_SYMLINK_FOLLOW = 0x400;
+        const AT_EMPTY_PATH = 0x1000;
+    }
+}
+
+/// 系统调用sys_name_to_handle_at
+/// # 说明
+/// + 为路径所指的文件生成文件句柄，文件改名或移动后句柄依然有效，可交给 sys_open_by_handle_at 重新打开
+/// # 参数
+/// + dirfd: 相对路径的起点目录，AT_FDCWD 为当前工作目录
+/// + path: 路径，flags 含 AT_EMPTY_PATH 时可为空串，表示 dirfd 本身
+/// + handle: 用户的 `struct file_handle`，调用前 `handle_bytes` 为 `f_handle` 的容量
+/// + mount_id: 写入文件所在文件系统的编号
+/// + flags: AT_EMPTY_PATH、AT_SYMLINK_FOLLOW
+/// # 返回值
+/// + 成功返回 0
+/// + `f_handle` 容量不足时把所需的字节数写回 `handle_bytes`，返回 EOVERFLOW
+/// + 文件系统不支持文件句柄时返回 EOPNOTSUPP
+pub fn sys_name_to_handle_at(
+    dirfd: usize,
+    path: *const u8,
+    handle: *mut FileHandleHeader,
+    mount_id: *mut i32,
+    flags: u32,
+) -> isize {
+    let task = current_task().unwrap();
+    let token = task.get_user_token();
+    let flags = match NameToHandleFlags::from_bits(flags) {
+        Some(flags) => flags,
+        None => return EINVAL,
+    };
+    let path = match translated_str(token, path) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    let mut header = FileHandleHeader::default();
+    if let Err(errno) = copy_from_user(token, handle, &mut header) {
+        return errno;
+    }
+    if header.handle_bytes > MAX_HANDLE_SZ {
+        return EINVAL;
+    }
+    if path.is_empty() && !flags.contains(NameToHandleFlags::AT_EMPTY_PATH) {
+        return ENOENT;
+    }
+    info!(
+        "[sys_name_to_handle_at] dirfd: {}, path: {}, flags: {:?}",
+        dirfd as isize, path, flags
+    );
+    let file_descriptor = match dirfd {
+        AT_FDCWD => task.fs.lock().working_inode.as_ref().clone(),
+        fd => match task.files.lock().get_ref(fd) {
+            Ok(file_descriptor) => file_descriptor.clone(),
+            Err(errno) => return errno,
+        },
+    };
+    let file = match file_descriptor.open(&path, OpenFlags::O_RDONLY, false) {
+        Ok(file_descriptor) => file_descriptor.file,
+        Err(errno) => return errno,
+    };
+    let (inode, (ino, generation)) = match (file.get_dirtree_node(), file.file_handle()) {
+        (Some(inode), Some(handle)) => (inode, handle),
+        _ => return EOPNOTSUPP,
+    };
+    let handle_bytes = core::mem::size_of::<FileId>() as u32;
+    if header.handle_bytes < handle_bytes {
+        header.handle_bytes = handle_bytes;
+        return match copy_to_user(token, &header, handle) {
+            Ok(()) => EOVERFLOW,
+            Err(errno) => errno,
+        };
+    }
+    let file_id = FileId {
+        fs_id: inode.fs_id() as u32,
+        generation,
+        ino,
+    };
+    let header = FileHandleHeader {
+        handle_bytes,
+        handle_type: FILEID_INO64_GEN,
+    };
+    let file_id_ptr = handle.wrapping_add(1) as *mut FileId;
+    if let Err(errno) = copy_to_user(token, &header, handle)
+        .and_then(|()| copy_to_user(token, &file_id, file_id_ptr))
+        .and_then(|()| copy_to_user(token, &(file_id.fs_id as i32), mount_id))
+    {
+        return errno;
+    }
+    SUCCESS
+}
+
+/// 系统调用sys_open_by_handle_at
+/// # 说明
+/// + 按 sys_name_to_handle_at 生成的文件句柄打开文件
+/// # 参数
+/// + mount_fd: 句柄所属文件系统中的任一文件，AT_FDCWD 为当前工作目录
+/// + handle: 用户的 `struct file_handle`
+/// + flags: 与 openat 相同的打开方式
+/// # 返回值
+/// + 成功返回新的文件描述符
+/// + 句柄不属于 mount_fd 所在的文件系统，或文件已被删除、inode 已被回收时返回 ESTALE
+/// + 需要特权，有效用户不是 root 时返回 EPERM
+pub fn sys_open_by_handle_at(mount_fd: usize, handle: *const FileHandleHeader, flags: u32) -> isize {
+    // 按句柄打开绕过了路径上各级目录的搜索权限与 chroot，Linux 要求 CAP_DAC_READ_SEARCH
+    if super::process::sys_geteuid() != 0 {
+        return EPERM;
+    }
+    let task = current_task().unwrap();
+    let token = task.get_user_token();
+    let mut header = FileHandleHeader::default();
+    if let Err(errno) = copy_from_user(token, handle, &mut header) {
+        return errno;
+    }
+    if header.handle_bytes == 0 || header.handle_bytes > MAX_HANDLE_SZ {
+        return EINVAL;
+    }
+    if header.handle_type != FILEID_INO64_GEN
+        || header.handle_bytes as usize != core::mem::size_of::<FileId>()
+    {
+        return ESTALE;
+    }
+    let mut file_id = FileId::default();
+    if let Err(errno) = copy_from_user(token, handle.wrapping_add(1) as *const FileId, &mut file_id) {
+        return errno;
+    }
+    let flags = match OpenFlags::from_bits(flags) {
+        Some(flags) => flags,
+        None => return EINVAL,
+    };
+    info!(
+        "[sys_open_by_handle_at] mount_fd: {}, handle: {:?}, flags: {:?}",
+        mount_fd as isize, file_id, flags
+    );
+    let file_descriptor = match mount_fd {
+        AT_FDCWD => task.fs.lock().working_inode.as_ref().clone(),
+        fd => match task.files.lock().get_ref(fd) {
+            Ok(file_descriptor) => file_descriptor.clone(),
+            Err(errno) => return errno,
+        },
+    };
+    match file_descriptor.file.get_dirtree_node() {
+        Some(inode) if inode.fs_id() as u32 == file_id.fs_id => {}
+        _ => return ESTALE,
+    }
+    // 找不到缓存的节点时要从磁盘读出 inode，不能持有文件描述符表的锁
+    let new_file_descriptor =
+        match file_descriptor.open_by_handle(file_id.ino, file_id.generation, flags) {
+            Ok(file_descriptor) => file_descriptor,
+            Err(errno) => return errno,
+        };
+    match task.files.lock().insert(new_file_descriptor) {
+        Ok(fd) => fd as isize,
+        Err(errno) => errno,
+    }
+}
+
 pub fn sys_renameat2(
     olddirfd: usize,
     oldpath: *const u8,
@@ -1252,11 +1579,21 @@ pub fn sys_renameat2(
 
 pub fn sys_ioctl(fd: usize, cmd: u32, arg: usize) -> isize {
     let task = current_task().unwrap();
-    let fd_table = task.files.lock();
-    let file_descriptor = match fd_table.get_ref(fd) {
-        Ok(file_descriptor) => file_descriptor,
+    // 回环设备的 LOOP_SET_FD 要查看文件描述符表，调用 ioctl 时不能持有它的锁
+    let file_descriptor = match task.files.lock().get_ref(fd) {
+        Ok(file_descriptor) => file_descriptor.clone(),
         Err(errno) => return errno,
     };
+    if cmd == FICLONE {
+        let src = match task.files.lock().get_ref(arg) {
+            Ok(src) => src.clone(),
+            Err(errno) => return errno,
+        };
+        return match file_descriptor.reflink_from(&src) {
+            Ok(()) => SUCCESS,
+            Err(errno) => errno,
+        };
+    }
     file_descriptor.ioctl(cmd, arg)
 }
 
@@ -1269,6 +1606,145 @@ pub fn sys_ppoll(fds: usize, nfds: usize, tmo_p: usize, sigmask: usize) -> isize
     )
 }
 
+/// 创建 signalfd，`fd` 为 -1 以外的值时改为替换已有 signalfd 的信号集
+/// # 参数
+/// + mask: 用户态的信号集，`sizemask` 须为它的字节数 8
+/// + flags: 只能包含 SFD_NONBLOCK 与 SFD_CLOEXEC，它们与 O_NONBLOCK、O_CLOEXEC 相同
+pub fn sys_signalfd4(fd: isize, mask: usize, sizemask: usize, flags: u32) -> isize {
+    const VALID_FLAGS: OpenFlags =
+        OpenFlags::from_bits_truncate(0o2000000 /* SFD_CLOEXEC */ | 0o4000 /* SFD_NONBLOCK */);
+    let flags = match OpenFlags::from_bits(flags) {
+        Some(flags) if VALID_FLAGS.contains(flags) => flags,
+        _ => {
+            warn!("[sys_signalfd4] invalid flags: {:#x}", flags);
+            return EINVAL;
+        }
+    };
+    if sizemask != core::mem::size_of::<u64>() {
+        return EINVAL;
+    }
+    let task = current_task().unwrap();
+    let mask = match try_get_from_user(task.get_user_token(), mask as *const u64) {
+        Ok(Some(mask)) => crate::task::Signals::from_bits_truncate(mask as _),
+        Ok(None) | Err(_) => return EFAULT,
+    };
+    info!(
+        "[sys_signalfd4] fd: {}, mask: {:?}, flags: {:?}",
+        fd, mask, flags
+    );
+    let mut fd_table = task.files.lock();
+    if fd != -1 {
+        let file_descriptor = match fd_table.get_ref(fd as usize) {
+            Ok(file_descriptor) => file_descriptor,
+            Err(errno) => return errno,
+        };
+        return match file_descriptor.file.downcast_ref::<SignalFd>() {
+            Some(signalfd) => {
+                signalfd.set_mask(mask);
+                fd
+            }
+            None => EINVAL,
+        };
+    }
+    match fd_table.insert(FileDescriptor::new(
+        flags.contains(OpenFlags::O_CLOEXEC),
+        flags.contains(OpenFlags::O_NONBLOCK),
+        Arc::new(SignalFd::new(mask, flags.contains(OpenFlags::O_NONBLOCK))),
+    )) {
+        Ok(fd) => fd as isize,
+        Err(errno) => errno,
+    }
+}
+
+pub fn sys_epoll_create1(flags: u32) -> isize {
+    // 只接受 EPOLL_CLOEXEC，它与 O_CLOEXEC 相同
+    let cloexec = match OpenFlags::from_bits(flags) {
+        Some(OpenFlags::O_CLOEXEC) => true,
+        Some(OpenFlags::O_RDONLY) => false,
+        _ => {
+            warn!("[sys_epoll_create1] invalid flags: {:#x}", flags);
+            return EINVAL;
+        }
+    };
+    let task = current_task().unwrap();
+    let mut fd_table = task.files.lock();
+    match fd_table.insert(FileDescriptor::new(
+        cloexec,
+        false,
+        Arc::new(EpollInstance::new()),
+    )) {
+        Ok(fd) => fd as isize,
+        Err(errno) => errno,
+    }
+}
+
+pub fn sys_epoll_ctl(epfd: usize, op: usize, fd: usize, event: usize) -> isize {
+    let task = current_task().unwrap();
+    let token = task.get_user_token();
+    let fd_table = task.files.lock();
+    let epoll_file = match fd_table.get_ref(epfd) {
+        Ok(file_descriptor) => file_descriptor.file.clone(),
+        Err(errno) => return errno,
+    };
+    let file = match fd_table.get_ref(fd) {
+        Ok(file_descriptor) => file_descriptor.file.clone(),
+        Err(errno) => return errno,
+    };
+    drop(fd_table);
+    let epoll = match epoll_file.downcast_ref::<EpollInstance>() {
+        Some(epoll) => epoll,
+        None => return EINVAL,
+    };
+    if epfd == fd {
+        return EINVAL;
+    }
+    let event = if op == EPOLL_CTL_DEL {
+        EpollEvent::default()
+    } else {
+        match try_get_from_user(token, event as *const EpollEvent) {
+            Ok(Some(event)) => event,
+            Ok(None) | Err(_) => return EFAULT,
+        }
+    };
+    info!(
+        "[sys_epoll_ctl] epfd: {}, op: {}, fd: {}, event: {:?}",
+        epfd, op, fd, event
+    );
+    match epoll.control(op, fd, &file, event) {
+        Ok(()) => SUCCESS,
+        Err(errno) => errno,
+    }
+}
+
+pub fn sys_epoll_pwait(
+    epfd: usize,
+    events: usize,
+    maxevents: usize,
+    timeout: i32,
+    sigmask: usize,
+) -> isize {
+    let task = current_task().unwrap();
+    let epoll_file = match task.files.lock().get_ref(epfd) {
+        Ok(file_descriptor) => file_descriptor.file.clone(),
+        Err(errno) => return errno,
+    };
+    drop(task);
+    let epoll = match epoll_file.downcast_ref::<EpollInstance>() {
+        Some(epoll) => epoll,
+        None => return EINVAL,
+    };
+    if maxevents as i32 <= 0 {
+        return EINVAL;
+    }
+    epoll_pwait(
+        epoll,
+        events as *mut EpollEvent,
+        maxevents as i32 as usize,
+        timeout as isize,
+        sigmask as *const crate::task::Signals,
+    )
+}
+
 pub fn sys_mkdirat(dirfd: usize, path: *const u8, mode: u32) -> isize {
     let task = current_task().unwrap();
     let token = task.get_user_token();
@@ -1364,8 +1840,14 @@ pub fn sys_umount2(target: *const u8, flags: u32) -> isize {
         None => return EINVAL,
     };
     info!("[sys_umount2] target: {}, flags: {:?}", target, flags);
-    warn!("[sys_umount2] fake implementation!");
-    SUCCESS
+    let mount_point = match mount_point(&target) {
+        Ok(mount_point) => mount_point,
+        Err(errno) => return errno,
+    };
+    match current_mnt_ns().unmount(&mount_point) {
+        Ok(_) => SUCCESS,
+        Err(errno) => errno,
+    }
 }
 
 bitflags! {
@@ -1402,6 +1884,66 @@ bitflags! {
     }
 }
 
+impl MountFlags {
+    /// 挂载点属性对应的 `MOUNT_ATTR_*`
+    fn mount_attr(&self) -> u64 {
+        [
+            (MountFlags::MS_RDONLY, MOUNT_ATTR_RDONLY),
+            (MountFlags::MS_NOSUID, MOUNT_ATTR_NOSUID),
+            (MountFlags::MS_NODEV, MOUNT_ATTR_NODEV),
+            (MountFlags::MS_NOEXEC, MOUNT_ATTR_NOEXEC),
+            (MountFlags::MS_NOATIME, MOUNT_ATTR_NOATIME),
+            (MountFlags::MS_STRICTATIME, MOUNT_ATTR_STRICTATIME),
+            (MountFlags::MS_NODIRATIME, MOUNT_ATTR_NODIRATIME),
+        ]
+        .iter()
+        .filter(|(flag, _)| self.contains(*flag))
+        .fold(0, |attr, (_, bit)| attr | bit)
+    }
+}
+
+/// 以工作目录为起点打开目录 `path`，返回其目录树节点
+fn open_dir_node(path: &str) -> Result<Arc<DirectoryTreeNode>, isize> {
+    let working_inode = current_task().unwrap().fs.lock().working_inode.clone();
+    working_inode
+        .open(path, OpenFlags::O_DIRECTORY | OpenFlags::O_RDONLY, false)?
+        .file
+        .get_dirtree_node()
+        .ok_or(ENOENT)
+}
+
+/// 挂载点的绝对路径，挂载点须是已存在的目录
+/// # 说明
+/// + 由父目录的路径加上最后一个组件得到：挂载点上已有绑定挂载时，打开它得到的是绑定的源目录
+fn mount_point(target: &str) -> Result<String, isize> {
+    let node = open_dir_node(target)?;
+    let trimmed = target.trim_end_matches('/');
+    let (parent, name) = match trimmed.rsplit_once('/') {
+        Some(("", name)) => ("/", name),
+        Some((parent, name)) => (parent, name),
+        None => (".", trimmed),
+    };
+    if name.is_empty() || name == "." || name == ".." {
+        return Ok(node.get_cwd());
+    }
+    let parent = open_dir_node(parent)?.get_cwd();
+    Ok(if parent == "/" {
+        format!("/{}", name)
+    } else {
+        format!("{}/{}", parent, name)
+    })
+}
+
+/// 系统调用sys_mount
+/// # 说明
+/// + 只在挂载表中记录新的文件系统挂载，挂载点下看到的仍是原来的文件；
+///   source 是已设置好的块设备文件（回环设备、/dev/dm-0 或磁盘分区如 /dev/vda2）时，挂载点下看到的是设备上的 FAT32 文件系统，
+///   filesystemtype 须是 vfat；设备只读时须带 MS_RDONLY，否则返回 EACCES
+/// + 含 MS_BIND 时把目录 source 绑定挂载到 target 上，两个路径下是同一组文件；source 须是目录
+/// + 含 MS_SHARED 等传播类型时只改变挂载点上最近一次挂载的传播类型，含 MS_REMOUNT 时只改变其属性，
+///   这两种情况下 source 与 filesystemtype 可以为空
+/// + 挂载到共享的挂载之下时，同一对等组中的其他挂载与其从属挂载的对应位置上也会出现这次挂载，
+///   卸载时一并卸载；传播只发生在同一挂载命名空间之内
 pub fn sys_mount(
     source: *const u8,
     target: *const u8,
@@ -1409,32 +1951,364 @@ pub fn sys_mount(
     mountflags: usize,
     data: *const u8,
 ) -> isize {
-    if source.is_null() || target.is_null() || filesystemtype.is_null() {
+    if target.is_null() {
         return EINVAL;
     }
     let token = current_user_token();
-    let source = match translated_str(token, source) {
-        Ok(source) => source,
-        Err(errno) => return errno,
-    };
-    let target = match translated_str(token, target) {
-        Ok(target) => target,
-        Err(errno) => return errno,
-    };
-    let filesystemtype = match translated_str(token, filesystemtype) {
-        Ok(filesystemtype) => filesystemtype,
-        Err(errno) => return errno,
+    let string = |ptr: *const u8| {
+        if ptr.is_null() {
+            Ok(String::new())
+        } else {
+            translated_str(token, ptr)
+        }
     };
+    let (source, target, filesystemtype) =
+        match (string(source), string(target), string(filesystemtype)) {
+            (Ok(source), Ok(target), Ok(filesystemtype)) => (source, target, filesystemtype),
+            (Err(errno), _, _) | (_, Err(errno), _) | (_, _, Err(errno)) => return errno,
+        };
     // infallible
     let mountflags = MountFlags::from_bits(mountflags).unwrap();
     info!(
         "[sys_mount] source: {}, target: {}, filesystemtype: {}, mountflags: {:?}, data: {:?}",
         source, target, filesystemtype, mountflags, data
     );
-    warn!("[sys_mount] fake implementation!");
+    let mount_point = match mount_point(&target) {
+        Ok(mount_point) => mount_point,
+        Err(errno) => return errno,
+    };
+    let propagation = mountflags
+        & (MountFlags::MS_SHARED
+            | MountFlags::MS_PRIVATE
+            | MountFlags::MS_SLAVE
+            | MountFlags::MS_UNBINDABLE);
+    let ns = current_mnt_ns();
+    let mut mounts = ns.mounts.lock();
+    if !propagation.is_empty() {
+        if propagation.bits().count_ones() != 1 {
+            return EINVAL;
+        }
+        return match mounts.set_propagation(&mount_point, propagation.bits() as u64) {
+            Ok(()) => SUCCESS,
+            Err(errno) => errno,
+        };
+    }
+    if mountflags.contains(MountFlags::MS_REMOUNT) {
+        return match mounts.topmost_mut(&mount_point) {
+            Some(mount) => {
+                mount.attr = mountflags.mount_attr();
+                SUCCESS
+            }
+            None => EINVAL,
+        };
+    }
+    if mountflags.contains(MountFlags::MS_BIND) {
+        drop(mounts);
+        let (source, target) = match (open_dir_node(&source), open_dir_node(&target)) {
+            (Ok(source), Ok(target)) => (source, target),
+            (Err(errno), _) | (_, Err(errno)) => return errno,
+        };
+        let source_path = source.get_cwd();
+        return match ns.bind(source, &source_path, target, &mount_point, mountflags.mount_attr()) {
+            Ok(_) => SUCCESS,
+            Err(errno) => errno,
+        };
+    }
+    if filesystemtype.is_empty() {
+        return EINVAL;
+    }
+    let working_inode = current_task().unwrap().fs.lock().working_inode.clone();
+    let block_device = working_inode
+        .open(&source, OpenFlags::O_RDONLY, false)
+        .map_or(Err(ENOTBLK), |source| source.file.block_device());
+    if !matches!(block_device, Err(ENOTBLK)) {
+        drop(mounts);
+        let block_device = match block_device {
+            Ok(block_device) => block_device,
+            Err(errno) => return errno,
+        };
+        if filesystemtype != "vfat" {
+            return EINVAL;
+        }
+        let target = match open_dir_node(&target) {
+            Ok(target) => target,
+            Err(errno) => return errno,
+        };
+        let read_only = mountflags.contains(MountFlags::MS_RDONLY);
+        let root = match open_fs_root(block_device, &target, read_only) {
+            Ok(root) => root,
+            Err(errno) => return errno,
+        };
+        ns.mount_fs(
+            root,
+            &source,
+            target,
+            &mount_point,
+            &filesystemtype,
+            mountflags.mount_attr(),
+        );
+        return SUCCESS;
+    }
+    mounts.add(&source, &mount_point, &filesystemtype, mountflags.mount_attr());
     SUCCESS
 }
 
+/// 系统调用 sys_pivot_root
+/// # 说明
+/// + 把调用者所在挂载命名空间的根目录换成 new_root，原来的根目录移到 put_old 上，
+///   new_root 必须是挂载点，put_old 必须是 new_root 或其下的目录
+/// + 调用者的工作目录是原来的根目录时改为新的根目录，其他任务的工作目录不变
+/// # 错误
+/// + new_root 已经是根目录时返回 EBUSY，new_root 不是挂载点或 put_old 不在其下时返回 EINVAL
+pub fn sys_pivot_root(new_root: *const u8, put_old: *const u8) -> isize {
+    let task = current_task().unwrap();
+    let token = task.get_user_token();
+    let new_root = match translated_str(token, new_root) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    let put_old = match translated_str(token, put_old) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    info!("[sys_pivot_root] new_root: {}, put_old: {}", new_root, put_old);
+    let (new_root, put_old) = match (open_dir_node(&new_root), open_dir_node(&put_old)) {
+        (Ok(new_root), Ok(put_old)) => (new_root, put_old),
+        (Err(errno), _) | (_, Err(errno)) => return errno,
+    };
+    let ns = current_mnt_ns();
+    if ns.is_root(&new_root) {
+        return EBUSY;
+    }
+    let old_root = ns.root();
+    let (new_root_path, put_old_path) = (new_root.get_cwd(), put_old.get_cwd());
+    if let Err(errno) = ns.pivot_root(new_root, &new_root_path, put_old, &put_old_path) {
+        return errno;
+    }
+    let working_inode = task.fs.lock().working_inode.clone();
+    let in_old_root = working_inode
+        .file
+        .get_dirtree_node()
+        .map_or(false, |cwd| Arc::ptr_eq(&cwd, &old_root));
+    if in_old_root {
+        if let Ok(new_working_inode) = working_inode.cd("/") {
+            task.fs.lock().working_inode = new_working_inode;
+        }
+    }
+    SUCCESS
+}
+
+/// statmount 与 listmount 的 `struct mnt_id_req`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MntIdReq {
+    /// 结构体的大小，用于区分版本
+    pub size: u32,
+    pub spare: u32,
+    pub mnt_id: u64,
+    /// statmount 中是要返回的字段 `STATMOUNT_*`，listmount 中是上次返回的最后一个挂载 id
+    pub param: u64,
+}
+
+/// 第一版 `struct mnt_id_req` 的大小
+const MNT_ID_REQ_SIZE_VER0: u32 = 24;
+/// listmount 列出全部挂载
+const LSMT_ROOT: u64 = u64::MAX;
+
+bitflags! {
+    /// statmount 要返回的字段
+    pub struct StatmountMask: u64 {
+        /// `sb_dev_*`、`sb_magic`、`sb_flags`
+        const STATMOUNT_SB_BASIC = 0x1;
+        /// `mnt_id`、`mnt_parent_id`、`mnt_attr`、`mnt_propagation` 等
+        const STATMOUNT_MNT_BASIC = 0x2;
+        const STATMOUNT_PROPAGATE_FROM = 0x4;
+        const STATMOUNT_MNT_ROOT = 0x8;
+        const STATMOUNT_MNT_POINT = 0x10;
+        const STATMOUNT_FS_TYPE = 0x20;
+    }
+}
+
+/// statmount 的 `struct statmount`
+/// # 说明
+/// + 字符串依次存放在结构体之后，`mnt_root`、`mnt_point`、`fs_type` 是它们相对结构体末尾的偏移
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Statmount {
+    /// 写入的总字节数，含字符串
+    pub size: u32,
+    pub spare1: u32,
+    /// 实际填写了的字段
+    pub mask: u64,
+    pub sb_dev_major: u32,
+    pub sb_dev_minor: u32,
+    pub sb_magic: u64,
+    pub sb_flags: u32,
+    pub fs_type: u32,
+    pub mnt_id: u64,
+    pub mnt_parent_id: u64,
+    pub mnt_id_old: u32,
+    pub mnt_parent_id_old: u32,
+    pub mnt_attr: u64,
+    pub mnt_propagation: u64,
+    pub mnt_peer_group: u64,
+    pub mnt_master: u64,
+    pub propagate_from: u64,
+    pub mnt_root: u32,
+    pub mnt_point: u32,
+    pub spare2: [u64; 50],
+}
+
+/// 读取用户的 `struct mnt_id_req` 并检查版本
+fn get_mnt_id_req(token: usize, req: *const MntIdReq) -> Result<MntIdReq, isize> {
+    let mut mnt_id_req = MntIdReq::default();
+    copy_from_user(token, req, &mut mnt_id_req)?;
+    if mnt_id_req.size < MNT_ID_REQ_SIZE_VER0 || mnt_id_req.spare != 0 {
+        return Err(EINVAL);
+    }
+    Ok(mnt_id_req)
+}
+
+/// 系统调用sys_statmount
+/// # 说明
+/// + 返回挂载 `req.mnt_id` 的信息，`req.param` 选择要返回的字段
+/// # 返回值
+/// + 成功返回 0
+/// + 挂载不存在时返回 ENOENT
+/// + `bufsize` 放不下结构体与所选字符串时返回 EOVERFLOW
+pub fn sys_statmount(req: *const MntIdReq, buf: *mut Statmount, bufsize: usize, flags: u32) -> isize {
+    let token = current_user_token();
+    if flags != 0 {
+        return EINVAL;
+    }
+    let req = match get_mnt_id_req(token, req) {
+        Ok(req) => req,
+        Err(errno) => return errno,
+    };
+    let mask = StatmountMask::from_bits_truncate(req.param);
+    let ns = current_mnt_ns();
+    let mounts = ns.mounts.lock();
+    let mount = match mounts.get(req.mnt_id) {
+        Some(mount) => mount,
+        None => return ENOENT,
+    };
+    let mut statmount = Statmount {
+        size: 0,
+        spare1: 0,
+        mask: mask.bits(),
+        sb_dev_major: 0,
+        sb_dev_minor: 0,
+        sb_magic: 0,
+        sb_flags: 0,
+        fs_type: 0,
+        mnt_id: 0,
+        mnt_parent_id: 0,
+        mnt_id_old: 0,
+        mnt_parent_id_old: 0,
+        mnt_attr: 0,
+        mnt_propagation: 0,
+        mnt_peer_group: 0,
+        mnt_master: 0,
+        propagate_from: 0,
+        mnt_root: 0,
+        mnt_point: 0,
+        spare2: [0; 50],
+    };
+    if mask.contains(StatmountMask::STATMOUNT_SB_BASIC) {
+        // 根文件系统所在的块设备与 stat 中一致，其余文件系统用挂载 id 作次设备号
+        (statmount.sb_dev_major, statmount.sb_dev_minor) = match mount.id {
+            ROOT_MOUNT_ID => (8, 0),
+            id => (0, id as u32),
+        };
+        statmount.sb_magic = mount.magic();
+        statmount.sb_flags = (mount.attr & MOUNT_ATTR_RDONLY) as u32;
+    }
+    if mask.contains(StatmountMask::STATMOUNT_MNT_BASIC) {
+        statmount.mnt_id = mount.id;
+        statmount.mnt_parent_id = mount.parent_id;
+        statmount.mnt_id_old = mount.id as u32;
+        statmount.mnt_parent_id_old = mount.parent_id as u32;
+        statmount.mnt_attr = mount.attr;
+        statmount.mnt_propagation = mount.propagation;
+        statmount.mnt_peer_group = mount.peer_group;
+        statmount.mnt_master = mount.master;
+    }
+    let mut strings = Vec::new();
+    let mut push_string = |string: &str| {
+        let offset = strings.len() as u32;
+        strings.extend_from_slice(string.as_bytes());
+        strings.push(0);
+        offset
+    };
+    if mask.contains(StatmountMask::STATMOUNT_MNT_ROOT) {
+        statmount.mnt_root = push_string(&mount.root);
+    }
+    if mask.contains(StatmountMask::STATMOUNT_MNT_POINT) {
+        statmount.mnt_point = push_string(&mount.mount_point);
+    }
+    if mask.contains(StatmountMask::STATMOUNT_FS_TYPE) {
+        statmount.fs_type = push_string(&mount.fs_type);
+    }
+    drop(mounts);
+    let header_size = core::mem::size_of::<Statmount>();
+    if bufsize < header_size {
+        return EINVAL;
+    }
+    if bufsize < header_size + strings.len() {
+        return EOVERFLOW;
+    }
+    statmount.size = (header_size + strings.len()) as u32;
+    if let Err(errno) = copy_to_user(token, &statmount, buf) {
+        return errno;
+    }
+    if !strings.is_empty() {
+        let str_ptr = buf.wrapping_add(1) as *mut u8;
+        if let Err(errno) = copy_to_user_array(token, strings.as_ptr(), str_ptr, strings.len()) {
+            return errno;
+        }
+    }
+    SUCCESS
+}
+
+/// 系统调用sys_listmount
+/// # 说明
+/// + 按 id 从小到大列出挂载 `req.mnt_id` 之下的所有挂载，`req.mnt_id` 为 LSMT_ROOT 时列出全部挂载
+/// + `req.param` 为上次返回的最后一个 id，只列出比它大的，首次调用时为 0
+/// # 返回值
+/// + 写入 `mnt_ids` 的 id 个数，至多 `nr_mnt_ids` 个
+pub fn sys_listmount(
+    req: *const MntIdReq,
+    mnt_ids: *mut u64,
+    nr_mnt_ids: usize,
+    flags: u32,
+) -> isize {
+    let token = current_user_token();
+    if flags != 0 {
+        return EINVAL;
+    }
+    let req = match get_mnt_id_req(token, req) {
+        Ok(req) => req,
+        Err(errno) => return errno,
+    };
+    let ns = current_mnt_ns();
+    let mounts = ns.mounts.lock();
+    let mut ids = match req.mnt_id {
+        LSMT_ROOT => mounts.iter().map(|mount| mount.id).collect(),
+        id if mounts.get(id).is_some() => mounts.descendants(id),
+        _ => return ENOENT,
+    };
+    drop(mounts);
+    ids.sort_unstable();
+    ids.retain(|&id| id > req.param);
+    ids.truncate(nr_mnt_ids);
+    if !ids.is_empty() {
+        if let Err(errno) = copy_to_user_array(token, ids.as_ptr(), mnt_ids, ids.len()) {
+            return errno;
+        }
+    }
+    ids.len() as isize
+}
+
 bitflags! {
     pub struct UtimensatFlags: u32 {
         const AT_SYMLINK_NOFOLLOW = 0x100;
@@ -1755,18 +2629,27 @@ pub fn sys_msync(addr: usize, length: usize, flags: u32) -> isize {
         Some(flags) => flags,
         None => return EINVAL,
     };
+    if flags.contains(MsyncFlags::MS_ASYNC | MsyncFlags::MS_SYNC) {
+        return EINVAL;
+    }
     let task = current_task().unwrap();
-    if !task
-        .vm
-        .lock()
-        .contains_valid_buffer(addr, length, MapPermission::empty())
-    {
+    let vm = task.vm.lock();
+    if !vm.contains_valid_buffer(addr, length, MapPermission::empty()) {
         return ENOMEM;
     }
+    let files = vm.shared_files(addr, length);
+    // 写回可能睡眠，先释放地址空间的锁
+    drop(vm);
     info!(
         "[sys_msync] addr: {:X}, length: {:X}, flags: {:?}",
-        addr, flags, flags
+        addr, length, flags
     );
+    // 共享映射写入的页已经在页缓存中，其他进程立即可见；MS_SYNC 时写回到磁盘
+    if flags.contains(MsyncFlags::MS_SYNC) {
+        for file in files {
+            file.fsync();
+        }
+    }
     SUCCESS
 }
 
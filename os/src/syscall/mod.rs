@@ -14,7 +14,7 @@
 //! | Memory | mmap, munmap, brk, mprotect | `process` |
 //! | Network | socket, bind, connect | `net` |
 //! | Signals | sigaction, kill, sigreturn | `process` |
-//! | Time | clock_gettime, nanosleep | `process` |
+//! | Time | clock_gettime, clock_settime, nanosleep, timer_create | `process` |
 //!
 //! # Architecture
 //!
@@ -25,6 +25,7 @@
 #[macro_use]
 mod syscall_macro;
 
+pub mod audit;
 pub mod context;
 pub mod dispatch;
 pub mod errno;
@@ -34,6 +35,7 @@ mod net;
 mod process;
 mod syscall_id;
 
+use crate::task::ptrace;
 use core::convert::TryFrom;
 use fs::*;
 use log::{error, info};
@@ -47,8 +49,10 @@ use syscall_id::*;
 /// Returns a human-readable name for debugging and logging
 pub fn syscall_name(id: usize) -> &'static str {
     match id {
+        SYSCALL_EPOLL_CREATE1 => "epoll_create1",
+        SYSCALL_EPOLL_CTL => "epoll_ctl",
+        SYSCALL_EPOLL_PWAIT => "epoll_pwait",
         SYSCALL_DUP => "dup",
-        SYSCALL_DUP2 => "dup2",
         SYSCALL_DUP3 => "dup3",
         SYSCALL_OPEN => "open",
         SYSCALL_GET_TIME => "get_time",
@@ -60,10 +64,17 @@ pub fn syscall_name(id: usize) -> &'static str {
         SYSCALL_LINKAT => "linkat",
         SYSCALL_UMOUNT2 => "umount2",
         SYSCALL_MOUNT => "mount",
+        SYSCALL_PIVOT_ROOT => "pivot_root",
+        SYSCALL_STATMOUNT => "statmount",
+        SYSCALL_LISTMOUNT => "listmount",
         SYSCALL_FACCESSAT => "faccessat",
         SYSCALL_CHDIR => "chdir",
+        SYSCALL_CHROOT => "chroot",
         SYSCALL_FCHMODAT => "fchmodat",
         SYSCALL_OPENAT => "openat",
+        SYSCALL_OPENAT2 => "openat2",
+        SYSCALL_NAME_TO_HANDLE_AT => "name_to_handle_at",
+        SYSCALL_OPEN_BY_HANDLE_AT => "open_by_handle_at",
         SYSCALL_CLOSE => "close",
         SYSCALL_PIPE2 => "pipe2",
         SYSCALL_GETDENTS64 => "getdents64",
@@ -78,6 +89,7 @@ pub fn syscall_name(id: usize) -> &'static str {
         SYSCALL_SPLICE => "splice",
         SYSCALL_PSELECT6 => "pselect6",
         SYSCALL_PPOLL => "ppoll",
+        SYSCALL_SIGNALFD4 => "signalfd4",
         SYSCALL_READLINKAT => "readlinkat",
         SYSCALL_FSTATAT => "fstatat",
         SYSCALL_FSTAT => "fstat",
@@ -85,19 +97,29 @@ pub fn syscall_name(id: usize) -> &'static str {
         SYSCALL_FTRUNCATE => "ftruncate",
         SYSCALL_FSYNC => "fsync",
         SYSCALL_UTIMENSAT => "utimensat",
+        SYSCALL_ACCT => "acct",
         SYSCALL_EXIT => "exit",
         SYSCALL_EXIT_GROUP => "exit_GROUP",
         SYSCALL_SET_TID_ADDRESS => "set_tid_address",
+        SYSCALL_UNSHARE => "unshare",
         SYSCALL_FUTEX => "futex",
         SYSCALL_SET_ROBUST_LIST => "set_robust_list",
         SYSCALL_GET_ROBUST_LIST => "get_robust_list",
         SYSCALL_NANOSLEEP => "nanosleep",
         SYSCALL_GETITIMER => "getitimer",
         SYSCALL_SETITIMER => "setitimer",
+        SYSCALL_TIMER_CREATE => "timer_create",
+        SYSCALL_TIMER_GETTIME => "timer_gettime",
+        SYSCALL_TIMER_GETOVERRUN => "timer_getoverrun",
+        SYSCALL_TIMER_SETTIME => "timer_settime",
+        SYSCALL_TIMER_DELETE => "timer_delete",
+        SYSCALL_CLOCK_SETTIME => "clock_settime",
         SYSCALL_CLOCK_GETTIME => "clock_gettime",
         SYSCALL_CLOCK_NANOSLEEP => "clock_nanosleep",
         SYSCALL_SYSLOG => "syslog",
+        SYSCALL_PTRACE => "ptrace",
         SYSCALL_YIELD => "yield",
+        SYSCALL_SCHED_YIELD_TO => "sched_yield_to",
         SYSCALL_KILL => "kill",
         SYSCALL_TKILL => "tkill",
         SYSCALL_TGKILL => "tgkill",
@@ -105,13 +127,16 @@ pub fn syscall_name(id: usize) -> &'static str {
         SYSCALL_SIGPROCMASK => "sigprocmask",
         SYSCALL_SIGTIMEDWAIT => "sigtimedwait",
         SYSCALL_SIGRETURN => "sigreturn",
+        SYSCALL_REBOOT => "reboot",
         SYSCALL_TIMES => "times",
         SYSCALL_SETPGID => "setpgid",
         SYSCALL_GETPGID => "getpgid",
+        SYSCALL_GETSID => "getsid",
         SYSCALL_SETSID => "setsid",
         SYSCALL_UNAME => "uname",
         SYSCALL_GETRUSAGE => "getrusage",
         SYSCALL_UMASK => "umask",
+        SYSCALL_GETCPU => "getcpu",
         SYSCALL_GET_TIME_OF_DAY => "get_time_of_day",
         SYSCALL_GETPID => "getpid",
         SYSCALL_GETPPID => "getppid",
@@ -140,6 +165,7 @@ pub fn syscall_name(id: usize) -> &'static str {
         SYSCALL_MMAP => "mmap",
         SYSCALL_MPROTECT => "mprotect",
         SYSCALL_MSYNC => "msync",
+        SYSCALL_MINCORE => "mincore",
         SYSCALL_WAIT4 => "wait4",
         SYSCALL_PRLIMIT => "prlimit",
         SYSCALL_RENAMEAT2 => "renameat2",
@@ -152,6 +178,7 @@ pub fn syscall_name(id: usize) -> &'static str {
         SYSCALL_LS => "ls",
         SYSCALL_SHUTDOWN => "shutdown",
         SYSCALL_CLEAR => "clear",
+        SYSCALL_WATCHPOINT => "watchpoint",
         _ => "unknown",
     }
 }
@@ -215,6 +242,8 @@ fn handle_unsupported_syscall(id: usize, args: &[usize; 6]) -> isize {
 /// 2. Dispatches to the appropriate handler via the function pointer table
 /// 3. Handles unsupported syscalls with proper error reporting
 /// 4. Optionally logs the syscall exit
+/// 5. Records watched syscalls in the audit log, see [`audit`]
+/// 6. Stops a traced task at syscall entry and exit after `PTRACE_SYSCALL`
 ///
 /// # Arguments
 /// * `syscall_id` - The syscall number from user space
@@ -230,22 +259,77 @@ pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
     if should_log {
         log_syscall_entry(name, syscall_id, &args);
     }
+    // Arguments are read before the call, execve replaces the address space
+    let audit_detail = if audit::is_watched(syscall_id) {
+        audit::describe(syscall_id, &args)
+    } else {
+        None
+    };
     
+    ptrace::syscall_entry_stop();
     let ret = match dispatch::dispatch_syscall(syscall_id, args) {
         Some((_name, result)) => result,
         None => handle_unsupported_syscall(syscall_id, &args),
     };
+    ptrace::syscall_exit_stop(ret);
     
     if should_log {
         log_syscall_exit(name, syscall_id, ret);
     }
+    if let Some(detail) = audit_detail {
+        audit::log(syscall_id, &detail, ret);
+    }
     
     ret
 }
 
-/// Random number generation syscall (placeholder implementation)
+bitflags! {
+    pub struct GetRandomFlags: u32 {
+        /// 熵池尚未初始化完成时返回 EAGAIN 而不是等待
+        const GRND_NONBLOCK = 0x1;
+        /// 取自阻塞的随机源，这里与默认的随机源是同一个熵池
+        const GRND_RANDOM = 0x2;
+        /// 熵池尚未初始化完成时也立即返回
+        const GRND_INSECURE = 0x4;
+    }
+}
+
+/// Fill `buflen` bytes at `buf` from the kernel entropy pool
 ///
-/// TODO: Implement proper random number generation with entropy pool
-pub fn sys_getrandom(_buf: usize, _buflen: usize, _flags: u32) -> isize {
-    0
+/// Until the pool has collected enough entropy the call waits, or fails with
+/// `EAGAIN` under `GRND_NONBLOCK` and `EINTR` when a signal arrives;
+/// `GRND_INSECURE` returns at once. Returns the number of bytes written.
+pub fn sys_getrandom(buf: usize, buflen: usize, flags: u32) -> isize {
+    use crate::mm::{translated_byte_buffer, UserBuffer};
+    use crate::task::{current_task, suspend_current_and_run_next};
+    use crate::utils::random::{fill_user, pool_ready};
+    use errno::{EAGAIN, EINTR, EINVAL};
+
+    let flags = match GetRandomFlags::from_bits(flags) {
+        Some(flags) => flags,
+        None => return EINVAL,
+    };
+    if flags.contains(GetRandomFlags::GRND_INSECURE | GetRandomFlags::GRND_RANDOM) {
+        return EINVAL;
+    }
+    if buflen == 0 {
+        return 0;
+    }
+    let task = current_task().unwrap();
+    while !flags.contains(GetRandomFlags::GRND_INSECURE) && !pool_ready() {
+        if flags.contains(GetRandomFlags::GRND_NONBLOCK) {
+            return EAGAIN;
+        }
+        if !task.acquire_inner_lock().sigpending.is_empty() {
+            return EINTR;
+        }
+        suspend_current_and_run_next();
+    }
+    let mut user_buf = match translated_byte_buffer(task.get_user_token(), buf as *const u8, buflen)
+    {
+        Ok(buffers) => UserBuffer::new(buffers),
+        Err(errno) => return errno,
+    };
+    fill_user(&mut user_buf);
+    user_buf.len() as isize
 }
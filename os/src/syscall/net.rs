@@ -1,15 +1,18 @@
-use crate::mm::{translated_ref, translated_refmut};
+use crate::mm::{
+    copy_to_user_array, translated_byte_buffer, translated_ref, translated_refmut, UserBuffer,
+};
 use crate::{
-    config::PAGE_SIZE, fs::FileDescriptor, net::{
-        address::{self, SocketAddrv4},
-        make_unix_socket_pair, Socket, SocketType, TCP_MSS,
+    fs::FileDescriptor, net::{
+        address,
+        make_unix_socket_pair, Socket, SocketType, UnixAddr, UnixSocket, AF_UNIX, TCP_MSS,
     }, 
     task::current_task,
+    utils::error::SyscallRet,
 };
 use super::errno::*;
 
+use alloc::sync::Arc;
 use log::info;
-use smoltcp::wire::IpListenEndpoint;
 /// level
 const SOL_SOCKET: u32 = 1;
 const SOL_TCP: u32 = 6;
@@ -23,6 +26,21 @@ const SO_SNDBUF: u32 = 7;
 const SO_RCVBUF: u32 = 8;
 const SO_KEEPALIVE: u32 = 9;
 
+/// `sockfd` 是 AF_UNIX 套接字时返回它，地址与连接由 [`UnixSocket`] 处理
+fn unix_socket(sockfd: u32) -> Option<Arc<UnixSocket>> {
+    let task = current_task().unwrap();
+    let file = task.files.lock().get_ref(sockfd as usize).ok()?.file.clone();
+    file.downcast_arc::<UnixSocket>().ok()
+}
+
+/// 把 inet 套接字操作的结果转换为系统调用的返回值
+fn socket_ret(ret: SyscallRet) -> isize {
+    match ret {
+        Ok(value) => value as isize,
+        Err(err) => -(err as isize),
+    }
+}
+
 pub fn sys_socket(domain: u32, socket_type: u32, protocol: u32) -> isize {
     info!(
         "[sys_socket] domain: {}, type: {}, protocol: {}",
@@ -43,15 +61,24 @@ pub fn sys_socket(domain: u32, socket_type: u32, protocol: u32) -> isize {
 
 pub fn sys_bind(sockfd: u32, addr: usize, addrlen: u32) -> isize {
     let addr_buf = trans_ref!(addr, addrlen);
+    if let Some(socket) = unix_socket(sockfd) {
+        return match UnixAddr::parse(addr_buf).and_then(|addr| socket.bind(addr)) {
+            Ok(()) => SUCCESS,
+            Err(errno) => errno,
+        };
+    }
     let socket = get_socket!(sockfd);
-    let endpoint = address::listen_endpoint(addr_buf).unwrap();
+    let endpoint = match address::listen_endpoint(addr_buf) {
+        Ok(endpoint) => endpoint,
+        Err(err) => return -(err as isize),
+    };
     match socket.socket_type() {
-        SocketType::SOCK_STREAM => socket.bind(endpoint).unwrap() as isize,
+        SocketType::SOCK_STREAM => socket_ret(socket.bind(endpoint)),
         SocketType::SOCK_DGRAM => {
             let res = current_task().unwrap().socket_table.lock().can_bind(endpoint);
             if res.is_none(){
                 info!("[sys_bind] not find port exist");
-                socket.bind(endpoint).unwrap() as isize
+                socket_ret(socket.bind(endpoint))
             }else {
                 let (_,sock) = res.unwrap();
                 current_task().unwrap().socket_table.lock().insert(sockfd as usize, sock.clone());
@@ -63,30 +90,67 @@ pub fn sys_bind(sockfd: u32, addr: usize, addrlen: u32) -> isize {
     }
 }
 
-pub fn sys_listen(sockfd: u32, _backlog: u32) -> isize {
+pub fn sys_listen(sockfd: u32, backlog: u32) -> isize {
+    if let Some(socket) = unix_socket(sockfd) {
+        return match socket.listen(backlog as usize) {
+            Ok(()) => SUCCESS,
+            Err(errno) => errno,
+        };
+    }
     let socket = get_socket!(sockfd);
-    socket.listen().unwrap() as isize
+    socket_ret(socket.listen())
 }
 
 pub  fn sys_accept(sockfd: u32, addr: usize, addrlen: usize) -> isize {
+    if let Some(socket) = unix_socket(sockfd) {
+        let server = match socket.accept() {
+            Ok(server) => server,
+            Err(errno) => return errno,
+        };
+        if let Err(errno) = server.peer_addr().and_then(|peer| peer.fill(addr, addrlen)) {
+            return errno;
+        }
+        return match server.install(false) {
+            Ok(fd) => fd as isize,
+            Err(errno) => errno,
+        };
+    }
     let socket = get_socket!(sockfd);
-    socket.accept(sockfd, addr, addrlen).unwrap() as isize
+    socket_ret(socket.accept(sockfd, addr, addrlen))
 }
 
 pub  fn sys_connect(sockfd: u32, addr: usize, addrlen: u32) -> isize {
     let addr_buf = trans_ref!(addr, addrlen);
+    if let Some(socket) = unix_socket(sockfd) {
+        return match UnixAddr::parse(addr_buf).and_then(|addr| socket.connect(addr)) {
+            Ok(()) => SUCCESS,
+            Err(errno) => errno,
+        };
+    }
     let socket = get_socket!(sockfd);
-    socket.connect(addr_buf).unwrap() as isize
+    socket_ret(socket.connect(addr_buf))
 }
 
 pub fn sys_getsockname(sockfd: u32, addr: usize, addrlen: usize) -> isize {
+    if let Some(socket) = unix_socket(sockfd) {
+        return match socket.local_addr().fill(addr, addrlen) {
+            Ok(()) => SUCCESS,
+            Err(errno) => errno,
+        };
+    }
     let socket = get_socket!(sockfd);
-    socket.addr(addr, addrlen).unwrap() as isize
+    socket_ret(socket.addr(addr, addrlen))
 }
 
 pub fn sys_getpeername(sockfd: u32, addr: usize, addrlen: usize) -> isize {
+    if let Some(socket) = unix_socket(sockfd) {
+        return match socket.peer_addr().and_then(|peer| peer.fill(addr, addrlen)) {
+            Ok(()) => SUCCESS,
+            Err(errno) => errno,
+        };
+    }
     let socket = get_socket!(sockfd);
-    socket.peer_addr(addr, addrlen).unwrap() as isize
+    socket_ret(socket.peer_addr(addr, addrlen))
 }
 
 pub fn sys_sendto(
@@ -98,30 +162,39 @@ pub fn sys_sendto(
     addrlen: u32,
 ) -> isize {
     let task = current_task().unwrap();
-    let socket_file = match task.files.lock().get_ref(sockfd as usize) {
-        Ok(file) => file.clone(),
-        Err(e) => return e,
-    };
-    let buf = trans_ref!(buf, len);
+    if let Some(socket) = unix_socket(sockfd) {
+        let buf = match translated_byte_buffer(task.get_user_token(), buf as *const u8, len) {
+            Ok(buffer) => UserBuffer::new(buffer),
+            Err(errno) => return errno,
+        };
+        let dest = if dest_addr == 0 {
+            None
+        } else {
+            match UnixAddr::parse(trans_ref!(dest_addr, addrlen)) {
+                Ok(addr) => Some(addr),
+                Err(errno) => return errno,
+            }
+        };
+        return match socket.send_to(buf, dest) {
+            Ok(len) => len as isize,
+            Err(errno) => errno,
+        };
+    }
     let socket = get_socket!(sockfd);
     log::info!("[sys_sendto] get socket sockfd: {}", sockfd);
-    let mut offset = 0 as usize; 
-    let len = match socket.socket_type() {
-        SocketType::SOCK_STREAM => socket_file.file.write(Some(&mut offset),buf),
-        SocketType::SOCK_DGRAM => {
-            info!("[sys_sendto] socket is udp");
-            if socket.loacl_endpoint().port == 0 {
-                let addr = SocketAddrv4::new([0; 16].as_slice());
-                let endpoint = IpListenEndpoint::from(addr);
-                let _ = socket.bind(endpoint);
-            }
-            let dest_addr = trans_ref!(dest_addr, addrlen);
-            let _ = socket.connect(dest_addr);
-            socket_file.file.write(Some(&mut offset),buf)
+    let buf = match translated_byte_buffer(task.get_user_token(), buf as *const u8, len) {
+        Ok(buffer) => UserBuffer::new(buffer),
+        Err(errno) => return errno,
+    };
+    let dest = if dest_addr == 0 {
+        None
+    } else {
+        match address::endpoint(trans_ref!(dest_addr, addrlen)) {
+            Ok(endpoint) => Some(endpoint),
+            Err(err) => return -(err as isize),
         }
-        _ => todo!(),
     };
-    len as isize
+    socket_ret(socket.send_to(buf, dest))
 }
 
 pub  fn sys_recvfrom(
@@ -132,34 +205,37 @@ pub  fn sys_recvfrom(
     src_addr: usize,
     addrlen: usize,
 ) -> isize {
-    let socket_file = current_task().unwrap().files.lock().get_ref(sockfd as usize).unwrap().clone();
-    let task = current_task().unwrap();
-    let token = task.get_user_token();
-    let buf = translated_refmut(token, buf as *mut u8).unwrap();
-    let buf = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, len as usize) };
-    //info!("[sys_recvfrom] file filags: {:?}", socket_file.flags);
+    if let Some(socket) = unix_socket(sockfd) {
+        let token = current_task().unwrap().get_user_token();
+        let buf = match translated_byte_buffer(token, buf as *const u8, len as usize) {
+            Ok(buffer) => UserBuffer::new(buffer),
+            Err(errno) => return errno,
+        };
+        return match socket.recv_from(buf) {
+            Ok((len, sender)) => match sender.fill(src_addr, addrlen) {
+                Ok(()) => len as isize,
+                Err(errno) => errno,
+            },
+            Err(errno) => errno,
+        };
+    }
     let socket = get_socket!(sockfd);
-
     info!("[sys_recvfrom] get socket sockfd: {}", sockfd);
-
-    let mut offset = 0 as usize;
-    match socket.socket_type() {
-        SocketType::SOCK_STREAM => {
-            let len = socket_file.file.read(Some(&mut offset),buf);
-            if src_addr != 0 {
-                let _ = socket.peer_addr(src_addr, addrlen);
-            }
-            len as isize
-        }
-        SocketType::SOCK_DGRAM => {
-            let len = socket_file.file.read(Some(&mut offset),buf);
-            if src_addr != 0 {
-                let _ = socket.peer_addr(src_addr, addrlen);
-            }
-            len as isize
+    let token = current_task().unwrap().get_user_token();
+    let buf = match translated_byte_buffer(token, buf as *const u8, len as usize) {
+        Ok(buffer) => UserBuffer::new(buffer),
+        Err(errno) => return errno,
+    };
+    let (len, sender) = match socket.recv_from(buf) {
+        Ok(received) => received,
+        Err(err) => return -(err as isize),
+    };
+    if let Some(sender) = sender {
+        if let Err(err) = address::fill_with_endpoint(sender, src_addr, addrlen) {
+            return -(err as isize);
         }
-        _ => todo!(),
     }
+    len as isize
 }
 
 pub fn sys_getsockopt(
@@ -281,13 +357,38 @@ pub fn sys_socketpair(domain: u32, socket_type: u32, protocol: u32, sv: usize) -
         "[sys_socketpair] domain {}, type {}, protocol {}, sv {}",
         domain, socket_type, protocol, sv
     );
-    let len = 2 * core::mem::size_of::<u32>();
-    let sv = unsafe { core::slice::from_raw_parts_mut(sv as *mut u32, len) };
-    let (socket1, socket2) = make_unix_socket_pair::<PAGE_SIZE>();
-    let fd1 = current_task().unwrap().files.lock().insert(FileDescriptor::new(false, false, socket1));
-    let fd2 = current_task().unwrap().files.lock().insert(FileDescriptor::new(false, false, socket2));
-    sv[0] = fd1.unwrap() as u32;
-    sv[1] = fd2.unwrap() as u32;
-    info!("[sys_socketpair] new sv: {:?}", sv);
-    0 as isize
+    if domain as u16 != AF_UNIX {
+        return EAFNOSUPPORT;
+    }
+    let socket_type = match SocketType::from_bits(socket_type) {
+        Some(socket_type) => socket_type,
+        None => return EINVAL,
+    };
+    let kind = match socket_type.kind() {
+        Some(kind) => kind,
+        None => return EINVAL,
+    };
+    let cloexec = socket_type.contains(SocketType::SOCK_CLOEXEC);
+    let (socket1, socket2) =
+        make_unix_socket_pair(kind, socket_type.contains(SocketType::SOCK_NONBLOCK));
+    let fd1 = match socket1.install(cloexec) {
+        Ok(fd) => fd,
+        Err(errno) => return errno,
+    };
+    let fd2 = match socket2.install(cloexec) {
+        Ok(fd) => fd,
+        Err(errno) => {
+            let task = current_task().unwrap();
+            let _ = task.files.lock().remove(fd1);
+            task.socket_table.lock().take(fd1);
+            return errno;
+        }
+    };
+    info!("[sys_socketpair] new sv: [{}, {}]", fd1, fd2);
+    let fds = [fd1 as u32, fd2 as u32];
+    let token = current_task().unwrap().get_user_token();
+    match copy_to_user_array(token, fds.as_ptr(), sv as *mut u32, 2) {
+        Ok(()) => SUCCESS,
+        Err(errno) => errno,
+    }
 }
@@ -16,23 +16,32 @@
 
 use crate::config::{PAGE_SIZE, SYSTEM_TASK_LIMIT, USER_STACK_SIZE};
 use crate::fs::OpenFlags;
-use crate::hal::shutdown;
-use crate::hal::{MachineContext, TrapContext};
+use crate::hal::{reboot, set_watchpoint, shutdown, single_step_supported};
+use crate::hal::{MachineContext, TrapContext, TrapContextOps};
 use crate::mm::{
-    copy_from_user, copy_to_user, copy_to_user_string, get_from_user, translated_byte_buffer,
-    translated_ref, translated_refmut, translated_str, try_get_from_user, MapFlags, MapPermission,
-    UserBuffer,
+    copy_from_user, copy_from_user_array, copy_to_user, copy_to_user_array, copy_to_user_string,
+    get_from_user, translated_byte_buffer, translated_ref, translated_refmut, translated_str,
+    try_get_from_user, MapFlags, MapPermission, UserBuffer,
 };
 use crate::show_frame_consumption;
 use crate::syscall::errno::*;
+use crate::syscall::io_ops::IoVec;
+use crate::task::acct;
+use crate::task::posix_timer::{
+    SigEvent, CLOCK_MONOTONIC, CLOCK_REALTIME, CLOCK_REALTIME_COARSE, TIMER_ABSTIME,
+};
+use crate::task::ptrace;
 use crate::task::threads::{do_futex_wait, FutexCmd};
 use crate::task::{
     add_task, block_current_and_run_next, current_task, current_user_token,
     exit_current_and_run_next, exit_group_and_run_next, find_task_by_pid, find_task_by_tgid,
-    procs_count, signal::*, suspend_current_and_run_next, threads, wait_with_timeout,
-    wake_interruptible, Rusage, TaskStatus,
+    procs_count, set_sched_policy, signal::*, suspend_current_and_run_next, threads,
+    wait_with_timeout, yield_to, Rusage, INITPROC,
+};
+use crate::timer::{
+    get_time_ms, get_time_sec, realtime_now, realtime_to_monotonic, set_realtime, ITimerSpec,
+    ITimerVal, TimeSpec, TimeVal, TimeZone, Times, NSEC_PER_SEC, NSEC_PER_USEC,
 };
-use crate::timer::{get_time_ms, get_time_sec, ITimerVal, TimeSpec, TimeVal, TimeZone, Times};
 use alloc::boxed::Box;
 use alloc::string::{String, ToString};
 use alloc::sync::Arc;
@@ -49,6 +58,263 @@ pub fn sys_shutdown() -> isize {
     shutdown()
 }
 
+pub const LINUX_REBOOT_MAGIC1: u32 = 0xfee1dead;
+pub const LINUX_REBOOT_MAGIC2: u32 = 672274793;
+pub const LINUX_REBOOT_MAGIC2A: u32 = 85072278;
+pub const LINUX_REBOOT_MAGIC2B: u32 = 369367448;
+pub const LINUX_REBOOT_MAGIC2C: u32 = 537993216;
+
+pub const LINUX_REBOOT_CMD_RESTART: u32 = 0x01234567;
+pub const LINUX_REBOOT_CMD_POWER_OFF: u32 = 0x4321fedc;
+
+/// Power off or restart the machine
+///
+/// # Arguments
+/// * `magic1` - Must be `LINUX_REBOOT_MAGIC1`
+/// * `magic2` - Must be one of the `LINUX_REBOOT_MAGIC2*` values
+/// * `cmd` - `LINUX_REBOOT_CMD_POWER_OFF` or `LINUX_REBOOT_CMD_RESTART`
+/// * `_arg` - Only used by `LINUX_REBOOT_CMD_RESTART2`, which is not supported
+///
+/// # Returns
+/// Does not return on success, `EPERM` if the caller is not root (checked first),
+/// `EINVAL` for a bad magic number or an unsupported command
+pub fn sys_reboot(magic1: u32, magic2: u32, cmd: u32, _arg: usize) -> isize {
+    if sys_geteuid() != 0 {
+        return EPERM;
+    }
+    if magic1 != LINUX_REBOOT_MAGIC1
+        || !matches!(
+            magic2,
+            LINUX_REBOOT_MAGIC2 | LINUX_REBOOT_MAGIC2A | LINUX_REBOOT_MAGIC2B | LINUX_REBOOT_MAGIC2C
+        )
+    {
+        return EINVAL;
+    }
+    match cmd {
+        LINUX_REBOOT_CMD_POWER_OFF => {
+            info!("[sys_reboot] power off");
+            shutdown()
+        }
+        LINUX_REBOOT_CMD_RESTART => {
+            info!("[sys_reboot] restart");
+            reboot()
+        }
+        _ => {
+            warn!("[sys_reboot] unsupported cmd: {:#x}", cmd);
+            EINVAL
+        }
+    }
+}
+
+/// Turn BSD process accounting on or off
+///
+/// While on, every exiting process appends a record to the accounting file,
+/// see [`crate::task::acct`].
+///
+/// # Arguments
+/// * `path` - File to append the records to, null turns accounting off
+///
+/// # Returns
+/// `SUCCESS`, `EPERM` if the caller is not root, or the error of opening `path` for writing
+pub fn sys_acct(path: *const u8) -> isize {
+    if sys_geteuid() != 0 {
+        return EPERM;
+    }
+    if path.is_null() {
+        info!("[sys_acct] accounting off");
+        acct::set_acct_file(None);
+        return SUCCESS;
+    }
+    let task = current_task().unwrap();
+    let path = match translated_str(task.get_user_token(), path) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    let working_inode = task.fs.lock().working_inode.clone();
+    match working_inode.open(&path, OpenFlags::O_WRONLY | OpenFlags::O_APPEND, false) {
+        Ok(file) => {
+            info!("[sys_acct] accounting to {}", path);
+            acct::set_acct_file(Some(file));
+            SUCCESS
+        }
+        Err(errno) => errno,
+    }
+}
+
+pub const PTRACE_TRACEME: usize = 0;
+pub const PTRACE_PEEKTEXT: usize = 1;
+pub const PTRACE_PEEKDATA: usize = 2;
+pub const PTRACE_POKETEXT: usize = 4;
+pub const PTRACE_POKEDATA: usize = 5;
+pub const PTRACE_CONT: usize = 7;
+pub const PTRACE_KILL: usize = 8;
+pub const PTRACE_SINGLESTEP: usize = 9;
+pub const PTRACE_GETREGS: usize = 12;
+pub const PTRACE_DETACH: usize = 17;
+pub const PTRACE_SYSCALL: usize = 24;
+pub const PTRACE_SETOPTIONS: usize = 0x4200;
+pub const PTRACE_GETREGSET: usize = 0x4204;
+/// `PTRACE_GETREGSET` register set of the general registers
+pub const NT_PRSTATUS: usize = 1;
+
+/// Process tracing
+///
+/// A task asks to be traced by its parent with `PTRACE_TRACEME`. The parent then
+/// sees the tracee stop before every signal delivery, and at syscall entry and
+/// exit after `PTRACE_SYSCALL`, through `wait4` even without `WUNTRACED`. While
+/// the tracee is stopped the parent may read and write its memory one word at a
+/// time, read its registers and resume it, see [`crate::task::ptrace`].
+///
+/// A task can also step itself without a tracer: with `pid` 0 or its own pid,
+/// after `PTRACE_SINGLESTEP` every user instruction it executes is followed by a
+/// `SIGTRAP`, until `PTRACE_CONT` turns stepping off. Stepping pauses while
+/// `SIGTRAP` is blocked, so a `SIGTRAP` handler itself is not stepped.
+///
+/// # Arguments
+/// * `request` - One of the `PTRACE_*` requests above
+/// * `pid` - The tracee, or 0 or the caller's own pid to step itself
+/// * `addr` - Tracee address to peek or poke, or the register set for `PTRACE_GETREGSET`
+/// * `data` - Signal to inject on resume, word to poke, options, or where to store
+///   the peeked word or the registers
+///
+/// # Returns
+/// 0 on success (`PTRACE_TRACEME`, resume and poke requests), `EPERM` if the caller
+/// is already traced, `ESRCH` if `pid` is not a traced child or is not stopped,
+/// `EIO` for an unsupported request, a bad signal or an inaccessible tracee address
+pub fn sys_ptrace(request: usize, pid: usize, addr: usize, data: usize) -> isize {
+    let task = current_task().unwrap();
+    if request == PTRACE_TRACEME {
+        let mut inner = task.acquire_inner_lock();
+        if inner.ptrace.is_some() {
+            return EPERM;
+        }
+        inner.ptrace = Some(ptrace::Ptrace::default());
+        return SUCCESS;
+    }
+    if pid == 0 || pid == task.pid.0 {
+        // 没有跟踪者时单步执行自身
+        let single_step = match request {
+            PTRACE_SINGLESTEP if single_step_supported() => true,
+            PTRACE_CONT => false,
+            _ => {
+                warn!("[sys_ptrace] unsupported request: {}", request);
+                return EIO;
+            }
+        };
+        if data != 0 {
+            return EIO;
+        }
+        task.acquire_inner_lock().single_step = single_step;
+        return SUCCESS;
+    }
+    let tracee = match ptrace::traced_child(&task, pid) {
+        Some(tracee) => tracee,
+        None => return ESRCH,
+    };
+    if request == PTRACE_KILL {
+        send_signal(tracee, Signals::SIGKILL);
+        return SUCCESS;
+    }
+    if !ptrace::is_stopped(&tracee.acquire_inner_lock()) {
+        return ESRCH;
+    }
+    let token = task.get_user_token();
+    match request {
+        PTRACE_PEEKTEXT | PTRACE_PEEKDATA => match ptrace::peek_word(&tracee, addr) {
+            Ok(word) => match copy_to_user(token, &word, data as *mut usize) {
+                Ok(()) => SUCCESS,
+                Err(errno) => errno,
+            },
+            Err(errno) => errno,
+        },
+        PTRACE_POKETEXT | PTRACE_POKEDATA => match ptrace::poke_word(&tracee, addr, data) {
+            Ok(()) => SUCCESS,
+            Err(errno) => errno,
+        },
+        PTRACE_GETREGS => {
+            let regs = tracee.acquire_inner_lock().get_trap_cx().gp;
+            match copy_to_user(token, &regs, data as *mut _) {
+                Ok(()) => SUCCESS,
+                Err(errno) => errno,
+            }
+        }
+        PTRACE_GETREGSET => {
+            if addr != NT_PRSTATUS {
+                return EINVAL;
+            }
+            let mut iov = match get_from_user(token, data as *const IoVec) {
+                Ok(iov) => iov,
+                Err(errno) => return errno,
+            };
+            let regs = tracee.acquire_inner_lock().get_trap_cx().gp;
+            iov.len = iov.len.min(core::mem::size_of_val(&regs));
+            let copied = copy_to_user_array(
+                token,
+                &regs as *const _ as *const u8,
+                iov.base as *mut u8,
+                iov.len,
+            );
+            match copied.and_then(|()| copy_to_user(token, &iov, data as *mut IoVec)) {
+                Ok(()) => SUCCESS,
+                Err(errno) => errno,
+            }
+        }
+        PTRACE_SETOPTIONS => {
+            if data & !ptrace::PTRACE_O_TRACESYSGOOD != 0 {
+                return EINVAL;
+            }
+            tracee.acquire_inner_lock().ptrace.as_mut().unwrap().options = data;
+            SUCCESS
+        }
+        PTRACE_CONT | PTRACE_SYSCALL | PTRACE_SINGLESTEP | PTRACE_DETACH => {
+            if Signals::from_signum(data).is_err() {
+                return EIO;
+            }
+            match request {
+                PTRACE_DETACH => ptrace::detach(&tracee, data),
+                PTRACE_SINGLESTEP if !single_step_supported() => return EIO,
+                _ => ptrace::resume(
+                    tracee,
+                    data,
+                    request == PTRACE_SYSCALL,
+                    request == PTRACE_SINGLESTEP,
+                ),
+            }
+            SUCCESS
+        }
+        _ => {
+            warn!("[sys_ptrace] unsupported request: {}", request);
+            EIO
+        }
+    }
+}
+
+/// Trap on writes to `addr`, same value as Linux `HW_BREAKPOINT_W`
+pub const WATCH_WRITE: u32 = 2;
+
+/// Set a hardware write watchpoint for the calling task
+///
+/// The next user-mode store to `addr` stops before it is performed and sends
+/// `SIGTRAP`. The watchpoint is one-shot and is removed when it fires.
+///
+/// # Arguments
+/// * `addr` - Watched address, 0 removes the current watchpoint
+/// * `kind` - Only `WATCH_WRITE` is supported
+///
+/// # Returns
+/// 0 on success, `EINVAL` for an unsupported `kind`, `EBUSY` if a watchpoint is
+/// already set, `ENODEV` without hardware trigger support
+pub fn sys_watchpoint(addr: usize, kind: u32) -> isize {
+    if addr != 0 && kind != WATCH_WRITE {
+        return EINVAL;
+    }
+    let pid = current_task().unwrap().pid.0;
+    match set_watchpoint(pid, addr) {
+        Ok(()) => SUCCESS,
+        Err(errno) => errno,
+    }
+}
+
 /// Terminate the calling thread
 /// 
 /// # Arguments
@@ -115,6 +381,24 @@ pub fn sys_yield() -> isize {
     SUCCESS
 }
 
+/// Yield the CPU to thread `tid`, such as the holder of a lock the caller spins
+/// on: it runs next on its CPU ahead of fairer tasks.
+/// # Return Conditions
+/// Returns `SUCCESS` after the caller runs again, or `ESRCH` without yielding if
+/// no thread `tid` exists.
+pub fn sys_sched_yield_to(tid: usize) -> isize {
+    if yield_to(tid) {
+        SUCCESS
+    } else {
+        ESRCH
+    }
+}
+
+/// Send signal `sig` to process `pid` (`pid` > 0), to the caller's process group
+/// (`pid` == 0), to every process but init and the caller (`pid` == -1), or to
+/// process group `-pid` (`pid` < -1). Signal 0 only checks that the target exists.
+/// # Return Conditions
+/// Returns `SUCCESS`, `EINVAL` for an invalid signal, or `ESRCH` if no target exists.
 pub fn sys_kill(pid: usize, sig: usize) -> isize {
     let signal = match Signals::from_signum(sig) {
         Ok(signal) => signal,
@@ -124,32 +408,33 @@ pub fn sys_kill(pid: usize, sig: usize) -> isize {
     if pid == 10 {
         return SUCCESS;
     }
-    if pid > 0 {
-        // [Warning] in current implementation,
-        // signal will be sent to an arbitrary task with target `pid` (`tgid` more precisely).
-        // But manual also require that the target task should not mask this signal.
-        if let Some(task) = find_task_by_tgid(pid) {
-            if !signal.is_empty() {
-                let mut inner = task.acquire_inner_lock();
-                inner.add_signal(signal);
-                // wake up target process if it is sleeping
-                if inner.task_status == TaskStatus::Interruptible {
-                    inner.task_status = TaskStatus::Ready;
-                    drop(inner);
-                    wake_interruptible(task);
+    match pid as isize {
+        pid if pid > 0 => {
+            // [Warning] in current implementation,
+            // signal will be sent to an arbitrary task with target `pid` (`tgid` more precisely).
+            // But manual also require that the target task should not mask this signal.
+            match find_task_by_tgid(pid as usize) {
+                Some(task) => {
+                    send_signal(task, signal);
+                    SUCCESS
                 }
+                None => ESRCH,
+            }
+        }
+        0 => kill_pgrp(current_task().unwrap().getpgid(), signal),
+        -1 => {
+            let caller = current_task().unwrap().tgid;
+            let targets =
+                find_processes(|task, _| task.tgid != caller && task.tgid != INITPROC.tgid);
+            if targets.is_empty() {
+                return ESRCH;
+            }
+            for task in targets {
+                send_signal(task, signal);
             }
             SUCCESS
-        } else {
-            ESRCH
         }
-    } else if pid == 0 {
-        SUCCESS
-    } else if (pid as isize) == -1 {
-        todo!()
-    } else {
-        // (pid as isize) < -1
-        todo!()
+        pgid => kill_pgrp(-pgid as usize, signal),
     }
 }
 
@@ -160,16 +445,7 @@ pub fn sys_tkill(tid: usize, sig: usize) -> isize {
     };
     if tid > 0 {
         if let Some(task) = find_task_by_pid(tid) {
-            if !signal.is_empty() {
-                let mut inner = task.acquire_inner_lock();
-                inner.add_signal(signal);
-                // wake up target process if it is sleeping
-                if inner.task_status == TaskStatus::Interruptible {
-                    inner.task_status = TaskStatus::Ready;
-                    drop(inner);
-                    wake_interruptible(task);
-                }
-            }
+            send_signal(task, signal);
             SUCCESS
         } else {
             ESRCH
@@ -191,15 +467,8 @@ pub fn sys_tgkill(tgid: usize, tid: usize, sig: usize) -> isize {
     };
     if let Some(task) = find_task_by_tgid(tgid) {
         if !signal.is_empty() {
-            let mut inner = task.acquire_inner_lock();
             if task.pid.0 == tid {
-                inner.add_signal(signal);
-                // wake up target process if it is sleeping
-                if inner.task_status == TaskStatus::Interruptible {
-                    inner.task_status = TaskStatus::Ready;
-                    drop(inner);
-                    wake_interruptible(task);
-                }
+                send_signal(task, signal);
             } else {
                 warn!(
                     "[sys_tgkill] tid {} does not match task's tid {}",
@@ -295,11 +564,125 @@ pub fn sys_setitimer(
     }
 }
 
+/// # 描述
+/// 创建 POSIX 定时器，定时器 id 写入 `timerid`
+/// # 参数
+/// * `clockid`: `CLOCK_REALTIME` 或 `CLOCK_MONOTONIC`
+/// * `sevp`: 到期的通知方式，为空时向进程发送 SIGALRM
+/// * `timerid`: 存放定时器 id
+pub fn sys_timer_create(clockid: usize, sevp: *const SigEvent, timerid: *mut i32) -> isize {
+    if clockid != CLOCK_REALTIME && clockid != CLOCK_MONOTONIC {
+        return EINVAL;
+    }
+    let task = current_task().unwrap();
+    let token = task.get_user_token();
+    let event = match try_get_from_user(token, sevp) {
+        Ok(event) => event,
+        Err(errno) => return errno,
+    };
+    let mut timers = task.posix_timers.lock();
+    let id = match timers.create(&task, clockid, event) {
+        Ok(id) => id,
+        Err(errno) => return errno,
+    };
+    if copy_to_user(token, &(id as i32), timerid).is_err() {
+        timers.delete(id);
+        return EFAULT;
+    }
+    SUCCESS
+}
+
+/// # 描述
+/// 启动或停止 POSIX 定时器
+/// # 参数
+/// * `timerid`: 定时器 id
+/// * `flags`: `TIMER_ABSTIME` 表示 `it_value` 是绝对时间
+/// * `new_value`: `it_value` 为 0 时停止定时器，否则在 `it_value` 后到期，之后以 `it_interval` 为周期
+/// * `old_value`: 不为空时存放原来的剩余时间与周期
+pub fn sys_timer_settime(
+    timerid: usize,
+    flags: u32,
+    new_value: *const ITimerSpec,
+    old_value: *mut ITimerSpec,
+) -> isize {
+    let task = current_task().unwrap();
+    let token = task.get_user_token();
+    let timer = match task.posix_timers.lock().get(timerid) {
+        Some(timer) => timer,
+        None => return EINVAL,
+    };
+    let new_value = match get_from_user(token, new_value) {
+        Ok(new_value) => new_value,
+        Err(errno) => return errno,
+    };
+    if new_value.it_value.tv_nsec >= NSEC_PER_SEC || new_value.it_interval.tv_nsec >= NSEC_PER_SEC
+    {
+        return EINVAL;
+    }
+    let expires = if new_value.it_value.is_zero() {
+        new_value.it_value
+    } else if flags & TIMER_ABSTIME != 0 {
+        // CLOCK_REALTIME 的到期时刻按当前的偏移换算，之后再设置时钟不影响它
+        if timer.clock() == CLOCK_REALTIME {
+            realtime_to_monotonic(new_value.it_value)
+        } else {
+            new_value.it_value
+        }
+    } else {
+        TimeSpec::now() + new_value.it_value
+    };
+    let old = timer.set(expires, new_value.it_interval);
+    if !old_value.is_null() && copy_to_user(token, &old, old_value).is_err() {
+        return EFAULT;
+    }
+    SUCCESS
+}
+
+/// # 描述
+/// 读取 POSIX 定时器的剩余时间与周期
+pub fn sys_timer_gettime(timerid: usize, curr_value: *mut ITimerSpec) -> isize {
+    let task = current_task().unwrap();
+    let timer = match task.posix_timers.lock().get(timerid) {
+        Some(timer) => timer,
+        None => return EINVAL,
+    };
+    if copy_to_user(task.get_user_token(), &timer.get(), curr_value).is_err() {
+        return EFAULT;
+    }
+    SUCCESS
+}
+
+/// # 描述
+/// POSIX 定时器最近一次发送的信号递送前又到期的次数
+pub fn sys_timer_getoverrun(timerid: usize) -> isize {
+    let task = current_task().unwrap();
+    let timer = task.posix_timers.lock().get(timerid);
+    match timer {
+        Some(timer) => timer.overrun() as isize,
+        None => EINVAL,
+    }
+}
+
+/// # 描述
+/// 删除 POSIX 定时器，还未递送的信号仍然挂起
+pub fn sys_timer_delete(timerid: usize) -> isize {
+    let task = current_task().unwrap();
+    if task.posix_timers.lock().delete(timerid) {
+        SUCCESS
+    } else {
+        EINVAL
+    }
+}
+
 pub fn sys_gettimeofday(tv: *mut TimeVal, _tz: *mut TimeZone) -> isize {
     // Timezone is currently NOT supported.
     if !tv.is_null() {
         let token = current_user_token();
-        let timeval = &TimeVal::now();
+        let now = realtime_now();
+        let timeval = &TimeVal {
+            tv_sec: now.tv_sec,
+            tv_usec: now.tv_nsec / NSEC_PER_USEC,
+        };
         if copy_to_user(token, timeval, tv).is_err() {
             log::error!("[sys_gettimeofday] Failed to copy to {:?}", tv);
             return EFAULT;
@@ -379,39 +762,65 @@ pub fn sys_getegid() -> isize {
     0 // root group
 }
 
-// Warning, we don't support this syscall in fact, task.setpgid() won't take effect for some reason
-// So it just pretend to do this work.
-// Fortunately, that won't make difference when we just try to run busybox sh so far.
+/// Move process `pid` (the caller if 0) into process group `pgid` (a new group
+/// led by `pid` if 0).
+/// # Return Conditions
+/// Returns `SUCCESS`, `EINVAL` if `pgid` is negative, or `ESRCH` if there is no
+/// process `pid`. Sessions are not tracked, so any existing group may be joined.
 pub fn sys_setpgid(pid: usize, pgid: usize) -> isize {
-    /* An attempt.*/
-    let task = crate::task::find_task_by_tgid(pid);
+    if (pgid as isize) < 0 {
+        return EINVAL;
+    }
+    let task = if pid == 0 {
+        current_task()
+    } else {
+        find_task_by_tgid(pid)
+    };
     match task {
-        Some(task) => task.setpgid(pgid),
+        Some(task) => task.setpgid(if pgid == 0 { task.tgid } else { pgid }),
         None => ESRCH,
     }
 }
 
+/// Process group of process `pid`, the caller if 0.
 pub fn sys_getpgid(pid: usize) -> isize {
-    /* An attempt.*/
-    let task = crate::task::find_task_by_tgid(pid);
+    let task = if pid == 0 {
+        current_task()
+    } else {
+        find_task_by_tgid(pid)
+    };
     match task {
         Some(task) => task.getpgid() as isize,
         None => ESRCH,
     }
 }
+/// Session of process `pid`, the caller if 0.
+pub fn sys_getsid(pid: usize) -> isize {
+    let task = if pid == 0 {
+        current_task()
+    } else {
+        find_task_by_tgid(pid)
+    };
+    match task {
+        Some(task) => task.acquire_inner_lock().sid as isize,
+        None => ESRCH,
+    }
+}
+
 /// creates a new session if the calling process is not a process group leader.
-/// The calling process is the leader of the new session
-/// 当前进程脱离父进程，从父进程的子进程列表中移除当前进程，当前进程的父进程设置为空。
+/// The calling process is the leader of the new session and of a new process group,
+/// and has no controlling terminal. Returns the new session ID.
 pub fn sys_setsid() -> isize {
     let task = current_task().unwrap();
-    if let Some(parent) = task.acquire_inner_lock().parent.as_ref().unwrap().upgrade() {
-        parent
-            .acquire_inner_lock()
-            .children
-            .retain(|x| x.tid != task.tid);
+    // 进程组的组长不能创建新会话，否则组中的其他进程会和组长分属两个会话
+    if !find_processes(|_, pgid| pgid == task.tgid).is_empty() {
+        return EPERM;
     }
-    task.acquire_inner_lock().parent = None;
-    SUCCESS
+    let mut inner = task.acquire_inner_lock();
+    inner.sid = task.tgid;
+    inner.pgid = task.tgid;
+    inner.ctty = None;
+    task.tgid as isize
 }
 
 // For user, tid is pid in kernel
@@ -456,7 +865,7 @@ pub fn sys_sysinfo(info: *mut Sysinfo) -> isize {
                 procs as usize * LINUX_SYSINFO_LOADS_SCALE / SEC_5_MIN,
                 procs as usize * LINUX_SYSINFO_LOADS_SCALE / SEC_15_MIN,
             ],
-            totalram: crate::config::MEMORY_END - crate::config::MEMORY_START,
+            totalram: crate::mm::memory_end() - crate::config::MEMORY_START,
             freeram: crate::mm::unallocated_frames() * PAGE_SIZE,
             sharedram: UNIMPLEMENT,
             bufferram: UNIMPLEMENT,
@@ -604,6 +1013,22 @@ pub fn sys_clone(
     new_pid as isize
 }
 
+/// 系统调用 sys_unshare
+/// # 说明
+/// + 目前只支持 CLONE_NEWNS：调用者得到所在挂载命名空间的副本，之后的挂载与 pivot_root 只影响副本
+/// + 其余标志返回 EINVAL
+pub fn sys_unshare(flags: u32) -> isize {
+    let flags = match CloneFlags::from_bits(flags) {
+        Some(flags) if flags == CloneFlags::CLONE_NEWNS => flags,
+        _ => return EINVAL,
+    };
+    info!("[sys_unshare] flags: {:?}", flags);
+    let task = current_task().unwrap();
+    let mut mnt_ns = task.mnt_ns.lock();
+    *mnt_ns = mnt_ns.copy();
+    SUCCESS
+}
+
 /// 执行可执行文件
 /// # 参数
 /// + pathname：文件路径
@@ -673,7 +1098,7 @@ pub fn sys_execve(
         envp_vec.len()
     );
     // 获取当前工作目录的文件描述符
-    let working_inode = &task.fs.lock().working_inode;
+    let working_inode = task.fs.lock().working_inode.clone();
 
     match working_inode.open(&path, OpenFlags::O_RDONLY, false) {
         // 检查打开的文件
@@ -784,10 +1209,42 @@ pub fn sys_wait4(pid: isize, status: *mut u32, option: u32, _ru: *mut Rusage) ->
                 }
                 return found_pid as isize;
             }
+        } else if let Some((found_pid, report)) = inner
+            .children
+            .iter()
+            .filter(|p| pid == -1 || pid as usize == p.getpid())
+            .find_map(|p| {
+                // ++++ temporarily hold child PCB lock
+                let mut child_inner = p.acquire_inner_lock();
+                let report = child_inner.stop_report.filter(|&report| {
+                    if report == WAIT_CONTINUED {
+                        option.contains(WaitOption::WCONTINUED)
+                    } else {
+                        // 跟踪停止总是报告给跟踪者
+                        option.contains(WaitOption::WSTOPPED) || child_inner.ptrace.is_some()
+                    }
+                })?;
+                if !option.contains(WaitOption::WNOWAIT) {
+                    child_inner.stop_report = None;
+                }
+                Some((p.getpid(), report))
+                // ++++ release child PCB lock
+            })
+        {
+            // a child stopped (WUNTRACED) or continued (WCONTINUED) and stays our child
+            if !status.is_null() {
+                match translated_refmut(token, status) {
+                    Ok(word) => *word = report,
+                    Err(errno) => return errno,
+                };
+            }
+            return found_pid as isize;
         } else {
             drop(inner);
             if option.contains(WaitOption::WNOHANG) {
                 return SUCCESS;
+            } else if signal_pending() {
+                return ERESTART;
             } else {
                 block_current_and_run_next();
                 debug!("[sys_wait4] --resumed--");
@@ -952,6 +1409,13 @@ bitflags! {
 /// * `timeout`: `*const TimeSpec`,
 /// * `uaddr2`: `usize`,
 /// * `val3`: `u32`,
+/// # 返回值
+/// * `FUTEX_WAKE`: 唤醒的任务数
+/// * `FUTEX_REQUEUE`, `FUTEX_CMP_REQUEUE`: 唤醒与移到 `uaddr2` 的任务总数；
+///   `FUTEX_CMP_REQUEUE` 在 `*uaddr != val3` 时返回 `EAGAIN`
+/// * `FUTEX_WAKE_OP`: 两个地址上唤醒的任务总数，`val3` 编码的操作未知时返回 `ENOSYS`
+/// * `FUTEX_LOCK_PI`, `FUTEX_TRYLOCK_PI`, `FUTEX_UNLOCK_PI`: 见 [`threads::do_futex_lock_pi`]
+///   与 [`threads::do_futex_unlock_pi`]；`FUTEX_LOCK_PI` 的 `timeout` 是绝对时间
 pub fn sys_futex(
     uaddr: *mut u32,
     futex_op: u32,
@@ -998,7 +1462,7 @@ pub fn sys_futex(
             let futex_word_addr = futex_word as *const u32 as usize;
             task.futex.lock().wake(futex_word_addr, val)
         }
-        FutexCmd::Requeue => {
+        FutexCmd::Requeue | FutexCmd::CmpRequeue | FutexCmd::WakeOp => {
             if uaddr2.is_null() || uaddr2.align_offset(4) != 0 {
                 return EINVAL;
             }
@@ -1006,9 +1470,33 @@ pub fn sys_futex(
                 Ok(futex_word_2) => futex_word_2,
                 Err(errno) => return errno,
             };
-            task.futex
-                .lock()
-                .requeue(futex_word, futex_word_2, val, timeout as u32)
+            // for these operations the `timeout` argument is `val2`
+            let val2 = timeout as usize as u32;
+            let mut futex = task.futex.lock();
+            match cmd {
+                FutexCmd::WakeOp => match threads::FutexWakeOp::decode(val3) {
+                    Ok(op) => futex.wake_op(futex_word, futex_word_2, val, val2, op),
+                    Err(errno) => errno,
+                },
+                // compared under the futex lock, so no waiter can slip in between
+                FutexCmd::CmpRequeue if *futex_word != val3 => EAGAIN,
+                _ => futex.requeue(futex_word, futex_word_2, val, val2),
+            }
+        }
+        FutexCmd::LockPi | FutexCmd::TrylockPi => {
+            let timeout = match cmd {
+                FutexCmd::LockPi => match try_get_from_user(token, timeout) {
+                    Ok(timeout) => timeout,
+                    Err(errno) => return errno,
+                },
+                _ => None,
+            };
+            drop(task);
+            threads::do_futex_lock_pi(futex_word, timeout, cmd == FutexCmd::TrylockPi)
+        }
+        FutexCmd::UnlockPi => {
+            drop(task);
+            threads::do_futex_unlock_pi(futex_word)
         }
         FutexCmd::Invalid => EINVAL,
         _ => todo!(),
@@ -1064,7 +1552,13 @@ pub fn sys_mmap(
         "[mmap] start:{:X}; len:{:X}; prot:{:?}; flags:{:?}; fd:{}; offset:{:X}",
         start, len, prot, flags, fd as isize, offset
     );
-    memory_set.mmap(start, len, prot, flags, fd, offset)
+    let addr = memory_set.mmap(start, len, prot, flags, fd, offset);
+    // prefaulting is best effort, the mapping stands even if it stops early
+    if addr >= 0 && flags.contains(MapFlags::MAP_POPULATE) {
+        let populated = memory_set.populate(addr as usize, len.max(PAGE_SIZE));
+        info!("[mmap] populated {} pages", populated);
+    }
+    addr
 }
 
 /// # Versions
@@ -1082,13 +1576,41 @@ pub fn sys_memorybarrier(_cmd: usize, _flags: usize, _cpu_id: usize) -> isize {
 
 pub fn sys_munmap(start: usize, len: usize) -> isize {
     let task = current_task().unwrap();
-    let result = task.vm.lock().munmap(start, len);
+    let mut vm = task.vm.lock();
+    let files = vm.shared_files(start, len);
+    let result = vm.munmap(start, len);
+    // 写回可能睡眠，先释放地址空间的锁
+    drop(vm);
+    // 解除共享映射时把写入的页写回文件
+    for file in files {
+        file.fsync();
+    }
     match result {
         Ok(_) => SUCCESS,
         Err(errno) => errno,
     }
 }
 
+/// Report which pages of `[addr, addr + len)` are resident, one byte per page in `vec`
+pub fn sys_mincore(addr: usize, len: usize, vec: *mut u8) -> isize {
+    let task = current_task().unwrap();
+    // released before copying out, writing `vec` may fault
+    let result = task.vm.lock().mincore(addr, len);
+    match result {
+        Ok(residency) if residency.is_empty() => SUCCESS,
+        Ok(residency) => match copy_to_user_array(
+            task.get_user_token(),
+            residency.as_ptr(),
+            vec,
+            residency.len(),
+        ) {
+            Ok(()) => SUCCESS,
+            Err(errno) => errno,
+        },
+        Err(errno) => errno,
+    }
+}
+
 pub fn sys_mprotect(addr: usize, len: usize, prot: usize) -> isize {
     let task = current_task().unwrap();
     let result = task.vm.lock().mprotect(addr, len, prot);
@@ -1101,7 +1623,11 @@ pub fn sys_mprotect(addr: usize, len: usize, prot: usize) -> isize {
 pub fn sys_clock_gettime(clk_id: usize, tp: *mut TimeSpec) -> isize {
     if !tp.is_null() {
         let token = current_user_token();
-        let timespec = &TimeSpec::now();
+        // 除 CLOCK_REALTIME 外的时钟都按启动以来的时间计
+        let timespec = &match clk_id {
+            CLOCK_REALTIME | CLOCK_REALTIME_COARSE => realtime_now(),
+            _ => TimeSpec::now(),
+        };
         if copy_to_user(token, timespec, tp).is_err() {
             log::error!("[sys_clock_gettime] Failed to copy to {:?}", tp);
             return EFAULT;
@@ -1111,6 +1637,30 @@ pub fn sys_clock_gettime(clk_id: usize, tp: *mut TimeSpec) -> isize {
     SUCCESS
 }
 
+/// # 描述
+/// 设置时钟，只有 CLOCK_REALTIME 可以设置，CLOCK_MONOTONIC 等时钟不受影响
+/// # 错误
+/// + `EINVAL`：时钟不是 CLOCK_REALTIME，或 `tv_nsec` 不小于 1 秒
+/// + `EPERM`：调用者不是特权用户
+pub fn sys_clock_settime(clk_id: usize, tp: *const TimeSpec) -> isize {
+    if clk_id != CLOCK_REALTIME {
+        return EINVAL;
+    }
+    let time = match get_from_user(current_user_token(), tp) {
+        Ok(time) => time,
+        Err(errno) => return errno,
+    };
+    if time.tv_nsec >= NSEC_PER_SEC {
+        return EINVAL;
+    }
+    if sys_geteuid() != 0 {
+        return EPERM;
+    }
+    info!("[sys_clock_settime] clk_id: {}, tp: {:?}", clk_id, time);
+    set_realtime(time);
+    SUCCESS
+}
+
 pub fn sys_clock_nanosleep(
     clk_id: usize,
     flags: u32,
@@ -1139,7 +1689,12 @@ pub fn sys_clock_nanosleep(
     }
     
     let end = if flags == 1 {
-        req // 绝对时间
+        // 绝对时间，CLOCK_REALTIME 的时刻按当前的偏移换算
+        if clk_id == CLOCK_REALTIME {
+            realtime_to_monotonic(req)
+        } else {
+            req
+        }
     } else {
         TimeSpec::now() + req // 相对时间
     };
@@ -1231,7 +1786,8 @@ pub fn sys_sigreturn() -> isize {
     )
     .unwrap(); // restore trap_cx
                // This should be `Ok(())`.
-    return trap_cx.gp.a0 as isize; // return a0: not modify any of trap_cx
+    trap_cx.invalidate_fp();
+    return trap_cx.return_value() as isize; // return a0: not modify any of trap_cx
 }
 
 /// Get process times
@@ -1268,9 +1824,20 @@ pub fn sys_getrusage(who: isize, usage: *mut Rusage) -> isize {
     SUCCESS
 }
 
+/// The pages may be dropped under memory pressure unless written to first
+const MADV_FREE: u32 = 8;
+
+/// Only `MADV_FREE` has an effect, other advice is accepted and ignored
 pub fn sys_madvise(addr: usize, length: usize, advice: u32) -> isize {
     // info!("[sys_madvise] addr: {}, length: {}, advice: {}", addr, length, advice);
-    SUCCESS
+    match advice {
+        #[cfg(feature = "oom_handler")]
+        MADV_FREE => match current_task().unwrap().vm.lock().lazy_free(addr, length) {
+            Ok(()) => SUCCESS,
+            Err(errno) => errno,
+        },
+        _ => SUCCESS,
+    }
 }
 
 /// Priority target types for getpriority/setpriority syscalls
@@ -1366,6 +1933,8 @@ pub fn sys_getpriority(which: i32, who: i32) -> isize {
 
 use crate::task::cfs_scheduler::SchedPolicy;
 use crate::config::MAX_CPU_NUM;
+use crate::task::cpuset;
+use crate::task::processor::current_cpu_id;
 
 /// sched_param structure for sched_setscheduler/sched_getscheduler
 #[repr(C)]
@@ -1374,6 +1943,16 @@ pub struct SchedParam {
     pub sched_priority: i32,
 }
 
+/// Static priority `sched_priority` as a RT priority for `policy`, `None` if
+/// it is out of the range of the policy
+fn rt_priority_of(policy: SchedPolicy, sched_priority: i32) -> Option<u8> {
+    match (policy.is_realtime(), sched_priority) {
+        (true, 1..=99) => Some(sched_priority as u8),
+        (false, 0) => Some(0),
+        _ => None,
+    }
+}
+
 /// Set scheduling policy and parameters
 /// 
 /// # Arguments
@@ -1402,28 +1981,24 @@ pub fn sys_sched_setscheduler(pid: usize, policy: u32, param: *const SchedParam)
         }
     };
     
-    let priority = if !param.is_null() {
-        let token = current_user_token();
-        match try_get_from_user::<SchedParam>(token, param) {
-            Ok(Some(p)) => p.sched_priority as u8,
-            Ok(None) => 0,
-            Err(_) => return EFAULT,
-        }
-    } else {
-        0
+    if param.is_null() {
+        return EINVAL;
+    }
+    let token = current_user_token();
+    let priority = match try_get_from_user::<SchedParam>(token, param) {
+        Ok(Some(p)) => p.sched_priority,
+        Ok(None) => return EINVAL,
+        Err(_) => return EFAULT,
     };
     
-    // Validate RT priority
-    if sched_policy.is_realtime() {
-        if priority < 1 || priority > 99 {
-            return EINVAL;
-        }
-    }
+    // RT policies take a priority of 1-99, the others only 0
+    let priority = match rt_priority_of(sched_policy, priority) {
+        Some(priority) => priority,
+        None => return EINVAL,
+    };
     
-    {
-        let mut inner = task.acquire_inner_lock();
-        inner.sched_entity.set_policy(sched_policy, priority);
-    }
+    // A queued task moves to the run queue of its new policy
+    set_sched_policy(&task, sched_policy, priority);
     
     info!("[sys_sched_setscheduler] pid={} policy={:?} prio={}", 
           task.pid.0, sched_policy, priority);
@@ -1443,7 +2018,7 @@ pub fn sys_sched_getscheduler(pid: usize) -> isize {
     
     let policy = {
         let inner = task.acquire_inner_lock();
-        inner.sched_entity.policy
+        inner.sched_entity.normal_policy
     };
     
     policy as isize
@@ -1466,16 +2041,15 @@ pub fn sys_sched_setparam(pid: usize, param: *const SchedParam) -> isize {
     
     let token = current_user_token();
     let priority = match try_get_from_user::<SchedParam>(token, param) {
-        Ok(Some(p)) => p.sched_priority as u8,
+        Ok(Some(p)) => p.sched_priority,
         Ok(None) => return EINVAL,
         Err(_) => return EFAULT,
     };
     
-    {
-        let mut inner = task.acquire_inner_lock();
-        if inner.sched_entity.policy.is_realtime() {
-            inner.sched_entity.rt_priority = priority.clamp(1, 99);
-        }
+    let policy = task.acquire_inner_lock().sched_entity.normal_policy;
+    match rt_priority_of(policy, priority) {
+        Some(priority) => set_sched_policy(&task, policy, priority),
+        None => return EINVAL,
     }
     
     SUCCESS
@@ -1498,7 +2072,7 @@ pub fn sys_sched_getparam(pid: usize, param: *mut SchedParam) -> isize {
     
     let priority = {
         let inner = task.acquire_inner_lock();
-        inner.sched_entity.rt_priority as i32
+        inner.sched_entity.normal_rt_priority as i32
     };
     
     let token = current_user_token();
@@ -1525,25 +2099,50 @@ pub fn sys_sched_setaffinity(pid: usize, cpusetsize: usize, mask: *const usize)
         return EINVAL;
     }
     
+    // cpu_set_t is a bitmap in bytes of any length: bits of CPUs we do not have
+    // are ignored, a short set leaves the higher CPUs out
+    let mut bytes = [0u8; core::mem::size_of::<usize>()];
+    let len = cpusetsize.min(bytes.len());
     let token = current_user_token();
-    let affinity_mask = match try_get_from_user::<usize>(token, mask) {
-        Ok(Some(m)) => m,
-        Ok(None) => return EINVAL, // Should not happen since we checked is_null
-        Err(_) => return EFAULT,
-    };
+    if copy_from_user_array(token, mask as *const u8, bytes.as_mut_ptr(), len).is_err() {
+        return EFAULT;
+    }
+    let valid_cpus = (1usize << MAX_CPU_NUM) - 1;
+    let affinity_mask = usize::from_le_bytes(bytes) & valid_cpus;
     
     // Validate that at least one valid CPU is set
-    let valid_cpus = (1usize << MAX_CPU_NUM) - 1;
-    if (affinity_mask & valid_cpus) == 0 {
+    if affinity_mask == 0 {
         return EINVAL;
     }
     
-    {
+    let allowed = {
         let mut inner = task.acquire_inner_lock();
+        // The mask must leave the task some CPU of its cpuset
+        if affinity_mask & cpuset::cpus(inner.sched_entity.cpuset) == 0 {
+            return EINVAL;
+        }
         inner.sched_entity.set_affinity(affinity_mask);
-    }
+        inner.sched_entity.allowed_cpus()
+    };
     
     info!("[sys_sched_setaffinity] pid={} mask={:#x}", task.pid.0, affinity_mask);
+    // A task queued elsewhere is moved when it is next picked, see `run_tasks`;
+    // the caller leaves a CPU it may no longer run on right away
+    if Arc::ptr_eq(&task, &current_task().unwrap()) && allowed & (1 << current_cpu_id()) == 0 {
+        suspend_current_and_run_next();
+    }
+    SUCCESS
+}
+
+/// Report the CPU the caller runs on, the node is always 0
+pub fn sys_getcpu(cpu: *mut u32, node: *mut u32) -> isize {
+    let token = current_user_token();
+    if !cpu.is_null() && copy_to_user(token, &(current_cpu_id() as u32), cpu).is_err() {
+        return EFAULT;
+    }
+    if !node.is_null() && copy_to_user(token, &0u32, node).is_err() {
+        return EFAULT;
+    }
     SUCCESS
 }
 
@@ -1558,13 +2157,17 @@ pub fn sys_sched_getaffinity(pid: usize, cpusetsize: usize, mask: *mut usize) ->
         }
     };
     
-    if mask.is_null() || cpusetsize == 0 {
+    // The set must hold every CPU and be made of whole words, as on Linux
+    if mask.is_null()
+        || cpusetsize * 8 < MAX_CPU_NUM
+        || cpusetsize % core::mem::size_of::<usize>() != 0
+    {
         return EINVAL;
     }
     
     let affinity = {
         let inner = task.acquire_inner_lock();
-        inner.sched_entity.cpu_affinity
+        inner.sched_entity.allowed_cpus()
     };
     
     let token = current_user_token();
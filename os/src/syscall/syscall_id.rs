@@ -1,7 +1,9 @@
 pub const SYSCALL_GETCWD: usize = 17;
-pub const SYSCALL_DUP3: usize = 20;
+pub const SYSCALL_EPOLL_CREATE1: usize = 20;
+pub const SYSCALL_EPOLL_CTL: usize = 21;
+pub const SYSCALL_EPOLL_PWAIT: usize = 22;
 pub const SYSCALL_DUP: usize = 23;
-pub const SYSCALL_DUP2: usize = 24;
+pub const SYSCALL_DUP3: usize = 24;
 pub const SYSCALL_FCNTL: usize = 25;
 pub const SYSCALL_IOCTL: usize = 29;
 pub const SYSCALL_MKDIRAT: usize = 34;
@@ -9,10 +11,12 @@ pub const SYSCALL_UNLINKAT: usize = 35;
 pub const SYSCALL_LINKAT: usize = 37;
 pub const SYSCALL_UMOUNT2: usize = 39;
 pub const SYSCALL_MOUNT: usize = 40;
+pub const SYSCALL_PIVOT_ROOT: usize = 41;
 pub const SYSCALL_STATFS: usize = 43;
 pub const SYSCALL_FTRUNCATE: usize = 46;
 pub const SYSCALL_FACCESSAT: usize = 48;
 pub const SYSCALL_CHDIR: usize = 49;
+pub const SYSCALL_CHROOT: usize = 51;
 pub const SYSCALL_FCHMODAT: usize = 53;
 pub const SYSCALL_OPENAT: usize = 56;
 pub const SYSCALL_CLOSE: usize = 57;
@@ -28,24 +32,34 @@ pub const SYSCALL_PWRITE: usize = 68;
 pub const SYSCALL_SENDFILE: usize = 71;
 pub const SYSCALL_PSELECT6: usize = 72;
 pub const SYSCALL_PPOLL: usize = 73;
+pub const SYSCALL_SIGNALFD4: usize = 74;
 pub const SYSCALL_SPLICE: usize = 76;
 pub const SYSCALL_READLINKAT: usize = 78;
 pub const SYSCALL_FSTATAT: usize = 79;
 pub const SYSCALL_FSTAT: usize = 80;
 pub const SYSCALL_FSYNC: usize = 82;
 pub const SYSCALL_UTIMENSAT: usize = 88;
+pub const SYSCALL_ACCT: usize = 89;
 pub const SYSCALL_EXIT: usize = 93;
 pub const SYSCALL_EXIT_GROUP: usize = 94;
 pub const SYSCALL_SET_TID_ADDRESS: usize = 96;
+pub const SYSCALL_UNSHARE: usize = 97;
 pub const SYSCALL_FUTEX: usize = 98;
 pub const SYSCALL_SET_ROBUST_LIST: usize = 99;
 pub const SYSCALL_GET_ROBUST_LIST: usize = 100;
 pub const SYSCALL_NANOSLEEP: usize = 101;
 pub const SYSCALL_GETITIMER: usize = 102;
 pub const SYSCALL_SETITIMER: usize = 103;
+pub const SYSCALL_TIMER_CREATE: usize = 107;
+pub const SYSCALL_TIMER_GETTIME: usize = 108;
+pub const SYSCALL_TIMER_GETOVERRUN: usize = 109;
+pub const SYSCALL_TIMER_SETTIME: usize = 110;
+pub const SYSCALL_TIMER_DELETE: usize = 111;
+pub const SYSCALL_CLOCK_SETTIME: usize = 112;
 pub const SYSCALL_CLOCK_GETTIME: usize = 113;
 pub const SYSCALL_CLOCK_NANOSLEEP: usize = 115;
 pub const SYSCALL_SYSLOG: usize = 116;
+pub const SYSCALL_PTRACE: usize = 117;
 pub const SYSCALL_YIELD: usize = 124;
 pub const SYSCALL_KILL: usize = 129;
 pub const SYSCALL_TKILL: usize = 130;
@@ -56,13 +70,16 @@ pub const SYSCALL_SIGTIMEDWAIT: usize = 137;
 pub const SYSCALL_SIGRETURN: usize = 139;
 pub const SYSCALL_SETPRIORITY: usize = 140;
 pub const SYSCALL_GETPRIORITY: usize = 141;
+pub const SYSCALL_REBOOT: usize = 142;
 pub const SYSCALL_TIMES: usize = 153;
 pub const SYSCALL_SETPGID: usize = 154;
 pub const SYSCALL_GETPGID: usize = 155;
+pub const SYSCALL_GETSID: usize = 156;
 pub const SYSCALL_SETSID: usize = 157;
 pub const SYSCALL_UNAME: usize = 160;
 pub const SYSCALL_GETRUSAGE: usize = 165;
 pub const SYSCALL_UMASK: usize = 166;
+pub const SYSCALL_GETCPU: usize = 168;
 pub const SYSCALL_GET_TIME_OF_DAY: usize = 169;
 pub const SYSCALL_GETPID: usize = 172;
 pub const SYSCALL_GETPPID: usize = 173;
@@ -94,15 +111,21 @@ pub const SYSCALL_EXECVE: usize = 221;
 pub const SYSCALL_MMAP: usize = 222;
 pub const SYSCALL_MPROTECT: usize = 226;
 pub const SYSCALL_MSYNC: usize = 227;
+pub const SYSCALL_MINCORE: usize = 232;
 pub const SYSCALL_MADVISE: usize = 233;
 pub const SYSCALL_WAIT4: usize = 260; // wait is implemented as wait4(pid, status, options, 0) in pub lib.
 pub const SYSCALL_PRLIMIT: usize = 261;
+pub const SYSCALL_NAME_TO_HANDLE_AT: usize = 264;
+pub const SYSCALL_OPEN_BY_HANDLE_AT: usize = 265;
 pub const SYSCALL_RENAMEAT2: usize = 276;
 pub const SYSCALL_GETRANDOM: usize = 278;
 pub const SYSCALL_MEMBARRIER: usize = 283;
 pub const SYSCALL_COPY_FILE_RANGE: usize = 285;
 pub const SYSCALL_STATX: usize = 291;
+pub const SYSCALL_OPENAT2: usize = 437;
 pub const SYSCALL_FACCESSAT2: usize = 439;
+pub const SYSCALL_STATMOUNT: usize = 457;
+pub const SYSCALL_LISTMOUNT: usize = 458;
 
 // Scheduler syscalls
 pub const SYSCALL_SCHED_SETPARAM: usize = 118;
@@ -119,5 +142,7 @@ pub const SYSCALL_SCHED_GET_PRIORITY_MIN: usize = 126;
 pub const SYSCALL_LS: usize = 500;
 pub const SYSCALL_SHUTDOWN: usize = 501;
 pub const SYSCALL_CLEAR: usize = 502;
+pub const SYSCALL_WATCHPOINT: usize = 503;
 pub const SYSCALL_OPEN: usize = 506; //where?
+pub const SYSCALL_SCHED_YIELD_TO: usize = 507;
 pub const SYSCALL_GET_TIME: usize = 1690; //you mean get time of day by 169?
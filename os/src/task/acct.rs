@@ -0,0 +1,148 @@
+//! BSD 进程记账
+//!
+//! 用 acct(2) 指定记账文件后，每个进程（线程组的主线程）退出时向文件末尾追加一条
+//! [`AcctV3`] 记录，格式与 Linux 的 `struct acct_v3` 相同，可以用 `lastcomm` 等工具读取。
+//! 记账文件不可用（如已被删除或磁盘已满）时静默跳过这条记录。
+
+use super::TaskControlBlock;
+use crate::fs::FileDescriptor;
+use crate::timer::TimeVal;
+use spin::Mutex;
+
+/// 记录中的时间单位为 1/`AHZ` 秒
+pub const AHZ: usize = 100;
+/// 进程名的最大长度
+pub const ACCT_COMM: usize = 16;
+/// `ac_version` 字段，表示记录为 v3 格式
+const ACCT_VERSION: u8 = 3;
+
+/// 一条记账记录，共 64 字节，`comp_t` 类型的字段用 [`encode_comp_t`] 编码
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AcctV3 {
+    pub ac_flag: u8,
+    pub ac_version: u8,
+    pub ac_tty: u16,
+    /// 与 wait 得到的状态相同
+    pub ac_exitcode: u32,
+    pub ac_uid: u32,
+    pub ac_gid: u32,
+    pub ac_pid: u32,
+    pub ac_ppid: u32,
+    /// 进程创建的时间，单位为秒
+    pub ac_btime: u32,
+    /// 进程存活的时长，单位为 1/AHZ 秒，按 IEEE 754 单精度浮点编码
+    pub ac_etime: u32,
+    pub ac_utime: u16,
+    pub ac_stime: u16,
+    pub ac_mem: u16,
+    pub ac_io: u16,
+    pub ac_rw: u16,
+    pub ac_minflt: u16,
+    pub ac_majflt: u16,
+    pub ac_swaps: u16,
+    pub ac_comm: [u8; ACCT_COMM],
+}
+
+impl AcctV3 {
+    fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(
+                self as *const Self as *const u8,
+                core::mem::size_of::<Self>(),
+            )
+        }
+    }
+}
+
+/// 当前的记账文件，为 `None` 时不记账
+static ACCT_FILE: Mutex<Option<FileDescriptor>> = Mutex::new(None);
+
+/// 开始向 `file` 记账，为 `None` 时停止记账
+pub fn set_acct_file(file: Option<FileDescriptor>) {
+    *ACCT_FILE.lock() = file;
+}
+
+/// 将时长编码为 `comp_t`：13 位尾数与 3 位以 8 为底的指数
+pub fn encode_comp_t(mut value: usize) -> u16 {
+    const MANTSIZE: u32 = 13;
+    const EXPSIZE: u32 = 3;
+    const MAXFRACT: usize = (1 << MANTSIZE) - 1;
+    let mut exp = 0u16;
+    let mut rnd = 0;
+    while value > MAXFRACT {
+        rnd = value & (1 << (EXPSIZE - 1));
+        value >>= EXPSIZE;
+        exp += 1;
+    }
+    // 按被移出的最高位四舍五入
+    if rnd != 0 {
+        value += 1;
+        if value > MAXFRACT {
+            value >>= EXPSIZE;
+            exp += 1;
+        }
+    }
+    if exp > u16::MAX >> MANTSIZE {
+        return u16::MAX;
+    }
+    (exp << MANTSIZE) + value as u16
+}
+
+/// 不借助浮点运算将整数编码为单精度浮点数的位模式，多余的低位直接截断
+pub fn encode_float(mut value: u64) -> u32 {
+    if value == 0 {
+        return 0;
+    }
+    let mut exp = 190u32;
+    while (value as i64) > 0 {
+        value <<= 1;
+        exp -= 1;
+    }
+    ((value >> 40) as u32 & 0x7f_ffff) | (exp << 23)
+}
+
+fn to_ahz(time: TimeVal) -> usize {
+    time.tv_sec * AHZ + time.tv_usec / (1_000_000 / AHZ)
+}
+
+/// 进程退出时调用，记账开启时追加一条记录；线程退出不记账
+pub fn record_exit(task: &TaskControlBlock, exit_code: u32) {
+    if task.pid.0 != task.tgid {
+        return;
+    }
+    let acct_file = ACCT_FILE.lock();
+    let file = match acct_file.as_ref() {
+        Some(file) => file,
+        None => return,
+    };
+    let (rusage, ppid) = {
+        let inner = task.acquire_inner_lock();
+        let ppid = inner
+            .parent
+            .as_ref()
+            .and_then(|parent| parent.upgrade())
+            .map_or(0, |parent| parent.pid.0);
+        (inner.rusage, ppid)
+    };
+    let mut record = AcctV3 {
+        ac_version: ACCT_VERSION,
+        ac_exitcode: exit_code,
+        ac_pid: task.pid.0 as u32,
+        ac_ppid: ppid as u32,
+        ac_btime: task.start_time.tv_sec as u32,
+        ac_etime: encode_float(to_ahz(TimeVal::now() - task.start_time) as u64),
+        ac_utime: encode_comp_t(to_ahz(rusage.ru_utime)),
+        ac_stime: encode_comp_t(to_ahz(rusage.ru_stime)),
+        ..AcctV3::default()
+    };
+    // 进程名为可执行文件路径的最后一段
+    if let Some(path) = task.exe.lock().get_cwd() {
+        let name = path.rsplit('/').next().unwrap_or("").as_bytes();
+        let len = name.len().min(ACCT_COMM - 1);
+        record.ac_comm[..len].copy_from_slice(&name[..len]);
+    }
+    // 持锁追加，多个 CPU 上同时退出的进程不会写到同一位置
+    let mut offset = file.get_size();
+    file.write(Some(&mut offset), record.as_bytes());
+}
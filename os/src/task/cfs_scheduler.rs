@@ -38,9 +38,26 @@
 //! # Configuration
 //!
 //! The scheduler behavior can be tuned via constants:
-//! - `SCHED_LATENCY_NS`: Target latency for all tasks to run once
+//! - `SCHED_LATENCY_NS`: Target latency for all tasks to run once, stretched by
+//!   `sched_period` when more than `SCHED_LATENCY_NS / MIN_GRANULARITY_NS` are runnable
 //! - `MIN_GRANULARITY_NS`: Minimum time slice to avoid excessive context switches
 //! - `NICE_0_WEIGHT`: Base weight for nice value 0
+//!
+//! # Vruntime Normalization
+//!
+//! Vruntimes only grow, so once a run queue's `min_vruntime` passes
+//! `VRUNTIME_NORMALIZE_THRESHOLD` the queue subtracts it from every queued task
+//! and adds it to its own `vruntime_base`. Each entity remembers the base its
+//! vruntime is measured against and is rebased when it is enqueued again, on
+//! the same queue after sleeping or on another one after migrating.
+//!
+//! # First Come First Served
+//!
+//! With the `sched_fifo` feature the queue keeps its interface but orders tasks
+//! by arrival: every enqueued task is placed behind the last queued one, a
+//! running task gives way on the next tick whenever another one waits, and
+//! wakeups never preempt. Nice values are then ignored, which makes the feature
+//! a baseline to compare CFS against.
 
 use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
@@ -48,8 +65,12 @@ use alloc::vec::Vec;
 use core::cmp::Ordering;
 use core::sync::atomic::Ordering as AtomicOrdering;
 
+use crate::task::cpuset::{self, ROOT_CPUSET};
 use crate::task::TaskControlBlock;
 use crate::task::task::TASK_NOT_RUNNING;
+use crate::utils::telemetry::RUN_QUEUE_LENGTH;
+#[cfg(feature = "sched_debug")]
+use crate::task::sched_debug::{self, SchedEventKind};
 
 // ============================================================================
 // CFS Configuration Constants
@@ -59,6 +80,17 @@ use crate::task::task::TASK_NOT_RUNNING;
 /// This is the "period" over which CFS tries to be fair
 pub const SCHED_LATENCY_NS: u64 = 6_000_000; // 6ms
 
+/// `min_vruntime` at which a run queue rebases its vruntimes to 0,
+/// far enough below `u64::MAX` that no task can overflow before the next pick
+pub const VRUNTIME_NORMALIZE_THRESHOLD: u64 = 1 << 62;
+
+/// How far past the leftmost vruntime a gang pick may reach for a thread
+/// whose siblings are running on other CPUs
+pub const GANG_VRUNTIME_SLACK_NS: u64 = SCHED_LATENCY_NS;
+
+/// Queued tasks a gang pick looks at before falling back to the leftmost
+const GANG_SCAN_LIMIT: usize = 8;
+
 /// Minimum time slice per task (nanoseconds)
 /// Prevents excessive context switching with many tasks
 pub const MIN_GRANULARITY_NS: u64 = 750_000; // 0.75ms
@@ -70,6 +102,10 @@ pub const NICE_0_WEIGHT: u32 = 1024;
 /// Preemption granularity - minimum vruntime difference to preempt (nanoseconds)
 pub const WAKEUP_GRANULARITY_NS: u64 = 1_000_000; // 1ms
 
+/// A task that moved to another CPU more recently than this is still cache-warm
+/// there and is not stolen again (nanoseconds)
+pub const MIGRATION_COOLDOWN_NS: u64 = 5_000_000; // 5ms
+
 // ============================================================================
 // Nice Value to Weight Mapping
 // ============================================================================
@@ -158,6 +194,8 @@ impl SchedPolicy {
 pub struct SchedEntity {
     /// Virtual runtime - the key metric for CFS ordering
     pub vruntime: u64,
+    /// `vruntime_base` of the run queue `vruntime` is relative to
+    pub vruntime_base: u64,
     /// Nice value (-20 to 19)
     pub nice: i8,
     /// Cached weight from nice value
@@ -170,27 +208,49 @@ pub struct SchedEntity {
     pub prev_sum_exec_runtime: u64,
     /// Last CPU this task ran on (for wake-up affinity)
     pub last_cpu: usize,
+    /// When `last_cpu` last changed (nanoseconds), 0 if it never did
+    pub last_migration: u64,
     /// Scheduling policy (Normal, Fifo, RR, etc.)
     pub policy: SchedPolicy,
     /// Real-time priority (1-99, higher is more important)
     pub rt_priority: u8,
+    /// Policy set through `sched_setscheduler`; `policy` differs from it
+    /// only while a priority-inheritance boost is in effect
+    pub normal_policy: SchedPolicy,
+    /// Real-time priority set through `sched_setscheduler`
+    pub normal_rt_priority: u8,
+    /// Highest real-time priority among the waiters on PI futexes this task
+    /// owns, 0 when there is none
+    pub pi_priority: u8,
+    /// Switched out by preemption rather than by yielding, a RT task then goes
+    /// back to the head of its queue
+    pub preempted: bool,
     /// CPU affinity mask (bitmask of allowed CPUs)
     pub cpu_affinity: usize,
+    /// Cpuset the task belongs to, further restricting `cpu_affinity`
+    pub cpuset: usize,
 }
 
 impl Default for SchedEntity {
     fn default() -> Self {
         Self {
             vruntime: 0,
+            vruntime_base: 0,
             nice: 0,
             weight: NICE_0_WEIGHT,
             sum_exec_runtime: 0,
             exec_start: 0,
             prev_sum_exec_runtime: 0,
             last_cpu: 0,
+            last_migration: 0,
             policy: SchedPolicy::default(),
             rt_priority: 0,
+            normal_policy: SchedPolicy::default(),
+            normal_rt_priority: 0,
+            pi_priority: 0,
+            preempted: false,
             cpu_affinity: usize::MAX, // All CPUs allowed by default
+            cpuset: ROOT_CPUSET,
         }
     }
 }
@@ -205,22 +265,59 @@ impl SchedEntity {
         }
     }
     
-    /// Create new scheduling entity with RT policy and priority
-    pub fn new_rt(policy: SchedPolicy, priority: u8) -> Self {
+    /// Entity of a task forked from one with `parent`: it keeps the nice value,
+    /// affinity and cpuset but none of the runtime statistics
+    pub fn inherit(parent: &SchedEntity) -> Self {
         Self {
-            policy,
-            rt_priority: priority.min(99).max(1),
-            ..Default::default()
+            cpu_affinity: parent.cpu_affinity,
+            cpuset: parent.cpuset,
+            ..Self::new(parent.nice)
         }
     }
+
+    /// Create new scheduling entity with RT policy and priority
+    pub fn new_rt(policy: SchedPolicy, priority: u8) -> Self {
+        let mut entity = Self::default();
+        entity.set_policy(policy, priority);
+        entity
+    }
     
     /// Set the scheduling policy
     pub fn set_policy(&mut self, policy: SchedPolicy, priority: u8) {
-        self.policy = policy;
+        self.normal_policy = policy;
         if policy.is_realtime() {
-            self.rt_priority = priority.min(99).max(1);
+            self.normal_rt_priority = priority.min(99).max(1);
+        } else {
+            self.normal_rt_priority = 0;
+        }
+        self.update_effective_policy();
+    }
+
+    /// Set the priority inherited from PI futex waiters, 0 drops the boost
+    pub fn set_pi_priority(&mut self, priority: u8) {
+        self.pi_priority = priority.min(99);
+        self.update_effective_policy();
+    }
+
+    /// Whether the task runs with a priority inherited from a PI futex waiter
+    #[inline]
+    pub fn is_pi_boosted(&self) -> bool {
+        self.pi_priority > self.normal_rt_priority
+    }
+
+    /// The effective policy is the normal one unless an inherited priority is
+    /// higher, a boosted non-RT task then runs as SCHED_FIFO
+    fn update_effective_policy(&mut self) {
+        if self.is_pi_boosted() {
+            self.policy = if self.normal_policy.is_realtime() {
+                self.normal_policy
+            } else {
+                SchedPolicy::Fifo
+            };
+            self.rt_priority = self.pi_priority;
         } else {
-            self.rt_priority = 0;
+            self.policy = self.normal_policy;
+            self.rt_priority = self.normal_rt_priority;
         }
     }
     
@@ -229,11 +326,36 @@ impl SchedEntity {
     pub fn set_last_cpu(&mut self, cpu: usize) {
         self.last_cpu = cpu;
     }
+
+    /// Move the task to `cpu`, recording the time if it changes CPU
+    #[inline]
+    pub fn migrate_to(&mut self, cpu: usize, now: u64) {
+        if cpu != self.last_cpu {
+            self.last_cpu = cpu;
+            self.last_migration = now;
+        }
+    }
+
+    /// Whether the task changed CPU within the last `MIGRATION_COOLDOWN_NS`
+    #[inline]
+    pub fn recently_migrated(&self, now: u64) -> bool {
+        self.last_migration != 0 && now.saturating_sub(self.last_migration) < MIGRATION_COOLDOWN_NS
+    }
     
+    /// CPUs the task may run on: its affinity within its cpuset, or the whole
+    /// cpuset if the cpuset has since shrunk away from the affinity mask
+    pub fn allowed_cpus(&self) -> usize {
+        let cpuset = cpuset::cpus(self.cpuset);
+        match self.cpu_affinity & cpuset {
+            0 => cpuset,
+            allowed => allowed,
+        }
+    }
+
     /// Check if task is allowed to run on given CPU
     #[inline]
     pub fn can_run_on(&self, cpu: usize) -> bool {
-        (self.cpu_affinity & (1 << cpu)) != 0
+        (self.allowed_cpus() & (1 << cpu)) != 0
     }
     
     /// Set CPU affinity mask
@@ -260,6 +382,23 @@ impl SchedEntity {
         }
     }
 
+    /// Runtime since the task was last picked to run
+    #[inline]
+    pub fn slice_runtime(&self) -> u64 {
+        self.sum_exec_runtime.saturating_sub(self.prev_sum_exec_runtime)
+    }
+
+    /// Express `vruntime` relative to a run queue whose base is `base`
+    #[inline]
+    pub fn rebase(&mut self, base: u64) {
+        self.vruntime = if base >= self.vruntime_base {
+            self.vruntime.saturating_sub(base - self.vruntime_base)
+        } else {
+            self.vruntime.saturating_add(self.vruntime_base - base)
+        };
+        self.vruntime_base = base;
+    }
+
     /// Update runtime statistics after execution
     pub fn update_runtime(&mut self, now: u64) {
         if self.exec_start == 0 {
@@ -300,19 +439,57 @@ impl PartialOrd for RunQueueKey {
     }
 }
 
+/// Scheduling period for `nr_running` tasks: `SCHED_LATENCY_NS`, stretched to
+/// `nr_running * MIN_GRANULARITY_NS` once that is longer (Linux's `__sched_period`)
+pub const fn sched_period(nr_running: usize) -> u64 {
+    let stretched = nr_running as u64 * MIN_GRANULARITY_NS;
+    if stretched > SCHED_LATENCY_NS {
+        stretched
+    } else {
+        SCHED_LATENCY_NS
+    }
+}
+
+/// Share of the period for a task of `weight` among `nr_running` tasks weighing
+/// `total_weight` together, never below `MIN_GRANULARITY_NS`
+pub fn weighted_slice(weight: u64, total_weight: u64, nr_running: usize) -> u64 {
+    let slice = sched_period(nr_running) * weight / total_weight.max(1);
+    slice.max(MIN_GRANULARITY_NS)
+}
+
+/// A queued task with the weight it was enqueued with
+///
+/// The weight is kept so that removal subtracts exactly what enqueue added,
+/// even if the task's nice value changes while it is queued.
+struct QueuedTask {
+    task: Arc<TaskControlBlock>,
+    weight: u64,
+}
+
 /// CFS Run Queue using a BTreeMap for O(log n) operations
 /// 
 /// In Linux, this would be a red-black tree, but Rust's BTreeMap
 /// provides similar O(log n) guarantees with better cache locality.
+///
+/// `total_weight` is always the sum of the queued tasks' weights, the number of
+/// runnable tasks is the size of the tree. `keys` indexes the same tasks by tid,
+/// so finding a task's entry never scans the tree.
 pub struct CfsRunQueue {
     /// Tasks ordered by vruntime
-    tasks: BTreeMap<RunQueueKey, Arc<TaskControlBlock>>,
-    /// Minimum vruntime in the queue (for new task placement)
+    tasks: BTreeMap<RunQueueKey, QueuedTask>,
+    /// Key each queued task is filed under in `tasks`, by tid
+    keys: BTreeMap<usize, RunQueueKey>,
+    /// Vruntime of the last picked task, never decreasing (for task placement).
+    /// Only picks advance it, the picked task keeps running from there
     min_vruntime: u64,
+    /// Total subtracted from the vruntimes of this queue by normalization
+    vruntime_base: u64,
     /// Total weight of all runnable tasks
     total_weight: u64,
-    /// Number of runnable tasks
-    nr_running: usize,
+    /// CPU whose `RUN_QUEUE_LENGTH` gauge follows the queue length, if any
+    gauge_cpu: Option<usize>,
+    /// Task a directed yield asked to run next, ahead of the leftmost one
+    next: Option<RunQueueKey>,
 }
 
 impl Default for CfsRunQueue {
@@ -326,39 +503,87 @@ impl CfsRunQueue {
     pub fn new() -> Self {
         Self {
             tasks: BTreeMap::new(),
+            keys: BTreeMap::new(),
             min_vruntime: 0,
+            vruntime_base: 0,
             total_weight: 0,
-            nr_running: 0,
+            gauge_cpu: None,
+            next: None,
+        }
+    }
+
+    /// Make this the run queue of `cpu_id`: its length is published in the
+    /// `RUN_QUEUE_LENGTH` gauge on every enqueue and dequeue
+    pub fn report_length_for(&mut self, cpu_id: usize) {
+        self.gauge_cpu = Some(cpu_id);
+        self.update_length_gauge();
+    }
+
+    #[inline]
+    fn update_length_gauge(&self) {
+        if let Some(cpu_id) = self.gauge_cpu {
+            RUN_QUEUE_LENGTH.set(cpu_id, self.tasks.len() as u64);
         }
     }
 
     /// Check if the queue is empty
     #[inline]
     pub fn is_empty(&self) -> bool {
-        self.nr_running == 0
+        self.tasks.is_empty()
     }
 
     /// Get number of runnable tasks
     #[inline]
     pub fn len(&self) -> usize {
-        self.nr_running
+        self.tasks.len()
+    }
+
+    /// Sum of the weights of the queued tasks
+    #[inline]
+    pub fn total_weight(&self) -> u64 {
+        self.total_weight
+    }
+
+    /// Vruntime new and waking tasks are placed relative to
+    #[inline]
+    pub fn min_vruntime(&self) -> u64 {
+        self.min_vruntime
     }
 
-    /// Calculate time slice for a task based on its weight and total load
+    /// Time slice of a task of `weight` running on this queue's CPU alongside the
+    /// queued tasks
     pub fn calc_time_slice(&self, weight: u32) -> u64 {
-        if self.nr_running <= 1 {
-            return SCHED_LATENCY_NS;
+        let weight = weight as u64;
+        weighted_slice(weight, self.total_weight + weight, self.tasks.len() + 1)
+    }
+
+    /// Tick check for the task running on this queue's CPU, which is not queued
+    /// itself: preempt once it has used up its slice among the queued tasks, or
+    /// when the leftmost queued task lags far enough behind it.
+    /// A task alone on its CPU is never preempted.
+    #[cfg(not(feature = "sched_fifo"))]
+    pub fn tick_preempt(&self, curr: &SchedEntity) -> bool {
+        let next_vruntime = match self.tasks.first_key_value() {
+            Some((key, _)) => key.vruntime,
+            None => return false,
+        };
+        let ran = curr.slice_runtime();
+        if ran >= self.calc_time_slice(curr.weight) {
+            return true;
         }
+        ran >= MIN_GRANULARITY_NS
+            && next_vruntime.saturating_add(WAKEUP_GRANULARITY_NS) < curr.vruntime
+    }
 
-        // Time slice proportional to weight
-        let slice = (SCHED_LATENCY_NS * weight as u64) / self.total_weight.max(1);
-        
-        // Enforce minimum granularity
-        slice.max(MIN_GRANULARITY_NS)
+    /// First come first served: any queued task takes over on the next tick
+    #[cfg(feature = "sched_fifo")]
+    pub fn tick_preempt(&self, _curr: &SchedEntity) -> bool {
+        !self.tasks.is_empty()
     }
 
     /// Place a new task's vruntime appropriately
     /// New tasks get the current minimum vruntime to prevent starvation
+    #[cfg(not(feature = "sched_fifo"))]
     fn place_entity(&self, entity: &mut SchedEntity, initial: bool) {
         let mut vruntime = self.min_vruntime;
         
@@ -373,8 +598,19 @@ impl CfsRunQueue {
         entity.vruntime = entity.vruntime.max(vruntime);
     }
 
+    /// First come first served: every task queues up behind the last one
+    #[cfg(feature = "sched_fifo")]
+    fn place_entity(&self, entity: &mut SchedEntity, _initial: bool) {
+        entity.vruntime = self
+            .tasks
+            .last_key_value()
+            .map_or(self.min_vruntime, |(key, _)| key.vruntime)
+            + 1;
+    }
+
     /// Add a task to the run queue
     pub fn enqueue(&mut self, task: Arc<TaskControlBlock>, entity: &mut SchedEntity, is_new: bool) {
+        entity.rebase(self.vruntime_base);
         self.place_entity(entity, is_new);
         
         let key = RunQueueKey {
@@ -382,34 +618,142 @@ impl CfsRunQueue {
             tid: task.pid.0,
         };
         
-        self.tasks.insert(key, task);
-        self.total_weight += entity.weight as u64;
-        self.nr_running += 1;
+        #[cfg(feature = "sched_debug")]
+        sched_debug::record(SchedEventKind::Enqueue, key.tid, key.vruntime, 0);
+        // enqueueing a task twice replaces the old entry instead of counting it
+        // again; the entry is found by tid since placement moved its vruntime
+        if let Some(old) = self.keys.insert(key.tid, key) {
+            if let Some(queued) = self.tasks.remove(&old) {
+                self.total_weight -= queued.weight;
+            }
+        }
+        let weight = entity.weight as u64;
+        self.tasks.insert(key, QueuedTask { task, weight });
+        self.total_weight += weight;
+        self.update_length_gauge();
     }
 
-    /// Remove a task from the run queue
-    pub fn dequeue(&mut self, task: &Arc<TaskControlBlock>, entity: &SchedEntity) {
+    /// Take the task filed under `key` off the queue
+    fn take(&mut self, key: &RunQueueKey) -> Option<QueuedTask> {
+        let queued = self.tasks.remove(key)?;
+        self.keys.remove(&key.tid);
+        Some(queued)
+    }
+
+    /// Account for a task leaving the queue by any path
+    fn removed(&mut self, queued: &QueuedTask) {
+        self.total_weight -= queued.weight;
+        self.update_length_gauge();
+    }
+
+    /// Remove a task that has not been picked from the run queue, false if it
+    /// was not queued.
+    ///
+    /// Picking a task already takes it off the queue and adjusts the counters
+    /// once, so dequeuing a picked task finds nothing and changes nothing.
+    pub fn dequeue(&mut self, task: &Arc<TaskControlBlock>, entity: &SchedEntity) -> bool {
         let key = RunQueueKey {
             vruntime: entity.vruntime,
             tid: task.pid.0,
         };
         
-        if self.tasks.remove(&key).is_some() {
-            self.total_weight = self.total_weight.saturating_sub(entity.weight as u64);
-            self.nr_running = self.nr_running.saturating_sub(1);
+        match self.take(&key) {
+            Some(queued) => {
+                #[cfg(feature = "sched_debug")]
+                sched_debug::record(SchedEventKind::Dequeue, key.tid, key.vruntime, 0);
+                self.removed(&queued);
+                true
+            }
+            None => false,
         }
     }
 
-    /// Pick the task with the lowest vruntime (leftmost in the tree)
+    /// Pick the task with the lowest vruntime (leftmost in the tree), or the
+    /// buddy set by `set_next` if it is still queued
+    ///
+    /// The task leaves the queue here; it comes back through `enqueue` once
+    /// it is switched out while still runnable.
     pub fn pick_next(&mut self) -> Option<Arc<TaskControlBlock>> {
-        let (key, task) = self.tasks.pop_first()?;
-        
-        // Update min_vruntime
-        self.min_vruntime = self.min_vruntime.max(key.vruntime);
-        self.total_weight = self.total_weight.saturating_sub(NICE_0_WEIGHT as u64); // Approximate
-        self.nr_running = self.nr_running.saturating_sub(1);
+        if self.min_vruntime >= VRUNTIME_NORMALIZE_THRESHOLD {
+            self.normalize();
+        }
+        let leftmost = *self.tasks.first_key_value()?.0;
+        let key = self
+            .next
+            .take()
+            .filter(|next| self.tasks.contains_key(next))
+            .unwrap_or(leftmost);
+        let queued = self.take(&key)?;
+
+        // min_vruntime must not pass the leftmost task, even if the buddy ran
+        self.min_vruntime = self.min_vruntime.max(leftmost.vruntime);
+        self.removed(&queued);
+        #[cfg(feature = "sched_debug")]
+        sched_debug::record(SchedEventKind::Pick, key.tid, key.vruntime, 0);
         
-        Some(task)
+        Some(queued.task)
+    }
+
+    /// Have the queued task `tid` picked next regardless of its vruntime, false
+    /// if it is not queued here. Used by `sched_yield_to` to hand the CPU to
+    /// a lock holder; the buddy is dropped once picked or gone.
+    pub fn set_next(&mut self, tid: usize) -> bool {
+        self.next = self.keys.get(&tid).copied();
+        self.next.is_some()
+    }
+
+    /// Pick a thread of one of the `tgids` instead of the leftmost task, if one is
+    /// queued within `GANG_VRUNTIME_SLACK_NS` of it among the first few tasks.
+    /// Used by gang scheduling to run threads of a process at the same time.
+    pub fn pick_sibling(&mut self, tgids: &[usize]) -> Option<Arc<TaskControlBlock>> {
+        // a directed yield outranks gang scheduling
+        if self.next.is_some_and(|next| self.tasks.contains_key(&next)) {
+            return None;
+        }
+        let leftmost = self.tasks.first_key_value()?.0.vruntime;
+        let limit = leftmost.saturating_add(GANG_VRUNTIME_SLACK_NS);
+        let key = self
+            .tasks
+            .iter()
+            .take(GANG_SCAN_LIMIT)
+            .take_while(|(key, _)| key.vruntime <= limit)
+            .find(|(_, queued)| tgids.contains(&queued.task.tgid))
+            .map(|(key, _)| *key)?;
+        let queued = self.take(&key)?;
+        // the leftmost task is still queued, min_vruntime must not pass it
+        self.min_vruntime = self.min_vruntime.max(leftmost);
+        self.removed(&queued);
+        #[cfg(feature = "sched_debug")]
+        sched_debug::record(SchedEventKind::Pick, key.tid, key.vruntime, 0);
+        Some(queued.task)
+    }
+
+    /// Subtract `min_vruntime` from every queued task, keeping their order.
+    ///
+    /// Runs at a pick, when none of this CPU's runnable tasks is running; tasks
+    /// away from the queue are rebased when they come back.
+    fn normalize(&mut self) {
+        let base = self.min_vruntime;
+        let tasks = core::mem::take(&mut self.tasks);
+        self.keys.clear();
+        self.vruntime_base += base;
+        self.min_vruntime = 0;
+        if let Some(next) = self.next.as_mut() {
+            next.vruntime = next.vruntime.saturating_sub(base);
+        }
+        for (key, queued) in tasks {
+            let key = RunQueueKey {
+                vruntime: key.vruntime.saturating_sub(base),
+                tid: key.tid,
+            };
+            queued
+                .task
+                .acquire_inner_lock()
+                .sched_entity
+                .rebase(self.vruntime_base);
+            self.keys.insert(key.tid, key);
+            self.tasks.insert(key, queued);
+        }
     }
 
     /// Steal a task that can run on the target CPU (for work stealing)
@@ -419,13 +763,26 @@ impl CfsRunQueue {
     /// Safety: Only steals tasks with valid context (task_cx.ra != 0)
     /// Safety: Only steals tasks not currently running on any CPU
     /// Safety: Only steals tasks that have finished their context switch (on_cpu == false)
-    pub fn steal_for_cpu(&mut self, target_cpu: usize) -> Option<Arc<TaskControlBlock>> {
+    ///
+    /// Tasks that migrated within `MIGRATION_COOLDOWN_NS` of `now` are left where
+    /// they are, and so are tasks weighing more than `max_weight`; the stolen task
+    /// has its `last_cpu` moved to `target_cpu`.
+    pub fn steal_for_cpu(
+        &mut self,
+        target_cpu: usize,
+        now: u64,
+        max_weight: u64,
+    ) -> Option<Arc<TaskControlBlock>> {
         // Find a task that can run on target_cpu
         // We iterate from the back (highest vruntime = least urgent) for fairness
         let key_to_steal = self.tasks
             .iter()
             .rev()  // Start from highest vruntime (least urgent)
-            .find_map(|(key, task)| {
+            .find_map(|(key, QueuedTask { task, weight })| {
+                if *weight > max_weight {
+                    return None;
+                }
+
                 // 【关键安全检查1】检查任务是否正在进行上下文切换
                 // 参考 starry-mix: 等待 on_cpu 变为 false
                 if task.on_cpu.load(AtomicOrdering::Acquire) {
@@ -453,6 +810,11 @@ impl CfsRunQueue {
                     return None;
                 }
                 
+                // 刚迁移过的任务缓存仍在原 CPU 上，不再偷取
+                if inner.sched_entity.recently_migrated(now) {
+                    return None;
+                }
+
                 // 检查 CPU 亲和性
                 if inner.sched_entity.can_run_on(target_cpu) {
                     Some(*key)
@@ -462,51 +824,67 @@ impl CfsRunQueue {
             });
         
         if let Some(key) = key_to_steal {
-            if let Some(task) = self.tasks.remove(&key) {
-                // Update accounting
-                let weight = task.acquire_inner_lock().sched_entity.weight as u64;
-                self.total_weight = self.total_weight.saturating_sub(weight);
-                self.nr_running = self.nr_running.saturating_sub(1);
-                return Some(task);
+            if let Some(queued) = self.take(&key) {
+                self.removed(&queued);
+                queued
+                    .task
+                    .acquire_inner_lock()
+                    .sched_entity
+                    .migrate_to(target_cpu, now);
+                #[cfg(feature = "sched_debug")]
+                sched_debug::record(SchedEventKind::Migrate, key.tid, key.vruntime, target_cpu);
+                return Some(queued.task);
             }
         }
         
         None
     }
 
+    /// How far the vruntime of `entity`, queued here before, is ahead of
+    /// min_vruntime; a task moved to another queue keeps this lag there
+    pub fn lag_of(&self, entity: &SchedEntity) -> u64 {
+        let mut entity = *entity;
+        entity.rebase(self.vruntime_base);
+        entity.vruntime.saturating_sub(self.min_vruntime)
+    }
+
+    /// Enqueue a task taken off another CPU's queue `lag` ahead of min_vruntime,
+    /// see [`Self::lag_of`]
+    pub fn enqueue_migrated(
+        &mut self,
+        task: Arc<TaskControlBlock>,
+        entity: &mut SchedEntity,
+        lag: u64,
+    ) {
+        entity.vruntime_base = self.vruntime_base;
+        entity.vruntime = self.min_vruntime + lag;
+        self.enqueue(task, entity, false);
+    }
+
     /// Peek at the next task without removing it
     pub fn peek_next(&self) -> Option<&Arc<TaskControlBlock>> {
-        self.tasks.first_key_value().map(|(_, task)| task)
+        self.tasks.first_key_value().map(|(_, queued)| &queued.task)
     }
 
     /// Check if a waking task should preempt the current task
     pub fn should_preempt(&self, curr_entity: &SchedEntity, wake_entity: &SchedEntity) -> bool {
+        // first come first served never lets a waking task jump ahead
+        if cfg!(feature = "sched_fifo") {
+            return false;
+        }
         // The waking task should preempt if its vruntime is significantly less
         let vdiff = curr_entity.vruntime.saturating_sub(wake_entity.vruntime);
         vdiff > WAKEUP_GRANULARITY_NS
     }
 
-    /// Update min_vruntime from current queue state
-    fn update_min_vruntime(&mut self) {
-        if let Some((key, _)) = self.tasks.first_key_value() {
-            self.min_vruntime = self.min_vruntime.max(key.vruntime);
-        }
-    }
-
     /// Find task by PID
     pub fn find_by_pid(&self, pid: usize) -> Option<Arc<TaskControlBlock>> {
-        self.tasks
-            .values()
-            .find(|t| t.pid.0 == pid)
-            .cloned()
+        self.iter().find(|t| t.pid.0 == pid).cloned()
     }
 
     /// Find task by TGID
     pub fn find_by_tgid(&self, tgid: usize) -> Option<Arc<TaskControlBlock>> {
-        self.tasks
-            .values()
-            .find(|t| t.tgid == tgid)
-            .cloned()
+        self.iter().find(|t| t.tgid == tgid).cloned()
     }
 
     /// Remove all tasks with a given TGID (for thread group exit)
@@ -515,17 +893,14 @@ impl CfsRunQueue {
         let mut removed = Vec::new();
         let keys_to_remove: Vec<_> = self.tasks
             .iter()
-            .filter(|(_, task)| task.tgid == tgid)
+            .filter(|(_, queued)| queued.task.tgid == tgid)
             .map(|(key, _)| *key)
             .collect();
         
         for key in keys_to_remove {
-            if let Some(task) = self.tasks.remove(&key) {
-                // Update accounting
-                let weight = task.acquire_inner_lock().sched_entity.weight as u64;
-                self.total_weight = self.total_weight.saturating_sub(weight);
-                self.nr_running = self.nr_running.saturating_sub(1);
-                removed.push(task);
+            if let Some(queued) = self.take(&key) {
+                self.removed(&queued);
+                removed.push(queued.task);
             }
         }
         removed
@@ -538,22 +913,20 @@ impl CfsRunQueue {
     {
         let keys_to_remove: Vec<_> = self.tasks
             .iter()
-            .filter(|(_, task)| !f(task))
+            .filter(|(_, queued)| !f(&queued.task))
             .map(|(key, _)| *key)
             .collect();
         
         for key in keys_to_remove {
-            if let Some(task) = self.tasks.remove(&key) {
-                let weight = task.acquire_inner_lock().sched_entity.weight as u64;
-                self.total_weight = self.total_weight.saturating_sub(weight);
-                self.nr_running = self.nr_running.saturating_sub(1);
+            if let Some(queued) = self.take(&key) {
+                self.removed(&queued);
             }
         }
     }
 
     /// Get all tasks (for debugging)
     pub fn iter(&self) -> impl Iterator<Item = &Arc<TaskControlBlock>> {
-        self.tasks.values()
+        self.tasks.values().map(|queued| &queued.task)
     }
 }
 
@@ -562,18 +935,23 @@ impl CfsRunQueue {
 // ============================================================================
 
 /// Statistics for scheduler analysis and tuning
+///
+/// Each processor keeps one, updated on every switch away from a task,
+/// see `processor::cfs_stats` and `/proc/schedstat`
 #[derive(Debug, Default, Clone, Copy)]
 pub struct CfsStats {
     /// Number of context switches
     pub context_switches: u64,
-    /// Number of voluntary preemptions
+    /// Number of voluntary switches (yield, block or exit)
     pub voluntary_preempt: u64,
-    /// Number of involuntary preemptions (time slice expired)
+    /// Number of involuntary switches (preempted by a timer or other interrupt)
     pub involuntary_preempt: u64,
     /// Total time tasks spent waiting
     pub wait_time: u64,
     /// Total time tasks spent running
     pub run_time: u64,
+    /// Total time the CPU spent in its idle task
+    pub idle_time: u64,
 }
 
 impl CfsStats {
@@ -43,4 +43,17 @@ impl TaskContext {
             s: [0; 12],
         }
     }
+
+    /// Create a task context that starts a kernel thread at `entry`
+    ///
+    /// # Arguments
+    /// * `kstack_ptr` - Kernel stack pointer
+    /// * `entry` - Kernel function the thread begins in, never returns
+    pub fn goto_kernel_entry(kstack_ptr: usize, entry: fn() -> !) -> Self {
+        Self {
+            ra: entry as usize,
+            sp: kstack_ptr,
+            s: [0; 12],
+        }
+    }
 }
@@ -0,0 +1,145 @@
+//! Cpusets: confine groups of tasks to a subset of the CPUs
+//!
+//! Every task belongs to a cpuset, an index into a fixed table stored in its
+//! `SchedEntity`; forked processes and threads join their parent's. Cpuset 0 is
+//! the root: it spans all CPUs and never changes.
+//!
+//! Writing a CPU list such as `0-1` to `/sys/fs/cgroup/cpuset.cpus` from a task
+//! in the root moves the task into a new cpuset, which its later children join.
+//! Writing from a task already outside the root changes the CPUs of its cpuset,
+//! and with them those of every current member. A cpuset is recycled once its
+//! last member is gone.
+//!
+//! The scheduler only places a task on CPUs both in its affinity mask and in
+//! its cpuset, see `SchedEntity::allowed_cpus`.
+
+use crate::config::MAX_CPU_NUM;
+use crate::syscall::errno::{EINVAL, ENOSPC};
+use alloc::string::String;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Cpusets that can exist at the same time, the root included
+pub const MAX_CPUSETS: usize = 16;
+/// The cpuset every task starts in
+pub const ROOT_CPUSET: usize = 0;
+/// Mask of every CPU the kernel runs on
+pub const ALL_CPUS: usize = (1 << MAX_CPU_NUM) - 1;
+
+struct Cpuset {
+    /// Allowed CPUs as a bitmask
+    cpus: AtomicUsize,
+    /// Tasks in the cpuset, the slot is free when 0
+    members: AtomicUsize,
+}
+
+impl Cpuset {
+    const fn new() -> Self {
+        Self {
+            cpus: AtomicUsize::new(ALL_CPUS),
+            members: AtomicUsize::new(0),
+        }
+    }
+}
+
+static CPUSETS: [Cpuset; MAX_CPUSETS] = {
+    const CPUSET: Cpuset = Cpuset::new();
+    [CPUSET; MAX_CPUSETS]
+};
+
+/// CPUs of cpuset `id`
+pub fn cpus(id: usize) -> usize {
+    match id {
+        ROOT_CPUSET => ALL_CPUS,
+        id if id < MAX_CPUSETS => CPUSETS[id].cpus.load(Ordering::Acquire),
+        _ => ALL_CPUS,
+    }
+}
+
+/// A new task joins cpuset `id`
+pub fn join(id: usize) {
+    if id != ROOT_CPUSET && id < MAX_CPUSETS {
+        CPUSETS[id].members.fetch_add(1, Ordering::AcqRel);
+    }
+}
+
+/// A task of cpuset `id` is gone
+pub fn leave(id: usize) {
+    if id != ROOT_CPUSET && id < MAX_CPUSETS {
+        CPUSETS[id].members.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Give `cpus` to cpuset `id`, or to a new cpuset whose only member is the caller
+/// if `id` is the root. Returns the cpuset now holding `cpus`.
+pub fn assign(id: usize, cpus: usize) -> Result<usize, isize> {
+    let cpus = cpus & ALL_CPUS;
+    if cpus == 0 {
+        return Err(EINVAL);
+    }
+    if id != ROOT_CPUSET {
+        CPUSETS
+            .get(id)
+            .ok_or(EINVAL)?
+            .cpus
+            .store(cpus, Ordering::Release);
+        return Ok(id);
+    }
+    for (id, cpuset) in CPUSETS.iter().enumerate().skip(1) {
+        if cpuset
+            .members
+            .compare_exchange(0, 1, Ordering::AcqRel, Ordering::Relaxed)
+            .is_ok()
+        {
+            cpuset.cpus.store(cpus, Ordering::Release);
+            return Ok(id);
+        }
+    }
+    Err(ENOSPC)
+}
+
+/// Parse a CPU list such as `0-1,3` into a bitmask
+pub fn parse_cpu_list(list: &str) -> Result<usize, isize> {
+    let mut mask = 0usize;
+    for range in list.split(',') {
+        let (first, last) = match range.split_once('-') {
+            Some((first, last)) => (first, last),
+            None => (range, range),
+        };
+        let first: usize = first.trim().parse().map_err(|_| EINVAL)?;
+        let last: usize = last.trim().parse().map_err(|_| EINVAL)?;
+        if first > last || last >= usize::BITS as usize {
+            return Err(EINVAL);
+        }
+        for cpu in first..=last {
+            mask |= 1 << cpu;
+        }
+    }
+    Ok(mask)
+}
+
+/// Format a bitmask as a CPU list such as `0-1,3`
+pub fn format_cpu_list(mask: usize) -> String {
+    let mut list = String::new();
+    let mut cpu = 0;
+    while cpu < usize::BITS as usize {
+        if mask & (1 << cpu) == 0 {
+            cpu += 1;
+            continue;
+        }
+        let first = cpu;
+        while cpu + 1 < usize::BITS as usize && mask & (1 << (cpu + 1)) != 0 {
+            cpu += 1;
+        }
+        if !list.is_empty() {
+            list.push(',');
+        }
+        match cpu - first {
+            0 => write!(list, "{}", first),
+            _ => write!(list, "{}-{}", first, cpu),
+        }
+        .ok();
+        cpu += 1;
+    }
+    list
+}
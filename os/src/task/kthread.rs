@@ -0,0 +1,32 @@
+//! 内核线程
+//!
+//! 内核线程与用户任务一样由调度器调度，但从内核函数开始运行且从不返回用户态。
+//! 调度器切换到任务时关闭了中断，内核线程全程在关中断的状态下运行，
+//! 因此每次只应做少量工作，然后通过 [`sleep`] 让出 CPU。
+
+use super::TaskControlBlock;
+use super::{add_task, block_current_and_run_next, current_task, wait_with_timeout};
+use crate::timer::TimeSpec;
+use alloc::sync::Arc;
+
+/// 创建内核线程并加入就绪队列
+///
+/// 必须在 initproc 之后调用，否则内核线程会占用 initproc 的 pid
+pub fn spawn(name: &'static str, entry: fn() -> !) -> Arc<TaskControlBlock> {
+    let task = Arc::new(TaskControlBlock::new_kernel_thread(entry));
+    log::info!("[kthread] {} started, pid {}", name, task.pid.0);
+    add_task(task.clone());
+    task
+}
+
+/// 当前内核线程睡眠 `duration`，期间不占用 CPU
+pub fn sleep(duration: TimeSpec) {
+    let end = TimeSpec::now() + duration;
+    // 被信号等提前唤醒时继续睡眠
+    while TimeSpec::now() < end {
+        let task = current_task().unwrap();
+        wait_with_timeout(Arc::downgrade(&task), end);
+        drop(task);
+        block_current_and_run_next();
+    }
+}
@@ -11,8 +11,16 @@
     - 任务唤醒时优先回到 last_cpu（利用缓存亲和性）
     - 如果 last_cpu 忙，回退到当前 CPU
     - 新任务通过 clone/fork 自然分布到不同 CPU
+
+    开启 work_stealing feature 后，本地队列为空的 CPU 会按 nr_running 提示
+    从最繁忙的 CPU 偷取 CFS 任务，尝试次数有上限，两轮扫描之间短暂退避
+
+    开启 load_balance feature 后，时钟中断每隔 LOAD_BALANCE_INTERVAL_NS 比较一次各 CPU
+    的 CFS 负载，把任务从负载最重的 CPU 迁到最轻的 CPU，不必等到某个 CPU 空闲
 */
 use core::cmp::Ordering;
+#[cfg(feature = "load_balance")]
+use core::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
 
 #[cfg(feature = "oom_handler")]
 use crate::config::SYSTEM_TASK_LIMIT;
@@ -23,14 +31,18 @@ use crate::timer::TimeSpec;
 use crate::config::MAX_CPU_NUM;
 use crate::utils::InterruptGuard;
 
-use super::cfs_scheduler::CfsRunQueue;
+use super::cfs_scheduler::{CfsRunQueue, SchedEntity, SchedPolicy};
 use super::sched_class::{RtRunQueue, IdleRunQueue, get_sched_class, SchedClass};
+use super::posix_timer::PosixTimer;
 use super::TaskControlBlock;
 use alloc::collections::{BinaryHeap, VecDeque};
 use alloc::sync::{Arc, Weak};
 use lazy_static::*;
 use spin::Mutex;
-use crate::task::processor::current_cpu_id;
+use crate::task::processor::{
+    current_cpu_id, gang_sched_enabled, kick_cpu, resched_cpu, running_entity, running_tgids,
+};
+use crate::utils::telemetry::GANG_PICKS;
 
 #[cfg(feature = "oom_handler")]
 /// 任务的激活状态跟踪器
@@ -125,8 +137,13 @@ impl TaskManager {
     pub fn add(&mut self, task: Arc<TaskControlBlock>) {
         let mut inner = task.acquire_inner_lock();
         let sched_class = get_sched_class(&inner.sched_entity);
+        let preempted = core::mem::take(&mut inner.sched_entity.preempted);
         
         match sched_class {
+            // 被抢占的 RT 任务回到同优先级队列的队首，RR 任务时间片用完时才排到队尾
+            SchedClass::Rt if preempted && !RtRunQueue::slice_expired(&inner.sched_entity) => {
+                self.rt_rq.enqueue_front(task.clone(), &inner.sched_entity);
+            }
             SchedClass::Rt => {
                 self.rt_rq.enqueue(task.clone(), &inner.sched_entity);
             }
@@ -140,6 +157,14 @@ impl TaskManager {
         }
         drop(inner);
     }
+    /// 从就绪队列中移除任务 `task`，`entity` 是它入队时的调度实体，不在本队列中时返回 false
+    pub fn remove(&mut self, task: &Arc<TaskControlBlock>, entity: &SchedEntity) -> bool {
+        match get_sched_class(entity) {
+            SchedClass::Rt => self.rt_rq.dequeue(task, entity),
+            SchedClass::Cfs => self.cfs_rq.dequeue(task, entity),
+            SchedClass::Idle => self.idle_rq.dequeue(task),
+        }
+    }
     /// 从就绪队列中取出下一个任务（按优先级：RT -> CFS -> Idle）
     #[cfg(feature = "oom_handler")]
     pub fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
@@ -174,30 +199,94 @@ impl TaskManager {
         self.idle_rq.pick_next()
     }
     
-    /// 尝试从CFS队列偷取一个任务（用于Work Stealing）
-    /// 返回vruntime最大的任务（即最不紧急的任务）
-    pub fn steal_from_cfs(&mut self) -> Option<Arc<TaskControlBlock>> {
-        // 偷取CFS队列中vruntime最大的任务
-        // 这需要CfsRunQueue提供pop_last方法
-        // 暂时使用pick_next，后续可以优化
-        if self.cfs_rq.len() >= 2 {
-            self.cfs_rq.pick_next()
-        } else {
-            None
+    /// 组调度：没有 RT 任务时，优先取出与 `tgids` 同一线程组、vruntime 相差不大的 CFS 任务，
+    /// 让同一进程的线程同时在多个 CPU 上运行；没有合适的任务时与 [`Self::fetch`] 相同
+    pub fn fetch_gang(&mut self, tgids: &[usize]) -> Option<Arc<TaskControlBlock>> {
+        if self.rt_rq.is_empty() {
+            if let Some(task) = self.cfs_rq.pick_sibling(tgids) {
+                #[cfg(feature = "oom_handler")]
+                self.active_tracker.mark_active(task.pid.0);
+                GANG_PICKS.inc();
+                return Some(task);
+            }
         }
+        self.fetch()
     }
-    
-    /// 尝试从CFS队列偷取一个可以在指定CPU上运行的任务
-    /// 会检查任务的CPU亲和性，确保只偷取可以在目标CPU上运行的任务
+
+    /// 定向让出：下次从 CFS 队列取任务时优先取出线程 `tid`，它不在本队列中时返回 false
+    pub fn set_next_buddy(&mut self, tid: usize) -> bool {
+        self.cfs_rq.set_next(tid)
+    }
+
+    /// 尝试从CFS队列偷取一个可以在指定CPU上运行的任务（用于Work Stealing）
+    /// 会检查任务的CPU亲和性，确保只偷取可以在目标CPU上运行的任务，
+    /// 偷取只能经由此方法，以免绑定在某个CPU上的任务被其他CPU取走
     pub fn steal_from_cfs_for_cpu(&mut self, target_cpu: usize) -> Option<Arc<TaskControlBlock>> {
         if self.cfs_rq.len() < 2 {
             return None;
         }
         
-        // 使用带亲和性检查的偷取方法
-        self.cfs_rq.steal_for_cpu(target_cpu)
+        // 使用带亲和性检查的偷取方法，刚迁移过的任务不会再被偷走
+        self.cfs_rq
+            .steal_for_cpu(target_cpu, crate::timer::get_time_ns() as u64, u64::MAX)
     }
     
+    /// CFS 负载，`running` 是本队列所在 CPU 上正在运行的任务，它不在队列中
+    pub fn cfs_load(&self, running: Option<&SchedEntity>) -> CpuLoad {
+        let mut load = CpuLoad {
+            nr_running: self.cfs_rq.len(),
+            weight: self.cfs_rq.total_weight(),
+        };
+        if let Some(entity) = running.filter(|entity| get_sched_class(entity) == SchedClass::Cfs) {
+            load.nr_running += 1;
+            load.weight += entity.weight as u64;
+        }
+        load
+    }
+
+    /// 负载均衡：迁出一个可以在 `target_cpu` 上运行、权重不超过 `max_weight` 的 CFS 任务，
+    /// 同时返回它的 vruntime 超出本队列 min_vruntime 的部分
+    pub fn detach_for_balance(
+        &mut self,
+        target_cpu: usize,
+        max_weight: u64,
+    ) -> Option<(Arc<TaskControlBlock>, u64)> {
+        let task = self.cfs_rq.steal_for_cpu(
+            target_cpu,
+            crate::timer::get_time_ns() as u64,
+            max_weight,
+        )?;
+        let lag = self.cfs_rq.lag_of(&task.acquire_inner_lock().sched_entity);
+        Some((task, lag))
+    }
+
+    /// 加入由 [`Self::detach_for_balance`] 从其他 CPU 迁来的任务，保持它领先 min_vruntime 的部分
+    pub fn attach_balanced(&mut self, task: Arc<TaskControlBlock>, lag: u64) {
+        let mut inner = task.acquire_inner_lock();
+        self.cfs_rq
+            .enqueue_migrated(task.clone(), &mut inner.sched_entity, lag);
+    }
+
+    /// 时钟中断时，正在本 CPU 上运行的任务 `curr` 是否应被抢占：
+    /// CFS 任务在时间片用完或有 RT 任务等待时才让出；RT 任务只让给优先级更高的任务，
+    /// RR 任务时间片用完时还让给同优先级的任务；Idle 任务每个时钟中断让出一次
+    pub fn should_preempt_on_tick(&self, curr: &SchedEntity) -> bool {
+        match get_sched_class(curr) {
+            SchedClass::Cfs => !self.rt_rq.is_empty() || self.cfs_rq.tick_preempt(curr),
+            SchedClass::Rt => self.rt_rq.tick_preempt(curr),
+            SchedClass::Idle => true,
+        }
+    }
+    /// 刚被唤醒的任务 `wake` 是否应立即抢占本队列所在 CPU 上正在运行的 `curr`：
+    /// RT 任务抢占非 RT 任务与优先级更低的 RT 任务，CFS 任务在 vruntime 落后足够多时抢占
+    pub fn should_preempt_on_wakeup(&self, curr: &SchedEntity, wake: &SchedEntity) -> bool {
+        match (get_sched_class(wake), get_sched_class(curr)) {
+            (SchedClass::Rt, _) => self.rt_rq.should_preempt(curr, wake),
+            (SchedClass::Cfs, SchedClass::Cfs) => self.cfs_rq.should_preempt(curr, wake),
+            (SchedClass::Cfs, SchedClass::Idle) => true,
+            _ => false,
+        }
+    }
     /// 获取总任务数
     pub fn total_count(&self) -> usize {
         self.rt_rq.len() + self.cfs_rq.len() + self.idle_rq.len()
@@ -260,8 +349,8 @@ impl TaskManager {
     pub fn interruptible_count(&self) -> u16 {
         self.interruptible_queue.len() as u16
     }
-    /// 这个函数会将`task`从`interruptible_queue`中删除，并加入`ready_queue`。
-    /// 如果一切正常的话，这个`task`将会被加入`ready_queue`。如果`task`已经被唤醒，那么什么也不会发生。
+    /// 这个函数会将`task`从`interruptible_queue`中删除，并加入其调度类的就绪队列。
+    /// 如果一切正常的话，这个`task`将会被加入就绪队列。如果`task`已经被唤醒，那么什么也不会发生。
     /// # 注意
     /// 这个函数不会改变`task_status`，你应该手动改变它以保持一致性。
     pub fn wake_interruptible(&mut self, task: Arc<TaskControlBlock>) {
@@ -373,8 +462,10 @@ lazy_static! {
     /// 每个元素对应一个 CPU 核的 TaskManager
     pub static ref TASK_MANAGERS: Vec<Mutex<TaskManager>> = {
         let mut v = Vec::new();
-        for _ in 0..MAX_CPU_NUM {
-            v.push(Mutex::new(TaskManager::new()));
+        for cpu_id in 0..MAX_CPU_NUM {
+            let mut manager = TaskManager::new();
+            manager.cfs_rq.report_length_for(cpu_id);
+            v.push(Mutex::new(manager));
         }
         v
     };
@@ -384,25 +475,43 @@ lazy_static! {
 pub fn add_task(task: Arc<TaskControlBlock>) {
     let _guard = InterruptGuard::new();
     
-    // Wake-up Affinity: 优先使用任务上次运行的CPU
-    let last_cpu = {
+    // Wake-up Affinity: 优先使用任务上次运行的CPU，但不能超出亲和性与 cpuset
+    let (last_cpu, allowed) = {
         let inner = task.acquire_inner_lock();
-        inner.sched_entity.last_cpu
+        (inner.sched_entity.last_cpu, inner.sched_entity.allowed_cpus())
     };
     
     let current_cpu = current_cpu_id();
     
     // 如果last_cpu有效且可用，尝试将任务添加到last_cpu
-    if last_cpu < MAX_CPU_NUM && last_cpu != current_cpu {
+    if last_cpu < MAX_CPU_NUM && last_cpu != current_cpu && allowed & (1 << last_cpu) != 0 {
         // 使用try_lock避免死锁
         if let Some(mut manager) = TASK_MANAGERS[last_cpu].try_lock() {
             manager.add(task);
+            drop(manager);
+            kick_cpu(last_cpu);
             return;
         }
     }
     
-    // Fallback: 添加到当前CPU
-    TASK_MANAGERS[current_cpu].lock().add(task);
+    // Fallback: 添加到当前CPU，当前CPU不允许时添加到允许的第一个CPU
+    let target_cpu = fallback_cpu(allowed, current_cpu);
+    task.acquire_inner_lock()
+        .sched_entity
+        .migrate_to(target_cpu, crate::timer::get_time_ns() as u64);
+    TASK_MANAGERS[target_cpu].lock().add(task);
+    if target_cpu != current_cpu {
+        kick_cpu(target_cpu);
+    }
+}
+
+/// 无法放到 last_cpu 时任务的去处：`allowed` 含当前CPU时为当前CPU，否则为其中编号最小的CPU
+pub fn fallback_cpu(allowed: usize, current_cpu: usize) -> usize {
+    if allowed & (1 << current_cpu) != 0 {
+        current_cpu
+    } else {
+        allowed.trailing_zeros() as usize
+    }
 }
 
 /// 添加任务到指定CPU的队列（用于work stealing后的re-add）
@@ -410,6 +519,7 @@ pub fn add_task_to_cpu(task: Arc<TaskControlBlock>, cpu_id: usize) {
     let _guard = InterruptGuard::new();
     if cpu_id < MAX_CPU_NUM {
         TASK_MANAGERS[cpu_id].lock().add(task);
+        kick_cpu(cpu_id);
     } else {
         TASK_MANAGERS[current_cpu_id()].lock().add(task);
     }
@@ -425,8 +535,16 @@ pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
         panic!("[fetch_task] Invalid cpu_id {} (tp register corrupted)!", cpu_id);
     }
     
-    // 1. 尝试从本地获取
-    let task = TASK_MANAGERS[cpu_id].lock().fetch();
+    // 1. 尝试从本地获取，开启组调度时优先选其他 CPU 上正在运行的线程组
+    let task = if gang_sched_enabled() {
+        let tgids = running_tgids(cpu_id);
+        TASK_MANAGERS[cpu_id].lock().fetch_gang(&tgids)
+    } else {
+        TASK_MANAGERS[cpu_id].lock().fetch()
+    };
+    // 2. 本地为空时从其他 CPU 偷取
+    #[cfg(feature = "work_stealing")]
+    let task = task.or_else(|| steal_task(cpu_id));
     // 返回本地任务或 None
     // 
     // 【关于 Work Stealing 的决定】
@@ -446,6 +564,175 @@ pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
     task
 }
 
+/// 一轮扫描中最多尝试加锁的 CPU 数
+#[cfg(feature = "work_stealing")]
+pub const MAX_STEAL_ATTEMPTS: usize = 2;
+/// 扫描轮数，两轮之间退避
+#[cfg(feature = "work_stealing")]
+pub const STEAL_ROUNDS: usize = 2;
+/// 第一次退避的自旋次数，此后每轮加倍
+#[cfg(feature = "work_stealing")]
+const STEAL_BACKOFF_SPINS: usize = 64;
+
+/// 可偷取（至少两个 CFS 任务）且本轮未尝试过的最繁忙 CPU，
+/// 队列长度取自无需加锁即可读取的 `RUN_QUEUE_LENGTH`，只作为提示
+#[cfg(feature = "work_stealing")]
+fn busiest_cpu(cpu_id: usize, tried: usize) -> Option<usize> {
+    use crate::utils::telemetry::RUN_QUEUE_LENGTH;
+    (0..MAX_CPU_NUM)
+        .filter(|&victim| victim != cpu_id && tried & (1 << victim) == 0)
+        .map(|victim| (victim, RUN_QUEUE_LENGTH.get(victim)))
+        .filter(|&(_, nr_running)| nr_running >= 2)
+        .max_by_key(|&(_, nr_running)| nr_running)
+        .map(|(victim, _)| victim)
+}
+
+/// 为 `cpu_id` 偷取一个任务：每轮按提示从最繁忙的 CPU 开始 try_lock，
+/// 最多尝试 `MAX_STEAL_ATTEMPTS` 个，所有提示都表明无任务可偷时不加任何锁
+#[cfg(feature = "work_stealing")]
+fn steal_task(cpu_id: usize) -> Option<Arc<TaskControlBlock>> {
+    use crate::utils::telemetry::{WORK_STEALS, WORK_STEAL_ATTEMPTS};
+    for round in 0..STEAL_ROUNDS {
+        let mut tried = 0;
+        for _ in 0..MAX_STEAL_ATTEMPTS {
+            let victim = match busiest_cpu(cpu_id, tried) {
+                Some(victim) => victim,
+                None if tried == 0 => return None,
+                None => break,
+            };
+            tried |= 1 << victim;
+            WORK_STEAL_ATTEMPTS.inc();
+            if let Some(mut manager) = TASK_MANAGERS[victim].try_lock() {
+                let task = manager.steal_from_cfs_for_cpu(cpu_id);
+                if task.is_some() {
+                    WORK_STEALS.inc();
+                    return task;
+                }
+            }
+        }
+        for _ in 0..STEAL_BACKOFF_SPINS << round {
+            core::hint::spin_loop();
+        }
+    }
+    None
+}
+
+/// 一个 CPU 上 CFS 任务的负载，周期性负载均衡据此选出迁出与迁入的 CPU
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuLoad {
+    /// 排队与正在运行的 CFS 任务数
+    pub nr_running: usize,
+    /// 这些任务的权重之和
+    pub weight: u64,
+}
+
+/// 两次周期性负载均衡之间的间隔
+#[cfg(feature = "load_balance")]
+pub const LOAD_BALANCE_INTERVAL_NS: u64 = 20_000_000; // 20ms
+/// 一次负载均衡最多迁移的任务数
+#[cfg(feature = "load_balance")]
+const MAX_BALANCE_MIGRATIONS: usize = 4;
+/// 下一次负载均衡的时间，先到的 CPU 通过 CAS 推迟它并执行这一轮
+#[cfg(feature = "load_balance")]
+static NEXT_BALANCE_NS: AtomicU64 = AtomicU64::new(0);
+
+/// 从各 CPU 的负载中选出迁出任务的 CPU（权重最大且至少有两个 CFS 任务）与迁入任务的 CPU
+/// （权重最小），同时返回两者的权重之差；锁不到的 CPU 为 `None`，不参与比较。
+/// 迁移权重不超过差值一半的任务总能缩小差距，因此两个 CPU 不会互相来回迁移
+pub fn balance_pair(loads: &[Option<CpuLoad>]) -> Option<(usize, usize, u64)> {
+    let known = || {
+        loads
+            .iter()
+            .enumerate()
+            .filter_map(|(cpu_id, load)| Some((cpu_id, (*load)?)))
+    };
+    let (busiest, busiest_load) = known()
+        .filter(|(_, load)| load.nr_running >= 2)
+        .max_by_key(|(_, load)| load.weight)?;
+    let (idlest, idlest_load) = known().min_by_key(|(_, load)| load.weight)?;
+    let imbalance = busiest_load.weight.checked_sub(idlest_load.weight)?;
+    if busiest == idlest || imbalance == 0 {
+        return None;
+    }
+    Some((busiest, idlest, imbalance))
+}
+
+/// 时钟中断时调用：每隔 `LOAD_BALANCE_INTERVAL_NS` 由先到的 CPU 做一轮负载均衡
+#[cfg(feature = "load_balance")]
+pub fn load_balance_tick() {
+    let now = crate::timer::get_time_ns() as u64;
+    let next = NEXT_BALANCE_NS.load(AtomicOrdering::Relaxed);
+    if now < next
+        || NEXT_BALANCE_NS
+            .compare_exchange(
+                next,
+                now + LOAD_BALANCE_INTERVAL_NS,
+                AtomicOrdering::Relaxed,
+                AtomicOrdering::Relaxed,
+            )
+            .is_err()
+    {
+        return;
+    }
+    let _guard = InterruptGuard::new();
+    load_balance();
+}
+
+/// `online` 中各 CPU 的 CFS 负载；其余 CPU 为 `None`，不参与均衡：
+/// 没有启动的 CPU 队列为空、权重为 0，否则总会被选为迁入任务的 CPU
+#[cfg(feature = "load_balance")]
+pub fn cpu_loads(online: impl IntoIterator<Item = usize>) -> [Option<CpuLoad>; MAX_CPU_NUM] {
+    let mut loads = [None; MAX_CPU_NUM];
+    for cpu_id in online {
+        let running = running_entity(cpu_id);
+        loads[cpu_id] = TASK_MANAGERS[cpu_id]
+            .try_lock()
+            .map(|manager| manager.cfs_load(running.as_ref()));
+    }
+    loads
+}
+
+/// 把任务从 CFS 负载最重的 CPU 迁到最轻的 CPU，只迁移亲和性允许在目标 CPU 上运行的任务，
+/// 锁不到的队列本轮跳过，返回迁移的任务数
+#[cfg(feature = "load_balance")]
+fn load_balance() -> usize {
+    use crate::utils::telemetry::{online_cpus, BALANCE_MIGRATIONS};
+    let loads = cpu_loads(online_cpus());
+    let (busiest, idlest, mut imbalance) = match balance_pair(&loads) {
+        Some(pair) => pair,
+        None => return 0,
+    };
+    let mut migrated = 0;
+    while migrated < MAX_BALANCE_MIGRATIONS {
+        let detached = TASK_MANAGERS[busiest]
+            .try_lock()
+            .and_then(|mut manager| manager.detach_for_balance(idlest, imbalance / 2));
+        let (task, lag) = match detached {
+            Some(detached) => detached,
+            None => break,
+        };
+        let weight = task.acquire_inner_lock().sched_entity.weight as u64;
+        TASK_MANAGERS[idlest].lock().attach_balanced(task, lag);
+        imbalance = imbalance.saturating_sub(2 * weight);
+        migrated += 1;
+    }
+    if migrated > 0 {
+        BALANCE_MIGRATIONS.add(migrated as u64);
+        kick_cpu(idlest);
+    }
+    migrated
+}
+
+/// 所有 CPU 都空闲时偷取不应尝试任何加锁，返回偷取结果与尝试次数
+#[cfg(all(feature = "work_stealing", feature = "selftest"))]
+pub fn steal_attempts_when_idle() -> (bool, u64) {
+    use crate::utils::telemetry::WORK_STEAL_ATTEMPTS;
+    let _guard = InterruptGuard::new();
+    let before = WORK_STEAL_ATTEMPTS.get();
+    let stolen = steal_task(current_cpu_id()).is_some();
+    (stolen, WORK_STEAL_ATTEMPTS.get() - before)
+}
+
 #[cfg(feature = "oom_handler")]
 pub fn do_oom(req: usize) -> Result<(), ()> {
     let _guard = InterruptGuard::new();
@@ -517,6 +804,15 @@ pub fn find_task_by_tgid(tgid: usize) -> Option<Arc<TaskControlBlock>> {
     None
 }
 
+/// 让就绪队列中的线程 `tid` 成为所在 CPU 下一个运行的 CFS 任务，返回该 CPU；
+/// 线程不在任何 CFS 就绪队列中（正在运行、睡眠或不存在）时返回 `None`
+pub fn set_next_buddy(tid: usize) -> Option<usize> {
+    let _guard = InterruptGuard::new();
+    TASK_MANAGERS
+        .iter()
+        .position(|manager| manager.lock().set_next_buddy(tid))
+}
+
 /*todo()
 // 在 TCB 中记录 CPU ID（更高效） 在 TaskControlBlock 结构体中增加 pub last_cpu: usize 字段。
 在 add_task 或 sleep 时更新 last_cpu。
@@ -546,10 +842,16 @@ pub fn wake_interruptible(task: Arc<TaskControlBlock>) {
     loop {
         let mut all_checked = true;
         
-        for manager in TASK_MANAGERS.iter() {
+        for (cpu_id, manager) in TASK_MANAGERS.iter().enumerate() {
             // 使用 try_lock 避免阻塞等待其他 CPU 的锁
             if let Some(mut manager) = manager.try_lock() {
                 if manager.try_wake_interruptible(Arc::clone(&task)).is_ok() {
+                    let preempt = wakeup_preempts(&manager, cpu_id, &task);
+                    drop(manager);
+                    kick_cpu(cpu_id);
+                    if preempt {
+                        resched_cpu(cpu_id);
+                    }
                     return; // 成功唤醒
                 }
             } else {
@@ -568,6 +870,70 @@ pub fn wake_interruptible(task: Arc<TaskControlBlock>) {
     }
 }
 
+/// 任务刚被唤醒进 `cpu_id` 的队列 `manager`，它是否应抢占该 CPU 上正在运行的任务。
+/// 调用者持有队列锁，任务或处理器的锁被占用时放弃抢占，留给时钟中断处理
+fn wakeup_preempts(manager: &TaskManager, cpu_id: usize, task: &Arc<TaskControlBlock>) -> bool {
+    let wake = match task.try_acquire_inner_lock() {
+        Some(inner) => inner.sched_entity,
+        None => return false,
+    };
+    match running_entity(cpu_id) {
+        Some(curr) => manager.should_preempt_on_wakeup(&curr, &wake),
+        None => false,
+    }
+}
+
+/// 修改任务的调度策略与实时优先级，任务在就绪队列中时移到新调度类对应的队列
+pub fn set_sched_policy(task: &Arc<TaskControlBlock>, policy: SchedPolicy, priority: u8) {
+    update_sched_entity(task, |entity| entity.set_policy(policy, priority));
+}
+
+/// 设置任务从 PI futex 等待者继承的实时优先级，0 表示取消提升
+pub fn set_pi_priority(task: &Arc<TaskControlBlock>, priority: u8) {
+    update_sched_entity(task, |entity| entity.set_pi_priority(priority));
+}
+
+/// 用 `f` 修改任务的调度实体，任务在就绪队列中时先出队再按新的调度类入队
+fn update_sched_entity(task: &Arc<TaskControlBlock>, f: impl FnOnce(&mut SchedEntity)) {
+    let _guard = InterruptGuard::new();
+    let owner = task.acquire_inner_lock().sched_entity.last_cpu;
+    for (cpu_id, manager) in TASK_MANAGERS.iter().enumerate() {
+        if cpu_id == owner {
+            continue;
+        }
+        let mut manager = manager.lock();
+        let old = task.acquire_inner_lock().sched_entity;
+        if manager.remove(task, &old) {
+            f(&mut task.acquire_inner_lock().sched_entity);
+            manager.add(task.clone());
+            return;
+        }
+    }
+    // 任务所属 CPU 的队列最后检查，并持有它的锁修改调度实体：任务正在运行或在睡眠时，
+    // 修改与唤醒回该 CPU 的入队互斥，下次入队时进入新的队列
+    let mut manager = TASK_MANAGERS[owner].lock();
+    let old = task.acquire_inner_lock().sched_entity;
+    let queued = manager.remove(task, &old);
+    f(&mut task.acquire_inner_lock().sched_entity);
+    if queued {
+        manager.add(task.clone());
+    }
+}
+
+/// 时钟中断时当前 CPU 上正在运行的任务是否应被抢占
+pub fn should_preempt_on_tick(curr: &SchedEntity) -> bool {
+    let _guard = InterruptGuard::new();
+    TASK_MANAGERS[current_cpu_id()]
+        .lock()
+        .should_preempt_on_tick(curr)
+}
+
+/// 指定 CPU 的就绪队列是否非空
+pub fn has_ready_task(cpu_id: usize) -> bool {
+    let _guard = InterruptGuard::new();
+    TASK_MANAGERS[cpu_id].lock().ready_count() != 0
+}
+
 /// 返回就绪队列中的任务数量
 pub fn procs_count() -> u16 {
     let _guard = InterruptGuard::new();
@@ -683,6 +1049,9 @@ impl WaitQueue {
                     // 建议改回使用当前 manager 的方法，避免死锁。
                     
                      if manager.try_wake_interruptible(task.clone()).is_ok() {
+                        if wakeup_preempts(&manager, cpu_id, &task) {
+                            resched_cpu(cpu_id);
+                        }
                         cnt += 1;
                      } else {
                         // 如果任务不在当前核，我们需要释放当前锁去唤醒其他核吗？
@@ -711,11 +1080,19 @@ impl WaitQueue {
     }
 }
 
-/// 表示一个等待超时的任务
+/// 超时后要处理的对象
+enum TimeoutTarget {
+    /// 唤醒睡眠的任务
+    Task(Weak<TaskControlBlock>),
+    /// POSIX 定时器到期，带着设置定时器时的代数，定时器重新设置或删除后这一项作废
+    Timer(Weak<PosixTimer>, u64),
+}
+
+/// 表示一个等待超时的任务或定时器
 pub struct TimeoutWaiter {
-    /// 任务的弱引用
-    task: Weak<TaskControlBlock>,
-    /// 任务超时时间
+    /// 超时后要处理的对象
+    target: TimeoutTarget,
+    /// 超时时间
     timeout: TimeSpec,
 }
 
@@ -757,10 +1134,21 @@ impl TimeoutWaitQueue {
     /// 这个函数会将一个`task`添加到`WaitQueue`但是**不会**阻塞这个任务，
     /// 如果想要阻塞一个`task`，使用`block_current_and_run_next()`函数
     pub fn add_task(&mut self, task: Weak<TaskControlBlock>, timeout: TimeSpec) {
-        self.inner.push(TimeoutWaiter { task, timeout });
+        self.inner.push(TimeoutWaiter {
+            target: TimeoutTarget::Task(task),
+            timeout,
+        });
+    }
+    /// 在 `timeout` 时让第 `generation` 代的定时器 `timer` 到期
+    pub fn add_timer(&mut self, timer: Weak<PosixTimer>, generation: u64, timeout: TimeSpec) {
+        self.inner.push(TimeoutWaiter {
+            target: TimeoutTarget::Timer(timer, generation),
+            timeout,
+        });
     }
-    /// 唤醒所有超时的任务
-    pub fn wake_expired(&mut self, now: TimeSpec) {
+    /// 唤醒所有超时的任务，返回到期的定时器，由调用者释放队列锁后处理
+    pub fn wake_expired(&mut self, now: TimeSpec) -> Vec<(Arc<PosixTimer>, u64)> {
+        let mut timers = Vec::new();
         // 入口日志，确认中断是否触发
         // log::info!("[wake_expired] Enter. Checking expired tasks...");
         // 获取任务管理器
@@ -783,8 +1171,18 @@ impl TimeoutWaitQueue {
                 break;
             // 唤醒超时任务
             } else {
+                let task = match waiter.target {
+                    TimeoutTarget::Task(task) => task,
+                    TimeoutTarget::Timer(timer, generation) => {
+                        // 定时器已被删除时直接丢弃
+                        if let Some(timer) = timer.upgrade() {
+                            timers.push((timer, generation));
+                        }
+                        continue;
+                    }
+                };
                 // 将弱引用升级为强引用
-                match waiter.task.upgrade() {
+                match task.upgrade() {
                     Some(task) => {
                         // ==== 修改开始 ====
                         let pid = task.pid.0;
@@ -819,6 +1217,8 @@ impl TimeoutWaitQueue {
                              drop(manager);
                              super::wake_interruptible(task);
                              manager = TASK_MANAGERS[cpu_id].lock();
+                        } else if wakeup_preempts(&manager, cpu_id, &task) {
+                            resched_cpu(cpu_id);
                         }
                     }
                     // task is dead, just ignore
@@ -830,6 +1230,7 @@ impl TimeoutWaitQueue {
             }
         }
         // log::info!("[wake_expired] Finished. Unlocking TASK_MANAGERS.");
+        timers
     }
     #[allow(unused)]
     // debug use only
@@ -853,10 +1254,21 @@ pub fn wait_with_timeout(task: Weak<TaskControlBlock>, timeout: TimeSpec) {
     queue.add_task(task, timeout);
 }
 
-/// 唤醒全局超时等待队列中所有已超时的任务
-pub fn do_wake_expired() {
+/// 在 `timeout` 时让第 `generation` 代的定时器 `timer` 到期
+pub fn arm_timer(timer: Weak<PosixTimer>, generation: u64, timeout: TimeSpec) {
     let _guard = InterruptGuard::new();
     TIMEOUT_WAITQUEUE
         .lock()
-        .wake_expired(crate::timer::TimeSpec::now());
+        .add_timer(timer, generation, timeout);
+}
+
+/// 唤醒全局超时等待队列中所有已超时的任务，并处理到期的定时器
+pub fn do_wake_expired() {
+    let _guard = InterruptGuard::new();
+    let now = crate::timer::TimeSpec::now();
+    let timers = TIMEOUT_WAITQUEUE.lock().wake_expired(now);
+    // 定时器发送信号与重新设置都要再获取队列锁与任务管理器的锁
+    for (timer, generation) in timers {
+        timer.expire(generation, now);
+    }
 }
\ No newline at end of file
@@ -1,10 +1,17 @@
+pub mod acct;
 mod context;
 pub mod cfs_scheduler;
+pub mod cpuset;
 mod elf;
+pub mod kthread;
 mod manager;
 pub mod pid;
+pub mod posix_timer;
 pub mod processor;
+pub mod ptrace;
 pub mod sched_class;
+#[cfg(feature = "sched_debug")]
+pub mod sched_debug;
 pub mod signal;
 pub mod state_machine;
 pub mod task;
@@ -15,6 +22,7 @@ use crate::hal::__switch;
 use crate::{
     fs::{OpenFlags, ROOT_FD},
     mm::translated_refmut,
+    syscall::errno::{ENOENT, ENOEXEC},
     utils::InterruptGuard,
 };
 use alloc::{collections::VecDeque, sync::Arc};
@@ -23,14 +31,25 @@ pub use elf::{load_elf_interp, AuxvEntry, AuxvType, ELFInfo};
 use lazy_static::*;
 use log::warn;
 use manager::fetch_task;
+use crate::utils::telemetry::DIRECTED_YIELDS;
 pub use manager::{
-    add_task, do_oom, do_wake_expired, find_task_by_pid, find_task_by_tgid, procs_count,
+    add_task, do_oom, do_wake_expired, find_task_by_pid, find_task_by_tgid, has_ready_task,
+    procs_count, set_sched_policy,
     sleep_interruptible, wait_with_timeout, wake_interruptible,
 };
+#[cfg(feature = "selftest")]
+pub use manager::{balance_pair, fallback_cpu, CpuLoad, TaskManager};
+#[cfg(feature = "load_balance")]
+pub use manager::load_balance_tick;
+#[cfg(all(feature = "work_stealing", feature = "selftest"))]
+pub use manager::steal_attempts_when_idle;
+#[cfg(all(feature = "load_balance", feature = "selftest"))]
+pub use manager::cpu_loads;
 // pub use pid::RecycleAllocator;
 pub use pid::{pid_alloc, trap_cx_bottom_from_tid, ustack_bottom_from_tid, PidHandle};
 pub use processor::{
     current_task, current_trap_cx, current_user_token, run_tasks, schedule, take_current_task,
+    try_current_task,
 };
 pub use signal::*;
 pub use task::{RobustList, Rusage, TaskControlBlock, TaskStatus};
@@ -50,7 +69,66 @@ pub fn try_yield() {
     }
 }
 
+/// 当前任务主动让出 CPU（yield 或等待资源时轮询），记为自愿切换
 pub fn suspend_current_and_run_next() {
+    switch_out_current(true);
+}
+
+/// 定向让出：让就绪的线程 `tid`（如持有锁的线程）成为所在 CPU 下一个运行的任务，
+/// 它在其他 CPU 上排队时要求那个 CPU 立即重新调度，然后当前任务让出 CPU。
+/// 线程正在运行或睡眠时与普通的让出相同，线程不存在时不让出并返回 false
+pub fn yield_to(tid: usize) -> bool {
+    match manager::set_next_buddy(tid) {
+        Some(cpu_id) => {
+            DIRECTED_YIELDS.inc();
+            if cpu_id != current_cpu_id() {
+                processor::request_resched(cpu_id);
+            }
+        }
+        None if find_task_by_pid(tid).is_none() => return false,
+        None => {}
+    }
+    suspend_current_and_run_next();
+    true
+}
+
+/// 中断抢占当前任务，记为非自愿切换
+pub fn preempt_current_and_run_next() {
+    switch_out_current(false);
+}
+
+/// 时钟中断时调用：把运行时间记到当前任务上，只有时间片用完
+/// 或有更应运行的任务在等待时才抢占，同一 CPU 上只有它一个任务时继续运行
+pub fn task_tick() {
+    let preempt = {
+        let _guard = InterruptGuard::new();
+        let task = match current_task() {
+            Some(task) => task,
+            None => return,
+        };
+        let entity = {
+            let mut inner = task.acquire_inner_lock();
+            inner
+                .sched_entity
+                .update_runtime(crate::timer::get_time_ns() as u64);
+            inner.sched_entity
+        };
+        manager::should_preempt_on_tick(&entity)
+    };
+    if preempt {
+        preempt_current_and_run_next();
+    }
+}
+
+/// 返回用户态前调用：唤醒的任务要求抢占本 CPU 时让出
+pub fn resched_if_needed() {
+    if processor::take_need_resched() && current_task().is_some() {
+        preempt_current_and_run_next();
+    }
+}
+
+/// 将当前任务放回就绪队列并切换到调度循环
+fn switch_out_current(voluntary: bool) {
     let _guard = InterruptGuard::new();
     let cpu_id = processor::current_cpu_id();
 
@@ -59,6 +137,7 @@ pub fn suspend_current_and_run_next() {
             let mut task_inner = task.acquire_inner_lock();
             let ptr = &mut task_inner.task_cx as *mut TaskContext;
             task_inner.task_status = TaskStatus::Ready;
+            task_inner.sched_entity.preempted = !voluntary;
             ptr
         };
 
@@ -70,7 +149,7 @@ pub fn suspend_current_and_run_next() {
             processor.set_pending(task);
         }
         
-        schedule(task_cx_ptr);
+        schedule(task_cx_ptr, voluntary);
         
         // Debug: check ra after resuming from schedule (RISC-V only)
         #[cfg(target_arch = "riscv64")]
@@ -78,7 +157,7 @@ pub fn suspend_current_and_run_next() {
             let ra_after: usize;
             unsafe { core::arch::asm!("mv {}, ra", out(reg) ra_after); }
             if ra_after == 0 {
-                panic!("[CPU {}] suspend_current_and_run_next: ra=0 after schedule()!", cpu_id);
+                panic!("[CPU {}] switch_out_current: ra=0 after schedule()!", cpu_id);
             }
         }
     }
@@ -108,13 +187,53 @@ pub fn block_current_and_run_next() {
     // 在 schedule 之后, run_tasks 会检测到 pending_task
     // 但 block 不是加入 ready 队列，而是加入 interruptible 队列
     // 所以需要标记这个 task 是要 block 而不是 ready
-    schedule(task_cx_ptr);
+    schedule(task_cx_ptr, true);
+}
+
+/// 当前任务被停止信号 `signal` 停止：记下给父进程 wait4 的停止状态并通知父进程，
+/// 然后睡眠直到被 SIGCONT 或 SIGKILL 唤醒；这两个信号已经挂起时不睡眠
+pub fn stop_current_and_run_next(signal: Signals) {
+    let task = current_task().unwrap();
+    task.acquire_inner_lock().stop_report = Some((signal.to_signum().unwrap() as u32) << 8 | 0x7f);
+    notify_parent(&task);
+    drop(task);
+    sleep_stopped(|inner| {
+        inner
+            .sigpending
+            .intersects(Signals::SIGCONT | Signals::SIGKILL)
+    });
+}
+
+/// 当前任务进入停止态并让出处理器，直到被唤醒；`resumed` 为真时不睡眠
+fn sleep_stopped(resumed: impl FnOnce(&task::TaskControlBlockInner) -> bool) {
+    let _guard = InterruptGuard::new();
+    let cpu_id = processor::current_cpu_id();
+    let task = take_current_task().unwrap();
+    let task_cx_ptr = {
+        let mut task_inner = task.acquire_inner_lock();
+        let ptr = &mut task_inner.task_cx as *mut TaskContext;
+        // 检查与设置状态在同一把锁下，唤醒者要么看到停止态并唤醒它，要么在此之前已让 `resumed` 成立
+        task_inner.task_status = if resumed(&task_inner) {
+            TaskStatus::Ready
+        } else {
+            TaskStatus::Stopped
+        };
+        ptr
+    };
+    processor::PROCESSORS[cpu_id].lock().set_pending(task);
+    schedule(task_cx_ptr, true);
 }
 
 pub fn do_exit(task: Arc<TaskControlBlock>, exit_code: u32) {
     // 多核安全重构：避免嵌套锁导致死锁
     // 策略：分阶段执行，每阶段只持有一把锁
     
+    crate::hal::clear_watchpoint(task.pid.0);
+    acct::record_exit(&task, exit_code);
+    crate::fs::dev::tty::release_ctty_on_exit(&task);
+    // 退出的任务不再使用挂载命名空间，最后一个任务退出后 pivot_root 占用的目录可以删除
+    *task.mnt_ns.lock() = crate::fs::mount::INIT_MNT_NS.clone();
+
     // === 阶段1：收集需要的信息并设置基本状态 ===
     let (need_signal_parent, parent_task_opt, children_to_move, clear_child_tid, user_token) = {
         let mut inner = task.acquire_inner_lock();
@@ -170,6 +289,9 @@ pub fn do_exit(task: Arc<TaskControlBlock>, exit_code: u32) {
         for child in children_to_move.iter() {
             let mut child_inner = child.acquire_inner_lock();
             child_inner.parent = Some(Arc::downgrade(&INITPROC));
+            drop(child_inner);
+            // 跟踪者退出，被跟踪的子进程脱离跟踪
+            ptrace::detach(child, 0);
         }
         
         // 然后更新 initproc 的子任务列表
@@ -193,7 +315,9 @@ pub fn do_exit(task: Arc<TaskControlBlock>, exit_code: u32) {
         }
     }
     
-    // === 阶段4：处理 clear_child_tid (futex) ===
+    // === 阶段4：释放持有的 robust futex 与 PI futex，处理 clear_child_tid (futex) ===
+    let robust_list_head = task.acquire_inner_lock().robust_list.head;
+    threads::exit_robust_futexes(&task, user_token, robust_list_head);
     if clear_child_tid != 0 {
         log::debug!(
             "[do_exit] do futex wake on clear_child_tid: {:X}",
@@ -233,7 +357,7 @@ pub fn exit_current_and_run_next(exit_code: u32) -> ! {
     do_exit(task, exit_code);
     // we do not have to save task context
     let mut _unused = TaskContext::zero_init();
-    schedule(&mut _unused as *mut _);
+    schedule(&mut _unused as *mut _, true);
     panic!("Unreachable");
 }
 
@@ -275,15 +399,51 @@ pub fn exit_group_and_run_next(exit_code: u32) -> ! {
     }
     
     let mut _unused = TaskContext::zero_init();
-    schedule(&mut _unused as *mut _);
+    schedule(&mut _unused as *mut _, true);
     panic!("Unreachable");
 }
 
 lazy_static! {
-    pub static ref INITPROC: Arc<TaskControlBlock> = Arc::new({
-        let elf = ROOT_FD.open("initproc", OpenFlags::O_RDONLY, true).unwrap();
-        TaskControlBlock::new(elf)
-    });
+    pub static ref INITPROC: Arc<TaskControlBlock> = Arc::new(load_initproc());
+}
+
+/// 加载第一个用户程序，失败时打印诊断信息后关机，而不是直接 panic
+fn load_initproc() -> TaskControlBlock {
+    let cmdline = crate::cmdline::get();
+    let path = cmdline.init_path();
+    let elf = match ROOT_FD.open(path, OpenFlags::O_RDONLY, true) {
+        Ok(elf) => elf,
+        Err(ENOENT) => initproc_failed(path, "not found", ENOENT),
+        Err(errno) => initproc_failed(path, "cannot be opened", errno),
+    };
+    match TaskControlBlock::new(elf) {
+        Ok(task) => task,
+        Err(ENOEXEC) => initproc_failed(path, "not a valid ELF executable", ENOEXEC),
+        Err(errno) => initproc_failed(path, "cannot be loaded", errno),
+    }
+}
+
+/// 列出根目录，帮助判断是文件系统镜像不对还是 `init=` 写错
+fn initproc_failed(path: &str, reason: &str, errno: isize) -> ! {
+    println!(
+        "[kernel] cannot start init process \"{}\": {} ({})",
+        path, reason, errno
+    );
+    println!("[kernel] contents of /:");
+    match ROOT_FD.file.open_subfile() {
+        Ok(entries) => {
+            for (name, file) in entries {
+                println!(
+                    "[kernel]     {}{}",
+                    name,
+                    if file.is_dir() { "/" } else { "" }
+                );
+            }
+        }
+        Err(errno) => println!("[kernel]     cannot be listed ({})", errno),
+    }
+    println!("[kernel] use init=<path> on the kernel command line to start another program");
+    crate::hal::shutdown()
 }
 
 pub fn add_initproc() {
@@ -304,4 +464,6 @@ pub fn init_task_subsystem() {
     let _ = PROCESSORS.len();
     let _ = TASK_MANAGERS.len();
     let _ = TIMEOUT_WAITQUEUE.lock();
+    // 空闲任务是内核线程，同样须在 initproc 之后创建
+    processor::init_idle_tasks();
 }
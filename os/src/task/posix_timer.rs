@@ -0,0 +1,266 @@
+//! POSIX 间隔定时器 (timer_create)
+//!
+//! 每个进程有自己的定时器表，线程之间共享，fork 出的子进程不继承，execve 时清空。
+//! 定时器的到期时间与睡眠的超时放在同一个全局超时等待队列中，由时钟中断里的
+//! `do_wake_expired` 处理：到期时向进程或 `SIGEV_THREAD_ID` 指定的线程发送信号，
+//! 上一个信号还没有递送时只增加超限计数 (`timer_getoverrun`)。
+//! 到期时间都按启动以来的时间计，CLOCK_REALTIME 定时器的绝对到期时刻在设置时换算，
+//! 之后 clock_settime 不会移动已经启动的定时器。
+
+use super::manager::{arm_timer, find_task_by_tgid};
+use super::{send_signal, Signals, TaskControlBlock, TaskStatus};
+use crate::syscall::errno::*;
+use crate::timer::{ITimerSpec, TimeSpec};
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use spin::Mutex;
+
+pub const CLOCK_REALTIME: usize = 0;
+pub const CLOCK_MONOTONIC: usize = 1;
+pub const CLOCK_REALTIME_COARSE: usize = 5;
+
+/// 到期时发送 `sigev_signo` 给进程
+pub const SIGEV_SIGNAL: i32 = 0;
+/// 到期时不通知
+pub const SIGEV_NONE: i32 = 1;
+/// 由 C 库创建线程调用通知函数，内核中与 `SIGEV_SIGNAL` 相同
+pub const SIGEV_THREAD: i32 = 2;
+/// 到期时发送 `sigev_signo` 给线程 `sigev_tid`
+pub const SIGEV_THREAD_ID: i32 = 4;
+
+/// `timer_settime` 的 `it_value` 是绝对时间
+pub const TIMER_ABSTIME: u32 = 1;
+
+/// 超限计数的上限 (`DELAYTIMER_MAX`)
+const DELAYTIMER_MAX: usize = i32::MAX as usize;
+/// 每个进程最多的定时器数
+const TIMER_MAX: usize = 256;
+
+/// `struct sigevent`
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct SigEvent {
+    pub sigev_value: usize,
+    pub sigev_signo: i32,
+    pub sigev_notify: i32,
+    /// `SIGEV_THREAD_ID` 的目标线程，与 `SIGEV_THREAD` 的函数指针共用
+    pub sigev_tid: i32,
+    _pad: [i32; 11],
+}
+
+/// 定时器可变的状态
+struct TimerState {
+    /// 下一次到期的时间，`None` 表示未启动
+    expires: Option<TimeSpec>,
+    /// 周期，为 0 时只到期一次
+    interval: TimeSpec,
+    /// 每次设置或删除定时器加一，队列中旧的到期项随之作废
+    generation: u64,
+    /// 最近一次发送信号以来，信号尚未递送时又到期的次数
+    overrun: usize,
+}
+
+/// 一个 POSIX 定时器
+pub struct PosixTimer {
+    /// 创建定时器时指定的时钟
+    clock: usize,
+    /// 到期时发送的信号，`SIGEV_NONE` 时为 `None`
+    signal: Option<Signals>,
+    /// 接收信号的线程，对进程的通知是创建定时器的线程
+    target: Weak<TaskControlBlock>,
+    /// 对进程的通知在创建定时器的线程退出后发给线程组 `tgid` 中的其他线程
+    tgid: Option<usize>,
+    state: Mutex<TimerState>,
+}
+
+impl PosixTimer {
+    /// 定时器的时钟
+    pub fn clock(&self) -> usize {
+        self.clock
+    }
+
+    /// 剩余时间与周期 (`timer_gettime`)
+    pub fn get(&self) -> ITimerSpec {
+        let state = self.state.lock();
+        ITimerSpec {
+            it_interval: state.interval,
+            // 刚到期、还未被处理时剩余时间为 0
+            it_value: state
+                .expires
+                .map_or(TimeSpec::new(), |expires| expires - TimeSpec::now()),
+        }
+    }
+
+    /// 在绝对时间 `expires` 启动定时器，之后以 `interval` 为周期；`expires` 为 0 时停止定时器。
+    /// 返回原来的剩余时间与周期
+    pub fn set(self: &Arc<Self>, expires: TimeSpec, interval: TimeSpec) -> ITimerSpec {
+        let old = self.get();
+        let mut state = self.state.lock();
+        state.generation += 1;
+        state.interval = interval;
+        state.overrun = 0;
+        if expires.is_zero() {
+            state.expires = None;
+        } else {
+            state.expires = Some(expires);
+            arm_timer(Arc::downgrade(self), state.generation, expires);
+        }
+        old
+    }
+
+    /// 停止定时器，队列中的到期项不再生效
+    fn disarm(&self) {
+        let mut state = self.state.lock();
+        state.generation += 1;
+        state.expires = None;
+    }
+
+    /// 最近一次发送的信号的超限计数 (`timer_getoverrun`)
+    pub fn overrun(&self) -> usize {
+        self.state.lock().overrun
+    }
+
+    /// 第 `generation` 代的到期项在 `now` 到期：周期定时器重新启动，错过的周期计入超限，
+    /// 再向目标发送信号；目标的这个信号还挂起着时只增加超限计数
+    pub fn expire(self: &Arc<Self>, generation: u64, now: TimeSpec) {
+        let mut state = self.state.lock();
+        let expires = match state.expires {
+            Some(expires) if state.generation == generation => expires,
+            _ => return,
+        };
+        let mut missed = 0;
+        if state.interval.is_zero() {
+            state.expires = None;
+        } else {
+            let interval = state.interval.to_ns();
+            missed = (now - expires).to_ns() / interval;
+            let next = expires + TimeSpec::from_ns(interval * (missed + 1));
+            state.expires = Some(next);
+            arm_timer(Arc::downgrade(self), generation, next);
+        }
+        let signal = match self.signal {
+            Some(signal) => signal,
+            None => return,
+        };
+        let target = match self.target() {
+            Some(target) => target,
+            None => return,
+        };
+        if target.acquire_inner_lock().sigpending.contains(signal) {
+            state.overrun = (state.overrun + missed + 1).min(DELAYTIMER_MAX);
+            return;
+        }
+        state.overrun = missed.min(DELAYTIMER_MAX);
+        drop(state);
+        send_signal(target, signal);
+    }
+
+    /// 接收信号的线程
+    fn target(&self) -> Option<Arc<TaskControlBlock>> {
+        let target = self
+            .target
+            .upgrade()
+            .filter(|task| task.acquire_inner_lock().task_status != TaskStatus::Zombie);
+        match (target, self.tgid) {
+            (Some(target), _) => Some(target),
+            (None, Some(tgid)) => find_task_by_tgid(tgid),
+            (None, None) => None,
+        }
+    }
+}
+
+/// 进程的定时器表，按定时器 id 索引
+pub struct PosixTimers {
+    timers: BTreeMap<usize, Arc<PosixTimer>>,
+}
+
+impl PosixTimers {
+    pub fn new() -> Self {
+        Self {
+            timers: BTreeMap::new(),
+        }
+    }
+
+    /// 为 `task` 创建时钟为 `clock` 的定时器，`event` 为 `None` 时到期向进程发送 SIGALRM；
+    /// 返回定时器 id
+    /// # 错误
+    /// + `EINVAL`：通知方式或信号无效，或 `SIGEV_THREAD_ID` 的线程不在当前线程组中
+    /// + `EAGAIN`：定时器数达到上限
+    pub fn create(
+        &mut self,
+        task: &Arc<TaskControlBlock>,
+        clock: usize,
+        event: Option<SigEvent>,
+    ) -> Result<usize, isize> {
+        let (signum, notify, tid) = match event {
+            Some(event) => (
+                event.sigev_signo as usize,
+                event.sigev_notify,
+                event.sigev_tid,
+            ),
+            None => (Signals::SIGALRM.to_signum().unwrap(), SIGEV_SIGNAL, 0),
+        };
+        let signal = match notify {
+            SIGEV_NONE => None,
+            SIGEV_SIGNAL | SIGEV_THREAD | SIGEV_THREAD_ID => match Signals::from_signum(signum) {
+                Ok(signal) if signum != 0 => Some(signal),
+                _ => return Err(EINVAL),
+            },
+            _ => return Err(EINVAL),
+        };
+        let (target, tgid) = if notify == SIGEV_THREAD_ID {
+            match super::find_task_by_pid(tid as usize) {
+                Some(thread) if tid > 0 && thread.tgid == task.tgid => {
+                    (Arc::downgrade(&thread), None)
+                }
+                _ => return Err(EINVAL),
+            }
+        } else {
+            (Arc::downgrade(task), Some(task.tgid))
+        };
+        if self.timers.len() >= TIMER_MAX {
+            return Err(EAGAIN);
+        }
+        let id = (0..).find(|id| !self.timers.contains_key(id)).unwrap();
+        self.timers.insert(
+            id,
+            Arc::new(PosixTimer {
+                clock,
+                signal,
+                target,
+                tgid,
+                state: Mutex::new(TimerState {
+                    expires: None,
+                    interval: TimeSpec::new(),
+                    generation: 0,
+                    overrun: 0,
+                }),
+            }),
+        );
+        Ok(id)
+    }
+
+    /// id 为 `id` 的定时器
+    pub fn get(&self, id: usize) -> Option<Arc<PosixTimer>> {
+        self.timers.get(&id).cloned()
+    }
+
+    /// 删除定时器，返回它是否存在
+    pub fn delete(&mut self, id: usize) -> bool {
+        match self.timers.remove(&id) {
+            Some(timer) => {
+                timer.disarm();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 删除所有定时器 (execve)
+    pub fn clear(&mut self) {
+        for timer in self.timers.values() {
+            timer.disarm();
+        }
+        self.timers.clear();
+    }
+}
@@ -1,14 +1,21 @@
 use super::__switch;
-use super::{fetch_task, add_task, sleep_interruptible, TaskStatus};
+use super::{fetch_task, add_task, has_ready_task, sleep_interruptible, TaskStatus};
 use super::{TaskContext, TaskControlBlock};
 use super::task::TASK_NOT_RUNNING;
-use crate::hal::{TrapContext, disable_interrupts, restore_interrupts};
+use crate::hal::{TrapContext, disable_interrupts, idle_wait, restore_interrupts, wake_cpu};
+use super::cfs_scheduler::{CfsStats, SchedEntity};
+use crate::utils::telemetry::{
+    run_queue_imbalance, CONTEXT_SWITCHES, IDLE_WAKEUP_IPIS, RUN_QUEUE_LENGTH,
+    WAKEUP_PREEMPTIONS,
+};
 use crate::timer::get_time_ns;
+use alloc::string::String;
 use alloc::sync::Arc;
+use core::fmt::Write;
 use lazy_static::*;
 use spin::Mutex;
 use core::arch::asm;
-use core::sync::atomic::Ordering;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use crate::config::MAX_CPU_NUM;
 use alloc::vec::Vec;
 
@@ -16,11 +23,15 @@ use alloc::vec::Vec;
 pub struct Processor {
     /// 当前正在运行的任务
     current: Option<Arc<TaskControlBlock>>,
-    /// 空闲任务的上下文，用于在任务切换时保存和恢复状态
+    /// 调度循环的上下文，任务（包括空闲任务）让出 CPU 时切换回这里
     idle_task_cx: TaskContext,
+    /// 本 CPU 的空闲任务，没有就绪任务时运行，它不进入任何就绪队列
+    idle_task: Option<Arc<TaskControlBlock>>,
     /// 等待被加入就绪队列的任务（上下文已保存，等待被重新调度）
     /// 用于解决多核竞争问题：任务上下文保存后才能被其他CPU偷取
     pending_task: Option<Arc<TaskControlBlock>>,
+    /// 本 CPU 的调度统计，见 `/proc/schedstat`
+    stats: CfsStats,
 }
 
 impl Processor {
@@ -31,8 +42,10 @@ impl Processor {
             current: None,
             // 空闲任务的上下文
             idle_task_cx: TaskContext::zero_init(),
+            idle_task: None,
             // 等待加入队列的任务
             pending_task: None,
+            stats: CfsStats::default(),
         }
     }
     /// 获取空闲任务的上下文指针
@@ -75,6 +88,89 @@ lazy_static! {
     };
 }
 
+/// 各 CPU 是否正处于空闲等待（见 [`run_tasks`]）
+static CPU_IDLE: [AtomicBool; MAX_CPU_NUM] = {
+    const INIT: AtomicBool = AtomicBool::new(false);
+    [INIT; MAX_CPU_NUM]
+};
+
+/// 向 `cpu_id` 的就绪队列加入任务后调用，目标核空闲时发送 IPI 唤醒它
+pub fn kick_cpu(cpu_id: usize) {
+    if cpu_id != current_cpu_id() && CPU_IDLE[cpu_id].load(Ordering::SeqCst) {
+        IDLE_WAKEUP_IPIS.inc();
+        wake_cpu(cpu_id);
+    }
+}
+
+/// 各 CPU 是否应尽快抢占正在运行的任务，唤醒了更应运行的任务时置位，
+/// 在返回用户态前检查（见 [`super::resched_if_needed`]）
+static NEED_RESCHED: [AtomicBool; MAX_CPU_NUM] = {
+    const INIT: AtomicBool = AtomicBool::new(false);
+    [INIT; MAX_CPU_NUM]
+};
+
+/// 被唤醒的任务应抢占 `cpu_id` 上正在运行的任务，要求该 CPU 尽快重新调度
+pub fn resched_cpu(cpu_id: usize) {
+    WAKEUP_PREEMPTIONS.inc();
+    request_resched(cpu_id);
+}
+
+/// 要求 `cpu_id` 尽快重新调度，其他 CPU 由 IPI 打断，在软件中断处理中让出
+pub fn request_resched(cpu_id: usize) {
+    NEED_RESCHED[cpu_id].store(true, Ordering::SeqCst);
+    if cpu_id != current_cpu_id() {
+        wake_cpu(cpu_id);
+    }
+}
+
+/// 取出并清除本 CPU 的重新调度标记
+pub fn take_need_resched() -> bool {
+    NEED_RESCHED[current_cpu_id()].swap(false, Ordering::SeqCst)
+}
+
+/// `cpu_id` 上正在运行的任务的调度实体，空闲或锁被占用时返回 `None`
+pub fn running_entity(cpu_id: usize) -> Option<SchedEntity> {
+    let was_enabled = disable_interrupts();
+    let task = PROCESSORS[cpu_id]
+        .try_lock()
+        .and_then(|processor| processor.current());
+    restore_interrupts(was_enabled);
+    let task = task?;
+    let inner = task.try_acquire_inner_lock()?;
+    Some(inner.sched_entity)
+}
+
+/// 各 CPU 上正在运行的任务的线程组，空闲时为 [`NO_TGID`]，供组调度参考
+static RUNNING_TGID: [AtomicUsize; MAX_CPU_NUM] = {
+    const INIT: AtomicUsize = AtomicUsize::new(NO_TGID);
+    [INIT; MAX_CPU_NUM]
+};
+
+/// [`RUNNING_TGID`] 中表示空闲的值
+pub const NO_TGID: usize = usize::MAX;
+
+/// 是否开启组调度，见 `/proc/sys/kernel/sched_gang`
+static GANG_SCHED: AtomicBool = AtomicBool::new(false);
+
+pub fn gang_sched_enabled() -> bool {
+    GANG_SCHED.load(Ordering::Relaxed)
+}
+
+pub fn set_gang_sched(enabled: bool) {
+    GANG_SCHED.store(enabled, Ordering::Relaxed);
+}
+
+/// 其他 CPU 上正在运行的线程组，`cpu_id` 自己与空闲 CPU 的位置为 [`NO_TGID`]
+pub fn running_tgids(cpu_id: usize) -> [usize; MAX_CPU_NUM] {
+    let mut tgids = [NO_TGID; MAX_CPU_NUM];
+    for (other, tgid) in tgids.iter_mut().enumerate() {
+        if other != cpu_id {
+            *tgid = RUNNING_TGID[other].load(Ordering::Relaxed);
+        }
+    }
+    tgids
+}
+
 /// 运行任务调度
 
 pub fn run_tasks() {
@@ -109,6 +205,8 @@ pub fn run_tasks() {
                 let now = get_time_ns() as u64;
                 let mut inner = pending.acquire_inner_lock();
                 inner.sched_entity.update_runtime(now);
+                // 时钟中断中已记入的部分也算在内
+                processor.stats.run_time += inner.sched_entity.slice_runtime();
             }
             
             // 根据任务状态决定加入哪个队列
@@ -120,8 +218,8 @@ pub fn run_tasks() {
                     // 正常的 suspend 调用，加入就绪队列
                     add_task(pending);
                 }
-                TaskStatus::Interruptible => {
-                    // block 调用，加入可中断等待队列
+                TaskStatus::Interruptible | TaskStatus::Stopped => {
+                    // block 调用或被信号停止，加入可中断等待队列
                     sleep_interruptible(pending);
                 }
                 _ => {
@@ -132,6 +230,8 @@ pub fn run_tasks() {
             processor = PROCESSORS[cpu_id].lock();
         }
         
+        // 取出的任务已离开就绪队列，队列计数只在此减一次；
+        // 它被切换出去后经 pending 路径重新入队，期间不应再对它 dequeue
         if let Some(task) = fetch_task() {
             // 【关键】参考 starry-mix: 等待任务完成上一次的调度过程
             // 如果任务的 on_cpu 仍为 true，说明上一个 CPU 还没完成切换
@@ -148,6 +248,12 @@ pub fn run_tasks() {
                 }
             }
             
+            // 任务入队后 cpuset 可能已缩小到不含本 CPU，交给 add_task 重新放置
+            if !task.acquire_inner_lock().sched_entity.can_run_on(cpu_id) {
+                add_task(task);
+                continue;
+            }
+            
             // 【关键】原子检查：确保任务不会同时在多个CPU上运行
             let prev_cpu = task.running_on_cpu.compare_exchange(
                 TASK_NOT_RUNNING,
@@ -182,10 +288,15 @@ pub fn run_tasks() {
                 }
                 
                 task_inner.task_status = TaskStatus::Running;
+                // 换上新任务后，之前为抢占而置的标记已经满足
+                NEED_RESCHED[cpu_id].store(false, Ordering::SeqCst);
                 // CFS: 记录任务开始执行的时间
-                task_inner.sched_entity.exec_start = get_time_ns() as u64;
-                // Wake-up Affinity: 记录任务当前运行的CPU
-                task_inner.sched_entity.set_last_cpu(cpu_id);
+                let now = get_time_ns() as u64;
+                task_inner.sched_entity.exec_start = now;
+                task_inner.sched_entity.prev_sum_exec_runtime =
+                    task_inner.sched_entity.sum_exec_runtime;
+                // Wake-up Affinity: 记录任务当前运行的CPU，换了 CPU 时记下迁移时间
+                task_inner.sched_entity.migrate_to(cpu_id, now);
                 &task_inner.task_cx as *const TaskContext
             };
             
@@ -214,6 +325,7 @@ pub fn run_tasks() {
             // Memory barrier to ensure on_cpu is visible before __switch
             core::sync::atomic::fence(core::sync::atomic::Ordering::SeqCst);
             
+            RUNNING_TGID[cpu_id].store(task.tgid, Ordering::Relaxed);
             processor.current = Some(task);
             drop(processor);
             
@@ -222,6 +334,7 @@ pub fn run_tasks() {
                 __switch(idle_task_cx_ptr, next_task_cx_ptr);
             }
             // 回到这里时，任务已被挂起，pending_task已设置（如果是正常suspend）
+            RUNNING_TGID[cpu_id].store(NO_TGID, Ordering::Relaxed);
             
             // 【安全检查】验证 __switch 返回后 tp 寄存器仍有效
             #[cfg(target_arch = "riscv64")]
@@ -234,15 +347,56 @@ pub fn run_tasks() {
             }
             // 继续循环会处理pending_task
         } else {
-            // 没有任务，释放锁
+            // 没有就绪任务，切换到本 CPU 的空闲任务，它切换回来时记入空闲时间
+            let idle = processor
+                .idle_task
+                .clone()
+                .expect("[run_tasks] idle task not created");
+            let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
+            let idle_cx_ptr = &idle.acquire_inner_lock().task_cx as *const TaskContext;
             drop(processor);
 
-            // 【Idle 状态处理】
-            // 必须开启中断才能被唤醒（响应时钟中断或其他）
-            restore_interrupts(true);
-            
-            // 可选：使用 wfi 等待以降低功耗
-            // riscv::asm::wfi();
+            let start = get_time_ns() as u64;
+            unsafe {
+                __switch(idle_task_cx_ptr, idle_cx_ptr);
+            }
+            let idle_time = get_time_ns() as u64 - start;
+            PROCESSORS[cpu_id].lock().stats.idle_time += idle_time;
+        }
+    }
+}
+
+/// 为每个 CPU 创建空闲任务，须在 initproc 之后调用以免占用其 pid
+pub fn init_idle_tasks() {
+    for processor in PROCESSORS.iter() {
+        let idle = Arc::new(TaskControlBlock::new_kernel_thread(idle_loop));
+        processor.lock().idle_task = Some(idle);
+    }
+}
+
+/// 空闲任务：等到有中断或新任务后切换回调度循环，电源管理也在这里进行
+fn idle_loop() -> ! {
+    loop {
+        let cpu_id = current_cpu_id();
+        // 先标记空闲再复查队列：在此之后入队的一方必然能看到标记并发送 IPI，
+        // 而挂起的 IPI 会让 idle_wait 立即返回，因此不会丢失唤醒
+        CPU_IDLE[cpu_id].store(true, Ordering::SeqCst);
+        if !has_ready_task(cpu_id) {
+            idle_wait();
+        }
+        CPU_IDLE[cpu_id].store(false, Ordering::SeqCst);
+
+        // 开中断处理唤醒本核的中断（时钟中断或 IPI），再关中断切回调度循环
+        restore_interrupts(true);
+        disable_interrupts();
+        let (idle_cx_ptr, idle_task_cx_ptr) = {
+            let mut processor = PROCESSORS[cpu_id].lock();
+            let idle = processor.idle_task.clone().unwrap();
+            let idle_cx_ptr = &mut idle.acquire_inner_lock().task_cx as *mut TaskContext;
+            (idle_cx_ptr, processor.get_idle_task_cx_ptr())
+        };
+        unsafe {
+            __switch(idle_cx_ptr, idle_task_cx_ptr);
         }
     }
 }
@@ -270,6 +424,17 @@ pub fn current_task() -> Option<Arc<TaskControlBlock>> {
     task
 }
 
+/// 不等待锁的 `current_task`，供 panic 处理使用：本核可能正持有处理器的锁。
+/// 锁被占用时返回 `Err(())`
+pub fn try_current_task() -> Result<Option<Arc<TaskControlBlock>>, ()> {
+    let cpu_id = current_cpu_id();
+    if cpu_id >= MAX_CPU_NUM {
+        return Err(());
+    }
+    let processor = PROCESSORS[cpu_id].try_lock().ok_or(())?;
+    Ok(processor.current())
+}
+
 /// 获取当前正在运行的任务的用户态页表令牌
 pub fn current_user_token() -> usize {
     // 【关键修复】防止 Idle 时 Panic
@@ -295,7 +460,8 @@ pub fn current_trap_cx() -> &'static mut TrapContext {
     }
 }
 
-pub fn schedule(switched_task_cx_ptr: *mut TaskContext) {
+/// 切换回本 CPU 的调度循环，`voluntary` 区分任务主动让出与被中断抢占
+pub fn schedule(switched_task_cx_ptr: *mut TaskContext, voluntary: bool) {
     let cpu_id = current_cpu_id();
     
     // Sanity check: verify CPU id is valid
@@ -306,7 +472,12 @@ pub fn schedule(switched_task_cx_ptr: *mut TaskContext) {
     // 【关键修复】关中断防止死锁
     disable_interrupts();
     
-    let idle_task_cx_ptr = PROCESSORS[cpu_id].lock().get_idle_task_cx_ptr();
+    let idle_task_cx_ptr = {
+        let mut processor = PROCESSORS[cpu_id].lock();
+        processor.stats.record_switch(voluntary);
+        processor.get_idle_task_cx_ptr()
+    };
+    CONTEXT_SWITCHES.inc();
     
     // Debug: Check idle_task_cx before switching back
     let idle_ra = unsafe { (*idle_task_cx_ptr).ra };
@@ -327,6 +498,49 @@ pub fn schedule(switched_task_cx_ptr: *mut TaskContext) {
     // log::info!("[schedule] Back from idle (Resumed)!");
 }
 
+/// `cpu_id` 的调度统计
+pub fn cfs_stats(cpu_id: usize) -> CfsStats {
+    let was_enabled = disable_interrupts();
+    let stats = PROCESSORS[cpu_id].lock().stats;
+    restore_interrupts(was_enabled);
+    stats
+}
+
+/// `/proc/schedstat` 的内容，每个 CPU 一行，最后是运行队列的不均衡度
+pub fn format_schedstat() -> String {
+    let mut output = String::from(
+        "# cpu context_switches voluntary involuntary run_time_ns nr_running idle_time_ns\n",
+    );
+    for cpu_id in 0..MAX_CPU_NUM {
+        let stats = cfs_stats(cpu_id);
+        writeln!(
+            output,
+            "cpu{} {} {} {} {} {} {}",
+            cpu_id,
+            stats.context_switches,
+            stats.voluntary_preempt,
+            stats.involuntary_preempt,
+            stats.run_time,
+            RUN_QUEUE_LENGTH.get(cpu_id),
+            stats.idle_time
+        )
+        .ok();
+    }
+    // 在线 CPU 中最长与最短运行队列之差
+    writeln!(output, "imbalance {}", run_queue_imbalance()).ok();
+    output
+}
+
+/// `/proc/self/sched` 的内容：读取它的任务自己的统计，与其他任务无关
+pub fn format_self_sched() -> String {
+    let task = current_task().unwrap();
+    let inner = task.acquire_inner_lock();
+    let mut output = String::new();
+    writeln!(output, "fpu_saves : {}", inner.fpu_saves).ok();
+    writeln!(output, "fpu_restores : {}", inner.fpu_restores).ok();
+    output
+}
+
 pub fn current_cpu_id() -> usize {
     #[cfg(target_arch = "riscv64")]
     {
@@ -0,0 +1,198 @@
+//! 进程跟踪 (ptrace)
+//!
+//! 进程用 `PTRACE_TRACEME` 让父进程成为跟踪者。被跟踪的任务在递送信号之前，以及
+//! `PTRACE_SYSCALL` 之后每个系统调用的入口与出口处停下并通知父进程；父进程用 wait4
+//! 得到停止状态（不需要 `WUNTRACED`），读写它的内存与寄存器，再让它继续运行。
+//! 跟踪者退出时，被跟踪的子进程交给 initproc 的同时脱离跟踪。
+
+use super::task::TaskControlBlockInner;
+use super::{current_task, notify_parent, sleep_stopped, wake_interruptible};
+use super::{Signals, TaskControlBlock, TaskStatus};
+use crate::hal::TrapContextOps;
+use crate::mm::{frame_reserve, VirtAddr};
+use crate::syscall::errno::*;
+use alloc::sync::Arc;
+use core::mem::size_of;
+
+/// 系统调用停止的状态字中信号为 `SIGTRAP | 0x80`，与信号递送停止区分开
+pub const PTRACE_O_TRACESYSGOOD: usize = 1;
+
+/// 任务的跟踪状态，跟踪者总是它的父进程
+#[derive(Debug, Default)]
+pub struct Ptrace {
+    /// `PTRACE_SETOPTIONS` 设置的选项
+    pub options: usize,
+    /// 在系统调用的入口与出口处停下 (`PTRACE_SYSCALL`)
+    pub syscall_stops: bool,
+    /// 正处于跟踪停止中，等待跟踪者让它继续
+    pub stopped: bool,
+    /// 跟踪者让它继续时注入的信号，0 表示不递送信号
+    pub resume_signal: usize,
+}
+
+/// 任务是否停止着，跟踪者只能检查与修改停止的任务
+pub fn is_stopped(inner: &TaskControlBlockInner) -> bool {
+    inner.ptrace.as_ref().map_or(false, |ptrace| ptrace.stopped)
+        || inner.task_status == TaskStatus::Stopped
+}
+
+/// 当前任务进入跟踪停止，`status` 是跟踪者 wait4 得到的状态字；
+/// 睡眠直到跟踪者让它继续、脱离跟踪或收到 SIGKILL，返回跟踪者注入的信号
+pub fn ptrace_stop(status: u32) -> usize {
+    let task = current_task().unwrap();
+    {
+        let mut inner = task.acquire_inner_lock();
+        match inner.ptrace.as_mut() {
+            Some(ptrace) => {
+                ptrace.stopped = true;
+                ptrace.resume_signal = 0;
+            }
+            None => return 0,
+        }
+        inner.stop_report = Some(status);
+    }
+    notify_parent(&task);
+    drop(task);
+    sleep_stopped(|inner| {
+        !inner.ptrace.as_ref().map_or(false, |ptrace| ptrace.stopped)
+            || inner.sigpending.contains(Signals::SIGKILL)
+    });
+    let task = current_task().unwrap();
+    let mut inner = task.acquire_inner_lock();
+    match inner.ptrace.as_mut() {
+        Some(ptrace) => {
+            ptrace.stopped = false;
+            core::mem::take(&mut ptrace.resume_signal)
+        }
+        None => 0,
+    }
+}
+
+/// 系统调用的入口或出口停止；跟踪者注入的信号挂起，返回用户态前递送
+fn syscall_stop() {
+    let task = current_task().unwrap();
+    let status = match task.acquire_inner_lock().ptrace.as_ref() {
+        Some(ptrace) if ptrace.syscall_stops => {
+            let mut signum = Signals::SIGTRAP.to_signum().unwrap() as u32;
+            if ptrace.options & PTRACE_O_TRACESYSGOOD != 0 {
+                signum |= 0x80;
+            }
+            signum << 8 | 0x7f
+        }
+        _ => return,
+    };
+    drop(task);
+    let signum = ptrace_stop(status);
+    if signum != 0 {
+        let task = current_task().unwrap();
+        task.acquire_inner_lock()
+            .add_signal(Signals::from_signum(signum).unwrap());
+    }
+}
+
+/// 系统调用入口处的跟踪停止，跟踪者可以读取系统调用号与参数
+pub fn syscall_entry_stop() {
+    syscall_stop();
+}
+
+/// 系统调用出口处的跟踪停止，返回值 `ret` 先写入陷阱上下文，跟踪者可以读取
+pub fn syscall_exit_stop(ret: isize) {
+    let task = current_task().unwrap();
+    let inner = task.acquire_inner_lock();
+    if !inner
+        .ptrace
+        .as_ref()
+        .map_or(false, |ptrace| ptrace.syscall_stops)
+    {
+        return;
+    }
+    inner.get_trap_cx().set_return(ret as usize);
+    drop(inner);
+    drop(task);
+    syscall_stop();
+}
+
+/// 调用者 `tracer` 跟踪的子进程 `pid`
+pub fn traced_child(tracer: &Arc<TaskControlBlock>, pid: usize) -> Option<Arc<TaskControlBlock>> {
+    let child = tracer
+        .acquire_inner_lock()
+        .children
+        .iter()
+        .find(|child| child.pid.0 == pid)
+        .cloned()?;
+    let traced = child.acquire_inner_lock().ptrace.is_some();
+    traced.then_some(child)
+}
+
+/// 让停止的被跟踪任务继续运行，`signum` 是注入的信号
+/// # 说明
+/// + 处于跟踪停止时，信号递送停止的信号换成 `signum`（0 表示丢弃），其他停止则挂起 `signum`
+/// + 被停止信号停止的任务也一并继续运行
+pub fn resume(
+    tracee: Arc<TaskControlBlock>,
+    signum: usize,
+    syscall_stops: bool,
+    single_step: bool,
+) {
+    let mut inner = tracee.acquire_inner_lock();
+    inner.single_step = single_step;
+    let ptrace = inner.ptrace.as_mut().unwrap();
+    ptrace.syscall_stops = syscall_stops;
+    if ptrace.stopped {
+        ptrace.stopped = false;
+        ptrace.resume_signal = signum;
+    } else if signum != 0 {
+        inner.add_signal(Signals::from_signum(signum).unwrap());
+    }
+    let wake = inner.task_status == TaskStatus::Stopped;
+    if wake {
+        inner.task_status = TaskStatus::Ready;
+    }
+    drop(inner);
+    if wake {
+        wake_interruptible(tracee);
+    }
+}
+
+/// 任务脱离跟踪，处于跟踪停止时以注入信号 `signum` 继续运行
+pub fn detach(tracee: &Arc<TaskControlBlock>, signum: usize) {
+    let mut inner = tracee.acquire_inner_lock();
+    let ptrace = match inner.ptrace.take() {
+        Some(ptrace) => ptrace,
+        None => return,
+    };
+    inner.single_step = false;
+    if signum != 0 {
+        inner.add_signal(Signals::from_signum(signum).unwrap());
+    }
+    let wake = ptrace.stopped && inner.task_status == TaskStatus::Stopped;
+    if wake {
+        inner.task_status = TaskStatus::Ready;
+    }
+    drop(inner);
+    if wake {
+        wake_interruptible(tracee.clone());
+    }
+}
+
+/// 被跟踪任务地址 `addr` 处一个字在内核中的地址，`addr` 须按字对齐
+fn word_of(tracee: &TaskControlBlock, addr: usize, write: bool) -> Result<*mut usize, isize> {
+    if addr % size_of::<usize>() != 0 {
+        return Err(EIO);
+    }
+    frame_reserve(3);
+    match tracee.vm.lock().access_remote(VirtAddr::from(addr), write) {
+        Ok(pa) => Ok(pa.0 as *mut usize),
+        Err(_) => Err(EIO),
+    }
+}
+
+/// 读取被跟踪任务地址 `addr` 处的一个字 (`PTRACE_PEEKDATA`)
+pub fn peek_word(tracee: &TaskControlBlock, addr: usize) -> Result<usize, isize> {
+    word_of(tracee, addr, false).map(|word| unsafe { word.read() })
+}
+
+/// 向被跟踪任务地址 `addr` 处写入一个字 (`PTRACE_POKEDATA`)，只读的私有映射也可以写入
+pub fn poke_word(tracee: &TaskControlBlock, addr: usize, data: usize) -> Result<(), isize> {
+    word_of(tracee, addr, true).map(|word| unsafe { word.write(data) })
+}
@@ -104,21 +104,38 @@ impl RtRunQueue {
         self.nr_running += 1;
     }
     
-    /// Remove a task from the RT run queue
-    pub fn dequeue(&mut self, task: &Arc<TaskControlBlock>, entity: &SchedEntity) {
+    /// Add a preempted task back to the head of its priority queue, it runs
+    /// again before the other tasks of its priority
+    pub fn enqueue_front(&mut self, task: Arc<TaskControlBlock>, entity: &SchedEntity) {
         let prio = entity.rt_priority.min(MAX_RT_PRIO) as usize;
         if prio == 0 {
-            return;
+            return; // Invalid RT priority
+        }
+        
+        self.queues[prio].push_front(task);
+        self.bitmap |= 1u128 << prio;
+        self.nr_running += 1;
+    }
+    
+    /// Remove a task from the RT run queue, returns false if it is not queued
+    pub fn dequeue(&mut self, task: &Arc<TaskControlBlock>, entity: &SchedEntity) -> bool {
+        let prio = entity.rt_priority.min(MAX_RT_PRIO) as usize;
+        if prio == 0 {
+            return false;
         }
         
         let queue = &mut self.queues[prio];
-        if let Some(pos) = queue.iter().position(|t| Arc::ptr_eq(t, task)) {
-            queue.remove(pos);
-            self.nr_running = self.nr_running.saturating_sub(1);
-            
-            if queue.is_empty() {
-                self.bitmap &= !(1u128 << prio);
+        match queue.iter().position(|t| Arc::ptr_eq(t, task)) {
+            Some(pos) => {
+                queue.remove(pos);
+                self.nr_running = self.nr_running.saturating_sub(1);
+                
+                if queue.is_empty() {
+                    self.bitmap &= !(1u128 << prio);
+                }
+                true
             }
+            None => false,
         }
     }
     
@@ -164,6 +181,23 @@ impl RtRunQueue {
         self.bitmap |= 1u128 << prio;
     }
     
+    /// Whether `entity` is a RR task that has used up its timeslice
+    pub fn slice_expired(entity: &SchedEntity) -> bool {
+        entity.policy == SchedPolicy::RoundRobin && entity.slice_runtime() >= RR_TIMESLICE_NS
+    }
+    
+    /// Tick check for the RT task running on this queue's CPU, which is not queued
+    /// itself: preempt it for a queued task of higher priority. A RR task also
+    /// yields to the tasks of its own priority once its timeslice is used up,
+    /// a FIFO task otherwise runs until it blocks or yields.
+    pub fn tick_preempt(&self, curr: &SchedEntity) -> bool {
+        let prio = curr.rt_priority.min(MAX_RT_PRIO) as usize;
+        if self.bitmap >> (prio + 1) != 0 {
+            return true;
+        }
+        Self::slice_expired(curr) && self.bitmap & (1u128 << prio) != 0
+    }
+    
     /// Check if a waking RT task should preempt the current task
     pub fn should_preempt(&self, curr_entity: &SchedEntity, wake_entity: &SchedEntity) -> bool {
         // RT tasks always preempt non-RT tasks
@@ -241,10 +275,14 @@ impl IdleRunQueue {
         self.queue.push_back(task);
     }
     
-    /// Remove task
-    pub fn dequeue(&mut self, task: &Arc<TaskControlBlock>) {
-        if let Some(pos) = self.queue.iter().position(|t| Arc::ptr_eq(t, task)) {
-            self.queue.remove(pos);
+    /// Remove task, returns false if it is not queued
+    pub fn dequeue(&mut self, task: &Arc<TaskControlBlock>) -> bool {
+        match self.queue.iter().position(|t| Arc::ptr_eq(t, task)) {
+            Some(pos) => {
+                self.queue.remove(pos);
+                true
+            }
+            None => false,
         }
     }
     
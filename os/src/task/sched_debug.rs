@@ -0,0 +1,201 @@
+//! Scheduler event log
+//!
+//! With the `sched_debug` feature every CFS run queue operation appends an event
+//! to a ring buffer of the CPU that performed it: enqueue, dequeue, pick and
+//! migrate, each with a timestamp, the task's pid and its vruntime. The buffers
+//! are read through `/proc/sched_debug`.
+//!
+//! Each CPU only ever writes its own buffer, always with interrupts disabled
+//! (the run queues are only touched under the task manager locks), so writers
+//! never race. Readers on other CPUs check a per-slot sequence number instead of
+//! taking a lock, and skip a slot that is rewritten while it is being copied.
+
+use crate::config::MAX_CPU_NUM;
+use crate::task::processor::current_cpu_id;
+use crate::timer::get_time_ns;
+use alloc::string::String;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// Events kept per CPU, the oldest are overwritten first
+pub const EVENTS_PER_CPU: usize = 256;
+
+/// A scheduler decision
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchedEventKind {
+    /// Task added to a run queue
+    Enqueue = 1,
+    /// Task removed from a run queue without running
+    Dequeue = 2,
+    /// Task chosen to run next
+    Pick = 3,
+    /// Task taken from a run queue to run on another CPU
+    Migrate = 4,
+}
+
+impl SchedEventKind {
+    fn from_raw(raw: u64) -> Option<Self> {
+        match raw {
+            1 => Some(Self::Enqueue),
+            2 => Some(Self::Dequeue),
+            3 => Some(Self::Pick),
+            4 => Some(Self::Migrate),
+            _ => None,
+        }
+    }
+
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Enqueue => "enqueue",
+            Self::Dequeue => "dequeue",
+            Self::Pick => "pick",
+            Self::Migrate => "migrate",
+        }
+    }
+}
+
+/// One logged event, as read back from a ring buffer
+#[derive(Debug, Clone, Copy)]
+pub struct SchedEvent {
+    pub time_ns: u64,
+    pub kind: SchedEventKind,
+    pub pid: usize,
+    pub vruntime: u64,
+    /// Destination CPU of a migration
+    pub target_cpu: usize,
+}
+
+/// `info` packs the kind, target CPU and pid of an event
+const KIND_SHIFT: u32 = 56;
+const TARGET_SHIFT: u32 = 48;
+const PID_MASK: u64 = (1 << TARGET_SHIFT) - 1;
+
+/// A slot is being written while its sequence number is odd
+struct Slot {
+    seq: AtomicU64,
+    time_ns: AtomicU64,
+    info: AtomicU64,
+    vruntime: AtomicU64,
+}
+
+impl Slot {
+    const fn new() -> Self {
+        Self {
+            seq: AtomicU64::new(0),
+            time_ns: AtomicU64::new(0),
+            info: AtomicU64::new(0),
+            vruntime: AtomicU64::new(0),
+        }
+    }
+}
+
+struct EventRing {
+    /// Events written so far, the next one goes to `head % EVENTS_PER_CPU`
+    head: AtomicUsize,
+    slots: [Slot; EVENTS_PER_CPU],
+}
+
+impl EventRing {
+    const fn new() -> Self {
+        const SLOT: Slot = Slot::new();
+        Self {
+            head: AtomicUsize::new(0),
+            slots: [SLOT; EVENTS_PER_CPU],
+        }
+    }
+
+    /// Only called by the CPU owning the ring
+    fn push(&self, time_ns: u64, info: u64, vruntime: u64) {
+        let head = self.head.load(Ordering::Relaxed);
+        let slot = &self.slots[head % EVENTS_PER_CPU];
+        let seq = slot.seq.load(Ordering::Relaxed);
+        slot.seq.store(seq + 1, Ordering::Relaxed);
+        core::sync::atomic::fence(Ordering::Release);
+        slot.time_ns.store(time_ns, Ordering::Relaxed);
+        slot.info.store(info, Ordering::Relaxed);
+        slot.vruntime.store(vruntime, Ordering::Relaxed);
+        slot.seq.store(seq + 2, Ordering::Release);
+        self.head.store(head + 1, Ordering::Release);
+    }
+
+    /// Copy out one slot, `None` if it is empty or rewritten meanwhile
+    fn read(&self, index: usize) -> Option<SchedEvent> {
+        let slot = &self.slots[index % EVENTS_PER_CPU];
+        let seq = slot.seq.load(Ordering::Acquire);
+        if seq == 0 || seq % 2 == 1 {
+            return None;
+        }
+        let time_ns = slot.time_ns.load(Ordering::Relaxed);
+        let info = slot.info.load(Ordering::Relaxed);
+        let vruntime = slot.vruntime.load(Ordering::Relaxed);
+        core::sync::atomic::fence(Ordering::Acquire);
+        if slot.seq.load(Ordering::Relaxed) != seq {
+            return None;
+        }
+        Some(SchedEvent {
+            time_ns,
+            kind: SchedEventKind::from_raw(info >> KIND_SHIFT)?,
+            pid: (info & PID_MASK) as usize,
+            vruntime,
+            target_cpu: ((info >> TARGET_SHIFT) & 0xff) as usize,
+        })
+    }
+
+    /// Events still in the ring, oldest first
+    fn for_each(&self, mut f: impl FnMut(SchedEvent)) {
+        let head = self.head.load(Ordering::Acquire);
+        for index in head.saturating_sub(EVENTS_PER_CPU)..head {
+            if let Some(event) = self.read(index) {
+                f(event);
+            }
+        }
+    }
+}
+
+static RINGS: [EventRing; MAX_CPU_NUM] = {
+    const RING: EventRing = EventRing::new();
+    [RING; MAX_CPU_NUM]
+};
+
+/// Log an event on the current CPU, `target_cpu` only matters for migrations
+pub fn record(kind: SchedEventKind, pid: usize, vruntime: u64, target_cpu: usize) {
+    let cpu_id = current_cpu_id();
+    if cpu_id >= MAX_CPU_NUM {
+        return;
+    }
+    let info = (kind as u64) << KIND_SHIFT
+        | ((target_cpu as u64) & 0xff) << TARGET_SHIFT
+        | (pid as u64) & PID_MASK;
+    RINGS[cpu_id].push(get_time_ns() as u64, info, vruntime);
+}
+
+/// Events logged by `cpu_id`, oldest first
+pub fn for_each_event(cpu_id: usize, f: impl FnMut(SchedEvent)) {
+    if let Some(ring) = RINGS.get(cpu_id) {
+        ring.for_each(f);
+    }
+}
+
+/// Contents of `/proc/sched_debug`: one section per CPU, one line per event
+pub fn format_events() -> String {
+    let mut output = String::new();
+    for cpu_id in 0..MAX_CPU_NUM {
+        writeln!(output, "cpu {}:", cpu_id).ok();
+        for_each_event(cpu_id, |event| {
+            write!(
+                output,
+                "  {:>14} {:<8} pid {:<6} vruntime {}",
+                event.time_ns,
+                event.kind.as_str(),
+                event.pid,
+                event.vruntime
+            )
+            .ok();
+            if event.kind == SchedEventKind::Migrate {
+                write!(output, " to cpu {}", event.target_cpu).ok();
+            }
+            writeln!(output).ok();
+        });
+    }
+    output
+}
@@ -12,11 +12,12 @@ use crate::hal::{
 use crate::signal_type;
 use alloc::boxed::Box;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use core::fmt::{self, Debug, Formatter};
 use core::mem::size_of;
 use log::{debug, error, trace, warn};
 
-use crate::hal::TrapContext;
+use crate::hal::{TrapContext, TrapContextOps};
 
 use crate::config::*;
 use crate::mm::{
@@ -24,7 +25,11 @@ use crate::mm::{
 };
 use crate::syscall::errno::*;
 use crate::task::manager::wait_with_timeout;
-use crate::task::{block_current_and_run_next, exit_current_and_run_next, exit_group_and_run_next};
+use crate::task::ptrace::ptrace_stop;
+use crate::task::{
+    block_current_and_run_next, exit_current_and_run_next, exit_group_and_run_next,
+    stop_current_and_run_next, wake_interruptible, TaskControlBlock, TaskStatus, INITPROC,
+};
 use crate::timer::TimeSpec;
 
 use super::current_task;
@@ -138,6 +143,13 @@ impl Signals {
     const CAN_NOT_BE_MASKED: Signals =
         Signals::from_bits_truncate(1 << 3 | 1 << 8 | 1 << 10 | 1 << 18);
     const EMPTY: Signals = Signals::empty();
+    /// 默认动作是停止进程的信号
+    pub const STOP_SIGNALS: Signals = Signals::from_bits_truncate(
+        Signals::SIGSTOP.bits()
+            | Signals::SIGTSTP.bits()
+            | Signals::SIGTTIN.bits()
+            | Signals::SIGTTOU.bits(),
+    );
     /// if 0 <= signum < 64, return `Ok(Signals)`, else return `Err()` (illeagal)
     pub fn from_signum(signum: usize) -> Result<Signals, ()> {
         match signum {
@@ -249,6 +261,10 @@ impl SigAction {
             mask: Signals::empty(),
         }
     }
+    /// 信号被忽略（SIG_IGN）
+    pub fn is_ignore(&self) -> bool {
+        self.handler == SigHandler::SIG_IGN
+    }
 }
 
 impl Debug for SigAction {
@@ -299,12 +315,16 @@ pub fn sigaction(signum: usize, act: *const SigAction, oldact: *mut SigAction) -
                     return EFAULT;
                 }
                 sigact.mask.remove(Signals::CAN_NOT_BE_MASKED);
-                if !(sigact.handler == SigHandler::SIG_DFL || sigact.handler == SigHandler::SIG_IGN)
-                {
+                // 只有默认动作不需要记录；改为忽略时丢弃已挂起的该信号
+                if sigact.handler != SigHandler::SIG_DFL {
                     task.sighand.lock()[signum - 1] = Some(Box::new(sigact));
                 } else {
                     task.sighand.lock()[signum - 1] = None;
                 }
+                if sigact.is_ignore() {
+                    let signal = Signals::from_signum(signum).unwrap();
+                    task.acquire_inner_lock().sigpending.remove(signal);
+                }
                 trace!("[sigaction] *act: {:?}", sigact);
             }
             SUCCESS
@@ -343,9 +363,23 @@ impl SignalStack {
 pub fn do_signal() {
     let task = current_task().unwrap();
     let mut inner = task.acquire_inner_lock();
+    // 这次陷入是否是系统调用；scause 可能已被阻塞期间的内核中断改写，不能据此判断
+    let in_syscall = core::mem::take(&mut inner.in_syscall);
     while let Some(signum) = inner.sigpending.difference(inner.sigmask).peek_front() {
         let signal = Signals::from_bits_truncate(1 << (signum - 1));
         inner.sigpending.remove(signal);
+        // 被跟踪的任务在递送信号前停下，由跟踪者决定递送哪个信号
+        let (signum, signal) = if inner.ptrace.is_some() && signal != Signals::SIGKILL {
+            drop(inner);
+            let signum = ptrace_stop((signum as u32) << 8 | 0x7f);
+            inner = task.acquire_inner_lock();
+            if signum == 0 {
+                continue;
+            }
+            (signum, Signals::from_bits_truncate(1 << (signum - 1)))
+        } else {
+            (signum, signal)
+        };
         trace!(
             "[do_signal] signal: {:?}, pending: {:?}, sigmask: {:?}",
             signal,
@@ -355,20 +389,22 @@ pub fn do_signal() {
         let mut sighand = task.sighand.lock();
         // user-defined handler
         if let Some(act) = &sighand[signum - 1] {
+            if act.is_ignore() {
+                trace!("[do_signal] Ignore {:?}", signal);
+                continue;
+            }
             let trap_cx = inner.get_trap_cx();
             // if this syscall wants to restart
-            if get_exception_cause().is_syscall() && trap_cx.gp.a0 == ERESTART as usize {
+            if in_syscall && trap_cx.return_value() == ERESTART as usize {
                 // and if `SA_RESTART` is set
                 if act.flags.contains(SigActionFlags::SA_RESTART) {
                     debug!("[do_signal] syscall will restart after sigreturn");
-                    // back to `ecall`
-                    trap_cx.gp.pc -= 4;
-                    // restore syscall parameter `a0`
-                    trap_cx.gp.a0 = trap_cx.origin_a0;
+                    // back to `ecall` and restore syscall parameter `a0`
+                    trap_cx.restart_syscall();
                 } else {
                     debug!("[do_signal] syscall was interrupted");
                     // will return EINTR after sigreturn
-                    trap_cx.gp.a0 = EINTR as usize;
+                    trap_cx.set_return(EINTR as usize);
                 }
             }
             let ucontext_addr = (trap_cx.gp.sp - size_of::<UserContext>()) & !0x7;
@@ -424,14 +460,14 @@ pub fn do_signal() {
                     ) // push MachineContext into user stack
                     .unwrap(); //(This Result was NOT checked and may be usable if left unchecked.)
                 }
-                trap_cx.gp.a0 = signum; // a0 <- signum
+                trap_cx.set_return(signum); // a0 <- signum
                 trap_cx.set_sp(sig_sp); // update sp, because we've pushed something into stack
                 trap_cx.gp.ra = if act.flags.contains(SigActionFlags::SA_RESTORER) {
                     act.restorer // legacy, signal trampoline provided by C library's wrapper function
                 } else {
                     SIGNAL_TRAMPOLINE // ra <- __call_sigreturn, when handler ret, we will go to __call_sigreturn
                 };
-                trap_cx.gp.pc = act.handler.addr().unwrap(); // restore pc with addr of handler
+                trap_cx.set_pc(act.handler.addr().unwrap()); // restore pc with addr of handler
             } else {
                 error!(
                     "[do_signal] User stack will overflow after push trap context! Send SIGSEGV."
@@ -472,7 +508,7 @@ pub fn do_signal() {
                         println!(
                         "[kernel] {:?} in application, instruction addr = {:#x}, bad instruction = {:#x}, core dumped.",
                         scause,
-                        inner.get_trap_cx().gp.pc,
+                        inner.get_trap_cx().pc(),
                         stval,
                         );
                     } else {
@@ -482,7 +518,7 @@ pub fn do_signal() {
                         "[kernel] {:?} in application, bad addr = {:#x}, bad instruction = {:#x}, core dumped.",
                         scause,
                         stval,
-                        inner.get_trap_cx().gp.pc,
+                        inner.get_trap_cx().pc(),
                         );
                     };
                     drop(inner);
@@ -496,16 +532,19 @@ pub fn do_signal() {
                     trace!("[do_signal] Ignore {:?}", signal);
                     continue;
                 }
-                // stop (or we should say block) current process
-                Signals::SIGTSTP | Signals::SIGTTIN | Signals::SIGTTOU => {
+                // stop current process until SIGCONT or SIGKILL
+                Signals::SIGSTOP | Signals::SIGTSTP | Signals::SIGTTIN | Signals::SIGTTOU => {
+                    // the syscall interrupted by the stop is restarted once continued
+                    let trap_cx = inner.get_trap_cx();
+                    if in_syscall && trap_cx.return_value() == ERESTART as usize {
+                        trap_cx.restart_syscall();
+                    }
                     drop(inner);
                     drop(sighand);
                     drop(task);
-                    block_current_and_run_next();
-                    // because this loop require `inner`, and we have `drop(inner)` above, so `break` is compulsory
-                    // this would cause some signals won't be handled immediately when this process resumes
-                    // but it doesn't matter, maybe
-                    break;
+                    stop_current_and_run_next(signal);
+                    // handle the signals that arrived while stopped, such as SIGKILL
+                    return do_signal();
                 }
                 // for all other signals, we should terminate current process
                 _ => {
@@ -518,6 +557,132 @@ pub fn do_signal() {
             }
         }
     }
+    // 没有处理函数运行，被信号打断的系统调用透明地重新执行
+    let trap_cx = inner.get_trap_cx();
+    if in_syscall && trap_cx.return_value() == ERESTART as usize {
+        trap_cx.restart_syscall();
+    }
+}
+
+/// wait4 报告子进程被 SIGCONT 继续时的状态字
+pub const WAIT_CONTINUED: u32 = 0xffff;
+
+/// 向任务 `task` 发送信号 `signal`，唤醒可中断睡眠的任务
+/// # 说明
+/// + 停止信号丢弃挂起的 SIGCONT；SIGCONT 丢弃挂起的停止信号，
+///   并让停止的任务继续运行，同时通知父进程（wait4 的 WCONTINUED）
+/// + 停止的任务只被 SIGCONT 与 SIGKILL 唤醒，其余信号等它继续后才处理
+pub fn send_signal(task: Arc<TaskControlBlock>, signal: Signals) {
+    if signal.is_empty() {
+        return;
+    }
+    let mut inner = task.acquire_inner_lock();
+    let mut continued = false;
+    if signal == Signals::SIGCONT {
+        inner.sigpending.remove(Signals::STOP_SIGNALS);
+        let stop_unreported = matches!(inner.stop_report, Some(status) if status != WAIT_CONTINUED);
+        if inner.task_status == TaskStatus::Stopped || stop_unreported {
+            inner.stop_report = Some(WAIT_CONTINUED);
+            continued = true;
+        }
+    } else if Signals::STOP_SIGNALS.contains(signal) {
+        inner.sigpending.remove(Signals::SIGCONT);
+    }
+    inner.add_signal(signal);
+    let wake = match inner.task_status {
+        TaskStatus::Interruptible => true,
+        TaskStatus::Stopped => signal == Signals::SIGCONT || signal == Signals::SIGKILL,
+        _ => false,
+    };
+    if wake {
+        inner.task_status = TaskStatus::Ready;
+    }
+    drop(inner);
+    if continued {
+        notify_parent(&task);
+    }
+    if wake {
+        wake_interruptible(task);
+    }
+}
+
+/// 任务停止或继续时通知父进程：父进程没有为 SIGCHLD 设置 SA_NOCLDSTOP 时发送 SIGCHLD，
+/// 并唤醒在 wait4 中等待的父进程
+pub fn notify_parent(task: &Arc<TaskControlBlock>) {
+    let parent = match task
+        .acquire_inner_lock()
+        .parent
+        .as_ref()
+        .and_then(|parent| parent.upgrade())
+    {
+        Some(parent) => parent,
+        None => return,
+    };
+    let sigchld = Signals::SIGCHLD.to_signum().unwrap();
+    let nocldstop = parent.sighand.lock()[sigchld - 1]
+        .as_ref()
+        .map_or(false, |act| {
+            act.flags.contains(SigActionFlags::SA_NOCLDSTOP)
+        });
+    let mut parent_inner = parent.acquire_inner_lock();
+    if !nocldstop {
+        parent_inner.add_signal(Signals::SIGCHLD);
+    }
+    if parent_inner.task_status == TaskStatus::Interruptible {
+        parent_inner.task_status = TaskStatus::Ready;
+        drop(parent_inner);
+        wake_interruptible(parent);
+    }
+}
+
+/// 所有进程（线程组的主线程）中满足 `pred` 的，从 initproc 开始遍历进程树
+pub fn find_processes(
+    mut pred: impl FnMut(&TaskControlBlock, usize) -> bool,
+) -> Vec<Arc<TaskControlBlock>> {
+    let mut found = Vec::new();
+    let mut stack = alloc::vec![INITPROC.clone()];
+    while let Some(task) = stack.pop() {
+        let inner = task.acquire_inner_lock();
+        stack.extend(inner.children.iter().cloned());
+        let pgid = inner.pgid;
+        drop(inner);
+        if task.pid.0 == task.tgid && pred(&task, pgid) {
+            found.push(task);
+        }
+    }
+    found
+}
+
+/// 向进程组 `pgid` 中的每个进程发送信号 `signal`，进程组不存在时返回 ESRCH
+pub fn kill_pgrp(pgid: usize, signal: Signals) -> isize {
+    let members = find_processes(|_, task_pgid| task_pgid == pgid);
+    if members.is_empty() {
+        return ESRCH;
+    }
+    for task in members {
+        send_signal(task, signal);
+    }
+    SUCCESS
+}
+
+/// 当前任务是否有未被屏蔽的信号挂起
+/// # 说明
+/// 阻塞的系统调用在有信号挂起时返回 ERESTART：信号没有处理函数（被忽略、默认忽略，
+/// 或停止后继续）或处理函数设置了 SA_RESTART 时系统调用重新执行，否则返回 EINTR
+pub fn signal_pending() -> bool {
+    let task = current_task().unwrap();
+    let inner = task.acquire_inner_lock();
+    !(inner.sigpending - inner.sigmask).is_empty()
+}
+
+/// 当前任务是否忽略或屏蔽了信号 `signal`
+pub fn signal_ignored_or_blocked(signal: Signals) -> bool {
+    let task = current_task().unwrap();
+    let signum = signal.to_signum().unwrap();
+    let ignored = task.sighand.lock()[signum - 1]
+        .as_ref()
+        .map_or(false, |act| act.is_ignore());
+    ignored || task.acquire_inner_lock().sigmask.contains(signal)
 }
 
 bitflags! {
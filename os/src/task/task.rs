@@ -9,18 +9,22 @@
 
 use super::manager::TASK_MANAGERS;
 use super::pid::RecycleAllocator;
+use super::ptrace::Ptrace;
 use super::signal::*;
+use super::posix_timer::PosixTimers;
 use super::threads::Futex;
 use super::TaskContext;
 use super::{pid_alloc, PidHandle};
 use crate::config::MMAP_BASE;
+use crate::fs::dev::tty::TerminalSession;
 use crate::fs::file_descriptor::FdTable;
+use crate::fs::mount::{MountNamespace, INIT_MNT_NS};
 use crate::fs::{FileDescriptor, OpenFlags, ROOT_FD};
 use crate::hal::trap_cx_bottom_from_tid;
 use crate::hal::ustack_bottom_from_tid;
 use crate::hal::TrapImpl;
 use crate::hal::{kstack_alloc, KernelStack};
-use crate::hal::{trap_handler, TrapContext};
+use crate::hal::{trap_handler, TrapContext, TrapContextOps};
 use crate::mm::PageTableImpl;
 use crate::mm::{MemorySet, PhysPageNum, VirtAddr, KERNEL_SPACE};
 use crate::net::SocketTable;
@@ -36,6 +40,7 @@ use log::trace;
 use spin::{Mutex, MutexGuard};
 use crate::task::processor::current_cpu_id;
 use crate::task::cfs_scheduler::SchedEntity;
+use crate::task::cpuset;
 
 /// 无效的 CPU ID，表示任务未在任何 CPU 上运行
 pub const TASK_NOT_RUNNING: usize = usize::MAX;
@@ -45,6 +50,10 @@ pub const TASK_NOT_RUNNING: usize = usize::MAX;
 pub struct FsStatus {
     /// Current working directory file descriptor
     pub working_inode: Arc<FileDescriptor>,
+    /// Root directory set by chroot, `None` for the mount namespace's root.
+    /// Path walks lock `fs` to read it, so callers must not hold the lock while
+    /// resolving paths.
+    pub root: Option<Arc<FileDescriptor>>,
 }
 
 /// Task control block (TCB)
@@ -69,6 +78,8 @@ pub struct TaskControlBlock {
     pub ustack_base: usize,
     /// Exit signal
     pub exit_signal: Signals,
+    /// Creation time, recorded by process accounting
+    pub start_time: TimeVal,
     
     /// 【调试】记录当前任务正在哪个 CPU 上运行，用于检测双重运行
     /// TASK_NOT_RUNNING 表示不在任何 CPU 上运行
@@ -95,12 +106,16 @@ pub struct TaskControlBlock {
     pub socket_table: Arc<Mutex<SocketTable>>,
     /// Filesystem state
     pub fs: Arc<Mutex<FsStatus>>,
+    /// Mount namespace, replaced by unshare(CLONE_NEWNS)
+    pub mnt_ns: Mutex<Arc<MountNamespace>>,
     /// Virtual memory space
     pub vm: Arc<Mutex<MemorySet<PageTableImpl>>>,
     /// Signal handler table
     pub sighand: Arc<Mutex<Vec<Option<Box<SigAction>>>>>,
     /// Futex (fast userspace mutex)
     pub futex: Arc<Mutex<Futex>>,
+    /// POSIX timers of the process, shared by its threads
+    pub posix_timers: Arc<Mutex<PosixTimers>>,
 }
 
 /// Timer type enumeration for interval timer operations
@@ -166,6 +181,10 @@ pub struct TaskControlBlockInner {
     pub heap_pt: usize,
     /// Process group ID
     pub pgid: usize,
+    /// Session ID
+    pub sid: usize,
+    /// Controlling terminal, shared by the processes of the session
+    pub ctty: Option<Arc<Mutex<TerminalSession>>>,
     /// Resource usage statistics
     pub rusage: Rusage,
     /// Process clock information
@@ -174,6 +193,19 @@ pub struct TaskControlBlockInner {
     pub timer: [ITimerVal; 3],
     /// CFS scheduling entity
     pub sched_entity: SchedEntity,
+    /// Trap with `SIGTRAP` after every user instruction (`PTRACE_SINGLESTEP`)
+    pub single_step: bool,
+    /// Set when a syscall returns, consumed by `do_signal` to tell whether the trap
+    /// being returned from may be restarted
+    pub in_syscall: bool,
+    /// Stop or continue not yet reported to the parent by `wait4`, as a wait status
+    pub stop_report: Option<u32>,
+    /// Tracing state, `Some` while the parent traces this task (`PTRACE_TRACEME`)
+    pub ptrace: Option<Ptrace>,
+    /// Floating point contexts saved on trap by the lazy FPU, shown in `/proc/self/sched`
+    pub fpu_saves: usize,
+    /// Floating point contexts loaded on first use by the lazy FPU
+    pub fpu_restores: usize,
 }
 
 /// Robust mutex list
@@ -285,6 +317,12 @@ impl Debug for Rusage {
 }
 
 impl TaskControlBlockInner {
+    /// 控制终端；终端挂断、被别的会话抢走或调用了 setsid 后返回 None
+    pub fn controlling_terminal(&self) -> Option<&Arc<Mutex<TerminalSession>>> {
+        self.ctty
+            .as_ref()
+            .filter(|ctty| ctty.lock().sid == self.sid)
+    }
     /// 获取陷阱上下文
     pub fn get_trap_cx(&self) -> &'static mut TrapContext {
         self.trap_cx_ppn.get_mut()
@@ -397,6 +435,10 @@ impl TaskControlBlock {
     pub fn acquire_inner_lock(&self) -> MutexGuard<TaskControlBlockInner> {
         self.inner.lock()
     }
+    /// 不等待锁地获取任务内部状态，锁被占用时返回 `None`
+    pub fn try_acquire_inner_lock(&self) -> Option<MutexGuard<TaskControlBlockInner>> {
+        self.inner.try_lock()
+    }
     /// 获取陷阱上下文的用户虚拟地址
     pub fn trap_cx_user_va(&self) -> usize {
         // 从线程ID计算陷阱上下文的用户虚拟地址
@@ -409,18 +451,19 @@ impl TaskControlBlock {
     }
     /// !!!!!!!!!!!!!!!!WARNING!!!!!!!!!!!!!!!!!!!!!
     /// 当前仅用于initproc加载。如果在其他地方使用，必须更改bin_path。
-    /// 任务创建（仅用于initproc）
-    pub fn new(elf: FileDescriptor) -> Self {
+    /// 任务创建（仅用于initproc），ELF 无法解析时返回 ENOEXEC
+    pub fn new(elf: FileDescriptor) -> Result<Self, isize> {
         // 将ELF文件映射到内核空间
         let elf_data = elf.map_to_kernel_space(MMAP_BASE);
         // 带有ELF程序头/跳板的内存集（MemorySet）
         // 解析ELF文件，初始化内存映射
-        let (mut memory_set, user_heap, elf_info) = MemorySet::from_elf(elf_data).unwrap();
-        // 在内核空间中删除ELF区域
+        let loaded = MemorySet::from_elf(elf_data);
+        // 在内核空间中删除ELF区域，解析失败时也要删除
         crate::mm::KERNEL_SPACE
             .lock()
             .remove_area_with_start_vpn(VirtAddr::from(MMAP_BASE).floor())
             .unwrap();
+        let (mut memory_set, user_heap, elf_info) = loaded?;
 
         // 获取线程ID分配器
         let tid_allocator = Arc::new(Mutex::new(RecycleAllocator::new()));
@@ -451,6 +494,7 @@ impl TaskControlBlock {
             kstack,
             ustack_base: ustack_bottom_from_tid(tid),
             exit_signal: Signals::empty(),
+            start_time: TimeVal::now(),
             running_on_cpu: AtomicUsize::new(TASK_NOT_RUNNING),
             on_cpu: AtomicBool::new(false),
             exe: Arc::new(Mutex::new(elf)),
@@ -468,7 +512,9 @@ impl TaskControlBlock {
                         .open(".", OpenFlags::O_RDONLY | OpenFlags::O_DIRECTORY, true)
                         .unwrap(),
                 ),
+                root: None,
             })),
+            mnt_ns: Mutex::new(INIT_MNT_NS.clone()),
             vm: Arc::new(Mutex::new(memory_set)),
             sighand: Arc::new(Mutex::new({
                 let mut vec = Vec::with_capacity(64);
@@ -476,6 +522,7 @@ impl TaskControlBlock {
                 vec
             })),
             futex: Arc::new(Mutex::new(Futex::new())),
+            posix_timers: Arc::new(Mutex::new(PosixTimers::new())),
             inner: Mutex::new(TaskControlBlockInner {
                 sigmask: Signals::empty(),
                 sigpending: Signals::empty(),
@@ -490,10 +537,18 @@ impl TaskControlBlock {
                 heap_bottom: user_heap,
                 heap_pt: user_heap,
                 pgid,
+                sid: pgid,
+                ctty: None,
                 rusage: Rusage::new(),
                 clock: ProcClock::new(),
                 timer: [ITimerVal::new(); 3],
                 sched_entity: SchedEntity::default(),
+                single_step: false,
+                in_syscall: false,
+                stop_report: None,
+                ptrace: None,
+                fpu_saves: 0,
+                fpu_restores: 0,
             }),
         };
         // 准备用户空间的陷阱上下文
@@ -510,7 +565,82 @@ impl TaskControlBlock {
         // 这是必须的，因为 app_init_context 将 kernel_tp 初始化为 0
         trap_cx.kernel_tp = current_cpu_id();
         trace!("[new] trap_cx:{:?}", *trap_cx);
-        task_control_block
+        Ok(task_control_block)
+    }
+
+    /// 创建内核线程的任务控制块，调度到它时从 `entry` 开始在内核态运行
+    ///
+    /// 内核线程没有用户地址空间，只在空的地址空间中分配陷阱上下文，
+    /// 供调度器写入 `kernel_tp`；它从不返回用户态，因此不处理信号。
+    pub fn new_kernel_thread(entry: fn() -> !) -> Self {
+        let tid_allocator = Arc::new(Mutex::new(RecycleAllocator::new()));
+        let pid_handle = pid_alloc();
+        let tid = tid_allocator.lock().alloc();
+        let tgid = pid_handle.0;
+        let pgid = pid_handle.0;
+        let kstack = kstack_alloc();
+        let kstack_top = kstack.get_top();
+
+        let mut memory_set = MemorySet::new_bare();
+        memory_set.alloc_user_res(tid, false);
+        let trap_cx_ppn = memory_set
+            .translate(VirtAddr::from(trap_cx_bottom_from_tid(tid)).into())
+            .unwrap();
+        Self {
+            pid: pid_handle,
+            tid,
+            tgid,
+            kstack,
+            ustack_base: ustack_bottom_from_tid(tid),
+            exit_signal: Signals::empty(),
+            start_time: TimeVal::now(),
+            running_on_cpu: AtomicUsize::new(TASK_NOT_RUNNING),
+            on_cpu: AtomicBool::new(false),
+            exe: Arc::new(Mutex::new(ROOT_FD.as_ref().clone())),
+            tid_allocator,
+            files: Arc::new(Mutex::new(FdTable::new(Vec::new()))),
+            socket_table: Arc::new(Mutex::new(SocketTable::new())),
+            fs: Arc::new(Mutex::new(FsStatus {
+                working_inode: ROOT_FD.clone(),
+                root: None,
+            })),
+            mnt_ns: Mutex::new(INIT_MNT_NS.clone()),
+            vm: Arc::new(Mutex::new(memory_set)),
+            sighand: Arc::new(Mutex::new({
+                let mut vec = Vec::with_capacity(64);
+                vec.resize(64, None);
+                vec
+            })),
+            futex: Arc::new(Mutex::new(Futex::new())),
+            posix_timers: Arc::new(Mutex::new(PosixTimers::new())),
+            inner: Mutex::new(TaskControlBlockInner {
+                sigmask: Signals::empty(),
+                sigpending: Signals::empty(),
+                trap_cx_ppn,
+                task_cx: TaskContext::goto_kernel_entry(kstack_top, entry),
+                task_status: TaskStatus::Ready,
+                parent: None,
+                children: Vec::new(),
+                exit_code: 0,
+                clear_child_tid: 0,
+                robust_list: RobustList::default(),
+                heap_bottom: 0,
+                heap_pt: 0,
+                pgid,
+                sid: pgid,
+                ctty: None,
+                rusage: Rusage::new(),
+                clock: ProcClock::new(),
+                timer: [ITimerVal::new(); 3],
+                sched_entity: SchedEntity::default(),
+                single_step: false,
+                in_syscall: false,
+                stop_report: None,
+                ptrace: None,
+                fpu_saves: 0,
+                fpu_restores: 0,
+            }),
+        }
     }
 
     /// 加载ELF文件
@@ -523,8 +653,20 @@ impl TaskControlBlock {
         // 将ELF文件映射到内核空间
         let elf_data = elf.map_to_kernel_space(MMAP_BASE);
         // 带有ELF程序头/跳板/陷阱上下文/用户栈的内存集（MemorySet）
-        let (mut memory_set, program_break, elf_info) = MemorySet::from_elf(elf_data)?;
+        let (mut memory_set, program_break, elf_info) = match MemorySet::from_elf(elf_data) {
+            Ok(loaded) => loaded,
+            Err(errno) => {
+                // 不是合法的 ELF 时同样要删除临时映射，否则下一次 exec 无法再映射
+                crate::mm::KERNEL_SPACE
+                    .lock()
+                    .remove_area_with_start_vpn(VirtAddr::from(MMAP_BASE).floor())
+                    .unwrap();
+                return Err(errno);
+            }
+        };
         log::trace!("[load_elf] ELF file mapped");
+        // 观察点地址属于旧的地址空间
+        crate::hal::clear_watchpoint(self.pid.0);
 
         // 为 glibc 分配用户 heap 空间（0x1c0000 ~ 0x1c4000）
         use crate::mm::{VirtAddr, MapPermission};
@@ -586,6 +728,11 @@ impl TaskControlBlock {
         inner.clear_child_tid = 0;
         // 重置robust_list
         inner.robust_list = RobustList::default();
+        inner.single_step = false;
+        // 被跟踪的进程 execve 成功后收到 SIGTRAP，跟踪者由此得知新程序开始运行
+        if inner.ptrace.is_some() {
+            inner.add_signal(Signals::SIGTRAP);
+        }
         // 更新堆指针
         inner.heap_bottom = program_break;
         inner.heap_pt = program_break;
@@ -593,22 +740,19 @@ impl TaskControlBlock {
         *self.exe.lock() = elf;
         // 清理资源
         // 关闭原文件描述符
-        self.files.lock().iter_mut().for_each(|fd| match fd {
-            Some(file) => {
-                if file.get_cloexec() {
-                    *fd = None;
-                }
-            }
-            None => (),
-        });
+        self.files.lock().close_on_exec();
         // 替换内存映射
         *self.vm.lock() = memory_set;
-        // 清空信号处理函数表
+        // 清空信号处理函数表，被忽略的信号仍然被忽略
         for sigact in self.sighand.lock().iter_mut() {
-            *sigact = None;
+            if !sigact.as_ref().map_or(false, |act| act.is_ignore()) {
+                *sigact = None;
+            }
         }
         // 清空futex
         self.futex.lock().clear();
+        // 删除 POSIX 定时器
+        self.posix_timers.lock().clear();
         // 检查当前任务是否是多线程任务
         if self.tid_allocator.lock().get_allocated() > 1 {
             // 遍历所有 CPU 的管理器进行清理
@@ -690,6 +834,7 @@ impl TaskControlBlock {
                 ustack_bottom_from_tid(tid)
             },
             exit_signal,
+            start_time: TimeVal::now(),
             running_on_cpu: AtomicUsize::new(TASK_NOT_RUNNING),
             on_cpu: AtomicBool::new(false),
 
@@ -709,6 +854,11 @@ impl TaskControlBlock {
             } else {
                 Arc::new(Mutex::new(self.fs.lock().clone()))
             },
+            mnt_ns: Mutex::new(if flags.contains(CloneFlags::CLONE_NEWNS) {
+                self.mnt_ns.lock().copy()
+            } else {
+                self.mnt_ns.lock().clone()
+            }),
             vm: memory_set,
             sighand: if flags.contains(CloneFlags::CLONE_SIGHAND) {
                 self.sighand.clone()
@@ -721,9 +871,16 @@ impl TaskControlBlock {
                 // maybe should do clone here?
                 Arc::new(Mutex::new(Futex::new()))
             },
+            posix_timers: if flags.contains(CloneFlags::CLONE_THREAD) {
+                self.posix_timers.clone()
+            } else {
+                Arc::new(Mutex::new(PosixTimers::new()))
+            },
             inner: Mutex::new(TaskControlBlockInner {
                 // inherited
                 pgid: parent_inner.pgid,
+                sid: parent_inner.sid,
+                ctty: parent_inner.ctty.clone(),
                 heap_bottom: parent_inner.heap_bottom,
                 heap_pt: parent_inner.heap_pt,
                 // clone
@@ -749,8 +906,14 @@ impl TaskControlBlock {
                 // constants
                 task_status: TaskStatus::Ready,
                 exit_code: 0,
-                // CFS: inherit nice value from parent
-                sched_entity: SchedEntity::new(parent_inner.sched_entity.nice),
+                // CFS: 继承父任务的 nice 值、CPU 亲和性与 cpuset
+                sched_entity: SchedEntity::inherit(&parent_inner.sched_entity),
+                single_step: false,
+                in_syscall: false,
+                stop_report: None,
+                ptrace: None,
+                fpu_saves: 0,
+                fpu_restores: 0,
             }),
         });
         // 添加到父进程或者祖父进程的子进程列表
@@ -784,7 +947,9 @@ impl TaskControlBlock {
             trap_cx.gp.tp = tls;
         }
         // 对于子进程，fork返回0
-        trap_cx.gp.a0 = 0;
+        trap_cx.set_return(0);
+        // 子任务从未装载过浮点寄存器，首次使用时从复制来的上下文装载
+        trap_cx.invalidate_fp();
         // 修改陷阱上下文中的内核栈指针
         trap_cx.kernel_sp = kstack_top;
         // 【关键修复】设置 kernel_tp
@@ -822,9 +987,10 @@ impl TaskControlBlock {
 }
 
 impl Drop for TaskControlBlock {
-    /// 当任务控制块被销毁时，释放线程ID
+    /// 当任务控制块被销毁时，释放线程ID并退出所在的 cpuset
     fn drop(&mut self) {
         self.tid_allocator.lock().dealloc(self.tid);
+        cpuset::leave(self.inner.get_mut().sched_entity.cpuset);
     }
 }
 
@@ -839,4 +1005,6 @@ pub enum TaskStatus {
     Zombie,
     /// 可中断态
     Interruptible,
+    /// 停止态：被停止信号停止，只有 SIGCONT 与 SIGKILL 能让它继续
+    Stopped,
 }
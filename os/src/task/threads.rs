@@ -9,16 +9,35 @@
     此文件内容用于
     内容与RISCV版本相同，无需修改
 */
-use crate::{syscall::errno::*, task::current_task, timer::TimeSpec};
-use alloc::{collections::BTreeMap, sync::Arc};
+use crate::{
+    mm::{PageTable, PageTableImpl, VirtAddr},
+    syscall::errno::*,
+    task::current_task,
+    timer::TimeSpec,
+};
+use alloc::{
+    collections::BTreeMap,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::sync::atomic::{AtomicU32, Ordering};
 use log::*;
 use num_enum::FromPrimitive;
 
 use super::{
     block_current_and_run_next,
-    manager::{wait_with_timeout, WaitQueue},
+    manager::{set_pi_priority, wait_with_timeout, WaitQueue},
+    TaskControlBlock,
 };
 
+/// A PI futex word or robust futex word has waiters blocked in the kernel,
+/// so the owner must unlock it through the kernel
+pub const FUTEX_WAITERS: u32 = 0x8000_0000;
+/// The owner of the futex word died without unlocking it
+pub const FUTEX_OWNER_DIED: u32 = 0x4000_0000;
+/// The bits of a PI futex word or robust futex word holding the owner's tid
+pub const FUTEX_TID_MASK: u32 = 0x3fff_ffff;
+
 #[allow(unused)]
 #[derive(Debug, Eq, PartialEq, FromPrimitive)]
 #[repr(u32)]
@@ -41,17 +60,36 @@ pub enum FutexCmd {
 
     /// File descriptor operations (not implemented)
     Fd = 2,
-    /// Requeue waiters (not implemented)
+    /// Requeue waiters
+    ///
+    /// Wakes at most `val` waiters and moves at most `val2` of the
+    /// remaining ones to the futex at `uaddr2`.
     Requeue = 3,
-    /// Compare and requeue (not implemented)
+    /// Compare and requeue
+    ///
+    /// Same as `Requeue`, but fails with `EAGAIN` unless the futex word
+    /// still contains `val3`.
     CmpRequeue = 4,
-    /// Wake with operation (not implemented)
+    /// Wake with operation
+    ///
+    /// Applies the operation encoded in `val3` to the word at `uaddr2`,
+    /// wakes at most `val` waiters on `uaddr`, and if the old value at
+    /// `uaddr2` passes the encoded comparison, at most `val2` waiters
+    /// on `uaddr2`. See [`FutexWakeOp`].
     WakeOp = 5,
-    /// Priority inheritance lock (not implemented)
+    /// Priority inheritance lock
+    ///
+    /// Takes the lock whose futex word holds the owner's tid, blocking
+    /// until the owner hands it over. While blocked, the owner runs with
+    /// at least the waiter's real-time priority.
     LockPi = 6,
-    /// Priority inheritance unlock (not implemented)
+    /// Priority inheritance unlock
+    ///
+    /// Hands the lock over to the highest-priority waiter.
     UnlockPi = 7,
-    /// Try priority inheritance lock (not implemented)
+    /// Try priority inheritance lock
+    ///
+    /// Same as `LockPi`, but fails with `EAGAIN` instead of blocking.
     TrylockPi = 8,
     /// Wait with bitset (not implemented)
     WaitBitset = 9,
@@ -61,12 +99,137 @@ pub enum FutexCmd {
     Invalid,
 }
 
+/// The operation of `FUTEX_WAKE_OP`, encoded in `val3` as
+/// `op:4 | cmp:4 | oparg:12 | cmparg:12` from the most significant bits
+///
+/// The word at `uaddr2` is replaced by `old <op> oparg` and the waiters on
+/// `uaddr2` are woken if `old <cmp> cmparg` holds. Both arguments are signed
+/// 12-bit values; with `FUTEX_OP_OPARG_SHIFT` set in `op`, the operand is
+/// `1 << oparg` instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FutexWakeOp {
+    op: u32,
+    oparg: u32,
+    cmp: u32,
+    cmparg: i32,
+}
+
+impl FutexWakeOp {
+    const OPARG_SHIFT: u32 = 8;
+    const OP_SET: u32 = 0;
+    const OP_ADD: u32 = 1;
+    const OP_OR: u32 = 2;
+    const OP_ANDN: u32 = 3;
+    const OP_XOR: u32 = 4;
+    const CMP_EQ: u32 = 0;
+    const CMP_NE: u32 = 1;
+    const CMP_LT: u32 = 2;
+    const CMP_LE: u32 = 3;
+    const CMP_GT: u32 = 4;
+    const CMP_GE: u32 = 5;
+
+    /// Decode `val3`, `ENOSYS` for an unknown operation or comparison
+    pub fn decode(val3: u32) -> Result<Self, isize> {
+        let op = val3 >> 28;
+        let cmp = (val3 >> 24) & 0xf;
+        // 两个参数都是有符号的 12 位数
+        let mut oparg = ((val3 << 8) as i32 >> 20) as u32;
+        let cmparg = (val3 << 20) as i32 >> 20;
+        if op & Self::OPARG_SHIFT != 0 {
+            oparg = 1 << (oparg & 31);
+        }
+        let op = op & !Self::OPARG_SHIFT;
+        if op > Self::OP_XOR || cmp > Self::CMP_GE {
+            return Err(ENOSYS);
+        }
+        Ok(Self {
+            op,
+            oparg,
+            cmp,
+            cmparg,
+        })
+    }
+
+    /// The new value of the word at `uaddr2` whose old value is `old`
+    pub fn apply(&self, old: u32) -> u32 {
+        match self.op {
+            Self::OP_SET => self.oparg,
+            Self::OP_ADD => old.wrapping_add(self.oparg),
+            Self::OP_OR => old | self.oparg,
+            Self::OP_ANDN => old & !self.oparg,
+            _ => old ^ self.oparg,
+        }
+    }
+
+    /// Whether the waiters on `uaddr2` are woken, given its old value `old`
+    pub fn test(&self, old: u32) -> bool {
+        let old = old as i32;
+        match self.cmp {
+            Self::CMP_EQ => old == self.cmparg,
+            Self::CMP_NE => old != self.cmparg,
+            Self::CMP_LT => old < self.cmparg,
+            Self::CMP_LE => old <= self.cmparg,
+            Self::CMP_GT => old > self.cmparg,
+            _ => old >= self.cmparg,
+        }
+    }
+}
+
 /// Fast Userspace Mutex (Futex)
 ///
 /// Manages wait queues for futex operations. Maps futex addresses
 /// to their associated wait queues.
 pub struct Futex {
     inner: BTreeMap<usize, WaitQueue>,
+    /// Waiters of the PI futexes, keyed by the address of the futex word
+    pi: BTreeMap<usize, PiFutex>,
+}
+
+/// Owner and waiters of a contended priority-inheritance futex
+struct PiFutex {
+    /// Tid of the owner, the one the waiters' priority is lent to
+    owner: usize,
+    /// Blocked waiters with their real-time priority when they blocked
+    waiters: Vec<(Weak<TaskControlBlock>, u8)>,
+}
+
+impl PiFutex {
+    fn new(owner: usize) -> Self {
+        Self {
+            owner,
+            waiters: Vec::new(),
+        }
+    }
+
+    /// Highest real-time priority among the waiters, 0 if none is real-time
+    fn top_priority(&self) -> u8 {
+        self.waiters
+            .iter()
+            .map(|&(_, priority)| priority)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Remove and return the waiter the lock goes to next: the one with the
+    /// highest priority, the earliest among equals. Exited waiters are dropped
+    fn pop_top_waiter(&mut self) -> Option<Arc<TaskControlBlock>> {
+        self.waiters.retain(|(task, _)| task.strong_count() != 0);
+        let mut top = None;
+        for (i, &(_, priority)) in self.waiters.iter().enumerate() {
+            match top {
+                Some((_, top_priority)) if top_priority >= priority => {}
+                _ => top = Some((i, priority)),
+            }
+        }
+        let (task, _) = self.waiters.remove(top?.0);
+        task.upgrade()
+    }
+
+    /// Remove `task` from the waiters
+    fn remove(&mut self, task: &Arc<TaskControlBlock>) {
+        self.waiters
+            .retain(|(waiter, _)| !core::ptr::eq(waiter.as_ptr(), Arc::as_ptr(task)));
+    }
 }
 
 /// Implement futex wait operation
@@ -82,7 +245,9 @@ pub struct Futex {
 /// # Returns
 /// * `SUCCESS` on successful wake
 /// * `EAGAIN` if futex value doesn't match
-/// * `EINTR` if interrupted by signal
+/// * `ERESTART` if interrupted by signal while waiting without a timeout, so that the
+///   wait is restarted under `SA_RESTART`
+/// * `EINTR` if interrupted by signal while waiting with a timeout
 ///
 /// # Note
 /// Currently ignores the `rt_clk` parameter
@@ -93,6 +258,11 @@ pub fn do_futex_wait(futex_word: &mut u32, val: u32, timeout: Option<TimeSpec>)
     // Get futex address as key
     let futex_word_addr = futex_word as *const u32 as usize;
 
+    let task = current_task().unwrap();
+    // 获取 Futex 的锁，以便修改等待队列。
+    // 比较在锁内进行，与 FUTEX_CMP_REQUEUE 等修改等待队列的操作互斥
+    let mut futex = task.futex.lock();
+
     // Atomically check value and block
     if *futex_word != val {
         trace!(
@@ -102,11 +272,6 @@ pub fn do_futex_wait(futex_word: &mut u32, val: u32, timeout: Option<TimeSpec>)
         );
         return EAGAIN;
     } else {
-        let task = current_task().unwrap();
-
-        // 获取 Futex 的锁，以便修改等待队列。
-        let mut futex = task.futex.lock();
-
         // 从 Futex 的等待队列中移除当前地址对应的队列（如果存在），否则创建一个新的等待队列。
         let mut wait_queue = if let Some(wait_queue) = futex.inner.remove(&futex_word_addr) {
             wait_queue
@@ -141,8 +306,8 @@ pub fn do_futex_wait(futex_word: &mut u32, val: u32, timeout: Option<TimeSpec>)
         let inner = task.acquire_inner_lock();
         // 检查是否有未屏蔽的信号挂起
         if !inner.sigpending.difference(inner.sigmask).is_empty() {
-            // 有未屏蔽的信号，返回 `EINTR` 错误。
-            return EINTR;
+            // 有未屏蔽的信号：没有超时的等待可以重新开始，有超时的等待与 Linux 一样返回 `EINTR`
+            return if timeout.is_none() { ERESTART } else { EINTR };
         }
 
         // 如果没有信号中断，返回成功。
@@ -156,6 +321,7 @@ impl Futex {
     pub fn new() -> Self {
         Self {
             inner: BTreeMap::new(),
+            pi: BTreeMap::new(),
         }
     }
 
@@ -172,7 +338,8 @@ impl Futex {
         }
     }
 
-    /// 重新排列
+    /// 唤醒 `futex_word` 上最多 val 个任务，再把最多 val2 个剩余的任务移到 `futex_word_2` 的等待队列，
+    /// 返回唤醒与移动的任务总数
     pub fn requeue(&mut self, futex_word: &u32, futex_word_2: &u32, val: u32, val2: u32) -> isize {
         let futex_word_addr = futex_word as *const u32 as usize;
         let futex_word_addr_2 = futex_word_2 as *const u32 as usize;
@@ -181,6 +348,10 @@ impl Futex {
         } else {
             0
         };
+        // 移到同一个队列上什么也不用做，而且两个队列会在下面互相覆盖
+        if futex_word_addr == futex_word_addr_2 {
+            return wake_cnt;
+        }
         if let Some(mut wait_queue) = self.inner.remove(&futex_word_addr) {
             let mut wait_queue_2 = if let Some(wait_queue) = self.inner.remove(&futex_word_addr_2) {
                 wait_queue
@@ -209,8 +380,345 @@ impl Futex {
         }
     }
 
+    /// 对 `futex_word_2` 原子地执行 `op`，唤醒 `futex_word` 上最多 val 个任务，
+    /// 原值满足 `op` 的比较时再唤醒 `futex_word_2` 上最多 val2 个任务，返回唤醒的任务总数
+    pub fn wake_op(
+        &mut self,
+        futex_word: &u32,
+        futex_word_2: &mut u32,
+        val: u32,
+        val2: u32,
+        op: FutexWakeOp,
+    ) -> isize {
+        let futex_word_addr = futex_word as *const u32 as usize;
+        let futex_word_addr_2 = futex_word_2 as *const u32 as usize;
+        // 其他核上的用户线程可能同时修改这个字，需要原子的读-改-写
+        let word = unsafe { &*(futex_word_2 as *mut u32 as *const AtomicU32) };
+        let old = word
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |old| {
+                Some(op.apply(old))
+            })
+            .unwrap();
+        let mut woken = self.wake(futex_word_addr, val);
+        if op.test(old) {
+            woken += self.wake(futex_word_addr_2, val2);
+        }
+        woken
+    }
+
+    /// 线程 `owner` 应继承的实时优先级：它持有的所有 PI futex 上等待者的最高优先级
+    fn pi_boost(&self, owner: usize) -> u8 {
+        self.pi
+            .values()
+            .filter(|pi| pi.owner == owner)
+            .map(PiFutex::top_priority)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// 把地址为 `futex_word_addr` 的 PI futex 交给优先级最高的等待者，字中再或上 `flags`；
+    /// 没有等待者时字改为 `flags`。返回新的持有者与它应继承的优先级，由调用者唤醒它
+    fn pi_handoff(
+        &mut self,
+        futex_word_addr: usize,
+        flags: u32,
+    ) -> Option<(Arc<TaskControlBlock>, u8)> {
+        let word = unsafe { &*(futex_word_addr as *const AtomicU32) };
+        let next = self
+            .pi
+            .get_mut(&futex_word_addr)
+            .and_then(PiFutex::pop_top_waiter);
+        match next {
+            Some(task) => {
+                let tid = task.pid.0;
+                let pi = self.pi.get_mut(&futex_word_addr).unwrap();
+                pi.owner = tid;
+                // 还有等待者时新的持有者也要经内核解锁
+                if pi.waiters.is_empty() {
+                    self.pi.remove(&futex_word_addr);
+                    word.store(tid as u32 | flags, Ordering::SeqCst);
+                } else {
+                    word.store(tid as u32 | flags | FUTEX_WAITERS, Ordering::SeqCst);
+                }
+                let boost = self.pi_boost(tid);
+                Some((task, boost))
+            }
+            None => {
+                self.pi.remove(&futex_word_addr);
+                word.store(flags, Ordering::SeqCst);
+                None
+            }
+        }
+    }
+
     /// 清空队列
     pub fn clear(&mut self) {
         self.inner.clear();
+        self.pi.clear();
+    }
+}
+
+/// 同一线程组中线程号为 `tid` 的线程；线程与线程组的首进程有相同的父进程
+fn find_thread(task: &Arc<TaskControlBlock>, tid: usize) -> Option<Arc<TaskControlBlock>> {
+    if task.pid.0 == tid {
+        return Some(task.clone());
+    }
+    let parent = task
+        .acquire_inner_lock()
+        .parent
+        .as_ref()
+        .and_then(Weak::upgrade)?;
+    let parent_inner = parent.acquire_inner_lock();
+    parent_inner
+        .children
+        .iter()
+        .find(|child| child.pid.0 == tid && child.tgid == task.tgid)
+        .cloned()
+}
+
+/// 唤醒在 PI futex 上阻塞、刚得到锁的任务 `task`
+fn wake_pi_owner(task: &Arc<TaskControlBlock>) {
+    let mut wait_queue = WaitQueue::new();
+    wait_queue.add_task(Arc::downgrade(task));
+    wait_queue.wake_all();
+}
+
+/// 获取 PI futex (`FUTEX_LOCK_PI`, `FUTEX_TRYLOCK_PI`)
+///
+/// 字为 0（可能带 `FUTEX_OWNER_DIED`）时写入当前线程的 tid 并返回；否则设置
+/// `FUTEX_WAITERS` 并阻塞，持有者在等待期间继承当前线程的实时优先级，解锁时把锁
+/// 直接交给优先级最高的等待者。`timeout` 是绝对时间
+///
+/// # 返回值
+/// * `SUCCESS`：已持有锁，字中带 `FUTEX_OWNER_DIED` 时前一个持有者死亡时没有解锁
+/// * `EDEADLK`：当前线程已经持有锁
+/// * `EAGAIN`：`trylock` 且锁已被持有
+/// * `ESRCH`：字中的持有者不存在
+/// * `ETIMEDOUT`：超时
+/// * `ERESTART`：被信号打断
+pub fn do_futex_lock_pi(futex_word: &mut u32, timeout: Option<TimeSpec>, trylock: bool) -> isize {
+    let futex_word_addr = futex_word as *mut u32 as usize;
+    let word = unsafe { &*(futex_word_addr as *const AtomicU32) };
+    let mut waited = false;
+    loop {
+        let task = current_task().unwrap();
+        let tid = task.pid.0 as u32;
+        let mut futex = task.futex.lock();
+        let val = word.load(Ordering::SeqCst);
+        let owner = val & FUTEX_TID_MASK;
+        if owner == tid {
+            // 醒来时锁已经由解锁者交给了当前线程
+            return if waited { SUCCESS } else { EDEADLK };
+        }
+        if waited {
+            let inner = task.acquire_inner_lock();
+            let interrupted = !inner.sigpending.difference(inner.sigmask).is_empty();
+            drop(inner);
+            let timed_out = timeout.map_or(false, |timeout| TimeSpec::now() >= timeout);
+            if interrupted || timed_out {
+                // 不再等待，持有者不再继承当前线程的优先级
+                if let Some(pi) = futex.pi.get_mut(&futex_word_addr) {
+                    pi.remove(&task);
+                }
+                let boost = futex.pi_boost(owner as usize);
+                if let Some(owner) = find_thread(&task, owner as usize) {
+                    drop(futex);
+                    set_pi_priority(&owner, boost);
+                }
+                return if interrupted { ERESTART } else { ETIMEDOUT };
+            }
+        }
+        if owner == 0 {
+            let waiters = futex
+                .pi
+                .get(&futex_word_addr)
+                .map_or(false, |pi| !pi.waiters.is_empty());
+            let mut locked = tid | (val & FUTEX_OWNER_DIED);
+            if waiters {
+                locked |= FUTEX_WAITERS;
+            }
+            if word
+                .compare_exchange(val, locked, Ordering::SeqCst, Ordering::SeqCst)
+                .is_err()
+            {
+                continue;
+            }
+            // 锁空出来时还有等待者（比如持有者死亡），当前线程继承它们的优先级
+            if let Some(pi) = futex.pi.get_mut(&futex_word_addr) {
+                pi.owner = tid as usize;
+                let boost = futex.pi_boost(tid as usize);
+                drop(futex);
+                set_pi_priority(&task, boost);
+            }
+            return SUCCESS;
+        }
+        if trylock {
+            return EAGAIN;
+        }
+        // 先找到持有者，持有者不存在时不能留下没有等待者的 FUTEX_WAITERS
+        let owner = owner as usize;
+        let owner_task = match find_thread(&task, owner) {
+            Some(owner_task) => owner_task,
+            None => return ESRCH,
+        };
+        // 设置 FUTEX_WAITERS，持有者解锁时就会进入内核把锁交出来
+        if word
+            .compare_exchange(val, val | FUTEX_WAITERS, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            continue;
+        }
+        let priority = task.acquire_inner_lock().sched_entity.rt_priority;
+        let pi = futex
+            .pi
+            .entry(futex_word_addr)
+            .or_insert_with(|| PiFutex::new(owner));
+        pi.owner = owner;
+        pi.waiters.push((Arc::downgrade(&task), priority));
+        let boost = futex.pi_boost(owner);
+        if let Some(timeout) = timeout {
+            wait_with_timeout(Arc::downgrade(&task), timeout);
+        }
+        drop(futex);
+        set_pi_priority(&owner_task, boost);
+        drop(owner_task);
+        drop(task);
+        block_current_and_run_next();
+        waited = true;
+    }
+}
+
+/// 释放 PI futex (`FUTEX_UNLOCK_PI`)，锁交给优先级最高的等待者；
+/// 当前线程不是持有者时返回 `EPERM`
+pub fn do_futex_unlock_pi(futex_word: &mut u32) -> isize {
+    let futex_word_addr = futex_word as *mut u32 as usize;
+    let task = current_task().unwrap();
+    let tid = task.pid.0;
+    let mut futex = task.futex.lock();
+    if *futex_word & FUTEX_TID_MASK != tid as u32 {
+        return EPERM;
+    }
+    let next = futex.pi_handoff(futex_word_addr, 0);
+    let boost = futex.pi_boost(tid);
+    drop(futex);
+    set_pi_priority(&task, boost);
+    if let Some((next, boost)) = next {
+        set_pi_priority(&next, boost);
+        wake_pi_owner(&next);
+    }
+    SUCCESS
+}
+
+/// `struct robust_list_head`，`list` 是链表头，链表项中的 futex 字位于项地址加
+/// `futex_offset` 处，`list_op_pending` 是正在加锁或解锁、可能还不在链表中的项
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct RobustListHead {
+    list: usize,
+    futex_offset: isize,
+    list_op_pending: usize,
+}
+
+/// 最多处理的 robust 链表项数，防止用户构造的环
+const ROBUST_LIST_LIMIT: usize = 2048;
+
+/// 用户地址 `addr` 处的对象。退出的任务不能再处理缺页，只访问已经映射的内存
+fn mapped_user_ref<T>(token: usize, addr: usize) -> Option<&'static T> {
+    if addr == 0 || addr % core::mem::align_of::<T>() != 0 {
+        return None;
+    }
+    PageTableImpl::from_token(token)
+        .translate_va(VirtAddr::from(addr))
+        .map(|pa| pa.get_ref())
+}
+
+/// 线程 `tid` 死亡时仍持有的 robust futex：字改为 `FUTEX_OWNER_DIED`，并唤醒一个等待者；
+/// PI futex 则直接交给优先级最高的等待者
+fn handle_futex_death(
+    futex: &mut Futex,
+    token: usize,
+    uaddr: usize,
+    tid: usize,
+    pi: bool,
+    woken: &mut Vec<(Arc<TaskControlBlock>, u8)>,
+) {
+    let word = match mapped_user_ref::<AtomicU32>(token, uaddr) {
+        Some(word) => word,
+        None => return,
+    };
+    let val = word.load(Ordering::SeqCst);
+    if val & FUTEX_TID_MASK != tid as u32 {
+        return;
+    }
+    let futex_word_addr = word as *const AtomicU32 as usize;
+    if pi {
+        woken.extend(futex.pi_handoff(futex_word_addr, FUTEX_OWNER_DIED));
+    } else {
+        word.store((val & FUTEX_WAITERS) | FUTEX_OWNER_DIED, Ordering::SeqCst);
+        if val & FUTEX_WAITERS != 0 {
+            futex.wake(futex_word_addr, 1);
+        }
+    }
+}
+
+/// 任务退出时释放它持有的 futex：遍历用户态的 robust 链表（`set_robust_list` 登记的
+/// `robust_list_head`，项地址的最低位表示 PI futex），再把仍由它持有的 PI futex 交给等待者
+pub fn exit_robust_futexes(task: &Arc<TaskControlBlock>, token: usize, robust_list_head: usize) {
+    let tid = task.pid.0;
+    let mut woken = Vec::new();
+    let mut futex = task.futex.lock();
+    if let Some(head) = mapped_user_ref::<RobustListHead>(token, robust_list_head).copied() {
+        let futex_of = |entry: usize| (entry & !1).wrapping_add(head.futex_offset as usize);
+        let mut entry = head.list;
+        let mut limit = ROBUST_LIST_LIMIT;
+        while entry != robust_list_head && limit != 0 {
+            // 先读出下一项，唤醒的线程可能立即释放这一项
+            let next = match mapped_user_ref::<usize>(token, entry & !1) {
+                Some(next) => *next,
+                None => break,
+            };
+            if entry & !1 != head.list_op_pending & !1 {
+                handle_futex_death(
+                    &mut futex,
+                    token,
+                    futex_of(entry),
+                    tid,
+                    entry & 1 != 0,
+                    &mut woken,
+                );
+            }
+            entry = next;
+            limit -= 1;
+        }
+        if head.list_op_pending != 0 {
+            let pending = head.list_op_pending;
+            handle_futex_death(
+                &mut futex,
+                token,
+                futex_of(pending),
+                tid,
+                pending & 1 != 0,
+                &mut woken,
+            );
+        }
+    }
+    let owned: Vec<usize> = futex
+        .pi
+        .iter()
+        .filter(|(_, pi)| pi.owner == tid)
+        .map(|(&futex_word_addr, _)| futex_word_addr)
+        .collect();
+    for futex_word_addr in owned {
+        let word = unsafe { &*(futex_word_addr as *const AtomicU32) };
+        if word.load(Ordering::SeqCst) & FUTEX_TID_MASK == tid as u32 {
+            woken.extend(futex.pi_handoff(futex_word_addr, FUTEX_OWNER_DIED));
+        } else {
+            futex.pi.get_mut(&futex_word_addr).unwrap().owner = 0;
+        }
+    }
+    drop(futex);
+    for (task, boost) in woken {
+        set_pi_priority(&task, boost);
+        wake_pi_owner(&task);
     }
 }
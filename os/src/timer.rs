@@ -317,6 +317,22 @@ impl ITimerVal {
     }
 }
 
+/// `struct itimerspec` of the POSIX timers
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct ITimerSpec {
+    pub it_interval: TimeSpec,
+    pub it_value: TimeSpec,
+}
+impl ITimerSpec {
+    pub fn new() -> Self {
+        Self {
+            it_interval: TimeSpec::new(),
+            it_value: TimeSpec::new(),
+        }
+    }
+}
+
 #[derive(Clone, Copy)]
 /// Store the current process times used in the `time()`.
 #[repr(C)]
@@ -344,7 +360,8 @@ pub trait TimeSource {
     fn uptime(&self) -> u64;
 }
 
-/// 系统启动时间偏移（使 current_time = Unix 时间）
+/// CLOCK_REALTIME 与启动以来的时间之差（纳秒），使 `realtime_now` 为 Unix 时间。
+/// 启动时由 RTC 或 `now=` 启动参数设置，之后由 clock_settime 修改
 static BOOT_TIME_OFFSET: AtomicU64 = AtomicU64::new(0);
 
 static mut TIME_SOURCE: Option<&'static dyn TimeSource> = None;
@@ -359,8 +376,7 @@ pub fn init_time_source(ts: &'static dyn TimeSource) {
 /// 从引导参数（cmdline）中提取 `now=` 时间戳（Unix 时间）
 pub fn init_time_from_cmdline(cmdline: &str) {
     if let Some(ts) = parse_cmdline_boot_time(cmdline) {
-        let uptime = uptime();
-        BOOT_TIME_OFFSET.store(ts - uptime, core::sync::atomic::Ordering::Relaxed);
+        set_realtime(TimeSpec::from_s(ts as usize));
     } else {
         panic!("no valid now= timestamp in cmdline");
     }
@@ -368,7 +384,35 @@ pub fn init_time_from_cmdline(cmdline: &str) {
 
 /// 当前 Unix 时间戳
 pub fn current_time() -> u64 {
-    uptime() + BOOT_TIME_OFFSET.load(core::sync::atomic::Ordering::Relaxed)
+    realtime_now().tv_sec as u64
+}
+
+/// 从 RTC 读出当前时间作为 CLOCK_REALTIME 的初值；没有 RTC 时 CLOCK_REALTIME 从 0 开始
+pub fn init_realtime() {
+    if let Some(secs) = crate::fs::dev::hwclock::read_rtc() {
+        set_realtime(TimeSpec::from_s(secs as usize));
+    }
+}
+
+/// 当前的 CLOCK_REALTIME
+pub fn realtime_now() -> TimeSpec {
+    TimeSpec::now() + realtime_offset()
+}
+
+/// 把 CLOCK_REALTIME 设为 `time` (clock_settime)，CLOCK_MONOTONIC 不受影响；
+/// `time` 早于启动时刻时按启动时刻处理
+pub fn set_realtime(time: TimeSpec) {
+    let offset = time - TimeSpec::now();
+    BOOT_TIME_OFFSET.store(offset.to_ns() as u64, core::sync::atomic::Ordering::Relaxed);
+}
+
+/// 按当前的偏移把 CLOCK_REALTIME 的时刻换算成启动以来的时间
+pub fn realtime_to_monotonic(time: TimeSpec) -> TimeSpec {
+    time - realtime_offset()
+}
+
+fn realtime_offset() -> TimeSpec {
+    TimeSpec::from_ns(BOOT_TIME_OFFSET.load(core::sync::atomic::Ordering::Relaxed) as usize)
 }
 
 /// 获取系统启动以来的时间（秒）
@@ -1,6 +1,11 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+
 use rand_core::RngCore;
+use spin::Mutex;
 
-use crate::timer::get_time_ms;
+use crate::mm::UserBuffer;
+use crate::timer::{get_time, get_time_ms};
+use crate::utils::InterruptGuard;
 
 pub struct Rng {
     pub seed: usize,
@@ -45,3 +50,177 @@ impl Rng {
 }
 
 pub static mut RNG: Rng = Rng { seed: BIGPRIME };
+
+/// 熵池的 32 位字数，与一个 ChaCha 块相同
+const POOL_WORDS: usize = 16;
+/// 熵池最多记入的熵（位）
+const POOL_BITS: usize = 512;
+/// 记入的熵达到该值后熵池才算初始化完成，此前阻塞的 getrandom 一直等待
+pub const POOL_READY_BITS: usize = 256;
+/// 启动时测量计时器抖动的采样次数
+const JITTER_SAMPLES: usize = 2048;
+
+/// 熵池：样本异或进池中轮流的位置，每填满一轮做一次 ChaCha 置换；
+/// 输出以池内容为密钥生成 ChaCha 块，输出后池内容换成一个新块，
+/// 之后无法再从池的状态反推已经给出的随机数
+pub struct EntropyPool {
+    state: [u32; POOL_WORDS],
+    /// 下一个样本混入的位置
+    cursor: usize,
+    /// 估计已混入的熵（位）
+    entropy_bits: usize,
+    /// 输出块计数，保证同一密钥下每块都不同
+    counter: u64,
+}
+
+fn quarter_round(state: &mut [u32; POOL_WORDS], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(12);
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
+
+/// ChaCha20 的 20 轮置换
+fn permute(state: &mut [u32; POOL_WORDS]) {
+    for _ in 0..10 {
+        quarter_round(state, 0, 4, 8, 12);
+        quarter_round(state, 1, 5, 9, 13);
+        quarter_round(state, 2, 6, 10, 14);
+        quarter_round(state, 3, 7, 11, 15);
+        quarter_round(state, 0, 5, 10, 15);
+        quarter_round(state, 1, 6, 11, 12);
+        quarter_round(state, 2, 7, 8, 13);
+        quarter_round(state, 3, 4, 9, 14);
+    }
+}
+
+/// 以 `key` 与块号 `counter` 生成一个 ChaCha 块，置换结果加上输入，不能反推 `key`
+fn chacha_block(key: &[u32; POOL_WORDS], counter: u64) -> [u32; POOL_WORDS] {
+    let mut input = *key;
+    input[12] ^= counter as u32;
+    input[13] ^= (counter >> 32) as u32;
+    let mut block = input;
+    permute(&mut block);
+    for (word, input) in block.iter_mut().zip(input.iter()) {
+        *word = word.wrapping_add(*input);
+    }
+    block
+}
+
+impl EntropyPool {
+    pub const fn new() -> Self {
+        Self {
+            // "expand 32-byte k"，与 ChaCha 的常量相同，其余位置等待样本混入
+            state: [
+                0x61707865, 0x3320646e, 0x79622d32, 0x6b206574, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            ],
+            cursor: 4,
+            entropy_bits: 0,
+            counter: 0,
+        }
+    }
+
+    /// 混入一个样本，并记入估计含有的 `credit_bits` 位熵
+    pub fn mix(&mut self, sample: u64, credit_bits: usize) {
+        self.state[self.cursor] ^= sample as u32;
+        self.state[self.cursor + 1] ^= (sample >> 32) as u32;
+        self.cursor += 2;
+        if self.cursor == POOL_WORDS {
+            permute(&mut self.state);
+            self.cursor = 4;
+        }
+        self.entropy_bits = (self.entropy_bits + credit_bits).min(POOL_BITS);
+    }
+
+    /// 记入的熵是否已足够让输出不可预测
+    pub fn is_ready(&self) -> bool {
+        self.entropy_bits >= POOL_READY_BITS
+    }
+
+    /// 用随机字节填满 `dest`
+    pub fn fill(&mut self, dest: &mut [u8]) {
+        // 先把上次置换后混入的样本也带进密钥
+        permute(&mut self.state);
+        let key = self.state;
+        for chunk in dest.chunks_mut(POOL_WORDS * 4) {
+            let block = chacha_block(&key, self.counter);
+            self.counter += 1;
+            for (bytes, word) in chunk.chunks_mut(4).zip(block.iter()) {
+                bytes.copy_from_slice(&word.to_le_bytes()[..bytes.len()]);
+            }
+        }
+        self.state = chacha_block(&key, self.counter);
+        self.counter += 1;
+    }
+}
+
+/// 全局熵池
+pub static ENTROPY_POOL: Mutex<EntropyPool> = Mutex::new(EntropyPool::new());
+/// 熵池初始化完成后置位且不再清除，阻塞的 getrandom 不必加锁即可检查
+static POOL_READY: AtomicBool = AtomicBool::new(false);
+
+/// 熵池是否已初始化完成
+pub fn pool_ready() -> bool {
+    POOL_READY.load(Ordering::Acquire)
+}
+
+/// 混入样本后检查熵池是否已初始化完成
+fn mix_into(pool: &mut EntropyPool, sample: u64, credit_bits: usize) {
+    pool.mix(sample, credit_bits);
+    if pool.is_ready() && !pool_ready() {
+        POOL_READY.store(true, Ordering::Release);
+    }
+}
+
+/// 启动时调用：反复测量一段长度随前一次结果变化的自旋所用的时间，把计时值混入熵池，
+/// 相邻两次耗时之差发生变化的样本记 1 位熵
+pub fn init() {
+    let _guard = InterruptGuard::new();
+    let mut pool = ENTROPY_POOL.lock();
+    let mut last = get_time() as u64;
+    let mut last_delta = 0;
+    for _ in 0..JITTER_SAMPLES {
+        for _ in 0..(last & 0x3f) {
+            core::hint::spin_loop();
+        }
+        let now = get_time() as u64;
+        let delta = now.wrapping_sub(last);
+        mix_into(&mut pool, now, (delta != last_delta) as usize);
+        last = now;
+        last_delta = delta;
+    }
+    log::info!(
+        "[random] {} bits of timer jitter collected",
+        pool.entropy_bits
+    );
+}
+
+/// 中断处理时调用：混入中断号与到达的时刻，每次记 1 位熵；
+/// 熵池正被占用时丢弃这个样本，中断处理从不等待
+pub fn add_interrupt_randomness(irq: usize) {
+    if let Some(mut pool) = ENTROPY_POOL.try_lock() {
+        mix_into(&mut pool, (get_time() as u64) ^ ((irq as u64) << 56), 1);
+    }
+}
+
+/// 每次加锁填充的字节数，熵池的锁不会被长时间占用
+const FILL_CHUNK: usize = 256;
+
+/// 用熵池输出填满 `dest`，不检查熵池是否已初始化完成
+pub fn fill_random(dest: &mut [u8]) {
+    for chunk in dest.chunks_mut(FILL_CHUNK) {
+        let _guard = InterruptGuard::new();
+        ENTROPY_POOL.lock().fill(chunk);
+    }
+}
+
+/// 用熵池输出填满用户缓冲区 `buf`
+pub fn fill_user(buf: &mut UserBuffer) {
+    for buffer in buf.buffers.iter_mut() {
+        fill_random(buffer);
+    }
+}
@@ -56,6 +56,11 @@ use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use alloc::string::String;
 use alloc::vec::Vec;
 use alloc::collections::BTreeMap;
+use alloc::borrow::Cow;
+use crate::fs::{OpenFlags, ROOT_FD};
+use crate::syscall::errno::{EINVAL, EIO, ENOENT, ENOTDIR};
+use crate::task::kthread;
+use crate::timer::TimeSpec;
 
 // ============================================================================
 // Metric Types
@@ -107,6 +112,15 @@ impl Counter {
         self.value.store(0, Ordering::Relaxed);
     }
 
+    /// Get the value and reset to zero in one step
+    ///
+    /// Increments racing with the call land either in the returned value or
+    /// in the next interval, never in neither, so interval deltas add up.
+    #[inline]
+    pub fn take(&self) -> u64 {
+        self.value.swap(0, Ordering::Relaxed)
+    }
+
     /// Get metric name
     #[inline]
     pub const fn name(&self) -> &'static str {
@@ -157,6 +171,18 @@ impl Gauge {
         self.value.fetch_sub(1, Ordering::Relaxed);
     }
 
+    /// Increase by `delta`
+    #[inline]
+    pub fn add(&self, delta: u64) {
+        self.value.fetch_add(delta, Ordering::Relaxed);
+    }
+
+    /// Decrease by `delta`
+    #[inline]
+    pub fn sub(&self, delta: u64) {
+        self.value.fetch_sub(delta, Ordering::Relaxed);
+    }
+
     /// Get current value
     #[inline]
     pub fn get(&self) -> u64 {
@@ -283,23 +309,37 @@ impl Histogram {
     }
 
     /// Get percentile value (approximate)
+    ///
+    /// Interpolates linearly within the bucket holding the `p`-th percentile,
+    /// assuming its observations are spread evenly between the bucket bounds.
+    /// The bounds are narrowed to the observed min and max, which also bounds
+    /// the last, open-ended bucket.
     pub fn percentile(&self, p: f64) -> u64 {
         let total = self.count.load(Ordering::Relaxed);
         if total == 0 {
             return 0;
         }
+        let min = self.min.load(Ordering::Relaxed);
+        let max = self.max.load(Ordering::Relaxed);
 
-        let target = ((total as f64) * p / 100.0) as u64;
+        let rank = (total as f64) * p.clamp(0.0, 100.0) / 100.0;
         let mut cumulative = 0u64;
 
         for (i, bucket) in self.buckets.iter().enumerate() {
-            cumulative += bucket.load(Ordering::Relaxed);
-            if cumulative >= target {
-                return LATENCY_BUCKETS[i];
+            let count = bucket.load(Ordering::Relaxed);
+            if count == 0 || ((cumulative + count) as f64) < rank {
+                cumulative += count;
+                continue;
             }
+            // values in bucket i are in (LATENCY_BUCKETS[i - 1], LATENCY_BUCKETS[i]]
+            let lower = if i == 0 { 0 } else { LATENCY_BUCKETS[i - 1] }.max(min);
+            let upper = LATENCY_BUCKETS[i].min(max).max(lower);
+            let fraction = ((rank - cumulative as f64) / count as f64).clamp(0.0, 1.0);
+            // interpolate on the width only, so large bounds keep their precision
+            return lower + ((upper - lower) as f64 * fraction) as u64;
         }
 
-        LATENCY_BUCKETS[15]
+        max
     }
 
     /// Get name
@@ -332,6 +372,85 @@ pub struct HistogramSummary {
     pub max: u64,
 }
 
+// ============================================================================
+// Labeled Counters
+// ============================================================================
+
+/// Distinct label values a [`LabeledCounter`] keeps before folding new ones
+/// into [`OVERFLOW_LABEL`]
+pub const MAX_LABELS: usize = 64;
+
+/// Label value that counts everything past [`MAX_LABELS`]
+pub const OVERFLOW_LABEL: &str = "other";
+
+/// A family of counters told apart by the value of one label,
+/// e.g. syscalls by name or interrupts by device
+///
+/// Label values are `&'static str` so recording never allocates a key.
+pub struct LabeledCounter {
+    name: &'static str,
+    description: &'static str,
+    label: &'static str,
+    values: spin::Mutex<BTreeMap<&'static str, u64>>,
+}
+
+impl LabeledCounter {
+    /// Create a new labeled counter, `label` is the label's key
+    pub const fn new(name: &'static str, description: &'static str, label: &'static str) -> Self {
+        Self {
+            name,
+            description,
+            label,
+            values: spin::Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Increment the counter for `value` by 1
+    #[inline]
+    pub fn inc(&self, value: &'static str) {
+        self.add(value, 1);
+    }
+
+    /// Add `delta` to the counter for `value`
+    pub fn add(&self, value: &'static str, delta: u64) {
+        let mut values = self.values.lock();
+        let key = if values.len() < MAX_LABELS || values.contains_key(value) {
+            value
+        } else {
+            OVERFLOW_LABEL
+        };
+        *values.entry(key).or_insert(0) += delta;
+    }
+
+    /// Get the count for `value`
+    pub fn get(&self, value: &str) -> u64 {
+        self.values.lock().get(value).copied().unwrap_or(0)
+    }
+
+    /// Snapshot of all `(value, count)` pairs, ordered by value
+    pub fn snapshot(&self) -> Vec<(&'static str, u64)> {
+        self.values.lock().iter().map(|(&k, &v)| (k, v)).collect()
+    }
+
+    /// Write one `name{label="value"}: count` line per label value
+    pub fn format_into(&self, output: &mut impl core::fmt::Write) -> core::fmt::Result {
+        for (value, count) in self.snapshot() {
+            writeln!(output, "{}{{{}=\"{}\"}}: {}", self.name, self.label, value, count)?;
+        }
+        Ok(())
+    }
+
+    /// Get counter name
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Get counter description
+    pub const fn description(&self) -> &'static str {
+        self.description
+    }
+}
+
 // ============================================================================
 // Per-CPU Counters
 // ============================================================================
@@ -368,6 +487,15 @@ impl PerCpuCounter {
         self.counters.iter().map(|c| c.load(Ordering::Relaxed)).sum()
     }
 
+    /// Get the sum across all CPUs and reset every CPU's count to zero,
+    /// without losing increments that race the reset, see [`Counter::take`]
+    pub fn take(&self) -> u64 {
+        self.counters
+            .iter()
+            .map(|c| c.swap(0, Ordering::Relaxed))
+            .sum()
+    }
+
     /// Get per-CPU values
     pub fn per_cpu(&self) -> [u64; MAX_CPU_NUM] {
         let mut result = [0u64; MAX_CPU_NUM];
@@ -378,6 +506,55 @@ impl PerCpuCounter {
     }
 }
 
+/// Per-CPU gauge, each CPU's value is set by the code owning that CPU's state
+pub struct PerCpuGauge {
+    name: &'static str,
+    values: [AtomicU64; MAX_CPU_NUM],
+}
+
+impl PerCpuGauge {
+    /// Create new per-CPU gauge
+    pub const fn new(name: &'static str) -> Self {
+        const ZERO: AtomicU64 = AtomicU64::new(0);
+        Self {
+            name,
+            values: [ZERO; MAX_CPU_NUM],
+        }
+    }
+
+    /// Set the value of `cpu_id`
+    #[inline]
+    pub fn set(&self, cpu_id: usize, val: u64) {
+        if cpu_id < MAX_CPU_NUM {
+            self.values[cpu_id].store(val, Ordering::Relaxed);
+        }
+    }
+
+    /// Get the value of `cpu_id`
+    #[inline]
+    pub fn get(&self, cpu_id: usize) -> u64 {
+        self.values
+            .get(cpu_id)
+            .map_or(0, |value| value.load(Ordering::Relaxed))
+    }
+
+    /// Difference between the largest and smallest value among `cpus`,
+    /// 0 when fewer than two CPUs are given
+    pub fn imbalance(&self, cpus: impl Iterator<Item = usize>) -> u64 {
+        let (min, max) = cpus
+            .filter(|&cpu_id| cpu_id < MAX_CPU_NUM)
+            .map(|cpu_id| self.get(cpu_id))
+            .fold((u64::MAX, 0), |(min, max), value| (min.min(value), max.max(value)));
+        max.saturating_sub(min)
+    }
+
+    /// Get name
+    #[inline]
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
 // ============================================================================
 // Kernel Metrics Instances
 // ============================================================================
@@ -388,33 +565,151 @@ pub static SYSCALL_TOTAL: Counter = Counter::new(
     "Total number of system calls executed"
 );
 
+/// System calls executed, by syscall name
+pub static SYSCALLS_BY_NAME: LabeledCounter = LabeledCounter::new(
+    "kernel_syscalls",
+    "System calls executed, by syscall name",
+    "name"
+);
+
 /// Active task count
 pub static ACTIVE_TASKS: Gauge = Gauge::new(
     "kernel_tasks_active",
     "Number of currently active tasks"
 );
 
+/// Open file descriptors across all fd tables
+pub static OPEN_FDS: Gauge = Gauge::new(
+    "kernel_open_fds",
+    "Number of open file descriptors in all tasks"
+);
+
 /// Syscall latency histogram
 pub static SYSCALL_LATENCY: Histogram = Histogram::new(
     "kernel_syscall_latency_ns",
     "System call latency in nanoseconds"
 );
 
+/// Lock attempts on other CPUs' run queues made while stealing work
+pub static WORK_STEAL_ATTEMPTS: Counter = Counter::new(
+    "kernel_work_steal_attempts_total",
+    "Run queue locks tried by idle CPUs looking for a task to steal"
+);
+
+/// Tasks stolen from another CPU's run queue
+pub static WORK_STEALS: Counter = Counter::new(
+    "kernel_work_steals_total",
+    "Tasks taken from another CPU's run queue by an idle CPU"
+);
+
+/// Tasks moved between run queues by the periodic load balancer
+pub static BALANCE_MIGRATIONS: Counter = Counter::new(
+    "kernel_balance_migrations_total",
+    "Tasks moved from the busiest to the idlest CPU by the periodic load balancer"
+);
+
 /// Page fault count
 pub static PAGE_FAULTS: PerCpuCounter = PerCpuCounter::new("kernel_page_faults_total");
 
+/// Runnable tasks queued on each CPU's CFS run queue
+pub static RUN_QUEUE_LENGTH: PerCpuGauge = PerCpuGauge::new("kernel_run_queue_length");
+
 /// Context switch count  
 pub static CONTEXT_SWITCHES: PerCpuCounter = PerCpuCounter::new("kernel_context_switches_total");
 
 /// Interrupt count
 pub static INTERRUPTS: PerCpuCounter = PerCpuCounter::new("kernel_interrupts_total");
 
+/// Lazy FPU: FP register sets written back to a task context
+pub static FPU_SAVES: Counter = Counter::new(
+    "kernel_fpu_saves_total",
+    "FP register sets saved because the task dirtied them"
+);
+
+/// Lazy FPU: FP register sets loaded on first use after a switch
+pub static FPU_RESTORES: Counter = Counter::new(
+    "kernel_fpu_restores_total",
+    "FP register sets loaded on first FP instruction"
+);
+
+/// Lazy vector context: vector register sets written back to a task context
+pub static VECTOR_SAVES: Counter = Counter::new(
+    "kernel_vector_saves_total",
+    "Vector register sets saved because the task dirtied them"
+);
+
+/// Lazy vector context: vector register sets loaded on first use after a switch
+pub static VECTOR_RESTORES: Counter = Counter::new(
+    "kernel_vector_restores_total",
+    "Vector register sets loaded on first vector instruction"
+);
+
+/// Idle harts that entered SBI HSM retentive suspend
+pub static HART_SUSPENDS: Counter = Counter::new(
+    "kernel_hart_suspends_total",
+    "Times an idle hart entered retentive suspend"
+);
+
+/// IPIs sent to wake an idle hart after queueing a task on it
+pub static IDLE_WAKEUP_IPIS: Counter = Counter::new(
+    "kernel_idle_wakeup_ipis_total",
+    "IPIs sent to wake an idle hart for a newly queued task"
+);
+
+/// Running tasks asked to give way to a task that just woke up
+pub static WAKEUP_PREEMPTIONS: Counter = Counter::new(
+    "kernel_wakeup_preemptions_total",
+    "Reschedules requested because a woken task should preempt the running one"
+);
+
+/// Picks that chose a thread of a process running on another CPU
+pub static GANG_PICKS: Counter = Counter::new(
+    "kernel_gang_picks_total",
+    "Tasks picked ahead of the leftmost one to run beside their siblings"
+);
+
+/// Yields that handed the CPU to a named task
+pub static DIRECTED_YIELDS: Counter = Counter::new(
+    "kernel_directed_yields_total",
+    "sched_yield_to calls that made the target the next task of its CPU"
+);
+
+/// Snapshots written by the metrics exporter thread
+pub static METRICS_EXPORTS: Counter = Counter::new(
+    "kernel_metrics_exports_total",
+    "Metrics snapshots written to the export file"
+);
+
+/// Cache flush commands sent to block devices
+pub static BLOCK_FLUSHES: Counter = Counter::new(
+    "kernel_block_flushes_total",
+    "Write cache flushes sent to block devices, issued by fsync"
+);
+
+/// Requests sent to block devices by the block request queue
+pub static BLOCK_REQUESTS: Counter = Counter::new(
+    "kernel_block_requests_total",
+    "Block device requests dispatched by the request queue"
+);
+
+/// Queued block requests merged into an adjacent one
+pub static BLOCK_MERGES: Counter = Counter::new(
+    "kernel_block_merges_total",
+    "Queued block requests merged into an adjacent request"
+);
+
+/// Pages currently held by file page caches
+pub static PAGE_CACHE_PAGES: Gauge = Gauge::new(
+    "kernel_page_cache_pages",
+    "Pages of file data cached in memory"
+);
+
 // ============================================================================
 // Diagnostic Subsystem
 // ============================================================================
 
-/// Kernel health status
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Kernel health status, ordered from best to worst
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum HealthStatus {
     /// Everything is working normally
     Healthy,
@@ -485,17 +780,50 @@ pub fn run_diagnostics() -> Vec<DiagnosticResult> {
     // File descriptor check
     results.push(check_fd_usage());
 
+    // Scheduler liveness check
+    results.push(check_hart_heartbeats());
+
+    results
+}
+
+/// Worst status among `results`, healthy if there are none
+pub fn overall_status(results: &[DiagnosticResult]) -> HealthStatus {
     results
+        .iter()
+        .map(|result| result.status)
+        .max()
+        .unwrap_or(HealthStatus::Healthy)
+}
+
+/// Run the diagnostics and format them for `/proc/health`:
+/// the overall status first, then one `name: status` line per check
+pub fn format_health() -> String {
+    use alloc::fmt::Write;
+    let results = run_diagnostics();
+    let mut output = String::with_capacity(256);
+    writeln!(output, "status: {}", overall_status(&results).as_str()).ok();
+    for result in &results {
+        write!(output, "{}: {}", result.name, result.status.as_str()).ok();
+        if let Some(message) = &result.message {
+            write!(output, " ({})", message).ok();
+        }
+        writeln!(output).ok();
+    }
+    output
 }
 
+/// Free frames at or below which memory is reported degraded
+pub const LOW_MEMORY_FRAMES: usize = 1000;
+/// Free frames at or below which memory is reported unhealthy
+pub const CRITICAL_MEMORY_FRAMES: usize = 100;
+
 /// Check memory subsystem health
 fn check_memory() -> DiagnosticResult {
     let free_frames = crate::mm::unallocated_frames();
-    let threshold = 100; // Minimum free frames
     
-    if free_frames > threshold * 10 {
+    if free_frames > LOW_MEMORY_FRAMES {
         DiagnosticResult::healthy("memory")
-    } else if free_frames > threshold {
+    } else if free_frames > CRITICAL_MEMORY_FRAMES {
         DiagnosticResult::degraded("memory", "Low memory warning")
     } else {
         DiagnosticResult::unhealthy("memory", "Critical memory shortage")
@@ -516,10 +844,68 @@ fn check_task_count() -> DiagnosticResult {
     }
 }
 
-/// Check file descriptor usage
+/// Check open file descriptors against the system-wide limit
 fn check_fd_usage() -> DiagnosticResult {
-    // Simplified check - in a real implementation, would track global FD usage
-    DiagnosticResult::healthy("file_descriptors")
+    let open = OPEN_FDS.get();
+    let limit = crate::config::SYSTEM_OPEN_FILE_LIMIT as u64;
+
+    if open < limit / 2 {
+        DiagnosticResult::healthy("file_descriptors")
+    } else if open < limit * 9 / 10 {
+        DiagnosticResult::degraded("file_descriptors", "High file descriptor usage")
+    } else {
+        DiagnosticResult::unhealthy("file_descriptors", "File descriptor limit nearly reached")
+    }
+}
+
+/// A hart whose last timer interrupt is older than this is considered stalled
+const HEARTBEAT_STALL_MS: u64 = 1000;
+
+/// Time of the last timer interrupt taken by each hart in ms, 0 before its first
+static HEARTBEATS: [AtomicU64; MAX_CPU_NUM] = {
+    const NEVER: AtomicU64 = AtomicU64::new(0);
+    [NEVER; MAX_CPU_NUM]
+};
+
+/// CPUs that have taken a timer interrupt, i.e. are up and scheduling
+pub fn online_cpus() -> impl Iterator<Item = usize> {
+    (0..MAX_CPU_NUM).filter(|&cpu_id| HEARTBEATS[cpu_id].load(Ordering::Relaxed) != 0)
+}
+
+/// Spread between the longest and the shortest run queue of the online CPUs
+pub fn run_queue_imbalance() -> u64 {
+    RUN_QUEUE_LENGTH.imbalance(online_cpus())
+}
+
+/// Record that the current hart is alive, called on every timer interrupt
+#[inline]
+pub fn heartbeat() {
+    let cpu_id = crate::task::processor::current_cpu_id();
+    if cpu_id < MAX_CPU_NUM {
+        let now = crate::timer::get_time_ms() as u64;
+        HEARTBEATS[cpu_id].store(now.max(1), Ordering::Relaxed);
+    }
+}
+
+/// Check that every hart that has started is still taking timer interrupts.
+/// Harts that never took one, e.g. not started with `nosmp`, are left out
+fn check_hart_heartbeats() -> DiagnosticResult {
+    use alloc::fmt::Write;
+    let now = crate::timer::get_time_ms() as u64;
+    let mut stalled = String::new();
+    for (cpu_id, beat) in HEARTBEATS.iter().enumerate() {
+        let last = beat.load(Ordering::Relaxed);
+        if last != 0 && now.saturating_sub(last) > HEARTBEAT_STALL_MS {
+            write!(stalled, " {} ({} ms ago)", cpu_id, now - last).ok();
+        }
+    }
+    if stalled.is_empty() {
+        DiagnosticResult::healthy("scheduler")
+    } else {
+        let mut message = String::from("Stalled harts:");
+        message.push_str(&stalled);
+        DiagnosticResult::unhealthy("scheduler", &message)
+    }
 }
 
 // ============================================================================
@@ -534,6 +920,7 @@ pub fn format_metrics() -> String {
     writeln!(output, "=== Kernel Metrics ===").ok();
     writeln!(output, "{}: {}", SYSCALL_TOTAL.name(), SYSCALL_TOTAL.get()).ok();
     writeln!(output, "{}: {}", ACTIVE_TASKS.name(), ACTIVE_TASKS.get()).ok();
+    writeln!(output, "{}: {}", OPEN_FDS.name(), OPEN_FDS.get()).ok();
     
     let latency = SYSCALL_LATENCY.summary();
     writeln!(output, "syscall_latency_avg_ns: {}", latency.avg).ok();
@@ -541,7 +928,43 @@ pub fn format_metrics() -> String {
     
     writeln!(output, "page_faults_total: {}", PAGE_FAULTS.sum()).ok();
     writeln!(output, "context_switches_total: {}", CONTEXT_SWITCHES.sum()).ok();
+    for cpu_id in online_cpus() {
+        writeln!(
+            output,
+            "{}{{cpu=\"{}\"}}: {}",
+            RUN_QUEUE_LENGTH.name(),
+            cpu_id,
+            RUN_QUEUE_LENGTH.get(cpu_id)
+        )
+        .ok();
+    }
+    writeln!(output, "run_queue_imbalance: {}", run_queue_imbalance()).ok();
+    let (voluntary, involuntary) = (0..MAX_CPU_NUM)
+        .map(crate::task::processor::cfs_stats)
+        .fold((0, 0), |(voluntary, involuntary), stats| {
+            (voluntary + stats.voluntary_preempt, involuntary + stats.involuntary_preempt)
+        });
+    writeln!(output, "voluntary_switches_total: {}", voluntary).ok();
+    writeln!(output, "involuntary_switches_total: {}", involuntary).ok();
     writeln!(output, "interrupts_total: {}", INTERRUPTS.sum()).ok();
+    writeln!(output, "{}: {}", FPU_SAVES.name(), FPU_SAVES.get()).ok();
+    writeln!(output, "{}: {}", FPU_RESTORES.name(), FPU_RESTORES.get()).ok();
+    writeln!(output, "{}: {}", VECTOR_SAVES.name(), VECTOR_SAVES.get()).ok();
+    writeln!(output, "{}: {}", VECTOR_RESTORES.name(), VECTOR_RESTORES.get()).ok();
+    writeln!(output, "{}: {}", HART_SUSPENDS.name(), HART_SUSPENDS.get()).ok();
+    writeln!(output, "{}: {}", IDLE_WAKEUP_IPIS.name(), IDLE_WAKEUP_IPIS.get()).ok();
+    writeln!(output, "{}: {}", WAKEUP_PREEMPTIONS.name(), WAKEUP_PREEMPTIONS.get()).ok();
+    writeln!(output, "{}: {}", GANG_PICKS.name(), GANG_PICKS.get()).ok();
+    writeln!(output, "{}: {}", DIRECTED_YIELDS.name(), DIRECTED_YIELDS.get()).ok();
+    writeln!(output, "{}: {}", WORK_STEAL_ATTEMPTS.name(), WORK_STEAL_ATTEMPTS.get()).ok();
+    writeln!(output, "{}: {}", WORK_STEALS.name(), WORK_STEALS.get()).ok();
+    writeln!(output, "{}: {}", BALANCE_MIGRATIONS.name(), BALANCE_MIGRATIONS.get()).ok();
+    writeln!(output, "{}: {}", PAGE_CACHE_PAGES.name(), PAGE_CACHE_PAGES.get()).ok();
+    writeln!(output, "{}: {}", BLOCK_FLUSHES.name(), BLOCK_FLUSHES.get()).ok();
+    writeln!(output, "{}: {}", BLOCK_REQUESTS.name(), BLOCK_REQUESTS.get()).ok();
+    writeln!(output, "{}: {}", BLOCK_MERGES.name(), BLOCK_MERGES.get()).ok();
+    writeln!(output, "{}: {}", METRICS_EXPORTS.name(), METRICS_EXPORTS.get()).ok();
+    SYSCALLS_BY_NAME.format_into(&mut output).ok();
 
     output
 }
@@ -550,3 +973,75 @@ pub fn format_metrics() -> String {
 pub fn log_metrics() {
     log::info!("{}", format_metrics());
 }
+
+// ============================================================================
+// Metrics File Export
+// ============================================================================
+
+/// Seconds between two snapshots written by [`metrics_exporter`], 0 pauses it.
+/// Tunable through `/proc/sys/kernel/metrics_export_interval`
+pub static EXPORT_INTERVAL_SECS: AtomicUsize = AtomicUsize::new(10);
+
+/// File [`metrics_exporter`] writes to.
+/// Tunable through `/proc/sys/kernel/metrics_export_path`
+static EXPORT_PATH: spin::Mutex<Cow<'static, str>> =
+    spin::Mutex::new(Cow::Borrowed("/var/log/metrics"));
+
+/// Get the path snapshots are written to
+pub fn export_path() -> String {
+    String::from(&**EXPORT_PATH.lock())
+}
+
+/// Set the path snapshots are written to, it must be absolute
+pub fn set_export_path(path: &str) -> Result<(), isize> {
+    if !path.starts_with('/') || path.ends_with('/') {
+        return Err(EINVAL);
+    }
+    *EXPORT_PATH.lock() = Cow::Owned(String::from(path));
+    Ok(())
+}
+
+/// Write a snapshot of [`format_metrics`] to the export path, replacing the last one
+pub fn export_metrics() -> Result<(), isize> {
+    let file = ROOT_FD.open(
+        &export_path(),
+        OpenFlags::O_WRONLY | OpenFlags::O_CREAT | OpenFlags::O_TRUNC,
+        false,
+    )?;
+    let text = format_metrics();
+    if file.write(None, text.as_bytes()) != text.len() {
+        return Err(EIO);
+    }
+    METRICS_EXPORTS.inc();
+    Ok(())
+}
+
+/// Body of the metrics exporter kernel thread
+///
+/// Wakes every second so that a new interval takes effect without waiting out
+/// the old one. While the directory of the export path does not exist, e.g. the
+/// filesystem holding it is not mounted yet, snapshots are skipped and retried
+/// on the next interval.
+pub fn metrics_exporter() -> ! {
+    let mut last = TimeSpec::now();
+    let mut deferred = false;
+    loop {
+        kthread::sleep(TimeSpec::from_s(1));
+        let interval = EXPORT_INTERVAL_SECS.load(Ordering::Relaxed);
+        let now = TimeSpec::now();
+        if interval == 0 || now < last + TimeSpec::from_s(interval) {
+            continue;
+        }
+        last = now;
+        match export_metrics() {
+            Ok(()) => deferred = false,
+            Err(ENOENT) | Err(ENOTDIR) => {
+                if !deferred {
+                    log::info!("[metrics] {} not reachable yet, deferring", export_path());
+                    deferred = true;
+                }
+            }
+            Err(errno) => log::warn!("[metrics] export to {} failed: {}", export_path(), errno),
+        }
+    }
+}
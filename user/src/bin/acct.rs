@@ -0,0 +1,76 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use core::convert::TryInto;
+use user_lib::{acct, close, exit, fork, open, read, waitpid, OpenFlags};
+
+const ACCT_FILE: &str = "/acct_test\0";
+/// 一条 acct_v3 记录的长度
+const RECORD_SIZE: usize = 64;
+const EXIT_CODE: i32 = 7;
+
+/// 开启进程记账后运行一个子进程，它退出时应在记账文件中留下 pid 与退出码都对得上的记录
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open(
+        ACCT_FILE,
+        OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC,
+    );
+    if fd < 0 {
+        println!("[acct] FAILED: cannot create {}", ACCT_FILE);
+        return -1;
+    }
+    close(fd as usize);
+    let ret = acct(Some(ACCT_FILE));
+    if ret != 0 {
+        println!("[acct] FAILED: acct returned {}", ret);
+        return -1;
+    }
+    let pid = fork();
+    if pid == 0 {
+        exit(EXIT_CODE);
+    }
+    let mut exit_code: i32 = 0;
+    waitpid(pid as usize, &mut exit_code);
+    acct(None);
+
+    let fd = open(ACCT_FILE, OpenFlags::RDONLY);
+    let mut buf = [0u8; 64 * RECORD_SIZE];
+    let len = read(fd as usize, &mut buf).max(0) as usize;
+    close(fd as usize);
+    if len == 0 || len % RECORD_SIZE != 0 {
+        println!("[acct] FAILED: accounting file holds {} bytes", len);
+        return -1;
+    }
+    // struct acct_v3：ac_version 在偏移 1，ac_exitcode 在 4，ac_pid 在 16，ac_comm 在 48
+    let field = |record: &[u8], offset: usize| {
+        u32::from_le_bytes(record[offset..offset + 4].try_into().unwrap())
+    };
+    let record = match buf[..len]
+        .chunks(RECORD_SIZE)
+        .find(|record| field(record, 16) == pid as u32)
+    {
+        Some(record) => record,
+        None => {
+            println!("[acct] FAILED: no record for child {}", pid);
+            return -1;
+        }
+    };
+    let comm = &record[48..];
+    let comm = &comm[..comm.iter().position(|&b| b == 0).unwrap_or(comm.len())];
+    println!(
+        "[acct] {} records, child {} exit code {:#x} comm {:?}",
+        len / RECORD_SIZE,
+        pid,
+        field(record, 4),
+        core::str::from_utf8(comm).unwrap_or("?")
+    );
+    if record[1] != 3 || field(record, 4) != (EXIT_CODE as u32) << 8 {
+        println!("[acct] FAILED: malformed record");
+        return -1;
+    }
+    println!("[acct] passed");
+    0
+}
@@ -0,0 +1,85 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+use alloc::format;
+use user_lib::{close, exec, exit, fork, open, read, waitpid, write, OpenFlags};
+
+const WATCHED: &str = "/proc/sys/kernel/audit_syscalls\0";
+/// 不存在的程序：execve 失败同样应被审计
+const PROBE: &str = "/audit_probe\0";
+
+fn read_file(path: &str, buf: &mut [u8]) -> Option<usize> {
+    let fd = open(path, OpenFlags::RDONLY);
+    if fd < 0 {
+        return None;
+    }
+    let mut len = 0;
+    while len < buf.len() {
+        match read(fd as usize, &mut buf[len..]) {
+            n if n > 0 => len += n as usize,
+            _ => break,
+        }
+    }
+    close(fd as usize);
+    Some(len)
+}
+
+fn write_file(path: &str, data: &[u8]) -> isize {
+    let fd = open(path, OpenFlags::WRONLY);
+    if fd < 0 {
+        return fd;
+    }
+    let ret = write(fd as usize, data);
+    close(fd as usize);
+    ret
+}
+
+/// 只审计 execve，子进程 execve 后 /proc/audit 中应有带程序路径的记录
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut saved = [0u8; 256];
+    let saved_len = match read_file(WATCHED, &mut saved) {
+        Some(len) => len,
+        None => {
+            println!("[audit] FAILED: cannot read audit_syscalls");
+            return -1;
+        }
+    };
+    let mut watched = [0u8; 64];
+    if write_file(WATCHED, b"execve\n") < 0
+        || read_file(WATCHED, &mut watched).map(|len| &watched[..len]) != Some(b"execve\n")
+    {
+        println!("[audit] FAILED: cannot watch only execve");
+        return -1;
+    }
+    let pid = fork();
+    if pid == 0 {
+        let args = [PROBE.as_ptr(), core::ptr::null()];
+        exec(PROBE, &args, &[core::ptr::null()]);
+        exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    waitpid(pid as usize, &mut exit_code);
+    write_file(WATCHED, &saved[..saved_len]);
+
+    let mut buf = [0u8; 32 * 1024];
+    let len = read_file("/proc/audit\0", &mut buf).unwrap_or(0);
+    let log = core::str::from_utf8(&buf[..len]).unwrap_or("");
+    let pid_field = format!("pid={} ", pid);
+    match log.lines().find(|line| {
+        line.contains(pid_field.as_str())
+            && line.contains("syscall=execve")
+            && line.contains("path=\"/audit_probe\"")
+    }) {
+        Some(record) => println!("[audit] {}", record),
+        None => {
+            println!("[audit] FAILED: no execve record for pid {}", pid);
+            return -1;
+        }
+    }
+    println!("[audit] passed");
+    0
+}
@@ -0,0 +1,108 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+/// 把 `/bind_a` 绑定挂载到 `/bind_b` 上：在任一路径下创建的文件在另一路径下可见，
+/// 卸载后 `/bind_b` 恢复为空目录
+#[cfg(target_arch = "riscv64")]
+mod workload {
+    use user_lib::{
+        close, mkdir, mount, open, read, report, rmdir, umount, unlink, write, OpenFlags, MS_BIND,
+    };
+
+    const SOURCE: &str = "/bind_a\0";
+    const TARGET: &str = "/bind_b\0";
+    const DATA: &[u8] = b"bind mount";
+
+    /// 创建内容为 `DATA` 的文件
+    fn create(path: &str) -> bool {
+        let fd = open(path, OpenFlags::CREATE | OpenFlags::WRONLY);
+        if fd < 0 {
+            return false;
+        }
+        let written = write(fd as usize, DATA);
+        close(fd as usize);
+        written == DATA.len() as isize
+    }
+
+    /// 读出 `path` 的内容
+    fn read_all<'a>(path: &str, buf: &'a mut [u8]) -> Option<&'a [u8]> {
+        let fd = open(path, OpenFlags::RDONLY);
+        if fd < 0 {
+            return None;
+        }
+        let len = read(fd as usize, buf);
+        close(fd as usize);
+        if len < 0 {
+            None
+        } else {
+            Some(&buf[..len as usize])
+        }
+    }
+
+    fn readable(path: &str) -> bool {
+        read_all(path, &mut [0u8; 16]) == Some(DATA)
+    }
+
+    fn check() -> Result<(), &'static str> {
+        if !create("/bind_a/from_source\0") || !readable("/bind_b/from_source\0") {
+            return Err("a file created in the source does not appear at the mount point");
+        }
+        if !create("/bind_b/from_target\0") || !readable("/bind_a/from_target\0") {
+            return Err("a file created at the mount point does not appear in the source");
+        }
+        let mut buf = [0u8; 1024];
+        let listed = read_all("/proc/mounts\0", &mut buf)
+            .and_then(|mounts| core::str::from_utf8(mounts).ok())
+            .map_or(false, |mounts| mounts.contains(" /bind_b "));
+        if !listed {
+            return Err("/proc/mounts does not list the bind mount");
+        }
+        if umount(TARGET) != 0 {
+            return Err("umount failed");
+        }
+        if readable("/bind_b/from_source\0") {
+            return Err("the source is still visible after umount");
+        }
+        if !readable("/bind_a/from_source\0") {
+            return Err("umount removed files from the source");
+        }
+        Ok(())
+    }
+
+    pub fn main() -> i32 {
+        if mkdir(SOURCE) < 0 || mkdir(TARGET) < 0 {
+            println!("[bind_mount] FAILED: cannot create the directories");
+            return -1;
+        }
+        let result = if mount(SOURCE, TARGET, "\0", MS_BIND) != 0 {
+            Err("mount with MS_BIND failed")
+        } else {
+            check()
+        };
+        unlink("/bind_a/from_source\0");
+        unlink("/bind_a/from_target\0");
+        let removed = rmdir(TARGET) == 0 && rmdir(SOURCE) == 0;
+        report(
+            "bind_mount",
+            result.and(if removed {
+                Ok(())
+            } else {
+                Err("the directories stayed in use after umount")
+            }),
+        )
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    #[cfg(target_arch = "riscv64")]
+    return workload::main();
+    #[cfg(not(target_arch = "riscv64"))]
+    {
+        println!("[bind_mount] skipped: the loongarch64 syscall stub passes only three arguments");
+        0
+    }
+}
@@ -0,0 +1,136 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+/// 一个线程在 CPU 0 上不停计数，主线程在同一 CPU 上读一个不在页缓存中的大文件：
+/// 块设备请求由中断通知完成时，主线程在读的过程中睡眠，计数线程应得以运行
+#[cfg(target_arch = "riscv64")]
+mod workload {
+    use core::ptr::addr_of_mut;
+    use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use user_lib::{
+        close, exit, open, read, report, thread_spawn, unlink, write, yield_, OpenFlags,
+    };
+
+    const DATA: &str = "/block_irq_data\0";
+    const CPUSET_CPUS: &str = "/sys/fs/cgroup/cpuset.cpus\0";
+    const DROP_CACHES: &str = "/proc/sys/vm/drop_caches\0";
+    const FILE_SIZE: usize = 1024 * 1024;
+    const STACK_SIZE: usize = 16 * 1024;
+
+    static mut STACKS: [[u8; STACK_SIZE]; 1] = [[0; STACK_SIZE]; 1];
+    static mut BUF: [u8; FILE_SIZE] = [0; FILE_SIZE];
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    static STOP: AtomicBool = AtomicBool::new(false);
+    static FINISHED: AtomicBool = AtomicBool::new(false);
+
+    extern "C" fn counter(_: usize) -> ! {
+        while !STOP.load(Ordering::Acquire) {
+            COUNTER.fetch_add(1, Ordering::Relaxed);
+        }
+        FINISHED.store(true, Ordering::Release);
+        exit(0)
+    }
+
+    fn write_file(path: &str, flags: OpenFlags, data: &[u8]) -> bool {
+        let fd = open(path, flags);
+        if fd < 0 {
+            return false;
+        }
+        let written = write(fd as usize, data);
+        close(fd as usize);
+        written == data.len() as isize
+    }
+
+    /// `/proc/interrupts` 中外部中断（9 号）的次数
+    fn external_interrupts() -> usize {
+        let fd = open("/proc/interrupts\0", OpenFlags::RDONLY);
+        if fd < 0 {
+            return 0;
+        }
+        let mut buf = [0u8; 512];
+        let len = read(fd as usize, &mut buf);
+        close(fd as usize);
+        core::str::from_utf8(&buf[..len.max(0) as usize])
+            .ok()
+            .and_then(|text| {
+                text.lines()
+                    .find_map(|line| line.strip_prefix("9:"))
+                    .and_then(|count| count.trim().parse().ok())
+            })
+            .unwrap_or(0)
+    }
+
+    /// 读文件期间计数线程前进的次数，读失败时为 `None`
+    fn counted_during_read() -> Option<usize> {
+        let fd = open(DATA, OpenFlags::RDONLY);
+        if fd < 0 {
+            return None;
+        }
+        let before = COUNTER.load(Ordering::Relaxed);
+        let len = read(fd as usize, unsafe { &mut *addr_of_mut!(BUF) });
+        let after = COUNTER.load(Ordering::Relaxed);
+        close(fd as usize);
+        (len == FILE_SIZE as isize).then_some(after - before)
+    }
+
+    fn check() -> Result<(), &'static str> {
+        if !write_file(CPUSET_CPUS, OpenFlags::WRONLY, b"0\n") {
+            return Err("cannot confine the process to CPU 0");
+        }
+        yield_();
+        if !write_file(DATA, OpenFlags::CREATE | OpenFlags::WRONLY, unsafe {
+            &*addr_of_mut!(BUF)
+        }) {
+            return Err("cannot write the test file");
+        }
+        if !write_file(DROP_CACHES, OpenFlags::WRONLY, b"1\n") {
+            return Err("cannot drop the page cache");
+        }
+        if thread_spawn(counter, 0, unsafe { &mut STACKS[0] }) < 0 {
+            return Err("cannot spawn the counting thread");
+        }
+        let interrupts = external_interrupts();
+        let counted = counted_during_read();
+        STOP.store(true, Ordering::Release);
+        while !FINISHED.load(Ordering::Acquire) {
+            yield_();
+        }
+        let counted = counted.ok_or("cannot read the test file back")?;
+        if external_interrupts() == interrupts {
+            println!("[block_irq] skipped: block requests are polled, not completed by interrupts");
+            return Ok(());
+        }
+        println!(
+            "[block_irq] counter advanced {} times during the read",
+            counted
+        );
+        if counted == 0 {
+            return Err("no other task ran while the read waited for the disk");
+        }
+        Ok(())
+    }
+
+    pub fn main() -> i32 {
+        let buf = unsafe { &mut *addr_of_mut!(BUF) };
+        buf.iter_mut()
+            .enumerate()
+            .for_each(|(i, byte)| *byte = i as u8);
+        let result = check();
+        unlink(DATA);
+        report("block_irq", result)
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    #[cfg(target_arch = "riscv64")]
+    return workload::main();
+    #[cfg(not(target_arch = "riscv64"))]
+    {
+        println!("[block_irq] skipped: the virtio interrupt path is only wired up on riscv64");
+        0
+    }
+}
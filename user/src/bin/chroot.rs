@@ -0,0 +1,97 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    chdir, chroot, close, exit, fork, mkdir, open, read, rmdir, unlink, waitpid, write, OpenFlags,
+};
+
+const JAIL: &str = "/chroot_jail\0";
+const INNER: &str = "/chroot_jail/inner\0";
+const INSIDE: &str = "/chroot_jail/inner/inside\0";
+const OUTSIDE: &str = "/chroot_outside\0";
+const DATA: &[u8] = b"chroot";
+
+/// 创建内容为 `DATA` 的文件
+fn create(path: &str) -> bool {
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::WRONLY);
+    if fd < 0 {
+        return false;
+    }
+    let written = write(fd as usize, DATA);
+    close(fd as usize);
+    written == DATA.len() as isize
+}
+
+/// `path` 能否打开并读出 `DATA`
+fn readable(path: &str) -> bool {
+    let fd = open(path, OpenFlags::RDONLY);
+    if fd < 0 {
+        return false;
+    }
+    let mut buf = [0u8; 16];
+    let len = read(fd as usize, &mut buf);
+    close(fd as usize);
+    len >= 0 && &buf[..len as usize] == DATA
+}
+
+/// 在子进程中运行：chroot 之后绝对路径在 `JAIL` 中解析，".." 无法越出它
+fn child() -> Result<(), &'static str> {
+    if chroot(JAIL) != 0 {
+        return Err("chroot failed");
+    }
+    if chdir("/\0") != 0 {
+        return Err("chdir to the new root failed");
+    }
+    if !readable("/inner/inside\0") || !readable("inner/inside\0") {
+        return Err("absolute paths do not resolve inside the new root");
+    }
+    if readable(OUTSIDE) || readable("/../../chroot_outside\0") {
+        return Err("a file outside the new root is reachable by an absolute path");
+    }
+    if readable("../../chroot_outside\0") {
+        return Err("../.. escaped the new root");
+    }
+    if chdir("inner\0") != 0 || chdir("../..\0") != 0 || chdir("..\0") != 0 {
+        return Err("chdir towards the parent of the new root failed");
+    }
+    if readable("chroot_outside\0") || !readable("inner/inside\0") {
+        return Err("chdir(\"..\") left the new root");
+    }
+    Ok(())
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    if mkdir(JAIL) < 0 || mkdir(INNER) < 0 || !create(INSIDE) || !create(OUTSIDE) {
+        println!("[chroot] FAILED: cannot create the test files");
+        return -1;
+    }
+    let pid = fork();
+    if pid == 0 {
+        exit(match child() {
+            Ok(()) => 0,
+            Err(reason) => {
+                println!("[chroot] FAILED: {}", reason);
+                1
+            }
+        });
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    let parent_unconfined = readable(OUTSIDE);
+    unlink(INSIDE);
+    unlink(OUTSIDE);
+    rmdir(INNER);
+    rmdir(JAIL);
+    if exit_code != 0 {
+        return -1;
+    }
+    if !parent_unconfined {
+        println!("[chroot] FAILED: the parent's root changed");
+        return -1;
+    }
+    println!("[chroot] passed");
+    0
+}
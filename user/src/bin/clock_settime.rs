@@ -0,0 +1,47 @@
+#![no_std]
+#![no_main]
+
+extern crate user_lib;
+
+use user_lib::{clock_gettime, clock_settime, report, CLOCK_MONOTONIC, CLOCK_REALTIME};
+
+const EINVAL: isize = -22;
+/// CLOCK_REALTIME 向后拨的秒数
+const SHIFT: usize = 1000;
+
+/// clock_settime 只移动 CLOCK_REALTIME：设置后 CLOCK_REALTIME 向后拨动，CLOCK_MONOTONIC 不变；
+/// 设置 CLOCK_MONOTONIC 或纳秒数越界的时间返回 EINVAL
+fn check() -> Result<(), &'static str> {
+    let mut mono = [0usize; 2];
+    let mut real = [0usize; 2];
+    clock_gettime(CLOCK_MONOTONIC, &mut mono);
+    clock_gettime(CLOCK_REALTIME, &mut real);
+    let start = real;
+    if clock_settime(CLOCK_REALTIME, &[real[0] + SHIFT, real[1]]) != 0 {
+        return Err("setting CLOCK_REALTIME failed");
+    }
+    let mut moved = [0usize; 2];
+    clock_gettime(CLOCK_REALTIME, &mut moved);
+    let mut after = [0usize; 2];
+    clock_gettime(CLOCK_MONOTONIC, &mut after);
+    // 恢复原来的时间，忽略期间流逝的时间
+    clock_settime(CLOCK_REALTIME, &start);
+    if moved[0] < start[0] + SHIFT || moved[0] > start[0] + SHIFT + 10 {
+        return Err("CLOCK_REALTIME did not move to the time set");
+    }
+    if after[0] < mono[0] || after[0] > mono[0] + 10 {
+        return Err("setting CLOCK_REALTIME moved CLOCK_MONOTONIC");
+    }
+    if clock_settime(CLOCK_MONOTONIC, &after) != EINVAL {
+        return Err("setting CLOCK_MONOTONIC did not fail with EINVAL");
+    }
+    if clock_settime(CLOCK_REALTIME, &[start[0], 1_000_000_000]) != EINVAL {
+        return Err("a time with tv_nsec of one second was accepted");
+    }
+    Ok(())
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    report("clock_settime", check())
+}
@@ -0,0 +1,73 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    exit, fork, mmap, munmap, sleep, waitpid, MAP_ANONYMOUS, MAP_PRIVATE, PROT_READ, PROT_WRITE,
+};
+
+const PAGE_SIZE: usize = 4096;
+/// 映射很大但只写两页，fork 只应处理在内存中的页
+const AREA_SIZE: usize = 64 * 1024 * 1024;
+/// 两页相隔很远，中间都是没有分配的洞
+const FAR_PAGE: usize = AREA_SIZE - PAGE_SIZE;
+
+fn slot(area: usize, offset: usize) -> *mut usize {
+    (area + offset) as *mut usize
+}
+
+/// fork 后父子进程共享页面，任一方写入时只复制被写的那一页：
+/// 父进程 fork 后立即写入，子进程仍看到 fork 时的内容；
+/// 子进程的写入也不会出现在父进程中
+#[no_mangle]
+pub fn main() -> i32 {
+    let area = mmap(
+        0,
+        AREA_SIZE,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANONYMOUS,
+        usize::MAX,
+        0,
+    );
+    if area < 0 {
+        println!("[cow_fork] FAILED: mmap returned {}", area);
+        return -1;
+    }
+    let area = area as usize;
+    unsafe {
+        *slot(area, 0) = 1;
+        *slot(area, FAR_PAGE) = 2;
+    }
+    let pid = fork();
+    if pid == 0 {
+        // 等父进程写完，父进程此前的 TLB 项若仍可写，写入会漏到共享的页中
+        sleep(20);
+        let (near, far) = unsafe { (*slot(area, 0), *slot(area, FAR_PAGE)) };
+        unsafe { *slot(area, FAR_PAGE) = 20 };
+        let far_written = unsafe { *slot(area, FAR_PAGE) };
+        exit(if near == 1 && far == 2 && far_written == 20 {
+            0
+        } else {
+            1
+        });
+    }
+    unsafe { *slot(area, 0) = 10 };
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    let (near, far) = unsafe { (*slot(area, 0), *slot(area, FAR_PAGE)) };
+    munmap(area, AREA_SIZE);
+    if exit_code != 0 {
+        println!("[cow_fork] FAILED: the child saw a write the parent made after fork");
+        return -1;
+    }
+    if near != 10 || far != 2 {
+        println!(
+            "[cow_fork] FAILED: parent sees {} and {}, expected 10 and 2",
+            near, far
+        );
+        return -1;
+    }
+    println!("[cow_fork] passed");
+    0
+}
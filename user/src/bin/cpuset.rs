@@ -0,0 +1,87 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use core::hint::black_box;
+use user_lib::{
+    close, exit, fork, get_time, getcpu, open, read, waitpid, write, yield_, OpenFlags,
+};
+
+const CPUSET_CPUS: &str = "/sys/fs/cgroup/cpuset.cpus\0";
+const PROCESS_NUM: usize = 4;
+/// 子进程忙等的时长
+const SPIN_MS: isize = 300;
+/// cpuset 内的 CPU：0 与 1
+const ALLOWED_CPUS: isize = 2;
+
+fn read_cpus(buf: &mut [u8]) -> Option<&str> {
+    let fd = open(CPUSET_CPUS, OpenFlags::RDONLY);
+    if fd < 0 {
+        return None;
+    }
+    let len = read(fd as usize, buf);
+    close(fd as usize);
+    core::str::from_utf8(&buf[..len.max(0) as usize]).ok()
+}
+
+/// 把自己限制在 CPU 0-1 上，随后 fork 的忙等子进程都不应出现在 CPU 2-3 上
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open(CPUSET_CPUS, OpenFlags::WRONLY);
+    if fd < 0 {
+        println!("[cpuset] FAILED: cannot open /sys/fs/cgroup/cpuset.cpus");
+        return -1;
+    }
+    let written = write(fd as usize, b"0-1\n");
+    close(fd as usize);
+    if written < 0 {
+        println!("[cpuset] FAILED: writing the CPU list returned {}", written);
+        return -1;
+    }
+    let mut buf = [0u8; 64];
+    match read_cpus(&mut buf) {
+        Some("0-1\n") => {}
+        other => {
+            println!("[cpuset] FAILED: cpuset.cpus reads {:?}", other);
+            return -1;
+        }
+    }
+    // 让出 CPU 后父进程自己也被放回 cpuset 内
+    yield_();
+    if getcpu() >= ALLOWED_CPUS {
+        println!("[cpuset] FAILED: parent still on CPU {}", getcpu());
+        return -1;
+    }
+    let mut pids = [0isize; PROCESS_NUM];
+    for pid in pids.iter_mut() {
+        *pid = fork();
+        if *pid == 0 {
+            let start = get_time();
+            let mut acc = 0usize;
+            while get_time() - start < SPIN_MS {
+                acc = black_box(acc.wrapping_add(1));
+                let cpu = getcpu();
+                if !(0..ALLOWED_CPUS).contains(&cpu) {
+                    exit(cpu as i32 + 1);
+                }
+            }
+            exit(0);
+        }
+    }
+    let mut failed = false;
+    for pid in pids.iter() {
+        let mut exit_code: i32 = 0;
+        waitpid(*pid as usize, &mut exit_code);
+        let code = (exit_code >> 8) & 0xff;
+        if code != 0 {
+            println!("[cpuset] FAILED: child {} ran on CPU {}", pid, code - 1);
+            failed = true;
+        }
+    }
+    if failed {
+        return -1;
+    }
+    println!("[cpuset] passed");
+    0
+}
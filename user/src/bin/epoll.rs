@@ -0,0 +1,186 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+/// epoll 实例监视管道：水平触发、边沿触发与 EPOLLONESHOT 的语义，以及 epoll_ctl 的错误
+#[cfg(target_arch = "riscv64")]
+mod workload {
+    use user_lib::{
+        close, epoll_create1, epoll_ctl, epoll_wait, open, pipe, read, report, unlink, write,
+        EpollEvent, OpenFlags, EPOLLET, EPOLLHUP, EPOLLIN, EPOLLONESHOT, EPOLL_CTL_ADD,
+        EPOLL_CTL_DEL, EPOLL_CTL_MOD,
+    };
+
+    const EPERM: isize = -1;
+    const ENOENT: isize = -2;
+    const EEXIST: isize = -17;
+    const EINVAL: isize = -22;
+    const ELOOP: isize = -40;
+    const FILE: &str = "/epoll_regular\0";
+    const DATA: u64 = 0x1234_5678_9abc;
+
+    fn event(events: u32) -> EpollEvent {
+        EpollEvent { events, data: DATA }
+    }
+
+    /// 等待 `timeout` 毫秒，返回就绪事件数；只有一个事件时检查它的 data
+    fn wait(epfd: usize, timeout: i32) -> Result<(isize, u32), &'static str> {
+        let mut events = [EpollEvent::default(); 4];
+        let ret = epoll_wait(epfd, &mut events, timeout);
+        if ret == 1 && events[0].data != DATA {
+            return Err("the event does not carry the registered data");
+        }
+        Ok((ret, events[0].events))
+    }
+
+    /// 管道读端的三种触发方式
+    fn check_triggers(epfd: usize, rfd: usize, wfd: usize) -> Result<(), &'static str> {
+        if epoll_ctl(epfd, EPOLL_CTL_ADD, rfd, &event(EPOLLIN)) != 0 {
+            return Err("cannot add the read end");
+        }
+        if epoll_ctl(epfd, EPOLL_CTL_ADD, rfd, &event(EPOLLIN)) != EEXIST {
+            return Err("adding the read end twice did not fail with EEXIST");
+        }
+        if wait(epfd, 0)?.0 != 0 {
+            return Err("an empty pipe was reported ready");
+        }
+        if wait(epfd, 50)?.0 != 0 {
+            return Err("a wait on an empty pipe did not time out");
+        }
+        write(wfd, b"a");
+        if wait(epfd, -1)? != (1, EPOLLIN) {
+            return Err("written data was not reported");
+        }
+        if wait(epfd, 0)?.0 != 1 {
+            return Err("a level-triggered pipe with unread data was not reported again");
+        }
+        // 边沿触发：没有新数据时不再报告
+        if epoll_ctl(epfd, EPOLL_CTL_MOD, rfd, &event(EPOLLIN | EPOLLET)) != 0 {
+            return Err("cannot switch to edge-triggered");
+        }
+        if wait(epfd, 0)?.0 != 1 {
+            return Err("unread data was not reported after EPOLL_CTL_MOD");
+        }
+        if wait(epfd, 0)?.0 != 0 {
+            return Err("an edge-triggered pipe was reported twice without new data");
+        }
+        write(wfd, b"b");
+        if wait(epfd, 0)?.0 != 1 {
+            return Err("new data on an edge-triggered pipe was not reported");
+        }
+        // EPOLLONESHOT：报告一次后停用，直到再次 EPOLL_CTL_MOD
+        if epoll_ctl(epfd, EPOLL_CTL_MOD, rfd, &event(EPOLLIN | EPOLLONESHOT)) != 0 {
+            return Err("cannot switch to one-shot");
+        }
+        if wait(epfd, 0)?.0 != 1 || wait(epfd, 0)?.0 != 0 {
+            return Err("a one-shot registration was not reported exactly once");
+        }
+        if epoll_ctl(epfd, EPOLL_CTL_MOD, rfd, &event(EPOLLIN | EPOLLONESHOT)) != 0
+            || wait(epfd, 0)?.0 != 1
+        {
+            return Err("EPOLL_CTL_MOD did not rearm a one-shot registration");
+        }
+        let mut buf = [0u8; 2];
+        if read(rfd, &mut buf) != 2 {
+            return Err("cannot drain the pipe");
+        }
+        if epoll_ctl(epfd, EPOLL_CTL_DEL, rfd, &event(0)) != 0 {
+            return Err("cannot remove the read end");
+        }
+        if epoll_ctl(epfd, EPOLL_CTL_DEL, rfd, &event(0)) != ENOENT {
+            return Err("removing the read end twice did not fail with ENOENT");
+        }
+        Ok(())
+    }
+
+    /// 写端关闭后读端报告 EPOLLHUP，读端关闭后它的登记随之消失
+    fn check_close(epfd: usize, rfd: usize, wfd: usize) -> Result<(), &'static str> {
+        if epoll_ctl(epfd, EPOLL_CTL_ADD, rfd, &event(EPOLLIN)) != 0 {
+            return Err("cannot add the read end again");
+        }
+        close(wfd);
+        match wait(epfd, 0)? {
+            (1, events) if events & EPOLLHUP != 0 => {}
+            _ => return Err("closing the write end was not reported as EPOLLHUP"),
+        }
+        close(rfd);
+        if wait(epfd, 0)?.0 != 0 {
+            return Err("a closed pipe was still reported");
+        }
+        Ok(())
+    }
+
+    /// 不能监视的对象与 epoll 实例的嵌套
+    fn check_errors(epfd: usize) -> Result<(), &'static str> {
+        if epoll_create1(0x1) != EINVAL {
+            return Err("epoll_create1 accepted unknown flags");
+        }
+        if epoll_ctl(epfd, EPOLL_CTL_ADD, epfd, &event(EPOLLIN)) != EINVAL {
+            return Err("an epoll instance could watch itself");
+        }
+        let fd = open(FILE, OpenFlags::CREATE | OpenFlags::RDWR);
+        if fd < 0 {
+            return Err("cannot create a regular file");
+        }
+        let ret = epoll_ctl(epfd, EPOLL_CTL_ADD, fd as usize, &event(EPOLLIN));
+        close(fd as usize);
+        unlink(FILE);
+        if ret != EPERM {
+            return Err("watching a regular file did not fail with EPERM");
+        }
+        let outer = epoll_create1(0);
+        if outer < 0 {
+            return Err("cannot create a second epoll instance");
+        }
+        let outer = outer as usize;
+        let nested = epoll_ctl(outer, EPOLL_CTL_ADD, epfd, &event(EPOLLIN));
+        let looped = epoll_ctl(epfd, EPOLL_CTL_ADD, outer, &event(EPOLLIN));
+        close(outer);
+        if nested != 0 {
+            return Err("cannot nest an epoll instance");
+        }
+        if looped != ELOOP {
+            return Err("a loop of epoll instances did not fail with ELOOP");
+        }
+        Ok(())
+    }
+
+    fn check(epfd: usize) -> Result<(), &'static str> {
+        let mut fds = [0i32; 2];
+        if pipe(&mut fds) != 0 {
+            return Err("cannot create a pipe");
+        }
+        let (rfd, wfd) = (fds[0] as usize, fds[1] as usize);
+        if let Err(reason) = check_triggers(epfd, rfd, wfd) {
+            close(rfd);
+            close(wfd);
+            return Err(reason);
+        }
+        check_close(epfd, rfd, wfd)?;
+        check_errors(epfd)
+    }
+
+    pub fn main() -> i32 {
+        let epfd = epoll_create1(0);
+        if epfd < 0 {
+            println!("[epoll] FAILED: epoll_create1 returned {}", epfd);
+            return -1;
+        }
+        let result = check(epfd as usize);
+        close(epfd as usize);
+        report("epoll", result)
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    #[cfg(target_arch = "riscv64")]
+    return workload::main();
+    #[cfg(not(target_arch = "riscv64"))]
+    {
+        println!("[epoll] skipped: six-argument system calls are only passed on riscv64");
+        0
+    }
+}
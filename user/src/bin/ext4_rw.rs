@@ -0,0 +1,142 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    close, ftruncate, lseek, mkdir, open, read, report, rmdir, unlink, write, OpenFlags, SEEK_END,
+    SEEK_SET,
+};
+
+const DIR: &str = "/ext4_rw\0";
+const FILE: &str = "/ext4_rw/data\0";
+/// 跨过几个块，最后一块只写了一部分
+const LENGTH: usize = 3 * 4096 + 100;
+const SHRUNK: usize = 5000;
+const GROWN: usize = 12000;
+
+fn pattern(offset: usize) -> u8 {
+    (offset % 251) as u8
+}
+
+/// 从 `offset` 读到文件末尾，逐字节与 `expected` 比较，返回读到的字节数
+fn read_back(
+    fd: usize,
+    offset: usize,
+    expected: impl Fn(usize) -> u8,
+) -> Result<usize, &'static str> {
+    if lseek(fd, offset as isize, SEEK_SET) != offset as isize {
+        return Err("cannot seek");
+    }
+    let mut buf = [0u8; 1000];
+    let mut total = offset;
+    loop {
+        let len = read(fd, &mut buf);
+        if len < 0 {
+            return Err("read failed");
+        }
+        if len == 0 {
+            return Ok(total);
+        }
+        for (i, &byte) in buf[..len as usize].iter().enumerate() {
+            if byte != expected(total + i) {
+                println!("[ext4_rw] byte {} is {:#x}", total + i, byte);
+                return Err("the file reads back wrong");
+            }
+        }
+        total += len as usize;
+    }
+}
+
+/// `/proc/mounts` 中的根文件系统是否为 ext4
+fn root_is_ext4() -> bool {
+    let fd = open("/proc/mounts\0", OpenFlags::RDONLY);
+    if fd < 0 {
+        return false;
+    }
+    let mut buf = [0u8; 1024];
+    let len = read(fd as usize, &mut buf);
+    close(fd as usize);
+    len > 0
+        && core::str::from_utf8(&buf[..len as usize])
+            .map_or(false, |mounts| mounts.contains("/dev/root / ext4 "))
+}
+
+/// 截短再扩展，扩展出的部分读出 0，之后写入扩展出的部分
+fn check_truncate(fd: usize) -> Result<(), &'static str> {
+    if read_back(fd, 0, pattern)? != LENGTH {
+        return Err("wrong length after reopening");
+    }
+    if ftruncate(fd, SHRUNK) != 0 || lseek(fd, 0, SEEK_END) != SHRUNK as isize {
+        return Err("cannot shrink the file");
+    }
+    if read_back(fd, 0, pattern)? != SHRUNK {
+        return Err("wrong length after shrinking");
+    }
+    // 截掉的数据不能在扩展后重新出现
+    if ftruncate(fd, GROWN) != 0 || lseek(fd, 0, SEEK_END) != GROWN as isize {
+        return Err("cannot grow the file");
+    }
+    let grown = |offset| if offset < SHRUNK { pattern(offset) } else { 0 };
+    if read_back(fd, 0, grown)? != GROWN {
+        return Err("wrong length after growing");
+    }
+    if lseek(fd, 10000, SEEK_SET) != 10000 || write(fd, b"tail") != 4 {
+        return Err("cannot write into the grown region");
+    }
+    let written = |offset| match offset {
+        10000..=10003 => b"tail"[offset - 10000],
+        _ => grown(offset),
+    };
+    if read_back(fd, 0, written)? != GROWN {
+        return Err("wrong length after writing");
+    }
+    Ok(())
+}
+
+/// 写入后关闭，再打开读回并截短、扩展
+fn check() -> Result<(), &'static str> {
+    let fd = open(FILE, OpenFlags::CREATE | OpenFlags::RDWR);
+    if fd < 0 {
+        return Err("cannot create the file");
+    }
+    let fd = fd as usize;
+    let data: [u8; LENGTH] = core::array::from_fn(pattern);
+    // 分两次写，第二次从块中间开始
+    let written = write(fd, &data[..4000]) + write(fd, &data[4000..]);
+    close(fd);
+    if written != LENGTH as isize {
+        return Err("cannot write the file");
+    }
+
+    let fd = open(FILE, OpenFlags::RDWR);
+    if fd < 0 {
+        return Err("cannot reopen the file");
+    }
+    let result = check_truncate(fd as usize);
+    close(fd as usize);
+    result
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    if !root_is_ext4() {
+        println!("[ext4_rw] the root file system is not ext4, skipped");
+        return 0;
+    }
+    if mkdir(DIR) != 0 {
+        println!("[ext4_rw] FAILED: cannot create {}", DIR);
+        return -1;
+    }
+    let mut result = check();
+    if unlink(FILE) != 0 && result.is_ok() {
+        result = Err("cannot unlink the file");
+    }
+    if result.is_ok() && open(FILE, OpenFlags::RDONLY) >= 0 {
+        result = Err("the unlinked file can still be opened");
+    }
+    if rmdir(DIR) != 0 && result.is_ok() {
+        result = Err("cannot remove the emptied directory");
+    }
+    report("ext4_rw", result)
+}
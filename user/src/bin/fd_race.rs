@@ -0,0 +1,124 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+/// 两个线程共享 fd 表，同时反复 dup、fcntl(F_DUPFD) 与 close 相互重叠的低号 fd：
+/// 同一个 fd 不应同时分给两个线程，每次 close 都应成功，
+/// 结束后最小的空闲 fd 应与开始时相同
+#[cfg(target_arch = "riscv64")]
+mod workload {
+    use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use user_lib::{close, dup, exit, fcntl, open, report, thread_spawn, OpenFlags, F_DUPFD};
+
+    const ITERATIONS: usize = 2000;
+    /// 每个线程同时持有的 fd 数
+    const HELD: usize = 4;
+    const MAX_FD: usize = 128;
+    const STACK_SIZE: usize = 16 * 1024;
+
+    static mut STACKS: [[u8; STACK_SIZE]; 1] = [[0; STACK_SIZE]; 1];
+    /// 每个 fd 当前属于哪个线程，0 表示不属于任何线程
+    static OWNER: [AtomicUsize; MAX_FD] = [const { AtomicUsize::new(0) }; MAX_FD];
+    static ERROR: AtomicUsize = AtomicUsize::new(0);
+    static FINISHED: AtomicBool = AtomicBool::new(false);
+
+    const DOUBLE_ALLOCATION: usize = 1;
+    const CLOSE_FAILED: usize = 2;
+    const DUP_FAILED: usize = 3;
+
+    /// 线程 `id`（1 或 2）反复复制 `base` 再关闭，偶数轮改用 F_DUPFD
+    fn hammer(id: usize, base: usize) {
+        let mut held = [0usize; HELD];
+        for round in 0..ITERATIONS {
+            if ERROR.load(Ordering::Relaxed) != 0 {
+                return;
+            }
+            for slot in held.iter_mut() {
+                let fd = if round % 2 == 0 {
+                    fcntl(base, F_DUPFD, base + 1)
+                } else {
+                    dup(base)
+                };
+                if fd < 0 || fd as usize >= MAX_FD {
+                    ERROR.store(DUP_FAILED, Ordering::Relaxed);
+                    return;
+                }
+                if OWNER[fd as usize]
+                    .compare_exchange(0, id, Ordering::AcqRel, Ordering::Relaxed)
+                    .is_err()
+                {
+                    ERROR.store(DOUBLE_ALLOCATION, Ordering::Relaxed);
+                    return;
+                }
+                *slot = fd as usize;
+            }
+            for &fd in held.iter() {
+                // 先放弃所有权再关闭：fd 关闭前不会分给另一个线程
+                OWNER[fd].store(0, Ordering::Release);
+                if close(fd) != 0 {
+                    ERROR.store(CLOSE_FAILED, Ordering::Relaxed);
+                    return;
+                }
+            }
+        }
+    }
+
+    extern "C" fn worker(base: usize) -> ! {
+        hammer(2, base);
+        FINISHED.store(true, Ordering::Release);
+        exit(0)
+    }
+
+    fn check(base: usize) -> Result<(), &'static str> {
+        let lowest = dup(base);
+        if lowest < 0 || close(lowest as usize) != 0 {
+            return Err("cannot dup the base descriptor");
+        }
+        if thread_spawn(worker, base, unsafe { &mut STACKS[0] }) < 0 {
+            return Err("cannot spawn the second thread");
+        }
+        hammer(1, base);
+        while !FINISHED.load(Ordering::Acquire) {
+            user_lib::yield_();
+        }
+        match ERROR.load(Ordering::Relaxed) {
+            DOUBLE_ALLOCATION => return Err("one fd was handed to both threads"),
+            CLOSE_FAILED => return Err("closing an fd the thread owned failed"),
+            DUP_FAILED => return Err("dup failed"),
+            _ => {}
+        }
+        // 全部关闭后空闲 fd 集合应与开始时相同
+        let after = dup(base);
+        if after >= 0 {
+            close(after as usize);
+        }
+        if after != lowest {
+            return Err("the lowest free fd changed after the threads closed everything");
+        }
+        Ok(())
+    }
+
+    pub fn main() -> i32 {
+        let base = open("/dev/null\0", OpenFlags::RDONLY);
+        if base < 0 {
+            println!("[fd_race] FAILED: cannot open /dev/null");
+            return -1;
+        }
+        let result = check(base as usize);
+        close(base as usize);
+        report("fd_race", result)
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    #[cfg(target_arch = "riscv64")]
+    return workload::main();
+    #[cfg(not(target_arch = "riscv64"))]
+    {
+        println!("[fd_race] skipped: threads are only spawned on riscv64");
+        0
+    }
+}
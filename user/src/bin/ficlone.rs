@@ -0,0 +1,103 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    close, ioctl, lseek, open, read, report, statfs, unlink, write, OpenFlags, Statfs, FICLONE,
+    SEEK_SET,
+};
+
+const EBADF: isize = -9;
+const EINVAL: isize = -22;
+const EOPNOTSUPP: isize = -95;
+const SRC: &str = "/ficlone_src\0";
+const DST: &str = "/ficlone_dst\0";
+const DATA: &[u8] = b"original contents of the source file";
+
+/// 从头读出文件，与 `expected` 比较
+fn contents_are(fd: usize, expected: &[u8]) -> bool {
+    let mut buf = [0u8; 128];
+    lseek(fd, 0, SEEK_SET) == 0
+        && read(fd, &mut buf) == expected.len() as isize
+        && &buf[..expected.len()] == expected
+}
+
+/// 文件所在文件系统的空闲块数
+fn free_blocks() -> u64 {
+    let mut buf = Statfs::default();
+    statfs(SRC, &mut buf);
+    buf.f_bfree
+}
+
+/// 副本与原文件共享块、内容相同，修改副本后原文件不变
+fn check_clone(src: usize, dst: usize, free_before: u64) -> Result<(), &'static str> {
+    if free_blocks() != free_before {
+        return Err("the clone did not share the source blocks");
+    }
+    if !contents_are(dst, DATA) {
+        return Err("the clone does not have the source contents");
+    }
+    if lseek(dst, 0, SEEK_SET) != 0 || write(dst, b"CHANGED") != 7 {
+        return Err("cannot modify the clone");
+    }
+    if !contents_are(src, DATA) {
+        return Err("modifying the clone changed the source");
+    }
+    Ok(())
+}
+
+fn check(src: usize, dst: usize) -> Result<(), &'static str> {
+    if write(src, DATA) != DATA.len() as isize {
+        return Err("cannot write the source file");
+    }
+    // 参数错误先于是否支持检查
+    let read_only = open(DST, OpenFlags::RDONLY);
+    if read_only < 0 {
+        return Err("cannot reopen the destination read-only");
+    }
+    let ret = ioctl(read_only as usize, FICLONE, src);
+    close(read_only as usize);
+    if ret != EBADF {
+        return Err("cloning into a read-only descriptor did not fail with EBADF");
+    }
+    let dir = open("/\0", OpenFlags::RDONLY);
+    let ret = ioctl(dst, FICLONE, dir as usize);
+    close(dir as usize);
+    if ret != EINVAL {
+        return Err("cloning a directory did not fail with EINVAL");
+    }
+    let free_before = free_blocks();
+    match ioctl(dst, FICLONE, src) {
+        0 => check_clone(src, dst, free_before),
+        EOPNOTSUPP => {
+            println!("[ficlone] the root filesystem cannot share blocks");
+            if contents_are(dst, b"") {
+                Ok(())
+            } else {
+                Err("a refused FICLONE changed the destination")
+            }
+        }
+        _ => Err("FICLONE failed with an unexpected error"),
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let src = open(SRC, OpenFlags::CREATE | OpenFlags::RDWR);
+    let dst = open(DST, OpenFlags::CREATE | OpenFlags::RDWR);
+    let result = if src < 0 || dst < 0 {
+        Err("cannot create the test files")
+    } else {
+        check(src as usize, dst as usize)
+    };
+    if src >= 0 {
+        close(src as usize);
+    }
+    if dst >= 0 {
+        close(dst as usize);
+    }
+    unlink(SRC);
+    unlink(DST);
+    report("ficlone", result)
+}
@@ -0,0 +1,107 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+/// 为文件生成文件句柄，再按句柄打开并读出写入的内容；空文件的句柄在写入后不变；
+/// 句柄的版本号不符或文件被删除后，按句柄打开返回 ESTALE。按句柄打开需要以 root 运行
+#[cfg(target_arch = "riscv64")]
+mod workload {
+    use user_lib::{
+        close, name_to_handle_at, open, open_by_handle_at, read, report, unlink, write, FileHandle,
+        OpenFlags, AT_FDCWD,
+    };
+
+    const EOVERFLOW: isize = -75;
+    const ESTALE: isize = -116;
+    const PATH: &str = "file_handle_test\0";
+    const DATA: &[u8] = b"reopened by file handle";
+
+    /// 按句柄打开文件并读出全部内容，与 `DATA` 比较
+    fn read_back(handle: &FileHandle) -> Result<(), &'static str> {
+        let fd = open_by_handle_at(AT_FDCWD, handle, OpenFlags::RDONLY);
+        if fd < 0 {
+            return Err("open_by_handle_at failed");
+        }
+        let mut buf = [0u8; 64];
+        let len = read(fd as usize, &mut buf);
+        close(fd as usize);
+        if len < 0 || &buf[..len as usize] != DATA {
+            return Err("data read through the handle differs");
+        }
+        Ok(())
+    }
+
+    /// `empty`: 文件还是空的时生成的句柄，写入数据后句柄不应改变
+    fn check(empty: &FileHandle) -> Result<(), &'static str> {
+        let mut mount_id = -1;
+        let mut handle = FileHandle::default();
+        if name_to_handle_at(AT_FDCWD, PATH, &mut handle, &mut mount_id, 0) != EOVERFLOW
+            || handle.handle_bytes == 0
+        {
+            return Err("an empty handle buffer was not refused with EOVERFLOW");
+        }
+        if name_to_handle_at(AT_FDCWD, PATH, &mut handle, &mut mount_id, 0) != 0 {
+            return Err("name_to_handle_at failed");
+        }
+        if mount_id < 0 {
+            return Err("mount_id was not filled in");
+        }
+        if handle.f_handle != empty.f_handle {
+            return Err("the handle changed when the empty file was written");
+        }
+        read_back(&handle)?;
+        // f_handle 的第 4 到 7 字节是 inode 的版本号
+        let mut recycled = handle;
+        recycled.f_handle[4] = recycled.f_handle[4].wrapping_add(1);
+        if open_by_handle_at(AT_FDCWD, &recycled, OpenFlags::RDONLY) != ESTALE {
+            return Err("a handle with another generation was not refused with ESTALE");
+        }
+        if unlink(PATH) < 0 {
+            return Err("cannot remove the file");
+        }
+        if open_by_handle_at(AT_FDCWD, &handle, OpenFlags::RDONLY) != ESTALE {
+            return Err("the handle of a removed file was not refused with ESTALE");
+        }
+        Ok(())
+    }
+
+    pub fn main() -> i32 {
+        let fd = open(PATH, OpenFlags::CREATE | OpenFlags::WRONLY);
+        if fd < 0 {
+            println!("[file_handle] FAILED: cannot create the file");
+            return -1;
+        }
+        let mut mount_id = -1;
+        let mut empty = FileHandle {
+            handle_bytes: 16,
+            ..FileHandle::default()
+        };
+        let result = if name_to_handle_at(AT_FDCWD, PATH, &mut empty, &mut mount_id, 0) != 0 {
+            close(fd as usize);
+            Err("name_to_handle_at failed on the empty file")
+        } else {
+            let written = write(fd as usize, DATA);
+            close(fd as usize);
+            if written != DATA.len() as isize {
+                Err("cannot write the file")
+            } else {
+                check(&empty)
+            }
+        };
+        unlink(PATH);
+        report("file_handle", result)
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    #[cfg(target_arch = "riscv64")]
+    return workload::main();
+    #[cfg(not(target_arch = "riscv64"))]
+    {
+        println!("[file_handle] skipped: the loongarch64 syscall stub passes only three arguments");
+        0
+    }
+}
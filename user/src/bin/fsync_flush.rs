@@ -0,0 +1,117 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, fsync, get_time, open, read, report, sleep, unlink, write, OpenFlags};
+
+const INTERVAL: &str = "/proc/sys/kernel/metrics_export_interval\0";
+const PATH: &str = "/proc/sys/kernel/metrics_export_path\0";
+const EXPORT_FILE: &str = "/tmp/fsync_flush_metrics\0";
+const DATA: &str = "/fsync_flush_data\0";
+const EXPORTS_KEY: &str = "kernel_metrics_exports_total: ";
+const FLUSHES_KEY: &str = "kernel_block_flushes_total: ";
+/// 等待导出线程写入的最长时间，导出间隔设为 1 秒
+const TIMEOUT_MS: isize = 5000;
+
+/// 读出整个文件，返回读到的长度
+fn read_file(path: &str, buf: &mut [u8]) -> Option<usize> {
+    let fd = open(path, OpenFlags::RDONLY);
+    if fd < 0 {
+        return None;
+    }
+    let mut len = 0;
+    while len < buf.len() {
+        match read(fd as usize, &mut buf[len..]) {
+            n if n > 0 => len += n as usize,
+            _ => break,
+        }
+    }
+    close(fd as usize);
+    Some(len)
+}
+
+fn write_file(path: &str, value: &[u8]) -> bool {
+    let fd = open(path, OpenFlags::WRONLY);
+    if fd < 0 {
+        return false;
+    }
+    let ret = write(fd as usize, value);
+    close(fd as usize);
+    ret == value.len() as isize
+}
+
+/// 导出文件中 `key` 之后的数值
+fn metric(text: &str, key: &str) -> Option<usize> {
+    let start = text.find(key)? + key.len();
+    text[start..].split('\n').next()?.trim().parse().ok()
+}
+
+/// 等待导出次数大于 `after` 的快照，返回其中的导出次数与刷新次数
+fn wait_for_snapshot(after: Option<usize>) -> Option<(usize, usize)> {
+    let start = get_time();
+    while get_time() - start < TIMEOUT_MS {
+        let mut buf = [0u8; 8192];
+        if let Some(len) = read_file(EXPORT_FILE, &mut buf) {
+            let text = core::str::from_utf8(&buf[..len]).unwrap_or("");
+            if let (Some(exports), Some(flushes)) =
+                (metric(text, EXPORTS_KEY), metric(text, FLUSHES_KEY))
+            {
+                if after.map_or(true, |after| exports > after) {
+                    return Some((exports, flushes));
+                }
+            }
+        }
+        sleep(100);
+    }
+    None
+}
+
+/// 写入磁盘上的文件并 fsync，块设备收到的刷新次数应当增加
+fn check() -> Result<(), &'static str> {
+    let (_, before) = wait_for_snapshot(None).ok_or("metrics snapshot not written")?;
+    let fd = open(DATA, OpenFlags::CREATE | OpenFlags::WRONLY);
+    if fd < 0 {
+        return Err("cannot create the data file");
+    }
+    let written = write(fd as usize, b"durable");
+    let synced = fsync(fd as usize);
+    close(fd as usize);
+    unlink(DATA);
+    if written != 7 || synced != 0 {
+        return Err("write or fsync failed");
+    }
+    // fsync 返回后读到的快照可能早于它，再等一次才能保证看到刷新
+    let (exports, _) = wait_for_snapshot(None).ok_or("metrics snapshot not written")?;
+    let (_, after) = wait_for_snapshot(Some(exports + 1)).ok_or("metrics snapshot not updated")?;
+    println!("[fsync_flush] block flushes {} then {}", before, after);
+    if after <= before {
+        return Err("fsync did not flush the block device");
+    }
+    Ok(())
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut old_interval = [0u8; 32];
+    let mut old_path = [0u8; 256];
+    let (interval_len, path_len) = match (
+        read_file(INTERVAL, &mut old_interval),
+        read_file(PATH, &mut old_path),
+    ) {
+        (Some(interval_len), Some(path_len)) => (interval_len, path_len),
+        _ => {
+            println!("[fsync_flush] FAILED: /proc/sys/kernel tunables missing");
+            return -1;
+        }
+    };
+    if !write_file(PATH, b"/tmp/fsync_flush_metrics\n") || !write_file(INTERVAL, b"1\n") {
+        println!("[fsync_flush] FAILED: tunables not writable");
+        return -1;
+    }
+    let result = check();
+    write_file(INTERVAL, &old_interval[..interval_len]);
+    write_file(PATH, &old_path[..path_len]);
+    unlink(EXPORT_FILE);
+    report("fsync_flush", result)
+}
@@ -0,0 +1,174 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+/// 优先级继承 futex：FUTEX_LOCK_PI 在字中写入持有者的 tid，重复加锁返回 EDEADLK，
+/// 非持有者解锁返回 EPERM，解锁时锁直接交给阻塞的等待者；
+/// 线程持有 robust 链表中的锁退出后，字被标记为 FUTEX_OWNER_DIED，锁可以再被获取
+#[cfg(target_arch = "riscv64")]
+mod workload {
+    use core::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+    use user_lib::{
+        exit, futex, gettid, report, set_robust_list, thread_spawn, yield_, RobustListHead,
+        FUTEX_LOCK_PI, FUTEX_OWNER_DIED, FUTEX_PRIVATE_FLAG, FUTEX_TID_MASK, FUTEX_TRYLOCK_PI,
+        FUTEX_UNLOCK_PI, FUTEX_WAITERS,
+    };
+
+    const EPERM: isize = -1;
+    const EAGAIN: isize = -11;
+    const EDEADLK: isize = -35;
+    const STACK_SIZE: usize = 16 * 1024;
+    /// 等待另一个线程时最多让出处理器的次数
+    const PATIENCE: usize = 200000;
+
+    static mut CONTENDER_STACK: [u8; STACK_SIZE] = [0; STACK_SIZE];
+    static mut DYING_STACK: [u8; STACK_SIZE] = [0; STACK_SIZE];
+    static LOCK: AtomicU32 = AtomicU32::new(0);
+    /// 竞争线程的检查结果：0 尚未结束，1 通过，其他值是失败的步骤
+    static CONTENDER: AtomicUsize = AtomicUsize::new(0);
+
+    /// robust 链表中唯一的一项，futex 字紧跟在指向下一项的指针之后
+    #[repr(C)]
+    struct RobustMutex {
+        next: AtomicUsize,
+        word: AtomicU32,
+    }
+
+    static ROBUST: RobustMutex = RobustMutex {
+        next: AtomicUsize::new(0),
+        word: AtomicU32::new(0),
+    };
+    static ROBUST_HELD: AtomicBool = AtomicBool::new(false);
+
+    fn futex_pi(word: &AtomicU32, op: usize) -> isize {
+        futex(word, op | FUTEX_PRIVATE_FLAG, 0, 0, word, 0)
+    }
+
+    /// 让出处理器直到 `cond` 成立
+    fn wait_until(cond: impl Fn() -> bool) -> bool {
+        for _ in 0..PATIENCE {
+            if cond() {
+                return true;
+            }
+            yield_();
+        }
+        false
+    }
+
+    extern "C" fn contender(_arg: usize) -> ! {
+        let result = if futex_pi(&LOCK, FUTEX_UNLOCK_PI) != EPERM {
+            2
+        } else if futex_pi(&LOCK, FUTEX_TRYLOCK_PI) != EAGAIN {
+            3
+        } else if futex_pi(&LOCK, FUTEX_LOCK_PI) != 0
+            || LOCK.load(Ordering::Relaxed) & FUTEX_TID_MASK != gettid() as u32
+        {
+            4
+        } else if futex_pi(&LOCK, FUTEX_UNLOCK_PI) != 0 {
+            5
+        } else {
+            1
+        };
+        CONTENDER.store(result, Ordering::Release);
+        exit(0)
+    }
+
+    /// 登记 robust 链表，持有锁后不解锁就退出
+    extern "C" fn dying(_arg: usize) -> ! {
+        // 链表头在线程栈上，线程退出时内核还能读到它
+        let head = RobustListHead {
+            list: &ROBUST as *const RobustMutex as usize,
+            futex_offset: 8,
+            list_op_pending: 0,
+        };
+        // 链表项的 next 指回链表头表示结束
+        ROBUST
+            .next
+            .store(&head as *const RobustListHead as usize, Ordering::Relaxed);
+        set_robust_list(&head);
+        ROBUST.word.store(gettid() as u32, Ordering::Release);
+        ROBUST_HELD.store(true, Ordering::Release);
+        exit(0)
+    }
+
+    fn check_lock() -> Result<(), &'static str> {
+        let tid = gettid() as u32;
+        if futex_pi(&LOCK, FUTEX_LOCK_PI) != 0 || LOCK.load(Ordering::Relaxed) != tid {
+            return Err("FUTEX_LOCK_PI did not store the owner's tid");
+        }
+        if futex_pi(&LOCK, FUTEX_LOCK_PI) != EDEADLK {
+            return Err("relocking a held PI futex did not fail with EDEADLK");
+        }
+        if thread_spawn(contender, 0, unsafe {
+            &mut *core::ptr::addr_of_mut!(CONTENDER_STACK)
+        }) < 0
+        {
+            return Err("cannot spawn the contending thread");
+        }
+        if !wait_until(|| LOCK.load(Ordering::Relaxed) & FUTEX_WAITERS != 0) {
+            return Err("the contending thread did not block on the lock");
+        }
+        // 让竞争线程进入睡眠
+        for _ in 0..100 {
+            yield_();
+        }
+        if futex_pi(&LOCK, FUTEX_UNLOCK_PI) != 0 {
+            return Err("FUTEX_UNLOCK_PI failed");
+        }
+        if !wait_until(|| CONTENDER.load(Ordering::Acquire) != 0) {
+            return Err("the lock was never handed over to the waiter");
+        }
+        match CONTENDER.load(Ordering::Acquire) {
+            1 => {}
+            2 => return Err("a thread not owning the lock unlocked it"),
+            3 => return Err("FUTEX_TRYLOCK_PI on a held lock did not fail with EAGAIN"),
+            4 => return Err("the waiter did not get the lock with its tid"),
+            _ => return Err("the new owner could not unlock the lock"),
+        }
+        if LOCK.load(Ordering::Relaxed) != 0 {
+            return Err("the lock is still held after the last unlock");
+        }
+        Ok(())
+    }
+
+    fn check_robust() -> Result<(), &'static str> {
+        if thread_spawn(dying, 0, unsafe {
+            &mut *core::ptr::addr_of_mut!(DYING_STACK)
+        }) < 0
+        {
+            return Err("cannot spawn the dying thread");
+        }
+        if !wait_until(|| ROBUST.word.load(Ordering::Acquire) & FUTEX_OWNER_DIED != 0) {
+            return Err("the lock held by an exited thread was not marked FUTEX_OWNER_DIED");
+        }
+        if !ROBUST_HELD.load(Ordering::Acquire)
+            || ROBUST.word.load(Ordering::Relaxed) != FUTEX_OWNER_DIED
+        {
+            return Err("the owner tid was not cleared from the robust futex word");
+        }
+        let tid = gettid() as u32;
+        if futex_pi(&ROBUST.word, FUTEX_TRYLOCK_PI) != 0
+            || ROBUST.word.load(Ordering::Relaxed) != tid | FUTEX_OWNER_DIED
+        {
+            return Err("the lock of a dead owner could not be taken");
+        }
+        Ok(())
+    }
+
+    pub fn main() -> i32 {
+        report("futex_pi", check_lock().and_then(|()| check_robust()))
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    #[cfg(target_arch = "riscv64")]
+    return workload::main();
+    #[cfg(not(target_arch = "riscv64"))]
+    {
+        println!("[futex_pi] skipped: threads are only spawned on riscv64");
+        0
+    }
+}
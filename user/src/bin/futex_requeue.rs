@@ -0,0 +1,176 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+/// 用 futex 实现互斥锁与条件变量：广播用 FUTEX_CMP_REQUEUE 只唤醒一个等待者，
+/// 其余的移到互斥锁上，由解锁依次唤醒，所有等待者都应醒来；
+/// 另外检查 FUTEX_CMP_REQUEUE 的比较与 FUTEX_WAKE_OP 对第二个字的运算
+#[cfg(target_arch = "riscv64")]
+mod workload {
+    use core::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+    use user_lib::{
+        exit, futex, report, thread_spawn, yield_, FUTEX_CMP_REQUEUE, FUTEX_PRIVATE_FLAG,
+        FUTEX_WAIT, FUTEX_WAKE, FUTEX_WAKE_OP,
+    };
+
+    const EAGAIN: isize = -11;
+    const ENOSYS: isize = -38;
+    const WAITERS: usize = 4;
+    const STACK_SIZE: usize = 16 * 1024;
+    /// 等待所有等待者醒来时最多让出处理器的次数
+    const PATIENCE: usize = 200000;
+
+    static mut STACKS: [[u8; STACK_SIZE]; WAITERS] = [[0; STACK_SIZE]; WAITERS];
+    /// 互斥锁：0 未上锁，1 已上锁，2 已上锁且可能有等待者
+    static LOCK: AtomicU32 = AtomicU32::new(0);
+    /// 条件变量的序号，每次广播加一
+    static SEQ: AtomicU32 = AtomicU32::new(0);
+    /// 条件变量保护的条件
+    static READY: AtomicBool = AtomicBool::new(false);
+    static WAITING: AtomicUsize = AtomicUsize::new(0);
+    static WOKEN: AtomicUsize = AtomicUsize::new(0);
+
+    fn futex_op(
+        uaddr: &AtomicU32,
+        op: usize,
+        val: u32,
+        val2: usize,
+        uaddr2: &AtomicU32,
+        val3: u32,
+    ) -> isize {
+        futex(uaddr, op | FUTEX_PRIVATE_FLAG, val, val2, uaddr2, val3)
+    }
+
+    /// 按有竞争的方式加锁，锁一直标记为可能有等待者
+    fn lock_contended() {
+        while LOCK.swap(2, Ordering::Acquire) != 0 {
+            futex_op(&LOCK, FUTEX_WAIT, 2, 0, &LOCK, 0);
+        }
+    }
+
+    fn lock() {
+        if LOCK
+            .compare_exchange(0, 1, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            lock_contended();
+        }
+    }
+
+    fn unlock() {
+        if LOCK.swap(0, Ordering::Release) == 2 {
+            futex_op(&LOCK, FUTEX_WAKE, 1, 0, &LOCK, 0);
+        }
+    }
+
+    /// 持有锁时调用：释放锁并等待广播，醒来后重新持有锁
+    fn cond_wait() {
+        let seq = SEQ.load(Ordering::Relaxed);
+        WAITING.fetch_add(1, Ordering::Relaxed);
+        unlock();
+        futex_op(&SEQ, FUTEX_WAIT, seq, 0, &SEQ, 0);
+        // 可能是被移到锁上之后由解锁唤醒的，其他被移过来的等待者还在锁上睡眠
+        lock_contended();
+    }
+
+    /// 唤醒一个等待者，其余的移到锁上；调用者持有锁
+    fn cond_broadcast() -> isize {
+        let seq = SEQ.fetch_add(1, Ordering::Relaxed) + 1;
+        let moved = futex_op(&SEQ, FUTEX_CMP_REQUEUE, 1, i32::MAX as usize, &LOCK, seq);
+        if moved > 1 {
+            // 移到锁上的等待者要靠解锁唤醒
+            LOCK.store(2, Ordering::Relaxed);
+        }
+        moved
+    }
+
+    extern "C" fn waiter(_arg: usize) -> ! {
+        lock();
+        while !READY.load(Ordering::Relaxed) {
+            cond_wait();
+        }
+        WOKEN.fetch_add(1, Ordering::Relaxed);
+        unlock();
+        exit(0)
+    }
+
+    /// `FUTEX_OP(op, oparg, cmp, cmparg)`
+    fn encode(op: u32, oparg: u32, cmp: u32, cmparg: u32) -> u32 {
+        (op << 28) | (cmp << 24) | ((oparg & 0xfff) << 12) | (cmparg & 0xfff)
+    }
+
+    fn check_wake_op() -> Result<(), &'static str> {
+        let idle = AtomicU32::new(0);
+        let word = AtomicU32::new(5);
+        // FUTEX_OP_ADD 3，旧值大于 4 时唤醒；两个地址上都没有等待者
+        if futex_op(&idle, FUTEX_WAKE_OP, 1, 1, &word, encode(1, 3, 4, 4)) != 0
+            || word.load(Ordering::Relaxed) != 8
+        {
+            return Err("FUTEX_WAKE_OP did not add to the second word");
+        }
+        // FUTEX_OP_OR 与 FUTEX_OP_OPARG_SHIFT：或上 1 << 4
+        futex_op(&idle, FUTEX_WAKE_OP, 1, 1, &word, encode(8 | 2, 4, 0, 0));
+        if word.load(Ordering::Relaxed) != 24 {
+            return Err("FUTEX_WAKE_OP ignored FUTEX_OP_OPARG_SHIFT");
+        }
+        if futex_op(&idle, FUTEX_WAKE_OP, 1, 1, &word, encode(7, 0, 0, 0)) != ENOSYS
+            || word.load(Ordering::Relaxed) != 24
+        {
+            return Err("FUTEX_WAKE_OP accepted an unknown operation");
+        }
+        Ok(())
+    }
+
+    fn check_broadcast() -> Result<(), &'static str> {
+        let seq = SEQ.load(Ordering::Relaxed);
+        if futex_op(&SEQ, FUTEX_CMP_REQUEUE, 1, 1, &LOCK, seq + 1) != EAGAIN {
+            return Err("FUTEX_CMP_REQUEUE ignored a changed futex word");
+        }
+        for stack in unsafe { STACKS.iter_mut() } {
+            if thread_spawn(waiter, 0, stack) < 0 {
+                return Err("cannot spawn a waiter thread");
+            }
+        }
+        while WAITING.load(Ordering::Relaxed) < WAITERS {
+            yield_();
+        }
+        // 让最后一个等待者也进入睡眠
+        for _ in 0..100 {
+            yield_();
+        }
+        lock();
+        READY.store(true, Ordering::Relaxed);
+        let moved = cond_broadcast();
+        unlock();
+        if moved < 0 || moved as usize > WAITERS {
+            return Err("FUTEX_CMP_REQUEUE returned a wrong count");
+        }
+        for _ in 0..PATIENCE {
+            if WOKEN.load(Ordering::Relaxed) == WAITERS {
+                return Ok(());
+            }
+            yield_();
+        }
+        Err("waiters requeued onto the mutex were never woken")
+    }
+
+    pub fn main() -> i32 {
+        report(
+            "futex_requeue",
+            check_wake_op().and_then(|()| check_broadcast()),
+        )
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    #[cfg(target_arch = "riscv64")]
+    return workload::main();
+    #[cfg(not(target_arch = "riscv64"))]
+    {
+        println!("[futex_requeue] skipped: threads are only spawned on riscv64");
+        0
+    }
+}
@@ -0,0 +1,135 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+/// 同一进程的几个线程频繁在屏障上忙等，另有同样多的忙等进程争抢 CPU，
+/// 开启组调度后线程更常同时运行，完成同样轮数所需的时间应不长于关闭时
+#[cfg(target_arch = "riscv64")]
+mod workload {
+    use core::hint::black_box;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use user_lib::{
+        close, exit, fork, get_time, kill, open, thread_spawn, waitpid, write, yield_, OpenFlags,
+        SIGKILL,
+    };
+
+    const THREADS: usize = 4;
+    const HOGS: usize = 4;
+    const ROUNDS: usize = 200;
+    const STACK_SIZE: usize = 16 * 1024;
+    const GANG_SYSCTL: &str = "/proc/sys/kernel/sched_gang\0";
+
+    static mut STACKS: [[u8; STACK_SIZE]; THREADS] = [[0; STACK_SIZE]; THREADS];
+    /// 本轮已到达屏障的线程数
+    static ARRIVED: AtomicUsize = AtomicUsize::new(0);
+    /// 屏障放行的次数
+    static GENERATION: AtomicUsize = AtomicUsize::new(0);
+    static FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+    fn barrier() {
+        let generation = GENERATION.load(Ordering::Acquire);
+        if ARRIVED.fetch_add(1, Ordering::AcqRel) + 1 == THREADS {
+            ARRIVED.store(0, Ordering::Relaxed);
+            GENERATION.fetch_add(1, Ordering::Release);
+        } else {
+            while GENERATION.load(Ordering::Acquire) == generation {
+                core::hint::spin_loop();
+            }
+        }
+    }
+
+    extern "C" fn worker(_: usize) -> ! {
+        let mut acc = 0usize;
+        for _ in 0..ROUNDS {
+            for _ in 0..2000 {
+                acc = black_box(acc.wrapping_add(1));
+            }
+            barrier();
+        }
+        FINISHED.fetch_add(1, Ordering::Release);
+        exit(0)
+    }
+
+    fn set_gang(enabled: bool) -> bool {
+        let fd = open(GANG_SYSCTL, OpenFlags::WRONLY);
+        if fd < 0 {
+            return false;
+        }
+        let value: &[u8] = if enabled { b"1\n" } else { b"0\n" };
+        let ret = write(fd as usize, value);
+        close(fd as usize);
+        ret == value.len() as isize
+    }
+
+    /// 在忙等进程的干扰下跑完所有轮次，返回所用毫秒数
+    fn run(gang: bool) -> Option<isize> {
+        if !set_gang(gang) {
+            return None;
+        }
+        ARRIVED.store(0, Ordering::Relaxed);
+        GENERATION.store(0, Ordering::Relaxed);
+        FINISHED.store(0, Ordering::Relaxed);
+        let mut hogs = [0isize; HOGS];
+        for hog in hogs.iter_mut() {
+            *hog = fork();
+            if *hog == 0 {
+                let mut acc = 0usize;
+                loop {
+                    acc = black_box(acc.wrapping_add(1));
+                }
+            }
+        }
+        let start = get_time();
+        for i in 0..THREADS {
+            let stack = unsafe { &mut STACKS[i] };
+            if thread_spawn(worker, i, stack) < 0 {
+                return None;
+            }
+        }
+        while FINISHED.load(Ordering::Acquire) < THREADS {
+            yield_();
+        }
+        let elapsed = get_time() - start;
+        for hog in hogs.iter() {
+            kill(*hog as usize, SIGKILL);
+            let mut exit_code: i32 = 0;
+            waitpid(*hog as usize, &mut exit_code);
+        }
+        Some(elapsed)
+    }
+
+    pub fn main() -> i32 {
+        let (plain, gang) = match (run(false), run(true)) {
+            (Some(plain), Some(gang)) => (plain, gang),
+            _ => {
+                println!("[gang_sched] FAILED: cannot switch gang scheduling or spawn threads");
+                return -1;
+            }
+        };
+        set_gang(false);
+        println!(
+            "[gang_sched] {} rounds: {} ms plain, {} ms gang",
+            ROUNDS, plain, gang
+        );
+        // 留出计时与调度抖动的余量
+        if gang > plain + plain / 10 {
+            println!("[gang_sched] FAILED: gang scheduling made the barrier workload slower");
+            return -1;
+        }
+        println!("[gang_sched] passed");
+        0
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    #[cfg(target_arch = "riscv64")]
+    return workload::main();
+    #[cfg(not(target_arch = "riscv64"))]
+    {
+        println!("[gang_sched] skipped: threads are only spawned on riscv64");
+        0
+    }
+}
@@ -0,0 +1,94 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use user_lib::{close, getdents64, open, report, unlink, OpenFlags};
+
+const NAME_LEN: usize = 200;
+/// 只比长名字的记录（224 字节）稍大，目录项需要分多次读出
+const BUF_SIZE: usize = 256;
+/// `d_ino`、`d_off`、`d_reclen` 与 `d_type` 共占的字节数
+const HEADER_LEN: usize = 19;
+
+/// 逐次读出目录 `fd` 的所有目录项，检查每条记录的 d_reclen，返回是否见到名为 `name` 的项
+fn find_entry(fd: usize, name: &str) -> Result<bool, String> {
+    let mut buf = [0u8; BUF_SIZE];
+    let mut found = false;
+    loop {
+        let len = getdents64(fd, &mut buf);
+        if len < 0 {
+            return Err(format!("getdents64 returned {}", len));
+        }
+        if len == 0 {
+            return Ok(found);
+        }
+        let len = len as usize;
+        if len > BUF_SIZE {
+            return Err(format!(
+                "{} bytes returned for a {} byte buffer",
+                len, BUF_SIZE
+            ));
+        }
+        let mut pos = 0;
+        while pos < len {
+            let reclen = u16::from_ne_bytes([buf[pos + 16], buf[pos + 17]]) as usize;
+            if reclen <= HEADER_LEN || reclen % 8 != 0 || pos + reclen > len {
+                return Err(format!("malformed record of length {} at {}", reclen, pos));
+            }
+            let name_end = match buf[pos + HEADER_LEN..pos + reclen]
+                .iter()
+                .position(|&byte| byte == 0)
+            {
+                Some(end) => pos + HEADER_LEN + end,
+                None => return Err(format!("unterminated name at {}", pos)),
+            };
+            // 名字之后的填充不超过对齐所需
+            if reclen - (name_end + 1 - pos) >= 8 {
+                return Err(format!("record of length {} padded too much", reclen));
+            }
+            if &buf[pos + HEADER_LEN..name_end] == name.as_bytes() {
+                found = true;
+            }
+            pos += reclen;
+        }
+    }
+}
+
+/// 建一个 200 字符长的文件名，getdents64 应返回完整的名字与 8 字节对齐的 d_reclen
+#[no_mangle]
+pub fn main() -> i32 {
+    let name: String = (0..NAME_LEN)
+        .map(|i| (b'a' + (i % 26) as u8) as char)
+        .collect();
+    let path = format!("{}\0", name);
+    let fd = open(&path, OpenFlags::CREATE | OpenFlags::WRONLY);
+    if fd < 0 {
+        println!(
+            "[getdents_long_name] FAILED: cannot create the file: {}",
+            fd
+        );
+        return -1;
+    }
+    close(fd as usize);
+    let dir = open(".\0", OpenFlags::RDONLY);
+    let result = if dir < 0 {
+        Err(format!("cannot open the directory: {}", dir))
+    } else {
+        let result = find_entry(dir as usize, &name);
+        close(dir as usize);
+        result
+    };
+    unlink(&path);
+    report(
+        "getdents_long_name",
+        result.and_then(|listed| match listed {
+            true => Ok(()),
+            false => Err(String::from("the long name was not listed in full")),
+        }),
+    )
+}
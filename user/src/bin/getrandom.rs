@@ -0,0 +1,56 @@
+#![no_std]
+#![no_main]
+
+extern crate user_lib;
+use user_lib::{
+    close, getrandom, open, read, report, OpenFlags, GRND_INSECURE, GRND_NONBLOCK, GRND_RANDOM,
+};
+
+/// 跨越页边界，检查内核把整个用户缓冲区都填满了
+const LEN: usize = 5000;
+
+/// 两次取得的随机数互不相同，末尾的字节也被填充；各种标志组合都返回请求的长度，
+/// 未知标志与 GRND_INSECURE | GRND_RANDOM 被拒绝
+fn check() -> Result<(), &'static str> {
+    let mut first = [0u8; LEN];
+    let mut second = [0u8; LEN];
+    if getrandom(&mut first, 0) != LEN as isize {
+        return Err("getrandom did not fill the whole buffer");
+    }
+    if getrandom(&mut second, GRND_NONBLOCK) != LEN as isize {
+        return Err("the pool is not ready after boot");
+    }
+    if first[..] == second[..] {
+        return Err("two calls returned the same bytes");
+    }
+    if first[LEN - 64..].iter().all(|&byte| byte == 0) {
+        return Err("the tail of the buffer was left empty");
+    }
+    for flags in [GRND_RANDOM, GRND_INSECURE, GRND_RANDOM | GRND_NONBLOCK] {
+        if getrandom(&mut first[..16], flags) != 16 {
+            return Err("a valid flag combination failed");
+        }
+    }
+    if getrandom(&mut first[..16], GRND_INSECURE | GRND_RANDOM) >= 0 {
+        return Err("GRND_INSECURE | GRND_RANDOM was accepted");
+    }
+    if getrandom(&mut first[..16], 0x80) >= 0 {
+        return Err("an unknown flag was accepted");
+    }
+    let fd = open("/dev/urandom\0", OpenFlags::RDONLY);
+    if fd < 0 {
+        return Err("cannot open /dev/urandom");
+    }
+    let mut from_dev = [0u8; 64];
+    let len = read(fd as usize, &mut from_dev);
+    close(fd as usize);
+    if len != 64 || from_dev.iter().all(|&byte| byte == 0) {
+        return Err("/dev/urandom returned no random bytes");
+    }
+    Ok(())
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    report("getrandom", check())
+}
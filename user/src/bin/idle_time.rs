@@ -0,0 +1,53 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, nanosleep, open, read, OpenFlags};
+
+/// 睡眠的时长，期间本进程所在的 CPU 无事可做
+const SLEEP_MS: usize = 200;
+
+/// 所有 CPU 的空闲时间之和，单位纳秒
+fn idle_time() -> Option<usize> {
+    let fd = open("/proc/schedstat\0", OpenFlags::RDONLY);
+    if fd < 0 {
+        return None;
+    }
+    let mut buf = [0u8; 1024];
+    let len = read(fd as usize, &mut buf);
+    close(fd as usize);
+    let text = core::str::from_utf8(&buf[..len.max(0) as usize]).ok()?;
+    let mut total = 0;
+    // "cpu<N> <context_switches> <voluntary> <involuntary> <run_time_ns> <nr_running> <idle_time_ns>"
+    for line in text.lines().filter(|line| line.starts_with("cpu")) {
+        total += line.split_whitespace().nth(6)?.parse::<usize>().ok()?;
+    }
+    Some(total)
+}
+
+/// 睡眠期间没有别的任务可运行，各 CPU 由空闲任务占据，空闲时间应随之增长
+#[no_mangle]
+pub fn main() -> i32 {
+    let before = match idle_time() {
+        Some(before) => before,
+        None => {
+            println!("[idle_time] FAILED: /proc/schedstat missing or malformed");
+            return -1;
+        }
+    };
+    nanosleep(SLEEP_MS);
+    let after = idle_time().unwrap_or(before);
+    let idle_ms = (after - before) / 1_000_000;
+    println!(
+        "[idle_time] {} ms idle while sleeping {} ms",
+        idle_ms, SLEEP_MS
+    );
+    // 时钟中断与唤醒本身也要占用一些时间
+    if idle_ms < SLEEP_MS / 2 {
+        println!("[idle_time] FAILED: idle task did not account the idle time");
+        return -1;
+    }
+    println!("[idle_time] passed");
+    0
+}
@@ -0,0 +1,50 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use core::hint::black_box;
+use user_lib::{exit, fork, get_time, sleep, waitpid};
+
+// 应不少于 CPU 核心数，使空闲核都有机会被唤醒
+const PROCESS_NUM: usize = 4;
+const ROUND_NUM: usize = 5;
+const SPIN_NUM: usize = 2_000_000;
+
+fn busy_worker(seed: usize) -> ! {
+    let mut acc = seed;
+    for i in 0..SPIN_NUM {
+        acc = black_box(acc.wrapping_mul(31).wrapping_add(i));
+    }
+    exit(if acc == usize::MAX { 1 } else { 0 });
+}
+
+/// 每轮先让其他核空闲（进入 HSM 挂起），再一次性创建多个计算任务，
+/// 观察空闲核能否被唤醒并完成任务。内核侧可对照
+/// kernel_hart_suspends_total / kernel_idle_wakeup_ipis_total 两个计数器。
+#[no_mangle]
+pub fn main() -> i32 {
+    println!("[idle_wakeup] {} rounds, {} tasks per round", ROUND_NUM, PROCESS_NUM);
+    for round in 0..ROUND_NUM {
+        sleep(50);
+        let start = get_time();
+        let mut pids = [0isize; PROCESS_NUM];
+        for (i, pid) in pids.iter_mut().enumerate() {
+            *pid = fork();
+            if *pid == 0 {
+                busy_worker(i);
+            }
+        }
+        for pid in pids.iter() {
+            let mut exit_code: i32 = 0;
+            waitpid(*pid as usize, &mut exit_code);
+            if exit_code != 0 {
+                println!("[idle_wakeup] FAILED: task {} exited with {}", pid, exit_code);
+                return -1;
+            }
+        }
+        println!("[idle_wakeup] round {}: {} ms", round, get_time() - start);
+    }
+    println!("[idle_wakeup] passed");
+    0
+}
@@ -0,0 +1,198 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+/// 经回环地址的 TCP 与 UDP：建立连接、收发数据、连接被拒绝、非阻塞接收
+#[cfg(target_arch = "riscv64")]
+mod workload {
+    use core::ptr::addr_of_mut;
+    use user_lib::{
+        accept, bind, close, connect, getsockname, listen, read, recvfrom, report, sendto, socket,
+        write, SockaddrIn, AF_INET, SOCK_DGRAM, SOCK_NONBLOCK, SOCK_STREAM,
+    };
+
+    const EAGAIN: isize = -11;
+    const ECONNREFUSED: isize = -111;
+    const LOOPBACK: [u8; 4] = [127, 0, 0, 1];
+    const TCP_PORT: u16 = 5201;
+    const UDP_PORT: u16 = 5202;
+    /// 没有套接字监听的端口
+    const CLOSED_PORT: u16 = 5203;
+    /// 一次写入的数据跨过多个页，也多于一个 TCP 报文段
+    const BULK_SIZE: usize = 64 * 1024;
+
+    static mut BULK: [u8; BULK_SIZE] = [0; BULK_SIZE];
+    static mut RECEIVED: [u8; BULK_SIZE] = [0; BULK_SIZE];
+
+    fn new_socket(socket_type: usize) -> Result<usize, &'static str> {
+        let fd = socket(AF_INET, socket_type, 0);
+        if fd < 0 {
+            return Err("cannot create a socket");
+        }
+        Ok(fd as usize)
+    }
+
+    /// 读满 `buf`，对端提前关闭或出错时返回已读到的字节数
+    fn read_all(fd: usize, buf: &mut [u8]) -> usize {
+        let mut done = 0;
+        while done < buf.len() {
+            let len = read(fd, &mut buf[done..]);
+            if len <= 0 {
+                break;
+            }
+            done += len as usize;
+        }
+        done
+    }
+
+    /// 连接建立后数据双向可达，大块数据完整送达，客户端关闭后服务端读到文件末尾
+    fn check_tcp_exchange(client: usize, server: usize) -> Result<(), &'static str> {
+        let mut buf = [0u8; 16];
+        if write(client, b"ping") != 4 || read(server, &mut buf) != 4 || &buf[..4] != b"ping" {
+            return Err("data did not travel from the client to the server");
+        }
+        if write(server, b"pong") != 4 || read(client, &mut buf) != 4 || &buf[..4] != b"pong" {
+            return Err("data did not travel from the server to the client");
+        }
+        let (bulk, received) = unsafe { (&mut *addr_of_mut!(BULK), &mut *addr_of_mut!(RECEIVED)) };
+        for (i, byte) in bulk.iter_mut().enumerate() {
+            *byte = (i % 251) as u8;
+        }
+        if write(client, bulk) != BULK_SIZE as isize {
+            return Err("a large write was not accepted in full");
+        }
+        if read_all(server, received) != BULK_SIZE || bulk[..] != received[..] {
+            return Err("a large write did not arrive intact");
+        }
+        Ok(())
+    }
+
+    fn check_tcp(listener: usize) -> Result<(), &'static str> {
+        let (addr, len) = SockaddrIn::new(LOOPBACK, TCP_PORT);
+        if bind(listener, &addr, len) != 0 || listen(listener, 4) != 0 {
+            return Err("cannot listen on the loopback address");
+        }
+        let mut name = SockaddrIn::default();
+        let mut name_len = core::mem::size_of::<SockaddrIn>() as u32;
+        if getsockname(listener, &mut name, &mut name_len) != 0
+            || name_len as usize != len
+            || name.port() != TCP_PORT
+        {
+            return Err("getsockname did not return the bound port");
+        }
+        // 回环上的握手在 connect 中完成，同一进程中随后 accept 即可取得连接
+        let client = new_socket(SOCK_STREAM)?;
+        if connect(client, &addr, len) != 0 {
+            close(client);
+            return Err("cannot connect to the listening socket");
+        }
+        let mut peer = SockaddrIn::default();
+        let mut peer_len = core::mem::size_of::<SockaddrIn>() as u32;
+        let server = accept(listener, &mut peer, &mut peer_len);
+        if server < 0 {
+            close(client);
+            return Err("accept failed");
+        }
+        let server = server as usize;
+        if peer.sin_addr != LOOPBACK {
+            close(client);
+            close(server);
+            return Err("accept did not report the client address");
+        }
+        let result = check_tcp_exchange(client, server);
+        close(client);
+        let mut buf = [0u8; 16];
+        let eof = read(server, &mut buf);
+        close(server);
+        result?;
+        if eof != 0 {
+            return Err("reading after the client closed did not return end of file");
+        }
+        Ok(())
+    }
+
+    /// 没有套接字监听的端口回复 RST，connect 返回 ECONNREFUSED
+    fn check_refused() -> Result<(), &'static str> {
+        let (addr, len) = SockaddrIn::new(LOOPBACK, CLOSED_PORT);
+        let client = new_socket(SOCK_STREAM)?;
+        let ret = connect(client, &addr, len);
+        close(client);
+        if ret != ECONNREFUSED {
+            return Err("connecting to a closed port did not fail with ECONNREFUSED");
+        }
+        Ok(())
+    }
+
+    /// 数据报送达绑定的端口，接收方从 recvfrom 得到发送者的地址并回复
+    fn check_udp(server: usize, client: usize) -> Result<(), &'static str> {
+        let (addr, len) = SockaddrIn::new(LOOPBACK, UDP_PORT);
+        if bind(server, &addr, len) != 0 {
+            return Err("cannot bind a datagram socket");
+        }
+        if sendto(client, b"datagram", Some((&addr, len))) != 8 {
+            return Err("sendto failed");
+        }
+        let mut buf = [0u8; 16];
+        let mut sender = SockaddrIn::default();
+        let mut sender_len = core::mem::size_of::<SockaddrIn>() as u32;
+        if recvfrom(server, &mut buf, &mut sender, &mut sender_len) != 8 || &buf[..8] != b"datagram"
+        {
+            return Err("the datagram did not arrive");
+        }
+        if sender_len as usize != len || sender.port() == 0 {
+            return Err("recvfrom did not report the sender");
+        }
+        if sendto(server, b"reply", Some((&sender, sender_len as usize))) != 5
+            || read(client, &mut buf) != 5
+            || &buf[..5] != b"reply"
+        {
+            return Err("the reply did not reach the sender");
+        }
+        Ok(())
+    }
+
+    /// 非阻塞的数据报套接字没有数据时返回 EAGAIN
+    fn check_nonblock() -> Result<(), &'static str> {
+        let fd = new_socket(SOCK_DGRAM | SOCK_NONBLOCK)?;
+        let (addr, len) = SockaddrIn::new(LOOPBACK, UDP_PORT + 10);
+        let mut buf = [0u8; 16];
+        let ok = bind(fd, &addr, len) == 0 && read(fd, &mut buf) == EAGAIN;
+        close(fd);
+        if !ok {
+            return Err("a nonblocking read without data did not fail with EAGAIN");
+        }
+        Ok(())
+    }
+
+    fn check() -> Result<(), &'static str> {
+        let listener = new_socket(SOCK_STREAM)?;
+        let result = check_tcp(listener);
+        close(listener);
+        result?;
+        check_refused()?;
+        let server = new_socket(SOCK_DGRAM)?;
+        let client = new_socket(SOCK_DGRAM)?;
+        let result = check_udp(server, client);
+        close(server);
+        close(client);
+        result?;
+        check_nonblock()
+    }
+
+    pub fn main() -> i32 {
+        report("inet_socket", check())
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    #[cfg(target_arch = "riscv64")]
+    return workload::main();
+    #[cfg(not(target_arch = "riscv64"))]
+    {
+        println!("[inet_socket] skipped: six-argument system calls are only passed on riscv64");
+        0
+    }
+}
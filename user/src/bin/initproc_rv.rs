@@ -1,7 +1,10 @@
 #![no_std]
 #![no_main]
 
-use user_lib::{exec, exit, fork, shutdown, waitpid, println};
+use user_lib::{
+    exec, exit, fork, reboot, shutdown, waitpid, println, LINUX_REBOOT_CMD_POWER_OFF,
+    LINUX_REBOOT_MAGIC1, LINUX_REBOOT_MAGIC2,
+};
 
 #[no_mangle]
 #[link_section = ".text.entry"]
@@ -54,6 +57,8 @@ fn main() -> i32 {
     }
 
     println!("[initproc] All tests finished!");
+    reboot(LINUX_REBOOT_MAGIC1, LINUX_REBOOT_MAGIC2, LINUX_REBOOT_CMD_POWER_OFF);
+    // 内核不支持 reboot 时退回到非标准的 shutdown
     shutdown();
     0
 }
\ No newline at end of file
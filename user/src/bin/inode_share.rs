@@ -0,0 +1,71 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, lseek, mkdir, open, report, rmdir, unlink, write, OpenFlags, SEEK_END};
+
+const DIR: &str = "/inode_share\0";
+const FILE: &str = "/inode_share/data\0";
+const DROP_CACHES: &str = "/proc/sys/vm/drop_caches\0";
+
+/// `fd` 所指文件的大小
+fn size(fd: usize) -> isize {
+    lseek(fd, 0, SEEK_END)
+}
+
+fn write_file(path: &str, data: &[u8]) -> bool {
+    let fd = open(path, OpenFlags::WRONLY);
+    if fd < 0 {
+        return false;
+    }
+    let written = write(fd as usize, data);
+    close(fd as usize);
+    written == data.len() as isize
+}
+
+/// 经一个 fd 写入的数据使另一个 fd 看到的大小一同变化。
+/// 第二个 fd 在释放目录项之后打开：此时路径须重新从磁盘查找，仍应得到同一个 inode
+fn check(first: usize) -> Result<(), &'static str> {
+    if write(first, &[b'a'; 100]) != 100 {
+        return Err("write through the first fd failed");
+    }
+    if !write_file(DROP_CACHES, b"2\n") {
+        return Err("cannot drop the dentries");
+    }
+    let second = open(FILE, OpenFlags::RDWR);
+    if second < 0 {
+        return Err("cannot open the file a second time");
+    }
+    let second = second as usize;
+    let result = if size(second) != 100 {
+        Err("the second fd does not see the data written through the first")
+    } else if lseek(second, 0, SEEK_END) < 0 || write(second, &[b'b'; 50]) != 50 {
+        Err("write through the second fd failed")
+    } else if size(first) != 150 {
+        Err("the first fd does not see the data written through the second")
+    } else {
+        Ok(())
+    };
+    close(second);
+    result
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    if mkdir(DIR) < 0 {
+        println!("[inode_share] FAILED: cannot create {}", DIR);
+        return -1;
+    }
+    let fd = open(FILE, OpenFlags::CREATE | OpenFlags::RDWR);
+    let result = if fd < 0 {
+        Err("cannot create the file")
+    } else {
+        let result = check(fd as usize);
+        close(fd as usize);
+        result
+    };
+    unlink(FILE);
+    rmdir(DIR);
+    report("inode_share", result)
+}
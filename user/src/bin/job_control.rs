@@ -0,0 +1,87 @@
+#![no_std]
+#![no_main]
+
+extern crate user_lib;
+
+use user_lib::{
+    exit, fork, getpgid, getpid, kill, report, setpgid, waitpid_options, yield_, SIGCONT, SIGKILL,
+    SIGSTOP, WCONTINUED, WUNTRACED,
+};
+
+const ESRCH: isize = -3;
+/// wait4 报告子进程被 SIGCONT 继续时的状态字
+const STATUS_CONTINUED: i32 = 0xffff;
+
+/// 等待子进程 `pid` 的下一个状态变化，返回状态字
+fn wait_status(pid: isize, options: usize) -> Result<i32, &'static str> {
+    let mut status = 0;
+    if waitpid_options(pid, &mut status, options) != pid {
+        return Err("wait4 did not report the child");
+    }
+    Ok(status)
+}
+
+/// 子进程停止自己，wait4 依次报告停止、继续与退出
+fn check_stop_continue() -> Result<(), &'static str> {
+    let pid = fork();
+    if pid == 0 {
+        kill(getpid() as usize, SIGSTOP);
+        exit(7);
+    }
+    let stopped = wait_status(pid, WUNTRACED)?;
+    if stopped & 0xff != 0x7f || (stopped >> 8) & 0xff != SIGSTOP as i32 {
+        return Err("the stopped child was not reported as stopped by SIGSTOP");
+    }
+    kill(pid as usize, SIGCONT);
+    if wait_status(pid, WCONTINUED)? != STATUS_CONTINUED {
+        return Err("the continued child was not reported as continued");
+    }
+    let exited = wait_status(pid, 0)?;
+    if exited & 0x7f != 0 || (exited >> 8) & 0xff != 7 {
+        return Err("the continued child did not run to its exit");
+    }
+    Ok(())
+}
+
+/// 向子进程所在的进程组发送 SIGSTOP 与 SIGKILL
+fn check_process_group() -> Result<(), &'static str> {
+    let pid = fork();
+    if pid == 0 {
+        setpgid(0, 0);
+        loop {
+            yield_();
+        }
+    }
+    // 父进程也设置一次，不必等子进程先运行
+    setpgid(pid as usize, pid as usize);
+    if getpgid(pid as usize) != pid {
+        return Err("the child did not become the leader of a new process group");
+    }
+    kill(-pid as usize, SIGSTOP);
+    let stopped = wait_status(pid, WUNTRACED)?;
+    kill(-pid as usize, SIGKILL);
+    let killed = wait_status(pid, 0)?;
+    if stopped & 0xff != 0x7f {
+        return Err("SIGSTOP sent to the process group did not stop the child");
+    }
+    if killed & 0x7f != SIGKILL as i32 {
+        return Err("SIGKILL sent to the process group did not kill the stopped child");
+    }
+    if kill(-pid as usize, SIGCONT) != ESRCH {
+        return Err("signalling an empty process group did not fail with ESRCH");
+    }
+    Ok(())
+}
+
+fn check() -> Result<(), &'static str> {
+    if setpgid(0, 0) != 0 || getpgid(0) != getpid() {
+        return Err("setpgid(0, 0) did not make the caller a process group leader");
+    }
+    check_stop_continue()?;
+    check_process_group()
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    report("job_control", check())
+}
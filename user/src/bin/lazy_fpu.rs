@@ -0,0 +1,188 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use core::hint::black_box;
+use user_lib::{
+    close, exit, fork, get_time, kill, open, read, waitpid, waitpid_options, yield_, OpenFlags,
+    SIGKILL, WNOHANG,
+};
+
+// 只做整数运算的进程数，应多于 CPU 核心数
+const INT_PROCESS_NUM: usize = 6;
+const LOOP_NUM: usize = 300;
+/// 浮点任务须在这段时间（毫秒）内跑完循环
+const FP_TIMEOUT_MS: isize = 10_000;
+
+const SCHED: &str = "/proc/self/sched\0";
+const SAVES_KEY: &str = "fpu_saves : ";
+const RESTORES_KEY: &str = "fpu_restores : ";
+
+/// 整数任务的浮点上下文被保存或装载过
+const EXIT_FP_SWITCHED: i32 = 2;
+/// 读不到 `/proc/self/sched`
+const EXIT_NO_STATS: i32 = 3;
+
+/// 本任务的浮点上下文保存与装载次数，只统计读取者自己，不受系统中其他任务影响
+#[derive(Debug, Clone, Copy)]
+struct FpuStats {
+    saves: usize,
+    restores: usize,
+}
+
+fn metric(text: &str, key: &str) -> Option<usize> {
+    let start = text.find(key)? + key.len();
+    text[start..].split('\n').next()?.trim().parse().ok()
+}
+
+fn fpu_stats() -> Option<FpuStats> {
+    let fd = open(SCHED, OpenFlags::RDONLY);
+    if fd < 0 {
+        return None;
+    }
+    let mut buf = [0u8; 256];
+    let mut len = 0;
+    while len < buf.len() {
+        match read(fd as usize, &mut buf[len..]) {
+            n if n > 0 => len += n as usize,
+            _ => break,
+        }
+    }
+    close(fd as usize);
+    let text = core::str::from_utf8(&buf[..len]).ok()?;
+    Some(FpuStats {
+        saves: metric(text, SAVES_KEY)?,
+        restores: metric(text, RESTORES_KEY)?,
+    })
+}
+
+/// 纯整数任务：与浮点任务轮流运行，自己的浮点上下文不应被保存或装载过一次
+fn int_worker(id: usize) -> ! {
+    let mut acc = id;
+    for i in 0..LOOP_NUM {
+        acc = black_box(acc.wrapping_mul(31).wrapping_add(i));
+        yield_();
+    }
+    match fpu_stats() {
+        Some(stats) if stats.saves == 0 && stats.restores == 0 => exit(0),
+        Some(stats) => {
+            println!("[lazy_fpu] integer task {}: {:?}", id, stats);
+            exit(EXIT_FP_SWITCHED)
+        }
+        None => exit(EXIT_NO_STATS),
+    }
+}
+
+/// 浮点任务：在频繁让出 CPU 的同时累加，检查浮点状态没有被其他任务破坏，
+/// 且每次改写过浮点寄存器后的陷入都保存了它们
+fn fp_worker() -> ! {
+    let step = black_box(0.5f64);
+    let mut acc = black_box(1.0f64);
+    for _ in 0..LOOP_NUM {
+        acc += step;
+        yield_();
+    }
+    let expected = 1.0 + 0.5 * LOOP_NUM as f64;
+    if acc != expected {
+        exit(1);
+    }
+    match fpu_stats() {
+        Some(stats) => {
+            println!(
+                "[lazy_fpu] fp task: {} saves, {} restores for {} switches",
+                stats.saves, stats.restores, LOOP_NUM
+            );
+            if stats.saves < LOOP_NUM || stats.restores == 0 {
+                exit(EXIT_FP_SWITCHED);
+            }
+            exit(0)
+        }
+        None => exit(EXIT_NO_STATS),
+    }
+}
+
+/// 等待浮点任务退出，返回其状态字；超时则杀死它并返回 `None`。
+/// 首次浮点指令装载寄存器后若仍陷入，任务会卡在这条指令上，永远跑不完循环
+fn wait_fp_worker(pid: usize) -> Option<i32> {
+    let deadline = get_time() + FP_TIMEOUT_MS;
+    let mut status: i32 = 0;
+    loop {
+        match waitpid_options(pid as isize, &mut status, WNOHANG) {
+            0 if get_time() < deadline => {
+                yield_();
+            }
+            0 => {
+                kill(pid, SIGKILL);
+                waitpid(pid, &mut status);
+                return None;
+            }
+            _ => return Some(status),
+        }
+    }
+}
+
+/// 每次让出 CPU 时，浮点任务在上一次陷入之后改写过浮点寄存器，整数任务从不使用浮点单元。
+/// 惰性保存/装载时只有浮点任务自己的浮点上下文被搬运；若每次上下文切换都保存/装载，
+/// 整数任务的计数也会随切换次数增长。计数按任务统计，系统中其他浮点任务不影响结果
+#[no_mangle]
+pub fn main() -> i32 {
+    println!("[lazy_fpu] 1 fp task, {} integer tasks", INT_PROCESS_NUM);
+    // 只有 RISC-V 惰性切换浮点上下文并统计这两项
+    let counted = cfg!(target_arch = "riscv64");
+
+    let mut pids = [0isize; INT_PROCESS_NUM + 1];
+    for i in 0..INT_PROCESS_NUM {
+        let pid = fork();
+        if pid == 0 {
+            int_worker(i);
+        }
+        pids[i] = pid;
+    }
+    let pid = fork();
+    if pid == 0 {
+        fp_worker();
+    }
+    pids[INT_PROCESS_NUM] = pid;
+
+    let mut failed = false;
+    for (i, pid) in pids.iter().enumerate() {
+        let mut status: i32 = 0;
+        if i == INT_PROCESS_NUM {
+            match wait_fp_worker(*pid as usize) {
+                Some(fp_status) => status = fp_status,
+                None => {
+                    println!("[lazy_fpu] fp task did not finish its loop");
+                    failed = true;
+                    continue;
+                }
+            }
+        } else {
+            waitpid(*pid as usize, &mut status);
+        }
+        let exit_code = (status >> 8) & 0xff;
+        match exit_code {
+            0 => {}
+            EXIT_FP_SWITCHED if !counted => {}
+            EXIT_FP_SWITCHED => {
+                println!("[lazy_fpu] task {}: fp context switched eagerly", i);
+                failed = true;
+            }
+            EXIT_NO_STATS => {
+                println!("[lazy_fpu] task {}: {} not readable", i, SCHED.trim_end_matches('\0'));
+                failed = true;
+            }
+            _ => {
+                println!("[lazy_fpu] task {} failed with {}", i, exit_code);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        println!("[lazy_fpu] FAILED");
+        -1
+    } else {
+        println!("[lazy_fpu] passed");
+        0
+    }
+}
@@ -0,0 +1,235 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+/// 在普通文件中写入一个 FAT32 镜像，关联到 `/dev/loop0` 后挂载，
+/// 应能从挂载点读出镜像中的文件；卸载后挂载点下不再有该文件。
+/// 再以只读方式关联同一镜像并以 MS_RDONLY 挂载，读文件成功而修改返回 EROFS
+#[cfg(target_arch = "riscv64")]
+mod workload {
+    use user_lib::{
+        close, ioctl, mkdir, mount, open, read, report, rmdir, umount, unlink, write, OpenFlags,
+        LOOP_CLR_FD, LOOP_SET_FD, MS_RDONLY,
+    };
+
+    const ENXIO: isize = -6;
+    const EACCES: isize = -13;
+    const EBUSY: isize = -16;
+    const EROFS: isize = -30;
+    const IMAGE: &str = "/loop_device.img\0";
+    const MOUNT_POINT: &str = "/loop_device_mnt\0";
+    const FILE: &str = "/loop_device_mnt/HELLO.TXT\0";
+    const NEW_FILE: &str = "/loop_device_mnt/NEW.TXT\0";
+    const NEW_DIR: &str = "/loop_device_mnt/NEWDIR\0";
+    const DATA: &[u8] = b"read from a FAT32 image through /dev/loop0";
+
+    /// 镜像的布局：512 字节的扇区，每簇一个扇区，32 个保留扇区，两份各 16 个扇区的 FAT，
+    /// 根目录在 2 号簇（64 号扇区），文件在 3 号簇（65 号扇区）
+    const SECTOR_SIZE: usize = 512;
+    const TOTAL_SECTORS: usize = 2048;
+    const RESERVED_SECTORS: usize = 32;
+    const FAT_SECTORS: usize = 16;
+    const FIRST_FAT: usize = RESERVED_SECTORS;
+    const SECOND_FAT: usize = RESERVED_SECTORS + FAT_SECTORS;
+    const ROOT_DIR_SECTOR: usize = RESERVED_SECTORS + 2 * FAT_SECTORS;
+    const FILE_SECTOR: usize = ROOT_DIR_SECTOR + 1;
+
+    fn put(buf: &mut [u8], offset: usize, bytes: &[u8]) {
+        buf[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// 镜像中第 `index` 个扇区的内容
+    fn sector(index: usize, buf: &mut [u8]) {
+        buf.fill(0);
+        match index {
+            0 => {
+                put(buf, 0, &[0xEB, 0x58, 0x90]);
+                put(buf, 3, b"MSWIN4.1");
+                put(buf, 11, &(SECTOR_SIZE as u16).to_le_bytes());
+                buf[13] = 1;
+                put(buf, 14, &(RESERVED_SECTORS as u16).to_le_bytes());
+                buf[16] = 2;
+                buf[21] = 0xF8;
+                put(buf, 32, &(TOTAL_SECTORS as u32).to_le_bytes());
+                put(buf, 36, &(FAT_SECTORS as u32).to_le_bytes());
+                put(buf, 44, &2u32.to_le_bytes());
+                put(buf, 48, &1u16.to_le_bytes());
+                put(buf, 50, &6u16.to_le_bytes());
+                buf[66] = 0x29;
+                put(buf, 71, b"LOOPDEVICE ");
+                put(buf, 82, b"FAT32   ");
+                put(buf, 510, &[0x55, 0xAA]);
+            }
+            // 0 与 1 号表项保留，2 号簇（根目录）与 3 号簇（文件）各自是一条簇链的结尾
+            FIRST_FAT | SECOND_FAT => {
+                put(buf, 0, &0x0FFFFFF8u32.to_le_bytes());
+                for entry in 1..4 {
+                    put(buf, entry * 4, &0x0FFFFFFFu32.to_le_bytes());
+                }
+            }
+            ROOT_DIR_SECTOR => {
+                put(buf, 0, b"HELLO   TXT");
+                buf[11] = 0x20;
+                put(buf, 26, &3u16.to_le_bytes());
+                put(buf, 28, &(DATA.len() as u32).to_le_bytes());
+            }
+            FILE_SECTOR => put(buf, 0, DATA),
+            _ => {}
+        }
+    }
+
+    fn create_image() -> Result<(), &'static str> {
+        let fd = open(IMAGE, OpenFlags::CREATE | OpenFlags::WRONLY);
+        if fd < 0 {
+            return Err("cannot create the image file");
+        }
+        let mut buf = [0u8; SECTOR_SIZE];
+        let mut written = true;
+        for index in 0..TOTAL_SECTORS {
+            sector(index, &mut buf);
+            written &= write(fd as usize, &buf) == SECTOR_SIZE as isize;
+        }
+        close(fd as usize);
+        if written {
+            Ok(())
+        } else {
+            Err("cannot write the image file")
+        }
+    }
+
+    /// 读出 `path` 的内容与 `DATA` 比较
+    fn has_data(path: &str) -> bool {
+        let fd = open(path, OpenFlags::RDONLY);
+        if fd < 0 {
+            return false;
+        }
+        let mut buf = [0u8; 128];
+        let len = read(fd as usize, &mut buf);
+        close(fd as usize);
+        len >= 0 && &buf[..len as usize] == DATA
+    }
+
+    /// `/proc/mounts` 中是否有 `line`
+    fn in_proc_mounts(line: &str) -> bool {
+        let fd = open("/proc/mounts\0", OpenFlags::RDONLY);
+        if fd < 0 {
+            return false;
+        }
+        let mut buf = [0u8; 2048];
+        let len = read(fd as usize, &mut buf);
+        close(fd as usize);
+        len > 0
+            && core::str::from_utf8(&buf[..len as usize])
+                .map_or(false, |mounts| mounts.contains(line))
+    }
+
+    fn check(loop_fd: usize, image_fd: usize) -> Result<(), &'static str> {
+        if mount("/dev/loop0\0", MOUNT_POINT, "vfat\0", 0) != ENXIO {
+            return Err("mounting a loop device without a backing file was not refused with ENXIO");
+        }
+        if ioctl(loop_fd, LOOP_SET_FD, image_fd) != 0 {
+            return Err("LOOP_SET_FD failed");
+        }
+        if ioctl(loop_fd, LOOP_SET_FD, image_fd) != EBUSY {
+            return Err("a second LOOP_SET_FD was not refused with EBUSY");
+        }
+        if mount("/dev/loop0\0", MOUNT_POINT, "vfat\0", 0) != 0 {
+            ioctl(loop_fd, LOOP_CLR_FD, 0);
+            return Err("mounting the loop device failed");
+        }
+        let mounted = if !has_data(FILE) {
+            Err("the file in the image cannot be read from the mount point")
+        } else if !in_proc_mounts("/dev/loop0 /loop_device_mnt vfat rw") {
+            Err("/proc/mounts does not list the loop mount")
+        } else {
+            Ok(())
+        };
+        let unmounted = umount(MOUNT_POINT) == 0 && !has_data(FILE);
+        let cleared = ioctl(loop_fd, LOOP_CLR_FD, 0) == 0;
+        mounted?;
+        if !unmounted {
+            return Err("the file is still visible after umount");
+        }
+        if !cleared {
+            return Err("LOOP_CLR_FD failed");
+        }
+        Ok(())
+    }
+
+    /// 在只读挂载中读文件成功，创建、以写方式打开与删除文件都返回 EROFS
+    fn check_read_only_mount() -> Result<(), &'static str> {
+        if !has_data(FILE) {
+            return Err("the file cannot be read from the read-only mount");
+        }
+        if !in_proc_mounts("/dev/loop0 /loop_device_mnt vfat ro") {
+            return Err("/proc/mounts does not list the mount as read-only");
+        }
+        if open(NEW_FILE, OpenFlags::CREATE | OpenFlags::WRONLY) != EROFS {
+            return Err("creating a file was not refused with EROFS");
+        }
+        if open(FILE, OpenFlags::WRONLY) != EROFS {
+            return Err("opening a file for writing was not refused with EROFS");
+        }
+        if mkdir(NEW_DIR) != EROFS || unlink(FILE) != EROFS {
+            return Err("mkdir or unlink was not refused with EROFS");
+        }
+        Ok(())
+    }
+
+    /// 以只读方式打开的镜像使回环设备只读，只能以 MS_RDONLY 挂载
+    fn check_read_only(loop_fd: usize, image_fd: usize) -> Result<(), &'static str> {
+        if ioctl(loop_fd, LOOP_SET_FD, image_fd) != 0 {
+            return Err("LOOP_SET_FD of the image opened read-only failed");
+        }
+        let result = if mount("/dev/loop0\0", MOUNT_POINT, "vfat\0", 0) != EACCES {
+            Err("a read-write mount of a read-only device was not refused with EACCES")
+        } else if mount("/dev/loop0\0", MOUNT_POINT, "vfat\0", MS_RDONLY) != 0 {
+            Err("mounting the read-only device with MS_RDONLY failed")
+        } else {
+            let result = check_read_only_mount();
+            umount(MOUNT_POINT);
+            result
+        };
+        ioctl(loop_fd, LOOP_CLR_FD, 0);
+        result
+    }
+
+    pub fn main() -> i32 {
+        let result = create_image().and_then(|()| {
+            if mkdir(MOUNT_POINT) < 0 {
+                return Err("cannot create the mount point");
+            }
+            let image_fd = open(IMAGE, OpenFlags::RDWR);
+            let read_only_fd = open(IMAGE, OpenFlags::RDONLY);
+            let loop_fd = open("/dev/loop0\0", OpenFlags::RDWR);
+            let result = if image_fd < 0 || read_only_fd < 0 || loop_fd < 0 {
+                Err("cannot open the image or /dev/loop0")
+            } else {
+                check(loop_fd as usize, image_fd as usize)
+                    .and_then(|()| check_read_only(loop_fd as usize, read_only_fd as usize))
+            };
+            for fd in [image_fd, read_only_fd, loop_fd] {
+                if fd >= 0 {
+                    close(fd as usize);
+                }
+            }
+            result
+        });
+        rmdir(MOUNT_POINT);
+        unlink(IMAGE);
+        report("loop_device", result)
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    #[cfg(target_arch = "riscv64")]
+    return workload::main();
+    #[cfg(not(target_arch = "riscv64"))]
+    {
+        println!("[loop_device] skipped: the loongarch64 syscall stub passes only three arguments");
+        0
+    }
+}
@@ -0,0 +1,71 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    exit, fork, mincore, mmap, waitpid, MAP_ANONYMOUS, MAP_GROWSDOWN, MAP_PRIVATE, PROT_READ,
+    PROT_WRITE,
+};
+
+const PAGE_SIZE: usize = 4096;
+/// 在区域下方写入的页数，小于各架构的栈大小限制
+const GROW_PAGES: usize = 4;
+
+fn map_anonymous(flags: usize) -> isize {
+    mmap(
+        0,
+        PAGE_SIZE,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANONYMOUS | flags,
+        usize::MAX,
+        0,
+    )
+}
+
+/// 在一个普通映射之上建立一页的 MAP_GROWSDOWN 区域，
+/// 向其下方写入应使区域向下扩展，而逼近下方映射的写入应收到 SIGSEGV
+#[no_mangle]
+pub fn main() -> i32 {
+    let floor = map_anonymous(0);
+    let stack = map_anonymous(MAP_GROWSDOWN);
+    if floor < 0 || stack < 0 {
+        println!(
+            "[map_growsdown] FAILED: mmap returned {} and {}",
+            floor, stack
+        );
+        return -1;
+    }
+    let (floor, stack) = (floor as usize, stack as usize);
+    let bottom = stack - GROW_PAGES * PAGE_SIZE;
+    for page in 0..GROW_PAGES {
+        unsafe { *((bottom + page * PAGE_SIZE) as *mut usize) = page + 1 };
+    }
+    let mut vec = [0u8; GROW_PAGES + 1];
+    let ret = mincore(bottom, (GROW_PAGES + 1) * PAGE_SIZE, &mut vec);
+    let intact = (0..GROW_PAGES)
+        .all(|page| unsafe { *((bottom + page * PAGE_SIZE) as *const usize) } == page + 1);
+    println!(
+        "[map_growsdown] area at {:#x} grown down to {:#x}, mincore returned {}",
+        stack, bottom, ret
+    );
+    if ret != 0 || vec[..GROW_PAGES].iter().any(|&resident| resident & 1 == 0) || !intact {
+        println!("[map_growsdown] FAILED: area did not grow over the written pages");
+        return -1;
+    }
+    // 紧贴下方映射的页是保护页，区域不能长到那里
+    let pid = fork();
+    if pid == 0 {
+        unsafe { *((floor + PAGE_SIZE) as *mut usize) = 1 };
+        println!("[map_growsdown] FAILED: area grew into the guard page");
+        exit(0);
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    if exit_code == 0 {
+        println!("[map_growsdown] FAILED: write above the guard page did not fault");
+        return -1;
+    }
+    println!("[map_growsdown] passed");
+    0
+}
@@ -0,0 +1,75 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    mincore, mmap, munmap, MAP_ANONYMOUS, MAP_POPULATE, MAP_PRIVATE, PROT_READ, PROT_WRITE,
+};
+
+const PAGE_SIZE: usize = 4096;
+const PAGES: usize = 16;
+
+/// 映射 PAGES 页匿名内存，返回起始地址与 mincore 报告的驻留页数
+fn map_and_count(flags: usize) -> Result<(usize, usize), isize> {
+    let len = PAGES * PAGE_SIZE;
+    let addr = mmap(
+        0,
+        len,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANONYMOUS | flags,
+        usize::MAX,
+        0,
+    );
+    if addr < 0 {
+        return Err(addr);
+    }
+    let mut vec = [0u8; PAGES];
+    let ret = mincore(addr as usize, len, &mut vec);
+    if ret != 0 {
+        return Err(ret);
+    }
+    Ok((
+        addr as usize,
+        vec.iter().filter(|&&resident| resident & 1 != 0).count(),
+    ))
+}
+
+/// 不带 MAP_POPULATE 的映射按需分配，一页都不驻留；
+/// 带 MAP_POPULATE 的映射在 mmap 返回时就全部驻留，且内容为零
+#[no_mangle]
+pub fn main() -> i32 {
+    let (lazy, lazy_resident) = match map_and_count(0) {
+        Ok(result) => result,
+        Err(err) => {
+            println!("[map_populate] FAILED: lazy mapping, error {}", err);
+            return -1;
+        }
+    };
+    let (populated, resident) = match map_and_count(MAP_POPULATE) {
+        Ok(result) => result,
+        Err(err) => {
+            println!("[map_populate] FAILED: MAP_POPULATE mapping, error {}", err);
+            return -1;
+        }
+    };
+    println!(
+        "[map_populate] resident pages: {}/{} lazy, {}/{} populated",
+        lazy_resident, PAGES, resident, PAGES
+    );
+    let zeroed = unsafe { core::slice::from_raw_parts(populated as *const u8, PAGES * PAGE_SIZE) }
+        .iter()
+        .all(|&b| b == 0);
+    munmap(lazy, PAGES * PAGE_SIZE);
+    munmap(populated, PAGES * PAGE_SIZE);
+    if lazy_resident != 0 {
+        println!("[map_populate] FAILED: pages resident before first access");
+        return -1;
+    }
+    if resident != PAGES || !zeroed {
+        println!("[map_populate] FAILED: MAP_POPULATE mapping not fully resident and zeroed");
+        return -1;
+    }
+    println!("[map_populate] passed");
+    0
+}
@@ -0,0 +1,107 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    close, exit, fork, lseek, mmap, msync, munmap, open, read, report, unlink, waitpid, write,
+    OpenFlags, MAP_SHARED, MS_SYNC, PROT_READ, PROT_WRITE, SEEK_SET,
+};
+
+const PAGE_SIZE: usize = 4096;
+const FILE: &str = "/map_shared\0";
+const MAP_PAGES: usize = 2;
+/// 父进程通过映射写入的位置
+const PARENT_AT: usize = 10;
+/// fork 出的子进程通过映射写入的位置
+const CHILD_AT: usize = PAGE_SIZE + 5;
+
+fn byte_in_file(fd: usize, at: usize) -> Result<u8, &'static str> {
+    let mut buf = [0u8; 1];
+    if lseek(fd, at as isize, SEEK_SET) != at as isize || read(fd, &mut buf) != 1 {
+        return Err("cannot read the test file");
+    }
+    Ok(buf[0])
+}
+
+/// 通过共享映射写入的内容 read() 立即可见，fork 出的子进程写入的内容父进程也可见，
+/// msync 与 munmap 之后文件中仍是写入的内容
+fn check(fd: usize) -> Result<(), &'static str> {
+    if write(fd, &[b'a'; MAP_PAGES * PAGE_SIZE]) != (MAP_PAGES * PAGE_SIZE) as isize {
+        return Err("cannot write the test file");
+    }
+    let area = mmap(
+        0,
+        MAP_PAGES * PAGE_SIZE,
+        PROT_READ | PROT_WRITE,
+        MAP_SHARED,
+        fd,
+        0,
+    );
+    if area < 0 {
+        return Err("mmap failed");
+    }
+    let area = area as usize;
+    unsafe { *((area + PARENT_AT) as *mut u8) = b'P' };
+    if byte_in_file(fd, PARENT_AT)? != b'P' {
+        munmap(area, MAP_PAGES * PAGE_SIZE);
+        return Err("a write through the mapping is not seen by read()");
+    }
+    let pid = fork();
+    if pid == 0 {
+        unsafe { *((area + CHILD_AT) as *mut u8) = b'C' };
+        exit(0);
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    if unsafe { *((area + CHILD_AT) as *const u8) } != b'C' {
+        munmap(area, MAP_PAGES * PAGE_SIZE);
+        return Err("the parent does not see the child's write");
+    }
+    if msync(area, MAP_PAGES * PAGE_SIZE, MS_SYNC) != 0 {
+        munmap(area, MAP_PAGES * PAGE_SIZE);
+        return Err("msync failed");
+    }
+    if munmap(area, MAP_PAGES * PAGE_SIZE) != 0 {
+        return Err("munmap failed");
+    }
+    if byte_in_file(fd, PARENT_AT)? != b'P' || byte_in_file(fd, CHILD_AT)? != b'C' {
+        return Err("the file lost the writes after munmap");
+    }
+    Ok(())
+}
+
+/// 只读打开的文件不能建立可写的共享映射
+fn check_read_only() -> Result<(), &'static str> {
+    let fd = open(FILE, OpenFlags::RDONLY);
+    if fd < 0 {
+        return Err("cannot reopen the test file");
+    }
+    let area = mmap(
+        0,
+        PAGE_SIZE,
+        PROT_READ | PROT_WRITE,
+        MAP_SHARED,
+        fd as usize,
+        0,
+    );
+    close(fd as usize);
+    if area >= 0 {
+        munmap(area as usize, PAGE_SIZE);
+        return Err("a writable shared mapping of a read-only file was allowed");
+    }
+    Ok(())
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open(FILE, OpenFlags::CREATE | OpenFlags::RDWR);
+    if fd < 0 {
+        println!("[map_shared] FAILED: cannot create the test file");
+        return -1;
+    }
+    let result = check(fd as usize).and_then(|()| check_read_only());
+    close(fd as usize);
+    unlink(FILE);
+    report("map_shared", result)
+}
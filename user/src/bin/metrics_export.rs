@@ -0,0 +1,103 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, get_time, open, read, sleep, write, OpenFlags};
+
+const INTERVAL: &str = "/proc/sys/kernel/metrics_export_interval\0";
+const PATH: &str = "/proc/sys/kernel/metrics_export_path\0";
+const EXPORT_FILE: &str = "/tmp/metrics_export\0";
+const EXPORTS_KEY: &str = "kernel_metrics_exports_total: ";
+/// 等待导出线程写入的最长时间，导出间隔设为 1 秒
+const TIMEOUT_MS: isize = 5000;
+
+/// 读出整个文件，返回读到的长度
+fn read_file(path: &str, buf: &mut [u8]) -> Option<usize> {
+    let fd = open(path, OpenFlags::RDONLY);
+    if fd < 0 {
+        return None;
+    }
+    let mut len = 0;
+    while len < buf.len() {
+        match read(fd as usize, &mut buf[len..]) {
+            n if n > 0 => len += n as usize,
+            _ => break,
+        }
+    }
+    close(fd as usize);
+    Some(len)
+}
+
+fn write_file(path: &str, value: &[u8]) -> bool {
+    let fd = open(path, OpenFlags::WRONLY);
+    if fd < 0 {
+        return false;
+    }
+    let ret = write(fd as usize, value);
+    close(fd as usize);
+    ret == value.len() as isize
+}
+
+/// 导出文件中记录的此前导出次数
+fn exports_in_snapshot() -> Option<usize> {
+    let mut buf = [0u8; 8192];
+    let len = read_file(EXPORT_FILE, &mut buf)?;
+    let text = core::str::from_utf8(&buf[..len]).ok()?;
+    let start = text.find(EXPORTS_KEY)? + EXPORTS_KEY.len();
+    let digits = text[start..].split('\n').next()?;
+    digits.trim().parse().ok()
+}
+
+/// 等待导出文件中的导出次数大于 `after`
+fn wait_for_snapshot(after: Option<usize>) -> Option<usize> {
+    let start = get_time();
+    while get_time() - start < TIMEOUT_MS {
+        if let Some(exports) = exports_in_snapshot() {
+            if after.map_or(true, |after| exports > after) {
+                return Some(exports);
+            }
+        }
+        sleep(100);
+    }
+    None
+}
+
+/// 把导出路径指向 /tmp、间隔设为 1 秒，导出文件应当出现并随时间更新
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut old_interval = [0u8; 32];
+    let mut old_path = [0u8; 256];
+    let (interval_len, path_len) = match (
+        read_file(INTERVAL, &mut old_interval),
+        read_file(PATH, &mut old_path),
+    ) {
+        (Some(interval_len), Some(path_len)) => (interval_len, path_len),
+        _ => {
+            println!("[metrics_export] FAILED: /proc/sys/kernel tunables missing");
+            return -1;
+        }
+    };
+    if !write_file(PATH, b"/tmp/metrics_export\n") || !write_file(INTERVAL, b"1\n") {
+        println!("[metrics_export] FAILED: tunables not writable");
+        return -1;
+    }
+    let first = wait_for_snapshot(None);
+    let second = first.and_then(|first| wait_for_snapshot(Some(first)));
+    write_file(INTERVAL, &old_interval[..interval_len]);
+    write_file(PATH, &old_path[..path_len]);
+    println!(
+        "[metrics_export] snapshots with export counts {:?} then {:?}",
+        first, second
+    );
+    if first.is_none() {
+        println!("[metrics_export] FAILED: export file not written");
+        return -1;
+    }
+    if second.is_none() {
+        println!("[metrics_export] FAILED: export file not updated");
+        return -1;
+    }
+    println!("[metrics_export] passed");
+    0
+}
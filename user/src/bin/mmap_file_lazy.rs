@@ -0,0 +1,85 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    close, lseek, mincore, mmap, munmap, open, report, unlink, write, OpenFlags, MAP_PRIVATE,
+    PROT_READ, SEEK_CUR,
+};
+
+const PAGE_SIZE: usize = 4096;
+const FILE: &str = "/mmap_file_lazy\0";
+/// 文件的页数，第 i 页的内容都是 b'a' + i
+const FILE_PAGES: usize = 4;
+/// 从文件第 1 页起映射 3 页
+const MAP_OFFSET: usize = PAGE_SIZE;
+const MAP_PAGES: usize = 3;
+
+fn page_byte(page: usize) -> u8 {
+    b'a' + page as u8
+}
+
+fn resident(start: usize, pages: usize) -> Result<[u8; MAP_PAGES], &'static str> {
+    let mut vec = [0u8; MAP_PAGES];
+    if mincore(start, pages * PAGE_SIZE, &mut vec[..pages]) != 0 {
+        return Err("mincore failed");
+    }
+    Ok(vec)
+}
+
+/// 映射时不读入任何页，访问哪页才读入哪页；
+/// 读入的是映射偏移处的内容，去掉映射开头的页后其余页的偏移不变
+fn check(fd: usize) -> Result<(), &'static str> {
+    for page in 0..FILE_PAGES {
+        if write(fd, &[page_byte(page); PAGE_SIZE]) != PAGE_SIZE as isize {
+            return Err("cannot write the test file");
+        }
+    }
+    let area = mmap(
+        0,
+        MAP_PAGES * PAGE_SIZE,
+        PROT_READ,
+        MAP_PRIVATE,
+        fd,
+        MAP_OFFSET,
+    );
+    if area < 0 {
+        return Err("mmap failed");
+    }
+    let area = area as usize;
+    if resident(area, MAP_PAGES)?.iter().any(|&page| page & 1 != 0) {
+        munmap(area, MAP_PAGES * PAGE_SIZE);
+        return Err("pages were read in at map time");
+    }
+    let last = unsafe { *((area + 2 * PAGE_SIZE + 100) as *const u8) };
+    let after_fault = resident(area, MAP_PAGES)?;
+    if last != page_byte(3) || after_fault != [0, 0, 1] {
+        munmap(area, MAP_PAGES * PAGE_SIZE);
+        return Err("a fault did not read in exactly the touched page at the mapped offset");
+    }
+    if lseek(fd, 0, SEEK_CUR) != (FILE_PAGES * PAGE_SIZE) as isize {
+        munmap(area, MAP_PAGES * PAGE_SIZE);
+        return Err("a fault moved the file offset");
+    }
+    munmap(area, PAGE_SIZE);
+    let middle = unsafe { *((area + PAGE_SIZE) as *const u8) };
+    munmap(area + PAGE_SIZE, (MAP_PAGES - 1) * PAGE_SIZE);
+    if middle != page_byte(2) {
+        return Err("after unmapping the first page the rest read the wrong offset");
+    }
+    Ok(())
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open(FILE, OpenFlags::CREATE | OpenFlags::RDWR);
+    if fd < 0 {
+        println!("[mmap_file_lazy] FAILED: cannot create the test file");
+        return -1;
+    }
+    let result = check(fd as usize);
+    close(fd as usize);
+    unlink(FILE);
+    report("mmap_file_lazy", result)
+}
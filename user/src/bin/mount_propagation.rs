@@ -0,0 +1,97 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+/// `/prop_a` 绑定到自身后设为共享，再绑定到 `/prop_b`（对等）与 `/prop_c`（设为私有）：
+/// 挂载在 `/prop_a/sub` 上的 tmpfs 应出现在 `/prop_b/sub`，不出现在 `/prop_c/sub`，
+/// 卸载时一并从 `/prop_b/sub` 卸载
+#[cfg(target_arch = "riscv64")]
+mod workload {
+    use user_lib::{
+        close, mkdir, mount, open, read, report, rmdir, umount, OpenFlags, MS_BIND, MS_PRIVATE,
+        MS_SHARED,
+    };
+
+    const A: &str = "/prop_a\0";
+    const SUB: &str = "/prop_a/sub\0";
+    const B: &str = "/prop_b\0";
+    const C: &str = "/prop_c\0";
+
+    /// `/proc/mounts` 中是否有挂载在 `mount_point` 上的 tmpfs
+    fn tmpfs_at(mount_point: &str) -> bool {
+        let fd = open("/proc/mounts\0", OpenFlags::RDONLY);
+        if fd < 0 {
+            return false;
+        }
+        let mut buf = [0u8; 2048];
+        let len = read(fd as usize, &mut buf);
+        close(fd as usize);
+        len > 0
+            && core::str::from_utf8(&buf[..len as usize]).map_or(false, |mounts| {
+                mounts.lines().any(|line| {
+                    let mut fields = line.split(' ');
+                    fields.nth(1) == Some(mount_point) && fields.next() == Some("tmpfs")
+                })
+            })
+    }
+
+    fn check() -> Result<(), &'static str> {
+        if mount(A, A, "\0", MS_BIND) != 0 || mount("none\0", A, "\0", MS_SHARED) != 0 {
+            return Err("cannot make /prop_a a shared mount");
+        }
+        if mount(A, B, "\0", MS_BIND) != 0 {
+            return Err("cannot bind /prop_a onto /prop_b");
+        }
+        if mount(A, C, "\0", MS_BIND) != 0 || mount("none\0", C, "\0", MS_PRIVATE) != 0 {
+            return Err("cannot bind /prop_a onto /prop_c as a private mount");
+        }
+        if mount("tmpfs\0", SUB, "tmpfs\0", 0) != 0 {
+            return Err("cannot mount tmpfs on /prop_a/sub");
+        }
+        if !tmpfs_at("/prop_a/sub") || !tmpfs_at("/prop_b/sub") {
+            return Err("the mount did not propagate to the shared peer");
+        }
+        if tmpfs_at("/prop_c/sub") {
+            return Err("the mount propagated to the private mount");
+        }
+        if umount(SUB) != 0 {
+            return Err("cannot unmount /prop_a/sub");
+        }
+        if tmpfs_at("/prop_b/sub") {
+            return Err("the unmount did not propagate to the shared peer");
+        }
+        Ok(())
+    }
+
+    pub fn main() -> i32 {
+        for dir in [A, SUB, B, C] {
+            if mkdir(dir) < 0 {
+                println!("[mount_propagation] FAILED: cannot create the directories");
+                return -1;
+            }
+        }
+        let result = check();
+        for dir in [C, B, A] {
+            umount(dir);
+        }
+        for dir in [C, B, SUB, A] {
+            rmdir(dir);
+        }
+        report("mount_propagation", result)
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    #[cfg(target_arch = "riscv64")]
+    return workload::main();
+    #[cfg(not(target_arch = "riscv64"))]
+    {
+        println!(
+            "[mount_propagation] skipped: the loongarch64 syscall stub passes only three arguments"
+        );
+        0
+    }
+}
@@ -0,0 +1,96 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+/// RESOLVE_BENEATH 下以目录为起点的路径不能越出该目录，
+/// 越出时 openat2 返回 EXDEV；open_how 多出的非零字段返回 E2BIG
+#[cfg(target_arch = "riscv64")]
+mod workload {
+    use user_lib::{
+        close, mkdir, open, openat2, report, rmdir, unlink, OpenFlags, OpenHow, RESOLVE_BENEATH,
+    };
+
+    const E2BIG: isize = -7;
+    const EXDEV: isize = -18;
+    const DIR: &str = "openat2_dir\0";
+    const INNER: &str = "openat2_dir/inner\0";
+    const CREATE: u64 = 0o100;
+
+    /// 新版本的 open_how，末尾多出一个字段
+    #[repr(C)]
+    struct OpenHowNext {
+        how: OpenHow,
+        extra: u64,
+    }
+
+    /// 在 `dirfd` 下逐项检查，返回第一个不符合预期的情形
+    fn check(dirfd: isize) -> Result<(), &'static str> {
+        let beneath = OpenHow {
+            resolve: RESOLVE_BENEATH,
+            ..OpenHow::default()
+        };
+        if openat2(dirfd, "../escape\0", &beneath) != EXDEV {
+            return Err("../escape was not refused with EXDEV");
+        }
+        if openat2(dirfd, "inner/../../escape\0", &beneath) != EXDEV {
+            return Err("inner/../../escape was not refused with EXDEV");
+        }
+        if openat2(dirfd, "/escape\0", &beneath) != EXDEV {
+            return Err("absolute path was not refused with EXDEV");
+        }
+        let create = OpenHow {
+            flags: CREATE,
+            mode: 0o644,
+            resolve: RESOLVE_BENEATH,
+        };
+        let fd = openat2(dirfd, "inner\0", &create);
+        if fd < 0 {
+            return Err("creating a file beneath the directory failed");
+        }
+        close(fd as usize);
+        let fd = openat2(dirfd, "inner/../inner\0", &beneath);
+        if fd < 0 {
+            return Err("path staying beneath the directory was refused");
+        }
+        close(fd as usize);
+        let next = OpenHowNext {
+            how: OpenHow::default(),
+            extra: 1,
+        };
+        if openat2(dirfd, "inner\0", &next) != E2BIG {
+            return Err("unknown non-zero open_how field was not refused with E2BIG");
+        }
+        Ok(())
+    }
+
+    pub fn main() -> i32 {
+        if mkdir(DIR) < 0 {
+            println!("[openat2] FAILED: cannot create the directory");
+            return -1;
+        }
+        let dirfd = open(DIR, OpenFlags::RDONLY);
+        let result = if dirfd < 0 {
+            Err("cannot open the directory")
+        } else {
+            let result = check(dirfd);
+            close(dirfd as usize);
+            result
+        };
+        unlink(INNER);
+        rmdir(DIR);
+        report("openat2", result)
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    #[cfg(target_arch = "riscv64")]
+    return workload::main();
+    #[cfg(not(target_arch = "riscv64"))]
+    {
+        println!("[openat2] skipped: the loongarch64 syscall stub passes only three arguments");
+        0
+    }
+}
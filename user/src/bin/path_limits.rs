@@ -0,0 +1,87 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::string::String;
+use user_lib::{close, mkdir, open, report, write, OpenFlags};
+
+const ENAMETOOLONG: isize = -36;
+const PATH_MAX_SYSCTL: &str = "/proc/sys/fs/path_max\0";
+
+/// 以 "/" 开头、由 "./" 重复组成、总长 `len` 字节的路径，解析后就是根目录
+fn dot_path(len: usize) -> String {
+    let mut path = String::from("/");
+    while path.len() + 2 <= len {
+        path.push_str("./");
+    }
+    while path.len() < len {
+        path.push('/');
+    }
+    path.push('\0');
+    path
+}
+
+/// 写入路径长度上限
+fn set_path_max(value: &str) -> bool {
+    let fd = open(PATH_MAX_SYSCTL, OpenFlags::WRONLY);
+    if fd < 0 {
+        return false;
+    }
+    let ret = write(fd as usize, value.as_bytes());
+    close(fd as usize);
+    ret == value.len() as isize
+}
+
+/// 打开 `path`，成功时关闭它，返回 open 的返回值
+fn try_open(path: &str) -> isize {
+    let fd = open(path, OpenFlags::RDONLY);
+    if fd >= 0 {
+        close(fd as usize);
+    }
+    fd
+}
+
+fn check() -> Result<(), &'static str> {
+    // 4096 字节以下的路径可以解析，达到 PATH_MAX 的返回 ENAMETOOLONG
+    if try_open(&dot_path(4000)) < 0 {
+        return Err("a 4000-byte path was rejected");
+    }
+    if try_open(&dot_path(5000)) != ENAMETOOLONG {
+        return Err("a 5000-byte path did not fail with ENAMETOOLONG");
+    }
+    // 长于 NAME_MAX 的一级名字不能创建，也不能查找
+    let mut long_name = String::from("/");
+    long_name.extend(core::iter::repeat('n').take(300));
+    long_name.push('\0');
+    if open(&long_name, OpenFlags::CREATE | OpenFlags::RDWR) != ENAMETOOLONG {
+        return Err("creating a 300-byte name did not fail with ENAMETOOLONG");
+    }
+    if mkdir(&long_name) != ENAMETOOLONG {
+        return Err("mkdir with a 300-byte name did not fail with ENAMETOOLONG");
+    }
+    // 调低上限后，原本可以解析的路径也被拒绝
+    if !set_path_max("1024") {
+        return Err("cannot lower the path length limit");
+    }
+    let lowered = try_open(&dot_path(2000));
+    if !set_path_max("4096") {
+        return Err("cannot restore the path length limit");
+    }
+    if lowered != ENAMETOOLONG {
+        return Err("a 2000-byte path was accepted under a 1024-byte limit");
+    }
+    if set_path_max("8192") {
+        return Err("the limit was raised above PATH_MAX");
+    }
+    // 目录树不跟随符号链接，也没有创建符号链接的系统调用，符号链接环无从构造
+    println!("[path_limits] symbolic links are never followed, no loop to test");
+    Ok(())
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    report("path_limits", check())
+}
@@ -0,0 +1,158 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+/// 子进程在新的挂载命名空间中把根目录换成挂载了 tmpfs 的目录：
+/// 之后 "/" 是该目录，原来的根目录在 put_old 下；父进程的根目录不受影响
+#[cfg(target_arch = "riscv64")]
+mod workload {
+    use user_lib::{
+        chdir, close, exit, fork, mkdir, mount, open, pivot_root, read, report, rmdir, umount,
+        unlink, unshare, waitpid, write, OpenFlags, CLONE_NEWNS,
+    };
+
+    const EBUSY: isize = -16;
+    const EINVAL: isize = -22;
+    const NEW_ROOT: &str = "/pivot_root_new\0";
+    const PUT_OLD: &str = "/pivot_root_new/old\0";
+    const MARKER: &str = "/pivot_root_new/marker\0";
+    const DATA: &[u8] = b"inside the new root";
+
+    /// 读出 `path` 的内容与 `expected` 比较
+    fn has_content(path: &str, expected: &[u8]) -> bool {
+        let fd = open(path, OpenFlags::RDONLY);
+        if fd < 0 {
+            return false;
+        }
+        let mut buf = [0u8; 1024];
+        let len = read(fd as usize, &mut buf);
+        close(fd as usize);
+        len >= 0 && &buf[..len as usize] == expected
+    }
+
+    /// `/proc/mounts`（pivot_root 之后在 `/old/proc/mounts`）中是否有 `line`
+    fn mounts_contain(path: &str, line: &str) -> bool {
+        let fd = open(path, OpenFlags::RDONLY);
+        if fd < 0 {
+            return false;
+        }
+        let mut buf = [0u8; 1024];
+        let len = read(fd as usize, &mut buf);
+        close(fd as usize);
+        len > 0
+            && core::str::from_utf8(&buf[..len as usize])
+                .map_or(false, |mounts| mounts.contains(line))
+    }
+
+    /// 在子进程中运行，检查 pivot_root 前后看到的目录树
+    fn child() -> Result<(), &'static str> {
+        if unshare(CLONE_NEWNS) != 0 {
+            return Err("unshare(CLONE_NEWNS) failed");
+        }
+        if mount("tmpfs\0", NEW_ROOT, "tmpfs\0", 0) != 0 {
+            return Err("mount failed");
+        }
+        if chdir("/\0") != 0 {
+            return Err("chdir failed");
+        }
+        if pivot_root(PUT_OLD, PUT_OLD) != EINVAL {
+            return Err("pivot_root to a directory that is not a mount point succeeded");
+        }
+        if pivot_root(NEW_ROOT, PUT_OLD) != 0 {
+            return Err("pivot_root failed");
+        }
+        if !has_content("/marker\0", DATA) || !has_content("marker\0", DATA) {
+            return Err("the new root or the working directory is not the tmpfs directory");
+        }
+        if !has_content("/old/pivot_root_new/marker\0", DATA) {
+            return Err("the old root is not reachable below put_old");
+        }
+        if !has_content("/../../marker\0", DATA) {
+            return Err("\"..\" leaves the new root");
+        }
+        // 工作目录是被移走的旧根目录时，".." 是 put_old 的父目录
+        if chdir("/old\0") != 0 || !has_content("../marker\0", DATA) {
+            return Err("\"..\" of the old root does not lead to the new root");
+        }
+        if open(NEW_ROOT, OpenFlags::RDONLY) >= 0 {
+            return Err("the new root is still reachable by its old path");
+        }
+        if pivot_root("/\0", "/old\0") != EBUSY {
+            return Err("pivoting to the current root was not refused with EBUSY");
+        }
+        if !mounts_contain("/old/proc/mounts\0", "tmpfs / tmpfs rw")
+            || !mounts_contain("/old/proc/mounts\0", "/dev/root /old ")
+        {
+            return Err("/proc/mounts does not show the pivoted mounts");
+        }
+        Ok(())
+    }
+
+    fn check() -> Result<(), &'static str> {
+        let pid = fork();
+        if pid == 0 {
+            exit(match child() {
+                Ok(()) => 0,
+                Err(reason) => {
+                    println!("[pivot_root] FAILED: {}", reason);
+                    1
+                }
+            });
+        }
+        let mut exit_code = 0;
+        waitpid(pid as usize, &mut exit_code);
+        if exit_code != 0 {
+            return Err("the child failed");
+        }
+        if !has_content(MARKER, DATA) {
+            return Err("the parent's root changed");
+        }
+        // 子进程的命名空间中的挂载不出现在父进程中
+        if umount(NEW_ROOT) == 0 {
+            return Err("the child's mount leaked into the parent's namespace");
+        }
+        Ok(())
+    }
+
+    pub fn main() -> i32 {
+        if mkdir(NEW_ROOT) < 0 || mkdir(PUT_OLD) < 0 {
+            println!("[pivot_root] FAILED: cannot create the directories");
+            return -1;
+        }
+        let fd = open(MARKER, OpenFlags::CREATE | OpenFlags::WRONLY);
+        let result = if fd < 0 {
+            Err("cannot create the marker file")
+        } else {
+            let written = write(fd as usize, DATA);
+            close(fd as usize);
+            if written != DATA.len() as isize {
+                Err("cannot write the marker file")
+            } else {
+                check()
+            }
+        };
+        unlink(MARKER);
+        let removed = rmdir(PUT_OLD) == 0 && rmdir(NEW_ROOT) == 0;
+        report(
+            "pivot_root",
+            result.and(if removed {
+                Ok(())
+            } else {
+                Err("the directories stayed in use after the namespace was gone")
+            }),
+        )
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    #[cfg(target_arch = "riscv64")]
+    return workload::main();
+    #[cfg(not(target_arch = "riscv64"))]
+    {
+        println!("[pivot_root] skipped: the loongarch64 syscall stub passes only three arguments");
+        0
+    }
+}
@@ -0,0 +1,167 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+/// POSIX 定时器：到期信号经屏蔽后由 signalfd 读出，一次性定时器只到期一次，
+/// 信号挂起期间周期定时器的到期计入超限，SIGEV_THREAD_ID 只接受本线程组的线程，
+/// 删除后的定时器 id 无效
+#[cfg(target_arch = "riscv64")]
+mod workload {
+    use user_lib::{
+        close, gettid, nanosleep, read, report, signalfd, sigprocmask, sleep, timer_create,
+        timer_delete, timer_getoverrun, timer_gettime, timer_settime, ITimerSpec, SigEvent,
+        CLOCK_MONOTONIC, SFD_NONBLOCK, SIGEV_NONE, SIGEV_SIGNAL, SIGEV_THREAD_ID, SIGUSR1,
+        SIG_BLOCK,
+    };
+
+    const EAGAIN: isize = -11;
+    const EINVAL: isize = -22;
+    const SIGINFO_SIZE: usize = 128;
+    const USR1: u64 = 1 << (SIGUSR1 - 1);
+    const MS: usize = 1_000_000;
+
+    /// 读出一个挂起的信号，返回它的编号
+    fn read_signal(fd: usize) -> Result<usize, isize> {
+        let mut buf = [0u8; SIGINFO_SIZE];
+        match read(fd, &mut buf) {
+            len if len < 0 => Err(len),
+            _ => Ok(u32::from_ne_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize),
+        }
+    }
+
+    /// `value_ms` 毫秒后到期，之后以 `interval_ms` 毫秒为周期
+    fn spec(value_ms: usize, interval_ms: usize) -> ITimerSpec {
+        ITimerSpec {
+            it_interval: [interval_ms / 1000, interval_ms % 1000 * MS],
+            it_value: [value_ms / 1000, value_ms % 1000 * MS],
+        }
+    }
+
+    fn check_one_shot(timer: usize, sfd: usize) -> Result<(), &'static str> {
+        let mut curr = ITimerSpec::default();
+        if timer_gettime(timer, &mut curr) != 0 || curr.it_value != [0, 0] {
+            return Err("a new timer is not disarmed");
+        }
+        if timer_settime(timer, 0, &spec(20, 0), None) != 0 {
+            return Err("cannot arm the timer");
+        }
+        timer_gettime(timer, &mut curr);
+        if curr.it_value == [0, 0] || curr.it_value[0] != 0 || curr.it_value[1] > 20 * MS {
+            return Err("timer_gettime did not report the time left");
+        }
+        if read_signal(sfd) != Err(EAGAIN) {
+            return Err("the timer expired early");
+        }
+        nanosleep(40);
+        if read_signal(sfd) != Ok(SIGUSR1) {
+            return Err("the expired timer sent no SIGUSR1");
+        }
+        nanosleep(40);
+        if read_signal(sfd) != Err(EAGAIN) {
+            return Err("a one-shot timer expired twice");
+        }
+        timer_gettime(timer, &mut curr);
+        if curr.it_value != [0, 0] {
+            return Err("an expired one-shot timer is still armed");
+        }
+        Ok(())
+    }
+
+    fn check_overrun(timer: usize, sfd: usize) -> Result<(), &'static str> {
+        if timer_settime(timer, 0, &spec(5, 5), None) != 0 {
+            return Err("cannot arm a periodic timer");
+        }
+        // 信号一直挂起，之后的到期都是超限；挂起的信号会打断 nanosleep，这里让出处理器等待
+        sleep(100);
+        let overrun = timer_getoverrun(timer);
+        if read_signal(sfd) != Ok(SIGUSR1) {
+            return Err("the periodic timer sent no SIGUSR1");
+        }
+        let mut old = ITimerSpec::default();
+        if timer_settime(timer, 0, &spec(0, 0), Some(&mut old)) != 0 {
+            return Err("cannot disarm the timer");
+        }
+        if old.it_interval != [0, 5 * MS] {
+            return Err("timer_settime did not return the old interval");
+        }
+        if overrun < 5 {
+            return Err("expirations while the signal was pending were not counted");
+        }
+        Ok(())
+    }
+
+    fn check_notify() -> Result<(), &'static str> {
+        let tid = gettid() as i32;
+        let thread = timer_create(
+            CLOCK_MONOTONIC,
+            Some(&SigEvent::new(SIGEV_THREAD_ID, SIGUSR1, tid)),
+        );
+        if thread < 0 {
+            return Err("SIGEV_THREAD_ID to the calling thread was rejected");
+        }
+        timer_delete(thread as usize);
+        let stranger = SigEvent::new(SIGEV_THREAD_ID, SIGUSR1, 0x3fff_0000);
+        if timer_create(CLOCK_MONOTONIC, Some(&stranger)) != EINVAL {
+            return Err("SIGEV_THREAD_ID to a missing thread was accepted");
+        }
+        if timer_create(CLOCK_MONOTONIC, Some(&SigEvent::new(SIGEV_SIGNAL, 0, 0))) != EINVAL {
+            return Err("a timer with signal 0 was accepted");
+        }
+        if timer_create(16, None) != EINVAL {
+            return Err("a timer on an unknown clock was accepted");
+        }
+        let silent = timer_create(CLOCK_MONOTONIC, Some(&SigEvent::new(SIGEV_NONE, 0, 0)));
+        if silent < 0 {
+            return Err("SIGEV_NONE was rejected");
+        }
+        timer_delete(silent as usize);
+        Ok(())
+    }
+
+    fn check() -> Result<(), &'static str> {
+        sigprocmask(SIG_BLOCK, USR1);
+        let sfd = signalfd(-1, USR1, SFD_NONBLOCK);
+        if sfd < 0 {
+            return Err("cannot create a signalfd");
+        }
+        let sfd = sfd as usize;
+        let timer = timer_create(
+            CLOCK_MONOTONIC,
+            Some(&SigEvent::new(SIGEV_SIGNAL, SIGUSR1, 0)),
+        );
+        if timer < 0 {
+            return Err("timer_create failed");
+        }
+        let timer = timer as usize;
+        let result = check_one_shot(timer, sfd)
+            .and_then(|()| check_overrun(timer, sfd))
+            .and_then(|()| check_notify());
+        close(sfd);
+        result?;
+        let mut curr = ITimerSpec::default();
+        if timer_delete(timer) != 0 {
+            return Err("timer_delete failed");
+        }
+        if timer_delete(timer) != EINVAL || timer_gettime(timer, &mut curr) != EINVAL {
+            return Err("a deleted timer id is still valid");
+        }
+        Ok(())
+    }
+
+    pub fn main() -> i32 {
+        report("posix_timer", check())
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    #[cfg(target_arch = "riscv64")]
+    return workload::main();
+    #[cfg(not(target_arch = "riscv64"))]
+    {
+        println!("[posix_timer] skipped: timer_settime takes four arguments");
+        0
+    }
+}
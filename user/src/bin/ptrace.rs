@@ -0,0 +1,106 @@
+#![no_std]
+#![no_main]
+
+extern crate user_lib;
+
+use user_lib::{
+    exit, fork, getpid, kill, ptrace, report, waitpid_options, NT_PRSTATUS, PTRACE_CONT,
+    PTRACE_GETREGSET, PTRACE_O_TRACESYSGOOD, PTRACE_PEEKDATA, PTRACE_POKEDATA, PTRACE_SETOPTIONS,
+    PTRACE_SYSCALL, PTRACE_TRACEME, SIGSTOP, SIGTRAP,
+};
+
+const EPERM: isize = -1;
+const SYSCALL_EXIT: usize = 93;
+/// 通用寄存器组中 a0 与 a7 的下标
+#[cfg(target_arch = "riscv64")]
+const REG_A0: usize = 10;
+#[cfg(target_arch = "loongarch64")]
+const REG_A0: usize = 4;
+const REG_A7: usize = REG_A0 + 7;
+
+/// 子进程退出码的来源，跟踪者改写子进程中的这个值
+static mut VALUE: usize = 7;
+
+/// 子进程：请求父进程跟踪，停止自己，之后以 VALUE 退出
+fn tracee() -> ! {
+    if ptrace(PTRACE_TRACEME, 0, 0, 0) != 0 {
+        exit(1);
+    }
+    if ptrace(PTRACE_TRACEME, 0, 0, 0) != EPERM {
+        exit(2);
+    }
+    kill(getpid() as usize, SIGSTOP);
+    exit(unsafe { core::ptr::read_volatile(core::ptr::addr_of!(VALUE)) } as i32);
+}
+
+/// 等待被跟踪的子进程停止，返回停止它的信号
+fn wait_stop(pid: isize) -> Result<usize, &'static str> {
+    let mut status = 0;
+    if waitpid_options(pid, &mut status, 0) != pid {
+        return Err("wait4 did not report the tracee");
+    }
+    if status & 0xff != 0x7f {
+        return Err("the tracee did not stop");
+    }
+    Ok((status >> 8) as usize & 0xff)
+}
+
+fn check() -> Result<(), &'static str> {
+    let pid = fork();
+    if pid == 0 {
+        tracee();
+    }
+    if wait_stop(pid)? != SIGSTOP {
+        return Err("the tracee did not stop before SIGSTOP was delivered");
+    }
+    let addr = unsafe { core::ptr::addr_of!(VALUE) } as usize;
+    let mut word = 0usize;
+    if ptrace(
+        PTRACE_PEEKDATA,
+        pid as usize,
+        addr,
+        &mut word as *mut usize as usize,
+    ) != 0
+        || word != 7
+    {
+        return Err("PTRACE_PEEKDATA did not read the tracee's memory");
+    }
+    if ptrace(PTRACE_POKEDATA, pid as usize, addr, 42) != 0 {
+        return Err("PTRACE_POKEDATA failed");
+    }
+    if unsafe { core::ptr::read_volatile(core::ptr::addr_of!(VALUE)) } != 7 {
+        return Err("PTRACE_POKEDATA wrote the tracer's copy-on-write page");
+    }
+    ptrace(PTRACE_SETOPTIONS, pid as usize, 0, PTRACE_O_TRACESYSGOOD);
+    // 丢弃 SIGSTOP，在下一个系统调用 exit 的入口处停下
+    ptrace(PTRACE_SYSCALL, pid as usize, 0, 0);
+    if wait_stop(pid)? != SIGTRAP | 0x80 {
+        return Err("the tracee did not stop at syscall entry");
+    }
+    let mut regs = [0usize; 32];
+    let mut iov = [regs.as_mut_ptr() as usize, core::mem::size_of_val(&regs)];
+    if ptrace(
+        PTRACE_GETREGSET,
+        pid as usize,
+        NT_PRSTATUS,
+        iov.as_mut_ptr() as usize,
+    ) != 0
+    {
+        return Err("PTRACE_GETREGSET failed");
+    }
+    if regs[REG_A7] != SYSCALL_EXIT || regs[REG_A0] != 42 {
+        return Err("the registers at syscall entry are not those of exit(42)");
+    }
+    ptrace(PTRACE_CONT, pid as usize, 0, 0);
+    let mut status = 0;
+    waitpid_options(pid, &mut status, 0);
+    if status & 0x7f != 0 || (status >> 8) & 0xff != 42 {
+        return Err("the tracee did not exit with the poked value");
+    }
+    Ok(())
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    report("ptrace", check())
+}
@@ -0,0 +1,83 @@
+#![no_std]
+#![no_main]
+
+extern crate user_lib;
+extern crate alloc;
+use alloc::format;
+use user_lib::{close, ioctl, open, read, report, write, OpenFlags, TIOCGPTN, TIOCSPTLCK};
+
+/// 从 `fd` 读出一次，检查读到的正好是 `expected`
+fn expect_read(fd: usize, expected: &[u8], err: &'static str) -> Result<(), &'static str> {
+    let mut buf = [0u8; 64];
+    let len = read(fd, &mut buf);
+    if len < 0 || &buf[..len as usize] != expected {
+        return Err(err);
+    }
+    Ok(())
+}
+
+fn check_pair(master: usize, index: u32) -> Result<(), &'static str> {
+    let path = format!("/dev/pts/{}", index);
+    let path = path.as_str();
+    if open(path, OpenFlags::RDWR) >= 0 {
+        return Err("a locked slave was opened");
+    }
+    let unlock: i32 = 0;
+    if ioctl(master, TIOCSPTLCK, &unlock as *const i32 as usize) != 0 {
+        return Err("TIOCSPTLCK failed");
+    }
+    let slave = open(path, OpenFlags::RDWR);
+    if slave < 0 {
+        return Err("cannot open the unlocked slave");
+    }
+    let slave = slave as usize;
+    // 规范模式：退格删去 'x'，回车变为换行后这一行才可读，回显经过 ONLCR
+    write(master, b"hix\x7f\r");
+    expect_read(slave, b"hi\n", "the slave did not read the edited line")?;
+    expect_read(master, b"hix\x08 \x08\r\n", "wrong echo on the master")?;
+    write(slave, b"ok\n");
+    expect_read(master, b"ok\r\n", "the slave output was not translated")?;
+    // 行首的 Ctrl-D 是文件结束
+    write(master, b"\x04");
+    expect_read(slave, b"", "VEOF did not read as end of file")?;
+    close(slave);
+    let mut buf = [0u8; 8];
+    if read(master, &mut buf) >= 0 {
+        return Err("the master read no error after the slave closed");
+    }
+    Ok(())
+}
+
+/// 打开 /dev/ptmx 得到一对新的伪终端，从设备解锁前不能打开；
+/// 主设备写入的数据经行规程由从设备读出，从设备的输出由主设备读出；
+/// 主设备关闭后 /dev/pts/N 消失
+fn check() -> Result<(), &'static str> {
+    let master = open("/dev/ptmx", OpenFlags::RDWR);
+    if master < 0 {
+        return Err("cannot open /dev/ptmx");
+    }
+    let master = master as usize;
+    let mut index: u32 = u32::MAX;
+    if ioctl(master, TIOCGPTN, &mut index as *mut u32 as usize) != 0 {
+        return Err("TIOCGPTN failed");
+    }
+    let result = check_pair(master, index);
+    close(master);
+    result?;
+    let again = open("/dev/ptmx", OpenFlags::RDWR);
+    if again < 0 {
+        return Err("cannot open /dev/ptmx again");
+    }
+    let mut reused: u32 = u32::MAX;
+    ioctl(again as usize, TIOCGPTN, &mut reused as *mut u32 as usize);
+    close(again as usize);
+    if reused != index {
+        return Err("the index of a closed pty was not reused");
+    }
+    Ok(())
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    report("pty", check())
+}
@@ -0,0 +1,176 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+/// 在 `/dev/ram0` 上写入一个空的 FAT32 文件系统并挂载，在其中创建的文件
+/// 卸载后再次挂载仍能读出
+#[cfg(target_arch = "riscv64")]
+mod workload {
+    use user_lib::{
+        close, ioctl, lseek, mkdir, mount, open, pread, read, report, rmdir, umount, write,
+        OpenFlags, BLKGETSIZE64,
+    };
+
+    const ENOTTY: isize = -25;
+    const DEVICE: &str = "/dev/ram0\0";
+    const MOUNT_POINT: &str = "/ram_disk_mnt\0";
+    const FILE: &str = "/ram_disk_mnt/SCRATCH.TXT\0";
+    const DATA: &[u8] = b"scratch data kept on /dev/ram0";
+
+    /// 文件系统的布局：512 字节的扇区，每簇一个扇区，32 个保留扇区，两份各 16 个扇区的 FAT，
+    /// 根目录在 2 号簇（64 号扇区）
+    const SECTOR_SIZE: usize = 512;
+    const TOTAL_SECTORS: usize = 2048;
+    const RESERVED_SECTORS: usize = 32;
+    const FAT_SECTORS: usize = 16;
+    const FIRST_FAT: usize = RESERVED_SECTORS;
+    const SECOND_FAT: usize = RESERVED_SECTORS + FAT_SECTORS;
+
+    fn put(buf: &mut [u8], offset: usize, bytes: &[u8]) {
+        buf[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// 空文件系统第 `index` 个扇区的内容
+    fn sector(index: usize, buf: &mut [u8]) {
+        buf.fill(0);
+        match index {
+            0 => {
+                put(buf, 0, &[0xEB, 0x58, 0x90]);
+                put(buf, 3, b"MSWIN4.1");
+                put(buf, 11, &(SECTOR_SIZE as u16).to_le_bytes());
+                buf[13] = 1;
+                put(buf, 14, &(RESERVED_SECTORS as u16).to_le_bytes());
+                buf[16] = 2;
+                buf[21] = 0xF8;
+                put(buf, 32, &(TOTAL_SECTORS as u32).to_le_bytes());
+                put(buf, 36, &(FAT_SECTORS as u32).to_le_bytes());
+                put(buf, 44, &2u32.to_le_bytes());
+                put(buf, 48, &1u16.to_le_bytes());
+                put(buf, 50, &6u16.to_le_bytes());
+                buf[66] = 0x29;
+                put(buf, 71, b"RAMDISK    ");
+                put(buf, 82, b"FAT32   ");
+                put(buf, 510, &[0x55, 0xAA]);
+            }
+            // 0 与 1 号表项保留，2 号簇（根目录）是一条簇链的结尾
+            FIRST_FAT | SECOND_FAT => {
+                put(buf, 0, &0x0FFFFFF8u32.to_le_bytes());
+                put(buf, 4, &0x0FFFFFFFu32.to_le_bytes());
+                put(buf, 8, &0x0FFFFFFFu32.to_le_bytes());
+            }
+            _ => {}
+        }
+    }
+
+    /// 把空文件系统写到设备开头
+    fn format(fd: usize) -> Result<(), &'static str> {
+        let mut size = 0u64;
+        if ioctl(fd, BLKGETSIZE64, &mut size as *mut u64 as usize) != 0 {
+            return Err("BLKGETSIZE64 failed");
+        }
+        if size < (TOTAL_SECTORS * SECTOR_SIZE) as u64 {
+            return Err("the RAM disk is smaller than the filesystem");
+        }
+        if ioctl(fd, 0x1234, 0) != ENOTTY {
+            return Err("an unknown ioctl was not refused with ENOTTY");
+        }
+        let mut buf = [0u8; SECTOR_SIZE];
+        for index in 0..TOTAL_SECTORS {
+            sector(index, &mut buf);
+            if write(fd, &buf) != SECTOR_SIZE as isize {
+                return Err("cannot write the filesystem to /dev/ram0");
+            }
+        }
+        Ok(())
+    }
+
+    /// pread 读出 OEM 名，之后从文件偏移量 0 处 read 仍读到跳转指令
+    fn check_pread(fd: usize) -> Result<(), &'static str> {
+        if lseek(fd, 0, 0) != 0 {
+            return Err("cannot seek back to the start of /dev/ram0");
+        }
+        let mut oem = [0u8; 8];
+        if pread(fd, &mut oem, 3) != 8 || &oem != b"MSWIN4.1" {
+            return Err("pread at an offset read the wrong bytes");
+        }
+        let mut jump = [0u8; 3];
+        if read(fd, &mut jump) != 3 || jump != [0xEB, 0x58, 0x90] {
+            return Err("pread moved the file offset");
+        }
+        Ok(())
+    }
+
+    /// 读出 `FILE` 的内容与 `DATA` 比较
+    fn has_data() -> bool {
+        let fd = open(FILE, OpenFlags::RDONLY);
+        if fd < 0 {
+            return false;
+        }
+        let mut buf = [0u8; 128];
+        let len = read(fd as usize, &mut buf);
+        close(fd as usize);
+        len >= 0 && &buf[..len as usize] == DATA
+    }
+
+    fn check() -> Result<(), &'static str> {
+        if mount(DEVICE, MOUNT_POINT, "vfat\0", 0) != 0 {
+            return Err("mounting /dev/ram0 failed");
+        }
+        let fd = open(FILE, OpenFlags::CREATE | OpenFlags::WRONLY);
+        let written = fd >= 0 && write(fd as usize, DATA) == DATA.len() as isize;
+        if fd >= 0 {
+            close(fd as usize);
+        }
+        let read_back = has_data();
+        if umount(MOUNT_POINT) != 0 {
+            return Err("umount failed");
+        }
+        if !written {
+            return Err("cannot create a file on the RAM disk");
+        }
+        if !read_back {
+            return Err("the file cannot be read back");
+        }
+        if mount(DEVICE, MOUNT_POINT, "vfat\0", 0) != 0 {
+            return Err("mounting /dev/ram0 again failed");
+        }
+        let kept = has_data();
+        umount(MOUNT_POINT);
+        if !kept {
+            return Err("the file is gone after mounting the RAM disk again");
+        }
+        Ok(())
+    }
+
+    pub fn main() -> i32 {
+        let fd = open(DEVICE, OpenFlags::RDWR);
+        if fd < 0 {
+            println!("[ram_disk] skipped: no /dev/ram0, booted with ramdisk_size=0");
+            return 0;
+        }
+        let formatted = format(fd as usize).and_then(|()| check_pread(fd as usize));
+        close(fd as usize);
+        let result = formatted.and_then(|()| {
+            if mkdir(MOUNT_POINT) < 0 {
+                return Err("cannot create the mount point");
+            }
+            let result = check();
+            rmdir(MOUNT_POINT);
+            result
+        });
+        report("ram_disk", result)
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    #[cfg(target_arch = "riscv64")]
+    return workload::main();
+    #[cfg(not(target_arch = "riscv64"))]
+    {
+        println!("[ram_disk] skipped: the loongarch64 syscall stub passes only three arguments");
+        0
+    }
+}
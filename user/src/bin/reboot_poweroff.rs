@@ -0,0 +1,39 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{reboot, LINUX_REBOOT_CMD_POWER_OFF, LINUX_REBOOT_MAGIC1, LINUX_REBOOT_MAGIC2};
+
+const EINVAL: isize = -22;
+
+/// 先检查非法参数被拒绝，再请求关机。
+/// 关机成功时 QEMU 以退出码 0 结束；整机流程见 `make -f make/rv64.mk poweroff-test`
+#[no_mangle]
+pub fn main() -> i32 {
+    println!("[reboot_poweroff] checking argument validation");
+    let bad_magic = [
+        (0, LINUX_REBOOT_MAGIC2),
+        (LINUX_REBOOT_MAGIC1, 0),
+        (LINUX_REBOOT_MAGIC2, LINUX_REBOOT_MAGIC1),
+    ];
+    for (magic1, magic2) in bad_magic {
+        let ret = reboot(magic1, magic2, LINUX_REBOOT_CMD_POWER_OFF);
+        if ret != EINVAL {
+            println!(
+                "[reboot_poweroff] FAILED: magic {:#x}/{:#x} returned {}",
+                magic1, magic2, ret
+            );
+            return -1;
+        }
+    }
+    if reboot(LINUX_REBOOT_MAGIC1, LINUX_REBOOT_MAGIC2, 0xdead_beef) != EINVAL {
+        println!("[reboot_poweroff] FAILED: unknown cmd was accepted");
+        return -1;
+    }
+
+    println!("[reboot_poweroff] powering off");
+    reboot(LINUX_REBOOT_MAGIC1, LINUX_REBOOT_MAGIC2, LINUX_REBOOT_CMD_POWER_OFF);
+    println!("[reboot_poweroff] FAILED: still running after power off");
+    -1
+}
@@ -0,0 +1,113 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use core::hint::black_box;
+use user_lib::{close, exit, fork, get_time, open, read, waitpid, yield_, OpenFlags};
+
+const PROCESS_NUM: usize = 6;
+/// 子进程忙等的时长
+const SPIN_MS: isize = 300;
+
+/// 从 /proc/schedstat 读出的运行队列状态
+struct RunQueues {
+    /// 最长的运行队列
+    longest: usize,
+    imbalance: usize,
+    /// 发生过上下文切换的 CPU 数，即在线 CPU 数
+    online: usize,
+}
+
+fn run_queues() -> Option<RunQueues> {
+    let fd = open("/proc/schedstat\0", OpenFlags::RDONLY);
+    if fd < 0 {
+        return None;
+    }
+    let mut buf = [0u8; 1024];
+    let len = read(fd as usize, &mut buf);
+    close(fd as usize);
+    let text = core::str::from_utf8(&buf[..len.max(0) as usize]).ok()?;
+    let mut queues = RunQueues {
+        longest: 0,
+        imbalance: 0,
+        online: 0,
+    };
+    for line in text.lines() {
+        let mut fields = line.split_whitespace();
+        match fields.next()? {
+            // "cpu<N> <context_switches> <voluntary> <involuntary> <run_time_ns> <nr_running>"
+            cpu if cpu.starts_with("cpu") => {
+                let fields: [usize; 5] = {
+                    let mut values = [0; 5];
+                    for value in values.iter_mut() {
+                        *value = fields.next()?.parse().ok()?;
+                    }
+                    values
+                };
+                if fields[0] > 0 {
+                    queues.online += 1;
+                }
+                queues.longest = queues.longest.max(fields[4]);
+            }
+            "imbalance" => queues.imbalance = fields.next()?.parse().ok()?,
+            _ => {}
+        }
+    }
+    Some(queues)
+}
+
+/// 一次 fork 出多个忙等的子进程，它们先堆积在父进程所在的 CPU 上，
+/// 运行队列长度与不均衡度应当升高，子进程退出后不均衡度应当回落
+#[no_mangle]
+pub fn main() -> i32 {
+    if run_queues().is_none() {
+        println!("[runqueue_imbalance] FAILED: /proc/schedstat missing or malformed");
+        return -1;
+    }
+    let mut pids = [0isize; PROCESS_NUM];
+    for pid in pids.iter_mut() {
+        *pid = fork();
+        if *pid == 0 {
+            let start = get_time();
+            let mut acc = 0usize;
+            while get_time() - start < SPIN_MS {
+                acc = black_box(acc.wrapping_add(1));
+            }
+            exit(0);
+        }
+    }
+    let (mut longest, mut peak, mut online) = (0, 0, 0);
+    let start = get_time();
+    while get_time() - start < SPIN_MS / 2 {
+        if let Some(queues) = run_queues() {
+            longest = longest.max(queues.longest);
+            peak = peak.max(queues.imbalance);
+            online = online.max(queues.online);
+        }
+        yield_();
+    }
+    for pid in pids.iter() {
+        let mut exit_code: i32 = 0;
+        waitpid(*pid as usize, &mut exit_code);
+    }
+    let settled = run_queues().map_or(usize::MAX, |queues| queues.imbalance);
+    println!(
+        "[runqueue_imbalance] {} online CPUs, longest queue {}, imbalance peak {} then {}",
+        online, longest, peak, settled
+    );
+    if longest < 2 {
+        println!("[runqueue_imbalance] FAILED: queued children not counted");
+        return -1;
+    }
+    if online > 1 && peak == 0 {
+        println!("[runqueue_imbalance] FAILED: children piled on one CPU but no imbalance");
+        return -1;
+    }
+    if settled > 1 {
+        println!("[runqueue_imbalance] FAILED: imbalance stayed after the children exited");
+        return -1;
+    }
+    println!("[runqueue_imbalance] passed");
+    0
+}
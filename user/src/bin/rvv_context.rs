@@ -0,0 +1,90 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{exit, fork, waitpid, yield_};
+
+const LOOP_NUM: usize = 200;
+
+/// 用 `pattern` 填满 v1，反复让出 CPU 后检查 v1 与 vl 是否保持不变
+#[cfg(target_arch = "riscv64")]
+fn vector_worker(pattern: u64) -> ! {
+    use core::arch::asm;
+    let vl_before: usize;
+    unsafe {
+        asm!(
+            ".option push",
+            ".option arch, +v",
+            "vsetvli {vl}, zero, e64, m1, ta, ma",
+            "vmv.v.x v1, {pat}",
+            ".option pop",
+            vl = out(reg) vl_before,
+            pat = in(reg) pattern,
+        );
+    }
+    for _ in 0..LOOP_NUM {
+        yield_();
+    }
+    let vl_after: usize;
+    let first: u64;
+    let mismatch: usize;
+    unsafe {
+        asm!(
+            ".option push",
+            ".option arch, +v",
+            "csrr {vl}, vl",
+            "vmv.x.s {first}, v1",
+            // 统计 v1 中与 pattern 不相等的元素个数
+            "vmsne.vx v2, v1, {pat}",
+            "vcpop.m {bad}, v2",
+            ".option pop",
+            vl = out(reg) vl_after,
+            first = out(reg) first,
+            bad = out(reg) mismatch,
+            pat = in(reg) pattern,
+        );
+    }
+    if vl_after != vl_before || first != pattern || mismatch != 0 {
+        println!(
+            "[rvv_context] pattern {:#x}: vl {} -> {}, v1[0] = {:#x}, {} bad lanes",
+            pattern, vl_before, vl_after, first, mismatch
+        );
+        exit(1);
+    }
+    exit(0);
+}
+
+#[cfg(not(target_arch = "riscv64"))]
+fn vector_worker(_pattern: u64) -> ! {
+    exit(0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    println!("[rvv_context] two tasks with independent vector state");
+
+    let patterns = [0x5555_5555_5555_5555u64, 0xaaaa_aaaa_0000_ffffu64];
+    let mut pids = [0isize; 2];
+    for (i, pattern) in patterns.iter().enumerate() {
+        let pid = fork();
+        if pid == 0 {
+            vector_worker(*pattern);
+        }
+        pids[i] = pid;
+    }
+
+    let mut failed = false;
+    for pid in pids.iter() {
+        let mut exit_code: i32 = 0;
+        waitpid(*pid as usize, &mut exit_code);
+        failed |= exit_code != 0;
+    }
+    if failed {
+        println!("[rvv_context] FAILED");
+        -1
+    } else {
+        println!("[rvv_context] passed");
+        0
+    }
+}
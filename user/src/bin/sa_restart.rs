@@ -0,0 +1,86 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+/// 阻塞在管道读上时被信号打断：处理函数设置了 SA_RESTART 时读在处理函数返回后继续，
+/// 读到之后写入的数据；没有设置时读返回 EINTR
+#[cfg(target_arch = "riscv64")]
+mod workload {
+    use user_lib::{
+        close, exit, fork, getpid, kill, nanosleep, pipe, read, report, sigaction, waitpid, write,
+        SigAction, SA_RESTART, SIGUSR1,
+    };
+
+    const EINTR: isize = -4;
+
+    static mut HANDLED: usize = 0;
+
+    extern "C" fn on_sigusr1(_signum: usize) {
+        unsafe { HANDLED += 1 };
+    }
+
+    /// 子进程先向父进程发送 SIGUSR1，再写入一个字节；返回父进程读管道的结果
+    fn interrupted_read(flags: usize) -> Result<isize, &'static str> {
+        let act = SigAction {
+            handler: on_sigusr1 as usize,
+            flags,
+            restorer: 0,
+            mask: 0,
+        };
+        if sigaction(SIGUSR1, &act) != 0 {
+            return Err("sigaction failed");
+        }
+        unsafe { HANDLED = 0 };
+        let mut fds = [0i32; 2];
+        if pipe(&mut fds) != 0 {
+            return Err("cannot create a pipe");
+        }
+        let parent = getpid() as usize;
+        let pid = fork();
+        if pid == 0 {
+            close(fds[0] as usize);
+            nanosleep(20);
+            kill(parent, SIGUSR1);
+            nanosleep(20);
+            write(fds[1] as usize, b"x");
+            exit(0);
+        }
+        let mut buf = [0u8; 1];
+        let ret = read(fds[0] as usize, &mut buf);
+        let mut exit_code = 0;
+        waitpid(pid as usize, &mut exit_code);
+        close(fds[0] as usize);
+        close(fds[1] as usize);
+        if unsafe { HANDLED } != 1 {
+            return Err("the handler did not run exactly once");
+        }
+        Ok(ret)
+    }
+
+    fn check() -> Result<(), &'static str> {
+        if interrupted_read(SA_RESTART)? != 1 {
+            return Err("the read was not restarted under SA_RESTART");
+        }
+        if interrupted_read(0)? != EINTR {
+            return Err("the read did not fail with EINTR without SA_RESTART");
+        }
+        Ok(())
+    }
+
+    pub fn main() -> i32 {
+        report("sa_restart", check())
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    #[cfg(target_arch = "riscv64")]
+    return workload::main();
+    #[cfg(not(target_arch = "riscv64"))]
+    {
+        println!("[sa_restart] skipped: sigaction is only wired up on riscv64");
+        0
+    }
+}
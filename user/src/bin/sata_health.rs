@@ -0,0 +1,82 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    close, ioctl, open, read, report, DiskHealth, OpenFlags, BLKGETSIZE64, DISK_GET_HEALTH,
+};
+
+const ENOTTY: isize = -25;
+
+/// 去掉字段末尾的 NUL 填充
+fn field(bytes: &[u8]) -> &str {
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    core::str::from_utf8(&bytes[..len]).unwrap_or("?")
+}
+
+/// `/sys/block/sda/size` 给出的扇区数
+fn sys_sectors() -> Option<u64> {
+    let fd = open("/sys/block/sda/size\0", OpenFlags::RDONLY);
+    if fd < 0 {
+        return None;
+    }
+    let mut buf = [0u8; 32];
+    let len = read(fd as usize, &mut buf);
+    close(fd as usize);
+    core::str::from_utf8(&buf[..len.max(0) as usize])
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// 经 ioctl 读出的容量应与身份信息和 /sys/block/sda/size 一致
+fn check(fd: usize) -> Result<(), &'static str> {
+    let mut bytes = 0u64;
+    if ioctl(fd, BLKGETSIZE64, &mut bytes as *mut u64 as usize) != 0 {
+        return Err("BLKGETSIZE64 failed");
+    }
+    let mut health = DiskHealth {
+        sectors: 0,
+        temperature: 0,
+        reserved: 0,
+        model: [0; 40],
+        serial: [0; 24],
+    };
+    if ioctl(fd, DISK_GET_HEALTH, &mut health as *mut DiskHealth as usize) != 0 {
+        return Err("DISK_GET_HEALTH failed");
+    }
+    println!(
+        "[sata_health] model \"{}\" serial \"{}\" {} sectors, temperature {}",
+        field(&health.model),
+        field(&health.serial),
+        health.sectors,
+        health.temperature
+    );
+    if bytes == 0 || bytes != health.sectors * 512 {
+        return Err("BLKGETSIZE64 does not match the sectors reported by the drive");
+    }
+    if sys_sectors() != Some(health.sectors) {
+        return Err("/sys/block/sda/size does not match the drive");
+    }
+    if field(&health.model).is_empty() {
+        return Err("the drive reported no model");
+    }
+    if ioctl(fd, 0x1234, 0) != ENOTTY {
+        return Err("an unknown ioctl was not refused with ENOTTY");
+    }
+    Ok(())
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("/dev/sda\0", OpenFlags::RDONLY);
+    if fd < 0 {
+        println!("[sata_health] skipped: no SATA disk");
+        return 0;
+    }
+    let result = check(fd as usize);
+    close(fd as usize);
+    report("sata_health", result)
+}
@@ -0,0 +1,52 @@
+#![no_std]
+#![no_main]
+
+extern crate user_lib;
+use user_lib::{getcpu, report, sched_getaffinity, sched_setaffinity};
+
+/// 与 glibc 的 cpu_set_t 一样大
+const CPU_SET_BYTES: usize = 128;
+
+fn affinity() -> Result<u8, &'static str> {
+    let mut mask = [0u8; CPU_SET_BYTES];
+    if sched_getaffinity(0, &mut mask) != core::mem::size_of::<usize>() as isize {
+        return Err("sched_getaffinity did not report a word of mask");
+    }
+    Ok(mask[0])
+}
+
+/// 把自己绑定到 CPU 0 后立即在 CPU 0 上运行；
+/// 不含任何存在的 CPU 的掩码与放不下所有 CPU 的缓冲区都被拒绝
+fn check() -> Result<(), &'static str> {
+    if affinity()? & 1 == 0 {
+        return Err("CPU 0 is not in the initial mask");
+    }
+    let mut only_cpu0 = [0u8; CPU_SET_BYTES];
+    only_cpu0[0] = 1;
+    if sched_setaffinity(0, &only_cpu0) != 0 {
+        return Err("cannot bind to CPU 0");
+    }
+    if getcpu() != 0 {
+        return Err("still running on another CPU after binding to CPU 0");
+    }
+    if affinity()? != 1 {
+        return Err("the mask read back is not the one set");
+    }
+    let mut no_such_cpu = [0u8; CPU_SET_BYTES];
+    no_such_cpu[CPU_SET_BYTES - 1] = 0x80;
+    if sched_setaffinity(0, &no_such_cpu) >= 0 {
+        return Err("a mask without any existing CPU was accepted");
+    }
+    if sched_getaffinity(0, &mut [0u8; 1]) >= 0 {
+        return Err("a buffer too small for the mask was accepted");
+    }
+    if sched_setaffinity(0, &[0xffu8; CPU_SET_BYTES]) != 0 {
+        return Err("cannot allow all CPUs again");
+    }
+    Ok(())
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    report("sched_affinity", check())
+}
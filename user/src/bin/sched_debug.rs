@@ -0,0 +1,65 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, exit, fork, open, read, waitpid, yield_, OpenFlags};
+
+const PROCESS_NUM: usize = 4;
+const YIELD_NUM: usize = 8;
+
+/// 事件日志中某个 pid 的 pick 事件数
+fn count_picks(log: &str, pid: isize) -> usize {
+    log.lines()
+        .filter(|line| {
+            let mut words = line.split_whitespace();
+            // "<time> pick pid <pid> vruntime <vruntime>"
+            words.nth(1) == Some("pick") && words.nth(1).and_then(|p| p.parse().ok()) == Some(pid)
+        })
+        .count()
+}
+
+/// 运行几个反复让出 CPU 的子进程，它们都应在 /proc/sched_debug 中留下 pick 事件
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut pids = [0isize; PROCESS_NUM];
+    for pid in pids.iter_mut() {
+        *pid = fork();
+        if *pid == 0 {
+            for _ in 0..YIELD_NUM {
+                yield_();
+            }
+            exit(0);
+        }
+    }
+    for pid in pids.iter() {
+        let mut exit_code: i32 = 0;
+        waitpid(*pid as usize, &mut exit_code);
+    }
+    let fd = open("/proc/sched_debug\0", OpenFlags::RDONLY);
+    if fd < 0 {
+        println!("[sched_debug] skipped: kernel built without the sched_debug feature");
+        return 0;
+    }
+    // 每个 CPU 256 个事件，每个事件一行约 60 字节
+    let mut buf = [0u8; 96 * 1024];
+    let mut len = 0;
+    while len < buf.len() {
+        match read(fd as usize, &mut buf[len..]) {
+            n if n > 0 => len += n as usize,
+            _ => break,
+        }
+    }
+    close(fd as usize);
+    let log = core::str::from_utf8(&buf[..len]).unwrap_or("");
+    for pid in pids.iter() {
+        let picks = count_picks(log, *pid);
+        println!("[sched_debug] pid {}: {} pick events", pid, picks);
+        if picks == 0 {
+            println!("[sched_debug] FAILED: no pick event for pid {}", pid);
+            return -1;
+        }
+    }
+    println!("[sched_debug] passed");
+    0
+}
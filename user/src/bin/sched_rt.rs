@@ -0,0 +1,62 @@
+#![no_std]
+#![no_main]
+
+extern crate user_lib;
+use user_lib::{
+    exit, fork, get_time, report, sched_getscheduler, sched_setaffinity, sched_setscheduler,
+    waitpid, yield_, SCHED_FIFO, SCHED_OTHER, SCHED_RR,
+};
+
+/// 父进程以 SCHED_FIFO 忙等的时长
+const SPIN_MS: isize = 100;
+
+/// 策略与优先级的检查：实时策略的优先级为 1-99，其他策略只能为 0
+fn check_policy() -> Result<(), &'static str> {
+    if sched_setscheduler(0, SCHED_FIFO, 0) >= 0 || sched_setscheduler(0, SCHED_RR, 100) >= 0 {
+        return Err("a RT priority out of 1-99 was accepted");
+    }
+    if sched_setscheduler(0, SCHED_OTHER, 5) >= 0 {
+        return Err("a priority for SCHED_OTHER was accepted");
+    }
+    if sched_setscheduler(0, SCHED_RR, 10) != 0 || sched_getscheduler(0) != SCHED_RR as isize {
+        return Err("cannot switch to SCHED_RR");
+    }
+    if sched_setscheduler(0, SCHED_OTHER, 0) != 0 || sched_getscheduler(0) != SCHED_OTHER as isize {
+        return Err("cannot switch back to SCHED_OTHER");
+    }
+    Ok(())
+}
+
+/// 同在 CPU 0 上：SCHED_FIFO 的父进程即使不断让出 CPU，普通优先级的子进程
+/// 也要等到父进程回到 SCHED_OTHER 后才能运行（fork 出的子进程不继承实时策略）
+fn check_fifo() -> Result<(), &'static str> {
+    let mut cpu0 = [0u8; 8];
+    cpu0[0] = 1;
+    if sched_setaffinity(0, &cpu0) != 0 {
+        return Err("cannot bind to CPU 0");
+    }
+    if sched_setscheduler(0, SCHED_FIFO, 10) != 0 {
+        return Err("cannot switch to SCHED_FIFO");
+    }
+    let start = get_time();
+    let pid = fork();
+    if pid == 0 {
+        // 以 10ms 为单位报告子进程开始运行时距 fork 的时间
+        exit(((get_time() - start) / 10).min(255) as i32);
+    }
+    while get_time() - start < SPIN_MS {
+        yield_();
+    }
+    sched_setscheduler(0, SCHED_OTHER, 0);
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    if (((exit_code >> 8) & 0xff) as isize) * 10 < SPIN_MS - 10 {
+        return Err("a normal task ran while a FIFO task was runnable");
+    }
+    Ok(())
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    report("sched_rt", check_policy().and_then(|()| check_fifo()))
+}
@@ -0,0 +1,87 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use core::hint::black_box;
+use user_lib::{close, exit, fork, get_time, open, read, waitpid, yield_, OpenFlags};
+
+const YIELD_NUM: usize = 20;
+/// 忙等的时长，远大于时间片
+const SPIN_MS: isize = 200;
+
+fn spin(ms: isize) {
+    let start = get_time();
+    let mut acc = 0usize;
+    while get_time() - start < ms {
+        acc = black_box(acc.wrapping_add(1));
+    }
+}
+
+/// 所有 CPU 的自愿与非自愿切换次数之和
+fn switches() -> Option<(usize, usize)> {
+    let fd = open("/proc/schedstat\0", OpenFlags::RDONLY);
+    if fd < 0 {
+        return None;
+    }
+    let mut buf = [0u8; 1024];
+    let len = read(fd as usize, &mut buf);
+    close(fd as usize);
+    let text = core::str::from_utf8(&buf[..len.max(0) as usize]).ok()?;
+    let mut total = (0, 0);
+    // "cpu<N> <context_switches> <voluntary> <involuntary> <run_time_ns>"
+    for line in text.lines().filter(|line| line.starts_with("cpu")) {
+        let mut fields = line.split_whitespace().skip(2);
+        total.0 += fields.next()?.parse::<usize>().ok()?;
+        total.1 += fields.next()?.parse::<usize>().ok()?;
+    }
+    Some(total)
+}
+
+/// yield 应计入自愿切换；独占 CPU 的忙等不应被时钟中断抢占，
+/// 与兄弟进程同时忙等时用完时间片被抢占，应计入非自愿切换
+#[no_mangle]
+pub fn main() -> i32 {
+    let before = match switches() {
+        Some(before) => before,
+        None => {
+            println!("[schedstat] FAILED: /proc/schedstat missing or malformed");
+            return -1;
+        }
+    };
+    for _ in 0..YIELD_NUM {
+        yield_();
+    }
+    let yielded = switches().unwrap_or(before);
+    spin(SPIN_MS);
+    let alone = switches().unwrap_or(yielded);
+    // 子进程与父进程在同一个 CPU 上排队，两者轮流用完时间片
+    let pid = fork();
+    if pid == 0 {
+        spin(SPIN_MS);
+        exit(0);
+    }
+    spin(SPIN_MS);
+    let mut exit_code: i32 = 0;
+    waitpid(pid as usize, &mut exit_code);
+    let shared = switches().unwrap_or(alone);
+    println!(
+        "[schedstat] voluntary {} -> {}, involuntary {} -> {} (alone) -> {} (shared)",
+        before.0, yielded.0, yielded.1, alone.1, shared.1
+    );
+    if yielded.0 < before.0 + YIELD_NUM {
+        println!("[schedstat] FAILED: yields not counted as voluntary switches");
+        return -1;
+    }
+    // 其他 CPU 上偶尔有进程被抢占，容忍一次
+    if alone.1 > yielded.1 + 1 {
+        println!("[schedstat] FAILED: sole task preempted before its slice ran out");
+        return -1;
+    }
+    if shared.1 <= alone.1 {
+        println!("[schedstat] FAILED: slice expiry not counted as involuntary switch");
+        return -1;
+    }
+    println!("[schedstat] passed");
+    0
+}
@@ -0,0 +1,78 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    close, ftruncate, lseek, open, report, unlink, write, OpenFlags, SEEK_CUR, SEEK_DATA, SEEK_HOLE,
+};
+
+const ENXIO: isize = -6;
+const FILE: &str = "/seek_hole_data\0";
+const DENSE: &str = "/seek_hole_dense\0";
+const DATA_LEN: usize = 100;
+const LENGTH: usize = 1024 * 1024;
+
+/// 开头写入数据再扩展到 1MiB：数据在开头，空洞从数据所在的最后一块之后开始直到末尾
+fn check_sparse(fd: usize) -> Result<(), &'static str> {
+    if write(fd, &[b'd'; DATA_LEN]) != DATA_LEN as isize || ftruncate(fd, LENGTH) != 0 {
+        return Err("cannot create the sparse file");
+    }
+    if lseek(fd, 0, SEEK_DATA) != 0 || lseek(fd, 50, SEEK_DATA) != 50 {
+        return Err("SEEK_DATA inside the data did not stay put");
+    }
+    let hole = lseek(fd, 0, SEEK_HOLE);
+    println!("[seek_hole] hole starts at {}", hole);
+    if hole < DATA_LEN as isize || hole >= LENGTH as isize {
+        return Err("SEEK_HOLE did not find the hole after the data");
+    }
+    if lseek(fd, hole + 10, SEEK_HOLE) != hole + 10 {
+        return Err("SEEK_HOLE inside the hole did not stay put");
+    }
+    let offset = lseek(fd, 0, SEEK_CUR);
+    if lseek(fd, hole, SEEK_DATA) != ENXIO {
+        return Err("SEEK_DATA in the trailing hole did not fail with ENXIO");
+    }
+    if lseek(fd, 0, SEEK_CUR) != offset {
+        return Err("a failed SEEK_DATA moved the offset");
+    }
+    if lseek(fd, LENGTH as isize, SEEK_DATA) != ENXIO
+        || lseek(fd, LENGTH as isize, SEEK_HOLE) != ENXIO
+    {
+        return Err("seeking at the end of file did not fail with ENXIO");
+    }
+    Ok(())
+}
+
+/// 没有空洞的文件整个都是数据，唯一的空洞是文件末尾
+fn check_dense(fd: usize) -> Result<(), &'static str> {
+    if write(fd, &[b'd'; DATA_LEN]) != DATA_LEN as isize {
+        return Err("cannot write the dense file");
+    }
+    if lseek(fd, 10, SEEK_DATA) != 10 {
+        return Err("SEEK_DATA in a dense file did not return the offset");
+    }
+    if lseek(fd, 10, SEEK_HOLE) != DATA_LEN as isize {
+        return Err("SEEK_HOLE in a dense file did not return the file size");
+    }
+    Ok(())
+}
+
+fn run(path: &str, check: fn(usize) -> Result<(), &'static str>) -> Result<(), &'static str> {
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::RDWR);
+    if fd < 0 {
+        return Err("cannot create the test file");
+    }
+    let result = check(fd as usize);
+    close(fd as usize);
+    unlink(path);
+    result
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    report(
+        "seek_hole",
+        run(FILE, check_sparse).and_then(|()| run(DENSE, check_dense)),
+    )
+}
@@ -0,0 +1,118 @@
+#![no_std]
+#![no_main]
+
+extern crate user_lib;
+extern crate alloc;
+use alloc::format;
+use user_lib::{
+    close, exit, fork, getpgid, getpid, getsid, ioctl, open, pipe, read, report, setpgid, setsid,
+    waitpid_options, write, yield_, OpenFlags, SIGHUP, TIOCGPTN, TIOCGSID, TIOCSPGRP, TIOCSPTLCK,
+};
+
+/// 等待子进程 `pid` 结束，返回状态字
+fn wait_exit(pid: isize) -> i32 {
+    let mut status = 0;
+    waitpid_options(pid, &mut status, 0);
+    status
+}
+
+/// 子进程中：建立新会话，检查会话号、进程组号，以及会话首进程不能再调用 setsid
+fn new_session() -> i32 {
+    let sid = setsid();
+    if sid != getpid() {
+        return 1;
+    }
+    if getsid(0) != sid || getpgid(0) != sid {
+        return 2;
+    }
+    if setsid() >= 0 {
+        return 3;
+    }
+    0
+}
+
+/// 子进程中：会话首进程打开的伪终端从设备成为控制终端，前台进程组设为孙进程所在的组，
+/// 主设备关闭后孙进程收到 SIGHUP，终端不再控制这个会话
+fn hang_up() -> i32 {
+    setsid();
+    let master = open("/dev/ptmx", OpenFlags::RDWR);
+    if master < 0 {
+        return 1;
+    }
+    let master = master as usize;
+    let mut index: u32 = 0;
+    let unlock: i32 = 0;
+    ioctl(master, TIOCGPTN, &mut index as *mut u32 as usize);
+    ioctl(master, TIOCSPTLCK, &unlock as *const i32 as usize);
+    let slave = open(format!("/dev/pts/{}", index).as_str(), OpenFlags::RDWR);
+    if slave < 0 {
+        return 2;
+    }
+    let slave = slave as usize;
+    let mut sid: u32 = 0;
+    if ioctl(slave, TIOCGSID, &mut sid as *mut u32 as usize) != 0 || sid as isize != getpid() {
+        return 3;
+    }
+    let mut ready = [0i32; 2];
+    pipe(&mut ready);
+    let pid = fork();
+    if pid == 0 {
+        // 孙进程不能持有主设备，否则关闭主设备不会挂断终端
+        close(master);
+        setpgid(0, 0);
+        write(ready[1] as usize, b"x");
+        loop {
+            yield_();
+        }
+    }
+    setpgid(pid as usize, pid as usize);
+    let pgid = pid as u32;
+    if ioctl(slave, TIOCSPGRP, &pgid as *const u32 as usize) != 0 {
+        return 4;
+    }
+    let mut byte = [0u8; 1];
+    read(ready[0] as usize, &mut byte);
+    close(master);
+    if wait_exit(pid) & 0x7f != SIGHUP as i32 {
+        return 5;
+    }
+    if ioctl(slave, TIOCGSID, &mut sid as *mut u32 as usize) == 0 {
+        return 6;
+    }
+    0
+}
+
+/// 在子进程中运行 `f`，返回它的退出码
+fn run_child(f: fn() -> i32) -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        exit(f());
+    }
+    (wait_exit(pid) >> 8) & 0xff
+}
+
+fn check() -> Result<(), &'static str> {
+    match run_child(new_session) {
+        0 => {}
+        1 => return Err("setsid did not return the caller's pid"),
+        2 => return Err("the new session and process group IDs are not the caller's pid"),
+        _ => return Err("a session leader created another session"),
+    }
+    setpgid(0, 0);
+    if setsid() >= 0 {
+        return Err("a process group leader created a new session");
+    }
+    match run_child(hang_up) {
+        0 => Ok(()),
+        1 | 2 => Err("cannot open a pseudo-terminal pair"),
+        3 => Err("the slave opened by a session leader did not become its controlling terminal"),
+        4 => Err("TIOCSPGRP on the controlling terminal failed"),
+        5 => Err("the foreground process group got no SIGHUP when the master closed"),
+        _ => Err("the hung up terminal still controls the session"),
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    report("session", check())
+}
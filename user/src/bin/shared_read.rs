@@ -0,0 +1,130 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+/// 两个线程经同一个 fd 并发读一个文件：合起来每个字节恰好读到一次，不重复也不遗漏
+#[cfg(target_arch = "riscv64")]
+mod workload {
+    use core::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
+    use user_lib::{close, exit, open, read, report, thread_spawn, unlink, write, OpenFlags};
+
+    const FILE: &str = "/shared_read_data\0";
+    /// 文件由 `WORDS` 个小端 u32 组成，第 k 个的值为 k
+    const WORDS: usize = 4096;
+    /// 每次读 3 个字：读的起点总是 4 字节对齐，又不与页对齐
+    const READ_SIZE: usize = 12;
+    const STACK_SIZE: usize = 16 * 1024;
+
+    static mut STACKS: [[u8; STACK_SIZE]; 1] = [[0; STACK_SIZE]; 1];
+    /// 每个字被读到的次数
+    static SEEN: [AtomicU8; WORDS] = [const { AtomicU8::new(0) }; WORDS];
+    static CORRUPT: AtomicBool = AtomicBool::new(false);
+    static FINISHED: AtomicBool = AtomicBool::new(false);
+    /// 两个线程各读到的字节数
+    static BYTES: [AtomicUsize; 2] = [const { AtomicUsize::new(0) }; 2];
+
+    /// 读到文件末尾，把读到的每个字记入 `SEEN`
+    fn drain(id: usize, fd: usize) {
+        let mut buf = [0u8; READ_SIZE];
+        loop {
+            let len = read(fd, &mut buf);
+            if len <= 0 {
+                return;
+            }
+            let len = len as usize;
+            if len % 4 != 0 {
+                CORRUPT.store(true, Ordering::Relaxed);
+                return;
+            }
+            for word in buf[..len].chunks(4) {
+                let k = u32::from_le_bytes([word[0], word[1], word[2], word[3]]) as usize;
+                if k >= WORDS {
+                    CORRUPT.store(true, Ordering::Relaxed);
+                    return;
+                }
+                SEEN[k].fetch_add(1, Ordering::Relaxed);
+            }
+            BYTES[id].fetch_add(len, Ordering::Relaxed);
+        }
+    }
+
+    extern "C" fn worker(fd: usize) -> ! {
+        drain(1, fd);
+        FINISHED.store(true, Ordering::Release);
+        exit(0)
+    }
+
+    fn create() -> Result<(), &'static str> {
+        let fd = open(FILE, OpenFlags::CREATE | OpenFlags::WRONLY);
+        if fd < 0 {
+            return Err("cannot create the file");
+        }
+        let mut ok = true;
+        for chunk in 0..WORDS / 256 {
+            let mut buf = [0u8; 1024];
+            for (i, word) in buf.chunks_mut(4).enumerate() {
+                word.copy_from_slice(&((chunk * 256 + i) as u32).to_le_bytes());
+            }
+            ok &= write(fd as usize, &buf) == buf.len() as isize;
+        }
+        close(fd as usize);
+        if ok {
+            Ok(())
+        } else {
+            Err("cannot write the file")
+        }
+    }
+
+    fn check() -> Result<(), &'static str> {
+        create()?;
+        let fd = open(FILE, OpenFlags::RDONLY);
+        if fd < 0 {
+            return Err("cannot open the file");
+        }
+        let fd = fd as usize;
+        if thread_spawn(worker, fd, unsafe { &mut STACKS[0] }) < 0 {
+            close(fd);
+            return Err("cannot spawn the second thread");
+        }
+        drain(0, fd);
+        while !FINISHED.load(Ordering::Acquire) {
+            user_lib::yield_();
+        }
+        close(fd);
+        println!(
+            "[shared_read] the threads read {} and {} bytes",
+            BYTES[0].load(Ordering::Relaxed),
+            BYTES[1].load(Ordering::Relaxed)
+        );
+        if CORRUPT.load(Ordering::Relaxed) {
+            return Err("a read did not start on a word boundary");
+        }
+        match SEEN.iter().map(|seen| seen.load(Ordering::Relaxed)).max() {
+            Some(count) if count > 1 => return Err("some bytes were read twice"),
+            _ => {}
+        }
+        if SEEN.iter().any(|seen| seen.load(Ordering::Relaxed) == 0) {
+            return Err("some bytes were skipped");
+        }
+        Ok(())
+    }
+
+    pub fn main() -> i32 {
+        let result = check();
+        unlink(FILE);
+        report("shared_read", result)
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    #[cfg(target_arch = "riscv64")]
+    return workload::main();
+    #[cfg(not(target_arch = "riscv64"))]
+    {
+        println!("[shared_read] skipped: threads are only spawned on riscv64");
+        0
+    }
+}
@@ -0,0 +1,58 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{close, ftruncate, lseek, open, read, report, unlink, write, OpenFlags, SEEK_SET};
+
+const FILE: &str = "/short_read_data\0";
+
+/// 读到文件末尾时只返回剩余的字节数，到达或越过末尾后返回 0
+fn check(fd: usize) -> Result<(), &'static str> {
+    if write(fd, &[b'x'; 100]) != 100 {
+        return Err("cannot write 100 bytes");
+    }
+    let mut buf = [0u8; 200];
+    if lseek(fd, 0, SEEK_SET) != 0 {
+        return Err("cannot seek to the start");
+    }
+    if read(fd, &mut buf) != 100 {
+        return Err("a 200-byte read of a 100-byte file did not return 100");
+    }
+    if buf[..100].iter().any(|&byte| byte != b'x') {
+        return Err("the data read back differs");
+    }
+    if read(fd, &mut buf) != 0 {
+        return Err("a read at the end of file did not return 0");
+    }
+    if lseek(fd, 150, SEEK_SET) != 150 || read(fd, &mut buf) != 0 {
+        return Err("a read past the end of file did not return 0");
+    }
+    // 另一个 fd 截短文件后，停在旧数据中间的读只能读到新的末尾
+    let other = open(FILE, OpenFlags::RDWR);
+    if other < 0 {
+        return Err("cannot open the file a second time");
+    }
+    let truncated = ftruncate(other as usize, 40);
+    close(other as usize);
+    if truncated != 0 {
+        return Err("ftruncate failed");
+    }
+    if lseek(fd, 30, SEEK_SET) != 30 || read(fd, &mut buf) != 10 {
+        return Err("a read across the truncated end did not return the remaining 10 bytes");
+    }
+    Ok(())
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open(FILE, OpenFlags::CREATE | OpenFlags::RDWR);
+    if fd < 0 {
+        println!("[short_read] FAILED: cannot create {}", FILE);
+        return -1;
+    }
+    let result = check(fd as usize);
+    close(fd as usize);
+    unlink(FILE);
+    report("short_read", result)
+}
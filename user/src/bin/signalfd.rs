@@ -0,0 +1,138 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+/// 屏蔽的信号经 signalfd 读出：不递送、可被 epoll 等待、读出后不再挂起
+#[cfg(target_arch = "riscv64")]
+mod workload {
+    use user_lib::{
+        close, epoll_create1, epoll_ctl, epoll_wait, exit, fork, getpid, kill, read, report,
+        signalfd, sigprocmask, sleep, waitpid, EpollEvent, EPOLLIN, EPOLL_CTL_ADD, SFD_NONBLOCK,
+        SIGUSR1, SIGUSR2, SIG_BLOCK, SIG_UNBLOCK,
+    };
+
+    const EAGAIN: isize = -11;
+    const EINVAL: isize = -22;
+    const SIGINFO_SIZE: usize = 128;
+    const USR1: u64 = 1 << (SIGUSR1 - 1);
+    const USR2: u64 = 1 << (SIGUSR2 - 1);
+
+    /// 读出一个 `signalfd_siginfo`，返回其中的信号编号
+    fn read_signal(fd: usize) -> Result<usize, isize> {
+        let mut buf = [0u8; 2 * SIGINFO_SIZE];
+        match read(fd, &mut buf) {
+            len if len < 0 => Err(len),
+            len if len as usize == SIGINFO_SIZE => {
+                Ok(u32::from_ne_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize)
+            }
+            _ => Err(EINVAL),
+        }
+    }
+
+    fn check_nonblock(sfd: usize) -> Result<(), &'static str> {
+        if read_signal(sfd) != Err(EAGAIN) {
+            return Err("a read with no pending signal did not fail with EAGAIN");
+        }
+        let mut small = [0u8; SIGINFO_SIZE / 2];
+        if read(sfd, &mut small) != EINVAL {
+            return Err("a read into a buffer smaller than signalfd_siginfo did not fail");
+        }
+        let epfd = epoll_create1(0);
+        if epfd < 0 {
+            return Err("cannot create an epoll instance");
+        }
+        let epfd = epfd as usize;
+        let mut events = [EpollEvent::default(); 1];
+        let event = EpollEvent {
+            events: EPOLLIN,
+            data: 0,
+        };
+        let added = epoll_ctl(epfd, EPOLL_CTL_ADD, sfd, &event);
+        let idle = epoll_wait(epfd, &mut events, 0);
+        kill(getpid() as usize, SIGUSR1);
+        let ready = epoll_wait(epfd, &mut events, 0);
+        close(epfd);
+        if added != 0 || idle != 0 {
+            return Err("the signalfd was ready with no pending signal");
+        }
+        if ready != 1 || events[0].events & EPOLLIN == 0 {
+            return Err("a pending signal did not make the signalfd readable");
+        }
+        if read_signal(sfd) != Ok(SIGUSR1) {
+            return Err("the pending SIGUSR1 was not read");
+        }
+        if read_signal(sfd) != Err(EAGAIN) {
+            return Err("a signal that was read stayed pending");
+        }
+        // 不在信号集中的信号读不出，替换信号集后才能读出
+        kill(getpid() as usize, SIGUSR2);
+        if read_signal(sfd) != Err(EAGAIN) {
+            return Err("a signal outside the mask was read");
+        }
+        if signalfd(sfd as isize, USR1 | USR2, 0) != sfd as isize {
+            return Err("cannot change the mask of the signalfd");
+        }
+        if read_signal(sfd) != Ok(SIGUSR2) {
+            return Err("SIGUSR2 was not read after adding it to the mask");
+        }
+        Ok(())
+    }
+
+    /// 阻塞的读等到子进程发来信号
+    fn check_blocking() -> Result<(), &'static str> {
+        let sfd = signalfd(-1, USR1, 0);
+        if sfd < 0 {
+            return Err("cannot create a blocking signalfd");
+        }
+        let sfd = sfd as usize;
+        let parent = getpid() as usize;
+        let pid = fork();
+        if pid == 0 {
+            sleep(50);
+            kill(parent, SIGUSR1);
+            exit(0);
+        }
+        let result = read_signal(sfd);
+        let mut exit_code = 0;
+        waitpid(pid as usize, &mut exit_code);
+        close(sfd);
+        if result != Ok(SIGUSR1) {
+            return Err("a blocking read did not return the signal sent later");
+        }
+        Ok(())
+    }
+
+    fn check() -> Result<(), &'static str> {
+        let sfd = signalfd(-1, USR1, SFD_NONBLOCK);
+        if sfd < 0 {
+            return Err("cannot create a signalfd");
+        }
+        let result = check_nonblock(sfd as usize);
+        close(sfd as usize);
+        result?;
+        check_blocking()
+    }
+
+    pub fn main() -> i32 {
+        if sigprocmask(SIG_BLOCK, USR1 | USR2) != 0 {
+            println!("[signalfd] FAILED: cannot block SIGUSR1 and SIGUSR2");
+            return -1;
+        }
+        let result = check();
+        sigprocmask(SIG_UNBLOCK, USR1 | USR2);
+        report("signalfd", result)
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    #[cfg(target_arch = "riscv64")]
+    return workload::main();
+    #[cfg(not(target_arch = "riscv64"))]
+    {
+        println!("[signalfd] skipped: six-argument system calls are only passed on riscv64");
+        0
+    }
+}
@@ -0,0 +1,137 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+#[cfg(target_arch = "riscv64")]
+use user_lib::{ptrace, PTRACE_CONT, PTRACE_SINGLESTEP};
+
+/// 单步执行的指令条数，与 `stepped_block` 中的 `.rept` 一致
+#[cfg(target_arch = "riscv64")]
+const STEP_NUM: usize = 16;
+#[cfg(target_arch = "riscv64")]
+const EIO: isize = -5;
+
+#[cfg(target_arch = "riscv64")]
+static mut TRAPS: usize = 0;
+/// 断点位置落在被单步代码块之内的 SIGTRAP 次数
+#[cfg(target_arch = "riscv64")]
+static mut STEPS: usize = 0;
+#[cfg(target_arch = "riscv64")]
+static mut BLOCK: [usize; 2] = [0; 2];
+
+/// ucontext 中 mcontext.pc 的偏移：flags, link, stack (24 字节), sigmask, 128 字节填充
+#[cfg(target_arch = "riscv64")]
+const UCONTEXT_PC: usize = 176;
+
+/// 单步在指令执行完之后陷入，此时的 pc 是下一条指令，
+/// 因此代码块中第 i 条指令对应的 pc 落在 (start, end] 之内
+#[cfg(target_arch = "riscv64")]
+extern "C" fn on_sigtrap(_signum: usize, _info: usize, ucontext: usize) {
+    unsafe {
+        let pc = *((ucontext + UCONTEXT_PC) as *const usize);
+        TRAPS += 1;
+        if BLOCK[0] < pc && pc <= BLOCK[1] {
+            STEPS += 1;
+        }
+    }
+}
+
+/// 打开单步，执行 STEP_NUM 条 addi，再关闭单步，返回 addi 的累加结果
+#[cfg(target_arch = "riscv64")]
+fn stepped_block() -> usize {
+    use core::arch::asm;
+    let mut count: usize = 0;
+    unsafe {
+        asm!(
+            ".option push",
+            ".option norvc",
+            "la t0, 1f",
+            "sd t0, 0({block})",
+            "la t0, 2f",
+            "sd t0, 8({block})",
+            // ptrace(PTRACE_SINGLESTEP, 0, 0, 0)
+            "li a7, 117",
+            "li a0, 9",
+            "li a1, 0",
+            "li a2, 0",
+            "li a3, 0",
+            "ecall",
+            "1:",
+            ".rept 16",
+            "addi {count}, {count}, 1",
+            ".endr",
+            "2:",
+            // ptrace(PTRACE_CONT, 0, 0, 0)
+            "li a7, 117",
+            "li a0, 7",
+            "li a1, 0",
+            "li a2, 0",
+            "li a3, 0",
+            "ecall",
+            ".option pop",
+            block = in(reg) core::ptr::addr_of_mut!(BLOCK),
+            count = inout(reg) count,
+            out("t0") _,
+            out("a0") _,
+            out("a1") _,
+            out("a2") _,
+            out("a3") _,
+            out("a7") _,
+        );
+    }
+    count
+}
+
+/// 单步一段固定长度的代码，SIGTRAP 的次数应与其中执行的指令条数相同
+#[cfg(target_arch = "riscv64")]
+#[no_mangle]
+pub fn main() -> i32 {
+    use user_lib::{sigaction, SigAction, SA_SIGINFO, SIGTRAP};
+    let act = SigAction {
+        handler: on_sigtrap as usize,
+        flags: SA_SIGINFO,
+        restorer: 0,
+        mask: 0,
+    };
+    if sigaction(SIGTRAP, &act) != 0 {
+        println!("[single_step] FAILED: sigaction");
+        return -1;
+    }
+    match ptrace(PTRACE_SINGLESTEP, 0, 0, 0) {
+        0 => {
+            ptrace(PTRACE_CONT, 0, 0, 0);
+        }
+        EIO => {
+            println!("[single_step] skipped: no hardware single-step support");
+            return 0;
+        }
+        err => {
+            println!("[single_step] FAILED: ptrace returned {}", err);
+            return -1;
+        }
+    }
+    unsafe { TRAPS = 0 };
+    let count = stepped_block();
+    let (traps, steps) = unsafe { (TRAPS, STEPS) };
+    println!(
+        "[single_step] {} instructions stepped, {} SIGTRAPs in total",
+        steps, traps
+    );
+    if count != STEP_NUM || steps != STEP_NUM {
+        println!(
+            "[single_step] FAILED: expected {} SIGTRAPs in the block, got {}",
+            STEP_NUM, steps
+        );
+        return -1;
+    }
+    println!("[single_step] passed");
+    0
+}
+
+#[cfg(not(target_arch = "riscv64"))]
+#[no_mangle]
+pub fn main() -> i32 {
+    println!("[single_step] skipped: riscv64 only");
+    0
+}
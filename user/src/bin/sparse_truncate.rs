@@ -0,0 +1,83 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{
+    close, ftruncate, lseek, open, read, report, statfs, unlink, write, OpenFlags, Statfs,
+    SEEK_END, SEEK_SET,
+};
+
+const FILE: &str = "/sparse_truncate_data\0";
+const LENGTH: usize = 1024 * 1024;
+
+/// 文件所在文件系统的空闲字节数
+fn free_bytes() -> Option<u64> {
+    let mut buf = Statfs::default();
+    if statfs(FILE, &mut buf) != 0 {
+        return None;
+    }
+    Some(buf.f_bfree * buf.f_bsize as u64)
+}
+
+/// 把文件扩展到 1MiB：扩展出的部分读出 0，且在写入之前不占用磁盘空间
+fn check(fd: usize) -> Result<(), &'static str> {
+    let before = free_bytes().ok_or("statfs failed")?;
+    if ftruncate(fd, LENGTH) != 0 {
+        return Err("ftruncate failed");
+    }
+    if lseek(fd, 0, SEEK_END) != LENGTH as isize {
+        return Err("the file size is not 1MiB");
+    }
+    if lseek(fd, 0, SEEK_SET) != 0 {
+        return Err("cannot seek to the start");
+    }
+    let mut buf = [0xffu8; 4096];
+    let mut total = 0;
+    loop {
+        let len = read(fd, &mut buf);
+        if len < 0 {
+            return Err("read failed");
+        }
+        if len == 0 {
+            break;
+        }
+        if buf[..len as usize].iter().any(|&byte| byte != 0) {
+            return Err("the extended region does not read as zeros");
+        }
+        total += len as usize;
+    }
+    if total != LENGTH {
+        return Err("the reads did not return 1MiB");
+    }
+    let after = free_bytes().ok_or("statfs failed")?;
+    println!("[sparse_truncate] free bytes {} then {}", before, after);
+    if before.saturating_sub(after) >= LENGTH as u64 {
+        return Err("the free space dropped by 1MiB");
+    }
+    // 写入空洞后数据可读回，写入位置之后仍读出 0
+    if lseek(fd, 8192, SEEK_SET) != 8192 || write(fd, b"data") != 4 {
+        return Err("cannot write into the hole");
+    }
+    let mut back = [0xffu8; 8];
+    if lseek(fd, 8192, SEEK_SET) != 8192 || read(fd, &mut back) != 8 {
+        return Err("cannot read the written data back");
+    }
+    if &back != b"data\0\0\0\0" {
+        return Err("the data written into the hole reads back wrong");
+    }
+    Ok(())
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open(FILE, OpenFlags::CREATE | OpenFlags::RDWR);
+    if fd < 0 {
+        println!("[sparse_truncate] FAILED: cannot create {}", FILE);
+        return -1;
+    }
+    let result = check(fd as usize);
+    close(fd as usize);
+    unlink(FILE);
+    report("sparse_truncate", result)
+}
@@ -0,0 +1,131 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+/// 在目录上挂载 tmpfs，listmount 应列出它，statmount 应返回其挂载点与文件系统类型；
+/// 卸载后 listmount 不再列出它
+#[cfg(target_arch = "riscv64")]
+mod workload {
+    use user_lib::{
+        close, listmount, mkdir, mount, open, read, report, rmdir, statmount, umount, OpenFlags,
+        Statmount, LSMT_ROOT, STATMOUNT_FS_TYPE, STATMOUNT_MNT_BASIC, STATMOUNT_MNT_POINT,
+        STATMOUNT_SB_BASIC,
+    };
+
+    const DIR: &str = "/statmount_mnt\0";
+    const TMPFS_MAGIC: u64 = 0x01021994;
+    const MAX_MOUNTS: usize = 16;
+
+    /// 每次只取一个 id，逐个列出全部挂载，返回挂载数
+    fn list_all(ids: &mut [u64; MAX_MOUNTS]) -> Result<usize, &'static str> {
+        let mut count = 0;
+        let mut last_id = 0;
+        while count < MAX_MOUNTS {
+            match listmount(LSMT_ROOT, last_id, &mut ids[count..count + 1]) {
+                0 => return Ok(count),
+                1 => {
+                    last_id = ids[count];
+                    count += 1;
+                }
+                _ => return Err("listmount failed"),
+            }
+        }
+        Err("too many mounts")
+    }
+
+    /// 列出的挂载中挂载点为 `DIR` 的那个
+    fn find_tmpfs(ids: &[u64], buf: &mut Statmount) -> Result<Option<u64>, &'static str> {
+        let mask = STATMOUNT_MNT_BASIC | STATMOUNT_MNT_POINT | STATMOUNT_FS_TYPE;
+        for &id in ids {
+            if statmount(id, mask, buf) != 0 {
+                return Err("statmount of a listed mount failed");
+            }
+            if buf.mnt_id != id {
+                return Err("statmount returned another mount id");
+            }
+            if buf.string(buf.mnt_point) == DIR.trim_end_matches('\0') {
+                return Ok(Some(id));
+            }
+        }
+        Ok(None)
+    }
+
+    /// `/proc/mounts` 中是否有 tmpfs 挂载在 `DIR` 上
+    fn in_proc_mounts() -> bool {
+        let fd = open("/proc/mounts\0", OpenFlags::RDONLY);
+        if fd < 0 {
+            return false;
+        }
+        let mut buf = [0u8; 1024];
+        let len = read(fd as usize, &mut buf);
+        close(fd as usize);
+        len > 0
+            && core::str::from_utf8(&buf[..len as usize]).map_or(false, |mounts| {
+                mounts.contains("tmpfs /statmount_mnt tmpfs rw")
+            })
+    }
+
+    fn check(buf: &mut Statmount) -> Result<(), &'static str> {
+        let mut ids = [0u64; MAX_MOUNTS];
+        let count = list_all(&mut ids)?;
+        let id = find_tmpfs(&ids[..count], buf)?.ok_or("the tmpfs mount was not listed")?;
+        let mut all = [0u64; MAX_MOUNTS];
+        if listmount(LSMT_ROOT, 0, &mut all) as usize != count || all[..count] != ids[..count] {
+            return Err("listing at once differs from listing one by one");
+        }
+        let mask = STATMOUNT_SB_BASIC | STATMOUNT_MNT_BASIC | STATMOUNT_FS_TYPE;
+        if statmount(id, mask, buf) != 0 {
+            return Err("statmount of the tmpfs mount failed");
+        }
+        if buf.string(buf.fs_type) != "tmpfs" || buf.sb_magic != TMPFS_MAGIC {
+            return Err("statmount did not report a tmpfs");
+        }
+        let parent = buf.mnt_parent_id;
+        if statmount(parent, STATMOUNT_MNT_POINT, buf) != 0 || buf.string(buf.mnt_point) != "/" {
+            return Err("the parent of the tmpfs mount is not the root mount");
+        }
+        if !in_proc_mounts() {
+            return Err("/proc/mounts does not list the tmpfs mount");
+        }
+        Ok(())
+    }
+
+    pub fn main() -> i32 {
+        let mut buf = Statmount::new();
+        let buf = &mut buf;
+        if mkdir(DIR) < 0 {
+            println!("[statmount] FAILED: cannot create the mount point");
+            return -1;
+        }
+        let result = if mount("tmpfs\0", DIR, "tmpfs\0", 0) != 0 {
+            Err("mount failed")
+        } else {
+            let result = check(buf);
+            if umount(DIR) != 0 {
+                Err("umount failed")
+            } else {
+                let mut ids = [0u64; MAX_MOUNTS];
+                match list_all(&mut ids).and_then(|count| find_tmpfs(&ids[..count], buf)) {
+                    Ok(None) => result,
+                    Ok(Some(_)) => Err("the tmpfs mount is still listed after umount"),
+                    Err(reason) => Err(reason),
+                }
+            }
+        };
+        rmdir(DIR);
+        report("statmount", result)
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    #[cfg(target_arch = "riscv64")]
+    return workload::main();
+    #[cfg(not(target_arch = "riscv64"))]
+    {
+        println!("[statmount] skipped: the loongarch64 syscall stub passes only three arguments");
+        0
+    }
+}
@@ -0,0 +1,53 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use core::ptr::{addr_of_mut, read_volatile, write_volatile};
+use user_lib::{exit, fork, getpid, waitpid};
+
+const PAGE_SIZE: usize = 4096;
+const PAGE_NUM: usize = 64;
+
+// 位于 .bss，首次访问每一页时都会触发缺页异常
+static mut LAZY_BUF: [u8; PAGE_NUM * PAGE_SIZE] = [0; PAGE_NUM * PAGE_SIZE];
+
+/// 逐页写入并在每次缺页之间穿插系统调用，检查两条陷入路径互不干扰
+fn touch_pages(pid: isize) -> bool {
+    let buf = unsafe { &mut *addr_of_mut!(LAZY_BUF) };
+    for i in 0..PAGE_NUM {
+        unsafe { write_volatile(&mut buf[i * PAGE_SIZE], i as u8) };
+        if getpid() != pid {
+            println!("[trap_parity] getpid changed after page fault on page {}", i);
+            return false;
+        }
+    }
+    (0..PAGE_NUM).all(|i| unsafe { read_volatile(&buf[i * PAGE_SIZE]) } == i as u8)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    println!("[trap_parity] page faults interleaved with syscalls");
+
+    let pid = getpid();
+    if !touch_pages(pid) {
+        println!("[trap_parity] FAILED: lazy page contents");
+        return -1;
+    }
+
+    // 非法访问应以 SIGSEGV 结束子进程，而不是让内核 panic
+    let child = fork();
+    if child == 0 {
+        unsafe { write_volatile(8 as *mut usize, 0) };
+        exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    waitpid(child as usize, &mut exit_code);
+    if exit_code == 0 {
+        println!("[trap_parity] FAILED: bad access was not signalled");
+        return -1;
+    }
+
+    println!("[trap_parity] passed");
+    0
+}
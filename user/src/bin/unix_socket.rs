@@ -0,0 +1,223 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+/// AF_UNIX 的流与数据报套接字：socketpair、路径地址与抽象地址
+#[cfg(target_arch = "riscv64")]
+mod workload {
+    use user_lib::{
+        accept, bind, close, connect, getsockname, listen, read, recvfrom, report, sendto, socket,
+        socketpair, unlink, write, SockaddrUn, AF_UNIX, SOCK_DGRAM, SOCK_NONBLOCK, SOCK_STREAM,
+    };
+
+    const ENOENT: isize = -2;
+    const EAGAIN: isize = -11;
+    const EADDRINUSE: isize = -98;
+    const ECONNREFUSED: isize = -111;
+    const PATH: &str = "/tmp/unix_socket_test.sock";
+    const PATH_Z: &str = "/tmp/unix_socket_test.sock\0";
+
+    fn new_socket(socket_type: usize) -> Result<usize, &'static str> {
+        let fd = socket(AF_UNIX, socket_type, 0);
+        if fd < 0 {
+            return Err("cannot create a socket");
+        }
+        Ok(fd as usize)
+    }
+
+    /// 流套接字对：数据双向可达，一端关闭后另一端读到文件末尾
+    fn check_stream_pair() -> Result<(), &'static str> {
+        let mut sv = [0u32; 2];
+        if socketpair(AF_UNIX, SOCK_STREAM, 0, &mut sv) != 0 {
+            return Err("socketpair(SOCK_STREAM) failed");
+        }
+        let (a, b) = (sv[0] as usize, sv[1] as usize);
+        let mut buf = [0u8; 16];
+        if write(a, b"ping") != 4 || read(b, &mut buf) != 4 || &buf[..4] != b"ping" {
+            return Err("data did not travel from the first socket to the second");
+        }
+        if write(b, b"pong") != 4 || read(a, &mut buf) != 4 || &buf[..4] != b"pong" {
+            return Err("data did not travel from the second socket to the first");
+        }
+        close(a);
+        let ret = read(b, &mut buf);
+        close(b);
+        if ret != 0 {
+            return Err("reading after the peer closed did not return end of file");
+        }
+        Ok(())
+    }
+
+    /// 数据报套接字对：每次读出一个完整的报文
+    fn check_dgram_pair() -> Result<(), &'static str> {
+        let mut sv = [0u32; 2];
+        if socketpair(AF_UNIX, SOCK_DGRAM, 0, &mut sv) != 0 {
+            return Err("socketpair(SOCK_DGRAM) failed");
+        }
+        let (a, b) = (sv[0] as usize, sv[1] as usize);
+        let mut buf = [0u8; 16];
+        let ok = write(a, b"one") == 3
+            && write(a, b"three") == 5
+            && read(b, &mut buf) == 3
+            && read(b, &mut buf) == 5
+            && &buf[..5] == b"three";
+        close(a);
+        close(b);
+        if !ok {
+            return Err("datagram boundaries were not preserved");
+        }
+        Ok(())
+    }
+
+    /// bind 到路径上创建套接字文件，connect 经路径找到监听者
+    fn check_path_stream(listener: usize) -> Result<(), &'static str> {
+        let (addr, len) = SockaddrUn::path(PATH);
+        if bind(listener, &addr, len) != 0 {
+            return Err("cannot bind to a path");
+        }
+        let other = new_socket(SOCK_STREAM)?;
+        let ret = bind(other, &addr, len);
+        close(other);
+        if ret != EADDRINUSE {
+            return Err("binding an existing path did not fail with EADDRINUSE");
+        }
+        let mut name = SockaddrUn::default();
+        let mut name_len = core::mem::size_of::<SockaddrUn>() as u32;
+        if getsockname(listener, &mut name, &mut name_len) != 0
+            || name_len as usize != len
+            || &name.sun_path[..PATH.len()] != PATH.as_bytes()
+        {
+            return Err("getsockname did not return the bound path");
+        }
+        if listen(listener, 4) != 0 {
+            return Err("listen failed");
+        }
+        // 连接进入 backlog 后 connect 即返回，同一进程中可以再 accept
+        let client = new_socket(SOCK_STREAM)?;
+        if connect(client, &addr, len) != 0 {
+            close(client);
+            return Err("cannot connect to the bound path");
+        }
+        let mut peer = SockaddrUn::default();
+        let mut peer_len = core::mem::size_of::<SockaddrUn>() as u32;
+        let server = accept(listener, &mut peer, &mut peer_len);
+        if server < 0 {
+            close(client);
+            return Err("accept failed");
+        }
+        let server = server as usize;
+        let mut buf = [0u8; 16];
+        let ok = write(client, b"hello") == 5
+            && read(server, &mut buf) == 5
+            && &buf[..5] == b"hello"
+            && write(server, b"world") == 5
+            && read(client, &mut buf) == 5
+            && &buf[..5] == b"world";
+        close(client);
+        close(server);
+        if !ok {
+            return Err("data did not travel over the accepted connection");
+        }
+        // 客户端没有绑定地址，对端地址只有地址族
+        if peer_len != 2 {
+            return Err("accept did not report an unnamed peer");
+        }
+        Ok(())
+    }
+
+    /// 没有连接等待时非阻塞的 accept 返回 EAGAIN
+    fn check_nonblock_accept() -> Result<(), &'static str> {
+        let listener = new_socket(SOCK_STREAM | SOCK_NONBLOCK)?;
+        let (addr, len) = SockaddrUn::abstract_name("unix_socket_nonblock");
+        let mut peer = SockaddrUn::default();
+        let mut peer_len = core::mem::size_of::<SockaddrUn>() as u32;
+        let ok = bind(listener, &addr, len) == 0
+            && listen(listener, 0) == 0
+            && accept(listener, &mut peer, &mut peer_len) == EAGAIN;
+        close(listener);
+        if !ok {
+            return Err("a nonblocking accept without connections did not fail with EAGAIN");
+        }
+        Ok(())
+    }
+
+    /// 抽象地址的数据报套接字：名字不出现在文件系统中，关闭后名字被释放
+    fn check_abstract_dgram() -> Result<(), &'static str> {
+        let (addr, len) = SockaddrUn::abstract_name("unix_socket_test");
+        let server = new_socket(SOCK_DGRAM)?;
+        if bind(server, &addr, len) != 0 {
+            close(server);
+            return Err("cannot bind to an abstract name");
+        }
+        let client = new_socket(SOCK_DGRAM)?;
+        let sent = sendto(client, b"datagram", Some((&addr, len)));
+        let mut buf = [0u8; 16];
+        let mut sender = SockaddrUn::default();
+        let mut sender_len = core::mem::size_of::<SockaddrUn>() as u32;
+        let received = recvfrom(server, &mut buf, &mut sender, &mut sender_len);
+        close(client);
+        close(server);
+        if sent != 8 || received != 8 || &buf[..8] != b"datagram" {
+            return Err("the datagram was not delivered to the abstract name");
+        }
+        let again = new_socket(SOCK_DGRAM)?;
+        let ret = bind(again, &addr, len);
+        close(again);
+        if ret != 0 {
+            return Err("the abstract name was not released when its socket closed");
+        }
+        Ok(())
+    }
+
+    /// 路径不存在时 connect 返回 ENOENT，监听者关闭后返回 ECONNREFUSED
+    fn check_refused() -> Result<(), &'static str> {
+        let (addr, len) = SockaddrUn::path(PATH);
+        let client = new_socket(SOCK_STREAM)?;
+        let ret = connect(client, &addr, len);
+        close(client);
+        if ret != ECONNREFUSED {
+            return Err("connecting to a closed listener did not fail with ECONNREFUSED");
+        }
+        if unlink(PATH_Z) != 0 {
+            return Err("cannot unlink the socket file");
+        }
+        let client = new_socket(SOCK_STREAM)?;
+        let ret = connect(client, &addr, len);
+        close(client);
+        if ret != ENOENT {
+            return Err("connecting to a removed path did not fail with ENOENT");
+        }
+        Ok(())
+    }
+
+    fn check() -> Result<(), &'static str> {
+        check_stream_pair()?;
+        check_dgram_pair()?;
+        let listener = new_socket(SOCK_STREAM)?;
+        let result = check_path_stream(listener);
+        close(listener);
+        result?;
+        check_nonblock_accept()?;
+        check_abstract_dgram()?;
+        check_refused()
+    }
+
+    pub fn main() -> i32 {
+        let result = check();
+        unlink(PATH_Z);
+        report("unix_socket", result)
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    #[cfg(target_arch = "riscv64")]
+    return workload::main();
+    #[cfg(not(target_arch = "riscv64"))]
+    {
+        println!("[unix_socket] skipped: six-argument system calls are only passed on riscv64");
+        0
+    }
+}
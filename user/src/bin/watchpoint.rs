@@ -0,0 +1,49 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use core::ptr::{addr_of_mut, write_volatile};
+use user_lib::{exit, fork, waitpid, watchpoint, WATCH_WRITE};
+
+const SIGTRAP: i32 = 5;
+const ENODEV: isize = -19;
+/// 子进程因固件不支持硬件触发器而退出时的退出码
+const EXIT_UNSUPPORTED: i32 = 2;
+
+static mut WATCHED: usize = 0;
+static mut UNWATCHED: usize = 0;
+
+/// 子进程在 WATCHED 上设置写观察点，先写另一个变量，再写 WATCHED，
+/// 应在第二次写入时被 SIGTRAP 终止。
+/// 需要支持 SBI DBTR 扩展的固件 (OpenSBI 1.5 及以上)，否则跳过。
+#[no_mangle]
+pub fn main() -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        let addr = unsafe { addr_of_mut!(WATCHED) } as usize;
+        match watchpoint(addr, WATCH_WRITE) {
+            0 => {}
+            ENODEV => exit(EXIT_UNSUPPORTED),
+            _ => exit(1),
+        }
+        unsafe {
+            write_volatile(addr_of_mut!(UNWATCHED), 1);
+            write_volatile(addr_of_mut!(WATCHED), 1);
+        }
+        // 观察点没有触发
+        exit(0);
+    }
+    let mut status: i32 = 0;
+    waitpid(pid as usize, &mut status);
+    if status == EXIT_UNSUPPORTED << 8 {
+        println!("[watchpoint] skipped: no hardware trigger support");
+        return 0;
+    }
+    if status & 0x7f != SIGTRAP {
+        println!("[watchpoint] FAILED: child exit status {:#x}", status);
+        return -1;
+    }
+    println!("[watchpoint] passed");
+    0
+}
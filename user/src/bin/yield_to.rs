@@ -0,0 +1,156 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+/// 几个线程在同一个 CPU 上等待另一个线程持有的锁，忙等一会后让出 CPU；
+/// 定向让给持锁线程时，它应比普通让出时更快地完成临界区并释放锁
+#[cfg(target_arch = "riscv64")]
+mod workload {
+    use core::hint::black_box;
+    use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use user_lib::{close, exit, get_time, open, thread_spawn, write, yield_, yield_to, OpenFlags};
+
+    /// 等锁的线程，不含同样等锁的主线程
+    const WAITERS: usize = 2;
+    const STACK_SIZE: usize = 16 * 1024;
+    /// 持锁线程独占 CPU 时临界区的时长，跨过数个时钟中断
+    const HOLD_MS: usize = 100;
+    /// 等锁的线程每次让出前忙等的时长
+    const SPIN_MS: isize = 5;
+    const CPUSET_CPUS: &str = "/sys/fs/cgroup/cpuset.cpus\0";
+
+    static mut STACKS: [[u8; STACK_SIZE]; WAITERS + 1] = [[0; STACK_SIZE]; WAITERS + 1];
+    static LOCKED: AtomicBool = AtomicBool::new(false);
+    static RELEASED: AtomicBool = AtomicBool::new(false);
+    /// 持锁线程的 tid
+    static HOLDER: AtomicUsize = AtomicUsize::new(0);
+    /// 临界区的循环次数
+    static WORK: AtomicUsize = AtomicUsize::new(0);
+    /// 从取得锁到释放锁经过的毫秒数
+    static HELD_MS: AtomicUsize = AtomicUsize::new(0);
+    static DONATE: AtomicBool = AtomicBool::new(false);
+    static FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+    fn spin_iterations(iterations: usize) {
+        let mut acc = 0usize;
+        for _ in 0..iterations {
+            acc = black_box(acc.wrapping_add(1));
+        }
+    }
+
+    extern "C" fn holder(_: usize) -> ! {
+        let start = get_time();
+        LOCKED.store(true, Ordering::Release);
+        spin_iterations(WORK.load(Ordering::Relaxed));
+        HELD_MS.store((get_time() - start) as usize, Ordering::Relaxed);
+        LOCKED.store(false, Ordering::Release);
+        RELEASED.store(true, Ordering::Release);
+        FINISHED.fetch_add(1, Ordering::Release);
+        exit(0)
+    }
+
+    /// 等到锁被释放：锁被持有时忙等 `SPIN_MS`，仍未释放就让出 CPU
+    fn wait_for_release() {
+        while !RELEASED.load(Ordering::Acquire) {
+            if LOCKED.load(Ordering::Acquire) {
+                let start = get_time();
+                while LOCKED.load(Ordering::Acquire) && get_time() - start < SPIN_MS {
+                    core::hint::spin_loop();
+                }
+                if !LOCKED.load(Ordering::Acquire) {
+                    continue;
+                }
+            }
+            if DONATE.load(Ordering::Relaxed) {
+                yield_to(HOLDER.load(Ordering::Relaxed));
+            } else {
+                yield_();
+            }
+        }
+    }
+
+    extern "C" fn waiter(_: usize) -> ! {
+        wait_for_release();
+        FINISHED.fetch_add(1, Ordering::Release);
+        exit(0)
+    }
+
+    /// 返回持锁线程从取得锁到释放锁所用的毫秒数
+    fn run(donate: bool) -> Option<usize> {
+        LOCKED.store(false, Ordering::Relaxed);
+        RELEASED.store(false, Ordering::Relaxed);
+        FINISHED.store(0, Ordering::Relaxed);
+        DONATE.store(donate, Ordering::Relaxed);
+        let tid = thread_spawn(holder, 0, unsafe { &mut STACKS[0] });
+        if tid < 0 {
+            return None;
+        }
+        HOLDER.store(tid as usize, Ordering::Relaxed);
+        for i in 1..=WAITERS {
+            if thread_spawn(waiter, i, unsafe { &mut STACKS[i] }) < 0 {
+                return None;
+            }
+        }
+        wait_for_release();
+        while FINISHED.load(Ordering::Acquire) < WAITERS + 1 {
+            yield_();
+        }
+        Some(HELD_MS.load(Ordering::Relaxed))
+    }
+
+    pub fn main() -> i32 {
+        // 所有线程都在 CPU 0 上排队
+        let fd = open(CPUSET_CPUS, OpenFlags::WRONLY);
+        let written = if fd < 0 {
+            fd
+        } else {
+            write(fd as usize, b"0\n")
+        };
+        if fd >= 0 {
+            close(fd as usize);
+        }
+        if written < 0 {
+            println!("[yield_to] FAILED: cannot confine the process to CPU 0");
+            return -1;
+        }
+        yield_();
+        // 校准临界区的循环次数
+        let start = get_time();
+        let mut iterations = 0usize;
+        while get_time() - start < 20 {
+            spin_iterations(1000);
+            iterations += 1000;
+        }
+        WORK.store(iterations * HOLD_MS / 20, Ordering::Relaxed);
+        let (plain, donate) = match (run(false), run(true)) {
+            (Some(plain), Some(donate)) => (plain, donate),
+            _ => {
+                println!("[yield_to] FAILED: cannot spawn threads");
+                return -1;
+            }
+        };
+        println!(
+            "[yield_to] lock held {} ms with plain yield, {} ms when donating",
+            plain, donate
+        );
+        if donate >= plain {
+            println!("[yield_to] FAILED: donating the CPU did not speed up the holder");
+            return -1;
+        }
+        println!("[yield_to] passed");
+        0
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    #[cfg(target_arch = "riscv64")]
+    return workload::main();
+    #[cfg(not(target_arch = "riscv64"))]
+    {
+        println!("[yield_to] skipped: threads are only spawned on riscv64");
+        0
+    }
+}
@@ -107,12 +107,27 @@ fn main(_argc: usize, _argv: &[&str]) -> i32 {
     panic!("Cannot find main!");
 }
 
+/// 输出测试程序 `name` 的结果并返回退出码：
+/// 通过时打印 `[name] passed` 并返回 0，失败时打印 `[name] FAILED: 原因` 并返回 -1
+pub fn report<E: core::fmt::Display>(name: &str, result: Result<(), E>) -> i32 {
+    match result {
+        Ok(()) => {
+            println!("[{}] passed", name);
+            0
+        }
+        Err(reason) => {
+            println!("[{}] FAILED: {}", name, reason);
+            -1
+        }
+    }
+}
+
 bitflags! {
     pub struct OpenFlags: u32 {
         const RDONLY = 0;
         const WRONLY = 1 << 0;
         const RDWR = 1 << 1;
-        const CREATE = 1 << 9;
-        const TRUNC = 1 << 10;
+        const CREATE = 0o100;
+        const TRUNC = 0o1000;
     }
 }
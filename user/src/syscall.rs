@@ -3,6 +3,9 @@
 use core::arch::global_asm;
 
 const SYSCALL_GETCWD: usize = 17;
+const SYSCALL_EPOLL_CREATE1: usize = 20;
+const SYSCALL_EPOLL_CTL: usize = 21;
+const SYSCALL_EPOLL_PWAIT: usize = 22;
 const SYSCALL_DUP: usize = 23;
 const SYSCALL_DUP3: usize = 24;
 const SYSCALL_FCNTL: usize = 25;
@@ -12,8 +15,12 @@ const SYSCALL_UNLINKAT: usize = 35;
 const SYSCALL_LINKAT: usize = 37;
 const SYSCALL_UMOUNT2: usize = 39;
 const SYSCALL_MOUNT: usize = 40;
+const SYSCALL_PIVOT_ROOT: usize = 41;
+const SYSCALL_STATFS: usize = 43;
+const SYSCALL_FTRUNCATE: usize = 46;
 const SYSCALL_FACCESSAT: usize = 48;
 const SYSCALL_CHDIR: usize = 49;
+const SYSCALL_CHROOT: usize = 51;
 const SYSCALL_OPENAT: usize = 56;
 const SYSCALL_CLOSE: usize = 57;
 const SYSCALL_PIPE: usize = 59;
@@ -23,31 +30,52 @@ const SYSCALL_READ: usize = 63;
 const SYSCALL_WRITE: usize = 64;
 const SYSCALL_READV: usize = 65;
 const SYSCALL_WRITEV: usize = 66;
+const SYSCALL_PREAD64: usize = 67;
 const SYSCALL_SENDFILE: usize = 71;
 const SYSCALL_PSELECT6: usize = 72;
 const SYSCALL_PPOLL: usize = 73;
+const SYSCALL_SIGNALFD4: usize = 74;
 const SYSCALL_READLINKAT: usize = 78;
 const SYSCALL_NEW_FSTATAT: usize = 79;
 const SYSCALL_FSTAT: usize = 80;
 const SYSCALL_FSYNC: usize = 82;
 const SYSCALL_UTIMENSAT: usize = 88;
+const SYSCALL_ACCT: usize = 89;
 const SYSCALL_EXIT: usize = 93;
 const SYSCALL_EXIT_GRUOP: usize = 94;
 const SYSCALL_SET_TID_ADDRESS: usize = 96;
+const SYSCALL_UNSHARE: usize = 97;
+const SYSCALL_FUTEX: usize = 98;
+const SYSCALL_SET_ROBUST_LIST: usize = 99;
 const SYSCALL_NANOSLEEP: usize = 101;
 const SYSCALL_GETITIMER: usize = 102;
 const SYSCALL_SETITIMER: usize = 103;
+const SYSCALL_TIMER_CREATE: usize = 107;
+const SYSCALL_TIMER_GETTIME: usize = 108;
+const SYSCALL_TIMER_GETOVERRUN: usize = 109;
+const SYSCALL_TIMER_SETTIME: usize = 110;
+const SYSCALL_TIMER_DELETE: usize = 111;
+const SYSCALL_CLOCK_SETTIME: usize = 112;
 const SYSCALL_CLOCK_GETTIME: usize = 113;
+const SYSCALL_SCHED_SETSCHEDULER: usize = 119;
+const SYSCALL_SCHED_GETSCHEDULER: usize = 120;
+const SYSCALL_SCHED_SETAFFINITY: usize = 122;
+const SYSCALL_SCHED_GETAFFINITY: usize = 123;
 const SYSCALL_YIELD: usize = 124;
+const SYSCALL_PTRACE: usize = 117;
 const SYSCALL_KILL: usize = 129;
 const SYSCALL_SIGACTION: usize = 134;
 const SYSCALL_SIGPROCMASK: usize = 135;
 const SYSCALL_SIGRETURN: usize = 139;
+const SYSCALL_REBOOT: usize = 142;
 const SYSCALL_TIMES: usize = 153;
 const SYSCALL_SETPGID: usize = 154;
 const SYSCALL_GETPGID: usize = 155;
+const SYSCALL_GETSID: usize = 156;
+const SYSCALL_SETSID: usize = 157;
 const SYSCALL_UNAME: usize = 160;
 const SYSCALL_GETRUSAGE: usize = 165;
+const SYSCALL_GETCPU: usize = 168;
 const SYSCALL_GET_TIME_OF_DAY: usize = 169;
 const SYSCALL_GETPID: usize = 172;
 const SYSCALL_GETPPID: usize = 173;
@@ -56,6 +84,15 @@ const SYSCALL_GETEUID: usize = 175;
 const SYSCALL_GETGID: usize = 176;
 const SYSCALL_GETEGID: usize = 177;
 const SYSCALL_GETTID: usize = 178;
+const SYSCALL_SOCKET: usize = 198;
+const SYSCALL_SOCKETPAIR: usize = 199;
+const SYSCALL_BIND: usize = 200;
+const SYSCALL_LISTEN: usize = 201;
+const SYSCALL_ACCEPT: usize = 202;
+const SYSCALL_CONNECT: usize = 203;
+const SYSCALL_GETSOCKNAME: usize = 204;
+const SYSCALL_SENDTO: usize = 206;
+const SYSCALL_RECVFROM: usize = 207;
 const SYSCALL_SBRK: usize = 213;
 const SYSCALL_BRK: usize = 214;
 const SYSCALL_MUNMAP: usize = 215;
@@ -63,16 +100,26 @@ const SYSCALL_CLONE: usize = 220;
 const SYSCALL_EXECVE: usize = 221;
 const SYSCALL_MMAP: usize = 222;
 const SYSCALL_MPROTECT: usize = 226;
+const SYSCALL_MSYNC: usize = 227;
+const SYSCALL_MINCORE: usize = 232;
 const SYSCALL_WAIT4: usize = 260;
 const SYSCALL_PRLIMIT: usize = 261;
 const SYSCALL_RENAMEAT2: usize = 276;
+const SYSCALL_GETRANDOM: usize = 278;
 const SYSCALL_COPY_FILE_RANGE: usize = 285;
 const SYSCALL_STATX: usize = 291;
 // Not standard POSIX sys_call
 const SYSCALL_LS: usize = 500;
 const SYSCALL_SHUTDOWN: usize = 501;
+const SYSCALL_WATCHPOINT: usize = 503;
 const SYSCALL_CLEAR: usize = 502;
 const SYSCALL_OPEN: usize = 506; //where?
+const SYSCALL_SCHED_YIELD_TO: usize = 507;
+const SYSCALL_OPENAT2: usize = 437;
+const SYSCALL_NAME_TO_HANDLE_AT: usize = 264;
+const SYSCALL_STATMOUNT: usize = 457;
+const SYSCALL_LISTMOUNT: usize = 458;
+const SYSCALL_OPEN_BY_HANDLE_AT: usize = 265;
 const SYSCALL_GET_TIME: usize = 1690; //you mean get time of day by 169?
 
 #[cfg(target_arch = "loongarch64")]
@@ -145,10 +192,149 @@ pub fn sys_dup(fd: usize) -> isize {
     syscall(SYSCALL_DUP, [fd, 0, 0])
 }
 
+pub fn sys_fcntl(fd: usize, cmd: u32, arg: usize) -> isize {
+    syscall(SYSCALL_FCNTL, [fd, cmd as usize, arg])
+}
+
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    syscall(SYSCALL_LSEEK, [fd, offset as usize, whence])
+}
+
+pub fn sys_pread(fd: usize, buffer: &mut [u8], offset: usize) -> isize {
+    syscall6(
+        SYSCALL_PREAD64,
+        [fd, buffer.as_mut_ptr() as usize, buffer.len(), offset, 0, 0],
+    )
+}
+
+pub fn sys_ftruncate(fd: usize, length: usize) -> isize {
+    syscall(SYSCALL_FTRUNCATE, [fd, length, 0])
+}
+
 pub fn sys_open(path: &str, flags: u32) -> isize {
     syscall(SYSCALL_OPEN, [path.as_ptr() as usize, flags as usize, 0])
 }
 
+pub fn sys_getdents64(fd: usize, buf: &mut [u8]) -> isize {
+    syscall(SYSCALL_GETDENTS64, [fd, buf.as_mut_ptr() as usize, buf.len()])
+}
+
+pub fn sys_unlinkat(dirfd: isize, path: &str, flags: u32) -> isize {
+    syscall(SYSCALL_UNLINKAT, [dirfd as usize, path.as_ptr() as usize, flags as usize])
+}
+
+pub fn sys_mkdirat(dirfd: isize, path: &str, mode: u32) -> isize {
+    syscall(SYSCALL_MKDIRAT, [dirfd as usize, path.as_ptr() as usize, mode as usize])
+}
+
+pub fn sys_openat2(dirfd: isize, path: &str, how: *const u8, size: usize) -> isize {
+    syscall6(
+        SYSCALL_OPENAT2,
+        [dirfd as usize, path.as_ptr() as usize, how as usize, size, 0, 0],
+    )
+}
+
+pub fn sys_epoll_create1(flags: u32) -> isize {
+    syscall(SYSCALL_EPOLL_CREATE1, [flags as usize, 0, 0])
+}
+
+pub fn sys_epoll_ctl(epfd: usize, op: usize, fd: usize, event: *const u8) -> isize {
+    syscall6(SYSCALL_EPOLL_CTL, [epfd, op, fd, event as usize, 0, 0])
+}
+
+pub fn sys_epoll_pwait(epfd: usize, events: *mut u8, maxevents: usize, timeout: i32) -> isize {
+    syscall6(
+        SYSCALL_EPOLL_PWAIT,
+        [epfd, events as usize, maxevents, timeout as usize, 0, 8],
+    )
+}
+
+pub fn sys_ioctl(fd: usize, cmd: u32, arg: usize) -> isize {
+    syscall(SYSCALL_IOCTL, [fd, cmd as usize, arg])
+}
+
+pub fn sys_statfs(path: &str, buf: *mut u8) -> isize {
+    syscall(SYSCALL_STATFS, [path.as_ptr() as usize, buf as usize, 0])
+}
+
+pub fn sys_fsync(fd: usize) -> isize {
+    syscall(SYSCALL_FSYNC, [fd, 0, 0])
+}
+
+pub fn sys_mount(source: &str, target: &str, fstype: &str, flags: usize) -> isize {
+    syscall6(
+        SYSCALL_MOUNT,
+        [
+            source.as_ptr() as usize,
+            target.as_ptr() as usize,
+            fstype.as_ptr() as usize,
+            flags,
+            0,
+            0,
+        ],
+    )
+}
+
+pub fn sys_umount2(target: &str, flags: u32) -> isize {
+    syscall(SYSCALL_UMOUNT2, [target.as_ptr() as usize, flags as usize, 0])
+}
+
+pub fn sys_pivot_root(new_root: &str, put_old: &str) -> isize {
+    syscall(
+        SYSCALL_PIVOT_ROOT,
+        [new_root.as_ptr() as usize, put_old.as_ptr() as usize, 0],
+    )
+}
+
+pub fn sys_unshare(flags: u32) -> isize {
+    syscall(SYSCALL_UNSHARE, [flags as usize, 0, 0])
+}
+
+pub fn sys_statmount(req: *const u8, buf: *mut u8, bufsize: usize, flags: u32) -> isize {
+    syscall6(
+        SYSCALL_STATMOUNT,
+        [req as usize, buf as usize, bufsize, flags as usize, 0, 0],
+    )
+}
+
+pub fn sys_listmount(req: *const u8, mnt_ids: *mut u64, nr_mnt_ids: usize, flags: u32) -> isize {
+    syscall6(
+        SYSCALL_LISTMOUNT,
+        [req as usize, mnt_ids as usize, nr_mnt_ids, flags as usize, 0, 0],
+    )
+}
+
+pub fn sys_name_to_handle_at(
+    dirfd: isize,
+    path: &str,
+    handle: *mut u8,
+    mount_id: *mut i32,
+    flags: u32,
+) -> isize {
+    syscall6(
+        SYSCALL_NAME_TO_HANDLE_AT,
+        [
+            dirfd as usize,
+            path.as_ptr() as usize,
+            handle as usize,
+            mount_id as usize,
+            flags as usize,
+            0,
+        ],
+    )
+}
+
+pub fn sys_open_by_handle_at(mount_fd: isize, handle: *const u8, flags: u32) -> isize {
+    syscall(
+        SYSCALL_OPEN_BY_HANDLE_AT,
+        [mount_fd as usize, handle as usize, flags as usize],
+    )
+}
+
+pub fn sys_acct(path: *const u8) -> isize {
+    syscall(SYSCALL_ACCT, [path as usize, 0, 0])
+}
+
 pub fn sys_close(fd: usize) -> isize {
     syscall(SYSCALL_CLOSE, [fd, 0, 0])
 }
@@ -177,6 +363,10 @@ pub fn sys_yield() -> isize {
     syscall(SYSCALL_YIELD, [0, 0, 0])
 }
 
+pub fn sys_sched_yield_to(tid: usize) -> isize {
+    syscall(SYSCALL_SCHED_YIELD_TO, [tid, 0, 0])
+}
+
 pub fn sys_get_time() -> isize {
     syscall(SYSCALL_GET_TIME, [0, 0, 0])
 }
@@ -185,6 +375,62 @@ pub fn sys_getpid() -> isize {
     syscall(SYSCALL_GETPID, [0, 0, 0])
 }
 
+pub fn sys_gettid() -> isize {
+    syscall(SYSCALL_GETTID, [0, 0, 0])
+}
+
+pub fn sys_nanosleep(req: &[usize; 2]) -> isize {
+    syscall(SYSCALL_NANOSLEEP, [req.as_ptr() as usize, 0, 0])
+}
+
+pub fn sys_timer_create(clockid: usize, sevp: usize, timerid: *mut i32) -> isize {
+    syscall(SYSCALL_TIMER_CREATE, [clockid, sevp, timerid as usize])
+}
+
+pub fn sys_timer_settime(timerid: usize, flags: u32, new_value: usize, old_value: usize) -> isize {
+    syscall6(SYSCALL_TIMER_SETTIME, [timerid, flags as usize, new_value, old_value, 0, 0])
+}
+
+pub fn sys_timer_gettime(timerid: usize, curr_value: usize) -> isize {
+    syscall(SYSCALL_TIMER_GETTIME, [timerid, curr_value, 0])
+}
+
+pub fn sys_timer_getoverrun(timerid: usize) -> isize {
+    syscall(SYSCALL_TIMER_GETOVERRUN, [timerid, 0, 0])
+}
+
+pub fn sys_timer_delete(timerid: usize) -> isize {
+    syscall(SYSCALL_TIMER_DELETE, [timerid, 0, 0])
+}
+
+pub fn sys_clock_settime(clockid: usize, tp: *const usize) -> isize {
+    syscall(SYSCALL_CLOCK_SETTIME, [clockid, tp as usize, 0])
+}
+
+pub fn sys_clock_gettime(clockid: usize, tp: *mut usize) -> isize {
+    syscall(SYSCALL_CLOCK_GETTIME, [clockid, tp as usize, 0])
+}
+
+pub fn sys_getcpu(cpu: *mut u32) -> isize {
+    syscall(SYSCALL_GETCPU, [cpu as usize, 0, 0])
+}
+
+pub fn sys_sched_setscheduler(pid: usize, policy: usize, param: *const i32) -> isize {
+    syscall(SYSCALL_SCHED_SETSCHEDULER, [pid, policy, param as usize])
+}
+
+pub fn sys_sched_getscheduler(pid: usize) -> isize {
+    syscall(SYSCALL_SCHED_GETSCHEDULER, [pid, 0, 0])
+}
+
+pub fn sys_sched_setaffinity(pid: usize, cpusetsize: usize, mask: *const u8) -> isize {
+    syscall(SYSCALL_SCHED_SETAFFINITY, [pid, cpusetsize, mask as usize])
+}
+
+pub fn sys_sched_getaffinity(pid: usize, cpusetsize: usize, mask: *mut u8) -> isize {
+    syscall(SYSCALL_SCHED_GETAFFINITY, [pid, cpusetsize, mask as usize])
+}
+
 pub fn sys_fork() -> isize {
     const SIGCHLD: usize = 17;
     syscall(SYSCALL_CLONE, [SIGCHLD, 0, 0])
@@ -212,12 +458,167 @@ pub fn sys_chdir(path: &str) -> isize {
     )
 }
 
+pub fn sys_chroot(path: &str) -> isize {
+    syscall(SYSCALL_CHROOT, [path.as_ptr() as usize, 0, 0])
+}
+
 pub fn sys_waitpid(pid: isize, exit_code: *mut i32) -> isize {
     syscall(SYSCALL_WAIT4, [pid as usize, exit_code as usize, 0])
 }
+pub fn sys_wait4(pid: isize, status: *mut i32, options: usize) -> isize {
+    syscall(SYSCALL_WAIT4, [pid as usize, status as usize, options])
+}
+pub fn sys_setpgid(pid: usize, pgid: usize) -> isize {
+    syscall(SYSCALL_SETPGID, [pid, pgid, 0])
+}
+pub fn sys_getpgid(pid: usize) -> isize {
+    syscall(SYSCALL_GETPGID, [pid, 0, 0])
+}
+pub fn sys_getsid(pid: usize) -> isize {
+    syscall(SYSCALL_GETSID, [pid, 0, 0])
+}
+pub fn sys_setsid() -> isize {
+    syscall(SYSCALL_SETSID, [0, 0, 0])
+}
 pub fn sys_shutdown() -> isize {
     syscall(SYSCALL_SHUTDOWN, [0, 0, 0])
 }
+pub fn sys_reboot(magic1: u32, magic2: u32, cmd: u32) -> isize {
+    syscall(SYSCALL_REBOOT, [magic1 as usize, magic2 as usize, cmd as usize])
+}
+pub fn sys_watchpoint(addr: usize, kind: u32) -> isize {
+    syscall(SYSCALL_WATCHPOINT, [addr, kind as usize, 0])
+}
+pub fn sys_sigprocmask(how: usize, set: *const u8, oldset: *mut u8) -> isize {
+    syscall(SYSCALL_SIGPROCMASK, [how, set as usize, oldset as usize])
+}
+
+pub fn sys_signalfd4(fd: isize, mask: *const u8, flags: u32) -> isize {
+    syscall6(
+        SYSCALL_SIGNALFD4,
+        [fd as usize, mask as usize, 8, flags as usize, 0, 0],
+    )
+}
+
+pub fn sys_socket(domain: usize, socket_type: usize, protocol: usize) -> isize {
+    syscall(SYSCALL_SOCKET, [domain, socket_type, protocol])
+}
+
+pub fn sys_socketpair(domain: usize, socket_type: usize, protocol: usize, sv: *mut u32) -> isize {
+    syscall6(
+        SYSCALL_SOCKETPAIR,
+        [domain, socket_type, protocol, sv as usize, 0, 0],
+    )
+}
+
+pub fn sys_bind(sockfd: usize, addr: *const u8, addrlen: usize) -> isize {
+    syscall(SYSCALL_BIND, [sockfd, addr as usize, addrlen])
+}
+
+pub fn sys_listen(sockfd: usize, backlog: usize) -> isize {
+    syscall(SYSCALL_LISTEN, [sockfd, backlog, 0])
+}
+
+pub fn sys_accept(sockfd: usize, addr: *mut u8, addrlen: *mut u32) -> isize {
+    syscall(SYSCALL_ACCEPT, [sockfd, addr as usize, addrlen as usize])
+}
+
+pub fn sys_connect(sockfd: usize, addr: *const u8, addrlen: usize) -> isize {
+    syscall(SYSCALL_CONNECT, [sockfd, addr as usize, addrlen])
+}
+
+pub fn sys_getsockname(sockfd: usize, addr: *mut u8, addrlen: *mut u32) -> isize {
+    syscall(
+        SYSCALL_GETSOCKNAME,
+        [sockfd, addr as usize, addrlen as usize],
+    )
+}
+
+pub fn sys_sendto(sockfd: usize, buf: &[u8], dest_addr: *const u8, addrlen: usize) -> isize {
+    syscall6(
+        SYSCALL_SENDTO,
+        [
+            sockfd,
+            buf.as_ptr() as usize,
+            buf.len(),
+            0,
+            dest_addr as usize,
+            addrlen,
+        ],
+    )
+}
+
+pub fn sys_recvfrom(sockfd: usize, buf: &mut [u8], src_addr: *mut u8, addrlen: *mut u32) -> isize {
+    syscall6(
+        SYSCALL_RECVFROM,
+        [
+            sockfd,
+            buf.as_mut_ptr() as usize,
+            buf.len(),
+            0,
+            src_addr as usize,
+            addrlen as usize,
+        ],
+    )
+}
+
+pub fn sys_kill(pid: usize, signum: usize) -> isize {
+    syscall(SYSCALL_KILL, [pid, signum, 0])
+}
+/// clone 出共享地址空间的线程，子线程在 `stack_top` 上从 `entry(arg)` 开始执行
+#[cfg(target_arch = "riscv64")]
+pub fn sys_thread_spawn(entry: extern "C" fn(usize) -> !, arg: usize, stack_top: usize) -> isize {
+    // CLONE_VM | CLONE_FS | CLONE_FILES | CLONE_SIGHAND | CLONE_THREAD
+    const FLAGS: usize = 0x100 | 0x200 | 0x400 | 0x800 | 0x10000;
+    let mut ret: isize;
+    unsafe {
+        core::arch::asm!(
+            "ecall",
+            // 子线程的寄存器与父线程相同，栈已换成新栈，跳到入口后不再返回
+            "bnez a0, 1f",
+            "mv a0, {arg}",
+            "jr {entry}",
+            "1:",
+            entry = in(reg) entry,
+            arg = in(reg) arg,
+            inlateout("x10") FLAGS => ret,
+            in("x11") stack_top,
+            in("x12") 0,
+            in("x13") 0,
+            in("x14") 0,
+            in("x17") SYSCALL_CLONE,
+        );
+    }
+    ret
+}
+pub fn sys_futex(uaddr: usize, op: usize, val: u32, val2: usize, uaddr2: usize, val3: u32) -> isize {
+    syscall6(SYSCALL_FUTEX, [uaddr, op, val as usize, val2, uaddr2, val3 as usize])
+}
+pub fn sys_set_robust_list(head: usize, len: usize) -> isize {
+    syscall(SYSCALL_SET_ROBUST_LIST, [head, len, 0])
+}
+pub fn sys_sigaction(signum: usize, act: usize, oldact: usize) -> isize {
+    syscall(SYSCALL_SIGACTION, [signum, act, oldact])
+}
+pub fn sys_ptrace(request: usize, pid: usize, addr: usize, data: usize) -> isize {
+    syscall6(SYSCALL_PTRACE, [request, pid, addr, data, 0, 0])
+}
+pub fn sys_mmap(start: usize, len: usize, prot: usize, flags: usize, fd: usize, offset: usize) -> isize {
+    syscall6(SYSCALL_MMAP, [start, len, prot, flags, fd, offset])
+}
+pub fn sys_munmap(start: usize, len: usize) -> isize {
+    syscall(SYSCALL_MUNMAP, [start, len, 0])
+}
+pub fn sys_msync(start: usize, len: usize, flags: usize) -> isize {
+    syscall(SYSCALL_MSYNC, [start, len, flags])
+}
+pub fn sys_mincore(start: usize, len: usize, vec: *mut u8) -> isize {
+    syscall(SYSCALL_MINCORE, [start, len, vec as usize])
+}
+
+pub fn sys_getrandom(buf: *mut u8, buflen: usize, flags: u32) -> isize {
+    syscall(SYSCALL_GETRANDOM, [buf as usize, buflen, flags as usize])
+}
 
 pub fn sys_copy_file_range(
     fd_in: i32,
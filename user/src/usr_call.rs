@@ -1,19 +1,323 @@
 use crate::syscall::*;
+use core::sync::atomic::AtomicU32;
 pub fn dup(fd: usize) -> isize {
     sys_dup(fd)
 }
+/// fcntl 的命令：复制到不小于 `arg` 的最小空闲 fd 上
+pub const F_DUPFD: u32 = 0;
+pub fn fcntl(fd: usize, cmd: u32, arg: usize) -> isize {
+    sys_fcntl(fd, cmd, arg)
+}
+pub const SEEK_SET: usize = 0;
+pub const SEEK_CUR: usize = 1;
+pub const SEEK_END: usize = 2;
+/// 移到偏移处或之后的第一个数据，或第一个空洞
+pub const SEEK_DATA: usize = 3;
+pub const SEEK_HOLE: usize = 4;
+/// 移动 `fd` 的文件偏移，返回新的偏移
+pub fn lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    sys_lseek(fd, offset, whence)
+}
+/// 把文件 `fd` 截断或扩展到 `length` 字节
+pub fn ftruncate(fd: usize, length: usize) -> isize {
+    sys_ftruncate(fd, length)
+}
 pub fn open(path: &str, flags: crate::OpenFlags) -> isize {
     sys_open(path, flags.bits)
 }
 pub fn close(fd: usize) -> isize {
     sys_close(fd)
 }
+/// 把文件 `fd` 的数据写回磁盘并让磁盘清空写缓存
+pub fn fsync(fd: usize) -> isize {
+    sys_fsync(fd)
+}
+/// 读取目录 `fd` 的目录项，按 `struct linux_dirent64` 的布局写入 `buf`，返回写入的字节数
+pub fn getdents64(fd: usize, buf: &mut [u8]) -> isize {
+    sys_getdents64(fd, buf)
+}
+/// 删除文件，`path` 须以 `\0` 结尾
+pub fn unlink(path: &str) -> isize {
+    sys_unlinkat(AT_FDCWD, path, 0)
+}
+/// 创建目录，`path` 须以 `\0` 结尾
+pub fn mkdir(path: &str) -> isize {
+    sys_mkdirat(AT_FDCWD, path, 0o755)
+}
+/// 删除空目录，`path` 须以 `\0` 结尾
+pub fn rmdir(path: &str) -> isize {
+    const AT_REMOVEDIR: u32 = 0x200;
+    sys_unlinkat(AT_FDCWD, path, AT_REMOVEDIR)
+}
+/// 以目录 `dirfd` 为起点打开 `path`（须以 `\0` 结尾），`resolve` 限制路径解析的方式。
+/// `how` 通常是 [`OpenHow`]，也可以是以它开头、末尾追加了字段的更新版本
+pub fn openat2<T>(dirfd: isize, path: &str, how: &T) -> isize {
+    sys_openat2(
+        dirfd,
+        path,
+        how as *const T as *const u8,
+        core::mem::size_of::<T>(),
+    )
+}
+pub const AT_FDCWD: isize = -100;
+/// openat2 的 `struct open_how`
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct OpenHow {
+    pub flags: u64,
+    pub mode: u64,
+    pub resolve: u64,
+}
+/// 路径中不允许出现符号链接
+pub const RESOLVE_NO_SYMLINKS: u64 = 0x04;
+/// 路径不允许越出 dirfd
+pub const RESOLVE_BENEATH: u64 = 0x08;
+/// `struct file_handle`，`f_handle` 的容量足以放下内核生成的句柄
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct FileHandle {
+    /// 调用前为 `f_handle` 的容量，调用后为句柄的实际长度
+    pub handle_bytes: u32,
+    pub handle_type: i32,
+    pub f_handle: [u8; 16],
+}
+/// 为 `path`（须以 `\0` 结尾）生成文件句柄，`mount_id` 写入文件所在文件系统的编号
+pub fn name_to_handle_at(
+    dirfd: isize,
+    path: &str,
+    handle: &mut FileHandle,
+    mount_id: &mut i32,
+    flags: u32,
+) -> isize {
+    sys_name_to_handle_at(
+        dirfd,
+        path,
+        handle as *mut FileHandle as *mut u8,
+        mount_id,
+        flags,
+    )
+}
+/// 按文件句柄打开文件，`mount_fd` 是同一文件系统中的任一文件
+pub fn open_by_handle_at(mount_fd: isize, handle: &FileHandle, flags: crate::OpenFlags) -> isize {
+    sys_open_by_handle_at(mount_fd, handle as *const FileHandle as *const u8, flags.bits)
+}
+/// 回环设备的 ioctl：关联文件描述符 `arg` 所指的文件，或解除关联
+pub const LOOP_SET_FD: u32 = 0x4C00;
+pub const LOOP_CLR_FD: u32 = 0x4C01;
+/// 磁盘的 ioctl：以字节计的容量，以及身份与健康信息 `DiskHealth`
+pub const BLKGETSIZE64: u32 = 0x8008_1272;
+pub const DISK_GET_HEALTH: u32 = 0x4401;
+/// 与内核的 `DiskHealth` 布局相同，字符串以 NUL 填充
+#[repr(C)]
+pub struct DiskHealth {
+    /// 以 512 字节扇区计的容量
+    pub sectors: u64,
+    /// 摄氏温度，磁盘不报告时为 -1
+    pub temperature: i32,
+    pub reserved: u32,
+    pub model: [u8; 40],
+    pub serial: [u8; 24],
+}
+/// ioctl(dst_fd, FICLONE, src_fd)：让 `dst_fd` 成为 `src_fd` 的写时复制副本
+pub const FICLONE: u32 = 0x4004_9409;
+/// 伪终端主设备的 ioctl：取得从设备的编号 N（/dev/pts/N），锁住或解锁从设备
+pub const TIOCGPTN: u32 = 0x8004_5430;
+pub const TIOCSPTLCK: u32 = 0x4004_5431;
+/// 终端的 ioctl：设置前台进程组，取得控制该终端的会话
+pub const TIOCSPGRP: u32 = 0x5410;
+pub const TIOCGSID: u32 = 0x5429;
+/// 对文件描述符 `fd` 执行设备相关的操作 `cmd`
+pub fn ioctl(fd: usize, cmd: u32, arg: usize) -> isize {
+    sys_ioctl(fd, cmd, arg)
+}
+/// epoll 的事件与标志
+pub const EPOLLIN: u32 = 0x001;
+pub const EPOLLOUT: u32 = 0x004;
+pub const EPOLLHUP: u32 = 0x010;
+pub const EPOLLONESHOT: u32 = 1 << 30;
+pub const EPOLLET: u32 = 1 << 31;
+/// epoll_ctl 的操作
+pub const EPOLL_CTL_ADD: usize = 1;
+pub const EPOLL_CTL_DEL: usize = 2;
+pub const EPOLL_CTL_MOD: usize = 3;
+/// `struct epoll_event`，RISC-V 与 LoongArch 上不紧凑排列
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct EpollEvent {
+    pub events: u32,
+    pub data: u64,
+}
+/// 创建 epoll 实例，`flags` 只能是 0 或 O_CLOEXEC
+pub fn epoll_create1(flags: u32) -> isize {
+    sys_epoll_create1(flags)
+}
+/// 在 epoll 实例 `epfd` 中添加、修改或删除对 `fd` 的监视
+pub fn epoll_ctl(epfd: usize, op: usize, fd: usize, event: &EpollEvent) -> isize {
+    sys_epoll_ctl(epfd, op, fd, event as *const EpollEvent as *const u8)
+}
+/// 等待就绪事件，`timeout` 以毫秒计，负数表示一直等待；返回写入 `events` 的事件数
+pub fn epoll_wait(epfd: usize, events: &mut [EpollEvent], timeout: i32) -> isize {
+    sys_epoll_pwait(epfd, events.as_mut_ptr() as *mut u8, events.len(), timeout)
+}
+/// `struct statfs`
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct Statfs {
+    pub f_type: usize,
+    pub f_bsize: usize,
+    pub f_blocks: u64,
+    pub f_bfree: u64,
+    pub f_bavail: u64,
+    pub f_files: u64,
+    pub f_ffree: u64,
+    pub f_fsid: [i32; 2],
+    pub f_namelen: usize,
+    pub f_frsize: usize,
+    pub f_flag: usize,
+    pub f_spare: [usize; 4],
+}
+/// `path`（须以 `\0` 结尾）所在文件系统的容量与空闲空间
+pub fn statfs(path: &str, buf: &mut Statfs) -> isize {
+    sys_statfs(path, buf as *mut Statfs as *mut u8)
+}
+/// mount 的标志：以只读方式挂载
+pub const MS_RDONLY: usize = 1;
+/// mount 的标志：把目录 `source` 绑定挂载到 `target` 上
+pub const MS_BIND: usize = 4096;
+/// mount 的标志：把 `target` 上的挂载设为私有，或设为共享，之后绑定它得到的挂载与它互为对等
+pub const MS_PRIVATE: usize = 1 << 18;
+pub const MS_SHARED: usize = 1 << 20;
+/// 挂载文件系统，各字符串须以 `\0` 结尾
+pub fn mount(source: &str, target: &str, fstype: &str, flags: usize) -> isize {
+    sys_mount(source, target, fstype, flags)
+}
+/// 卸载 `target`（须以 `\0` 结尾）上最近一次的挂载
+pub fn umount(target: &str) -> isize {
+    sys_umount2(target, 0)
+}
+/// 把根目录换成挂载点 `new_root`，原来的根目录移到 `put_old` 上，路径须以 `\0` 结尾
+pub fn pivot_root(new_root: &str, put_old: &str) -> isize {
+    sys_pivot_root(new_root, put_old)
+}
+/// unshare 的标志：得到挂载命名空间的副本
+pub const CLONE_NEWNS: u32 = 0x20000;
+/// 不再与其他任务共享 `flags` 指定的资源
+pub fn unshare(flags: u32) -> isize {
+    sys_unshare(flags)
+}
+/// statmount 与 listmount 的 `struct mnt_id_req`
+#[repr(C)]
+struct MntIdReq {
+    size: u32,
+    spare: u32,
+    mnt_id: u64,
+    param: u64,
+}
+impl MntIdReq {
+    fn new(mnt_id: u64, param: u64) -> Self {
+        Self {
+            size: core::mem::size_of::<Self>() as u32,
+            spare: 0,
+            mnt_id,
+            param,
+        }
+    }
+}
+/// listmount 列出全部挂载
+pub const LSMT_ROOT: u64 = u64::MAX;
+pub const STATMOUNT_SB_BASIC: u64 = 0x1;
+pub const STATMOUNT_MNT_BASIC: u64 = 0x2;
+pub const STATMOUNT_MNT_ROOT: u64 = 0x8;
+pub const STATMOUNT_MNT_POINT: u64 = 0x10;
+pub const STATMOUNT_FS_TYPE: u64 = 0x20;
+/// `struct statmount` 与其后存放字符串的空间
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Statmount {
+    pub size: u32,
+    pub spare1: u32,
+    pub mask: u64,
+    pub sb_dev_major: u32,
+    pub sb_dev_minor: u32,
+    pub sb_magic: u64,
+    pub sb_flags: u32,
+    pub fs_type: u32,
+    pub mnt_id: u64,
+    pub mnt_parent_id: u64,
+    pub mnt_id_old: u32,
+    pub mnt_parent_id_old: u32,
+    pub mnt_attr: u64,
+    pub mnt_propagation: u64,
+    pub mnt_peer_group: u64,
+    pub mnt_master: u64,
+    pub propagate_from: u64,
+    pub mnt_root: u32,
+    pub mnt_point: u32,
+    pub spare2: [u64; 50],
+    pub str: [u8; 256],
+}
+impl Statmount {
+    pub const fn new() -> Self {
+        Self {
+            size: 0,
+            spare1: 0,
+            mask: 0,
+            sb_dev_major: 0,
+            sb_dev_minor: 0,
+            sb_magic: 0,
+            sb_flags: 0,
+            fs_type: 0,
+            mnt_id: 0,
+            mnt_parent_id: 0,
+            mnt_id_old: 0,
+            mnt_parent_id_old: 0,
+            mnt_attr: 0,
+            mnt_propagation: 0,
+            mnt_peer_group: 0,
+            mnt_master: 0,
+            propagate_from: 0,
+            mnt_root: 0,
+            mnt_point: 0,
+            spare2: [0; 50],
+            str: [0; 256],
+        }
+    }
+    /// `str` 中从 `offset` 开始的字符串，即 `fs_type`、`mnt_point` 等字段所指的字符串
+    pub fn string(&self, offset: u32) -> &str {
+        let bytes = &self.str[offset as usize..];
+        let len = bytes.iter().position(|&byte| byte == 0).unwrap_or(bytes.len());
+        core::str::from_utf8(&bytes[..len]).unwrap_or("")
+    }
+}
+/// 读取挂载 `mnt_id` 的信息，`mask` 为要返回的 `STATMOUNT_*` 字段
+pub fn statmount(mnt_id: u64, mask: u64, buf: &mut Statmount) -> isize {
+    let req = MntIdReq::new(mnt_id, mask);
+    sys_statmount(
+        &req as *const MntIdReq as *const u8,
+        buf as *mut Statmount as *mut u8,
+        core::mem::size_of::<Statmount>(),
+        0,
+    )
+}
+/// 列出挂载 `mnt_id` 之下 id 大于 `last_id` 的挂载，返回写入 `mnt_ids` 的个数
+pub fn listmount(mnt_id: u64, last_id: u64, mnt_ids: &mut [u64]) -> isize {
+    let req = MntIdReq::new(mnt_id, last_id);
+    sys_listmount(
+        &req as *const MntIdReq as *const u8,
+        mnt_ids.as_mut_ptr(),
+        mnt_ids.len(),
+        0,
+    )
+}
 pub fn pipe(pipe_fd: &mut [i32]) -> isize {
     sys_pipe(pipe_fd)
 }
 pub fn read(fd: usize, buf: &mut [u8]) -> isize {
     sys_read(fd, buf)
 }
+pub fn pread(fd: usize, buf: &mut [u8], offset: usize) -> isize {
+    sys_pread(fd, buf, offset)
+}
 pub fn write(fd: usize, buf: &[u8]) -> isize {
     sys_write(fd, buf)
 }
@@ -28,12 +332,56 @@ pub fn exit(exit_code: i32) -> ! {
 pub fn yield_() -> isize {
     sys_yield()
 }
+/// 把 CPU 让给线程 `tid`（如持有锁的线程），它就绪时在所在 CPU 上紧接着运行
+pub fn yield_to(tid: usize) -> isize {
+    sys_sched_yield_to(tid)
+}
 pub fn get_time() -> isize {
     sys_get_time()
 }
+/// 开启进程记账，记录追加到 `path`（须以 `\0` 结尾）；为 `None` 时关闭
+pub fn acct(path: Option<&str>) -> isize {
+    sys_acct(path.map_or(core::ptr::null(), |path| path.as_ptr()))
+}
 pub fn getpid() -> isize {
     sys_getpid()
 }
+pub fn gettid() -> isize {
+    sys_gettid()
+}
+/// 当前运行所在的 CPU 编号，失败时返回错误码
+pub fn getcpu() -> isize {
+    let mut cpu = 0u32;
+    match sys_getcpu(&mut cpu) {
+        0 => cpu as isize,
+        err => err,
+    }
+}
+pub const SCHED_OTHER: usize = 0;
+pub const SCHED_FIFO: usize = 1;
+pub const SCHED_RR: usize = 2;
+/// `priority` 即 sched_param 中的 sched_priority，实时策略为 1-99，其他策略为 0
+pub fn sched_setscheduler(pid: usize, policy: usize, priority: i32) -> isize {
+    sys_sched_setscheduler(pid, policy, &priority)
+}
+pub fn sched_getscheduler(pid: usize) -> isize {
+    sys_sched_getscheduler(pid)
+}
+/// `mask` 是 cpu_set_t 的字节，第 i 位为 1 表示可以在 CPU i 上运行
+pub fn sched_setaffinity(pid: usize, mask: &[u8]) -> isize {
+    sys_sched_setaffinity(pid, mask.len(), mask.as_ptr())
+}
+/// 成功时返回内核写入 `mask` 的字节数
+pub fn sched_getaffinity(pid: usize, mask: &mut [u8]) -> isize {
+    sys_sched_getaffinity(pid, mask.len(), mask.as_mut_ptr())
+}
+pub const GRND_NONBLOCK: u32 = 0x1;
+pub const GRND_RANDOM: u32 = 0x2;
+pub const GRND_INSECURE: u32 = 0x4;
+/// 用内核熵池的输出填满 `buf`，返回写入的字节数
+pub fn getrandom(buf: &mut [u8], flags: u32) -> isize {
+    sys_getrandom(buf.as_mut_ptr(), buf.len(), flags)
+}
 pub fn fork() -> isize {
     sys_fork()
 }
@@ -43,6 +391,10 @@ pub fn exec(path: &str, args: &[*const u8], envp: &[*const u8]) -> isize {
 pub fn chdir(path: &str) -> isize {
     sys_chdir(path)
 }
+/// 把根目录设为 `path`（须以 `\0` 结尾），之后的绝对路径从该目录开始解析
+pub fn chroot(path: &str) -> isize {
+    sys_chroot(path)
+}
 
 pub fn wait(exit_code: &mut i32) -> isize {
     sys_waitpid(-1, exit_code as *mut _)
@@ -51,12 +403,382 @@ pub fn wait(exit_code: &mut i32) -> isize {
 pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize {
     sys_waitpid(pid as isize, exit_code as *mut _)
 }
+
+/// wait4 的选项：报告停止与继续的子进程，WNOHANG 时没有可报告的子进程立即返回 0
+pub const WNOHANG: usize = 1;
+pub const WUNTRACED: usize = 2;
+pub const WCONTINUED: usize = 8;
+/// 按 `options` 等待子进程 `pid` 退出、停止或继续，`status` 为 Linux 的状态字
+pub fn waitpid_options(pid: isize, status: &mut i32, options: usize) -> isize {
+    sys_wait4(pid, status as *mut _, options)
+}
+
+/// `pid` 与 `pgid` 为 0 时分别表示当前进程与 `pid` 本身
+pub fn setpgid(pid: usize, pgid: usize) -> isize {
+    sys_setpgid(pid, pgid)
+}
+pub fn getpgid(pid: usize) -> isize {
+    sys_getpgid(pid)
+}
+/// 创建以当前进程为首进程的新会话，返回会话号；当前进程已是进程组组长时失败
+pub fn setsid() -> isize {
+    sys_setsid()
+}
+pub fn getsid(pid: usize) -> isize {
+    sys_getsid(pid)
+}
 pub fn sleep(period_ms: usize) {
     let start = sys_get_time();
     while sys_get_time() < start + period_ms as isize {
         sys_yield();
     }
 }
+/// 阻塞睡眠 `period_ms` 毫秒，期间不占用 CPU
+pub fn nanosleep(period_ms: usize) -> isize {
+    sys_nanosleep(&[period_ms / 1000, period_ms % 1000 * 1_000_000])
+}
+pub const CLOCK_REALTIME: usize = 0;
+pub const CLOCK_MONOTONIC: usize = 1;
+/// sigevent 的通知方式
+pub const SIGEV_SIGNAL: i32 = 0;
+pub const SIGEV_NONE: i32 = 1;
+pub const SIGEV_THREAD_ID: i32 = 4;
+/// timer_settime 的 `it_value` 是绝对时间
+pub const TIMER_ABSTIME: u32 = 1;
+
+/// `struct sigevent`，`sigev_tid` 是 `SIGEV_THREAD_ID` 的目标线程
+#[repr(C)]
+pub struct SigEvent {
+    pub sigev_value: usize,
+    pub sigev_signo: i32,
+    pub sigev_notify: i32,
+    pub sigev_tid: i32,
+    pub pad: [i32; 11],
+}
+
+impl SigEvent {
+    pub fn new(notify: i32, signo: usize, tid: i32) -> Self {
+        Self {
+            sigev_value: 0,
+            sigev_signo: signo as i32,
+            sigev_notify: notify,
+            sigev_tid: tid,
+            pad: [0; 11],
+        }
+    }
+}
+
+/// `struct itimerspec`，时间为 `[秒, 纳秒]`
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct ITimerSpec {
+    pub it_interval: [usize; 2],
+    pub it_value: [usize; 2],
+}
+
+/// 创建 POSIX 定时器，`event` 为 `None` 时到期向进程发送 SIGALRM；返回定时器 id
+pub fn timer_create(clockid: usize, event: Option<&SigEvent>) -> isize {
+    let mut timerid = 0i32;
+    let sevp = event.map_or(0, |event| event as *const SigEvent as usize);
+    match sys_timer_create(clockid, sevp, &mut timerid) {
+        0 => timerid as isize,
+        errno => errno,
+    }
+}
+/// 启动或停止定时器，`old` 不为 `None` 时存放原来的设置
+pub fn timer_settime(timerid: usize, flags: u32, new: &ITimerSpec, old: Option<&mut ITimerSpec>) -> isize {
+    let old = old.map_or(0, |old| old as *mut ITimerSpec as usize);
+    sys_timer_settime(timerid, flags, new as *const ITimerSpec as usize, old)
+}
+pub fn timer_gettime(timerid: usize, curr: &mut ITimerSpec) -> isize {
+    sys_timer_gettime(timerid, curr as *mut ITimerSpec as usize)
+}
+pub fn timer_getoverrun(timerid: usize) -> isize {
+    sys_timer_getoverrun(timerid)
+}
+pub fn timer_delete(timerid: usize) -> isize {
+    sys_timer_delete(timerid)
+}
+/// 时间为 `[秒, 纳秒]`
+pub fn clock_gettime(clockid: usize, tp: &mut [usize; 2]) -> isize {
+    sys_clock_gettime(clockid, tp.as_mut_ptr())
+}
+pub fn clock_settime(clockid: usize, tp: &[usize; 2]) -> isize {
+    sys_clock_settime(clockid, tp.as_ptr())
+}
 pub fn shutdown() -> isize{
     sys_shutdown()
-}
\ No newline at end of file
+}
+
+pub const LINUX_REBOOT_MAGIC1: u32 = 0xfee1dead;
+pub const LINUX_REBOOT_MAGIC2: u32 = 672274793;
+pub const LINUX_REBOOT_CMD_RESTART: u32 = 0x01234567;
+pub const LINUX_REBOOT_CMD_POWER_OFF: u32 = 0x4321fedc;
+
+/// 只在失败时返回
+pub fn reboot(magic1: u32, magic2: u32, cmd: u32) -> isize {
+    sys_reboot(magic1, magic2, cmd)
+}
+
+/// 写观察点，与 Linux 的 HW_BREAKPOINT_W 相同
+pub const WATCH_WRITE: u32 = 2;
+
+/// 下一次写 `addr` 时收到 SIGTRAP，观察点只触发一次；`addr` 为 0 时清除
+pub fn watchpoint(addr: usize, kind: u32) -> isize {
+    sys_watchpoint(addr, kind)
+}
+
+pub const SIGHUP: usize = 1;
+pub const SIGTRAP: usize = 5;
+pub const SA_SIGINFO: usize = 4;
+/// 被处理函数打断的阻塞系统调用在处理函数返回后重新执行，而不是返回 EINTR
+pub const SA_RESTART: usize = 0x1000_0000;
+
+pub const SIGKILL: usize = 9;
+pub const SIGUSR1: usize = 10;
+pub const SIGUSR2: usize = 12;
+pub const SIGCONT: usize = 18;
+pub const SIGSTOP: usize = 19;
+pub const SIGTSTP: usize = 20;
+
+/// sigprocmask 的 `how`
+pub const SIG_BLOCK: usize = 0;
+pub const SIG_UNBLOCK: usize = 1;
+/// 按 `how` 修改当前任务屏蔽的信号集合，`set` 的第 n - 1 位对应信号 n
+pub fn sigprocmask(how: usize, set: u64) -> isize {
+    // LoongArch 上内核的信号集有 128 位，高位补 0
+    let set = [set, 0];
+    sys_sigprocmask(how, set.as_ptr() as *const u8, core::ptr::null_mut())
+}
+
+/// signalfd4 的标志
+pub const SFD_NONBLOCK: u32 = 0o4000;
+pub const SFD_CLOEXEC: u32 = 0o2000000;
+/// 创建读出 `mask` 中信号的 signalfd，`fd` 不为 -1 时改为替换它的信号集
+pub fn signalfd(fd: isize, mask: u64, flags: u32) -> isize {
+    sys_signalfd4(fd, &mask as *const u64 as *const u8, flags)
+}
+
+/// 套接字的地址族与类型
+pub const AF_UNIX: usize = 1;
+pub const AF_INET: usize = 2;
+pub const SOCK_STREAM: usize = 1;
+pub const SOCK_DGRAM: usize = 2;
+pub const SOCK_NONBLOCK: usize = 0o4000;
+/// `struct sockaddr_un`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SockaddrUn {
+    pub sun_family: u16,
+    pub sun_path: [u8; 108],
+}
+impl SockaddrUn {
+    /// 文件系统中的路径 `path`，返回地址及其长度
+    pub fn path(path: &str) -> (Self, usize) {
+        let mut addr = Self {
+            sun_family: AF_UNIX as u16,
+            sun_path: [0; 108],
+        };
+        addr.sun_path[..path.len()].copy_from_slice(path.as_bytes());
+        (addr, 2 + path.len() + 1)
+    }
+    /// 抽象命名空间中的名字 `name`，返回地址及其长度
+    pub fn abstract_name(name: &str) -> (Self, usize) {
+        let mut addr = Self {
+            sun_family: AF_UNIX as u16,
+            sun_path: [0; 108],
+        };
+        addr.sun_path[1..1 + name.len()].copy_from_slice(name.as_bytes());
+        (addr, 2 + 1 + name.len())
+    }
+}
+impl Default for SockaddrUn {
+    fn default() -> Self {
+        Self {
+            sun_family: 0,
+            sun_path: [0; 108],
+        }
+    }
+}
+/// `struct sockaddr_in`，端口与地址都是网络字节序
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct SockaddrIn {
+    pub sin_family: u16,
+    pub sin_port: [u8; 2],
+    pub sin_addr: [u8; 4],
+    pub sin_zero: [u8; 8],
+}
+impl SockaddrIn {
+    /// IPv4 地址 `addr` 的 `port` 端口，返回地址及其长度
+    pub fn new(addr: [u8; 4], port: u16) -> (Self, usize) {
+        let addr = Self {
+            sin_family: AF_INET as u16,
+            sin_port: port.to_be_bytes(),
+            sin_addr: addr,
+            sin_zero: [0; 8],
+        };
+        (addr, core::mem::size_of::<Self>())
+    }
+    pub fn port(&self) -> u16 {
+        u16::from_be_bytes(self.sin_port)
+    }
+}
+/// 可以传给套接字系统调用的地址结构
+pub trait Sockaddr: Copy {}
+impl Sockaddr for SockaddrUn {}
+impl Sockaddr for SockaddrIn {}
+pub fn socket(domain: usize, socket_type: usize, protocol: usize) -> isize {
+    sys_socket(domain, socket_type, protocol)
+}
+/// 创建一对互相连接的套接字，fd 写入 `sv`
+pub fn socketpair(domain: usize, socket_type: usize, protocol: usize, sv: &mut [u32; 2]) -> isize {
+    sys_socketpair(domain, socket_type, protocol, sv.as_mut_ptr())
+}
+pub fn bind<A: Sockaddr>(sockfd: usize, addr: &A, addrlen: usize) -> isize {
+    sys_bind(sockfd, addr as *const A as *const u8, addrlen)
+}
+pub fn listen(sockfd: usize, backlog: usize) -> isize {
+    sys_listen(sockfd, backlog)
+}
+/// 接受一个连接，对端地址写入 `addr`，`addrlen` 传入缓冲区长度、返回地址长度
+pub fn accept<A: Sockaddr>(sockfd: usize, addr: &mut A, addrlen: &mut u32) -> isize {
+    sys_accept(sockfd, addr as *mut A as *mut u8, addrlen)
+}
+pub fn connect<A: Sockaddr>(sockfd: usize, addr: &A, addrlen: usize) -> isize {
+    sys_connect(sockfd, addr as *const A as *const u8, addrlen)
+}
+pub fn getsockname<A: Sockaddr>(sockfd: usize, addr: &mut A, addrlen: &mut u32) -> isize {
+    sys_getsockname(sockfd, addr as *mut A as *mut u8, addrlen)
+}
+/// 向 `dest` 发送 `buf`，`dest` 为 None 时发往已连接的对端
+pub fn sendto<A: Sockaddr>(sockfd: usize, buf: &[u8], dest: Option<(&A, usize)>) -> isize {
+    match dest {
+        Some((addr, addrlen)) => sys_sendto(sockfd, buf, addr as *const A as *const u8, addrlen),
+        None => sys_sendto(sockfd, buf, core::ptr::null(), 0),
+    }
+}
+/// 接收数据到 `buf`，发送者的地址写入 `addr`
+pub fn recvfrom<A: Sockaddr>(
+    sockfd: usize,
+    buf: &mut [u8],
+    addr: &mut A,
+    addrlen: &mut u32,
+) -> isize {
+    sys_recvfrom(sockfd, buf, addr as *mut A as *mut u8, addrlen)
+}
+
+pub fn kill(pid: usize, signum: usize) -> isize {
+    sys_kill(pid, signum)
+}
+
+/// 创建与当前进程同属一个线程组的线程，在 `stack` 上从 `entry(arg)` 开始执行，返回线程 id
+#[cfg(target_arch = "riscv64")]
+pub fn thread_spawn(entry: extern "C" fn(usize) -> !, arg: usize, stack: &'static mut [u8]) -> isize {
+    let stack_top = (stack.as_mut_ptr() as usize + stack.len()) & !0xf;
+    sys_thread_spawn(entry, arg, stack_top)
+}
+
+/// 与内核中 riscv 的 `SigAction` 布局相同
+#[cfg(target_arch = "riscv64")]
+#[repr(C)]
+pub struct SigAction {
+    pub handler: usize,
+    pub flags: usize,
+    pub restorer: usize,
+    pub mask: usize,
+}
+
+#[cfg(target_arch = "riscv64")]
+pub fn sigaction(signum: usize, act: &SigAction) -> isize {
+    sys_sigaction(signum, act as *const SigAction as usize, 0)
+}
+
+pub const FUTEX_WAIT: usize = 0;
+pub const FUTEX_WAKE: usize = 1;
+pub const FUTEX_REQUEUE: usize = 3;
+pub const FUTEX_CMP_REQUEUE: usize = 4;
+pub const FUTEX_WAKE_OP: usize = 5;
+pub const FUTEX_LOCK_PI: usize = 6;
+pub const FUTEX_UNLOCK_PI: usize = 7;
+pub const FUTEX_TRYLOCK_PI: usize = 8;
+pub const FUTEX_PRIVATE_FLAG: usize = 128;
+/// PI 与 robust futex 字中的标志位与持有者 tid
+pub const FUTEX_WAITERS: u32 = 0x8000_0000;
+pub const FUTEX_OWNER_DIED: u32 = 0x4000_0000;
+pub const FUTEX_TID_MASK: u32 = 0x3fff_ffff;
+
+/// 对 `uaddr` 处的 futex 字执行 `op`；`val2` 在等待操作中是超时时间的地址，
+/// 在 requeue 与 wake-op 操作中是第二个计数
+pub fn futex(uaddr: &AtomicU32, op: usize, val: u32, val2: usize, uaddr2: &AtomicU32, val3: u32) -> isize {
+    sys_futex(
+        uaddr.as_ptr() as usize,
+        op,
+        val,
+        val2,
+        uaddr2.as_ptr() as usize,
+        val3,
+    )
+}
+
+pub const PTRACE_TRACEME: usize = 0;
+pub const PTRACE_PEEKDATA: usize = 2;
+pub const PTRACE_POKEDATA: usize = 5;
+pub const PTRACE_CONT: usize = 7;
+pub const PTRACE_SINGLESTEP: usize = 9;
+pub const PTRACE_SYSCALL: usize = 24;
+pub const PTRACE_SETOPTIONS: usize = 0x4200;
+pub const PTRACE_GETREGSET: usize = 0x4204;
+pub const PTRACE_O_TRACESYSGOOD: usize = 1;
+/// `PTRACE_GETREGSET` 的通用寄存器组
+pub const NT_PRSTATUS: usize = 1;
+
+/// 跟踪子进程，`pid` 为 0 时对自己开关单步
+pub fn ptrace(request: usize, pid: usize, addr: usize, data: usize) -> isize {
+    sys_ptrace(request, pid, addr, data)
+}
+
+pub const PROT_READ: usize = 1;
+pub const PROT_WRITE: usize = 2;
+pub const MAP_SHARED: usize = 0x01;
+pub const MAP_PRIVATE: usize = 0x02;
+pub const MAP_ANONYMOUS: usize = 0x20;
+pub const MAP_GROWSDOWN: usize = 0x0100;
+pub const MAP_POPULATE: usize = 0x8000;
+
+/// 成功时返回映射的起始地址，失败时返回负的错误码
+pub fn mmap(start: usize, len: usize, prot: usize, flags: usize, fd: usize, offset: usize) -> isize {
+    sys_mmap(start, len, prot, flags, fd, offset)
+}
+
+pub fn munmap(start: usize, len: usize) -> isize {
+    sys_munmap(start, len)
+}
+
+pub const MS_SYNC: usize = 4;
+
+pub fn msync(start: usize, len: usize, flags: usize) -> isize {
+    sys_msync(start, len, flags)
+}
+
+/// `vec` 中每页一个字节，页在内存中时为 1
+pub fn mincore(start: usize, len: usize, vec: &mut [u8]) -> isize {
+    sys_mincore(start, len, vec.as_mut_ptr())
+}
+
+/// 与内核 `struct robust_list_head` 布局相同：`list` 指向第一个链表项（链表以指回
+/// 自身结束），链表项中的 futex 字位于项地址加 `futex_offset` 处
+#[repr(C)]
+pub struct RobustListHead {
+    pub list: usize,
+    pub futex_offset: isize,
+    pub list_op_pending: usize,
+}
+
+/// 登记当前线程的 robust futex 链表，线程退出时内核释放其中仍由它持有的 futex
+pub fn set_robust_list(head: &RobustListHead) -> isize {
+    sys_set_robust_list(
+        head as *const RobustListHead as usize,
+        core::mem::size_of::<RobustListHead>(),
+    )
+}